@@ -10,6 +10,9 @@ pub const TOOL_NAME_META_KEY: &str = "tool_name";
 /// Key used in ACP ToolCall meta to store the session id when a subagent is spawned.
 pub const SUBAGENT_SESSION_ID_META_KEY: &str = "subagent_session_id";
 
+/// The permission option picked when the user edits a tool call's input and runs it.
+pub const EDITED_INPUT_OPTION_ID: &str = "allow_edited";
+
 /// Helper to extract tool name from ACP meta
 pub fn tool_name_from_meta(meta: &Option<acp::Meta>) -> Option<SharedString> {
     meta.as_ref()
@@ -489,6 +492,8 @@ pub enum ToolCallStatus {
     WaitingForConfirmation {
         options: PermissionOptions,
         respond_tx: oneshot::Sender<acp::PermissionOptionId>,
+        /// Set for tools that let the user change their input before allowing them to run.
+        editable_input: Option<EditableToolInput>,
     },
     /// The tool call is currently running.
     InProgress,
@@ -502,6 +507,50 @@ pub enum ToolCallStatus {
     Canceled,
 }
 
+/// The input of a tool call waiting for confirmation, which the user can edit before running
+/// the call with it.
+pub struct EditableToolInput {
+    /// The input the model asked to run the tool with.
+    pub input: serde_json::Value,
+    validate: Box<dyn Fn(&serde_json::Value) -> Result<()> + Send>,
+    edited_input_tx: oneshot::Sender<serde_json::Value>,
+}
+
+impl EditableToolInput {
+    /// Returns the input to offer for editing, along with a receiver for the input the user
+    /// ran the call with instead. `validate` explains why an edited input can't be used.
+    pub fn new(
+        input: serde_json::Value,
+        validate: impl Fn(&serde_json::Value) -> Result<()> + Send + 'static,
+    ) -> (Self, oneshot::Receiver<serde_json::Value>) {
+        let (edited_input_tx, edited_input_rx) = oneshot::channel();
+        let editable_input = Self {
+            input,
+            validate: Box::new(validate),
+            edited_input_tx,
+        };
+        (editable_input, edited_input_rx)
+    }
+
+    pub fn validate(&self, input: &serde_json::Value) -> Result<()> {
+        (self.validate)(input)
+    }
+
+    /// Hands the input the user ran the call with to the tool. Callers are expected to have
+    /// validated it.
+    pub fn send_edited_input(self, input: serde_json::Value) {
+        self.edited_input_tx.send(input).log_err();
+    }
+}
+
+impl std::fmt::Debug for EditableToolInput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EditableToolInput")
+            .field("input", &self.input)
+            .finish_non_exhaustive()
+    }
+}
+
 impl From<acp::ToolCallStatus> for ToolCallStatus {
     fn from(status: acp::ToolCallStatus) -> Self {
         match status {
@@ -720,7 +769,7 @@ impl ToolCallContent {
             acp::ToolCallContent::Diff(diff) => Ok(Some(Self::Diff(cx.new(|cx| {
                 Diff::finalized(
                     diff.path.to_string_lossy().into_owned(),
-                    diff.old_text,
+                    diff.old_text.map(Arc::from),
                     diff.new_text,
                     language_registry,
                     cx,
@@ -1761,12 +1810,25 @@ impl AcpThread {
         tool_call: acp::ToolCallUpdate,
         options: PermissionOptions,
         cx: &mut Context<Self>,
+    ) -> Result<Task<acp::RequestPermissionOutcome>> {
+        self.request_tool_call_authorization_with_editable_input(tool_call, options, None, cx)
+    }
+
+    /// Like [`Self::request_tool_call_authorization`], but also lets the user edit the tool
+    /// call's input and run it with that, see [`Self::authorize_tool_call_with_edited_input`].
+    pub fn request_tool_call_authorization_with_editable_input(
+        &mut self,
+        tool_call: acp::ToolCallUpdate,
+        options: PermissionOptions,
+        editable_input: Option<EditableToolInput>,
+        cx: &mut Context<Self>,
     ) -> Result<Task<acp::RequestPermissionOutcome>> {
         let (tx, rx) = oneshot::channel();
 
         let status = ToolCallStatus::WaitingForConfirmation {
             options,
             respond_tx: tx,
+            editable_input,
         };
 
         let tool_call_id = tool_call.tool_call_id.clone();
@@ -1822,6 +1884,41 @@ impl AcpThread {
         cx.emit(AcpThreadEvent::EntryUpdated(ix));
     }
 
+    /// Allows a tool call waiting for confirmation to run with `input` instead of the input
+    /// the model gave it. Fails without changing anything if the tool call doesn't let its
+    /// input be edited, or if `input` isn't valid for it.
+    pub fn authorize_tool_call_with_edited_input(
+        &mut self,
+        id: acp::ToolCallId,
+        input: serde_json::Value,
+        cx: &mut Context<Self>,
+    ) -> Result<()> {
+        let (ix, call) = self.tool_call_mut(&id).context("tool call not found")?;
+        let ToolCallStatus::WaitingForConfirmation {
+            editable_input: Some(editable_input),
+            ..
+        } = &call.status
+        else {
+            anyhow::bail!("the input of this tool call can't be edited");
+        };
+        editable_input.validate(&input)?;
+
+        if let ToolCallStatus::WaitingForConfirmation {
+            respond_tx,
+            editable_input: Some(editable_input),
+            ..
+        } = mem::replace(&mut call.status, ToolCallStatus::InProgress)
+        {
+            editable_input.send_edited_input(input);
+            respond_tx
+                .send(acp::PermissionOptionId::new(EDITED_INPUT_OPTION_ID))
+                .log_err();
+        }
+
+        cx.emit(AcpThreadEvent::EntryUpdated(ix));
+        Ok(())
+    }
+
     pub fn plan(&self) -> &Plan {
         &self.plan
     }
@@ -4068,6 +4165,83 @@ mod tests {
         });
     }
 
+    #[gpui::test]
+    async fn test_authorize_tool_call_with_edited_input(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        let project = Project::test(fs, [], cx).await;
+        let connection = Rc::new(FakeAgentConnection::new());
+        let thread = cx
+            .update(|cx| connection.new_session(project, Path::new(path!("/test")), cx))
+            .await
+            .unwrap();
+
+        let tool_call_id = acp::ToolCallId::new("terminal");
+        let (editable_input, mut edited_input_rx) =
+            EditableToolInput::new(json!({ "command": "echo helo" }), |input| {
+                anyhow::ensure!(input["command"].is_string(), "`command` must be a string");
+                Ok(())
+            });
+        let outcome = thread
+            .update(cx, |thread, cx| {
+                thread.request_tool_call_authorization_with_editable_input(
+                    acp::ToolCallUpdate::new(
+                        tool_call_id.clone(),
+                        acp::ToolCallUpdateFields::new().title("echo helo"),
+                    ),
+                    PermissionOptions::Flat(vec![acp::PermissionOption::new(
+                        acp::PermissionOptionId::new("allow"),
+                        "Allow",
+                        acp::PermissionOptionKind::AllowOnce,
+                    )]),
+                    Some(editable_input),
+                    cx,
+                )
+            })
+            .unwrap();
+
+        // An input the tool can't take is rejected, and the call keeps waiting for confirmation.
+        thread.update(cx, |thread, cx| {
+            let error = thread
+                .authorize_tool_call_with_edited_input(
+                    tool_call_id.clone(),
+                    json!({ "command": 1 }),
+                    cx,
+                )
+                .unwrap_err();
+            assert_eq!(error.to_string(), "`command` must be a string");
+            let (_, tool_call) = thread.tool_call(&tool_call_id).unwrap();
+            assert!(matches!(
+                tool_call.status,
+                ToolCallStatus::WaitingForConfirmation { .. }
+            ));
+        });
+
+        thread.update(cx, |thread, cx| {
+            thread
+                .authorize_tool_call_with_edited_input(
+                    tool_call_id.clone(),
+                    json!({ "command": "echo hello" }),
+                    cx,
+                )
+                .unwrap();
+            let (_, tool_call) = thread.tool_call(&tool_call_id).unwrap();
+            assert!(matches!(tool_call.status, ToolCallStatus::InProgress));
+        });
+        assert_eq!(
+            edited_input_rx.try_recv().unwrap(),
+            Some(json!({ "command": "echo hello" }))
+        );
+        let acp::RequestPermissionOutcome::Selected(outcome) = outcome.await else {
+            panic!("expected the edited input's option to be selected");
+        };
+        assert_eq!(
+            outcome.option_id,
+            acp::PermissionOptionId::new(EDITED_INPUT_OPTION_ID)
+        );
+    }
+
     /// Tests that restoring a checkpoint properly cleans up terminals that were
     /// created after that checkpoint, and cancels any in-progress generation.
     ///
@@ -17,14 +17,14 @@ pub enum Diff {
 impl Diff {
     pub fn finalized(
         path: String,
-        old_text: Option<String>,
+        old_text: Option<Arc<str>>,
         new_text: String,
         language_registry: Arc<LanguageRegistry>,
         cx: &mut Context<Self>,
     ) -> Self {
         let multibuffer = cx.new(|_cx| MultiBuffer::without_headers(Capability::ReadOnly));
         let new_buffer = cx.new(|cx| Buffer::local(new_text, cx));
-        let base_text = old_text.clone().unwrap_or(String::new()).into();
+        let base_text = old_text.clone().unwrap_or_default();
         let task = cx.spawn({
             let multibuffer = multibuffer.clone();
             let path = path.clone();
@@ -39,7 +39,7 @@ impl Diff {
                 buffer.update(cx, |buffer, _| buffer.parsing_idle()).await;
 
                 let diff = build_buffer_diff(
-                    old_text.unwrap_or("".into()).into(),
+                    old_text.unwrap_or_default(),
                     &buffer,
                     Some(language_registry.clone()),
                     cx,
@@ -79,6 +79,7 @@ impl Diff {
             path,
             base_text,
             new_buffer,
+            marked_ranges: Vec::new(),
             _update_diff: task,
         })
     }
@@ -117,6 +118,7 @@ impl Diff {
             new_buffer: buffer,
             diff: buffer_diff,
             revealed_ranges: Vec::new(),
+            marked_ranges: Vec::new(),
             update_diff: Task::ready(Ok(())),
         })
     }
@@ -127,6 +129,33 @@ impl Diff {
         }
     }
 
+    /// Marks a range of the new text as needing a closer look, like an edit that was applied
+    /// somewhere that didn't exactly match what the agent expected.
+    pub fn mark_range(&mut self, range: Range<Anchor>, cx: &mut Context<Self>) {
+        if let Self::Pending(diff) = self {
+            diff.marked_ranges.push(range);
+            cx.notify();
+        }
+    }
+
+    /// The marked ranges, in the buffer shown by [`Self::multibuffer`].
+    pub fn marked_ranges(&self, cx: &App) -> Vec<Range<Point>> {
+        match self {
+            Self::Pending(PendingDiff {
+                new_buffer,
+                marked_ranges,
+                ..
+            }) => {
+                let buffer = new_buffer.read(cx);
+                marked_ranges
+                    .iter()
+                    .map(|range| range.to_point(buffer))
+                    .collect()
+            }
+            Self::Finalized(FinalizedDiff { marked_ranges, .. }) => marked_ranges.clone(),
+        }
+    }
+
     pub fn finalize(&mut self, cx: &mut Context<Self>) {
         if let Self::Pending(diff) = self {
             *self = Self::Finalized(diff.finalize(cx));
@@ -222,6 +251,7 @@ pub struct PendingDiff {
     new_buffer: Entity<Buffer>,
     diff: Entity<BufferDiff>,
     revealed_ranges: Vec<Range<Anchor>>,
+    marked_ranges: Vec<Range<Anchor>>,
     _subscription: Subscription,
     update_diff: Task<Result<()>>,
 }
@@ -272,6 +302,11 @@ impl PendingDiff {
         let ranges = self.excerpt_ranges(cx);
         let base_text = self.base_text.clone();
         let new_buffer = self.new_buffer.read(cx);
+        let marked_ranges = self
+            .marked_ranges
+            .iter()
+            .map(|range| range.to_point(new_buffer))
+            .collect();
         let language_registry = new_buffer.language_registry();
 
         let path = new_buffer
@@ -328,6 +363,7 @@ impl PendingDiff {
             base_text: self.base_text.clone(),
             multibuffer: self.multibuffer.clone(),
             new_buffer: self.new_buffer.clone(),
+            marked_ranges,
             _update_diff: update_diff,
         }
     }
@@ -392,6 +428,7 @@ pub struct FinalizedDiff {
     base_text: Arc<str>,
     new_buffer: Entity<Buffer>,
     multibuffer: Entity<MultiBuffer>,
+    marked_ranges: Vec<Range<Point>>,
     _update_diff: Task<Result<()>>,
 }
 
@@ -7,7 +7,7 @@ use gpui::{
     App, AppContext, AsyncApp, Context, Entity, SharedString, Subscription, Task, WeakEntity,
 };
 use language::{Anchor, Buffer, BufferEvent, Point, ToOffset, ToPoint};
-use project::{Project, ProjectItem, lsp_store::OpenLspBufferHandle};
+use project::{Project, ProjectItem, ProjectPath, lsp_store::OpenLspBufferHandle};
 use std::{cmp, ops::Range, sync::Arc};
 use text::{Edit, Patch, Rope};
 use util::{RangeExt, ResultExt as _};
@@ -42,6 +42,46 @@ pub struct LastRejectUndo {
     pub buffers: Vec<PerBufferUndo>,
 }
 
+/// What a buffer contained before the agent changed it, as recorded between
+/// [`ActionLog::start_checkpoint`] and [`ActionLog::finish_checkpoint`].
+#[derive(Clone)]
+pub struct BufferCheckpoint {
+    pub buffer: Entity<Buffer>,
+    /// The buffer's text before the agent first touched it, or `None` if its file didn't exist.
+    ///
+    /// Ropes share their chunks with the buffer's text, so this only costs memory for the parts
+    /// that were edited since.
+    pub original_content: Option<Rope>,
+    /// The buffer's version when the checkpoint was finished. The buffer isn't restored if it
+    /// was edited after that.
+    pub final_version: clock::Global,
+}
+
+/// What [`ActionLog::restore_checkpoint`] did with one of the checkpoint's files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestoreOutcome {
+    /// The file's original content was written back and saved.
+    Restored,
+    /// The file didn't exist before the checkpoint, so it was deleted.
+    Deleted,
+    /// The file was edited after the checkpoint was finished, so it was left alone.
+    ChangedSinceCheckpoint,
+    Failed(SharedString),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RestoredFile {
+    pub path: ProjectPath,
+    pub outcome: RestoreOutcome,
+}
+
+/// A buffer seen while a checkpoint was being recorded.
+struct RecordedBuffer {
+    original_content: Option<Rope>,
+    /// Whether the agent edited, created, or deleted the buffer, as opposed to only reading it.
+    changed: bool,
+}
+
 /// Tracks actions performed by tools in a thread
 pub struct ActionLog {
     /// Buffers that we want to notify the model about when they change.
@@ -50,6 +90,12 @@ pub struct ActionLog {
     project: Entity<Project>,
     /// Stores undo information for the most recent reject operation
     last_reject_undo: Option<LastRejectUndo>,
+    /// The buffers seen since [`Self::start_checkpoint`] was called, if a checkpoint is being
+    /// recorded.
+    checkpoint: Option<BTreeMap<Entity<Buffer>, RecordedBuffer>>,
+    /// The commands the agent is running, most recently started last. Tracked files that change
+    /// on disk meanwhile are attributed to the latest one.
+    running_commands: Vec<SharedString>,
 }
 
 impl ActionLog {
@@ -59,6 +105,8 @@ impl ActionLog {
             tracked_buffers: BTreeMap::default(),
             project,
             last_reject_undo: None,
+            checkpoint: None,
+            running_commands: Vec::new(),
         }
     }
 
@@ -72,17 +120,33 @@ impl ActionLog {
         is_created: bool,
         cx: &mut Context<Self>,
     ) -> &mut TrackedBuffer {
+        // Every tool reads, creates, or is about to delete a buffer before editing it, so this is
+        // the last chance to see what the buffer contained.
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.entry(buffer.clone()).or_insert_with(|| {
+                let buffer = buffer.read(cx);
+                let exists = buffer.file().is_some_and(|file| file.disk_state().exists());
+                RecordedBuffer {
+                    original_content: exists.then(|| buffer.as_rope().clone()),
+                    changed: false,
+                }
+            });
+        }
+
         let status = if is_created {
             if let Some(tracked) = self.tracked_buffers.remove(&buffer) {
                 match tracked.status {
                     TrackedBufferStatus::Created {
                         existing_file_content,
+                        created_directories,
                     } => TrackedBufferStatus::Created {
                         existing_file_content,
+                        created_directories,
                     },
                     TrackedBufferStatus::Modified | TrackedBufferStatus::Deleted => {
                         TrackedBufferStatus::Created {
                             existing_file_content: Some(tracked.diff_base),
+                            created_directories: Vec::new(),
                         }
                     }
                 }
@@ -93,10 +157,12 @@ impl ActionLog {
             {
                 TrackedBufferStatus::Created {
                     existing_file_content: Some(buffer.read(cx).as_rope().clone()),
+                    created_directories: Vec::new(),
                 }
             } else {
                 TrackedBufferStatus::Created {
                     existing_file_content: None,
+                    created_directories: Vec::new(),
                 }
             }
         } else {
@@ -141,6 +207,8 @@ impl ActionLog {
                     version: buffer.read(cx).version(),
                     diff,
                     diff_update: diff_update_tx,
+                    reloading_for: None,
+                    modified_by: None,
                     _open_lsp_handle: open_lsp_handle,
                     _maintain_diff: cx.spawn({
                         let buffer = buffer.clone();
@@ -177,6 +245,18 @@ impl ActionLog {
             BufferEvent::FileHandleChanged => {
                 self.handle_buffer_file_changed(buffer, cx);
             }
+            BufferEvent::ReloadNeeded => {
+                if let Some(command) = self.running_commands.last()
+                    && let Some(tracked_buffer) = self.tracked_buffers.get_mut(&buffer)
+                {
+                    tracked_buffer.reloading_for = Some(command.clone());
+                }
+            }
+            BufferEvent::Reloaded => {
+                if let Some(tracked_buffer) = self.tracked_buffers.get_mut(&buffer) {
+                    tracked_buffer.reloading_for = None;
+                }
+            }
             _ => {}
         };
     }
@@ -185,7 +265,15 @@ impl ActionLog {
         let Some(tracked_buffer) = self.tracked_buffers.get_mut(&buffer) else {
             return;
         };
-        tracked_buffer.schedule_diff_update(ChangeAuthor::User, cx);
+        // A reload that picks up a command's changes is reviewed like the agent's own edits.
+        if let Some(command) = tracked_buffer.reloading_for.clone() {
+            tracked_buffer.modified_by = Some(command);
+            tracked_buffer.version = buffer.read(cx).version();
+            tracked_buffer.schedule_diff_update(ChangeAuthor::Agent, cx);
+            self.record_checkpoint_change(&buffer);
+        } else {
+            tracked_buffer.schedule_diff_update(ChangeAuthor::User, cx);
+        }
     }
 
     fn handle_buffer_file_changed(&mut self, buffer: Entity<Buffer>, cx: &mut Context<Self>) {
@@ -501,19 +589,85 @@ impl ActionLog {
 
     /// Mark a buffer as created by agent, so we can refresh it in the context
     pub fn buffer_created(&mut self, buffer: Entity<Buffer>, cx: &mut Context<Self>) {
-        self.track_buffer_internal(buffer, true, cx);
+        self.track_buffer_internal(buffer.clone(), true, cx);
+        self.record_checkpoint_change(&buffer);
+    }
+
+    /// Records the directories that were created to hold a buffer created by the agent, so
+    /// they can be removed again if the file is rejected.
+    ///
+    /// `directories` should be ordered from the outermost to the innermost directory.
+    pub fn directories_created(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        directories: impl IntoIterator<Item = ProjectPath>,
+    ) {
+        if let Some(TrackedBuffer {
+            status:
+                TrackedBufferStatus::Created {
+                    existing_file_content: None,
+                    created_directories,
+                },
+            ..
+        }) = self.tracked_buffers.get_mut(buffer)
+        {
+            created_directories.extend(directories);
+        }
     }
 
     /// Mark a buffer as edited by agent, so we can refresh it in the context
     pub fn buffer_edited(&mut self, buffer: Entity<Buffer>, cx: &mut Context<Self>) {
         let new_version = buffer.read(cx).version();
-        let tracked_buffer = self.track_buffer_internal(buffer, false, cx);
+        let tracked_buffer = self.track_buffer_internal(buffer.clone(), false, cx);
         if let TrackedBufferStatus::Deleted = tracked_buffer.status {
             tracked_buffer.status = TrackedBufferStatus::Modified;
         }
 
         tracked_buffer.version = new_version;
         tracked_buffer.schedule_diff_update(ChangeAuthor::Agent, cx);
+        self.record_checkpoint_change(&buffer);
+    }
+
+    /// Starts attributing changes to tracked files on disk to a command the agent is running,
+    /// until [`Self::finish_command`] is called.
+    pub fn start_command(&mut self, command: SharedString) {
+        self.running_commands.push(command);
+    }
+
+    /// Stops attributing changes on disk to `command`. The buffers whose files it changed are
+    /// reloaded first, so that their changes are attributed to it even if the file watcher
+    /// hasn't caught up yet. Buffers that aren't tracked are left alone.
+    pub fn finish_command(
+        &mut self,
+        command: &str,
+        changed_buffers: impl IntoIterator<Item = Entity<Buffer>>,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        let Some(ix) = self
+            .running_commands
+            .iter()
+            .rposition(|running| running.as_ref() == command)
+        else {
+            return Task::ready(());
+        };
+        let command = self.running_commands.remove(ix);
+
+        let mut reloads = Vec::new();
+        for buffer in changed_buffers {
+            let Some(tracked_buffer) = self.tracked_buffers.get_mut(&buffer) else {
+                continue;
+            };
+            tracked_buffer.reloading_for = Some(command.clone());
+            reloads.push(buffer.update(cx, |buffer, cx| buffer.reload(cx)));
+        }
+        cx.background_spawn(async move {
+            futures::future::join_all(reloads).await;
+        })
+    }
+
+    /// The command that last changed the buffer's file on disk, if its changes are being tracked.
+    pub fn modified_by(&self, buffer: &Entity<Buffer>) -> Option<SharedString> {
+        self.tracked_buffers.get(buffer)?.modified_by.clone()
     }
 
     pub fn will_delete_buffer(&mut self, buffer: Entity<Buffer>, cx: &mut Context<Self>) {
@@ -530,7 +684,124 @@ impl ActionLog {
             }
             TrackedBufferStatus::Deleted => {}
         }
+        self.record_checkpoint_change(&buffer);
+        cx.notify();
+    }
+
+    /// Starts recording what each buffer contains when the agent first touches it, so that the
+    /// buffers it goes on to change can be restored with [`Self::restore_checkpoint`].
+    pub fn start_checkpoint(&mut self) {
+        self.checkpoint = Some(BTreeMap::default());
+    }
+
+    /// Stops recording and returns the buffers the agent changed since
+    /// [`Self::start_checkpoint`], along with what they contained before.
+    pub fn finish_checkpoint(&mut self, cx: &App) -> Vec<BufferCheckpoint> {
+        let Some(checkpoint) = self.checkpoint.take() else {
+            return Vec::new();
+        };
+        checkpoint
+            .into_iter()
+            .filter(|(buffer, recorded)| {
+                // A file the agent created and deleted again has nothing to restore.
+                recorded.changed
+                    && (recorded.original_content.is_some()
+                        || buffer
+                            .read(cx)
+                            .file()
+                            .is_some_and(|file| file.disk_state().exists()))
+            })
+            .map(|(buffer, recorded)| BufferCheckpoint {
+                final_version: buffer.read(cx).version(),
+                buffer,
+                original_content: recorded.original_content,
+            })
+            .collect()
+    }
+
+    fn record_checkpoint_change(&mut self, buffer: &Entity<Buffer>) {
+        if let Some(recorded) = self
+            .checkpoint
+            .as_mut()
+            .and_then(|checkpoint| checkpoint.get_mut(buffer))
+        {
+            recorded.changed = true;
+        }
+    }
+
+    /// Puts the checkpoint's buffers back the way they were before the agent changed them, saving
+    /// each one and deleting the files that didn't exist. Buffers edited after the checkpoint was
+    /// finished are left alone, since restoring them would discard those edits.
+    pub fn restore_checkpoint(
+        &mut self,
+        checkpoint: Vec<BufferCheckpoint>,
+        cx: &mut Context<Self>,
+    ) -> Task<Vec<RestoredFile>> {
+        let mut restores = Vec::with_capacity(checkpoint.len());
+        for BufferCheckpoint {
+            buffer,
+            original_content,
+            final_version,
+        } in checkpoint
+        {
+            let Some(path) = buffer.read(cx).project_path(cx) else {
+                continue;
+            };
+            if buffer.read(cx).has_edits_since(&final_version) {
+                restores.push(Task::ready(RestoredFile {
+                    path,
+                    outcome: RestoreOutcome::ChangedSinceCheckpoint,
+                }));
+                continue;
+            }
+
+            let project = self.project.clone();
+            let restore: Task<Result<RestoreOutcome>> = match original_content {
+                Some(original_content) => {
+                    let diff = buffer.read(cx).diff(original_content.to_string(), cx);
+                    cx.spawn(async move |_, cx| {
+                        let diff = diff.await;
+                        buffer.update(cx, |buffer, cx| buffer.apply_diff(diff, cx));
+                        project
+                            .update(cx, |project, cx| project.save_buffer(buffer, cx))
+                            .await?;
+                        Ok(RestoreOutcome::Restored)
+                    })
+                }
+                None => {
+                    let created_directories = match self.tracked_buffers.remove(&buffer) {
+                        Some(TrackedBuffer {
+                            status:
+                                TrackedBufferStatus::Created {
+                                    existing_file_content: None,
+                                    created_directories,
+                                },
+                            ..
+                        }) => created_directories,
+                        _ => Vec::new(),
+                    };
+                    let delete_file = buffer.read(cx).entry_id(cx).and_then(|entry_id| {
+                        project.update(cx, |project, cx| project.delete_entry(entry_id, false, cx))
+                    });
+                    cx.spawn(async move |_, cx| {
+                        if let Some(delete_file) = delete_file {
+                            delete_file.await?;
+                        }
+                        remove_empty_directories(&project, created_directories, cx).await?;
+                        Ok(RestoreOutcome::Deleted)
+                    })
+                }
+            };
+            restores.push(cx.background_spawn(async move {
+                let outcome = restore
+                    .await
+                    .unwrap_or_else(|error| RestoreOutcome::Failed(format!("{error:#}").into()));
+                RestoredFile { path, outcome }
+            }));
+        }
         cx.notify();
+
+        cx.background_spawn(futures::future::join_all(restores))
     }
 
     pub fn keep_edits_in_range(
@@ -620,6 +891,7 @@ impl ActionLog {
         let task = match &tracked_buffer.status {
             TrackedBufferStatus::Created {
                 existing_file_content,
+                created_directories,
             } => {
                 let task = if let Some(existing_file_content) = existing_file_content {
                     // Capture the agent's content before restoring existing file content
@@ -659,7 +931,7 @@ impl ActionLog {
                         initial_version == current_version && current_content == tracked_content;
 
                     if is_ai_only_content {
-                        buffer
+                        let delete_file = buffer
                             .read(cx)
                             .entry_id(cx)
                             .and_then(|entry_id| {
@@ -667,7 +939,13 @@ impl ActionLog {
                                     project.delete_entry(entry_id, false, cx)
                                 })
                             })
-                            .unwrap_or(Task::ready(Ok(())))
+                            .unwrap_or(Task::ready(Ok(())));
+                        let created_directories = created_directories.clone();
+                        let project = self.project.clone();
+                        cx.spawn(async move |_, cx| {
+                            delete_file.await?;
+                            remove_empty_directories(&project, created_directories, cx).await
+                        })
                     } else {
                         // Not sure how to disentangle edits made by the user
                         // from edits made by the AI at this point.
@@ -798,6 +1076,7 @@ impl ActionLog {
                         tracked_buffer.status = TrackedBufferStatus::Modified;
                     }
                     tracked_buffer.unreviewed_edits.clear();
+                    tracked_buffer.modified_by = None;
                     tracked_buffer.diff_base = tracked_buffer.snapshot.as_rope().clone();
                     tracked_buffer.schedule_diff_update(ChangeAuthor::User, cx);
                     true
@@ -1115,9 +1394,38 @@ enum ChangeAuthor {
     Agent,
 }
 
+/// Removes the given directories, innermost first, stopping at the first one that isn't empty.
+async fn remove_empty_directories(
+    project: &Entity<Project>,
+    directories: Vec<ProjectPath>,
+    cx: &mut AsyncApp,
+) -> Result<()> {
+    for directory in directories.into_iter().rev() {
+        let delete = project.update(cx, |project, cx| {
+            let worktree = project.worktree_for_id(directory.worktree_id, cx)?;
+            let snapshot = worktree.read(cx).snapshot();
+            let entry = snapshot.entry_for_path(&directory.path)?;
+            let is_empty = snapshot.child_entries(&directory.path).next().is_none();
+            if !entry.is_dir() || !is_empty {
+                return None;
+            }
+            project.delete_entry(entry.id, false, cx)
+        });
+        let Some(delete) = delete else {
+            break;
+        };
+        delete.await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 enum TrackedBufferStatus {
-    Created { existing_file_content: Option<Rope> },
+    Created {
+        existing_file_content: Option<Rope>,
+        /// Directories that were created to hold the file, from the outermost to the innermost.
+        created_directories: Vec<ProjectPath>,
+    },
     Modified,
     Deleted,
 }
@@ -1131,6 +1439,10 @@ pub struct TrackedBuffer {
     diff: Entity<BufferDiff>,
     snapshot: text::BufferSnapshot,
     diff_update: mpsc::UnboundedSender<(ChangeAuthor, text::BufferSnapshot)>,
+    /// The command whose changes the buffer is being reloaded with.
+    reloading_for: Option<SharedString>,
+    /// The command that last changed the file on disk.
+    modified_by: Option<SharedString>,
     _open_lsp_handle: OpenLspBufferHandle,
     _maintain_diff: Task<()>,
     _subscription: Subscription,
@@ -1511,6 +1823,167 @@ mod tests {
         assert_eq!(unreviewed_hunks(&action_log, cx), vec![]);
     }
 
+    #[gpui::test]
+    async fn test_restore_checkpoint(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/dir"),
+            json!({"a": "one\ntwo\nthree\n", "b": "four\nfive\n", "c": "six\n"}),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/dir").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let open_buffer = |path: &str| {
+            let path = project
+                .read_with(cx, |project, cx| project.find_project_path(path, cx))
+                .unwrap();
+            project.update(cx, |project, cx| project.open_buffer(path, cx))
+        };
+        let buffer_a = open_buffer("dir/a").await.unwrap();
+        let buffer_b = open_buffer("dir/b").await.unwrap();
+        let buffer_c = open_buffer("dir/c").await.unwrap();
+
+        action_log.update(cx, |log, _| log.start_checkpoint());
+        cx.update(|cx| {
+            for (buffer, new_text) in [(&buffer_a, "one\n2\nthree\n"), (&buffer_b, "")] {
+                action_log.update(cx, |log, cx| log.buffer_read(buffer.clone(), cx));
+                buffer.update(cx, |buffer, cx| buffer.set_text(new_text, cx));
+                action_log.update(cx, |log, cx| log.buffer_edited(buffer.clone(), cx));
+            }
+            // Buffers that were only read aren't part of the checkpoint.
+            action_log.update(cx, |log, cx| log.buffer_read(buffer_c.clone(), cx));
+        });
+        for buffer in [&buffer_a, &buffer_b] {
+            project
+                .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))
+                .await
+                .unwrap();
+        }
+        let checkpoint = action_log.update(cx, |log, cx| log.finish_checkpoint(cx));
+        assert_eq!(checkpoint.len(), 2);
+
+        let restored = action_log
+            .update(cx, |log, cx| log.restore_checkpoint(checkpoint, cx))
+            .await;
+        assert_eq!(
+            restored
+                .iter()
+                .map(|file| file.outcome.clone())
+                .collect::<Vec<_>>(),
+            [RestoreOutcome::Restored, RestoreOutcome::Restored]
+        );
+        assert_eq!(
+            buffer_a.read_with(cx, |buffer, _| buffer.text()),
+            "one\ntwo\nthree\n"
+        );
+        assert!(!buffer_b.read_with(cx, |buffer, _| buffer.is_dirty()));
+        assert_eq!(
+            fs.load(path!("/dir/a").as_ref()).await.unwrap(),
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(
+            fs.load(path!("/dir/b").as_ref()).await.unwrap(),
+            "four\nfive\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_restore_checkpoint_deletes_created_files(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/dir"), json!({})).await;
+        let project = Project::test(fs.clone(), [path!("/dir").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let file_path = project
+            .read_with(cx, |project, cx| {
+                project.find_project_path("dir/new_file", cx)
+            })
+            .unwrap();
+        let buffer = project
+            .update(cx, |project, cx| project.open_buffer(file_path, cx))
+            .await
+            .unwrap();
+
+        action_log.update(cx, |log, _| log.start_checkpoint());
+        cx.update(|cx| {
+            action_log.update(cx, |log, cx| log.buffer_created(buffer.clone(), cx));
+            buffer.update(cx, |buffer, cx| buffer.set_text("content", cx));
+            action_log.update(cx, |log, cx| log.buffer_edited(buffer.clone(), cx));
+        });
+        project
+            .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))
+            .await
+            .unwrap();
+        cx.run_until_parked();
+        assert!(fs.is_file(path!("/dir/new_file").as_ref()).await);
+        let checkpoint = action_log.update(cx, |log, cx| log.finish_checkpoint(cx));
+
+        let restored = action_log
+            .update(cx, |log, cx| log.restore_checkpoint(checkpoint, cx))
+            .await;
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].outcome, RestoreOutcome::Deleted);
+        cx.run_until_parked();
+        assert!(!fs.is_file(path!("/dir/new_file").as_ref()).await);
+        assert_eq!(unreviewed_hunks(&action_log, cx), vec![]);
+    }
+
+    #[gpui::test]
+    async fn test_restore_checkpoint_skips_files_changed_since(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/dir"), json!({"a": "one\n", "b": "two\n"}))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/dir").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let open_buffer = |path: &str| {
+            let path = project
+                .read_with(cx, |project, cx| project.find_project_path(path, cx))
+                .unwrap();
+            project.update(cx, |project, cx| project.open_buffer(path, cx))
+        };
+        let buffer_a = open_buffer("dir/a").await.unwrap();
+        let buffer_b = open_buffer("dir/b").await.unwrap();
+
+        action_log.update(cx, |log, _| log.start_checkpoint());
+        cx.update(|cx| {
+            for buffer in [&buffer_a, &buffer_b] {
+                action_log.update(cx, |log, cx| log.buffer_read(buffer.clone(), cx));
+                buffer.update(cx, |buffer, cx| buffer.edit([(0..0, "agent ")], None, cx));
+                action_log.update(cx, |log, cx| log.buffer_edited(buffer.clone(), cx));
+            }
+        });
+        let checkpoint = action_log.update(cx, |log, cx| log.finish_checkpoint(cx));
+
+        // The user keeps working on `a` after the agent is done.
+        buffer_a.update(cx, |buffer, cx| buffer.edit([(0..0, "user ")], None, cx));
+
+        let restored = action_log
+            .update(cx, |log, cx| log.restore_checkpoint(checkpoint, cx))
+            .await;
+        let outcome = |buffer: &Entity<Buffer>| {
+            let path = buffer.read_with(cx, |buffer, cx| buffer.project_path(cx).unwrap());
+            restored
+                .iter()
+                .find(|file| file.path == path)
+                .map(|file| file.outcome.clone())
+        };
+        assert_eq!(
+            outcome(&buffer_a),
+            Some(RestoreOutcome::ChangedSinceCheckpoint)
+        );
+        assert_eq!(outcome(&buffer_b), Some(RestoreOutcome::Restored));
+        assert_eq!(
+            buffer_a.read_with(cx, |buffer, _| buffer.text()),
+            "user agent one\n"
+        );
+        assert_eq!(buffer_b.read_with(cx, |buffer, _| buffer.text()), "two\n");
+    }
+
     #[gpui::test(iterations = 10)]
     async fn test_overwriting_files(cx: &mut TestAppContext) {
         init_test(cx);
@@ -1651,6 +2124,84 @@ mod tests {
         );
     }
 
+    #[gpui::test]
+    async fn test_command_changes(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/dir"),
+            json!({"file": "fn main(){\nprintln!(\"hi\");\n}\n"}),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/dir").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let file_path = project
+            .read_with(cx, |project, cx| project.find_project_path("dir/file", cx))
+            .unwrap();
+        let buffer = project
+            .update(cx, |project, cx| project.open_buffer(file_path, cx))
+            .await
+            .unwrap();
+        action_log.update(cx, |log, cx| log.buffer_read(buffer.clone(), cx));
+
+        // Changes on disk while a command runs are attributed to it.
+        action_log.update(cx, |log, _| log.start_command("cargo fmt".into()));
+        fs.save(
+            path!("/dir/file").as_ref(),
+            &"fn main() {\n    println!(\"hi\");\n}\n".into(),
+            language::LineEnding::Unix,
+        )
+        .await
+        .unwrap();
+        cx.run_until_parked();
+        action_log
+            .update(cx, |log, cx| {
+                log.finish_command("cargo fmt", [buffer.clone()], cx)
+            })
+            .await;
+        cx.run_until_parked();
+        assert_eq!(
+            unreviewed_hunks(&action_log, cx),
+            vec![(
+                buffer.clone(),
+                vec![HunkStatus {
+                    range: Point::new(0, 0)..Point::new(2, 0),
+                    diff_status: DiffHunkStatusKind::Modified,
+                    old_text: "fn main(){\nprintln!(\"hi\");\n".into(),
+                }],
+            )]
+        );
+        action_log.read_with(cx, |log, cx| {
+            assert_eq!(log.modified_by(&buffer), Some("cargo fmt".into()));
+            assert_eq!(log.stale_buffers(cx).count(), 0);
+        });
+
+        // Once it's finished, changes on disk are the user's again.
+        fs.save(
+            path!("/dir/file").as_ref(),
+            &"fn main() {\n    println!(\"hi\");\n}\n// done\n".into(),
+            language::LineEnding::Unix,
+        )
+        .await
+        .unwrap();
+        cx.run_until_parked();
+        assert_eq!(
+            unreviewed_hunks(&action_log, cx),
+            vec![(
+                buffer.clone(),
+                vec![HunkStatus {
+                    range: Point::new(0, 0)..Point::new(2, 0),
+                    diff_status: DiffHunkStatusKind::Modified,
+                    old_text: "fn main(){\nprintln!(\"hi\");\n".into(),
+                }],
+            )]
+        );
+        action_log.read_with(cx, |log, cx| {
+            assert_eq!(log.stale_buffers(cx).collect::<Vec<_>>(), vec![&buffer]);
+        });
+    }
+
     #[gpui::test(iterations = 10)]
     async fn test_deleting_files(cx: &mut TestAppContext) {
         init_test(cx);
@@ -2094,6 +2645,75 @@ mod tests {
         assert_eq!(unreviewed_hunks(&action_log, cx), vec![]);
     }
 
+    #[gpui::test]
+    async fn test_reject_created_file_removes_created_directories(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/dir"), json!({})).await;
+        let project = Project::test(fs.clone(), [path!("/dir").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+
+        let mut created_directories = Vec::new();
+        for directory in ["dir/a", "dir/a/b", "dir/a/b/c"] {
+            let directory = project
+                .read_with(cx, |project, cx| project.find_project_path(directory, cx))
+                .unwrap();
+            project
+                .update(cx, |project, cx| {
+                    project.create_entry(directory.clone(), true, cx)
+                })
+                .await
+                .unwrap();
+            created_directories.push(directory);
+        }
+        // A file the user added next to the agent's directories keeps its parent around.
+        fs.insert_file(path!("/dir/a/user_file"), b"user".to_vec())
+            .await;
+        cx.run_until_parked();
+
+        let file_path = project
+            .read_with(cx, |project, cx| {
+                project.find_project_path("dir/a/b/c/new_file", cx)
+            })
+            .unwrap();
+        let buffer = project
+            .update(cx, |project, cx| project.open_buffer(file_path, cx))
+            .await
+            .unwrap();
+        cx.update(|cx| {
+            action_log.update(cx, |log, cx| {
+                log.buffer_created(buffer.clone(), cx);
+                log.directories_created(&buffer, created_directories);
+            });
+            buffer.update(cx, |buffer, cx| buffer.set_text("content", cx));
+            action_log.update(cx, |log, cx| log.buffer_edited(buffer.clone(), cx));
+        });
+        project
+            .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))
+            .await
+            .unwrap();
+        cx.run_until_parked();
+
+        action_log
+            .update(cx, |log, cx| {
+                let (task, _) = log.reject_edits_in_ranges(
+                    buffer.clone(),
+                    vec![Point::new(0, 0)..Point::new(0, 7)],
+                    None,
+                    cx,
+                );
+                task
+            })
+            .await
+            .unwrap();
+        cx.run_until_parked();
+        assert!(!fs.is_dir(path!("/dir/a/b").as_ref()).await);
+        assert!(fs.is_dir(path!("/dir/a").as_ref()).await);
+        assert!(fs.is_file(path!("/dir/a/user_file").as_ref()).await);
+        assert_eq!(unreviewed_hunks(&action_log, cx), vec![]);
+    }
+
     #[gpui::test]
     async fn test_reject_created_file_with_user_edits(cx: &mut TestAppContext) {
         init_test(cx);
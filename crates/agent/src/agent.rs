@@ -1,33 +1,53 @@
 mod db;
 mod edit_agent;
+mod edit_approval;
+mod history_compaction;
+mod instruction_files;
 mod legacy_thread;
 mod native_agent_server;
 pub mod outline;
 mod pattern_extraction;
+mod task_list;
 mod templates;
 #[cfg(test)]
 mod tests;
 mod thread;
 mod thread_store;
+mod tool_output_budget;
 mod tool_permissions;
+mod tool_scheduler;
+mod tool_stats;
 mod tools;
+mod usage_ledger;
+mod working_set;
+mod worktree_scope;
 
 use context_server::ContextServerId;
 pub use db::*;
+pub use edit_approval::*;
+pub use history_compaction::*;
 pub use native_agent_server::NativeAgentServer;
 pub use pattern_extraction::*;
 pub use shell_command_parser::extract_commands;
+pub use task_list::*;
 pub use templates::*;
 pub use thread::*;
 pub use thread_store::*;
+pub use tool_output_budget::*;
 pub use tool_permissions::*;
+pub use tool_scheduler::*;
+pub use tool_stats::*;
 pub use tools::*;
+pub use usage_ledger::*;
+pub use working_set::*;
+pub use worktree_scope::*;
 
 use acp_thread::{
     AcpThread, AgentModelSelector, AgentSessionInfo, AgentSessionList, AgentSessionListRequest,
     AgentSessionListResponse, TokenUsageRatio, UserMessageId,
 };
 use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
 use anyhow::{Context as _, Result, anyhow};
 use chrono::{DateTime, Utc};
 use collections::{HashMap, HashSet, IndexMap};
@@ -38,14 +58,16 @@ use futures::{FutureExt as _, StreamExt as _, future};
 use gpui::{
     App, AppContext, AsyncApp, Context, Entity, SharedString, Subscription, Task, WeakEntity,
 };
-use language_model::{IconOrSvg, LanguageModel, LanguageModelProvider, LanguageModelRegistry};
+use language_model::{
+    IconOrSvg, LanguageModel, LanguageModelProvider, LanguageModelRegistry, LanguageModelToolUseId,
+};
 use project::{Project, ProjectItem, ProjectPath, Worktree};
 use prompt_store::{
     ProjectContext, PromptStore, RULES_FILE_NAMES, RulesFileContext, UserRulesContext,
     WorktreeContext,
 };
 use serde::{Deserialize, Serialize};
-use settings::{LanguageModelSelection, update_settings_file};
+use settings::{LanguageModelSelection, Settings as _, update_settings_file};
 use std::any::Any;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
@@ -507,14 +529,22 @@ impl NativeAgent {
             root_name,
             abs_path,
             rules_file: None,
+            instruction_files: Vec::new(),
         };
 
+        let instruction_files_task = instruction_files::load_instruction_file_contexts(
+            &worktree,
+            &project,
+            &AgentSettings::get_global(cx).instruction_files,
+            cx,
+        );
         let rules_task = Self::load_worktree_rules_file(worktree, project, cx);
-        let Some(rules_task) = rules_task else {
-            return Task::ready((context, None));
-        };
 
         cx.spawn(async move |_| {
+            context.instruction_files = instruction_files_task.await;
+            let Some(rules_task) = rules_task else {
+                return (context, None);
+            };
             let (rules_file, rules_file_error) = match rules_task.await {
                 Ok(rules_file) => (Some(rules_file), None),
                 Err(err) => (
@@ -613,17 +643,19 @@ impl NativeAgent {
         &mut self,
         _project: Entity<Project>,
         event: &project::Event,
-        _cx: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) {
         match event {
             project::Event::WorktreeAdded(_) | project::Event::WorktreeRemoved(_) => {
                 self.project_context_needs_refresh.send(()).ok();
             }
             project::Event::WorktreeUpdatedEntries(_, items) => {
+                let instruction_files = &AgentSettings::get_global(cx).instruction_files;
                 if items.iter().any(|(path, _, _)| {
                     RULES_FILE_NAMES
                         .iter()
                         .any(|name| path.as_ref() == RelPath::unix(name).unwrap())
+                        || instruction_files.is_match(path)
                 }) {
                     self.project_context_needs_refresh.send(()).ok();
                 }
@@ -987,6 +1019,23 @@ impl NativeAgentConnection {
         self.0.update(cx, |this, cx| this.load_thread(id, cx))
     }
 
+    /// Applies an edit from the session's history again, against the files as they are now.
+    pub fn reapply_tool_call(
+        &self,
+        session_id: acp::SessionId,
+        tool_call_id: &acp::ToolCallId,
+        cx: &mut App,
+    ) -> Task<Result<()>> {
+        let tool_use_id = LanguageModelToolUseId::from(tool_call_id.0.to_string());
+        let task = self.run_turn(session_id, cx, move |thread, cx| {
+            thread.update(cx, |thread, cx| thread.reapply_tool_call(&tool_use_id, cx))
+        });
+        cx.background_spawn(async move {
+            task.await?;
+            Ok(())
+        })
+    }
+
     fn run_turn(
         &self,
         session_id: acp::SessionId,
@@ -1050,9 +1099,15 @@ impl NativeAgentConnection {
                                 options,
                                 response,
                                 context: _,
+                                editable_input,
                             }) => {
                                 let outcome_task = acp_thread.update(cx, |thread, cx| {
-                                    thread.request_tool_call_authorization(tool_call, options, cx)
+                                    thread.request_tool_call_authorization_with_editable_input(
+                                        tool_call,
+                                        options,
+                                        editable_input,
+                                        cx,
+                                    )
                                 })??;
                                 cx.background_spawn(async move {
                                     if let acp::RequestPermissionOutcome::Selected(
@@ -1082,6 +1137,9 @@ impl NativeAgentConnection {
                                     thread.subagent_spawned(session_id, cx);
                                 })?;
                             }
+                            ThreadEvent::Plan(plan) => {
+                                acp_thread.update(cx, |thread, cx| thread.update_plan(plan, cx))?;
+                            }
                             ThreadEvent::Retry(status) => {
                                 acp_thread.update(cx, |thread, cx| {
                                     thread.update_retry_status(status, cx)
@@ -1425,7 +1483,11 @@ impl acp_thread::AgentConnection for NativeAgentConnection {
 
     fn session_list(&self, cx: &mut App) -> Option<Rc<dyn AgentSessionList>> {
         let thread_store = self.0.read(cx).thread_store.clone();
-        Some(Rc::new(NativeAgentSessionList::new(thread_store, cx)) as _)
+        Some(Rc::new(NativeAgentSessionList::new(
+            self.0.downgrade(),
+            thread_store,
+            cx,
+        )) as _)
     }
 
     fn telemetry(&self) -> Option<Rc<dyn acp_thread::AgentTelemetry>> {
@@ -1455,6 +1517,7 @@ impl acp_thread::AgentTelemetry for NativeAgentConnection {
 }
 
 pub struct NativeAgentSessionList {
+    agent: WeakEntity<NativeAgent>,
     thread_store: Entity<ThreadStore>,
     updates_tx: smol::channel::Sender<acp_thread::SessionListUpdate>,
     updates_rx: smol::channel::Receiver<acp_thread::SessionListUpdate>,
@@ -1462,7 +1525,11 @@ pub struct NativeAgentSessionList {
 }
 
 impl NativeAgentSessionList {
-    fn new(thread_store: Entity<ThreadStore>, cx: &mut App) -> Self {
+    fn new(
+        agent: WeakEntity<NativeAgent>,
+        thread_store: Entity<ThreadStore>,
+        cx: &mut App,
+    ) -> Self {
         let (tx, rx) = smol::channel::unbounded();
         let this_tx = tx.clone();
         let subscription = cx.observe(&thread_store, move |_, _| {
@@ -1471,6 +1538,7 @@ impl NativeAgentSessionList {
                 .ok();
         });
         Self {
+            agent,
             thread_store,
             updates_tx: tx,
             updates_rx: rx,
@@ -1491,6 +1559,24 @@ impl NativeAgentSessionList {
     pub fn thread_store(&self) -> &Entity<ThreadStore> {
         &self.thread_store
     }
+
+    /// Drops the checkpoints of the open threads that are being deleted, all of them if
+    /// `session_id` is `None`.
+    fn clear_checkpoints(&self, session_id: Option<&acp::SessionId>, cx: &mut App) {
+        let Some(agent) = self.agent.upgrade() else {
+            return;
+        };
+        let threads = agent
+            .read(cx)
+            .sessions
+            .iter()
+            .filter(|(id, _)| session_id.is_none_or(|session_id| *id == session_id))
+            .map(|(_, session)| session.thread.clone())
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.update(cx, |thread, cx| thread.clear_checkpoints(cx));
+        }
+    }
 }
 
 impl AgentSessionList for NativeAgentSessionList {
@@ -1513,11 +1599,13 @@ impl AgentSessionList for NativeAgentSessionList {
     }
 
     fn delete_session(&self, session_id: &acp::SessionId, cx: &mut App) -> Task<Result<()>> {
+        self.clear_checkpoints(Some(session_id), cx);
         self.thread_store
             .update(cx, |store, cx| store.delete_thread(session_id.clone(), cx))
     }
 
     fn delete_sessions(&self, cx: &mut App) -> Task<Result<()>> {
+        self.clear_checkpoints(None, cx);
         self.thread_store
             .update(cx, |store, cx| store.delete_threads(cx))
     }
@@ -1934,7 +2022,8 @@ mod internal_tests {
                 vec![WorktreeContext {
                     root_name: "a".into(),
                     abs_path: Path::new("/a").into(),
-                    rules_file: None
+                    rules_file: None,
+                    instruction_files: Vec::new(),
                 }]
             )
         });
@@ -1956,12 +2045,104 @@ mod internal_tests {
                         path_in_worktree: rel_path(".rules").into(),
                         text: "".into(),
                         project_entry_id: rules_entry.id.to_usize()
-                    })
+                    }),
+                    instruction_files: Vec::new(),
                 }]
             )
         });
     }
 
+    #[gpui::test]
+    async fn test_discovering_instruction_files(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            "/",
+            json!({
+                "a": {
+                    "AGENTS.md": "# Agents\n\nRun the tests before finishing.\n",
+                    "CONTRIBUTING.md": "Sign the CLA.",
+                    "docs": { "CONTRIBUTING.md": "Document every setting." },
+                },
+                "b": {
+                    ".zed": { "agent.md": "Prefer small commits." },
+                },
+                "c": { "README.md": "Nothing to see." },
+            }),
+        )
+        .await;
+        let project = Project::test(
+            fs.clone(),
+            ["/a".as_ref(), "/b".as_ref(), "/c".as_ref()],
+            cx,
+        )
+        .await;
+        let thread_store = cx.new(|cx| ThreadStore::new(cx));
+        let agent = NativeAgent::new(
+            project.clone(),
+            thread_store,
+            Templates::new(),
+            None,
+            fs.clone(),
+            &mut cx.to_async(),
+        )
+        .await
+        .unwrap();
+        cx.run_until_parked();
+
+        let instruction_files = |agent: &Entity<NativeAgent>, cx: &mut TestAppContext| {
+            agent.read_with(cx, |agent, cx| {
+                let project_context = agent.project_context.read(cx);
+                assert_eq!(
+                    project_context.has_instruction_files,
+                    project_context
+                        .worktrees
+                        .iter()
+                        .any(|worktree| !worktree.instruction_files.is_empty())
+                );
+                project_context
+                    .worktrees
+                    .iter()
+                    .flat_map(|worktree| {
+                        worktree.instruction_files.iter().map(|file| {
+                            format!(
+                                "{}/{}: {}",
+                                worktree.root_name,
+                                file.path_in_worktree.as_unix_str(),
+                                file.excerpt
+                            )
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            instruction_files(&agent, cx),
+            [
+                "a/AGENTS.md: # Agents Run the tests before finishing.",
+                "a/CONTRIBUTING.md: Sign the CLA.",
+                "a/docs/CONTRIBUTING.md: Document every setting.",
+                "b/.zed/agent.md: Prefer small commits.",
+            ]
+        );
+
+        // Edits to the files are picked up.
+        fs.insert_file("/c/AGENTS.md", b"Use tabs.".to_vec()).await;
+        fs.remove_file("/a/CONTRIBUTING.md".as_ref(), Default::default())
+            .await
+            .unwrap();
+        cx.run_until_parked();
+        assert_eq!(
+            instruction_files(&agent, cx),
+            [
+                "a/AGENTS.md: # Agents Run the tests before finishing.",
+                "a/docs/CONTRIBUTING.md: Document every setting.",
+                "b/.zed/agent.md: Prefer small commits.",
+                "c/AGENTS.md: Use tabs.",
+            ]
+        );
+    }
+
     #[gpui::test]
     async fn test_listing_models(cx: &mut TestAppContext) {
         init_test(cx);
@@ -64,6 +64,16 @@ pub struct DbThread {
     pub thinking_enabled: bool,
     #[serde(default)]
     pub thinking_effort: Option<String>,
+    #[serde(default)]
+    pub working_set: crate::WorkingSet,
+    #[serde(default)]
+    pub task_list: crate::TaskList,
+    #[serde(default)]
+    pub tool_stats: crate::ToolStats,
+    #[serde(default)]
+    pub usage_ledger: crate::UsageLedger,
+    #[serde(default)]
+    pub worktree_scope: Option<crate::WorktreeScope>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,6 +115,11 @@ impl SharedThread {
             speed: None,
             thinking_enabled: false,
             thinking_effort: None,
+            working_set: Default::default(),
+            task_list: Default::default(),
+            tool_stats: Default::default(),
+            usage_ledger: Default::default(),
+            worktree_scope: None,
         }
     }
 
@@ -282,6 +297,11 @@ impl DbThread {
             speed: None,
             thinking_enabled: false,
             thinking_effort: None,
+            working_set: Default::default(),
+            task_list: Default::default(),
+            tool_stats: Default::default(),
+            usage_ledger: Default::default(),
+            worktree_scope: None,
         })
     }
 }
@@ -632,6 +652,11 @@ mod tests {
             speed: None,
             thinking_enabled: false,
             thinking_effort: None,
+            working_set: Default::default(),
+            task_list: Default::default(),
+            tool_stats: Default::default(),
+            usage_ledger: Default::default(),
+            worktree_scope: None,
         }
     }
 
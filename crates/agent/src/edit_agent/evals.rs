@@ -1069,6 +1069,10 @@ fn eval_create_empty_file() {
                             ListDirectoryTool::NAME,
                             ListDirectoryToolInput {
                                 path: "root".to_string(),
+                                max_depth: None,
+                                include: None,
+                                exclude: None,
+                                respect_gitignore: true,
                             },
                         ),
                     ],
@@ -1078,7 +1082,7 @@ fn eval_create_empty_file() {
                     [tool_result(
                         "toolu_01GAF8TtsgpjKxCr8fgQLDgR",
                         ListDirectoryTool::NAME,
-                        "root/TODO\nroot/TODO2\nroot/new.txt\n",
+                        "root/ (3 entries)\n  TODO\n  TODO2\n  new.txt\n",
                     )],
                 ),
                 message(
@@ -1523,6 +1527,7 @@ impl EditAgentTest {
                 root_name: "root".to_string(),
                 abs_path: Path::new("/path/to/root").into(),
                 rules_file: None,
+                instruction_files: Vec::new(),
             }];
             let project_context = ProjectContext::new(worktrees, Vec::default());
             let tool_names = tools
@@ -1533,6 +1538,7 @@ impl EditAgentTest {
                 project: &project_context,
                 available_tools: tool_names,
                 model_name: None,
+                scoped_roots: Vec::new(),
             };
             let templates = Templates::new();
             template.render(&templates).unwrap()
@@ -12,10 +12,21 @@ pub struct StreamingFuzzyMatcher {
     query_lines: Vec<String>,
     line_hint: Option<u32>,
     incomplete_line: String,
-    matches: Vec<Range<usize>>,
+    matches: Vec<FuzzyMatch>,
+    best_candidate: Option<FuzzyMatch>,
+    min_similarity: f32,
     matrix: SearchMatrix,
 }
 
+/// A range of the buffer that the query matched, along with how similar its lines are to the
+/// query's, from 0 to 1. Whitespace is ignored when comparing lines, so a match that only
+/// differs in whitespace has a similarity of 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    pub range: Range<usize>,
+    pub similarity: f32,
+}
+
 impl StreamingFuzzyMatcher {
     pub fn new(snapshot: TextBufferSnapshot) -> Self {
         let buffer_line_count = snapshot.max_point().row as usize + 1;
@@ -25,10 +36,19 @@ impl StreamingFuzzyMatcher {
             line_hint: None,
             incomplete_line: String::new(),
             matches: Vec::new(),
+            best_candidate: None,
+            min_similarity: 0.,
             matrix: SearchMatrix::new(buffer_line_count + 1),
         }
     }
 
+    /// Only reports matches at least this similar to the query, both while streaming and once
+    /// finished.
+    pub fn with_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
     /// Returns the query lines.
     pub fn query_lines(&self) -> &[String] {
         &self.query_lines
@@ -62,7 +82,7 @@ impl StreamingFuzzyMatcher {
         }
 
         let best_match = self.select_best_match();
-        best_match.or_else(|| self.matches.first().cloned())
+        best_match.or_else(|| self.matches.first().map(|m| m.range.clone()))
     }
 
     /// Finish processing and return the final best match(es).
@@ -70,6 +90,14 @@ impl StreamingFuzzyMatcher {
     /// This processes any remaining incomplete line before returning the final
     /// match result.
     pub fn finish(&mut self) -> Vec<Range<usize>> {
+        self.finish_with_similarity()
+            .into_iter()
+            .map(|m| m.range)
+            .collect()
+    }
+
+    /// Like [`Self::finish`], but also returns how similar each match is to the query.
+    pub fn finish_with_similarity(&mut self) -> Vec<FuzzyMatch> {
         // Process any remaining incomplete line
         if !self.incomplete_line.is_empty() {
             self.query_lines.push(self.incomplete_line.clone());
@@ -79,7 +107,13 @@ impl StreamingFuzzyMatcher {
         self.matches.clone()
     }
 
-    fn resolve_location_fuzzy(&mut self) -> Vec<Range<usize>> {
+    /// The most similar range found for the query, even when it wasn't similar enough to be
+    /// reported as a match. Useful for showing what the query came closest to.
+    pub fn best_candidate(&self) -> Option<&FuzzyMatch> {
+        self.best_candidate.as_ref()
+    }
+
+    fn resolve_location_fuzzy(&mut self) -> Vec<FuzzyMatch> {
         let new_query_line_count = self.query_lines.len();
         let old_query_line_count = self.matrix.rows.saturating_sub(1);
         if new_query_line_count == old_query_line_count {
@@ -118,7 +152,8 @@ impl StreamingFuzzyMatcher {
                     SearchDirection::Left,
                 );
                 let diagonal = SearchState::new(
-                    if query_line == buffer_line {
+                    if query_line == buffer_line || eq_ignoring_whitespace(query_line, buffer_line)
+                    {
                         self.matrix.get(row, col).cost
                     } else if fuzzy_eq(query_line, buffer_line) {
                         self.matrix.get(row, col).cost + REPLACEMENT_COST
@@ -154,8 +189,10 @@ impl StreamingFuzzyMatcher {
 
         // Find ranges for the matches
         let mut valid_matches = Vec::new();
+        self.best_candidate = None;
         for &buffer_row_end in &matches_with_best_cost {
             let mut matched_lines = 0;
+            let mut line_similarity_sum = 0.;
             let mut query_row = new_query_line_count;
             let mut buffer_row_start = buffer_row_end;
             while query_row > 0 && buffer_row_start > 0 {
@@ -165,6 +202,10 @@ impl StreamingFuzzyMatcher {
                         query_row -= 1;
                         buffer_row_start -= 1;
                         matched_lines += 1;
+                        line_similarity_sum += line_similarity(
+                            &self.query_lines[query_row],
+                            &self.buffer_line(buffer_row_start),
+                        );
                     }
                     SearchDirection::Up => {
                         query_row -= 1;
@@ -176,21 +217,38 @@ impl StreamingFuzzyMatcher {
             }
 
             let matched_buffer_row_count = buffer_row_end - buffer_row_start;
-            let matched_ratio = matched_lines as f32
-                / (matched_buffer_row_count as f32).max(new_query_line_count as f32);
-            if matched_ratio >= 0.8 {
-                let buffer_start_ix = self
-                    .snapshot
-                    .point_to_offset(Point::new(buffer_row_start, 0));
-                let buffer_end_ix = self.snapshot.point_to_offset(Point::new(
-                    buffer_row_end - 1,
-                    self.snapshot.line_len(buffer_row_end - 1),
-                ));
-                valid_matches.push((buffer_row_start, buffer_start_ix..buffer_end_ix));
+            let max_row_count = (matched_buffer_row_count as f32).max(new_query_line_count as f32);
+            let matched_ratio = matched_lines as f32 / max_row_count;
+            let buffer_start_ix = self
+                .snapshot
+                .point_to_offset(Point::new(buffer_row_start, 0));
+            let buffer_end_ix = self.snapshot.point_to_offset(Point::new(
+                buffer_row_end - 1,
+                self.snapshot.line_len(buffer_row_end - 1),
+            ));
+            let candidate = FuzzyMatch {
+                range: buffer_start_ix..buffer_end_ix,
+                similarity: line_similarity_sum / max_row_count,
+            };
+            if self
+                .best_candidate
+                .as_ref()
+                .is_none_or(|best| candidate.similarity > best.similarity)
+            {
+                self.best_candidate = Some(candidate.clone());
+            }
+            if matched_ratio >= 0.8 && candidate.similarity >= self.min_similarity {
+                valid_matches.push(candidate);
             }
         }
 
-        valid_matches.into_iter().map(|(_, range)| range).collect()
+        valid_matches
+    }
+
+    fn buffer_line(&self, row: u32) -> String {
+        self.snapshot
+            .text_for_range(Point::new(row, 0)..Point::new(row, self.snapshot.line_len(row)))
+            .collect()
     }
 
     /// Return the best match with starting position close enough to line_hint.
@@ -205,7 +263,7 @@ impl StreamingFuzzyMatcher {
         }
 
         if self.matches.len() == 1 {
-            return self.matches.first().cloned();
+            return self.matches.first().map(|m| m.range.clone());
         }
 
         let Some(line_hint) = self.line_hint else {
@@ -216,7 +274,7 @@ impl StreamingFuzzyMatcher {
         let mut best_match = None;
         let mut best_distance = u32::MAX;
 
-        for range in &self.matches {
+        for FuzzyMatch { range, .. } in &self.matches {
             let start_point = self.snapshot.offset_to_point(range.start);
             let start_line = start_point.row;
             let distance = start_line.abs_diff(line_hint);
@@ -231,6 +289,22 @@ impl StreamingFuzzyMatcher {
     }
 }
 
+fn eq_ignoring_whitespace(left: &str, right: &str) -> bool {
+    left.chars()
+        .filter(|c| !c.is_whitespace())
+        .eq(right.chars().filter(|c| !c.is_whitespace()))
+}
+
+/// How similar two lines are, from 0 to 1, ignoring whitespace differences.
+fn line_similarity(query_line: &str, buffer_line: &str) -> f32 {
+    if eq_ignoring_whitespace(query_line, buffer_line) {
+        return 1.;
+    }
+    let non_whitespace =
+        |line: &str| -> String { line.chars().filter(|c| !c.is_whitespace()).collect() };
+    strsim::normalized_levenshtein(&non_whitespace(query_line), &non_whitespace(buffer_line)) as f32
+}
+
 fn fuzzy_eq(left: &str, right: &str) -> bool {
     const THRESHOLD: f64 = 0.8;
 
@@ -722,6 +796,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_match_similarity() {
+        let buffer = TextBuffer::new(
+            ReplicaId::LOCAL,
+            BufferId::new(1).unwrap(),
+            indoc! {"
+                fn add(a: u32, b: u32) -> u32 {
+                    a + b
+                }
+            "},
+        );
+        let snapshot = buffer.snapshot();
+        let similarity = |query: &str, min_similarity: f32| {
+            let mut matcher =
+                StreamingFuzzyMatcher::new(snapshot.clone()).with_min_similarity(min_similarity);
+            matcher.push(query, None);
+            let matches = matcher.finish_with_similarity();
+            let best_candidate = matcher.best_candidate().cloned();
+            (
+                matches.first().map(|m| m.similarity),
+                best_candidate.map(|candidate| candidate.similarity),
+            )
+        };
+
+        // Whitespace differences don't count against a match.
+        assert_eq!(
+            similarity("fn add(a: u32, b: u32) -> u32 {\na+b\n}", 0.8),
+            (Some(1.), Some(1.))
+        );
+
+        let (matched, candidate) = similarity("fn add(a: u32, b: u64) -> u32 {\na + b\n}", 0.8);
+        let matched = matched.unwrap();
+        assert!(0.8 < matched && matched < 1., "{matched}");
+        assert_eq!(candidate, Some(matched));
+
+        // Matches below the minimum similarity are only reported as candidates.
+        let (matched, candidate) = similarity("fn add(a: u32, b: u64) -> u32 {\na + b\n}", 0.99);
+        assert_eq!(matched, None);
+        assert!(candidate.unwrap() < 0.99);
+    }
+
     #[track_caller]
     fn assert_location_resolution(text_with_expected_range: &str, query: &str, rng: &mut StdRng) {
         let (text, expected_ranges) = marked_text_ranges(text_with_expected_range, false);
@@ -0,0 +1,54 @@
+use fs::MTime;
+use gpui::Entity;
+use language::Buffer;
+use std::{path::PathBuf, sync::Arc};
+
+/// Buffers the agent edited without saving, because `edits_require_approval` is on. They're
+/// saved once the user approves the edits, or put back the way they were if they reject them.
+#[derive(Default)]
+pub struct PendingApproval {
+    buffers: Vec<PendingBuffer>,
+}
+
+#[derive(Clone)]
+pub struct PendingBuffer {
+    pub buffer: Entity<Buffer>,
+    pub abs_path: PathBuf,
+    /// The buffer's text before the first edit that's waiting for approval.
+    pub original_text: Arc<str>,
+    /// The file's mtime at the time, which tells whether it was saved some other way since.
+    pub original_mtime: Option<MTime>,
+}
+
+impl PendingApproval {
+    /// Adds an edit to `buffer`. Later edits to a buffer that's already pending are folded into
+    /// the earlier ones, so rejecting them restores the text from before the first.
+    pub fn insert(&mut self, pending: PendingBuffer) {
+        if !self.contains(&pending.buffer) {
+            self.buffers.push(pending);
+        }
+    }
+
+    pub fn contains(&self, buffer: &Entity<Buffer>) -> bool {
+        self.buffers
+            .iter()
+            .any(|pending| pending.buffer.entity_id() == buffer.entity_id())
+    }
+
+    /// The pending buffers, in the order they were first edited.
+    pub fn buffers(&self) -> &[PendingBuffer] {
+        &self.buffers
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<PendingBuffer> {
+        std::mem::take(&mut self.buffers)
+    }
+}
@@ -0,0 +1,414 @@
+use collections::HashMap;
+use language_model::{
+    LanguageModel, LanguageModelRequestMessage, LanguageModelToolResult,
+    LanguageModelToolResultContent, MessageContent,
+};
+use std::fmt::Write as _;
+
+use crate::{AgentTool, EditFileTool, StreamingEditFileTool};
+
+/// How much of the model's context window a thread's request may fill before its oldest tool
+/// results are compacted.
+///
+/// Compacting replaces a tool result in the request with a one-line stub saying what the tool
+/// was called on, so the model can call it again if it still needs the output. The thread keeps
+/// the full result, which is what the agent panel shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryBudget {
+    max_tokens: u64,
+}
+
+impl HistoryBudget {
+    /// The share of the context window, in percent, that a request may fill.
+    const CONTEXT_WINDOW_PERCENT: u64 = 85;
+    /// The most recent tool results are never compacted, since the model is likely still
+    /// working with them.
+    pub const KEEP_RECENT_TOOL_RESULTS: usize = 5;
+    /// Like the tool output budget, a rough average is all that's needed here.
+    const BYTES_PER_TOKEN: u64 = 4;
+
+    pub fn new(max_tokens: u64) -> Self {
+        Self { max_tokens }
+    }
+
+    pub fn for_model(model: &dyn LanguageModel) -> Self {
+        Self::new(model.max_token_count() / 100 * Self::CONTEXT_WINDOW_PERCENT)
+    }
+
+    pub fn max_tokens(&self) -> u64 {
+        self.max_tokens
+    }
+
+    /// Estimates how many tokens `content` takes up in a request.
+    pub fn estimate_tokens(content: &MessageContent) -> u64 {
+        let bytes = match content {
+            MessageContent::Text(text)
+            | MessageContent::Thinking { text, .. }
+            | MessageContent::RedactedThinking(text) => text.len(),
+            MessageContent::Image(image) => return image.estimate_tokens() as u64,
+            MessageContent::ToolUse(tool_use) => tool_use.raw_input.len(),
+            MessageContent::ToolResult(tool_result) => match &tool_result.content {
+                LanguageModelToolResultContent::Text(text) => text.len(),
+                LanguageModelToolResultContent::Image(image) => {
+                    return image.estimate_tokens() as u64;
+                }
+            },
+        };
+        (bytes as u64).div_ceil(Self::BYTES_PER_TOKEN)
+    }
+
+    /// Compacts the oldest tool results in `messages`, except for the most recent ones, until
+    /// the request fits the budget according to `count_tokens`. Returns how many were
+    /// compacted.
+    ///
+    /// Only tool results are replaced, so the user's messages and the model's own responses
+    /// are always sent as they are.
+    pub fn compact(
+        &self,
+        messages: &mut [LanguageModelRequestMessage],
+        count_tokens: impl Fn(&MessageContent) -> u64,
+    ) -> usize {
+        let mut total_tokens = messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .map(&count_tokens)
+            .sum::<u64>();
+        if total_tokens <= self.max_tokens {
+            return 0;
+        }
+
+        let tool_inputs = messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .filter_map(|content| match content {
+                MessageContent::ToolUse(tool_use) => {
+                    Some((tool_use.id.clone(), tool_use.input.clone()))
+                }
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let tool_results = messages
+            .iter()
+            .enumerate()
+            .flat_map(|(message_ix, message)| {
+                message
+                    .content
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, content)| matches!(content, MessageContent::ToolResult(_)))
+                    .map(move |(content_ix, _)| (message_ix, content_ix))
+            })
+            .collect::<Vec<_>>();
+        let compactable = tool_results
+            .len()
+            .saturating_sub(Self::KEEP_RECENT_TOOL_RESULTS);
+
+        let mut compacted = 0;
+        for (message_ix, content_ix) in tool_results.into_iter().take(compactable) {
+            if total_tokens <= self.max_tokens {
+                break;
+            }
+            let content = &mut messages[message_ix].content[content_ix];
+            let MessageContent::ToolResult(tool_result) = content else {
+                continue;
+            };
+            let stub = MessageContent::ToolResult(LanguageModelToolResult {
+                content: compacted_tool_result(
+                    tool_result,
+                    tool_inputs.get(&tool_result.tool_use_id),
+                )
+                .into(),
+                ..tool_result.clone()
+            });
+            let (tokens_before, tokens_after) = (count_tokens(content), count_tokens(&stub));
+            // Short results, like a confirmation that a file was saved, are kept as they are.
+            if tokens_after >= tokens_before {
+                continue;
+            }
+            *content = stub;
+            total_tokens -= tokens_before - tokens_after;
+            compacted += 1;
+        }
+        compacted
+    }
+}
+
+/// A one-line stand-in for `tool_result`, like "[read_file src/main.rs — returned a 312-line
+/// result, ...]", naming the path, command or query the tool was called with.
+fn compacted_tool_result(
+    tool_result: &LanguageModelToolResult,
+    input: Option<&serde_json::Value>,
+) -> String {
+    const TARGET_KEYS: [&str; 6] = ["path", "command", "regex", "glob", "query", "url"];
+    const MAX_TARGET_CHARS: usize = 80;
+
+    let mut stub = format!("[{}", tool_result.tool_name);
+    let target = input.and_then(|input| {
+        TARGET_KEYS
+            .iter()
+            .find_map(|key| input.get(key)?.as_str())
+            .and_then(|target| target.lines().next())
+    });
+    if let Some(target) = target {
+        write!(
+            stub,
+            " {}",
+            util::truncate_and_trailoff(target, MAX_TARGET_CHARS)
+        )
+        .ok();
+    }
+
+    let is_edit =
+        [EditFileTool::NAME, StreamingEditFileTool::NAME].contains(&tool_result.tool_name.as_ref());
+    match &tool_result.content {
+        LanguageModelToolResultContent::Text(text) if is_edit && !tool_result.is_error => {
+            let (added, removed) = diff_line_counts(text);
+            write!(
+                stub,
+                " — edited, +{added} -{removed} lines; the diff was removed from the history \
+                 to save space]"
+            )
+            .ok();
+        }
+        LanguageModelToolResultContent::Text(text) => {
+            let outcome = if tool_result.is_error {
+                "failed with a"
+            } else {
+                "returned a"
+            };
+            write!(
+                stub,
+                " — {outcome} {}-line result, removed from the history to save space; \
+                 call the tool again if you need it]",
+                text.lines().count()
+            )
+            .ok();
+        }
+        LanguageModelToolResultContent::Image(_) => {
+            stub.push_str(
+                " — returned an image, removed from the history to save space; \
+                 call the tool again if you need it]",
+            );
+        }
+    }
+    stub
+}
+
+/// The lines added and removed by the unified diffs in `text`.
+fn diff_line_counts(text: &str) -> (usize, usize) {
+    let mut added = 0;
+    let mut removed = 0;
+    for line in text.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            continue;
+        }
+        if line.starts_with('+') {
+            added += 1;
+        } else if line.starts_with('-') {
+            removed += 1;
+        }
+    }
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentMessage, AgentMessageContent, ReadFileTool};
+    use language_model::{LanguageModelToolUse, Role};
+    use serde_json::json;
+
+    /// Counts a token per byte of text, which keeps the numbers in the tests easy to follow.
+    fn count_bytes(content: &MessageContent) -> u64 {
+        match content {
+            MessageContent::Text(text) => text.len() as u64,
+            MessageContent::ToolUse(tool_use) => tool_use.raw_input.len() as u64,
+            MessageContent::ToolResult(tool_result) => match &tool_result.content {
+                LanguageModelToolResultContent::Text(text) => text.len() as u64,
+                LanguageModelToolResultContent::Image(_) => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    fn user_message(text: &str) -> LanguageModelRequestMessage {
+        LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![MessageContent::Text(text.into())],
+            cache: false,
+            reasoning_details: None,
+        }
+    }
+
+    /// An agent turn that called `tool_name` once per input, with the matching output.
+    fn agent_message(
+        tool_name: &str,
+        calls: impl IntoIterator<Item = (serde_json::Value, String)>,
+    ) -> AgentMessage {
+        let mut message = AgentMessage::default();
+        message
+            .content
+            .push(AgentMessageContent::Text("Let me look.".into()));
+        for (ix, (input, output)) in calls.into_iter().enumerate() {
+            let id = format!("{tool_name}-{ix}");
+            message
+                .content
+                .push(AgentMessageContent::ToolUse(LanguageModelToolUse {
+                    id: id.clone().into(),
+                    name: tool_name.into(),
+                    raw_input: input.to_string(),
+                    input,
+                    is_input_complete: true,
+                    thought_signature: None,
+                }));
+            message.tool_results.insert(
+                id.clone().into(),
+                LanguageModelToolResult {
+                    tool_use_id: id.into(),
+                    tool_name: tool_name.into(),
+                    is_error: false,
+                    content: output.into(),
+                    output: None,
+                },
+            );
+        }
+        message
+    }
+
+    fn read_files(count: usize) -> AgentMessage {
+        agent_message(
+            ReadFileTool::NAME,
+            (0..count).map(|ix| {
+                (
+                    json!({ "path": format!("src/file_{ix}.rs") }),
+                    "fn main() {}\n".repeat(100),
+                )
+            }),
+        )
+    }
+
+    fn tool_results(messages: &[LanguageModelRequestMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .filter_map(|content| match content {
+                MessageContent::ToolResult(tool_result) => match &tool_result.content {
+                    LanguageModelToolResultContent::Text(text) => Some(text.to_string()),
+                    LanguageModelToolResultContent::Image(_) => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn request(agent_message: &AgentMessage) -> Vec<LanguageModelRequestMessage> {
+        let mut messages = vec![user_message("Read every file.")];
+        messages.extend(agent_message.to_request());
+        messages
+    }
+
+    fn total_tokens(messages: &[LanguageModelRequestMessage]) -> u64 {
+        messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .map(count_bytes)
+            .sum()
+    }
+
+    #[test]
+    fn test_compacts_only_once_over_budget() {
+        let mut messages = request(&read_files(8));
+        let original = messages.clone();
+        let total = total_tokens(&messages);
+
+        assert_eq!(
+            HistoryBudget::new(total).compact(&mut messages, count_bytes),
+            0
+        );
+        assert_eq!(messages, original);
+
+        // Going a single token over compacts the oldest result, and nothing else.
+        assert_eq!(
+            HistoryBudget::new(total - 1).compact(&mut messages, count_bytes),
+            1
+        );
+        let results = tool_results(&messages);
+        assert_eq!(
+            results[0],
+            "[read_file src/file_0.rs — returned a 100-line result, removed from the history to \
+             save space; call the tool again if you need it]"
+        );
+        assert_eq!(results[1..], tool_results(&original)[1..]);
+        assert!(total_tokens(&messages) < total);
+    }
+
+    #[test]
+    fn test_keeps_recent_results_and_messages() {
+        let mut messages = request(&read_files(8));
+        let original = messages.clone();
+
+        // Even when the budget can't be met, the most recent results are sent as they are.
+        assert_eq!(HistoryBudget::new(0).compact(&mut messages, count_bytes), 3);
+        let results = tool_results(&messages);
+        let original_results = tool_results(&original);
+        assert!(
+            results[..3]
+                .iter()
+                .all(|result| result.starts_with("[read_file"))
+        );
+        assert_eq!(
+            results[3..],
+            original_results[8 - HistoryBudget::KEEP_RECENT_TOOL_RESULTS..]
+        );
+
+        // Only the tool results changed.
+        for (message, original) in messages.iter().zip(&original) {
+            assert_eq!(message.role, original.role);
+            for (content, original) in message.content.iter().zip(&original.content) {
+                if !matches!(content, MessageContent::ToolResult(_)) {
+                    assert_eq!(content, original);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_results_are_kept() {
+        let message = agent_message(
+            ReadFileTool::NAME,
+            (0..8).map(|ix| (json!({ "path": format!("src/file_{ix}.rs") }), "ok".into())),
+        );
+        let mut messages = request(&message);
+        let original = messages.clone();
+        assert_eq!(HistoryBudget::new(0).compact(&mut messages, count_bytes), 0);
+        assert_eq!(messages, original);
+    }
+
+    #[test]
+    fn test_edit_results_are_summarized() {
+        let diff = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3 +1,4 @@\n fn main() {\n-    \
+                    println!(\"hi\");\n+    let name = \"world\";\n+    println!(\"hello \
+                    {name}\");\n }\n"
+            .repeat(10);
+        let message = agent_message(
+            EditFileTool::NAME,
+            (0..6).map(|_| (json!({ "path": "src/main.rs" }), diff.clone())),
+        );
+        let mut messages = request(&message);
+        assert_eq!(HistoryBudget::new(0).compact(&mut messages, count_bytes), 1);
+        assert_eq!(
+            tool_results(&messages)[0],
+            "[edit_file src/main.rs — edited, +20 -10 lines; the diff was removed from the \
+             history to save space]"
+        );
+    }
+
+    #[test]
+    fn test_thread_messages_are_unchanged() {
+        let message = read_files(8);
+        let original = message.clone();
+        let mut messages = request(&message);
+        assert_eq!(HistoryBudget::new(0).compact(&mut messages, count_bytes), 3);
+        assert_eq!(message, original);
+    }
+}
@@ -0,0 +1,163 @@
+//! Finds the files that describe a project's conventions, like `AGENTS.md` or `CONTRIBUTING.md`.
+//!
+//! The system prompt only lists them with a short excerpt, and the `read_instructions` tool reads
+//! them in full, so that a long contribution guide doesn't take up context until it's needed.
+
+use anyhow::Result;
+use futures::future;
+use gpui::{App, Entity, Task};
+use project::{Project, ProjectPath, Worktree};
+use prompt_store::InstructionFileContext;
+use std::{fmt::Write as _, sync::Arc};
+use util::{ResultExt as _, paths::PathMatcher, rel_path::RelPath};
+
+/// Instruction files longer than this, in bytes, are cut off when the agent reads them.
+pub const MAX_INSTRUCTION_FILE_LEN: usize = 32 * 1024;
+/// The number of characters of each instruction file that's shown in the system prompt.
+const EXCERPT_LEN: usize = 160;
+
+/// Paths in `worktree` that match the instruction file globs, in path order.
+pub fn instruction_file_paths(worktree: &Worktree, globs: &PathMatcher) -> Vec<Arc<RelPath>> {
+    if globs.sources().next().is_none() {
+        return Vec::new();
+    }
+    worktree
+        .files(false, 0)
+        .filter(|entry| globs.is_match(&entry.path))
+        .map(|entry| entry.path.clone())
+        .collect()
+}
+
+/// Loads the instruction files in `worktree` for the system prompt.
+pub fn load_instruction_file_contexts(
+    worktree: &Entity<Worktree>,
+    project: &Entity<Project>,
+    globs: &PathMatcher,
+    cx: &mut App,
+) -> Task<Vec<InstructionFileContext>> {
+    let worktree_id = worktree.read(cx).id();
+    let load_tasks = instruction_file_paths(worktree.read(cx), globs)
+        .into_iter()
+        .map(|path_in_worktree| {
+            let project_path = ProjectPath {
+                worktree_id,
+                path: path_in_worktree.clone(),
+            };
+            let text = load_instruction_file(project, project_path, cx);
+            async move {
+                let text = text.await.log_err()?;
+                Some(InstructionFileContext {
+                    path_in_worktree,
+                    excerpt: excerpt(&text),
+                    len: text.len(),
+                })
+            }
+        })
+        .collect::<Vec<_>>();
+    cx.background_spawn(async move {
+        future::join_all(load_tasks)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    })
+}
+
+/// Loads an instruction file's text through its buffer, so that it works for remote projects
+/// and reflects what the user sees.
+pub fn load_instruction_file(
+    project: &Entity<Project>,
+    project_path: ProjectPath,
+    cx: &mut App,
+) -> Task<Result<String>> {
+    let buffer_task = project.update(cx, |project, cx| project.open_buffer(project_path, cx));
+    cx.spawn(async move |cx| {
+        let buffer = buffer_task.await?;
+        Ok(buffer.read_with(cx, |buffer, _| buffer.text()))
+    })
+}
+
+/// The start of `text` on a single line, for the system prompt.
+fn excerpt(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    util::truncate_and_trailoff(&text, EXCERPT_LEN)
+}
+
+/// Concatenates instruction files, given as their display path and text, with a header for each.
+/// Files over [`MAX_INSTRUCTION_FILE_LEN`] are cut off at a line boundary, with a note saying so.
+pub fn format_instruction_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut output = String::new();
+    for (path, text) in files {
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        writeln!(output, "## `{path}`\n").ok();
+        if text.len() > MAX_INSTRUCTION_FILE_LEN {
+            let shown = util::truncate_lines_to_byte_limit(text, MAX_INSTRUCTION_FILE_LEN);
+            output.push_str(shown.trim_end());
+            write!(
+                output,
+                "\n\n[This file is {} bytes long, so only its first {} bytes are shown. \
+                Use the `read_file` tool to read the rest.]",
+                text.len(),
+                shown.len()
+            )
+            .ok();
+        } else {
+            output.push_str(text.trim_end());
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(
+            excerpt("# Contributing\n\nRun `cargo test`   before\nopening a PR.\n"),
+            "# Contributing Run `cargo test` before opening a PR."
+        );
+        let excerpt = excerpt(&"word ".repeat(100));
+        assert_eq!(excerpt.chars().count(), EXCERPT_LEN + 1);
+        assert!(excerpt.ends_with('…'));
+    }
+
+    #[test]
+    fn test_format_instruction_files() {
+        assert_eq!(
+            format_instruction_files([
+                ("a/AGENTS.md", "Use tabs.\n"),
+                ("b/CONTRIBUTING.md", "Sign the CLA."),
+            ]),
+            "## `a/AGENTS.md`\n\nUse tabs.\n\n## `b/CONTRIBUTING.md`\n\nSign the CLA."
+        );
+    }
+
+    #[test]
+    fn test_long_instruction_files_are_cut_off() {
+        let line = format!("{}\n", "x".repeat(99));
+        let text = line.repeat(MAX_INSTRUCTION_FILE_LEN / line.len() + 10);
+        let output = format_instruction_files([("a/CONTRIBUTING.md", text.as_str())]);
+
+        let shown_len = MAX_INSTRUCTION_FILE_LEN / line.len() * line.len();
+        assert!(output.contains(&format!(
+            "[This file is {} bytes long, so only its first {shown_len} bytes are shown.",
+            text.len()
+        )));
+        let shown = output
+            .strip_prefix("## `a/CONTRIBUTING.md`\n\n")
+            .unwrap()
+            .split("\n\n")
+            .next()
+            .unwrap();
+        assert_eq!(shown, text[..shown_len].trim_end());
+
+        // A file right at the limit is shown in full.
+        let text = "x".repeat(MAX_INSTRUCTION_FILE_LEN);
+        let output = format_instruction_files([("a/AGENTS.md", text.as_str())]);
+        assert!(!output.contains("bytes are shown"));
+    }
+}
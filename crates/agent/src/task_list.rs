@@ -0,0 +1,295 @@
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use itertools::Itertools as _;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The most tasks a list holds, which keeps it a plan rather than a log.
+pub const MAX_TASKS: usize = 50;
+
+/// The plan the agent keeps for a long task, which the user sees as a checklist.
+///
+/// It's saved with the thread, so a conversation that's reopened shows the plan where it left off.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskList {
+    items: Vec<TaskListItem>,
+    /// The id of the last task added. Ids aren't reused, so that a removed task's id can't end up
+    /// referring to a different one.
+    last_id: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskListItem {
+    pub id: u32,
+    pub title: String,
+    pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[schemars(inline)]
+pub enum TaskStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+impl TaskList {
+    pub fn items(&self) -> &[TaskListItem] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_completed(&self) -> bool {
+        !self.is_empty()
+            && self
+                .items
+                .iter()
+                .all(|item| item.status == TaskStatus::Completed)
+    }
+
+    /// Adds a pending task to the end of the list, returning its id.
+    pub fn add(&mut self, title: String, notes: Option<String>) -> Result<u32> {
+        if self.items.len() >= MAX_TASKS {
+            return Err(anyhow!(
+                "The task list already has {MAX_TASKS} tasks, which is as many as it can hold. \
+                Remove tasks that are done or no longer needed first."
+            ));
+        }
+        self.last_id += 1;
+        self.items.push(TaskListItem {
+            id: self.last_id,
+            title,
+            status: TaskStatus::Pending,
+            notes,
+        });
+        Ok(self.last_id)
+    }
+
+    /// Changes the fields of the task that are given, leaving the others as they are.
+    pub fn update(
+        &mut self,
+        id: u32,
+        title: Option<String>,
+        status: Option<TaskStatus>,
+        notes: Option<String>,
+    ) -> Result<()> {
+        let item = self.item_mut(id)?;
+        if let Some(title) = title {
+            item.title = title;
+        }
+        if let Some(status) = status {
+            item.status = status;
+        }
+        if let Some(notes) = notes {
+            item.notes = Some(notes).filter(|notes| !notes.is_empty());
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<TaskListItem> {
+        let ix = self.index_of(id)?;
+        Ok(self.items.remove(ix))
+    }
+
+    fn item_mut(&mut self, id: u32) -> Result<&mut TaskListItem> {
+        let ix = self.index_of(id)?;
+        Ok(&mut self.items[ix])
+    }
+
+    fn index_of(&self, id: u32) -> Result<usize> {
+        self.items
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| {
+                if self.is_empty() {
+                    anyhow!("There's no task with id {id}, since the task list is empty.")
+                } else {
+                    anyhow!(
+                        "There's no task with id {id}. The current ids are {}.",
+                        self.items.iter().map(|item| item.id).join(", ")
+                    )
+                }
+            })
+    }
+
+    pub fn to_acp_plan(&self) -> acp::Plan {
+        acp::Plan::new(
+            self.items
+                .iter()
+                .map(|item| {
+                    let status = match item.status {
+                        TaskStatus::Pending => acp::PlanEntryStatus::Pending,
+                        TaskStatus::InProgress => acp::PlanEntryStatus::InProgress,
+                        TaskStatus::Completed => acp::PlanEntryStatus::Completed,
+                    };
+                    acp::PlanEntry::new(item.title.clone(), acp::PlanEntryPriority::Medium, status)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The list as the agent sees it: one line per task, with its notes below it.
+impl fmt::Display for TaskList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "The task list is empty.");
+        }
+        let completed = self
+            .items
+            .iter()
+            .filter(|item| item.status == TaskStatus::Completed)
+            .count();
+        write!(
+            f,
+            "Task list ({completed} of {} completed):",
+            self.items.len()
+        )?;
+        for item in &self.items {
+            let status = match item.status {
+                TaskStatus::Pending => "[ ]",
+                TaskStatus::InProgress => "[~]",
+                TaskStatus::Completed => "[x]",
+            };
+            write!(f, "\n{status} {}. {}", item.id, item.title)?;
+            if let Some(notes) = &item.notes {
+                for line in notes.lines() {
+                    write!(f, "\n    {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_task_list_operations() {
+        let mut task_list = TaskList::default();
+        assert_eq!(task_list.to_string(), "The task list is empty.");
+
+        let parser = task_list.add("Write the parser".into(), None).unwrap();
+        let tests = task_list
+            .add("Add tests".into(), Some("Cover the error cases".into()))
+            .unwrap();
+        let docs = task_list.add("Update the docs".into(), None).unwrap();
+        assert_eq!((parser, tests, docs), (1, 2, 3));
+
+        task_list
+            .update(parser, None, Some(TaskStatus::Completed), None)
+            .unwrap();
+        task_list
+            .update(
+                tests,
+                Some("Add parser tests".into()),
+                Some(TaskStatus::InProgress),
+                Some("Cover the error cases\nand empty input".into()),
+            )
+            .unwrap();
+        assert_eq!(
+            task_list.to_string(),
+            indoc! {"
+                Task list (1 of 3 completed):
+                [x] 1. Write the parser
+                [~] 2. Add parser tests
+                    Cover the error cases
+                    and empty input
+                [ ] 3. Update the docs"}
+        );
+
+        // Removed ids aren't handed out again.
+        assert_eq!(task_list.remove(docs).unwrap().title, "Update the docs");
+        assert_eq!(
+            task_list.add("Update the changelog".into(), None).unwrap(),
+            4
+        );
+
+        let error = task_list.update(3, None, None, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "There's no task with id 3. The current ids are 1, 2, 4."
+        );
+        let error = TaskList::default().remove(1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "There's no task with id 1, since the task list is empty."
+        );
+
+        // Empty notes clear them.
+        task_list
+            .update(tests, None, None, Some(String::new()))
+            .unwrap();
+        assert_eq!(task_list.items()[1].notes, None);
+    }
+
+    #[test]
+    fn test_completing_all_tasks() {
+        let mut task_list = TaskList::default();
+        assert!(!task_list.is_completed());
+        let first = task_list.add("First".into(), None).unwrap();
+        let second = task_list.add("Second".into(), None).unwrap();
+        task_list
+            .update(first, None, Some(TaskStatus::Completed), None)
+            .unwrap();
+        assert!(!task_list.is_completed());
+        task_list
+            .update(second, None, Some(TaskStatus::Completed), None)
+            .unwrap();
+        assert!(task_list.is_completed());
+        assert!(
+            task_list
+                .to_acp_plan()
+                .entries
+                .iter()
+                .all(|entry| matches!(entry.status, acp::PlanEntryStatus::Completed))
+        );
+    }
+
+    #[test]
+    fn test_task_list_is_capped() {
+        let mut task_list = TaskList::default();
+        for ix in 0..MAX_TASKS {
+            task_list.add(format!("Task {ix}"), None).unwrap();
+        }
+        let error = task_list.add("One too many".into(), None).unwrap_err();
+        assert!(
+            error.to_string().contains("already has 50 tasks"),
+            "{error}"
+        );
+
+        // Removing a task makes room for another.
+        task_list.remove(1).unwrap();
+        task_list.add("One more".into(), None).unwrap();
+        assert_eq!(task_list.items().len(), MAX_TASKS);
+    }
+
+    #[test]
+    fn test_task_list_serialization_round_trip() {
+        let mut task_list = TaskList::default();
+        let first = task_list
+            .add("First".into(), Some("Some notes".into()))
+            .unwrap();
+        task_list.add("Second".into(), None).unwrap();
+        task_list
+            .update(first, None, Some(TaskStatus::InProgress), None)
+            .unwrap();
+        task_list.remove(2).unwrap();
+
+        let json = serde_json::to_string(&task_list).unwrap();
+        let mut deserialized = serde_json::from_str::<TaskList>(&json).unwrap();
+        assert_eq!(deserialized, task_list);
+        // The next id carries over too.
+        assert_eq!(deserialized.add("Third".into(), None).unwrap(), 3);
+    }
+}
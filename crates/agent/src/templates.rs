@@ -39,6 +39,8 @@ pub struct SystemPromptTemplate<'a> {
     pub project: &'a prompt_store::ProjectContext,
     pub available_tools: Vec<SharedString>,
     pub model_name: Option<String>,
+    /// The roots the thread's tools are confined to. Empty when they can use every root.
+    pub scoped_roots: Vec<SharedString>,
 }
 
 impl Template for SystemPromptTemplate<'_> {
@@ -81,10 +83,73 @@ mod tests {
             project: &project,
             available_tools: vec!["echo".into()],
             model_name: Some("test-model".to_string()),
+            scoped_roots: Vec::new(),
         };
         let templates = Templates::new();
         let rendered = template.render(&templates).unwrap();
         assert!(rendered.contains("## Fixing Diagnostics"));
         assert!(rendered.contains("test-model"));
     }
+
+    #[test]
+    fn test_system_prompt_lists_instruction_files() {
+        let project = prompt_store::ProjectContext::new(
+            vec![prompt_store::WorktreeContext {
+                root_name: "zed".into(),
+                abs_path: std::path::Path::new("/zed").into(),
+                rules_file: None,
+                instruction_files: vec![prompt_store::InstructionFileContext {
+                    path_in_worktree: util::rel_path::rel_path("CONTRIBUTING.md").into(),
+                    excerpt: "# Contributing Run `cargo test` before opening a PR.".into(),
+                    len: 4096,
+                }],
+            }],
+            Vec::new(),
+        );
+        let template = SystemPromptTemplate {
+            project: &project,
+            available_tools: vec!["read_instructions".into()],
+            model_name: None,
+            scoped_roots: Vec::new(),
+        };
+        let rendered = template.render(&Templates::new()).unwrap();
+        assert!(rendered.contains("## Project Instructions"));
+        assert!(rendered.contains("Use the `read_instructions` tool"));
+        assert!(rendered.contains(
+            "- `zed/CONTRIBUTING.md` (4096 bytes): # Contributing Run `cargo test` before opening a PR."
+        ));
+
+        let rendered = SystemPromptTemplate {
+            project: &prompt_store::ProjectContext::default(),
+            available_tools: vec!["read_instructions".into()],
+            model_name: None,
+            scoped_roots: Vec::new(),
+        }
+        .render(&Templates::new())
+        .unwrap();
+        assert!(!rendered.contains("## Project Instructions"));
+    }
+
+    #[test]
+    fn test_system_prompt_lists_scoped_roots() {
+        let project = prompt_store::ProjectContext::default();
+        let template = |scoped_roots: Vec<SharedString>| SystemPromptTemplate {
+            project: &project,
+            available_tools: vec!["read_file".into()],
+            model_name: None,
+            scoped_roots,
+        };
+
+        let rendered = template(vec!["frontend".into()])
+            .render(&Templates::new())
+            .unwrap();
+        assert!(
+            rendered.contains(
+                "you may only read and change files in these root directories: `frontend`"
+            )
+        );
+
+        let rendered = template(Vec::new()).render(&Templates::new()).unwrap();
+        assert!(!rendered.contains("you may only read and change files"));
+    }
 }
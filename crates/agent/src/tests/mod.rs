@@ -177,6 +177,8 @@ impl SubagentHandle for FakeSubagentHandle {
 struct FakeThreadEnvironment {
     terminal_handle: Option<Rc<FakeTerminalHandle>>,
     subagent_handle: Option<Rc<FakeSubagentHandle>>,
+    /// The commands that terminals were created for.
+    commands: std::cell::RefCell<Vec<String>>,
 }
 
 impl FakeThreadEnvironment {
@@ -191,11 +193,12 @@ impl FakeThreadEnvironment {
 impl crate::ThreadEnvironment for FakeThreadEnvironment {
     fn create_terminal(
         &self,
-        _command: String,
+        command: String,
         _cwd: Option<std::path::PathBuf>,
         _output_byte_limit: Option<u64>,
         _cx: &mut AsyncApp,
     ) -> Task<Result<Rc<dyn crate::TerminalHandle>>> {
+        self.commands.borrow_mut().push(command);
         let handle = self
             .terminal_handle
             .clone()
@@ -293,7 +296,11 @@ async fn test_terminal_tool_timeout_kills_handle(cx: &mut TestAppContext) {
     let handle = environment.terminal_handle.clone().unwrap();
 
     #[allow(clippy::arc_with_non_send_sync)]
-    let tool = Arc::new(crate::TerminalTool::new(project, environment));
+    let tool = Arc::new(crate::TerminalTool::new(
+        project,
+        gpui::WeakEntity::new_invalid(),
+        environment,
+    ));
     let (event_stream, mut rx) = crate::ToolCallEventStream::test();
 
     let task = cx.update(|cx| {
@@ -302,6 +309,7 @@ async fn test_terminal_tool_timeout_kills_handle(cx: &mut TestAppContext) {
                 command: "sleep 1000".to_string(),
                 cd: ".".to_string(),
                 timeout_ms: Some(5),
+                watch: None,
             }),
             event_stream,
             cx,
@@ -360,7 +368,11 @@ async fn test_terminal_tool_without_timeout_does_not_kill_handle(cx: &mut TestAp
     let handle = environment.terminal_handle.clone().unwrap();
 
     #[allow(clippy::arc_with_non_send_sync)]
-    let tool = Arc::new(crate::TerminalTool::new(project, environment));
+    let tool = Arc::new(crate::TerminalTool::new(
+        project,
+        gpui::WeakEntity::new_invalid(),
+        environment,
+    ));
     let (event_stream, mut rx) = crate::ToolCallEventStream::test();
 
     let _task = cx.update(|cx| {
@@ -369,6 +381,7 @@ async fn test_terminal_tool_without_timeout_does_not_kill_handle(cx: &mut TestAp
                 command: "sleep 1000".to_string(),
                 cd: ".".to_string(),
                 timeout_ms: None,
+                watch: None,
             }),
             event_stream,
             cx,
@@ -395,6 +408,165 @@ async fn test_terminal_tool_without_timeout_does_not_kill_handle(cx: &mut TestAp
     );
 }
 
+#[gpui::test]
+async fn test_terminal_tool_tracks_files_changed_by_command(cx: &mut TestAppContext) {
+    init_test(cx);
+    always_allow_tools(cx);
+
+    let fs = FakeFs::new(cx.executor());
+    fs.insert_tree(
+        path!("/root"),
+        json!({
+            "src": {
+                "main.rs": "fn main(){\n}\n",
+                "lib.rs": "pub fn lib(){\n}\n",
+            },
+            "generated": {
+                "schema.rs": "// v1\n",
+            },
+        }),
+    )
+    .await;
+    let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+    let context_server_registry =
+        cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+    let model = Arc::new(FakeLanguageModel::default());
+    let thread = cx.new(|cx| {
+        Thread::new(
+            project.clone(),
+            cx.new(|_cx| ProjectContext::default()),
+            context_server_registry,
+            Templates::new(),
+            Some(model.clone()),
+            cx,
+        )
+    });
+    let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
+
+    let read_tool = Arc::new(crate::ReadFileTool::new(
+        thread.downgrade(),
+        project.clone(),
+        action_log.clone(),
+    ));
+    cx.update(|cx| {
+        read_tool.run(
+            ToolInput::resolved(crate::ReadFileToolInput {
+                path: "root/src/main.rs".to_string(),
+                start_line: None,
+                end_line: None,
+            }),
+            crate::ToolCallEventStream::test().0,
+            cx,
+        )
+    })
+    .await
+    .unwrap();
+
+    let environment = Rc::new(cx.update(|cx| {
+        FakeThreadEnvironment::default().with_terminal(FakeTerminalHandle::new_never_exits(cx))
+    }));
+    let handle = environment.terminal_handle.clone().unwrap();
+    #[allow(clippy::arc_with_non_send_sync)]
+    let terminal_tool = Arc::new(crate::TerminalTool::new(
+        project.clone(),
+        thread.downgrade(),
+        environment,
+    ));
+    let task = cx.update(|cx| {
+        terminal_tool.run(
+            ToolInput::resolved(crate::TerminalToolInput {
+                command: "cargo fmt && ./codegen.sh".to_string(),
+                cd: "root".to_string(),
+                timeout_ms: None,
+                watch: Some("root/generated/**".to_string()),
+            }),
+            crate::ToolCallEventStream::test().0,
+            cx,
+        )
+    });
+    cx.run_until_parked();
+
+    // The command formats the file the agent read and regenerates a watched file. It also
+    // changes a file the agent never saw, which isn't checked.
+    cx.background_executor.advance_clock(Duration::from_secs(2));
+    for (path, content) in [
+        (path!("/root/src/main.rs"), "fn main() {}\n"),
+        (path!("/root/src/lib.rs"), "pub fn lib() {}\n"),
+        (path!("/root/generated/schema.rs"), "// v2\n"),
+    ] {
+        fs.save(path.as_ref(), &content.into(), language::LineEnding::Unix)
+            .await
+            .unwrap();
+    }
+    handle.signal_exit();
+    let output = task.await.unwrap();
+    assert!(
+        output.ends_with(
+            "Files changed by this command:\n- `root/generated/schema.rs`\n- `root/src/main.rs`"
+        ),
+        "{output}"
+    );
+    cx.run_until_parked();
+
+    let buffer = project
+        .update(cx, |project, cx| {
+            let project_path = project.find_project_path("root/src/main.rs", cx).unwrap();
+            project.open_buffer(project_path, cx)
+        })
+        .await
+        .unwrap();
+    thread.read_with(cx, |thread, cx| {
+        assert_eq!(
+            thread
+                .working_set()
+                .last_known_mtime(Path::new(path!("/root/src/main.rs"))),
+            buffer.read(cx).file().unwrap().disk_state().mtime()
+        );
+        assert!(
+            thread
+                .working_set()
+                .get(Path::new(path!("/root/src/lib.rs")))
+                .is_none()
+        );
+        let action_log = thread.action_log().read(cx);
+        assert_eq!(
+            action_log
+                .changed_buffers(cx)
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![buffer.clone()]
+        );
+        assert_eq!(
+            action_log.modified_by(&buffer),
+            Some("cargo fmt && ./codegen.sh".into())
+        );
+    });
+
+    // Editing the formatted file isn't blocked by the command having changed it, so the edit
+    // goes on to ask the model for the new text.
+    let languages = project.read_with(cx, |project, _| project.languages().clone());
+    let edit_tool = Arc::new(crate::EditFileTool::new(
+        project.clone(),
+        thread.downgrade(),
+        languages,
+        Templates::new(),
+    ));
+    let edit_task = cx.update(|cx| {
+        edit_tool.run(
+            ToolInput::resolved(crate::EditFileToolInput {
+                display_description: "Edit the formatted file".into(),
+                path: "root/src/main.rs".into(),
+                mode: crate::EditFileMode::Edit,
+            }),
+            crate::ToolCallEventStream::test().0,
+            cx,
+        )
+    });
+    cx.run_until_parked();
+    assert_eq!(model.pending_completions().len(), 1);
+    drop(edit_task);
+}
+
 #[gpui::test]
 async fn test_thinking(cx: &mut TestAppContext) {
     let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
@@ -811,7 +983,6 @@ async fn test_tool_authorization(cx: &mut TestAppContext) {
     ));
     fake_model.end_last_completion_stream();
     let tool_call_auth_1 = next_tool_call_authorization(&mut events).await;
-    let tool_call_auth_2 = next_tool_call_authorization(&mut events).await;
 
     // Approve the first - send "allow" option_id (UI transforms "once" to "allow")
     tool_call_auth_1
@@ -820,6 +991,9 @@ async fn test_tool_authorization(cx: &mut TestAppContext) {
         .unwrap();
     cx.run_until_parked();
 
+    // The second call only asks once the first is done, since they run one at a time.
+    let tool_call_auth_2 = next_tool_call_authorization(&mut events).await;
+
     // Reject the second - send "deny" option_id directly since Deny is now a button
     tool_call_auth_2
         .response
@@ -916,6 +1090,74 @@ async fn test_tool_authorization(cx: &mut TestAppContext) {
     );
 }
 
+#[gpui::test]
+async fn test_tool_authorization_with_edited_input(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    let fake_model = model.as_fake();
+
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(EditableEchoTool);
+            thread.send(UserMessageId::new(), ["abc"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    let input = json!({ "text": "helo" });
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_1".into(),
+            name: EditableEchoTool::NAME.into(),
+            raw_input: input.to_string(),
+            input: input.clone(),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+
+    let tool_call_auth = next_tool_call_authorization(&mut events).await;
+    let editable_input = tool_call_auth
+        .editable_input
+        .expect("the tool's input should be editable");
+    assert_eq!(editable_input.input, input);
+    assert!(editable_input.validate(&json!({ "text": 1 })).is_err());
+    editable_input.send_edited_input(json!({ "text": "hello" }));
+    tool_call_auth
+        .response
+        .send(acp::PermissionOptionId::new(
+            acp_thread::EDITED_INPUT_OPTION_ID,
+        ))
+        .unwrap();
+    cx.run_until_parked();
+
+    // The model's tool use keeps the input it gave, while the result says what actually ran.
+    let completion = fake_model.pending_completions().pop().unwrap();
+    let tool_use_message = &completion.messages[completion.messages.len() - 2];
+    assert!(tool_use_message.content.iter().any(|content| matches!(
+        content,
+        MessageContent::ToolUse(tool_use) if tool_use.input == input
+    )));
+    let message = completion.messages.last().unwrap();
+    assert_eq!(
+        message.content,
+        vec![MessageContent::ToolResult(LanguageModelToolResult {
+            tool_use_id: "tool_id_1".into(),
+            tool_name: EditableEchoTool::NAME.into(),
+            is_error: false,
+            content: indoc! {r#"
+                The user edited this tool call's input before running it. It ran with:
+                ```json
+                {
+                  "text": "hello"
+                }
+                ```
+                hello"#}
+            .into(),
+            output: Some("hello".into())
+        })]
+    );
+}
+
 #[gpui::test]
 async fn test_tool_hallucination(cx: &mut TestAppContext) {
     let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
@@ -1457,13 +1699,6 @@ async fn test_mcp_tools(cx: &mut TestAppContext) {
     assert_eq!(
         completion.messages.last().unwrap().content,
         vec![
-            MessageContent::ToolResult(LanguageModelToolResult {
-                tool_use_id: "tool_3".into(),
-                tool_name: "echo".into(),
-                is_error: false,
-                content: "native".into(),
-                output: Some("native".into()),
-            },),
             MessageContent::ToolResult(LanguageModelToolResult {
                 tool_use_id: "tool_2".into(),
                 tool_name: "test_server_echo".into(),
@@ -1471,6 +1706,13 @@ async fn test_mcp_tools(cx: &mut TestAppContext) {
                 content: "mcp".into(),
                 output: Some("mcp".into()),
             },),
+            MessageContent::ToolResult(LanguageModelToolResult {
+                tool_use_id: "tool_3".into(),
+                tool_name: "echo".into(),
+                is_error: false,
+                content: "native".into(),
+                output: Some("native".into()),
+            },),
         ]
     );
     fake_model.end_last_completion_stream();
@@ -1944,6 +2186,7 @@ async fn test_terminal_tool_cancellation_captures_output(cx: &mut TestAppContext
         .update(cx, |thread, cx| {
             thread.add_tool(crate::TerminalTool::new(
                 thread.project().clone(),
+                cx.weak_entity(),
                 environment,
             ));
             thread.send(UserMessageId::new(), ["run a command"], cx)
@@ -2102,23 +2345,370 @@ async fn test_cancellation_aware_tool_responds_to_cancellation(cx: &mut TestAppC
         }
     }
 
-    // Verify the tool detected cancellation via its flag
-    assert!(
-        was_cancelled.load(std::sync::atomic::Ordering::SeqCst),
-        "tool should have detected cancellation via event_stream.cancelled_by_user()"
+    // Verify the tool detected cancellation via its flag
+    assert!(
+        was_cancelled.load(std::sync::atomic::Ordering::SeqCst),
+        "tool should have detected cancellation via event_stream.cancelled_by_user()"
+    );
+
+    // Collect remaining events
+    let remaining_events = collect_events_until_stop(&mut events, cx).await;
+
+    // Verify we got a cancellation stop event
+    assert_eq!(
+        stop_events(remaining_events),
+        vec![acp::StopReason::Cancelled],
+    );
+
+    // Verify we can send a new message after cancellation
+    verify_thread_recovery(&thread, &fake_model, cx).await;
+}
+
+#[gpui::test]
+async fn test_tool_stats(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
+    let (cancellation_aware_tool, _) = CancellationAwareTool::new();
+
+    let first_message_id = UserMessageId::new();
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(EchoTool);
+            thread.add_tool(cancellation_aware_tool);
+            thread.send(first_message_id.clone(), ["echo twice"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    for (id, input) in [("echo_1", json!({"text": "hi"})), ("echo_2", json!({}))] {
+        fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+            LanguageModelToolUse {
+                id: id.into(),
+                name: EchoTool::NAME.into(),
+                raw_input: input.to_string(),
+                input,
+                is_input_complete: true,
+                thought_signature: None,
+            },
+        ));
+    }
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_text_chunk("Done");
+    fake_model.end_last_completion_stream();
+    collect_events_until_stop(&mut events, cx).await;
+
+    let stats = thread.read_with(cx, |thread, _| thread.tool_stats().clone());
+    assert_eq!(
+        stats
+            .calls()
+            .iter()
+            .map(|call| (call.tool_use_id.to_string(), call.outcome))
+            .collect::<Vec<_>>(),
+        vec![
+            ("echo_1".to_string(), ToolCallOutcome::Ok),
+            ("echo_2".to_string(), ToolCallOutcome::Error),
+        ]
+    );
+    assert!(
+        stats
+            .calls()
+            .iter()
+            .all(|call| call.turn.as_ref() == Some(&first_message_id))
+    );
+    let summary = stats.turn_by_tool(&first_message_id);
+    assert_eq!(summary.len(), 1);
+    assert_eq!((summary[0].calls, summary[0].errors), (2, 1));
+
+    // A tool that's still running when the turn is cancelled is recorded as cancelled, not
+    // as an error.
+    let second_message_id = UserMessageId::new();
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.send(second_message_id.clone(), ["run until cancelled"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "cancellation_aware_1".into(),
+            name: "cancellation_aware".into(),
+            raw_input: "{}".into(),
+            input: json!({}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    let tool_use_id = language_model::LanguageModelToolUseId::from("cancellation_aware_1");
+    assert!(
+        thread
+            .read_with(cx, |thread, _| thread.tool_call_running_for(&tool_use_id))
+            .is_some()
+    );
+
+    thread.update(cx, |thread, cx| thread.cancel(cx)).await;
+    collect_events_until_stop(&mut events, cx).await;
+    thread.read_with(cx, |thread, _| {
+        assert_eq!(thread.tool_call_running_for(&tool_use_id), None);
+        let calls = thread
+            .tool_stats()
+            .turn_calls(&second_message_id)
+            .collect::<Vec<_>>();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].outcome, ToolCallOutcome::Cancelled);
+        assert_eq!(thread.tool_stats().calls().len(), 3);
+    });
+}
+
+fn slow_tool_use(name: &str, id: &str, ms: u64) -> LanguageModelCompletionEvent {
+    let input = json!({"id": id, "ms": ms});
+    LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+        id: id.into(),
+        name: name.into(),
+        raw_input: input.to_string(),
+        input,
+        is_input_complete: true,
+        thought_signature: None,
+    })
+}
+
+fn tool_result_ids(completion: &LanguageModelRequest) -> Vec<String> {
+    completion
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            MessageContent::ToolResult(result) => Some(result.tool_use_id.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Advances the clock 10ms at a time, so that each timer that fires can start the next one.
+fn advance_clock_by_steps(duration: Duration, cx: &mut TestAppContext) {
+    let step = Duration::from_millis(10);
+    let mut elapsed = Duration::ZERO;
+    while elapsed < duration {
+        cx.executor().advance_clock(step);
+        cx.run_until_parked();
+        elapsed += step;
+    }
+}
+
+#[gpui::test]
+async fn test_read_only_tool_calls_run_concurrently(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    cx.update(|cx| {
+        let mut settings = agent_settings::AgentSettings::get_global(cx).clone();
+        settings.max_concurrent_tool_calls = 2;
+        agent_settings::AgentSettings::override_global(settings, cx);
+    });
+    let fake_model = model.as_fake();
+    let log = Arc::new(parking_lot::Mutex::new(SlowToolLog::default()));
+
+    let _events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(SlowReadTool { log: log.clone() });
+            thread.send(UserMessageId::new(), ["read a few things"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    for (id, ms) in [("a", 50), ("b", 10), ("c", 30), ("d", 20), ("e", 20)] {
+        fake_model.send_last_completion_stream_event(slow_tool_use(SlowReadTool::NAME, id, ms));
+        cx.run_until_parked();
+    }
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    assert_eq!(log.lock().running, 2);
+
+    advance_clock_by_steps(Duration::from_millis(100), cx);
+    assert_eq!(log.lock().max_running, 2);
+    assert_eq!(
+        log.lock().events,
+        [
+            "start a", "start b", "end b", "start c", "end c", "start d", "end a", "start e",
+            "end d", "end e",
+        ]
+    );
+
+    // The calls finished out of order, but their results go back to the model in call order.
+    let completion = fake_model.pending_completions().pop().unwrap();
+    assert_eq!(tool_result_ids(&completion), ["a", "b", "c", "d", "e"]);
+}
+
+#[gpui::test]
+async fn test_writing_tool_calls_wait_for_reads(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
+    let log = Arc::new(parking_lot::Mutex::new(SlowToolLog::default()));
+
+    let _events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(SlowReadTool { log: log.clone() });
+            thread.add_tool(SlowWriteTool { log: log.clone() });
+            thread.send(UserMessageId::new(), ["read and write"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    for (name, id, ms) in [
+        (SlowWriteTool::NAME, "w1", 10),
+        (SlowReadTool::NAME, "r1", 30),
+        (SlowWriteTool::NAME, "w2", 10),
+        (SlowReadTool::NAME, "r2", 20),
+    ] {
+        fake_model.send_last_completion_stream_event(slow_tool_use(name, id, ms));
+        cx.run_until_parked();
+    }
+
+    // Reads start while the model is still making calls, but writes wait for it to finish.
+    assert_eq!(log.lock().events, ["start r1", "start r2"]);
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    assert_eq!(log.lock().events, ["start r1", "start r2"]);
+
+    advance_clock_by_steps(Duration::from_millis(100), cx);
+    assert_eq!(log.lock().max_running, 2);
+    assert_eq!(
+        log.lock().events,
+        [
+            "start r1", "start r2", "end r2", "end r1", "start w1", "end w1", "start w2", "end w2",
+        ]
+    );
+
+    let completion = fake_model.pending_completions().pop().unwrap();
+    assert_eq!(tool_result_ids(&completion), ["w1", "r1", "w2", "r2"]);
+}
+
+#[gpui::test]
+async fn test_cancel_scheduled_tool_calls(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
+    let log = Arc::new(parking_lot::Mutex::new(SlowToolLog::default()));
+
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(SlowReadTool { log: log.clone() });
+            thread.add_tool(SlowWriteTool { log: log.clone() });
+            thread.send(UserMessageId::new(), ["read and write"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    for (name, id) in [
+        (SlowReadTool::NAME, "r1"),
+        (SlowReadTool::NAME, "r2"),
+        (SlowWriteTool::NAME, "w1"),
+    ] {
+        fake_model.send_last_completion_stream_event(slow_tool_use(name, id, 100));
+        cx.run_until_parked();
+    }
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    assert_eq!(log.lock().events, ["start r1", "start r2"]);
+
+    // Cancelling stops the running reads, and the queued write never starts.
+    thread.update(cx, |thread, cx| thread.cancel(cx)).await;
+    let events = collect_events_until_stop(&mut events, cx).await;
+    assert_eq!(stop_events(events), vec![acp::StopReason::Cancelled]);
+    advance_clock_by_steps(Duration::from_millis(200), cx);
+    let mut log_events = log.lock().events.clone();
+    log_events.sort();
+    assert_eq!(
+        log_events,
+        ["cancel r1", "cancel r2", "start r1", "start r2"]
     );
+    assert_eq!(log.lock().running, 0);
+    thread.read_with(cx, |thread, _| {
+        let message = thread.last_message().unwrap();
+        let tool_results = &message.as_agent_message().unwrap().tool_results;
+        let write_result = &tool_results[&language_model::LanguageModelToolUseId::from("w1")];
+        assert_eq!(write_result.content, "Tool canceled by user".into());
+    });
+}
 
-    // Collect remaining events
-    let remaining_events = collect_events_until_stop(&mut events, cx).await;
+#[gpui::test]
+async fn test_worktree_scope_changes_apply_to_later_tool_calls(cx: &mut TestAppContext) {
+    let ThreadTest {
+        model, thread, fs, ..
+    } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
 
-    // Verify we got a cancellation stop event
-    assert_eq!(
-        stop_events(remaining_events),
-        vec![acp::StopReason::Cancelled],
+    fs.insert_tree(path!("/backend"), json!({"api.md": "# API"}))
+        .await;
+    let project = thread.read_with(cx, |thread, _| thread.project().clone());
+    project
+        .update(cx, |project, cx| {
+            project.find_or_create_worktree(path!("/backend"), true, cx)
+        })
+        .await
+        .unwrap();
+    cx.run_until_parked();
+    let test_root = project.read_with(cx, |project, cx| {
+        project
+            .worktrees(cx)
+            .find(|worktree| worktree.read(cx).root_name_str() == "test")
+            .unwrap()
+            .read(cx)
+            .id()
+    });
+
+    let read_api_docs = |id: &str| {
+        let input = json!({"path": "backend/api.md"});
+        LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+            id: id.into(),
+            name: ReadFileTool::NAME.into(),
+            raw_input: input.to_string(),
+            input,
+            is_input_complete: true,
+            thought_signature: None,
+        })
+    };
+    let tool_result = |id: &str, cx: &mut TestAppContext| {
+        thread.read_with(cx, |thread, _| {
+            let message = thread.last_message().unwrap();
+            let tool_results = &message.as_agent_message().unwrap().tool_results;
+            tool_results[&language_model::LanguageModelToolUseId::from(id)]
+                .content
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+    };
+
+    let _events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(ReadFileTool::new(
+                cx.weak_entity(),
+                thread.project().clone(),
+                thread.action_log().clone(),
+            ));
+            thread.set_worktree_scope(Some(vec![test_root]), cx);
+            thread.send(UserMessageId::new(), ["read the API docs"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_event(read_api_docs("read_1"));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    let result = tool_result("read_1", cx);
+    assert!(
+        result.contains("backend/api.md is outside the agent's allowed scope (test)"),
+        "{result}"
     );
 
-    // Verify we can send a new message after cancellation
-    verify_thread_recovery(&thread, &fake_model, cx).await;
+    // Clearing the scope mid-turn lets the next call through.
+    thread.update(cx, |thread, cx| thread.set_worktree_scope(None, cx));
+    fake_model.send_last_completion_stream_event(read_api_docs("read_2"));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+    assert!(tool_result("read_2", cx).contains("# API"));
 }
 
 /// Helper to verify thread can recover after cancellation by sending a simple message.
@@ -2227,6 +2817,7 @@ async fn test_truncate_while_terminal_tool_running(cx: &mut TestAppContext) {
         .update(cx, |thread, cx| {
             thread.add_tool(crate::TerminalTool::new(
                 thread.project().clone(),
+                cx.weak_entity(),
                 environment,
             ));
             thread.send(message_id.clone(), ["run a command"], cx)
@@ -2279,8 +2870,9 @@ async fn test_truncate_while_terminal_tool_running(cx: &mut TestAppContext) {
 }
 
 #[gpui::test]
-async fn test_cancel_multiple_concurrent_terminal_tools(cx: &mut TestAppContext) {
-    // Tests that cancellation properly kills all running terminal tools when multiple are active.
+async fn test_cancel_running_and_queued_terminal_tools(cx: &mut TestAppContext) {
+    // Terminal commands run one at a time, so cancelling kills the running one and keeps the
+    // queued one from ever starting.
     let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
     always_allow_tools(cx);
     let fake_model = model.as_fake();
@@ -2291,6 +2883,7 @@ async fn test_cancel_multiple_concurrent_terminal_tools(cx: &mut TestAppContext)
         .update(cx, |thread, cx| {
             thread.add_tool(crate::TerminalTool::new(
                 thread.project().clone(),
+                cx.weak_entity(),
                 environment.clone(),
             ));
             thread.send(UserMessageId::new(), ["run multiple commands"], cx)
@@ -2322,7 +2915,7 @@ async fn test_cancel_multiple_concurrent_terminal_tools(cx: &mut TestAppContext)
     ));
     fake_model.end_last_completion_stream();
 
-    // Wait for both terminal tools to start by counting terminal content updates
+    // Wait for the first terminal tool to start by counting terminal content updates
     let mut terminals_started = 0;
     let deadline = cx.executor().num_cpus() * 100;
     for _ in 0..deadline {
@@ -2339,13 +2932,10 @@ async fn test_cancel_multiple_concurrent_terminal_tools(cx: &mut TestAppContext)
                         .any(|c| matches!(c, acp::ToolCallContent::Terminal(_)))
                 }) {
                     terminals_started += 1;
-                    if terminals_started >= 2 {
-                        break;
-                    }
                 }
             }
         }
-        if terminals_started >= 2 {
+        if terminals_started >= 1 {
             break;
         }
 
@@ -2353,38 +2943,179 @@ async fn test_cancel_multiple_concurrent_terminal_tools(cx: &mut TestAppContext)
             .timer(Duration::from_millis(10))
             .await;
     }
-    assert!(
-        terminals_started >= 2,
-        "expected 2 terminal tools to start, got {terminals_started}"
+    cx.run_until_parked();
+    assert_eq!(
+        environment.handles().len(),
+        1,
+        "expected only the first terminal tool to start"
     );
 
-    // Cancel the thread while both terminals are running
+    // Cancel the thread while the first terminal is running
     thread.update(cx, |thread, cx| thread.cancel(cx)).detach();
 
     // Collect remaining events
     let remaining_events = collect_events_until_stop(&mut events, cx).await;
 
-    // Verify both terminal handles were killed
+    // Verify the running terminal was killed and the queued one never started
     let handles = environment.handles();
     assert_eq!(
         handles.len(),
-        2,
-        "expected 2 terminal handles to be created"
+        1,
+        "expected the queued terminal tool not to start"
     );
     assert!(
         handles[0].was_killed(),
-        "expected first terminal handle to be killed on cancellation"
+        "expected the running terminal handle to be killed on cancellation"
+    );
+    thread.read_with(cx, |thread, _| {
+        let message = thread.last_message().unwrap();
+        let result = &message.as_agent_message().unwrap().tool_results
+            [&language_model::LanguageModelToolUseId::from("terminal_tool_2")];
+        assert_eq!(result.content, "Tool canceled by user".into());
+    });
+
+    // Verify we got a cancellation stop event
+    assert_eq!(
+        stop_events(remaining_events),
+        vec![acp::StopReason::Cancelled],
     );
+}
+
+/// The text shown on a tool card by a content update, if the event is one.
+fn tool_call_content_text(event: &Result<ThreadEvent>) -> Option<String> {
+    let Ok(ThreadEvent::ToolCallUpdate(acp_thread::ToolCallUpdate::UpdateFields(update))) = event
+    else {
+        return None;
+    };
+    let content = update.fields.content.as_ref()?;
+    Some(
+        content
+            .iter()
+            .filter_map(|content| match content {
+                acp::ToolCallContent::Content(acp::Content {
+                    content: acp::ContentBlock::Text(text),
+                    ..
+                }) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+fn partial_terminal_tool_use(id: &str, input: serde_json::Value) -> LanguageModelToolUse {
+    LanguageModelToolUse {
+        id: id.into(),
+        name: TerminalTool::NAME.into(),
+        raw_input: input.to_string(),
+        input,
+        is_input_complete: false,
+        thought_signature: None,
+    }
+}
+
+#[gpui::test]
+async fn test_terminal_command_previewed_while_input_streams(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
+
+    let environment = Rc::new(MultiTerminalEnvironment::new());
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(crate::TerminalTool::new(
+                thread.project().clone(),
+                cx.weak_entity(),
+                environment.clone(),
+            ));
+            thread.send(UserMessageId::new(), ["run the tests"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        partial_terminal_tool_use("terminal_tool_1", json!({"command": "cargo te"})),
+    ));
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        partial_terminal_tool_use("terminal_tool_1", json!({"command": "cargo test"})),
+    ));
+    cx.run_until_parked();
+
+    let previews = std::iter::from_fn(|| events.next().now_or_never().flatten())
+        .filter_map(|event| tool_call_content_text(&event))
+        .collect::<Vec<_>>();
+    assert_eq!(previews, ["```\ncargo te\n```\n", "```\ncargo test\n```\n"]);
     assert!(
-        handles[1].was_killed(),
-        "expected second terminal handle to be killed on cancellation"
+        environment.handles().is_empty(),
+        "the command shouldn't run before its input is complete"
     );
 
-    // Verify we got a cancellation stop event
+    // Once the input is complete, the preview makes way for the terminal.
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "terminal_tool_1".into(),
+            name: TerminalTool::NAME.into(),
+            raw_input: r#"{"command": "cargo test", "cd": "."}"#.into(),
+            input: json!({"command": "cargo test", "cd": "."}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+
+    let contents = std::iter::from_fn(|| events.next().now_or_never().flatten())
+        .filter_map(|event| tool_call_content_text(&event))
+        .collect::<Vec<_>>();
+    assert_eq!(contents.first().map(String::as_str), Some(""));
+    assert_eq!(environment.handles().len(), 1);
+
+    thread.update(cx, |thread, cx| thread.cancel(cx)).detach();
+    collect_events_until_stop(&mut events, cx).await;
+}
+
+#[gpui::test]
+async fn test_cancel_while_tool_input_streams(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    always_allow_tools(cx);
+    let fake_model = model.as_fake();
+
+    let environment = Rc::new(MultiTerminalEnvironment::new());
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(crate::TerminalTool::new(
+                thread.project().clone(),
+                cx.weak_entity(),
+                environment.clone(),
+            ));
+            thread.send(UserMessageId::new(), ["clean up"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        partial_terminal_tool_use("terminal_tool_1", json!({"command": "rm -rf tar"})),
+    ));
+    cx.run_until_parked();
+
+    // The user cancels after seeing where the command is going.
+    thread.update(cx, |thread, cx| thread.cancel(cx)).detach();
+    let remaining_events = collect_events_until_stop(&mut events, cx).await;
     assert_eq!(
         stop_events(remaining_events),
         vec![acp::StopReason::Cancelled],
     );
+
+    assert!(
+        environment.handles().is_empty(),
+        "a call cancelled while its input streamed should never run"
+    );
+    thread.read_with(cx, |thread, _| {
+        let message = thread.last_message().unwrap();
+        let result = &message.as_agent_message().unwrap().tool_results
+            [&language_model::LanguageModelToolUseId::from("terminal_tool_1")];
+        assert_eq!(result.content, "Tool canceled by user".into());
+    });
 }
 
 #[gpui::test]
@@ -2404,6 +3135,7 @@ async fn test_terminal_tool_stopped_via_terminal_card_button(cx: &mut TestAppCon
         .update(cx, |thread, cx| {
             thread.add_tool(crate::TerminalTool::new(
                 thread.project().clone(),
+                cx.weak_entity(),
                 environment,
             ));
             thread.send(UserMessageId::new(), ["run a command"], cx)
@@ -2498,6 +3230,7 @@ async fn test_terminal_tool_timeout_expires(cx: &mut TestAppContext) {
         .update(cx, |thread, cx| {
             thread.add_tool(crate::TerminalTool::new(
                 thread.project().clone(),
+                cx.weak_entity(),
                 environment,
             ));
             thread.send(UserMessageId::new(), ["run a command with timeout"], cx)
@@ -3009,10 +3742,10 @@ async fn test_building_request_with_pending_tools(cx: &mut TestAppContext) {
     };
     fake_model.send_last_completion_stream_text_chunk("Hi!");
     fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
-        permission_tool_use,
+        echo_tool_use.clone(),
     ));
     fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
-        echo_tool_use.clone(),
+        permission_tool_use,
     ));
     fake_model.end_last_completion_stream();
     cx.run_until_parked();
@@ -3563,7 +4296,10 @@ async fn setup(cx: &mut TestAppContext, model: TestModel) -> ThreadTest {
                             ToolRequiringPermission::NAME: true,
                             InfiniteTool::NAME: true,
                             CancellationAwareTool::NAME: true,
+                            SlowReadTool::NAME: true,
+                            SlowWriteTool::NAME: true,
                             (TerminalTool::NAME): true,
+                            (ReadFileTool::NAME): true,
                         }
                     }
                 }
@@ -3942,6 +4678,138 @@ async fn test_tokens_before_message_after_truncate(cx: &mut TestAppContext) {
     });
 }
 
+#[gpui::test]
+async fn test_terminal_tool_runs_edited_command(cx: &mut TestAppContext) {
+    init_test(cx);
+
+    let fs = FakeFs::new(cx.executor());
+    fs.insert_tree("/root", json!({})).await;
+    let project = Project::test(fs, ["/root".as_ref()], cx).await;
+    confirm_terminal_commands(cx);
+
+    let environment = Rc::new(cx.update(|cx| {
+        FakeThreadEnvironment::default()
+            .with_terminal(FakeTerminalHandle::new_with_immediate_exit(cx, 0))
+    }));
+    #[allow(clippy::arc_with_non_send_sync)]
+    let tool = Arc::new(crate::TerminalTool::new(
+        project,
+        gpui::WeakEntity::new_invalid(),
+        environment.clone(),
+    ));
+    let (event_stream, mut rx) = crate::ToolCallEventStream::test();
+
+    let task = cx.update(|cx| {
+        tool.run(
+            ToolInput::resolved(crate::TerminalToolInput {
+                command: "ehco hello".to_string(),
+                cd: ".".to_string(),
+                timeout_ms: None,
+                watch: None,
+            }),
+            event_stream.clone(),
+            cx,
+        )
+    });
+
+    let auth = rx.expect_authorization().await;
+    let editable_input = auth
+        .editable_input
+        .expect("terminal commands should be editable");
+    assert_eq!(editable_input.input["command"], "ehco hello");
+    let error = editable_input
+        .validate(&json!({ "command": "echo hello" }))
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("missing field `cd`"),
+        "unexpected error: {error}"
+    );
+
+    let edited_input = json!({ "command": "echo hello", "cd": "." });
+    editable_input.send_edited_input(edited_input.clone());
+    auth.response
+        .send(acp::PermissionOptionId::new(
+            acp_thread::EDITED_INPUT_OPTION_ID,
+        ))
+        .unwrap();
+
+    task.await.unwrap();
+    assert_eq!(
+        *environment.commands.borrow(),
+        vec!["echo hello".to_string()]
+    );
+    assert_eq!(event_stream.edited_input(), Some(edited_input));
+}
+
+#[gpui::test]
+async fn test_terminal_tool_allow_and_deny_ignore_editable_input(cx: &mut TestAppContext) {
+    init_test(cx);
+
+    let fs = FakeFs::new(cx.executor());
+    fs.insert_tree("/root", json!({})).await;
+    let project = Project::test(fs, ["/root".as_ref()], cx).await;
+    confirm_terminal_commands(cx);
+
+    for (option_id, expected_commands) in [("allow", vec!["echo hello"]), ("deny", vec![])] {
+        let environment = Rc::new(cx.update(|cx| {
+            FakeThreadEnvironment::default()
+                .with_terminal(FakeTerminalHandle::new_with_immediate_exit(cx, 0))
+        }));
+        #[allow(clippy::arc_with_non_send_sync)]
+        let tool = Arc::new(crate::TerminalTool::new(
+            project.clone(),
+            gpui::WeakEntity::new_invalid(),
+            environment.clone(),
+        ));
+        let (event_stream, mut rx) = crate::ToolCallEventStream::test();
+
+        let task = cx.update(|cx| {
+            tool.run(
+                ToolInput::resolved(crate::TerminalToolInput {
+                    command: "echo hello".to_string(),
+                    cd: ".".to_string(),
+                    timeout_ms: None,
+                    watch: None,
+                }),
+                event_stream.clone(),
+                cx,
+            )
+        });
+
+        let auth = rx.expect_authorization().await;
+        assert!(auth.editable_input.is_some());
+        auth.response
+            .send(acp::PermissionOptionId::new(option_id))
+            .unwrap();
+
+        let result = task.await;
+        assert_eq!(
+            result.is_ok(),
+            option_id == "allow",
+            "{option_id}: {result:?}"
+        );
+        assert_eq!(*environment.commands.borrow(), expected_commands);
+        assert_eq!(event_stream.edited_input(), None);
+    }
+}
+
+fn confirm_terminal_commands(cx: &mut TestAppContext) {
+    cx.update(|cx| {
+        let mut settings = agent_settings::AgentSettings::get_global(cx).clone();
+        settings.tool_permissions.tools.insert(
+            TerminalTool::NAME.into(),
+            agent_settings::ToolRules {
+                default: Some(settings::ToolPermissionMode::Confirm),
+                always_allow: vec![],
+                always_deny: vec![],
+                always_confirm: vec![],
+                invalid_patterns: vec![],
+            },
+        );
+        agent_settings::AgentSettings::override_global(settings, cx);
+    });
+}
+
 #[gpui::test]
 async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
     init_test(cx);
@@ -3974,7 +4842,11 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
         });
 
         #[allow(clippy::arc_with_non_send_sync)]
-        let tool = Arc::new(crate::TerminalTool::new(project.clone(), environment));
+        let tool = Arc::new(crate::TerminalTool::new(
+            project.clone(),
+            gpui::WeakEntity::new_invalid(),
+            environment,
+        ));
         let (event_stream, _rx) = crate::ToolCallEventStream::test();
 
         let task = cx.update(|cx| {
@@ -3983,6 +4855,7 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
                     command: "rm -rf /".to_string(),
                     cd: ".".to_string(),
                     timeout_ms: None,
+                    watch: None,
                 }),
                 event_stream,
                 cx,
@@ -4026,7 +4899,11 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
         });
 
         #[allow(clippy::arc_with_non_send_sync)]
-        let tool = Arc::new(crate::TerminalTool::new(project.clone(), environment));
+        let tool = Arc::new(crate::TerminalTool::new(
+            project.clone(),
+            gpui::WeakEntity::new_invalid(),
+            environment,
+        ));
         let (event_stream, mut rx) = crate::ToolCallEventStream::test();
 
         let task = cx.update(|cx| {
@@ -4035,6 +4912,7 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
                     command: "echo hello".to_string(),
                     cd: ".".to_string(),
                     timeout_ms: None,
+                    watch: None,
                 }),
                 event_stream,
                 cx,
@@ -4084,7 +4962,11 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
         });
 
         #[allow(clippy::arc_with_non_send_sync)]
-        let tool = Arc::new(crate::TerminalTool::new(project.clone(), environment));
+        let tool = Arc::new(crate::TerminalTool::new(
+            project.clone(),
+            gpui::WeakEntity::new_invalid(),
+            environment,
+        ));
         let (event_stream, mut rx) = crate::ToolCallEventStream::test();
 
         let _task = cx.update(|cx| {
@@ -4093,6 +4975,7 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
                     command: "sudo rm file".to_string(),
                     cd: ".".to_string(),
                     timeout_ms: None,
+                    watch: None,
                 }),
                 event_stream,
                 cx,
@@ -4131,7 +5014,11 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
         });
 
         #[allow(clippy::arc_with_non_send_sync)]
-        let tool = Arc::new(crate::TerminalTool::new(project.clone(), environment));
+        let tool = Arc::new(crate::TerminalTool::new(
+            project.clone(),
+            gpui::WeakEntity::new_invalid(),
+            environment,
+        ));
         let (event_stream, _rx) = crate::ToolCallEventStream::test();
 
         let task = cx.update(|cx| {
@@ -4140,6 +5027,7 @@ async fn test_terminal_tool_permission_rules(cx: &mut TestAppContext) {
                     command: "echo hello".to_string(),
                     cd: ".".to_string(),
                     timeout_ms: None,
+                    watch: None,
                 }),
                 event_stream,
                 cx,
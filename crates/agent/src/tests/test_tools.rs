@@ -1,5 +1,6 @@
 use super::*;
 use agent_settings::AgentSettings;
+use futures::FutureExt as _;
 use gpui::{App, SharedString, Task};
 use std::future;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -165,6 +166,60 @@ impl AgentTool for ToolRequiringPermission {
     }
 }
 
+#[derive(JsonSchema, Serialize, Deserialize)]
+pub struct EditableEchoToolInput {
+    /// The text to echo.
+    pub text: String,
+}
+
+/// A tool that asks for permission before echoing its input, which the user can edit.
+pub struct EditableEchoTool;
+
+impl AgentTool for EditableEchoTool {
+    type Input = EditableEchoToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "editable_echo";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Other
+    }
+
+    fn allows_input_editing() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        _input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        "Echo".into()
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<String, String>> {
+        cx.spawn(async move |cx| {
+            let input = input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+
+            let authorize = cx.update(|cx| {
+                let context =
+                    crate::ToolPermissionContext::new(Self::NAME, vec![input.text.clone()]);
+                event_stream.authorize_input::<Self>("Echo?", context, &input, cx)
+            });
+            let input = authorize.await.map_err(|e| e.to_string())?.unwrap_or(input);
+            Ok(input.text)
+        })
+    }
+}
+
 #[derive(JsonSchema, Serialize, Deserialize)]
 pub struct InfiniteToolInput {}
 
@@ -318,3 +373,124 @@ impl AgentTool for WordListTool {
         })
     }
 }
+
+/// What the [`SlowReadTool`]s and [`SlowWriteTool`]s sharing a log have done, in order.
+#[derive(Default)]
+pub struct SlowToolLog {
+    pub events: Vec<String>,
+    pub running: usize,
+    pub max_running: usize,
+}
+
+/// A tool that takes a while to run.
+#[derive(JsonSchema, Serialize, Deserialize)]
+pub struct SlowToolInput {
+    /// An id to tell the calls apart.
+    id: String,
+    /// How long to run for, in milliseconds.
+    ms: u64,
+}
+
+fn run_slow_tool(
+    log: Arc<parking_lot::Mutex<SlowToolLog>>,
+    input: ToolInput<SlowToolInput>,
+    event_stream: ToolCallEventStream,
+    cx: &mut App,
+) -> Task<Result<String, String>> {
+    let executor = cx.background_executor().clone();
+    cx.foreground_executor().spawn(async move {
+        let input = input
+            .recv()
+            .await
+            .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+        {
+            let mut log = log.lock();
+            log.events.push(format!("start {}", input.id));
+            log.running += 1;
+            log.max_running = log.max_running.max(log.running);
+        }
+        let cancelled = futures::select! {
+            _ = executor.timer(Duration::from_millis(input.ms)).fuse() => false,
+            _ = event_stream.cancelled_by_user().fuse() => true,
+        };
+        let mut log = log.lock();
+        log.running -= 1;
+        if cancelled {
+            log.events.push(format!("cancel {}", input.id));
+            Err("Tool cancelled by user".to_string())
+        } else {
+            log.events.push(format!("end {}", input.id));
+            Ok(input.id)
+        }
+    })
+}
+
+/// A read-only tool that takes a while to run.
+pub struct SlowReadTool {
+    pub log: Arc<parking_lot::Mutex<SlowToolLog>>,
+}
+
+impl AgentTool for SlowReadTool {
+    type Input = SlowToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "slow_read";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        _input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        "Slow Read".into()
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<String, String>> {
+        run_slow_tool(self.log.clone(), input, event_stream, cx)
+    }
+}
+
+/// A tool that changes things and takes a while to run.
+pub struct SlowWriteTool {
+    pub log: Arc<parking_lot::Mutex<SlowToolLog>>,
+}
+
+impl AgentTool for SlowWriteTool {
+    type Input = SlowToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "slow_write";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn initial_title(
+        &self,
+        _input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        "Slow Write".into()
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<String, String>> {
+        run_slow_tool(self.log.clone(), input, event_stream, cx)
+    }
+}
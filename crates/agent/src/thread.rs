@@ -1,13 +1,16 @@
 use crate::{
-    ContextServerRegistry, CopyPathTool, CreateDirectoryTool, DbLanguageModel, DbThread,
-    DeletePathTool, DiagnosticsTool, EditFileTool, FetchTool, FindPathTool, GrepTool,
-    ListDirectoryTool, MovePathTool, NowTool, OpenTool, ProjectSnapshot, ReadFileTool,
-    RestoreFileFromDiskTool, SaveFileTool, SpawnAgentTool, StreamingEditFileTool,
-    SystemPromptTemplate, Template, Templates, TerminalTool, ToolPermissionDecision, WebSearchTool,
-    decide_permission_from_settings,
+    CodeSymbolsTool, ContextServerRegistry, CopyPathTool, CreateDirectoryTool, DbLanguageModel,
+    DbThread, DeletePathTool, DiagnosticsTool, EditFileTool, EditGrants, FetchTool,
+    FileHistoryTool, FindPathTool, GrepTool, HistoryBudget, ListDirectoryTool,
+    ListTouchedFilesTool, MovePathTool, NowTool, OpenTool, PendingApproval, PendingBuffer,
+    ProjectSnapshot, ReadFileTool, ReadInstructionsTool, RestoreFileFromDiskTool, SaveFileTool,
+    SpawnAgentTool, StreamingEditFileTool, SystemPromptTemplate, TaskList, TaskListTool, Template,
+    Templates, TerminalTool, ToolCallOutcome, ToolCallTiming, ToolOutputBudget,
+    ToolPermissionDecision, ToolScheduler, ToolStats, ToolTimingSummary, TouchedFileStatus,
+    UsageLedger, WebSearchTool, WorkingSet, WorktreeScope, decide_permission_from_settings,
 };
 use acp_thread::{MentionUri, UserMessageId};
-use action_log::ActionLog;
+use action_log::{ActionLog, BufferCheckpoint, RestoredFile};
 use feature_flags::{
     FeatureFlagAppExt as _, StreamingEditFileToolFeatureFlag, SubagentsFeatureFlag,
 };
@@ -43,7 +46,8 @@ use language_model::{
     LanguageModelToolUseId, Role, SelectedModel, Speed, StopReason, TokenUsage,
     ZED_CLOUD_PROVIDER_ID,
 };
-use project::Project;
+use project::lsp_store::{FormatTrigger, LspFormatTarget};
+use project::{Project, ProjectPath, WorktreeId};
 use prompt_store::ProjectContext;
 use schemars::{JsonSchema, Schema};
 use serde::de::DeserializeOwned;
@@ -51,7 +55,7 @@ use serde::{Deserialize, Serialize};
 use settings::{LanguageModelSelection, Settings, ToolPermissionMode, update_settings_file};
 use smol::stream::StreamExt;
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, VecDeque},
     marker::PhantomData,
     ops::RangeInclusive,
     path::Path,
@@ -66,6 +70,8 @@ use uuid::Uuid;
 const TOOL_CANCELED_MESSAGE: &str = "Tool canceled by user";
 pub const MAX_TOOL_NAME_LENGTH: usize = 64;
 pub const MAX_SUBAGENT_DEPTH: u8 = 1;
+/// How many of the most recent user turns keep a checkpoint of the files they changed.
+const MAX_CHECKPOINTS: usize = 10;
 
 /// Context passed to a subagent thread for lifecycle management
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -447,6 +453,23 @@ fn codeblock_tag(full_path: &Path, line_range: Option<&RangeInclusive<u32>>) ->
 }
 
 impl AgentMessage {
+    /// Puts the tool results in the order the tools were called, whatever order they finished in.
+    fn sort_tool_results(&mut self) {
+        let call_order = self
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                AgentMessageContent::ToolUse(tool_use) => Some(tool_use.id.clone()),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(ix, tool_use_id)| (tool_use_id, ix))
+            .collect::<HashMap<_, _>>();
+        self.tool_results.sort_by_cached_key(|tool_use_id, _| {
+            call_order.get(tool_use_id).copied().unwrap_or(usize::MAX)
+        });
+    }
+
     pub fn to_markdown(&self) -> String {
         let mut markdown = String::new();
 
@@ -640,6 +663,7 @@ pub enum ThreadEvent {
     ToolCallUpdate(acp_thread::ToolCallUpdate),
     ToolCallAuthorization(ToolCallAuthorization),
     SubagentSpawned(acp::SessionId),
+    Plan(acp::Plan),
     Retry(acp_thread::RetryStatus),
     Stop(acp::StopReason),
 }
@@ -657,20 +681,65 @@ pub struct ToolPermissionContext {
     pub tool_name: String,
     pub input_values: Vec<String>,
     pub scope: ToolPermissionScope,
+    /// The grants the prompt offers for the rest of the thread, for prompts about edits.
+    pub edit_grants: Vec<EditGrantScope>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolPermissionScope {
     ToolInput,
     SymlinkTarget,
+    UnsavedChanges,
+    Credential,
+}
+
+/// What an approval for an edit also allows for the rest of the thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditGrantScope {
+    /// Later edits to the same file.
+    File,
+    /// Later edits to any file in the same directory, or below it.
+    Directory,
+}
+
+impl EditGrantScope {
+    pub fn option_id(self) -> acp::PermissionOptionId {
+        acp::PermissionOptionId::new(self.id())
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            EditGrantScope::File => ALLOW_FILE_FOR_SESSION,
+            EditGrantScope::Directory => ALLOW_DIRECTORY_FOR_SESSION,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            EditGrantScope::File => "Allow for this file (this session)",
+            EditGrantScope::Directory => "Allow for this directory (this session)",
+        }
+    }
+
+    fn from_option_id(option_id: &str) -> Option<Self> {
+        match option_id {
+            ALLOW_FILE_FOR_SESSION => Some(EditGrantScope::File),
+            ALLOW_DIRECTORY_FOR_SESSION => Some(EditGrantScope::Directory),
+            _ => None,
+        }
+    }
 }
 
+const ALLOW_FILE_FOR_SESSION: &str = "allow_file_for_session";
+const ALLOW_DIRECTORY_FOR_SESSION: &str = "allow_directory_for_session";
+
 impl ToolPermissionContext {
     pub fn new(tool_name: impl Into<String>, input_values: Vec<String>) -> Self {
         Self {
             tool_name: tool_name.into(),
             input_values,
             scope: ToolPermissionScope::ToolInput,
+            edit_grants: Vec::new(),
         }
     }
 
@@ -679,9 +748,34 @@ impl ToolPermissionContext {
             tool_name: tool_name.into(),
             input_values: target_paths,
             scope: ToolPermissionScope::SymlinkTarget,
+            edit_grants: Vec::new(),
         }
     }
 
+    pub fn unsaved_changes(tool_name: impl Into<String>, paths: Vec<String>) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            input_values: paths,
+            scope: ToolPermissionScope::UnsavedChanges,
+            edit_grants: Vec::new(),
+        }
+    }
+
+    pub fn credential(tool_name: impl Into<String>, paths: Vec<String>) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            input_values: paths,
+            scope: ToolPermissionScope::Credential,
+            edit_grants: Vec::new(),
+        }
+    }
+
+    /// Offers to also allow later edits to the same file or directory for the rest of the thread.
+    pub fn with_edit_grants(mut self, edit_grants: Vec<EditGrantScope>) -> Self {
+        self.edit_grants = edit_grants;
+        self
+    }
+
     /// Builds the permission options for this tool context.
     ///
     /// This is the canonical source for permission option generation.
@@ -712,19 +806,30 @@ impl ToolPermissionContext {
 
         let tool_name = &self.tool_name;
         let input_values = &self.input_values;
-        if self.scope == ToolPermissionScope::SymlinkTarget {
-            return acp_thread::PermissionOptions::Flat(vec![
-                acp::PermissionOption::new(
-                    acp::PermissionOptionId::new("allow"),
-                    "Yes",
-                    acp::PermissionOptionKind::AllowOnce,
-                ),
+        if matches!(
+            self.scope,
+            ToolPermissionScope::SymlinkTarget
+                | ToolPermissionScope::UnsavedChanges
+                | ToolPermissionScope::Credential
+        ) {
+            let mut options = vec![acp::PermissionOption::new(
+                acp::PermissionOptionId::new("allow"),
+                "Yes",
+                acp::PermissionOptionKind::AllowOnce,
+            )];
+            options.extend(self.edit_grants.iter().map(|scope| {
                 acp::PermissionOption::new(
-                    acp::PermissionOptionId::new("deny"),
-                    "No",
-                    acp::PermissionOptionKind::RejectOnce,
-                ),
-            ]);
+                    scope.option_id(),
+                    scope.label(),
+                    acp::PermissionOptionKind::AllowAlways,
+                )
+            }));
+            options.push(acp::PermissionOption::new(
+                acp::PermissionOptionId::new("deny"),
+                "No",
+                acp::PermissionOptionKind::RejectOnce,
+            ));
+            return acp_thread::PermissionOptions::Flat(options);
         }
 
         // Check if the user's shell supports POSIX-like command chaining.
@@ -823,6 +928,17 @@ impl ToolPermissionContext {
             }
         }
 
+        // Grants for the rest of the thread have nothing to deny beyond this one edit.
+        for scope in &self.edit_grants {
+            push_choice(
+                scope.label().to_string(),
+                scope.id().to_string(),
+                "deny".to_string(),
+                acp::PermissionOptionKind::AllowAlways,
+                acp::PermissionOptionKind::RejectOnce,
+            );
+        }
+
         push_choice(
             "Only this time".to_string(),
             "allow".to_string(),
@@ -841,6 +957,7 @@ pub struct ToolCallAuthorization {
     pub options: acp_thread::PermissionOptions,
     pub response: oneshot::Sender<acp::PermissionOptionId>,
     pub context: Option<ToolPermissionContext>,
+    pub editable_input: Option<acp_thread::EditableToolInput>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -890,8 +1007,31 @@ pub struct Thread {
     pub(crate) prompt_capabilities_rx: watch::Receiver<acp::PromptCapabilities>,
     pub(crate) project: Entity<Project>,
     pub(crate) action_log: Entity<ActionLog>,
-    /// Tracks the last time files were read by the agent, to detect external modifications
-    pub(crate) file_read_times: HashMap<PathBuf, fs::MTime>,
+    /// The files the agent has read or edited, which is also used to detect external
+    /// modifications since.
+    working_set: WorkingSet,
+    /// The plan the agent keeps with the `task_list` tool.
+    task_list: TaskList,
+    /// How long the agent's tool calls took.
+    tool_stats: ToolStats,
+    /// The tokens used by every request sent to the model.
+    usage_ledger: UsageLedger,
+    /// The project roots the agent's tools are confined to, when the user picked some.
+    worktree_scope: Option<WorktreeScope>,
+    /// When the tool calls that haven't finished yet were requested and started running.
+    tool_call_timers: HashMap<LanguageModelToolUseId, ToolCallTimer>,
+    /// The files changed in response to recent user messages, as they were before, oldest first.
+    checkpoints: VecDeque<TurnCheckpoint>,
+    /// The user message whose turn the action log is recording a checkpoint for.
+    recording_checkpoint: Option<UserMessageId>,
+    /// Whether the user agreed to let the agent edit files on top of their unsaved changes.
+    pub(crate) unsaved_edits_authorized: bool,
+    /// Buffers the agent edited that won't be saved until the user approves the edits.
+    pending_approval: PendingApproval,
+    /// How many old tool results were left out of the latest request to fit the context window.
+    compacted_tool_results: usize,
+    /// The files and directories the user allowed the agent to edit without asking again.
+    pub(crate) edit_grants: EditGrants,
     /// True if this thread was imported from a shared thread and can be synced.
     imported: bool,
     /// If this is a subagent thread, contains context about the parent
@@ -926,6 +1066,7 @@ impl Thread {
             parent_thread_id: parent_thread.read(cx).id().clone(),
             depth: parent_thread.read(cx).depth() + 1,
         });
+        thread.worktree_scope = parent_thread.read(cx).worktree_scope.clone();
         thread
     }
 
@@ -985,7 +1126,18 @@ impl Thread {
             prompt_capabilities_rx,
             project,
             action_log,
-            file_read_times: HashMap::default(),
+            working_set: WorkingSet::default(),
+            task_list: TaskList::default(),
+            tool_stats: ToolStats::default(),
+            usage_ledger: UsageLedger::default(),
+            worktree_scope: None,
+            tool_call_timers: HashMap::default(),
+            checkpoints: VecDeque::new(),
+            recording_checkpoint: None,
+            unsaved_edits_authorized: false,
+            pending_approval: PendingApproval::default(),
+            compacted_tool_results: 0,
+            edit_grants: EditGrants::default(),
             imported: false,
             subagent_context: None,
             running_subagents: Vec::new(),
@@ -1032,6 +1184,9 @@ impl Thread {
                 Message::Resume => {}
             }
         }
+        if !self.task_list.is_empty() {
+            stream.send_plan(self.task_list.to_acp_plan());
+        }
         rx
     }
 
@@ -1109,6 +1264,8 @@ impl Thread {
                 tool_use.id.clone(),
                 stream.clone(),
                 Some(self.project.read(cx).fs().clone()),
+                self.tool_output_budget(),
+                self.tool_worktree_scope(cx),
                 cancellation_rx,
             );
             tool.replay(tool_use.input.clone(), output, tool_event_stream, cx)
@@ -1124,6 +1281,77 @@ impl Thread {
         );
     }
 
+    /// Applies a tool call from this thread's history again, reporting the result as updates to
+    /// that tool call.
+    pub fn reapply_tool_call(
+        &mut self,
+        tool_use_id: &LanguageModelToolUseId,
+        cx: &mut Context<Self>,
+    ) -> Result<mpsc::UnboundedReceiver<Result<ThreadEvent>>> {
+        let (tool_use, tool_result) = self
+            .messages
+            .iter()
+            .find_map(|message| {
+                let Message::Agent(message) = message else {
+                    return None;
+                };
+                message.content.iter().find_map(|content| match content {
+                    AgentMessageContent::ToolUse(tool_use) if &tool_use.id == tool_use_id => {
+                        Some((
+                            tool_use.clone(),
+                            message.tool_results.get(tool_use_id).cloned(),
+                        ))
+                    }
+                    _ => None,
+                })
+            })
+            .context("Tool call not found")?;
+        let output = tool_result
+            .filter(|result| !result.is_error)
+            .and_then(|result| result.output)
+            .context("Only tool calls that succeeded can be re-applied")?;
+        let tool = self
+            .tools
+            .get(tool_use.name.as_ref())
+            .cloned()
+            .with_context(|| format!("Tool {} is not available", tool_use.name))?;
+
+        let (tx, rx) = mpsc::unbounded();
+        let stream = ThreadEventStream(tx);
+        let (cancellation_tx, cancellation_rx) = watch::channel(false);
+        let tool_event_stream = ToolCallEventStream::new(
+            tool_use.id.clone(),
+            stream.clone(),
+            Some(self.project.read(cx).fs().clone()),
+            self.tool_output_budget(),
+            self.tool_worktree_scope(cx),
+            cancellation_rx,
+        );
+        let task = tool.reapply(tool_use.input.clone(), output, tool_event_stream, cx)?;
+        stream.update_tool_call_fields(
+            &tool_use.id,
+            acp::ToolCallUpdateFields::new().status(acp::ToolCallStatus::InProgress),
+            None,
+        );
+        cx.foreground_executor()
+            .spawn(async move {
+                let _cancellation_tx = cancellation_tx;
+                let (status, output) = match task.await {
+                    Ok(output) => (acp::ToolCallStatus::Completed, output),
+                    Err(output) => (acp::ToolCallStatus::Failed, output),
+                };
+                stream.update_tool_call_fields(
+                    &tool_use.id,
+                    acp::ToolCallUpdateFields::new()
+                        .status(status)
+                        .raw_output(Some(output.raw_output)),
+                    None,
+                );
+            })
+            .detach();
+        Ok(rx)
+    }
+
     pub fn from_db(
         id: acp::SessionId,
         db_thread: DbThread,
@@ -1165,6 +1393,10 @@ impl Thread {
             watch::channel(Self::prompt_capabilities(model.as_deref()));
 
         let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let worktree_scope = db_thread.worktree_scope.map(|mut scope| {
+            scope.resolve(project.read(cx), cx);
+            scope
+        });
 
         Self {
             id,
@@ -1200,7 +1432,18 @@ impl Thread {
             updated_at: db_thread.updated_at,
             prompt_capabilities_tx,
             prompt_capabilities_rx,
-            file_read_times: HashMap::default(),
+            working_set: db_thread.working_set,
+            task_list: db_thread.task_list,
+            tool_stats: db_thread.tool_stats,
+            usage_ledger: db_thread.usage_ledger,
+            worktree_scope,
+            tool_call_timers: HashMap::default(),
+            checkpoints: VecDeque::new(),
+            recording_checkpoint: None,
+            unsaved_edits_authorized: false,
+            pending_approval: PendingApproval::default(),
+            compacted_tool_results: 0,
+            edit_grants: EditGrants::default(),
             imported: db_thread.imported,
             subagent_context: db_thread.subagent_context,
             running_subagents: Vec::new(),
@@ -1227,6 +1470,11 @@ impl Thread {
             speed: self.speed,
             thinking_enabled: self.thinking_enabled,
             thinking_effort: self.thinking_effort.clone(),
+            working_set: self.working_set.clone(),
+            task_list: self.task_list.clone(),
+            tool_stats: self.tool_stats.clone(),
+            usage_ledger: self.usage_ledger.clone(),
+            worktree_scope: self.worktree_scope.clone(),
         };
 
         cx.background_spawn(async move {
@@ -1268,10 +1516,401 @@ impl Thread {
         self.messages.is_empty() && self.title.is_none()
     }
 
+    pub fn working_set(&self) -> &WorkingSet {
+        &self.working_set
+    }
+
+    pub fn task_list(&self) -> &TaskList {
+        &self.task_list
+    }
+
+    pub(crate) fn update_task_list<R>(
+        &mut self,
+        update: impl FnOnce(&mut TaskList) -> Result<R>,
+        cx: &mut Context<Self>,
+    ) -> Result<R> {
+        let result = update(&mut self.task_list)?;
+        cx.notify();
+        Ok(result)
+    }
+
+    pub fn tool_stats(&self) -> &ToolStats {
+        &self.tool_stats
+    }
+
+    /// The tool calls made in response to the latest user message, added up by tool.
+    pub fn latest_turn_tool_stats(&self) -> Vec<ToolTimingSummary> {
+        self.last_user_message()
+            .map(|message| self.tool_stats.turn_by_tool(&message.id))
+            .unwrap_or_default()
+    }
+
+    pub fn usage_ledger(&self) -> &UsageLedger {
+        &self.usage_ledger
+    }
+
+    /// Whether the thread's estimated cost is past the budget in the user's settings.
+    pub fn exceeds_cost_budget(&self, cx: &App) -> bool {
+        let settings = AgentSettings::get_global(cx);
+        settings.thread_cost_budget.is_some_and(|budget| {
+            self.usage_ledger
+                .exceeds_budget(budget, &settings.model_prices)
+        })
+    }
+
+    /// How long a tool call has been running, if it's still running.
+    pub fn tool_call_running_for(&self, tool_use_id: &LanguageModelToolUseId) -> Option<Duration> {
+        let started_at = self.tool_call_timers.get(tool_use_id)?.started_at?;
+        Some(started_at.elapsed())
+    }
+
+    fn tool_call_timer(&mut self, tool_use_id: &LanguageModelToolUseId) -> &mut ToolCallTimer {
+        let turn = self.last_user_message().map(|message| message.id.clone());
+        self.tool_call_timers
+            .entry(tool_use_id.clone())
+            .or_insert_with(|| ToolCallTimer {
+                turn,
+                requested_at: Instant::now(),
+                started_at: None,
+            })
+    }
+
+    fn finish_tool_call(
+        &mut self,
+        tool_use_id: LanguageModelToolUseId,
+        tool_name: Arc<str>,
+        outcome: ToolCallOutcome,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(timer) = self.tool_call_timers.remove(&tool_use_id) else {
+            return;
+        };
+        let finished_at = Instant::now();
+        let started_at = timer.started_at.unwrap_or(finished_at);
+        self.tool_stats.record(ToolCallTiming {
+            tool_use_id,
+            tool_name,
+            turn: timer.turn,
+            queued: started_at.saturating_duration_since(timer.requested_at),
+            running: finished_at.saturating_duration_since(started_at),
+            outcome,
+        });
+        cx.notify();
+    }
+
+    pub(crate) fn record_file_read(
+        &mut self,
+        abs_path: PathBuf,
+        mtime: Option<fs::MTime>,
+        cx: &mut Context<Self>,
+    ) {
+        self.working_set.record_read(abs_path, mtime, Utc::now());
+        cx.notify();
+    }
+
+    pub(crate) fn record_file_outline(&mut self, abs_path: PathBuf, cx: &mut Context<Self>) {
+        self.working_set.record_outline(abs_path, Utc::now());
+        cx.notify();
+    }
+
+    pub(crate) fn record_file_edit(
+        &mut self,
+        abs_path: PathBuf,
+        mtime: Option<fs::MTime>,
+        cx: &mut Context<Self>,
+    ) {
+        self.working_set.record_edit(abs_path, mtime, Utc::now());
+        cx.notify();
+    }
+
+    pub fn pending_approval(&self) -> &PendingApproval {
+        &self.pending_approval
+    }
+
+    pub(crate) fn hold_for_approval(&mut self, pending: PendingBuffer, cx: &mut Context<Self>) {
+        self.pending_approval.insert(pending);
+        cx.notify();
+    }
+
+    /// Saves the buffers whose edits were waiting for approval, formatting them first as saving
+    /// them from the editor would. Buffers that fail to save stay pending.
+    pub fn approve_pending_edits(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let pending = self.pending_approval.take();
+        cx.notify();
+        if pending.is_empty() {
+            return Task::ready(Ok(()));
+        }
+
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        cx.spawn(async move |this, cx| {
+            let buffers = pending
+                .iter()
+                .map(|pending| pending.buffer.clone())
+                .collect::<HashSet<_>>();
+            action_log.update(cx, |log, cx| {
+                for buffer in &buffers {
+                    log.buffer_edited(buffer.clone(), cx);
+                }
+            });
+            project
+                .update(cx, |project, cx| {
+                    project.format(
+                        buffers,
+                        LspFormatTarget::Buffers,
+                        false,
+                        FormatTrigger::Save,
+                        cx,
+                    )
+                })
+                .await
+                .log_err();
+
+            let mut first_error = None;
+            for pending in pending {
+                let saved = project
+                    .update(cx, |project, cx| {
+                        project.save_buffer(pending.buffer.clone(), cx)
+                    })
+                    .await;
+                if let Err(error) = saved {
+                    first_error.get_or_insert(error);
+                    this.update(cx, |thread, cx| thread.hold_for_approval(pending, cx))?;
+                    continue;
+                }
+
+                action_log.update(cx, |log, cx| log.buffer_edited(pending.buffer.clone(), cx));
+                let mtime = pending.buffer.read_with(cx, |buffer, _| {
+                    buffer.file().and_then(|file| file.disk_state().mtime())
+                });
+                this.update(cx, |thread, cx| {
+                    thread.record_file_edit(pending.abs_path, mtime, cx);
+                })?;
+            }
+            first_error.map_or(Ok(()), Err)
+        })
+    }
+
+    /// Puts the buffers whose edits were waiting for approval back the way they were before the
+    /// first of them.
+    pub fn reject_pending_edits(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let pending = self.pending_approval.take();
+        cx.notify();
+        if pending.is_empty() {
+            return Task::ready(Ok(()));
+        }
+
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        cx.spawn(async move |this, cx| {
+            let mut unchanged_on_disk = HashSet::default();
+            for pending in pending {
+                let saved_since = pending.buffer.update(cx, |buffer, cx| {
+                    buffer.set_text(pending.original_text.clone(), cx);
+                    buffer.file().and_then(|file| file.disk_state().mtime())
+                        != pending.original_mtime
+                });
+                action_log.update(cx, |log, cx| log.buffer_edited(pending.buffer.clone(), cx));
+
+                // A buffer that was saved some other way in the meantime has the edits on disk,
+                // so it's saved again to undo them there too.
+                if saved_since {
+                    project
+                        .update(cx, |project, cx| {
+                            project.save_buffer(pending.buffer.clone(), cx)
+                        })
+                        .await?;
+                    let mtime = pending.buffer.read_with(cx, |buffer, _| {
+                        buffer.file().and_then(|file| file.disk_state().mtime())
+                    });
+                    this.update(cx, |thread, cx| {
+                        thread.record_file_edit(pending.abs_path, mtime, cx);
+                    })?;
+                } else {
+                    unchanged_on_disk.insert(pending.buffer);
+                }
+            }
+
+            // The file still has the original text on disk, so reloading it clears the buffer's
+            // unsaved changes without touching the file.
+            if !unchanged_on_disk.is_empty() {
+                project
+                    .update(cx, |project, cx| {
+                        project.reload_buffers(unchanged_on_disk, false, cx)
+                    })
+                    .await
+                    .log_err();
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn record_file_changed_externally(
+        &mut self,
+        abs_path: &Path,
+        mtime: Option<fs::MTime>,
+        cx: &mut Context<Self>,
+    ) {
+        self.working_set.record_external_change(abs_path, mtime);
+        cx.notify();
+    }
+
+    fn start_checkpoint(&mut self, cx: &mut Context<Self>) {
+        self.finish_checkpoint(cx);
+        let Some(turn_id) = self.last_user_message().map(|message| message.id.clone()) else {
+            return;
+        };
+        self.action_log
+            .update(cx, |action_log, _| action_log.start_checkpoint());
+        self.recording_checkpoint = Some(turn_id);
+    }
+
+    fn finish_checkpoint(&mut self, cx: &mut Context<Self>) {
+        let Some(turn_id) = self.recording_checkpoint.take() else {
+            return;
+        };
+        let buffers = self
+            .action_log
+            .update(cx, |action_log, cx| action_log.finish_checkpoint(cx));
+        if buffers.is_empty() {
+            return;
+        }
+
+        // A resumed turn adds to its existing checkpoint, where the contents from before the
+        // turn started are the ones to keep.
+        if let Some(checkpoint) = self
+            .checkpoints
+            .back_mut()
+            .filter(|checkpoint| checkpoint.turn_id == turn_id)
+        {
+            for buffer in buffers {
+                match checkpoint
+                    .buffers
+                    .iter_mut()
+                    .find(|existing| existing.buffer == buffer.buffer)
+                {
+                    Some(existing) => existing.final_version = buffer.final_version,
+                    None => checkpoint.buffers.push(buffer),
+                }
+            }
+        } else {
+            self.checkpoints
+                .push_back(TurnCheckpoint { turn_id, buffers });
+            if self.checkpoints.len() > MAX_CHECKPOINTS {
+                self.checkpoints.pop_front();
+            }
+        }
+        cx.notify();
+    }
+
+    /// Whether the files changed in response to the given user message can be reverted with
+    /// [`Self::restore_checkpoint`].
+    pub fn has_checkpoint(&self, turn_id: &UserMessageId) -> bool {
+        self.checkpoints
+            .iter()
+            .any(|checkpoint| &checkpoint.turn_id == turn_id)
+    }
+
+    /// Reverts the files changed in response to the given user message, deleting the ones the
+    /// response created. Files that were edited since are left alone and reported as such.
+    pub fn restore_checkpoint(
+        &mut self,
+        turn_id: UserMessageId,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Vec<RestoredFile>>> {
+        let Some(ix) = self
+            .checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.turn_id == turn_id)
+        else {
+            return Task::ready(Err(anyhow!("No changes to revert for this message")));
+        };
+        let Some(checkpoint) = self.checkpoints.remove(ix) else {
+            return Task::ready(Ok(Vec::new()));
+        };
+        cx.notify();
+
+        let restore = self.action_log.update(cx, |action_log, cx| {
+            action_log.restore_checkpoint(checkpoint.buffers, cx)
+        });
+        cx.background_spawn(async move { Ok(restore.await) })
+    }
+
+    /// Drops every checkpoint, which is done when the thread is deleted.
+    pub fn clear_checkpoints(&mut self, cx: &mut Context<Self>) {
+        self.checkpoints.clear();
+        cx.notify();
+    }
+
+    /// The files read or edited in this thread, most recently touched first, along with
+    /// whether each one has changed on disk since the agent last saw it.
+    pub fn touched_files(&self, cx: &App) -> Vec<TouchedFileStatus> {
+        let project = self.project.read(cx);
+        let mut files = self
+            .working_set
+            .iter()
+            .map(|(abs_path, file)| {
+                let project_path = project.project_path_for_absolute_path(abs_path, cx);
+                // Files outside the project aren't watched, so there's no way to tell.
+                let is_stale = project_path.as_ref().is_some_and(|project_path| {
+                    let current_mtime = project
+                        .entry_for_path(project_path, cx)
+                        .and_then(|entry| entry.mtime);
+                    file.has_changed_since(current_mtime)
+                });
+                TouchedFileStatus {
+                    abs_path: abs_path.to_path_buf(),
+                    project_path,
+                    file: *file,
+                    is_stale,
+                }
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| b.file.last_touched_at().cmp(&a.file.last_touched_at()));
+        files
+    }
+
     pub fn model(&self) -> Option<&Arc<dyn LanguageModel>> {
         self.model.as_ref()
     }
 
+    /// How much output each tool call may send back to the current model.
+    pub fn tool_output_budget(&self) -> ToolOutputBudget {
+        self.model
+            .as_ref()
+            .map(|model| ToolOutputBudget::for_model(model.as_ref()))
+            .unwrap_or_default()
+    }
+
+    /// How many of the thread's older tool results were replaced with a short stub in the last
+    /// request, because the whole history no longer fit the model's context window.
+    pub fn compacted_tool_results(&self) -> usize {
+        self.compacted_tool_results
+    }
+
+    pub fn worktree_scope(&self) -> Option<&WorktreeScope> {
+        self.worktree_scope.as_ref()
+    }
+
+    /// Confines the agent's tools to the given project roots, starting with the next tool call.
+    /// Passing `None`, or only roots that aren't in the project, lets them use every root again.
+    pub fn set_worktree_scope(
+        &mut self,
+        worktree_ids: Option<Vec<WorktreeId>>,
+        cx: &mut Context<Self>,
+    ) {
+        self.worktree_scope = worktree_ids
+            .and_then(|worktree_ids| WorktreeScope::new(&worktree_ids, self.project.read(cx), cx));
+        cx.notify();
+    }
+
+    /// The scope to hand to a tool call, matched up with the roots that are open right now.
+    fn tool_worktree_scope(&self, cx: &App) -> Option<WorktreeScope> {
+        let mut scope = self.worktree_scope.clone()?;
+        scope.resolve(self.project.read(cx), cx);
+        Some(scope)
+    }
+
     pub fn set_model(&mut self, model: Arc<dyn LanguageModel>, cx: &mut Context<Self>) {
         let old_usage = self.latest_token_usage();
         self.model = Some(model);
@@ -1338,6 +1977,7 @@ impl Thread {
         cx: &mut Context<Self>,
     ) {
         let language_registry = self.project.read(cx).languages().clone();
+        self.add_tool(CodeSymbolsTool::new(cx.weak_entity(), self.project.clone()));
         self.add_tool(CopyPathTool::new(self.project.clone()));
         self.add_tool(CreateDirectoryTool::new(self.project.clone()));
         self.add_tool(DeletePathTool::new(
@@ -1357,9 +1997,16 @@ impl Thread {
             language_registry,
         ));
         self.add_tool(FetchTool::new(self.project.read(cx).client().http_client()));
+        self.add_tool(FileHistoryTool::new(self.project.clone()));
         self.add_tool(FindPathTool::new(self.project.clone()));
+        self.add_tool(FormatFileTool::new(
+            cx.weak_entity(),
+            self.project.clone(),
+            self.action_log.clone(),
+        ));
         self.add_tool(GrepTool::new(self.project.clone()));
         self.add_tool(ListDirectoryTool::new(self.project.clone()));
+        self.add_tool(ListTouchedFilesTool::new(cx.weak_entity()));
         self.add_tool(MovePathTool::new(self.project.clone()));
         self.add_tool(NowTool);
         self.add_tool(OpenTool::new(self.project.clone()));
@@ -1368,9 +2015,15 @@ impl Thread {
             self.project.clone(),
             self.action_log.clone(),
         ));
+        self.add_tool(ReadInstructionsTool::new(self.project.clone()));
         self.add_tool(SaveFileTool::new(self.project.clone()));
         self.add_tool(RestoreFileFromDiskTool::new(self.project.clone()));
-        self.add_tool(TerminalTool::new(self.project.clone(), environment.clone()));
+        self.add_tool(TaskListTool::new(cx.weak_entity()));
+        self.add_tool(TerminalTool::new(
+            self.project.clone(),
+            cx.weak_entity(),
+            environment.clone(),
+        ));
         self.add_tool(WebSearchTool);
 
         if cx.has_flag::<SubagentsFeatureFlag>() && self.depth() < MAX_SUBAGENT_DEPTH {
@@ -1415,6 +2068,9 @@ impl Thread {
             }
         }
 
+        // Tools stop editing once they see the cancellation, so the checkpoint is complete.
+        self.finish_checkpoint(cx);
+
         let Some(running_turn) = self.running_turn.take() else {
             self.flush_pending_message(cx);
             return Task::ready(());
@@ -1446,6 +2102,7 @@ impl Thread {
 
         self.request_token_usage
             .insert(last_user_message.id.clone(), update);
+        self.usage_ledger.update_request(update);
         cx.emit(TokenUsageUpdated(self.latest_token_usage()));
         cx.notify();
     }
@@ -1465,6 +2122,7 @@ impl Thread {
             match message {
                 Message::User(message) => {
                     self.request_token_usage.remove(&message.id);
+                    self.tool_stats.remove_turn(&message.id);
                 }
                 Message::Agent(_) | Message::Resume => {}
             }
@@ -1653,6 +2311,7 @@ impl Thread {
         let event_stream = ThreadEventStream(events_tx);
         let message_ix = self.messages.len().saturating_sub(1);
         self.clear_summary();
+        self.start_checkpoint(cx);
         let (cancellation_tx, mut cancellation_rx) = watch::channel(false);
         self.running_turn = Some(RunningTurn {
             event_stream: event_stream.clone(),
@@ -1679,7 +2338,10 @@ impl Thread {
                     return;
                 }
 
-                _ = this.update(cx, |this, cx| this.flush_pending_message(cx));
+                _ = this.update(cx, |this, cx| {
+                    this.flush_pending_message(cx);
+                    this.finish_checkpoint(cx);
+                });
 
                 match turn_result {
                     Ok(()) => {
@@ -1719,8 +2381,22 @@ impl Thread {
         let mut attempt = 0;
         let mut intent = CompletionIntent::UserPrompt;
         loop {
-            let request =
-                this.update(cx, |this, cx| this.build_completion_request(intent, cx))??;
+            let request = this.update(cx, |this, cx| {
+                let (request, compacted_tool_results) =
+                    this.build_compacted_completion_request(intent, cx)?;
+                if this.compacted_tool_results != compacted_tool_results {
+                    this.compacted_tool_results = compacted_tool_results;
+                    cx.notify();
+                }
+                if let Some(turn) = this.last_user_message().map(|message| message.id.clone()) {
+                    this.usage_ledger.start_request(
+                        turn,
+                        model.provider_id().0.as_ref().into(),
+                        model.id().0.as_ref().into(),
+                    );
+                }
+                anyhow::Ok(request)
+            })??;
 
             telemetry::event!(
                 "Agent Thread Completion",
@@ -1737,6 +2413,9 @@ impl Thread {
                 Ok(events) => (events.fuse(), None),
                 Err(err) => (stream::empty().boxed().fuse(), Some(err)),
             };
+            let mut tool_scheduler = ToolScheduler::new(
+                cx.update(|cx| AgentSettings::get_global(cx).max_concurrent_tool_calls),
+            );
             let mut tool_results = FuturesUnordered::new();
             let mut cancelled = false;
             loop {
@@ -1774,6 +2453,7 @@ impl Thread {
                                     event,
                                     event_stream,
                                     cancellation_rx.clone(),
+                                    &mut tool_scheduler,
                                     cx,
                                 ) {
                                     Ok(Some(task)) => batch_tool_results.push(task),
@@ -1808,6 +2488,17 @@ impl Thread {
             // tool execution, which could cause deadlocks when tools spawn subagents
             // that need their own permits.
             drop(events);
+            tool_scheduler.all_calls_made();
+
+            // Tools still waiting on input the model never finished writing, such as after a
+            // failed request, get an error instead of waiting forever.
+            if !cancelled {
+                this.update(cx, |this, _| {
+                    if let Some(running_turn) = this.running_turn.as_mut() {
+                        running_turn.streaming_tool_inputs.clear();
+                    }
+                })?;
+            }
 
             let end_turn = tool_results.is_empty();
             while let Some(tool_result) = tool_results.next().await {
@@ -1846,6 +2537,7 @@ impl Thread {
             if let Some(error) = error {
                 attempt += 1;
                 let retry = this.update(cx, |this, cx| {
+                    this.usage_ledger.discard_failed_request();
                     let user_store = this.user_store.read(cx);
                     this.handle_completion_error(error, attempt, user_store.plan())
                 })??;
@@ -1933,6 +2625,7 @@ impl Thread {
         event: LanguageModelCompletionEvent,
         event_stream: &ThreadEventStream,
         cancellation_rx: watch::Receiver<bool>,
+        tool_scheduler: &mut ToolScheduler,
         cx: &mut Context<Self>,
     ) -> Result<Option<Task<LanguageModelToolResult>>> {
         log::trace!("Handling streamed completion event: {:?}", event);
@@ -1961,7 +2654,13 @@ impl Thread {
                 }
             }
             ToolUse(tool_use) => {
-                return Ok(self.handle_tool_use_event(tool_use, event_stream, cancellation_rx, cx));
+                return Ok(self.handle_tool_use_event(
+                    tool_use,
+                    event_stream,
+                    cancellation_rx,
+                    tool_scheduler,
+                    cx,
+                ));
             }
             ToolUseJsonParseError {
                 id,
@@ -2049,6 +2748,7 @@ impl Thread {
         tool_use: LanguageModelToolUse,
         event_stream: &ThreadEventStream,
         cancellation_rx: watch::Receiver<bool>,
+        tool_scheduler: &mut ToolScheduler,
         cx: &mut Context<Self>,
     ) -> Option<Task<LanguageModelToolResult>> {
         cx.notify();
@@ -2074,8 +2774,12 @@ impl Thread {
             }));
         };
 
+        self.tool_call_timer(&tool_use.id);
+
         if !tool_use.is_input_complete {
-            if tool.supports_input_streaming() {
+            // Tools that preview their input are started early too, so that the preview shows
+            // up and the call can be cancelled before it ever runs.
+            if tool.supports_input_streaming() || !tool.preview_fields().is_empty() {
                 let running_turn = self.running_turn.as_mut()?;
                 if let Some(sender) = running_turn.streaming_tool_inputs.get(&tool_use.id) {
                     sender.send_partial(tool_use.input);
@@ -2097,6 +2801,7 @@ impl Thread {
                     tool_use.name,
                     event_stream,
                     cancellation_rx,
+                    tool_scheduler,
                     cx,
                 ));
             } else {
@@ -2123,34 +2828,82 @@ impl Thread {
             tool_use.name,
             event_stream,
             cancellation_rx,
+            tool_scheduler,
             cx,
         ))
     }
 
     fn run_tool(
-        &self,
+        &mut self,
         tool: Arc<dyn AnyAgentTool>,
         tool_input: ToolInput<serde_json::Value>,
         tool_use_id: LanguageModelToolUseId,
         tool_name: Arc<str>,
         event_stream: &ThreadEventStream,
         cancellation_rx: watch::Receiver<bool>,
+        tool_scheduler: &mut ToolScheduler,
         cx: &mut Context<Self>,
     ) -> Task<LanguageModelToolResult> {
+        let permit = tool_scheduler.schedule(tool.is_read_only());
         let fs = self.project.read(cx).fs().clone();
         let tool_event_stream = ToolCallEventStream::new(
             tool_use_id.clone(),
             event_stream.clone(),
             Some(fs),
-            cancellation_rx,
-        );
-        tool_event_stream.update_fields(
-            acp::ToolCallUpdateFields::new().status(acp::ToolCallStatus::InProgress),
+            self.tool_output_budget(),
+            self.tool_worktree_scope(cx),
+            cancellation_rx.clone(),
         );
         let supports_images = self.model().is_some_and(|model| model.supports_images());
-        let tool_result = tool.run(tool_input, tool_event_stream, cx);
-        cx.foreground_executor().spawn(async move {
-            let (is_error, output) = match tool_result.await {
+        let preview_fields = if tool.supports_input_streaming() {
+            &[]
+        } else {
+            tool.preview_fields()
+        };
+        cx.spawn(async move |this, cx| {
+            let mut tool_input = tool_input;
+            if !preview_fields.is_empty() {
+                preview_tool_input(&mut tool_input, preview_fields, &tool_event_stream).await;
+            }
+
+            // Calls still waiting for their turn when the turn is cancelled never run.
+            let permit = futures::select_biased! {
+                _ = tool_event_stream.cancelled_by_user().fuse() => None,
+                permit = permit.fuse() => Some(permit),
+            };
+            let Some(_permit) = permit else {
+                this.update(cx, |this, cx| {
+                    this.finish_tool_call(
+                        tool_use_id.clone(),
+                        tool_name.clone(),
+                        ToolCallOutcome::Cancelled,
+                        cx,
+                    )
+                })
+                .ok();
+                return LanguageModelToolResult {
+                    tool_use_id,
+                    tool_name,
+                    is_error: true,
+                    content: LanguageModelToolResultContent::Text(TOOL_CANCELED_MESSAGE.into()),
+                    output: None,
+                };
+            };
+
+            let tool_result = this.update(cx, |this, cx| {
+                // Timing calls here rather than in each tool means every tool shows up in
+                // `tool_stats`.
+                this.tool_call_timer(&tool_use_id).started_at = Some(Instant::now());
+                tool_event_stream.update_fields(
+                    acp::ToolCallUpdateFields::new().status(acp::ToolCallStatus::InProgress),
+                );
+                tool.run(tool_input, tool_event_stream.clone(), cx)
+            });
+            let tool_result = match tool_result {
+                Ok(tool_result) => tool_result.await,
+                Err(error) => Err(AgentToolOutput::from_error(error.to_string())),
+            };
+            let (is_error, mut output) = match tool_result {
                 Ok(mut output) => {
                     if let LanguageModelToolResultContent::Image(_) = &output.llm_output
                         && !supports_images
@@ -2165,6 +2918,21 @@ impl Thread {
                 }
                 Err(output) => (true, output),
             };
+            if let Some(edited_input) = tool_event_stream.edited_input() {
+                output.llm_output = with_edited_input_note(output.llm_output, &edited_input);
+            }
+
+            let outcome = if *cancellation_rx.borrow() {
+                ToolCallOutcome::Cancelled
+            } else if is_error {
+                ToolCallOutcome::Error
+            } else {
+                ToolCallOutcome::Ok
+            };
+            this.update(cx, |this, cx| {
+                this.finish_tool_call(tool_use_id.clone(), tool_name.clone(), outcome, cx)
+            })
+            .ok();
 
             LanguageModelToolResult {
                 tool_use_id,
@@ -2437,6 +3205,7 @@ impl Thread {
                 );
             }
         }
+        message.sort_tool_results();
 
         self.messages.push(Message::Agent(message));
         self.updated_at = Utc::now();
@@ -2449,6 +3218,18 @@ impl Thread {
         completion_intent: CompletionIntent,
         cx: &App,
     ) -> Result<LanguageModelRequest> {
+        let (request, _) = self.build_compacted_completion_request(completion_intent, cx)?;
+        Ok(request)
+    }
+
+    /// Builds the request for the next completion, along with how many tool results had to be
+    /// compacted for it to fit the model's context window. The thread's own messages are left
+    /// as they are.
+    fn build_compacted_completion_request(
+        &self,
+        completion_intent: CompletionIntent,
+        cx: &App,
+    ) -> Result<(LanguageModelRequest, usize)> {
         let model = self.model().context("No language model configured")?;
         let tools = if let Some(turn) = self.running_turn.as_ref() {
             turn.tools
@@ -2477,8 +3258,15 @@ impl Thread {
             .unwrap_or_default();
 
         log::debug!("Request includes {} tools", available_tools.len());
-        let messages = self.build_request_messages(available_tools, cx);
+        let mut messages = self.build_request_messages(available_tools, cx);
         log::debug!("Request will include {} messages", messages.len());
+        let compacted_tool_results = HistoryBudget::for_model(model.as_ref())
+            .compact(&mut messages, HistoryBudget::estimate_tokens);
+        if compacted_tool_results > 0 {
+            log::debug!(
+                "Compacted {compacted_tool_results} tool results to fit the context window"
+            );
+        }
 
         let request = LanguageModelRequest {
             thread_id: Some(self.id.to_string()),
@@ -2495,7 +3283,7 @@ impl Thread {
         };
 
         log::debug!("Completion request built successfully");
-        Ok(request)
+        Ok((request, compacted_tool_results))
     }
 
     fn enabled_tools(
@@ -2662,6 +3450,11 @@ impl Thread {
             project: self.project_context.read(cx),
             available_tools,
             model_name: self.model.as_ref().map(|m| m.name().0.to_string()),
+            scoped_roots: self
+                .worktree_scope
+                .iter()
+                .flat_map(|scope| scope.root_names().cloned())
+                .collect(),
         }
         .render(&self.templates)
         .context("failed to build system prompt")
@@ -2818,6 +3611,12 @@ impl Thread {
     }
 }
 
+/// The files changed in response to a user message, as they were before.
+struct TurnCheckpoint {
+    turn_id: UserMessageId,
+    buffers: Vec<BufferCheckpoint>,
+}
+
 struct RunningTurn {
     /// Holds the task that handles agent interaction until the end of the turn.
     /// Survives across multiple requests as the model performs tool calls and
@@ -2831,8 +3630,8 @@ struct RunningTurn {
     /// Sender to signal tool cancellation. When cancel is called, this is
     /// set to true so all tools can detect user-initiated cancellation.
     cancellation_tx: watch::Sender<bool>,
-    /// Senders for tools that support input streaming and have already been
-    /// started but are still receiving input from the LLM.
+    /// Senders for tools that support input streaming or preview their input,
+    /// and have already been started but are still receiving input from the LLM.
     streaming_tool_inputs: HashMap<LanguageModelToolUseId, ToolInputSender>,
 }
 
@@ -2845,6 +3644,13 @@ impl RunningTurn {
     }
 }
 
+struct ToolCallTimer {
+    turn: Option<UserMessageId>,
+    requested_at: Instant,
+    /// When the tool started running, which may be before its input finished streaming.
+    started_at: Option<Instant>,
+}
+
 pub struct TokenUsageUpdated(pub Option<acp_thread::TokenUsage>);
 
 impl EventEmitter<TokenUsageUpdated> for Thread {}
@@ -2988,6 +3794,24 @@ where
         false
     }
 
+    /// The string fields of the input to show on the tool card while the model is still
+    /// writing it, such as the command to run, for tools that don't stream their input.
+    fn preview_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns whether the tool only looks at the project without changing it. Calls to
+    /// read-only tools run alongside each other, while other calls run one at a time.
+    fn is_read_only() -> bool {
+        false
+    }
+
+    /// Returns whether the user can edit the tool's input when asked to authorize a call, and
+    /// run the call with that instead. See [`ToolCallEventStream::authorize_input`].
+    fn allows_input_editing() -> bool {
+        false
+    }
+
     /// Some tools rely on a provider for the underlying billing or other reasons.
     /// Allow the tool to check if they are compatible, or should be filtered out.
     fn supports_provider(_provider: &LanguageModelProviderId) -> bool {
@@ -3019,6 +3843,18 @@ where
         Ok(())
     }
 
+    /// Applies the changes of a previous execution again, reconciling them with the current
+    /// state of the project. Returns `None` if the tool doesn't support this.
+    fn reapply(
+        self: Arc<Self>,
+        _input: Self::Input,
+        _output: Self::Output,
+        _event_stream: ToolCallEventStream,
+        _cx: &mut App,
+    ) -> Option<Task<Result<Self::Output, Self::Output>>> {
+        None
+    }
+
     fn erase(self) -> Arc<dyn AnyAgentTool> {
         Arc::new(Erased(Arc::new(self)))
     }
@@ -3051,6 +3887,14 @@ pub trait AnyAgentTool {
     fn supports_input_streaming(&self) -> bool {
         false
     }
+    /// See [`AgentTool::preview_fields`].
+    fn preview_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// See [`AgentTool::is_read_only`].
+    fn is_read_only(&self) -> bool {
+        false
+    }
     fn supports_provider(&self, _provider: &LanguageModelProviderId) -> bool {
         true
     }
@@ -3068,6 +3912,16 @@ pub trait AnyAgentTool {
         event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Result<()>;
+    /// See [`AgentTool::reapply`].
+    fn reapply(
+        self: Arc<Self>,
+        _input: serde_json::Value,
+        _output: serde_json::Value,
+        _event_stream: ToolCallEventStream,
+        _cx: &mut App,
+    ) -> Result<Task<Result<AgentToolOutput, AgentToolOutput>>> {
+        Err(anyhow!("{} can't be re-applied", self.name()))
+    }
 }
 
 impl<T> AnyAgentTool for Erased<Arc<T>>
@@ -3090,6 +3944,14 @@ where
         T::supports_input_streaming()
     }
 
+    fn preview_fields(&self) -> &'static [&'static str] {
+        T::preview_fields()
+    }
+
+    fn is_read_only(&self) -> bool {
+        T::is_read_only()
+    }
+
     fn initial_title(&self, input: serde_json::Value, _cx: &mut App) -> SharedString {
         let parsed_input = serde_json::from_value(input.clone()).map_err(|_| input);
         self.0.initial_title(parsed_input, _cx)
@@ -3113,27 +3975,7 @@ where
     ) -> Task<Result<AgentToolOutput, AgentToolOutput>> {
         let tool_input: ToolInput<T::Input> = input.cast();
         let task = self.0.clone().run(tool_input, event_stream, cx);
-        cx.spawn(async move |_cx| match task.await {
-            Ok(output) => {
-                let raw_output = serde_json::to_value(&output).map_err(|e| {
-                    AgentToolOutput::from_error(format!("Failed to serialize tool output: {e}"))
-                })?;
-                Ok(AgentToolOutput {
-                    llm_output: output.into(),
-                    raw_output,
-                })
-            }
-            Err(error_output) => {
-                let raw_output = serde_json::to_value(&error_output).unwrap_or_else(|e| {
-                    log::error!("Failed to serialize tool error output: {e}");
-                    serde_json::Value::Null
-                });
-                Err(AgentToolOutput {
-                    llm_output: error_output.into(),
-                    raw_output,
-                })
-            }
-        })
+        cx.spawn(async move |_cx| erase_tool_output(task.await))
     }
 
     fn replay(
@@ -3147,6 +3989,115 @@ where
         let output = serde_json::from_value(output)?;
         self.0.replay(input, output, event_stream, cx)
     }
+
+    fn reapply(
+        self: Arc<Self>,
+        input: serde_json::Value,
+        output: serde_json::Value,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Result<Task<Result<AgentToolOutput, AgentToolOutput>>> {
+        let input = serde_json::from_value(input)?;
+        let output = serde_json::from_value(output)?;
+        let task = self
+            .0
+            .clone()
+            .reapply(input, output, event_stream, cx)
+            .with_context(|| format!("{} can't be re-applied", T::NAME))?;
+        Ok(cx.spawn(async move |_cx| erase_tool_output(task.await)))
+    }
+}
+
+/// Shows the `fields` of a tool call's input on its card as the model writes them, until the
+/// input is complete or the user cancels the call.
+async fn preview_tool_input(
+    tool_input: &mut ToolInput<serde_json::Value>,
+    fields: &[&str],
+    event_stream: &ToolCallEventStream,
+) {
+    let mut preview = None;
+    loop {
+        let partial_input = futures::select_biased! {
+            _ = event_stream.cancelled_by_user().fuse() => return,
+            partial_input = tool_input.recv_partial().fuse() => partial_input,
+        };
+        let Some(partial_input) = partial_input else {
+            break;
+        };
+        if let Some(new_preview) = input_preview(&partial_input, fields)
+            && preview.as_ref() != Some(&new_preview)
+        {
+            event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                acp::ToolCallContent::Content(acp::Content::new(new_preview.clone())),
+            ]));
+            preview = Some(new_preview);
+        }
+    }
+
+    // Once the input is complete, the tool's title and output take over.
+    if preview.is_some() {
+        event_stream.update_fields(acp::ToolCallUpdateFields::new().content(Vec::new()));
+    }
+}
+
+fn input_preview(partial_input: &serde_json::Value, fields: &[&str]) -> Option<String> {
+    let values = fields
+        .iter()
+        .filter_map(|field| partial_input.get(field)?.as_str())
+        .filter(|value| !value.is_empty())
+        .map(|text| MarkdownCodeBlock { tag: "", text }.to_string())
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join("\n"))
+}
+
+/// Tells the model what a tool call ran with, when the user edited its input before allowing
+/// it. The model's own input stays in its tool use.
+fn with_edited_input_note(
+    content: LanguageModelToolResultContent,
+    edited_input: &serde_json::Value,
+) -> LanguageModelToolResultContent {
+    let LanguageModelToolResultContent::Text(text) = &content else {
+        return content;
+    };
+    let edited_input = serde_json::to_string_pretty(edited_input).unwrap_or_default();
+    let note = MarkdownCodeBlock {
+        tag: "json",
+        text: &edited_input,
+    };
+    LanguageModelToolResultContent::Text(
+        format!(
+            "The user edited this tool call's input before running it. \
+             It ran with:\n{note}\n{text}"
+        )
+        .into(),
+    )
+}
+
+fn erase_tool_output<O>(result: Result<O, O>) -> Result<AgentToolOutput, AgentToolOutput>
+where
+    O: Serialize + Into<LanguageModelToolResultContent>,
+{
+    match result {
+        Ok(output) => {
+            let raw_output = serde_json::to_value(&output).map_err(|e| {
+                AgentToolOutput::from_error(format!("Failed to serialize tool output: {e}"))
+            })?;
+            Ok(AgentToolOutput {
+                llm_output: output.into(),
+                raw_output,
+            })
+        }
+        Err(error_output) => {
+            let raw_output = serde_json::to_value(&error_output).unwrap_or_else(|e| {
+                log::error!("Failed to serialize tool error output: {e}");
+                serde_json::Value::Null
+            });
+            Err(AgentToolOutput {
+                llm_output: error_output.into(),
+                raw_output,
+            })
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -3218,6 +4169,10 @@ impl ThreadEventStream {
             .ok();
     }
 
+    fn send_plan(&self, plan: acp::Plan) {
+        self.0.unbounded_send(Ok(ThreadEvent::Plan(plan))).ok();
+    }
+
     fn send_retry(&self, status: acp_thread::RetryStatus) {
         self.0.unbounded_send(Ok(ThreadEvent::Retry(status))).ok();
     }
@@ -3242,7 +4197,11 @@ pub struct ToolCallEventStream {
     tool_use_id: LanguageModelToolUseId,
     stream: ThreadEventStream,
     fs: Option<Arc<dyn Fs>>,
+    output_budget: ToolOutputBudget,
+    worktree_scope: Option<WorktreeScope>,
     cancellation_rx: watch::Receiver<bool>,
+    /// The input the user ran the call with instead of the model's, if they edited it.
+    edited_input: Arc<parking_lot::Mutex<Option<serde_json::Value>>>,
 }
 
 impl ToolCallEventStream {
@@ -3261,6 +4220,8 @@ impl ToolCallEventStream {
             "test_id".into(),
             ThreadEventStream(events_tx),
             None,
+            ToolOutputBudget::default(),
+            None,
             cancellation_rx,
         );
 
@@ -3277,17 +4238,36 @@ impl ToolCallEventStream {
         cancellation_tx.send(true).ok();
     }
 
+    /// Replaces the output budget, so that tests can exercise truncation with small outputs.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn with_output_budget(mut self, output_budget: ToolOutputBudget) -> Self {
+        self.output_budget = output_budget;
+        self
+    }
+
+    /// Confines the tool call to a scope, so that tests can exercise it without a thread.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn with_worktree_scope(mut self, worktree_scope: WorktreeScope) -> Self {
+        self.worktree_scope = Some(worktree_scope);
+        self
+    }
+
     fn new(
         tool_use_id: LanguageModelToolUseId,
         stream: ThreadEventStream,
         fs: Option<Arc<dyn Fs>>,
+        output_budget: ToolOutputBudget,
+        worktree_scope: Option<WorktreeScope>,
         cancellation_rx: watch::Receiver<bool>,
     ) -> Self {
         Self {
             tool_use_id,
             stream,
             fs,
+            output_budget,
+            worktree_scope,
             cancellation_rx,
+            edited_input: Arc::default(),
         }
     }
 
@@ -3319,6 +4299,51 @@ impl ToolCallEventStream {
         &self.tool_use_id
     }
 
+    /// The input the user edited this call's input to before allowing it, if they did.
+    pub fn edited_input(&self) -> Option<serde_json::Value> {
+        self.edited_input.lock().clone()
+    }
+
+    /// The budget to apply to this tool call's output before returning it to the model.
+    pub fn output_budget(&self) -> ToolOutputBudget {
+        self.output_budget
+    }
+
+    /// Fails when the thread is confined to some of the project's roots and `project_path`,
+    /// which the model asked for as `path`, isn't in one of them. Tools check this as soon as
+    /// they've resolved a path, before asking for authorization.
+    pub fn check_worktree_scope(
+        &self,
+        project_path: &ProjectPath,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        match &self.worktree_scope {
+            Some(scope) => scope.check(project_path, path.as_ref()),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`Self::check_worktree_scope`], for a path the model gave that the tool hasn't
+    /// resolved yet. Paths that aren't in any of the project's roots are left for the tool to
+    /// reject.
+    pub fn check_worktree_scope_for_path(
+        &self,
+        path: &str,
+        project: &Entity<Project>,
+        cx: &App,
+    ) -> Result<()> {
+        match project.read(cx).find_project_path(path, cx) {
+            Some(project_path) => self.check_worktree_scope(&project_path, path),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_in_worktree_scope(&self, worktree_id: WorktreeId) -> bool {
+        self.worktree_scope
+            .as_ref()
+            .is_none_or(|scope| scope.contains(worktree_id))
+    }
+
     pub fn update_fields(&self, fields: acp::ToolCallUpdateFields) {
         self.stream
             .update_tool_call_fields(&self.tool_use_id, fields, None);
@@ -3346,6 +4371,11 @@ impl ToolCallEventStream {
             .ok();
     }
 
+    /// Shows `plan` as the thread's plan, replacing the previous one.
+    pub fn update_plan(&self, plan: acp::Plan) {
+        self.stream.send_plan(plan);
+    }
+
     pub fn subagent_spawned(&self, id: acp::SessionId) {
         self.stream
             .0
@@ -3422,6 +4452,7 @@ impl ToolCallEventStream {
                     ]),
                     response: response_tx,
                     context: None,
+                    editable_input: None,
                 },
             )))
         {
@@ -3476,9 +4507,71 @@ impl ToolCallEventStream {
         context: ToolPermissionContext,
         cx: &mut App,
     ) -> Task<Result<()>> {
+        let authorize = self.authorize_edit(title, context, cx);
+        cx.background_spawn(async move { authorize.await.map(|_| ()) })
+    }
+
+    /// Like [`Self::authorize`], but for tools that [allow input editing], the user can also
+    /// edit `input` and run the call with that. Resolves to the edited input if they did.
+    ///
+    /// [allow input editing]: AgentTool::allows_input_editing
+    pub fn authorize_input<T: AgentTool>(
+        &self,
+        title: impl Into<String>,
+        context: ToolPermissionContext,
+        input: &T::Input,
+        cx: &mut App,
+    ) -> Task<Result<Option<T::Input>>> {
+        let editable_input = if T::allows_input_editing() {
+            serde_json::to_value(input).log_err().map(|input| {
+                acp_thread::EditableToolInput::new(input, |edited_input| {
+                    serde_json::from_value::<T::Input>(edited_input.clone())?;
+                    Ok(())
+                })
+            })
+        } else {
+            None
+        };
+        let (editable_input, edited_input_rx) = editable_input.unzip();
+        let authorize = self.request_authorization(title, context, editable_input, cx);
+
+        let stream = self.clone();
+        cx.spawn(async move |_cx| {
+            authorize.await?;
+            // The edited input is sent before the response that picks it.
+            let Some(Ok(Some(input))) = edited_input_rx.map(|mut rx| rx.try_recv()) else {
+                return Ok(None);
+            };
+            let typed_input = serde_json::from_value::<T::Input>(input.clone())
+                .context("invalid edited tool input")?;
+            stream.update_fields(acp::ToolCallUpdateFields::new().raw_input(input.clone()));
+            *stream.edited_input.lock() = Some(input);
+            Ok(Some(typed_input))
+        })
+    }
+
+    /// Like [`Self::authorize`], but also returns the grant the user picked among the ones
+    /// offered by [`ToolPermissionContext::with_edit_grants`], if any.
+    pub fn authorize_edit(
+        &self,
+        title: impl Into<String>,
+        context: ToolPermissionContext,
+        cx: &mut App,
+    ) -> Task<Result<Option<EditGrantScope>>> {
+        self.request_authorization(title, context, None, cx)
+    }
+
+    fn request_authorization(
+        &self,
+        title: impl Into<String>,
+        context: ToolPermissionContext,
+        editable_input: Option<acp_thread::EditableToolInput>,
+        cx: &mut App,
+    ) -> Task<Result<Option<EditGrantScope>>> {
         use settings::ToolPermissionMode;
 
         let options = context.build_permission_options();
+        let edit_grants = context.edit_grants.clone();
 
         let (response_tx, response_rx) = oneshot::channel();
         if let Err(error) = self
@@ -3493,6 +4586,7 @@ impl ToolCallEventStream {
                     options,
                     response: response_tx,
                     context: Some(context),
+                    editable_input,
                 },
             )))
         {
@@ -3519,7 +4613,7 @@ impl ToolCallEventStream {
                         });
                     });
                 }
-                return Ok(());
+                return Ok(None);
             }
 
             // Handle "always deny tool" - e.g., "always_deny:terminal"
@@ -3556,7 +4650,7 @@ impl ToolCallEventStream {
                 } else {
                     log::error!("Failed to parse always allow pattern: missing newline separator in '{rest}'");
                 }
-                return Ok(());
+                return Ok(None);
             }
 
             // Handle "always deny pattern" - e.g., "always_deny_pattern:mcp:server:tool\n^cargo\s"
@@ -3580,9 +4674,17 @@ impl ToolCallEventStream {
                 return Err(anyhow!("Permission to run tool denied by user"));
             }
 
-            // Handle simple "allow" (allow once)
-            if response_str == "allow" {
-                return Ok(());
+            // Handle simple "allow" (allow once), and allowing with an edited input, which the
+            // caller receives separately.
+            if response_str == "allow" || response_str == acp_thread::EDITED_INPUT_OPTION_ID {
+                return Ok(None);
+            }
+
+            // Handle grants for the rest of the thread - e.g., "allow_file_for_session"
+            if let Some(scope) = EditGrantScope::from_option_id(&response_str)
+                && edit_grants.contains(&scope)
+            {
+                return Ok(Some(scope));
             }
 
             // Handle simple "deny" (deny once)
@@ -3594,6 +4696,27 @@ impl ToolCallEventStream {
 #[cfg(any(test, feature = "test-support"))]
 pub struct ToolCallEventStreamReceiver(mpsc::UnboundedReceiver<Result<ThreadEvent>>);
 
+#[cfg(any(test, feature = "test-support"))]
+impl ToolCallAuthorization {
+    pub fn option_ids(&self) -> Vec<acp::PermissionOptionId> {
+        match &self.options {
+            acp_thread::PermissionOptions::Flat(options) => options
+                .iter()
+                .map(|option| option.option_id.clone())
+                .collect(),
+            acp_thread::PermissionOptions::Dropdown(choices) => choices
+                .iter()
+                .flat_map(|choice| {
+                    [
+                        choice.allow.option_id.clone(),
+                        choice.deny.option_id.clone(),
+                    ]
+                })
+                .collect(),
+        }
+    }
+}
+
 #[cfg(any(test, feature = "test-support"))]
 impl ToolCallEventStreamReceiver {
     pub async fn expect_authorization(&mut self) -> ToolCallAuthorization {
@@ -145,6 +145,11 @@ mod tests {
             speed: None,
             thinking_enabled: false,
             thinking_effort: None,
+            working_set: Default::default(),
+            task_list: Default::default(),
+            tool_stats: Default::default(),
+            usage_ledger: Default::default(),
+            worktree_scope: None,
         }
     }
 
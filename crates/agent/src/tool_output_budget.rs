@@ -0,0 +1,399 @@
+use language_model::LanguageModel;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How much of a tool's output can be sent to the model in a single tool result.
+///
+/// Tools apply the budget to their text before building the tool result, so that one large
+/// result (such as reading a generated bundle) can't fill the model's context window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ToolOutputBudget {
+    max_bytes: usize,
+}
+
+impl ToolOutputBudget {
+    pub const MIN_BYTES: usize = 8 * 1024;
+    pub const MAX_BYTES: usize = 256 * 1024;
+    /// A rough average for source code and English text, which is all we need for a budget.
+    const BYTES_PER_TOKEN: u64 = 4;
+    /// The largest share of the context window a single tool result may take up.
+    const CONTEXT_WINDOW_FRACTION: u64 = 8;
+
+    pub fn new(max_bytes: usize) -> Self {
+        Self { max_bytes }
+    }
+
+    pub fn for_model(model: &dyn LanguageModel) -> Self {
+        let max_tokens = model.max_token_count() / Self::CONTEXT_WINDOW_FRACTION;
+        let max_bytes =
+            usize::try_from(max_tokens.saturating_mul(Self::BYTES_PER_TOKEN)).unwrap_or(usize::MAX);
+        Self::new(max_bytes.clamp(Self::MIN_BYTES, Self::MAX_BYTES))
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Keeps the start and the end of `text`, replacing the middle with an elision marker.
+    ///
+    /// Cuts are made at line boundaries where that doesn't waste much of the budget, and never
+    /// inside a UTF-8 character.
+    pub fn truncate_text(&self, text: String) -> BudgetedOutput {
+        let total_bytes = text.len();
+        if total_bytes <= self.max_bytes {
+            return BudgetedOutput::complete(text);
+        }
+
+        let head_budget = self.max_bytes / 2;
+        let tail_budget = self.max_bytes - head_budget;
+
+        let mut head_end = text.floor_char_boundary(head_budget);
+        if let Some(newline_ix) = text[..head_end].rfind('\n')
+            && newline_ix + 1 >= head_budget / 2
+        {
+            head_end = newline_ix + 1;
+        }
+
+        let mut tail_start = text.ceil_char_boundary(total_bytes - tail_budget);
+        if let Some(newline_ix) = text[tail_start..].find('\n')
+            && newline_ix < tail_budget / 2
+        {
+            tail_start += newline_ix + 1;
+        }
+
+        let (head, tail) = (&text[..head_end], &text[tail_start..]);
+        let omitted_bytes = tail_start - head_end;
+        let separator = if head.is_empty() || head.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        BudgetedOutput {
+            text: format!("{head}{separator}[... {omitted_bytes} bytes omitted ...]\n{tail}"),
+            truncation: Some(OutputTruncation {
+                shown_bytes: head.len() + tail.len(),
+                total_bytes,
+            }),
+        }
+    }
+
+    /// Keeps the start of a Markdown document, followed by the headings from the rest of it, so
+    /// that the model can see what else the document covers.
+    pub fn truncate_markdown(&self, markdown: String) -> BudgetedOutput {
+        let total_bytes = markdown.len();
+        if total_bytes <= self.max_bytes {
+            return BudgetedOutput::complete(markdown);
+        }
+
+        // Headings may use up to a quarter of the budget, and the start of the document gets
+        // whatever they leave.
+        let headings_budget = self.max_bytes / 4;
+        let mut headings = Vec::new();
+        let mut headings_len = 0;
+        for (start, heading) in markdown_headings(&markdown) {
+            if start < self.max_bytes - headings_budget {
+                continue;
+            }
+            if headings_len + heading.len() + 1 > headings_budget {
+                break;
+            }
+            headings.push((start, heading));
+            headings_len += heading.len() + 1;
+        }
+
+        let head_budget = self.max_bytes - headings_len;
+        let mut head_end = markdown.floor_char_boundary(head_budget);
+        if let Some(newline_ix) = markdown[..head_end].rfind('\n')
+            && newline_ix + 1 >= head_budget / 2
+        {
+            head_end = newline_ix + 1;
+        }
+        let head = &markdown[..head_end];
+        let mut shown_bytes = head.len();
+        let mut text = head.to_string();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let headings = headings
+            .into_iter()
+            .filter(|(start, _)| *start >= head_end)
+            .map(|(_, heading)| heading)
+            .collect::<Vec<_>>();
+        if headings.is_empty() {
+            text.push_str(&format!(
+                "[... {} bytes omitted ...]\n",
+                total_bytes - shown_bytes
+            ));
+        } else {
+            shown_bytes += headings.iter().map(|heading| heading.len()).sum::<usize>();
+            text.push_str(&format!(
+                "[... {} bytes omitted, leaving only these headings ...]\n",
+                total_bytes - shown_bytes
+            ));
+            for heading in headings {
+                text.push_str(heading);
+                text.push('\n');
+            }
+        }
+        BudgetedOutput {
+            text,
+            truncation: Some(OutputTruncation {
+                shown_bytes,
+                total_bytes,
+            }),
+        }
+    }
+
+    /// Keeps as many leading hunks of a unified diff as fit, so that the result is still a
+    /// valid diff.
+    pub fn truncate_diff(&self, diff: String) -> BudgetedOutput {
+        let total_bytes = diff.len();
+        if total_bytes <= self.max_bytes {
+            return BudgetedOutput::complete(diff);
+        }
+
+        // Hunks start at lines beginning with `@@`. Anything before the first one is a header.
+        let hunk_starts = diff
+            .match_indices("@@")
+            .map(|(ix, _)| ix)
+            .filter(|&ix| ix == 0 || diff.as_bytes()[ix - 1] == b'\n');
+        let mut kept_bytes = 0;
+        for hunk_start in hunk_starts.chain([total_bytes]) {
+            if hunk_start > self.max_bytes {
+                break;
+            }
+            kept_bytes = hunk_start;
+        }
+
+        let mut text = diff;
+        text.truncate(kept_bytes);
+        BudgetedOutput {
+            text,
+            truncation: Some(OutputTruncation {
+                shown_bytes: kept_bytes,
+                total_bytes,
+            }),
+        }
+    }
+}
+
+/// The ATX headings in `markdown`, along with their byte offsets, leaving out lines in fenced
+/// code blocks.
+pub(crate) fn markdown_headings(markdown: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut in_code_block = false;
+    let mut offset = 0;
+    markdown.split_inclusive('\n').filter_map(move |line| {
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            return None;
+        }
+        let level = trimmed.bytes().take_while(|byte| *byte == b'#').count();
+        (!in_code_block && (1..=6).contains(&level) && trimmed[level..].starts_with([' ', '\t']))
+            .then_some((start, line))
+    })
+}
+
+impl Default for ToolOutputBudget {
+    /// The budget for threads without a model, such as in tests.
+    fn default() -> Self {
+        Self::new(64 * 1024)
+    }
+}
+
+/// Text that has had a [`ToolOutputBudget`] applied to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudgetedOutput {
+    pub text: String,
+    /// Set when the text didn't fit the budget.
+    pub truncation: Option<OutputTruncation>,
+}
+
+impl BudgetedOutput {
+    fn complete(text: String) -> Self {
+        Self {
+            text,
+            truncation: None,
+        }
+    }
+
+    /// Returns the text, followed by a note telling the model it was truncated.
+    pub fn into_text_with_notice(self) -> String {
+        match self.truncation {
+            Some(truncation) => format!("{}\n\n{truncation}", self.text),
+            None => self.text,
+        }
+    }
+}
+
+/// How much of a tool's output was cut to fit its [`ToolOutputBudget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputTruncation {
+    pub shown_bytes: usize,
+    pub total_bytes: usize,
+}
+
+impl fmt::Display for OutputTruncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "output truncated: {} of {} bytes shown; refine your request",
+            self.shown_bytes, self.total_bytes
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use language_model::fake_provider::FakeLanguageModel;
+
+    #[test]
+    fn test_budget_for_model() {
+        // The fake model has a context window of a million tokens.
+        assert_eq!(
+            ToolOutputBudget::for_model(&FakeLanguageModel::default()).max_bytes(),
+            ToolOutputBudget::MAX_BYTES
+        );
+    }
+
+    #[test]
+    fn test_truncate_text_at_exact_boundary() {
+        let budget = ToolOutputBudget::new(10);
+        assert_eq!(
+            budget.truncate_text("0123456789".into()),
+            BudgetedOutput::complete("0123456789".into())
+        );
+
+        let output = budget.truncate_text("0123456789a".into());
+        assert_eq!(output.text, "01234\n[... 1 bytes omitted ...]\n6789a");
+        assert_eq!(
+            output.truncation,
+            Some(OutputTruncation {
+                shown_bytes: 10,
+                total_bytes: 11
+            })
+        );
+        assert_eq!(
+            output.into_text_with_notice(),
+            "01234\n[... 1 bytes omitted ...]\n6789a\n\noutput truncated: 10 of 11 bytes shown; refine your request"
+        );
+    }
+
+    #[test]
+    fn test_truncate_text_at_line_boundaries() {
+        let text = (0..10).map(|ix| format!("line {ix}\n")).collect::<String>();
+        let output = ToolOutputBudget::new(30).truncate_text(text);
+        assert_eq!(
+            output.text,
+            "line 0\nline 1\n[... 42 bytes omitted ...]\nline 8\nline 9\n"
+        );
+        assert_eq!(
+            output.truncation.map(|truncation| truncation.shown_bytes),
+            Some(28)
+        );
+    }
+
+    #[test]
+    fn test_truncate_text_does_not_split_characters() {
+        // Every character is three bytes long, so most budgets end inside a character.
+        let text = "日本語のテキスト".to_string();
+        for max_bytes in 0..text.len() {
+            let output = ToolOutputBudget::new(max_bytes).truncate_text(text.clone());
+            let truncation = output.truncation.unwrap();
+            assert!(truncation.shown_bytes <= max_bytes);
+            assert_eq!(
+                output.text.chars().filter(|c| !c.is_ascii()).count() * 3,
+                truncation.shown_bytes
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncate_markdown_keeps_later_headings() {
+        let markdown = indoc! {"
+            # Guide
+            Zed is a code editor.
+            ## Install
+            ```sh
+            # not a heading
+            curl -f https://zed.dev/install.sh | sh
+            ```
+            ## Usage
+            Open a folder to get started.
+            ### Flags
+            --wait waits for the file to close.
+        "};
+        assert_eq!(
+            ToolOutputBudget::new(markdown.len())
+                .truncate_markdown(markdown.into())
+                .truncation,
+            None
+        );
+
+        let output = ToolOutputBudget::new(80).truncate_markdown(markdown.into());
+        assert_eq!(
+            output.text,
+            indoc! {"
+                # Guide
+                Zed is a code editor.
+                ## Install
+                ```sh
+                [... 128 bytes omitted, leaving only these headings ...]
+                ## Usage
+                ### Flags
+            "}
+        );
+        assert_eq!(
+            output.truncation,
+            Some(OutputTruncation {
+                shown_bytes: 64,
+                total_bytes: markdown.len()
+            })
+        );
+    }
+
+    #[test]
+    fn test_truncate_diff_keeps_whole_hunks() {
+        let diff = indoc! {"
+            @@ -1,2 +1,2 @@
+            -one
+            +ONE
+             two
+            @@ -10,2 +10,2 @@
+             ten
+            -eleven
+            +ELEVEN
+            @@ -20,1 +20,1 @@
+            -twenty
+            +TWENTY
+        "};
+        let first_hunk_len = diff.find("@@ -10").unwrap();
+        let two_hunks_len = diff.find("@@ -20").unwrap();
+
+        let budget = ToolOutputBudget::new(diff.len());
+        assert_eq!(budget.truncate_diff(diff.into()).truncation, None);
+
+        let output = ToolOutputBudget::new(diff.len() - 1).truncate_diff(diff.into());
+        assert_eq!(output.text, &diff[..two_hunks_len]);
+        assert_eq!(
+            output.truncation,
+            Some(OutputTruncation {
+                shown_bytes: two_hunks_len,
+                total_bytes: diff.len()
+            })
+        );
+
+        let output = ToolOutputBudget::new(two_hunks_len - 1).truncate_diff(diff.into());
+        assert_eq!(output.text, &diff[..first_hunk_len]);
+        assert!(output.text.ends_with("two\n"));
+
+        // A hunk that doesn't fit on its own is left out rather than cut in half.
+        let output = ToolOutputBudget::new(first_hunk_len - 1).truncate_diff(diff.into());
+        assert_eq!(output.text, "");
+    }
+}
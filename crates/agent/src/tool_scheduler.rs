@@ -0,0 +1,144 @@
+use futures::{
+    FutureExt as _,
+    channel::oneshot,
+    future::{self, BoxFuture, Shared},
+};
+use smol::lock::{Semaphore, SemaphoreGuardArc};
+use std::sync::Arc;
+
+/// Resolves once the call it was made for has finished, or was dropped without running.
+type CallFinished = Shared<oneshot::Receiver<()>>;
+
+/// Decides when each of the tool calls in an assistant message may run.
+///
+/// Read-only calls start as soon as they're made, with at most `max_concurrent_reads` running
+/// at once. Calls that change things run one at a time in the order they were made, and only
+/// once the model is done making calls and every read-only call has finished, so that neither
+/// they nor the reads see a project that's halfway through being changed.
+pub struct ToolScheduler {
+    read_permits: Arc<Semaphore>,
+    reads: Vec<CallFinished>,
+    all_calls_made_tx: Option<oneshot::Sender<Vec<CallFinished>>>,
+    /// Resolves with every read-only call once [`Self::all_calls_made`] is called.
+    all_reads: Shared<oneshot::Receiver<Vec<CallFinished>>>,
+    last_write: Option<CallFinished>,
+}
+
+/// Held by a tool call while it runs. Dropping it lets the calls waiting on it run.
+pub struct ToolCallPermit {
+    _read_permit: Option<SemaphoreGuardArc>,
+    _finished_tx: oneshot::Sender<()>,
+}
+
+impl ToolScheduler {
+    pub fn new(max_concurrent_reads: usize) -> Self {
+        let (all_calls_made_tx, all_reads) = oneshot::channel();
+        Self {
+            read_permits: Arc::new(Semaphore::new(max_concurrent_reads.max(1))),
+            reads: Vec::new(),
+            all_calls_made_tx: Some(all_calls_made_tx),
+            all_reads: all_reads.shared(),
+            last_write: None,
+        }
+    }
+
+    /// Queues a call, returning a future that resolves once it may run.
+    pub fn schedule(&mut self, read_only: bool) -> BoxFuture<'static, ToolCallPermit> {
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let finished_rx = finished_rx.shared();
+        if read_only {
+            self.reads.push(finished_rx);
+            let read_permit = self.read_permits.acquire_arc();
+            async move {
+                ToolCallPermit {
+                    _read_permit: Some(read_permit.await),
+                    _finished_tx: finished_tx,
+                }
+            }
+            .boxed()
+        } else {
+            let last_write = self.last_write.replace(finished_rx);
+            let all_reads = self.all_reads.clone();
+            async move {
+                if let Ok(reads) = all_reads.await {
+                    future::join_all(reads).await;
+                }
+                if let Some(last_write) = last_write {
+                    last_write.await.ok();
+                }
+                ToolCallPermit {
+                    _read_permit: None,
+                    _finished_tx: finished_tx,
+                }
+            }
+            .boxed()
+        }
+    }
+
+    /// Lets the calls that change things run once the reads are done, since the model won't
+    /// make any more calls in this message.
+    pub fn all_calls_made(&mut self) {
+        if let Some(all_calls_made_tx) = self.all_calls_made_tx.take() {
+            all_calls_made_tx.send(self.reads.clone()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn is_ready(permit: &mut BoxFuture<'static, ToolCallPermit>) -> bool {
+        block_on(async { futures::poll!(permit) }).is_ready()
+    }
+
+    #[test]
+    fn test_reads_are_capped() {
+        let mut scheduler = ToolScheduler::new(2);
+        let mut reads = (0..3).map(|_| scheduler.schedule(true)).collect::<Vec<_>>();
+        let first = block_on(&mut reads[0]);
+        let _second = block_on(&mut reads[1]);
+        assert!(!is_ready(&mut reads[2]));
+
+        drop(first);
+        assert!(is_ready(&mut reads[2]));
+    }
+
+    #[test]
+    fn test_writes_wait_for_reads_and_each_other() {
+        let mut scheduler = ToolScheduler::new(4);
+        let mut first_write = scheduler.schedule(false);
+        let mut read = scheduler.schedule(true);
+        let mut second_write = scheduler.schedule(false);
+
+        // The read made after the write still goes first.
+        let read_permit = block_on(&mut read);
+        assert!(!is_ready(&mut first_write));
+
+        // Writes wait for the model to stop making calls, even once the reads are done.
+        drop(read_permit);
+        assert!(!is_ready(&mut first_write));
+        scheduler.all_calls_made();
+        let first_write_permit = block_on(&mut first_write);
+        assert!(!is_ready(&mut second_write));
+
+        drop(first_write_permit);
+        assert!(is_ready(&mut second_write));
+    }
+
+    #[test]
+    fn test_dropped_calls_dont_block_writes() {
+        let mut scheduler = ToolScheduler::new(1);
+        let held_read = scheduler.schedule(true);
+        let queued_read = scheduler.schedule(true);
+        let mut write = scheduler.schedule(false);
+        scheduler.all_calls_made();
+
+        let held_read = block_on(held_read);
+        drop(queued_read);
+        assert!(!is_ready(&mut write));
+        drop(held_read);
+        assert!(is_ready(&mut write));
+    }
+}
@@ -0,0 +1,244 @@
+use acp_thread::UserMessageId;
+use collections::HashMap;
+use language_model::LanguageModelToolUseId;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+/// How a tool call ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallOutcome {
+    Ok,
+    Error,
+    /// The user cancelled the turn while the tool was running.
+    Cancelled,
+}
+
+/// Where a single tool call spent its time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCallTiming {
+    pub tool_use_id: LanguageModelToolUseId,
+    pub tool_name: Arc<str>,
+    /// The user message whose turn made the call.
+    pub turn: Option<UserMessageId>,
+    /// From the model starting the call to the tool starting to run, which is mostly spent
+    /// streaming the tool's input.
+    pub queued: Duration,
+    pub running: Duration,
+    pub outcome: ToolCallOutcome,
+}
+
+impl ToolCallTiming {
+    pub fn total(&self) -> Duration {
+        self.queued + self.running
+    }
+}
+
+/// The calls to one tool, added up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolTimingSummary {
+    pub tool_name: Arc<str>,
+    pub calls: usize,
+    pub errors: usize,
+    pub cancelled: usize,
+    pub queued: Duration,
+    pub running: Duration,
+    pub slowest: Duration,
+}
+
+impl ToolTimingSummary {
+    fn new(tool_name: Arc<str>) -> Self {
+        Self {
+            tool_name,
+            calls: 0,
+            errors: 0,
+            cancelled: 0,
+            queued: Duration::ZERO,
+            running: Duration::ZERO,
+            slowest: Duration::ZERO,
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.queued + self.running
+    }
+
+    pub fn average(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(calls) if calls > 0 => self.total() / calls,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// How long the agent's tool calls took over the course of a thread.
+///
+/// It's saved with the thread, so the numbers are still there to look into when it's reopened.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolStats {
+    calls: Vec<ToolCallTiming>,
+}
+
+impl ToolStats {
+    pub fn calls(&self) -> &[ToolCallTiming] {
+        &self.calls
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    pub fn record(&mut self, timing: ToolCallTiming) {
+        self.calls.push(timing);
+    }
+
+    /// Forgets the calls made in response to a user message, when it's removed from the thread.
+    pub fn remove_turn(&mut self, turn: &UserMessageId) {
+        self.calls.retain(|call| call.turn.as_ref() != Some(turn));
+    }
+
+    pub fn turn_calls<'a>(
+        &'a self,
+        turn: &'a UserMessageId,
+    ) -> impl Iterator<Item = &'a ToolCallTiming> {
+        self.calls
+            .iter()
+            .filter(move |call| call.turn.as_ref() == Some(turn))
+    }
+
+    /// Every call in the thread, added up by tool, slowest overall first.
+    pub fn by_tool(&self) -> Vec<ToolTimingSummary> {
+        summarize(&self.calls)
+    }
+
+    /// The calls made in response to a user message, added up by tool, slowest overall first.
+    pub fn turn_by_tool(&self, turn: &UserMessageId) -> Vec<ToolTimingSummary> {
+        summarize(self.turn_calls(turn))
+    }
+}
+
+fn summarize<'a>(calls: impl IntoIterator<Item = &'a ToolCallTiming>) -> Vec<ToolTimingSummary> {
+    let mut summaries = HashMap::<Arc<str>, ToolTimingSummary>::default();
+    for call in calls {
+        let summary = summaries
+            .entry(call.tool_name.clone())
+            .or_insert_with(|| ToolTimingSummary::new(call.tool_name.clone()));
+        summary.calls += 1;
+        match call.outcome {
+            ToolCallOutcome::Ok => {}
+            ToolCallOutcome::Error => summary.errors += 1,
+            ToolCallOutcome::Cancelled => summary.cancelled += 1,
+        }
+        summary.queued += call.queued;
+        summary.running += call.running;
+        summary.slowest = summary.slowest.max(call.total());
+    }
+    let mut summaries = summaries.into_values().collect::<Vec<_>>();
+    summaries.sort_by(|a, b| {
+        b.total()
+            .cmp(&a.total())
+            .then_with(|| a.tool_name.cmp(&b.tool_name))
+    });
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(
+        tool_name: &str,
+        turn: &UserMessageId,
+        queued_ms: u64,
+        running_ms: u64,
+        outcome: ToolCallOutcome,
+    ) -> ToolCallTiming {
+        ToolCallTiming {
+            tool_use_id: format!("{tool_name}-{queued_ms}-{running_ms}").into(),
+            tool_name: tool_name.into(),
+            turn: Some(turn.clone()),
+            queued: Duration::from_millis(queued_ms),
+            running: Duration::from_millis(running_ms),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_tool_stats_aggregation() {
+        let first_turn = UserMessageId::new();
+        let second_turn = UserMessageId::new();
+        let mut stats = ToolStats::default();
+        stats.record(timing("grep", &first_turn, 100, 400, ToolCallOutcome::Ok));
+        stats.record(timing(
+            "edit_file",
+            &first_turn,
+            3000,
+            5100,
+            ToolCallOutcome::Ok,
+        ));
+        stats.record(timing("grep", &first_turn, 0, 1500, ToolCallOutcome::Error));
+        stats.record(timing(
+            "terminal",
+            &second_turn,
+            0,
+            2000,
+            ToolCallOutcome::Cancelled,
+        ));
+        stats.record(timing("grep", &second_turn, 0, 300, ToolCallOutcome::Ok));
+
+        assert_eq!(
+            stats.turn_by_tool(&first_turn),
+            vec![
+                ToolTimingSummary {
+                    tool_name: "edit_file".into(),
+                    calls: 1,
+                    errors: 0,
+                    cancelled: 0,
+                    queued: Duration::from_millis(3000),
+                    running: Duration::from_millis(5100),
+                    slowest: Duration::from_millis(8100),
+                },
+                ToolTimingSummary {
+                    tool_name: "grep".into(),
+                    calls: 2,
+                    errors: 1,
+                    cancelled: 0,
+                    queued: Duration::from_millis(100),
+                    running: Duration::from_millis(1900),
+                    slowest: Duration::from_millis(1500),
+                },
+            ]
+        );
+
+        let by_tool = stats.by_tool();
+        assert_eq!(
+            by_tool
+                .iter()
+                .map(|summary| (summary.tool_name.as_ref(), summary.calls, summary.total()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("edit_file", 1, Duration::from_millis(8100)),
+                ("grep", 3, Duration::from_millis(2300)),
+                ("terminal", 1, Duration::from_millis(2000)),
+            ]
+        );
+        assert_eq!(by_tool[1].average(), Duration::from_nanos(766_666_666));
+        assert_eq!(by_tool[2].cancelled, 1);
+    }
+
+    #[test]
+    fn test_removing_a_turn() {
+        let first_turn = UserMessageId::new();
+        let second_turn = UserMessageId::new();
+        let mut stats = ToolStats::default();
+        stats.record(timing("grep", &first_turn, 0, 100, ToolCallOutcome::Ok));
+        stats.record(timing("grep", &second_turn, 0, 200, ToolCallOutcome::Ok));
+
+        stats.remove_turn(&second_turn);
+        assert_eq!(stats.turn_calls(&second_turn).count(), 0);
+        assert_eq!(stats.by_tool()[0].total(), Duration::from_millis(100));
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<ToolStats>(&json).unwrap(), stats);
+    }
+}
@@ -1,3 +1,5 @@
+mod code_fence;
+mod code_symbols_tool;
 mod context_server_registry;
 mod copy_path_tool;
 mod create_directory_tool;
@@ -5,17 +7,25 @@ mod delete_path_tool;
 mod diagnostics_tool;
 mod edit_file_tool;
 mod fetch_tool;
+mod file_encoding;
+mod file_history_tool;
 mod find_path_tool;
+mod format_file_tool;
 mod grep_tool;
 mod list_directory_tool;
+mod list_touched_files_tool;
 mod move_path_tool;
 mod now_tool;
 mod open_tool;
 mod read_file_tool;
+mod read_instructions_tool;
 mod restore_file_from_disk_tool;
 mod save_file_tool;
+mod secret_scan;
 mod spawn_agent_tool;
 mod streaming_edit_file_tool;
+mod syntax_check;
+mod task_list_tool;
 mod terminal_tool;
 mod tool_edit_parser;
 mod tool_permissions;
@@ -24,6 +34,7 @@ mod web_search_tool;
 use crate::AgentTool;
 use language_model::{LanguageModelRequestTool, LanguageModelToolSchemaFormat};
 
+pub use code_symbols_tool::*;
 pub use context_server_registry::*;
 pub use copy_path_tool::*;
 pub use create_directory_tool::*;
@@ -31,17 +42,22 @@ pub use delete_path_tool::*;
 pub use diagnostics_tool::*;
 pub use edit_file_tool::*;
 pub use fetch_tool::*;
+pub use file_history_tool::*;
 pub use find_path_tool::*;
+pub use format_file_tool::*;
 pub use grep_tool::*;
 pub use list_directory_tool::*;
+pub use list_touched_files_tool::*;
 pub use move_path_tool::*;
 pub use now_tool::*;
 pub use open_tool::*;
 pub use read_file_tool::*;
+pub use read_instructions_tool::*;
 pub use restore_file_from_disk_tool::*;
 pub use save_file_tool::*;
 pub use spawn_agent_tool::*;
 pub use streaming_edit_file_tool::*;
+pub use task_list_tool::*;
 pub use terminal_tool::*;
 pub use tool_permissions::*;
 pub use web_search_tool::*;
@@ -115,22 +131,28 @@ macro_rules! tools {
 }
 
 tools! {
+    CodeSymbolsTool,
     CopyPathTool,
     CreateDirectoryTool,
     DeletePathTool,
     DiagnosticsTool,
     EditFileTool,
     FetchTool,
+    FileHistoryTool,
     FindPathTool,
+    FormatFileTool,
     GrepTool,
     ListDirectoryTool,
+    ListTouchedFilesTool,
     MovePathTool,
     NowTool,
     OpenTool,
     ReadFileTool,
+    ReadInstructionsTool,
     RestoreFileFromDiskTool,
     SaveFileTool,
     SpawnAgentTool,
+    TaskListTool,
     TerminalTool,
     WebSearchTool,
 }
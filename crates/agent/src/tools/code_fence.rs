@@ -0,0 +1,619 @@
+//! Strips the markdown code fences that models sometimes wrap around the text they send to the
+//! edit tools, which would otherwise be written into the file along with the code.
+//!
+//! Only files whose language can't legitimately contain a fence are sanitized, and only text that
+//! is a single fenced block loses its fences. Text that mixes prose with a fenced block, or whose
+//! fence names a different language, is refused rather than guessed at.
+
+use super::tool_edit_parser::ToolEditEvent;
+use anyhow::anyhow;
+use language::Language;
+use serde::{Deserialize, Serialize};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Languages whose files can contain code fences as part of their content.
+const FENCED_LANGUAGES: &[&str] = &[
+    "Markdown",
+    "Markdown-Inline",
+    "MDX",
+    "AsciiDoc",
+    "Plain Text",
+    "reStructuredText",
+];
+
+/// Extensions of the files that can contain code fences, for when their language is unknown.
+const FENCED_EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "adoc", "asciidoc", "txt", "rst"];
+
+/// A code fence that wrapped part of the tool input and was removed before it was written.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrippedFence {
+    /// The part of the input that was fenced, such as `new_text of edit 0`.
+    pub field: String,
+    /// The fence's opening line, such as ```` ```rust ````.
+    pub fence: String,
+}
+
+/// Strips the code fences around the texts of an edit tool call as they stream in.
+pub struct CodeFenceSanitizer {
+    language: Arc<Language>,
+    file_name: String,
+    /// The info strings that name the file's language.
+    fence_languages: Arc<[String]>,
+    content: FenceFilter,
+    old_texts: Vec<FenceFilter>,
+    new_texts: Vec<FenceFilter>,
+    stripped: Vec<StrippedFence>,
+}
+
+impl CodeFenceSanitizer {
+    /// Returns `None` for files that can contain code fences, like Markdown, which are never
+    /// changed.
+    pub fn new(language: Arc<Language>, path: &Path) -> Option<Self> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let can_contain_fences = FENCED_LANGUAGES.contains(&language.name().as_ref())
+            || extension
+                .as_deref()
+                .is_some_and(|extension| FENCED_EXTENSIONS.contains(&extension));
+        if can_contain_fences {
+            return None;
+        }
+        let fence_languages = [
+            language.name().as_ref().to_lowercase(),
+            language.code_fence_block_name().to_lowercase(),
+        ]
+        .into_iter()
+        .chain(
+            language
+                .path_suffixes()
+                .iter()
+                .map(|suffix| suffix.to_lowercase()),
+        )
+        .chain(extension)
+        .collect::<Arc<[_]>>();
+        Some(Self {
+            language,
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            content: FenceFilter::new(fence_languages.clone()),
+            fence_languages,
+            old_texts: Vec::new(),
+            new_texts: Vec::new(),
+            stripped: Vec::new(),
+        })
+    }
+
+    /// Strips the fences from the chunks in `events`, holding back the text that may turn out to
+    /// be a fence until more of it has streamed in.
+    pub fn filter(
+        &mut self,
+        events: impl IntoIterator<Item = ToolEditEvent>,
+    ) -> anyhow::Result<Vec<ToolEditEvent>> {
+        let mut filtered = Vec::new();
+        for event in events {
+            match event {
+                ToolEditEvent::ContentChunk { chunk } => {
+                    let mut text = String::new();
+                    let result = self.content.push(&chunk, &mut text).map(|()| None);
+                    self.check(result, "content")?;
+                    if !text.is_empty() {
+                        filtered.push(ToolEditEvent::ContentChunk { chunk: text });
+                    }
+                }
+                ToolEditEvent::OldTextChunk {
+                    edit_index,
+                    chunk,
+                    done,
+                } => {
+                    let chunk = self.filter_edit_text(edit_index, false, &chunk, done)?;
+                    if done || !chunk.is_empty() {
+                        filtered.push(ToolEditEvent::OldTextChunk {
+                            edit_index,
+                            chunk,
+                            done,
+                        });
+                    }
+                }
+                ToolEditEvent::NewTextChunk {
+                    edit_index,
+                    chunk,
+                    done,
+                } => {
+                    let chunk = self.filter_edit_text(edit_index, true, &chunk, done)?;
+                    if done || !chunk.is_empty() {
+                        filtered.push(ToolEditEvent::NewTextChunk {
+                            edit_index,
+                            chunk,
+                            done,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(filtered)
+    }
+
+    /// Ends the content written in write mode, returning the text that was held back.
+    pub fn finish_content(&mut self) -> anyhow::Result<Option<ToolEditEvent>> {
+        let mut text = String::new();
+        let result = self.content.finish(&mut text, true);
+        self.check(result, "content")?;
+        Ok((!text.is_empty()).then_some(ToolEditEvent::ContentChunk { chunk: text }))
+    }
+
+    /// The fences that were stripped so far.
+    pub fn take_stripped(&mut self) -> Vec<StrippedFence> {
+        mem::take(&mut self.stripped)
+    }
+
+    fn filter_edit_text(
+        &mut self,
+        edit_index: usize,
+        is_new_text: bool,
+        chunk: &str,
+        done: bool,
+    ) -> anyhow::Result<String> {
+        let filters = if is_new_text {
+            &mut self.new_texts
+        } else {
+            &mut self.old_texts
+        };
+        if filters.len() <= edit_index {
+            filters.resize_with(edit_index + 1, || {
+                FenceFilter::new(self.fence_languages.clone())
+            });
+        }
+        let filter = &mut filters[edit_index];
+
+        let mut text = String::new();
+        let mut result = filter.push(chunk, &mut text).map(|()| None);
+        if done && result.is_ok() {
+            result = filter.finish(&mut text, false);
+        }
+        let field = format!(
+            "{} of edit {edit_index}",
+            if is_new_text { "new_text" } else { "old_text" }
+        );
+        self.check(result, &field)?;
+        Ok(text)
+    }
+
+    /// Records the fence that a finished text was stripped of, or turns its problem into an
+    /// error for the model.
+    fn check(
+        &mut self,
+        result: Result<Option<Fence>, FenceProblem>,
+        field: &str,
+    ) -> anyhow::Result<()> {
+        const ADVICE: &str = "The text you send is written into the file exactly as it is, so \
+            send only the file's text, without markdown code fences or explanations.";
+        match result {
+            Ok(Some(fence)) => {
+                self.stripped.push(StrippedFence {
+                    field: field.to_string(),
+                    fence: fence.line,
+                });
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(FenceProblem::Mismatch(fence)) => Err(anyhow!(
+                "The {field} is wrapped in a {} code fence, but {} is a {} file. {ADVICE}",
+                fence.line,
+                self.file_name,
+                self.language.name()
+            )),
+            Err(FenceProblem::Mixed) => Err(anyhow!(
+                "The {field} mixes prose with a fenced code block, so it's unclear which of it \
+                belongs in {}. {ADVICE}",
+                self.file_name
+            )),
+            Err(FenceProblem::Unclosed(fence)) => Err(anyhow!(
+                "The {field} starts with a {} code fence that is never closed. {ADVICE}",
+                fence.line
+            )),
+        }
+    }
+}
+
+enum FenceProblem {
+    /// The fence names a different language than the file's.
+    Mismatch(Fence),
+    /// The text has prose before or after a fenced block.
+    Mixed,
+    /// The text opens a fence and never closes it.
+    Unclosed(Fence),
+}
+
+#[derive(Clone, Debug)]
+struct Fence {
+    character: char,
+    len: usize,
+    /// The opening line, without its indentation.
+    line: String,
+    /// The language named by the info string, lowercased, or an empty string.
+    info: String,
+}
+
+impl Fence {
+    /// Parses a line that opens a fenced code block, following CommonMark.
+    fn parse_opening(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        let rest = line.trim_start_matches(' ');
+        if line.len() - rest.len() > 3 {
+            return None;
+        }
+        let character = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let len = rest.len() - rest.trim_start_matches(character).len();
+        if len < 3 {
+            return None;
+        }
+        let info = &rest[len..];
+        if character == '`' && info.contains('`') {
+            return None;
+        }
+        let language = info
+            .split([' ', '\t', ',', '{', '}'])
+            .find(|word| !word.is_empty())
+            .unwrap_or_default()
+            .trim_start_matches('.');
+        Some(Self {
+            character,
+            len,
+            line: rest.to_string(),
+            info: language.to_lowercase(),
+        })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        let rest = line.trim_start_matches(' ');
+        line.len() - rest.len() <= 3
+            && rest.len() >= self.len
+            && rest.chars().all(|c| c == self.character)
+    }
+}
+
+/// Whether `partial`, the start of a line, could still turn out to be a fence.
+fn could_be_fence(partial: &str) -> bool {
+    let rest = partial.trim_start_matches(' ');
+    if partial.len() - rest.len() > 3 {
+        return false;
+    }
+    let Some(character) = rest.chars().next() else {
+        return true;
+    };
+    if character != '`' && character != '~' {
+        return false;
+    }
+    let len = rest.len() - rest.trim_start_matches(character).len();
+    len >= 3 || len == rest.len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Strips the fence around a single text of the tool input as it streams in.
+struct FenceFilter {
+    /// The info strings an opening fence may have, besides an empty one.
+    fence_languages: Arc<[String]>,
+    state: FilterState,
+    /// The text of the current line that hasn't been looked at yet.
+    pending: String,
+    /// Whether the start of the current line was already passed on, so the rest of it is too.
+    mid_line: bool,
+}
+
+enum FilterState {
+    /// Only blank lines so far, which are held until the first line shows whether the text is
+    /// fenced.
+    Start { held: String },
+    /// The text opened with `fence`. What's inside is passed on, except for the newline before
+    /// what may be the closing fence.
+    Fenced {
+        fence: Fence,
+        newline_held: bool,
+        closed: bool,
+    },
+    /// The text didn't open with a fence. A fenced block within it is held until something
+    /// other than blank lines follows it, since a block that ends the text means the text is
+    /// prose followed by code.
+    Unfenced { block: Option<HeldBlock> },
+}
+
+impl Default for FilterState {
+    fn default() -> Self {
+        Self::Start {
+            held: String::new(),
+        }
+    }
+}
+
+struct HeldBlock {
+    fence: Fence,
+    text: String,
+    closed: bool,
+}
+
+impl FenceFilter {
+    fn new(fence_languages: Arc<[String]>) -> Self {
+        Self {
+            fence_languages,
+            state: FilterState::default(),
+            pending: String::new(),
+            mid_line: false,
+        }
+    }
+
+    fn push(&mut self, chunk: &str, output: &mut String) -> Result<(), FenceProblem> {
+        self.pending.push_str(chunk);
+        while let Some(newline) = self.pending.find('\n') {
+            let line = self.pending.drain(..=newline).collect::<String>();
+            if mem::take(&mut self.mid_line) {
+                self.continue_line(&line, output);
+            } else {
+                self.push_line(&line, output)?;
+            }
+        }
+        if !self.pending.is_empty() {
+            self.push_partial_line(output)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the text, returning the fence it was stripped of. `keep_final_newline` keeps the
+    /// newline before the closing fence, which ends a whole file but not an edit's text.
+    fn finish(
+        &mut self,
+        output: &mut String,
+        keep_final_newline: bool,
+    ) -> Result<Option<Fence>, FenceProblem> {
+        let rest = mem::take(&mut self.pending);
+        if mem::take(&mut self.mid_line) {
+            self.continue_line(&rest, output);
+        } else if !rest.is_empty() {
+            self.push_line(&rest, output)?;
+        }
+
+        match mem::take(&mut self.state) {
+            FilterState::Start { held } => {
+                output.push_str(&held);
+                Ok(None)
+            }
+            FilterState::Fenced {
+                fence,
+                newline_held,
+                closed,
+            } => {
+                if !closed {
+                    return Err(FenceProblem::Unclosed(fence));
+                }
+                if newline_held && keep_final_newline {
+                    output.push('\n');
+                }
+                Ok(Some(fence))
+            }
+            FilterState::Unfenced { block: Some(block) } if block.closed => {
+                Err(FenceProblem::Mixed)
+            }
+            FilterState::Unfenced { block } => {
+                if let Some(block) = block {
+                    output.push_str(&block.text);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn push_line(&mut self, line: &str, output: &mut String) -> Result<(), FenceProblem> {
+        match &mut self.state {
+            FilterState::Start { held } => {
+                if is_blank(line) {
+                    held.push_str(line);
+                } else if let Some(fence) = Fence::parse_opening(line) {
+                    if !fence.info.is_empty() && !self.fence_languages.contains(&fence.info) {
+                        return Err(FenceProblem::Mismatch(fence));
+                    }
+                    self.state = FilterState::Fenced {
+                        fence,
+                        newline_held: false,
+                        closed: false,
+                    };
+                } else {
+                    output.push_str(held);
+                    self.state = FilterState::Unfenced { block: None };
+                    self.push_line(line, output)?;
+                }
+            }
+            FilterState::Fenced { closed: true, .. } => {
+                if !is_blank(line) {
+                    return Err(FenceProblem::Mixed);
+                }
+            }
+            FilterState::Fenced { fence, closed, .. } => {
+                if fence.is_closed_by(line) {
+                    *closed = true;
+                } else {
+                    self.continue_line(line, output);
+                }
+            }
+            FilterState::Unfenced { block: None } => {
+                if let Some(fence) = Fence::parse_opening(line) {
+                    self.state = FilterState::Unfenced {
+                        block: Some(HeldBlock {
+                            fence,
+                            text: line.to_string(),
+                            closed: false,
+                        }),
+                    };
+                } else {
+                    output.push_str(line);
+                }
+            }
+            FilterState::Unfenced { block: Some(block) } => {
+                if !block.closed {
+                    block.closed = block.fence.is_closed_by(line);
+                    block.text.push_str(line);
+                } else if is_blank(line) {
+                    block.text.push_str(line);
+                } else {
+                    output.push_str(&block.text);
+                    self.state = FilterState::Unfenced { block: None };
+                    self.push_line(line, output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes on the start of a line once it's clear that it isn't a fence, so that text streams
+    /// in as it arrives rather than a line at a time.
+    fn push_partial_line(&mut self, output: &mut String) -> Result<(), FenceProblem> {
+        if self.mid_line {
+            let partial = mem::take(&mut self.pending);
+            self.continue_line(&partial, output);
+            self.mid_line = true;
+            return Ok(());
+        }
+        match &mut self.state {
+            FilterState::Start { held } => {
+                if is_blank(&self.pending) || could_be_fence(&self.pending) {
+                    return Ok(());
+                }
+                output.push_str(held);
+                self.state = FilterState::Unfenced { block: None };
+            }
+            FilterState::Fenced { closed: true, .. } => {
+                if is_blank(&self.pending) {
+                    return Ok(());
+                }
+                return Err(FenceProblem::Mixed);
+            }
+            FilterState::Fenced { .. } | FilterState::Unfenced { block: None } => {
+                if could_be_fence(&self.pending) {
+                    return Ok(());
+                }
+            }
+            FilterState::Unfenced { block: Some(_) } => return Ok(()),
+        }
+        let partial = mem::take(&mut self.pending);
+        self.continue_line(&partial, output);
+        self.mid_line = true;
+        Ok(())
+    }
+
+    /// Passes on text that isn't a fence.
+    fn continue_line(&mut self, text: &str, output: &mut String) {
+        match &mut self.state {
+            FilterState::Fenced { newline_held, .. } => {
+                if mem::take(newline_held) {
+                    output.push('\n');
+                }
+                match text.strip_suffix('\n') {
+                    Some(line) => {
+                        output.push_str(line);
+                        *newline_held = true;
+                    }
+                    None => output.push_str(text),
+                }
+            }
+            _ => output.push_str(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Filters `text` whole and a character at a time, checking that both give the same result.
+    fn filter(text: &str, keep_final_newline: bool) -> Result<(String, Option<String>), String> {
+        let run = |chunks: Vec<&str>| {
+            let mut filter = FenceFilter::new(Arc::from(["rust".to_string(), "rs".to_string()]));
+            let mut output = String::new();
+            let result = chunks
+                .into_iter()
+                .try_for_each(|chunk| filter.push(chunk, &mut output))
+                .and_then(|()| filter.finish(&mut output, keep_final_newline));
+            match result {
+                Ok(fence) => Ok((output, fence.map(|fence| fence.line))),
+                Err(FenceProblem::Mismatch(fence)) => Err(format!("mismatch: {}", fence.line)),
+                Err(FenceProblem::Mixed) => Err("mixed".to_string()),
+                Err(FenceProblem::Unclosed(fence)) => Err(format!("unclosed: {}", fence.line)),
+            }
+        };
+        let whole = run(vec![text]);
+        let chars = text
+            .char_indices()
+            .map(|(ix, c)| &text[ix..ix + c.len_utf8()])
+            .collect();
+        assert_eq!(
+            run(chars),
+            whole,
+            "streaming changed the result for {text:?}"
+        );
+        whole
+    }
+
+    #[test]
+    fn test_strips_wrapping_fence() {
+        assert_eq!(
+            filter("```rust\nfn main() {}\n```", false),
+            Ok(("fn main() {}".to_string(), Some("```rust".to_string())))
+        );
+        assert_eq!(
+            filter("\n```rs\nfn main() {}\n\n```\n\n", true),
+            Ok(("fn main() {}\n\n".to_string(), Some("```rs".to_string())))
+        );
+        assert_eq!(
+            filter("~~~~\nlet fence = \"```\";\n~~~~", false),
+            Ok(("let fence = \"```\";".to_string(), Some("~~~~".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_leaves_unfenced_text() {
+        for text in [
+            "fn main() {}\n",
+            "    let x = 1;",
+            "let s = r#\"\n```rust\nfn a() {}\n```\n\"#;\n",
+            "let s = \"``` is a fence\";",
+            "\n\n",
+        ] {
+            assert_eq!(filter(text, false), Ok((text.to_string(), None)));
+        }
+    }
+
+    #[test]
+    fn test_refuses_unclear_fences() {
+        assert_eq!(
+            filter("Here's the fix:\n\n```rust\nfn a() {}\n```\n", false),
+            Err("mixed".to_string())
+        );
+        assert_eq!(
+            filter("```rust\nfn a() {}\n```\nThis renames `b` to `a`.", false),
+            Err("mixed".to_string())
+        );
+        assert_eq!(
+            filter("```python\nprint(1)\n```", false),
+            Err("mismatch: ```python".to_string())
+        );
+        assert_eq!(
+            filter("```rust\nfn a() {}\n", false),
+            Err("unclosed: ```rust".to_string())
+        );
+    }
+
+    #[test]
+    fn test_markdown_is_never_sanitized() {
+        assert!(
+            CodeFenceSanitizer::new(language::markdown_lang(), Path::new("README.md")).is_none()
+        );
+        assert!(CodeFenceSanitizer::new(language::rust_lang(), Path::new("main.rs")).is_some());
+    }
+}
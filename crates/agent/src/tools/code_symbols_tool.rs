@@ -0,0 +1,680 @@
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use futures::{StreamExt as _, stream};
+use gpui::{App, AppContext as _, AsyncApp, Entity, SharedString, Task, WeakEntity};
+use language::{BufferSnapshot, Point};
+use project::{Project, ProjectPath, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings;
+use std::{fmt::Write as _, path::PathBuf, sync::Arc};
+use util::{markdown::MarkdownInlineCode, paths::PathMatcher, rel_path::RelPath};
+
+use super::tool_permissions::{
+    ResolvedProjectPath, authorize_symlink_access, canonicalize_worktree_roots,
+    resolve_project_path,
+};
+use crate::{AgentTool, Thread, ToolCallEventStream, ToolInput};
+
+/// Lists the symbols (functions, types, methods, and so on) defined in a file, or in the files of
+/// a directory, without their contents.
+///
+/// - Each symbol is shown on one line, indented under the symbol it's nested in, with its first
+///   source line and its line range, like `pub fn area [L12-18]: pub fn area(&self) -> f64`.
+/// - Use this to get the lay of the land before reading specific sections with read_file and its
+///   start_line and end_line parameters.
+/// - Markdown files are outlined by their headings. Files in languages without outline support
+///   are listed with a note instead.
+/// - Seeing a file's outline doesn't count as reading it: read the parts you change with
+///   read_file before editing the file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CodeSymbolsToolInput {
+    /// The relative path of the file or directory to outline.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    ///
+    /// <example>
+    /// If the project has the following root directories:
+    ///
+    /// - /a/b/directory1
+    /// - /c/d/directory2
+    ///
+    /// If you want the symbols in `lib.rs` in `directory1`, you should use the path `directory1/lib.rs`.
+    /// If you want the symbols in all the files of `src` in `directory2`, you should use the path `directory2/src`.
+    /// </example>
+    pub path: String,
+    /// When `path` is a directory, only files whose path matches this glob are outlined, e.g.
+    /// "**/*.rs". The glob is matched against the whole path, starting with the root directory.
+    /// All files in the directory are outlined when it's omitted.
+    #[serde(default)]
+    pub glob: Option<String>,
+}
+
+/// How many files of a directory are outlined at most.
+const MAX_FILES: usize = 100;
+/// How many files are opened and outlined at the same time.
+const MAX_CONCURRENT_FILES: usize = 8;
+/// The least of the output budget each file gets, however many files there are.
+const MIN_BYTES_PER_FILE: usize = 1024;
+/// Signature lines longer than this are cut short.
+const MAX_SIGNATURE_LEN: usize = 160;
+
+pub struct CodeSymbolsTool {
+    thread: WeakEntity<Thread>,
+    project: Entity<Project>,
+}
+
+impl CodeSymbolsTool {
+    pub fn new(thread: WeakEntity<Thread>, project: Entity<Project>) -> Self {
+        Self { thread, project }
+    }
+}
+
+impl AgentTool for CodeSymbolsTool {
+    type Input = CodeSymbolsToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "code_symbols";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        if let Ok(input) = input {
+            let path = MarkdownInlineCode(&input.path);
+            match &input.glob {
+                Some(glob) => format!("List symbols in {path} ({})", MarkdownInlineCode(glob)),
+                None => format!("List symbols in {path}"),
+            }
+            .into()
+        } else {
+            "List symbols".into()
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output, Self::Output>> {
+        let project = self.project.clone();
+        let thread = self.thread.clone();
+        cx.spawn(async move |cx| {
+            let input = input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+
+            let fs = project.read_with(cx, |project, _cx| project.fs().clone());
+            let canonical_roots = canonicalize_worktree_roots(&project, &fs, cx).await;
+
+            let (project_path, symlink_canonical_target) = project
+                .read_with(cx, |project, cx| -> anyhow::Result<_> {
+                    let resolved =
+                        resolve_project_path(project, &input.path, &canonical_roots, cx)?;
+                    event_stream.check_worktree_scope(resolved.project_path(), &input.path)?;
+                    Ok(match resolved {
+                        ResolvedProjectPath::Safe(path) => (path, None),
+                        ResolvedProjectPath::SymlinkEscape {
+                            project_path,
+                            canonical_target,
+                        } => (project_path, Some(canonical_target)),
+                    })
+                })
+                .map_err(|e| e.to_string())?;
+
+            if let Some(canonical_target) = &symlink_canonical_target {
+                let authorize = cx.update(|cx| {
+                    authorize_symlink_access(
+                        Self::NAME,
+                        &input.path,
+                        canonical_target,
+                        &event_stream,
+                        cx,
+                    )
+                });
+                authorize.await.map_err(|e| e.to_string())?;
+            }
+
+            let (files, skipped_files) = cx
+                .update(|cx| files_to_outline(&input, &project_path, project.read(cx), cx))
+                .map_err(|e| e.to_string())?;
+
+            let mut outlines = stream::iter(files)
+                .map(|file| outline_file(file, project.clone(), cx.clone()))
+                .buffer_unordered(MAX_CONCURRENT_FILES)
+                .collect::<Vec<_>>()
+                .await;
+            outlines.sort_by(|a, b| a.path.cmp(&b.path));
+
+            thread
+                .update(cx, |thread, cx| {
+                    for abs_path in outlines.iter().filter_map(|file| file.abs_path.clone()) {
+                        thread.record_file_outline(abs_path, cx);
+                    }
+                })
+                .ok();
+
+            let output_budget = event_stream.output_budget();
+            // Some of the budget is kept for the file headers and notes.
+            let bytes_per_file = (output_budget.max_bytes() * 9 / 10 / outlines.len().max(1))
+                .max(MIN_BYTES_PER_FILE);
+            let mut output = String::new();
+            for file in &outlines {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                file.render(&mut output, bytes_per_file);
+            }
+            if outlines.is_empty() {
+                output.push_str("No files to outline.\n");
+            }
+            if skipped_files > 0 {
+                writeln!(
+                    output,
+                    "\n{skipped_files} more files were not outlined. Pass a narrower path or glob to see them."
+                )
+                .ok();
+            }
+            Ok(output_budget.truncate_text(output).into_text_with_notice())
+        })
+    }
+}
+
+/// A file to outline, along with the path it's shown with.
+struct FileToOutline {
+    path: String,
+    project_path: ProjectPath,
+}
+
+/// Returns the files to outline for `project_path`, which is either a file or a directory, and how
+/// many more matching files there were beyond [`MAX_FILES`].
+fn files_to_outline(
+    input: &CodeSymbolsToolInput,
+    project_path: &ProjectPath,
+    project: &Project,
+    cx: &App,
+) -> Result<(Vec<FileToOutline>, usize)> {
+    let worktree = project
+        .worktree_for_id(project_path.worktree_id, cx)
+        .with_context(|| format!("{} is not in a known worktree", &input.path))?;
+    let snapshot = worktree.read(cx).snapshot();
+    let global_settings = WorktreeSettings::get_global(cx);
+    let worktree_settings = WorktreeSettings::get(Some(project_path.into()), cx);
+    let is_hidden = |path: &RelPath| {
+        global_settings.is_path_excluded(path)
+            || global_settings.is_path_private(path)
+            || worktree_settings.is_path_excluded(path)
+            || worktree_settings.is_path_private(path)
+    };
+
+    if is_hidden(project_path.path.as_ref()) {
+        anyhow::bail!(
+            "Cannot list symbols because the path matches the `file_scan_exclusions` or `private_files` setting: {}",
+            &input.path
+        );
+    }
+    let entry = snapshot
+        .entry_for_path(&project_path.path)
+        .ok_or_else(|| anyhow!("Path not found: {}", &input.path))?;
+    if !entry.is_dir() {
+        let file = FileToOutline {
+            path: input.path.clone(),
+            project_path: project_path.clone(),
+        };
+        return Ok((vec![file], 0));
+    }
+
+    let glob = input
+        .glob
+        .as_ref()
+        .map(|glob| {
+            PathMatcher::new([glob], project.path_style(cx))
+                .with_context(|| format!("invalid glob pattern {glob:?}"))
+        })
+        .transpose()?;
+    let mut files = snapshot
+        .traverse_from_path(true, false, false, &project_path.path)
+        .take_while(|entry| entry.path.starts_with(&project_path.path))
+        .filter(|entry| !is_hidden(entry.path.as_ref()))
+        .filter_map(|entry| {
+            let full_path = snapshot.root_name().join(&entry.path);
+            if glob.as_ref().is_some_and(|glob| !glob.is_match(&full_path)) {
+                return None;
+            }
+            Some(FileToOutline {
+                path: full_path.display(project.path_style(cx)).into_owned(),
+                project_path: ProjectPath {
+                    worktree_id: project_path.worktree_id,
+                    path: entry.path.clone(),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    let skipped_files = files.len().saturating_sub(MAX_FILES);
+    files.truncate(MAX_FILES);
+    Ok((files, skipped_files))
+}
+
+struct FileOutline {
+    path: String,
+    abs_path: Option<PathBuf>,
+    /// The file's symbols, or a note saying why there aren't any.
+    symbols: Result<Vec<Symbol>, String>,
+}
+
+struct Symbol {
+    depth: usize,
+    text: String,
+    /// The first line of the symbol's source, when it says more than `text`.
+    signature: Option<String>,
+    /// The symbol's first and last line, 0-based.
+    rows: (u32, u32),
+}
+
+impl FileOutline {
+    /// Writes the outline to `output`, leaving out the symbols that don't fit in `max_bytes`.
+    fn render(&self, output: &mut String, max_bytes: usize) {
+        writeln!(output, "# {}", self.path).ok();
+        let symbols = match &self.symbols {
+            Ok(symbols) if symbols.is_empty() => {
+                output.push_str("No symbols found.\n");
+                return;
+            }
+            Ok(symbols) => symbols,
+            Err(note) => {
+                writeln!(output, "{note}").ok();
+                return;
+            }
+        };
+
+        let start = output.len();
+        for (ix, symbol) in symbols.iter().enumerate() {
+            let line_start = output.len();
+            for _ in 0..symbol.depth {
+                output.push_str("  ");
+            }
+            output.push_str(&symbol.text);
+            let (start_row, end_row) = symbol.rows;
+            if start_row == end_row {
+                write!(output, " [L{}]", start_row + 1).ok();
+            } else {
+                write!(output, " [L{}-{}]", start_row + 1, end_row + 1).ok();
+            }
+            if let Some(signature) = &symbol.signature {
+                write!(output, ": {signature}").ok();
+            }
+            output.push('\n');
+
+            if output.len() - start > max_bytes && ix > 0 {
+                output.truncate(line_start);
+                writeln!(
+                    output,
+                    "[... {} more symbols omitted; list the symbols of this file on its own to see them ...]",
+                    symbols.len() - ix
+                )
+                .ok();
+                break;
+            }
+        }
+    }
+}
+
+async fn outline_file(
+    file: FileToOutline,
+    project: Entity<Project>,
+    mut cx: AsyncApp,
+) -> FileOutline {
+    let abs_path = project.read_with(&cx, |project, cx| {
+        project.absolute_path(&file.project_path, cx)
+    });
+    let symbols = buffer_symbols(&file, &project, &mut cx)
+        .await
+        .unwrap_or_else(|error| Err(format!("Could not open the file: {error}")));
+    FileOutline {
+        path: file.path,
+        abs_path,
+        symbols,
+    }
+}
+
+async fn buffer_symbols(
+    file: &FileToOutline,
+    project: &Entity<Project>,
+    cx: &mut AsyncApp,
+) -> Result<Result<Vec<Symbol>, String>> {
+    let buffer = project
+        .update(cx, |project, cx| {
+            project.open_buffer(file.project_path.clone(), cx)
+        })
+        .await?;
+    buffer
+        .read_with(cx, |buffer, _| buffer.parsing_idle())
+        .await;
+    let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+    let is_markdown = file
+        .project_path
+        .path
+        .extension()
+        .is_some_and(|extension| matches!(extension, "md" | "markdown" | "mdx"));
+    Ok(cx
+        .background_spawn(async move { snapshot_symbols(&snapshot, is_markdown) })
+        .await)
+}
+
+/// The symbols in `snapshot`, from its language's outline, or from its headings for Markdown
+/// files in languages without one.
+fn snapshot_symbols(snapshot: &BufferSnapshot, is_markdown: bool) -> Result<Vec<Symbol>, String> {
+    let has_outline = snapshot
+        .language()
+        .and_then(|language| language.grammar())
+        .is_some_and(|grammar| grammar.outline_config.is_some());
+    if has_outline {
+        Ok(snapshot
+            .outline(None)
+            .items
+            .into_iter()
+            .map(|item| {
+                let item = item.to_point(snapshot);
+                let signature = signature(snapshot, item.source_range_for_text.start.row)
+                    .filter(|signature| *signature != item.text);
+                Symbol {
+                    depth: item.depth,
+                    text: item.text,
+                    signature,
+                    rows: (item.range.start.row, item.range.end.row),
+                }
+            })
+            .collect())
+    } else if is_markdown {
+        Ok(markdown_symbols(snapshot))
+    } else {
+        Err("Listing symbols is not supported for this file's language.".to_string())
+    }
+}
+
+/// Row `row` of the buffer, trimmed of whitespace and of the punctuation that opens a body or
+/// ends a declaration.
+fn signature(snapshot: &BufferSnapshot, row: u32) -> Option<String> {
+    let line = snapshot
+        .text_for_range(Point::new(row, 0)..Point::new(row, snapshot.line_len(row)))
+        .collect::<String>();
+    let line = line
+        .trim()
+        .trim_end_matches(['{', ',', ';', ':'])
+        .trim_end();
+    if line.is_empty() {
+        return None;
+    }
+    Some(if line.len() > MAX_SIGNATURE_LEN {
+        format!("{}…", &line[..line.floor_char_boundary(MAX_SIGNATURE_LEN)])
+    } else {
+        line.to_string()
+    })
+}
+
+/// A Markdown file's headings, each spanning the lines up to the next heading at the same level or
+/// above.
+fn markdown_symbols(snapshot: &BufferSnapshot) -> Vec<Symbol> {
+    let text = snapshot.text();
+    let headings = crate::tool_output_budget::markdown_headings(&text)
+        .map(|(offset, line)| {
+            let line = line.trim();
+            let level = line.bytes().take_while(|byte| *byte == b'#').count();
+            (snapshot.offset_to_point(offset).row, level, line)
+        })
+        .collect::<Vec<_>>();
+    let min_level = headings
+        .iter()
+        .map(|(_, level, _)| *level)
+        .min()
+        .unwrap_or(1);
+    let last_row = snapshot.max_point().row;
+    headings
+        .iter()
+        .enumerate()
+        .map(|(ix, (row, level, line))| {
+            let end_row = headings[ix + 1..]
+                .iter()
+                .find(|(_, next_level, _)| next_level <= level)
+                .map_or(last_row, |(next_row, _, _)| next_row.saturating_sub(1));
+            Symbol {
+                depth: level - min_level,
+                text: line.to_string(),
+                signature: None,
+                rows: (*row, end_row.max(*row)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServerRegistry, Templates};
+    use gpui::TestAppContext;
+    use language_model::fake_provider::FakeLanguageModel;
+    use project::FakeFs;
+    use prompt_store::ProjectContext;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use std::path::Path;
+    use util::path;
+
+    const RUST_SOURCE: &str = indoc::indoc! {"
+        pub struct Circle {
+            radius: f64,
+        }
+
+        impl Circle {
+            pub fn new(radius: f64) -> Self {
+                Self { radius }
+            }
+
+            pub fn area(&self) -> f64 {
+                std::f64::consts::PI * self.radius * self.radius
+            }
+        }
+    "};
+
+    const MARKDOWN_SOURCE: &str = indoc::indoc! {"
+        # Shapes
+
+        Some shapes.
+
+        ## Circles
+
+        ```sh
+        # not a heading
+        ```
+
+        ## Squares
+
+        # Appendix
+    "};
+
+    #[gpui::test]
+    async fn test_rust_symbols(cx: &mut TestAppContext) {
+        let (tool, thread) = setup(json!({ "circle.rs": RUST_SOURCE }), cx).await;
+
+        let output = run(&tool, "root/circle.rs", None, cx).await.unwrap();
+        assert_eq!(
+            output,
+            indoc::indoc! {"
+                # root/circle.rs
+                pub struct Circle [L1-3]
+                  radius [L2]: radius: f64
+                impl Circle [L5-13]
+                  pub fn new [L6-8]: pub fn new(radius: f64) -> Self
+                  pub fn area [L10-12]: pub fn area(&self) -> f64
+            "}
+        );
+
+        // Seeing the outline is recorded, but doesn't count as a read.
+        thread.read_with(cx, |thread, _| {
+            let file = thread
+                .working_set()
+                .get(Path::new(path!("/root/circle.rs")))
+                .unwrap();
+            assert!(file.last_outlined_at.is_some());
+            assert!(!file.was_read());
+            assert_eq!(file.mtime, None);
+        });
+    }
+
+    #[gpui::test]
+    async fn test_markdown_symbols(cx: &mut TestAppContext) {
+        let (tool, _) = setup(json!({ "shapes.md": MARKDOWN_SOURCE }), cx).await;
+
+        let output = run(&tool, "root/shapes.md", None, cx).await.unwrap();
+        assert_eq!(
+            output,
+            indoc::indoc! {"
+                # root/shapes.md
+                # Shapes [L1-12]
+                  ## Circles [L5-10]
+                  ## Squares [L11-12]
+                # Appendix [L13-14]
+            "}
+        );
+    }
+
+    #[gpui::test]
+    async fn test_directory_symbols(cx: &mut TestAppContext) {
+        let (tool, _) = setup(
+            json!({
+                "src": {
+                    "circle.rs": RUST_SOURCE,
+                    "notes.txt": "Nothing to see here.",
+                    "nested": {
+                        "empty.rs": "// No symbols.",
+                    },
+                },
+                "shapes.md": MARKDOWN_SOURCE,
+            }),
+            cx,
+        )
+        .await;
+
+        let output = run(&tool, "root", None, cx).await.unwrap();
+        let headers = output
+            .lines()
+            .filter(|line| line.starts_with("# root/"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "# root/shapes.md",
+                "# root/src/circle.rs",
+                "# root/src/nested/empty.rs",
+                "# root/src/notes.txt",
+            ]
+        );
+        assert!(
+            output.contains("# root/src/nested/empty.rs\nNo symbols found.\n"),
+            "{output}"
+        );
+        assert!(
+            output.contains(
+                "# root/src/notes.txt\nListing symbols is not supported for this file's language.\n"
+            ),
+            "{output}"
+        );
+
+        let output = run(&tool, "root", Some("**/*.rs"), cx).await.unwrap();
+        assert!(output.starts_with("# root/src/circle.rs\n"), "{output}");
+        assert!(!output.contains("shapes.md"), "{output}");
+        assert!(!output.contains("notes.txt"), "{output}");
+
+        let error = run(&tool, "root/missing", None, cx).await.unwrap_err();
+        assert!(error.contains("not in the project"), "{error}");
+    }
+
+    #[gpui::test]
+    async fn test_symbols_truncated_per_file(cx: &mut TestAppContext) {
+        let source = (0..2000)
+            .map(|ix| format!("fn function_{ix}() {{}}\n"))
+            .collect::<String>();
+        let (tool, _) = setup(json!({ "a.rs": source.clone(), "b.rs": source }), cx).await;
+
+        let output = run(&tool, "root", None, cx).await.unwrap();
+        // Both files get their share of the budget.
+        for file in ["a.rs", "b.rs"] {
+            let file_output = output
+                .split(&format!("# root/{file}\n"))
+                .nth(1)
+                .unwrap()
+                .split("\n# ")
+                .next()
+                .unwrap();
+            assert!(
+                file_output.starts_with("fn function_0 [L1]"),
+                "{file_output}"
+            );
+            assert!(
+                file_output.contains("more symbols omitted; list the symbols of this file"),
+                "{file_output}"
+            );
+        }
+    }
+
+    async fn setup(
+        tree: serde_json::Value,
+        cx: &mut TestAppContext,
+    ) -> (Arc<CodeSymbolsTool>, Entity<Thread>) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), tree).await;
+        let project = Project::test(fs, [path!("/root").as_ref()], cx).await;
+        project.read_with(cx, |project, _| {
+            project.languages().add(language::rust_lang())
+        });
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(Arc::new(FakeLanguageModel::default())),
+                cx,
+            )
+        });
+        let tool = Arc::new(CodeSymbolsTool::new(thread.downgrade(), project));
+        (tool, thread)
+    }
+
+    async fn run(
+        tool: &Arc<CodeSymbolsTool>,
+        path: &str,
+        glob: Option<&str>,
+        cx: &mut TestAppContext,
+    ) -> Result<String, String> {
+        let input = CodeSymbolsToolInput {
+            path: path.to_string(),
+            glob: glob.map(ToString::to_string),
+        };
+        cx.update(|cx| {
+            tool.clone().run(
+                ToolInput::resolved(input),
+                ToolCallEventStream::test().0,
+                cx,
+            )
+        })
+        .await
+    }
+}
@@ -306,6 +306,14 @@ impl AnyAgentTool for ContextServerTool {
         ToolKind::Other
     }
 
+    fn is_read_only(&self) -> bool {
+        self.tool
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.read_only_hint)
+            .unwrap_or(false)
+    }
+
     fn initial_title(&self, _input: serde_json::Value, _cx: &mut App) -> SharedString {
         format!("Run MCP tool `{}`", self.tool.name).into()
     }
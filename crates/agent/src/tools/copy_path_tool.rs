@@ -92,6 +92,12 @@ impl AgentTool for CopyPathTool {
                 .await
                 .map_err(|e| format!("Failed to receive tool input: {e}"))?;
             let paths = vec![input.source_path.clone(), input.destination_path.clone()];
+            cx.update(|cx| {
+                paths.iter().try_for_each(|path| {
+                    event_stream.check_worktree_scope_for_path(path, &project, cx)
+                })
+            })
+            .map_err(|e| e.to_string())?;
             let decision = cx.update(|cx| {
                 decide_permission_for_paths(Self::NAME, &paths, &AgentSettings::get_global(cx))
             });
@@ -80,6 +80,8 @@ impl AgentTool for CreateDirectoryTool {
                 .recv()
                 .await
                 .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+            cx.update(|cx| event_stream.check_worktree_scope_for_path(&input.path, &project, cx))
+                .map_err(|e| e.to_string())?;
             let decision = cx.update(|cx| {
                 decide_permission_for_path(Self::NAME, &input.path, AgentSettings::get_global(cx))
             });
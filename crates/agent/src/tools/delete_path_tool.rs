@@ -85,6 +85,8 @@ impl AgentTool for DeletePathTool {
                 .await
                 .map_err(|e| format!("Failed to receive tool input: {e}"))?;
             let path = input.path;
+            cx.update(|cx| event_stream.check_worktree_scope_for_path(&path, &project, cx))
+                .map_err(|e| e.to_string())?;
 
             let decision = cx.update(|cx| {
                 decide_permission_for_path(Self::NAME, &path, AgentSettings::get_global(cx))
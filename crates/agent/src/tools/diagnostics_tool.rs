@@ -70,6 +70,10 @@ impl AgentTool for DiagnosticsTool {
         acp::ToolKind::Read
     }
 
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
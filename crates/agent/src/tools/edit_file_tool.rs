@@ -1,12 +1,15 @@
+use super::file_encoding;
 use super::restore_file_from_disk_tool::RestoreFileFromDiskTool;
 use super::save_file_tool::SaveFileTool;
-use super::tool_permissions::authorize_file_edit;
+use super::secret_scan::{self, SecretFinding};
+use super::tool_permissions::{authorize_credential_edit, authorize_file_edit};
 use crate::{
-    AgentTool, Templates, Thread, ToolCallEventStream, ToolInput,
+    AgentTool, OutputTruncation, PendingBuffer, Templates, Thread, ToolCallEventStream, ToolInput,
     edit_agent::{EditAgent, EditAgentOutput, EditAgentOutputEvent, EditFormat},
 };
 use acp_thread::Diff;
 use agent_client_protocol::{self as acp, ToolCallLocation, ToolCallUpdateFields};
+use agent_settings::AgentSettings;
 use anyhow::{Context as _, Result};
 use cloud_llm_client::CompletionIntent;
 use collections::HashSet;
@@ -20,6 +23,7 @@ use project::lsp_store::{FormatTrigger, LspFormatTarget};
 use project::{Project, ProjectPath};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use settings::Settings as _;
 use std::path::PathBuf;
 use std::sync::Arc;
 use ui::SharedString;
@@ -104,8 +108,17 @@ pub enum EditFileToolOutput {
         old_text: Arc<String>,
         #[serde(default)]
         diff: String,
+        /// Set when `diff` was cut short to fit the thread's tool output budget.
+        #[serde(default)]
+        diff_truncation: Option<OutputTruncation>,
         #[serde(alias = "raw_output")]
         edit_agent_output: EditAgentOutput,
+        /// Credentials that the edits added, which the user chose to keep.
+        #[serde(default)]
+        credentials: Vec<SecretFinding>,
+        /// Whether the edits were left unsaved until the user approves them.
+        #[serde(default)]
+        pending_approval: bool,
     },
     Error {
         error: String,
@@ -116,17 +129,43 @@ impl std::fmt::Display for EditFileToolOutput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             EditFileToolOutput::Success {
-                diff, input_path, ..
+                diff,
+                diff_truncation,
+                input_path,
+                credentials,
+                pending_approval,
+                ..
             } => {
-                if diff.is_empty() {
-                    write!(f, "No edits were made.")
-                } else {
+                if diff.is_empty() && diff_truncation.is_none() {
+                    return write!(f, "No edits were made.");
+                }
+                write!(
+                    f,
+                    "Edited {}:\n\n```diff\n{diff}\n```",
+                    input_path.display()
+                )?;
+                if let Some(truncation) = diff_truncation {
+                    write!(f, "\n\n{truncation}")?;
+                }
+                if !credentials.is_empty() {
                     write!(
                         f,
-                        "Edited {}:\n\n```diff\n{diff}\n```",
-                        input_path.display()
-                    )
+                        "\n\nThe user kept these edits even though they appear to add credentials. \
+                        Don't repeat them in your responses or copy them into other files:"
+                    )?;
+                    for credential in credentials {
+                        write!(f, "\n- {credential}")?;
+                    }
                 }
+                if *pending_approval {
+                    write!(
+                        f,
+                        "\n\nThese edits were applied in the editor but NOT saved: the user reviews edits \
+                        before they're written to disk, and may approve or reject them. Until then, \
+                        don't assume they're on disk, for example when running commands that read the file."
+                    )?;
+                }
+                Ok(())
             }
             EditFileToolOutput::Error { error } => write!(f, "{error}"),
         }
@@ -255,11 +294,14 @@ impl AgentTool for EditFileTool {
 
             let (project_path, abs_path, allow_thinking, authorize) =
                 cx.update(|cx| {
-                    let project_path = resolve_path(&input, project.clone(), cx).map_err(|err| {
-                        EditFileToolOutput::Error {
+                    let project_path = resolve_path(&input, project.clone(), cx)
+                        .and_then(|project_path| {
+                            event_stream.check_worktree_scope(&project_path, &input.path)?;
+                            Ok(project_path)
+                        })
+                        .map_err(|err| EditFileToolOutput::Error {
                             error: err.to_string(),
-                        }
-                    })?;
+                        })?;
                     let abs_path = project.read(cx).absolute_path(&project_path, cx);
                     if let Some(abs_path) = abs_path.clone() {
                         event_stream.update_fields(
@@ -300,13 +342,34 @@ impl AgentTool for EditFileTool {
                         project.open_buffer(project_path.clone(), cx)
                     })
                     .await?;
+                if let Some(error) = buffer.read_with(cx, |buffer, _| {
+                    file_encoding::unsupported_encoding_error(
+                        buffer,
+                        &input.path.display().to_string(),
+                    )
+                }) {
+                    anyhow::bail!("{error}");
+                }
+                if matches!(input.mode, EditFileMode::Create) {
+                    buffer.update(cx, |buffer, cx| {
+                        file_encoding::prepare_new_file(buffer, &project_path, cx)
+                    });
+                }
+
+                let (was_pending, hold_for_approval) = self.thread.read_with(cx, |thread, cx| {
+                    let was_pending = thread.pending_approval().contains(&buffer);
+                    let hold_for_approval = abs_path.is_some()
+                        && (was_pending || AgentSettings::get_global(cx).edits_require_approval);
+                    (was_pending, hold_for_approval)
+                })?;
 
                 // Check if the file has been modified since the agent last read it
                 if let Some(abs_path) = abs_path.as_ref() {
                     let (last_read_mtime, current_mtime, is_dirty, has_save_tool, has_restore_tool) = self.thread.update(cx, |thread, cx| {
-                        let last_read = thread.file_read_times.get(abs_path).copied();
+                        let last_read = thread.working_set().last_known_mtime(abs_path);
                         let current = buffer.read(cx).file().and_then(|file| file.disk_state().mtime());
-                        let dirty = buffer.read(cx).is_dirty();
+                        // Edits waiting for approval are the agent's own unsaved changes.
+                        let dirty = buffer.read(cx).is_dirty() && !was_pending;
                         let has_save = thread.has_tool(SaveFileTool::NAME);
                         let has_restore = thread.has_tool(RestoreFileFromDiskTool::NAME);
                         (last_read, current, dirty, has_save, has_restore)
@@ -434,47 +497,138 @@ impl AgentTool for EditFileTool {
 
                 let edit_agent_output = output.await?;
 
-                let format_on_save_enabled = buffer.read_with(cx, |buffer, cx| {
-                    let settings = language_settings::language_settings(
-                        buffer.language().map(|l| l.name()),
-                        buffer.file(),
-                        cx,
-                    );
-                    settings.format_on_save != FormatOnSave::Off
+                let secret_scan = cx.update(|cx| {
+                    let settings = &AgentSettings::get_global(cx).edit_secret_scan;
+                    secret_scan::should_scan(&project_path.path, settings)
+                        .then(|| settings.clone())
                 });
+                let credentials = match secret_scan {
+                    Some(settings) => {
+                        let snapshot = buffer.read_with(cx, |buffer, _cx| buffer.text_snapshot());
+                        let old_text = old_text.clone();
+                        cx.background_spawn(async move {
+                            secret_scan::introduced_secrets(
+                                &old_text,
+                                &snapshot.text(),
+                                &settings,
+                            )
+                        })
+                        .await
+                    }
+                    None => Vec::new(),
+                };
+                if !credentials.is_empty() {
+                    let authorize = cx.update(|cx| {
+                        authorize_credential_edit(
+                            Self::NAME,
+                            &input.path,
+                            &credentials,
+                            &event_stream,
+                            cx,
+                        )
+                    });
+                    if authorize.await.is_err() {
+                        buffer.update(cx, |buffer, cx| {
+                            let edits = buffer
+                                .edits_since::<usize>(old_snapshot.version())
+                                .map(|edit| {
+                                    let old_text =
+                                        old_snapshot.text_for_range(edit.old).collect::<String>();
+                                    (edit.new, old_text)
+                                })
+                                .collect::<Vec<_>>();
+                            buffer.edit(edits, None, cx);
+                        });
+                        // Unless it has edits waiting for approval, the buffer had no unsaved
+                        // changes before the edits, so reloading it clears its dirty state without
+                        // touching the file.
+                        if !was_pending {
+                            project
+                                .update(cx, |project, cx| {
+                                    project.reload_buffers(
+                                        HashSet::from_iter([buffer.clone()]),
+                                        false,
+                                        cx,
+                                    )
+                                })
+                                .await
+                                .log_err();
+                        }
+                        let credentials = credentials
+                            .iter()
+                            .map(|credential| format!("- {credential}"))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        anyhow::bail!(
+                            "The edits to {} were reverted because they appear to add credentials, \
+                            and the user didn't approve them:\n{credentials}\n\n\
+                            The file is unchanged. Don't write credentials into files; read them \
+                            from the environment or ask the user how they want to provide them.",
+                            input.path.display()
+                        );
+                    }
+                }
 
-                if format_on_save_enabled {
+                if hold_for_approval && let Some(abs_path) = abs_path.as_ref() {
                     action_log.update(cx, |log, cx| {
                         log.buffer_edited(buffer.clone(), cx);
                     });
-
-                    let format_task = project.update(cx, |project, cx| {
-                        project.format(
-                            HashSet::from_iter([buffer.clone()]),
-                            LspFormatTarget::Buffers,
-                            false, // Don't push to history since the tool did it.
-                            FormatTrigger::Save,
+                    let original_mtime = buffer.read_with(cx, |buffer, _| {
+                        buffer.file().and_then(|file| file.disk_state().mtime())
+                    });
+                    self.thread.update(cx, |thread, cx| {
+                        thread.hold_for_approval(
+                            PendingBuffer {
+                                buffer: buffer.clone(),
+                                abs_path: abs_path.clone(),
+                                original_text: old_text.as_str().into(),
+                                original_mtime,
+                            },
                             cx,
-                        )
+                        );
+                    })?;
+                } else {
+                    let format_on_save_enabled = buffer.read_with(cx, |buffer, cx| {
+                        let settings = language_settings::language_settings(
+                            buffer.language().map(|l| l.name()),
+                            buffer.file(),
+                            cx,
+                        );
+                        settings.format_on_save != FormatOnSave::Off
                     });
-                    format_task.await.log_err();
-                }
 
-                project
-                    .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))
-                    .await?;
+                    if format_on_save_enabled {
+                        action_log.update(cx, |log, cx| {
+                            log.buffer_edited(buffer.clone(), cx);
+                        });
+
+                        let format_task = project.update(cx, |project, cx| {
+                            project.format(
+                                HashSet::from_iter([buffer.clone()]),
+                                LspFormatTarget::Buffers,
+                                false, // Don't push to history since the tool did it.
+                                FormatTrigger::Save,
+                                cx,
+                            )
+                        });
+                        format_task.await.log_err();
+                    }
 
-                action_log.update(cx, |log, cx| {
-                    log.buffer_edited(buffer.clone(), cx);
-                });
+                    project
+                        .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))
+                        .await?;
 
-                // Update the recorded read time after a successful edit so consecutive edits work
-                if let Some(abs_path) = abs_path.as_ref() {
-                    if let Some(new_mtime) = buffer.read_with(cx, |buffer, _| {
-                        buffer.file().and_then(|file| file.disk_state().mtime())
-                    }) {
-                        self.thread.update(cx, |thread, _| {
-                            thread.file_read_times.insert(abs_path.to_path_buf(), new_mtime);
+                    action_log.update(cx, |log, cx| {
+                        log.buffer_edited(buffer.clone(), cx);
+                    });
+
+                    // Update the recorded mtime after a successful edit so consecutive edits work
+                    if let Some(abs_path) = abs_path.as_ref() {
+                        let new_mtime = buffer.read_with(cx, |buffer, _| {
+                            buffer.file().and_then(|file| file.disk_state().mtime())
+                        });
+                        self.thread.update(cx, |thread, cx| {
+                            thread.record_file_edit(abs_path.to_path_buf(), new_mtime, cx);
                         })?;
                     }
                 }
@@ -519,12 +673,16 @@ impl AgentTool for EditFileTool {
                     );
                 }
 
+                let diff = event_stream.output_budget().truncate_diff(unified_diff);
                 anyhow::Ok(EditFileToolOutput::Success {
                     input_path: input.path,
                     new_text,
                     old_text,
-                    diff: unified_diff,
+                    diff: diff.text,
+                    diff_truncation: diff.truncation,
                     edit_agent_output,
+                    credentials,
+                    pending_approval: hold_for_approval,
                 })
             }.await;
             result
@@ -549,7 +707,7 @@ impl AgentTool for EditFileTool {
                 event_stream.update_diff(cx.new(|cx| {
                     Diff::finalized(
                         input_path.to_string_lossy().into_owned(),
-                        Some(old_text.to_string()),
+                        Some(Arc::from(old_text.as_str())),
                         new_text,
                         self.language_registry.clone(),
                         cx,
@@ -633,7 +791,7 @@ fn resolve_path(
 mod tests {
     use super::*;
     use crate::tools::tool_permissions::{SensitiveSettingsKind, sensitive_settings_kind};
-    use crate::{ContextServerRegistry, Templates};
+    use crate::{ContextServerRegistry, EditGrantScope, Templates, WorktreeScope};
     use fs::Fs as _;
     use gpui::{TestAppContext, UpdateGlobal};
     use language_model::fake_provider::FakeLanguageModel;
@@ -1458,6 +1616,10 @@ mod tests {
             title.contains("points outside the project"),
             "title should mention symlink escape, got: {title}"
         );
+        // Only the file can be allowed for the rest of the thread, not the directory it's in.
+        let option_ids = auth.option_ids();
+        assert!(option_ids.contains(&EditGrantScope::File.option_id()));
+        assert!(!option_ids.contains(&EditGrantScope::Directory.option_id()));
     }
 
     #[gpui::test]
@@ -1690,6 +1852,192 @@ mod tests {
         }
     }
 
+    #[gpui::test]
+    async fn test_file_grant_skips_later_prompts(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_tree(path!("/outside"), json!({ "a.txt": "", "b.txt": "" }))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let (_thread, tool) = edit_file_tool(&project, cx);
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let auth = authorize_edit(&tool, path!("/outside/a.txt"), &stream_tx, cx);
+        let event = stream_rx.expect_authorization().await;
+        let option_ids = event.option_ids();
+        assert!(option_ids.contains(&EditGrantScope::File.option_id()));
+        assert!(option_ids.contains(&EditGrantScope::Directory.option_id()));
+        event
+            .response
+            .send(EditGrantScope::File.option_id())
+            .unwrap();
+        auth.await.unwrap();
+
+        // Editing the same file again doesn't ask.
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        authorize_edit(&tool, path!("/outside/a.txt"), &stream_tx, cx)
+            .await
+            .unwrap();
+        assert!(stream_rx.try_next().is_err());
+
+        // Other files in the same directory still do.
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let _auth = authorize_edit(&tool, path!("/outside/b.txt"), &stream_tx, cx);
+        stream_rx.expect_authorization().await;
+    }
+
+    #[gpui::test]
+    async fn test_directory_grant_covers_other_files(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_tree(
+            path!("/outside"),
+            json!({
+                "config": { "a.txt": "", "b.txt": "", "nested": { "c.txt": "" } },
+                "d.txt": "",
+            }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let (_thread, tool) = edit_file_tool(&project, cx);
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let auth = authorize_edit(&tool, path!("/outside/config/a.txt"), &stream_tx, cx);
+        stream_rx
+            .expect_authorization()
+            .await
+            .response
+            .send(EditGrantScope::Directory.option_id())
+            .unwrap();
+        auth.await.unwrap();
+
+        for path in [
+            path!("/outside/config/b.txt"),
+            path!("/outside/config/nested/c.txt"),
+        ] {
+            let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+            authorize_edit(&tool, path, &stream_tx, cx).await.unwrap();
+            assert!(stream_rx.try_next().is_err(), "{path} should be allowed");
+        }
+
+        // The grant doesn't extend to the parent directory.
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let _auth = authorize_edit(&tool, path!("/outside/d.txt"), &stream_tx, cx);
+        stream_rx.expect_authorization().await;
+    }
+
+    #[gpui::test]
+    async fn test_edit_grants_do_not_override_deny_rules(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_tree(path!("/outside"), json!({ "a.txt": "" }))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let (_thread, tool) = edit_file_tool(&project, cx);
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let auth = authorize_edit(&tool, path!("/outside/a.txt"), &stream_tx, cx);
+        stream_rx
+            .expect_authorization()
+            .await
+            .response
+            .send(EditGrantScope::Directory.option_id())
+            .unwrap();
+        auth.await.unwrap();
+
+        cx.update(|cx| {
+            let mut settings = agent_settings::AgentSettings::get_global(cx).clone();
+            settings.tool_permissions.tools.insert(
+                "edit_file".into(),
+                agent_settings::ToolRules {
+                    default: Some(settings::ToolPermissionMode::Deny),
+                    ..Default::default()
+                },
+            );
+            agent_settings::AgentSettings::override_global(settings, cx);
+        });
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let result = authorize_edit(&tool, path!("/outside/a.txt"), &stream_tx, cx).await;
+        assert!(result.is_err(), "deny rules should win over edit grants");
+        assert!(stream_rx.try_next().is_err());
+    }
+
+    #[gpui::test]
+    async fn test_edit_grants_are_scoped_to_their_thread(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_tree(path!("/outside"), json!({ "a.txt": "" }))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let (_thread, tool) = edit_file_tool(&project, cx);
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let auth = authorize_edit(&tool, path!("/outside/a.txt"), &stream_tx, cx);
+        stream_rx
+            .expect_authorization()
+            .await
+            .response
+            .send(EditGrantScope::Directory.option_id())
+            .unwrap();
+        auth.await.unwrap();
+
+        let (_other_thread, other_tool) = edit_file_tool(&project, cx);
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let _auth = authorize_edit(&other_tool, path!("/outside/a.txt"), &stream_tx, cx);
+        stream_rx.expect_authorization().await;
+    }
+
+    fn edit_file_tool(
+        project: &Entity<Project>,
+        cx: &mut TestAppContext,
+    ) -> (Entity<Thread>, Arc<EditFileTool>) {
+        let language_registry = project.read_with(cx, |project, _| project.languages().clone());
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let model = Arc::new(FakeLanguageModel::default());
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        let tool = Arc::new(EditFileTool::new(
+            project.clone(),
+            thread.downgrade(),
+            language_registry,
+            Templates::new(),
+        ));
+        (thread, tool)
+    }
+
+    fn authorize_edit(
+        tool: &Arc<EditFileTool>,
+        path: &str,
+        event_stream: &ToolCallEventStream,
+        cx: &mut TestAppContext,
+    ) -> Task<Result<()>> {
+        cx.update(|cx| {
+            tool.authorize(
+                &EditFileToolInput {
+                    display_description: "Edit file".into(),
+                    path: path.into(),
+                    mode: EditFileMode::Edit,
+                },
+                event_stream,
+                cx,
+            )
+        })
+    }
+
     #[gpui::test]
     async fn test_needs_confirmation_with_multiple_worktrees(cx: &mut TestAppContext) {
         init_test(cx);
@@ -1801,6 +2149,85 @@ mod tests {
         }
     }
 
+    #[gpui::test]
+    async fn test_edit_outside_worktree_scope(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree(
+            "/workspace/frontend",
+            json!({"src": {"main.js": "console.log('frontend');"}}),
+        )
+        .await;
+        fs.insert_tree(
+            "/workspace/backend",
+            json!({"src": {"main.rs": "fn main() {}"}}),
+        )
+        .await;
+        let project = Project::test(
+            fs.clone(),
+            [
+                path!("/workspace/frontend").as_ref(),
+                path!("/workspace/backend").as_ref(),
+            ],
+            cx,
+        )
+        .await;
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let language_registry = project.read_with(cx, |project, _cx| project.languages().clone());
+        let model = Arc::new(FakeLanguageModel::default());
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        let tool = Arc::new(EditFileTool::new(
+            project.clone(),
+            thread.downgrade(),
+            language_registry,
+            Templates::new(),
+        ));
+        let scope = project.read_with(cx, |project, cx| {
+            let frontend = project
+                .worktrees(cx)
+                .find(|worktree| worktree.read(cx).root_name_str() == "frontend")
+                .unwrap();
+            WorktreeScope::new(&[frontend.read(cx).id()], project, cx).unwrap()
+        });
+
+        let (stream_tx, mut stream_rx) = ToolCallEventStream::test();
+        let result = cx
+            .update(|cx| {
+                tool.clone().run(
+                    ToolInput::resolved(EditFileToolInput {
+                        display_description: "Edit the backend".into(),
+                        path: "backend/src/main.rs".into(),
+                        mode: EditFileMode::Edit,
+                    }),
+                    stream_tx.with_worktree_scope(scope),
+                    cx,
+                )
+            })
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "backend/src/main.rs is outside the agent's allowed scope (frontend)"
+        );
+        // The edit is turned away before the user is asked to allow it.
+        while let Ok(Some(event)) = stream_rx.try_next() {
+            assert!(
+                !matches!(event, Ok(crate::ThreadEvent::ToolCallAuthorization(_))),
+                "an edit outside the scope shouldn't ask for authorization"
+            );
+        }
+    }
+
     #[gpui::test]
     async fn test_needs_confirmation_edge_cases(cx: &mut TestAppContext) {
         init_test(cx);
@@ -2183,7 +2610,7 @@ mod tests {
     }
 
     #[gpui::test]
-    async fn test_file_read_times_tracking(cx: &mut TestAppContext) {
+    async fn test_working_set_read_tracking(cx: &mut TestAppContext) {
         init_test(cx);
 
         let fs = project::FakeFs::new(cx.executor());
@@ -2210,9 +2637,9 @@ mod tests {
         });
         let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
 
-        // Initially, file_read_times should be empty
-        let is_empty = thread.read_with(cx, |thread, _| thread.file_read_times.is_empty());
-        assert!(is_empty, "file_read_times should start empty");
+        // Initially, the working set should be empty
+        let is_empty = thread.read_with(cx, |thread, _| thread.working_set().is_empty());
+        assert!(is_empty, "working set should start empty");
 
         // Create read tool
         let read_tool = Arc::new(crate::ReadFileTool::new(
@@ -2236,17 +2663,17 @@ mod tests {
         .await
         .unwrap();
 
-        // Verify that file_read_times now contains an entry for the file
+        // Verify that the working set now contains an entry for the file
         let has_entry = thread.read_with(cx, |thread, _| {
-            thread.file_read_times.len() == 1
+            thread.working_set().len() == 1
                 && thread
-                    .file_read_times
-                    .keys()
-                    .any(|path| path.ends_with("test.txt"))
+                    .working_set()
+                    .iter()
+                    .any(|(path, file)| path.ends_with("test.txt") && file.mtime.is_some())
         });
         assert!(
             has_entry,
-            "file_read_times should contain an entry after reading the file"
+            "working set should contain an entry after reading the file"
         );
 
         // Read the file again - should update the entry
@@ -2265,10 +2692,10 @@ mod tests {
         .unwrap();
 
         // Should still have exactly one entry
-        let has_one_entry = thread.read_with(cx, |thread, _| thread.file_read_times.len() == 1);
+        let has_one_entry = thread.read_with(cx, |thread, _| thread.working_set().len() == 1);
         assert!(
             has_one_entry,
-            "file_read_times should still have one entry after re-reading"
+            "working set should still have one entry after re-reading"
         );
     }
 
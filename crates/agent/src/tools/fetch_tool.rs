@@ -1,18 +1,22 @@
+use std::net::IpAddr;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::{borrow::Cow, cell::RefCell};
 
 use agent_client_protocol as acp;
-use agent_settings::AgentSettings;
-use anyhow::{Context as _, Result, bail};
+use agent_settings::{AgentSettings, FetchSettings};
+use anyhow::{Context as _, Result, anyhow, bail};
+use collections::HashSet;
 use futures::{AsyncReadExt as _, FutureExt as _};
 use gpui::{App, AppContext as _, Task};
 use html_to_markdown::{TagHandler, convert_html_to_markdown, markdown};
 use http_client::{AsyncBody, HttpClientWithUrl};
+use parking_lot::Mutex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::Settings;
 use ui::SharedString;
+use url::{Host, Url};
 use util::markdown::{MarkdownEscaped, MarkdownInlineCode};
 
 use crate::{
@@ -20,6 +24,12 @@ use crate::{
     decide_permission_from_settings,
 };
 
+/// How many redirects are followed before the fetch fails.
+const MAX_REDIRECTS: usize = 5;
+/// How much of a response body is read. The output budget is applied after converting it to
+/// Markdown, so this only keeps huge downloads from being read in full.
+const MAX_BODY_BYTES: u64 = 8 * 1024 * 1024;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum ContentType {
     Html,
@@ -27,7 +37,11 @@ enum ContentType {
     Json,
 }
 
-/// Fetches a URL and returns the content as Markdown.
+/// Fetches a URL and returns the content as Markdown, preceded by the URL it was fetched from
+/// after any redirects and its content type.
+///
+/// Only http and https URLs can be fetched. Long pages are cut short, keeping the start of the
+/// page and the headings of the rest.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct FetchToolInput {
     /// The URL to fetch.
@@ -36,25 +50,68 @@ pub struct FetchToolInput {
 
 pub struct FetchTool {
     http_client: Arc<HttpClientWithUrl>,
+    /// The domains the user has allowed fetching from in this thread, so that they're only
+    /// asked about each domain once.
+    approved_domains: Arc<Mutex<HashSet<String>>>,
+}
+
+/// A fetched page, converted to Markdown.
+struct FetchedPage {
+    url: Url,
+    content_type: String,
+    text: String,
 }
 
 impl FetchTool {
     pub fn new(http_client: Arc<HttpClientWithUrl>) -> Self {
-        Self { http_client }
+        Self {
+            http_client,
+            approved_domains: Default::default(),
+        }
     }
 
-    async fn build_message(http_client: Arc<HttpClientWithUrl>, url: &str) -> Result<String> {
-        let url = if !url.starts_with("https://") && !url.starts_with("http://") {
-            Cow::Owned(format!("https://{url}"))
-        } else {
+    fn parse_url(url: &str) -> Result<Url> {
+        let url = if url.contains("://") {
             Cow::Borrowed(url)
+        } else {
+            Cow::Owned(format!("https://{url}"))
         };
+        Url::parse(&url).with_context(|| format!("invalid URL {url:?}"))
+    }
 
-        let mut response = http_client.get(&url, AsyncBody::default(), true).await?;
+    async fn fetch(
+        http_client: Arc<HttpClientWithUrl>,
+        mut url: Url,
+        settings: &FetchSettings,
+    ) -> Result<FetchedPage> {
+        let mut redirects = 0;
+        let mut response = loop {
+            check_url(&url, settings)?;
+            check_resolved_addresses(&url, settings).await?;
+
+            let response = http_client
+                .get(url.as_str(), AsyncBody::default(), false)
+                .await?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            let Some(location) = response.headers().get("location") else {
+                break response;
+            };
+            let location = location.to_str().context("invalid Location header")?;
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                bail!("too many redirects, the last one was to {location}");
+            }
+            url = url
+                .join(location)
+                .with_context(|| format!("invalid redirect to {location:?}"))?;
+        };
 
         let mut body = Vec::new();
         response
             .body_mut()
+            .take(MAX_BODY_BYTES)
             .read_to_end(&mut body)
             .await
             .context("error reading response body")?;
@@ -72,17 +129,10 @@ impl FetchTool {
         };
         let content_type = content_type
             .to_str()
-            .context("invalid Content-Type header")?;
+            .context("invalid Content-Type header")?
+            .to_string();
 
-        let content_type = if content_type.starts_with("text/plain") {
-            ContentType::Plaintext
-        } else if content_type.starts_with("application/json") {
-            ContentType::Json
-        } else {
-            ContentType::Html
-        };
-
-        match content_type {
+        let text = match Self::content_type(&content_type) {
             ContentType::Html => {
                 let mut handlers: Vec<TagHandler> = vec![
                     Rc::new(RefCell::new(markdown::WebpageChromeRemover)),
@@ -92,7 +142,10 @@ impl FetchTool {
                     Rc::new(RefCell::new(markdown::TableHandler::new())),
                     Rc::new(RefCell::new(markdown::StyledTextHandler)),
                 ];
-                if url.contains("wikipedia.org") {
+                if url
+                    .host_str()
+                    .is_some_and(|host| host == "wikipedia.org" || host.ends_with(".wikipedia.org"))
+                {
                     use html_to_markdown::structure::wikipedia;
 
                     handlers.push(Rc::new(RefCell::new(wikipedia::WikipediaChromeRemover)));
@@ -104,18 +157,133 @@ impl FetchTool {
                     handlers.push(Rc::new(RefCell::new(markdown::CodeHandler)));
                 }
 
-                convert_html_to_markdown(&body[..], &mut handlers)
+                convert_html_to_markdown(&body[..], &mut handlers)?
             }
-            ContentType::Plaintext => Ok(std::str::from_utf8(&body)?.to_owned()),
+            // The body may have been cut off in the middle of a character.
+            ContentType::Plaintext => String::from_utf8_lossy(&body).into_owned(),
             ContentType::Json => {
                 let json: serde_json::Value = serde_json::from_slice(&body)?;
 
-                Ok(format!(
-                    "```json\n{}\n```",
-                    serde_json::to_string_pretty(&json)?
-                ))
+                format!("```json\n{}\n```", serde_json::to_string_pretty(&json)?)
             }
+        };
+
+        Ok(FetchedPage {
+            url,
+            content_type,
+            text,
+        })
+    }
+
+    fn content_type(content_type: &str) -> ContentType {
+        if ["text/plain", "text/markdown", "text/x-markdown"]
+            .iter()
+            .any(|plaintext| content_type.starts_with(plaintext))
+        {
+            ContentType::Plaintext
+        } else if content_type.starts_with("application/json") {
+            ContentType::Json
+        } else {
+            ContentType::Html
+        }
+    }
+}
+
+/// Fails when the fetch settings don't allow fetching `url`. Checked before asking to fetch it,
+/// and again for every redirect.
+fn check_url(url: &Url, settings: &FetchSettings) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!(
+            "only http and https URLs can be fetched, not {} URLs",
+            url.scheme()
+        );
+    }
+    let (Some(host), Some(host_str)) = (url.host(), url.host_str()) else {
+        bail!("{url} has no host");
+    };
+
+    if settings
+        .denied_domains
+        .iter()
+        .any(|domain| is_in_domain(host_str, domain))
+    {
+        bail!("{host_str} is in the agent's `fetch.denied_domains` setting");
+    }
+    if !settings.allowed_domains.is_empty()
+        && !settings
+            .allowed_domains
+            .iter()
+            .any(|domain| is_in_domain(host_str, domain))
+    {
+        bail!("{host_str} isn't in the agent's `fetch.allowed_domains` setting");
+    }
+
+    let is_private = match host {
+        Host::Domain(domain) => domain == "localhost" || domain.ends_with(".localhost"),
+        Host::Ipv4(ip) => is_private_address(IpAddr::V4(ip)),
+        Host::Ipv6(ip) => is_private_address(IpAddr::V6(ip)),
+    };
+    if is_private && !settings.allow_private_network {
+        return Err(private_network_error(host_str));
+    }
+    Ok(())
+}
+
+/// Fails when the domain of `url` resolves to a private network address, which would get
+/// around [`check_url`]. Domains that don't resolve are left for the HTTP client to report,
+/// since behind a proxy they may only resolve on the other side of it.
+async fn check_resolved_addresses(url: &Url, settings: &FetchSettings) -> Result<()> {
+    if settings.allow_private_network {
+        return Ok(());
+    }
+    let (Some(Host::Domain(domain)), Some(port)) = (url.host(), url.port_or_known_default()) else {
+        return Ok(());
+    };
+    let Ok(addresses) = smol::net::resolve((domain, port)).await else {
+        return Ok(());
+    };
+    if addresses
+        .iter()
+        .any(|address| is_private_address(address.ip()))
+    {
+        return Err(private_network_error(domain));
+    }
+    Ok(())
+}
+
+fn private_network_error(host: &str) -> anyhow::Error {
+    anyhow!(
+        "{host} is a loopback or private network address, and the agent's \
+        `fetch.allow_private_network` setting is off"
+    )
+}
+
+fn is_in_domain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|subdomain| subdomain.is_empty() || subdomain.ends_with('.'))
+}
+
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // The shared address space used for carrier-grade NAT.
+                || (first == 100 && second & 0xc0 == 64)
         }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_address(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
     }
 }
 
@@ -129,6 +297,14 @@ impl AgentTool for FetchTool {
         acp::ToolKind::Fetch
     }
 
+    fn preview_fields() -> &'static [&'static str] {
+        &["url"]
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -147,25 +323,37 @@ impl AgentTool for FetchTool {
         cx: &mut App,
     ) -> Task<Result<Self::Output, Self::Output>> {
         let http_client = self.http_client.clone();
+        let approved_domains = self.approved_domains.clone();
         cx.spawn(async move |cx| {
             let input: FetchToolInput = input
                 .recv()
                 .await
                 .map_err(|e| format!("Failed to receive tool input: {e}"))?;
 
-            let decision = cx.update(|cx| {
-                decide_permission_from_settings(
-                    Self::NAME,
-                    std::slice::from_ref(&input.url),
-                    AgentSettings::get_global(cx),
+            let (decision, settings) = cx.update(|cx| {
+                let settings = AgentSettings::get_global(cx);
+                (
+                    decide_permission_from_settings(
+                        Self::NAME,
+                        std::slice::from_ref(&input.url),
+                        settings,
+                    ),
+                    settings.fetch.clone(),
                 )
             });
 
+            let url = Self::parse_url(&input.url).map_err(|e| e.to_string())?;
+            check_url(&url, &settings).map_err(|e| e.to_string())?;
+            let domain = url.host_str().unwrap_or_default().to_string();
+
             let authorize = match decision {
                 ToolPermissionDecision::Allow => None,
                 ToolPermissionDecision::Deny(reason) => {
                     return Err(reason);
                 }
+                ToolPermissionDecision::Confirm if approved_domains.lock().contains(&domain) => {
+                    None
+                }
                 ToolPermissionDecision::Confirm => Some(cx.update(|cx| {
                     let context =
                         crate::ToolPermissionContext::new(Self::NAME, vec![input.url.clone()]);
@@ -179,25 +367,302 @@ impl AgentTool for FetchTool {
 
             let fetch_task = cx.background_spawn({
                 let http_client = http_client.clone();
-                let url = input.url.clone();
                 async move {
                     if let Some(authorize) = authorize {
                         authorize.await?;
+                        approved_domains.lock().insert(domain);
                     }
-                    Self::build_message(http_client, &url).await
+                    Self::fetch(http_client, url, &settings).await
                 }
             });
 
-            let text = futures::select! {
+            let page = futures::select! {
                 result = fetch_task.fuse() => result.map_err(|e| e.to_string())?,
                 _ = event_stream.cancelled_by_user().fuse() => {
                     return Err("Fetch cancelled by user".to_string());
                 }
             };
-            if text.trim().is_empty() {
+            if page.text.trim().is_empty() {
                 return Err("no textual content found".to_string());
             }
-            Ok(text)
+            let text = event_stream
+                .output_budget()
+                .truncate_markdown(page.text)
+                .into_text_with_notice();
+            Ok(format!(
+                "URL: {}\nContent-Type: {}\n\n{text}",
+                page.url, page.content_type
+            ))
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToolOutputBudget;
+    use gpui::TestAppContext;
+    use gpui::http_client::{FakeHttpClient, Response};
+    use settings::{SettingsStore, ToolPermissionMode};
+
+    #[gpui::test]
+    async fn test_fetch_follows_redirects(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Allow);
+        allow_private_network(cx);
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create(
+            |request| async move {
+                match request.uri().path() {
+                    "/old" => redirect("/new"),
+                    "/new" => redirect("http://127.0.0.1:8080/docs/page"),
+                    "/docs/page" => page("text/plain; charset=utf-8", "Hello"),
+                    _ => redirect("/loop"),
+                }
+            },
+        )));
+
+        assert_eq!(
+            fetch(&tool, "http://127.0.0.1:8080/old", cx).await,
+            Ok("URL: http://127.0.0.1:8080/docs/page\n\
+                Content-Type: text/plain; charset=utf-8\n\nHello"
+                .to_string())
+        );
+
+        let error = fetch(&tool, "http://127.0.0.1:8080/loop", cx)
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("too many redirects"), "{error}");
+    }
+
+    #[gpui::test]
+    async fn test_fetch_truncates_large_pages(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Allow);
+        allow_private_network(cx);
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create(|_| async move {
+            let mut body = "# Guide\n".to_string();
+            for section in 1..=3 {
+                body.push_str(&format!("## Section {section}\n"));
+                body.push_str(&"Lorem ipsum dolor sit amet.\n".repeat(1000));
+            }
+            page("text/markdown", &body)
+        })));
+
+        let (event_stream, _) = ToolCallEventStream::test();
+        let event_stream = event_stream.with_output_budget(ToolOutputBudget::new(1024));
+        let input = FetchToolInput {
+            url: "http://127.0.0.1/guide.md".into(),
+        };
+        let output = cx
+            .update(|cx| tool.run(ToolInput::resolved(input), event_stream, cx))
+            .await
+            .unwrap();
+
+        let (head, rest) = output.split_once("[... ").unwrap();
+        assert!(head.starts_with("URL: http://127.0.0.1/guide.md\n"));
+        assert!(head.contains("# Guide\n## Section 1\nLorem ipsum"));
+        assert!(
+            rest.contains(
+                "omitted, leaving only these headings ...]\n## Section 2\n## Section 3\n"
+            )
+        );
+        assert!(rest.ends_with("bytes shown; refine your request"));
+    }
+
+    #[gpui::test]
+    async fn test_fetch_refuses_private_addresses(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Allow);
+        let requested_paths = Arc::new(Mutex::new(Vec::new()));
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create({
+            let requested_paths = requested_paths.clone();
+            move |request| {
+                requested_paths
+                    .lock()
+                    .push(request.uri().path().to_string());
+                async move {
+                    match request.uri().path() {
+                        "/redirect" => redirect("http://10.0.0.1/admin"),
+                        _ => page("text/plain", "Hello"),
+                    }
+                }
+            }
+        })));
+
+        for url in [
+            "http://127.0.0.1:8080/",
+            "localhost:3000",
+            "http://app.localhost/",
+            "http://192.168.1.10/",
+            "http://[::1]/",
+            "http://[::ffff:10.0.0.1]/",
+        ] {
+            let error = fetch(&tool, url, cx).await.unwrap_err();
+            assert!(error.contains("private network address"), "{url}: {error}");
+        }
+        assert_eq!(requested_paths.lock().len(), 0);
+
+        let error = fetch(&tool, "file:///etc/passwd", cx).await.unwrap_err();
+        assert_eq!(
+            error,
+            "only http and https URLs can be fetched, not file URLs"
+        );
+
+        // Redirects to private addresses are refused without following them.
+        let error = fetch(&tool, "http://93.184.216.34/redirect", cx)
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("10.0.0.1 is a loopback"), "{error}");
+        assert_eq!(*requested_paths.lock(), ["/redirect"]);
+
+        allow_private_network(cx);
+        assert!(fetch(&tool, "http://127.0.0.1:8080/", cx).await.is_ok());
+    }
+
+    #[gpui::test]
+    async fn test_fetch_domain_settings(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Allow);
+        cx.update(|cx| {
+            let mut settings = AgentSettings::get_global(cx).clone();
+            // Resolving domains is only needed to look for private network addresses.
+            settings.fetch.allow_private_network = true;
+            settings.fetch.allowed_domains = vec!["docs.rs".into()];
+            settings.fetch.denied_domains = vec!["internal.docs.rs".into()];
+            AgentSettings::override_global(settings, cx);
+        });
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create(|_| async move {
+            page("text/plain", "Hello")
+        })));
+
+        assert!(fetch(&tool, "https://docs.rs/gpui", cx).await.is_ok());
+        assert!(fetch(&tool, "https://api.docs.rs/gpui", cx).await.is_ok());
+        assert_eq!(
+            fetch(&tool, "https://notdocs.rs/gpui", cx).await,
+            Err("notdocs.rs isn't in the agent's `fetch.allowed_domains` setting".into())
+        );
+        assert_eq!(
+            fetch(&tool, "https://a.internal.docs.rs/", cx).await,
+            Err("a.internal.docs.rs is in the agent's `fetch.denied_domains` setting".into())
+        );
+    }
+
+    #[gpui::test]
+    async fn test_fetch_asks_once_per_domain(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Confirm);
+        allow_private_network(cx);
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create(|_| async move {
+            page("text/plain", "Hello")
+        })));
+
+        for (url, asks) in [
+            ("http://127.0.0.1/a", true),
+            ("http://127.0.0.1/b", false),
+            ("http://127.0.0.2/a", true),
+        ] {
+            let (event_stream, mut event_rx) = ToolCallEventStream::test();
+            let input = FetchToolInput { url: url.into() };
+            let task = cx.update(|cx| {
+                tool.clone()
+                    .run(ToolInput::resolved(input), event_stream, cx)
+            });
+            if asks {
+                event_rx
+                    .expect_authorization()
+                    .await
+                    .response
+                    .send(acp::PermissionOptionId::new("allow"))
+                    .unwrap();
+            }
+            assert!(task.await.is_ok(), "{url}");
+            assert!(
+                !matches!(
+                    event_rx.try_next(),
+                    Ok(Some(Ok(crate::ThreadEvent::ToolCallAuthorization(_))))
+                ),
+                "{url}"
+            );
+        }
+    }
+
+    #[gpui::test]
+    async fn test_fetch_converts_html(cx: &mut TestAppContext) {
+        init_test(cx, ToolPermissionMode::Allow);
+        allow_private_network(cx);
+        let tool = Arc::new(FetchTool::new(FakeHttpClient::create(|_| async move {
+            page(
+                "text/html; charset=utf-8",
+                include_str!("fixtures/fetch_page.html"),
+            )
+        })));
+
+        let output = fetch(&tool, "http://127.0.0.1/settings", cx).await.unwrap();
+        assert!(output.starts_with(
+            "URL: http://127.0.0.1/settings\nContent-Type: text/html; charset=utf-8\n\n# Settings\n"
+        ));
+        for content in [
+            "## Themes",
+            "Settings are read from `settings.json` when the app starts.",
+            "Pick a theme with the **theme** setting:",
+            "```\n{ \"theme\": \"One Dark\" }\n```",
+            "- Light themes",
+        ] {
+            assert!(output.contains(content), "missing {content:?} in {output}");
+        }
+        for chrome in [
+            "Example Docs",
+            "analytics",
+            "Search",
+            "Install",
+            "Version 2",
+            "Leftover",
+            "trackPageView",
+            "Keybindings",
+            "JavaScript",
+        ] {
+            assert!(!output.contains(chrome), "found {chrome:?} in {output}");
+        }
+    }
+
+    async fn fetch(
+        tool: &Arc<FetchTool>,
+        url: &str,
+        cx: &mut TestAppContext,
+    ) -> Result<String, String> {
+        let (event_stream, _) = ToolCallEventStream::test();
+        let input = FetchToolInput { url: url.into() };
+        cx.update(|cx| {
+            tool.clone()
+                .run(ToolInput::resolved(input), event_stream, cx)
+        })
+        .await
+    }
+
+    fn redirect(location: &str) -> Result<Response<AsyncBody>> {
+        Ok(Response::builder()
+            .status(302)
+            .header("location", location)
+            .body(AsyncBody::default())?)
+    }
+
+    fn page(content_type: &str, body: &str) -> Result<Response<AsyncBody>> {
+        Ok(Response::builder()
+            .status(200)
+            .header("content-type", content_type)
+            .body(AsyncBody::from(body.to_string()))?)
+    }
+
+    fn allow_private_network(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let mut settings = AgentSettings::get_global(cx).clone();
+            settings.fetch.allow_private_network = true;
+            AgentSettings::override_global(settings, cx);
+        });
+    }
+
+    fn init_test(cx: &mut TestAppContext, default_permission: ToolPermissionMode) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+            let mut settings = AgentSettings::get_global(cx).clone();
+            settings.tool_permissions.default = default_permission;
+            AgentSettings::override_global(settings, cx);
+        });
+    }
+}
@@ -0,0 +1,28 @@
+use gpui::App;
+use language::Buffer;
+use project::ProjectPath;
+use settings::Settings as _;
+use worktree::WorktreeSettings;
+
+/// Explains why the agent can't edit `buffer`, if its file isn't UTF-8. Edits are matched and
+/// written as UTF-8 text, so a file in a legacy encoding would fail to match in confusing ways,
+/// or be mangled when saved.
+pub fn unsupported_encoding_error(buffer: &Buffer, path: &str) -> Option<String> {
+    let encoding = buffer.encoding();
+    (encoding != encoding_rs::UTF_8).then(|| {
+        format!(
+            "{path} is encoded as {}, not UTF-8, so it can't be edited. \
+            Ask the user to convert it to UTF-8 if they want you to edit it.",
+            encoding.name()
+        )
+    })
+}
+
+/// Gives a file the agent is about to create a byte order mark if the project asks for one.
+/// Existing files keep whatever they were loaded with.
+pub fn prepare_new_file(buffer: &mut Buffer, project_path: &ProjectPath, cx: &App) {
+    if buffer.file().is_some_and(|file| file.disk_state().exists()) {
+        return;
+    }
+    buffer.set_has_bom(WorktreeSettings::get(Some(project_path.into()), cx).new_file_bom);
+}
@@ -0,0 +1,756 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, SecondsFormat};
+use git::repository::{FileHistoryEntry, RepoPath, is_binary_content};
+use gpui::{App, AsyncApp, Entity, SharedString, Task};
+use language_model::LanguageModelToolResultContent;
+use project::{Project, ProjectPath, git_store::Repository};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use util::markdown::MarkdownInlineCode;
+
+use crate::{AgentTool, OutputTruncation, ToolCallEventStream, ToolInput, ToolOutputBudget};
+
+/// The number of commits shown when neither a range nor a number of commits is given.
+const DEFAULT_COMMIT_COUNT: usize = 5;
+/// The most commits whose diffs are loaded in a single call.
+const MAX_COMMIT_COUNT: usize = 50;
+
+/// Shows how a file changed in git history. For each commit that changed it, lists the short hash, author, date, and subject, along with the diff hunks of that file.
+///
+/// Use this to understand what recently changed in a file before editing it, or to find out when and why some code changed.
+///
+/// <guidelines>
+/// - Pass either `range` or `last_n_commits`, not both. Without either, the last 5 commits that changed the file are shown.
+/// - Set `uncommitted` to also see the changes in the working tree that haven't been committed yet.
+/// - Long histories are cut off to fit the output. Narrow the range to see older changes in full.
+/// </guidelines>
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FileHistoryToolInput {
+    /// The path of the file to show the history of.
+    ///
+    /// This path should never be absolute, and the first component
+    /// of the path should always be a root directory in a project.
+    ///
+    /// <example>
+    /// If the project has the following root directories:
+    ///
+    /// - lorem
+    /// - ipsum
+    ///
+    /// If you want the history of `dolor.txt` in `ipsum`, you should use the path `ipsum/dolor.txt`.
+    /// </example>
+    pub path: String,
+    /// A git revision range to list the commits of, like `main..HEAD` or `v1.0..v1.1`.
+    #[serde(default)]
+    pub range: Option<String>,
+    /// How many of the most recent commits that changed the file to show.
+    #[serde(default)]
+    pub last_n_commits: Option<usize>,
+    /// Whether to also show the working tree's changes to the file since the last commit.
+    #[serde(default)]
+    pub uncommitted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FileHistoryToolOutput {
+    Success {
+        path: String,
+        /// The commits that changed the file, newest first.
+        commits: Vec<FileHistoryCommit>,
+        /// The working tree's changes against `HEAD`, when they were asked for.
+        #[serde(default)]
+        uncommitted_hunks: Option<Vec<String>>,
+        /// The number of commits in the range that were left out, past [`MAX_COMMIT_COUNT`].
+        #[serde(default)]
+        omitted_commits: usize,
+        /// Set when hunks were left out to fit the thread's tool output budget.
+        #[serde(default)]
+        truncation: Option<OutputTruncation>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileHistoryCommit {
+    pub sha: String,
+    pub short_sha: String,
+    pub author: String,
+    /// When the commit was made, in RFC 3339 format.
+    pub date: String,
+    pub subject: String,
+    /// The unified diff hunks for the file, each starting with its `@@` header.
+    pub hunks: Vec<String>,
+    /// Set when some of the hunks didn't fit the output budget.
+    #[serde(default)]
+    pub hunks_omitted: bool,
+}
+
+impl std::fmt::Display for FileHistoryToolOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileHistoryToolOutput::Success {
+                path,
+                commits,
+                uncommitted_hunks,
+                omitted_commits,
+                truncation,
+            } => {
+                write!(f, "# History of {}", MarkdownInlineCode(path))?;
+                if let Some(hunks) = uncommitted_hunks {
+                    write!(f, "\n\n## Uncommitted changes\n\n")?;
+                    if hunks.is_empty() {
+                        write!(f, "No uncommitted changes.")?;
+                    } else {
+                        write_hunks(f, hunks)?;
+                    }
+                }
+                if commits.is_empty() {
+                    write!(f, "\n\nNo commits changed this file.")?;
+                }
+                for commit in commits {
+                    write!(
+                        f,
+                        "\n\n## {} {}\n\nBy {} on {}\n\n",
+                        commit.short_sha, commit.subject, commit.author, commit.date
+                    )?;
+                    if !commit.hunks.is_empty() {
+                        write_hunks(f, &commit.hunks)?;
+                        if commit.hunks_omitted {
+                            write!(f, "\n\n[Later hunks omitted.]")?;
+                        }
+                    } else if commit.hunks_omitted {
+                        write!(f, "[Diff omitted.]")?;
+                    } else {
+                        write!(
+                            f,
+                            "No changes to this path. The file may have had another name in this commit."
+                        )?;
+                    }
+                }
+                if *omitted_commits > 0 {
+                    write!(
+                        f,
+                        "\n\n[{omitted_commits} older commits in the range aren't shown. Narrow the range to see them.]"
+                    )?;
+                }
+                if let Some(truncation) = truncation {
+                    write!(f, "\n\n{truncation}")?;
+                }
+                Ok(())
+            }
+            FileHistoryToolOutput::Error { error } => write!(f, "{error}"),
+        }
+    }
+}
+
+fn write_hunks(f: &mut std::fmt::Formatter<'_>, hunks: &[String]) -> std::fmt::Result {
+    let diff = hunks.concat();
+    write!(f, "```diff\n{diff}")?;
+    if !diff.ends_with('\n') {
+        writeln!(f)?;
+    }
+    write!(f, "```")
+}
+
+impl From<FileHistoryToolOutput> for LanguageModelToolResultContent {
+    fn from(output: FileHistoryToolOutput) -> Self {
+        output.to_string().into()
+    }
+}
+
+impl From<String> for FileHistoryToolOutput {
+    fn from(error: String) -> Self {
+        FileHistoryToolOutput::Error { error }
+    }
+}
+
+pub struct FileHistoryTool {
+    project: Entity<Project>,
+}
+
+impl FileHistoryTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for FileHistoryTool {
+    type Input = FileHistoryToolInput;
+    type Output = FileHistoryToolOutput;
+
+    const NAME: &'static str = "file_history";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("History of {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "File history".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output, Self::Output>> {
+        cx.spawn(async move |cx| {
+            let input = input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+            let history = load_file_history(&self.project, &input, cx)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(history.into_output(input.path, event_stream.output_budget()))
+        })
+    }
+}
+
+/// A file's history, with the full diff of each commit.
+struct FileHistoryDiffs {
+    commits: Vec<(FileHistoryEntry, Option<String>)>,
+    uncommitted_diff: Option<String>,
+    omitted_commits: usize,
+}
+
+async fn load_file_history(
+    project: &Entity<Project>,
+    input: &FileHistoryToolInput,
+    cx: &mut AsyncApp,
+) -> Result<FileHistoryDiffs> {
+    let path = &input.path;
+    if input.range.is_some() && input.last_n_commits.is_some() {
+        anyhow::bail!("Pass either `range` or `last_n_commits`, not both.");
+    }
+
+    let (project_path, exists, repository, repo_path) = project.read_with(cx, |project, cx| {
+        let project_path = project
+            .find_project_path(path, cx)
+            .ok_or_else(|| anyhow!("{path} not found"))?;
+        if project.repositories(cx).is_empty() {
+            anyhow::bail!("This project doesn't have a git repository.");
+        }
+        let (repository, repo_path) = project
+            .git_store()
+            .read(cx)
+            .repository_and_path_for_project_path(&project_path, cx)
+            .ok_or_else(|| anyhow!("{path} isn't in a git repository."))?;
+        let exists = project.entry_for_path(&project_path, cx).is_some();
+        anyhow::Ok((project_path, exists, repository, repo_path))
+    })?;
+
+    let history = match &input.range {
+        Some(range) => repository.update(cx, |repository, _| {
+            repository.file_history_in_range(repo_path.clone(), range.clone())
+        }),
+        None => {
+            let count = input.last_n_commits.unwrap_or(DEFAULT_COMMIT_COUNT);
+            repository.update(cx, |repository, _| {
+                repository.file_history_paginated(
+                    repo_path.clone(),
+                    0,
+                    Some(count.min(MAX_COMMIT_COUNT)),
+                )
+            })
+        }
+    }
+    .await??;
+    // Files that were deleted still have a history.
+    if !exists && history.entries.is_empty() {
+        anyhow::bail!("{path} not found");
+    }
+
+    let mut entries = history.entries;
+    let omitted_commits = entries.len().saturating_sub(MAX_COMMIT_COUNT);
+    entries.truncate(MAX_COMMIT_COUNT);
+
+    let mut commits = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let diff = load_commit_file_diff(&repository, &entry, &repo_path, path, cx).await?;
+        commits.push((entry, diff));
+    }
+
+    let uncommitted_diff = if input.uncommitted && exists {
+        Some(load_uncommitted_diff(project, project_path, path, cx).await?)
+    } else {
+        None
+    };
+
+    Ok(FileHistoryDiffs {
+        commits,
+        uncommitted_diff,
+        omitted_commits,
+    })
+}
+
+/// The diff of the file at `repo_path` in a commit, or `None` if the commit didn't change that
+/// path, which happens when the file was renamed since.
+async fn load_commit_file_diff(
+    repository: &Entity<Repository>,
+    entry: &FileHistoryEntry,
+    repo_path: &RepoPath,
+    path: &str,
+    cx: &mut AsyncApp,
+) -> Result<Option<String>> {
+    let commit_diff = repository
+        .update(cx, |repository, _| {
+            repository.load_commit_diff(entry.sha.to_string())
+        })
+        .await??;
+    let Some(file) = commit_diff
+        .files
+        .into_iter()
+        .find(|file| file.path == *repo_path)
+    else {
+        return Ok(None);
+    };
+    if file.is_binary {
+        anyhow::bail!("{path} is a binary file, so its history can't be shown as a diff.");
+    }
+    Ok(Some(language::unified_diff(
+        file.old_text.as_deref().unwrap_or(""),
+        file.new_text.as_deref().unwrap_or(""),
+    )))
+}
+
+/// The diff of the file's text against its text in `HEAD`.
+async fn load_uncommitted_diff(
+    project: &Entity<Project>,
+    project_path: ProjectPath,
+    path: &str,
+    cx: &mut AsyncApp,
+) -> Result<String> {
+    let buffer = project
+        .update(cx, |project, cx| project.open_buffer(project_path, cx))
+        .await?;
+    let diff = project
+        .update(cx, |project, cx| {
+            project.open_uncommitted_diff(buffer.clone(), cx)
+        })
+        .await?;
+    let (head_text, text) =
+        cx.update(|cx| (diff.read(cx).base_text_string(cx), buffer.read(cx).text()));
+    let head_text = head_text.unwrap_or_default();
+    if is_binary_content(text.as_bytes()) || is_binary_content(head_text.as_bytes()) {
+        anyhow::bail!("{path} is a binary file, so its history can't be shown as a diff.");
+    }
+    Ok(language::unified_diff(&head_text, &text))
+}
+
+impl FileHistoryDiffs {
+    /// Splits the diffs into hunks, keeping as many whole hunks as fit `budget`. The uncommitted
+    /// changes come first, then the commits from newest to oldest.
+    fn into_output(self, path: String, budget: ToolOutputBudget) -> FileHistoryToolOutput {
+        let mut remaining_bytes = budget.max_bytes();
+        let mut shown_bytes = 0;
+        let mut total_bytes = 0;
+        let mut budget_diff = |diff: String| -> (Vec<String>, bool) {
+            total_bytes += diff.len();
+            let output = ToolOutputBudget::new(remaining_bytes).truncate_diff(diff);
+            shown_bytes += output.text.len();
+            // Once a diff doesn't fit, later ones are left out too, so that what's shown is
+            // the most recent history without gaps.
+            remaining_bytes = match output.truncation {
+                Some(_) => 0,
+                None => remaining_bytes - output.text.len(),
+            };
+            (split_hunks(&output.text), output.truncation.is_some())
+        };
+
+        let uncommitted_hunks = self.uncommitted_diff.map(|diff| budget_diff(diff).0);
+        let commits = self
+            .commits
+            .into_iter()
+            .map(|(entry, diff)| {
+                let (hunks, hunks_omitted) = match diff {
+                    Some(diff) => budget_diff(diff),
+                    None => (Vec::new(), false),
+                };
+                FileHistoryCommit {
+                    short_sha: entry.sha[..git::SHORT_SHA_LENGTH.min(entry.sha.len())].to_string(),
+                    sha: entry.sha.to_string(),
+                    author: entry.author_name.to_string(),
+                    date: DateTime::from_timestamp(entry.commit_timestamp, 0)
+                        .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+                        .unwrap_or_default(),
+                    subject: entry.subject.to_string(),
+                    hunks,
+                    hunks_omitted,
+                }
+            })
+            .collect();
+
+        FileHistoryToolOutput::Success {
+            path,
+            commits,
+            uncommitted_hunks,
+            omitted_commits: self.omitted_commits,
+            truncation: (shown_bytes < total_bytes).then_some(OutputTruncation {
+                shown_bytes,
+                total_bytes,
+            }),
+        }
+    }
+}
+
+/// Splits a unified diff into its hunks, each starting with its `@@` header line.
+fn split_hunks(diff: &str) -> Vec<String> {
+    let mut hunks = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        match hunks.last_mut() {
+            Some(hunk) if !line.starts_with("@@") => hunk.push_str(line),
+            _ => hunks.push(line.to_string()),
+        }
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs::RealFs;
+    use gpui::TestAppContext;
+    use settings::SettingsStore;
+    use std::{path::Path, process::Command};
+
+    #[gpui::test]
+    async fn test_file_history_last_n_commits(cx: &mut TestAppContext) {
+        let (_dir, project) = init_fixture_repository(cx).await;
+        let tool = Arc::new(FileHistoryTool::new(project));
+
+        let output = run_tool(
+            &tool,
+            FileHistoryToolInput {
+                path: "repo/src/lib.rs".into(),
+                range: None,
+                last_n_commits: Some(2),
+                uncommitted: false,
+            },
+            ToolOutputBudget::default(),
+            cx,
+        )
+        .await
+        .unwrap();
+        let FileHistoryToolOutput::Success {
+            commits,
+            uncommitted_hunks,
+            truncation,
+            ..
+        } = &output
+        else {
+            panic!("expected success, got {output:?}");
+        };
+        assert_eq!(subjects(commits), ["Rename helper", "Add helper"]);
+        assert_eq!(commits[0].author, "Test Author");
+        assert_eq!(commits[0].short_sha.len(), git::SHORT_SHA_LENGTH);
+        assert_eq!(
+            commits[0].hunks,
+            ["@@ -1,3 +1,3 @@\n fn main() {}\n \n-fn helper() {}\n+fn renamed_helper() {}\n"]
+        );
+        // The other file changed in the same commit isn't included.
+        assert!(!output.to_string().contains("README"));
+        assert_eq!(*uncommitted_hunks, None);
+        assert_eq!(*truncation, None);
+        assert!(
+            output
+                .to_string()
+                .starts_with("# History of `repo/src/lib.rs`\n\n## ")
+        );
+    }
+
+    #[gpui::test]
+    async fn test_file_history_range(cx: &mut TestAppContext) {
+        let (_dir, project) = init_fixture_repository(cx).await;
+        let tool = Arc::new(FileHistoryTool::new(project));
+
+        let output = run_tool(
+            &tool,
+            FileHistoryToolInput {
+                path: "repo/src/lib.rs".into(),
+                range: Some("HEAD~3..HEAD~1".into()),
+                last_n_commits: None,
+                uncommitted: false,
+            },
+            ToolOutputBudget::default(),
+            cx,
+        )
+        .await
+        .unwrap();
+        let FileHistoryToolOutput::Success { commits, .. } = &output else {
+            panic!("expected success, got {output:?}");
+        };
+        // The commit that only touched the README is in the range, but isn't listed.
+        assert_eq!(subjects(commits), ["Add helper"]);
+        assert_eq!(
+            commits[0].hunks,
+            ["@@ -1,1 +1,3 @@\n fn main() {}\n+\n+fn helper() {}\n"]
+        );
+
+        let output = run_tool(
+            &tool,
+            FileHistoryToolInput {
+                path: "repo/src/lib.rs".into(),
+                range: Some("--all".into()),
+                last_n_commits: None,
+                uncommitted: false,
+            },
+            ToolOutputBudget::default(),
+            cx,
+        )
+        .await
+        .unwrap_err();
+        assert!(output.to_string().contains("invalid revision range"));
+    }
+
+    #[gpui::test]
+    async fn test_file_history_uncommitted(cx: &mut TestAppContext) {
+        let (dir, project) = init_fixture_repository(cx).await;
+        std::fs::write(
+            dir.path().join("repo/src/lib.rs"),
+            "fn main() {}\n\nfn renamed_helper() {}\n\nfn new_helper() {}\n",
+        )
+        .unwrap();
+        cx.run_until_parked();
+        let tool = Arc::new(FileHistoryTool::new(project));
+
+        let output = run_tool(
+            &tool,
+            FileHistoryToolInput {
+                path: "repo/src/lib.rs".into(),
+                range: None,
+                last_n_commits: Some(1),
+                uncommitted: true,
+            },
+            ToolOutputBudget::default(),
+            cx,
+        )
+        .await
+        .unwrap();
+        let FileHistoryToolOutput::Success {
+            commits,
+            uncommitted_hunks,
+            ..
+        } = &output
+        else {
+            panic!("expected success, got {output:?}");
+        };
+        assert_eq!(
+            uncommitted_hunks.as_deref(),
+            Some(
+                &["@@ -1,3 +1,5 @@\n fn main() {}\n \n fn renamed_helper() {}\n+\n+fn new_helper() {}\n"
+                    .to_string()][..]
+            )
+        );
+        assert_eq!(subjects(commits), ["Rename helper"]);
+        assert!(
+            output
+                .to_string()
+                .contains("## Uncommitted changes\n\n```diff\n@@")
+        );
+    }
+
+    #[gpui::test]
+    async fn test_file_history_budget_keeps_whole_hunks(cx: &mut TestAppContext) {
+        let (_dir, project) = init_fixture_repository(cx).await;
+        let tool = Arc::new(FileHistoryTool::new(project));
+        let input = || FileHistoryToolInput {
+            path: "repo/src/lib.rs".into(),
+            range: None,
+            last_n_commits: None,
+            uncommitted: false,
+        };
+
+        let FileHistoryToolOutput::Success { commits, .. } =
+            run_tool(&tool, input(), ToolOutputBudget::default(), cx)
+                .await
+                .unwrap()
+        else {
+            panic!("expected success");
+        };
+        let first_diff_len = commits[0].hunks.concat().len();
+
+        // Only the newest commit's diff fits, and the older ones are still listed.
+        let output = run_tool(&tool, input(), ToolOutputBudget::new(first_diff_len), cx)
+            .await
+            .unwrap();
+        let FileHistoryToolOutput::Success {
+            commits: budgeted_commits,
+            truncation,
+            ..
+        } = &output
+        else {
+            panic!("expected success, got {output:?}");
+        };
+        assert_eq!(subjects(budgeted_commits), subjects(&commits));
+        assert_eq!(budgeted_commits[0].hunks, commits[0].hunks);
+        assert!(!budgeted_commits[0].hunks_omitted);
+        assert!(
+            budgeted_commits[1..]
+                .iter()
+                .all(|commit| { commit.hunks.is_empty() && commit.hunks_omitted })
+        );
+        let total_bytes = commits
+            .iter()
+            .map(|commit| commit.hunks.concat().len())
+            .sum();
+        assert_eq!(
+            *truncation,
+            Some(OutputTruncation {
+                shown_bytes: first_diff_len,
+                total_bytes,
+            })
+        );
+        assert!(output.to_string().contains("[Diff omitted.]"));
+        assert!(output.to_string().ends_with("refine your request"));
+    }
+
+    #[gpui::test]
+    async fn test_file_history_errors(cx: &mut TestAppContext) {
+        let (dir, project) = init_fixture_repository(cx).await;
+        std::fs::write(dir.path().join("repo/data.bin"), b"\0\x01\x02").unwrap();
+        git(&dir.path().join("repo"), &["add", "data.bin"]);
+        git(&dir.path().join("repo"), &["commit", "-m", "Add data"]);
+        cx.run_until_parked();
+        let tool = Arc::new(FileHistoryTool::new(project));
+        assert_eq!(
+            history_error(&tool, "repo/data.bin", cx).await,
+            "repo/data.bin is a binary file, so its history can't be shown as a diff."
+        );
+        assert_eq!(
+            history_error(&tool, "repo/missing.rs", cx).await,
+            "repo/missing.rs not found"
+        );
+        assert_eq!(
+            history_error(&tool, "untracked/notes.txt", cx).await,
+            "untracked/notes.txt isn't in a git repository."
+        );
+    }
+
+    async fn run_tool(
+        tool: &Arc<FileHistoryTool>,
+        input: FileHistoryToolInput,
+        budget: ToolOutputBudget,
+        cx: &mut TestAppContext,
+    ) -> Result<FileHistoryToolOutput, FileHistoryToolOutput> {
+        let (event_stream, _) = ToolCallEventStream::test();
+        let event_stream = event_stream.with_output_budget(budget);
+        cx.update(|cx| {
+            tool.clone()
+                .run(ToolInput::resolved(input), event_stream, cx)
+        })
+        .await
+    }
+
+    async fn history_error(
+        tool: &Arc<FileHistoryTool>,
+        path: &str,
+        cx: &mut TestAppContext,
+    ) -> String {
+        let input = FileHistoryToolInput {
+            path: path.into(),
+            range: None,
+            last_n_commits: None,
+            uncommitted: false,
+        };
+        run_tool(tool, input, ToolOutputBudget::default(), cx)
+            .await
+            .unwrap_err()
+            .to_string()
+    }
+
+    fn subjects(commits: &[FileHistoryCommit]) -> Vec<&str> {
+        commits
+            .iter()
+            .map(|commit| commit.subject.as_str())
+            .collect()
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// A project with two worktrees: `repo`, a git repository where `src/lib.rs` has three
+    /// commits, and `untracked`, which isn't in a repository.
+    async fn init_fixture_repository(
+        cx: &mut TestAppContext,
+    ) -> (tempfile::TempDir, Entity<Project>) {
+        cx.executor().allow_parking();
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("untracked")).unwrap();
+        std::fs::write(dir.path().join("untracked/notes.txt"), "notes").unwrap();
+
+        git(&repo, &["init", "--initial-branch=main"]);
+        git(&repo, &["config", "user.name", "Test Author"]);
+        git(&repo, &["config", "user.email", "test@example.com"]);
+        for (file, text, subject) in [
+            ("src/lib.rs", "fn main() {}\n", "Initial commit"),
+            ("README.md", "# Repo\n", "Add README"),
+            (
+                "src/lib.rs",
+                "fn main() {}\n\nfn helper() {}\n",
+                "Add helper",
+            ),
+            ("README.md", "# Repo\n\nHelpers.\n", "Document helpers"),
+        ] {
+            std::fs::write(repo.join(file), text).unwrap();
+            git(&repo, &["add", file]);
+            git(&repo, &["commit", "-m", subject]);
+        }
+        // A commit that changes the file along with another one.
+        std::fs::write(
+            repo.join("src/lib.rs"),
+            "fn main() {}\n\nfn renamed_helper() {}\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("README.md"), "# Repo\n\nRenamed helpers.\n").unwrap();
+        git(&repo, &["commit", "-am", "Rename helper"]);
+
+        let project = Project::test(
+            Arc::new(RealFs::new(None, cx.executor())),
+            [repo.as_path(), dir.path().join("untracked").as_path()],
+            cx,
+        )
+        .await;
+        cx.executor().run_until_parked();
+        project
+            .update(cx, |project, cx| project.git_scans_complete(cx))
+            .await;
+        cx.executor().run_until_parked();
+        (dir, project)
+    }
+}
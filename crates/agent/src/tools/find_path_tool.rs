@@ -107,6 +107,14 @@ impl AgentTool for FindPathTool {
         acp::ToolKind::Search
     }
 
+    fn preview_fields() -> &'static [&'static str] {
+        &["glob"]
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
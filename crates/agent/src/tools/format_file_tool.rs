@@ -0,0 +1,726 @@
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
+use collections::HashSet;
+use futures::FutureExt as _;
+use gpui::{App, Entity, SharedString, Task, WeakEntity};
+use language::Buffer;
+use language::language_settings::{self, Formatter, LanguageSettings};
+use project::Project;
+use project::lsp_store::{FormatTrigger, LspFormatTarget};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::{LanguageServerFormatterSpecifier, Settings as _};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use util::markdown::MarkdownInlineCode;
+
+use super::restore_file_from_disk_tool::RestoreFileFromDiskTool;
+use super::save_file_tool::SaveFileTool;
+use super::tool_permissions::authorize_file_edit;
+use crate::{
+    AgentTool, Thread, ToolCallEventStream, ToolInput, ToolPermissionDecision,
+    decide_permission_for_path,
+};
+
+/// How long a formatter may run on a single file before it's cancelled.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Formats files with the formatter the project configures for their language, then saves them.
+///
+/// Use this tool when a file only needs to be formatted. Don't make a no-op edit or run a formatter in the terminal to get the same effect.
+/// Files with unsaved changes are not formatted.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FormatFileToolInput {
+    /// The paths of the files to format.
+    ///
+    /// Each path should start with one of the project's root directories.
+    pub paths: Vec<PathBuf>,
+}
+
+pub struct FormatFileTool {
+    thread: WeakEntity<Thread>,
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+}
+
+impl FormatFileTool {
+    pub fn new(
+        thread: WeakEntity<Thread>,
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+    ) -> Self {
+        Self {
+            thread,
+            project,
+            action_log,
+        }
+    }
+}
+
+impl AgentTool for FormatFileTool {
+    type Input = FormatFileToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "format_file";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) if input.paths.len() == 1 => format!(
+                "Format {}",
+                MarkdownInlineCode(&input.paths[0].to_string_lossy())
+            )
+            .into(),
+            Ok(input) => format!("Format {} files", input.paths.len()).into(),
+            Err(_) => "Format files".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<String, String>> {
+        cx.spawn(async move |cx| {
+            let input = input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+
+            // Check for any immediate deny before doing async work.
+            for path in &input.paths {
+                let path_str = path.to_string_lossy();
+                let decision = cx.update(|cx| {
+                    decide_permission_for_path(Self::NAME, &path_str, AgentSettings::get_global(cx))
+                });
+                if let ToolPermissionDecision::Deny(reason) = decision {
+                    return Err(reason);
+                }
+            }
+
+            let (has_save_tool, has_restore_tool) = self
+                .thread
+                .read_with(cx, |thread, _| {
+                    (
+                        thread.has_tool(SaveFileTool::NAME),
+                        thread.has_tool(RestoreFileFromDiskTool::NAME),
+                    )
+                })
+                .unwrap_or_default();
+
+            let mut formatted_paths: Vec<PathBuf> = Vec::new();
+            let mut unchanged_paths: Vec<PathBuf> = Vec::new();
+            let mut not_found_paths: Vec<PathBuf> = Vec::new();
+            let mut errors: Vec<(PathBuf, String)> = Vec::new();
+            let mut diff = String::new();
+
+            for path in input.paths {
+                let description = format!("Format {}", MarkdownInlineCode(&path.to_string_lossy()));
+                let authorize = cx.update(|cx| {
+                    authorize_file_edit(
+                        Self::NAME,
+                        &path,
+                        &description,
+                        &self.thread,
+                        &event_stream,
+                        cx,
+                    )
+                });
+                if let Err(error) = authorize.await {
+                    errors.push((path, error.to_string()));
+                    continue;
+                }
+
+                let Some(project_path) = self
+                    .project
+                    .read_with(cx, |project, cx| project.find_project_path(&path, cx))
+                else {
+                    not_found_paths.push(path);
+                    continue;
+                };
+
+                let abs_path = self
+                    .project
+                    .read_with(cx, |project, cx| project.absolute_path(&project_path, cx));
+                let open_buffer_task = self
+                    .project
+                    .update(cx, |project, cx| project.open_buffer(project_path, cx));
+                let buffer = futures::select! {
+                    result = open_buffer_task.fuse() => match result {
+                        Ok(buffer) => buffer,
+                        Err(error) => {
+                            errors.push((path, error.to_string()));
+                            continue;
+                        }
+                    },
+                    _ = event_stream.cancelled_by_user().fuse() => {
+                        return Err("Format cancelled by user".to_string());
+                    }
+                };
+
+                if buffer.read_with(cx, |buffer, _| buffer.is_dirty()) {
+                    let message = match (has_save_tool, has_restore_tool) {
+                        (true, true) => {
+                            "The file has unsaved changes. Ask the user whether to keep them, \
+                            then use the save_file or restore_file_from_disk tool and retry."
+                        }
+                        (true, false) => {
+                            "The file has unsaved changes. Ask the user whether to keep them, \
+                            then use the save_file tool or ask them to revert the file, and retry."
+                        }
+                        (false, true) => {
+                            "The file has unsaved changes. Ask the user whether to keep them, \
+                            then ask them to save the file or use the restore_file_from_disk tool, \
+                            and retry."
+                        }
+                        (false, false) => {
+                            "The file has unsaved changes. Ask the user to save or revert it, \
+                            then retry."
+                        }
+                    };
+                    errors.push((path, message.to_string()));
+                    continue;
+                }
+
+                // Language servers only format buffers they know about, so make sure they've
+                // been told about this one for as long as it's being formatted.
+                let _lsp_handle = self.project.update(cx, |project, cx| {
+                    project.register_buffer_with_language_servers(&buffer, cx)
+                });
+                if let Some(reason) =
+                    cx.update(|cx| unavailable_formatter_reason(&buffer, &self.project, cx))
+                {
+                    errors.push((path, reason));
+                    continue;
+                }
+
+                let old_snapshot = buffer.read_with(cx, |buffer, _| buffer.text_snapshot());
+                self.action_log.update(cx, |log, cx| {
+                    log.buffer_read(buffer.clone(), cx);
+                });
+
+                let format_task = self.project.update(cx, |project, cx| {
+                    project.format(
+                        HashSet::from_iter([buffer.clone()]),
+                        LspFormatTarget::Buffers,
+                        true,
+                        FormatTrigger::Manual,
+                        cx,
+                    )
+                });
+                let timeout = cx.background_executor().timer(FORMAT_TIMEOUT);
+                // Dropping the format task cancels the language server request or kills the
+                // formatter's process.
+                let format_result = futures::select! {
+                    result = format_task.fuse() => Some(result),
+                    _ = timeout.fuse() => None,
+                    _ = event_stream.cancelled_by_user().fuse() => {
+                        return Err("Format cancelled by user".to_string());
+                    }
+                };
+                let error = match format_result {
+                    Some(Ok(_)) => None,
+                    Some(Err(error)) => Some(format!("Formatting failed: {error}")),
+                    None => Some(format!(
+                        "The formatter didn't finish within {} seconds and was cancelled.",
+                        FORMAT_TIMEOUT.as_secs()
+                    )),
+                };
+                if let Some(error) = error {
+                    // Formatting may have been partially applied before failing, which we don't
+                    // want to leave behind as unsaved changes.
+                    if buffer.read_with(cx, |buffer, _| buffer.is_dirty()) {
+                        let buffers = HashSet::from_iter([buffer.clone()]);
+                        self.project
+                            .update(cx, |project, cx| project.reload_buffers(buffers, false, cx))
+                            .await
+                            .ok();
+                    }
+                    errors.push((path, error));
+                    continue;
+                }
+
+                let changed = buffer.read_with(cx, |buffer, _| {
+                    buffer.version().changed_since(old_snapshot.version())
+                });
+                if !changed {
+                    unchanged_paths.push(path);
+                    continue;
+                }
+
+                let save_task = self
+                    .project
+                    .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx));
+                let save_result = futures::select! {
+                    result = save_task.fuse() => result,
+                    _ = event_stream.cancelled_by_user().fuse() => {
+                        return Err("Format cancelled by user".to_string());
+                    }
+                };
+                if let Err(error) = save_result {
+                    errors.push((path, format!("Formatted, but saving failed: {error}")));
+                    continue;
+                }
+
+                self.action_log.update(cx, |log, cx| {
+                    log.buffer_edited(buffer.clone(), cx);
+                });
+                let (new_mtime, new_snapshot) = buffer.read_with(cx, |buffer, _| {
+                    (
+                        buffer.file().and_then(|file| file.disk_state().mtime()),
+                        buffer.text_snapshot(),
+                    )
+                });
+                if let Some(abs_path) = abs_path {
+                    self.thread
+                        .update(cx, |thread, cx| {
+                            thread.record_file_edit(abs_path, new_mtime, cx);
+                        })
+                        .ok();
+                }
+
+                let file_diff = cx
+                    .background_spawn(async move {
+                        language::unified_diff(&old_snapshot.text(), &new_snapshot.text())
+                    })
+                    .await;
+                diff.push_str(&format!(
+                    "--- {path}\n+++ {path}\n{file_diff}",
+                    path = path.display()
+                ));
+                if !diff.ends_with('\n') {
+                    diff.push('\n');
+                }
+                formatted_paths.push(path);
+            }
+
+            let mut lines: Vec<String> = Vec::new();
+            if !formatted_paths.is_empty() {
+                lines.push(format!("Formatted ({}):", formatted_paths.len()));
+                for path in &formatted_paths {
+                    lines.push(format!("- {}", path.display()));
+                }
+            }
+            if !unchanged_paths.is_empty() {
+                lines.push(format!("Already formatted ({}):", unchanged_paths.len()));
+                for path in &unchanged_paths {
+                    lines.push(format!("- {}", path.display()));
+                }
+            }
+            if !not_found_paths.is_empty() {
+                lines.push(format!("Not found ({}):", not_found_paths.len()));
+                for path in &not_found_paths {
+                    lines.push(format!("- {}", path.display()));
+                }
+            }
+            if !errors.is_empty() {
+                lines.push(format!("Not formatted ({}):", errors.len()));
+                for (path, error) in &errors {
+                    lines.push(format!("- {}: {}", path.display(), error));
+                }
+            }
+
+            if lines.is_empty() {
+                return Ok("No paths provided.".to_string());
+            }
+            let mut output = lines.join("\n");
+            if !diff.is_empty() {
+                let diff = event_stream.output_budget().truncate_diff(diff);
+                output.push_str(&format!("\n\n```diff\n{}```", diff.text));
+                if let Some(truncation) = diff.truncation {
+                    output.push_str(&format!("\n\n{truncation}"));
+                }
+            }
+            Ok(output)
+        })
+    }
+}
+
+/// Explains why none of the formatters configured for `buffer` can run, or returns `None` when
+/// at least one of them can.
+fn unavailable_formatter_reason(
+    buffer: &Entity<Buffer>,
+    project: &Entity<Project>,
+    cx: &mut App,
+) -> Option<String> {
+    let lsp_store = project.read(cx).lsp_store();
+    // Remote projects format on the host, which decides what's available there.
+    if lsp_store.read(cx).as_local().is_none() {
+        return None;
+    }
+
+    let servers = lsp_store.update(cx, |lsp_store, cx| {
+        buffer.update(cx, |buffer, cx| {
+            lsp_store
+                .running_language_servers_for_local_buffer(buffer, cx)
+                .map(|(adapter, server)| {
+                    let capabilities = server.capabilities();
+                    let can_format = matches!(
+                        capabilities.document_formatting_provider,
+                        Some(provider) if provider != lsp::OneOf::Left(false)
+                    ) || matches!(
+                        capabilities.document_range_formatting_provider,
+                        Some(provider) if provider != lsp::OneOf::Left(false)
+                    );
+                    (adapter.name.0.to_string(), can_format)
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let buffer = buffer.read(cx);
+    let language_name = buffer.language().map_or_else(
+        || "Plain Text".to_string(),
+        |language| language.name().to_string(),
+    );
+    let settings = language_settings::language_settings(
+        buffer.language().map(|language| language.name()),
+        buffer.file(),
+        cx,
+    );
+
+    let has_code_actions = settings
+        .code_actions_on_format
+        .values()
+        .any(|enabled| *enabled);
+    if has_code_actions && !servers.is_empty() {
+        return None;
+    }
+    if settings
+        .formatter
+        .as_ref()
+        .iter()
+        .any(|formatter| formatter_is_available(formatter, &settings, &servers))
+    {
+        return None;
+    }
+
+    let formatters = settings
+        .formatter
+        .as_ref()
+        .iter()
+        .map(describe_formatter)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let servers = if servers.is_empty() {
+        "none".to_string()
+    } else {
+        servers
+            .iter()
+            .map(|(name, can_format)| {
+                if *can_format {
+                    name.clone()
+                } else {
+                    format!("{name} (can't format)")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let prettier = if settings.prettier.allowed {
+        "allowed"
+    } else {
+        "not allowed"
+    };
+    Some(format!(
+        "No formatter is available. The `formatter` setting for {language_name} is: {formatters}. \
+        Prettier is {prettier} for {language_name}, and the language servers running for this \
+        file are: {servers}."
+    ))
+}
+
+fn formatter_is_available(
+    formatter: &Formatter,
+    settings: &LanguageSettings,
+    servers: &[(String, bool)],
+) -> bool {
+    match formatter {
+        Formatter::Auto => {
+            settings.prettier.allowed || servers.first().is_some_and(|(_, can_format)| *can_format)
+        }
+        Formatter::Prettier => settings.prettier.allowed,
+        Formatter::External { .. } => true,
+        Formatter::CodeAction(_) => !servers.is_empty(),
+        Formatter::LanguageServer(LanguageServerFormatterSpecifier::Current) => {
+            servers.first().is_some_and(|(_, can_format)| *can_format)
+        }
+        Formatter::LanguageServer(LanguageServerFormatterSpecifier::Specific { name }) => servers
+            .iter()
+            .any(|(server_name, can_format)| server_name == name && *can_format),
+    }
+}
+
+fn describe_formatter(formatter: &Formatter) -> String {
+    match formatter {
+        Formatter::Auto => "`auto`".to_string(),
+        Formatter::Prettier => "`prettier`".to_string(),
+        Formatter::External { command, .. } => format!("external command `{command}`"),
+        Formatter::CodeAction(action) => format!("code action `{action}`"),
+        Formatter::LanguageServer(LanguageServerFormatterSpecifier::Current) => {
+            "`language_server`".to_string()
+        }
+        Formatter::LanguageServer(LanguageServerFormatterSpecifier::Specific { name }) => {
+            format!("language server `{name}`")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServerRegistry, Templates};
+    use fs::Fs as _;
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use language_model::fake_provider::FakeLanguageModel;
+    use project::FakeFs;
+    use prompt_store::ProjectContext;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use util::path;
+
+    const UNFORMATTED_CONTENT: &str = "fn main() {println!(\"Hello!\");}\n";
+    const FORMATTED_CONTENT: &str = "fn main() {\n    println!(\"Hello!\");\n}\n";
+
+    fn init_test(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+    }
+
+    async fn setup(
+        content: &str,
+        cx: &mut TestAppContext,
+    ) -> (
+        Arc<FakeFs>,
+        Entity<Project>,
+        Entity<Thread>,
+        Arc<FormatFileTool>,
+    ) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/root"),
+            json!({
+                "main.rs": content,
+                "notes.txt": "some notes\n",
+            }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(Arc::new(FakeLanguageModel::default())),
+                cx,
+            )
+        });
+        let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
+        let tool = Arc::new(FormatFileTool::new(
+            thread.downgrade(),
+            project.clone(),
+            action_log,
+        ));
+        (fs, project, thread, tool)
+    }
+
+    /// Starts a fake Rust language server that can format documents, and opens `main.rs` in it.
+    async fn start_formatting_server(
+        project: &Entity<Project>,
+        cx: &mut TestAppContext,
+    ) -> (
+        lsp::FakeLanguageServer,
+        project::lsp_store::OpenLspBufferHandle,
+    ) {
+        let language_registry = project.read_with(cx, |project, _| project.languages().clone());
+        language_registry.add(Arc::new(language::Language::new(
+            language::LanguageConfig {
+                name: "Rust".into(),
+                matcher: language::LanguageMatcher {
+                    path_suffixes: vec!["rs".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            None,
+        )));
+        let mut fake_language_servers = language_registry.register_fake_lsp(
+            "Rust",
+            language::FakeLspAdapter {
+                capabilities: lsp::ServerCapabilities {
+                    document_formatting_provider: Some(lsp::OneOf::Left(true)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        let buffer = project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(path!("/root/main.rs"), cx)
+            })
+            .await
+            .unwrap();
+        let handle = project.update(cx, |project, cx| {
+            project.register_buffer_with_language_servers(&buffer, cx)
+        });
+        let fake_language_server = fake_language_servers.next().await.unwrap();
+        (fake_language_server, handle)
+    }
+
+    fn run_tool(
+        tool: &Arc<FormatFileTool>,
+        path: &str,
+        cx: &mut TestAppContext,
+    ) -> Task<Result<String, String>> {
+        cx.update(|cx| {
+            tool.clone().run(
+                ToolInput::resolved(FormatFileToolInput {
+                    paths: vec![PathBuf::from(path)],
+                }),
+                ToolCallEventStream::test().0,
+                cx,
+            )
+        })
+    }
+
+    #[gpui::test]
+    async fn test_format_file_saves_formatted_content(cx: &mut TestAppContext) {
+        let (fs, project, thread, tool) = setup(UNFORMATTED_CONTENT, cx).await;
+        let (fake_language_server, _handle) = start_formatting_server(&project, cx).await;
+        fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>(
+            |_, _| async move {
+                Ok(Some(vec![lsp::TextEdit {
+                    range: lsp::Range::new(lsp::Position::new(0, 0), lsp::Position::new(1, 0)),
+                    new_text: FORMATTED_CONTENT.to_string(),
+                }]))
+            },
+        );
+
+        let output = run_tool(&tool, "root/main.rs", cx).await.unwrap();
+        assert!(
+            output.contains("Formatted (1):\n- root/main.rs"),
+            "expected the file to be reported as formatted, got:\n{output}"
+        );
+        assert!(
+            output.contains("+    println!(\"Hello!\");"),
+            "expected the output to contain the diff, got:\n{output}"
+        );
+
+        let content = fs.load(path!("/root/main.rs").as_ref()).await.unwrap();
+        assert_eq!(content.replace("\r\n", "\n"), FORMATTED_CONTENT);
+
+        let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1,
+            "the formatting should be reviewable like any other agent edit"
+        );
+        thread.read_with(cx, |thread, _| {
+            assert!(
+                thread
+                    .working_set()
+                    .last_known_mtime(Path::new(path!("/root/main.rs")))
+                    .is_some(),
+                "the new mtime should be recorded so that later edits don't see the file as stale"
+            );
+        });
+    }
+
+    #[gpui::test]
+    async fn test_format_file_already_formatted(cx: &mut TestAppContext) {
+        let (fs, project, thread, tool) = setup(FORMATTED_CONTENT, cx).await;
+        let (fake_language_server, _handle) = start_formatting_server(&project, cx).await;
+        fake_language_server
+            .set_request_handler::<lsp::request::Formatting, _, _>(|_, _| async move { Ok(None) });
+
+        let output = run_tool(&tool, "root/main.rs", cx).await.unwrap();
+        assert_eq!(output, "Already formatted (1):\n- root/main.rs");
+
+        let content = fs.load(path!("/root/main.rs").as_ref()).await.unwrap();
+        assert_eq!(content.replace("\r\n", "\n"), FORMATTED_CONTENT);
+
+        let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
+        cx.run_until_parked();
+        assert!(action_log.read_with(cx, |log, cx| log.changed_buffers(cx).is_empty()));
+    }
+
+    #[gpui::test]
+    async fn test_format_file_without_formatter(cx: &mut TestAppContext) {
+        let (fs, _project, _thread, tool) = setup(UNFORMATTED_CONTENT, cx).await;
+
+        let output = run_tool(&tool, "root/notes.txt", cx).await.unwrap();
+        assert!(
+            output.contains("Not formatted (1):\n- root/notes.txt: No formatter is available."),
+            "expected a missing formatter error, got:\n{output}"
+        );
+        assert!(
+            output.contains("The `formatter` setting for Plain Text is: `auto`."),
+            "expected the configured formatters to be listed, got:\n{output}"
+        );
+        assert!(
+            output.contains("the language servers running for this file are: none."),
+            "expected the running language servers to be listed, got:\n{output}"
+        );
+
+        let content = fs.load(path!("/root/notes.txt").as_ref()).await.unwrap();
+        assert_eq!(content, "some notes\n");
+    }
+
+    #[gpui::test]
+    async fn test_format_file_timeout(cx: &mut TestAppContext) {
+        let (fs, project, _thread, tool) = setup(UNFORMATTED_CONTENT, cx).await;
+        let (fake_language_server, _handle) = start_formatting_server(&project, cx).await;
+        let request_cancelled = Arc::new(AtomicBool::new(false));
+        fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>({
+            let request_cancelled = request_cancelled.clone();
+            move |_, _| {
+                let request_cancelled = request_cancelled.clone();
+                async move {
+                    let _cancelled = util::defer(move || request_cancelled.store(true, SeqCst));
+                    futures::future::pending::<()>().await;
+                    Ok(None)
+                }
+            }
+        });
+
+        let task = run_tool(&tool, "root/main.rs", cx);
+        cx.run_until_parked();
+        assert!(!request_cancelled.load(SeqCst));
+
+        cx.executor().advance_clock(FORMAT_TIMEOUT);
+        let output = task.await.unwrap();
+        assert!(
+            output.contains("The formatter didn't finish within 30 seconds and was cancelled."),
+            "expected a timeout error, got:\n{output}"
+        );
+        cx.run_until_parked();
+        assert!(
+            request_cancelled.load(SeqCst),
+            "the formatting request should be cancelled"
+        );
+
+        let content = fs.load(path!("/root/main.rs").as_ref()).await.unwrap();
+        assert_eq!(content.replace("\r\n", "\n"), UNFORMATTED_CONTENT);
+    }
+}
@@ -86,6 +86,14 @@ impl AgentTool for GrepTool {
         acp::ToolKind::Search
     }
 
+    fn preview_fields() -> &'static [&'static str] {
+        &["regex"]
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -196,12 +204,22 @@ impl AgentTool for GrepTool {
                     continue;
                 }
 
-                let (Some(path), mut parse_status) = buffer.read_with(cx, |buffer, cx| {
-                    (buffer.file().map(|file| file.full_path(cx)), buffer.parse_status())
-                }) else {
+                let (Some((path, worktree_id)), mut parse_status) =
+                    buffer.read_with(cx, |buffer, cx| {
+                        let file = buffer
+                            .file()
+                            .map(|file| (file.full_path(cx), file.worktree_id(cx)));
+                        (file, buffer.parse_status())
+                    })
+                else {
                     continue;
                 };
 
+                // Matches in roots outside the thread's scope are left out, as if they weren't there
+                if !event_stream.is_in_worktree_scope(worktree_id) {
+                    continue;
+                }
+
                 // Check if this file should be excluded based on its worktree settings
                 if let Ok(Some(project_path)) = project.read_with(cx, |project, cx| {
                     project.find_project_path(&path, cx)
@@ -314,18 +332,20 @@ impl AgentTool for GrepTool {
                 }
             }
 
-            if matches_found == 0 {
-                Ok("No matches found".into())
+            let output = if matches_found == 0 {
+                "No matches found".into()
             } else if has_more_matches {
-                Ok(format!(
+                format!(
                     "Showing matches {}-{} (there were more matches found; use offset: {} to see next page):\n{output}",
                     input.offset + 1,
                     input.offset + matches_found,
                     input.offset + RESULTS_PER_PAGE,
-                ))
+                )
             } else {
-                Ok(format!("Found {matches_found} matches:\n{output}"))
-            }
+                format!("Found {matches_found} matches:\n{output}")
+            };
+            // Matches in minified or generated files can be a single enormous line.
+            Ok(event_stream.output_budget().truncate_text(output).into_text_with_notice())
         })
     }
 }
@@ -4,17 +4,29 @@ use super::tool_permissions::{
 };
 use crate::{AgentTool, ToolCallEventStream, ToolInput};
 use agent_client_protocol::ToolKind;
-use anyhow::{Context as _, Result, anyhow};
-use gpui::{App, Entity, SharedString, Task};
-use project::{Project, ProjectPath, WorktreeSettings};
+use anyhow::{Context as _, Result};
+use futures::FutureExt as _;
+use gpui::{App, AppContext as _, AsyncApp, Entity, SharedString, Task};
+use project::{Entry, Project, ProjectEntryId, ProjectPath, WorktreeSettings};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::Settings;
+use std::collections::VecDeque;
 use std::fmt::Write;
-use std::sync::Arc;
-use util::markdown::MarkdownInlineCode;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use util::{markdown::MarkdownInlineCode, paths::PathMatcher, rel_path::RelPath};
+use worktree::Snapshot;
+
+/// The most entries listed in a single call. Once it's reached, directories with entries that
+/// weren't listed say how many were left out.
+const MAX_ENTRIES: usize = 300;
 
-/// Lists files and directories in a given path. Prefer the `grep` or `find_path` tools when searching the codebase.
+/// Lists files and directories in a given path as an indented tree. Prefer the `grep` or `find_path` tools when searching the codebase.
+///
+/// Each directory is followed by the number of entries it contains. By default only the directory's direct children are listed; use `max_depth` to see more of the tree at once.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ListDirectoryToolInput {
     /// The fully-qualified path of the directory to list in the project.
@@ -39,6 +51,157 @@ pub struct ListDirectoryToolInput {
     /// If you wanna list contents in the directory `foo/baz`, you should use the path `foo/baz`.
     /// </example>
     pub path: String,
+    /// How many levels of the tree to list. Defaults to 1, which lists only the directory's direct children.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Only list files whose path matches this glob pattern, such as "**/*.rs". Directories are always listed so that matching files inside them can be found.
+    ///
+    /// Like the `exclude` pattern, it's matched against the full path including the project root directory.
+    #[serde(default)]
+    pub include: Option<String>,
+    /// Don't list files or directories whose path matches this glob pattern, such as "**/tests/**".
+    #[serde(default)]
+    pub exclude: Option<String>,
+    /// Whether to leave out entries ignored by `.gitignore` files. Defaults to true.
+    #[serde(default = "default_respect_gitignore")]
+    pub respect_gitignore: bool,
+}
+
+fn default_respect_gitignore() -> bool {
+    true
+}
+
+/// Decides which entries are listed.
+struct ListingFilter {
+    max_depth: usize,
+    include: Option<PathMatcher>,
+    exclude: Option<PathMatcher>,
+    respect_gitignore: bool,
+    global_settings: WorktreeSettings,
+    worktree_settings: WorktreeSettings,
+}
+
+impl ListingFilter {
+    fn new(
+        input: &ListDirectoryToolInput,
+        project: &Project,
+        project_path: &ProjectPath,
+        cx: &App,
+    ) -> Result<Self> {
+        let path_style = project.path_style(cx);
+        let matcher = |pattern: &Option<String>, name: &str| {
+            pattern
+                .as_ref()
+                .map(|pattern| {
+                    PathMatcher::new([pattern], path_style)
+                        .with_context(|| format!("invalid {name} glob pattern {pattern:?}"))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            max_depth: input.max_depth.unwrap_or(1).max(1),
+            include: matcher(&input.include, "include")?,
+            exclude: matcher(&input.exclude, "exclude")?,
+            respect_gitignore: input.respect_gitignore,
+            global_settings: WorktreeSettings::get_global(cx).clone(),
+            worktree_settings: WorktreeSettings::get(Some(project_path.into()), cx).clone(),
+        })
+    }
+
+    fn has_patterns(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some()
+    }
+
+    fn is_listed(&self, snapshot: &Snapshot, entry: &Entry) -> bool {
+        // Private and excluded files are never listed, regardless of the other filters.
+        if self.global_settings.is_path_private(&entry.path)
+            || self.global_settings.is_path_excluded(&entry.path)
+            || self.worktree_settings.is_path_private(&entry.path)
+            || self.worktree_settings.is_path_excluded(&entry.path)
+        {
+            return false;
+        }
+        if self.respect_gitignore && entry.is_ignored {
+            return false;
+        }
+
+        let full_path = snapshot.root_name().join(&entry.path);
+        if let Some(exclude) = &self.exclude
+            && exclude.is_match(&full_path)
+        {
+            return false;
+        }
+        if let Some(include) = &self.include
+            && !entry.is_dir()
+            && !include.is_match(&full_path)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Returns the listed children of a directory, with directories first.
+    fn children<'a>(&self, snapshot: &'a Snapshot, path: &RelPath) -> Vec<&'a Entry> {
+        let (mut directories, files): (Vec<_>, Vec<_>) = snapshot
+            .child_entries(path)
+            .filter(|entry| self.is_listed(snapshot, entry))
+            .partition(|entry| entry.is_dir());
+        directories.extend(files);
+        directories
+    }
+
+    /// Whether a directory's contents should be listed, rather than just its entry count.
+    fn expands(snapshot: &Snapshot, entry: &Entry) -> bool {
+        entry.is_dir() && unfollowed_symlink(snapshot, entry).is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnfollowedSymlink {
+    /// Reading outside the project needs the user's authorization, which is only asked for
+    /// when the symlink itself is the path being listed.
+    OutsideProject,
+    /// Following a link to one of its own ancestors would list the same entries forever.
+    Ancestor,
+}
+
+fn unfollowed_symlink(snapshot: &Snapshot, entry: &Entry) -> Option<UnfollowedSymlink> {
+    let target = entry.canonical_path.as_ref()?;
+    // Entries inside an authorized external directory are external too, but only symlinks
+    // leave the directory being listed.
+    if entry.is_external {
+        return Some(UnfollowedSymlink::OutsideProject);
+    }
+    let target = target.strip_prefix(snapshot.abs_path()).ok()?;
+    entry
+        .path
+        .as_std_path()
+        .starts_with(target)
+        .then_some(UnfollowedSymlink::Ancestor)
+}
+
+struct ListedDirectory {
+    /// How many children passed the filter, whether or not they were listed.
+    child_count: usize,
+    children: Vec<ListedEntry>,
+}
+
+struct ListedEntry {
+    name: String,
+    kind: ListedEntryKind,
+}
+
+enum ListedEntryKind {
+    File,
+    Directory {
+        /// `None` when the directory's contents haven't been scanned.
+        child_count: Option<usize>,
+        /// Where the directory points to, if it's a symlink.
+        symlink_target: Option<String>,
+        unfollowed: Option<UnfollowedSymlink>,
+        /// The index of the directory's own listing, if it was expanded.
+        listing: Option<usize>,
+    },
 }
 
 pub struct ListDirectoryTool {
@@ -50,75 +213,229 @@ impl ListDirectoryTool {
         Self { project }
     }
 
-    fn build_directory_output(
+    /// Expands the directories that will be listed but haven't been scanned yet, such as
+    /// gitignored directories when `respect_gitignore` is false.
+    async fn load_unscanned_directories(
         project: &Entity<Project>,
         project_path: &ProjectPath,
-        input_path: &str,
-        cx: &App,
-    ) -> Result<String> {
-        let worktree = project
-            .read(cx)
-            .worktree_for_id(project_path.worktree_id, cx)
-            .with_context(|| format!("{input_path} is not in a known worktree"))?;
-
-        let global_settings = WorktreeSettings::get_global(cx);
-        let worktree_settings = WorktreeSettings::get(Some(project_path.into()), cx);
-        let worktree_snapshot = worktree.read(cx).snapshot();
-        let worktree_root_name = worktree.read(cx).root_name();
-
-        let Some(entry) = worktree_snapshot.entry_for_path(&project_path.path) else {
-            return Err(anyhow!("Path not found: {}", input_path));
-        };
-
-        if !entry.is_dir() {
-            return Err(anyhow!("{input_path} is not a directory."));
+        filter: &ListingFilter,
+        cx: &mut AsyncApp,
+    ) -> Result<()> {
+        // Each round loads one more level of the tree.
+        for _ in 0..=filter.max_depth {
+            let expansions = project.update(cx, |project, cx| {
+                let snapshot = project
+                    .worktree_for_id(project_path.worktree_id, cx)?
+                    .read(cx)
+                    .snapshot();
+                let unscanned = Self::unscanned_directories(&snapshot, &project_path.path, filter);
+                Some(
+                    unscanned
+                        .into_iter()
+                        .filter_map(|entry_id| {
+                            project.expand_entry(project_path.worktree_id, entry_id, cx)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+            let Some(expansions) = expansions.filter(|expansions| !expansions.is_empty()) else {
+                break;
+            };
+            futures::future::try_join_all(expansions).await?;
         }
+        Ok(())
+    }
 
-        let mut folders = Vec::new();
-        let mut files = Vec::new();
-
-        for entry in worktree_snapshot.child_entries(&project_path.path) {
-            // Skip private and excluded files and directories
-            if global_settings.is_path_private(&entry.path)
-                || global_settings.is_path_excluded(&entry.path)
-            {
+    fn unscanned_directories(
+        snapshot: &Snapshot,
+        path: &RelPath,
+        filter: &ListingFilter,
+    ) -> Vec<ProjectEntryId> {
+        let mut unscanned = Vec::new();
+        // The root is at depth zero, so the directories at the deepest listed level are loaded
+        // too, which lets their entry counts be shown.
+        let mut queue = VecDeque::from([(path, 0)]);
+        while let Some((path, depth)) = queue.pop_front() {
+            let Some(entry) = snapshot.entry_for_path(path) else {
                 continue;
-            }
-
-            let project_path: ProjectPath = (worktree_snapshot.id(), entry.path.clone()).into();
-            if worktree_settings.is_path_excluded(&project_path.path)
-                || worktree_settings.is_path_private(&project_path.path)
-            {
+            };
+            if entry.kind.is_unloaded() {
+                unscanned.push(entry.id);
                 continue;
             }
+            if depth < filter.max_depth {
+                for child in filter.children(snapshot, path) {
+                    if ListingFilter::expands(snapshot, child) {
+                        queue.push_back((child.path.as_ref(), depth + 1));
+                    }
+                }
+            }
+            if unscanned.len() >= MAX_ENTRIES {
+                break;
+            }
+        }
+        unscanned
+    }
 
-            let full_path = worktree_root_name
-                .join(&entry.path)
-                .display(worktree_snapshot.path_style())
-                .into_owned();
-            if entry.is_dir() {
-                folders.push(full_path);
+    fn build_directory_output(
+        snapshot: &Snapshot,
+        path: &RelPath,
+        input_path: &str,
+        filter: &ListingFilter,
+        cancelled: &AtomicBool,
+    ) -> Result<String> {
+        let directories = Self::list_directories(snapshot, path, filter, cancelled)?;
+        let root = &directories[0];
+        if root.child_count == 0 {
+            return Ok(if filter.has_patterns() {
+                format!("No entries in {input_path} match the given patterns.\n")
             } else {
-                files.push(full_path);
-            }
+                format!("{input_path} is empty.\n")
+            });
         }
 
         let mut output = String::new();
+        let root_path = snapshot
+            .root_name()
+            .join(path)
+            .display(snapshot.path_style())
+            .into_owned();
+        writeln!(output, "{root_path}/ ({})", entry_count(root.child_count))?;
+        write_directory(&directories, 0, 1, &mut output)?;
+        Ok(output)
+    }
 
-        if !folders.is_empty() {
-            writeln!(output, "# Folders:\n{}", folders.join("\n")).unwrap();
-        }
+    /// Lists the tree breadth-first, so that when there are more than [`MAX_ENTRIES`], the
+    /// shallower levels are the ones that get listed.
+    fn list_directories(
+        snapshot: &Snapshot,
+        path: &RelPath,
+        filter: &ListingFilter,
+        cancelled: &AtomicBool,
+    ) -> Result<Vec<ListedDirectory>> {
+        let mut directories = Vec::<ListedDirectory>::new();
+        let mut remaining_entries = MAX_ENTRIES;
+        // The directories to list, along with their depth and the entry to link the listing to.
+        let mut queue = VecDeque::from([(path, 1, None)]);
+        while let Some((path, depth, parent)) = queue.pop_front() {
+            anyhow::ensure!(!cancelled.load(Ordering::Relaxed), "Listing was cancelled");
+
+            let children = filter.children(snapshot, path);
+            let directory_ix = directories.len();
+            if let Some((parent_ix, child_ix)) = parent {
+                let parent: &mut ListedDirectory = &mut directories[parent_ix];
+                if let ListedEntryKind::Directory { listing, .. } =
+                    &mut parent.children[child_ix].kind
+                {
+                    *listing = Some(directory_ix);
+                }
+            }
+            directories.push(ListedDirectory {
+                child_count: children.len(),
+                children: Vec::new(),
+            });
 
-        if !files.is_empty() {
-            writeln!(output, "\n# Files:\n{}", files.join("\n")).unwrap();
+            for entry in children {
+                if remaining_entries == 0 {
+                    break;
+                }
+                remaining_entries -= 1;
+
+                let name = entry.path.file_name().unwrap_or_default().to_string();
+                let kind = if entry.is_dir() {
+                    let child_ix = directories[directory_ix].children.len();
+                    let is_expanded =
+                        ListingFilter::expands(snapshot, entry) && !entry.kind.is_unloaded();
+                    if is_expanded && depth < filter.max_depth {
+                        queue.push_back((
+                            entry.path.as_ref(),
+                            depth + 1,
+                            Some((directory_ix, child_ix)),
+                        ));
+                    }
+                    ListedEntryKind::Directory {
+                        child_count: is_expanded
+                            .then(|| filter.children(snapshot, &entry.path).len()),
+                        symlink_target: entry.canonical_path.as_ref().map(|target| {
+                            target
+                                .strip_prefix(snapshot.abs_path())
+                                .map(|relative| snapshot.root_name().as_std_path().join(relative))
+                                .unwrap_or_else(|_| target.to_path_buf())
+                                .display()
+                                .to_string()
+                        }),
+                        unfollowed: unfollowed_symlink(snapshot, entry),
+                        listing: None,
+                    }
+                } else {
+                    ListedEntryKind::File
+                };
+                directories[directory_ix]
+                    .children
+                    .push(ListedEntry { name, kind });
+            }
         }
+        Ok(directories)
+    }
+}
 
-        if output.is_empty() {
-            writeln!(output, "{input_path} is empty.").unwrap();
+fn entry_count(count: usize) -> String {
+    match count {
+        0 => "empty".to_string(),
+        1 => "1 entry".to_string(),
+        count => format!("{count} entries"),
+    }
+}
+
+fn write_directory(
+    directories: &[ListedDirectory],
+    directory_ix: usize,
+    depth: usize,
+    output: &mut String,
+) -> std::fmt::Result {
+    let indent = "  ".repeat(depth);
+    let directory = &directories[directory_ix];
+    for entry in &directory.children {
+        match &entry.kind {
+            ListedEntryKind::File => writeln!(output, "{indent}{}", entry.name)?,
+            ListedEntryKind::Directory {
+                child_count,
+                symlink_target,
+                unfollowed,
+                listing,
+            } => {
+                write!(output, "{indent}{}/", entry.name)?;
+                if let Some(target) = symlink_target {
+                    write!(output, " -> {target}")?;
+                }
+                match child_count {
+                    Some(count) => writeln!(output, " ({})", entry_count(*count))?,
+                    None => match unfollowed {
+                        Some(UnfollowedSymlink::OutsideProject) => {
+                            writeln!(output, " (outside the project, not followed)")?
+                        }
+                        Some(UnfollowedSymlink::Ancestor) => {
+                            writeln!(output, " (links to a parent directory, not followed)")?
+                        }
+                        None => writeln!(output, " (not scanned)")?,
+                    },
+                }
+                if let Some(listing) = listing {
+                    write_directory(directories, *listing, depth + 1, output)?;
+                }
+            }
         }
+    }
 
-        Ok(output)
+    let unlisted = directory.child_count - directory.children.len();
+    if unlisted > 0 {
+        writeln!(
+            output,
+            "{indent}… {unlisted} more {}",
+            if unlisted == 1 { "entry" } else { "entries" }
+        )?;
     }
+    Ok(())
 }
 
 impl AgentTool for ListDirectoryTool {
@@ -131,6 +448,10 @@ impl AgentTool for ListDirectoryTool {
         ToolKind::Read
     }
 
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -165,6 +486,9 @@ impl AgentTool for ListDirectoryTool {
                         .worktrees(cx)
                         .filter_map(|worktree| {
                             let worktree = worktree.read(cx);
+                            if !event_stream.is_in_worktree_scope(worktree.id()) {
+                                return None;
+                            }
                             let root_entry = worktree.root_entry()?;
                             if root_entry.is_dir() {
                                 Some(root_entry.path.display(worktree.path_style()))
@@ -185,6 +509,7 @@ impl AgentTool for ListDirectoryTool {
             let (project_path, symlink_canonical_target) =
                 project.read_with(cx, |project, cx| -> anyhow::Result<_> {
                     let resolved = resolve_project_path(project, &input.path, &canonical_roots, cx)?;
+                    event_stream.check_worktree_scope(resolved.project_path(), &input.path)?;
                     Ok(match resolved {
                         ResolvedProjectPath::Safe(path) => (path, None),
                         ResolvedProjectPath::SymlinkEscape {
@@ -256,10 +581,48 @@ impl AgentTool for ListDirectoryTool {
                 authorize.await.map_err(|e| e.to_string())?;
             }
 
-            let list_path = input.path;
-            cx.update(|cx| {
-                Self::build_directory_output(&project, &project_path, &list_path, cx)
-            }).map_err(|e| e.to_string())
+            let filter = cx
+                .update(|cx| ListingFilter::new(&input, project.read(cx), &project_path, cx))
+                .map_err(|e| e.to_string())?;
+            Self::load_unscanned_directories(&project, &project_path, &filter, cx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let snapshot = project
+                .read_with(cx, |project, cx| {
+                    project
+                        .worktree_for_id(project_path.worktree_id, cx)
+                        .map(|worktree| worktree.read(cx).snapshot())
+                })
+                .ok_or_else(|| format!("{} is not in a known worktree", input.path))?;
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let listing = cx.background_spawn({
+                let cancelled = cancelled.clone();
+                async move {
+                    Self::build_directory_output(
+                        &snapshot,
+                        &project_path.path,
+                        &input.path,
+                        &filter,
+                        &cancelled,
+                    )
+                }
+            });
+
+            futures::select! {
+                output = listing.fuse() => output
+                    .map(|output| {
+                        event_stream
+                            .output_budget()
+                            .truncate_text(output)
+                            .into_text_with_notice()
+                    })
+                    .map_err(|e| e.to_string()),
+                _ = event_stream.cancelled_by_user().fuse() => {
+                    cancelled.store(true, Ordering::Relaxed);
+                    Err("Listing cancelled by user".to_string())
+                }
+            }
         })
     }
 }
@@ -285,6 +648,16 @@ mod tests {
         }
     }
 
+    fn list_input(path: &str) -> ListDirectoryToolInput {
+        ListDirectoryToolInput {
+            path: path.to_string(),
+            max_depth: None,
+            include: None,
+            exclude: None,
+            respect_gitignore: true,
+        }
+    }
+
     fn init_test(cx: &mut TestAppContext) {
         cx.update(|cx| {
             let settings_store = SettingsStore::test(cx);
@@ -324,9 +697,7 @@ mod tests {
         let tool = Arc::new(ListDirectoryTool::new(project));
 
         // Test listing root directory
-        let input = ListDirectoryToolInput {
-            path: "project".into(),
-        };
+        let input = list_input("project");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -339,21 +710,17 @@ mod tests {
             .unwrap();
         assert_eq!(
             output,
-            platform_paths(indoc! {"
-                # Folders:
-                project/src
-                project/tests
-
-                # Files:
-                project/Cargo.toml
-                project/README.md
-            "})
+            indoc! {"
+                project/ (4 entries)
+                  src/ (4 entries)
+                  tests/ (1 entry)
+                  Cargo.toml
+                  README.md
+            "}
         );
 
         // Test listing src directory
-        let input = ListDirectoryToolInput {
-            path: "project/src".into(),
-        };
+        let input = list_input("project/src");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -366,21 +733,14 @@ mod tests {
             .unwrap();
         assert_eq!(
             output,
-            platform_paths(indoc! {"
-                # Folders:
-                project/src/models
-                project/src/utils
-
-                # Files:
-                project/src/lib.rs
-                project/src/main.rs
-            "})
+            format!(
+                "{}/ (4 entries)\n  models/ (2 entries)\n  utils/ (1 entry)\n  lib.rs\n  main.rs\n",
+                platform_paths("project/src")
+            )
         );
 
         // Test listing directory with only files
-        let input = ListDirectoryToolInput {
-            path: "project/tests".into(),
-        };
+        let input = list_input("project/tests");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -391,9 +751,13 @@ mod tests {
             })
             .await
             .unwrap();
-        assert!(!output.contains("# Folders:"));
-        assert!(output.contains("# Files:"));
-        assert!(output.contains(&platform_paths("project/tests/integration_test.rs")));
+        assert_eq!(
+            output,
+            format!(
+                "{}/ (1 entry)\n  integration_test.rs\n",
+                platform_paths("project/tests")
+            )
+        );
     }
 
     #[gpui::test]
@@ -412,9 +776,7 @@ mod tests {
         let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
         let tool = Arc::new(ListDirectoryTool::new(project));
 
-        let input = ListDirectoryToolInput {
-            path: "project/empty_dir".into(),
-        };
+        let input = list_input("project/empty_dir");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -445,9 +807,7 @@ mod tests {
         let tool = Arc::new(ListDirectoryTool::new(project));
 
         // Test non-existent path
-        let input = ListDirectoryToolInput {
-            path: "project/nonexistent".into(),
-        };
+        let input = list_input("project/nonexistent");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -460,9 +820,7 @@ mod tests {
         assert!(output.unwrap_err().contains("Path not found"));
 
         // Test trying to list a file instead of directory
-        let input = ListDirectoryToolInput {
-            path: "project/file.txt".into(),
-        };
+        let input = list_input("project/file.txt");
         let output = cx
             .update(|cx| {
                 tool.run(
@@ -530,9 +888,7 @@ mod tests {
         let tool = Arc::new(ListDirectoryTool::new(project));
 
         // Listing root directory should exclude private and excluded files
-        let input = ListDirectoryToolInput {
-            path: "project".into(),
-        };
+        let input = list_input("project");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -563,9 +919,7 @@ mod tests {
         );
 
         // Trying to list an excluded directory should fail
-        let input = ListDirectoryToolInput {
-            path: "project/.secretdir".into(),
-        };
+        let input = list_input("project/.secretdir");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -581,9 +935,7 @@ mod tests {
         );
 
         // Listing a directory should exclude private files within it
-        let input = ListDirectoryToolInput {
-            path: "project/visible_dir".into(),
-        };
+        let input = list_input("project/visible_dir");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -692,9 +1044,7 @@ mod tests {
         let tool = Arc::new(ListDirectoryTool::new(project));
 
         // Test listing worktree1/src - should exclude secret.rs and config.toml based on local settings
-        let input = ListDirectoryToolInput {
-            path: "worktree1/src".into(),
-        };
+        let input = list_input("worktree1/src");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -716,9 +1066,7 @@ mod tests {
         );
 
         // Test listing worktree1/tests - should exclude fixture.sql based on local settings
-        let input = ListDirectoryToolInput {
-            path: "worktree1/tests".into(),
-        };
+        let input = list_input("worktree1/tests");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -736,9 +1084,7 @@ mod tests {
         );
 
         // Test listing worktree2/lib - should exclude private.js and data.json based on local settings
-        let input = ListDirectoryToolInput {
-            path: "worktree2/lib".into(),
-        };
+        let input = list_input("worktree2/lib");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -760,9 +1106,7 @@ mod tests {
         );
 
         // Test listing worktree2/docs - should exclude internal.md based on local settings
-        let input = ListDirectoryToolInput {
-            path: "worktree2/docs".into(),
-        };
+        let input = list_input("worktree2/docs");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -780,9 +1124,7 @@ mod tests {
         );
 
         // Test trying to list an excluded directory directly
-        let input = ListDirectoryToolInput {
-            path: "worktree1/src/secret.rs".into(),
-        };
+        let input = list_input("worktree1/src/secret.rs");
         let output = cx
             .update(|cx| {
                 tool.clone().run(
@@ -832,9 +1174,7 @@ mod tests {
         let (event_stream, mut event_rx) = ToolCallEventStream::test();
         let task = cx.update(|cx| {
             tool.clone().run(
-                ToolInput::resolved(ListDirectoryToolInput {
-                    path: "project/link_to_external".into(),
-                }),
+                ToolInput::resolved(list_input("project/link_to_external")),
                 event_stream,
                 cx,
             )
@@ -893,9 +1233,7 @@ mod tests {
         let (event_stream, mut event_rx) = ToolCallEventStream::test();
         let task = cx.update(|cx| {
             tool.clone().run(
-                ToolInput::resolved(ListDirectoryToolInput {
-                    path: "project/link_to_external".into(),
-                }),
+                ToolInput::resolved(list_input("project/link_to_external")),
                 event_stream,
                 cx,
             )
@@ -960,9 +1298,7 @@ mod tests {
         let result = cx
             .update(|cx| {
                 tool.clone().run(
-                    ToolInput::resolved(ListDirectoryToolInput {
-                        path: "project/link_to_external".into(),
-                    }),
+                    ToolInput::resolved(list_input("project/link_to_external")),
                     event_stream,
                     cx,
                 )
@@ -1013,9 +1349,7 @@ mod tests {
         let result = cx
             .update(|cx| {
                 tool.clone().run(
-                    ToolInput::resolved(ListDirectoryToolInput {
-                        path: "project/src".into(),
-                    }),
+                    ToolInput::resolved(list_input("project/src")),
                     event_stream,
                     cx,
                 )
@@ -1070,9 +1404,7 @@ mod tests {
         let result = cx
             .update(|cx| {
                 tool.clone().run(
-                    ToolInput::resolved(ListDirectoryToolInput {
-                        path: "project/link_dir".into(),
-                    }),
+                    ToolInput::resolved(list_input("project/link_dir")),
                     event_stream,
                     cx,
                 )
@@ -1095,4 +1427,268 @@ mod tests {
             "No authorization should be requested for intra-project symlinks",
         );
     }
+
+    async fn run_list_directory(
+        tool: &Arc<ListDirectoryTool>,
+        input: ListDirectoryToolInput,
+        cx: &mut TestAppContext,
+    ) -> String {
+        cx.update(|cx| {
+            tool.clone().run(
+                ToolInput::resolved(input),
+                ToolCallEventStream::test().0,
+                cx,
+            )
+        })
+        .await
+        .unwrap()
+    }
+
+    #[gpui::test]
+    async fn test_list_directory_max_depth(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/project"),
+            json!({
+                "src": {
+                    "main.rs": "fn main() {}",
+                    "models": {
+                        "user.rs": "struct User {}",
+                        "nested": {
+                            "deep.rs": "struct Deep {}"
+                        }
+                    }
+                },
+                "README.md": "# Project"
+            }),
+        )
+        .await;
+
+        let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
+        let tool = Arc::new(ListDirectoryTool::new(project));
+
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                max_depth: Some(2),
+                ..list_input("project")
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output,
+            indoc! {"
+                project/ (2 entries)
+                  src/ (2 entries)
+                    models/ (2 entries)
+                    main.rs
+                  README.md
+            "}
+        );
+
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                max_depth: Some(10),
+                include: Some("**/*.rs".into()),
+                exclude: Some("**/nested".into()),
+                ..list_input("project")
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output,
+            indoc! {"
+                project/ (1 entry)
+                  src/ (2 entries)
+                    models/ (1 entry)
+                      user.rs
+                    main.rs
+            "}
+        );
+
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                include: Some("**/*.py".into()),
+                ..list_input("project/src/models/nested")
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output,
+            "No entries in project/src/models/nested match the given patterns.\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_list_directory_respects_gitignore(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/project"),
+            json!({
+                ".git": {},
+                ".gitignore": "node_modules\n",
+                "node_modules": {
+                    "left-pad": {
+                        "index.js": "module.exports = leftPad;"
+                    }
+                },
+                "src": {
+                    "main.rs": "fn main() {}"
+                }
+            }),
+        )
+        .await;
+
+        let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
+        cx.executor().run_until_parked();
+        let tool = Arc::new(ListDirectoryTool::new(project));
+
+        let output = run_list_directory(&tool, list_input("project"), cx).await;
+        assert_eq!(
+            output,
+            indoc! {"
+                project/ (2 entries)
+                  src/ (1 entry)
+                  .gitignore
+            "}
+        );
+
+        // Ignored directories aren't scanned until they're listed.
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                max_depth: Some(2),
+                respect_gitignore: false,
+                ..list_input("project")
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output,
+            indoc! {"
+                project/ (3 entries)
+                  node_modules/ (1 entry)
+                    left-pad/ (1 entry)
+                  src/ (1 entry)
+                    main.rs
+                  .gitignore
+            "}
+        );
+    }
+
+    #[gpui::test]
+    async fn test_list_directory_summarizes_entries_over_limit(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let files = (0..MAX_ENTRIES + 50)
+            .map(|ix| (format!("file_{ix:03}.txt"), json!("")))
+            .collect::<serde_json::Map<_, _>>();
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/project"),
+            json!({
+                "many": files,
+                "other": {
+                    "a.txt": "",
+                    "b.txt": ""
+                },
+                "README.md": "# Project"
+            }),
+        )
+        .await;
+
+        let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
+        let tool = Arc::new(ListDirectoryTool::new(project));
+
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                max_depth: Some(2),
+                ..list_input("project")
+            },
+            cx,
+        )
+        .await;
+
+        // The root's entries are listed first, so the limit cuts into the deeper levels.
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), MAX_ENTRIES + 3, "{output}");
+        assert_eq!(lines[0], "project/ (3 entries)");
+        assert_eq!(lines[1], format!("  many/ ({} entries)", MAX_ENTRIES + 50));
+        assert_eq!(lines[2], "    file_000.txt");
+        assert!(
+            output.ends_with(concat!(
+                "    file_296.txt\n",
+                "    … 53 more entries\n",
+                "  other/ (2 entries)\n",
+                "    … 2 more entries\n",
+                "  README.md\n",
+            )),
+            "{output}"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_list_directory_symlink_loop(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/project"),
+            json!({
+                "a": {
+                    "file.txt": "content"
+                },
+                "b": {
+                    "other.txt": "content"
+                }
+            }),
+        )
+        .await;
+        fs.create_symlink(
+            path!("/project/a/loop").as_ref(),
+            PathBuf::from(path!("/project/a")),
+        )
+        .await
+        .unwrap();
+        fs.create_symlink(
+            path!("/project/a/to_b").as_ref(),
+            PathBuf::from(path!("/project/b")),
+        )
+        .await
+        .unwrap();
+
+        let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
+        cx.executor().run_until_parked();
+        let tool = Arc::new(ListDirectoryTool::new(project));
+
+        let output = run_list_directory(
+            &tool,
+            ListDirectoryToolInput {
+                max_depth: Some(10),
+                ..list_input("project/a")
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output,
+            format!(
+                "{}/ (3 entries)\n  loop/ -> {} (links to a parent directory, not followed)\n  to_b/ -> {} (1 entry)\n    other.txt\n  file.txt\n",
+                platform_paths("project/a"),
+                platform_paths("project/a"),
+                platform_paths("project/b"),
+            )
+        );
+    }
 }
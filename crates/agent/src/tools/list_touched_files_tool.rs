@@ -0,0 +1,319 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use agent_client_protocol as acp;
+use chrono::{DateTime, SecondsFormat, Utc};
+use gpui::{App, SharedString, Task, WeakEntity};
+use project::Project;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{AgentTool, Thread, ToolCallEventStream, ToolInput, TouchedFileStatus};
+
+/// Lists the files that have been read or edited in this conversation, most recently touched first.
+///
+/// For each file, reports when it was last read and edited, and whether it has changed on disk since then.
+///
+/// <guidelines>
+/// - Use this to check what you've already looked at before reading a file again.
+/// - A file that has changed on disk since you last saw it must be read again before you rely on or edit its contents.
+/// </guidelines>
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ListTouchedFilesToolInput {}
+
+pub struct ListTouchedFilesTool {
+    thread: WeakEntity<Thread>,
+}
+
+impl ListTouchedFilesTool {
+    pub fn new(thread: WeakEntity<Thread>) -> Self {
+        Self { thread }
+    }
+}
+
+impl AgentTool for ListTouchedFilesTool {
+    type Input = ListTouchedFilesToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "list_touched_files";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        _input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        "List files in this conversation".into()
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output, Self::Output>> {
+        cx.spawn(async move |cx| {
+            input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+
+            self.thread
+                .read_with(cx, |thread, cx| {
+                    let files = thread.touched_files(cx);
+                    format_touched_files(&files, thread.project().read(cx), cx)
+                })
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+fn format_touched_files(files: &[TouchedFileStatus], project: &Project, cx: &App) -> String {
+    if files.is_empty() {
+        return "No files have been read or edited in this conversation yet.".to_string();
+    }
+
+    let mut output = "Files read or edited in this conversation, most recent first:\n".to_string();
+    for file in files {
+        write!(output, "\n- `{}`:", display_path(file, project, cx)).ok();
+        let mut touches = Vec::new();
+        if let Some(last_read_at) = file.file.last_read_at {
+            touches.push(format!("read at {}", format_timestamp(last_read_at)));
+        }
+        if let Some(last_edited_at) = file.file.last_edited_at {
+            touches.push(format!("edited at {}", format_timestamp(last_edited_at)));
+        }
+        if let Some(last_outlined_at) = file.file.last_outlined_at {
+            touches.push(format!(
+                "outline viewed at {}",
+                format_timestamp(last_outlined_at)
+            ));
+        }
+        write!(output, " {}", touches.join(", ")).ok();
+        if file.is_stale {
+            output.push_str(" (changed on disk since; read it again before relying on it)");
+        }
+    }
+    output
+}
+
+/// Displays paths the way the other tools accept them, starting with the worktree's root name.
+fn display_path(file: &TouchedFileStatus, project: &Project, cx: &App) -> String {
+    file.project_path
+        .as_ref()
+        .and_then(|project_path| {
+            let worktree = project.worktree_for_id(project_path.worktree_id, cx)?;
+            let full_path = worktree.read(cx).root_name().join(&project_path.path);
+            Some(full_path.display(project.path_style(cx)).to_string())
+        })
+        .unwrap_or_else(|| file.abs_path.display().to_string())
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServerRegistry, ReadFileTool, ReadFileToolInput, Templates};
+    use fs::Fs as _;
+    use gpui::{AppContext as _, Entity, TestAppContext};
+    use language_model::{LanguageModelRegistry, fake_provider::FakeLanguageModel};
+    use project::FakeFs;
+    use prompt_store::ProjectContext;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use std::path::{Path, PathBuf};
+    use util::path;
+
+    #[gpui::test]
+    async fn test_list_touched_files(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/root"),
+            json!({
+                "edited.txt": "edited",
+                "read.txt": "read",
+            }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let thread = create_thread(&project, cx);
+
+        assert_eq!(
+            list_touched_files(&thread, cx).await,
+            "No files have been read or edited in this conversation yet."
+        );
+
+        read_file(&thread, &project, "root/edited.txt", cx).await;
+        read_file(&thread, &project, "root/read.txt", cx).await;
+        // The edit tools record their edits like this once the buffer has been saved.
+        let edited_path = PathBuf::from(path!("/root/edited.txt"));
+        thread.update(cx, |thread, cx| {
+            let mtime = thread.working_set().last_known_mtime(&edited_path);
+            thread.record_file_edit(edited_path.clone(), mtime, cx);
+        });
+
+        let files = thread.read_with(cx, |thread, cx| thread.touched_files(cx));
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| (
+                    file.abs_path.as_path(),
+                    file.file.was_read(),
+                    file.file.was_edited(),
+                    file.is_stale
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (edited_path.as_path(), true, true, false),
+                (Path::new(path!("/root/read.txt")), true, false, false),
+            ]
+        );
+        let output = list_touched_files(&thread, cx).await;
+        assert!(output.contains("- `root/edited.txt`: read at "), "{output}");
+        assert!(output.contains(", edited at "), "{output}");
+        assert!(!output.contains("changed on disk"), "{output}");
+
+        // Modifying the file outside of the agent marks it as stale.
+        cx.background_executor
+            .advance_clock(std::time::Duration::from_secs(2));
+        fs.save(
+            path!("/root/read.txt").as_ref(),
+            &"changed".into(),
+            language::LineEnding::Unix,
+        )
+        .await
+        .unwrap();
+        cx.run_until_parked();
+
+        let stale_files = thread.read_with(cx, |thread, cx| {
+            thread
+                .touched_files(cx)
+                .into_iter()
+                .filter(|file| file.is_stale)
+                .map(|file| file.abs_path)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(stale_files, [PathBuf::from(path!("/root/read.txt"))]);
+        let output = list_touched_files(&thread, cx).await;
+        assert!(
+            output.contains("`root/read.txt`: read at ")
+                && output.ends_with("(changed on disk since; read it again before relying on it)"),
+            "{output}"
+        );
+
+        // Reading it again brings it up to date.
+        read_file(&thread, &project, "root/read.txt", cx).await;
+        let output = list_touched_files(&thread, cx).await;
+        assert!(!output.contains("changed on disk"), "{output}");
+    }
+
+    #[gpui::test]
+    async fn test_working_set_survives_serialization(cx: &mut TestAppContext) {
+        init_test(cx);
+        cx.update(|cx| {
+            LanguageModelRegistry::test(cx);
+        });
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({ "file.txt": "contents" }))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let thread = create_thread(&project, cx);
+        read_file(&thread, &project, "root/file.txt", cx).await;
+
+        let db_thread = thread.read_with(cx, |thread, cx| thread.to_db(cx)).await;
+        let json = serde_json::to_value(&db_thread).unwrap();
+        let db_thread: crate::DbThread = serde_json::from_value(json).unwrap();
+
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let reopened_thread = cx.new(|cx| {
+            Thread::from_db(
+                acp::SessionId::new("reopened"),
+                db_thread,
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                cx,
+            )
+        });
+
+        let original = thread.read_with(cx, |thread, cx| thread.touched_files(cx));
+        let reopened = reopened_thread.read_with(cx, |thread, cx| thread.touched_files(cx));
+        assert_eq!(original.len(), 1);
+        assert_eq!(reopened, original);
+    }
+
+    fn create_thread(project: &Entity<Project>, cx: &mut TestAppContext) -> Entity<Thread> {
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(Arc::new(FakeLanguageModel::default())),
+                cx,
+            )
+        })
+    }
+
+    async fn read_file(
+        thread: &Entity<Thread>,
+        project: &Entity<Project>,
+        path: &str,
+        cx: &mut TestAppContext,
+    ) {
+        let action_log = thread.read_with(cx, |thread, _| thread.action_log().clone());
+        let tool = Arc::new(ReadFileTool::new(
+            thread.downgrade(),
+            project.clone(),
+            action_log,
+        ));
+        cx.update(|cx| {
+            tool.run(
+                ToolInput::resolved(ReadFileToolInput {
+                    path: path.to_string(),
+                    start_line: None,
+                    end_line: None,
+                }),
+                ToolCallEventStream::test().0,
+                cx,
+            )
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn list_touched_files(thread: &Entity<Thread>, cx: &mut TestAppContext) -> String {
+        let tool = Arc::new(ListTouchedFilesTool::new(thread.downgrade()));
+        cx.update(|cx| {
+            tool.run(
+                ToolInput::resolved(ListTouchedFilesToolInput {}),
+                ToolCallEventStream::test().0,
+                cx,
+            )
+        })
+        .await
+        .unwrap()
+    }
+
+    fn init_test(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+    }
+}
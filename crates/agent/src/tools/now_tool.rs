@@ -38,6 +38,10 @@ impl AgentTool for NowTool {
         acp::ToolKind::Other
     }
 
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         _input: Result<Self::Input, serde_json::Value>,
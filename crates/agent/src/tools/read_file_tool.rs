@@ -1,8 +1,9 @@
 use action_log::ActionLog;
 use agent_client_protocol::{self as acp, ToolCallUpdateFields};
 use anyhow::{Context as _, Result, anyhow};
+use fs::Fs;
 use futures::FutureExt as _;
-use gpui::{App, Entity, SharedString, Task, WeakEntity};
+use gpui::{App, AsyncApp, Entity, Image, ImageFormat, SharedString, Task, WeakEntity};
 use indoc::formatdoc;
 use language::Point;
 use language_model::{LanguageModelImage, LanguageModelToolResultContent};
@@ -10,7 +11,11 @@ use project::{AgentLocation, ImageItem, Project, WorktreeSettings, image_store};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::Settings;
-use std::sync::Arc;
+use std::{
+    io::{Cursor, Read as _},
+    path::Path,
+    sync::Arc,
+};
 use util::markdown::MarkdownCodeBlock;
 
 fn tool_content_err(e: impl std::fmt::Display) -> LanguageModelToolResultContent {
@@ -29,8 +34,9 @@ use crate::{AgentTool, Thread, ToolCallEventStream, ToolInput, outline};
 /// - For large files, this tool returns a file outline with symbol names and line numbers instead of the full content.
 ///   This outline IS a successful response - use the line numbers to read specific sections with start_line/end_line.
 ///   Do NOT retry reading the same file without line numbers if you receive an outline.
-/// - This tool supports reading image files. Supported formats: PNG, JPEG, WebP, GIF, BMP, TIFF.
+/// - This tool supports reading image files up to 5MB. Supported formats: PNG, JPEG, WebP, GIF, BMP, TIFF.
 ///   Image files are returned as visual content that you can analyze directly.
+/// - Other binary files can't be read.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ReadFileToolInput {
     /// The relative path of the file to read.
@@ -85,6 +91,10 @@ impl AgentTool for ReadFileTool {
         acp::ToolKind::Read
     }
 
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -133,6 +143,7 @@ impl AgentTool for ReadFileTool {
                 project.read_with(cx, |project, cx| {
                     let resolved =
                         resolve_project_path(project, &input.path, &canonical_roots, cx)?;
+                    event_stream.check_worktree_scope(resolved.project_path(), &input.path)?;
                     anyhow::Ok(match resolved {
                         ResolvedProjectPath::Safe(path) => (path, None),
                         ResolvedProjectPath::SymlinkEscape {
@@ -207,11 +218,67 @@ impl AgentTool for ReadFileTool {
                 ]));
             });
 
-            let is_image = project.read_with(cx, |_project, cx| {
+            // Local files are told apart by their contents, so that images are recognized
+            // whatever their extension, and other binary files aren't read as text.
+            let metadata = if project.read_with(cx, |project, _| project.is_local()) {
+                fs.metadata(&abs_path).await.ok().flatten()
+            } else {
+                None
+            };
+            if let Some(metadata) = metadata.filter(|metadata| !metadata.is_dir && !metadata.is_fifo) {
+                let header = read_file_header(fs.as_ref(), &abs_path, cx)
+                    .await
+                    .map_err(tool_content_err)?;
+                match FileKind::detect(&header) {
+                    FileKind::Text => {}
+                    FileKind::Image(format) => {
+                        let supports_images = thread
+                            .read_with(cx, |thread, _| {
+                                thread
+                                    .model()
+                                    .is_none_or(|model| model.supports_images())
+                            })
+                            .unwrap_or(true);
+                        let (image, description) = read_image_file(
+                            fs.clone(),
+                            &abs_path,
+                            &file_path,
+                            format,
+                            metadata.len,
+                            supports_images,
+                            cx,
+                        )
+                        .await
+                        .map_err(tool_content_err)?;
+                        event_stream.update_fields(ToolCallUpdateFields::new().content(vec![
+                            acp::ToolCallContent::Content(acp::Content::new(description)),
+                            acp::ToolCallContent::Content(acp::Content::new(
+                                acp::ContentBlock::Image(acp::ImageContent::new(
+                                    image.source.clone(),
+                                    "image/png",
+                                )),
+                            )),
+                        ]));
+                        thread
+                            .update(cx, |thread, cx| {
+                                thread.record_file_read(
+                                    abs_path.to_path_buf(),
+                                    Some(metadata.mtime),
+                                    cx,
+                                );
+                            })
+                            .ok();
+                        return Ok(image.into());
+                    }
+                    FileKind::Binary { mime_type } => {
+                        return Err(tool_content_err(format!(
+                            "{file_path} appears to be a binary file ({mime_type}) and can't be read as text."
+                        )));
+                    }
+                }
+            } else if project.read_with(cx, |_project, cx| {
                 image_store::is_image_file(&project, &project_path, cx)
-            });
-
-            if is_image {
+            }) {
 
                 let image_entity: Entity<ImageItem> = cx
                     .update(|cx| {
@@ -258,18 +325,18 @@ impl AgentTool for ReadFileTool {
                 return Err(tool_content_err(format!("{file_path} not found")));
             }
 
-            // Record the file read time and mtime
-            if let Some(mtime) = buffer.read_with(cx, |buffer, _| {
+            // Record the read and the file's mtime in the thread's working set
+            let mtime = buffer.read_with(cx, |buffer, _| {
                 buffer.file().and_then(|file| file.disk_state().mtime())
-            }) {
-                thread
-                    .update(cx, |thread, _| {
-                        thread.file_read_times.insert(abs_path.to_path_buf(), mtime);
-                    })
-                    .ok();
-            }
+            });
+            thread
+                .update(cx, |thread, cx| {
+                    thread.record_file_read(abs_path.to_path_buf(), mtime, cx);
+                })
+                .ok();
 
             let mut anchor = None;
+            let output_budget = event_stream.output_budget();
 
             // Check if specific line ranges are provided
             let result = if input.start_line.is_some() || input.end_line.is_some() {
@@ -295,7 +362,7 @@ impl AgentTool for ReadFileTool {
                     log.buffer_read(buffer.clone(), cx);
                 });
 
-                Ok(result.into())
+                Ok(output_budget.truncate_text(result).into_text_with_notice().into())
             } else {
                 // No line ranges specified, so check file size to see if it's too big.
                 let buffer_content = outline::get_buffer_content_or_outline(
@@ -309,8 +376,8 @@ impl AgentTool for ReadFileTool {
                     log.buffer_read(buffer.clone(), cx);
                 });
 
-                if buffer_content.is_outline {
-                    Ok(formatdoc! {"
+                let text = if buffer_content.is_outline {
+                    formatdoc! {"
                         SUCCESS: File outline retrieved. This file is too large to read all at once, so the outline below shows the file's structure with line numbers.
 
                         IMPORTANT: Do NOT retry this call without line numbers - you will get the same outline.
@@ -321,10 +388,10 @@ impl AgentTool for ReadFileTool {
                         NEXT STEPS: To read a specific symbol's implementation, call read_file with the same path plus start_line and end_line from the outline above.
                         For example, to read a function shown as [L100-150], use start_line: 100 and end_line: 150.", buffer_content.text
                     }
-                    .into())
                 } else {
-                    Ok(buffer_content.text.into())
-                }
+                    buffer_content.text
+                };
+                Ok(output_budget.truncate_text(text).into_text_with_notice().into())
             };
 
             project.update(cx, |project, cx| {
@@ -355,10 +422,150 @@ impl AgentTool for ReadFileTool {
     }
 }
 
+/// How much of a file is looked at to tell what kind of file it is.
+const FILE_HEADER_LEN: usize = 8192;
+
+/// The largest image file that can be read. Images are downscaled to fit the model's limits,
+/// but only after being loaded and decoded in full.
+const MAX_IMAGE_FILE_LEN: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+enum FileKind {
+    Text,
+    Image(ImageFormat),
+    Binary { mime_type: &'static str },
+}
+
+impl FileKind {
+    /// Tells what kind of file starts with `header`, by its magic bytes.
+    fn detect(header: &[u8]) -> Self {
+        if let Some(format) = image_format(header) {
+            return Self::Image(format);
+        }
+
+        const SIGNATURES: &[(&[u8], &str)] = &[
+            (b"%PDF-", "application/pdf"),
+            (b"PK\x03\x04", "application/zip"),
+            (b"\x1f\x8b", "application/gzip"),
+            (b"\x7fELF", "application/x-elf"),
+            (b"\0asm", "application/wasm"),
+            (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+            (b"\xca\xfe\xba\xbe", "application/java-vm"),
+            (b"SQLite format 3\0", "application/vnd.sqlite3"),
+            (b"\0\0\x01\0", "image/x-icon"),
+        ];
+        if let Some(&(_, mime_type)) = SIGNATURES
+            .iter()
+            .find(|(signature, _)| header.starts_with(signature))
+        {
+            return Self::Binary { mime_type };
+        }
+
+        // Text never contains NUL bytes, unless it's encoded as UTF-16, which starts with a
+        // byte order mark.
+        let is_utf16 = header.starts_with(b"\xff\xfe") || header.starts_with(b"\xfe\xff");
+        if header.contains(&0) && !is_utf16 {
+            let mime_type = if header.starts_with(b"MZ") {
+                "application/vnd.microsoft.portable-executable"
+            } else {
+                "application/octet-stream"
+            };
+            return Self::Binary { mime_type };
+        }
+
+        Self::Text
+    }
+}
+
+fn image_format(header: &[u8]) -> Option<ImageFormat> {
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if header.starts_with(b"\xff\xd8\xff") {
+        Some(ImageFormat::Jpeg)
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP".as_slice()) {
+        Some(ImageFormat::Webp)
+    } else if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+        Some(ImageFormat::Tiff)
+    } else if header.starts_with(b"BM") && header.get(6..10) == Some([0; 4].as_slice()) {
+        // "BM" alone could just as well start a text file, so the reserved bytes of the
+        // bitmap header have to be zero too.
+        Some(ImageFormat::Bmp)
+    } else {
+        None
+    }
+}
+
+fn image_format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Webp => "WebP",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::Svg => "SVG",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Tiff => "TIFF",
+        ImageFormat::Ico => "ICO",
+    }
+}
+
+async fn read_file_header(fs: &dyn Fs, abs_path: &Path, cx: &AsyncApp) -> Result<Vec<u8>> {
+    let file = fs.open_sync(abs_path).await?;
+    cx.background_spawn(async move {
+        let mut header = Vec::with_capacity(FILE_HEADER_LEN);
+        file.take(FILE_HEADER_LEN as u64).read_to_end(&mut header)?;
+        anyhow::Ok(header)
+    })
+    .await
+}
+
+async fn read_image_file(
+    fs: Arc<dyn Fs>,
+    abs_path: &Path,
+    path: &str,
+    format: ImageFormat,
+    file_len: u64,
+    supports_images: bool,
+    cx: &mut AsyncApp,
+) -> Result<(LanguageModelImage, String)> {
+    let format_name = image_format_name(format);
+    anyhow::ensure!(
+        file_len <= MAX_IMAGE_FILE_LEN,
+        "{path} is a {format_name} image of {:.1}MB, which exceeds the {}MB limit for images.",
+        file_len as f64 / (1024. * 1024.),
+        MAX_IMAGE_FILE_LEN / (1024 * 1024)
+    );
+
+    let bytes = fs.load_bytes(abs_path).await?;
+    let (bytes, (width, height)) = cx
+        .background_spawn(async move {
+            let decoder_format = image::ImageFormat::from_mime_type(format.mime_type())
+                .context("unsupported image format")?;
+            let dimensions = image::ImageReader::with_format(Cursor::new(&bytes), decoder_format)
+                .into_dimensions()?;
+            anyhow::Ok((bytes, dimensions))
+        })
+        .await
+        .with_context(|| format!("{path} is not a valid {format_name} image"))?;
+    anyhow::ensure!(
+        supports_images,
+        "{path} is a {format_name} image ({width}×{height}), but the current model doesn't support reading images."
+    );
+
+    // Large images are downscaled on the background executor to fit the model's limits.
+    let image = cx
+        .update(|cx| LanguageModelImage::from_image(Arc::new(Image::from_bytes(format, bytes)), cx))
+        .await
+        .with_context(|| format!("{path} could not be converted into an image for the model"))?;
+
+    Ok((image, format!("{width}×{height} {format_name}")))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{ContextServerRegistry, Templates, Thread};
+    use crate::{ContextServerRegistry, Templates, Thread, ToolOutputBudget};
     use agent_client_protocol as acp;
     use fs::Fs as _;
     use gpui::{AppContext, TestAppContext, UpdateGlobal as _};
@@ -596,6 +803,57 @@ mod test {
         assert_eq!(result.unwrap(), "Line 2\nLine 3\nLine 4\n".into());
     }
 
+    #[gpui::test]
+    async fn test_read_file_output_budget(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        let content = (1..=1000)
+            .map(|line| format!("line {line}\n"))
+            .collect::<String>();
+        fs.insert_tree(path!("/root"), json!({ "bundle.js": content }))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let model = Arc::new(FakeLanguageModel::default());
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        let tool = Arc::new(ReadFileTool::new(thread.downgrade(), project, action_log));
+        let result = cx
+            .update(|cx| {
+                let input = ReadFileToolInput {
+                    path: "root/bundle.js".to_string(),
+                    start_line: Some(1),
+                    end_line: None,
+                };
+                let event_stream = ToolCallEventStream::test()
+                    .0
+                    .with_output_budget(ToolOutputBudget::new(20));
+                tool.run(ToolInput::resolved(input), event_stream, cx)
+            })
+            .await;
+        assert_eq!(
+            error_text(result.unwrap()),
+            format!(
+                "line 1\n[... {} bytes omitted ...]\nline 1000\n\n\n\
+                output truncated: 17 of {} bytes shown; refine your request",
+                content.len() - 17,
+                content.len()
+            )
+        );
+    }
+
     #[gpui::test]
     async fn test_read_file_line_range_edge_cases(cx: &mut TestAppContext) {
         init_test(cx);
@@ -691,6 +949,58 @@ mod test {
         });
     }
 
+    fn encode_image(format: image::ImageFormat, width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut bytes, format)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    async fn image_test_tool(
+        fs: Arc<FakeFs>,
+        supports_images: bool,
+        cx: &mut TestAppContext,
+    ) -> Arc<ReadFileTool> {
+        let project = Project::test(fs, [path!("/root").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let model = Arc::new(FakeLanguageModel::default());
+        model.set_supports_images(supports_images);
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        Arc::new(ReadFileTool::new(thread.downgrade(), project, action_log))
+    }
+
+    async fn read_path(
+        tool: &Arc<ReadFileTool>,
+        path: &str,
+        cx: &mut TestAppContext,
+    ) -> Result<LanguageModelToolResultContent, LanguageModelToolResultContent> {
+        let (event_stream, _) = ToolCallEventStream::test();
+        cx.update(|cx| {
+            tool.clone().run(
+                ToolInput::resolved(ReadFileToolInput {
+                    path: path.to_string(),
+                    start_line: None,
+                    end_line: None,
+                }),
+                event_stream,
+                cx,
+            )
+        })
+        .await
+    }
+
     fn single_pixel_png() -> Vec<u8> {
         vec![
             0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
@@ -964,6 +1274,7 @@ mod test {
         let context_server_registry =
             cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
         let model = Arc::new(FakeLanguageModel::default());
+        model.set_supports_images(true);
         let thread = cx.new(|cx| {
             Thread::new(
                 project.clone(),
@@ -1008,6 +1319,154 @@ mod test {
         assert!(result.is_ok());
     }
 
+    #[gpui::test]
+    async fn test_read_image_formats(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        let images = [
+            ("logo.png", image::ImageFormat::Png, "3×2 PNG"),
+            ("photo.jpg", image::ImageFormat::Jpeg, "3×2 JPEG"),
+            ("animation.gif", image::ImageFormat::Gif, "3×2 GIF"),
+            ("picture.webp", image::ImageFormat::WebP, "3×2 WebP"),
+            // Images are recognized by their contents rather than their extension.
+            ("png_without_extension", image::ImageFormat::Png, "3×2 PNG"),
+        ];
+        for (name, format, _) in images {
+            fs.insert_file(
+                PathBuf::from(path!("/root")).join(name),
+                encode_image(format, 3, 2),
+            )
+            .await;
+        }
+        let tool = image_test_tool(fs, true, cx).await;
+
+        for (name, _, expected_description) in images {
+            let (event_stream, mut event_rx) = ToolCallEventStream::test();
+            let result = cx
+                .update(|cx| {
+                    tool.clone().run(
+                        ToolInput::resolved(ReadFileToolInput {
+                            path: format!("root/{name}"),
+                            start_line: None,
+                            end_line: None,
+                        }),
+                        event_stream,
+                        cx,
+                    )
+                })
+                .await;
+            let image = match result {
+                Ok(LanguageModelToolResultContent::Image(image)) => image,
+                other => panic!("expected {name} to be read as an image, got {other:?}"),
+            };
+            assert_eq!(
+                image.size,
+                Some(gpui::size(gpui::DevicePixels(3), gpui::DevicePixels(2)))
+            );
+
+            // The first update sets the location, and the second one shows the image along
+            // with its dimensions.
+            event_rx.expect_update_fields().await;
+            let content = event_rx.expect_update_fields().await.content.unwrap();
+            let acp::ToolCallContent::Content(description) = &content[0] else {
+                panic!("expected a description, got {content:?}");
+            };
+            let acp::ContentBlock::Text(description) = &description.content else {
+                panic!("expected a text description, got {description:?}");
+            };
+            assert_eq!(description.text, expected_description);
+            assert!(matches!(
+                &content[1],
+                acp::ToolCallContent::Content(content)
+                    if matches!(content.content, acp::ContentBlock::Image(_))
+            ));
+        }
+    }
+
+    #[gpui::test]
+    async fn test_read_image_size_limit(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        let mut large_image = encode_image(image::ImageFormat::Png, 1, 1);
+        large_image.resize(MAX_IMAGE_FILE_LEN as usize + 1, 0);
+        fs.insert_file(path!("/root/large.png"), large_image).await;
+        let tool = image_test_tool(fs, true, cx).await;
+
+        let result = read_path(&tool, "root/large.png", cx).await;
+        assert_eq!(
+            error_text(result.unwrap_err()),
+            "root/large.png is a PNG image of 5.0MB, which exceeds the 5MB limit for images."
+        );
+    }
+
+    #[gpui::test]
+    async fn test_read_image_without_image_support(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_file(
+            path!("/root/photo.jpg"),
+            encode_image(image::ImageFormat::Jpeg, 4, 3),
+        )
+        .await;
+        let tool = image_test_tool(fs, false, cx).await;
+
+        let result = read_path(&tool, "root/photo.jpg", cx).await;
+        assert_eq!(
+            error_text(result.unwrap_err()),
+            "root/photo.jpg is a JPEG image (4×3), but the current model doesn't support reading images."
+        );
+    }
+
+    #[gpui::test]
+    async fn test_read_binary_file(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({})).await;
+        fs.insert_file(path!("/root/archive.zip"), b"PK\x03\x04\x14\0\0\0".to_vec())
+            .await;
+        fs.insert_file(path!("/root/data.bin"), b"\x01\x02\0\x03".to_vec())
+            .await;
+        let tool = image_test_tool(fs, true, cx).await;
+
+        let result = read_path(&tool, "root/archive.zip", cx).await;
+        assert_eq!(
+            error_text(result.unwrap_err()),
+            "root/archive.zip appears to be a binary file (application/zip) and can't be read as text."
+        );
+        let result = read_path(&tool, "root/data.bin", cx).await;
+        assert_eq!(
+            error_text(result.unwrap_err()),
+            "root/data.bin appears to be a binary file (application/octet-stream) and can't be read as text."
+        );
+    }
+
+    #[test]
+    fn test_detect_file_kind() {
+        assert_eq!(FileKind::detect(b""), FileKind::Text);
+        assert_eq!(FileKind::detect("héllo\n".as_bytes()), FileKind::Text);
+        // UTF-16 text is full of NUL bytes, but isn't binary.
+        assert_eq!(FileKind::detect(b"\xff\xfeh\0i\0"), FileKind::Text);
+        // Text that happens to start like a bitmap.
+        assert_eq!(FileKind::detect(b"BMW models\n"), FileKind::Text);
+        assert_eq!(
+            FileKind::detect(b"%PDF-1.7\n"),
+            FileKind::Binary {
+                mime_type: "application/pdf"
+            }
+        );
+        assert_eq!(
+            FileKind::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            FileKind::Image(ImageFormat::Webp)
+        );
+    }
+
     #[gpui::test]
     async fn test_read_file_with_multiple_worktree_settings(cx: &mut TestAppContext) {
         init_test(cx);
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
+use collections::HashMap;
+use fs::MTime;
+use gpui::{App, Entity, SharedString, Task};
+use parking_lot::Mutex;
+use project::{Project, ProjectPath};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+
+use crate::instruction_files::{
+    format_instruction_files, instruction_file_paths, load_instruction_file,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolInput};
+
+/// Reads the project's instruction files in full. These are files like `AGENTS.md` or `CONTRIBUTING.md` that describe the project's conventions, and they're listed with a short excerpt in the system prompt.
+///
+/// <guidelines>
+/// - Read them before making changes that they might have conventions for, like adding code, tests, or documentation.
+/// - They're read again when they change, so use this again if the user says they've been updated.
+/// </guidelines>
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReadInstructionsToolInput {}
+
+pub struct ReadInstructionsTool {
+    project: Entity<Project>,
+    /// Each file's text as of its mtime, so that files that haven't changed aren't loaded again.
+    loaded_files: Mutex<HashMap<ProjectPath, (MTime, Arc<str>)>>,
+}
+
+impl ReadInstructionsTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self {
+            project,
+            loaded_files: Mutex::default(),
+        }
+    }
+
+    /// The instruction files in every visible worktree, with the path to show for each and its
+    /// current mtime.
+    fn instruction_files(&self, cx: &App) -> Vec<(String, ProjectPath, Option<MTime>)> {
+        let globs = &AgentSettings::get_global(cx).instruction_files;
+        let project = self.project.read(cx);
+        let path_style = project.path_style(cx);
+        project
+            .visible_worktrees(cx)
+            .flat_map(|worktree| {
+                let worktree = worktree.read(cx);
+                instruction_file_paths(worktree, globs)
+                    .into_iter()
+                    .map(|path| {
+                        let display_path = worktree
+                            .root_name()
+                            .join(&path)
+                            .display(path_style)
+                            .to_string();
+                        let mtime = worktree.entry_for_path(&path).and_then(|entry| entry.mtime);
+                        let project_path = ProjectPath {
+                            worktree_id: worktree.id(),
+                            path,
+                        };
+                        (display_path, project_path, mtime)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl AgentTool for ReadInstructionsTool {
+    type Input = ReadInstructionsToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "read_instructions";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        _input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        "Read project instructions".into()
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output, Self::Output>> {
+        cx.spawn(async move |cx| {
+            input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+
+            let files = cx.update(|cx| self.instruction_files(cx));
+            if files.is_empty() {
+                return Ok("This project has no instruction files.".to_string());
+            }
+
+            let mut texts = Vec::with_capacity(files.len());
+            for (display_path, project_path, mtime) in files {
+                let loaded_text = self
+                    .loaded_files
+                    .lock()
+                    .get(&project_path)
+                    .filter(|(loaded_mtime, _)| Some(*loaded_mtime) == mtime)
+                    .map(|(_, text)| text.clone());
+                let text = match loaded_text {
+                    Some(text) => text,
+                    None => {
+                        let text: Arc<str> = cx
+                            .update(|cx| {
+                                load_instruction_file(&self.project, project_path.clone(), cx)
+                            })
+                            .await
+                            .map_err(|e| format!("Failed to read `{display_path}`: {e}"))?
+                            .into();
+                        if let Some(mtime) = mtime {
+                            self.loaded_files
+                                .lock()
+                                .insert(project_path, (mtime, text.clone()));
+                        }
+                        text
+                    }
+                };
+                texts.push((display_path, text));
+            }
+
+            Ok(format_instruction_files(
+                texts.iter().map(|(path, text)| (path.as_str(), &**text)),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs::Fs as _;
+    use gpui::TestAppContext;
+    use project::FakeFs;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_read_instructions(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/root"),
+            json!({
+                "a": {
+                    "AGENTS.md": "Run the tests before finishing.\n",
+                    "src": { "main.rs": "fn main() {}" },
+                },
+                "b": { "CONTRIBUTING.md": "Sign the CLA." },
+            }),
+        )
+        .await;
+        let project = Project::test(
+            fs.clone(),
+            [path!("/root/a").as_ref(), path!("/root/b").as_ref()],
+            cx,
+        )
+        .await;
+        let tool = Arc::new(ReadInstructionsTool::new(project));
+
+        assert_eq!(
+            read_instructions(&tool, cx).await,
+            "## `a/AGENTS.md`\n\nRun the tests before finishing.\n\n\
+            ## `b/CONTRIBUTING.md`\n\nSign the CLA."
+        );
+
+        // Changes are picked up the next time the tool runs.
+        fs.save(
+            path!("/root/a/AGENTS.md").as_ref(),
+            &"Use tabs.".into(),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        cx.run_until_parked();
+        assert_eq!(
+            read_instructions(&tool, cx).await,
+            "## `a/AGENTS.md`\n\nUse tabs.\n\n## `b/CONTRIBUTING.md`\n\nSign the CLA."
+        );
+    }
+
+    #[gpui::test]
+    async fn test_read_instructions_without_files(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), json!({ "README.md": "Hello" }))
+            .await;
+        let project = Project::test(fs, [path!("/root").as_ref()], cx).await;
+        let tool = Arc::new(ReadInstructionsTool::new(project));
+
+        assert_eq!(
+            read_instructions(&tool, cx).await,
+            "This project has no instruction files."
+        );
+    }
+
+    async fn read_instructions(
+        tool: &Arc<ReadInstructionsTool>,
+        cx: &mut TestAppContext,
+    ) -> String {
+        let (event_stream, _) = ToolCallEventStream::test();
+        cx.update(|cx| {
+            tool.clone().run(
+                ToolInput::resolved(ReadInstructionsToolInput {}),
+                event_stream,
+                cx,
+            )
+        })
+        .await
+        .unwrap()
+    }
+
+    fn init_test(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+    }
+}
@@ -78,6 +78,12 @@ impl AgentTool for SpawnAgentTool {
         acp::ToolKind::Other
     }
 
+    // Subagents schedule their own tool calls, so running several at once is how they work in
+    // parallel.
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
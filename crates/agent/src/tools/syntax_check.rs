@@ -0,0 +1,164 @@
+//! Finds the syntax errors that an edit introduced, by parsing the file with its language's
+//! tree-sitter grammar before and after the edit.
+
+use language::{Language, Node, with_parser};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A spot where the parser couldn't make sense of the file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxError {
+    /// 1-based line where the error starts.
+    pub line: u32,
+    /// 1-based byte column where the error starts.
+    pub column: u32,
+    /// 1-based line where the error ends, which is far from `line` when the parser had to skip
+    /// a large part of the file, as it does for unbalanced braces.
+    pub end_line: u32,
+    /// The token the parser expected here but didn't find, such as a closing brace.
+    pub missing: Option<String>,
+    /// Byte range of the error in the parsed text.
+    #[serde(skip)]
+    pub range: Range<usize>,
+}
+
+impl SyntaxError {
+    fn new(node: Node) -> Self {
+        let start = node.start_position();
+        let mut end = node.end_position();
+        // An error ending right after a newline doesn't touch the following line.
+        if end.column == 0 && end.row > start.row {
+            end.row -= 1;
+        }
+        Self {
+            line: start.row as u32 + 1,
+            column: start.column as u32 + 1,
+            end_line: end.row as u32 + 1,
+            missing: node.is_missing().then(|| node.kind().to_string()),
+            range: node.byte_range(),
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.missing {
+            Some(missing) => write!(
+                f,
+                "line {}, column {}: missing `{missing}`",
+                self.line, self.column
+            ),
+            None if self.end_line > self.line => {
+                write!(f, "lines {}-{}: syntax error", self.line, self.end_line)
+            }
+            None => write!(
+                f,
+                "line {}, column {}: syntax error",
+                self.line, self.column
+            ),
+        }
+    }
+}
+
+/// Parses `text` as `language` and returns its syntax errors in document order, or `None` if
+/// the language has no grammar to parse it with.
+pub fn syntax_errors(text: &str, language: &Language) -> Option<Vec<SyntaxError>> {
+    let grammar = language.grammar()?;
+    let tree = with_parser(|parser| {
+        parser.set_language(&grammar.ts_language).ok()?;
+        parser.parse(text, None)
+    })?;
+
+    let mut errors = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        // Subtrees without errors are skipped, and so are the contents of an error node, which
+        // are just the tokens the parser had to give up on.
+        let mut descend = node.has_error();
+        if node.is_error() || node.is_missing() {
+            errors.push(SyntaxError::new(node));
+            descend = false;
+        }
+        if descend && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return Some(errors);
+            }
+        }
+    }
+}
+
+/// The errors in `errors` that don't touch any error in `baseline`, where `to_baseline` maps a
+/// range of the edited text to the corresponding range of the text `baseline` was parsed from.
+pub fn introduced_syntax_errors(
+    baseline: &[SyntaxError],
+    errors: Vec<SyntaxError>,
+    to_baseline: impl Fn(Range<usize>) -> Range<usize>,
+) -> Vec<SyntaxError> {
+    errors
+        .into_iter()
+        .filter(|error| {
+            let range = to_baseline(error.range.clone());
+            !baseline.iter().any(|existing| {
+                existing.range.start <= range.end && range.start <= existing.range.end
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use language::LanguageConfig;
+
+    #[test]
+    fn test_syntax_errors() {
+        let language = language::rust_lang();
+        assert_eq!(
+            syntax_errors("fn main() {\n    let x = 1;\n}\n", &language),
+            Some(Vec::new())
+        );
+
+        let describe = |text: &str| {
+            syntax_errors(text, &language)
+                .unwrap()
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            describe("fn one() -> u32 {\n    1 +\n}\n"),
+            ["line 2, column 8: missing `identifier`"]
+        );
+        assert_eq!(
+            describe("fn one() -> u32 {\n    1 }\n}\n"),
+            ["line 3, column 1: syntax error"]
+        );
+        assert_eq!(
+            describe("fn one() -> u32 {\n    1\n\nfn two() -> u32 {\n    2\n}\n"),
+            ["lines 1-6: syntax error"]
+        );
+
+        let plain_text = Language::new(LanguageConfig::default(), None);
+        assert_eq!(syntax_errors("fn main() {}\n", &plain_text), None);
+    }
+
+    #[test]
+    fn test_introduced_syntax_errors_ignores_existing_errors() {
+        let language = language::rust_lang();
+        let old_text = "fn a() { let = ; }\nfn b() {}\n";
+        let new_text = "fn a() { let = ; }\nfn b() { 1 }\n";
+        let baseline = syntax_errors(old_text, &language).unwrap();
+        assert!(!baseline.is_empty());
+
+        // The edit only grew `b`, so offsets inside `a` are the same in both texts.
+        let errors = syntax_errors(new_text, &language).unwrap();
+        assert_eq!(
+            introduced_syntax_errors(&baseline, errors, |range| range),
+            []
+        );
+    }
+}
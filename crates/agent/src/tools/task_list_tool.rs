@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result};
+use gpui::{App, SharedString, Task, WeakEntity};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{AgentTool, TaskList, TaskStatus, Thread, ToolCallEventStream, ToolInput};
+
+/// Keeps a list of the steps of the current task, which the user sees as a checklist that updates as you go.
+///
+/// Every call returns the whole list, with each task's id.
+///
+/// <guidelines>
+/// - Use this for tasks that take several steps, adding the steps before starting on them.
+/// - Mark a task `in_progress` when starting on it and complete it as soon as it's done, so the user can follow along.
+/// - Add, update, or remove tasks when the plan changes, rather than leaving the list out of date.
+/// - Skip it for simple requests that only take a step or two.
+/// </guidelines>
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TaskListToolInput {
+    /// What to do with the task list.
+    pub operation: TaskListOperation,
+    /// The id of the task to update, complete, or remove.
+    #[serde(default)]
+    pub id: Option<u32>,
+    /// The title of a task to add, or a new title for the task to update.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The new status of the task to update.
+    #[serde(default)]
+    pub status: Option<TaskStatus>,
+    /// Notes on a task to add, or new notes for the task to update or complete. An empty string clears them.
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[schemars(inline)]
+pub enum TaskListOperation {
+    /// Add a pending task to the end of the list. Requires `title`.
+    Add,
+    /// Change the title, status, or notes of a task. Requires `id`.
+    Update,
+    /// Mark a task as completed. Requires `id`.
+    Complete,
+    /// Remove a task from the list. Requires `id`.
+    Remove,
+    /// Return the list without changing it.
+    List,
+}
+
+pub struct TaskListTool {
+    thread: WeakEntity<Thread>,
+}
+
+impl TaskListTool {
+    pub fn new(thread: WeakEntity<Thread>) -> Self {
+        Self { thread }
+    }
+}
+
+impl AgentTool for TaskListTool {
+    type Input = TaskListToolInput;
+    type Output = String;
+
+    const NAME: &'static str = "task_list";
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Think
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        let Ok(input) = input else {
+            return "Update task list".into();
+        };
+        let id = input.id.map(|id| id.to_string()).unwrap_or_default();
+        match input.operation {
+            TaskListOperation::Add => match input.title {
+                Some(title) => format!("Add task: {title}").into(),
+                None => "Add task".into(),
+            },
+            TaskListOperation::Update => format!("Update task {id}").into(),
+            TaskListOperation::Complete => format!("Complete task {id}").into(),
+            TaskListOperation::Remove => format!("Remove task {id}").into(),
+            TaskListOperation::List => "List tasks".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: ToolInput<Self::Input>,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output, Self::Output>> {
+        cx.spawn(async move |cx| {
+            let input = input
+                .recv()
+                .await
+                .map_err(|e| format!("Failed to receive tool input: {e}"))?;
+            let operation = input.operation;
+
+            let task_list = self
+                .thread
+                .update(cx, |thread, cx| {
+                    thread.update_task_list(
+                        |task_list| {
+                            apply(input, task_list)?;
+                            Ok(task_list.clone())
+                        },
+                        cx,
+                    )
+                })
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+
+            if operation != TaskListOperation::List {
+                event_stream.update_plan(task_list.to_acp_plan());
+            }
+            Ok(task_list.to_string())
+        })
+    }
+}
+
+fn apply(input: TaskListToolInput, task_list: &mut TaskList) -> Result<()> {
+    let id = || {
+        input
+            .id
+            .context("`id` is required to change a task. Call with `list` to see the ids.")
+    };
+    match input.operation {
+        TaskListOperation::Add => {
+            let title = input
+                .title
+                .filter(|title| !title.trim().is_empty())
+                .context("`title` is required to add a task.")?;
+            task_list.add(title, input.notes.filter(|notes| !notes.is_empty()))?;
+        }
+        TaskListOperation::Update => {
+            task_list.update(id()?, input.title, input.status, input.notes)?;
+        }
+        TaskListOperation::Complete => {
+            task_list.update(id()?, None, Some(TaskStatus::Completed), input.notes)?;
+        }
+        TaskListOperation::Remove => {
+            task_list.remove(id()?)?;
+        }
+        TaskListOperation::List => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServerRegistry, Templates, ThreadEvent};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, Entity, TestAppContext};
+    use indoc::indoc;
+    use language_model::{LanguageModelRegistry, fake_provider::FakeLanguageModel};
+    use project::{FakeFs, Project};
+    use prompt_store::ProjectContext;
+    use settings::SettingsStore;
+
+    #[gpui::test]
+    async fn test_task_list_tool(cx: &mut TestAppContext) {
+        let thread = create_thread(cx).await;
+        let (event_stream, mut events) = ToolCallEventStream::test();
+
+        for title in ["Write the parser", "Add tests", "Update the docs"] {
+            run_tool(&thread, add(title), event_stream.clone(), cx)
+                .await
+                .unwrap();
+        }
+        let output = run_tool(
+            &thread,
+            TaskListToolInput {
+                status: Some(TaskStatus::InProgress),
+                ..change(TaskListOperation::Update, 2)
+            },
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            output,
+            indoc! {"
+                Task list (0 of 3 completed):
+                [ ] 1. Write the parser
+                [~] 2. Add tests
+                [ ] 3. Update the docs"}
+        );
+
+        run_tool(
+            &thread,
+            change(TaskListOperation::Remove, 3),
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap();
+        let error = run_tool(
+            &thread,
+            change(TaskListOperation::Complete, 3),
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "There's no task with id 3. The current ids are 1, 2."
+        );
+
+        // Each change updates the plan shown in the thread, and completing every task shows
+        // them all as completed.
+        for id in [1, 2] {
+            run_tool(
+                &thread,
+                change(TaskListOperation::Complete, id),
+                event_stream.clone(),
+                cx,
+            )
+            .await
+            .unwrap();
+        }
+        drop(event_stream);
+        let plans = events
+            .by_ref()
+            .filter_map(|event| {
+                futures::future::ready(match event {
+                    Ok(ThreadEvent::Plan(plan)) => Some(plan),
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(plans.len(), 7);
+        let entries = &plans.last().unwrap().entries;
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.content.as_str())
+                .collect::<Vec<_>>(),
+            ["Write the parser", "Add tests"]
+        );
+        assert!(
+            entries
+                .iter()
+                .all(|entry| matches!(entry.status, acp::PlanEntryStatus::Completed))
+        );
+        assert!(thread.read_with(cx, |thread, _| thread.task_list().is_completed()));
+    }
+
+    #[gpui::test]
+    async fn test_task_list_tool_errors(cx: &mut TestAppContext) {
+        let thread = create_thread(cx).await;
+        let (event_stream, mut events) = ToolCallEventStream::test();
+
+        let error = run_tool(
+            &thread,
+            change(TaskListOperation::Update, 1),
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "There's no task with id 1, since the task list is empty."
+        );
+        let error = run_tool(
+            &thread,
+            TaskListToolInput {
+                id: None,
+                ..change(TaskListOperation::Remove, 1)
+            },
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("`id` is required"), "{error}");
+        let error = run_tool(&thread, add(" "), event_stream.clone(), cx)
+            .await
+            .unwrap_err();
+        assert_eq!(error, "`title` is required to add a task.");
+
+        // Failed calls leave the plan as it was.
+        drop(event_stream);
+        assert!(events.next().await.is_none());
+    }
+
+    #[gpui::test]
+    async fn test_task_list_survives_serialization(cx: &mut TestAppContext) {
+        let thread = create_thread(cx).await;
+        let (event_stream, _events) = ToolCallEventStream::test();
+        run_tool(&thread, add("Write the parser"), event_stream.clone(), cx)
+            .await
+            .unwrap();
+        run_tool(
+            &thread,
+            change(TaskListOperation::Complete, 1),
+            event_stream.clone(),
+            cx,
+        )
+        .await
+        .unwrap();
+
+        let db_thread = thread.read_with(cx, |thread, cx| thread.to_db(cx)).await;
+        let json = serde_json::to_value(&db_thread).unwrap();
+        let db_thread: crate::DbThread = serde_json::from_value(json).unwrap();
+        let project = thread.read_with(cx, |thread, _| thread.project().clone());
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let reopened_thread = cx.new(|cx| {
+            Thread::from_db(
+                acp::SessionId::new("reopened"),
+                db_thread,
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                cx,
+            )
+        });
+        assert_eq!(
+            reopened_thread.read_with(cx, |thread, _| thread.task_list().clone()),
+            thread.read_with(cx, |thread, _| thread.task_list().clone())
+        );
+
+        // Replaying the conversation shows the plan too.
+        let mut replay = reopened_thread.update(cx, |thread, cx| thread.replay(cx));
+        let mut plan = None;
+        while let Some(event) = replay.next().await {
+            if let Ok(ThreadEvent::Plan(replayed_plan)) = event {
+                plan = Some(replayed_plan);
+            }
+        }
+        assert_eq!(plan.unwrap().entries.len(), 1);
+    }
+
+    fn add(title: &str) -> TaskListToolInput {
+        TaskListToolInput {
+            operation: TaskListOperation::Add,
+            id: None,
+            title: Some(title.to_string()),
+            status: None,
+            notes: None,
+        }
+    }
+
+    fn change(operation: TaskListOperation, id: u32) -> TaskListToolInput {
+        TaskListToolInput {
+            operation,
+            id: Some(id),
+            title: None,
+            status: None,
+            notes: None,
+        }
+    }
+
+    async fn run_tool(
+        thread: &Entity<Thread>,
+        input: TaskListToolInput,
+        event_stream: ToolCallEventStream,
+        cx: &mut TestAppContext,
+    ) -> Result<String, String> {
+        let tool = Arc::new(TaskListTool::new(thread.downgrade()));
+        cx.update(|cx| tool.run(ToolInput::resolved(input), event_stream, cx))
+            .await
+    }
+
+    async fn create_thread(cx: &mut TestAppContext) -> Entity<Thread> {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+            LanguageModelRegistry::test(cx);
+        });
+        let fs = FakeFs::new(cx.executor());
+        let project = Project::test(fs, [], cx).await;
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(Arc::new(FakeLanguageModel::default())),
+                cx,
+            )
+        })
+    }
+}
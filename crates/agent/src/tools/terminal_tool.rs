@@ -1,26 +1,35 @@
 use agent_client_protocol as acp;
 use agent_settings::AgentSettings;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use fs::{Fs, MTime};
 use futures::FutureExt as _;
-use gpui::{App, Entity, SharedString, Task};
+use gpui::{App, AppContext as _, AsyncApp, Entity, SharedString, Task, WeakEntity};
 use project::Project;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::Settings;
 use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
     time::Duration,
 };
+use util::paths::PathMatcher;
 
 use crate::{
-    AgentTool, ThreadEnvironment, ToolCallEventStream, ToolInput, ToolPermissionDecision,
+    AgentTool, Thread, ThreadEnvironment, ToolCallEventStream, ToolInput, ToolPermissionDecision,
     decide_permission_from_settings,
 };
 
 const COMMAND_OUTPUT_LIMIT: u64 = 16 * 1024;
 
+/// Files up to this size are hashed as well, since a command that rewrites a file quickly can
+/// leave its mtime unchanged.
+const HASHED_FILE_SIZE_LIMIT: u64 = 64 * 1024;
+
 /// Executes a shell one-liner and returns the combined output.
 ///
 /// This tool spawns a process using the user's shell, reads from stdout and stderr (preserving the order of writes), and returns a string with the combined output result.
@@ -45,17 +54,28 @@ pub struct TerminalToolInput {
     pub cd: String,
     /// Optional maximum runtime (in milliseconds). If exceeded, the running terminal task is killed.
     pub timeout_ms: Option<u64>,
+    /// Optional glob of other project files the command may change, such as the output of a code generator (e.g. "project/src/generated/**").
+    ///
+    /// Files you have read or edited are always checked for changes, and the ones the command changed are listed after its output.
+    #[serde(default)]
+    pub watch: Option<String>,
 }
 
 pub struct TerminalTool {
     project: Entity<Project>,
+    thread: WeakEntity<Thread>,
     environment: Rc<dyn ThreadEnvironment>,
 }
 
 impl TerminalTool {
-    pub fn new(project: Entity<Project>, environment: Rc<dyn ThreadEnvironment>) -> Self {
+    pub fn new(
+        project: Entity<Project>,
+        thread: WeakEntity<Thread>,
+        environment: Rc<dyn ThreadEnvironment>,
+    ) -> Self {
         Self {
             project,
+            thread,
             environment,
         }
     }
@@ -71,6 +91,14 @@ impl AgentTool for TerminalTool {
         acp::ToolKind::Execute
     }
 
+    fn preview_fields() -> &'static [&'static str] {
+        &["command"]
+    }
+
+    fn allows_input_editing() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -90,12 +118,12 @@ impl AgentTool for TerminalTool {
         cx: &mut App,
     ) -> Task<Result<Self::Output, Self::Output>> {
         cx.spawn(async move |cx| {
-            let input = input
+            let mut input = input
                 .recv()
                 .await
                 .map_err(|e| format!("Failed to receive tool input: {e}"))?;
 
-            let (working_dir, authorize) = cx.update(|cx| {
+            let (mut working_dir, authorize) = cx.update(|cx| {
                 let working_dir =
                     working_dir(&input, &self.project, cx).map_err(|err| err.to_string())?;
 
@@ -115,90 +143,150 @@ impl AgentTool for TerminalTool {
                             Self::NAME,
                             vec![input.command.clone()],
                         );
-                        Some(event_stream.authorize(
+                        Some(event_stream.authorize_input::<Self>(
                             self.initial_title(Ok(input.clone()), cx),
                             context,
+                            &input,
                             cx,
                         ))
                     }
                 };
                 Ok((working_dir, authorize))
             })?;
-            if let Some(authorize) = authorize {
-                authorize.await.map_err(|e| e.to_string())?;
+            if let Some(authorize) = authorize
+                && let Some(edited_input) = authorize.await.map_err(|e| e.to_string())?
+            {
+                working_dir =
+                    cx.update(|cx| self.check_edited_input(&edited_input, &event_stream, cx))?;
+                input = edited_input;
             }
 
-            let terminal = self
-                .environment
-                .create_terminal(
-                    input.command.clone(),
-                    working_dir,
-                    Some(COMMAND_OUTPUT_LIMIT),
-                    cx,
-                )
+            let watched_files = WatchedFiles::snapshot(&self.thread, &self.project, &input, cx)
                 .await
                 .map_err(|e| e.to_string())?;
+            let output = self
+                .run_command(&input, working_dir, &event_stream, cx)
+                .await;
+            let changed_files = match watched_files {
+                Some(watched_files) => watched_files.finish(cx).await,
+                None => Vec::new(),
+            };
 
-            let terminal_id = terminal.id(cx).map_err(|e| e.to_string())?;
-            event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
-                acp::ToolCallContent::Terminal(acp::Terminal::new(terminal_id)),
-            ]));
-
-            let timeout = input.timeout_ms.map(Duration::from_millis);
-
-            let mut timed_out = false;
-            let mut user_stopped_via_signal = false;
-            let wait_for_exit = terminal.wait_for_exit(cx).map_err(|e| e.to_string())?;
-
-            match timeout {
-                Some(timeout) => {
-                    let timeout_task = cx.background_executor().timer(timeout);
-
-                    futures::select! {
-                        _ = wait_for_exit.clone().fuse() => {},
-                        _ = timeout_task.fuse() => {
-                            timed_out = true;
-                            terminal.kill(cx).map_err(|e| e.to_string())?;
-                            wait_for_exit.await;
-                        }
-                        _ = event_stream.cancelled_by_user().fuse() => {
-                            user_stopped_via_signal = true;
-                            terminal.kill(cx).map_err(|e| e.to_string())?;
-                            wait_for_exit.await;
-                        }
+            let mut output = output?;
+            if !changed_files.is_empty() {
+                output.push_str("\n\nFiles changed by this command:");
+                for file in &changed_files {
+                    write!(output, "\n- `{}`", file.display_path).ok();
+                    if file.deleted {
+                        output.push_str(" (deleted)");
                     }
                 }
-                None => {
-                    futures::select! {
-                        _ = wait_for_exit.clone().fuse() => {},
-                        _ = event_stream.cancelled_by_user().fuse() => {
-                            user_stopped_via_signal = true;
-                            terminal.kill(cx).map_err(|e| e.to_string())?;
-                            wait_for_exit.await;
-                        }
+            }
+            Ok(output)
+        })
+    }
+}
+
+impl TerminalTool {
+    /// Checks the input the user edited the call to before allowing it, returning the working
+    /// directory to run its command in.
+    fn check_edited_input(
+        &self,
+        input: &TerminalToolInput,
+        event_stream: &ToolCallEventStream,
+        cx: &mut App,
+    ) -> Result<Option<PathBuf>, String> {
+        // The user allowed the command they wrote, but it still has to get past the deny rules.
+        let decision = decide_permission_from_settings(
+            Self::NAME,
+            std::slice::from_ref(&input.command),
+            AgentSettings::get_global(cx),
+        );
+        if let ToolPermissionDecision::Deny(reason) = decision {
+            return Err(reason);
+        }
+        let working_dir = working_dir(input, &self.project, cx).map_err(|err| err.to_string())?;
+        event_stream.update_fields(acp::ToolCallUpdateFields::new().title(input.command.clone()));
+        Ok(working_dir)
+    }
+
+    async fn run_command(
+        &self,
+        input: &TerminalToolInput,
+        working_dir: Option<PathBuf>,
+        event_stream: &ToolCallEventStream,
+        cx: &mut AsyncApp,
+    ) -> Result<String, String> {
+        let terminal = self
+            .environment
+            .create_terminal(
+                input.command.clone(),
+                working_dir,
+                Some(COMMAND_OUTPUT_LIMIT),
+                cx,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let terminal_id = terminal.id(cx).map_err(|e| e.to_string())?;
+        event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+            acp::ToolCallContent::Terminal(acp::Terminal::new(terminal_id)),
+        ]));
+
+        let timeout = input.timeout_ms.map(Duration::from_millis);
+
+        let mut timed_out = false;
+        let mut user_stopped_via_signal = false;
+        let wait_for_exit = terminal.wait_for_exit(cx).map_err(|e| e.to_string())?;
+
+        match timeout {
+            Some(timeout) => {
+                let timeout_task = cx.background_executor().timer(timeout);
+
+                futures::select! {
+                    _ = wait_for_exit.clone().fuse() => {},
+                    _ = timeout_task.fuse() => {
+                        timed_out = true;
+                        terminal.kill(cx).map_err(|e| e.to_string())?;
+                        wait_for_exit.await;
+                    }
+                    _ = event_stream.cancelled_by_user().fuse() => {
+                        user_stopped_via_signal = true;
+                        terminal.kill(cx).map_err(|e| e.to_string())?;
+                        wait_for_exit.await;
                     }
                 }
-            };
+            }
+            None => {
+                futures::select! {
+                    _ = wait_for_exit.clone().fuse() => {},
+                    _ = event_stream.cancelled_by_user().fuse() => {
+                        user_stopped_via_signal = true;
+                        terminal.kill(cx).map_err(|e| e.to_string())?;
+                        wait_for_exit.await;
+                    }
+                }
+            }
+        };
 
-            // Check if user stopped - we check both:
-            // 1. The cancellation signal from RunningTurn::cancel (e.g. user pressed main Stop button)
-            // 2. The terminal's user_stopped flag (e.g. user clicked Stop on the terminal card)
-            // Note: user_stopped_via_signal is already set above if we detected cancellation in the select!
-            // but we also check was_cancelled_by_user() for cases where cancellation happened after wait_for_exit completed
-            let user_stopped_via_signal =
-                user_stopped_via_signal || event_stream.was_cancelled_by_user();
-            let user_stopped_via_terminal = terminal.was_stopped_by_user(cx).unwrap_or(false);
-            let user_stopped = user_stopped_via_signal || user_stopped_via_terminal;
-
-            let output = terminal.current_output(cx).map_err(|e| e.to_string())?;
-
-            Ok(process_content(
-                output,
-                &input.command,
-                timed_out,
-                user_stopped,
-            ))
-        })
+        // Check if user stopped - we check both:
+        // 1. The cancellation signal from RunningTurn::cancel (e.g. user pressed main Stop button)
+        // 2. The terminal's user_stopped flag (e.g. user clicked Stop on the terminal card)
+        // Note: user_stopped_via_signal is already set above if we detected cancellation in the select!
+        // but we also check was_cancelled_by_user() for cases where cancellation happened after wait_for_exit completed
+        let user_stopped_via_signal =
+            user_stopped_via_signal || event_stream.was_cancelled_by_user();
+        let user_stopped_via_terminal = terminal.was_stopped_by_user(cx).unwrap_or(false);
+        let user_stopped = user_stopped_via_signal || user_stopped_via_terminal;
+
+        let output = terminal.current_output(cx).map_err(|e| e.to_string())?;
+
+        Ok(process_content(
+            output,
+            &input.command,
+            timed_out,
+            user_stopped,
+        ))
     }
 }
 
@@ -319,6 +407,189 @@ fn working_dir(
     }
 }
 
+/// What a file looked like on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FileState {
+    mtime: MTime,
+    content_hash: Option<u64>,
+}
+
+/// A file that a command changed.
+struct ChangedFile {
+    display_path: String,
+    deleted: bool,
+}
+
+/// The files a command may change, along with what they looked like on disk before it ran.
+struct WatchedFiles {
+    thread: WeakEntity<Thread>,
+    command: SharedString,
+    fs: Arc<dyn Fs>,
+    paths: Vec<PathBuf>,
+    states: Vec<Option<FileState>>,
+}
+
+impl WatchedFiles {
+    /// Records the state of the files in the thread's working set and the project files matching
+    /// the input's `watch` glob, then starts attributing changes on disk to the command.
+    ///
+    /// Returns `None` when the tool isn't running in a thread.
+    async fn snapshot(
+        thread: &WeakEntity<Thread>,
+        project: &Entity<Project>,
+        input: &TerminalToolInput,
+        cx: &mut AsyncApp,
+    ) -> Result<Option<Self>> {
+        let Some(thread) = thread.upgrade() else {
+            return Ok(None);
+        };
+        let (fs, mut paths, matching_files) = cx.update(|cx| {
+            let fs = project.read(cx).fs().clone();
+            let paths = thread
+                .read(cx)
+                .working_set()
+                .iter()
+                .map(|(abs_path, _)| abs_path.to_path_buf())
+                .collect::<BTreeSet<_>>();
+            let matching_files = input
+                .watch
+                .as_deref()
+                .map(|glob| files_matching(glob, project, cx))
+                .transpose()?;
+            anyhow::Ok((fs, paths, matching_files))
+        })?;
+        if let Some(matching_files) = matching_files {
+            paths.extend(matching_files.await);
+        }
+
+        let paths = paths.into_iter().collect::<Vec<_>>();
+        let states = cx
+            .background_spawn(file_states(fs.clone(), paths.clone()))
+            .await;
+        let command = SharedString::from(input.command.clone());
+        thread.update(cx, |thread, cx| {
+            thread.action_log().update(cx, |action_log, _| {
+                action_log.start_command(command.clone())
+            });
+        });
+        Ok(Some(Self {
+            thread: thread.downgrade(),
+            command,
+            fs,
+            paths,
+            states,
+        }))
+    }
+
+    /// Stops attributing changes on disk to the command, and returns the files it changed once
+    /// the thread's working set and action log have caught up with them.
+    async fn finish(self, cx: &mut AsyncApp) -> Vec<ChangedFile> {
+        let new_states = cx
+            .background_spawn(file_states(self.fs.clone(), self.paths.clone()))
+            .await;
+        let changes = self
+            .paths
+            .into_iter()
+            .zip(self.states.into_iter().zip(new_states))
+            .filter(|(_, (old_state, new_state))| old_state != new_state)
+            .map(|(abs_path, (_, new_state))| (abs_path, new_state))
+            .collect::<Vec<_>>();
+
+        let command = self.command;
+        let Ok((changed_files, finish_command)) = self.thread.update(cx, |thread, cx| {
+            let project = thread.project().clone();
+            let mut changed_buffers = Vec::new();
+            let mut changed_files = Vec::new();
+            for (abs_path, new_state) in changes {
+                thread.record_file_changed_externally(
+                    &abs_path,
+                    new_state.map(|state| state.mtime),
+                    cx,
+                );
+                let project = project.read(cx);
+                let project_path = project.project_path_for_absolute_path(&abs_path, cx);
+                if let Some(buffer) = project_path
+                    .as_ref()
+                    .and_then(|project_path| project.get_open_buffer(project_path, cx))
+                {
+                    changed_buffers.push(buffer);
+                }
+                let display_path = project_path
+                    .and_then(|project_path| {
+                        let worktree = project.worktree_for_id(project_path.worktree_id, cx)?;
+                        let full_path = worktree.read(cx).root_name().join(&project_path.path);
+                        Some(full_path.display(project.path_style(cx)).to_string())
+                    })
+                    .unwrap_or_else(|| abs_path.display().to_string());
+                changed_files.push(ChangedFile {
+                    display_path,
+                    deleted: new_state.is_none(),
+                });
+            }
+            let finish_command = thread.action_log().update(cx, |action_log, cx| {
+                action_log.finish_command(&command, changed_buffers, cx)
+            });
+            (changed_files, finish_command)
+        }) else {
+            return Vec::new();
+        };
+        finish_command.await;
+        changed_files
+    }
+}
+
+/// The files in the project's worktrees that match the glob.
+fn files_matching(glob: &str, project: &Entity<Project>, cx: &App) -> Result<Task<Vec<PathBuf>>> {
+    let project = project.read(cx);
+    let path_matcher = PathMatcher::new([glob], project.path_style(cx))
+        .map_err(|error| anyhow!("Invalid `watch` glob: {error}"))?;
+    let snapshots = project
+        .worktrees(cx)
+        .map(|worktree| worktree.read(cx).snapshot())
+        .collect::<Vec<_>>();
+    Ok(cx.background_spawn(async move {
+        let mut paths = Vec::new();
+        for snapshot in snapshots {
+            for entry in snapshot.entries(false, 0) {
+                if entry.is_file() && path_matcher.is_match(&snapshot.root_name().join(&entry.path))
+                {
+                    paths.push(snapshot.absolutize(&entry.path));
+                }
+            }
+        }
+        paths
+    }))
+}
+
+/// The state of each file on disk, or `None` for the ones that don't exist.
+async fn file_states(fs: Arc<dyn Fs>, paths: Vec<PathBuf>) -> Vec<Option<FileState>> {
+    let mut states = Vec::with_capacity(paths.len());
+    for path in paths {
+        states.push(file_state(fs.as_ref(), &path).await);
+    }
+    states
+}
+
+async fn file_state(fs: &dyn Fs, path: &Path) -> Option<FileState> {
+    let metadata = fs.metadata(path).await.ok()??;
+    if metadata.is_dir {
+        return None;
+    }
+    let content_hash = if metadata.len <= HASHED_FILE_SIZE_LIMIT {
+        fs.load_bytes(path).await.ok().map(|content| {
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            hasher.finish()
+        })
+    } else {
+        None
+    };
+    Some(FileState {
+        mtime: metadata.mtime,
+        content_hash,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -330,6 +601,7 @@ mod tests {
                 .to_string(),
             cd: ".".to_string(),
             timeout_ms: None,
+            watch: None,
         };
 
         let title = format_initial_title(Ok(input));
@@ -389,6 +661,7 @@ mod tests {
                 command: cmd.to_string(),
                 cd: ".".to_string(),
                 timeout_ms: None,
+                watch: None,
             };
 
             let title = format_initial_title(Ok(input));
@@ -426,6 +699,7 @@ mod tests {
             command: "echo 'hello world'".to_string(),
             cd: ".".to_string(),
             timeout_ms: None,
+            watch: None,
         };
 
         let title = format_initial_title(Ok(input));
@@ -455,6 +729,7 @@ mod tests {
             command: long_command,
             cd: ".".to_string(),
             timeout_ms: None,
+            watch: None,
         };
 
         let title = format_initial_title(Ok(input));
@@ -78,7 +78,7 @@ impl ToolEditParser {
                 if partial.new_text.is_some() {
                     // new_text appeared, so old_text is done — emit everything.
                     let start = state.old_text_emitted_len.min(old_text.len());
-                    let chunk = old_text[start..].to_string();
+                    let chunk = text_chunk(old_text, start, old_text.len());
                     state.old_text_done = true;
                     state.old_text_emitted_len = old_text.len();
                     events.push(ToolEditEvent::OldTextChunk {
@@ -89,7 +89,7 @@ impl ToolEditParser {
                 } else {
                     let safe_end = safe_emit_end(old_text);
                     if safe_end > state.old_text_emitted_len {
-                        let chunk = old_text[state.old_text_emitted_len..safe_end].to_string();
+                        let chunk = text_chunk(old_text, state.old_text_emitted_len, safe_end);
                         state.old_text_emitted_len = safe_end;
                         events.push(ToolEditEvent::OldTextChunk {
                             edit_index: index,
@@ -106,7 +106,7 @@ impl ToolEditParser {
             {
                 let safe_end = safe_emit_end(new_text);
                 if safe_end > state.new_text_emitted_len {
-                    let chunk = new_text[state.new_text_emitted_len..safe_end].to_string();
+                    let chunk = text_chunk(new_text, state.new_text_emitted_len, safe_end);
                     state.new_text_emitted_len = safe_end;
                     events.push(ToolEditEvent::NewTextChunk {
                         edit_index: index,
@@ -129,7 +129,7 @@ impl ToolEditParser {
 
         let safe_end = safe_emit_end(content);
         if safe_end > self.content_emitted_len {
-            let chunk = content[self.content_emitted_len..safe_end].to_string();
+            let chunk = text_chunk(content, self.content_emitted_len, safe_end);
             self.content_emitted_len = safe_end;
             events.push(ToolEditEvent::ContentChunk { chunk });
         }
@@ -160,7 +160,7 @@ impl ToolEditParser {
 
             if !state.old_text_done {
                 let start = state.old_text_emitted_len.min(edit.old_text.len());
-                let chunk = edit.old_text[start..].to_string();
+                let chunk = text_chunk(&edit.old_text, start, edit.old_text.len());
                 state.old_text_done = true;
                 state.old_text_emitted_len = edit.old_text.len();
                 events.push(ToolEditEvent::OldTextChunk {
@@ -172,7 +172,7 @@ impl ToolEditParser {
 
             if !state.new_text_done {
                 let start = state.new_text_emitted_len.min(edit.new_text.len());
-                let chunk = edit.new_text[start..].to_string();
+                let chunk = text_chunk(&edit.new_text, start, edit.new_text.len());
                 state.new_text_done = true;
                 state.new_text_emitted_len = edit.new_text.len();
                 events.push(ToolEditEvent::NewTextChunk {
@@ -192,7 +192,7 @@ impl ToolEditParser {
 
         let start = self.content_emitted_len.min(content.len());
         if content.len() > start {
-            let chunk = content[start..].to_string();
+            let chunk = text_chunk(content, start, content.len());
             self.content_emitted_len = content.len();
             events.push(ToolEditEvent::ContentChunk { chunk });
         }
@@ -252,6 +252,18 @@ fn safe_emit_end(text: &str) -> usize {
     }
 }
 
+/// Returns `text[start..end]`, leaving out a byte order mark at the very start of the text.
+/// Buffers keep a file's BOM out of their text and write it back on save, so one copied by the
+/// model would otherwise fail to match, or end up in the file twice.
+fn text_chunk(text: &str, start: usize, end: usize) -> String {
+    let chunk = &text[start..end];
+    if start == 0 {
+        chunk.strip_prefix('\u{feff}').unwrap_or(chunk).to_string()
+    } else {
+        chunk.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,6 +276,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hell".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -277,6 +290,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello w".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -291,6 +305,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello world".into()),
             new_text: Some("good".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -312,6 +327,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello world".into()),
             new_text: Some("goodbye world".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -326,6 +342,7 @@ mod tests {
         let events = parser.finalize_edits(&[Edit {
             old_text: "hello world".into(),
             new_text: "goodbye world".into(),
+            delete: false,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -345,6 +362,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("first old".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -358,6 +376,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("first old".into()),
             new_text: Some("first new".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -380,10 +399,12 @@ mod tests {
             PartialEdit {
                 old_text: Some("first old".into()),
                 new_text: Some("first new".into()),
+                delete: None,
             },
             PartialEdit {
                 old_text: Some("second".into()),
                 new_text: None,
+                delete: None,
             },
         ]);
         assert_eq!(
@@ -407,10 +428,12 @@ mod tests {
             Edit {
                 old_text: "first old".into(),
                 new_text: "first new".into(),
+                delete: false,
             },
             Edit {
                 old_text: "second old".into(),
                 new_text: "second new".into(),
+                delete: false,
             },
         ]);
         assert_eq!(
@@ -465,6 +488,53 @@ mod tests {
         assert!(events.is_empty());
     }
 
+    #[test]
+    fn test_leading_bom_left_out() {
+        let mut parser = ToolEditParser::default();
+        let events = parser.push_edits(&[PartialEdit {
+            old_text: Some("\u{feff}fn main".into()),
+            new_text: None,
+            delete: None,
+        }]);
+        assert_eq!(
+            events.as_slice(),
+            &[ToolEditEvent::OldTextChunk {
+                edit_index: 0,
+                chunk: "fn main".into(),
+                done: false,
+            }]
+        );
+        let events = parser.finalize_edits(&[Edit {
+            old_text: "\u{feff}fn main() {}".into(),
+            new_text: "\u{feff}fn main() { \u{feff} }".into(),
+            delete: false,
+        }]);
+        assert_eq!(
+            events.as_slice(),
+            &[
+                ToolEditEvent::OldTextChunk {
+                    edit_index: 0,
+                    chunk: "() {}".into(),
+                    done: true,
+                },
+                ToolEditEvent::NewTextChunk {
+                    edit_index: 0,
+                    chunk: "fn main() { \u{feff} }".into(),
+                    done: true,
+                },
+            ]
+        );
+
+        let mut parser = ToolEditParser::default();
+        let events = parser.finalize_content("\u{feff}hello");
+        assert_eq!(
+            events.as_slice(),
+            &[ToolEditEvent::ContentChunk {
+                chunk: "hello".into(),
+            }]
+        );
+    }
+
     #[test]
     fn test_finalize_content_with_remaining() {
         let mut parser = ToolEditParser::default();
@@ -534,6 +604,7 @@ mod tests {
         let events = parser.finalize_edits(&[Edit {
             old_text: "old".into(),
             new_text: "new".into(),
+            delete: false,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -560,10 +631,12 @@ mod tests {
             Edit {
                 old_text: "first old".into(),
                 new_text: "first new".into(),
+                delete: false,
             },
             Edit {
                 old_text: "second old".into(),
                 new_text: "second new".into(),
+                delete: false,
             },
         ]);
         assert_eq!(
@@ -600,6 +673,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("same".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -614,6 +688,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("same".into()),
             new_text: None,
+            delete: None,
         }]);
         assert!(events.is_empty());
     }
@@ -626,6 +701,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: None,
             new_text: None,
+            delete: None,
         }]);
         assert!(events.is_empty());
 
@@ -633,6 +709,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("text".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -652,6 +729,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("".into()),
             new_text: Some("inserted".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -678,6 +756,7 @@ mod tests {
         parser.push_edits(&[PartialEdit {
             old_text: Some("a".into()),
             new_text: Some("A".into()),
+            delete: None,
         }]);
 
         // Second edit appears
@@ -685,10 +764,12 @@ mod tests {
             PartialEdit {
                 old_text: Some("a".into()),
                 new_text: Some("A".into()),
+                delete: None,
             },
             PartialEdit {
                 old_text: Some("b".into()),
                 new_text: Some("B".into()),
+                delete: None,
             },
         ]);
 
@@ -697,14 +778,17 @@ mod tests {
             PartialEdit {
                 old_text: Some("a".into()),
                 new_text: Some("A".into()),
+                delete: None,
             },
             PartialEdit {
                 old_text: Some("b".into()),
                 new_text: Some("B".into()),
+                delete: None,
             },
             PartialEdit {
                 old_text: Some("c".into()),
                 new_text: None,
+                delete: None,
             },
         ]);
 
@@ -730,14 +814,17 @@ mod tests {
             Edit {
                 old_text: "a".into(),
                 new_text: "A".into(),
+                delete: false,
             },
             Edit {
                 old_text: "b".into(),
                 new_text: "B".into(),
+                delete: false,
             },
             Edit {
                 old_text: "c".into(),
                 new_text: "C".into(),
+                delete: false,
             },
         ]);
         assert_eq!(
@@ -765,11 +852,13 @@ mod tests {
         parser.push_edits(&[PartialEdit {
             old_text: Some("partial".into()),
             new_text: None,
+            delete: None,
         }]);
 
         let events = parser.finalize_edits(&[Edit {
             old_text: "partial old text".into(),
             new_text: "replacement".into(),
+            delete: false,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -795,11 +884,13 @@ mod tests {
         parser.push_edits(&[PartialEdit {
             old_text: Some("old".into()),
             new_text: Some("partial".into()),
+            delete: None,
         }]);
 
         let events = parser.finalize_edits(&[Edit {
             old_text: "old".into(),
             new_text: "partial new text".into(),
+            delete: false,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -818,6 +909,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("stable".into()),
             new_text: Some("also stable".into()),
+            delete: None,
         }]);
         assert_eq!(events.len(), 2); // old done + new chunk
 
@@ -825,6 +917,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("stable".into()),
             new_text: Some("also stable".into()),
+            delete: None,
         }]);
         assert!(events.is_empty());
 
@@ -832,6 +925,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("stable".into()),
             new_text: Some("also stable".into()),
+            delete: None,
         }]);
         assert!(events.is_empty());
     }
@@ -846,6 +940,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello,\\".into()), // fixer closed incomplete \n as \\
             new_text: None,
+            delete: None,
         }]);
         // The trailing `\` is held back — only "hello," is emitted.
         assert_eq!(
@@ -863,6 +958,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello,\n".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -877,6 +973,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("hello,\nworld".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -895,6 +992,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("line1\nline2".into()),
             new_text: None,
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -908,6 +1006,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("line1\nline2\nline3".into()),
             new_text: Some("LINE1\n".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
@@ -928,6 +1027,7 @@ mod tests {
         let events = parser.push_edits(&[PartialEdit {
             old_text: Some("line1\nline2\nline3".into()),
             new_text: Some("LINE1\nLINE2\nLINE3".into()),
+            delete: None,
         }]);
         assert_eq!(
             events.as_slice(),
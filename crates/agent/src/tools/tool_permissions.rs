@@ -1,10 +1,12 @@
+use super::secret_scan::SecretFinding;
 use crate::{
-    Thread, ToolCallEventStream, ToolPermissionContext, ToolPermissionDecision,
+    EditGrantScope, Thread, ToolCallEventStream, ToolPermissionContext, ToolPermissionDecision,
     decide_permission_for_path,
 };
 use anyhow::{Result, anyhow};
+use collections::HashSet;
 use fs::Fs;
-use gpui::{App, Entity, Task, WeakEntity};
+use gpui::{App, AsyncApp, Entity, Task, WeakEntity};
 use project::{Project, ProjectPath};
 use settings::Settings;
 use std::ffi::OsStr;
@@ -33,6 +35,46 @@ pub enum ResolvedProjectPath {
     },
 }
 
+impl ResolvedProjectPath {
+    pub fn project_path(&self) -> &ProjectPath {
+        match self {
+            Self::Safe(project_path) | Self::SymlinkEscape { project_path, .. } => project_path,
+        }
+    }
+}
+
+/// The files and directories that the user allowed the agent to edit for the rest of a thread,
+/// by canonical path, so that a grant can't be reached through a different spelling of a path.
+#[derive(Debug, Default)]
+pub struct EditGrants {
+    files: HashSet<PathBuf>,
+    directories: Vec<PathBuf>,
+}
+
+impl EditGrants {
+    pub fn covers(&self, canonical_path: &Path) -> bool {
+        self.files.contains(canonical_path)
+            || self
+                .directories
+                .iter()
+                .any(|directory| canonical_path.starts_with(directory))
+    }
+
+    /// Records a grant for `canonical_path`, or for the directory containing it.
+    pub fn grant(&mut self, scope: EditGrantScope, canonical_path: &Path) {
+        match scope {
+            EditGrantScope::File => {
+                self.files.insert(canonical_path.to_path_buf());
+            }
+            EditGrantScope::Directory => {
+                if let Some(directory) = canonical_path.parent() {
+                    self.directories.push(directory.to_path_buf());
+                }
+            }
+        }
+    }
+}
+
 /// Asynchronously canonicalizes the absolute paths of all worktrees in a
 /// project using the provided `Fs`. The returned paths can be passed to
 /// [`resolve_project_path`] and related helpers so that they don't need to
@@ -240,18 +282,25 @@ pub fn authorize_symlink_access(
     event_stream: &ToolCallEventStream,
     cx: &mut App,
 ) -> Task<Result<()>> {
+    let (title, context) = symlink_access_prompt(tool_name, display_path, canonical_target);
+    event_stream.authorize(title, context, cx)
+}
+
+fn symlink_access_prompt(
+    tool_name: &str,
+    display_path: &str,
+    canonical_target: &Path,
+) -> (String, ToolPermissionContext) {
     let title = format!(
         "`{}` points outside the project (symlink to `{}`)",
         display_path,
         canonical_target.display(),
     );
-
     let context = ToolPermissionContext::symlink_target(
         tool_name,
         vec![canonical_target.display().to_string()],
     );
-
-    event_stream.authorize(title, context, cx)
+    (title, context)
 }
 
 /// Creates a single authorization prompt for multiple symlink escapes.
@@ -290,6 +339,64 @@ pub fn authorize_symlink_escapes(
     event_stream.authorize(title, context, cx)
 }
 
+/// Asks the user whether the agent may edit a file on top of its unsaved changes.
+///
+/// The user is only asked once per thread, and not at all if they enabled
+/// `always_allow_unsaved_edits` in their settings.
+pub fn authorize_unsaved_edit(
+    tool_name: &str,
+    path: &Path,
+    thread: &WeakEntity<Thread>,
+    event_stream: &ToolCallEventStream,
+    cx: &mut App,
+) -> Task<Result<()>> {
+    if agent_settings::AgentSettings::get_global(cx).always_allow_unsaved_edits {
+        return Task::ready(Ok(()));
+    }
+    let already_authorized = thread
+        .read_with(cx, |thread, _| thread.unsaved_edits_authorized)
+        .unwrap_or(false);
+    if already_authorized {
+        return Task::ready(Ok(()));
+    }
+
+    let display_path = path.to_string_lossy();
+    let title = format!("Edit `{display_path}` without saving its unsaved changes?");
+    let context = ToolPermissionContext::unsaved_changes(tool_name, vec![display_path.into()]);
+    let authorize = event_stream.authorize(title, context, cx);
+
+    let thread = thread.clone();
+    cx.spawn(async move |cx| {
+        authorize.await?;
+        thread.update(cx, |thread, _| thread.unsaved_edits_authorized = true)?;
+        Ok(())
+    })
+}
+
+/// Asks the user whether to keep an edit that appears to add credentials to a file.
+///
+/// Unlike other edit prompts, this one can't be skipped by settings or by an earlier answer,
+/// since each credential is a separate decision.
+pub fn authorize_credential_edit(
+    tool_name: &str,
+    path: &Path,
+    findings: &[SecretFinding],
+    event_stream: &ToolCallEventStream,
+    cx: &mut App,
+) -> Task<Result<()>> {
+    let display_path = path.to_string_lossy();
+    let findings = findings
+        .iter()
+        .map(|finding| format!("{} on line {}", finding.kind, finding.line))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let title = format!(
+        "This edit appears to contain a credential: {findings} of `{display_path}`. Keep it?"
+    );
+    let context = ToolPermissionContext::credential(tool_name, vec![display_path.into()]);
+    event_stream.authorize(title, context, cx)
+}
+
 /// Checks whether a path escapes the project via symlink, without creating
 /// an authorization task. Useful for pre-filtering paths before settings checks.
 pub fn path_has_symlink_escape(
@@ -360,6 +467,14 @@ pub fn collect_symlink_escapes<'a>(
 /// two sequential prompts for the same operation would degrade UX without
 /// meaningfully improving security, since the user must already approve the
 /// more specific symlink-escape prompt.
+///
+/// # Session grants
+///
+/// The prompts also offer to allow later edits to the same file, or to its
+/// directory, for the rest of the thread. These are recorded on the thread by
+/// canonical path and checked before prompting, but only after deny rules from
+/// settings, which they never override. Symlink-escape prompts only offer the
+/// single-file grant.
 pub fn authorize_file_edit(
     tool_name: &str,
     path: &Path,
@@ -404,46 +519,58 @@ pub fn authorize_file_edit(
             resolve_project_path(project, &path_owned, &canonical_roots, cx)
         });
 
-        if let Ok(ResolvedProjectPath::SymlinkEscape {
-            canonical_target, ..
-        }) = &resolved
-        {
-            let authorize = cx.update(|cx| {
-                authorize_symlink_access(
-                    &tool_name,
-                    &path_owned.to_string_lossy(),
-                    canonical_target,
-                    &event_stream,
-                    cx,
-                )
-            });
-            return authorize.await;
-        }
-
-        // Create-mode paths may not resolve yet, so also inspect the parent path
-        // for symlink escapes before applying settings-based allow decisions.
-        if resolved.is_err() {
-            if let Some(parent_path) = path_owned.parent() {
-                let parent_resolved = project_entity.read_with(cx, |project, cx| {
-                    resolve_project_path(project, parent_path, &canonical_roots, cx)
+        // The canonical target to show in the symlink prompt, and the canonical path of the file
+        // itself. Create-mode paths may not resolve yet, so also inspect the parent path for
+        // symlink escapes before applying settings-based allow decisions.
+        let symlink_escape = match &resolved {
+            Ok(ResolvedProjectPath::SymlinkEscape {
+                canonical_target, ..
+            }) => Some((canonical_target.clone(), canonical_target.clone())),
+            Ok(ResolvedProjectPath::Safe(_)) => None,
+            Err(_) => {
+                let parent_resolved = path_owned.parent().map(|parent_path| {
+                    project_entity.read_with(cx, |project, cx| {
+                        resolve_project_path(project, parent_path, &canonical_roots, cx)
+                    })
                 });
-
-                if let Ok(ResolvedProjectPath::SymlinkEscape {
-                    canonical_target, ..
-                }) = &parent_resolved
-                {
-                    let authorize = cx.update(|cx| {
-                        authorize_symlink_access(
-                            &tool_name,
-                            &path_owned.to_string_lossy(),
-                            canonical_target,
-                            &event_stream,
-                            cx,
-                        )
-                    });
-                    return authorize.await;
+                match parent_resolved {
+                    Some(Ok(ResolvedProjectPath::SymlinkEscape {
+                        canonical_target, ..
+                    })) => {
+                        let canonical_path = match path_owned.file_name() {
+                            Some(file_name) => canonical_target.join(file_name),
+                            None => canonical_target.clone(),
+                        };
+                        Some((canonical_target, canonical_path))
+                    }
+                    _ => None,
                 }
             }
+        };
+
+        let canonical_path = match &symlink_escape {
+            Some((_, canonical_path)) => Some(canonical_path.clone()),
+            None => canonical_edit_path(&project_entity, &path_owned, fs.as_ref(), cx).await,
+        };
+        if let Some(canonical_path) = &canonical_path
+            && thread.read_with(cx, |thread, _| thread.edit_grants.covers(canonical_path))?
+        {
+            return Ok(());
+        }
+
+        if let Some((canonical_target, _)) = &symlink_escape {
+            let (title, context) =
+                symlink_access_prompt(&tool_name, &path_owned.to_string_lossy(), canonical_target);
+            return authorize_with_edit_grants(
+                title,
+                context,
+                &[EditGrantScope::File],
+                canonical_path.as_deref(),
+                &thread,
+                &event_stream,
+                cx,
+            )
+            .await;
         }
 
         let explicitly_allowed = matches!(decision, ToolPermissionDecision::Allow);
@@ -460,54 +587,73 @@ pub fn authorize_file_edit(
             return Ok(());
         }
 
-        match settings_kind {
+        let title = match settings_kind {
             Some(SensitiveSettingsKind::Local) => {
-                let authorize = cx.update(|cx| {
-                    let context = ToolPermissionContext::new(
-                        &tool_name,
-                        vec![path_owned.to_string_lossy().to_string()],
-                    );
-                    event_stream.authorize(
-                        format!("{} (local settings)", display_description),
-                        context,
-                        cx,
-                    )
-                });
-                return authorize.await;
+                format!("{} (local settings)", display_description)
             }
-            Some(SensitiveSettingsKind::Global) => {
-                let authorize = cx.update(|cx| {
-                    let context = ToolPermissionContext::new(
-                        &tool_name,
-                        vec![path_owned.to_string_lossy().to_string()],
-                    );
-                    event_stream.authorize(
-                        format!("{} (settings)", display_description),
-                        context,
-                        cx,
-                    )
-                });
-                return authorize.await;
-            }
-            None => {}
-        }
-
-        match resolved {
-            Ok(_) => Ok(()),
-            Err(_) => {
-                let authorize = cx.update(|cx| {
-                    let context = ToolPermissionContext::new(
-                        &tool_name,
-                        vec![path_owned.to_string_lossy().to_string()],
-                    );
-                    event_stream.authorize(&display_description, context, cx)
-                });
-                authorize.await
-            }
-        }
+            Some(SensitiveSettingsKind::Global) => format!("{} (settings)", display_description),
+            None if resolved.is_ok() => return Ok(()),
+            None => display_description,
+        };
+        let context =
+            ToolPermissionContext::new(&tool_name, vec![path_owned.to_string_lossy().to_string()]);
+        authorize_with_edit_grants(
+            title,
+            context,
+            &[EditGrantScope::File, EditGrantScope::Directory],
+            canonical_path.as_deref(),
+            &thread,
+            &event_stream,
+            cx,
+        )
+        .await
     })
 }
 
+/// The canonical absolute path of a file that's about to be edited, which may not exist yet.
+async fn canonical_edit_path(
+    project: &Entity<Project>,
+    path: &Path,
+    fs: &dyn Fs,
+    cx: &AsyncApp,
+) -> Option<PathBuf> {
+    let abs_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        project.read_with(cx, |project, cx| {
+            let project_path = project.find_project_path(path, cx)?;
+            project.absolute_path(&project_path, cx)
+        })?
+    };
+    canonicalize_with_ancestors(&abs_path, fs).await
+}
+
+/// Prompts for an edit, offering `edit_grants` when the edited file's canonical path is known,
+/// and records the grant the user picks on the thread.
+async fn authorize_with_edit_grants(
+    title: String,
+    context: ToolPermissionContext,
+    edit_grants: &[EditGrantScope],
+    canonical_path: Option<&Path>,
+    thread: &WeakEntity<Thread>,
+    event_stream: &ToolCallEventStream,
+    cx: &mut AsyncApp,
+) -> Result<()> {
+    let context = match canonical_path {
+        Some(_) => context.with_edit_grants(edit_grants.to_vec()),
+        None => context,
+    };
+    let authorize = cx.update(|cx| event_stream.authorize_edit(title, context, cx));
+    if let Some(scope) = authorize.await?
+        && let Some(canonical_path) = canonical_path
+    {
+        thread.update(cx, |thread, _| {
+            thread.edit_grants.grant(scope, canonical_path)
+        })?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
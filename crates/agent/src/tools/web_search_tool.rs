@@ -59,6 +59,14 @@ impl AgentTool for WebSearchTool {
         acp::ToolKind::Fetch
     }
 
+    fn preview_fields() -> &'static [&'static str] {
+        &["query"]
+    }
+
+    fn is_read_only() -> bool {
+        true
+    }
+
     fn initial_title(
         &self,
         _input: Result<Self::Input, serde_json::Value>,
@@ -0,0 +1,375 @@
+use acp_thread::UserMessageId;
+use agent_settings::ModelPrice;
+use collections::HashMap;
+use language_model::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Prices for well-known models, in US dollars per million tokens, matched against the start of
+/// the model's ID. The longest match wins, so dated and suffixed IDs get their family's price.
+const BUILT_IN_PRICES: &[(&str, ModelPrice)] = &[
+    (
+        "claude-opus-4",
+        ModelPrice::new(15., 75.).with_cache(18.75, 1.5),
+    ),
+    (
+        "claude-opus-4-5",
+        ModelPrice::new(5., 25.).with_cache(6.25, 0.5),
+    ),
+    (
+        "claude-sonnet-4",
+        ModelPrice::new(3., 15.).with_cache(3.75, 0.3),
+    ),
+    (
+        "claude-3-7-sonnet",
+        ModelPrice::new(3., 15.).with_cache(3.75, 0.3),
+    ),
+    (
+        "claude-3-5-sonnet",
+        ModelPrice::new(3., 15.).with_cache(3.75, 0.3),
+    ),
+    (
+        "claude-haiku-4-5",
+        ModelPrice::new(1., 5.).with_cache(1.25, 0.1),
+    ),
+    (
+        "claude-3-5-haiku",
+        ModelPrice::new(0.8, 4.).with_cache(1., 0.08),
+    ),
+    ("gpt-4o", ModelPrice::new(2.5, 10.).with_cache(2.5, 1.25)),
+    (
+        "gpt-4o-mini",
+        ModelPrice::new(0.15, 0.6).with_cache(0.15, 0.075),
+    ),
+    ("gpt-4.1", ModelPrice::new(2., 8.).with_cache(2., 0.5)),
+    (
+        "gpt-4.1-mini",
+        ModelPrice::new(0.4, 1.6).with_cache(0.4, 0.1),
+    ),
+    ("gpt-5", ModelPrice::new(1.25, 10.).with_cache(1.25, 0.125)),
+    (
+        "gpt-5-mini",
+        ModelPrice::new(0.25, 2.).with_cache(0.25, 0.025),
+    ),
+    ("o3", ModelPrice::new(2., 8.).with_cache(2., 0.5)),
+    ("o4-mini", ModelPrice::new(1.1, 4.4).with_cache(1.1, 0.275)),
+    (
+        "gemini-2.5-pro",
+        ModelPrice::new(1.25, 10.).with_cache(1.25, 0.31),
+    ),
+    (
+        "gemini-2.5-flash",
+        ModelPrice::new(0.3, 2.5).with_cache(0.3, 0.075),
+    ),
+];
+
+/// The price of a model, from the user's `model_prices` setting if it has one, keyed by
+/// `provider/model` or by the model's ID, and otherwise from the built-in prices.
+pub fn model_price(
+    overrides: &HashMap<String, ModelPrice>,
+    provider: &str,
+    model: &str,
+) -> Option<ModelPrice> {
+    overrides
+        .get(&format!("{provider}/{model}"))
+        .or_else(|| overrides.get(model))
+        .copied()
+        .or_else(|| {
+            BUILT_IN_PRICES
+                .iter()
+                .filter(|(prefix, _)| model.starts_with(prefix))
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(_, price)| *price)
+        })
+}
+
+/// One completion request sent to a model, and the tokens it reported using.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RequestUsage {
+    /// The user message whose turn sent the request.
+    pub turn: UserMessageId,
+    pub provider: Arc<str>,
+    pub model: Arc<str>,
+    /// `None` until the model reports usage, which some never do.
+    pub usage: Option<TokenUsage>,
+}
+
+impl RequestUsage {
+    pub fn cost(&self, prices: &HashMap<String, ModelPrice>) -> Option<f64> {
+        let price = model_price(prices, &self.provider, &self.model)?;
+        Some(price.cost(self.usage.as_ref()?))
+    }
+}
+
+/// The requests of a turn, or of a whole thread, added up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UsageSummary {
+    pub requests: usize,
+    /// `None` when none of the requests reported usage.
+    pub usage: Option<TokenUsage>,
+    /// `None` when none of the requests could be priced.
+    pub cost: Option<f64>,
+    /// How many of the requests didn't report usage, or are for a model without a price, and
+    /// so are missing from the cost.
+    pub unpriced_requests: usize,
+}
+
+impl UsageSummary {
+    fn add(&mut self, request: &RequestUsage, prices: &HashMap<String, ModelPrice>) {
+        self.requests += 1;
+        if let Some(usage) = request.usage {
+            self.usage = Some(self.usage.unwrap_or_default() + usage);
+        }
+        match request.cost(prices) {
+            Some(cost) => self.cost = Some(self.cost.unwrap_or_default() + cost),
+            None => self.unpriced_requests += 1,
+        }
+    }
+}
+
+/// The tokens used by every request a thread sent, and what they're estimated to have cost.
+///
+/// Requests are kept when the thread is truncated, since the tokens they used were still paid
+/// for. It's saved with the thread, so the running total survives reopening it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageLedger {
+    requests: Vec<RequestUsage>,
+}
+
+impl UsageLedger {
+    pub fn requests(&self) -> &[RequestUsage] {
+        &self.requests
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    pub fn start_request(&mut self, turn: UserMessageId, provider: Arc<str>, model: Arc<str>) {
+        self.requests.push(RequestUsage {
+            turn,
+            provider,
+            model,
+            usage: None,
+        });
+    }
+
+    /// Records the usage reported for the latest request. Models report their usage so far as a
+    /// request streams, so this replaces what was reported before.
+    pub fn update_request(&mut self, usage: TokenUsage) {
+        if let Some(request) = self.requests.last_mut() {
+            request.usage = Some(usage);
+        }
+    }
+
+    /// Forgets the latest request if it failed before reporting any usage, since requests that
+    /// fail that early usually aren't charged for.
+    pub fn discard_failed_request(&mut self) {
+        if self
+            .requests
+            .last()
+            .is_some_and(|request| request.usage.is_none())
+        {
+            self.requests.pop();
+        }
+    }
+
+    pub fn total(&self, prices: &HashMap<String, ModelPrice>) -> UsageSummary {
+        let mut summary = UsageSummary::default();
+        for request in &self.requests {
+            summary.add(request, prices);
+        }
+        summary
+    }
+
+    /// Each turn's requests added up, in the order the turns were taken.
+    pub fn by_turn(
+        &self,
+        prices: &HashMap<String, ModelPrice>,
+    ) -> Vec<(UserMessageId, UsageSummary)> {
+        let mut turns: Vec<(UserMessageId, UsageSummary)> = Vec::new();
+        for request in &self.requests {
+            match turns.last_mut() {
+                Some((turn, summary)) if *turn == request.turn => summary.add(request, prices),
+                _ => {
+                    let mut summary = UsageSummary::default();
+                    summary.add(request, prices);
+                    turns.push((request.turn.clone(), summary));
+                }
+            }
+        }
+        turns
+    }
+
+    /// Whether the thread's estimated cost is past `budget`.
+    pub fn exceeds_budget(&self, budget: f64, prices: &HashMap<String, ModelPrice>) -> bool {
+        self.total(prices).cost.is_some_and(|cost| cost > budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u64, output: u64, cache_write: u64, cache_read: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens: input,
+            output_tokens: output,
+            cache_creation_input_tokens: cache_write,
+            cache_read_input_tokens: cache_read,
+        }
+    }
+
+    fn request(ledger: &mut UsageLedger, turn: &UserMessageId, model: &str, usage: TokenUsage) {
+        ledger.start_request(turn.clone(), "anthropic".into(), model.into());
+        ledger.update_request(usage);
+    }
+
+    #[test]
+    fn test_usage_accumulates_per_turn_and_in_total() {
+        let prices = HashMap::default();
+        let first_turn = UserMessageId::new();
+        let second_turn = UserMessageId::new();
+        let mut ledger = UsageLedger::default();
+
+        // Usage is reported cumulatively while a request streams, so only the last update counts.
+        ledger.start_request(
+            first_turn.clone(),
+            "anthropic".into(),
+            "claude-sonnet-4-5".into(),
+        );
+        ledger.update_request(usage(1000, 10, 0, 0));
+        ledger.update_request(usage(1000, 200, 0, 0));
+        request(
+            &mut ledger,
+            &first_turn,
+            "claude-sonnet-4-5",
+            usage(200, 100, 0, 1000),
+        );
+        request(
+            &mut ledger,
+            &second_turn,
+            "claude-sonnet-4-5",
+            usage(500, 300, 2000, 0),
+        );
+
+        let turns = ledger.by_turn(&prices);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].0, first_turn);
+        assert_eq!(turns[0].1.requests, 2);
+        assert_eq!(turns[0].1.usage, Some(usage(1200, 300, 0, 1000)));
+        assert_eq!(turns[1].0, second_turn);
+        assert_eq!(turns[1].1.usage, Some(usage(500, 300, 2000, 0)));
+
+        let total = ledger.total(&prices);
+        assert_eq!(total.requests, 3);
+        assert_eq!(total.usage, Some(usage(1700, 600, 2000, 1000)));
+        assert_eq!(total.unpriced_requests, 0);
+        // 1700 * $3 + 600 * $15 + 2000 * $3.75 + 1000 * $0.30, per million tokens.
+        let cost = total.cost.unwrap();
+        assert!((cost - 0.0219).abs() < 1e-9, "{cost}");
+    }
+
+    #[test]
+    fn test_missing_usage_is_unavailable_rather_than_zero() {
+        let prices = HashMap::default();
+        let turn = UserMessageId::new();
+        let mut ledger = UsageLedger::default();
+        ledger.start_request(turn.clone(), "ollama".into(), "llama3".into());
+        let total = ledger.total(&prices);
+        assert_eq!(total.usage, None);
+        assert_eq!(total.cost, None);
+        assert_eq!(total.unpriced_requests, 1);
+
+        // A model without a price still reports its tokens, but no cost.
+        ledger.update_request(usage(100, 10, 0, 0));
+        let total = ledger.total(&prices);
+        assert_eq!(total.usage, Some(usage(100, 10, 0, 0)));
+        assert_eq!(total.cost, None);
+
+        // Failed requests are only forgotten when they didn't get as far as reporting usage.
+        ledger.discard_failed_request();
+        assert_eq!(ledger.requests().len(), 1);
+        ledger.start_request(turn, "ollama".into(), "llama3".into());
+        ledger.discard_failed_request();
+        assert_eq!(ledger.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_price_lookup() {
+        let mut overrides = HashMap::default();
+        assert_eq!(
+            model_price(&overrides, "anthropic", "claude-opus-4-5-20251101"),
+            Some(ModelPrice::new(5., 25.).with_cache(6.25, 0.5))
+        );
+        assert_eq!(
+            model_price(&overrides, "anthropic", "claude-opus-4-1"),
+            Some(ModelPrice::new(15., 75.).with_cache(18.75, 1.5))
+        );
+        assert_eq!(
+            model_price(&overrides, "openai", "gpt-4o-mini-2024-07-18").map(|price| price.input),
+            Some(0.15)
+        );
+        assert_eq!(model_price(&overrides, "ollama", "llama3"), None);
+
+        // Settings take precedence, with a `provider/model` key over a bare model ID.
+        overrides.insert("llama3".to_string(), ModelPrice::new(0.1, 0.2));
+        overrides.insert("gpt-4o".to_string(), ModelPrice::new(1., 2.));
+        overrides.insert("openrouter/gpt-4o".to_string(), ModelPrice::new(3., 4.));
+        assert_eq!(
+            model_price(&overrides, "ollama", "llama3"),
+            Some(ModelPrice::new(0.1, 0.2))
+        );
+        assert_eq!(
+            model_price(&overrides, "openai", "gpt-4o"),
+            Some(ModelPrice::new(1., 2.))
+        );
+        assert_eq!(
+            model_price(&overrides, "openrouter", "gpt-4o"),
+            Some(ModelPrice::new(3., 4.))
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let prices = HashMap::default();
+        let turn = UserMessageId::new();
+        let mut ledger = UsageLedger::default();
+        assert!(!ledger.exceeds_budget(0.5, &prices));
+
+        // $0.30 of output tokens.
+        request(
+            &mut ledger,
+            &turn,
+            "claude-sonnet-4",
+            usage(0, 20_000, 0, 0),
+        );
+        assert!(!ledger.exceeds_budget(0.5, &prices));
+        request(
+            &mut ledger,
+            &turn,
+            "claude-sonnet-4",
+            usage(0, 20_000, 0, 0),
+        );
+        assert!(ledger.exceeds_budget(0.5, &prices));
+
+        // Requests that can't be priced don't count towards the budget.
+        let mut ledger = UsageLedger::default();
+        ledger.start_request(turn, "ollama".into(), "llama3".into());
+        ledger.update_request(usage(10_000_000, 10_000_000, 0, 0));
+        assert!(!ledger.exceeds_budget(0.5, &prices));
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let turn = UserMessageId::new();
+        let mut ledger = UsageLedger::default();
+        request(&mut ledger, &turn, "claude-sonnet-4", usage(10, 20, 30, 40));
+        ledger.start_request(turn, "ollama".into(), "llama3".into());
+
+        let json = serde_json::to_string(&ledger).unwrap();
+        let restored = serde_json::from_str::<UsageLedger>(&json).unwrap();
+        assert_eq!(restored, ledger);
+        assert_eq!(restored.requests()[1].usage, None);
+    }
+}
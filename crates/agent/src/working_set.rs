@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use fs::MTime;
+use project::ProjectPath;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The files a thread has read or edited, keyed by absolute path.
+///
+/// Besides listing them for the model and the user, the working set remembers each file's mtime
+/// as of the agent's last read or edit, which is how edits to files that changed since then are
+/// caught. Files the agent only looked at the outline of are listed too, but don't get an mtime,
+/// since the agent hasn't seen their contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WorkingSet {
+    files: BTreeMap<PathBuf, TouchedFile>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TouchedFile {
+    pub last_read_at: Option<DateTime<Utc>>,
+    pub last_edited_at: Option<DateTime<Utc>>,
+    /// When the agent last looked at the file's outline with the `code_symbols` tool.
+    #[serde(default)]
+    pub last_outlined_at: Option<DateTime<Utc>>,
+    /// The file's mtime after the agent last read or edited it, if it was known.
+    pub mtime: Option<MTime>,
+}
+
+impl TouchedFile {
+    pub fn was_read(&self) -> bool {
+        self.last_read_at.is_some()
+    }
+
+    pub fn was_edited(&self) -> bool {
+        self.last_edited_at.is_some()
+    }
+
+    pub fn last_touched_at(&self) -> Option<DateTime<Utc>> {
+        self.last_read_at
+            .max(self.last_edited_at)
+            .max(self.last_outlined_at)
+    }
+
+    /// Whether the file changed on disk since the agent last saw it, given its current mtime.
+    /// A file that no longer exists has no mtime, and counts as changed.
+    pub fn has_changed_since(&self, current_mtime: Option<MTime>) -> bool {
+        self.mtime.is_some_and(|mtime| current_mtime != Some(mtime))
+    }
+}
+
+impl WorkingSet {
+    pub fn record_read(&mut self, abs_path: PathBuf, mtime: Option<MTime>, now: DateTime<Utc>) {
+        let file = self.touch(abs_path, mtime);
+        file.last_read_at = Some(now);
+    }
+
+    pub fn record_edit(&mut self, abs_path: PathBuf, mtime: Option<MTime>, now: DateTime<Utc>) {
+        let file = self.touch(abs_path, mtime);
+        file.last_edited_at = Some(now);
+    }
+
+    /// Records that the agent looked at the file's outline. Unlike a read, this leaves the
+    /// file's mtime alone, so it doesn't count as having seen the current contents.
+    pub fn record_outline(&mut self, abs_path: PathBuf, now: DateTime<Utc>) {
+        let file = self.touch(abs_path, None);
+        file.last_outlined_at = Some(now);
+    }
+
+    /// Updates the mtime of a file in the working set after something the agent ran changed it
+    /// on disk, and it was told so. Files it hasn't seen are left out.
+    pub fn record_external_change(&mut self, abs_path: &Path, mtime: Option<MTime>) {
+        if let Some(file) = self.files.get_mut(abs_path) {
+            file.mtime = mtime;
+        }
+    }
+
+    fn touch(&mut self, abs_path: PathBuf, mtime: Option<MTime>) -> &mut TouchedFile {
+        let file = self.files.entry(abs_path).or_insert(TouchedFile {
+            last_read_at: None,
+            last_edited_at: None,
+            last_outlined_at: None,
+            mtime: None,
+        });
+        // Keep the previous mtime when the new one isn't known, so that a later edit is still
+        // checked against it.
+        if mtime.is_some() {
+            file.mtime = mtime;
+        }
+        file
+    }
+
+    pub fn get(&self, abs_path: &Path) -> Option<&TouchedFile> {
+        self.files.get(abs_path)
+    }
+
+    /// The file's mtime as of the agent's last read or edit.
+    pub fn last_known_mtime(&self, abs_path: &Path) -> Option<MTime> {
+        self.files.get(abs_path)?.mtime
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &TouchedFile)> {
+        self.files.iter().map(|(path, file)| (path.as_path(), file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// A file in a thread's [`WorkingSet`], as returned by [`crate::Thread::touched_files`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TouchedFileStatus {
+    pub abs_path: PathBuf,
+    /// Set when the file is inside one of the project's worktrees.
+    pub project_path: Option<ProjectPath>,
+    pub file: TouchedFile,
+    /// Whether the file changed on disk since the agent last read or edited it.
+    pub is_stale: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    #[test]
+    fn test_read_then_edit_is_one_entry() {
+        let read_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let edited_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 5, 0).unwrap();
+        let path = PathBuf::from("/root/src/main.rs");
+
+        let mut working_set = WorkingSet::default();
+        working_set.record_read(
+            path.clone(),
+            Some(MTime::from_seconds_and_nanos(1, 0)),
+            read_at,
+        );
+        working_set.record_edit(path.clone(), None, edited_at);
+
+        assert_eq!(working_set.len(), 1);
+        let file = working_set.get(&path).unwrap();
+        assert!(file.was_read() && file.was_edited());
+        assert_eq!(file.last_touched_at(), Some(edited_at));
+        // An edit without a known mtime keeps the one from the read.
+        assert_eq!(
+            working_set.last_known_mtime(&path),
+            Some(MTime::from_seconds_and_nanos(1, 0))
+        );
+    }
+
+    #[test]
+    fn test_outline_does_not_count_as_read() {
+        let read_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let outlined_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 5, 0).unwrap();
+        let outlined = PathBuf::from("/root/src/lib.rs");
+        let read = PathBuf::from("/root/src/main.rs");
+
+        let mut working_set = WorkingSet::default();
+        working_set.record_outline(outlined.clone(), outlined_at);
+        let file = working_set.get(&outlined).unwrap();
+        assert!(!file.was_read());
+        assert_eq!(file.last_touched_at(), Some(outlined_at));
+        assert_eq!(working_set.last_known_mtime(&outlined), None);
+
+        // Looking at the outline of a file that was read keeps the mtime from the read.
+        let mtime = MTime::from_seconds_and_nanos(1, 0);
+        working_set.record_read(read.clone(), Some(mtime), read_at);
+        working_set.record_outline(read.clone(), outlined_at);
+        assert!(working_set.get(&read).unwrap().was_read());
+        assert_eq!(working_set.last_known_mtime(&read), Some(mtime));
+    }
+
+    #[test]
+    fn test_has_changed_since() {
+        let mtime = MTime::from_seconds_and_nanos(1, 0);
+        let file = TouchedFile {
+            last_read_at: None,
+            last_edited_at: None,
+            last_outlined_at: None,
+            mtime: Some(mtime),
+        };
+        assert!(!file.has_changed_since(Some(mtime)));
+        assert!(file.has_changed_since(Some(MTime::from_seconds_and_nanos(2, 0))));
+        assert!(file.has_changed_since(None));
+
+        let file = TouchedFile {
+            mtime: None,
+            ..file
+        };
+        assert!(!file.has_changed_since(None));
+    }
+}
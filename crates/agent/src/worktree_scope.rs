@@ -0,0 +1,135 @@
+use gpui::{App, SharedString};
+use itertools::Itertools as _;
+use project::{Project, ProjectPath, WorktreeId};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The project roots a thread's tools are confined to. Threads without a scope can use every
+/// root in the project.
+///
+/// Roots are kept by path, since worktree ids don't outlive the project. A root that isn't open
+/// stays in the scope without matching anything, so closing it never widens what the agent can
+/// reach.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorktreeScope {
+    roots: Vec<ScopedRoot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ScopedRoot {
+    abs_path: Arc<Path>,
+    root_name: SharedString,
+    #[serde(skip)]
+    worktree_id: Option<WorktreeId>,
+}
+
+impl WorktreeScope {
+    /// The scope covering `worktree_ids`, or `None` when none of them are in the project.
+    pub fn new(worktree_ids: &[WorktreeId], project: &Project, cx: &App) -> Option<Self> {
+        let roots = project
+            .worktrees(cx)
+            .filter_map(|worktree| {
+                let worktree = worktree.read(cx);
+                worktree_ids.contains(&worktree.id()).then(|| ScopedRoot {
+                    abs_path: worktree.abs_path(),
+                    root_name: worktree.root_name_str().to_string().into(),
+                    worktree_id: Some(worktree.id()),
+                })
+            })
+            .collect::<Vec<_>>();
+        (!roots.is_empty()).then_some(Self { roots })
+    }
+
+    /// Matches the roots up with the worktrees that are open in `project` now.
+    pub fn resolve(&mut self, project: &Project, cx: &App) {
+        for root in &mut self.roots {
+            root.worktree_id = project
+                .worktrees(cx)
+                .find(|worktree| worktree.read(cx).abs_path() == root.abs_path)
+                .map(|worktree| worktree.read(cx).id());
+        }
+    }
+
+    pub fn contains(&self, worktree_id: WorktreeId) -> bool {
+        self.roots
+            .iter()
+            .any(|root| root.worktree_id == Some(worktree_id))
+    }
+
+    pub fn worktree_ids(&self) -> impl Iterator<Item = WorktreeId> + '_ {
+        self.roots.iter().filter_map(|root| root.worktree_id)
+    }
+
+    pub fn root_names(&self) -> impl Iterator<Item = &SharedString> {
+        self.roots.iter().map(|root| &root.root_name)
+    }
+
+    /// The roots' names, for showing the scope to the user and the model.
+    pub fn label(&self) -> String {
+        self.root_names().join(", ")
+    }
+
+    /// Fails with an error for the model when `project_path`, which the model asked for as
+    /// `path`, is in a root outside the scope.
+    pub fn check(&self, project_path: &ProjectPath, path: &Path) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.contains(project_path.worktree_id),
+            "{} is outside the agent's allowed scope ({})",
+            path.display(),
+            self.label()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs::FakeFs;
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_scope_is_restored_by_path(cx: &mut TestAppContext) {
+        cx.update(|cx| cx.set_global(SettingsStore::test(cx)));
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/frontend"), json!({})).await;
+        fs.insert_tree(path!("/backend"), json!({})).await;
+        let project = Project::test(
+            fs.clone(),
+            [path!("/frontend").as_ref(), path!("/backend").as_ref()],
+            cx,
+        )
+        .await;
+        let (frontend, backend) = project.read_with(cx, |project, cx| {
+            let ids = project
+                .worktrees(cx)
+                .map(|worktree| worktree.read(cx).id())
+                .collect::<Vec<_>>();
+            (ids[0], ids[1])
+        });
+        let scope = project
+            .read_with(cx, |project, cx| {
+                WorktreeScope::new(&[frontend], project, cx)
+            })
+            .unwrap();
+        assert_eq!(scope.label(), "frontend");
+
+        // Worktree ids aren't saved, so a restored scope matches nothing until it's resolved.
+        let mut restored: WorktreeScope =
+            serde_json::from_value(serde_json::to_value(&scope).unwrap()).unwrap();
+        assert!(!restored.contains(frontend));
+        project.read_with(cx, |project, cx| restored.resolve(project, cx));
+        assert!(restored.contains(frontend));
+        assert!(!restored.contains(backend));
+
+        // Closing the root leaves the scope matching nothing rather than everything.
+        project.update(cx, |project, cx| project.remove_worktree(frontend, cx));
+        project.read_with(cx, |project, cx| restored.resolve(project, cx));
+        assert_eq!(restored.worktree_ids().count(), 0);
+        assert!(!restored.contains(backend));
+    }
+}
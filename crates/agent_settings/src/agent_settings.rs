@@ -4,16 +4,17 @@ use std::path::{Component, Path};
 use std::sync::{Arc, LazyLock};
 
 use agent_client_protocol::ModelId;
-use collections::{HashSet, IndexMap};
+use collections::{HashMap, HashSet, IndexMap};
 use gpui::{App, Pixels, px};
-use language_model::LanguageModel;
+use language_model::{LanguageModel, TokenUsage};
 use project::DisableAiSettings;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{
-    DefaultAgentView, DockPosition, LanguageModelParameters, LanguageModelSelection,
-    NotifyWhenAgentWaiting, RegisterSetting, Settings, ToolPermissionMode,
+    DefaultAgentView, DockPosition, EditSyntaxCheck, LanguageModelParameters,
+    LanguageModelSelection, NotifyWhenAgentWaiting, RegisterSetting, Settings, ToolPermissionMode,
 };
+use util::paths::{PathMatcher, PathStyle};
 
 pub use crate::agent_profile::*;
 
@@ -50,6 +51,23 @@ pub struct AgentSettings {
     pub use_modifier_to_send: bool,
     pub message_editor_min_lines: usize,
     pub show_turn_stats: bool,
+    pub verify_edits: bool,
+    pub edit_syntax_check: EditSyntaxCheck,
+    pub edit_secret_scan: EditSecretScan,
+    pub always_allow_unsaved_edits: bool,
+    pub edits_require_approval: bool,
+    pub edit_file_max_size: u64,
+    pub edit_file_chunk_size: usize,
+    pub edit_match_min_similarity: f32,
+    pub edit_match_exact_similarity: f32,
+    pub edit_tidy_deletions: bool,
+    pub max_concurrent_tool_calls: usize,
+    /// Files describing a project's conventions, which the agent is told about.
+    pub instruction_files: PathMatcher,
+    pub fetch: FetchSettings,
+    /// Prices from the user's settings, keyed by model ID or by `provider/model`.
+    pub model_prices: HashMap<String, ModelPrice>,
+    pub thread_cost_budget: Option<f64>,
     pub tool_permissions: ToolPermissions,
 }
 
@@ -191,6 +209,71 @@ impl CompiledRegex {
     }
 }
 
+/// The user's configuration for finding credentials in the agent's edits.
+#[derive(Clone, Debug, Default)]
+pub struct EditSecretScan {
+    pub enabled: bool,
+    /// Patterns from settings, checked along with the built-in ones.
+    pub patterns: Vec<SecretPattern>,
+    pub allowed_paths: PathMatcher,
+    pub ignored_matches: Vec<CompiledRegex>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SecretPattern {
+    pub name: String,
+    pub regex: CompiledRegex,
+    pub min_entropy: Option<f32>,
+}
+
+/// What a model costs, in US dollars per million tokens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+impl ModelPrice {
+    /// A price that charges the input price for cached tokens too.
+    pub const fn new(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            cache_write: input,
+            cache_read: input,
+        }
+    }
+
+    pub const fn with_cache(self, cache_write: f64, cache_read: f64) -> Self {
+        Self {
+            cache_write,
+            cache_read,
+            ..self
+        }
+    }
+
+    /// The estimated cost of `usage`, in US dollars.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let per_token = |price: f64, tokens: u64| price * tokens as f64 / 1_000_000.;
+        per_token(self.input, usage.input_tokens)
+            + per_token(self.output, usage.output_tokens)
+            + per_token(self.cache_write, usage.cache_creation_input_tokens)
+            + per_token(self.cache_read, usage.cache_read_input_tokens)
+    }
+}
+
+/// The user's restrictions on where the fetch tool may fetch from.
+#[derive(Clone, Debug, Default)]
+pub struct FetchSettings {
+    pub allow_private_network: bool,
+    /// When non-empty, the only domains that may be fetched from, along with their subdomains.
+    /// Lowercase, like the hosts of parsed URLs.
+    pub allowed_domains: Vec<String>,
+    pub denied_domains: Vec<String>,
+}
+
 pub const HARDCODED_SECURITY_DENIAL_MESSAGE: &str = "Blocked by built-in security rule. This operation is considered too \
      harmful to be allowed, and cannot be overridden by settings.";
 
@@ -437,6 +520,26 @@ impl Settings for AgentSettings {
             use_modifier_to_send: agent.use_modifier_to_send.unwrap(),
             message_editor_min_lines: agent.message_editor_min_lines.unwrap(),
             show_turn_stats: agent.show_turn_stats.unwrap(),
+            verify_edits: agent.verify_edits.unwrap(),
+            edit_syntax_check: agent.edit_syntax_check.unwrap(),
+            edit_secret_scan: compile_edit_secret_scan(agent.edit_secret_scan.unwrap_or_default()),
+            always_allow_unsaved_edits: agent.always_allow_unsaved_edits.unwrap(),
+            edits_require_approval: agent.edits_require_approval.unwrap(),
+            edit_file_max_size: agent.edit_file_max_size.unwrap(),
+            edit_file_chunk_size: agent.edit_file_chunk_size.unwrap(),
+            edit_match_min_similarity: agent.edit_match_min_similarity.unwrap().clamp(0., 1.),
+            edit_match_exact_similarity: agent.edit_match_exact_similarity.unwrap().clamp(0., 1.),
+            edit_tidy_deletions: agent.edit_tidy_deletions.unwrap(),
+            max_concurrent_tool_calls: agent.max_concurrent_tool_calls.unwrap().max(1),
+            instruction_files: PathMatcher::new(
+                agent.instruction_files.unwrap_or_default(),
+                PathStyle::local(),
+            )
+            .map_err(|error| log::error!("Invalid glob in instruction_files: {error}"))
+            .unwrap_or_default(),
+            fetch: fetch_settings(agent.fetch.unwrap_or_default()),
+            model_prices: model_prices(agent.model_prices.unwrap_or_default()),
+            thread_cost_budget: agent.thread_cost_budget.filter(|budget| *budget > 0.),
             tool_permissions: compile_tool_permissions(agent.tool_permissions),
         }
     }
@@ -504,6 +607,101 @@ fn compile_tool_permissions(content: Option<settings::ToolPermissionsContent>) -
     }
 }
 
+fn fetch_settings(content: settings::FetchSettingsContent) -> FetchSettings {
+    let normalize = |domains: Vec<String>| {
+        domains
+            .into_iter()
+            .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect()
+    };
+    FetchSettings {
+        allow_private_network: content.allow_private_network.unwrap_or(false),
+        allowed_domains: normalize(content.allowed_domains.unwrap_or_default()),
+        denied_domains: normalize(content.denied_domains.map(|v| v.0).unwrap_or_default()),
+    }
+}
+
+fn model_prices(
+    content: HashMap<String, settings::ModelPriceContent>,
+) -> HashMap<String, ModelPrice> {
+    content
+        .into_iter()
+        .filter_map(|(model, price)| {
+            let (Some(input), Some(output)) = (price.input, price.output) else {
+                log::error!(
+                    "The price of {model} in model_prices needs both an input and an output price"
+                );
+                return None;
+            };
+            let price = ModelPrice::new(input, output).with_cache(
+                price.cache_write.unwrap_or(input),
+                price.cache_read.unwrap_or(input),
+            );
+            Some((model, price))
+        })
+        .collect()
+}
+
+fn compile_edit_secret_scan(content: settings::EditSecretScanContent) -> EditSecretScan {
+    let patterns = content
+        .patterns
+        .map(|patterns| patterns.0)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|pattern| !pattern.pattern.is_empty())
+        .filter_map(|pattern| {
+            let case_sensitive = pattern.case_sensitive.unwrap_or(false);
+            let regex = CompiledRegex::try_new(&pattern.pattern, case_sensitive)
+                .map_err(|error| {
+                    log::error!(
+                        "Invalid regex pattern in edit_secret_scan.patterns: '{}' - {error}",
+                        pattern.pattern
+                    )
+                })
+                .ok()?;
+            Some(SecretPattern {
+                name: pattern.name,
+                regex,
+                min_entropy: pattern.min_entropy,
+            })
+        })
+        .collect();
+
+    let ignored_matches = content
+        .ignored_matches
+        .map(|patterns| patterns.0)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|pattern| {
+            CompiledRegex::try_new(&pattern, true)
+                .map_err(|error| {
+                    log::error!(
+                        "Invalid regex pattern in edit_secret_scan.ignored_matches: '{pattern}' - {error}"
+                    )
+                })
+                .ok()
+        })
+        .collect();
+
+    let allowed_paths = PathMatcher::new(
+        content
+            .allowed_paths
+            .map(|paths| paths.0)
+            .unwrap_or_default(),
+        PathStyle::local(),
+    )
+    .map_err(|error| log::error!("Invalid glob in edit_secret_scan.allowed_paths: {error}"))
+    .unwrap_or_default();
+
+    EditSecretScan {
+        enabled: content.enabled.unwrap_or(true),
+        patterns,
+        allowed_paths,
+        ignored_matches,
+    }
+}
+
 fn compile_regex_rules(
     rules: Vec<settings::ToolRegexRule>,
     rule_type: &str,
@@ -1871,6 +1871,27 @@ impl AgentPanel {
     }
 }
 
+/// A turn's or thread's tokens and estimated cost, or why they aren't known.
+fn usage_summary_label(summary: &agent::UsageSummary) -> String {
+    let Some(usage) = summary.usage else {
+        return "Usage unavailable".to_string();
+    };
+    let tokens = crate::text_thread_editor::humanize_token_count(usage.total_tokens());
+    match summary.cost {
+        Some(cost) if summary.unpriced_requests == 0 => {
+            format!(
+                "{tokens} tokens · {}",
+                crate::text_thread_editor::humanize_cost(cost)
+            )
+        }
+        Some(cost) => format!(
+            "{tokens} tokens · {} + unpriced",
+            crate::text_thread_editor::humanize_cost(cost)
+        ),
+        None => format!("{tokens} tokens · cost unavailable"),
+    }
+}
+
 impl Focusable for AgentPanel {
     fn focus_handle(&self, cx: &App) -> FocusHandle {
         match &self.active_view {
@@ -2356,6 +2377,10 @@ impl AgentPanel {
             | ActiveView::Configuration => None,
         };
 
+        let thread_cost = active_thread
+            .as_ref()
+            .and_then(|thread| Self::render_thread_cost(thread, cx));
+
         let new_thread_menu = PopoverMenu::new("new_thread_menu")
             .trigger_with_tooltip(
                 IconButton::new("new_thread_menu_btn", IconName::Plus).icon_size(IconSize::Small),
@@ -2748,6 +2773,7 @@ impl AgentPanel {
                     .gap(DynamicSpacing::Base02.rems(cx))
                     .pl(DynamicSpacing::Base04.rems(cx))
                     .pr(DynamicSpacing::Base06.rems(cx))
+                    .children(thread_cost)
                     .child(new_thread_menu)
                     .when(show_history_menu, |this| {
                         this.child(self.render_recent_entries_menu(
@@ -2760,6 +2786,79 @@ impl AgentPanel {
             )
     }
 
+    /// The thread's estimated cost so far, with each turn's share in the tooltip.
+    fn render_thread_cost(thread: &Entity<agent::Thread>, cx: &App) -> Option<AnyElement> {
+        let thread = thread.read(cx);
+        let ledger = thread.usage_ledger();
+        if ledger.is_empty() {
+            return None;
+        }
+
+        let settings = AgentSettings::get_global(cx);
+        let total = ledger.total(&settings.model_prices);
+        let over_budget = thread.exceeds_cost_budget(cx);
+        let budget = settings.thread_cost_budget;
+        let turns = ledger
+            .by_turn(&settings.model_prices)
+            .into_iter()
+            .enumerate()
+            .map(|(ix, (_, summary))| {
+                (
+                    SharedString::from(format!("Turn {}", ix + 1)),
+                    SharedString::from(usage_summary_label(&summary)),
+                )
+            })
+            .collect::<Vec<_>>();
+        let total_label = SharedString::from(usage_summary_label(&total));
+        let cost_label = match total.cost {
+            Some(cost) => crate::text_thread_editor::humanize_cost(cost),
+            None => "Cost unavailable".to_string(),
+        };
+
+        let row = |label: SharedString, value: SharedString| {
+            h_flex()
+                .justify_between()
+                .gap_4()
+                .child(Label::new(label).size(LabelSize::Small).color(Color::Muted))
+                .child(Label::new(value).size(LabelSize::Small))
+        };
+
+        Some(
+            div()
+                .id("thread-cost")
+                .px_1()
+                .child(
+                    Label::new(cost_label)
+                        .size(LabelSize::Small)
+                        .color(if over_budget {
+                            Color::Warning
+                        } else {
+                            Color::Muted
+                        }),
+                )
+                .tooltip(Tooltip::element(move |_, _| {
+                    v_flex()
+                        .gap_1()
+                        .child(Label::new("Estimated Thread Cost"))
+                        .children(
+                            turns
+                                .iter()
+                                .map(|(turn, value)| row(turn.clone(), value.clone())),
+                        )
+                        .child(ui::Divider::horizontal())
+                        .child(row("Total".into(), total_label.clone()))
+                        .when_some(budget, |this, budget| {
+                            this.child(row(
+                                "Budget".into(),
+                                crate::text_thread_editor::humanize_cost(budget).into(),
+                            ))
+                        })
+                        .into_any_element()
+                }))
+                .into_any_element(),
+        )
+    }
+
     fn should_render_trial_end_upsell(&self, cx: &mut Context<Self>) -> bool {
         if TrialEndUpsell::dismissed() {
             return false;
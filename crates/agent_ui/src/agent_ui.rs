@@ -540,7 +540,8 @@ mod tests {
     use gpui::{BorrowAppContext, TestAppContext, px};
     use project::DisableAiSettings;
     use settings::{
-        DefaultAgentView, DockPosition, NotifyWhenAgentWaiting, Settings, SettingsStore,
+        DefaultAgentView, DockPosition, EditSyntaxCheck, NotifyWhenAgentWaiting, Settings,
+        SettingsStore,
     };
 
     #[gpui::test]
@@ -584,6 +585,21 @@ mod tests {
             message_editor_min_lines: 1,
             tool_permissions: Default::default(),
             show_turn_stats: false,
+            verify_edits: false,
+            edit_syntax_check: EditSyntaxCheck::default(),
+            edit_secret_scan: Default::default(),
+            always_allow_unsaved_edits: false,
+            edits_require_approval: false,
+            edit_file_max_size: 2 * 1024 * 1024,
+            edit_file_chunk_size: 256 * 1024,
+            edit_match_min_similarity: 0.8,
+            edit_match_exact_similarity: 1.0,
+            edit_tidy_deletions: true,
+            max_concurrent_tool_calls: 4,
+            instruction_files: Default::default(),
+            fetch: Default::default(),
+            model_prices: Default::default(),
+            thread_cost_budget: None,
         };
 
         cx.update(|cx| {
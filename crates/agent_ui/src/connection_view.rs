@@ -1,8 +1,8 @@
 use acp_thread::{
     AcpThread, AcpThreadEvent, AgentSessionInfo, AgentThreadEntry, AssistantMessage,
-    AssistantMessageChunk, AuthRequired, LoadError, MentionUri, PermissionOptionChoice,
-    PermissionOptions, RetryStatus, ThreadStatus, ToolCall, ToolCallContent, ToolCallStatus,
-    UserMessageId,
+    AssistantMessageChunk, AuthRequired, EditableToolInput, LoadError, MentionUri,
+    PermissionOptionChoice, PermissionOptions, RetryStatus, ThreadStatus, ToolCall,
+    ToolCallContent, ToolCallStatus, UserMessageId,
 };
 use acp_thread::{AgentConnection, Plan};
 use action_log::{ActionLog, ActionLogTelemetry};
@@ -80,6 +80,8 @@ use crate::{
 };
 
 const STOPWATCH_THRESHOLD: Duration = Duration::from_secs(30);
+/// How long a tool runs before its card shows how long it's been running.
+const SLOW_TOOL_THRESHOLD: Duration = Duration::from_secs(10);
 const TOKEN_THRESHOLD: u64 = 250;
 
 mod thread_view;
@@ -267,6 +269,32 @@ impl Conversation {
         });
         cx.notify();
     }
+
+    pub fn authorize_tool_call_with_edited_input(
+        &mut self,
+        session_id: acp::SessionId,
+        tool_call_id: acp::ToolCallId,
+        input: serde_json::Value,
+        cx: &mut Context<Self>,
+    ) -> Result<()> {
+        let thread = self
+            .threads
+            .get(&session_id)
+            .ok_or_else(|| anyhow!("thread not found"))?;
+        thread.update(cx, |thread, cx| {
+            thread.authorize_tool_call_with_edited_input(tool_call_id, input, cx)
+        })?;
+
+        let agent_telemetry_id = thread.read(cx).connection().telemetry_id();
+        telemetry::event!(
+            "Agent Tool Call Authorized",
+            agent = agent_telemetry_id,
+            session = session_id,
+            option = acp_thread::EDITED_INPUT_OPTION_ID
+        );
+        cx.notify();
+        Ok(())
+    }
 }
 
 pub struct ConnectionView {
@@ -2620,6 +2648,15 @@ fn plan_label_markdown_style(
     }
 }
 
+/// Tool timings are mostly short, so they show tenths of a second until they reach a minute.
+fn tool_duration_display(duration: Duration) -> String {
+    if duration < Duration::from_secs(60) {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        duration_alt_display(duration)
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use acp_thread::{
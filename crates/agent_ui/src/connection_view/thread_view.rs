@@ -1,7 +1,10 @@
+use action_log::RestoreOutcome;
+use agent::ToolTimingSummary;
 use cloud_api_types::{SubmitAgentThreadFeedbackBody, SubmitAgentThreadFeedbackCommentsBody};
 use editor::actions::OpenExcerpts;
 use gpui::{Corner, List};
-use language_model::{LanguageModelEffortLevel, Speed};
+use language_model::{LanguageModelEffortLevel, LanguageModelToolUseId, Speed};
+use project::WorktreeId;
 use settings::update_settings_file;
 use ui::{ButtonLike, SplitButton, SplitButtonStyle, Tab};
 
@@ -150,6 +153,14 @@ impl ThreadFeedbackState {
     }
 }
 
+/// The input of a tool call waiting for confirmation, being edited before running the call
+/// with it.
+pub struct EditingToolInput {
+    tool_call_id: acp::ToolCallId,
+    editor: Entity<Editor>,
+    error: Option<SharedString>,
+}
+
 #[derive(Default, Clone, Copy)]
 struct DiffStats {
     lines_added: u32,
@@ -208,6 +219,7 @@ pub struct ThreadView {
     pub(super) thread_error: Option<ThreadError>,
     pub thread_error_markdown: Option<Entity<Markdown>>,
     pub token_limit_callout_dismissed: bool,
+    cost_budget_callout_dismissed: bool,
     pub last_token_limit_telemetry: Option<acp_thread::TokenUsageRatio>,
     thread_feedback: ThreadFeedbackState,
     pub list_state: ListState,
@@ -221,6 +233,7 @@ pub struct ThreadView {
     pub subagent_scroll_handles: RefCell<HashMap<agent_client_protocol::SessionId, ScrollHandle>>,
     pub edits_expanded: bool,
     pub plan_expanded: bool,
+    pub tool_stats_expanded: bool,
     pub queue_expanded: bool,
     pub editor_expanded: bool,
     pub should_be_following: bool,
@@ -238,6 +251,7 @@ pub struct ThreadView {
     /// The index corresponds to the position in the allow_options list.
     /// Default is the last option (index pointing to "Only this time").
     pub selected_permission_granularity: HashMap<agent_client_protocol::ToolCallId, usize>,
+    pub editing_tool_input: Option<EditingToolInput>,
     pub resume_thread_metadata: Option<AgentSessionInfo>,
     pub _cancel_task: Option<Task<()>>,
     pub skip_queue_processing_count: usize,
@@ -407,6 +421,7 @@ impl ThreadView {
             thread_error: None,
             thread_error_markdown: None,
             token_limit_callout_dismissed: false,
+            cost_budget_callout_dismissed: false,
             last_token_limit_telemetry: None,
             thread_feedback: Default::default(),
             expanded_tool_calls: HashSet::default(),
@@ -415,6 +430,7 @@ impl ThreadView {
             subagent_scroll_handles: RefCell::new(HashMap::default()),
             edits_expanded: false,
             plan_expanded: false,
+            tool_stats_expanded: false,
             queue_expanded: true,
             editor_expanded: false,
             should_be_following: false,
@@ -428,6 +444,7 @@ impl ThreadView {
             is_loading_contents: false,
             new_server_version_available: None,
             selected_permission_granularity: HashMap::default(),
+            editing_tool_input: None,
             _cancel_task: None,
             skip_queue_processing_count: 0,
             user_interrupted_generation: false,
@@ -1326,6 +1343,77 @@ impl ThreadView {
         cx.notify();
     }
 
+    fn start_editing_tool_input(
+        &mut self,
+        tool_call_id: acp::ToolCallId,
+        input: &serde_json::Value,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let text = serde_json::to_string_pretty(input).unwrap_or_default();
+        let editor = cx.new(|cx| {
+            let mut editor = Editor::auto_height(3, 16, window, cx);
+            editor.set_text(text, window, cx);
+            editor
+        });
+        editor.read(cx).focus_handle(cx).focus(window, cx);
+        self.editing_tool_input = Some(EditingToolInput {
+            tool_call_id,
+            editor,
+            error: None,
+        });
+        cx.notify();
+    }
+
+    fn cancel_editing_tool_input(&mut self, cx: &mut Context<Self>) {
+        self.editing_tool_input = None;
+        cx.notify();
+    }
+
+    fn run_edited_tool_input(
+        &mut self,
+        session_id: acp::SessionId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(editing) = self.editing_tool_input.as_ref() else {
+            return;
+        };
+        let tool_call_id = editing.tool_call_id.clone();
+        let text = editing.editor.read(cx).text(cx);
+
+        let result = serde_json::from_str::<serde_json::Value>(&text)
+            .map_err(|error| anyhow!("The input isn't valid JSON: {error}"))
+            .and_then(|input| {
+                self.conversation.update(cx, |conversation, cx| {
+                    conversation.authorize_tool_call_with_edited_input(
+                        session_id,
+                        tool_call_id,
+                        input,
+                        cx,
+                    )
+                })
+            });
+        match result {
+            Ok(()) => {
+                self.editing_tool_input = None;
+                if self.should_be_following {
+                    self.workspace
+                        .update(cx, |workspace, cx| {
+                            workspace.follow(CollaboratorId::Agent, window, cx);
+                        })
+                        .ok();
+                }
+            }
+            Err(error) => {
+                if let Some(editing) = self.editing_tool_input.as_mut() {
+                    editing.error = Some(format!("{error:#}").into());
+                }
+            }
+        }
+        cx.notify();
+    }
+
     pub fn allow_always(&mut self, _: &AllowAlways, window: &mut Window, cx: &mut Context<Self>) {
         self.authorize_pending_tool_call(acp::PermissionOptionKind::AllowAlways, window, cx);
     }
@@ -1644,6 +1732,64 @@ impl ThreadView {
             .detach_and_log_err(cx);
     }
 
+    fn revert_response_changes(&mut self, message_id: &UserMessageId, cx: &mut Context<Self>) {
+        let Some(thread) = self.as_native_thread(cx) else {
+            return;
+        };
+        let restore = thread.update(cx, |thread, cx| {
+            thread.restore_checkpoint(message_id.clone(), cx)
+        });
+        let workspace = self.workspace.clone();
+        cx.spawn(async move |_this, cx| {
+            let restored = restore.await?;
+            cx.update(|cx| {
+                let Some(workspace) = workspace.upgrade() else {
+                    return;
+                };
+                workspace.update(cx, |workspace, cx| {
+                    let path_style = workspace.path_style(cx);
+                    let mut reverted = 0;
+                    let mut skipped = Vec::new();
+                    for file in &restored {
+                        match &file.outcome {
+                            RestoreOutcome::Restored | RestoreOutcome::Deleted => reverted += 1,
+                            RestoreOutcome::ChangedSinceCheckpoint => {
+                                skipped.push(file.path.path.display(path_style).to_string())
+                            }
+                            RestoreOutcome::Failed(error) => {
+                                log::error!("Failed to revert {:?}: {error}", file.path);
+                                skipped.push(file.path.path.display(path_style).to_string())
+                            }
+                        }
+                    }
+                    let files = |count: usize| if count == 1 { "file" } else { "files" };
+                    let mut message = format!("Reverted {reverted} {}.", files(reverted));
+                    if !skipped.is_empty() {
+                        message.push_str(&format!(
+                            " Left {} alone because of changes made since: {}",
+                            files(skipped.len()),
+                            skipped.join(", ")
+                        ));
+                    }
+
+                    struct ResponseRevertedToast;
+                    let toast =
+                        Toast::new(NotificationId::unique::<ResponseRevertedToast>(), message);
+                    workspace.show_toast(
+                        if skipped.is_empty() {
+                            toast.autohide()
+                        } else {
+                            toast
+                        },
+                        cx,
+                    );
+                });
+            });
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
+    }
+
     pub fn clear_thread_error(&mut self, cx: &mut Context<Self>) {
         self.thread_error = None;
         self.thread_error_markdown = None;
@@ -1777,8 +1923,23 @@ impl ThreadView {
         let changed_buffers = action_log.read(cx).changed_buffers(cx);
         let plan = thread.plan();
         let queue_is_empty = !self.has_queued_messages();
-
-        if changed_buffers.is_empty() && plan.is_empty() && queue_is_empty {
+        let tool_stats = if AgentSettings::get_global(cx).show_turn_stats {
+            self.as_native_thread(cx)
+                .map(|thread| thread.read(cx).latest_turn_tool_stats())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let pending_approval = self
+            .as_native_thread(cx)
+            .map_or(0, |thread| thread.read(cx).pending_approval().len());
+
+        if changed_buffers.is_empty()
+            && plan.is_empty()
+            && queue_is_empty
+            && tool_stats.is_empty()
+            && pending_approval == 0
+        {
             return None;
         }
 
@@ -1792,6 +1953,7 @@ impl ThreadView {
         let plan_expanded = self.plan_expanded;
         let edits_expanded = self.edits_expanded;
         let queue_expanded = self.queue_expanded;
+        let tool_stats_expanded = self.tool_stats_expanded;
 
         v_flex()
             .mt_1()
@@ -1807,6 +1969,16 @@ impl ThreadView {
                 blur_radius: px(3.),
                 spread_radius: px(0.),
             }])
+            .when(pending_approval > 0, |this| {
+                this.child(self.render_pending_approval_bar(pending_approval, cx))
+                    .when(
+                        !plan.is_empty()
+                            || !changed_buffers.is_empty()
+                            || !queue_is_empty
+                            || !tool_stats.is_empty(),
+                        |this| this.child(Divider::horizontal().color(DividerColor::Border)),
+                    )
+            })
             .when(!plan.is_empty(), |this| {
                 this.child(self.render_plan_summary(plan, window, cx))
                     .when(plan_expanded, |parent| {
@@ -1842,10 +2014,221 @@ impl ThreadView {
                     parent.child(self.render_message_queue_entries(window, cx))
                 })
             })
+            .when(!tool_stats.is_empty(), |this| {
+                this.when(
+                    !plan.is_empty() || !changed_buffers.is_empty() || !queue_is_empty,
+                    |this| this.child(Divider::horizontal().color(DividerColor::Border)),
+                )
+                .child(self.render_tool_stats_summary(&tool_stats, cx))
+                .when(tool_stats_expanded, |parent| {
+                    parent.child(self.render_tool_stats_entries(&tool_stats, cx))
+                })
+            })
             .into_any()
             .into()
     }
 
+    /// The files with edits that won't be saved until the user approves them, when
+    /// `edits_require_approval` is on.
+    fn render_pending_approval_bar(
+        &self,
+        file_count: usize,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let approve_label = if file_count == 1 {
+            "Approve & Save (1 file)".to_string()
+        } else {
+            format!("Approve & Save ({file_count} files)")
+        };
+
+        h_flex()
+            .p_1()
+            .w_full()
+            .gap_1()
+            .justify_between()
+            .child(
+                h_flex()
+                    .pl_1()
+                    .gap_1p5()
+                    .child(
+                        Icon::new(IconName::FileDiff)
+                            .size(IconSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .child(
+                        Label::new("Edits Waiting for Approval")
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_1()
+                    .child(
+                        Button::new("reject_pending_edits", "Reject")
+                            .label_size(LabelSize::Small)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.reject_pending_edits(cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("approve_pending_edits", approve_label)
+                            .label_size(LabelSize::Small)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.approve_pending_edits(cx);
+                            })),
+                    ),
+            )
+    }
+
+    fn approve_pending_edits(&mut self, cx: &mut Context<Self>) {
+        let Some(thread) = self.as_native_thread(cx) else {
+            return;
+        };
+        thread
+            .update(cx, |thread, cx| thread.approve_pending_edits(cx))
+            .detach_and_log_err(cx);
+        cx.notify();
+    }
+
+    fn reject_pending_edits(&mut self, cx: &mut Context<Self>) {
+        let Some(thread) = self.as_native_thread(cx) else {
+            return;
+        };
+        thread
+            .update(cx, |thread, cx| thread.reject_pending_edits(cx))
+            .detach_and_log_err(cx);
+        cx.notify();
+    }
+
+    fn render_tool_stats_summary(
+        &self,
+        tool_stats: &[ToolTimingSummary],
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let tool_stats_expanded = self.tool_stats_expanded;
+        let calls = tool_stats
+            .iter()
+            .map(|summary| summary.calls)
+            .sum::<usize>();
+        let total = tool_stats
+            .iter()
+            .map(|summary| summary.total())
+            .sum::<Duration>();
+        let mut label = format!(
+            "{calls} tool {}, {} total",
+            if calls == 1 { "call" } else { "calls" },
+            tool_duration_display(total)
+        );
+        // The summaries are sorted slowest first.
+        if let Some(slowest) = tool_stats.first()
+            && calls > 1
+        {
+            label.push_str(&format!(
+                " — {} {}",
+                slowest.tool_name,
+                tool_duration_display(slowest.total())
+            ));
+        }
+
+        h_flex()
+            .id("tool_stats_summary")
+            .p_1()
+            .w_full()
+            .gap_1()
+            .when(tool_stats_expanded, |this| {
+                this.border_b_1().border_color(cx.theme().colors().border)
+            })
+            .child(Disclosure::new(
+                "tool_stats_disclosure",
+                tool_stats_expanded,
+            ))
+            .child(
+                h_flex()
+                    .w_full()
+                    .gap_1()
+                    .justify_between()
+                    .child(
+                        Label::new("This Turn")
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .child(
+                        Label::new(label)
+                            .size(LabelSize::Small)
+                            .color(Color::Muted)
+                            .truncate()
+                            .mr_1(),
+                    ),
+            )
+            .on_click(cx.listener(|this, _, _, cx| {
+                this.tool_stats_expanded = !this.tool_stats_expanded;
+                cx.notify();
+            }))
+    }
+
+    fn render_tool_stats_entries(
+        &self,
+        tool_stats: &[ToolTimingSummary],
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        v_flex()
+            .id("tool_stats_entries")
+            .max_h_40()
+            .overflow_y_scroll()
+            .children(tool_stats.iter().enumerate().map(|(index, summary)| {
+                let mut details = format!(
+                    "{} {}",
+                    summary.calls,
+                    if summary.calls == 1 { "call" } else { "calls" }
+                );
+                if summary.errors > 0 {
+                    details.push_str(&format!(", {} failed", summary.errors));
+                }
+                if summary.cancelled > 0 {
+                    details.push_str(&format!(", {} cancelled", summary.cancelled));
+                }
+                if summary.calls > 1 {
+                    details.push_str(&format!(
+                        ", slowest {}",
+                        tool_duration_display(summary.slowest)
+                    ));
+                }
+
+                h_flex()
+                    .py_1()
+                    .px_2()
+                    .gap_2()
+                    .justify_between()
+                    .bg(cx.theme().colors().editor_background)
+                    .when(index < tool_stats.len() - 1, |parent| {
+                        parent.border_color(cx.theme().colors().border).border_b_1()
+                    })
+                    .child(
+                        h_flex()
+                            .gap_1p5()
+                            .min_w_0()
+                            .child(
+                                Label::new(summary.tool_name.to_string())
+                                    .size(LabelSize::XSmall)
+                                    .buffer_font(cx)
+                                    .truncate(),
+                            )
+                            .child(
+                                Label::new(details)
+                                    .size(LabelSize::XSmall)
+                                    .color(Color::Muted),
+                            ),
+                    )
+                    .child(
+                        Label::new(tool_duration_display(summary.total()))
+                            .size(LabelSize::XSmall)
+                            .color(Color::Muted)
+                            .buffer_font(cx),
+                    )
+            }))
+    }
+
     fn render_edited_files(
         &self,
         action_log: &Entity<ActionLog>,
@@ -1914,6 +2297,13 @@ impl ThreadView {
 
                         let file_stats = DiffStats::single_file(buffer.read(cx), diff.read(cx), cx);
 
+                        let modified_by = action_log.read(cx).modified_by(buffer).map(|command| {
+                            Label::new(format!("modified by `{command}`"))
+                                .size(LabelSize::XSmall)
+                                .color(Color::Muted)
+                                .truncate()
+                        });
+
                         let buttons = self.render_edited_files_buttons(
                             index,
                             buffer,
@@ -1954,6 +2344,7 @@ impl ThreadView {
                                         )
                                         .label_size(LabelSize::XSmall),
                                     )
+                                    .children(modified_by)
                                     .hover(|s| s.bg(cx.theme().colors().element_hover))
                                     .tooltip({
                                         move |_, cx| {
@@ -2594,7 +2985,9 @@ impl ThreadView {
                     .child(
                         h_flex()
                             .gap_1()
+                            .children(self.render_history_compacted(cx))
                             .children(self.render_token_usage(cx))
+                            .children(self.render_worktree_scope_selector(cx))
                             .children(self.profile_selector.clone())
                             .map(|this| {
                                 // Either config_options_view OR (mode_selector + model_selector)
@@ -3013,6 +3406,30 @@ impl ThreadView {
         }
     }
 
+    fn render_history_compacted(&self, cx: &App) -> Option<impl IntoElement> {
+        let compacted = self.as_native_thread(cx)?.read(cx).compacted_tool_results();
+        if compacted == 0 {
+            return None;
+        }
+        let tooltip = format!(
+            "To fit the model's context window, the output of {compacted} older tool {} \
+             was left out of the last request. It's still shown in the thread.",
+            if compacted == 1 { "call" } else { "calls" }
+        );
+        Some(
+            h_flex()
+                .id("history-compacted")
+                .flex_shrink_0()
+                .mr_1()
+                .child(
+                    Label::new("History compacted")
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .tooltip(Tooltip::text(tooltip)),
+        )
+    }
+
     fn fast_mode_available(&self, cx: &Context<Self>) -> bool {
         if !cx.is_staff() {
             return false;
@@ -3260,6 +3677,124 @@ impl ThreadView {
             .anchor(Corner::BottomLeft)
     }
 
+    /// Lets the user confine the agent's tools to some of the project's roots. Only shown when
+    /// there's more than one root to choose from.
+    fn render_worktree_scope_selector(&self, cx: &Context<Self>) -> Option<AnyElement> {
+        let thread = self.as_native_thread(cx)?;
+        let project = self.project.upgrade()?;
+        let project = project.read(cx);
+        let roots = project
+            .visible_worktrees(cx)
+            .map(|worktree| {
+                let worktree = worktree.read(cx);
+                (
+                    worktree.id(),
+                    SharedString::from(worktree.root_name_str().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        if roots.len() < 2 {
+            return None;
+        }
+
+        let scope = thread.read(cx).worktree_scope().cloned().map(|mut scope| {
+            scope.resolve(project, cx);
+            scope
+        });
+        let label: SharedString = scope
+            .as_ref()
+            .map_or("All Roots".into(), |scope| scope.label().into());
+        let scoped_ids = scope.map(|scope| scope.worktree_ids().collect::<Vec<_>>());
+        let weak_self = cx.weak_entity();
+
+        let set_scope = move |scope: Option<Vec<WorktreeId>>, cx: &mut App| {
+            weak_self
+                .update(cx, |this, cx| {
+                    if let Some(thread) = this.as_native_thread(cx) {
+                        thread.update(cx, |thread, cx| thread.set_worktree_scope(scope, cx));
+                    }
+                    cx.notify();
+                })
+                .ok();
+        };
+
+        Some(
+            PopoverMenu::new("worktree-scope-selector")
+                .trigger_with_tooltip(
+                    ButtonLike::new("worktree-scope-selector-trigger")
+                        .child(
+                            Icon::new(IconName::Folder)
+                                .size(IconSize::XSmall)
+                                .color(Color::Muted),
+                        )
+                        .child(Label::new(label).size(LabelSize::Small).color(Color::Muted))
+                        .child(
+                            Icon::new(IconName::ChevronDown)
+                                .size(IconSize::XSmall)
+                                .color(Color::Muted),
+                        ),
+                    Tooltip::text("Choose Which Roots the Agent Can Use"),
+                )
+                .menu(move |window, cx| {
+                    let roots = roots.clone();
+                    let scoped_ids = scoped_ids.clone();
+                    let set_scope = set_scope.clone();
+                    Some(ContextMenu::build(
+                        window,
+                        cx,
+                        move |mut menu, _window, _cx| {
+                            menu = menu.header("Agent Can Use");
+                            menu.push_item(
+                                ContextMenuEntry::new("All Roots")
+                                    .toggleable(IconPosition::End, scoped_ids.is_none())
+                                    .handler({
+                                        let set_scope = set_scope.clone();
+                                        move |_window, cx| set_scope(None, cx)
+                                    }),
+                            );
+                            menu = menu.separator();
+
+                            for (worktree_id, root_name) in &roots {
+                                let worktree_id = *worktree_id;
+                                let is_selected = scoped_ids
+                                    .as_ref()
+                                    .is_none_or(|ids| ids.contains(&worktree_id));
+                                let mut ids = scoped_ids.clone().unwrap_or_else(|| {
+                                    roots.iter().map(|(worktree_id, _)| *worktree_id).collect()
+                                });
+                                if is_selected {
+                                    ids.retain(|id| *id != worktree_id);
+                                } else {
+                                    ids.push(worktree_id);
+                                }
+                                // Unticking the last root would leave the agent with nothing.
+                                let disabled = ids.is_empty();
+                                let scope = (ids.len() < roots.len()).then_some(ids);
+
+                                menu.push_item(
+                                    ContextMenuEntry::new(root_name.clone())
+                                        .toggleable(IconPosition::End, is_selected)
+                                        .disabled(disabled)
+                                        .handler({
+                                            let set_scope = set_scope.clone();
+                                            move |_window, cx| set_scope(scope.clone(), cx)
+                                        }),
+                                );
+                            }
+
+                            menu
+                        },
+                    ))
+                })
+                .offset(gpui::Point {
+                    x: px(0.0),
+                    y: px(-2.0),
+                })
+                .anchor(Corner::BottomRight)
+                .into_any_element(),
+        )
+    }
+
     fn render_send_button(&self, cx: &mut Context<Self>) -> AnyElement {
         let message_editor = self.message_editor.read(cx);
         let is_editor_empty = message_editor.is_empty(cx);
@@ -3598,6 +4133,29 @@ impl ThreadView {
                 let agent_name = self.agent_name.clone();
                 let is_subagent = self.is_subagent();
 
+                let revert_changes_button = message
+                    .id
+                    .clone()
+                    .filter(|message_id| {
+                        self.as_native_thread(cx)
+                            .is_some_and(|thread| thread.read(cx).has_checkpoint(message_id))
+                    })
+                    .map(|message_id| {
+                        h_flex().justify_end().child(
+                            Button::new("revert-response-changes", "Revert Changes from This Response")
+                                .icon(IconName::Undo)
+                                .icon_size(IconSize::XSmall)
+                                .icon_position(IconPosition::Start)
+                                .label_size(LabelSize::XSmall)
+                                .icon_color(Color::Muted)
+                                .color(Color::Muted)
+                                .tooltip(Tooltip::text("Puts back the files the agent changed in response to this message, unless they were edited since."))
+                                .on_click(cx.listener(move |this, _, _window, cx| {
+                                    this.revert_response_changes(&message_id, cx);
+                                })),
+                        )
+                    });
+
                 let non_editable_icon = || {
                     IconButton::new("non_editable", IconName::PencilUnavailable)
                         .icon_size(IconSize::Small)
@@ -3768,6 +4326,7 @@ impl ThreadView {
                                 }
                             }),
                     )
+                    .children(revert_changes_button)
                     .into_any()
             }
             AgentThreadEntry::AssistantMessage(AssistantMessage {
@@ -4702,9 +5261,13 @@ impl ThreadView {
             ToolCallStatus::Rejected | ToolCallStatus::Canceled | ToolCallStatus::Failed
         );
 
-        let confirmation_options = match &tool_call.status {
-            ToolCallStatus::WaitingForConfirmation { options, .. } => Some(options),
-            _ => None,
+        let (confirmation_options, editable_input) = match &tool_call.status {
+            ToolCallStatus::WaitingForConfirmation {
+                options,
+                editable_input,
+                ..
+            } => (Some(options), editable_input.as_ref()),
+            _ => (None, None),
         };
         let needs_confirmation = confirmation_options.is_some();
 
@@ -4960,6 +5523,7 @@ impl ThreadView {
                     self.id.clone(),
                     is_first,
                     options,
+                    editable_input,
                     entry_ix,
                     tool_call.id.clone(),
                     focus_handle,
@@ -5053,6 +5617,16 @@ impl ThreadView {
         );
         let is_terminal_tool = matches!(tool_call.kind, acp::ToolKind::Execute);
 
+        let slow_tool_elapsed = if matches!(tool_call.status, ToolCallStatus::InProgress) {
+            self.as_native_thread(cx).and_then(|thread| {
+                let tool_use_id = LanguageModelToolUseId::from(tool_call.id.0.to_string());
+                thread.read(cx).tool_call_running_for(&tool_use_id)
+            })
+        } else {
+            None
+        }
+        .filter(|elapsed| *elapsed > SLOW_TOOL_THRESHOLD);
+
         let is_edit =
             matches!(tool_call.kind, acp::ToolKind::Edit) || tool_call.diffs().next().is_some();
 
@@ -5091,7 +5665,11 @@ impl ThreadView {
 
         let tool_output_display = if is_open {
             match &tool_call.status {
-                ToolCallStatus::WaitingForConfirmation { options, .. } => v_flex()
+                ToolCallStatus::WaitingForConfirmation {
+                    options,
+                    editable_input,
+                    ..
+                } => v_flex()
                     .w_full()
                     .children(
                         tool_call
@@ -5184,6 +5762,7 @@ impl ThreadView {
                         self.id.clone(),
                         self.is_first_tool_call(active_session_id, &tool_call.id, cx),
                         options,
+                        editable_input.as_ref(),
                         entry_ix,
                         tool_call.id.clone(),
                         focus_handle,
@@ -5286,7 +5865,9 @@ impl ThreadView {
                         cx,
                     ))
                 } else {
-                    this.child(
+                    this.child(self.render_tool_call_context_menu(
+                        entry_ix,
+                        tool_call,
                         h_flex()
                             .group(&card_header_id)
                             .relative()
@@ -5311,6 +5892,22 @@ impl ThreadView {
                             .child(
                                 h_flex()
                                     .gap_0p5()
+                                    .when_some(slow_tool_elapsed, |this, elapsed| {
+                                        this.child(
+                                            h_flex()
+                                                .id(("slow-tool-indicator", entry_ix))
+                                                .gap_1()
+                                                .px_1()
+                                                .child(SpinnerLabel::new().size(LabelSize::XSmall))
+                                                .child(
+                                                    Label::new(duration_alt_display(elapsed))
+                                                        .buffer_font(cx)
+                                                        .size(LabelSize::XSmall)
+                                                        .color(Color::Muted),
+                                                )
+                                                .tooltip(Tooltip::text("Still running")),
+                                        )
+                                    })
                                     .when(is_collapsible || failed_or_canceled, |this| {
                                         let diff_for_discard = if has_revealed_diff
                                             && is_cancelled_edit
@@ -5448,29 +6045,101 @@ impl ThreadView {
                                                 }),
                                         )
                                     }),
-                            )
-
-                    )
+                            ),
+                        cx,
+                    ))
                 }
             })
             .children(tool_output_display)
     }
 
+    /// Lets completed edits be re-applied to the files as they are now, such as when revisiting
+    /// an older conversation.
+    fn render_tool_call_context_menu(
+        &self,
+        entry_ix: usize,
+        tool_call: &ToolCall,
+        header: impl IntoElement + 'static,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let can_reapply = tool_call.kind == acp::ToolKind::Edit
+            && matches!(tool_call.status, ToolCallStatus::Completed)
+            && self.thread.read(cx).status() == ThreadStatus::Idle
+            && self.as_native_connection(cx).is_some();
+        if !can_reapply {
+            return header.into_any_element();
+        }
+
+        let entity = cx.entity();
+        let tool_call_id = tool_call.id.clone();
+        right_click_menu(format!("tool-call-context-menu-{}", entry_ix))
+            .trigger(move |_, _, _| header)
+            .menu(move |window, cx| {
+                let entity = entity.clone();
+                let tool_call_id = tool_call_id.clone();
+                ContextMenu::build(window, cx, move |menu, _, _| {
+                    menu.entry("Re-apply Edit", None, move |_, cx| {
+                        entity.update(cx, |this, cx| {
+                            this.reapply_tool_call(&tool_call_id, cx);
+                        });
+                    })
+                })
+            })
+            .into_any_element()
+    }
+
+    fn reapply_tool_call(&mut self, tool_call_id: &acp::ToolCallId, cx: &mut Context<Self>) {
+        let Some(connection) = self.as_native_connection(cx) else {
+            return;
+        };
+        let session_id = self.thread.read(cx).session_id().clone();
+        connection
+            .reapply_tool_call(session_id, tool_call_id, cx)
+            .detach_and_log_err(cx);
+    }
+
     fn render_permission_buttons(
         &self,
         session_id: acp::SessionId,
         is_first: bool,
         options: &PermissionOptions,
+        editable_input: Option<&EditableToolInput>,
         entry_ix: usize,
         tool_call_id: acp::ToolCallId,
         focus_handle: &FocusHandle,
         cx: &Context<Self>,
     ) -> Div {
+        if editable_input.is_some()
+            && let Some(editing) = self
+                .editing_tool_input
+                .as_ref()
+                .filter(|editing| editing.tool_call_id == tool_call_id)
+        {
+            return self.render_tool_input_editor(session_id, editing, entry_ix, cx);
+        }
+
+        let edit_button = editable_input.map(|editable_input| {
+            Button::new(("edit-input-btn", entry_ix), "Edit & Run")
+                .icon(IconName::Pencil)
+                .icon_color(Color::Muted)
+                .icon_position(IconPosition::Start)
+                .icon_size(IconSize::XSmall)
+                .label_size(LabelSize::Small)
+                .on_click(cx.listener({
+                    let tool_call_id = tool_call_id.clone();
+                    let input = editable_input.input.clone();
+                    move |this, _, window, cx| {
+                        this.start_editing_tool_input(tool_call_id.clone(), &input, window, cx);
+                    }
+                }))
+        });
+
         match options {
             PermissionOptions::Flat(options) => self.render_permission_buttons_flat(
                 session_id,
                 is_first,
                 options,
+                edit_button,
                 entry_ix,
                 tool_call_id,
                 focus_handle,
@@ -5480,6 +6149,7 @@ impl ThreadView {
                 session_id,
                 is_first,
                 options,
+                edit_button,
                 entry_ix,
                 tool_call_id,
                 focus_handle,
@@ -5488,11 +6158,65 @@ impl ThreadView {
         }
     }
 
+    fn render_tool_input_editor(
+        &self,
+        session_id: acp::SessionId,
+        editing: &EditingToolInput,
+        entry_ix: usize,
+        cx: &Context<Self>,
+    ) -> Div {
+        v_flex()
+            .w_full()
+            .p_1()
+            .gap_1()
+            .border_t_1()
+            .border_color(self.tool_card_border_color(cx))
+            .child(
+                div()
+                    .p_1()
+                    .rounded_sm()
+                    .border_1()
+                    .border_color(cx.theme().colors().border_variant)
+                    .bg(cx.theme().colors().editor_background)
+                    .child(editing.editor.clone()),
+            )
+            .when_some(editing.error.clone(), |this, error| {
+                this.child(Label::new(error).size(LabelSize::Small).color(Color::Error))
+            })
+            .child(
+                h_flex()
+                    .gap_0p5()
+                    .child(
+                        Button::new(("run-edited-input-btn", entry_ix), "Run")
+                            .icon(IconName::Check)
+                            .icon_color(Color::Success)
+                            .icon_position(IconPosition::Start)
+                            .icon_size(IconSize::XSmall)
+                            .label_size(LabelSize::Small)
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.run_edited_tool_input(session_id.clone(), window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new(("cancel-edited-input-btn", entry_ix), "Cancel")
+                            .icon(IconName::Close)
+                            .icon_color(Color::Muted)
+                            .icon_position(IconPosition::Start)
+                            .icon_size(IconSize::XSmall)
+                            .label_size(LabelSize::Small)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.cancel_editing_tool_input(cx);
+                            })),
+                    ),
+            )
+    }
+
     fn render_permission_buttons_dropdown(
         &self,
         session_id: acp::SessionId,
         is_first: bool,
         choices: &[PermissionOptionChoice],
+        edit_button: Option<Button>,
         entry_ix: usize,
         tool_call_id: acp::ToolCallId,
         focus_handle: &FocusHandle,
@@ -5604,7 +6328,8 @@ impl ThreadView {
                                     );
                                 }
                             })),
-                    ),
+                    )
+                    .children(edit_button),
             )
             .child(self.render_permission_granularity_dropdown(
                 choices,
@@ -5694,6 +6419,7 @@ impl ThreadView {
         session_id: acp::SessionId,
         is_first: bool,
         options: &[acp::PermissionOption],
+        edit_button: Option<Button>,
         entry_ix: usize,
         tool_call_id: acp::ToolCallId,
         focus_handle: &FocusHandle,
@@ -5765,6 +6491,7 @@ impl ThreadView {
                         }
                     }))
             }))
+            .children(edit_button)
     }
 
     fn render_diff_loading(&self, cx: &Context<Self>) -> AnyElement {
@@ -7306,6 +8033,47 @@ impl ThreadView {
         )
     }
 
+    fn render_cost_budget_callout(&self, cx: &mut Context<Self>) -> Option<Callout> {
+        if self.cost_budget_callout_dismissed {
+            return None;
+        }
+        let thread = self.as_native_thread(cx)?;
+        let thread = thread.read(cx);
+        if !thread.exceeds_cost_budget(cx) {
+            return None;
+        }
+
+        let settings = AgentSettings::get_global(cx);
+        let cost = thread
+            .usage_ledger()
+            .total(&settings.model_prices)
+            .cost
+            .unwrap_or_default();
+        let budget = settings.thread_cost_budget.unwrap_or_default();
+        let description = format!(
+            "This thread has cost an estimated {}, past your {} budget.",
+            crate::text_thread_editor::humanize_cost(cost),
+            crate::text_thread_editor::humanize_cost(budget),
+        );
+
+        Some(
+            Callout::new()
+                .severity(Severity::Warning)
+                .icon(IconName::Warning)
+                .title("Thread over budget")
+                .description(description)
+                .dismiss_action(
+                    IconButton::new("dismiss-cost-budget", IconName::Close)
+                        .icon_size(IconSize::Small)
+                        .tooltip(Tooltip::text("Dismiss"))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.cost_budget_callout_dismissed = true;
+                            cx.notify();
+                        })),
+                ),
+        )
+    }
+
     fn open_permission_dropdown(
         &mut self,
         _: &crate::OpenPermissionDropdown,
@@ -7581,6 +8349,7 @@ impl Render for ThreadView {
                 |this, version| this.child(self.render_new_version_callout(&version, cx)),
             )
             .children(self.render_token_limit_callout(cx))
+            .children(self.render_cost_budget_callout(cx))
             .child(self.render_message_editor(window, cx))
     }
 }
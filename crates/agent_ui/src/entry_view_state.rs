@@ -16,7 +16,7 @@ use prompt_store::PromptStore;
 use rope::Point;
 use settings::Settings as _;
 use terminal_view::TerminalView;
-use theme::ThemeSettings;
+use theme::{ActiveTheme as _, ThemeSettings};
 use ui::{Context, TextSize};
 use workspace::Workspace;
 
@@ -427,10 +427,54 @@ fn create_editor_diff(
         editor.set_show_git_diff_gutter(false, cx);
         editor.set_expand_all_diff_hunks(cx);
         editor.set_text_style_refinement(diff_editor_text_style_refinement(cx));
+        highlight_marked_diff_ranges(&mut editor, &diff, cx);
+        cx.observe(&diff, |editor, diff, cx| {
+            highlight_marked_diff_ranges(editor, &diff, cx);
+        })
+        .detach();
         editor
     })
 }
 
+/// Highlights the lines of the diff that the tool marked, like edits that didn't exactly
+/// match the text they replaced.
+fn highlight_marked_diff_ranges(
+    editor: &mut Editor,
+    diff: &Entity<acp_thread::Diff>,
+    cx: &mut Context<Editor>,
+) {
+    enum MarkedDiffRange {}
+
+    let diff = diff.read(cx);
+    let multibuffer = diff.multibuffer().read(cx);
+    let ranges = multibuffer
+        .all_buffers()
+        .into_iter()
+        .next()
+        .map(|buffer| {
+            diff.marked_ranges(cx)
+                .into_iter()
+                .filter_map(|range| {
+                    Some(
+                        multibuffer.buffer_point_to_anchor(&buffer, range.start, cx)?
+                            ..multibuffer.buffer_point_to_anchor(&buffer, range.end, cx)?,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    editor.clear_row_highlights::<MarkedDiffRange>();
+    for range in ranges {
+        editor.highlight_rows::<MarkedDiffRange>(
+            range,
+            cx.theme().status().warning_background,
+            Default::default(),
+            cx,
+        );
+    }
+}
+
 fn diff_editor_text_style_refinement(cx: &mut App) -> TextStyleRefinement {
     TextStyleRefinement {
         font_size: Some(
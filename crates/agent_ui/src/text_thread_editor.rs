@@ -3136,6 +3136,15 @@ pub fn humanize_token_count(count: u64) -> String {
     }
 }
 
+/// Formats an estimated cost in US dollars, to the cent.
+pub fn humanize_cost(cost: f64) -> String {
+    if cost > 0. && cost < 0.01 {
+        "<$0.01".to_string()
+    } else {
+        format!("${cost:.2}")
+    }
+}
+
 pub fn make_lsp_adapter_delegate(
     project: &Entity<Project>,
     cx: &mut App,
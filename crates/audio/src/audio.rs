@@ -12,7 +12,6 @@ mod non_windows_and_freebsd_deps {
     pub(super) use libwebrtc::native::apm;
     pub(super) use parking_lot::Mutex;
     pub(super) use rodio::source::LimitSettings;
-    pub(super) use std::sync::Arc;
 }
 
 #[cfg(not(any(all(target_os = "windows", target_env = "gnu"), target_os = "freebsd")))]
@@ -25,7 +24,16 @@ use rodio::{
     source::{AutomaticGainControlSettings, Buffered},
 };
 use settings::Settings;
-use std::{io::Cursor, num::NonZero, path::PathBuf, sync::atomic::Ordering, time::Duration};
+use std::{
+    io::Cursor,
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 use util::ResultExt;
 
 mod audio_settings;
@@ -83,6 +91,8 @@ pub enum Sound {
     StartScreenshare,
     StopScreenshare,
     AgentDone,
+    IncomingCallClassic,
+    IncomingCallChime,
 }
 
 impl Sound {
@@ -96,15 +106,34 @@ impl Sound {
             Self::StartScreenshare => "start_screenshare",
             Self::StopScreenshare => "stop_screenshare",
             Self::AgentDone => "agent_done",
+            Self::IncomingCallClassic => "incoming_call_classic",
+            Self::IncomingCallChime => "incoming_call_chime",
         }
     }
 }
 
+/// What to play while someone is calling.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ringtone {
+    Sound(Sound),
+    /// A sound file picked by the user, with the sound to play instead if it can't be decoded.
+    File {
+        path: PathBuf,
+        fallback: Sound,
+    },
+}
+
+type DecodedSound = Buffered<Decoder<Cursor<Vec<u8>>>>;
+
 pub struct Audio {
     output_handle: Option<MixerDeviceSink>,
+    /// The device that `output_handle` was opened on, where `None` is the default one.
+    output_audio_device: Option<DeviceId>,
     #[cfg(not(any(all(target_os = "windows", target_env = "gnu"), target_os = "freebsd")))]
     pub echo_canceller: Arc<Mutex<apm::AudioProcessingModule>>,
-    source_cache: HashMap<Sound, Buffered<Decoder<Cursor<Vec<u8>>>>>,
+    source_cache: HashMap<Sound, DecodedSound>,
+    /// Set to stop the ringtone that's currently playing.
+    stop_ringtone: Option<Arc<AtomicBool>>,
     replays: replays::Replays,
 }
 
@@ -112,6 +141,7 @@ impl Default for Audio {
     fn default() -> Self {
         Self {
             output_handle: Default::default(),
+            output_audio_device: None,
             #[cfg(not(any(
                 all(target_os = "windows", target_env = "gnu"),
                 target_os = "freebsd"
@@ -120,6 +150,7 @@ impl Default for Audio {
                 true, false, false, false,
             ))),
             source_cache: Default::default(),
+            stop_ringtone: None,
             replays: Default::default(),
         }
     }
@@ -135,7 +166,8 @@ impl Audio {
         );
 
         if self.output_handle.is_none() {
-            let output_handle = open_output_stream(output_audio_device)?;
+            let output_handle = open_output_stream(output_audio_device.clone())?;
+            self.output_audio_device = output_audio_device;
 
             // The webrtc apm is not yet compiling for windows & freebsd
             #[cfg(not(any(
@@ -198,6 +230,9 @@ impl Audio {
             .constant_samplerate(SAMPLE_RATE)
             .limit(LimitSettings::live_performance())
             .process_buffer::<BUFFER_SIZE, _>(move |buffer| {
+                if !voip_parts.echo_cancellation {
+                    return;
+                }
                 let mut int_buffer: [i16; _] = buffer.map(|s| s.to_sample());
                 if voip_parts
                     .echo_canceller
@@ -245,10 +280,13 @@ impl Audio {
         Ok(stream)
     }
 
+    /// Plays a call participant's audio on `output_audio_device`. If the output is open on
+    /// another device, it's reopened on this one, and everything playing on the old one stops.
     pub fn play_voip_stream(
         source: impl rodio::Source + Send + 'static,
         speaker_name: String,
         is_staff: bool,
+        output_audio_device: Option<DeviceId>,
         cx: &mut App,
     ) -> anyhow::Result<()> {
         let (replay_source, source) = source
@@ -264,9 +302,11 @@ impl Audio {
             })
             .replayable(REPLAY_DURATION)
             .expect("REPLAY_DURATION is longer than 100ms");
-        let output_audio_device = AudioSettings::get_global(cx).output_audio_device.clone();
 
         cx.update_default_global(|this: &mut Self, _cx| {
+            if this.output_handle.is_some() && this.output_audio_device != output_audio_device {
+                this.output_handle.take();
+            }
             let output_mixer = this
                 .ensure_output_exists(output_audio_device)
                 .context("Could not get output mixer")?;
@@ -279,6 +319,10 @@ impl Audio {
     }
 
     pub fn play_sound(sound: Sound, cx: &mut App) {
+        Self::play_sound_at_volume(sound, 1.0, cx);
+    }
+
+    pub fn play_sound_at_volume(sound: Sound, volume: f32, cx: &mut App) {
         let output_audio_device = AudioSettings::get_global(cx).output_audio_device.clone();
         cx.update_default_global(|this: &mut Self, cx| {
             let source = this.sound_source(sound, cx).log_err()?;
@@ -287,7 +331,52 @@ impl Audio {
                 .context("Could not get output mixer")
                 .log_err()?;
 
-            output_mixer.add(source);
+            output_mixer.add(source.amplify(volume));
+            Some(())
+        });
+    }
+
+    /// Loops `ringtone` until [`Audio::stop_ringing`] is called, replacing any ringtone that's
+    /// already playing.
+    pub fn start_ringing(ringtone: &Ringtone, volume: f32, cx: &mut App) {
+        Self::play_ringtone(ringtone, volume, true, cx);
+    }
+
+    /// Plays `ringtone` once, so that it can be tried out before it's used for calls.
+    pub fn preview_ringtone(ringtone: &Ringtone, volume: f32, cx: &mut App) {
+        Self::play_ringtone(ringtone, volume, false, cx);
+    }
+
+    pub fn stop_ringing(cx: &mut App) {
+        cx.update_default_global(|this: &mut Self, _cx| this.stop_ringtone());
+    }
+
+    fn stop_ringtone(&mut self) {
+        if let Some(stop) = self.stop_ringtone.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn play_ringtone(ringtone: &Ringtone, volume: f32, repeat: bool, cx: &mut App) {
+        let output_audio_device = AudioSettings::get_global(cx).output_audio_device.clone();
+        cx.update_default_global(|this: &mut Self, cx| {
+            this.stop_ringtone();
+            let source = this
+                .ringtone_source(ringtone, cx)
+                .log_err()?
+                .amplify(volume);
+            let output_mixer = this
+                .ensure_output_exists(output_audio_device)
+                .context("Could not get output mixer")
+                .log_err()?;
+
+            let stop = Arc::new(AtomicBool::new(false));
+            if repeat {
+                output_mixer.add(stoppable(source.repeat_infinite(), stop.clone()));
+            } else {
+                output_mixer.add(stoppable(source, stop.clone()));
+            }
+            this.stop_ringtone = Some(stop);
             Some(())
         });
     }
@@ -298,7 +387,22 @@ impl Audio {
         });
     }
 
-    fn sound_source(&mut self, sound: Sound, cx: &App) -> Result<impl Source + use<>> {
+    fn ringtone_source(&mut self, ringtone: &Ringtone, cx: &App) -> Result<DecodedSound> {
+        match ringtone {
+            Ringtone::Sound(sound) => self.sound_source(*sound, cx),
+            Ringtone::File { path, fallback } => match decode_file(path) {
+                Ok(source) => Ok(source),
+                Err(error) => {
+                    log::error!(
+                        "Could not play ringtone {path:?}, playing the default instead: {error:#}"
+                    );
+                    self.sound_source(*fallback, cx)
+                }
+            },
+        }
+    }
+
+    fn sound_source(&mut self, sound: Sound, cx: &App) -> Result<DecodedSound> {
         if let Some(wav) = self.source_cache.get(&sound) {
             return Ok(wav.clone());
         }
@@ -319,9 +423,32 @@ impl Audio {
     }
 }
 
+fn decode_file(path: &Path) -> Result<DecodedSound> {
+    // The file isn't cached, so that changes to it are picked up by the next call.
+    let bytes = std::fs::read(path).context("Could not read file")?;
+    let source = Decoder::new(Cursor::new(bytes)).context("Could not decode file")?;
+    Ok(source.buffered())
+}
+
+fn stoppable(
+    source: impl Source + Send + 'static,
+    stop: Arc<AtomicBool>,
+) -> impl Source + Send + 'static {
+    source
+        .stoppable()
+        .periodic_access(Duration::from_millis(50), move |source| {
+            if stop.load(Ordering::Relaxed) {
+                source.stop();
+            }
+        })
+}
+
 #[cfg(not(any(all(target_os = "windows", target_env = "gnu"), target_os = "freebsd")))]
 pub struct VoipParts {
     echo_canceller: Arc<Mutex<apm::AudioProcessingModule>>,
+    /// Whether the microphone goes through `echo_canceller`. It's fixed once the microphone is
+    /// open.
+    echo_cancellation: bool,
     replays: replays::Replays,
     legacy_audio_compatible: bool,
     input_audio_device: Option<DeviceId>,
@@ -329,20 +456,22 @@ pub struct VoipParts {
 
 #[cfg(not(any(all(target_os = "windows", target_env = "gnu"), target_os = "freebsd")))]
 impl VoipParts {
-    pub fn new(cx: &AsyncApp) -> anyhow::Result<Self> {
+    pub fn new(
+        input_audio_device: Option<DeviceId>,
+        echo_cancellation: bool,
+        cx: &AsyncApp,
+    ) -> anyhow::Result<Self> {
         let (apm, replays) = cx.read_default_global::<Audio, _>(|audio, _| {
             (Arc::clone(&audio.echo_canceller), audio.replays.clone())
         });
         let legacy_audio_compatible =
             AudioSettings::try_read_global(cx, |settings| settings.legacy_audio_compatible)
                 .unwrap_or(true);
-        let input_audio_device =
-            AudioSettings::try_read_global(cx, |settings| settings.input_audio_device.clone())
-                .flatten();
 
         Ok(Self {
             legacy_audio_compatible,
             echo_canceller: apm,
+            echo_cancellation,
             replays,
             input_audio_device,
         })
@@ -42,13 +42,11 @@ pub struct AudioSettings {
     ///
     /// You need to rejoin a call for this setting to apply
     pub legacy_audio_compatible: bool,
-    /// Requires 'rodio_audio: true'
-    ///
-    /// Select specific output audio device.
+    /// Select specific output audio device. While it's unplugged during a call,
+    /// the system default is used instead.
     pub output_audio_device: Option<DeviceId>,
-    /// Requires 'rodio_audio: true'
-    ///
-    /// Select specific input audio device.
+    /// Select specific input audio device. While it's unplugged during a call,
+    /// the system default is used instead.
     pub input_audio_device: Option<DeviceId>,
 }
 
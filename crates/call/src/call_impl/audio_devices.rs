@@ -0,0 +1,204 @@
+//! Picks the microphone and speaker that a call uses, moving to another one when the one in use
+//! is unplugged.
+
+use livekit_client::{AudioDevice, AudioDeviceKind};
+
+/// Which device of one kind a call uses.
+///
+/// The user's preferred device is used whenever it's connected. When it isn't, or there's no
+/// preference, the call follows the system default, going back to the preferred device once it's
+/// plugged back in.
+#[derive(Debug)]
+pub struct AudioDeviceRouting {
+    kind: AudioDeviceKind,
+    preferred: Option<String>,
+    active: Option<AudioDevice>,
+    /// Whether the connected devices have been listed yet. Until then, the call uses the
+    /// preferred device if it's connected and the default one otherwise.
+    devices_listed: bool,
+}
+
+impl AudioDeviceRouting {
+    pub fn new(kind: AudioDeviceKind, preferred: Option<String>) -> Self {
+        Self {
+            kind,
+            preferred,
+            active: None,
+            devices_listed: false,
+        }
+    }
+
+    pub fn active(&self) -> Option<&AudioDevice> {
+        self.active.as_ref()
+    }
+
+    /// The id of the device to capture from or play on, where `None` is the default one.
+    pub fn device_id(&self) -> Option<String> {
+        match &self.active {
+            Some(device) => Some(device.id.clone()),
+            None => self.preferred.clone(),
+        }
+    }
+
+    /// Picks the device to use among the ones that are now connected. Returns it if the call
+    /// should move to it without the user having asked, which they should be told about.
+    ///
+    /// Nothing is returned when the devices are first listed, since the call already uses the
+    /// device that's picked then, nor when there's no device of this kind left to move to.
+    pub fn devices_changed(&mut self, devices: &[AudioDevice]) -> Option<AudioDevice> {
+        let previous_id = self.active.as_ref().map(|device| device.id.clone());
+        let devices_listed = std::mem::replace(&mut self.devices_listed, true);
+        self.active = self.pick(devices).cloned();
+        let active = self.active.as_ref()?;
+        (devices_listed && previous_id.as_ref() != Some(&active.id)).then(|| active.clone())
+    }
+
+    /// Sets the device the user prefers, where `None` follows the system default. Returns
+    /// whether the call should move to another device.
+    pub fn set_preferred(&mut self, preferred: Option<String>, devices: &[AudioDevice]) -> bool {
+        if self.preferred == preferred {
+            return false;
+        }
+        let previous_id = self.device_id();
+        self.preferred = preferred;
+        if self.devices_listed {
+            self.active = self.pick(devices).cloned();
+        }
+        self.device_id() != previous_id
+    }
+
+    fn pick<'a>(&self, devices: &'a [AudioDevice]) -> Option<&'a AudioDevice> {
+        let mut devices = devices.iter().filter(|device| device.kind == self.kind);
+        self.preferred
+            .as_ref()
+            .and_then(|preferred| devices.clone().find(|device| device.id == *preferred))
+            .or_else(|| devices.clone().find(|device| device.is_default))
+            .or_else(|| devices.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, kind: AudioDeviceKind, is_default: bool) -> AudioDevice {
+        AudioDevice {
+            id: id.to_string(),
+            name: format!("{id} name"),
+            kind,
+            is_default,
+        }
+    }
+
+    fn microphone(id: &str) -> AudioDevice {
+        device(id, AudioDeviceKind::Input, false)
+    }
+
+    fn default_microphone(id: &str) -> AudioDevice {
+        device(id, AudioDeviceKind::Input, true)
+    }
+
+    fn active_id(routing: &AudioDeviceRouting) -> Option<&str> {
+        routing.active().map(|device| device.id.as_str())
+    }
+
+    #[test]
+    fn test_preferred_device_fails_over_to_default() {
+        let mut routing =
+            AudioDeviceRouting::new(AudioDeviceKind::Input, Some("headset".to_string()));
+        assert_eq!(routing.device_id().as_deref(), Some("headset"));
+
+        // The first list doesn't switch anything, since the call was already set up with the
+        // preferred device.
+        let devices = [
+            default_microphone("built-in"),
+            microphone("headset"),
+            device("speakers", AudioDeviceKind::Output, true),
+        ];
+        assert_eq!(routing.devices_changed(&devices), None);
+        assert_eq!(active_id(&routing), Some("headset"));
+
+        // Unplugging the headset moves to the default microphone.
+        let devices = [
+            default_microphone("built-in"),
+            device("speakers", AudioDeviceKind::Output, true),
+        ];
+        assert_eq!(
+            routing.devices_changed(&devices),
+            Some(default_microphone("built-in"))
+        );
+        assert_eq!(routing.device_id().as_deref(), Some("built-in"));
+        assert_eq!(routing.preferred.as_deref(), Some("headset"));
+
+        // Nothing changes while the list stays the same.
+        assert_eq!(routing.devices_changed(&devices), None);
+
+        // Plugging it back in moves back to it.
+        let devices = [default_microphone("built-in"), microphone("headset")];
+        assert_eq!(
+            routing.devices_changed(&devices),
+            Some(microphone("headset"))
+        );
+        assert_eq!(active_id(&routing), Some("headset"));
+    }
+
+    #[test]
+    fn test_following_the_default_device() {
+        let mut routing = AudioDeviceRouting::new(AudioDeviceKind::Input, None);
+        assert_eq!(routing.device_id(), None);
+        assert_eq!(
+            routing.devices_changed(&[microphone("webcam"), default_microphone("built-in")]),
+            None
+        );
+        assert_eq!(active_id(&routing), Some("built-in"));
+
+        // A new default is followed.
+        assert_eq!(
+            routing.devices_changed(&[default_microphone("webcam"), microphone("built-in")]),
+            Some(default_microphone("webcam"))
+        );
+
+        // Without a default, any device of the right kind is used.
+        assert_eq!(
+            routing.devices_changed(&[
+                microphone("built-in"),
+                device("webcam", AudioDeviceKind::Output, true),
+            ]),
+            Some(microphone("built-in"))
+        );
+
+        // With no device left there's nothing to move to, but plugging one in uses it.
+        assert_eq!(routing.devices_changed(&[]), None);
+        assert_eq!(active_id(&routing), None);
+        assert_eq!(
+            routing.devices_changed(&[microphone("headset")]),
+            Some(microphone("headset"))
+        );
+    }
+
+    #[test]
+    fn test_setting_the_preferred_device() {
+        let devices = [default_microphone("built-in"), microphone("headset")];
+        let mut routing = AudioDeviceRouting::new(AudioDeviceKind::Input, None);
+
+        // Before the devices are listed, the preferred device is used as is.
+        assert!(routing.set_preferred(Some("headset".to_string()), &[]));
+        assert_eq!(routing.device_id().as_deref(), Some("headset"));
+        assert_eq!(routing.devices_changed(&devices), None);
+        assert_eq!(active_id(&routing), Some("headset"));
+
+        assert!(!routing.set_preferred(Some("headset".to_string()), &devices));
+        assert!(routing.set_preferred(None, &devices));
+        assert_eq!(active_id(&routing), Some("built-in"));
+        // Preferring the device that's already used by default doesn't move anything.
+        assert!(!routing.set_preferred(Some("built-in".to_string()), &devices));
+
+        // Preferring a device that isn't connected keeps using the default one until it is.
+        assert!(!routing.set_preferred(Some("usb".to_string()), &devices));
+        assert_eq!(active_id(&routing), Some("built-in"));
+        assert_eq!(
+            routing.devices_changed(&[default_microphone("built-in"), microphone("usb")]),
+            Some(microphone("usb"))
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use client::{ErrorCode, ErrorExt as _};
+use workspace::ParticipantCapabilities;
+
+/// Why an [`crate::ActiveCall`] failed to start, join, or answer a call.
+#[derive(Debug, thiserror::Error)]
+pub enum CallError {
+    #[error("user was already invited")]
+    AlreadyInvited,
+    #[error("cannot join while on another call")]
+    AlreadyInCall,
+    #[error("no incoming call")]
+    NoIncomingCall,
+    #[error("already asked to join this channel")]
+    AlreadyRequested,
+    #[error("no request to join the channel")]
+    NoJoinRequest,
+    #[error("you do not have permission to join this call")]
+    PermissionDenied,
+    /// The server doesn't report this yet, so nothing maps to it.
+    #[error("the call is full")]
+    ChannelFull,
+    #[error("could not reach the server")]
+    ServerUnreachable,
+    /// Either this client or the server is running an incompatible version.
+    #[error("the call requires a different version of Zed")]
+    RoomVersionMismatch,
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// The local participant's role doesn't allow what they tried to do in the room, so it was
+/// refused without asking the server.
+#[derive(Debug, thiserror::Error)]
+#[error("your role in this call doesn't allow you to {}", .capability.description())]
+pub struct PermissionDenied {
+    pub capability: ParticipantCapabilities,
+}
+
+impl CallError {
+    /// Converts back into an [`anyhow::Error`] whose [`ErrorCode`] matches the variant, for
+    /// callers that branch on error codes.
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            Self::PermissionDenied => ErrorCode::Forbidden.into(),
+            Self::ServerUnreachable => ErrorCode::Disconnected.into(),
+            Self::RoomVersionMismatch => ErrorCode::UpgradeRequired.into(),
+            Self::Other(error) => error,
+            error => anyhow::Error::new(error),
+        }
+    }
+}
+
+impl From<anyhow::Error> for CallError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.error_code() {
+            ErrorCode::Forbidden => Self::PermissionDenied,
+            ErrorCode::Disconnected => Self::ServerUnreachable,
+            ErrorCode::UpgradeRequired | ErrorCode::RemoteUpgradeRequired => {
+                Self::RoomVersionMismatch
+            }
+            _ => Self::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::ErrorCodeExt as _;
+
+    #[test]
+    fn test_error_codes_map_to_variants() {
+        assert!(matches!(
+            CallError::from(ErrorCode::Forbidden.anyhow()),
+            CallError::PermissionDenied
+        ));
+        assert!(matches!(
+            CallError::from(ErrorCode::Disconnected.anyhow()),
+            CallError::ServerUnreachable
+        ));
+        assert!(matches!(
+            CallError::from(ErrorCode::UpgradeRequired.anyhow()),
+            CallError::RoomVersionMismatch
+        ));
+        assert!(matches!(
+            CallError::from(ErrorCode::RemoteUpgradeRequired.anyhow()),
+            CallError::RoomVersionMismatch
+        ));
+
+        // Codes survive context added on the way up.
+        let error = ErrorCode::Forbidden
+            .message("user is not a channel member".into())
+            .anyhow()
+            .context("joining channel");
+        assert!(matches!(
+            CallError::from(error),
+            CallError::PermissionDenied
+        ));
+    }
+
+    #[test]
+    fn test_unmapped_errors_are_preserved() {
+        let error = CallError::from(ErrorCode::NoSuchChannel.anyhow());
+        let CallError::Other(error) = error else {
+            panic!("expected CallError::Other, got {error:?}");
+        };
+        assert_eq!(error.error_code(), ErrorCode::NoSuchChannel);
+
+        let error = CallError::from(anyhow::anyhow!("not connected"));
+        assert!(matches!(error, CallError::Other(_)));
+        assert_eq!(error.to_string(), "not connected");
+    }
+
+    #[test]
+    fn test_into_anyhow_round_trips_error_codes() {
+        let round_trip = |error: CallError| CallError::from(error.into_anyhow());
+        assert!(matches!(
+            round_trip(CallError::PermissionDenied),
+            CallError::PermissionDenied
+        ));
+        assert!(matches!(
+            round_trip(CallError::ServerUnreachable),
+            CallError::ServerUnreachable
+        ));
+        assert!(matches!(
+            round_trip(CallError::RoomVersionMismatch),
+            CallError::RoomVersionMismatch
+        ));
+        assert_eq!(
+            CallError::Other(ErrorCode::NoSuchChannel.anyhow())
+                .into_anyhow()
+                .error_code(),
+            ErrorCode::NoSuchChannel
+        );
+        assert_eq!(
+            CallError::AlreadyInvited.into_anyhow().to_string(),
+            "user was already invited"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use client::{ChannelId, User};
+use collections::HashMap;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How long a request to join a channel waits for an answer. After this, the admins it was sent
+/// to stop being asked, and the requester can ask again.
+pub const JOIN_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Someone who isn't a member of a channel, asking a channel admin in its call to let them in.
+#[derive(Clone, Debug)]
+pub struct JoinRequest {
+    pub channel_id: ChannelId,
+    pub requester: Arc<User>,
+    expires_at: Instant,
+}
+
+/// The requests to join channels that are waiting on an answer, both the ones the local user
+/// made and the ones they were asked.
+#[derive(Debug, Default)]
+pub struct JoinRequests {
+    /// When each of the local user's requests expires, by channel.
+    outgoing: HashMap<ChannelId, Instant>,
+    /// The requests the local user was asked to answer, oldest first.
+    incoming: Vec<JoinRequest>,
+}
+
+impl JoinRequests {
+    /// Records a request to join `channel_id`, unless one that hasn't expired is already
+    /// waiting for an answer.
+    pub fn start_outgoing(&mut self, channel_id: ChannelId, now: Instant) -> bool {
+        if self.is_outgoing(channel_id, now) {
+            return false;
+        }
+        self.outgoing.insert(channel_id, now + JOIN_REQUEST_TIMEOUT);
+        true
+    }
+
+    /// Forgets the request to join `channel_id`, once it's answered or couldn't be sent.
+    pub fn finish_outgoing(&mut self, channel_id: ChannelId) -> bool {
+        self.outgoing.remove(&channel_id).is_some()
+    }
+
+    pub fn is_outgoing(&self, channel_id: ChannelId, now: Instant) -> bool {
+        self.outgoing
+            .get(&channel_id)
+            .is_some_and(|expires_at| *expires_at > now)
+    }
+
+    pub fn incoming(&self) -> &[JoinRequest] {
+        &self.incoming
+    }
+
+    /// Adds a request from `requester` to join `channel_id`. The same user asking to join the
+    /// same channel again only restarts the request's timeout.
+    pub fn receive(&mut self, channel_id: ChannelId, requester: Arc<User>, now: Instant) {
+        let expires_at = now + JOIN_REQUEST_TIMEOUT;
+        if let Some(request) = self.incoming.iter_mut().find(|request| {
+            request.channel_id == channel_id && request.requester.id == requester.id
+        }) {
+            request.expires_at = expires_at;
+        } else {
+            self.incoming.push(JoinRequest {
+                channel_id,
+                requester,
+                expires_at,
+            });
+        }
+    }
+
+    /// Takes the request from `requester_id` to join `channel_id` out of the incoming requests,
+    /// to answer it or because it was answered elsewhere.
+    pub fn take_incoming(
+        &mut self,
+        requester_id: u64,
+        channel_id: ChannelId,
+    ) -> Option<JoinRequest> {
+        let ix = self.incoming.iter().position(|request| {
+            request.channel_id == channel_id && request.requester.id == requester_id
+        })?;
+        Some(self.incoming.remove(ix))
+    }
+
+    /// Drops the requests that have gone unanswered for too long, returning whether any of the
+    /// incoming ones were dropped.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        self.outgoing.retain(|_, expires_at| *expires_at > now);
+        let incoming_count = self.incoming.len();
+        self.incoming.retain(|request| request.expires_at > now);
+        self.incoming.len() != incoming_count
+    }
+
+    /// When the next request expires, if any are waiting.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.outgoing
+            .values()
+            .copied()
+            .chain(self.incoming.iter().map(|request| request.expires_at))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64) -> Arc<User> {
+        Arc::new(User {
+            id,
+            github_login: format!("user-{id}").into(),
+            avatar_uri: "".into(),
+            name: None,
+        })
+    }
+
+    fn incoming(requests: &JoinRequests) -> Vec<(u64, u64)> {
+        requests
+            .incoming()
+            .iter()
+            .map(|request| (request.requester.id, request.channel_id.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_outgoing_requests() {
+        let now = Instant::now();
+        let channel = ChannelId(1);
+        let mut requests = JoinRequests::default();
+
+        assert!(requests.start_outgoing(channel, now));
+        assert!(requests.is_outgoing(channel, now));
+        // Asking again while waiting for an answer is deduplicated, but other channels aren't.
+        assert!(!requests.start_outgoing(channel, now + Duration::from_secs(1)));
+        assert!(requests.start_outgoing(ChannelId(2), now));
+
+        assert!(requests.finish_outgoing(channel));
+        assert!(!requests.finish_outgoing(channel));
+        assert!(requests.start_outgoing(channel, now));
+
+        // An unanswered request can be made again once it expires.
+        let later = now + JOIN_REQUEST_TIMEOUT;
+        assert!(!requests.is_outgoing(channel, later));
+        assert!(requests.start_outgoing(channel, later));
+        assert_eq!(requests.next_expiry(), Some(now + JOIN_REQUEST_TIMEOUT));
+        requests.expire(later);
+        assert_eq!(requests.next_expiry(), Some(later + JOIN_REQUEST_TIMEOUT));
+    }
+
+    #[test]
+    fn test_incoming_requests() {
+        let now = Instant::now();
+        let mut requests = JoinRequests::default();
+
+        requests.receive(ChannelId(1), user(10), now);
+        requests.receive(ChannelId(2), user(10), now);
+        requests.receive(ChannelId(1), user(11), now + Duration::from_secs(10));
+        assert_eq!(incoming(&requests), [(10, 1), (10, 2), (11, 1)]);
+
+        // The same user asking for the same channel again restarts its timeout, and keeps its
+        // place.
+        requests.receive(ChannelId(1), user(10), now + Duration::from_secs(20));
+        assert_eq!(incoming(&requests), [(10, 1), (10, 2), (11, 1)]);
+        assert_eq!(requests.next_expiry(), Some(now + JOIN_REQUEST_TIMEOUT));
+
+        assert!(!requests.expire(now + Duration::from_secs(1)));
+        assert!(requests.expire(now + JOIN_REQUEST_TIMEOUT));
+        assert_eq!(incoming(&requests), [(10, 1), (11, 1)]);
+        assert!(requests.expire(now + JOIN_REQUEST_TIMEOUT + Duration::from_secs(10)));
+        assert_eq!(incoming(&requests), [(10, 1)]);
+
+        assert!(requests.take_incoming(11, ChannelId(1)).is_none());
+        let request = requests.take_incoming(10, ChannelId(1)).unwrap();
+        assert_eq!(request.requester.id, 10);
+        assert!(requests.incoming().is_empty());
+        assert_eq!(requests.next_expiry(), None);
+    }
+}
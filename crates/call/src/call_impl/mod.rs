@@ -1,33 +1,78 @@
+mod audio_devices;
+mod call_error;
+mod join_requests;
 pub mod participant;
+pub mod remote_control;
+mod ringer;
 pub mod room;
+pub mod screen_share_quality;
+mod secret_files;
+mod session_log;
 
 use anyhow::{Context as _, Result, anyhow};
-use audio::Audio;
-use client::{ChannelId, Client, TypedEnvelope, User, UserStore, ZED_ALWAYS_ACTIVE, proto};
+use audio::{Audio, Sound};
+use client::{
+    ChannelId, Client, ErrorExt as _, Status, TypedEnvelope, User, UserStore, ZED_ALWAYS_ACTIVE,
+    proto,
+};
 use collections::HashSet;
+use fs::Fs;
 use futures::{Future, FutureExt, channel::oneshot, future::Shared};
 use gpui::{
-    AnyView, App, AppContext as _, AsyncApp, Context, Entity, EventEmitter, Subscription, Task,
-    WeakEntity, Window,
+    AnyView, App, AppContext as _, AsyncApp, Context, Entity, EventEmitter, PromptLevel,
+    Subscription, Task, WeakEntity, Window,
 };
 use postage::watch;
-use project::Project;
+use project::{Project, ProjectPath};
+use ringer::RingCommand;
 use room::Event;
-use settings::Settings;
+use settings::{ChannelCallSettingsContent, Settings};
 use std::sync::Arc;
+use util::{
+    ResultExt as _,
+    state_cell::{self, StateCell},
+};
 use workspace::{
-    ActiveCallEvent, AnyActiveCall, GlobalAnyActiveCall, Pane, RemoteCollaborator, SharedScreen,
-    Workspace,
+    ActiveCallEvent, AnyActiveCall, GlobalAnyActiveCall, Pane, ParticipantCapabilities,
+    ProjectActivity, RemoteCollaborator, SharedScreen, Workspace,
+    shared_screen::{RemoteControlStatus, RequestControl, ScreenControl},
 };
 
-pub use livekit_client::{RemoteVideoTrack, RemoteVideoTrackView, RemoteVideoTrackViewEvent};
+pub use call_error::{CallError, PermissionDenied};
+pub use join_requests::{JOIN_REQUEST_TIMEOUT, JoinRequest};
+pub use livekit_client::{
+    AudioProcessing, RemoteVideoTrack, RemoteVideoTrackView, RemoteVideoTrackViewEvent,
+};
 pub use room::Room;
+pub use session_log::{Attendance, CallSummary, ParticipantSummary, SessionLog, SharedProject};
 
 use crate::call_settings::CallSettings;
+use join_requests::JoinRequests;
 
 pub fn init(client: Arc<Client>, user_store: Entity<UserStore>, cx: &mut App) {
     let active_call = cx.new(|cx| ActiveCall::new(client, user_store, cx));
-    cx.set_global(GlobalAnyActiveCall(Arc::new(ActiveCallEntity(active_call))))
+    cx.set_global(GlobalAnyActiveCall(Arc::new(ActiveCallEntity(active_call))));
+    cx.on_action(|_: &zed_actions::call::PreviewIncomingCallSound, cx| {
+        let settings = CallSettings::get_global(cx);
+        if let Some(ringtone) = settings.ringtone() {
+            let volume = settings.incoming_call_volume;
+            Audio::preview_ringtone(&ringtone, volume, cx);
+        }
+    });
+    cx.on_action(
+        |_: &zed_actions::call::RememberChannelCallPreferences, cx| {
+            ActiveCall::global(cx)
+                .read(cx)
+                .remember_channel_preferences(cx)
+                .log_err();
+        },
+    );
+}
+
+/// Plays a sound for something that happened in the call, at the configured volume.
+pub(crate) fn play_call_sound(sound: Sound, cx: &mut App) {
+    let volume = CallSettings::get_global(cx).call_notification_volume;
+    Audio::play_sound_at_volume(sound, volume, cx);
 }
 
 #[derive(Clone)]
@@ -70,10 +115,26 @@ impl AnyActiveCall for ActiveCallEntity {
             user: participant.user.clone(),
             peer_id: participant.peer_id,
             location: participant.location,
+            active_path: participant.active_path.clone(),
+            cursor_row: participant.cursor_row,
             participant_index: participant.participant_index,
+            capabilities: participant.capabilities(),
         })
     }
 
+    fn set_active_location(
+        &self,
+        project: &Entity<Project>,
+        active_path: Option<(ProjectPath, Option<u32>)>,
+        cx: &mut App,
+    ) {
+        if let Some(room) = self.0.read(cx).room().cloned() {
+            room.update(cx, |room, cx| {
+                room.set_active_location(project, active_path, cx)
+            });
+        }
+    }
+
     fn is_sharing_project(&self, cx: &App) -> bool {
         self.0
             .read(cx)
@@ -87,11 +148,12 @@ impl AnyActiveCall for ActiveCallEntity {
         })
     }
 
-    fn local_participant_is_guest(&self, cx: &App) -> bool {
+    fn local_capabilities(&self, cx: &App) -> ParticipantCapabilities {
         self.0
             .read(cx)
             .room()
-            .map_or(false, |room| room.read(cx).local_participant_is_guest())
+            .map(|room| room.read(cx).local_capabilities())
+            .unwrap_or_default()
     }
 
     fn client(&self, cx: &App) -> Arc<Client> {
@@ -99,7 +161,10 @@ impl AnyActiveCall for ActiveCallEntity {
     }
 
     fn share_on_join(&self, cx: &App) -> bool {
-        CallSettings::get_global(cx).share_on_join
+        match self.0.read(cx).room() {
+            Some(room) => room.read(cx).join_preferences().share_project,
+            None => CallSettings::get_global(cx).share_on_join,
+        }
     }
 
     fn join_channel(&self, channel_id: ChannelId, cx: &mut App) -> Task<Result<bool>> {
@@ -107,11 +172,28 @@ impl AnyActiveCall for ActiveCallEntity {
             .0
             .update(cx, |this, cx| this.join_channel(channel_id, cx));
         cx.spawn(async move |_cx| {
-            let result = task.await?;
+            let result = task.await.map_err(CallError::into_anyhow)?;
             Ok(result.is_some())
         })
     }
 
+    fn join_channel_listen_only(&self, channel_id: ChannelId, cx: &mut App) -> Task<Result<bool>> {
+        let task = self
+            .0
+            .update(cx, |this, cx| this.join_channel_listen_only(channel_id, cx));
+        cx.spawn(async move |_cx| {
+            let result = task.await.map_err(CallError::into_anyhow)?;
+            Ok(result.is_some())
+        })
+    }
+
+    fn request_to_join_channel(&self, channel_id: ChannelId, cx: &mut App) -> Task<Result<()>> {
+        let task = self
+            .0
+            .update(cx, |this, cx| this.request_to_join_channel(channel_id, cx));
+        cx.spawn(async move |_cx| task.await.map_err(CallError::into_anyhow))
+    }
+
     fn room_update_completed(&self, cx: &mut App) -> Task<()> {
         let Some(room) = self.0.read(cx).room().cloned() else {
             return Task::ready(());
@@ -122,9 +204,12 @@ impl AnyActiveCall for ActiveCallEntity {
         })
     }
 
-    fn most_active_project(&self, cx: &App) -> Option<(u64, u64)> {
-        let room = self.0.read(cx).room()?;
-        room.read(cx).most_active_project(cx)
+    fn project_activity(&self, cx: &App) -> Vec<ProjectActivity> {
+        self.0
+            .read(cx)
+            .room()
+            .map(|room| room.read(cx).project_activity(cx))
+            .unwrap_or_default()
     }
 
     fn share_project(&self, project: Entity<Project>, cx: &mut App) -> Task<Result<u64>> {
@@ -178,6 +263,28 @@ impl AnyActiveCall for ActiveCallEntity {
                             participant_id: *participant_id,
                         })
                     }
+                    room::Event::ProjectShared {
+                        project_id,
+                        remote_id,
+                    } => Some(ActiveCallEvent::ProjectShared {
+                        project_id: *project_id,
+                        remote_id: *remote_id,
+                    }),
+                    room::Event::ProjectUnshared { project_id } => {
+                        Some(ActiveCallEvent::ProjectUnshared {
+                            project_id: *project_id,
+                        })
+                    }
+                    room::Event::LocalCapabilitiesChanged { .. } => {
+                        Some(ActiveCallEvent::LocalCapabilitiesChanged)
+                    }
+                    room::Event::JoinProjectProgress {
+                        project_id,
+                        progress,
+                    } => Some(ActiveCallEvent::JoinProjectProgress {
+                        project_id: *project_id,
+                        progress: *progress,
+                    }),
                     _ => None,
                 };
                 if let Some(event) = mapped {
@@ -196,7 +303,7 @@ impl AnyActiveCall for ActiveCallEntity {
     ) -> Option<Entity<workspace::SharedScreen>> {
         let room = self.0.read(cx).room()?.clone();
         let participant = room.read(cx).remote_participant_for_peer_id(peer_id)?;
-        let track = participant.video_tracks.values().next()?.clone();
+        let track = participant.screen_share_tracks().next()?.clone();
         let user = participant.user.clone();
 
         for item in pane.read(cx).items_of_type::<SharedScreen>() {
@@ -205,6 +312,35 @@ impl AnyActiveCall for ActiveCallEntity {
             }
         }
 
+        let control = cx.new(|cx: &mut Context<ScreenControl>| {
+            cx.subscribe(
+                &room,
+                move |control: &mut ScreenControl,
+                      room: Entity<Room>,
+                      ev: &room::Event,
+                      cx: &mut Context<ScreenControl>| {
+                    if let room::Event::RemoteControlChanged { participant_id } = ev
+                        && *participant_id == peer_id
+                    {
+                        control.set_status(remote_control_status(room.read(cx), peer_id), cx);
+                    }
+                },
+            )
+            .detach();
+            let weak_room = room.downgrade();
+            cx.subscribe_self(move |_: &mut ScreenControl, _: &RequestControl, cx| {
+                weak_room
+                    .update(cx, |room, cx| room.request_control(peer_id, cx))
+                    .map(|task| task.detach_and_log_err(cx))
+                    .log_err();
+            })
+            .detach();
+            ScreenControl::default()
+        });
+        control.update(cx, |control, cx| {
+            control.set_status(remote_control_status(room.read(cx), peer_id), cx)
+        });
+
         Some(cx.new(|cx: &mut Context<SharedScreen>| {
             let my_sid = track.sid();
             cx.subscribe(
@@ -262,40 +398,125 @@ impl AnyActiveCall for ActiveCallEntity {
                 user,
                 AnyView::from(view),
                 clone_remote_video_track_view,
+                control,
                 cx,
             )
         }))
     }
 }
 
+fn remote_control_status(room: &Room, sharer: client::proto::PeerId) -> RemoteControlStatus {
+    let remote_control = room.remote_control();
+    if remote_control.controlled_screen(sharer).is_some() {
+        RemoteControlStatus::Granted
+    } else if remote_control.is_requesting(sharer) {
+        RemoteControlStatus::Requested
+    } else {
+        RemoteControlStatus::NotRequested
+    }
+}
+
+#[derive(Default)]
 pub struct OneAtATime {
-    cancel: Option<oneshot::Sender<()>>,
+    /// One sender per task that hasn't finished yet. Dropping a sender cancels its task.
+    cancel: Vec<oneshot::Sender<()>>,
+    /// Resolves once the most recently spawned task has finished.
+    last_task_finished: Option<Shared<oneshot::Receiver<()>>>,
+}
+
+/// Resolves once the task that was running before a [`OneAtATime`] spawn has finished,
+/// including dropping everything it was holding.
+#[derive(Clone)]
+pub struct PreviousTaskFinished(Option<Shared<oneshot::Receiver<()>>>);
+
+impl PreviousTaskFinished {
+    pub async fn wait(self) {
+        if let Some(finished) = self.0 {
+            finished.await.ok();
+        }
+    }
+}
+
+enum SpawnMode {
+    CancelPrevious,
+    Enqueue,
 }
 
 impl OneAtATime {
     /// spawn a task in the given context.
     /// if another task is spawned before that resolves, or if the OneAtATime itself is dropped, the first task will be cancelled and return Ok(None)
     /// otherwise you'll see the result of the task.
-    fn spawn<F, Fut, R>(&mut self, cx: &mut App, f: F) -> Task<Result<Option<R>>>
+    /// The new task only starts once the cancelled one has finished cleaning up.
+    pub fn spawn<F, Fut, R>(
+        &mut self,
+        cx: &mut App,
+        f: F,
+    ) -> (Task<Result<Option<R>>>, PreviousTaskFinished)
+    where
+        F: 'static + FnOnce(AsyncApp) -> Fut,
+        Fut: Future<Output = Result<R>>,
+        R: 'static,
+    {
+        self.spawn_with_mode(SpawnMode::CancelPrevious, cx, f)
+    }
+
+    /// Like [`Self::spawn`], but lets the previous task run to completion before starting the
+    /// new one instead of cancelling it.
+    pub fn enqueue<F, Fut, R>(
+        &mut self,
+        cx: &mut App,
+        f: F,
+    ) -> (Task<Result<Option<R>>>, PreviousTaskFinished)
+    where
+        F: 'static + FnOnce(AsyncApp) -> Fut,
+        Fut: Future<Output = Result<R>>,
+        R: 'static,
+    {
+        self.spawn_with_mode(SpawnMode::Enqueue, cx, f)
+    }
+
+    fn spawn_with_mode<F, Fut, R>(
+        &mut self,
+        mode: SpawnMode,
+        cx: &mut App,
+        f: F,
+    ) -> (Task<Result<Option<R>>>, PreviousTaskFinished)
     where
         F: 'static + FnOnce(AsyncApp) -> Fut,
         Fut: Future<Output = Result<R>>,
         R: 'static,
     {
-        let (tx, rx) = oneshot::channel();
-        self.cancel.replace(tx);
-        cx.spawn(async move |cx| {
-            futures::select_biased! {
-                _ = rx.fuse() => Ok(None),
-                result = f(cx.clone()).fuse() => result.map(Some),
+        match mode {
+            SpawnMode::CancelPrevious => self.cancel.clear(),
+            SpawnMode::Enqueue => self.cancel.retain(|cancel| !cancel.is_canceled()),
+        }
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        self.cancel.push(cancel_tx);
+
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let previous_task_finished =
+            PreviousTaskFinished(self.last_task_finished.replace(finished_rx.shared()));
+
+        let task = cx.spawn({
+            let previous_task_finished = previous_task_finished.clone();
+            async move |cx| {
+                // Dropped last, once `f`'s future has been dropped too.
+                let _finished_tx = finished_tx;
+                let run = async {
+                    previous_task_finished.wait().await;
+                    f(cx.clone()).await
+                };
+                futures::select_biased! {
+                    _ = cancel_rx.fuse() => Ok(None),
+                    result = run.fuse() => result.map(Some),
+                }
             }
-        })
+        });
+        (task, previous_task_finished)
     }
 
     fn running(&self) -> bool {
-        self.cancel
-            .as_ref()
-            .is_some_and(|cancel| !cancel.is_canceled())
+        self.cancel.iter().any(|cancel| !cancel.is_canceled())
     }
 }
 
@@ -307,6 +528,18 @@ pub struct IncomingCall {
     pub initial_project: Option<proto::ParticipantProject>,
 }
 
+impl IncomingCall {
+    /// The root names of the worktrees in the project the caller is sharing with the call, e.g.
+    /// "zed, docs", or `None` if they aren't sharing one.
+    pub fn initial_project_names(&self) -> Option<String> {
+        let project = self.initial_project.as_ref()?;
+        if project.worktree_root_names.is_empty() {
+            return None;
+        }
+        Some(project.worktree_root_names.join(", "))
+    }
+}
+
 /// Singleton global maintaining the user's participation in a room across workspaces.
 pub struct ActiveCall {
     room: Option<(Entity<Room>, Vec<Subscription>)>,
@@ -314,12 +547,14 @@ pub struct ActiveCall {
     location: Option<WeakEntity<Project>>,
     _join_debouncer: OneAtATime,
     pending_invites: HashSet<u64>,
-    incoming_call: (
-        watch::Sender<Option<IncomingCall>>,
-        watch::Receiver<Option<IncomingCall>>,
-    ),
+    incoming_call: StateCell<IncomingCall>,
+    join_requests: JoinRequests,
+    incoming_join_requests_tx: watch::Sender<Vec<JoinRequest>>,
+    incoming_join_requests_rx: watch::Receiver<Vec<JoinRequest>>,
+    join_request_expiry: Option<Task<()>>,
     client: Arc<Client>,
     user_store: Entity<UserStore>,
+    _ringer: Task<()>,
     _subscriptions: Vec<client::Subscription>,
 }
 
@@ -327,16 +562,51 @@ impl EventEmitter<Event> for ActiveCall {}
 
 impl ActiveCall {
     fn new(client: Arc<Client>, user_store: Entity<UserStore>, cx: &mut Context<Self>) -> Self {
+        let incoming_call = StateCell::new();
+        let this = cx.weak_entity();
+        let ringer = ringer::ring_for_incoming_calls(
+            incoming_call.subscribe(),
+            move |cx| {
+                this.upgrade()
+                    .is_some_and(|this| this.read(cx).room().is_some())
+            },
+            |command, cx| match command {
+                RingCommand::Start => {
+                    let settings = CallSettings::get_global(cx);
+                    if let Some(ringtone) = settings.ringtone() {
+                        let volume = settings.incoming_call_volume;
+                        Audio::start_ringing(&ringtone, volume, cx);
+                    }
+                }
+                RingCommand::Stop => Audio::stop_ringing(cx),
+            },
+            cx,
+        );
+        let (incoming_join_requests_tx, incoming_join_requests_rx) = watch::channel();
         Self {
             room: None,
             pending_room_creation: None,
             location: None,
             pending_invites: Default::default(),
-            incoming_call: watch::channel(),
-            _join_debouncer: OneAtATime { cancel: None },
+            incoming_call,
+            join_requests: JoinRequests::default(),
+            incoming_join_requests_tx,
+            incoming_join_requests_rx,
+            join_request_expiry: None,
+            _join_debouncer: OneAtATime::default(),
+            _ringer: ringer,
             _subscriptions: vec![
                 client.add_request_handler(cx.weak_entity(), Self::handle_incoming_call),
                 client.add_message_handler(cx.weak_entity(), Self::handle_call_canceled),
+                client.add_message_handler(cx.weak_entity(), Self::handle_join_channel_requested),
+                client.add_message_handler(
+                    cx.weak_entity(),
+                    Self::handle_join_channel_request_canceled,
+                ),
+                client.add_message_handler(
+                    cx.weak_entity(),
+                    Self::handle_join_channel_request_answered,
+                ),
             ],
             client,
             user_store,
@@ -368,7 +638,7 @@ impl ActiveCall {
             initial_project: envelope.payload.initial_project,
         };
         this.update(&mut cx, |this, _| {
-            *this.incoming_call.0.borrow_mut() = Some(call);
+            this.incoming_call.set(call);
         });
 
         Ok(proto::Ack {})
@@ -380,17 +650,70 @@ impl ActiveCall {
         mut cx: AsyncApp,
     ) -> Result<()> {
         this.update(&mut cx, |this, _| {
-            let mut incoming_call = this.incoming_call.0.borrow_mut();
-            if incoming_call
-                .as_ref()
-                .is_some_and(|call| call.room_id == envelope.payload.room_id)
+            this.incoming_call
+                .take_if(|call| call.room_id == envelope.payload.room_id);
+        });
+        Ok(())
+    }
+
+    async fn handle_join_channel_requested(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::JoinChannelRequested>,
+        mut cx: AsyncApp,
+    ) -> Result<()> {
+        let user_store = this.read_with(&cx, |this, _| this.user_store.clone());
+        let requester = user_store
+            .update(&mut cx, |user_store, cx| {
+                user_store.get_user(envelope.payload.requester_id, cx)
+            })
+            .await?;
+        this.update(&mut cx, |this, cx| {
+            let now = cx.background_executor().now();
+            this.join_requests
+                .receive(ChannelId(envelope.payload.channel_id), requester, now);
+            this.incoming_join_requests_changed(cx);
+        });
+        Ok(())
+    }
+
+    async fn handle_join_channel_request_canceled(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::JoinChannelRequestCanceled>,
+        mut cx: AsyncApp,
+    ) -> Result<()> {
+        this.update(&mut cx, |this, cx| {
+            if this
+                .join_requests
+                .take_incoming(
+                    envelope.payload.requester_id,
+                    ChannelId(envelope.payload.channel_id),
+                )
+                .is_some()
             {
-                incoming_call.take();
+                this.incoming_join_requests_changed(cx);
             }
         });
         Ok(())
     }
 
+    async fn handle_join_channel_request_answered(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::JoinChannelRequestAnswered>,
+        mut cx: AsyncApp,
+    ) -> Result<()> {
+        let channel_id = ChannelId(envelope.payload.channel_id);
+        this.update(&mut cx, |this, cx| {
+            this.join_requests.finish_outgoing(channel_id);
+            this.schedule_join_request_expiry(cx);
+            // An answer that arrives after the request expired still lets the user in.
+            cx.emit(Event::JoinRequestAnswered {
+                channel_id,
+                accepted: envelope.payload.accepted,
+            });
+        });
+        Ok(())
+    }
+
     pub fn global(cx: &App) -> Entity<Self> {
         Self::try_global(cx).unwrap()
     }
@@ -400,14 +723,20 @@ impl ActiveCall {
         any.0.entity().downcast::<Self>().ok()
     }
 
+    /// Calls `called_user_id`, creating a room first if there's none. The call only goes out
+    /// once `initial_project` is shared, even if it was already being shared when the invite was
+    /// made, so that the callee is offered the project as soon as they accept.
     pub fn invite(
         &mut self,
         called_user_id: u64,
         initial_project: Option<Entity<Project>>,
         cx: &mut Context<Self>,
-    ) -> Task<Result<()>> {
+    ) -> Task<Result<(), CallError>> {
+        if let Err(error) = self.check_connected() {
+            return Task::ready(Err(error));
+        }
         if !self.pending_invites.insert(called_user_id) {
-            return Task::ready(Err(anyhow!("user was already invited")));
+            return Task::ready(Err(CallError::AlreadyInvited));
         }
         cx.notify();
 
@@ -423,7 +752,7 @@ impl ActiveCall {
 
         let invite = if let Some(room) = room {
             cx.spawn(async move |_, cx| {
-                let room = room.await.map_err(|err| anyhow!("{err:?}"))?;
+                let room = room.await.map_err(|err| err.cloned())?;
 
                 let initial_project_id = if let Some(initial_project) = initial_project {
                     Some(
@@ -472,7 +801,7 @@ impl ActiveCall {
                 .shared();
             self.pending_room_creation = Some(room.clone());
             cx.background_spawn(async move {
-                room.await.map_err(|err| anyhow!("{err:?}"))?;
+                room.await.map_err(|err| err.cloned())?;
                 anyhow::Ok(())
             })
         };
@@ -492,7 +821,7 @@ impl ActiveCall {
                 this.pending_invites.remove(&called_user_id);
                 cx.notify();
             })?;
-            result
+            Ok(result?)
         })
     }
 
@@ -519,29 +848,60 @@ impl ActiveCall {
         })
     }
 
-    pub fn incoming(&self) -> watch::Receiver<Option<IncomingCall>> {
-        self.incoming_call.1.clone()
+    /// Yields the current incoming call, and then the latest one whenever it changes.
+    pub fn incoming(&self) -> state_cell::Subscription<IncomingCall> {
+        self.incoming_call.subscribe()
+    }
+
+    /// Joins the incoming call. The returned task resolves to the ID of the project the caller
+    /// is sharing with the call, if any, which is expected to be opened once the call is joined.
+    pub fn accept_incoming(
+        &mut self,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<u64>, CallError>> {
+        self.accept_incoming_internal(true, cx)
     }
 
-    pub fn accept_incoming(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+    /// Joins the incoming call without opening the project the caller is sharing with it, so
+    /// that nothing is synced until it's joined explicitly. The project stays listed in the room.
+    pub fn accept_incoming_call_only(
+        &mut self,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<u64>, CallError>> {
+        self.accept_incoming_internal(false, cx)
+    }
+
+    fn accept_incoming_internal(
+        &mut self,
+        join_initial_project: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<u64>, CallError>> {
         if self.room.is_some() {
-            return Task::ready(Err(anyhow!("cannot join while on another call")));
+            return Task::ready(Err(CallError::AlreadyInCall));
+        }
+        // Leave the incoming call in place when offline, so it can be accepted once reconnected.
+        if self.incoming_call.is_some()
+            && let Err(error) = self.check_connected()
+        {
+            return Task::ready(Err(error));
         }
 
-        let call = if let Some(call) = self.incoming_call.0.borrow_mut().take() {
-            call
-        } else {
-            return Task::ready(Err(anyhow!("no incoming call")));
+        let Some(call) = self.incoming_call.take() else {
+            return Task::ready(Err(CallError::NoIncomingCall));
         };
 
         if self.pending_room_creation.is_some() {
-            return Task::ready(Ok(()));
+            return Task::ready(Ok(None));
         }
 
         let room_id = call.room_id;
+        let initial_project_id = call
+            .initial_project
+            .filter(|_| join_initial_project)
+            .map(|project| project.id);
         let client = self.client.clone();
         let user_store = self.user_store.clone();
-        let join = self
+        let (join, _) = self
             ._join_debouncer
             .spawn(cx, move |cx| Room::join(room_id, client, user_store, cx));
 
@@ -552,17 +912,12 @@ impl ActiveCall {
             this.update(cx, |this, cx| {
                 this.report_call_event("Incoming Call Accepted", cx)
             })?;
-            Ok(())
+            Ok(initial_project_id)
         })
     }
 
-    pub fn decline_incoming(&mut self, _: &mut Context<Self>) -> Result<()> {
-        let call = self
-            .incoming_call
-            .0
-            .borrow_mut()
-            .take()
-            .context("no incoming call")?;
+    pub fn decline_incoming(&mut self, _: &mut Context<Self>) -> Result<(), CallError> {
+        let call = self.incoming_call.take().ok_or(CallError::NoIncomingCall)?;
         telemetry::event!("Incoming Call Declined", room_id = call.room_id);
         self.client.send(proto::DeclineCall {
             room_id: call.room_id,
@@ -570,27 +925,159 @@ impl ActiveCall {
         Ok(())
     }
 
+    /// Asks the channel admins in a channel's call to let the local user in. Once one of them
+    /// accepts, [`Self::join_channel`] succeeds.
+    pub fn request_to_join_channel(
+        &mut self,
+        channel_id: ChannelId,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<(), CallError>> {
+        if let Err(error) = self.check_connected() {
+            return Task::ready(Err(error));
+        }
+        let now = cx.background_executor().now();
+        if !self.join_requests.start_outgoing(channel_id, now) {
+            return Task::ready(Err(CallError::AlreadyRequested));
+        }
+        self.schedule_join_request_expiry(cx);
+
+        let client = self.client.clone();
+        cx.spawn(async move |this, cx| {
+            let result = client
+                .request(proto::RequestToJoinChannel {
+                    channel_id: channel_id.0,
+                })
+                .await;
+            this.update(cx, |this, cx| {
+                if result.is_ok() {
+                    telemetry::event!("Channel Join Requested", channel_id = channel_id.0);
+                } else {
+                    this.join_requests.finish_outgoing(channel_id);
+                    this.schedule_join_request_expiry(cx);
+                }
+            })?;
+            result?;
+            Ok(())
+        })
+    }
+
+    /// Whether the local user asked to join the channel and is waiting for an answer.
+    pub fn has_requested_to_join(&self, channel_id: ChannelId, cx: &App) -> bool {
+        self.join_requests
+            .is_outgoing(channel_id, cx.background_executor().now())
+    }
+
+    /// Yields the requests to join channels that are waiting on the local user's answer, and then
+    /// the latest ones whenever they change.
+    pub fn incoming_join_requests(&self) -> watch::Receiver<Vec<JoinRequest>> {
+        self.incoming_join_requests_rx.clone()
+    }
+
+    /// Answers a request to join a channel. Accepting it invites the requester into the channel,
+    /// and declining it tells them so without a reason.
+    pub fn respond_to_join_request(
+        &mut self,
+        requester_id: u64,
+        channel_id: ChannelId,
+        accept: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<(), CallError>> {
+        let Some(request) = self.join_requests.take_incoming(requester_id, channel_id) else {
+            return Task::ready(Err(CallError::NoJoinRequest));
+        };
+        self.incoming_join_requests_changed(cx);
+
+        let client = self.client.clone();
+        cx.background_spawn(async move {
+            client
+                .request(proto::RespondToJoinChannelRequest {
+                    channel_id: request.channel_id.0,
+                    requester_id: request.requester.id,
+                    accept,
+                })
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn incoming_join_requests_changed(&mut self, cx: &mut Context<Self>) {
+        *self.incoming_join_requests_tx.borrow_mut() = self.join_requests.incoming().to_vec();
+        self.schedule_join_request_expiry(cx);
+        cx.notify();
+    }
+
+    fn schedule_join_request_expiry(&mut self, cx: &mut Context<Self>) {
+        self.join_request_expiry = self.join_requests.next_expiry().map(|expires_at| {
+            let delay = expires_at.saturating_duration_since(cx.background_executor().now());
+            cx.spawn(async move |this, cx| {
+                cx.background_executor().timer(delay).await;
+                this.update(cx, |this, cx| {
+                    let now = cx.background_executor().now();
+                    if this.join_requests.expire(now) {
+                        this.incoming_join_requests_changed(cx);
+                    } else {
+                        this.schedule_join_request_expiry(cx);
+                    }
+                })
+                .ok();
+            })
+        });
+    }
+
     pub fn join_channel(
         &mut self,
         channel_id: ChannelId,
         cx: &mut Context<Self>,
-    ) -> Task<Result<Option<Entity<Room>>>> {
-        if let Some(room) = self.room().cloned() {
-            if room.read(cx).channel_id() == Some(channel_id) {
-                return Task::ready(Ok(Some(room)));
-            } else {
-                room.update(cx, |room, cx| room.clear_state(cx));
-            }
+    ) -> Task<Result<Option<Entity<Room>>, CallError>> {
+        self.join_channel_internal(channel_id, false, cx)
+    }
+
+    /// Joins the channel's call only to listen. Nothing is published, and the microphone and
+    /// camera aren't opened, until [`Room::start_participating`] is called.
+    pub fn join_channel_listen_only(
+        &mut self,
+        channel_id: ChannelId,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<Entity<Room>>, CallError>> {
+        self.join_channel_internal(channel_id, true, cx)
+    }
+
+    fn join_channel_internal(
+        &mut self,
+        channel_id: ChannelId,
+        listen_only: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<Entity<Room>>, CallError>> {
+        if let Some(room) = self.room().cloned()
+            && room.read(cx).channel_id() == Some(channel_id)
+        {
+            return Task::ready(Ok(Some(room)));
+        }
+        // Check before leaving the current room, so that failing to join doesn't also end it.
+        if let Err(error) = self.check_connected() {
+            return Task::ready(Err(error));
+        }
+        if let Some(room) = self.room() {
+            room.update(cx, |room, cx| room.clear_state(cx));
         }
 
         if self.pending_room_creation.is_some() {
             return Task::ready(Ok(None));
         }
 
+        let join_preferences = CallSettings::get_global(cx).join_preferences(Some(channel_id));
         let client = self.client.clone();
         let user_store = self.user_store.clone();
-        let join = self._join_debouncer.spawn(cx, move |cx| async move {
-            Room::join_channel(channel_id, client, user_store, cx).await
+        let (join, _) = self._join_debouncer.spawn(cx, move |cx| async move {
+            Room::join_channel(
+                channel_id,
+                join_preferences,
+                listen_only,
+                client,
+                user_store,
+                cx,
+            )
+            .await
         });
 
         cx.spawn(async move |this, cx| {
@@ -602,6 +1089,32 @@ impl ActiveCall {
         })
     }
 
+    /// Saves whether you're muted, deafened, and sharing a project as the preferences for
+    /// joining the current channel's call in the future.
+    pub fn remember_channel_preferences(&self, cx: &App) -> Result<()> {
+        let room = self.room().context("no active call")?.read(cx);
+        let channel_id = room.channel_id().context("the call isn't in a channel")?;
+        let export_summary_to_notes = CallSettings::get_global(cx)
+            .channels
+            .get(&channel_id)
+            .and_then(|channel| channel.export_summary_to_notes);
+        let preferences = ChannelCallSettingsContent {
+            mute_on_join: Some(room.muted_by_user()),
+            deafen_on_join: Some(room.is_deafened().unwrap_or(false)),
+            share_on_join: Some(room.is_sharing_project()),
+            export_summary_to_notes,
+        };
+        settings::update_settings_file(<dyn Fs>::global(cx), cx, move |settings, _| {
+            settings
+                .calls
+                .get_or_insert_default()
+                .channels
+                .get_or_insert_default()
+                .insert(channel_id.0, preferences);
+        });
+        Ok(())
+    }
+
     pub fn hang_up(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
         cx.notify();
         self.report_call_event("Call Ended", cx);
@@ -610,6 +1123,16 @@ impl ActiveCall {
 
         let channel_id = self.channel_id(cx);
         if let Some((room, _)) = self.room.take() {
+            if let Some(channel_id) = channel_id
+                && CallSettings::get_global(cx).exports_summary(channel_id)
+                && room.read(cx).remote_participants().is_empty()
+                && let Some(summary) = room.read(cx).call_summary()
+            {
+                cx.emit(Event::CallSummaryReady {
+                    channel_id,
+                    summary,
+                });
+            }
             cx.emit(Event::RoomLeft { channel_id });
             room.update(cx, |room, cx| room.leave(cx))
         } else {
@@ -630,6 +1153,44 @@ impl ActiveCall {
         }
     }
 
+    /// Shares `project`, first asking what to do about files in it that are likely to hold
+    /// secrets and haven't been asked about before. Resolves to `None` if the user cancels.
+    pub fn share_project_after_review(
+        &mut self,
+        project: Entity<Project>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<u64>>> {
+        let secret_files = secret_files::unreviewed_secret_files(&project, cx);
+        if secret_files.is_empty() {
+            let share = self.share_project(project, cx);
+            return cx.background_spawn(async move { share.await.map(Some) });
+        }
+
+        let detail = secret_files::describe_secret_files(&project, &secret_files, cx);
+        let answer = window.prompt(
+            PromptLevel::Warning,
+            "This project contains files that may hold secrets",
+            Some(&format!(
+                "{detail}\n\nGuests will be able to open these files unless you exclude them."
+            )),
+            &["Share Anyway", "Share Excluding These", "Cancel"],
+            cx,
+        );
+        cx.spawn(async move |this, cx| {
+            let exclude = match answer.await {
+                Ok(0) => false,
+                Ok(1) => true,
+                _ => return Ok(None),
+            };
+            cx.update(|cx| secret_files::review_secret_files(&project, &secret_files, exclude, cx))
+                .await?;
+            this.update(cx, |this, cx| this.share_project(project, cx))?
+                .await
+                .map(Some)
+        })
+    }
+
     pub fn unshare_project(
         &mut self,
         project: Entity<Project>,
@@ -685,6 +1246,11 @@ impl ActiveCall {
                         .and_then(|location| location.upgrade());
                     let channel_id = room.read(cx).channel_id();
                     cx.emit(Event::RoomJoined { channel_id });
+                    // The room may have connected before it was handed to us, in which case
+                    // nobody heard it say it started muted.
+                    if let Some(participant_count) = room.read(cx).auto_muted_on_join() {
+                        cx.emit(Event::MutedOnJoin { participant_count });
+                    }
                     room.update(cx, |room, cx| room.set_location(location.as_ref(), cx))
                 }
             } else {
@@ -706,6 +1272,16 @@ impl ActiveCall {
         &self.pending_invites
     }
 
+    /// Requests made while offline fail with an untyped "not connected" error, so check the
+    /// connection up front to report why.
+    fn check_connected(&self) -> Result<(), CallError> {
+        match *self.client.status().borrow() {
+            Status::Connected { .. } => Ok(()),
+            Status::UpgradeRequired => Err(CallError::RoomVersionMismatch),
+            _ => Err(CallError::ServerUnreachable),
+        }
+    }
+
     pub fn report_call_event(&self, operation: &'static str, cx: &mut App) {
         if let Some(room) = self.room() {
             let room = room.read(cx);
@@ -720,22 +1296,24 @@ impl ActiveCall {
 
 #[cfg(test)]
 mod test {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
     use gpui::TestAppContext;
 
     use crate::OneAtATime;
 
     #[gpui::test]
     async fn test_one_at_a_time(cx: &mut TestAppContext) {
-        let mut one_at_a_time = OneAtATime { cancel: None };
+        let mut one_at_a_time = OneAtATime::default();
 
         assert_eq!(
-            cx.update(|cx| one_at_a_time.spawn(cx, |_| async { Ok(1) }))
+            cx.update(|cx| one_at_a_time.spawn(cx, |_| async { Ok(1) }).0)
                 .await
                 .unwrap(),
             Some(1)
         );
 
-        let (a, b) = cx.update(|cx| {
+        let ((a, _), (b, _)) = cx.update(|cx| {
             (
                 one_at_a_time.spawn(cx, |_| async {
                     panic!("");
@@ -747,9 +1325,70 @@ mod test {
         assert_eq!(a.await.unwrap(), None::<u32>);
         assert_eq!(b.await.unwrap(), Some(3));
 
-        let promise = cx.update(|cx| one_at_a_time.spawn(cx, |_| async { Ok(4) }));
+        let (promise, _) = cx.update(|cx| one_at_a_time.spawn(cx, |_| async { Ok(4) }));
         drop(one_at_a_time);
 
         assert_eq!(promise.await.unwrap(), None);
     }
+
+    #[gpui::test]
+    async fn test_one_at_a_time_waits_for_cleanup(cx: &mut TestAppContext) {
+        let mut one_at_a_time = OneAtATime::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        // The first task is cancelled, and the second must not start until it has been dropped.
+        let (first, _) = cx.update(|cx| {
+            one_at_a_time.spawn(cx, {
+                let log = log.clone();
+                move |_| async move {
+                    let _cleanup = util::defer(move || log.borrow_mut().push("first cleaned up"));
+                    futures::future::pending::<anyhow::Result<u32>>().await
+                }
+            })
+        });
+        cx.run_until_parked();
+        let (second, previous_task_finished) = cx.update(|cx| {
+            one_at_a_time.spawn(cx, {
+                let log = log.clone();
+                move |_| async move {
+                    log.borrow_mut().push("second started");
+                    Ok(2)
+                }
+            })
+        });
+        previous_task_finished.wait().await;
+        assert_eq!(log.borrow().first(), Some(&"first cleaned up"));
+        assert_eq!(first.await.unwrap(), None);
+        assert_eq!(second.await.unwrap(), Some(2));
+        assert_eq!(*log.borrow(), ["first cleaned up", "second started"]);
+
+        // An enqueued task waits for the previous one to finish instead of cancelling it.
+        log.borrow_mut().clear();
+        let (first, _) = cx.update(|cx| {
+            one_at_a_time.spawn(cx, {
+                let log = log.clone();
+                move |cx| async move {
+                    cx.background_executor().timer(Duration::from_secs(1)).await;
+                    log.borrow_mut().push("first finished");
+                    Ok(1)
+                }
+            })
+        });
+        let (second, _) = cx.update(|cx| {
+            one_at_a_time.enqueue(cx, {
+                let log = log.clone();
+                move |_| async move {
+                    log.borrow_mut().push("second started");
+                    Ok(2)
+                }
+            })
+        });
+        cx.run_until_parked();
+        assert!(log.borrow().is_empty());
+
+        cx.executor().advance_clock(Duration::from_secs(1));
+        assert_eq!(first.await.unwrap(), Some(1));
+        assert_eq!(second.await.unwrap(), Some(2));
+        assert_eq!(*log.borrow(), ["first finished", "second started"]);
+    }
 }
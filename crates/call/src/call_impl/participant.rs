@@ -2,17 +2,26 @@ use client::{ParticipantIndex, User, proto};
 use collections::HashMap;
 use gpui::WeakEntity;
 use livekit_client::AudioStream;
-use project::Project;
-use std::sync::Arc;
+use project::{Project, ProjectPath, WorktreeId};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use util::{ResultExt as _, paths::PathStyle, rel_path::RelPath};
+use workspace::ParticipantCapabilities;
 
 pub use livekit_client::TrackSid;
-pub use livekit_client::{RemoteAudioTrack, RemoteVideoTrack};
+pub use livekit_client::{CameraDevice, RemoteAudioTrack, RemoteVideoTrack, TrackSource};
 
 #[derive(Clone, Default)]
 pub struct LocalParticipant {
     pub projects: Vec<proto::ParticipantProject>,
     pub active_project: Option<WeakEntity<Project>>,
     pub role: proto::ChannelRole,
+    pub hand_raised: bool,
+    /// The reaction most recently sent, and when, until it expires.
+    pub last_reaction: Option<(String, Instant)>,
 }
 
 impl LocalParticipant {
@@ -22,6 +31,10 @@ impl LocalParticipant {
             proto::ChannelRole::Admin | proto::ChannelRole::Member
         )
     }
+
+    pub fn capabilities(&self) -> ParticipantCapabilities {
+        ParticipantCapabilities::for_role(self.role)
+    }
 }
 
 pub struct RemoteParticipant {
@@ -33,13 +46,39 @@ pub struct RemoteParticipant {
     pub participant_index: ParticipantIndex,
     pub muted: bool,
     pub speaking: bool,
+    pub hand_raised: bool,
+    /// Whether the participant joined only to listen, without publishing any audio or video.
+    pub listening: bool,
+    /// The reaction most recently received from this participant, and when, until it expires.
+    pub last_reaction: Option<(String, Instant)>,
+    /// The file the participant has open in the shared project they're in, unless they keep
+    /// it to themselves.
+    pub active_path: Option<ProjectPath>,
+    /// The row of the participant's cursor in `active_path`, counting from zero.
+    pub cursor_row: Option<u32>,
+    /// Where the participant last published they are. It only applies while they're in the
+    /// project it names.
+    pub(crate) active_location: Option<ActiveLocation>,
+    /// Both screen share and camera tracks, which can be told apart by their [`TrackSource`].
     pub video_tracks: HashMap<TrackSid, RemoteVideoTrack>,
     pub audio_tracks: HashMap<TrackSid, (RemoteAudioTrack, AudioStream)>,
 }
 
 impl RemoteParticipant {
-    pub fn has_video_tracks(&self) -> bool {
-        !self.video_tracks.is_empty()
+    pub fn screen_share_tracks(&self) -> impl Iterator<Item = &RemoteVideoTrack> {
+        self.video_tracks
+            .values()
+            .filter(|track| track.source() == TrackSource::Screenshare)
+    }
+
+    pub fn camera_tracks(&self) -> impl Iterator<Item = &RemoteVideoTrack> {
+        self.video_tracks
+            .values()
+            .filter(|track| track.source() == TrackSource::Camera)
+    }
+
+    pub fn has_screen_share_tracks(&self) -> bool {
+        self.screen_share_tracks().next().is_some()
     }
 
     pub fn can_write(&self) -> bool {
@@ -48,4 +87,197 @@ impl RemoteParticipant {
             proto::ChannelRole::Admin | proto::ChannelRole::Member
         )
     }
+
+    pub fn capabilities(&self) -> ParticipantCapabilities {
+        ParticipantCapabilities::for_role(self.role)
+    }
+
+    /// Where in the project the participant is, such as `in src/main.rs:12`.
+    pub fn active_location_label(&self, path_style: PathStyle) -> Option<String> {
+        let path = self.active_path.as_ref()?.path.display(path_style);
+        Some(match self.cursor_row {
+            Some(row) => format!("in {path}:{}", row + 1),
+            None => format!("in {path}"),
+        })
+    }
+
+    /// Updates `active_path` and `cursor_row` from the participant's location and what they
+    /// last published, returning whether either changed.
+    pub(crate) fn update_active_path(&mut self) -> bool {
+        let active_location = self.active_location.as_ref().filter(|active_location| {
+            self.location
+                == workspace::ParticipantLocation::SharedProject {
+                    project_id: active_location.project_id,
+                }
+        });
+        let active_path = active_location.and_then(ActiveLocation::project_path);
+        let cursor_row = active_location
+            .filter(|_| active_path.is_some())
+            .and_then(|active_location| active_location.cursor_row);
+        let changed = active_path != self.active_path || cursor_row != self.cursor_row;
+        self.active_path = active_path;
+        self.cursor_row = cursor_row;
+        changed
+    }
+}
+
+/// How often the local participant's active file and cursor row are published, at most.
+pub const ACTIVE_LOCATION_THROTTLE: Duration = Duration::from_secs(1);
+
+/// The file a participant has open in a shared project, and where their cursor is in it, as
+/// they publish it to the others in the call.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ActiveLocation {
+    project_id: u64,
+    worktree_id: u64,
+    path: String,
+    #[serde(default)]
+    cursor_row: Option<u32>,
+}
+
+impl ActiveLocation {
+    /// What to publish for a participant with `active_path` open in the project with the given
+    /// remote id. Projects that aren't shared, or participants that chose not to
+    /// `share_active_location`, publish nothing beyond the project they're in.
+    pub fn new(
+        project_id: Option<u64>,
+        active_path: Option<(ProjectPath, Option<u32>)>,
+        share_active_location: bool,
+    ) -> Option<Self> {
+        if !share_active_location {
+            return None;
+        }
+        let (project_path, cursor_row) = active_path?;
+        Some(Self {
+            project_id: project_id?,
+            worktree_id: project_path.worktree_id.to_proto(),
+            path: project_path.path.to_proto(),
+            cursor_row,
+        })
+    }
+
+    fn project_path(&self) -> Option<ProjectPath> {
+        Some(ProjectPath {
+            worktree_id: WorktreeId::from_proto(self.worktree_id),
+            path: RelPath::from_proto(&self.path).log_err()?,
+        })
+    }
+}
+
+/// Keeps the local participant's active location from being published more than once per
+/// [`ACTIVE_LOCATION_THROTTLE`], while making sure the latest one is always published
+/// eventually.
+#[derive(Default)]
+pub(crate) struct ActiveLocationThrottle {
+    latest: Option<ActiveLocation>,
+    published: Option<ActiveLocation>,
+    published_at: Option<Instant>,
+}
+
+impl ActiveLocationThrottle {
+    /// Records where the local participant is now, returning how long to wait before
+    /// publishing it, or `None` when it's what was published last.
+    pub fn set(&mut self, location: Option<ActiveLocation>, now: Instant) -> Option<Duration> {
+        self.latest = location;
+        if self.latest == self.published {
+            return None;
+        }
+        Some(self.published_at.map_or(Duration::ZERO, |published_at| {
+            ACTIVE_LOCATION_THROTTLE.saturating_sub(now.saturating_duration_since(published_at))
+        }))
+    }
+
+    /// Marks the latest location as published, returning whether it needs publishing. It
+    /// doesn't while the last one was published too recently.
+    pub fn publish(&mut self, now: Instant) -> bool {
+        let published_recently = self.published_at.is_some_and(|published_at| {
+            now.saturating_duration_since(published_at) < ACTIVE_LOCATION_THROTTLE
+        });
+        if self.latest == self.published || published_recently {
+            return false;
+        }
+        self.published = self.latest.clone();
+        self.published_at = Some(now);
+        true
+    }
+
+    pub fn published(&self) -> Option<&ActiveLocation> {
+        self.published.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::rel_path::rel_path;
+
+    fn location(path: &str, cursor_row: u32) -> Option<ActiveLocation> {
+        let project_path = ProjectPath {
+            worktree_id: WorktreeId::from_proto(1),
+            path: rel_path(path).into(),
+        };
+        ActiveLocation::new(Some(7), Some((project_path, Some(cursor_row))), true)
+    }
+
+    #[test]
+    fn test_active_location_throttle() {
+        let start = Instant::now();
+        let mut throttle = ActiveLocationThrottle::default();
+
+        assert_eq!(
+            throttle.set(location("src/main.rs", 1), start),
+            Some(Duration::ZERO)
+        );
+        assert!(throttle.publish(start));
+        assert_eq!(throttle.published(), location("src/main.rs", 1).as_ref());
+
+        // Moves within the window wait for it to end, and only the last one goes out.
+        let soon = start + Duration::from_millis(300);
+        assert_eq!(
+            throttle.set(location("src/main.rs", 2), soon),
+            Some(Duration::from_millis(700))
+        );
+        assert_eq!(
+            throttle.set(location("src/main.rs", 3), soon),
+            Some(Duration::from_millis(700))
+        );
+        assert!(!throttle.publish(soon));
+        let later = start + ACTIVE_LOCATION_THROTTLE;
+        assert!(throttle.publish(later));
+        assert_eq!(throttle.published(), location("src/main.rs", 3).as_ref());
+
+        // Going back to where the participant was published to be needs nothing new.
+        let much_later = later + ACTIVE_LOCATION_THROTTLE;
+        assert_eq!(throttle.set(location("src/main.rs", 3), much_later), None);
+        assert!(!throttle.publish(much_later));
+    }
+
+    #[test]
+    fn test_active_location_privacy() {
+        let project_path = ProjectPath {
+            worktree_id: WorktreeId::from_proto(1),
+            path: rel_path("src/editor.rs").into(),
+        };
+        let active_path = Some((project_path.clone(), Some(419)));
+
+        let location = ActiveLocation::new(Some(7), active_path.clone(), true).unwrap();
+        assert_eq!(location.project_path(), Some(project_path));
+
+        // Only the project is shared when the participant keeps their location to themselves,
+        // or the project isn't shared.
+        assert_eq!(
+            ActiveLocation::new(Some(7), active_path.clone(), false),
+            None
+        );
+        assert_eq!(ActiveLocation::new(None, active_path, true), None);
+
+        // Switching sharing off takes back what was published before.
+        let mut throttle = ActiveLocationThrottle::default();
+        throttle.set(Some(location), Instant::now());
+        assert!(throttle.publish(Instant::now()));
+        let later = Instant::now() + ACTIVE_LOCATION_THROTTLE;
+        assert_eq!(throttle.set(None, later), Some(Duration::ZERO));
+        assert!(throttle.publish(later));
+        assert_eq!(throttle.published(), None);
+    }
 }
@@ -0,0 +1,365 @@
+//! Requests to control a shared screen, and the grants that answer them.
+//!
+//! A viewer asks the participant sharing their screen for control, and the sharer allows or
+//! denies it. Only one viewer controls a screen at a time, and only until the grant expires,
+//! the sharer revokes it, the screen stops being shared, or either of them leaves the call.
+//!
+//! This only tracks who is allowed to control which screen. Input isn't forwarded yet.
+
+use client::proto::PeerId;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// A message about screen control, sent over the call's data channel. Data messages reach
+/// everyone in the call, so each one names the user it's meant for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ControlMessage {
+    pub to_user_id: u64,
+    pub kind: ControlMessageKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ControlMessageKind {
+    /// Sent by a viewer to the sharer.
+    Request,
+    /// Sent by the sharer to a viewer, with how long control lasts.
+    Grant { duration_ms: u64 },
+    /// Sent by the sharer to a viewer whose request wasn't granted.
+    Deny,
+    /// Sent by the sharer to the viewer in control when control ends early or expires.
+    Revoke,
+}
+
+/// Permission for one participant to control a shared screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlGrant {
+    pub controller: PeerId,
+    pub expires_at: Instant,
+}
+
+/// The screen control state of the local participant, both as the sharer of a screen and as a
+/// viewer of other participants' screens.
+#[derive(Debug, Default)]
+pub struct RemoteControl {
+    /// Viewers asking to control the local screen, in the order they asked.
+    requests: Vec<PeerId>,
+    /// Who controls the local screen.
+    grant: Option<ControlGrant>,
+    /// The sharer the local participant asked for control, until they answer.
+    pending_request: Option<PeerId>,
+    /// The sharer whose screen the local participant controls.
+    controlled_screen: Option<(PeerId, ControlGrant)>,
+}
+
+impl RemoteControl {
+    /// Viewers waiting for an answer to their request to control the local screen.
+    pub fn requests(&self) -> &[PeerId] {
+        &self.requests
+    }
+
+    /// Who controls the local screen, if anyone.
+    pub fn grant(&self) -> Option<&ControlGrant> {
+        self.grant.as_ref()
+    }
+
+    /// Whether the local participant is waiting for `sharer` to answer a request for control.
+    pub fn is_requesting(&self, sharer: PeerId) -> bool {
+        self.pending_request == Some(sharer)
+    }
+
+    /// The grant that lets the local participant control `sharer`'s screen.
+    pub fn controlled_screen(&self, sharer: PeerId) -> Option<&ControlGrant> {
+        self.controlled_screen
+            .as_ref()
+            .filter(|(controlled_sharer, _)| *controlled_sharer == sharer)
+            .map(|(_, grant)| grant)
+    }
+
+    /// Records that the local participant asked `sharer` for control. Only one request can be
+    /// pending at a time.
+    pub(crate) fn request(&mut self, sharer: PeerId) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.controlled_screen(sharer).is_none(),
+            "already controlling this screen"
+        );
+        anyhow::ensure!(
+            self.pending_request.is_none(),
+            "already waiting for an answer to a request for control"
+        );
+        self.pending_request = Some(sharer);
+        Ok(())
+    }
+
+    /// Records a viewer's request to control the local screen. Returns whether it's new.
+    pub(crate) fn receive_request(&mut self, viewer: PeerId) -> bool {
+        if self.requests.contains(&viewer)
+            || self.grant.is_some_and(|grant| grant.controller == viewer)
+        {
+            return false;
+        }
+        self.requests.push(viewer);
+        true
+    }
+
+    /// Lets `viewer`, who must have asked, control the local screen until `duration` from
+    /// `now`. Returns the grant and the viewer who was in control before, if any.
+    pub(crate) fn grant_control(
+        &mut self,
+        viewer: PeerId,
+        now: Instant,
+        duration: Duration,
+    ) -> anyhow::Result<(ControlGrant, Option<PeerId>)> {
+        let ix = self
+            .requests
+            .iter()
+            .position(|requester| *requester == viewer)
+            .ok_or_else(|| anyhow::anyhow!("participant didn't request control"))?;
+        self.requests.remove(ix);
+        let grant = ControlGrant {
+            controller: viewer,
+            expires_at: now + duration,
+        };
+        let previous = self.grant.replace(grant).map(|grant| grant.controller);
+        Ok((grant, previous))
+    }
+
+    /// Drops `viewer`'s request. Returns whether there was one.
+    pub(crate) fn deny_control(&mut self, viewer: PeerId) -> bool {
+        let len = self.requests.len();
+        self.requests.retain(|requester| *requester != viewer);
+        self.requests.len() != len
+    }
+
+    /// Takes control of the local screen back. Returns who had it.
+    pub(crate) fn revoke_control(&mut self) -> Option<PeerId> {
+        self.grant.take().map(|grant| grant.controller)
+    }
+
+    /// Takes control of the local screen back if the grant expired by `now`. Returns who had it.
+    pub(crate) fn expire_grant(&mut self, now: Instant) -> Option<PeerId> {
+        if self.grant.is_some_and(|grant| grant.expires_at <= now) {
+            self.revoke_control()
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the requests and grant for the local screen once it's no longer shared. Returns
+    /// who had control.
+    pub(crate) fn stop_sharing(&mut self) -> Option<PeerId> {
+        self.requests.clear();
+        self.revoke_control()
+    }
+
+    /// Applies `sharer`'s answer granting control until `expires_at`. Returns whether it
+    /// answered a pending request.
+    pub(crate) fn receive_grant(
+        &mut self,
+        sharer: PeerId,
+        local_peer_id: PeerId,
+        expires_at: Instant,
+    ) -> bool {
+        if self.pending_request != Some(sharer) {
+            return false;
+        }
+        self.pending_request = None;
+        self.controlled_screen = Some((
+            sharer,
+            ControlGrant {
+                controller: local_peer_id,
+                expires_at,
+            },
+        ));
+        true
+    }
+
+    /// Applies `sharer`'s answer denying control. Returns whether it answered a pending request.
+    pub(crate) fn receive_deny(&mut self, sharer: PeerId) -> bool {
+        if self.pending_request != Some(sharer) {
+            return false;
+        }
+        self.pending_request = None;
+        true
+    }
+
+    /// Gives up control of `sharer`'s screen, and any unanswered request for it, because they
+    /// revoked it or stopped sharing. Returns whether anything changed.
+    pub(crate) fn release_screen(&mut self, sharer: PeerId) -> bool {
+        let was_requesting = self.pending_request.take_if(|request| *request == sharer);
+        let was_controlling = self
+            .controlled_screen
+            .take_if(|(controlled_sharer, _)| *controlled_sharer == sharer);
+        was_requesting.is_some() || was_controlling.is_some()
+    }
+
+    /// Gives up control of the controlled screen if the grant expired by `now`. Returns whose
+    /// screen it was.
+    pub(crate) fn expire_controlled_screen(&mut self, now: Instant) -> Option<PeerId> {
+        self.controlled_screen
+            .take_if(|(_, grant)| grant.expires_at <= now)
+            .map(|(sharer, _)| sharer)
+    }
+
+    /// Forgets everything involving a participant who left the call. Returns whether anything
+    /// changed.
+    pub(crate) fn participant_left(&mut self, peer_id: PeerId) -> bool {
+        let denied = self.deny_control(peer_id);
+        let revoked = self
+            .grant
+            .take_if(|grant| grant.controller == peer_id)
+            .is_some();
+        let released = self.release_screen(peer_id);
+        denied || revoked || released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(id: u32) -> PeerId {
+        PeerId { owner_id: 1, id }
+    }
+
+    const DURATION: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_sharer_grant_deny_and_revoke() {
+        let now = Instant::now();
+        let mut sharer = RemoteControl::default();
+
+        // Control can only be granted to viewers who asked for it.
+        assert!(sharer.grant_control(peer(2), now, DURATION).is_err());
+
+        assert!(sharer.receive_request(peer(2)));
+        assert!(!sharer.receive_request(peer(2)));
+        assert!(sharer.receive_request(peer(3)));
+        assert_eq!(sharer.requests(), [peer(2), peer(3)]);
+
+        let (grant, previous) = sharer.grant_control(peer(2), now, DURATION).unwrap();
+        assert_eq!(grant.controller, peer(2));
+        assert_eq!(grant.expires_at, now + DURATION);
+        assert_eq!(previous, None);
+        assert_eq!(sharer.grant(), Some(&grant));
+        assert_eq!(sharer.requests(), [peer(3)]);
+        // Asking again while in control doesn't queue another request.
+        assert!(!sharer.receive_request(peer(2)));
+
+        assert!(sharer.deny_control(peer(3)));
+        assert!(!sharer.deny_control(peer(3)));
+        assert_eq!(sharer.requests(), []);
+
+        // Granting control to someone else takes it from the current controller.
+        assert!(sharer.receive_request(peer(3)));
+        let (_, previous) = sharer.grant_control(peer(3), now, DURATION).unwrap();
+        assert_eq!(previous, Some(peer(2)));
+
+        assert_eq!(sharer.revoke_control(), Some(peer(3)));
+        assert_eq!(sharer.revoke_control(), None);
+        assert_eq!(sharer.grant(), None);
+    }
+
+    #[test]
+    fn test_sharer_grant_expires() {
+        let now = Instant::now();
+        let mut sharer = RemoteControl::default();
+        sharer.receive_request(peer(2));
+        sharer.grant_control(peer(2), now, DURATION).unwrap();
+
+        assert_eq!(sharer.expire_grant(now + DURATION / 2), None);
+        assert!(sharer.grant().is_some());
+        assert_eq!(sharer.expire_grant(now + DURATION), Some(peer(2)));
+        assert_eq!(sharer.grant(), None);
+    }
+
+    #[test]
+    fn test_sharer_state_is_cleared() {
+        let now = Instant::now();
+        let mut sharer = RemoteControl::default();
+        sharer.receive_request(peer(2));
+        sharer.receive_request(peer(3));
+        sharer.grant_control(peer(2), now, DURATION).unwrap();
+
+        // The viewer in control leaves.
+        assert!(sharer.participant_left(peer(2)));
+        assert_eq!(sharer.grant(), None);
+        assert_eq!(sharer.requests(), [peer(3)]);
+        assert!(!sharer.participant_left(peer(2)));
+
+        sharer.grant_control(peer(3), now, DURATION).unwrap();
+        sharer.receive_request(peer(4));
+        assert_eq!(sharer.stop_sharing(), Some(peer(3)));
+        assert_eq!(sharer.grant(), None);
+        assert_eq!(sharer.requests(), []);
+    }
+
+    #[test]
+    fn test_viewer_request_grant_and_revoke() {
+        let now = Instant::now();
+        let local = peer(2);
+        let mut viewer = RemoteControl::default();
+
+        // Answers nobody asked for are ignored.
+        assert!(!viewer.receive_grant(peer(1), local, now + DURATION));
+        assert!(!viewer.receive_deny(peer(1)));
+
+        viewer.request(peer(1)).unwrap();
+        assert!(viewer.is_requesting(peer(1)));
+        assert!(viewer.request(peer(3)).is_err());
+
+        assert!(viewer.receive_grant(peer(1), local, now + DURATION));
+        assert!(!viewer.is_requesting(peer(1)));
+        assert_eq!(
+            viewer.controlled_screen(peer(1)),
+            Some(&ControlGrant {
+                controller: local,
+                expires_at: now + DURATION,
+            })
+        );
+        assert_eq!(viewer.controlled_screen(peer(3)), None);
+        assert!(viewer.request(peer(1)).is_err());
+
+        assert!(viewer.release_screen(peer(1)));
+        assert!(!viewer.release_screen(peer(1)));
+        assert_eq!(viewer.controlled_screen(peer(1)), None);
+    }
+
+    #[test]
+    fn test_viewer_denied_and_expired() {
+        let now = Instant::now();
+        let local = peer(2);
+        let mut viewer = RemoteControl::default();
+
+        viewer.request(peer(1)).unwrap();
+        assert!(viewer.receive_deny(peer(1)));
+        assert!(!viewer.is_requesting(peer(1)));
+        // A grant arriving after the denial doesn't apply.
+        assert!(!viewer.receive_grant(peer(1), local, now + DURATION));
+
+        viewer.request(peer(1)).unwrap();
+        viewer.receive_grant(peer(1), local, now + DURATION);
+        assert_eq!(viewer.expire_controlled_screen(now), None);
+        assert_eq!(
+            viewer.expire_controlled_screen(now + DURATION),
+            Some(peer(1))
+        );
+        assert_eq!(viewer.controlled_screen(peer(1)), None);
+    }
+
+    #[test]
+    fn test_viewer_state_is_cleared_when_sharer_leaves() {
+        let now = Instant::now();
+        let mut viewer = RemoteControl::default();
+        viewer.request(peer(1)).unwrap();
+        assert!(viewer.participant_left(peer(1)));
+        assert!(!viewer.is_requesting(peer(1)));
+
+        viewer.request(peer(1)).unwrap();
+        viewer.receive_grant(peer(1), peer(2), now + DURATION);
+        assert!(viewer.participant_left(peer(1)));
+        assert_eq!(viewer.controlled_screen(peer(1)), None);
+        // Another request can be made once the previous one is gone.
+        viewer.request(peer(3)).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+use crate::IncomingCall;
+use futures::{FutureExt as _, StreamExt as _, future::Fuse};
+use gpui::{App, Task};
+use std::time::Duration;
+use util::state_cell;
+
+/// How long the ringtone plays for an unanswered call. The call itself stays open after this,
+/// until the caller cancels it.
+pub const RING_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RingCommand {
+    Start,
+    Stop,
+}
+
+#[derive(Debug, Default)]
+struct RingState {
+    /// The room of the call the ringtone is currently playing for.
+    ringing_room_id: Option<u64>,
+    /// The room of the last call the ringtone was started for. The subscription can deliver the
+    /// same call more than once, and a call that timed out shouldn't start ringing again.
+    rung_room_id: Option<u64>,
+}
+
+impl RingState {
+    fn incoming_call_changed(
+        &mut self,
+        room_id: Option<u64>,
+        is_in_room: bool,
+    ) -> Option<RingCommand> {
+        match room_id {
+            None => {
+                self.rung_room_id = None;
+                self.stop()
+            }
+            // The call is still shown, but it doesn't interrupt the current one with a sound.
+            Some(_) if is_in_room => self.stop(),
+            Some(room_id) if self.rung_room_id == Some(room_id) => None,
+            Some(room_id) => {
+                self.ringing_room_id = Some(room_id);
+                self.rung_room_id = Some(room_id);
+                Some(RingCommand::Start)
+            }
+        }
+    }
+
+    fn stop(&mut self) -> Option<RingCommand> {
+        self.ringing_room_id.take().map(|_| RingCommand::Stop)
+    }
+}
+
+/// Starts the ringtone when `incoming_call` receives a call, and stops it once the call is
+/// taken out of the cell by accepting, declining or cancelling it, or after [`RING_TIMEOUT`].
+pub fn ring_for_incoming_calls(
+    mut incoming_call: state_cell::Subscription<IncomingCall>,
+    is_in_room: impl Fn(&App) -> bool + 'static,
+    mut ring: impl FnMut(RingCommand, &mut App) + 'static,
+    cx: &mut App,
+) -> Task<()> {
+    cx.spawn(async move |cx| {
+        let mut state = RingState::default();
+        let mut timeout = Fuse::<Task<()>>::terminated();
+        loop {
+            let command = futures::select_biased! {
+                call = incoming_call.next().fuse() => {
+                    let Some(call) = call else {
+                        break;
+                    };
+                    let is_in_room = cx.update(|cx| is_in_room(cx));
+                    let command =
+                        state.incoming_call_changed(call.map(|call| call.room_id), is_in_room);
+                    if command == Some(RingCommand::Start) {
+                        timeout = cx.background_executor().timer(RING_TIMEOUT).fuse();
+                    }
+                    command
+                }
+                _ = timeout => state.stop(),
+            };
+            if let Some(command) = command {
+                cx.update(|cx| ring(command, cx));
+            }
+        }
+
+        if let Some(command) = state.stop() {
+            cx.update(|cx| ring(command, cx));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::User;
+    use gpui::TestAppContext;
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+    use util::state_cell::StateCell;
+
+    fn incoming_call(room_id: u64) -> Option<IncomingCall> {
+        Some(IncomingCall {
+            room_id,
+            calling_user: Arc::new(User {
+                id: 1,
+                github_login: "caller".into(),
+                avatar_uri: "".into(),
+                name: None,
+            }),
+            participants: Vec::new(),
+            initial_project: None,
+        })
+    }
+
+    struct TestRinger {
+        cell: StateCell<IncomingCall>,
+        commands: Rc<RefCell<Vec<RingCommand>>>,
+        is_in_room: Rc<RefCell<bool>>,
+        _task: Task<()>,
+    }
+
+    impl TestRinger {
+        fn new(cx: &mut TestAppContext) -> Self {
+            let cell = StateCell::new();
+            let commands = Rc::new(RefCell::new(Vec::new()));
+            let is_in_room = Rc::new(RefCell::new(false));
+            let task = cx.update(|cx| {
+                ring_for_incoming_calls(
+                    cell.subscribe(),
+                    {
+                        let is_in_room = is_in_room.clone();
+                        move |_| *is_in_room.borrow()
+                    },
+                    {
+                        let commands = commands.clone();
+                        move |command, _| commands.borrow_mut().push(command)
+                    },
+                    cx,
+                )
+            });
+            Self {
+                cell,
+                commands,
+                is_in_room,
+                _task: task,
+            }
+        }
+
+        fn set(&mut self, call: Option<IncomingCall>) {
+            match call {
+                Some(call) => {
+                    self.cell.set(call);
+                }
+                None => {
+                    self.cell.take();
+                }
+            }
+        }
+
+        fn take_commands(&self) -> Vec<RingCommand> {
+            self.commands.borrow_mut().drain(..).collect()
+        }
+    }
+
+    #[gpui::test]
+    async fn test_ringing_stops_when_call_is_taken(cx: &mut TestAppContext) {
+        let mut ringer = TestRinger::new(cx);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+
+        // Accepting, declining and cancelling all take the call out of the channel.
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Start]);
+        ringer.set(None);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Stop]);
+
+        // The same room can call again after a cancelled call.
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Start]);
+
+        // A different call replaces the current one, which restarts the ringtone.
+        ringer.set(incoming_call(2));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Start]);
+
+        // Dropping the cell stops the ringtone.
+        drop(ringer.cell);
+        cx.run_until_parked();
+        assert_eq!(
+            ringer.commands.borrow_mut().drain(..).collect::<Vec<_>>(),
+            [RingCommand::Stop]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_ringing_races(cx: &mut TestAppContext) {
+        let mut ringer = TestRinger::new(cx);
+
+        // A call that's cancelled before it's observed never rings.
+        ringer.set(incoming_call(1));
+        ringer.set(None);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+
+        // Re-sending the call that's ringing doesn't restart the ringtone.
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Start]);
+
+        // A call that's declined and cancelled at once only stops the ringtone once.
+        ringer.set(None);
+        ringer.set(None);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Stop]);
+
+        // Calls that arrive while in a room don't ring.
+        *ringer.is_in_room.borrow_mut() = true;
+        ringer.set(incoming_call(2));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+        *ringer.is_in_room.borrow_mut() = false;
+        ringer.set(None);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+    }
+
+    #[gpui::test]
+    async fn test_ringing_times_out(cx: &mut TestAppContext) {
+        let mut ringer = TestRinger::new(cx);
+
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Start]);
+
+        cx.executor().advance_clock(RING_TIMEOUT / 2);
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+
+        // The timeout isn't extended when the same call is re-sent.
+        cx.executor().advance_clock(RING_TIMEOUT / 2);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Stop]);
+
+        // A timed-out call stays silent until it's taken.
+        ringer.set(incoming_call(1));
+        cx.run_until_parked();
+        ringer.set(None);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+
+        // A timeout left over from an accepted call doesn't stop the next one.
+        ringer.set(incoming_call(2));
+        cx.run_until_parked();
+        ringer.set(None);
+        cx.run_until_parked();
+        cx.executor().advance_clock(RING_TIMEOUT / 2);
+        ringer.set(incoming_call(3));
+        cx.run_until_parked();
+        assert_eq!(
+            ringer.take_commands(),
+            [RingCommand::Start, RingCommand::Stop, RingCommand::Start]
+        );
+        cx.executor().advance_clock(RING_TIMEOUT / 2);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), []);
+        cx.executor().advance_clock(RING_TIMEOUT / 2);
+        cx.run_until_parked();
+        assert_eq!(ringer.take_commands(), [RingCommand::Stop]);
+    }
+}
@@ -1,33 +1,60 @@
 use crate::{
-    call_settings::CallSettings,
-    participant::{LocalParticipant, RemoteParticipant},
+    PermissionDenied,
+    audio_devices::AudioDeviceRouting,
+    call_settings::{CallSettings, JoinPreferences},
+    participant::{ActiveLocation, ActiveLocationThrottle, LocalParticipant, RemoteParticipant},
+    play_call_sound,
+    remote_control::{ControlMessage, ControlMessageKind, RemoteControl},
+    screen_share_quality::{QualityMonitor, ScreenSharePreset},
+    secret_files,
+    session_log::{CallSummary, SessionLog},
 };
 use anyhow::{Context as _, Result, anyhow};
-use audio::{Audio, Sound};
+use audio::{AudioSettings, Sound};
+use chrono::Utc;
 use client::{
-    ChannelId, Client, ParticipantIndex, TypedEnvelope, User, UserStore,
+    ChannelId, Client, ErrorExt as _, ParticipantIndex, TypedEnvelope, User, UserStore,
     proto::{self, PeerId},
 };
-use collections::{BTreeMap, HashMap, HashSet};
+use collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use feature_flags::FeatureFlagAppExt;
 use fs::Fs;
-use futures::StreamExt;
+use futures::{FutureExt as _, StreamExt, future::Shared};
 use gpui::{
-    App, AppContext as _, AsyncApp, Context, Entity, EventEmitter, FutureExt as _,
+    App, AppContext as _, AsyncApp, Context, Entity, EntityId, EventEmitter, FutureExt as _,
     ScreenCaptureSource, ScreenCaptureStream, Task, Timeout, WeakEntity,
 };
 use gpui_tokio::Tokio;
 use language::LanguageRegistry;
 use livekit::{LocalTrackPublication, ParticipantIdentity, RoomEvent};
-use livekit_client::{self as livekit, AudioStream, TrackSid};
+use livekit_client::{
+    self as livekit, AudioDevice, AudioDeviceKind, AudioProcessing, AudioProcessingChange,
+    AudioStream, CameraDevice, ScreenShareStream, TrackSid, TrackSource,
+};
 use postage::{sink::Sink, stream::Stream, watch};
-use project::Project;
-use settings::Settings as _;
+use project::{JoinProjectProgress, Project, ProjectPath};
+use serde::{Deserialize, Serialize};
+use settings::{AudioInputDeviceName, AudioOutputDeviceName, Settings as _, SettingsStore};
 use std::{future::Future, mem, rc::Rc, sync::Arc, time::Duration, time::Instant};
 use util::{ResultExt, TryFutureExt, paths::PathStyle, post_inc};
-use workspace::ParticipantLocation;
+use workspace::{ParticipantCapabilities, ParticipantLocation, ProjectActivity};
 
 pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the server gets to move a room before the room goes offline.
+pub const MIGRATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// The largest chat message that can be sent in a call, in bytes.
+pub const MAX_CHAT_MESSAGE_LEN: usize = 4096;
+/// How many chat messages are kept in a room's history before the oldest are discarded.
+pub const MAX_CHAT_HISTORY_LEN: usize = 300;
+const CHAT_MESSAGE_TOPIC: &str = "chat";
+/// How long a reaction is shown before it's cleared.
+pub const REACTION_DURATION: Duration = Duration::from_secs(3);
+/// The shortest interval between two reactions sent by the same participant.
+pub const REACTION_RATE_LIMIT: Duration = Duration::from_secs(1);
+/// The largest reaction that can be sent, in bytes, which fits emoji made of several code points.
+const MAX_REACTION_LEN: usize = 32;
+const REACTION_TOPIC: &str = "reaction";
+const REMOTE_CONTROL_TOPIC: &str = "remote_control";
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Event {
@@ -57,23 +84,148 @@ pub enum Event {
     RemoteProjectJoined {
         project_id: u64,
     },
+    /// A project being joined with [`Room::join_project`] got further along.
+    JoinProjectProgress {
+        project_id: u64,
+        progress: JoinProjectProgress,
+    },
     RemoteProjectInvitationDiscarded {
         project_id: u64,
     },
+    /// The local participant started sharing the given project.
+    ProjectShared {
+        project_id: EntityId,
+        remote_id: u64,
+    },
+    /// The local participant stopped sharing the given project, either explicitly
+    /// or because the server no longer lists it as shared.
+    ProjectUnshared {
+        project_id: EntityId,
+    },
     RoomLeft {
         channel_id: Option<ChannelId>,
     },
+    /// The server started moving the room, such as during a deploy. The call stays up, but
+    /// nothing is sent to the server until the move is done.
+    MigrationStarted,
+    /// The room finished moving. When it didn't succeed, the room is left.
+    MigrationFinished {
+        succeeded: bool,
+    },
+    /// A channel admin answered the local user's request to join the channel.
+    JoinRequestAnswered {
+        channel_id: ChannelId,
+        accepted: bool,
+    },
+    /// The microphone started out muted because more people than
+    /// `auto_mute_on_join_threshold` were already in the call.
+    MutedOnJoin {
+        participant_count: usize,
+    },
+    /// The local participant's screen share was published.
+    LocalScreenShareStarted {
+        preset: ScreenSharePreset,
+    },
+    /// The screen share's quality preset changed, or it was pinned or unpinned. `stepped_down`
+    /// is set when it was lowered because the connection couldn't keep up.
+    ScreenSharePresetChanged {
+        preset: ScreenSharePreset,
+        pinned: bool,
+        stepped_down: bool,
+    },
+    LocalCameraStarted,
+    LocalCameraStopped,
+    /// The camera being shared stopped producing frames, so it was unshared.
+    LocalCameraDisconnected {
+        device_id: String,
+    },
+    /// The call moved to another microphone or speaker without the user picking it, because the
+    /// one in use was unplugged, the system default changed, or the preferred one came back.
+    AudioDeviceSwitched {
+        device: AudioDevice,
+    },
+    ChatMessageReceived {
+        participant_id: proto::PeerId,
+        message: ChatMessage,
+    },
+    /// A participant raised or lowered their hand, or their reaction was shown or cleared.
+    ParticipantStateChanged {
+        participant_id: proto::PeerId,
+    },
+    /// The local participant hung up on a channel call that they were the last one in, and that
+    /// is set to have its summary added to the channel's notes.
+    CallSummaryReady {
+        channel_id: ChannelId,
+        summary: CallSummary,
+    },
+    /// A viewer asked to control the local participant's shared screen.
+    ControlRequested {
+        participant_id: proto::PeerId,
+    },
+    /// Control of a screen shared by or with the given participant was requested, granted,
+    /// denied, revoked, or expired.
+    RemoteControlChanged {
+        participant_id: proto::PeerId,
+    },
+    /// The local participant's role changed in a way that lets them do more or less.
+    LocalCapabilitiesChanged {
+        capabilities: ParticipantCapabilities,
+    },
+    /// A remote participant's role changed in a way that lets them do more or less.
+    ParticipantCapabilitiesChanged {
+        participant_id: proto::PeerId,
+        capabilities: ParticipantCapabilities,
+    },
+}
+
+/// The state each participant publishes in their LiveKit metadata, so that it's known to
+/// participants who join later.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ParticipantMetadata {
+    #[serde(default)]
+    hand_raised: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_location: Option<ActiveLocation>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    listening: bool,
+}
+
+/// What the local participant can't do while they're only listening, since each would open a
+/// device or publish a track.
+const PUBLISHING_CAPABILITIES: ParticipantCapabilities = ParticipantCapabilities::USE_MICROPHONE
+    .union(ParticipantCapabilities::USE_CAMERA)
+    .union(ParticipantCapabilities::SHARE_SCREEN);
+
+/// A text message sent to everyone in the call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub sender: Arc<User>,
+    pub text: String,
 }
 
 pub struct Room {
     id: u64,
     channel_id: Option<ChannelId>,
+    join_preferences: JoinPreferences,
+    /// Whether the call was joined only to listen, so nothing is published and no device is
+    /// opened until the local participant starts participating.
+    listen_only: bool,
     live_kit: Option<LiveKitRoom>,
     status: RoomStatus,
     shared_projects: HashSet<WeakEntity<Project>>,
+    /// Shares that have been requested but haven't completed, by project. Sharing a project
+    /// again while it's pending waits for the same share, so that a call inviting someone to
+    /// the project never goes out before the project is shared.
+    pending_shares: HashMap<EntityId, Shared<Task<Result<u64, Arc<anyhow::Error>>>>>,
     joined_projects: HashSet<WeakEntity<Project>>,
     local_participant: LocalParticipant,
     remote_participants: BTreeMap<u64, RemoteParticipant>,
+    /// How many others the last room update listed. Unlike `remote_participants`, it's known
+    /// as soon as the update arrives, without waiting for their users to load.
+    remote_participant_count: usize,
+    /// How many others were in the call when it was joined, if that's why the microphone
+    /// started out muted.
+    auto_muted_on_join: Option<usize>,
     pending_participants: Vec<Arc<User>>,
     participant_user_ids: HashSet<u64>,
     pending_call_count: usize,
@@ -81,12 +233,28 @@ pub struct Room {
     client: Arc<Client>,
     user_store: Entity<UserStore>,
     follows_by_leader_id_project_id: HashMap<(PeerId, u64), Vec<PeerId>>,
+    chat_messages: VecDeque<ChatMessage>,
+    /// Who has been in the call and what they shared, for the call's summary.
+    session_log: SessionLog,
+    remote_control: RemoteControl,
+    active_location_throttle: ActiveLocationThrottle,
+    publish_active_location: Option<Task<()>>,
+    screen_share_preset: ScreenSharePreset,
+    /// Whether the preset stays as it is even when the connection can't keep up.
+    screen_share_preset_pinned: bool,
+    screen_share_quality: QualityMonitor,
+    /// Checks the connection again once it may have been poor for long enough to lower the
+    /// screen share's preset.
+    screen_share_step_down: Option<Task<()>>,
     client_subscriptions: Vec<client::Subscription>,
     _subscriptions: Vec<gpui::Subscription>,
     room_update_completed_tx: watch::Sender<Option<()>>,
     room_update_completed_rx: watch::Receiver<Option<()>>,
     pending_room_update: Option<Task<()>>,
     maintain_connection: Option<Task<Option<()>>>,
+    /// The move to the room's new id, while the server is migrating it. Requests that name the
+    /// room wait for it.
+    migration: Option<Shared<Task<()>>>,
     created: Instant,
 }
 
@@ -97,10 +265,31 @@ impl Room {
         self.channel_id
     }
 
+    /// The preferences the room was joined with.
+    pub fn join_preferences(&self) -> JoinPreferences {
+        self.join_preferences
+    }
+
+    /// Whether the local participant joined only to listen and hasn't started participating.
+    pub fn is_listen_only(&self) -> bool {
+        self.listen_only
+    }
+
+    /// How many others were already in the call when it was joined, if the microphone started
+    /// out muted because there were too many.
+    pub fn auto_muted_on_join(&self) -> Option<usize> {
+        self.auto_muted_on_join
+    }
+
     pub fn is_sharing_project(&self) -> bool {
         !self.shared_projects.is_empty()
     }
 
+    /// Whether `project` is being shared, but the server hasn't confirmed it yet.
+    pub fn is_project_share_pending(&self, project: &Entity<Project>) -> bool {
+        self.pending_shares.contains_key(&project.entity_id())
+    }
+
     pub fn is_connected(&self, _: &App) -> bool {
         if let Some(live_kit) = self.live_kit.as_ref() {
             live_kit.room.connection_state() == livekit::ConnectionState::Connected
@@ -112,51 +301,74 @@ impl Room {
     fn new(
         id: u64,
         channel_id: Option<ChannelId>,
+        join_preferences: JoinPreferences,
         livekit_connection_info: Option<proto::LiveKitConnectionInfo>,
         client: Arc<Client>,
         user_store: Entity<UserStore>,
         cx: &mut Context<Self>,
     ) -> Self {
-        spawn_room_connection(livekit_connection_info, cx);
+        spawn_room_connection(livekit_connection_info, join_preferences, cx);
 
         let maintain_connection = cx.spawn({
             let client = client.clone();
             async move |this, cx| {
                 Self::maintain_connection(this, client.clone(), cx)
-                    .log_err()
+                    .log_err_with_context(move || format!("maintaining connection to room {id}"))
                     .await
             }
         });
 
-        Audio::play_sound(Sound::Joined, cx);
+        play_call_sound(Sound::Joined, cx);
 
         let (room_update_completed_tx, room_update_completed_rx) = watch::channel();
 
+        let mut session_log = SessionLog::default();
+        if let Some(user) = user_store.read(cx).current_user() {
+            session_log.joined(user.id, &user.github_login, Utc::now());
+        }
+
         Self {
             id,
             channel_id,
+            join_preferences,
+            listen_only: false,
             live_kit: None,
             status: RoomStatus::Online,
             shared_projects: Default::default(),
+            pending_shares: Default::default(),
             joined_projects: Default::default(),
             participant_user_ids: Default::default(),
             local_participant: Default::default(),
             remote_participants: Default::default(),
+            remote_participant_count: 0,
+            auto_muted_on_join: None,
             pending_participants: Default::default(),
             pending_call_count: 0,
             client_subscriptions: vec![
                 client.add_message_handler(cx.weak_entity(), Self::handle_room_updated),
+                client.add_message_handler(cx.weak_entity(), Self::handle_room_migrated),
             ],
             _subscriptions: vec![
                 cx.on_release(Self::released),
                 cx.on_app_quit(Self::app_will_quit),
+                cx.observe_global::<SettingsStore>(Self::audio_settings_changed),
             ],
             leave_when_empty: false,
             pending_room_update: None,
             client,
             user_store,
             follows_by_leader_id_project_id: Default::default(),
+            chat_messages: Default::default(),
+            session_log,
+            remote_control: Default::default(),
+            active_location_throttle: Default::default(),
+            publish_active_location: None,
+            screen_share_preset: ScreenSharePreset::default(),
+            screen_share_preset_pinned: false,
+            screen_share_quality: QualityMonitor::default(),
+            screen_share_step_down: None,
             maintain_connection: Some(maintain_connection),
+            migration: None,
             room_update_completed_tx,
             room_update_completed_rx,
             created: cx.background_executor().now(),
@@ -170,6 +382,7 @@ impl Room {
         user_store: Entity<UserStore>,
         cx: &mut App,
     ) -> Task<Result<Entity<Self>>> {
+        let join_preferences = CallSettings::get_global(cx).join_preferences(None);
         cx.spawn(async move |cx| {
             let response = client.request(proto::CreateRoom {}).await?;
             let room_proto = response.room.context("invalid room")?;
@@ -177,6 +390,7 @@ impl Room {
                 let mut room = Self::new(
                     room_proto.id,
                     None,
+                    join_preferences,
                     response.live_kit_connection_info,
                     client,
                     user_store,
@@ -214,6 +428,8 @@ impl Room {
 
     pub(crate) async fn join_channel(
         channel_id: ChannelId,
+        join_preferences: JoinPreferences,
+        listen_only: bool,
         client: Arc<Client>,
         user_store: Entity<UserStore>,
         cx: AsyncApp,
@@ -224,6 +440,8 @@ impl Room {
                     channel_id: channel_id.0,
                 })
                 .await?,
+            join_preferences,
+            listen_only,
             client,
             user_store,
             cx,
@@ -236,25 +454,28 @@ impl Room {
         user_store: Entity<UserStore>,
         cx: AsyncApp,
     ) -> Result<Entity<Self>> {
-        Self::from_join_response(
-            client.request(proto::JoinRoom { id: room_id }).await?,
-            client,
-            user_store,
-            cx,
-        )
+        let response = client.request(proto::JoinRoom { id: room_id }).await?;
+        // Accepting a call from someone in a channel joins that channel's room.
+        let channel_id = response.channel_id.map(ChannelId);
+        let join_preferences =
+            cx.update(|cx| CallSettings::get_global(cx).join_preferences(channel_id));
+        Self::from_join_response(response, join_preferences, false, client, user_store, cx)
     }
 
     fn released(&mut self, cx: &mut App) {
-        if self.status.is_online() {
+        if self.status.is_online() || self.status.is_migrating() {
             self.leave_internal(cx).detach_and_log_err(cx);
         }
     }
 
     fn app_will_quit(&mut self, cx: &mut Context<Self>) -> impl Future<Output = ()> + use<> {
-        let task = if self.status.is_online() {
+        let task = if self.status.is_online() || self.status.is_migrating() {
+            let room_id = self.id;
             let leave = self.leave_internal(cx);
             Some(cx.background_spawn(async move {
-                leave.await.log_err();
+                leave
+                    .log_err_with_context(move || format!("leaving room {room_id} on quit"))
+                    .await;
             }))
         } else {
             None
@@ -267,12 +488,10 @@ impl Room {
         }
     }
 
-    pub fn mute_on_join(cx: &App) -> bool {
-        CallSettings::get_global(cx).mute_on_join || client::IMPERSONATE_LOGIN.is_some()
-    }
-
     fn from_join_response(
         response: proto::JoinRoomResponse,
+        join_preferences: JoinPreferences,
+        listen_only: bool,
         client: Arc<Client>,
         user_store: Entity<UserStore>,
         mut cx: AsyncApp,
@@ -282,6 +501,7 @@ impl Room {
             Self::new(
                 room_proto.id,
                 response.channel_id.map(ChannelId),
+                join_preferences,
                 response.live_kit_connection_info,
                 client,
                 user_store,
@@ -289,6 +509,8 @@ impl Room {
             )
         });
         room.update(&mut cx, |room, cx| {
+            // This is set before the room connects to LiveKit, so that connecting publishes nothing.
+            room.listen_only = listen_only;
             room.leave_when_empty = room.channel_id.is_none();
             room.apply_room_update(room_proto, cx)?;
             anyhow::Ok(())
@@ -316,7 +538,7 @@ impl Room {
         }
 
         log::info!("leaving room");
-        Audio::play_sound(Sound::Leave, cx);
+        play_call_sound(Sound::Leave, cx);
 
         self.clear_state(cx);
 
@@ -328,6 +550,7 @@ impl Room {
     }
 
     pub(crate) fn clear_state(&mut self, cx: &mut App) {
+        self.pending_shares.clear();
         for project in self.shared_projects.drain() {
             if let Some(project) = project.upgrade() {
                 project.update(cx, |project, cx| {
@@ -348,10 +571,17 @@ impl Room {
         self.remote_participants.clear();
         self.pending_participants.clear();
         self.participant_user_ids.clear();
+        self.chat_messages.clear();
+        self.remote_control = Default::default();
+        self.local_participant.hand_raised = false;
+        self.local_participant.last_reaction = None;
+        self.active_location_throttle = Default::default();
+        self.publish_active_location.take();
         self.client_subscriptions.clear();
         self.live_kit.take();
         self.pending_room_update.take();
         self.maintain_connection.take();
+        self.migration.take();
     }
 
     fn emit_video_track_unsubscribed_events(&self, cx: &mut Context<Self>) {
@@ -391,8 +621,18 @@ impl Room {
                             log::info!("client reconnected, attempting to rejoin room");
 
                             let Some(this) = this.upgrade() else { break };
-                            let task = this.update(cx, |this, cx| this.rejoin(cx));
-                            if task.await.log_err().is_some() {
+                            let (room_id, task) =
+                                this.update(cx, |this, cx| (this.id, this.rejoin(cx)));
+                            let attempts_left = remaining_attempts - 1;
+                            if task
+                                .warn_on_err_with_context(move || {
+                                    format!(
+                                        "rejoining room {room_id}, {attempts_left} attempts left"
+                                    )
+                                })
+                                .await
+                                .is_some()
+                            {
                                 return true;
                             } else {
                                 remaining_attempts -= 1;
@@ -526,6 +766,109 @@ impl Room {
         })
     }
 
+    async fn handle_room_migrated(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::RoomMigrated>,
+        mut cx: AsyncApp,
+    ) -> Result<()> {
+        this.update(&mut cx, |this, cx| this.migrate(envelope.payload, cx));
+        Ok(())
+    }
+
+    /// Rejoins the room under the id the server moved it to, without leaving the call. Shared
+    /// projects are reshared and joined ones rejoined, and the participants that are still there
+    /// keep their tracks, so that views of their screens stay open.
+    fn migrate(&mut self, migration: proto::RoomMigrated, cx: &mut Context<Self>) {
+        if migration.room_id != self.id || !self.status.is_online() {
+            return;
+        }
+
+        log::info!(
+            "room {} is moving to {}",
+            migration.room_id,
+            migration.new_room_id
+        );
+        self.status = RoomStatus::Migrating;
+        self.id = migration.new_room_id;
+        let rejoin = self.rejoin(cx);
+        let live_kit_connection_info = migration.live_kit_connection_info;
+        let executor = cx.background_executor().clone();
+        let migration = cx.spawn(async move |this, cx| {
+            let result = match rejoin.with_timeout(MIGRATION_TIMEOUT, &executor).await {
+                Ok(result) => result,
+                Err(Timeout) => Err(anyhow!("timed out")),
+            };
+            this.update(cx, |this, cx| {
+                this.migration_finished(result, live_kit_connection_info, cx)
+            })
+            .ok();
+        });
+        self.migration = Some(migration.shared());
+        cx.emit(Event::MigrationStarted);
+        cx.notify();
+    }
+
+    fn migration_finished(
+        &mut self,
+        result: Result<()>,
+        live_kit_connection_info: Option<proto::LiveKitConnectionInfo>,
+        cx: &mut Context<Self>,
+    ) {
+        self.migration = None;
+        match result {
+            Ok(()) => {
+                log::info!("room moved to {}", self.id);
+                if let Some(connection_info) = live_kit_connection_info {
+                    self.reconnect_live_kit(connection_info, cx);
+                }
+                // The location wasn't sent while the room was moving, and the new room may not
+                // have it.
+                let active_project = self
+                    .local_participant
+                    .active_project
+                    .as_ref()
+                    .and_then(|project| project.upgrade());
+                self.set_location(active_project.as_ref(), cx)
+                    .detach_and_log_err(cx);
+                cx.emit(Event::MigrationFinished { succeeded: true });
+            }
+            Err(error) => {
+                log::error!("failed to move room {}: {error:#}", self.id);
+                cx.emit(Event::MigrationFinished { succeeded: false });
+                // A client that lost its connection meanwhile is already rejoining.
+                if self.status.is_migrating() {
+                    self.leave(cx).detach_and_log_err(cx);
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    /// Moves the call's audio and video to another LiveKit room, keeping the microphone muted
+    /// or deafened the way it was.
+    fn reconnect_live_kit(
+        &mut self,
+        connection_info: proto::LiveKitConnectionInfo,
+        cx: &mut Context<Self>,
+    ) {
+        let mut join_preferences = JoinPreferences {
+            auto_mute_threshold: None,
+            ..self.join_preferences
+        };
+        if let Some(mut live_kit) = self.live_kit.take() {
+            join_preferences.muted = live_kit.muted_by_user;
+            join_preferences.deafened = live_kit.deafened;
+            live_kit.stop_publishing(cx);
+        }
+        // Tracks from the old room won't be unsubscribed, since its events stop here.
+        self.emit_video_track_unsubscribed_events(cx);
+        for participant in self.remote_participants.values_mut() {
+            participant.video_tracks.clear();
+            participant.audio_tracks.clear();
+        }
+        spawn_room_connection(Some(connection_info), join_preferences, cx);
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
@@ -584,12 +927,35 @@ impl Room {
         self.local_participant.role == proto::ChannelRole::Guest
     }
 
+    /// What the local participant may do in the call, given their role in it.
+    pub fn local_capabilities(&self) -> ParticipantCapabilities {
+        self.local_participant.capabilities()
+    }
+
+    /// Fails with [`PermissionDenied`] unless the local participant has `capability`, or if
+    /// it would publish something while they're only listening.
+    fn check_capability(&self, capability: ParticipantCapabilities) -> Result<()> {
+        if !self.local_capabilities().contains(capability) {
+            Err(PermissionDenied { capability }.into())
+        } else if self.listen_only && capability.intersects(PUBLISHING_CAPABILITIES) {
+            Err(anyhow!(
+                "cannot {} while only listening",
+                capability.description()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn set_participant_role(
         &mut self,
         user_id: u64,
         role: proto::ChannelRole,
         cx: &Context<Self>,
     ) -> Task<Result<()>> {
+        if let Err(error) = self.check_capability(ParticipantCapabilities::MANAGE_PARTICIPANTS) {
+            return Task::ready(Err(error));
+        }
         let client = self.client.clone();
         let room_id = self.id;
         let role = role.into();
@@ -613,132 +979,686 @@ impl Room {
         self.participant_user_ids.contains(&user_id)
     }
 
+    pub fn session_log(&self) -> &SessionLog {
+        &self.session_log
+    }
+
+    /// Summarizes the call so far, as if it ended now.
+    pub fn call_summary(&self) -> Option<CallSummary> {
+        self.session_log.summary(Utc::now())
+    }
+
     pub fn followers_for(&self, leader_id: PeerId, project_id: u64) -> &[PeerId] {
         self.follows_by_leader_id_project_id
             .get(&(leader_id, project_id))
             .map_or(&[], |v| v.as_slice())
     }
 
-    /// Returns the most 'active' projects, defined as most people in the project
-    pub fn most_active_project(&self, cx: &App) -> Option<(u64, u64)> {
-        let mut project_hosts_and_guest_counts = HashMap::<u64, (Option<u64>, u32)>::default();
-        for participant in self.remote_participants.values() {
-            match participant.location {
-                ParticipantLocation::SharedProject { project_id } => {
-                    project_hosts_and_guest_counts
-                        .entry(project_id)
-                        .or_default()
-                        .1 += 1;
-                }
-                ParticipantLocation::External | ParticipantLocation::UnsharedProject => {}
-            }
-            for project in &participant.projects {
-                project_hosts_and_guest_counts
-                    .entry(project.id)
-                    .or_default()
-                    .0 = Some(participant.user.id);
-            }
+    /// The chat messages sent in this call since joining it, oldest first.
+    pub fn chat_messages(&self) -> &VecDeque<ChatMessage> {
+        &self.chat_messages
+    }
+
+    /// Sends a chat message to everyone in the call, adding it to the history once delivered.
+    ///
+    /// Messages can't be sent while the room is reconnecting; they fail instead of being
+    /// queued, so that they don't show up out of order once the connection is back.
+    pub fn send_chat_message(&mut self, text: String, cx: &mut Context<Self>) -> Task<Result<()>> {
+        if text.trim().is_empty() {
+            return Task::ready(Err(anyhow!("cannot send an empty chat message")));
         }
+        if text.len() > MAX_CHAT_MESSAGE_LEN {
+            return Task::ready(Err(anyhow!(
+                "chat message is {} bytes long, which exceeds the limit of {MAX_CHAT_MESSAGE_LEN}",
+                text.len()
+            )));
+        }
+        if !self.status.is_online() || !self.is_connected(cx) {
+            return Task::ready(Err(anyhow!("cannot send chat messages while reconnecting")));
+        }
+        let Some(live_kit) = self.live_kit.as_ref() else {
+            return Task::ready(Err(anyhow!("not connected to the call")));
+        };
+        let Some(sender) = self.local_participant_user(cx) else {
+            return Task::ready(Err(anyhow!("not signed in")));
+        };
 
-        if let Some(user) = self.user_store.read(cx).current_user() {
-            for project in &self.local_participant.projects {
-                project_hosts_and_guest_counts
-                    .entry(project.id)
-                    .or_default()
-                    .0 = Some(user.id);
-            }
+        let participant = live_kit.room.local_participant();
+        let payload = text.clone().into_bytes();
+        cx.spawn(async move |this, cx| {
+            participant
+                .publish_data(payload, CHAT_MESSAGE_TOPIC.to_string(), cx)
+                .await?;
+            this.update(cx, |this, cx| {
+                this.push_chat_message(ChatMessage { sender, text });
+                cx.notify();
+            })
+        })
+    }
+
+    fn push_chat_message(&mut self, message: ChatMessage) {
+        if self.chat_messages.len() == MAX_CHAT_HISTORY_LEN {
+            self.chat_messages.pop_front();
         }
+        self.chat_messages.push_back(message);
+    }
 
-        project_hosts_and_guest_counts
-            .into_iter()
-            .filter_map(|(id, (host, guest_count))| Some((id, host?, guest_count)))
-            .max_by_key(|(_, _, guest_count)| *guest_count)
-            .map(|(id, host, _)| (id, host))
+    /// Raises or lowers the local participant's hand.
+    ///
+    /// The hand is stored in the participant's metadata rather than sent as a message, so
+    /// that participants who join later see it too.
+    pub fn set_hand_raised(
+        &mut self,
+        hand_raised: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        if !self.status.is_online() || !self.is_connected(cx) {
+            return Task::ready(Err(anyhow!("cannot raise a hand while reconnecting")));
+        }
+        let publish = self.publish_metadata(hand_raised, cx);
+        cx.spawn(async move |this, cx| {
+            publish.await?;
+            this.update(cx, |this, cx| {
+                if this.local_participant.hand_raised != hand_raised {
+                    this.local_participant.hand_raised = hand_raised;
+                    this.emit_local_participant_state_changed(cx);
+                }
+                cx.notify();
+            })
+        })
     }
 
-    async fn handle_room_updated(
-        this: Entity<Self>,
-        envelope: TypedEnvelope<proto::RoomUpdated>,
-        mut cx: AsyncApp,
-    ) -> Result<()> {
-        let room = envelope.payload.room.context("invalid room")?;
-        this.update(&mut cx, |this, cx| this.apply_room_update(room, cx))
+    /// Publishes the local participant's state in their LiveKit metadata, with their hand
+    /// raised or not. Whether they're only listening is published along with it.
+    fn publish_metadata(&self, hand_raised: bool, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let Some(live_kit) = self.live_kit.as_ref() else {
+            return Task::ready(Err(anyhow!("not connected to the call")));
+        };
+        let metadata = ParticipantMetadata {
+            hand_raised,
+            active_location: self.active_location_throttle.published().cloned(),
+            listening: self.listen_only,
+        };
+        let metadata = match serde_json::to_string(&metadata) {
+            Ok(metadata) => metadata,
+            Err(error) => return Task::ready(Err(error.into())),
+        };
+
+        let participant = live_kit.room.local_participant();
+        cx.spawn(async move |_, cx| participant.set_metadata(metadata, cx).await)
     }
 
-    fn apply_room_update(&mut self, room: proto::Room, cx: &mut Context<Self>) -> Result<()> {
-        log::trace!(
-            "client {:?}. room update: {:?}",
-            self.client.user_id(),
-            &room
+    /// Tells the others in the call which file the local participant has open in `project`,
+    /// and which row their cursor is on, at most once per
+    /// [`crate::participant::ACTIVE_LOCATION_THROTTLE`].
+    ///
+    /// Only the project is shared when it isn't shared in the call, or when
+    /// `calls.share_active_location` is off.
+    pub fn set_active_location(
+        &mut self,
+        project: &Entity<Project>,
+        active_path: Option<(ProjectPath, Option<u32>)>,
+        cx: &mut Context<Self>,
+    ) {
+        if self.local_participant.active_project.as_ref() != Some(&project.downgrade()) {
+            return;
+        }
+        let location = ActiveLocation::new(
+            project.read(cx).remote_id(),
+            active_path,
+            CallSettings::get_global(cx).share_active_location,
         );
+        let now = cx.background_executor().now();
+        let Some(wait) = self.active_location_throttle.set(location, now) else {
+            return;
+        };
+        // A publish that's already waiting picks up the latest location when it's done.
+        if self.publish_active_location.is_some() {
+            return;
+        }
+        self.publish_active_location = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(wait).await;
+            this.update(cx, |this, cx| {
+                this.publish_active_location = None;
+                let now = cx.background_executor().now();
+                if this.active_location_throttle.publish(now) {
+                    this.publish_metadata(this.local_participant.hand_raised, cx)
+                        .detach_and_log_err(cx);
+                }
+            })
+            .ok();
+        }));
+    }
+
+    /// Shows a reaction next to the local participant for everyone in the call, for
+    /// [`REACTION_DURATION`].
+    ///
+    /// At most one reaction can be sent per [`REACTION_RATE_LIMIT`]; sending another one
+    /// sooner fails.
+    pub fn send_reaction(&mut self, emoji: &str, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let emoji = emoji.trim();
+        if emoji.is_empty() {
+            return Task::ready(Err(anyhow!("cannot send an empty reaction")));
+        }
+        if emoji.len() > MAX_REACTION_LEN {
+            return Task::ready(Err(anyhow!(
+                "reaction is {} bytes long, which exceeds the limit of {MAX_REACTION_LEN}",
+                emoji.len()
+            )));
+        }
+        if !self.status.is_online() || !self.is_connected(cx) {
+            return Task::ready(Err(anyhow!("cannot send reactions while reconnecting")));
+        }
+        let Some(live_kit) = self.live_kit.as_ref() else {
+            return Task::ready(Err(anyhow!("not connected to the call")));
+        };
+        let now = cx.background_executor().now();
+        if let Some((_, sent_at)) = &self.local_participant.last_reaction
+            && now.saturating_duration_since(*sent_at) < REACTION_RATE_LIMIT
+        {
+            return Task::ready(Err(anyhow!("reactions are being sent too quickly")));
+        }
 
-        self.pending_room_update = Some(self.start_room_connection(room, cx));
-
+        // Show the reaction right away, which also applies the rate limit to reactions sent
+        // while this one is still being delivered.
+        self.local_participant.last_reaction = Some((emoji.to_string(), now));
+        self.emit_local_participant_state_changed(cx);
+        self.clear_reaction_after_timeout(None, now, cx);
         cx.notify();
-        Ok(())
+
+        let participant = live_kit.room.local_participant();
+        let payload = emoji.as_bytes().to_vec();
+        cx.spawn(async move |_, cx| {
+            participant
+                .publish_data(payload, REACTION_TOPIC.to_string(), cx)
+                .await
+        })
     }
 
-    pub fn room_update_completed(&mut self) -> impl Future<Output = ()> + use<> {
-        let mut done_rx = self.room_update_completed_rx.clone();
-        async move {
-            while let Some(result) = done_rx.next().await {
-                if result.is_some() {
-                    break;
+    /// Clears the reaction shown at `shown_at` once it expires, unless it was replaced by a
+    /// newer one. `user_id` is `None` for the local participant.
+    fn clear_reaction_after_timeout(
+        &self,
+        user_id: Option<u64>,
+        shown_at: Instant,
+        cx: &mut Context<Self>,
+    ) {
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(REACTION_DURATION).await;
+            this.update(cx, |this, cx| {
+                let (last_reaction, participant_id) = match user_id {
+                    Some(user_id) => {
+                        let Some(participant) = this.remote_participants.get_mut(&user_id) else {
+                            return;
+                        };
+                        (&mut participant.last_reaction, Some(participant.peer_id))
+                    }
+                    None => (
+                        &mut this.local_participant.last_reaction,
+                        this.client.peer_id(),
+                    ),
+                };
+                if last_reaction
+                    .as_ref()
+                    .is_some_and(|(_, reacted_at)| *reacted_at == shown_at)
+                {
+                    *last_reaction = None;
+                    if let Some(participant_id) = participant_id {
+                        cx.emit(Event::ParticipantStateChanged { participant_id });
+                    }
+                    cx.notify();
                 }
-            }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn emit_local_participant_state_changed(&self, cx: &mut Context<Self>) {
+        if let Some(participant_id) = self.client.peer_id() {
+            cx.emit(Event::ParticipantStateChanged { participant_id });
         }
     }
 
-    fn start_room_connection(&self, mut room: proto::Room, cx: &mut Context<Self>) -> Task<()> {
-        // Filter ourselves out from the room's participants.
-        let local_participant_ix = room
-            .participants
-            .iter()
-            .position(|participant| Some(participant.user_id) == self.client.user_id());
-        let local_participant = local_participant_ix.map(|ix| room.participants.swap_remove(ix));
+    /// Who may control the local participant's shared screen, and which shared screens they
+    /// asked for or were given control of.
+    pub fn remote_control(&self) -> &RemoteControl {
+        &self.remote_control
+    }
 
-        let pending_participant_user_ids = room
-            .pending_participants
-            .iter()
-            .map(|p| p.user_id)
-            .collect::<Vec<_>>();
+    /// Asks the participant sharing their screen as `sharer` to let the local participant
+    /// control it. Their answer arrives as an [`Event::RemoteControlChanged`].
+    pub fn request_control(&mut self, sharer: PeerId, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let Some(participant) = self.remote_participant_for_peer_id(sharer) else {
+            return Task::ready(Err(anyhow!("no participant with peer id {sharer}")));
+        };
+        if !participant.has_screen_share_tracks() {
+            return Task::ready(Err(anyhow!(
+                "{} isn't sharing their screen",
+                participant.user.github_login
+            )));
+        }
+        if let Err(error) = self.remote_control.request(sharer) {
+            return Task::ready(Err(error));
+        }
+        cx.emit(Event::RemoteControlChanged {
+            participant_id: sharer,
+        });
+        cx.notify();
 
-        let remote_participant_user_ids = room
-            .participants
-            .iter()
-            .map(|p| p.user_id)
-            .collect::<Vec<_>>();
+        let send = self.send_control_message(sharer, ControlMessageKind::Request, cx);
+        cx.spawn(async move |this, cx| {
+            let result = send.await;
+            if result.is_err() {
+                // The sharer won't answer a request they never received.
+                this.update(cx, |this, cx| {
+                    if this.remote_control.receive_deny(sharer) {
+                        cx.emit(Event::RemoteControlChanged {
+                            participant_id: sharer,
+                        });
+                        cx.notify();
+                    }
+                })
+                .ok();
+            }
+            result
+        })
+    }
 
-        let (remote_participants, pending_participants) =
-            self.user_store.update(cx, move |user_store, cx| {
-                (
-                    user_store.get_users(remote_participant_user_ids, cx),
-                    user_store.get_users(pending_participant_user_ids, cx),
-                )
+    /// Lets `viewer`, who asked for it, control the local participant's shared screen for
+    /// [`CallSettings::remote_control_timeout`]. Whoever had control before loses it.
+    pub fn grant_control(&mut self, viewer: PeerId, cx: &mut Context<Self>) -> Task<Result<()>> {
+        if !self.is_sharing_screen() {
+            return Task::ready(Err(anyhow!("screen is not shared")));
+        }
+        let duration = CallSettings::get_global(cx).remote_control_timeout;
+        let now = cx.background_executor().now();
+        let previous_controller = match self.remote_control.grant_control(viewer, now, duration) {
+            Ok((_, previous_controller)) => previous_controller,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Some(previous_controller) = previous_controller {
+            cx.emit(Event::RemoteControlChanged {
+                participant_id: previous_controller,
             });
-        cx.spawn(async move |this, cx| {
-            let (remote_participants, pending_participants) =
-                futures::join!(remote_participants, pending_participants);
+            self.send_control_message(previous_controller, ControlMessageKind::Revoke, cx)
+                .detach_and_log_err(cx);
+        }
+        cx.emit(Event::RemoteControlChanged {
+            participant_id: viewer,
+        });
+        self.expire_control_grant_after_timeout(duration, cx);
+        cx.notify();
 
-            this.update(cx, |this, cx| {
-                this.participant_user_ids.clear();
+        let duration_ms = duration.as_millis() as u64;
+        self.send_control_message(viewer, ControlMessageKind::Grant { duration_ms }, cx)
+    }
 
-                if let Some(participant) = local_participant {
-                    let role = participant.role();
-                    this.local_participant.projects = participant.projects;
-                    if this.local_participant.role != role {
-                        this.local_participant.role = role;
+    /// Turns down `viewer`'s request to control the local participant's shared screen.
+    pub fn deny_control(&mut self, viewer: PeerId, cx: &mut Context<Self>) -> Task<Result<()>> {
+        if !self.remote_control.deny_control(viewer) {
+            return Task::ready(Err(anyhow!("participant didn't request control")));
+        }
+        cx.emit(Event::RemoteControlChanged {
+            participant_id: viewer,
+        });
+        cx.notify();
+        self.send_control_message(viewer, ControlMessageKind::Deny, cx)
+    }
 
-                        if role == proto::ChannelRole::Guest {
-                            for project in mem::take(&mut this.shared_projects) {
-                                if let Some(project) = project.upgrade() {
-                                    this.unshare_project(project, cx).log_err();
-                                }
-                            }
-                            this.local_participant.projects.clear();
-                            if let Some(livekit_room) = &mut this.live_kit {
-                                livekit_room.stop_publishing(cx);
+    /// Takes back control of the local participant's shared screen before the grant expires.
+    pub fn revoke_control(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let Some(controller) = self.remote_control.revoke_control() else {
+            return Task::ready(Ok(()));
+        };
+        cx.emit(Event::RemoteControlChanged {
+            participant_id: controller,
+        });
+        cx.notify();
+        self.send_control_message(controller, ControlMessageKind::Revoke, cx)
+    }
+
+    /// Forgets the requests for and grant of the local screen once it stops being shared.
+    fn stop_sharing_control(&mut self, cx: &mut Context<Self>) {
+        let requesters = self.remote_control.requests().to_vec();
+        let controller = self.remote_control.stop_sharing();
+        for participant_id in requesters.into_iter().chain(controller) {
+            cx.emit(Event::RemoteControlChanged { participant_id });
+        }
+        // Viewers who are still waiting for an answer see the screen share end, which is
+        // answer enough, but the controller is told explicitly.
+        if let Some(controller) = controller {
+            self.send_control_message(controller, ControlMessageKind::Revoke, cx)
+                .detach_and_log_err(cx);
+        }
+    }
+
+    fn send_control_message(
+        &self,
+        to: PeerId,
+        kind: ControlMessageKind,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        if !self.status.is_online() || !self.is_connected(cx) {
+            return Task::ready(Err(anyhow!(
+                "cannot send screen control messages while reconnecting"
+            )));
+        }
+        let Some(live_kit) = self.live_kit.as_ref() else {
+            return Task::ready(Err(anyhow!("not connected to the call")));
+        };
+        let Some(recipient) = self.remote_participant_for_peer_id(to) else {
+            return Task::ready(Err(anyhow!("no participant with peer id {to}")));
+        };
+        let message = ControlMessage {
+            to_user_id: recipient.user.id,
+            kind,
+        };
+        let payload = match serde_json::to_vec(&message) {
+            Ok(payload) => payload,
+            Err(error) => return Task::ready(Err(error.into())),
+        };
+
+        let participant = live_kit.room.local_participant();
+        cx.spawn(async move |_, cx| {
+            participant
+                .publish_data(payload, REMOTE_CONTROL_TOPIC.to_string(), cx)
+                .await
+        })
+    }
+
+    fn handle_control_message(
+        &mut self,
+        sender: PeerId,
+        kind: ControlMessageKind,
+        cx: &mut Context<Self>,
+    ) {
+        let changed = match kind {
+            ControlMessageKind::Request => {
+                if !self.is_sharing_screen() {
+                    // Answer right away rather than leave the viewer waiting.
+                    self.send_control_message(sender, ControlMessageKind::Deny, cx)
+                        .detach_and_log_err(cx);
+                    return;
+                }
+                let is_new = self.remote_control.receive_request(sender);
+                if is_new {
+                    cx.emit(Event::ControlRequested {
+                        participant_id: sender,
+                    });
+                }
+                is_new
+            }
+            ControlMessageKind::Grant { duration_ms } => {
+                let Some(local_peer_id) = self.client.peer_id() else {
+                    return;
+                };
+                let duration = Duration::from_millis(duration_ms);
+                let expires_at = cx.background_executor().now() + duration;
+                let granted = self
+                    .remote_control
+                    .receive_grant(sender, local_peer_id, expires_at);
+                if granted {
+                    self.release_controlled_screen_after_timeout(duration, cx);
+                }
+                granted
+            }
+            ControlMessageKind::Deny => self.remote_control.receive_deny(sender),
+            ControlMessageKind::Revoke => self.remote_control.release_screen(sender),
+        };
+        if changed {
+            cx.emit(Event::RemoteControlChanged {
+                participant_id: sender,
+            });
+            cx.notify();
+        }
+    }
+
+    /// Ends control of the local screen once the grant made now expires, unless it was
+    /// revoked or replaced by then.
+    fn expire_control_grant_after_timeout(&self, duration: Duration, cx: &mut Context<Self>) {
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(duration).await;
+            this.update(cx, |this, cx| {
+                let now = cx.background_executor().now();
+                if let Some(controller) = this.remote_control.expire_grant(now) {
+                    cx.emit(Event::RemoteControlChanged {
+                        participant_id: controller,
+                    });
+                    this.send_control_message(controller, ControlMessageKind::Revoke, cx)
+                        .detach_and_log_err(cx);
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Gives up control of a remote screen once the grant received now expires, in case the
+    /// sharer's revocation doesn't arrive.
+    fn release_controlled_screen_after_timeout(&self, duration: Duration, cx: &mut Context<Self>) {
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(duration).await;
+            this.update(cx, |this, cx| {
+                let now = cx.background_executor().now();
+                if let Some(sharer) = this.remote_control.expire_controlled_screen(now) {
+                    cx.emit(Event::RemoteControlChanged {
+                        participant_id: sharer,
+                    });
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Reads the state of the participants who were already in the call when it connected to
+    /// LiveKit, since no events are sent for metadata they set before then.
+    fn apply_existing_participant_metadata(&mut self, cx: &mut Context<Self>) {
+        let Some(live_kit) = self.live_kit.as_ref() else {
+            return;
+        };
+        let livekit_participants = live_kit.room.remote_participants();
+        for (identity, participant) in livekit_participants {
+            if let Ok(user_id) = identity.0.parse()
+                && self.remote_participants.contains_key(&user_id)
+            {
+                self.apply_participant_metadata(user_id, &participant.metadata(), cx)
+                    .warn_on_err();
+            }
+        }
+    }
+
+    /// Applies the state a remote participant published in their LiveKit metadata.
+    fn apply_participant_metadata(
+        &mut self,
+        user_id: u64,
+        metadata: &str,
+        cx: &mut Context<Self>,
+    ) -> Result<()> {
+        let participant = self
+            .remote_participants
+            .get_mut(&user_id)
+            .with_context(|| format!("metadata changed for unknown participant {user_id}"))?;
+        // Participants that never set any metadata have an empty string.
+        let metadata = if metadata.is_empty() {
+            ParticipantMetadata::default()
+        } else {
+            serde_json::from_str::<ParticipantMetadata>(metadata)
+                .with_context(|| format!("invalid metadata from participant {user_id}"))?
+        };
+        if participant.hand_raised != metadata.hand_raised
+            || participant.listening != metadata.listening
+        {
+            participant.hand_raised = metadata.hand_raised;
+            participant.listening = metadata.listening;
+            cx.emit(Event::ParticipantStateChanged {
+                participant_id: participant.peer_id,
+            });
+        }
+        participant.active_location = metadata.active_location;
+        if participant.update_active_path() {
+            cx.emit(Event::ParticipantLocationChanged {
+                participant_id: participant.peer_id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the projects shared in the room, with the most visited first.
+    ///
+    /// Projects with the same number of participants are ordered by id, so that the order
+    /// doesn't change between calls while the room stays the same.
+    pub fn project_activity(&self, cx: &App) -> Vec<ProjectActivity> {
+        let remote_participants =
+            self.remote_participants
+                .values()
+                .map(|participant| ParticipantPresence {
+                    user_id: participant.user.id,
+                    project_ids: participant
+                        .projects
+                        .iter()
+                        .map(|project| project.id)
+                        .collect(),
+                    location: match participant.location {
+                        ParticipantLocation::SharedProject { project_id } => Some(project_id),
+                        ParticipantLocation::External | ParticipantLocation::UnsharedProject => {
+                            None
+                        }
+                    },
+                });
+        let local_participant =
+            self.user_store
+                .read(cx)
+                .current_user()
+                .map(|user| ParticipantPresence {
+                    user_id: user.id,
+                    project_ids: self
+                        .local_participant
+                        .projects
+                        .iter()
+                        .map(|project| project.id)
+                        .collect(),
+                    location: self
+                        .local_participant
+                        .active_project
+                        .as_ref()
+                        .and_then(|project| project.upgrade())
+                        .and_then(|project| project.read(cx).remote_id()),
+                });
+        rank_project_activity(remote_participants.chain(local_participant))
+    }
+
+    /// Returns the project with the most participants in it.
+    pub fn most_active_project(&self, cx: &App) -> Option<ProjectActivity> {
+        self.project_activity(cx).into_iter().next()
+    }
+
+    async fn handle_room_updated(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::RoomUpdated>,
+        mut cx: AsyncApp,
+    ) -> Result<()> {
+        let room = envelope.payload.room.context("invalid room")?;
+        this.update(&mut cx, |this, cx| this.apply_room_update(room, cx))
+    }
+
+    fn apply_room_update(&mut self, room: proto::Room, cx: &mut Context<Self>) -> Result<()> {
+        log::trace!(
+            "client {:?}. room update: {:?}",
+            self.client.user_id(),
+            &room
+        );
+
+        let local_user_id = self.client.user_id();
+        self.remote_participant_count = room
+            .participants
+            .iter()
+            .filter(|participant| Some(participant.user_id) != local_user_id)
+            .count();
+        self.pending_room_update = Some(self.start_room_connection(room, cx));
+
+        cx.notify();
+        Ok(())
+    }
+
+    pub fn room_update_completed(&mut self) -> impl Future<Output = ()> + use<> {
+        let mut done_rx = self.room_update_completed_rx.clone();
+        async move {
+            while let Some(result) = done_rx.next().await {
+                if result.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn start_room_connection(&self, mut room: proto::Room, cx: &mut Context<Self>) -> Task<()> {
+        // Filter ourselves out from the room's participants.
+        let local_participant_ix = room
+            .participants
+            .iter()
+            .position(|participant| Some(participant.user_id) == self.client.user_id());
+        let local_participant = local_participant_ix.map(|ix| room.participants.swap_remove(ix));
+
+        let pending_participant_user_ids = room
+            .pending_participants
+            .iter()
+            .map(|p| p.user_id)
+            .collect::<Vec<_>>();
+
+        let remote_participant_user_ids = room
+            .participants
+            .iter()
+            .map(|p| p.user_id)
+            .collect::<Vec<_>>();
+
+        let (remote_participants, pending_participants) =
+            self.user_store.update(cx, move |user_store, cx| {
+                (
+                    user_store.get_users(remote_participant_user_ids, cx),
+                    user_store.get_users(pending_participant_user_ids, cx),
+                )
+            });
+        cx.spawn(async move |this, cx| {
+            let (remote_participants, pending_participants) =
+                futures::join!(remote_participants, pending_participants);
+
+            this.update(cx, |this, cx| {
+                let now = Utc::now();
+                this.participant_user_ids.clear();
+
+                if let Some(participant) = local_participant {
+                    let role = participant.role();
+                    let old_project_ids = this
+                        .local_participant
+                        .projects
+                        .iter()
+                        .map(|project| project.id)
+                        .collect::<HashSet<_>>();
+                    this.local_participant.projects = participant.projects;
+                    this.unshare_revoked_projects(old_project_ids, cx);
+                    if this.local_participant.role != role {
+                        let old_capabilities = this.local_capabilities();
+                        this.local_participant.role = role;
+                        let capabilities = this.local_capabilities();
+                        if capabilities != old_capabilities {
+                            cx.emit(Event::LocalCapabilitiesChanged { capabilities });
+                        }
+
+                        if role == proto::ChannelRole::Guest {
+                            for project in mem::take(&mut this.shared_projects) {
+                                if let Some(project) = project.upgrade() {
+                                    this.unshare_project(project, cx).log_err();
+                                }
+                            }
+                            this.local_participant.projects.clear();
+                            if let Some(livekit_room) = &mut this.live_kit {
+                                livekit_room.stop_publishing(cx);
                             }
+                            this.stop_sharing_control(cx);
                         }
 
                         this.joined_projects.retain(|project| {
@@ -766,6 +1686,7 @@ impl Room {
                         };
                         let participant_index = ParticipantIndex(participant.participant_index);
                         this.participant_user_ids.insert(participant.user_id);
+                        this.session_log.joined(user.id, &user.github_login, now);
 
                         let old_projects = this
                             .remote_participants
@@ -782,6 +1703,11 @@ impl Room {
 
                         for project in &participant.projects {
                             if !old_projects.contains(&project.id) {
+                                this.session_log.shared_project(
+                                    project.id,
+                                    user.id,
+                                    project.worktree_root_names.clone(),
+                                );
                                 cx.emit(Event::RemoteProjectShared {
                                     owner: user.clone(),
                                     project_id: project.id,
@@ -822,8 +1748,17 @@ impl Room {
                             if location != remote_participant.location
                                 || role != remote_participant.role
                             {
+                                let old_capabilities = remote_participant.capabilities();
                                 remote_participant.location = location;
                                 remote_participant.role = role;
+                                let capabilities = remote_participant.capabilities();
+                                if capabilities != old_capabilities {
+                                    cx.emit(Event::ParticipantCapabilitiesChanged {
+                                        participant_id: peer_id,
+                                        capabilities,
+                                    });
+                                }
+                                remote_participant.update_active_path();
                                 cx.emit(Event::ParticipantLocationChanged {
                                     participant_id: peer_id,
                                 });
@@ -840,6 +1775,12 @@ impl Room {
                                     role,
                                     muted: true,
                                     speaking: false,
+                                    hand_raised: false,
+                                    listening: false,
+                                    last_reaction: None,
+                                    active_path: None,
+                                    cursor_row: None,
+                                    active_location: None,
                                     video_tracks: Default::default(),
                                     audio_tracks: Default::default(),
                                 },
@@ -850,9 +1791,9 @@ impl Room {
                             // Dont play extra sounds over that.
                             if this.created.elapsed() > Duration::from_millis(100) {
                                 if let proto::ChannelRole::Guest = role {
-                                    Audio::play_sound(Sound::GuestJoined, cx);
+                                    play_call_sound(Sound::GuestJoined, cx);
                                 } else {
-                                    Audio::play_sound(Sound::Joined, cx);
+                                    play_call_sound(Sound::Joined, cx);
                                 }
                             }
 
@@ -860,6 +1801,12 @@ impl Room {
                                 && let Some(livekit_participant) = livekit_participants
                                     .get(&ParticipantIdentity(user.id.to_string()))
                             {
+                                this.apply_participant_metadata(
+                                    user.id,
+                                    &livekit_participant.metadata(),
+                                    cx,
+                                )
+                                .warn_on_err();
                                 for publication in
                                     livekit_participant.track_publications().into_values()
                                 {
@@ -879,10 +1826,13 @@ impl Room {
                         }
                     }
 
+                    let mut left_peer_ids = Vec::new();
                     this.remote_participants.retain(|user_id, participant| {
                         if this.participant_user_ids.contains(user_id) {
                             true
                         } else {
+                            this.session_log.left(*user_id, now);
+                            left_peer_ids.push(participant.peer_id);
                             for project in &participant.projects {
                                 cx.emit(Event::RemoteProjectUnshared {
                                     project_id: project.id,
@@ -894,6 +1844,11 @@ impl Room {
                             false
                         }
                     });
+                    for participant_id in left_peer_ids {
+                        if this.remote_control.participant_left(participant_id) {
+                            cx.emit(Event::RemoteControlChanged { participant_id });
+                        }
+                    }
                 }
 
                 if let Some(pending_participants) = pending_participants.log_err() {
@@ -980,7 +1935,11 @@ impl Room {
                             participant_id: participant.peer_id,
                         });
                         if let Some(live_kit) = self.live_kit.as_ref() {
-                            let stream = live_kit.room.play_remote_audio_track(&track, cx)?;
+                            let stream = live_kit.room.play_remote_audio_track(
+                                &track,
+                                live_kit.speaker.device_id(),
+                                cx,
+                            )?;
                             participant.audio_tracks.insert(track_id, (track, stream));
                             participant.muted = publication.is_muted();
                         }
@@ -989,6 +1948,9 @@ impl Room {
                         cx.emit(Event::RemoteVideoTracksChanged {
                             participant_id: participant.peer_id,
                         });
+                        if track.source() == TrackSource::Screenshare {
+                            self.session_log.shared_screen(user_id);
+                        }
                         participant.video_tracks.insert(track_id, track);
                     }
                 }
@@ -1021,6 +1983,13 @@ impl Room {
                             participant_id: participant.peer_id,
                         });
                         cx.emit(Event::RemoteVideoTrackUnsubscribed { sid: track.sid() });
+                        if !participant.has_screen_share_tracks()
+                            && self.remote_control.release_screen(participant.peer_id)
+                        {
+                            cx.emit(Event::RemoteControlChanged {
+                                participant_id: participant.peer_id,
+                            });
+                        }
                     }
                 }
             }
@@ -1067,6 +2036,7 @@ impl Room {
 
             RoomEvent::LocalTrackUnpublished { publication, .. } => {
                 log::info!("unpublished track {}", publication.sid());
+                let mut screen_unpublished = false;
                 if let Some(room) = &mut self.live_kit {
                     if let LocalTrack::Published {
                         track_publication, ..
@@ -1079,16 +2049,136 @@ impl Room {
                         track_publication, ..
                     } = &room.screen_track
                         && track_publication.sid() == publication.sid()
+                        && room.republishing_screen.as_ref() != Some(&publication.sid())
                     {
                         room.screen_track = LocalTrack::None;
+                        screen_unpublished = true;
                     }
+                    if let LocalTrack::Published {
+                        track_publication, ..
+                    } = &room.camera_track
+                        && track_publication.sid() == publication.sid()
+                    {
+                        room.camera_track = LocalTrack::None;
+                        cx.emit(Event::LocalCameraStopped);
+                    }
+                }
+                if screen_unpublished {
+                    self.screen_share_step_down = None;
+                    self.stop_sharing_control(cx);
                 }
             }
 
+            RoomEvent::ConnectionQualityChanged {
+                participant: livekit::Participant::Local(_),
+                quality,
+            } => {
+                let now = cx.background_executor().now();
+                self.screen_share_quality.quality_changed(quality, now);
+                self.schedule_screen_share_step_down(cx);
+            }
+
             RoomEvent::LocalTrackPublished { publication, .. } => {
                 log::info!("published track {:?}", publication.sid());
             }
 
+            RoomEvent::DataReceived {
+                payload,
+                topic,
+                participant,
+            } if topic.as_deref() == Some(CHAT_MESSAGE_TOPIC) => {
+                let participant = participant.context("chat message has no sender")?;
+                let user_id = participant.identity().0.parse()?;
+                let participant = self.remote_participants.get(&user_id).with_context(|| {
+                    format!("received chat message from unknown participant {user_id}")
+                })?;
+                anyhow::ensure!(
+                    payload.len() <= MAX_CHAT_MESSAGE_LEN,
+                    "chat message from {user_id} is {} bytes long",
+                    payload.len()
+                );
+                let message = ChatMessage {
+                    sender: participant.user.clone(),
+                    text: String::from_utf8(payload).context("invalid chat message")?,
+                };
+                let participant_id = participant.peer_id;
+                self.push_chat_message(message.clone());
+                cx.emit(Event::ChatMessageReceived {
+                    participant_id,
+                    message,
+                });
+            }
+
+            RoomEvent::DataReceived {
+                payload,
+                topic,
+                participant,
+            } if topic.as_deref() == Some(REACTION_TOPIC) => {
+                let participant = participant.context("reaction has no sender")?;
+                let user_id = participant.identity().0.parse()?;
+                let participant =
+                    self.remote_participants
+                        .get_mut(&user_id)
+                        .with_context(|| {
+                            format!("received reaction from unknown participant {user_id}")
+                        })?;
+                anyhow::ensure!(
+                    payload.len() <= MAX_REACTION_LEN,
+                    "reaction from {user_id} is {} bytes long",
+                    payload.len()
+                );
+                let emoji = String::from_utf8(payload).context("invalid reaction")?;
+                let now = cx.background_executor().now();
+                // Senders rate limit their own reactions, but two of them can still arrive closer
+                // together than that, so only reactions well within the limit are dropped.
+                if let Some((_, reacted_at)) = &participant.last_reaction
+                    && now.saturating_duration_since(*reacted_at) < REACTION_RATE_LIMIT / 2
+                {
+                    return Ok(());
+                }
+                participant.last_reaction = Some((emoji, now));
+                cx.emit(Event::ParticipantStateChanged {
+                    participant_id: participant.peer_id,
+                });
+                self.clear_reaction_after_timeout(Some(user_id), now, cx);
+            }
+
+            RoomEvent::DataReceived {
+                payload,
+                topic,
+                participant,
+            } if topic.as_deref() == Some(REMOTE_CONTROL_TOPIC) => {
+                let participant = participant.context("screen control message has no sender")?;
+                let user_id = participant.identity().0.parse()?;
+                let participant = self.remote_participants.get(&user_id).with_context(|| {
+                    format!("received screen control message from unknown participant {user_id}")
+                })?;
+                let sender = participant.peer_id;
+                let message: ControlMessage =
+                    serde_json::from_slice(&payload).context("invalid screen control message")?;
+                if Some(message.to_user_id) == self.client.user_id() {
+                    self.handle_control_message(sender, message.kind, cx);
+                }
+            }
+
+            RoomEvent::ParticipantMetadataChanged {
+                participant: livekit::Participant::Remote(participant),
+                metadata,
+                ..
+            } => {
+                let user_id = participant.identity().0.parse()?;
+                self.apply_participant_metadata(user_id, &metadata, cx)?;
+            }
+
+            RoomEvent::ParticipantConnected(participant) => {
+                // The room update for this participant may not have arrived yet, in which case
+                // their metadata is read once it does.
+                let user_id = participant.identity().0.parse()?;
+                if self.remote_participants.contains_key(&user_id) {
+                    self.apply_participant_metadata(user_id, &participant.metadata(), cx)?;
+                }
+            }
+
             RoomEvent::Disconnected { reason } => {
                 log::info!("disconnected from room: {reason:?}");
                 self.leave(cx).detach_and_log_err(cx);
@@ -1129,6 +2219,15 @@ impl Room {
         if self.status.is_offline() {
             return Task::ready(Err(anyhow!("room is offline")));
         }
+        if let Some(migration) = self.migration.clone() {
+            return cx.spawn(async move |this, cx| {
+                migration.await;
+                this.update(cx, |this, cx| {
+                    this.call(called_user_id, initial_project_id, cx)
+                })?
+                .await
+            });
+        }
 
         cx.notify();
         let client = self.client.clone();
@@ -1153,6 +2252,8 @@ impl Room {
         })
     }
 
+    /// Joins a project shared in the room, emitting [`Event::JoinProjectProgress`] as it goes.
+    /// Dropping the returned task cancels the join, and leaves the partially joined project.
     pub fn join_project(
         &mut self,
         id: u64,
@@ -1163,9 +2264,29 @@ impl Room {
         let client = self.client.clone();
         let user_store = self.user_store.clone();
         cx.emit(Event::RemoteProjectJoined { project_id: id });
+        let report_progress = {
+            let this = cx.weak_entity();
+            move |progress, cx: &mut App| {
+                this.update(cx, |_, cx| {
+                    cx.emit(Event::JoinProjectProgress {
+                        project_id: id,
+                        progress,
+                    })
+                })
+                .ok();
+            }
+        };
         cx.spawn(async move |this, cx| {
-            let project =
-                Project::in_room(id, client, user_store, language_registry, fs, cx.clone()).await?;
+            let project = Project::in_room_with_progress(
+                id,
+                client,
+                user_store,
+                language_registry,
+                fs,
+                report_progress,
+                cx.clone(),
+            )
+            .await?;
 
             this.update(cx, |this, cx| {
                 this.joined_projects.retain(|project| {
@@ -1189,6 +2310,55 @@ impl Room {
         if let Some(project_id) = project.read(cx).remote_id() {
             return Task::ready(Ok(project_id));
         }
+        if let Err(error) = self.check_capability(ParticipantCapabilities::SHARE_PROJECTS) {
+            return Task::ready(Err(error));
+        }
+        if let Some(migration) = self.migration.clone() {
+            return cx.spawn(async move |this, cx| {
+                migration.await;
+                this.update(cx, |this, cx| this.share_project(project, cx))?
+                    .await
+            });
+        }
+
+        let entity_id = project.entity_id();
+        let share = if let Some(share) = self.pending_shares.get(&entity_id) {
+            share.clone()
+        } else {
+            let share = self.request_share_project(project, cx);
+            let share = cx
+                .spawn(async move |this, cx| {
+                    let result = share.await.map_err(Arc::new);
+                    this.update(cx, |this, cx| {
+                        this.pending_shares.remove(&entity_id);
+                        cx.notify();
+                    })
+                    .ok();
+                    result
+                })
+                .shared();
+            self.pending_shares.insert(entity_id, share.clone());
+            cx.notify();
+            share
+        };
+        cx.background_spawn(async move { share.await.map_err(|error| error.cloned()) })
+    }
+
+    fn request_share_project(
+        &mut self,
+        project: Entity<Project>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<u64>> {
+        // Projects shared without going through the review, like when joining a call, still
+        // keep back the files that were excluded before.
+        secret_files::apply_secret_files_reviews(&project, cx);
+        let unreviewed_secret_files = secret_files::unreviewed_secret_files(&project, cx);
+        if !unreviewed_secret_files.is_empty() {
+            log::warn!(
+                "sharing a project with {} files that may hold secrets",
+                unreviewed_secret_files.len()
+            );
+        }
 
         let request = self.client.request(proto::ShareProject {
             room_id: self.id(),
@@ -1200,11 +2370,38 @@ impl Room {
         cx.spawn(async move |this, cx| {
             let response = request.await?;
 
-            project.update(cx, |project, cx| project.shared(response.project_id, cx))?;
+            if let Err(error) =
+                project.update(cx, |project, cx| project.shared(response.project_id, cx))
+            {
+                // The server already considers the project shared, so roll that back
+                // to keep it consistent with the local state.
+                this.update(cx, |this, _| {
+                    this.client.send(proto::UnshareProject {
+                        project_id: response.project_id,
+                    })
+                })??;
+                return Err(error);
+            }
 
             // If the user's location is in this project, it changes from UnsharedProject to SharedProject.
             this.update(cx, |this, cx| {
                 this.shared_projects.insert(project.downgrade());
+                if let Some(user_id) = this.client.user_id() {
+                    let worktree_root_names = project
+                        .read(cx)
+                        .worktree_root_names(cx)
+                        .map(ToString::to_string)
+                        .collect();
+                    this.session_log.shared_project(
+                        response.project_id,
+                        user_id,
+                        worktree_root_names,
+                    );
+                }
+                cx.emit(Event::ProjectShared {
+                    project_id: project.entity_id(),
+                    remote_id: response.project_id,
+                });
                 let active_project = this.local_participant.active_project.as_ref();
                 if active_project.is_some_and(|location| *location == project) {
                     this.set_location(Some(&project), cx)
@@ -1229,10 +2426,50 @@ impl Room {
         };
 
         self.client.send(proto::UnshareProject { project_id })?;
+        self.project_unshared(&project, cx)
+    }
+
+    /// Unshares projects that the server no longer lists for the local participant,
+    /// e.g. because the share was revoked on the server side.
+    fn unshare_revoked_projects(&mut self, old_project_ids: HashSet<u64>, cx: &mut Context<Self>) {
+        let new_project_ids = self
+            .local_participant
+            .projects
+            .iter()
+            .map(|project| project.id)
+            .collect::<HashSet<_>>();
+        let revoked_projects = self
+            .shared_projects
+            .iter()
+            .filter_map(|project| project.upgrade())
+            .filter(|project| {
+                project.read(cx).remote_id().is_some_and(|remote_id| {
+                    old_project_ids.contains(&remote_id) && !new_project_ids.contains(&remote_id)
+                })
+            })
+            .collect::<Vec<_>>();
+        for project in revoked_projects {
+            log::info!(
+                "project {:?} is no longer shared",
+                project.read(cx).remote_id()
+            );
+            self.project_unshared(&project, cx).log_err();
+        }
+    }
+
+    fn project_unshared(
+        &mut self,
+        project: &Entity<Project>,
+        cx: &mut Context<Self>,
+    ) -> Result<()> {
+        self.shared_projects.remove(&project.downgrade());
         project.update(cx, |this, cx| this.unshare(cx))?;
+        cx.emit(Event::ProjectUnshared {
+            project_id: project.entity_id(),
+        });
 
         if self.local_participant.active_project == Some(project.downgrade()) {
-            self.set_location(Some(&project), cx).detach_and_log_err(cx);
+            self.set_location(Some(project), cx).detach_and_log_err(cx);
         }
         Ok(())
     }
@@ -1265,6 +2502,10 @@ impl Room {
         };
 
         cx.notify();
+        // The location is sent once the room is done moving.
+        if self.status.is_migrating() {
+            return Task::ready(Ok(()));
+        }
         cx.background_spawn(async move {
             client
                 .request(proto::UpdateParticipantLocation {
@@ -1293,6 +2534,215 @@ impl Room {
         })
     }
 
+    pub fn is_sharing_camera(&self) -> bool {
+        self.live_kit
+            .as_ref()
+            .is_some_and(|live_kit| !matches!(live_kit.camera_track, LocalTrack::None))
+    }
+
+    pub fn camera_devices(&self) -> Result<Vec<CameraDevice>> {
+        self.live_kit
+            .as_ref()
+            .context("live-kit was not initialized")?
+            .room
+            .camera_devices()
+    }
+
+    /// The microphones and speakers that are connected, as of the last time they were listed.
+    pub fn audio_devices(&self) -> &[AudioDevice] {
+        self.live_kit
+            .as_ref()
+            .map_or(&[], |live_kit| &live_kit.audio_devices)
+    }
+
+    /// The microphone or speaker that the call uses, once the devices have been listed.
+    pub fn audio_device(&self, kind: AudioDeviceKind) -> Option<&AudioDevice> {
+        let live_kit = self.live_kit.as_ref()?;
+        match kind {
+            AudioDeviceKind::Input => live_kit.microphone.active(),
+            AudioDeviceKind::Output => live_kit.speaker.active(),
+        }
+    }
+
+    /// Captures from the microphone with the given id, or the system default if it's `None`,
+    /// without leaving the call. The choice is saved to the settings, so later calls use it too.
+    pub fn set_input_device(&mut self, device_id: Option<String>, cx: &mut Context<Self>) {
+        settings::update_settings_file(<dyn Fs>::global(cx), cx, {
+            let device_id = device_id.clone();
+            move |settings, _| {
+                settings.audio.get_or_insert_default().input_audio_device =
+                    device_id.map(|id| AudioInputDeviceName(Some(id)));
+            }
+        });
+        self.set_preferred_audio_device(AudioDeviceKind::Input, device_id, cx);
+    }
+
+    /// Plays the call on the speaker with the given id, or the system default if it's `None`.
+    /// Like [`Self::set_input_device`], the choice is saved for later calls.
+    pub fn set_output_device(&mut self, device_id: Option<String>, cx: &mut Context<Self>) {
+        settings::update_settings_file(<dyn Fs>::global(cx), cx, {
+            let device_id = device_id.clone();
+            move |settings, _| {
+                settings.audio.get_or_insert_default().output_audio_device =
+                    device_id.map(|id| AudioOutputDeviceName(Some(id)));
+            }
+        });
+        self.set_preferred_audio_device(AudioDeviceKind::Output, device_id, cx);
+    }
+
+    /// Applies devices picked elsewhere, like in the settings editor, to the ongoing call. Only
+    /// the settings that changed are applied, so that a device picked with
+    /// [`Self::set_input_device`] isn't undone before the settings file is reloaded.
+    fn audio_settings_changed(&mut self, cx: &mut Context<Self>) {
+        let settings_devices = preferred_audio_devices(cx);
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return;
+        };
+        let previous_settings_devices = mem::replace(
+            &mut live_kit.settings_audio_devices,
+            settings_devices.clone(),
+        );
+        let (input_device, output_device) = settings_devices;
+        if input_device != previous_settings_devices.0 {
+            self.set_preferred_audio_device(AudioDeviceKind::Input, input_device, cx);
+        }
+        if output_device != previous_settings_devices.1 {
+            self.set_preferred_audio_device(AudioDeviceKind::Output, output_device, cx);
+        }
+    }
+
+    fn set_preferred_audio_device(
+        &mut self,
+        kind: AudioDeviceKind,
+        device_id: Option<String>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return;
+        };
+        let routing = match kind {
+            AudioDeviceKind::Input => &mut live_kit.microphone,
+            AudioDeviceKind::Output => &mut live_kit.speaker,
+        };
+        if routing.set_preferred(device_id, &live_kit.audio_devices) {
+            self.switch_audio_device(kind, cx);
+        }
+    }
+
+    fn audio_devices_changed(&mut self, devices: Vec<AudioDevice>, cx: &mut Context<Self>) {
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return;
+        };
+        let switches = [
+            live_kit.microphone.devices_changed(&devices),
+            live_kit.speaker.devices_changed(&devices),
+        ];
+        live_kit.audio_devices = devices;
+        cx.notify();
+        for device in switches.into_iter().flatten() {
+            log::info!(
+                "switching to {:?} audio device {:?}",
+                device.kind,
+                device.name
+            );
+            self.switch_audio_device(device.kind, cx);
+            cx.emit(Event::AudioDeviceSwitched { device });
+        }
+    }
+
+    /// Moves the microphone or the remote participants' audio to the device that's now picked
+    /// for it, staying connected to the call.
+    fn switch_audio_device(&mut self, kind: AudioDeviceKind, cx: &mut Context<Self>) {
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return;
+        };
+        match kind {
+            AudioDeviceKind::Input => self.republish_microphone(cx),
+            AudioDeviceKind::Output => {
+                let output_device = live_kit.speaker.device_id();
+                // Stop every track before playing them again, so that none of them keeps the
+                // old device open.
+                let tracks = self
+                    .remote_participants
+                    .iter_mut()
+                    .flat_map(|(user_id, participant)| {
+                        participant
+                            .audio_tracks
+                            .drain()
+                            .map(|(sid, (track, _stream))| (*user_id, sid, track))
+                    })
+                    .collect::<Vec<_>>();
+                for (user_id, sid, track) in tracks {
+                    let stream = live_kit
+                        .room
+                        .play_remote_audio_track(&track, output_device.clone(), cx)
+                        .log_err();
+                    if let Some(stream) = stream
+                        && let Some(participant) = self.remote_participants.get_mut(&user_id)
+                    {
+                        participant.audio_tracks.insert(sid, (track, stream));
+                    }
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    /// Publishes the microphone again, if it's published, so that a new track picks up changes
+    /// to where or how it's captured. The new track is muted on publish if the old one was, so
+    /// that this never transmits audio the user didn't mean to.
+    fn republish_microphone(&mut self, cx: &mut Context<Self>) {
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return;
+        };
+        match mem::take(&mut live_kit.microphone_track) {
+            LocalTrack::None => return,
+            LocalTrack::Pending { .. } => {}
+            LocalTrack::Published {
+                track_publication, ..
+            } => {
+                let room = live_kit.room.clone();
+                let sid = track_publication.sid();
+                cx.spawn(async move |_, cx| room.unpublish_local_track(sid, cx).await)
+                    .detach_and_log_err(cx);
+            }
+        }
+        self.share_microphone(cx).detach_and_log_err(cx);
+    }
+
+    /// Whether `processing` is turned on for the microphone in this call, or `None` if it can't
+    /// be turned on or off. That's asked of the call's audio pipeline each time, so it's
+    /// accurate after the microphone moves to another device too.
+    pub fn audio_processing(&self, processing: AudioProcessing, cx: &App) -> Option<bool> {
+        self.live_kit
+            .as_ref()?
+            .room
+            .audio_processing(processing, cx)
+    }
+
+    /// Turns `processing` on or off for the rest of this call. Calls joined later go back to
+    /// what the settings say.
+    pub fn set_audio_processing(
+        &mut self,
+        processing: AudioProcessing,
+        enabled: bool,
+        cx: &mut Context<Self>,
+    ) -> Result<()> {
+        let live_kit = self
+            .live_kit
+            .as_ref()
+            .context("live-kit was not initialized")?;
+        match live_kit
+            .room
+            .set_audio_processing(processing, enabled, cx)?
+        {
+            AudioProcessingChange::Applied => {}
+            AudioProcessingChange::RequiresNewTrack => self.republish_microphone(cx),
+        }
+        cx.notify();
+        Ok(())
+    }
+
     pub fn is_sharing_mic(&self) -> bool {
         self.live_kit
             .as_ref()
@@ -1324,20 +2774,13 @@ impl Room {
     }
 
     pub fn can_use_microphone(&self) -> bool {
-        use proto::ChannelRole::*;
-
-        match self.local_participant.role {
-            Admin | Member | Talker => true,
-            Guest | Banned => false,
-        }
+        self.local_capabilities()
+            .contains(ParticipantCapabilities::USE_MICROPHONE)
     }
 
     pub fn can_share_projects(&self) -> bool {
-        use proto::ChannelRole::*;
-        match self.local_participant.role {
-            Admin | Member => true,
-            Guest | Banned | Talker => false,
-        }
+        self.local_capabilities()
+            .contains(ParticipantCapabilities::SHARE_PROJECTS)
     }
 
     #[track_caller]
@@ -1345,12 +2788,19 @@ impl Room {
         if self.status.is_offline() {
             return Task::ready(Err(anyhow!("room is offline")));
         }
+        if let Err(error) = self.check_capability(ParticipantCapabilities::USE_MICROPHONE) {
+            return Task::ready(Err(error));
+        }
 
-        let (room, publish_id) = if let Some(live_kit) = self.live_kit.as_mut() {
+        let (room, input_device, publish_id) = if let Some(live_kit) = self.live_kit.as_mut() {
             let publish_id = post_inc(&mut live_kit.next_publish_id);
             live_kit.microphone_track = LocalTrack::Pending { publish_id };
             cx.notify();
-            (live_kit.room.clone(), publish_id)
+            (
+                live_kit.room.clone(),
+                live_kit.microphone.device_id(),
+                publish_id,
+            )
         } else {
             return Task::ready(Err(anyhow!("live-kit was not initialized")));
         };
@@ -1365,7 +2815,7 @@ impl Room {
 
         cx.spawn(async move |this, cx| {
             let publication = room
-                .publish_local_microphone_track(user_name, is_staff, cx)
+                .publish_local_microphone_track(user_name, is_staff, input_device, cx)
                 .await;
             this.update(cx, |this, cx| {
                 let live_kit = this
@@ -1426,6 +2876,9 @@ impl Room {
         if self.is_sharing_screen() {
             return Task::ready(Err(anyhow!("screen was already shared")));
         }
+        if let Err(error) = self.check_capability(ParticipantCapabilities::SHARE_SCREEN) {
+            return Task::ready(Err(error));
+        }
 
         let (participant, publish_id) = if let Some(live_kit) = self.live_kit.as_mut() {
             let publish_id = post_inc(&mut live_kit.next_publish_id);
@@ -1436,8 +2889,11 @@ impl Room {
             return Task::ready(Err(anyhow!("live-kit was not initialized")));
         };
 
+        let limits = self.screen_share_preset.limits();
         cx.spawn(async move |this, cx| {
-            let publication = participant.publish_screenshare_track(&*source, cx).await;
+            let publication = participant
+                .publish_screenshare_track(&*source, limits, cx)
+                .await;
 
             this.update(cx, |this, cx| {
                 let live_kit = this
@@ -1464,12 +2920,23 @@ impl Room {
                         } else {
                             live_kit.screen_track = LocalTrack::Published {
                                 track_publication: publication,
-                                _stream: stream,
+                                _stream: Box::new(stream),
                             };
+                            if let Some(user_id) = this.client.user_id() {
+                                this.session_log.shared_screen(user_id);
+                            }
+                            cx.emit(Event::LocalScreenShareStarted {
+                                preset: this.screen_share_preset,
+                            });
+                            // The preset may have changed while the share was being published.
+                            if this.screen_share_preset.limits() != limits {
+                                this.update_screen_share_limits(cx).detach_and_log_err(cx);
+                            }
+                            this.schedule_screen_share_step_down(cx);
                             cx.notify();
                         }
 
-                        Audio::play_sound(Sound::StartScreenshare, cx);
+                        play_call_sound(Sound::StartScreenshare, cx);
                         Ok(())
                     }
                     Err(error) => {
@@ -1486,7 +2953,356 @@ impl Room {
         })
     }
 
+    pub fn screen_share_preset(&self) -> ScreenSharePreset {
+        self.screen_share_preset
+    }
+
+    pub fn is_screen_share_preset_pinned(&self) -> bool {
+        self.screen_share_preset_pinned
+    }
+
+    /// Picks the screen share's quality, applying it right away if the screen is being shared.
+    /// Unless the preset is pinned, it's still lowered if the connection can't keep up.
+    pub fn set_screen_share_preset(
+        &mut self,
+        preset: ScreenSharePreset,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        self.screen_share_quality.reset();
+        self.schedule_screen_share_step_down(cx);
+        self.change_screen_share_preset(preset, false, cx)
+    }
+
+    /// Pins the screen share's preset, so it isn't lowered when the connection can't keep up.
+    pub fn set_screen_share_preset_pinned(&mut self, pinned: bool, cx: &mut Context<Self>) {
+        if self.screen_share_preset_pinned == pinned {
+            return;
+        }
+        self.screen_share_preset_pinned = pinned;
+        self.screen_share_quality.reset();
+        self.schedule_screen_share_step_down(cx);
+        cx.emit(Event::ScreenSharePresetChanged {
+            preset: self.screen_share_preset,
+            pinned,
+            stepped_down: false,
+        });
+        cx.notify();
+    }
+
+    fn change_screen_share_preset(
+        &mut self,
+        preset: ScreenSharePreset,
+        stepped_down: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        if self.screen_share_preset == preset {
+            return Task::ready(Ok(()));
+        }
+        self.screen_share_preset = preset;
+        cx.emit(Event::ScreenSharePresetChanged {
+            preset,
+            pinned: self.screen_share_preset_pinned,
+            stepped_down,
+        });
+        cx.notify();
+        self.update_screen_share_limits(cx)
+    }
+
+    /// Applies the current preset to the published screen share, if there's one. The track is
+    /// published again for the encoder to pick up the new limits, and if the preset changes
+    /// again in the meantime, it's published once more when that's done.
+    fn update_screen_share_limits(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return Task::ready(Ok(()));
+        };
+        let LocalTrack::Published {
+            track_publication,
+            _stream: stream,
+        } = &live_kit.screen_track
+        else {
+            return Task::ready(Ok(()));
+        };
+        if live_kit.republishing_screen.is_some() {
+            return Task::ready(Ok(()));
+        }
+
+        let limits = self.screen_share_preset.limits();
+        stream.set_limits(limits);
+        let old_publication = track_publication.clone();
+        live_kit.republishing_screen = Some(old_publication.sid());
+        let participant = live_kit.room.local_participant();
+        cx.spawn(async move |this, cx| {
+            let publication = participant
+                .update_screenshare_limits(&old_publication, limits, cx)
+                .await;
+
+            this.update(cx, |this, cx| {
+                let live_kit = this
+                    .live_kit
+                    .as_mut()
+                    .context("live-kit was not initialized")?;
+                live_kit.republishing_screen = None;
+
+                let publication = match publication {
+                    Ok(publication) => publication,
+                    Err(error) => {
+                        // The old publication may be gone already, so the share is stopped
+                        // rather than left looking like it's still going.
+                        if matches!(
+                            &live_kit.screen_track,
+                            LocalTrack::Published { track_publication, .. }
+                                if track_publication.sid() == old_publication.sid()
+                        ) {
+                            this.unshare_screen(false, cx).log_err();
+                        }
+                        return Err(error.context("failed to change the screen share's quality"));
+                    }
+                };
+
+                match &mut live_kit.screen_track {
+                    LocalTrack::Published {
+                        track_publication, ..
+                    } if track_publication.sid() == old_publication.sid() => {
+                        *track_publication = publication;
+                        cx.notify();
+                        if this.screen_share_preset.limits() != limits {
+                            return Ok(this.update_screen_share_limits(cx));
+                        }
+                    }
+                    // The share was stopped in the meantime.
+                    _ => {
+                        let participant = live_kit.room.local_participant();
+                        let sid = publication.sid();
+                        cx.spawn(async move |_, cx| participant.unpublish_track(sid, cx).await)
+                            .detach_and_log_err(cx);
+                    }
+                }
+                Ok(Task::ready(Ok(())))
+            })??
+            .await
+        })
+    }
+
+    /// Waits until the connection may have been poor for long enough to lower the screen
+    /// share's preset, if it's being shared and the preset isn't pinned.
+    fn schedule_screen_share_step_down(&mut self, cx: &mut Context<Self>) {
+        self.screen_share_step_down = None;
+        if self.screen_share_preset_pinned || !self.is_sharing_screen() {
+            return;
+        }
+        let Some(deadline) = self.screen_share_quality.next_deadline() else {
+            return;
+        };
+
+        let executor = cx.background_executor().clone();
+        let delay = deadline.saturating_duration_since(executor.now());
+        self.screen_share_step_down = Some(cx.spawn(async move |this, cx| {
+            executor.timer(delay).await;
+            this.update(cx, |this, cx| {
+                let now = cx.background_executor().now();
+                if this.screen_share_quality.should_step_down(now)
+                    && let Some(preset) = this.screen_share_preset.lower()
+                {
+                    log::info!("lowering screen share quality to {preset:?}");
+                    this.change_screen_share_preset(preset, true, cx)
+                        .detach_and_log_err(cx);
+                }
+                this.schedule_screen_share_step_down(cx);
+            })
+            .ok();
+        }));
+    }
+
+    /// Publishes the camera with the given id, or the first one available.
+    pub fn share_camera(
+        &mut self,
+        device_id: Option<String>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        if self.status.is_offline() {
+            return Task::ready(Err(anyhow!("room is offline")));
+        }
+        if self.is_sharing_camera() {
+            return Task::ready(Err(anyhow!("camera was already shared")));
+        }
+        if let Err(error) = self.check_capability(ParticipantCapabilities::USE_CAMERA) {
+            return Task::ready(Err(error));
+        }
+
+        let (room, publish_id) = if let Some(live_kit) = self.live_kit.as_mut() {
+            let publish_id = post_inc(&mut live_kit.next_publish_id);
+            live_kit.camera_track = LocalTrack::Pending { publish_id };
+            cx.notify();
+            (live_kit.room.clone(), publish_id)
+        } else {
+            return Task::ready(Err(anyhow!("live-kit was not initialized")));
+        };
+
+        cx.spawn(async move |this, cx| {
+            let participant = room.local_participant();
+            let publication = async {
+                let devices = room.camera_devices()?;
+                let device =
+                    match &device_id {
+                        Some(device_id) => devices
+                            .into_iter()
+                            .find(|device| device.id == *device_id)
+                            .with_context(|| format!("camera {device_id:?} was not found"))?,
+                        None => devices
+                            .into_iter()
+                            .next()
+                            .context("no camera is available")?,
+                    };
+                participant.publish_camera_track(&device, cx).await
+            }
+            .await;
+
+            this.update(cx, |this, cx| {
+                let live_kit = this
+                    .live_kit
+                    .as_mut()
+                    .context("live-kit was not initialized")?;
+
+                let canceled = if let LocalTrack::Pending {
+                    publish_id: cur_publish_id,
+                } = &live_kit.camera_track
+                {
+                    *cur_publish_id != publish_id
+                } else {
+                    true
+                };
+
+                match publication {
+                    Ok((publication, stream)) => {
+                        if canceled {
+                            cx.spawn(async move |_, cx| {
+                                participant.unpublish_track(publication.sid(), cx).await
+                            })
+                            .detach()
+                        } else {
+                            let sid = publication.sid();
+                            let device_id = stream.device.id;
+                            let device_lost = stream.device_lost;
+                            let watch_device = cx.spawn(async move |this, cx| {
+                                if device_lost.await.is_ok() {
+                                    this.update(cx, |this, cx| {
+                                        this.camera_device_lost(sid, device_id, cx)
+                                    })
+                                    .ok();
+                                }
+                            });
+                            live_kit.camera_track = LocalTrack::Published {
+                                track_publication: publication,
+                                _stream: Box::new(watch_device),
+                            };
+                            cx.emit(Event::LocalCameraStarted);
+                            cx.notify();
+                        }
+                        Ok(())
+                    }
+                    Err(error) => {
+                        if canceled {
+                            Ok(())
+                        } else {
+                            live_kit.camera_track = LocalTrack::None;
+                            cx.notify();
+                            Err(error)
+                        }
+                    }
+                }
+            })?
+        })
+    }
+
+    pub fn unshare_camera(&mut self, cx: &mut Context<Self>) -> Result<()> {
+        anyhow::ensure!(!self.status.is_offline(), "room is offline");
+
+        let live_kit = self
+            .live_kit
+            .as_mut()
+            .context("live-kit was not initialized")?;
+        match mem::take(&mut live_kit.camera_track) {
+            LocalTrack::None => anyhow::bail!("camera was not shared"),
+            LocalTrack::Pending { .. } => {
+                cx.notify();
+                Ok(())
+            }
+            LocalTrack::Published {
+                track_publication, ..
+            } => {
+                let local_participant = live_kit.room.local_participant();
+                let sid = track_publication.sid();
+                cx.spawn(async move |_, cx| local_participant.unpublish_track(sid, cx).await)
+                    .detach_and_log_err(cx);
+                cx.emit(Event::LocalCameraStopped);
+                cx.notify();
+                Ok(())
+            }
+        }
+    }
+
+    /// Unpublishes the camera rather than leaving remote participants looking at its last frame.
+    fn camera_device_lost(&mut self, sid: TrackSid, device_id: String, cx: &mut Context<Self>) {
+        let is_current_track = self.live_kit.as_ref().is_some_and(|live_kit| {
+            matches!(
+                &live_kit.camera_track,
+                LocalTrack::Published { track_publication, .. } if track_publication.sid() == sid
+            )
+        });
+        if is_current_track {
+            log::warn!("camera {device_id:?} was disconnected");
+            cx.emit(Event::LocalCameraDisconnected { device_id });
+            self.unshare_camera(cx).log_err();
+        }
+    }
+
+    /// Stops only listening, publishing the microphone unless the usual rules for joining a
+    /// call say to start out muted. The microphone is only opened now, which is the point of
+    /// listening only. Participants whose role doesn't let them use it publish nothing.
+    pub fn start_participating(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        if !self.listen_only {
+            return Task::ready(Ok(()));
+        }
+        if !self.status.is_online() {
+            return Task::ready(Err(anyhow!(
+                "cannot start participating while reconnecting"
+            )));
+        }
+        let participant_count = self.remote_participant_count;
+        let auto_muted = self.join_preferences.auto_mutes(participant_count);
+        let muted_by_user =
+            self.join_preferences.muted || auto_muted || client::IMPERSONATE_LOGIN.is_some();
+        let Some(live_kit) = self.live_kit.as_mut() else {
+            return Task::ready(Err(anyhow!("not connected to the call")));
+        };
+        live_kit.muted_by_user = muted_by_user;
+        let deafened = live_kit.deafened;
+
+        self.listen_only = false;
+        let publish_metadata = self.publish_metadata(self.local_participant.hand_raised, cx);
+        if auto_muted {
+            self.auto_muted_on_join = Some(participant_count);
+            cx.emit(Event::MutedOnJoin { participant_count });
+        }
+        self.emit_local_participant_state_changed(cx);
+        cx.notify();
+
+        let share_microphone = (!muted_by_user && !deafened && self.can_use_microphone())
+            .then(|| self.share_microphone(cx));
+        cx.spawn(async move |_, _| {
+            publish_metadata.await?;
+            if let Some(share_microphone) = share_microphone {
+                share_microphone.await?;
+            }
+            Ok(())
+        })
+    }
+
     pub fn toggle_mute(&mut self, cx: &mut Context<Self>) {
+        // There's no microphone to unmute until the local participant starts participating.
+        if self.listen_only {
+            return;
+        }
         if let Some(live_kit) = self.live_kit.as_mut() {
             // When unmuting, undeafen if the user was deafened before.
             let was_deafened = live_kit.deafened;
@@ -1530,6 +3346,8 @@ impl Room {
 
     pub fn unshare_screen(&mut self, play_sound: bool, cx: &mut Context<Self>) -> Result<()> {
         anyhow::ensure!(!self.status.is_offline(), "room is offline");
+        self.stop_sharing_control(cx);
+        self.screen_share_step_down = None;
 
         let live_kit = self
             .live_kit
@@ -1553,7 +3371,7 @@ impl Room {
                 }
 
                 if play_sound {
-                    Audio::play_sound(Sound::StopScreenshare, cx);
+                    play_call_sound(Sound::StopScreenshare, cx);
                 }
 
                 Ok(())
@@ -1582,9 +3400,9 @@ impl Room {
         cx.notify();
 
         if should_mute {
-            Audio::play_sound(Sound::Mute, cx);
+            play_call_sound(Sound::Mute, cx);
         } else {
-            Audio::play_sound(Sound::Unmute, cx);
+            play_call_sound(Sound::Unmute, cx);
         }
 
         match &mut live_kit.microphone_track {
@@ -1615,6 +3433,7 @@ impl Room {
 
 fn spawn_room_connection(
     livekit_connection_info: Option<proto::LiveKitConnectionInfo>,
+    join_preferences: JoinPreferences,
     cx: &mut Context<Room>,
 ) {
     if let Some(connection_info) = livekit_connection_info {
@@ -1637,19 +3456,79 @@ fn spawn_room_connection(
                     }
                 });
 
-                let muted_by_user = Room::mute_on_join(cx);
+                let mut audio_device_updates = room.audio_device_updates(cx);
+                let _watch_audio_devices = cx.spawn(async move |this, cx| {
+                    while let Some(devices) = audio_device_updates.next().await {
+                        if this
+                            .update(cx, |this, cx| this.audio_devices_changed(devices, cx))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+                let settings_audio_devices = preferred_audio_devices(cx);
+                let microphone = AudioDeviceRouting::new(
+                    AudioDeviceKind::Input,
+                    settings_audio_devices.0.clone(),
+                );
+                let speaker = AudioDeviceRouting::new(
+                    AudioDeviceKind::Output,
+                    settings_audio_devices.1.clone(),
+                );
+                // Like the devices, the processing is set up before the microphone is published,
+                // so that its first track already uses it.
+                let call_settings = CallSettings::get_global(cx);
+                for processing in AudioProcessing::ALL {
+                    let enabled = call_settings.enables_audio_processing(processing);
+                    if room
+                        .audio_processing(processing, cx)
+                        .is_some_and(|current| current != enabled)
+                    {
+                        room.set_audio_processing(processing, enabled, cx).log_err();
+                    }
+                }
+
+                // These are in place before the microphone is published, so that joining muted
+                // or deafened never transmits any audio. The room's participants are known by
+                // now, since the join response is applied as soon as the room is created. Anyone
+                // joining after this point doesn't change how we joined.
+                // Listening only defers all of this to when the local participant starts
+                // participating.
+                let listen_only = this.listen_only;
+                let participant_count = this.remote_participant_count;
+                let auto_muted = !listen_only && join_preferences.auto_mutes(participant_count);
+                let muted_by_user = listen_only
+                    || join_preferences.muted
+                    || auto_muted
+                    || client::IMPERSONATE_LOGIN.is_some();
+                let deafened = !listen_only && join_preferences.deafened;
                 this.live_kit = Some(LiveKitRoom {
                     room: Rc::new(room),
                     screen_track: LocalTrack::None,
+                    republishing_screen: None,
+                    camera_track: LocalTrack::None,
                     microphone_track: LocalTrack::None,
                     next_publish_id: 0,
                     muted_by_user,
-                    deafened: false,
+                    deafened,
                     speaking: false,
+                    audio_devices: Vec::new(),
+                    microphone,
+                    speaker,
+                    settings_audio_devices,
                     _handle_updates,
+                    _watch_audio_devices,
                 });
+                this.apply_existing_participant_metadata(cx);
+                if auto_muted {
+                    this.auto_muted_on_join = Some(participant_count);
+                    cx.emit(Event::MutedOnJoin { participant_count });
+                }
 
-                if !muted_by_user && this.can_use_microphone() {
+                if listen_only {
+                    this.publish_metadata(this.local_participant.hand_raised, cx)
+                } else if !muted_by_user && !deafened && this.can_use_microphone() {
                     this.share_microphone(cx)
                 } else {
                     Task::ready(Ok(()))
@@ -1661,16 +3540,43 @@ fn spawn_room_connection(
     }
 }
 
+/// The ids of the microphone and speaker that the settings prefer, where `None` is the default.
+fn preferred_audio_devices(cx: &App) -> (Option<String>, Option<String>) {
+    let audio_settings = AudioSettings::get_global(cx);
+    (
+        audio_settings
+            .input_audio_device
+            .as_ref()
+            .map(ToString::to_string),
+        audio_settings
+            .output_audio_device
+            .as_ref()
+            .map(ToString::to_string),
+    )
+}
+
 struct LiveKitRoom {
     room: Rc<livekit::Room>,
-    screen_track: LocalTrack<dyn ScreenCaptureStream>,
+    screen_track: LocalTrack<ScreenShareStream>,
+    /// The screen share publication being replaced by one with other limits. Its unpublishing
+    /// doesn't mean the share stopped.
+    republishing_screen: Option<TrackSid>,
+    /// The published camera, along with the task watching for its device to be disconnected.
+    camera_track: LocalTrack<Task<()>>,
     microphone_track: LocalTrack<AudioStream>,
     /// Tracks whether we're currently in a muted state due to auto-mute from deafening or manual mute performed by user.
     muted_by_user: bool,
     deafened: bool,
     speaking: bool,
     next_publish_id: usize,
+    /// The microphones and speakers that are connected, as of the last time they were listed.
+    audio_devices: Vec<AudioDevice>,
+    microphone: AudioDeviceRouting,
+    speaker: AudioDeviceRouting,
+    /// The ids of the microphone and speaker in the settings, as of the last time they changed.
+    settings_audio_devices: (Option<String>, Option<String>),
     _handle_updates: Task<()>,
+    _watch_audio_devices: Task<()>,
 }
 
 impl LiveKitRoom {
@@ -1692,6 +3598,15 @@ impl LiveKitRoom {
             cx.notify();
         }
 
+        if let LocalTrack::Published {
+            track_publication, ..
+        } = mem::replace(&mut self.camera_track, LocalTrack::None)
+        {
+            tracks_to_unpublish.push(track_publication.sid());
+            cx.emit(Event::LocalCameraStopped);
+            cx.notify();
+        }
+
         let participant = self.room.local_participant();
         cx.spawn(async move |_, cx| {
             for sid in tracks_to_unpublish {
@@ -1719,6 +3634,8 @@ enum LocalTrack<Stream: ?Sized> {
 pub enum RoomStatus {
     Online,
     Rejoining,
+    /// The server is moving the room, while the call itself stays up.
+    Migrating,
     Offline,
 }
 
@@ -1727,7 +3644,128 @@ impl RoomStatus {
         matches!(self, RoomStatus::Offline)
     }
 
+    pub fn is_migrating(&self) -> bool {
+        matches!(self, RoomStatus::Migrating)
+    }
+
     pub fn is_online(&self) -> bool {
         matches!(self, RoomStatus::Online)
     }
 }
+
+/// What [`rank_project_activity`] needs to know about a participant in a room.
+struct ParticipantPresence {
+    user_id: u64,
+    /// The projects this participant shares.
+    project_ids: Vec<u64>,
+    /// The shared project this participant is in, if any.
+    location: Option<u64>,
+}
+
+fn rank_project_activity(
+    participants: impl IntoIterator<Item = ParticipantPresence>,
+) -> Vec<ProjectActivity> {
+    let participants = participants.into_iter().collect::<Vec<_>>();
+    let mut activity_by_project = BTreeMap::default();
+    for participant in &participants {
+        for &project_id in &participant.project_ids {
+            activity_by_project.insert(
+                project_id,
+                ProjectActivity {
+                    owner_user_id: participant.user_id,
+                    project_id,
+                    participant_count: 0,
+                    includes_host: false,
+                },
+            );
+        }
+    }
+    for participant in &participants {
+        if let Some(project_id) = participant.location
+            && let Some(activity) = activity_by_project.get_mut(&project_id)
+        {
+            activity.participant_count += 1;
+            activity.includes_host |= activity.owner_user_id == participant.user_id;
+        }
+    }
+
+    let mut activity = activity_by_project.into_values().collect::<Vec<_>>();
+    activity.sort_by(|a, b| {
+        b.participant_count
+            .cmp(&a.participant_count)
+            .then(a.project_id.cmp(&b.project_id))
+    });
+    activity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participant(
+        user_id: u64,
+        project_ids: &[u64],
+        location: Option<u64>,
+    ) -> ParticipantPresence {
+        ParticipantPresence {
+            user_id,
+            project_ids: project_ids.to_vec(),
+            location,
+        }
+    }
+
+    fn counts(activity: &[ProjectActivity]) -> Vec<(u64, usize, bool)> {
+        activity
+            .iter()
+            .map(|project| {
+                (
+                    project.project_id,
+                    project.participant_count,
+                    project.includes_host,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_project_activity_counts_participants() {
+        let activity = rank_project_activity([
+            participant(1, &[10], Some(10)),
+            participant(2, &[20, 30], Some(10)),
+            participant(3, &[], Some(20)),
+            participant(4, &[], Some(10)),
+            // Guests that aren't in a shared project don't count towards any.
+            participant(5, &[], None),
+            // Nor do participants in a project that isn't shared in this room.
+            participant(6, &[], Some(99)),
+        ]);
+        assert_eq!(
+            counts(&activity),
+            [(10, 3, true), (20, 1, false), (30, 0, false)]
+        );
+        assert_eq!(activity[0].owner_user_id, 1);
+        assert_eq!(activity[1].owner_user_id, 2);
+    }
+
+    #[test]
+    fn test_project_activity_breaks_ties_by_project_id() {
+        let participants = || {
+            [
+                participant(1, &[30], Some(30)),
+                participant(2, &[10], None),
+                participant(3, &[20], Some(20)),
+                participant(4, &[], Some(10)),
+            ]
+        };
+        let activity = rank_project_activity(participants());
+        assert_eq!(
+            counts(&activity),
+            [(10, 1, false), (20, 1, true), (30, 1, true)]
+        );
+
+        // The order doesn't depend on the order of the participants.
+        let mut reversed = participants();
+        reversed.reverse();
+        assert_eq!(rank_project_activity(reversed), activity);
+    }
+}
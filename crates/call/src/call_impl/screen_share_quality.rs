@@ -0,0 +1,210 @@
+//! How much bandwidth a screen share may use, and when to lower it because the connection can't
+//! keep up.
+
+use livekit_client::{ConnectionQuality, VideoEncodingLimits};
+use std::time::{Duration, Instant};
+
+/// How long the connection has to be poor before the screen share is lowered a notch.
+pub const STEP_DOWN_AFTER: Duration = Duration::from_secs(10);
+/// How long the connection has to be good again before an earlier poor stretch is forgotten.
+/// Shorter recoveries count as part of the same stretch, so a connection that keeps flapping
+/// between the two still gets the share lowered.
+pub const RECOVERY_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScreenSharePreset {
+    Low,
+    Balanced,
+    #[default]
+    High,
+}
+
+impl ScreenSharePreset {
+    pub const ALL: [Self; 3] = [Self::High, Self::Balanced, Self::Low];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Balanced => "Balanced",
+            Self::High => "High",
+        }
+    }
+
+    pub fn limits(&self) -> VideoEncodingLimits {
+        match self {
+            Self::Low => VideoEncodingLimits {
+                max_width: 1280,
+                max_height: 720,
+                max_framerate: 5.,
+                max_bitrate: 600_000,
+            },
+            Self::Balanced => VideoEncodingLimits {
+                max_width: 1920,
+                max_height: 1080,
+                max_framerate: 15.,
+                max_bitrate: 2_000_000,
+            },
+            Self::High => VideoEncodingLimits {
+                max_width: 3840,
+                max_height: 2160,
+                max_framerate: 30.,
+                max_bitrate: 6_000_000,
+            },
+        }
+    }
+
+    /// The preset one notch down, if there's one.
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            Self::Low => None,
+            Self::Balanced => Some(Self::Low),
+            Self::High => Some(Self::Balanced),
+        }
+    }
+}
+
+/// Watches the local participant's connection quality for sustained trouble.
+///
+/// LiveKit only reports the quality when it changes, so besides being told about each change,
+/// the monitor has to be polled at [`QualityMonitor::next_deadline`] to notice that the
+/// connection has stayed poor.
+#[derive(Debug, Default)]
+pub struct QualityMonitor {
+    /// When the current poor stretch started, or when the share was last lowered during it.
+    poor_since: Option<Instant>,
+    /// When the connection became good again during a poor stretch, if it still is.
+    recovered_since: Option<Instant>,
+}
+
+impl QualityMonitor {
+    pub fn quality_changed(&mut self, quality: ConnectionQuality, now: Instant) {
+        self.expire_recovery(now);
+        if quality <= ConnectionQuality::Poor {
+            self.poor_since.get_or_insert(now);
+            self.recovered_since = None;
+        } else if self.poor_since.is_some() {
+            self.recovered_since.get_or_insert(now);
+        }
+    }
+
+    /// Whether the share should be lowered a notch now. Once it has been, the connection has to
+    /// stay poor for another [`STEP_DOWN_AFTER`] before it's lowered again, to give the new
+    /// limits a chance to help.
+    pub fn should_step_down(&mut self, now: Instant) -> bool {
+        self.expire_recovery(now);
+        match self.poor_since {
+            Some(poor_since)
+                if self.recovered_since.is_none()
+                    && now.saturating_duration_since(poor_since) >= STEP_DOWN_AFTER =>
+            {
+                self.poor_since = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When [`QualityMonitor::should_step_down`] should be asked next, if anything could change
+    /// by then.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        match (self.poor_since, self.recovered_since) {
+            (Some(_), Some(recovered_since)) => Some(recovered_since + RECOVERY_PERIOD),
+            (Some(poor_since), None) => Some(poor_since + STEP_DOWN_AFTER),
+            (None, _) => None,
+        }
+    }
+
+    /// Starts over, e.g. once the preset was picked by hand.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn expire_recovery(&mut self, now: Instant) {
+        if let Some(recovered_since) = self.recovered_since
+            && now.saturating_duration_since(recovered_since) >= RECOVERY_PERIOD
+        {
+            self.poor_since = None;
+            self.recovered_since = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ConnectionQuality::*;
+
+    /// Plays `signals`, given as seconds since the start and the quality reported then, and
+    /// polls the monitor every second like the room would, returning the seconds at which the
+    /// share was lowered.
+    fn step_downs(signals: &[(u64, ConnectionQuality)], until: u64) -> Vec<u64> {
+        let start = Instant::now();
+        let mut monitor = QualityMonitor::default();
+        let mut step_downs = Vec::new();
+        for second in 0..=until {
+            let now = start + Duration::from_secs(second);
+            for (_, quality) in signals.iter().filter(|(at, _)| *at == second) {
+                monitor.quality_changed(*quality, now);
+            }
+            if monitor.should_step_down(now) {
+                step_downs.push(second);
+            }
+        }
+        step_downs
+    }
+
+    #[test]
+    fn test_sustained_poor_quality_steps_down() {
+        assert_eq!(step_downs(&[(0, Good), (3, Poor)], 12), []);
+        assert_eq!(step_downs(&[(0, Good), (3, Poor)], 13), [13]);
+        // It keeps stepping down while the connection stays poor, one notch per period.
+        assert_eq!(step_downs(&[(3, Poor)], 35), [13, 23, 33]);
+        assert_eq!(step_downs(&[(3, Lost)], 13), [13]);
+    }
+
+    #[test]
+    fn test_brief_poor_quality_is_ignored() {
+        assert_eq!(
+            step_downs(&[(0, Poor), (4, Excellent), (20, Poor), (25, Good)], 60),
+            []
+        );
+    }
+
+    #[test]
+    fn test_flapping_quality_still_steps_down() {
+        // Recoveries shorter than the recovery period don't end the poor stretch, but the share
+        // isn't lowered while the connection is good.
+        let flapping = [(0, Poor), (4, Good), (6, Poor), (9, Good), (11, Poor)];
+        assert_eq!(step_downs(&flapping, 30), [11, 21]);
+        // A long enough recovery starts the count over.
+        assert_eq!(step_downs(&[(0, Poor), (4, Good), (11, Poor)], 30), [21]);
+    }
+
+    #[test]
+    fn test_next_deadline() {
+        let start = Instant::now();
+        let mut monitor = QualityMonitor::default();
+        assert_eq!(monitor.next_deadline(), None);
+
+        monitor.quality_changed(Good, start);
+        assert_eq!(monitor.next_deadline(), None);
+
+        monitor.quality_changed(Poor, start + Duration::from_secs(1));
+        assert_eq!(
+            monitor.next_deadline(),
+            Some(start + Duration::from_secs(1) + STEP_DOWN_AFTER)
+        );
+
+        monitor.quality_changed(Good, start + Duration::from_secs(2));
+        assert_eq!(
+            monitor.next_deadline(),
+            Some(start + Duration::from_secs(2) + RECOVERY_PERIOD)
+        );
+        assert!(!monitor.should_step_down(start + Duration::from_secs(2) + RECOVERY_PERIOD));
+        assert_eq!(monitor.next_deadline(), None);
+
+        monitor.quality_changed(Poor, start + Duration::from_secs(20));
+        monitor.reset();
+        assert_eq!(monitor.next_deadline(), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Finds files that likely hold secrets in a project that's about to be shared, and remembers
+//! what the user chose to do about them so that sharing the project again doesn't ask again.
+
+use anyhow::Result;
+use collections::{HashMap, HashSet};
+use db::kvp::KEY_VALUE_STORE;
+use futures::future;
+use gpui::{App, Entity, Task};
+use project::{Project, ProjectPath, Worktree};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{collections::BTreeSet, path::Path, sync::Arc};
+use util::{ResultExt as _, paths::PathMatcher, rel_path::RelPath};
+
+use crate::call_settings::CallSettings;
+
+const REVIEW_KEY_PREFIX: &str = "shared_project_secret_files";
+
+/// What the user chose for the secret files in a worktree, by their path in it.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct SecretFilesReview {
+    /// Files that are shared with guests like any other.
+    shared: BTreeSet<String>,
+    /// Files that guests can't open.
+    excluded: BTreeSet<String>,
+}
+
+impl SecretFilesReview {
+    fn contains(&self, path: &RelPath) -> bool {
+        self.shared.contains(path.as_unix_str()) || self.excluded.contains(path.as_unix_str())
+    }
+
+    fn record(&mut self, path: &RelPath, exclude: bool) {
+        let path = path.as_unix_str().to_string();
+        if exclude {
+            self.shared.remove(&path);
+            self.excluded.insert(path);
+        } else {
+            self.excluded.remove(&path);
+            self.shared.insert(path);
+        }
+    }
+
+    fn excluded_paths(&self) -> HashSet<Arc<RelPath>> {
+        self.excluded
+            .iter()
+            .filter_map(|path| RelPath::unix(path).log_err())
+            .map(RelPath::into_arc)
+            .collect()
+    }
+}
+
+fn review_key(worktree_abs_path: &Path) -> String {
+    format!("{REVIEW_KEY_PREFIX}:{}", worktree_abs_path.display())
+}
+
+fn read_review(worktree_abs_path: &Path) -> SecretFilesReview {
+    KEY_VALUE_STORE
+        .read_kvp(&review_key(worktree_abs_path))
+        .log_err()
+        .flatten()
+        .and_then(|review| serde_json::from_str(&review).log_err())
+        .unwrap_or_default()
+}
+
+/// Files in `worktree` that guests could open and whose name matches `secret_files`. Files that
+/// are already private aren't included, since they're never shared.
+fn secret_files_in_worktree(worktree: &Worktree, secret_files: &PathMatcher) -> Vec<Arc<RelPath>> {
+    if secret_files.sources().next().is_none() {
+        return Vec::new();
+    }
+    worktree
+        .files(true, 0)
+        .filter(|entry| !entry.is_private && is_secret_file(&entry.path, secret_files))
+        .map(|entry| entry.path.clone())
+        .collect()
+}
+
+/// Secret files are matched by name, wherever they are in the worktree.
+fn is_secret_file(path: &RelPath, secret_files: &PathMatcher) -> bool {
+    path.file_name()
+        .and_then(|file_name| RelPath::unix(file_name).ok())
+        .is_some_and(|file_name| secret_files.is_match(file_name))
+}
+
+/// Secret files in the local worktrees of `project` that the user hasn't chosen what to do with.
+pub fn unreviewed_secret_files(project: &Entity<Project>, cx: &App) -> Vec<ProjectPath> {
+    let secret_files = &CallSettings::get_global(cx).secret_files;
+    project
+        .read(cx)
+        .visible_worktrees(cx)
+        .filter_map(|worktree| {
+            let worktree = worktree.read(cx);
+            if !worktree.is_local() {
+                return None;
+            }
+            let review = read_review(&worktree.abs_path());
+            Some(
+                secret_files_in_worktree(worktree, secret_files)
+                    .into_iter()
+                    .filter(move |path| !review.contains(path))
+                    .map(|path| ProjectPath {
+                        worktree_id: worktree.id(),
+                        path,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Keeps guests from opening the files that were excluded from sharing in earlier reviews.
+pub fn apply_secret_files_reviews(project: &Entity<Project>, cx: &mut App) {
+    for worktree in project.read(cx).visible_worktrees(cx).collect::<Vec<_>>() {
+        worktree.update(cx, |worktree, cx| {
+            if let Some(worktree) = worktree.as_local_mut() {
+                let excluded_paths = read_review(&worktree.abs_path()).excluded_paths();
+                worktree.set_unshared_paths(excluded_paths, cx);
+            }
+        });
+    }
+}
+
+/// Remembers that `files` should be shared, or excluded from sharing, and applies that to the
+/// project's worktrees.
+pub fn review_secret_files(
+    project: &Entity<Project>,
+    files: &[ProjectPath],
+    exclude: bool,
+    cx: &mut App,
+) -> Task<Result<()>> {
+    let mut files_by_worktree = HashMap::<_, Vec<_>>::default();
+    for file in files {
+        files_by_worktree
+            .entry(file.worktree_id)
+            .or_default()
+            .push(file.path.clone());
+    }
+
+    let mut writes = Vec::new();
+    for (worktree_id, paths) in files_by_worktree {
+        let Some(worktree) = project.read(cx).worktree_for_id(worktree_id, cx) else {
+            continue;
+        };
+        let Some(abs_path) = worktree
+            .read(cx)
+            .as_local()
+            .map(|worktree| worktree.abs_path().clone())
+        else {
+            continue;
+        };
+        let mut review = read_review(&abs_path);
+        for path in &paths {
+            review.record(path, exclude);
+        }
+        let excluded_paths = review.excluded_paths();
+        worktree.update(cx, |worktree, cx| {
+            if let Some(worktree) = worktree.as_local_mut() {
+                worktree.set_unshared_paths(excluded_paths, cx);
+            }
+        });
+        let key = review_key(&abs_path);
+        writes.push(async move {
+            KEY_VALUE_STORE
+                .write_kvp(key, serde_json::to_string(&review)?)
+                .await
+        });
+    }
+
+    cx.background_spawn(async move {
+        future::try_join_all(writes).await?;
+        Ok(())
+    })
+}
+
+/// The files for the share prompt, one per line, including their worktree's name.
+pub fn describe_secret_files(project: &Entity<Project>, files: &[ProjectPath], cx: &App) -> String {
+    let project = project.read(cx);
+    let path_style = project.path_style(cx);
+    files
+        .iter()
+        .filter_map(|file| {
+            let worktree = project.worktree_for_id(file.worktree_id, cx)?;
+            Some(
+                worktree
+                    .read(cx)
+                    .root_name()
+                    .join(&file.path)
+                    .display(path_style)
+                    .to_string(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs::FakeFs;
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use util::{path, paths::PathStyle, rel_path::rel_path};
+
+    #[test]
+    fn test_is_secret_file() {
+        let secret_files = PathMatcher::new(
+            [".env*", "id_rsa", "*.pem", "credentials.json"],
+            PathStyle::Posix,
+        )
+        .unwrap();
+        for path in [
+            ".env",
+            ".env.local",
+            "server/.env.production",
+            "id_rsa",
+            ".ssh/id_rsa",
+            "certs/server.pem",
+            "credentials.json",
+            "config/gcp/credentials.json",
+        ] {
+            assert!(is_secret_file(rel_path(path), &secret_files), "{path}");
+        }
+        for path in [
+            "environment.rs",
+            "docs/.env/README.md",
+            "id_rsa.pub",
+            "pem/README.md",
+            "credentials.json.example",
+            "src/credentials.rs",
+        ] {
+            assert!(!is_secret_file(rel_path(path), &secret_files), "{path}");
+        }
+    }
+
+    #[gpui::test]
+    async fn test_unreviewed_secret_files(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/detection"),
+            json!({
+                // Private by default, so it's never shared.
+                ".env": "",
+                "credentials.json": "",
+                "README.md": "",
+                ".ssh": {
+                    "id_rsa": "",
+                    "id_rsa.pub": "",
+                },
+            }),
+        )
+        .await;
+        let project = Project::test(fs, [path!("/detection").as_ref()], cx).await;
+
+        assert_eq!(
+            cx.read(|cx| describe_files(&project, cx)),
+            ["detection/.ssh/id_rsa", "detection/credentials.json"]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_secret_files_reviews_are_remembered(cx: &mut TestAppContext) {
+        init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/remembered"),
+            json!({ "credentials.json": "", "id_rsa": "", "deploy": { "id_rsa": "" } }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/remembered").as_ref()], cx).await;
+        let worktree_id =
+            cx.read(|cx| project.read(cx).worktrees(cx).next().unwrap().read(cx).id());
+        let project_path = |path: &str| ProjectPath {
+            worktree_id,
+            path: rel_path(path).into(),
+        };
+
+        cx.update(|cx| review_secret_files(&project, &[project_path("id_rsa")], true, cx))
+            .await
+            .unwrap();
+        cx.update(|cx| {
+            review_secret_files(&project, &[project_path("credentials.json")], false, cx)
+        })
+        .await
+        .unwrap();
+        cx.run_until_parked();
+        assert_eq!(
+            cx.read(|cx| describe_files(&project, cx)),
+            ["remembered/deploy/id_rsa"]
+        );
+        assert_eq!(private_files(&project, cx), ["id_rsa"]);
+
+        // Opening the project again doesn't ask about the same files, and keeps the excluded
+        // ones from guests once it's shared.
+        let project = Project::test(fs, [path!("/remembered").as_ref()], cx).await;
+        assert_eq!(
+            cx.read(|cx| describe_files(&project, cx)),
+            ["remembered/deploy/id_rsa"]
+        );
+        assert_eq!(private_files(&project, cx), Vec::<String>::new());
+        cx.update(|cx| apply_secret_files_reviews(&project, cx));
+        cx.run_until_parked();
+        assert_eq!(private_files(&project, cx), ["id_rsa"]);
+    }
+
+    fn describe_files(project: &Entity<Project>, cx: &App) -> Vec<String> {
+        let files = unreviewed_secret_files(project, cx);
+        describe_secret_files(project, &files, cx)
+            .lines()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn private_files(project: &Entity<Project>, cx: &TestAppContext) -> Vec<String> {
+        cx.read(|cx| {
+            let worktree = project.read(cx).worktrees(cx).next().unwrap();
+            worktree
+                .read(cx)
+                .files(true, 0)
+                .filter(|entry| entry.is_private)
+                .map(|entry| entry.path.as_unix_str().to_string())
+                .collect()
+        })
+    }
+
+    fn init_test(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+        });
+    }
+}
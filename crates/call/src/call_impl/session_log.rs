@@ -0,0 +1,330 @@
+//! A record of who was in a call and what they shared, kept so that a summary of the call can
+//! be added to the channel's notes when it ends.
+
+use crate::room::RECONNECT_TIMEOUT;
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write as _};
+use std::ops::Range;
+
+/// When a participant was in the call. `left_at` is `None` while they still are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attendance {
+    pub joined_at: DateTime<Utc>,
+    pub left_at: Option<DateTime<Utc>>,
+}
+
+/// Who was in a call and when, as seen by the local participant, keyed by user id.
+#[derive(Clone, Debug, Default)]
+pub struct SessionLog {
+    participants: BTreeMap<u64, ParticipantLog>,
+    /// Projects shared during the call, by their remote id, in the order they were shared.
+    shared_projects: Vec<(u64, SharedProject)>,
+    screen_sharers: BTreeSet<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct ParticipantLog {
+    login: String,
+    attendances: Vec<Attendance>,
+}
+
+impl SessionLog {
+    /// Records that a participant is in the call. Participants who come back within
+    /// [`RECONNECT_TIMEOUT`] of leaving are treated as never having left, since that's usually
+    /// their connection dropping rather than them hanging up.
+    pub fn joined(&mut self, user_id: u64, login: &str, at: DateTime<Utc>) {
+        let participant = self
+            .participants
+            .entry(user_id)
+            .or_insert_with(|| ParticipantLog {
+                login: login.to_string(),
+                attendances: Vec::new(),
+            });
+        match participant.attendances.last_mut() {
+            Some(last)
+                if last.left_at.is_none_or(|left_at| {
+                    (at - left_at)
+                        .to_std()
+                        .is_ok_and(|gap| gap <= RECONNECT_TIMEOUT)
+                }) =>
+            {
+                last.left_at = None;
+            }
+            _ => participant.attendances.push(Attendance {
+                joined_at: at,
+                left_at: None,
+            }),
+        }
+    }
+
+    pub fn left(&mut self, user_id: u64, at: DateTime<Utc>) {
+        if let Some(attendance) = self
+            .participants
+            .get_mut(&user_id)
+            .and_then(|participant| participant.attendances.last_mut())
+            && attendance.left_at.is_none()
+        {
+            attendance.left_at = Some(at);
+        }
+    }
+
+    pub fn shared_project(
+        &mut self,
+        project_id: u64,
+        owner_id: u64,
+        worktree_root_names: Vec<String>,
+    ) {
+        if self.shared_projects.iter().any(|(id, _)| *id == project_id) {
+            return;
+        }
+        let owner = self
+            .participants
+            .get(&owner_id)
+            .map_or_else(String::new, |participant| participant.login.clone());
+        self.shared_projects.push((
+            project_id,
+            SharedProject {
+                owner,
+                worktree_root_names,
+            },
+        ));
+    }
+
+    pub fn shared_screen(&mut self, user_id: u64) {
+        self.screen_sharers.insert(user_id);
+    }
+
+    /// When the given participant was in the call.
+    pub fn attendances(&self, user_id: u64) -> &[Attendance] {
+        self.participants
+            .get(&user_id)
+            .map_or(&[], |participant| &participant.attendances)
+    }
+
+    /// Summarizes the call, as if everyone still in it left at `ended_at`. Returns `None` when
+    /// nobody was ever in the call.
+    pub fn summary(&self, ended_at: DateTime<Utc>) -> Option<CallSummary> {
+        let mut participants = self
+            .participants
+            .values()
+            .filter(|participant| !participant.attendances.is_empty())
+            .map(|participant| ParticipantSummary {
+                login: participant.login.clone(),
+                attendances: participant
+                    .attendances
+                    .iter()
+                    .map(|attendance| attendance.joined_at..attendance.left_at.unwrap_or(ended_at))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        participants.sort_by_key(|participant| participant.attendances[0].start);
+        let started_at = participants.first()?.attendances[0].start;
+        Some(CallSummary {
+            started_at,
+            ended_at,
+            participants,
+            shared_projects: self
+                .shared_projects
+                .iter()
+                .map(|(_, project)| project.clone())
+                .collect(),
+            screen_sharers: self
+                .screen_sharers
+                .iter()
+                .filter_map(|user_id| Some(self.participants.get(user_id)?.login.clone()))
+                .collect(),
+        })
+    }
+}
+
+/// What happened in a call, for the channel's notes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallSummary {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Everyone who was in the call, including the local participant, by when they joined.
+    pub participants: Vec<ParticipantSummary>,
+    pub shared_projects: Vec<SharedProject>,
+    /// The logins of the participants who shared their screen.
+    pub screen_sharers: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParticipantSummary {
+    pub login: String,
+    pub attendances: Vec<Range<DateTime<Utc>>>,
+}
+
+impl ParticipantSummary {
+    /// How long the participant was in the call in total.
+    pub fn duration(&self) -> TimeDelta {
+        self.attendances
+            .iter()
+            .map(|attendance| attendance.end - attendance.start)
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedProject {
+    pub owner: String,
+    pub worktree_root_names: Vec<String>,
+}
+
+impl CallSummary {
+    /// Formats the summary as a Markdown section headed by the call's date, with times shown
+    /// in `timezone`.
+    pub fn to_markdown<Tz: TimeZone>(&self, timezone: &Tz) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let time = |time: &DateTime<Utc>| time.with_timezone(timezone).format("%H:%M");
+        let mut markdown = format!(
+            "## Call on {}\n\n- Time: {}–{} ({})\n- Participants:\n",
+            self.started_at.with_timezone(timezone).format("%Y-%m-%d"),
+            time(&self.started_at),
+            time(&self.ended_at),
+            format_duration(self.ended_at - self.started_at),
+        );
+        for participant in &self.participants {
+            let attendances = participant
+                .attendances
+                .iter()
+                .map(|attendance| format!("{}–{}", time(&attendance.start), time(&attendance.end)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                markdown,
+                "  - {}: {attendances} ({})",
+                participant.login,
+                format_duration(participant.duration())
+            )
+            .ok();
+        }
+        if !self.shared_projects.is_empty() {
+            markdown.push_str("- Shared projects:\n");
+            for project in &self.shared_projects {
+                writeln!(
+                    markdown,
+                    "  - {} by {}",
+                    project.worktree_root_names.join(", "),
+                    project.owner
+                )
+                .ok();
+            }
+        }
+        if self.screen_sharers.is_empty() {
+            markdown.push_str("- No screens were shared\n");
+        } else {
+            writeln!(
+                markdown,
+                "- Screens shared by {}",
+                self.screen_sharers.join(", ")
+            )
+            .ok();
+        }
+        markdown
+    }
+}
+
+fn format_duration(duration: TimeDelta) -> String {
+    let minutes = (duration.num_seconds() + 30) / 60;
+    if minutes < 60 {
+        format!("{minutes}m")
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + TimeDelta::minutes(minutes)
+    }
+
+    #[test]
+    fn test_leaving_and_rejoining() {
+        let mut log = SessionLog::default();
+        log.joined(1, "alice", at(0));
+        log.joined(2, "bob", at(1));
+
+        // Room updates keep listing everyone who's still in the call.
+        log.joined(1, "alice", at(2));
+        log.joined(2, "bob", at(2));
+
+        // Bob leaves and comes back later, which counts as two separate attendances.
+        log.left(2, at(10));
+        log.joined(2, "bob", at(15));
+        assert_eq!(
+            log.attendances(2),
+            [
+                Attendance {
+                    joined_at: at(1),
+                    left_at: Some(at(10)),
+                },
+                Attendance {
+                    joined_at: at(15),
+                    left_at: None,
+                },
+            ]
+        );
+
+        // Alice's connection drops briefly, which doesn't count as leaving.
+        log.left(1, at(20));
+        log.joined(1, "alice", at(20) + TimeDelta::seconds(5));
+        assert_eq!(
+            log.attendances(1),
+            [Attendance {
+                joined_at: at(0),
+                left_at: None,
+            }]
+        );
+
+        log.left(2, at(30));
+        log.left(2, at(31));
+        let summary = log.summary(at(40)).unwrap();
+        assert_eq!(summary.started_at, at(0));
+        assert_eq!(
+            summary
+                .participants
+                .iter()
+                .map(|participant| (participant.login.as_str(), participant.duration()))
+                .collect::<Vec<_>>(),
+            [
+                ("alice", TimeDelta::minutes(40)),
+                ("bob", TimeDelta::minutes(24)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary_markdown() {
+        let mut log = SessionLog::default();
+        assert_eq!(log.summary(at(0)), None);
+
+        log.joined(1, "alice", at(0));
+        log.joined(2, "bob", at(5));
+        log.shared_project(7, 2, vec!["zed".to_string(), "docs".to_string()]);
+        log.shared_project(7, 2, vec!["zed".to_string(), "docs".to_string()]);
+        log.shared_screen(1);
+        log.left(2, at(20));
+        log.joined(2, "bob", at(25));
+
+        let summary = log.summary(at(95)).unwrap();
+        assert_eq!(
+            summary.to_markdown(&Utc),
+            "## Call on 2023-11-14\n\
+            \n\
+            - Time: 22:13–23:48 (1h 35m)\n\
+            - Participants:\n  \
+            - alice: 22:13–23:48 (1h 35m)\n  \
+            - bob: 22:18–22:33, 22:38–23:48 (1h 25m)\n\
+            - Shared projects:\n  \
+            - zed, docs by bob\n\
+            - Screens shared by alice\n"
+        );
+    }
+}
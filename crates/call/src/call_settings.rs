@@ -1,17 +1,279 @@
-use settings::{RegisterSetting, Settings};
+use anyhow::{Result, anyhow};
+use audio::{Ringtone, Sound};
+use client::ChannelId;
+use collections::HashMap;
+use livekit_client::AudioProcessing;
+use settings::{ChannelCallSettingsContent, IncomingCallSound, RegisterSetting, Settings};
+use std::{path::PathBuf, time::Duration};
+use util::paths::{PathMatcher, PathStyle, home_dir};
 
 #[derive(Debug, RegisterSetting)]
 pub struct CallSettings {
     pub mute_on_join: bool,
+    /// Join muted when more than this many others are already in the call. `None` when disabled.
+    pub auto_mute_on_join_threshold: Option<usize>,
     pub share_on_join: bool,
+    /// Per-channel overrides of `mute_on_join` and `share_on_join`, and whether to export the
+    /// channel's call summaries.
+    pub channels: HashMap<ChannelId, ChannelCallSettingsContent>,
+    pub incoming_call_sound: IncomingCallSound,
+    /// Only set when the configured path is usable.
+    pub custom_incoming_call_sound_path: Option<PathBuf>,
+    pub incoming_call_volume: f32,
+    pub call_notification_volume: f32,
+    /// How long a grant to control the shared screen lasts.
+    pub remote_control_timeout: Duration,
+    /// Names of files to ask about before sharing a project that contains them.
+    pub secret_files: PathMatcher,
+    /// Whether others in the call can see which file, and which line of it, you're on.
+    pub share_active_location: bool,
+    /// How the microphone is processed when joining a call, until it's changed for the call.
+    pub noise_suppression: bool,
+    pub echo_cancellation: bool,
+    pub auto_gain: bool,
+}
+
+/// How to set up a call when joining it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JoinPreferences {
+    pub muted: bool,
+    pub deafened: bool,
+    /// Whether to share the active project when nobody else is in the call yet.
+    pub share_project: bool,
+    /// Join muted when more than this many others are already in the call. `None` when the
+    /// channel has its own `mute_on_join` preference, which takes precedence.
+    pub auto_mute_threshold: Option<usize>,
+}
+
+impl JoinPreferences {
+    /// Whether joining a call that already has `remote_participant_count` others in it should
+    /// mute the microphone, even though these preferences don't.
+    pub fn auto_mutes(&self, remote_participant_count: usize) -> bool {
+        !self.muted
+            && self
+                .auto_mute_threshold
+                .is_some_and(|threshold| remote_participant_count > threshold)
+    }
+}
+
+impl CallSettings {
+    /// The preferences for joining the given channel's call, or a direct call when `channel_id`
+    /// is `None`. Channels without their own preferences use the global settings.
+    pub fn join_preferences(&self, channel_id: Option<ChannelId>) -> JoinPreferences {
+        let channel = channel_id.and_then(|channel_id| self.channels.get(&channel_id));
+        let channel = channel.cloned().unwrap_or_default();
+        JoinPreferences {
+            muted: channel.mute_on_join.unwrap_or(self.mute_on_join),
+            deafened: channel.deafen_on_join.unwrap_or(false),
+            share_project: channel.share_on_join.unwrap_or(self.share_on_join),
+            auto_mute_threshold: self
+                .auto_mute_on_join_threshold
+                .filter(|_| channel.mute_on_join.is_none()),
+        }
+    }
+
+    /// Whether a summary of the given channel's call should be added to its notes when the last
+    /// participant leaves.
+    pub fn exports_summary(&self, channel_id: ChannelId) -> bool {
+        self.channels
+            .get(&channel_id)
+            .and_then(|channel| channel.export_summary_to_notes)
+            .unwrap_or(false)
+    }
+
+    /// Whether calls start out with `processing` turned on.
+    pub fn enables_audio_processing(&self, processing: AudioProcessing) -> bool {
+        match processing {
+            AudioProcessing::NoiseSuppression => self.noise_suppression,
+            AudioProcessing::EchoCancellation => self.echo_cancellation,
+            AudioProcessing::AutoGain => self.auto_gain,
+        }
+    }
+
+    /// The ringtone to play for incoming calls, or `None` if they should be silent.
+    pub fn ringtone(&self) -> Option<Ringtone> {
+        let default = Ringtone::Sound(Sound::IncomingCallClassic);
+        match self.incoming_call_sound {
+            IncomingCallSound::Classic => Some(default),
+            IncomingCallSound::Chime => Some(Ringtone::Sound(Sound::IncomingCallChime)),
+            IncomingCallSound::Custom => Some(self.custom_incoming_call_sound_path.clone().map_or(
+                default,
+                |path| Ringtone::File {
+                    path,
+                    fallback: Sound::IncomingCallClassic,
+                },
+            )),
+            IncomingCallSound::Silent => None,
+        }
+    }
 }
 
 impl Settings for CallSettings {
     fn from_settings(content: &settings::SettingsContent) -> Self {
         let call = content.calls.clone().unwrap();
+        let incoming_call_sound = call.incoming_call_sound.unwrap();
+        let custom_incoming_call_sound_path = call
+            .custom_incoming_call_sound_path
+            .filter(|_| incoming_call_sound == IncomingCallSound::Custom)
+            .and_then(|path| {
+                parse_sound_path(&path)
+                    .inspect_err(|error| {
+                        log::warn!("Ignoring `custom_incoming_call_sound_path`: {error}")
+                    })
+                    .ok()
+            });
         CallSettings {
             mute_on_join: call.mute_on_join.unwrap(),
+            auto_mute_on_join_threshold: call
+                .auto_mute_on_join_threshold
+                .filter(|threshold| *threshold > 0),
             share_on_join: call.share_on_join.unwrap(),
+            channels: call
+                .channels
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(channel_id, settings)| (ChannelId(channel_id), settings))
+                .collect(),
+            incoming_call_sound,
+            custom_incoming_call_sound_path,
+            incoming_call_volume: call.incoming_call_volume.unwrap().clamp(0., 1.),
+            call_notification_volume: call.call_notification_volume.unwrap().clamp(0., 1.),
+            remote_control_timeout: Duration::from_secs(
+                call.remote_control_timeout_minutes.unwrap().max(1) * 60,
+            ),
+            secret_files: PathMatcher::new(call.secret_files.unwrap_or_default(), PathStyle::Posix)
+                .inspect_err(|error| log::warn!("Ignoring invalid `secret_files`: {error}"))
+                .unwrap_or_default(),
+            share_active_location: call.share_active_location.unwrap(),
+            noise_suppression: call.noise_suppression.unwrap(),
+            echo_cancellation: call.echo_cancellation.unwrap(),
+            auto_gain: call.auto_gain.unwrap(),
+        }
+    }
+}
+
+fn parse_sound_path(path: &str) -> Result<PathBuf> {
+    let path = path.trim();
+    let path = if let Some(relative_path) = path.strip_prefix("~/") {
+        home_dir().join(relative_path)
+    } else {
+        PathBuf::from(path)
+    };
+    if !path.is_absolute() {
+        return Err(anyhow!("{path:?} is not an absolute path"));
+    }
+    let is_wav = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+    if !is_wav {
+        return Err(anyhow!("{path:?} is not a WAV file"));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_preferences_fall_back_to_global_settings() {
+        let settings = CallSettings {
+            mute_on_join: false,
+            auto_mute_on_join_threshold: Some(5),
+            share_on_join: true,
+            channels: HashMap::from_iter([
+                (
+                    ChannelId(1),
+                    ChannelCallSettingsContent {
+                        mute_on_join: Some(true),
+                        deafen_on_join: Some(true),
+                        share_on_join: Some(false),
+                        export_summary_to_notes: Some(true),
+                    },
+                ),
+                (
+                    ChannelId(2),
+                    ChannelCallSettingsContent {
+                        mute_on_join: Some(true),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            incoming_call_sound: IncomingCallSound::Classic,
+            custom_incoming_call_sound_path: None,
+            incoming_call_volume: 1.,
+            call_notification_volume: 1.,
+            remote_control_timeout: Duration::from_secs(15 * 60),
+            secret_files: PathMatcher::default(),
+            share_active_location: true,
+            noise_suppression: true,
+            echo_cancellation: true,
+            auto_gain: true,
+        };
+
+        assert_eq!(
+            settings.join_preferences(Some(ChannelId(1))),
+            JoinPreferences {
+                muted: true,
+                deafened: true,
+                share_project: false,
+                auto_mute_threshold: None,
+            }
+        );
+        assert_eq!(
+            settings.join_preferences(Some(ChannelId(2))),
+            JoinPreferences {
+                muted: true,
+                deafened: false,
+                share_project: true,
+                auto_mute_threshold: None,
+            }
+        );
+        let defaults = JoinPreferences {
+            muted: false,
+            deafened: false,
+            share_project: true,
+            auto_mute_threshold: Some(5),
+        };
+        assert_eq!(settings.join_preferences(Some(ChannelId(3))), defaults);
+        assert_eq!(settings.join_preferences(None), defaults);
+
+        assert!(settings.exports_summary(ChannelId(1)));
+        assert!(!settings.exports_summary(ChannelId(2)));
+        assert!(!settings.exports_summary(ChannelId(3)));
+    }
+
+    #[test]
+    fn test_auto_mute_on_join() {
+        let preferences = JoinPreferences {
+            auto_mute_threshold: Some(5),
+            ..Default::default()
+        };
+        assert!(!preferences.auto_mutes(0));
+        assert!(!preferences.auto_mutes(5));
+        assert!(preferences.auto_mutes(6));
+
+        // Preferences that already mute don't need to, and disabling the threshold never does.
+        let muted = JoinPreferences {
+            muted: true,
+            ..preferences
+        };
+        assert!(!muted.auto_mutes(6));
+        assert!(!JoinPreferences::default().auto_mutes(100));
+    }
+
+    #[test]
+    fn test_parse_sound_path() {
+        assert_eq!(
+            parse_sound_path("~/sounds/ring.WAV").unwrap(),
+            home_dir().join("sounds/ring.WAV")
+        );
+        assert!(parse_sound_path("sounds/ring.wav").is_err());
+        assert!(parse_sound_path("~/sounds/ring.mp3").is_err());
+        if cfg!(windows) {
+            assert!(parse_sound_path("C:\\sounds\\ring.wav").is_ok());
+        } else {
+            assert!(parse_sound_path("/sounds/ring.wav").is_ok());
         }
     }
 }
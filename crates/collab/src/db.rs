@@ -402,6 +402,14 @@ pub struct InviteMemberResult {
     pub notifications: NotificationBatch,
 }
 
+#[derive(Debug)]
+pub struct RespondToJoinChannelRequestResult {
+    /// The invitation sent to the requester, if the request was accepted.
+    pub invite: Option<InviteMemberResult>,
+    /// The channel admins in the call, who were all asked.
+    pub admin_ids: Vec<UserId>,
+}
+
 #[derive(Debug)]
 pub struct RespondToChannelInvite {
     pub membership_update: Option<MembershipUpdated>,
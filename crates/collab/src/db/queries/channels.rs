@@ -287,41 +287,177 @@ impl Database {
                 Err(ErrorCode::NotARootChannel.anyhow())?
             }
 
-            channel_member::ActiveModel {
-                id: ActiveValue::NotSet,
-                channel_id: ActiveValue::Set(channel_id),
-                user_id: ActiveValue::Set(invitee_id),
-                accepted: ActiveValue::Set(false),
-                role: ActiveValue::Set(role),
+            self.invite_channel_member_internal(channel, invitee_id, inviter_id, role, &tx)
+                .await
+        })
+        .await
+    }
+
+    async fn invite_channel_member_internal(
+        &self,
+        channel: channel::Model,
+        invitee_id: UserId,
+        inviter_id: UserId,
+        role: ChannelRole,
+        tx: &DatabaseTransaction,
+    ) -> Result<InviteMemberResult> {
+        channel_member::ActiveModel {
+            id: ActiveValue::NotSet,
+            channel_id: ActiveValue::Set(channel.id),
+            user_id: ActiveValue::Set(invitee_id),
+            accepted: ActiveValue::Set(false),
+            role: ActiveValue::Set(role),
+        }
+        .insert(tx)
+        .await?;
+
+        let channel = Channel::from_model(channel);
+
+        let notifications = self
+            .create_notification(
+                invitee_id,
+                rpc::Notification::ChannelInvitation {
+                    channel_id: channel.id.to_proto(),
+                    channel_name: channel.name.clone(),
+                    inviter_id: inviter_id.to_proto(),
+                },
+                true,
+                tx,
+            )
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(InviteMemberResult {
+            channel,
+            notifications,
+        })
+    }
+
+    /// Returns the admins in a channel's call, who can let in a user that isn't a member of the
+    /// channel.
+    pub async fn request_to_join_channel(
+        &self,
+        channel_id: ChannelId,
+        requester_id: UserId,
+    ) -> Result<Vec<UserId>> {
+        self.transaction(move |tx| async move {
+            let channel = self.get_channel_internal(channel_id, &tx).await?;
+            if let Some(membership) = self
+                .root_channel_membership(&channel, requester_id, &tx)
+                .await?
+            {
+                if membership.role == ChannelRole::Banned {
+                    Err(ErrorCode::Forbidden.anyhow())?
+                }
+                Err(anyhow!(
+                    "user is already a member of the channel or has been invited to it"
+                ))?
+            }
+            if channel.visibility == ChannelVisibility::Public {
+                Err(anyhow!("anyone can join a public channel"))?
             }
-            .insert(&*tx)
-            .await?;
 
-            let channel = Channel::from_model(channel);
-
-            let notifications = self
-                .create_notification(
-                    invitee_id,
-                    rpc::Notification::ChannelInvitation {
-                        channel_id: channel_id.to_proto(),
-                        channel_name: channel.name.clone(),
-                        inviter_id: inviter_id.to_proto(),
-                    },
-                    true,
-                    &tx,
+            let admin_ids = self.channel_call_admin_ids(&channel, &tx).await?;
+            if admin_ids.is_empty() {
+                Err(anyhow!("no channel admins are in the call"))?
+            }
+            Ok(admin_ids)
+        })
+        .await
+    }
+
+    /// Answers a request to join a channel. Accepting it invites the requester to the root
+    /// channel as a member, unless they've been invited or joined in the meantime.
+    pub async fn respond_to_join_channel_request(
+        &self,
+        channel_id: ChannelId,
+        requester_id: UserId,
+        admin_id: UserId,
+        accept: bool,
+    ) -> Result<RespondToJoinChannelRequestResult> {
+        self.transaction(move |tx| async move {
+            let channel = self.get_channel_internal(channel_id, &tx).await?;
+            self.check_user_is_channel_admin(&channel, admin_id, &tx)
+                .await?;
+
+            let invite = if accept
+                && self
+                    .root_channel_membership(&channel, requester_id, &tx)
+                    .await?
+                    .is_none()
+            {
+                let root = self.get_channel_internal(channel.root_id(), &tx).await?;
+                Some(
+                    self.invite_channel_member_internal(
+                        root,
+                        requester_id,
+                        admin_id,
+                        ChannelRole::Member,
+                        &tx,
+                    )
+                    .await?,
                 )
-                .await?
-                .into_iter()
-                .collect();
+            } else {
+                None
+            };
 
-            Ok(InviteMemberResult {
-                channel,
-                notifications,
+            Ok(RespondToJoinChannelRequestResult {
+                invite,
+                admin_ids: self.channel_call_admin_ids(&channel, &tx).await?,
             })
         })
         .await
     }
 
+    /// Returns a user's membership in the root of the given channel, whether or not they've
+    /// accepted it.
+    async fn root_channel_membership(
+        &self,
+        channel: &channel::Model,
+        user_id: UserId,
+        tx: &DatabaseTransaction,
+    ) -> Result<Option<channel_member::Model>> {
+        Ok(channel_member::Entity::find()
+            .filter(channel_member::Column::ChannelId.eq(channel.root_id()))
+            .filter(channel_member::Column::UserId.eq(user_id))
+            .one(tx)
+            .await?)
+    }
+
+    /// Returns the admins of the given channel who are in its call.
+    async fn channel_call_admin_ids(
+        &self,
+        channel: &channel::Model,
+        tx: &DatabaseTransaction,
+    ) -> Result<Vec<UserId>> {
+        let Some(room) = room::Entity::find()
+            .filter(room::Column::ChannelId.eq(channel.id))
+            .one(tx)
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        let participants = room_participant::Entity::find()
+            .filter(room_participant::Column::RoomId.eq(room.id))
+            .filter(room_participant::Column::AnsweringConnectionId.is_not_null())
+            .all(tx)
+            .await?;
+        let mut admin_ids = Vec::new();
+        for participant in participants {
+            if !admin_ids.contains(&participant.user_id)
+                && self
+                    .channel_role_for_user(channel, participant.user_id, tx)
+                    .await?
+                    == Some(ChannelRole::Admin)
+            {
+                admin_ids.push(participant.user_id);
+            }
+        }
+        Ok(admin_ids)
+    }
+
     fn sanitize_channel_name(name: &str) -> Result<&str> {
         let new_name = name.trim().trim_start_matches('#');
         if new_name.is_empty() {
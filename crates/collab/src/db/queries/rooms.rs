@@ -1259,6 +1259,12 @@ impl Database {
         })
     }
 
+    /// Returns the room with the given id, along with its participants.
+    pub async fn room(&self, room_id: RoomId) -> Result<proto::Room> {
+        self.transaction(|tx| async move { self.get_room(room_id, &tx).await })
+            .await
+    }
+
     pub async fn get_room(&self, room_id: RoomId, tx: &DatabaseTransaction) -> Result<proto::Room> {
         let (_, room) = self.get_channel_room(room_id, tx).await?;
         Ok(room)
@@ -6,8 +6,8 @@ use crate::{
     db::{
         self, BufferId, Capability, Channel, ChannelId, ChannelRole, ChannelsForUser, Database,
         InviteMemberResult, MembershipUpdated, NotificationId, ProjectId, RejoinedProject,
-        RemoveChannelMemberResult, RespondToChannelInvite, RoomId, ServerId, SharedThreadId, User,
-        UserId,
+        RemoveChannelMemberResult, RespondToChannelInvite, RespondToJoinChannelRequestResult,
+        RoomId, ServerId, SharedThreadId, User, UserId,
     },
     executor::Executor,
 };
@@ -394,6 +394,8 @@ impl Server {
             .add_request_handler(get_channel_members)
             .add_request_handler(respond_to_channel_invite)
             .add_request_handler(join_channel)
+            .add_request_handler(request_to_join_channel)
+            .add_request_handler(respond_to_join_channel_request)
             .add_request_handler(join_channel_chat)
             .add_message_handler(leave_channel_chat)
             .add_request_handler(send_channel_message)
@@ -639,6 +641,30 @@ impl Server {
         *self.id.lock()
     }
 
+    /// Asks everyone in a room to rejoin it without leaving the call, as when the room is moved
+    /// during a deploy. The room keeps its id and its audio and video, so participants only
+    /// re-establish their state with the server.
+    pub async fn migrate_room(&self, room_id: RoomId) -> Result<()> {
+        let room = self.app_state.db.room(room_id).await?;
+        broadcast(
+            None,
+            room.participants
+                .iter()
+                .filter_map(|participant| Some(participant.peer_id?.into())),
+            |connection_id| {
+                self.peer.send(
+                    connection_id,
+                    proto::RoomMigrated {
+                        room_id: room_id.to_proto(),
+                        new_room_id: room_id.to_proto(),
+                        live_kit_connection_info: None,
+                    },
+                )
+            },
+        );
+        Ok(())
+    }
+
     fn add_handler<F, Fut, M>(&mut self, handler: F) -> &mut Self
     where
         F: 'static + Send + Sync + Fn(TypedEnvelope<M>, MessageContext) -> Fut,
@@ -3324,6 +3350,106 @@ async fn join_channel_internal(
     Ok(())
 }
 
+/// Ask the channel admins in a channel's call to let you join it
+async fn request_to_join_channel(
+    request: proto::RequestToJoinChannel,
+    response: Response<proto::RequestToJoinChannel>,
+    session: MessageContext,
+) -> Result<()> {
+    let channel_id = ChannelId::from_proto(request.channel_id);
+    let admin_ids = session
+        .db()
+        .await
+        .request_to_join_channel(channel_id, session.user_id())
+        .await?;
+
+    let message = proto::JoinChannelRequested {
+        channel_id: channel_id.to_proto(),
+        requester_id: session.user_id().to_proto(),
+    };
+    let connection_pool = session.connection_pool().await;
+    for admin_id in admin_ids {
+        for connection_id in connection_pool.user_connection_ids(admin_id) {
+            session
+                .peer
+                .send(connection_id, message.clone())
+                .trace_err();
+        }
+    }
+
+    response.send(proto::Ack {})?;
+    Ok(())
+}
+
+/// Let in someone who asked to join a channel, or turn them away
+async fn respond_to_join_channel_request(
+    request: proto::RespondToJoinChannelRequest,
+    response: Response<proto::RespondToJoinChannelRequest>,
+    session: MessageContext,
+) -> Result<()> {
+    let channel_id = ChannelId::from_proto(request.channel_id);
+    let requester_id = UserId::from_proto(request.requester_id);
+    let RespondToJoinChannelRequestResult { invite, admin_ids } = session
+        .db()
+        .await
+        .respond_to_join_channel_request(
+            channel_id,
+            requester_id,
+            session.user_id(),
+            request.accept,
+        )
+        .await?;
+
+    let connection_pool = session.connection_pool().await;
+    // The invitation goes out first, so that it's in place when the requester joins.
+    if let Some(InviteMemberResult {
+        channel,
+        notifications,
+    }) = invite
+    {
+        let update = proto::UpdateChannels {
+            channel_invitations: vec![channel.to_proto()],
+            ..Default::default()
+        };
+        for connection_id in connection_pool.user_connection_ids(requester_id) {
+            session.peer.send(connection_id, update.clone())?;
+        }
+        send_notifications(&connection_pool, &session.peer, notifications);
+    }
+
+    for connection_id in connection_pool.user_connection_ids(requester_id) {
+        session
+            .peer
+            .send(
+                connection_id,
+                proto::JoinChannelRequestAnswered {
+                    channel_id: channel_id.to_proto(),
+                    accepted: request.accept,
+                },
+            )
+            .trace_err();
+    }
+
+    // The other admins that were asked no longer need to answer.
+    let canceled = proto::JoinChannelRequestCanceled {
+        channel_id: channel_id.to_proto(),
+        requester_id: requester_id.to_proto(),
+    };
+    for admin_id in admin_ids {
+        for connection_id in connection_pool.user_connection_ids(admin_id) {
+            if connection_id != session.connection_id {
+                session
+                    .peer
+                    .send(connection_id, canceled.clone())
+                    .trace_err();
+            }
+        }
+    }
+
+    response.send(proto::Ack {})?;
+    Ok(())
+}
+
 /// Start editing the channel notes
 async fn join_channel_buffer(
     request: proto::JoinChannelBuffer,
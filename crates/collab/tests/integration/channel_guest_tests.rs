@@ -1,11 +1,13 @@
 use crate::TestServer;
-use call::ActiveCall;
+use call::{ActiveCall, PermissionDenied, room};
 use chrono::Utc;
 use collab::db::ChannelId;
 use editor::Editor;
 use gpui::{BackgroundExecutor, TestAppContext};
 use rpc::proto;
+use std::{cell::RefCell, mem, rc::Rc};
 use util::rel_path::rel_path;
+use workspace::ParticipantCapabilities;
 #[gpui::test]
 async fn test_channel_guests(
     executor: BackgroundExecutor,
@@ -180,6 +182,199 @@ async fn test_channel_guest_promotion(cx_a: &mut TestAppContext, cx_b: &mut Test
     );
 }
 
+#[gpui::test]
+async fn test_channel_guest_capabilities(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+
+    let channel_id = server
+        .make_public_channel("the-channel", &client_a, cx_a)
+        .await;
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    active_call_b
+        .update(cx_b, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+
+    let events_a = capability_events(cx_a);
+    let events_b = capability_events(cx_b);
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let peer_id_b = client_b.peer_id().unwrap();
+
+    // B joined as a guest, and can only follow.
+    room_b.read_with(cx_b, |room, _| {
+        assert_eq!(room.local_capabilities(), ParticipantCapabilities::FOLLOW)
+    });
+    room_a.read_with(cx_a, |room, _| {
+        assert_eq!(
+            room.remote_participants()[&client_b.user_id().unwrap()].capabilities(),
+            ParticipantCapabilities::FOLLOW
+        )
+    });
+    let project_b = client_b.build_test_project(cx_b).await;
+    let error = active_call_b
+        .update(cx_b, |call, cx| call.share_project(project_b.clone(), cx))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error
+            .downcast_ref::<PermissionDenied>()
+            .map(|e| e.capability),
+        Some(ParticipantCapabilities::SHARE_PROJECTS)
+    );
+    let error = room_b
+        .update(cx_b, |room, cx| {
+            room.set_participant_role(client_a.user_id().unwrap(), proto::ChannelRole::Guest, cx)
+        })
+        .await
+        .unwrap_err();
+    assert!(error.is::<PermissionDenied>());
+
+    // B is promoted mid-call, which both sides hear about.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_participant_role(client_b.user_id().unwrap(), proto::ChannelRole::Talker, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    let talker = ParticipantCapabilities::for_role(proto::ChannelRole::Talker);
+    assert_eq!(
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::LocalCapabilitiesChanged {
+            capabilities: talker
+        }]
+    );
+    assert_eq!(
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![room::Event::ParticipantCapabilitiesChanged {
+            participant_id: peer_id_b,
+            capabilities: talker,
+        }]
+    );
+    room_b.read_with(cx_b, |room, _| {
+        assert!(room.can_use_microphone());
+        assert!(!room.can_share_projects());
+    });
+
+    // Setting the same role again changes nothing.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_participant_role(client_b.user_id().unwrap(), proto::ChannelRole::Talker, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert!(events_a.borrow().is_empty());
+    assert!(events_b.borrow().is_empty());
+}
+
+#[gpui::test]
+async fn test_channel_guest_listen_only(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let user_b_id = client_b.user_id().unwrap();
+
+    let channel_id = server
+        .make_public_channel("the-channel", &client_a, cx_a)
+        .await;
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    active_call_b
+        .update(cx_b, |call, cx| {
+            call.join_channel_listen_only(channel_id, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+
+    // The guest's role is what stops them using the microphone, before listening does.
+    let error = room_b
+        .update(cx_b, |room, cx| room.share_microphone(cx))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error
+            .downcast_ref::<PermissionDenied>()
+            .map(|e| e.capability),
+        Some(ParticipantCapabilities::USE_MICROPHONE)
+    );
+    room_a.read_with(cx_a, |room, _| {
+        assert!(room.remote_participants()[&user_b_id].listening)
+    });
+
+    // Being promoted doesn't publish anything while they're still listening.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_participant_role(user_b_id, proto::ChannelRole::Talker, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    room_b.read_with(cx_b, |room, _| {
+        assert!(room.can_use_microphone());
+        assert!(room.is_listen_only());
+        assert!(!room.is_sharing_mic());
+    });
+
+    room_b
+        .update(cx_b, |room, cx| room.start_participating(cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    room_b.read_with(cx_b, |room, _| {
+        assert!(!room.is_listen_only());
+        assert!(room.is_sharing_mic());
+    });
+    room_a.read_with(cx_a, |room, _| {
+        assert!(!room.remote_participants()[&user_b_id].listening)
+    });
+}
+
+fn capability_events(cx: &mut TestAppContext) -> Rc<RefCell<Vec<room::Event>>> {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let active_call = cx.read(ActiveCall::global);
+    cx.update({
+        let events = events.clone();
+        |cx| {
+            cx.subscribe(&active_call, move |_, event, _| {
+                if matches!(
+                    event,
+                    room::Event::LocalCapabilitiesChanged { .. }
+                        | room::Event::ParticipantCapabilitiesChanged { .. }
+                ) {
+                    events.borrow_mut().push(event.clone());
+                }
+            })
+            .detach()
+        }
+    });
+    events
+}
+
 #[gpui::test]
 async fn test_channel_requires_zed_cla(cx_a: &mut TestAppContext, cx_b: &mut TestAppContext) {
     let mut server = TestServer::start(cx_a.executor()).await;
@@ -1,18 +1,20 @@
-use crate::{RoomParticipants, TestServer, room_participants};
-use call::ActiveCall;
+use crate::{RoomParticipants, TestClient, TestServer, room_participants};
+use call::{ActiveCall, CallError, JOIN_REQUEST_TIMEOUT, room};
 use channel::{ChannelMembership, ChannelStore};
 use client::{ChannelId, User};
 use collab::{
     db::{self, UserId},
     rpc::RECONNECT_TIMEOUT,
 };
+use collections::HashMap;
 use futures::future::try_join_all;
-use gpui::{BackgroundExecutor, Entity, SharedString, TestAppContext};
+use gpui::{BackgroundExecutor, Entity, SharedString, TestAppContext, UpdateGlobal};
 use rpc::{
     RECEIVE_TIMEOUT,
     proto::{self, ChannelRole},
 };
-use std::sync::Arc;
+use settings::{ChannelCallSettingsContent, SettingsStore};
+use std::{cell::RefCell, mem, rc::Rc, sync::Arc};
 
 #[gpui::test]
 async fn test_core_channels(
@@ -631,6 +633,276 @@ async fn test_channel_jumping(executor: BackgroundExecutor, cx_a: &mut TestAppCo
     });
 }
 
+#[gpui::test]
+async fn test_channel_call_preferences(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let standup_id = server
+        .make_channel("standup", None, (&client_a, cx_a), &mut [])
+        .await;
+    let all_hands_id = server
+        .make_channel("all-hands", None, (&client_a, cx_a), &mut [])
+        .await;
+
+    // User A joins calls muted, except for the standup, where they only listen.
+    cx_a.update(|cx| {
+        SettingsStore::update_global(cx, |store, cx| {
+            store.update_user_settings(cx, |settings| {
+                let calls = settings.calls.get_or_insert_default();
+                calls.mute_on_join = Some(true);
+                calls.channels = Some(HashMap::from_iter([(
+                    standup_id.0,
+                    ChannelCallSettingsContent {
+                        mute_on_join: Some(false),
+                        deafen_on_join: Some(true),
+                        share_on_join: None,
+                        export_summary_to_notes: None,
+                    },
+                )]));
+            });
+        });
+    });
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let room_state = |cx: &mut TestAppContext| {
+        active_call_a.read_with(cx, |call, cx| {
+            let room = call.room().unwrap().read(cx);
+            (
+                room.muted_by_user(),
+                room.is_deafened(),
+                room.is_sharing_mic(),
+            )
+        })
+    };
+
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(standup_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (false, Some(true), false));
+
+    // Channels without their own preferences use the global settings.
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(all_hands_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (true, Some(false), false));
+
+    // So do direct calls.
+    active_call_a
+        .update(cx_a, |call, cx| call.hang_up(cx))
+        .await
+        .unwrap();
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    active_call_b
+        .update(cx_b, |call, cx| call.accept_incoming(cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (true, Some(false), false));
+    active_call_b.read_with(cx_b, |call, cx| {
+        let room = call.room().unwrap().read(cx);
+        assert!(!room.muted_by_user());
+        assert!(room.is_sharing_mic());
+    });
+}
+
+#[gpui::test]
+async fn test_auto_mute_on_join(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+    cx_d: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    let client_d = server.create_client(cx_d, "user_d").await;
+    let mut members = [
+        (&client_b, &mut *cx_b),
+        (&client_c, &mut *cx_c),
+        (&client_d, &mut *cx_d),
+    ];
+    let all_hands_id = server
+        .make_channel("all-hands", None, (&client_a, cx_a), &mut members)
+        .await;
+    let standup_id = server
+        .make_channel("standup", None, (&client_a, cx_a), &mut members)
+        .await;
+    let design_id = server
+        .make_channel("design", None, (&client_a, cx_a), &mut members)
+        .await;
+
+    // User A joins muted when more than one other person is in the call, except for the
+    // standup, where they always want to talk.
+    cx_a.update(|cx| {
+        SettingsStore::update_global(cx, |store, cx| {
+            store.update_user_settings(cx, |settings| {
+                let calls = settings.calls.get_or_insert_default();
+                calls.auto_mute_on_join_threshold = Some(1);
+                calls.channels = Some(HashMap::from_iter([(
+                    standup_id.0,
+                    ChannelCallSettingsContent {
+                        mute_on_join: Some(false),
+                        ..Default::default()
+                    },
+                )]));
+            });
+        });
+    });
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let muted_on_join_a = Rc::new(RefCell::new(Vec::new()));
+    cx_a.update({
+        let muted_on_join_a = muted_on_join_a.clone();
+        |cx| {
+            cx.subscribe(&active_call_a, move |_, event, _| {
+                if let room::Event::MutedOnJoin { participant_count } = event {
+                    muted_on_join_a.borrow_mut().push(*participant_count);
+                }
+            })
+            .detach()
+        }
+    });
+    let room_state = |cx: &mut TestAppContext| {
+        active_call_a.read_with(cx, |call, cx| {
+            let room = call.room().unwrap().read(cx);
+            (room.muted_by_user(), room.is_sharing_mic())
+        })
+    };
+
+    // Below the threshold, the call is joined with the microphone live.
+    join_channel(all_hands_id, &mut members[..1]).await;
+    executor.run_until_parked();
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(all_hands_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (false, true));
+    assert!(muted_on_join_a.borrow().is_empty());
+
+    // People joining after the microphone was published don't mute it.
+    join_channel(all_hands_id, &mut members[1..]).await;
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (false, true));
+    assert!(muted_on_join_a.borrow().is_empty());
+
+    // Above the threshold, it's muted before anything is published.
+    join_channel(design_id, &mut members[..2]).await;
+    executor.run_until_parked();
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(design_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (true, false));
+    assert_eq!(mem::take(&mut *muted_on_join_a.borrow_mut()), [2]);
+    active_call_a.read_with(cx_a, |call, cx| {
+        assert_eq!(call.room().unwrap().read(cx).auto_muted_on_join(), Some(2));
+    });
+
+    // A channel's own preference wins over the threshold.
+    join_channel(standup_id, &mut members).await;
+    executor.run_until_parked();
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(standup_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(room_state(cx_a), (false, true));
+    assert!(muted_on_join_a.borrow().is_empty());
+}
+
+async fn join_channel(channel_id: ChannelId, clients: &mut [(&TestClient, &mut TestAppContext)]) {
+    for (_, cx) in clients {
+        cx.read(ActiveCall::global)
+            .update(*cx, |call, cx| call.join_channel(channel_id, cx))
+            .await
+            .unwrap();
+    }
+}
+
+#[gpui::test]
+async fn test_listen_only_join(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let channel_id = server
+        .make_channel("standup", None, (&client_a, cx_a), &mut [(&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let user_a_id = client_a.user_id().unwrap();
+
+    active_call_b
+        .update(cx_b, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.join_channel_listen_only(channel_id, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+
+    // Nothing is published while listening, even when trying to unmute.
+    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
+    executor.run_until_parked();
+    room_a.read_with(cx_a, |room, _| {
+        assert!(room.is_listen_only());
+        assert!(!room.is_sharing_mic());
+    });
+    room_a
+        .update(cx_a, |room, cx| room.share_microphone(cx))
+        .await
+        .unwrap_err();
+    room_b.read_with(cx_b, |room, _| {
+        let participant_a = &room.remote_participants()[&user_a_id];
+        assert!(participant_a.listening);
+        assert!(participant_a.audio_tracks.is_empty());
+    });
+
+    // Participating publishes the microphone, following the usual rules for joining.
+    room_a
+        .update(cx_a, |room, cx| room.start_participating(cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    room_a.read_with(cx_a, |room, _| {
+        assert!(!room.is_listen_only());
+        assert!(!room.muted_by_user());
+        assert!(room.is_sharing_mic());
+    });
+    room_b.read_with(cx_b, |room, _| {
+        assert!(!room.remote_participants()[&user_a_id].listening);
+    });
+}
+
 #[gpui::test]
 async fn test_permissions_update_while_invited(
     executor: BackgroundExecutor,
@@ -1205,12 +1477,12 @@ async fn test_guest_access(
     let active_call_b = cx_b.read(ActiveCall::global);
 
     // Non-members should not be allowed to join
-    assert!(
+    assert!(matches!(
         active_call_b
             .update(cx_b, |call, cx| call.join_channel(channel_a, cx))
-            .await
-            .is_err()
-    );
+            .await,
+        Err(CallError::PermissionDenied)
+    ));
 
     // Make channels A and B public
     client_a
@@ -1275,12 +1547,12 @@ async fn test_invite_access(
     let active_call_b = cx_b.read(ActiveCall::global);
 
     // should not be allowed to join
-    assert!(
+    assert!(matches!(
         active_call_b
             .update(cx_b, |call, cx| call.join_channel(channel_b_id, cx))
-            .await
-            .is_err()
-    );
+            .await,
+        Err(CallError::PermissionDenied)
+    ));
 
     client_a
         .channel_store()
@@ -1314,6 +1586,166 @@ async fn test_invite_access(
     })
 }
 
+#[gpui::test]
+async fn test_request_to_join_channel(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    let user_b = client_b.user_id().unwrap();
+
+    // Users A and C are admins of the channel, and user B isn't a member.
+    let channel_id = server
+        .make_channel("zed", None, (&client_a, cx_a), &mut [(&client_c, cx_c)])
+        .await;
+    client_a
+        .channel_store()
+        .update(cx_a, |channel_store, cx| {
+            channel_store.set_member_role(
+                channel_id,
+                client_c.user_id().unwrap(),
+                ChannelRole::Admin,
+                cx,
+            )
+        })
+        .await
+        .unwrap();
+
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let active_call_c = cx_c.read(ActiveCall::global);
+    let answers_b = Rc::new(RefCell::new(Vec::new()));
+    cx_b.update({
+        let answers_b = answers_b.clone();
+        |cx| {
+            cx.subscribe(&active_call_b, move |_, event, _| {
+                if let room::Event::JoinRequestAnswered {
+                    channel_id,
+                    accepted,
+                } = event
+                {
+                    answers_b.borrow_mut().push((*channel_id, *accepted));
+                }
+            })
+            .detach()
+        }
+    });
+    let join_requests = |active_call: &Entity<ActiveCall>, cx: &mut TestAppContext| {
+        active_call.read_with(cx, |call, _| {
+            call.incoming_join_requests()
+                .borrow()
+                .iter()
+                .map(|request| (request.requester.id, request.channel_id))
+                .collect::<Vec<_>>()
+        })
+    };
+    let request_to_join = |cx: &mut TestAppContext| {
+        active_call_b.update(cx, |call, cx| call.request_to_join_channel(channel_id, cx))
+    };
+
+    // There's no one to ask until an admin is in the call.
+    assert!(request_to_join(cx_b).await.is_err());
+    assert!(active_call_b.read_with(cx_b, |call, cx| !call.has_requested_to_join(channel_id, cx)));
+
+    active_call_a
+        .update(cx_a, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    active_call_c
+        .update(cx_c, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+
+    // Every admin in the call is asked, and asking again while waiting is deduplicated.
+    request_to_join(cx_b).await.unwrap();
+    assert!(matches!(
+        request_to_join(cx_b).await,
+        Err(CallError::AlreadyRequested)
+    ));
+    executor.run_until_parked();
+    assert_eq!(join_requests(&active_call_a, cx_a), [(user_b, channel_id)]);
+    assert_eq!(join_requests(&active_call_c, cx_c), [(user_b, channel_id)]);
+
+    // User A declines, which takes the request away from user C too.
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.respond_to_join_request(user_b, channel_id, false, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(join_requests(&active_call_a, cx_a), []);
+    assert_eq!(join_requests(&active_call_c, cx_c), []);
+    assert_eq!(
+        mem::take(&mut *answers_b.borrow_mut()),
+        [(channel_id, false)]
+    );
+    assert!(matches!(
+        active_call_c
+            .update(cx_c, |call, cx| {
+                call.respond_to_join_request(user_b, channel_id, true, cx)
+            })
+            .await,
+        Err(CallError::NoJoinRequest)
+    ));
+    assert!(matches!(
+        active_call_b
+            .update(cx_b, |call, cx| call.join_channel(channel_id, cx))
+            .await,
+        Err(CallError::PermissionDenied)
+    ));
+
+    // A request that isn't answered expires on both sides.
+    request_to_join(cx_b).await.unwrap();
+    executor.run_until_parked();
+    assert_eq!(join_requests(&active_call_a, cx_a), [(user_b, channel_id)]);
+    executor.advance_clock(JOIN_REQUEST_TIMEOUT);
+    executor.run_until_parked();
+    assert_eq!(join_requests(&active_call_a, cx_a), []);
+    assert_eq!(join_requests(&active_call_c, cx_c), []);
+    assert!(active_call_b.read_with(cx_b, |call, cx| !call.has_requested_to_join(channel_id, cx)));
+
+    // Once user C accepts, user B can join the channel.
+    request_to_join(cx_b).await.unwrap();
+    executor.run_until_parked();
+    active_call_c
+        .update(cx_c, |call, cx| {
+            call.respond_to_join_request(user_b, channel_id, true, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(join_requests(&active_call_a, cx_a), []);
+    assert_eq!(
+        mem::take(&mut *answers_b.borrow_mut()),
+        [(channel_id, true)]
+    );
+    active_call_b
+        .update(cx_b, |call, cx| call.join_channel(channel_id, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    client_a
+        .channel_store()
+        .read_with(cx_a, |channel_store, _| {
+            assert!(
+                channel_store
+                    .channel_participants(channel_id)
+                    .iter()
+                    .any(|participant| participant.id == user_b)
+            );
+        });
+
+    // Members can't ask to join.
+    assert!(request_to_join(cx_b).await.is_err());
+}
+
 #[gpui::test]
 async fn test_leave_channel(cx_a: &mut TestAppContext, cx_b: &mut TestAppContext) {
     let (_server, _client_a, client_b, channel_id) = TestServer::start2(cx_a, cx_b).await;
@@ -1559,7 +1559,7 @@ async fn test_share_project(
 
     let incoming_call_b = active_call_b.read_with(cx_b, |call, _| call.incoming());
     executor.run_until_parked();
-    let call = incoming_call_b.borrow().clone().unwrap();
+    let call = incoming_call_b.get_cloned().unwrap();
     assert_eq!(call.calling_user.github_login, "user_a");
     let initial_project = call.initial_project.unwrap();
     active_call_b
@@ -1675,7 +1675,7 @@ async fn test_share_project(
 
     let incoming_call_c = active_call_c.read_with(cx_c, |call, _| call.incoming());
     executor.run_until_parked();
-    let call = incoming_call_c.borrow().clone().unwrap();
+    let call = incoming_call_c.get_cloned().unwrap();
     assert_eq!(call.calling_user.github_login, "user_b");
     let initial_project = call.initial_project.unwrap();
     active_call_c
@@ -6,9 +6,10 @@ use anyhow::{Result, anyhow};
 use assistant_slash_command::SlashCommandWorkingSet;
 use assistant_text_thread::TextThreadStore;
 use buffer_diff::{DiffHunkSecondaryStatus, DiffHunkStatus, assert_hunks};
-use call::{ActiveCall, Room, room};
+use call::{ActiveCall, CallError, Room, room, screen_share_quality::ScreenSharePreset};
 use client::{RECEIVE_TIMEOUT, User};
 use collab::rpc::{CLEANUP_TIMEOUT, RECONNECT_TIMEOUT};
+use collab_ui::notifications::incoming_call_notification::IncomingCallNotification;
 use collections::{BTreeMap, HashMap, HashSet};
 use fs::{FakeFs, Fs as _, RemoveOptions};
 use futures::{StreamExt as _, channel::mpsc};
@@ -17,7 +18,7 @@ use git::{
     status::{FileStatus, StatusCode, TrackedStatus, UnmergedStatus, UnmergedStatusCode},
 };
 use gpui::{
-    App, BackgroundExecutor, Entity, Modifiers, MouseButton, MouseDownEvent, TestAppContext,
+    App, BackgroundExecutor, Entity, Modifiers, MouseButton, MouseDownEvent, Task, TestAppContext,
     UpdateGlobal, px, size,
 };
 use language::{
@@ -30,7 +31,7 @@ use lsp::{DEFAULT_LSP_REQUEST_TIMEOUT, LanguageServerId, OneOf};
 use parking_lot::Mutex;
 use pretty_assertions::assert_eq;
 use project::{
-    DiagnosticSummary, HoverBlockKind, Project, ProjectPath,
+    DiagnosticSummary, HoverBlockKind, JoinProjectProgress, Project, ProjectPath,
     lsp_store::{FormatTrigger, LspFormatTarget, SymbolLocation},
     search::{SearchQuery, SearchResult},
 };
@@ -433,6 +434,14 @@ async fn test_calling_multiple_users_simultaneously(
     let c_invite = active_call_a.update(cx_a, |call, cx| {
         call.invite(client_c.user_id().unwrap(), None, cx)
     });
+    // Calling user B again while they're still being invited is rejected locally.
+    let b_invite_again = active_call_a.update(cx_a, |call, cx| {
+        call.invite(client_b.user_id().unwrap(), None, cx)
+    });
+    assert!(matches!(
+        b_invite_again.await,
+        Err(CallError::AlreadyInvited)
+    ));
     b_invite.await.unwrap();
     c_invite.await.unwrap();
 
@@ -711,6 +720,18 @@ async fn test_room_uniqueness(
         .update(cx_b, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
+    assert!(matches!(
+        active_call_b
+            .update(cx_b, |call, cx| call.accept_incoming(cx))
+            .await,
+        Err(CallError::AlreadyInCall)
+    ));
+    assert!(matches!(
+        active_call_b2
+            .update(cx_b2, |call, cx| call.accept_incoming(cx))
+            .await,
+        Err(CallError::NoIncomingCall)
+    ));
     active_call_c
         .update(cx_c, |call, cx| {
             call.invite(client_b.user_id().unwrap(), None, cx)
@@ -1478,6 +1499,159 @@ async fn test_unshare_project(
     });
 }
 
+#[gpui::test(iterations = 10)]
+async fn test_join_project_progress(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+
+    client_a
+        .fs()
+        .insert_tree(
+            "/a",
+            json!({
+                "a.txt": "a-contents",
+                "dir": {
+                    "b.txt": "b-contents",
+                    "c.txt": "c-contents",
+                },
+            }),
+        )
+        .await;
+    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
+    let project_id = cx_a
+        .read(ActiveCall::global)
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let entries = project_a.read_with(cx_a, |project, cx| {
+        project.worktrees(cx).next().unwrap().read(cx).entry_count()
+    });
+
+    let events_b = active_call_events(cx_b);
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let progress = events_b
+        .take()
+        .into_iter()
+        .filter_map(|event| match event {
+            room::Event::JoinProjectProgress {
+                project_id: id,
+                progress,
+            } => {
+                assert_eq!(id, project_id);
+                Some(progress)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // The phases come in order, and the join only returns once every entry arrived.
+    assert_eq!(
+        progress[..2],
+        [
+            JoinProjectProgress::Connecting,
+            JoinProjectProgress::WaitingForHost
+        ]
+    );
+    assert!(progress[2..].iter().all(|progress| matches!(
+        progress,
+        JoinProjectProgress::ReceivingWorktrees { worktrees: 1, .. }
+    )));
+    assert_eq!(
+        progress.last(),
+        Some(&JoinProjectProgress::ReceivingWorktrees {
+            worktrees: 1,
+            synced_worktrees: 1,
+            entries,
+        })
+    );
+    project_b.read_with(cx_b, |project, cx| {
+        let worktree = project.worktrees(cx).next().unwrap();
+        assert_eq!(worktree.read(cx).entry_count(), entries);
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_cancel_joining_project(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+
+    client_a
+        .fs()
+        .insert_tree(
+            "/a",
+            json!({ "a.txt": "a-contents", "b.txt": "b-contents" }),
+        )
+        .await;
+    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
+    let project_id = cx_a
+        .read(ActiveCall::global)
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+
+    // Client B gives up on joining as soon as the project exists and its entries start arriving.
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let join = Rc::new(RefCell::new(None::<Task<Result<Entity<Project>>>>));
+    let _subscription = cx_b.update(|cx| {
+        let join = join.clone();
+        cx.subscribe(&room_b, move |_, event: &room::Event, _| {
+            if let room::Event::JoinProjectProgress {
+                progress: JoinProjectProgress::ReceivingWorktrees { .. },
+                ..
+            } = event
+            {
+                join.borrow_mut().take();
+            }
+        })
+    });
+    let fs_b = FakeFs::new(cx_b.background_executor.clone());
+    *join.borrow_mut() = Some(room_b.update(cx_b, |room, cx| {
+        room.join_project(project_id, client_b.language_registry().clone(), fs_b, cx)
+    }));
+    executor.run_until_parked();
+    assert!(join.borrow().is_none());
+
+    // The abandoned project left the host's project, and client B is still in the room.
+    project_a.read_with(cx_a, |project, _| {
+        assert!(project.collaborators().is_empty());
+    });
+    active_call_b.read_with(cx_b, |call, _| assert!(call.room().is_some()));
+
+    // Joining again works, which it wouldn't if the abandoned project were still subscribed to
+    // the project's messages.
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    executor.run_until_parked();
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 1);
+    });
+    project_b.read_with(cx_b, |project, cx| {
+        let worktree = project.worktrees(cx).next().unwrap();
+        assert!(
+            worktree
+                .read(cx)
+                .entry_for_path(rel_path("a.txt"))
+                .is_some()
+        );
+    });
+}
+
 #[gpui::test(iterations = 10)]
 async fn test_project_reconnect(
     executor: BackgroundExecutor,
@@ -1869,7 +2043,13 @@ async fn test_active_call_events(
         .await
         .unwrap();
     executor.run_until_parked();
-    assert_eq!(mem::take(&mut *events_a.borrow_mut()), vec![]);
+    assert_eq!(
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![room::Event::ProjectShared {
+            project_id: project_a.entity_id(),
+            remote_id: project_a_id,
+        }]
+    );
     assert_eq!(
         mem::take(&mut *events_b.borrow_mut()),
         vec![room::Event::RemoteProjectShared {
@@ -1902,7 +2082,13 @@ async fn test_active_call_events(
             worktree_root_names: vec!["b".to_string()]
         }]
     );
-    assert_eq!(mem::take(&mut *events_b.borrow_mut()), vec![]);
+    assert_eq!(
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::ProjectShared {
+            project_id: project_b.entity_id(),
+            remote_id: project_b_id,
+        }]
+    );
 
     // Sharing a project twice is idempotent.
     let project_b_id_2 = active_call_b
@@ -1933,203 +2119,276 @@ async fn test_active_call_events(
     );
 }
 
-fn active_call_events(cx: &mut TestAppContext) -> Rc<RefCell<Vec<room::Event>>> {
-    let events = Rc::new(RefCell::new(Vec::new()));
-    let active_call = cx.read(ActiveCall::global);
-    cx.update({
-        let events = events.clone();
-        |cx| {
-            cx.subscribe(&active_call, move |_, event, _| {
-                events.borrow_mut().push(event.clone())
-            })
-            .detach()
-        }
-    });
-    events
-}
-
 #[gpui::test]
-async fn test_mute_deafen(
+async fn test_local_project_share_events(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
-
-    server
-        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
+    let channel_id = server
+        .make_public_channel("the-channel", &client_a, cx_a)
         .await;
 
     let active_call_a = cx_a.read(ActiveCall::global);
     let active_call_b = cx_b.read(ActiveCall::global);
-    let active_call_c = cx_c.read(ActiveCall::global);
-
-    // User A calls user B, B answers.
     active_call_a
-        .update(cx_a, |call, cx| {
-            call.invite(client_b.user_id().unwrap(), None, cx)
-        })
+        .update(cx_a, |call, cx| call.join_channel(channel_id, cx))
         .await
         .unwrap();
-    executor.run_until_parked();
     active_call_b
-        .update(cx_b, |call, cx| call.accept_incoming(cx))
+        .update(cx_b, |call, cx| call.join_channel(channel_id, cx))
         .await
         .unwrap();
     executor.run_until_parked();
 
-    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
-    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
-
-    room_a.read_with(cx_a, |room, _| assert!(!room.is_muted()));
-    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
+    let project_a = client_a.build_test_project(cx_a).await;
+    let project_b = client_b.build_test_project(cx_b).await;
+    let events_a = active_call_events(cx_a);
+    let events_b = active_call_events(cx_b);
 
-    // Users A and B are both unmuted.
-    assert_eq!(
-        participant_audio_state(&room_a, cx_a),
-        &[ParticipantAudioState {
-            user_id: client_b.user_id().unwrap(),
-            is_muted: false,
-            audio_tracks_playing: vec![true],
-        }]
-    );
+    // Sharing and then unsharing emits a matching pair of events.
+    let project_a_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    active_call_a
+        .update(cx_a, |call, cx| call.unshare_project(project_a.clone(), cx))
+        .unwrap();
+    executor.run_until_parked();
     assert_eq!(
-        participant_audio_state(&room_b, cx_b),
-        &[ParticipantAudioState {
-            user_id: client_a.user_id().unwrap(),
-            is_muted: false,
-            audio_tracks_playing: vec![true],
-        }]
+        local_share_events(&events_a),
+        vec![
+            room::Event::ProjectShared {
+                project_id: project_a.entity_id(),
+                remote_id: project_a_id,
+            },
+            room::Event::ProjectUnshared {
+                project_id: project_a.entity_id(),
+            },
+        ]
     );
+    active_call_a.read_with(cx_a, |call, cx| {
+        assert!(!call.room().unwrap().read(cx).is_sharing_project());
+    });
 
-    // User A mutes
-    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
+    // Guests can't share projects, so the failed share must not emit anything.
+    active_call_b
+        .update(cx_b, |call, cx| call.share_project(project_b.clone(), cx))
+        .await
+        .unwrap_err();
     executor.run_until_parked();
+    assert_eq!(local_share_events(&events_b), vec![]);
+    active_call_b.read_with(cx_b, |call, cx| {
+        assert!(!call.room().unwrap().read(cx).is_sharing_project());
+    });
+    project_b.read_with(cx_b, |project, _| assert!(!project.is_shared()));
+}
 
-    // User A hears user B, but B doesn't hear A.
-    room_a.read_with(cx_a, |room, _| assert!(room.is_muted()));
-    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
-    assert_eq!(
-        participant_audio_state(&room_a, cx_a),
-        &[ParticipantAudioState {
-            user_id: client_b.user_id().unwrap(),
-            is_muted: false,
-            audio_tracks_playing: vec![true],
-        }]
-    );
-    assert_eq!(
-        participant_audio_state(&room_b, cx_b),
-        &[ParticipantAudioState {
-            user_id: client_a.user_id().unwrap(),
-            is_muted: true,
-            audio_tracks_playing: vec![true],
-        }]
-    );
+#[gpui::test(iterations = 10)]
+async fn test_invite_while_initial_project_share_is_pending(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    client_a
+        .fs()
+        .insert_tree("/a", json!({ "a.txt": "a-contents" }))
+        .await;
+    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
 
-    // User A deafens
-    room_a.update(cx_a, |room, cx| room.toggle_deafen(cx));
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_c, cx_c)])
+        .await;
     executor.run_until_parked();
 
-    // User A does not hear user B.
-    room_a.read_with(cx_a, |room, _| assert!(room.is_muted()));
-    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_c = cx_c.read(ActiveCall::global);
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let events_a = active_call_events(cx_a);
+    let events_c = active_call_events(cx_c);
+
+    // User A starts sharing the project, and invites user C to it before the share completes.
+    let share = active_call_a.update(cx_a, |call, cx| call.share_project(project_a.clone(), cx));
+    assert!(room_a.read_with(cx_a, |room, _| room.is_project_share_pending(&project_a)));
+    let invite = active_call_a.update(cx_a, |call, cx| {
+        call.invite(client_c.user_id().unwrap(), Some(project_a.clone()), cx)
+    });
+
+    // User C accepts the call as soon as it comes in, while user A may still be waiting on
+    // the share and the invite.
+    let mut incoming_call_c = active_call_c.read_with(cx_c, |call, _| call.incoming());
+    let call_c = loop {
+        if let Some(call) = incoming_call_c.next().await.unwrap() {
+            break call;
+        }
+    };
+    active_call_c
+        .update(cx_c, |call, cx| call.accept_incoming(cx))
+        .await
+        .unwrap();
+
+    let project_a_id = share.await.unwrap();
+    invite.await.unwrap();
+    executor.run_until_parked();
+
+    // The call only went out once the project was shared, and it was shared just once.
     assert_eq!(
-        participant_audio_state(&room_a, cx_a),
-        &[ParticipantAudioState {
-            user_id: client_b.user_id().unwrap(),
-            is_muted: false,
-            audio_tracks_playing: vec![false],
-        }]
+        call_c.initial_project.map(|project| project.id),
+        Some(project_a_id)
     );
     assert_eq!(
-        participant_audio_state(&room_b, cx_b),
-        &[ParticipantAudioState {
-            user_id: client_a.user_id().unwrap(),
-            is_muted: true,
-            audio_tracks_playing: vec![true],
+        local_share_events(&events_a),
+        vec![room::Event::ProjectShared {
+            project_id: project_a.entity_id(),
+            remote_id: project_a_id,
         }]
     );
+    assert!(!room_a.read_with(cx_a, |room, _| room.is_project_share_pending(&project_a)));
 
-    // User B calls user C, C joins.
-    active_call_b
-        .update(cx_b, |call, cx| {
-            call.invite(client_c.user_id().unwrap(), None, cx)
+    // User C sees the project without anything else happening in the room.
+    let room_c = active_call_c.read_with(cx_c, |call, _| call.room().unwrap().clone());
+    let projects_c = room_c.read_with(cx_c, |room, _| {
+        room.remote_participants()
+            .get(&client_a.user_id().unwrap())
+            .map(|participant| {
+                participant
+                    .projects
+                    .iter()
+                    .map(|project| project.id)
+                    .collect::<Vec<_>>()
+            })
+    });
+    assert_eq!(projects_c, Some(vec![project_a_id]));
+    assert!(events_c.borrow().iter().any(|event| matches!(
+        event,
+        room::Event::RemoteProjectShared { project_id, .. } if *project_id == project_a_id
+    )));
+}
+
+#[gpui::test]
+async fn test_accept_incoming_call_without_initial_project(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
+        .await;
+    client_a
+        .fs()
+        .insert_tree(
+            "/a",
+            json!({
+                "zed": { "main.rs": "fn main() {}" },
+                "docs": { "README.md": "# Docs" },
+            }),
+        )
+        .await;
+    let (project_a, _) = client_a.build_local_project("/a/zed", cx_a).await;
+    let (worktree_2, _) = project_a
+        .update(cx_a, |project, cx| {
+            project.find_or_create_worktree("/a/docs", true, cx)
+        })
+        .await
+        .unwrap();
+    worktree_2
+        .read_with(cx_a, |tree, _| tree.as_local().unwrap().scan_complete())
+        .await;
+
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let active_call_c = cx_c.read(ActiveCall::global);
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+
+    // User A invites user B to the project, and B is told what's being shared.
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), Some(project_a.clone()), cx)
         })
         .await
         .unwrap();
     executor.run_until_parked();
-    active_call_c
+    let call_b = active_call_b
+        .read_with(cx_b, |call, _| call.incoming().get_cloned())
+        .unwrap();
+    assert_eq!(call_b.initial_project_names().as_deref(), Some("zed, docs"));
+    let mut notification_names = Vec::new();
+    for window in cx_b.windows() {
+        window
+            .update(cx_b, |window, _, cx| {
+                if let Ok(notification) = window.downcast::<IncomingCallNotification>() {
+                    notification_names.push(notification.read(cx).project_names().cloned());
+                }
+            })
+            .unwrap();
+    }
+    assert_eq!(notification_names, vec![Some("zed, docs".into())]);
+
+    // User B joins the call without the project, which is left for them to join later.
+    let project_to_join = active_call_b
+        .update(cx_b, |call, cx| call.accept_incoming_call_only(cx))
+        .await
+        .unwrap();
+    assert_eq!(project_to_join, None);
+
+    // User C accepts the call as usual, and is expected to open the project.
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_c.user_id().unwrap(), Some(project_a.clone()), cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    let project_to_join = active_call_c
         .update(cx_c, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
+    assert_eq!(project_to_join, Some(project_id));
     executor.run_until_parked();
 
-    // User A does not hear users B or C.
-    assert_eq!(
-        participant_audio_state(&room_a, cx_a),
-        &[
-            ParticipantAudioState {
-                user_id: client_b.user_id().unwrap(),
-                is_muted: false,
-                audio_tracks_playing: vec![false],
-            },
-            ParticipantAudioState {
-                user_id: client_c.user_id().unwrap(),
-                is_muted: false,
-                audio_tracks_playing: vec![false],
-            }
-        ]
-    );
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let projects_b = room_b.read_with(cx_b, |room, _| {
+        room.remote_participants()
+            .get(&client_a.user_id().unwrap())
+            .map(|participant| {
+                participant
+                    .projects
+                    .iter()
+                    .map(|project| (project.id, project.worktree_root_names.clone()))
+                    .collect::<Vec<_>>()
+            })
+    });
     assert_eq!(
-        participant_audio_state(&room_b, cx_b),
-        &[
-            ParticipantAudioState {
-                user_id: client_a.user_id().unwrap(),
-                is_muted: true,
-                audio_tracks_playing: vec![true],
-            },
-            ParticipantAudioState {
-                user_id: client_c.user_id().unwrap(),
-                is_muted: false,
-                audio_tracks_playing: vec![true],
-            }
-        ]
+        projects_b,
+        Some(vec![(
+            project_id,
+            vec!["zed".to_string(), "docs".to_string()]
+        )])
     );
-
-    #[derive(PartialEq, Eq, Debug)]
-    struct ParticipantAudioState {
-        user_id: u64,
-        is_muted: bool,
-        audio_tracks_playing: Vec<bool>,
-    }
-
-    fn participant_audio_state(
-        room: &Entity<Room>,
-        cx: &TestAppContext,
-    ) -> Vec<ParticipantAudioState> {
-        room.read_with(cx, |room, _| {
-            room.remote_participants()
-                .iter()
-                .map(|(user_id, participant)| ParticipantAudioState {
-                    user_id: *user_id,
-                    is_muted: participant.muted,
-                    audio_tracks_playing: participant
-                        .audio_tracks
-                        .values()
-                        .map(|(track, _)| track.enabled())
-                        .collect(),
-                })
-                .collect::<Vec<_>>()
-        })
-    }
 }
 
-#[gpui::test(iterations = 10)]
-async fn test_room_location(
+#[gpui::test]
+async fn test_sharing_project_with_secret_files(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -2137,165 +2396,137 @@ async fn test_room_location(
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    client_a.fs().insert_tree("/a", json!({})).await;
-    client_b.fs().insert_tree("/b", json!({})).await;
-
-    let active_call_a = cx_a.read(ActiveCall::global);
-    let active_call_b = cx_b.read(ActiveCall::global);
-
-    let a_notified = Rc::new(Cell::new(false));
-    cx_a.update({
-        let notified = a_notified.clone();
-        |cx| {
-            cx.observe(&active_call_a, move |_, _| notified.set(true))
-                .detach()
-        }
-    });
-
-    let b_notified = Rc::new(Cell::new(false));
-    cx_b.update({
-        let b_notified = b_notified.clone();
-        |cx| {
-            cx.observe(&active_call_b, move |_, _| b_notified.set(true))
-                .detach()
-        }
-    });
-
-    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
-    active_call_a
-        .update(cx_a, |call, cx| call.set_location(Some(&project_a), cx))
-        .await
-        .unwrap();
-    let (project_b, _) = client_b.build_local_project("/b", cx_b).await;
-
     server
         .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
 
-    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/secret-files"),
+            json!({
+                "credentials.json": "{}",
+                "main.rs": "fn main() {}",
+            }),
+        )
+        .await;
+    let (project_a, worktree_id) = client_a
+        .build_local_project(path!("/secret-files"), cx_a)
+        .await;
+    let (_workspace_a, cx_a) = client_a.build_workspace(&project_a, cx_a);
 
-    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
-    executor.run_until_parked();
-    assert!(a_notified.take());
-    assert_eq!(
-        participant_locations(&room_a, cx_a),
-        vec![("user_b".to_string(), ParticipantLocation::External)]
-    );
-    assert!(b_notified.take());
+    // Sharing anyway shares the project just like sharing it without asking.
+    let share = active_call_a.update_in(cx_a, |call, window, cx| {
+        call.share_project_after_review(project_a.clone(), window, cx)
+    });
+    cx_a.run_until_parked();
     assert_eq!(
-        participant_locations(&room_b, cx_b),
-        vec![("user_a".to_string(), ParticipantLocation::UnsharedProject)]
+        cx_a.pending_prompt(),
+        Some((
+            "This project contains files that may hold secrets".to_string(),
+            "secret-files/credentials.json\n\n\
+            Guests will be able to open these files unless you exclude them."
+                .to_string()
+        ))
     );
-
-    let project_a_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+    cx_a.simulate_prompt_answer("Share Anyway");
+    let project_id = share.await.unwrap().unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("credentials.json")), cx)
+        })
         .await
         .unwrap();
-    executor.run_until_parked();
-    assert!(a_notified.take());
-    assert_eq!(
-        participant_locations(&room_a, cx_a),
-        vec![("user_b".to_string(), ParticipantLocation::External)]
-    );
-    assert!(b_notified.take());
-    assert_eq!(
-        participant_locations(&room_b, cx_b),
-        vec![(
-            "user_a".to_string(),
-            ParticipantLocation::SharedProject {
-                project_id: project_a_id
-            }
-        )]
-    );
+    active_call_a
+        .update(cx_a, |call, cx| call.unshare_project(project_a.clone(), cx))
+        .unwrap();
+    cx_a.run_until_parked();
 
-    let project_b_id = active_call_b
-        .update(cx_b, |call, cx| call.share_project(project_b.clone(), cx))
+    // Only files that weren't asked about before are asked about, and excluded files can't
+    // be opened by guests.
+    client_a
+        .fs()
+        .insert_tree(path!("/secret-files/deploy"), json!({ "id_rsa": "key" }))
+        .await;
+    cx_a.run_until_parked();
+    let share = active_call_a.update_in(cx_a, |call, window, cx| {
+        call.share_project_after_review(project_a.clone(), window, cx)
+    });
+    cx_a.run_until_parked();
+    let (_, detail) = cx_a.pending_prompt().unwrap();
+    assert!(
+        detail.starts_with("secret-files/deploy/id_rsa\n\n"),
+        "{detail}"
+    );
+    cx_a.simulate_prompt_answer("Share Excluding These");
+    let project_id = share.await.unwrap().unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("deploy/id_rsa")), cx)
+        })
+        .await
+        .unwrap_err();
+    project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("main.rs")), cx)
+        })
         .await
         .unwrap();
-    executor.run_until_parked();
-    assert!(a_notified.take());
-    assert_eq!(
-        participant_locations(&room_a, cx_a),
-        vec![("user_b".to_string(), ParticipantLocation::External)]
-    );
-    assert!(b_notified.take());
-    assert_eq!(
-        participant_locations(&room_b, cx_b),
-        vec![(
-            "user_a".to_string(),
-            ParticipantLocation::SharedProject {
-                project_id: project_a_id
-            }
-        )]
-    );
+    active_call_a
+        .update(cx_a, |call, cx| call.unshare_project(project_a.clone(), cx))
+        .unwrap();
+    cx_a.run_until_parked();
 
-    active_call_b
-        .update(cx_b, |call, cx| call.set_location(Some(&project_b), cx))
+    // Sharing again doesn't ask, and still keeps the excluded file back.
+    let project_id = active_call_a
+        .update_in(cx_a, |call, window, cx| {
+            call.share_project_after_review(project_a.clone(), window, cx)
+        })
         .await
+        .unwrap()
         .unwrap();
-    executor.run_until_parked();
-    assert!(a_notified.take());
-    assert_eq!(
-        participant_locations(&room_a, cx_a),
-        vec![(
-            "user_b".to_string(),
-            ParticipantLocation::SharedProject {
-                project_id: project_b_id
-            }
-        )]
-    );
-    assert!(b_notified.take());
-    assert_eq!(
-        participant_locations(&room_b, cx_b),
-        vec![(
-            "user_a".to_string(),
-            ParticipantLocation::SharedProject {
-                project_id: project_a_id
-            }
-        )]
-    );
-
-    active_call_b
-        .update(cx_b, |call, cx| call.set_location(None, cx))
+    assert!(!cx_a.has_pending_prompt());
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("deploy/id_rsa")), cx)
+        })
         .await
-        .unwrap();
-    executor.run_until_parked();
-    assert!(a_notified.take());
-    assert_eq!(
-        participant_locations(&room_a, cx_a),
-        vec![("user_b".to_string(), ParticipantLocation::External)]
-    );
-    assert!(b_notified.take());
-    assert_eq!(
-        participant_locations(&room_b, cx_b),
-        vec![(
-            "user_a".to_string(),
-            ParticipantLocation::SharedProject {
-                project_id: project_a_id
-            }
-        )]
-    );
+        .unwrap_err();
+}
 
-    fn participant_locations(
-        room: &Entity<Room>,
-        cx: &TestAppContext,
-    ) -> Vec<(String, ParticipantLocation)> {
-        room.read_with(cx, |room, _| {
-            room.remote_participants()
-                .values()
-                .map(|participant| {
-                    (
-                        participant.user.github_login.to_string(),
-                        participant.location,
-                    )
-                })
-                .collect()
+fn local_share_events(events: &Rc<RefCell<Vec<room::Event>>>) -> Vec<room::Event> {
+    mem::take(&mut *events.borrow_mut())
+        .into_iter()
+        .filter(|event| {
+            matches!(
+                event,
+                room::Event::ProjectShared { .. } | room::Event::ProjectUnshared { .. }
+            )
         })
-    }
+        .collect()
 }
 
-#[gpui::test(iterations = 10)]
-async fn test_propagate_saves_and_fs_changes(
+fn active_call_events(cx: &mut TestAppContext) -> Rc<RefCell<Vec<room::Event>>> {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let active_call = cx.read(ActiveCall::global);
+    cx.update({
+        let events = events.clone();
+        |cx| {
+            cx.subscribe(&active_call, move |_, event, _| {
+                events.borrow_mut().push(event.clone())
+            })
+            .detach()
+        }
+    });
+    events
+}
+
+#[gpui::test]
+async fn test_mute_deafen(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -2307,245 +2538,175 @@ async fn test_propagate_saves_and_fs_changes(
     let client_c = server.create_client(cx_c, "user_c").await;
 
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
-
-    let rust = Arc::new(Language::new(
-        LanguageConfig {
-            name: "Rust".into(),
-            matcher: LanguageMatcher {
-                path_suffixes: vec!["rs".to_string()],
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        Some(tree_sitter_rust::LANGUAGE.into()),
-    ));
-    let javascript = Arc::new(Language::new(
-        LanguageConfig {
-            name: "JavaScript".into(),
-            matcher: LanguageMatcher {
-                path_suffixes: vec!["js".to_string()],
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        Some(tree_sitter_rust::LANGUAGE.into()),
-    ));
-    for client in [&client_a, &client_b, &client_c] {
-        client.language_registry().add(rust.clone());
-        client.language_registry().add(javascript.clone());
-    }
-
-    client_a
-        .fs()
-        .insert_tree(
-            path!("/a"),
-            json!({
-                "file1.rs": "",
-                "file2": ""
-            }),
-        )
+        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
-
-    let worktree_a = project_a.read_with(cx_a, |p, cx| p.worktrees(cx).next().unwrap());
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
-        .await
-        .unwrap();
 
-    // Join that worktree as clients B and C.
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
-    let project_c = client_c.join_remote_project(project_id, cx_c).await;
-
-    let worktree_b = project_b.read_with(cx_b, |p, cx| p.worktrees(cx).next().unwrap());
-
-    let worktree_c = project_c.read_with(cx_c, |p, cx| p.worktrees(cx).next().unwrap());
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let active_call_c = cx_c.read(ActiveCall::global);
 
-    // Open and edit a buffer as both guests B and C.
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
+    // User A calls user B, B answers.
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
         })
         .await
         .unwrap();
-    let buffer_c = project_c
-        .update(cx_c, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
-        })
+    executor.run_until_parked();
+    active_call_b
+        .update(cx_b, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
+    executor.run_until_parked();
 
-    buffer_b.read_with(cx_b, |buffer, _| {
-        assert_eq!(buffer.language().unwrap().name(), "Rust");
-    });
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
 
-    buffer_c.read_with(cx_c, |buffer, _| {
-        assert_eq!(buffer.language().unwrap().name(), "Rust");
-    });
-    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "i-am-b, ")], None, cx));
-    buffer_c.update(cx_c, |buf, cx| buf.edit([(0..0, "i-am-c, ")], None, cx));
+    room_a.read_with(cx_a, |room, _| assert!(!room.is_muted()));
+    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
 
-    // Open and edit that buffer as the host.
-    let buffer_a = project_a
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
-        })
-        .await
-        .unwrap();
+    // Users A and B are both unmuted.
+    assert_eq!(
+        participant_audio_state(&room_a, cx_a),
+        &[ParticipantAudioState {
+            user_id: client_b.user_id().unwrap(),
+            is_muted: false,
+            audio_tracks_playing: vec![true],
+        }]
+    );
+    assert_eq!(
+        participant_audio_state(&room_b, cx_b),
+        &[ParticipantAudioState {
+            user_id: client_a.user_id().unwrap(),
+            is_muted: false,
+            audio_tracks_playing: vec![true],
+        }]
+    );
 
+    // User A mutes
+    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
     executor.run_until_parked();
 
-    buffer_a.read_with(cx_a, |buf, _| assert_eq!(buf.text(), "i-am-c, i-am-b, "));
-    buffer_a.update(cx_a, |buf, cx| {
-        buf.edit([(buf.len()..buf.len(), "i-am-a")], None, cx)
-    });
+    // User A hears user B, but B doesn't hear A.
+    room_a.read_with(cx_a, |room, _| assert!(room.is_muted()));
+    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
+    assert_eq!(
+        participant_audio_state(&room_a, cx_a),
+        &[ParticipantAudioState {
+            user_id: client_b.user_id().unwrap(),
+            is_muted: false,
+            audio_tracks_playing: vec![true],
+        }]
+    );
+    assert_eq!(
+        participant_audio_state(&room_b, cx_b),
+        &[ParticipantAudioState {
+            user_id: client_a.user_id().unwrap(),
+            is_muted: true,
+            audio_tracks_playing: vec![true],
+        }]
+    );
 
+    // User A deafens
+    room_a.update(cx_a, |room, cx| room.toggle_deafen(cx));
     executor.run_until_parked();
 
-    buffer_a.read_with(cx_a, |buf, _| {
-        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
-    });
-
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
-    });
-
-    buffer_c.read_with(cx_c, |buf, _| {
-        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
-    });
-
-    // Edit the buffer as the host and concurrently save as guest B.
-    let save_b = project_b.update(cx_b, |project, cx| {
-        project.save_buffer(buffer_b.clone(), cx)
-    });
-    buffer_a.update(cx_a, |buf, cx| buf.edit([(0..0, "hi-a, ")], None, cx));
-    save_b.await.unwrap();
+    // User A does not hear user B.
+    room_a.read_with(cx_a, |room, _| assert!(room.is_muted()));
+    room_b.read_with(cx_b, |room, _| assert!(!room.is_muted()));
     assert_eq!(
-        client_a.fs().load("/a/file1.rs".as_ref()).await.unwrap(),
-        "hi-a, i-am-c, i-am-b, i-am-a"
+        participant_audio_state(&room_a, cx_a),
+        &[ParticipantAudioState {
+            user_id: client_b.user_id().unwrap(),
+            is_muted: false,
+            audio_tracks_playing: vec![false],
+        }]
+    );
+    assert_eq!(
+        participant_audio_state(&room_b, cx_b),
+        &[ParticipantAudioState {
+            user_id: client_a.user_id().unwrap(),
+            is_muted: true,
+            audio_tracks_playing: vec![true],
+        }]
     );
 
-    executor.run_until_parked();
-
-    buffer_a.read_with(cx_a, |buf, _| assert!(!buf.is_dirty()));
-
-    buffer_b.read_with(cx_b, |buf, _| assert!(!buf.is_dirty()));
-
-    buffer_c.read_with(cx_c, |buf, _| assert!(!buf.is_dirty()));
-
-    // Make changes on host's file system, see those changes on guest worktrees.
-    client_a
-        .fs()
-        .rename(
-            path!("/a/file1.rs").as_ref(),
-            path!("/a/file1.js").as_ref(),
-            Default::default(),
-        )
-        .await
-        .unwrap();
-    client_a
-        .fs()
-        .rename(
-            path!("/a/file2").as_ref(),
-            path!("/a/file3").as_ref(),
-            Default::default(),
-        )
+    // User B calls user C, C joins.
+    active_call_b
+        .update(cx_b, |call, cx| {
+            call.invite(client_c.user_id().unwrap(), None, cx)
+        })
         .await
         .unwrap();
-    client_a
-        .fs()
-        .insert_file(path!("/a/file4"), "4".into())
-        .await;
     executor.run_until_parked();
-
-    worktree_a.read_with(cx_a, |tree, _| {
-        assert_eq!(
-            tree.paths().collect::<Vec<_>>(),
-            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
-        )
-    });
-
-    worktree_b.read_with(cx_b, |tree, _| {
-        assert_eq!(
-            tree.paths().collect::<Vec<_>>(),
-            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
-        )
-    });
-
-    worktree_c.read_with(cx_c, |tree, _| {
-        assert_eq!(
-            tree.paths().collect::<Vec<_>>(),
-            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
-        )
-    });
-
-    // Ensure buffer files are updated as well.
-
-    buffer_a.read_with(cx_a, |buffer, _| {
-        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
-        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
-    });
-
-    buffer_b.read_with(cx_b, |buffer, _| {
-        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
-        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
-    });
-
-    buffer_c.read_with(cx_c, |buffer, _| {
-        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
-        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
-    });
-
-    let new_buffer_a = project_a
-        .update(cx_a, |p, cx| p.create_buffer(None, false, cx))
-        .await
-        .unwrap();
-
-    let new_buffer_id = new_buffer_a.read_with(cx_a, |buffer, _| buffer.remote_id());
-    let new_buffer_b = project_b
-        .update(cx_b, |p, cx| p.open_buffer_by_id(new_buffer_id, cx))
+    active_call_c
+        .update(cx_c, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
+    executor.run_until_parked();
 
-    new_buffer_b.read_with(cx_b, |buffer, _| {
-        assert!(buffer.file().is_none());
-    });
+    // User A does not hear users B or C.
+    assert_eq!(
+        participant_audio_state(&room_a, cx_a),
+        &[
+            ParticipantAudioState {
+                user_id: client_b.user_id().unwrap(),
+                is_muted: false,
+                audio_tracks_playing: vec![false],
+            },
+            ParticipantAudioState {
+                user_id: client_c.user_id().unwrap(),
+                is_muted: false,
+                audio_tracks_playing: vec![false],
+            }
+        ]
+    );
+    assert_eq!(
+        participant_audio_state(&room_b, cx_b),
+        &[
+            ParticipantAudioState {
+                user_id: client_a.user_id().unwrap(),
+                is_muted: true,
+                audio_tracks_playing: vec![true],
+            },
+            ParticipantAudioState {
+                user_id: client_c.user_id().unwrap(),
+                is_muted: false,
+                audio_tracks_playing: vec![true],
+            }
+        ]
+    );
 
-    new_buffer_a.update(cx_a, |buffer, cx| {
-        buffer.edit([(0..0, "ok")], None, cx);
-    });
-    project_a
-        .update(cx_a, |project, cx| {
-            let path = ProjectPath {
-                path: rel_path("file3.rs").into(),
-                worktree_id: worktree_a.read(cx).id(),
-            };
+    #[derive(PartialEq, Eq, Debug)]
+    struct ParticipantAudioState {
+        user_id: u64,
+        is_muted: bool,
+        audio_tracks_playing: Vec<bool>,
+    }
 
-            project.save_buffer_as(new_buffer_a.clone(), path, cx)
+    fn participant_audio_state(
+        room: &Entity<Room>,
+        cx: &TestAppContext,
+    ) -> Vec<ParticipantAudioState> {
+        room.read_with(cx, |room, _| {
+            room.remote_participants()
+                .iter()
+                .map(|(user_id, participant)| ParticipantAudioState {
+                    user_id: *user_id,
+                    is_muted: participant.muted,
+                    audio_tracks_playing: participant
+                        .audio_tracks
+                        .values()
+                        .map(|(track, _)| track.enabled())
+                        .collect(),
+                })
+                .collect::<Vec<_>>()
         })
-        .await
-        .unwrap();
-
-    executor.run_until_parked();
-
-    new_buffer_b.read_with(cx_b, |buffer_b, _| {
-        assert_eq!(
-            buffer_b.file().unwrap().path().as_ref(),
-            rel_path("file3.rs")
-        );
-
-        new_buffer_a.read_with(cx_a, |buffer_a, _| {
-            assert_eq!(buffer_b.saved_mtime(), buffer_a.saved_mtime());
-            assert_eq!(buffer_b.saved_version(), buffer_a.saved_version());
-        });
-    });
+    }
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_git_diff_base_change(
+async fn test_room_location(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -2553,343 +2714,300 @@ async fn test_git_diff_base_change(
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
-        .await;
+    client_a.fs().insert_tree("/a", json!({})).await;
+    client_b.fs().insert_tree("/b", json!({})).await;
+
     let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
 
-    client_a
-        .fs()
-        .insert_tree(
-            "/dir",
-            json!({
-            ".git": {},
-            "sub": {
-                ".git": {},
-                "b.txt": "
-                    one
-                    two
-                    three
-                ".unindent(),
-            },
-            "a.txt": "
-                    one
-                    two
-                    three
-                ".unindent(),
-            }),
-        )
-        .await;
+    let a_notified = Rc::new(Cell::new(false));
+    cx_a.update({
+        let notified = a_notified.clone();
+        |cx| {
+            cx.observe(&active_call_a, move |_, _| notified.set(true))
+                .detach()
+        }
+    });
 
-    let (project_local, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| {
-            call.share_project(project_local.clone(), cx)
-        })
+    let b_notified = Rc::new(Cell::new(false));
+    cx_b.update({
+        let b_notified = b_notified.clone();
+        |cx| {
+            cx.observe(&active_call_b, move |_, _| b_notified.set(true))
+                .detach()
+        }
+    });
+
+    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
+    active_call_a
+        .update(cx_a, |call, cx| call.set_location(Some(&project_a), cx))
         .await
         .unwrap();
+    let (project_b, _) = client_b.build_local_project("/b", cx_b).await;
 
-    let project_remote = client_b.join_remote_project(project_id, cx_b).await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
 
-    let staged_text = "
-        one
-        three
-    "
-    .unindent();
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
 
-    let committed_text = "
-        one
-        TWO
-        three
-    "
-    .unindent();
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    executor.run_until_parked();
+    assert!(a_notified.take());
+    assert_eq!(
+        participant_locations(&room_a, cx_a),
+        vec![("user_b".to_string(), ParticipantLocation::External)]
+    );
+    assert!(b_notified.take());
+    assert_eq!(
+        participant_locations(&room_b, cx_b),
+        vec![("user_a".to_string(), ParticipantLocation::UnsharedProject)]
+    );
 
-    let new_committed_text = "
-        one
-        TWO_HUNDRED
-        three
-    "
-    .unindent();
-
-    let new_staged_text = "
-        one
-        two
-    "
-    .unindent();
+    let project_a_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert!(a_notified.take());
+    assert_eq!(
+        participant_locations(&room_a, cx_a),
+        vec![("user_b".to_string(), ParticipantLocation::External)]
+    );
+    assert!(b_notified.take());
+    assert_eq!(
+        participant_locations(&room_b, cx_b),
+        vec![(
+            "user_a".to_string(),
+            ParticipantLocation::SharedProject {
+                project_id: project_a_id
+            }
+        )]
+    );
 
-    client_a
-        .fs()
-        .set_index_for_repo(Path::new("/dir/.git"), &[("a.txt", staged_text.clone())]);
-    client_a.fs().set_head_for_repo(
-        Path::new("/dir/.git"),
-        &[("a.txt", committed_text.clone())],
-        "deadbeef",
+    let project_b_id = active_call_b
+        .update(cx_b, |call, cx| call.share_project(project_b.clone(), cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert!(a_notified.take());
+    assert_eq!(
+        participant_locations(&room_a, cx_a),
+        vec![("user_b".to_string(), ParticipantLocation::External)]
+    );
+    assert!(b_notified.take());
+    assert_eq!(
+        participant_locations(&room_b, cx_b),
+        vec![(
+            "user_a".to_string(),
+            ParticipantLocation::SharedProject {
+                project_id: project_a_id
+            }
+        )]
     );
 
-    // Create the buffer
-    let buffer_local_a = project_local
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
-        })
+    active_call_b
+        .update(cx_b, |call, cx| call.set_location(Some(&project_b), cx))
         .await
         .unwrap();
-    let local_unstaged_diff_a = project_local
-        .update(cx_a, |p, cx| {
-            p.open_unstaged_diff(buffer_local_a.clone(), cx)
-        })
+    executor.run_until_parked();
+    assert!(a_notified.take());
+    assert_eq!(
+        participant_locations(&room_a, cx_a),
+        vec![(
+            "user_b".to_string(),
+            ParticipantLocation::SharedProject {
+                project_id: project_b_id
+            }
+        )]
+    );
+    assert!(b_notified.take());
+    assert_eq!(
+        participant_locations(&room_b, cx_b),
+        vec![(
+            "user_a".to_string(),
+            ParticipantLocation::SharedProject {
+                project_id: project_a_id
+            }
+        )]
+    );
+
+    active_call_b
+        .update(cx_b, |call, cx| call.set_location(None, cx))
         .await
         .unwrap();
+    executor.run_until_parked();
+    assert!(a_notified.take());
+    assert_eq!(
+        participant_locations(&room_a, cx_a),
+        vec![("user_b".to_string(), ParticipantLocation::External)]
+    );
+    assert!(b_notified.take());
+    assert_eq!(
+        participant_locations(&room_b, cx_b),
+        vec![(
+            "user_a".to_string(),
+            ParticipantLocation::SharedProject {
+                project_id: project_a_id
+            }
+        )]
+    );
 
-    // Wait for it to catch up to the new diff
+    fn participant_locations(
+        room: &Entity<Room>,
+        cx: &TestAppContext,
+    ) -> Vec<(String, ParticipantLocation)> {
+        room.read_with(cx, |room, _| {
+            room.remote_participants()
+                .values()
+                .map(|participant| {
+                    (
+                        participant.user.github_login.to_string(),
+                        participant.location,
+                    )
+                })
+                .collect()
+        })
+    }
+}
+
+#[gpui::test]
+async fn test_room_migration(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
     executor.run_until_parked();
-    local_unstaged_diff_a.read_with(cx_a, |diff, cx| {
-        let buffer = buffer_local_a.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
-        );
+
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
+    executor.run_until_parked();
+    assert!(room_a.read_with(cx_a, |room, _| room.is_muted()));
+
+    let events_a = active_call_events(cx_a);
+    let room_id = room_a.read_with(cx_a, |room, _| room.id());
+    server.migrate_room(room_id).await;
+    executor.run_until_parked();
+
+    // The room is rejoined in place rather than left and joined again.
+    assert_eq!(
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![
+            room::Event::MigrationStarted,
+            room::Event::MigrationFinished { succeeded: true }
+        ]
+    );
+    assert_eq!(
+        active_call_a.read_with(cx_a, |call, _| call.room().cloned()),
+        Some(room_a.clone())
+    );
+    room_a.read_with(cx_a, |room, _| {
+        assert!(room.status().is_online());
+        assert!(room.is_muted());
     });
+    assert_eq!(
+        room_participants(&room_a, cx_a),
+        RoomParticipants {
+            remote: vec!["user_b".to_string()],
+            pending: vec![],
+        }
+    );
+    assert_eq!(
+        room_participants(&room_b, cx_b),
+        RoomParticipants {
+            remote: vec!["user_a".to_string()],
+            pending: vec![],
+        }
+    );
+}
 
-    // Create remote buffer
-    let remote_buffer_a = project_remote
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
-        })
+#[gpui::test]
+async fn test_room_migration_with_shared_project(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+
+    let active_call_a = cx_a.read(ActiveCall::global);
+    client_a
+        .fs()
+        .insert_tree(path!("/a"), json!({ "a.txt": "a-contents" }))
+        .await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-    let remote_unstaged_diff_a = project_remote
-        .update(cx_b, |p, cx| {
-            p.open_unstaged_diff(remote_buffer_a.clone(), cx)
-        })
+    active_call_a
+        .update(cx_a, |call, cx| call.set_location(Some(&project_a), cx))
         .await
         .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    executor.run_until_parked();
 
-    // Wait remote buffer to catch up to the new diff
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let room_id = room_a.read_with(cx_a, |room, _| room.id());
+    server.migrate_room(room_id).await;
     executor.run_until_parked();
-    remote_unstaged_diff_a.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_a.read(cx);
+
+    // The project stays shared and the guest keeps collaborating on it.
+    assert_eq!(
+        project_a.read_with(cx_a, |project, _| project.remote_id()),
+        Some(project_id)
+    );
+    assert!(!project_b.read_with(cx_b, |project, cx| project.is_disconnected(cx)));
+    room_b.read_with(cx_b, |room, _| {
+        assert!(room.status().is_online());
+        let locations = room
+            .remote_participants()
+            .values()
+            .map(|participant| participant.location)
+            .collect::<Vec<_>>();
         assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
+            locations,
+            vec![ParticipantLocation::SharedProject { project_id }]
         );
     });
 
-    // Open uncommitted changes on the guest, without opening them on the host first
-    let remote_uncommitted_diff_a = project_remote
-        .update(cx_b, |p, cx| {
-            p.open_uncommitted_diff(remote_buffer_a.clone(), cx)
+    let buffer_b = project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
         })
         .await
         .unwrap();
+    buffer_b.update(cx_b, |buffer, cx| buffer.edit([(0..0, "b-")], None, cx));
     executor.run_until_parked();
-    remote_uncommitted_diff_a.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_a.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(committed_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(
-                1..2,
-                "TWO\n",
-                "two\n",
-                DiffHunkStatus::modified(DiffHunkSecondaryStatus::HasSecondaryHunk),
-            )],
-        );
-    });
 
-    // Update the index text of the open buffer
-    client_a.fs().set_index_for_repo(
-        Path::new("/dir/.git"),
-        &[("a.txt", new_staged_text.clone())],
-    );
-    client_a.fs().set_head_for_repo(
-        Path::new("/dir/.git"),
-        &[("a.txt", new_committed_text.clone())],
-        "deadbeef",
-    );
-
-    // Wait for buffer_local_a to receive it
-    executor.run_until_parked();
-    local_unstaged_diff_a.read_with(cx_a, |diff, cx| {
-        let buffer = buffer_local_a.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(new_staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
-        );
-    });
-
-    // Guest receives index text update
-    remote_unstaged_diff_a.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_a.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(new_staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
-        );
-    });
-
-    remote_uncommitted_diff_a.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_a.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(new_committed_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(
-                1..2,
-                "TWO_HUNDRED\n",
-                "two\n",
-                DiffHunkStatus::modified(DiffHunkSecondaryStatus::OverlapsWithSecondaryHunk),
-            )],
-        );
-    });
-
-    // Nested git dir
-    let staged_text = "
-        one
-        three
-    "
-    .unindent();
-
-    let new_staged_text = "
-        one
-        two
-    "
-    .unindent();
-
-    client_a.fs().set_index_for_repo(
-        Path::new("/dir/sub/.git"),
-        &[("b.txt", staged_text.clone())],
-    );
-
-    // Create the buffer
-    let buffer_local_b = project_local
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("sub/b.txt")), cx)
-        })
-        .await
-        .unwrap();
-    let local_unstaged_diff_b = project_local
-        .update(cx_a, |p, cx| {
-            p.open_unstaged_diff(buffer_local_b.clone(), cx)
-        })
-        .await
-        .unwrap();
-
-    // Wait for it to catch up to the new diff
-    executor.run_until_parked();
-    local_unstaged_diff_b.read_with(cx_a, |diff, cx| {
-        let buffer = buffer_local_b.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &diff.base_text_string(cx).unwrap(),
-            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
-        );
-    });
-
-    // Create remote buffer
-    let remote_buffer_b = project_remote
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("sub/b.txt")), cx)
-        })
-        .await
-        .unwrap();
-    let remote_unstaged_diff_b = project_remote
-        .update(cx_b, |p, cx| {
-            p.open_unstaged_diff(remote_buffer_b.clone(), cx)
+    let buffer_a = project_a
+        .update(cx_a, |project, cx| {
+            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
         })
         .await
         .unwrap();
-
-    executor.run_until_parked();
-    remote_unstaged_diff_b.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_b.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &staged_text,
-            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
-        );
-    });
-
-    // Updatet the staged text
-    client_a.fs().set_index_for_repo(
-        Path::new("/dir/sub/.git"),
-        &[("b.txt", new_staged_text.clone())],
-    );
-
-    // Wait for buffer_local_b to receive it
-    executor.run_until_parked();
-    local_unstaged_diff_b.read_with(cx_a, |diff, cx| {
-        let buffer = buffer_local_b.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(new_staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &new_staged_text,
-            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
-        );
-    });
-
-    remote_unstaged_diff_b.read_with(cx_b, |diff, cx| {
-        let buffer = remote_buffer_b.read(cx);
-        assert_eq!(
-            diff.base_text_string(cx).as_deref(),
-            Some(new_staged_text.as_str())
-        );
-        assert_hunks(
-            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
-            buffer,
-            &new_staged_text,
-            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
-        );
-    });
+    buffer_a.read_with(cx_a, |buffer, _| assert_eq!(buffer.text(), "b-a-contents"));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_git_branch_name(
+async fn test_propagate_saves_and_fs_changes(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -2899,153 +3017,283 @@ async fn test_git_branch_name(
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
     let client_c = server.create_client(cx_c, "user_c").await;
+
     server
         .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
+    let rust = Arc::new(Language::new(
+        LanguageConfig {
+            name: "Rust".into(),
+            matcher: LanguageMatcher {
+                path_suffixes: vec!["rs".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Some(tree_sitter_rust::LANGUAGE.into()),
+    ));
+    let javascript = Arc::new(Language::new(
+        LanguageConfig {
+            name: "JavaScript".into(),
+            matcher: LanguageMatcher {
+                path_suffixes: vec!["js".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Some(tree_sitter_rust::LANGUAGE.into()),
+    ));
+    for client in [&client_a, &client_b, &client_c] {
+        client.language_registry().add(rust.clone());
+        client.language_registry().add(javascript.clone());
+    }
+
     client_a
         .fs()
         .insert_tree(
-            "/dir",
+            path!("/a"),
             json!({
-            ".git": {},
+                "file1.rs": "",
+                "file2": ""
             }),
         )
         .await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
 
-    let (project_local, _worktree_id) = client_a.build_local_project("/dir", cx_a).await;
+    let worktree_a = project_a.read_with(cx_a, |p, cx| p.worktrees(cx).next().unwrap());
     let project_id = active_call_a
-        .update(cx_a, |call, cx| {
-            call.share_project(project_local.clone(), cx)
-        })
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
 
-    let project_remote = client_b.join_remote_project(project_id, cx_b).await;
-    client_a
-        .fs()
-        .set_branch_name(Path::new("/dir/.git"), Some("branch-1"));
+    // Join that worktree as clients B and C.
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let project_c = client_c.join_remote_project(project_id, cx_c).await;
 
-    // Wait for it to catch up to the new branch
-    executor.run_until_parked();
+    let worktree_b = project_b.read_with(cx_b, |p, cx| p.worktrees(cx).next().unwrap());
 
-    #[track_caller]
-    fn assert_branch(branch_name: Option<impl Into<String>>, project: &Project, cx: &App) {
-        let branch_name = branch_name.map(Into::into);
-        let repositories = project.repositories(cx).values().collect::<Vec<_>>();
-        assert_eq!(repositories.len(), 1);
-        let repository = repositories[0].clone();
-        assert_eq!(
-            repository
-                .read(cx)
-                .branch
-                .as_ref()
-                .map(|branch| branch.name().to_owned()),
-            branch_name
-        )
-    }
+    let worktree_c = project_c.read_with(cx_c, |p, cx| p.worktrees(cx).next().unwrap());
 
-    // Smoke test branch reading
+    // Open and edit a buffer as both guests B and C.
+    let buffer_b = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
+        })
+        .await
+        .unwrap();
+    let buffer_c = project_c
+        .update(cx_c, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
+        })
+        .await
+        .unwrap();
 
-    project_local.read_with(cx_a, |project, cx| {
-        assert_branch(Some("branch-1"), project, cx)
+    buffer_b.read_with(cx_b, |buffer, _| {
+        assert_eq!(buffer.language().unwrap().name(), "Rust");
     });
 
-    project_remote.read_with(cx_b, |project, cx| {
-        assert_branch(Some("branch-1"), project, cx)
+    buffer_c.read_with(cx_c, |buffer, _| {
+        assert_eq!(buffer.language().unwrap().name(), "Rust");
+    });
+    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "i-am-b, ")], None, cx));
+    buffer_c.update(cx_c, |buf, cx| buf.edit([(0..0, "i-am-c, ")], None, cx));
+
+    // Open and edit that buffer as the host.
+    let buffer_a = project_a
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("file1.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    executor.run_until_parked();
+
+    buffer_a.read_with(cx_a, |buf, _| assert_eq!(buf.text(), "i-am-c, i-am-b, "));
+    buffer_a.update(cx_a, |buf, cx| {
+        buf.edit([(buf.len()..buf.len(), "i-am-a")], None, cx)
+    });
+
+    executor.run_until_parked();
+
+    buffer_a.read_with(cx_a, |buf, _| {
+        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
+    });
+
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
+    });
+
+    buffer_c.read_with(cx_c, |buf, _| {
+        assert_eq!(buf.text(), "i-am-c, i-am-b, i-am-a");
+    });
+
+    // Edit the buffer as the host and concurrently save as guest B.
+    let save_b = project_b.update(cx_b, |project, cx| {
+        project.save_buffer(buffer_b.clone(), cx)
     });
+    buffer_a.update(cx_a, |buf, cx| buf.edit([(0..0, "hi-a, ")], None, cx));
+    save_b.await.unwrap();
+    assert_eq!(
+        client_a.fs().load("/a/file1.rs".as_ref()).await.unwrap(),
+        "hi-a, i-am-c, i-am-b, i-am-a"
+    );
+
+    executor.run_until_parked();
+
+    buffer_a.read_with(cx_a, |buf, _| assert!(!buf.is_dirty()));
+
+    buffer_b.read_with(cx_b, |buf, _| assert!(!buf.is_dirty()));
+
+    buffer_c.read_with(cx_c, |buf, _| assert!(!buf.is_dirty()));
 
+    // Make changes on host's file system, see those changes on guest worktrees.
     client_a
         .fs()
-        .set_branch_name(Path::new("/dir/.git"), Some("branch-2"));
-
-    // Wait for buffer_local_a to receive it
+        .rename(
+            path!("/a/file1.rs").as_ref(),
+            path!("/a/file1.js").as_ref(),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+    client_a
+        .fs()
+        .rename(
+            path!("/a/file2").as_ref(),
+            path!("/a/file3").as_ref(),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+    client_a
+        .fs()
+        .insert_file(path!("/a/file4"), "4".into())
+        .await;
     executor.run_until_parked();
 
-    // Smoke test branch reading
+    worktree_a.read_with(cx_a, |tree, _| {
+        assert_eq!(
+            tree.paths().collect::<Vec<_>>(),
+            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
+        )
+    });
 
-    project_local.read_with(cx_a, |project, cx| {
-        assert_branch(Some("branch-2"), project, cx)
+    worktree_b.read_with(cx_b, |tree, _| {
+        assert_eq!(
+            tree.paths().collect::<Vec<_>>(),
+            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
+        )
     });
 
-    project_remote.read_with(cx_b, |project, cx| {
-        assert_branch(Some("branch-2"), project, cx)
+    worktree_c.read_with(cx_c, |tree, _| {
+        assert_eq!(
+            tree.paths().collect::<Vec<_>>(),
+            [rel_path("file1.js"), rel_path("file3"), rel_path("file4")]
+        )
     });
 
-    let project_remote_c = client_c.join_remote_project(project_id, cx_c).await;
+    // Ensure buffer files are updated as well.
+
+    buffer_a.read_with(cx_a, |buffer, _| {
+        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
+        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
+    });
+
+    buffer_b.read_with(cx_b, |buffer, _| {
+        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
+        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
+    });
+
+    buffer_c.read_with(cx_c, |buffer, _| {
+        assert_eq!(buffer.file().unwrap().path().as_ref(), rel_path("file1.js"));
+        assert_eq!(buffer.language().unwrap().name(), "JavaScript");
+    });
+
+    let new_buffer_a = project_a
+        .update(cx_a, |p, cx| p.create_buffer(None, false, cx))
+        .await
+        .unwrap();
+
+    let new_buffer_id = new_buffer_a.read_with(cx_a, |buffer, _| buffer.remote_id());
+    let new_buffer_b = project_b
+        .update(cx_b, |p, cx| p.open_buffer_by_id(new_buffer_id, cx))
+        .await
+        .unwrap();
+
+    new_buffer_b.read_with(cx_b, |buffer, _| {
+        assert!(buffer.file().is_none());
+    });
+
+    new_buffer_a.update(cx_a, |buffer, cx| {
+        buffer.edit([(0..0, "ok")], None, cx);
+    });
+    project_a
+        .update(cx_a, |project, cx| {
+            let path = ProjectPath {
+                path: rel_path("file3.rs").into(),
+                worktree_id: worktree_a.read(cx).id(),
+            };
+
+            project.save_buffer_as(new_buffer_a.clone(), path, cx)
+        })
+        .await
+        .unwrap();
+
     executor.run_until_parked();
 
-    project_remote_c.read_with(cx_c, |project, cx| {
-        assert_branch(Some("branch-2"), project, cx)
+    new_buffer_b.read_with(cx_b, |buffer_b, _| {
+        assert_eq!(
+            buffer_b.file().unwrap().path().as_ref(),
+            rel_path("file3.rs")
+        );
+
+        new_buffer_a.read_with(cx_a, |buffer_a, _| {
+            assert_eq!(buffer_b.saved_mtime(), buffer_a.saved_mtime());
+            assert_eq!(buffer_b.saved_version(), buffer_a.saved_version());
+        });
     });
 }
 
-#[gpui::test]
-async fn test_git_status_sync(
+#[gpui::test(iterations = 10)]
+async fn test_git_diff_base_change(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
     client_a
         .fs()
         .insert_tree(
-            path!("/dir"),
+            "/dir",
             json!({
+            ".git": {},
+            "sub": {
                 ".git": {},
-                "a.txt": "a",
-                "b.txt": "b",
-                "c.txt": "c",
+                "b.txt": "
+                    one
+                    two
+                    three
+                ".unindent(),
+            },
+            "a.txt": "
+                    one
+                    two
+                    three
+                ".unindent(),
             }),
         )
         .await;
 
-    // Initially, a.txt is uncommitted, but present in the index,
-    // and b.txt is unmerged.
-    client_a.fs().set_head_for_repo(
-        path!("/dir/.git").as_ref(),
-        &[("b.txt", "B".into()), ("c.txt", "c".into())],
-        "deadbeef",
-    );
-    client_a.fs().set_index_for_repo(
-        path!("/dir/.git").as_ref(),
-        &[
-            ("a.txt", "".into()),
-            ("b.txt", "B".into()),
-            ("c.txt", "c".into()),
-        ],
-    );
-    client_a.fs().set_unmerged_paths_for_repo(
-        path!("/dir/.git").as_ref(),
-        &[(
-            repo_path("b.txt"),
-            UnmergedStatus {
-                first_head: UnmergedStatusCode::Updated,
-                second_head: UnmergedStatusCode::Deleted,
-            },
-        )],
-    );
-
-    const A_STATUS_START: FileStatus = FileStatus::Tracked(TrackedStatus {
-        index_status: StatusCode::Added,
-        worktree_status: StatusCode::Modified,
-    });
-    const B_STATUS_START: FileStatus = FileStatus::Unmerged(UnmergedStatus {
-        first_head: UnmergedStatusCode::Updated,
-        second_head: UnmergedStatusCode::Deleted,
-    });
-
-    let (project_local, _worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
+    let (project_local, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| {
             call.share_project(project_local.clone(), cx)
@@ -3055,544 +3303,409 @@ async fn test_git_status_sync(
 
     let project_remote = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Wait for it to catch up to the new status
-    executor.run_until_parked();
+    let staged_text = "
+        one
+        three
+    "
+    .unindent();
 
-    #[track_caller]
-    fn assert_status(file: &str, status: Option<FileStatus>, project: &Project, cx: &App) {
-        let file = repo_path(file);
-        let repos = project
-            .repositories(cx)
-            .values()
-            .cloned()
-            .collect::<Vec<_>>();
-        assert_eq!(repos.len(), 1);
-        let repo = repos.into_iter().next().unwrap();
-        assert_eq!(
-            repo.read(cx)
-                .status_for_path(&file)
-                .map(|entry| entry.status),
-            status
-        );
-    }
+    let committed_text = "
+        one
+        TWO
+        three
+    "
+    .unindent();
 
-    project_local.read_with(cx_a, |project, cx| {
-        assert_status("a.txt", Some(A_STATUS_START), project, cx);
-        assert_status("b.txt", Some(B_STATUS_START), project, cx);
-        assert_status("c.txt", None, project, cx);
-    });
-
-    project_remote.read_with(cx_b, |project, cx| {
-        assert_status("a.txt", Some(A_STATUS_START), project, cx);
-        assert_status("b.txt", Some(B_STATUS_START), project, cx);
-        assert_status("c.txt", None, project, cx);
-    });
+    let new_committed_text = "
+        one
+        TWO_HUNDRED
+        three
+    "
+    .unindent();
 
-    const A_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
-        index_status: StatusCode::Added,
-        worktree_status: StatusCode::Unmodified,
-    });
-    const B_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
-        index_status: StatusCode::Deleted,
-        worktree_status: StatusCode::Added,
-    });
-    const C_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
-        index_status: StatusCode::Unmodified,
-        worktree_status: StatusCode::Modified,
-    });
+    let new_staged_text = "
+        one
+        two
+    "
+    .unindent();
 
-    // Delete b.txt from the index, mark conflict as resolved,
-    // and modify c.txt in the working copy.
-    client_a.fs().set_index_for_repo(
-        path!("/dir/.git").as_ref(),
-        &[("a.txt", "a".into()), ("c.txt", "c".into())],
-    );
-    client_a
-        .fs()
-        .set_unmerged_paths_for_repo(path!("/dir/.git").as_ref(), &[]);
     client_a
         .fs()
-        .atomic_write(path!("/dir/c.txt").into(), "CC".into())
+        .set_index_for_repo(Path::new("/dir/.git"), &[("a.txt", staged_text.clone())]);
+    client_a.fs().set_head_for_repo(
+        Path::new("/dir/.git"),
+        &[("a.txt", committed_text.clone())],
+        "deadbeef",
+    );
+
+    // Create the buffer
+    let buffer_local_a = project_local
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+        })
         .await
         .unwrap();
-
-    // Wait for buffer_local_a to receive it
-    executor.run_until_parked();
-
-    // Smoke test status reading
-    project_local.read_with(cx_a, |project, cx| {
-        assert_status("a.txt", Some(A_STATUS_END), project, cx);
-        assert_status("b.txt", Some(B_STATUS_END), project, cx);
-        assert_status("c.txt", Some(C_STATUS_END), project, cx);
-    });
-
-    project_remote.read_with(cx_b, |project, cx| {
-        assert_status("a.txt", Some(A_STATUS_END), project, cx);
-        assert_status("b.txt", Some(B_STATUS_END), project, cx);
-        assert_status("c.txt", Some(C_STATUS_END), project, cx);
-    });
-
-    // And synchronization while joining
-    let project_remote_c = client_c.join_remote_project(project_id, cx_c).await;
-    executor.run_until_parked();
-
-    project_remote_c.read_with(cx_c, |project, cx| {
-        assert_status("a.txt", Some(A_STATUS_END), project, cx);
-        assert_status("b.txt", Some(B_STATUS_END), project, cx);
-        assert_status("c.txt", Some(C_STATUS_END), project, cx);
-    });
-
-    // Now remove the original git repository and check that collaborators are notified.
-    client_a
-        .fs()
-        .remove_dir(path!("/dir/.git").as_ref(), RemoveOptions::default())
+    let local_unstaged_diff_a = project_local
+        .update(cx_a, |p, cx| {
+            p.open_unstaged_diff(buffer_local_a.clone(), cx)
+        })
         .await
         .unwrap();
 
+    // Wait for it to catch up to the new diff
     executor.run_until_parked();
-    project_remote.update(cx_b, |project, cx| {
-        pretty_assertions::assert_eq!(
-            project.git_store().read(cx).repo_snapshots(cx),
-            HashMap::default()
+    local_unstaged_diff_a.read_with(cx_a, |diff, cx| {
+        let buffer = buffer_local_a.read(cx);
+        assert_eq!(
+            diff.base_text_string(cx).as_deref(),
+            Some(staged_text.as_str())
         );
-    });
-    project_remote_c.update(cx_c, |project, cx| {
-        pretty_assertions::assert_eq!(
-            project.git_store().read(cx).repo_snapshots(cx),
-            HashMap::default()
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
         );
     });
-}
-
-#[gpui::test(iterations = 10)]
-async fn test_fs_operations(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
 
-    client_a
-        .fs()
-        .insert_tree(
-            path!("/dir"),
-            json!({
-                "a.txt": "a-contents",
-                "b.txt": "b-contents",
-            }),
-        )
-        .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+    // Create remote buffer
+    let remote_buffer_a = project_remote
+        .update(cx_b, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+        })
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
-
-    let worktree_a = project_a.read_with(cx_a, |project, cx| project.worktrees(cx).next().unwrap());
-    let worktree_b = project_b.read_with(cx_b, |project, cx| project.worktrees(cx).next().unwrap());
-
-    let entry = project_b
-        .update(cx_b, |project, cx| {
-            project.create_entry((worktree_id, rel_path("c.txt")), false, cx)
+    let remote_unstaged_diff_a = project_remote
+        .update(cx_b, |p, cx| {
+            p.open_unstaged_diff(remote_buffer_a.clone(), cx)
         })
         .await
-        .unwrap()
-        .into_included()
         .unwrap();
 
-    worktree_a.read_with(cx_a, |worktree, _| {
+    // Wait remote buffer to catch up to the new diff
+    executor.run_until_parked();
+    remote_unstaged_diff_a.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_a.read(cx);
         assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [rel_path("a.txt"), rel_path("b.txt"), rel_path("c.txt")]
+            diff.base_text_string(cx).as_deref(),
+            Some(staged_text.as_str())
         );
-    });
-
-    worktree_b.read_with(cx_b, |worktree, _| {
-        assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [rel_path("a.txt"), rel_path("b.txt"), rel_path("c.txt")]
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
         );
     });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.rename_entry(entry.id, (worktree_id, rel_path("d.txt")).into(), cx)
+    // Open uncommitted changes on the guest, without opening them on the host first
+    let remote_uncommitted_diff_a = project_remote
+        .update(cx_b, |p, cx| {
+            p.open_uncommitted_diff(remote_buffer_a.clone(), cx)
         })
         .await
-        .unwrap()
-        .into_included()
         .unwrap();
-
-    worktree_a.read_with(cx_a, |worktree, _| {
+    executor.run_until_parked();
+    remote_uncommitted_diff_a.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_a.read(cx);
         assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [rel_path("a.txt"), rel_path("b.txt"), rel_path("d.txt")]
+            diff.base_text_string(cx).as_deref(),
+            Some(committed_text.as_str())
         );
-    });
-
-    worktree_b.read_with(cx_b, |worktree, _| {
-        assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["a.txt", "b.txt", "d.txt"]
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(
+                1..2,
+                "TWO\n",
+                "two\n",
+                DiffHunkStatus::modified(DiffHunkSecondaryStatus::HasSecondaryHunk),
+            )],
         );
     });
 
-    let dir_entry = project_b
-        .update(cx_b, |project, cx| {
-            project.create_entry((worktree_id, rel_path("DIR")), true, cx)
-        })
-        .await
-        .unwrap()
-        .into_included()
-        .unwrap();
+    // Update the index text of the open buffer
+    client_a.fs().set_index_for_repo(
+        Path::new("/dir/.git"),
+        &[("a.txt", new_staged_text.clone())],
+    );
+    client_a.fs().set_head_for_repo(
+        Path::new("/dir/.git"),
+        &[("a.txt", new_committed_text.clone())],
+        "deadbeef",
+    );
 
-    worktree_a.read_with(cx_a, |worktree, _| {
+    // Wait for buffer_local_a to receive it
+    executor.run_until_parked();
+    local_unstaged_diff_a.read_with(cx_a, |diff, cx| {
+        let buffer = buffer_local_a.read(cx);
         assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["DIR", "a.txt", "b.txt", "d.txt"]
+            diff.base_text_string(cx).as_deref(),
+            Some(new_staged_text.as_str())
+        );
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
         );
     });
 
-    worktree_b.read_with(cx_b, |worktree, _| {
+    // Guest receives index text update
+    remote_unstaged_diff_a.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_a.read(cx);
         assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["DIR", "a.txt", "b.txt", "d.txt"]
+            diff.base_text_string(cx).as_deref(),
+            Some(new_staged_text.as_str())
+        );
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
         );
     });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.create_entry((worktree_id, rel_path("DIR/e.txt")), false, cx)
-        })
-        .await
-        .unwrap()
-        .into_included()
-        .unwrap();
+    remote_uncommitted_diff_a.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_a.read(cx);
+        assert_eq!(
+            diff.base_text_string(cx).as_deref(),
+            Some(new_committed_text.as_str())
+        );
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(
+                1..2,
+                "TWO_HUNDRED\n",
+                "two\n",
+                DiffHunkStatus::modified(DiffHunkSecondaryStatus::OverlapsWithSecondaryHunk),
+            )],
+        );
+    });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.create_entry((worktree_id, rel_path("DIR/SUBDIR")), true, cx)
+    // Nested git dir
+    let staged_text = "
+        one
+        three
+    "
+    .unindent();
+
+    let new_staged_text = "
+        one
+        two
+    "
+    .unindent();
+
+    client_a.fs().set_index_for_repo(
+        Path::new("/dir/sub/.git"),
+        &[("b.txt", staged_text.clone())],
+    );
+
+    // Create the buffer
+    let buffer_local_b = project_local
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("sub/b.txt")), cx)
         })
         .await
-        .unwrap()
-        .into_included()
         .unwrap();
-
-    project_b
-        .update(cx_b, |project, cx| {
-            project.create_entry((worktree_id, rel_path("DIR/SUBDIR/f.txt")), false, cx)
+    let local_unstaged_diff_b = project_local
+        .update(cx_a, |p, cx| {
+            p.open_unstaged_diff(buffer_local_b.clone(), cx)
         })
         .await
-        .unwrap()
-        .into_included()
         .unwrap();
 
-    worktree_a.read_with(cx_a, |worktree, _| {
+    // Wait for it to catch up to the new diff
+    executor.run_until_parked();
+    local_unstaged_diff_b.read_with(cx_a, |diff, cx| {
+        let buffer = buffer_local_b.read(cx);
         assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [
-                rel_path("DIR"),
-                rel_path("DIR/SUBDIR"),
-                rel_path("DIR/SUBDIR/f.txt"),
-                rel_path("DIR/e.txt"),
-                rel_path("a.txt"),
-                rel_path("b.txt"),
-                rel_path("d.txt")
-            ]
+            diff.base_text_string(cx).as_deref(),
+            Some(staged_text.as_str())
         );
-    });
-
-    worktree_b.read_with(cx_b, |worktree, _| {
-        assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [
-                rel_path("DIR"),
-                rel_path("DIR/SUBDIR"),
-                rel_path("DIR/SUBDIR/f.txt"),
-                rel_path("DIR/e.txt"),
-                rel_path("a.txt"),
-                rel_path("b.txt"),
-                rel_path("d.txt")
-            ]
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &diff.base_text_string(cx).unwrap(),
+            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
         );
     });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.copy_entry(
-                entry.id,
-                (worktree_b.read(cx).id(), rel_path("f.txt")).into(),
-                cx,
-            )
+    // Create remote buffer
+    let remote_buffer_b = project_remote
+        .update(cx_b, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("sub/b.txt")), cx)
         })
         .await
-        .unwrap()
         .unwrap();
-
-    worktree_a.read_with(cx_a, |worktree, _| {
-        assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [
-                rel_path("DIR"),
-                rel_path("DIR/SUBDIR"),
-                rel_path("DIR/SUBDIR/f.txt"),
-                rel_path("DIR/e.txt"),
-                rel_path("a.txt"),
-                rel_path("b.txt"),
-                rel_path("d.txt"),
-                rel_path("f.txt")
-            ]
-        );
-    });
-
-    worktree_b.read_with(cx_b, |worktree, _| {
-        assert_eq!(
-            worktree.paths().collect::<Vec<_>>(),
-            [
-                rel_path("DIR"),
-                rel_path("DIR/SUBDIR"),
-                rel_path("DIR/SUBDIR/f.txt"),
-                rel_path("DIR/e.txt"),
-                rel_path("a.txt"),
-                rel_path("b.txt"),
-                rel_path("d.txt"),
-                rel_path("f.txt")
-            ]
-        );
-    });
-
-    project_b
-        .update(cx_b, |project, cx| {
-            project.delete_entry(dir_entry.id, false, cx).unwrap()
+    let remote_unstaged_diff_b = project_remote
+        .update(cx_b, |p, cx| {
+            p.open_unstaged_diff(remote_buffer_b.clone(), cx)
         })
         .await
         .unwrap();
-    executor.run_until_parked();
 
-    worktree_a.read_with(cx_a, |worktree, _| {
+    executor.run_until_parked();
+    remote_unstaged_diff_b.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_b.read(cx);
         assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["a.txt", "b.txt", "d.txt", "f.txt"]
+            diff.base_text_string(cx).as_deref(),
+            Some(staged_text.as_str())
         );
-    });
-
-    worktree_b.read_with(cx_b, |worktree, _| {
-        assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["a.txt", "b.txt", "d.txt", "f.txt"]
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &staged_text,
+            &[(1..2, "", "two\n", DiffHunkStatus::added_none())],
         );
     });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.delete_entry(entry.id, false, cx).unwrap()
-        })
-        .await
-        .unwrap();
+    // Updatet the staged text
+    client_a.fs().set_index_for_repo(
+        Path::new("/dir/sub/.git"),
+        &[("b.txt", new_staged_text.clone())],
+    );
 
-    worktree_a.read_with(cx_a, |worktree, _| {
+    // Wait for buffer_local_b to receive it
+    executor.run_until_parked();
+    local_unstaged_diff_b.read_with(cx_a, |diff, cx| {
+        let buffer = buffer_local_b.read(cx);
         assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["a.txt", "b.txt", "f.txt"]
+            diff.base_text_string(cx).as_deref(),
+            Some(new_staged_text.as_str())
+        );
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &new_staged_text,
+            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
         );
     });
 
-    worktree_b.read_with(cx_b, |worktree, _| {
+    remote_unstaged_diff_b.read_with(cx_b, |diff, cx| {
+        let buffer = remote_buffer_b.read(cx);
         assert_eq!(
-            worktree
-                .paths()
-                .map(|p| p.as_unix_str())
-                .collect::<Vec<_>>(),
-            ["a.txt", "b.txt", "f.txt"]
+            diff.base_text_string(cx).as_deref(),
+            Some(new_staged_text.as_str())
+        );
+        assert_hunks(
+            diff.snapshot(cx).hunks_in_row_range(0..4, buffer),
+            buffer,
+            &new_staged_text,
+            &[(2..3, "", "three\n", DiffHunkStatus::added_none())],
         );
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_local_settings(
+async fn test_git_branch_name(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    // As client A, open a project that contains some local settings files
     client_a
         .fs()
         .insert_tree(
             "/dir",
             json!({
-                ".zed": {
-                    "settings.json": r#"{ "tab_size": 2 }"#
-                },
-                "a": {
-                    ".zed": {
-                        "settings.json": r#"{ "tab_size": 8 }"#
-                    },
-                    "a.txt": "a-contents",
-                },
-                "b": {
-                    "b.txt": "b-contents",
-                }
+            ".git": {},
             }),
         )
         .await;
-    let (project_a, _) = client_a.build_local_project("/dir", cx_a).await;
-    executor.run_until_parked();
+
+    let (project_local, _worktree_id) = client_a.build_local_project("/dir", cx_a).await;
     let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .update(cx_a, |call, cx| {
+            call.share_project(project_local.clone(), cx)
+        })
         .await
         .unwrap();
-    executor.run_until_parked();
 
-    // As client B, join that project and observe the local settings.
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let project_remote = client_b.join_remote_project(project_id, cx_b).await;
+    client_a
+        .fs()
+        .set_branch_name(Path::new("/dir/.git"), Some("branch-1"));
 
-    let worktree_b = project_b.read_with(cx_b, |project, cx| project.worktrees(cx).next().unwrap());
+    // Wait for it to catch up to the new branch
     executor.run_until_parked();
-    cx_b.read(|cx| {
-        let store = cx.global::<SettingsStore>();
+
+    #[track_caller]
+    fn assert_branch(branch_name: Option<impl Into<String>>, project: &Project, cx: &App) {
+        let branch_name = branch_name.map(Into::into);
+        let repositories = project.repositories(cx).values().collect::<Vec<_>>();
+        assert_eq!(repositories.len(), 1);
+        let repository = repositories[0].clone();
         assert_eq!(
-            store
-                .local_settings(worktree_b.read(cx).id())
-                .map(|(path, content)| (
-                    path,
-                    content.all_languages.defaults.tab_size.map(Into::into)
-                ))
-                .collect::<Vec<_>>(),
-            &[
-                (rel_path("").into(), Some(2)),
-                (rel_path("a").into(), Some(8)),
-            ]
+            repository
+                .read(cx)
+                .branch
+                .as_ref()
+                .map(|branch| branch.name().to_owned()),
+            branch_name
         )
+    }
+
+    // Smoke test branch reading
+
+    project_local.read_with(cx_a, |project, cx| {
+        assert_branch(Some("branch-1"), project, cx)
     });
 
-    // As client A, update a settings file. As Client B, see the changed settings.
-    client_a
-        .fs()
-        .insert_file("/dir/.zed/settings.json", r#"{}"#.into())
-        .await;
-    executor.run_until_parked();
-    cx_b.read(|cx| {
-        let store = cx.global::<SettingsStore>();
-        assert_eq!(
-            store
-                .local_settings(worktree_b.read(cx).id())
-                .map(|(path, content)| (
-                    path,
-                    content.all_languages.defaults.tab_size.map(Into::into)
-                ))
-                .collect::<Vec<_>>(),
-            &[(rel_path("").into(), None), (rel_path("a").into(), Some(8)),]
-        )
+    project_remote.read_with(cx_b, |project, cx| {
+        assert_branch(Some("branch-1"), project, cx)
     });
 
-    // As client A, create and remove some settings files. As client B, see the changed settings.
-    client_a
-        .fs()
-        .remove_file("/dir/.zed/settings.json".as_ref(), Default::default())
-        .await
-        .unwrap();
-    client_a
-        .fs()
-        .create_dir("/dir/b/.zed".as_ref())
-        .await
-        .unwrap();
     client_a
         .fs()
-        .insert_file("/dir/b/.zed/settings.json", r#"{"tab_size": 4}"#.into())
-        .await;
+        .set_branch_name(Path::new("/dir/.git"), Some("branch-2"));
+
+    // Wait for buffer_local_a to receive it
     executor.run_until_parked();
-    cx_b.read(|cx| {
-        let store = cx.global::<SettingsStore>();
-        assert_eq!(
-            store
-                .local_settings(worktree_b.read(cx).id())
-                .map(|(path, content)| (
-                    path,
-                    content.all_languages.defaults.tab_size.map(Into::into)
-                ))
-                .collect::<Vec<_>>(),
-            &[
-                (rel_path("a").into(), Some(8)),
-                (rel_path("b").into(), Some(4)),
-            ]
-        )
+
+    // Smoke test branch reading
+
+    project_local.read_with(cx_a, |project, cx| {
+        assert_branch(Some("branch-2"), project, cx)
     });
 
-    // As client B, disconnect.
-    server.forbid_connections();
-    server.disconnect_client(client_b.peer_id().unwrap());
+    project_remote.read_with(cx_b, |project, cx| {
+        assert_branch(Some("branch-2"), project, cx)
+    });
 
-    // As client A, change and remove settings files while client B is disconnected.
-    client_a
-        .fs()
-        .insert_file("/dir/a/.zed/settings.json", r#"{"hard_tabs":true}"#.into())
-        .await;
-    client_a
-        .fs()
-        .remove_file("/dir/b/.zed/settings.json".as_ref(), Default::default())
-        .await
-        .unwrap();
+    let project_remote_c = client_c.join_remote_project(project_id, cx_c).await;
     executor.run_until_parked();
 
-    // As client B, reconnect and see the changed settings.
-    server.allow_connections();
-    executor.advance_clock(RECEIVE_TIMEOUT);
-    cx_b.read(|cx| {
-        let store = cx.global::<SettingsStore>();
-        assert_eq!(
-            store
-                .local_settings(worktree_b.read(cx).id())
-                .map(|(path, content)| (path, content.all_languages.defaults.hard_tabs))
-                .collect::<Vec<_>>(),
-            &[(rel_path("a").into(), Some(true))],
-        )
+    project_remote_c.read_with(cx_c, |project, cx| {
+        assert_branch(Some("branch-2"), project, cx)
     });
 }
 
-#[gpui::test(iterations = 10)]
-async fn test_buffer_conflict_after_save(
+#[gpui::test]
+async fn test_git_status_sync(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
@@ -3601,216 +3714,170 @@ async fn test_buffer_conflict_after_save(
         .insert_tree(
             path!("/dir"),
             json!({
-                "a.txt": "a-contents",
+                ".git": {},
+                "a.txt": "a",
+                "b.txt": "b",
+                "c.txt": "c",
             }),
         )
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
-        .await
-        .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
-
-    // Open a buffer as client B
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
-        })
-        .await
-        .unwrap();
 
-    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "world ")], None, cx));
+    // Initially, a.txt is uncommitted, but present in the index,
+    // and b.txt is unmerged.
+    client_a.fs().set_head_for_repo(
+        path!("/dir/.git").as_ref(),
+        &[("b.txt", "B".into()), ("c.txt", "c".into())],
+        "deadbeef",
+    );
+    client_a.fs().set_index_for_repo(
+        path!("/dir/.git").as_ref(),
+        &[
+            ("a.txt", "".into()),
+            ("b.txt", "B".into()),
+            ("c.txt", "c".into()),
+        ],
+    );
+    client_a.fs().set_unmerged_paths_for_repo(
+        path!("/dir/.git").as_ref(),
+        &[(
+            repo_path("b.txt"),
+            UnmergedStatus {
+                first_head: UnmergedStatusCode::Updated,
+                second_head: UnmergedStatusCode::Deleted,
+            },
+        )],
+    );
 
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert!(buf.is_dirty());
-        assert!(!buf.has_conflict());
+    const A_STATUS_START: FileStatus = FileStatus::Tracked(TrackedStatus {
+        index_status: StatusCode::Added,
+        worktree_status: StatusCode::Modified,
+    });
+    const B_STATUS_START: FileStatus = FileStatus::Unmerged(UnmergedStatus {
+        first_head: UnmergedStatusCode::Updated,
+        second_head: UnmergedStatusCode::Deleted,
     });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.save_buffer(buffer_b.clone(), cx)
+    let (project_local, _worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| {
+            call.share_project(project_local.clone(), cx)
         })
         .await
         .unwrap();
 
-    buffer_b.read_with(cx_b, |buffer_b, _| assert!(!buffer_b.is_dirty()));
+    let project_remote = client_b.join_remote_project(project_id, cx_b).await;
 
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert!(!buf.has_conflict());
-    });
+    // Wait for it to catch up to the new status
+    executor.run_until_parked();
 
-    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "hello ")], None, cx));
+    #[track_caller]
+    fn assert_status(file: &str, status: Option<FileStatus>, project: &Project, cx: &App) {
+        let file = repo_path(file);
+        let repos = project
+            .repositories(cx)
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(repos.len(), 1);
+        let repo = repos.into_iter().next().unwrap();
+        assert_eq!(
+            repo.read(cx)
+                .status_for_path(&file)
+                .map(|entry| entry.status),
+            status
+        );
+    }
 
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert!(buf.is_dirty());
-        assert!(!buf.has_conflict());
+    project_local.read_with(cx_a, |project, cx| {
+        assert_status("a.txt", Some(A_STATUS_START), project, cx);
+        assert_status("b.txt", Some(B_STATUS_START), project, cx);
+        assert_status("c.txt", None, project, cx);
     });
-}
 
-#[gpui::test(iterations = 10)]
-async fn test_buffer_reloading(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
+    project_remote.read_with(cx_b, |project, cx| {
+        assert_status("a.txt", Some(A_STATUS_START), project, cx);
+        assert_status("b.txt", Some(B_STATUS_START), project, cx);
+        assert_status("c.txt", None, project, cx);
+    });
 
-    client_a
-        .fs()
-        .insert_tree(
-            path!("/dir"),
-            json!({
-                "a.txt": "a\nb\nc",
-            }),
-        )
-        .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
-        .await
-        .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
-
-    // Open a buffer as client B
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
-        })
-        .await
-        .unwrap();
-
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert!(!buf.is_dirty());
-        assert!(!buf.has_conflict());
-        assert_eq!(buf.line_ending(), LineEnding::Unix);
+    const A_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
+        index_status: StatusCode::Added,
+        worktree_status: StatusCode::Unmodified,
+    });
+    const B_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
+        index_status: StatusCode::Deleted,
+        worktree_status: StatusCode::Added,
+    });
+    const C_STATUS_END: FileStatus = FileStatus::Tracked(TrackedStatus {
+        index_status: StatusCode::Unmodified,
+        worktree_status: StatusCode::Modified,
     });
 
-    let new_contents = Rope::from("d\ne\nf");
+    // Delete b.txt from the index, mark conflict as resolved,
+    // and modify c.txt in the working copy.
+    client_a.fs().set_index_for_repo(
+        path!("/dir/.git").as_ref(),
+        &[("a.txt", "a".into()), ("c.txt", "c".into())],
+    );
     client_a
         .fs()
-        .save(
-            path!("/dir/a.txt").as_ref(),
-            &new_contents,
-            LineEnding::Windows,
-        )
-        .await
-        .unwrap();
-
-    executor.run_until_parked();
-
-    buffer_b.read_with(cx_b, |buf, _| {
-        assert_eq!(buf.text(), new_contents.to_string());
-        assert!(!buf.is_dirty());
-        assert!(!buf.has_conflict());
-        assert_eq!(buf.line_ending(), LineEnding::Windows);
-    });
-}
-
-#[gpui::test(iterations = 10)]
-async fn test_editing_while_guest_opens_buffer(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
-
+        .set_unmerged_paths_for_repo(path!("/dir/.git").as_ref(), &[]);
     client_a
         .fs()
-        .insert_tree(path!("/dir"), json!({ "a.txt": "a-contents" }))
-        .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .atomic_write(path!("/dir/c.txt").into(), "CC".into())
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Open a buffer as client A
-    let buffer_a = project_a
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
-        })
-        .await
-        .unwrap();
+    // Wait for buffer_local_a to receive it
+    executor.run_until_parked();
 
-    // Start opening the same buffer as client B
-    let open_buffer = project_b.update(cx_b, |p, cx| {
-        p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+    // Smoke test status reading
+    project_local.read_with(cx_a, |project, cx| {
+        assert_status("a.txt", Some(A_STATUS_END), project, cx);
+        assert_status("b.txt", Some(B_STATUS_END), project, cx);
+        assert_status("c.txt", Some(C_STATUS_END), project, cx);
     });
-    let buffer_b = cx_b.executor().spawn(open_buffer);
 
-    // Edit the buffer as client A while client B is still opening it.
-    cx_b.executor().simulate_random_delay().await;
-    buffer_a.update(cx_a, |buf, cx| buf.edit([(0..0, "X")], None, cx));
-    cx_b.executor().simulate_random_delay().await;
-    buffer_a.update(cx_a, |buf, cx| buf.edit([(1..1, "Y")], None, cx));
+    project_remote.read_with(cx_b, |project, cx| {
+        assert_status("a.txt", Some(A_STATUS_END), project, cx);
+        assert_status("b.txt", Some(B_STATUS_END), project, cx);
+        assert_status("c.txt", Some(C_STATUS_END), project, cx);
+    });
 
-    let text = buffer_a.read_with(cx_a, |buf, _| buf.text());
-    let buffer_b = buffer_b.await.unwrap();
+    // And synchronization while joining
+    let project_remote_c = client_c.join_remote_project(project_id, cx_c).await;
     executor.run_until_parked();
 
-    buffer_b.read_with(cx_b, |buf, _| assert_eq!(buf.text(), text));
-}
-
-#[gpui::test(iterations = 10)]
-async fn test_leaving_worktree_while_opening_buffer(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
+    project_remote_c.read_with(cx_c, |project, cx| {
+        assert_status("a.txt", Some(A_STATUS_END), project, cx);
+        assert_status("b.txt", Some(B_STATUS_END), project, cx);
+        assert_status("c.txt", Some(C_STATUS_END), project, cx);
+    });
 
+    // Now remove the original git repository and check that collaborators are notified.
     client_a
         .fs()
-        .insert_tree("/dir", json!({ "a.txt": "a-contents" }))
-        .await;
-    let (project_a, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .remove_dir(path!("/dir/.git").as_ref(), RemoveOptions::default())
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // See that a guest has joined as client A.
     executor.run_until_parked();
-
-    project_a.read_with(cx_a, |p, _| assert_eq!(p.collaborators().len(), 1));
-
-    // Begin opening a buffer as client B, but leave the project before the open completes.
-    let open_buffer = project_b.update(cx_b, |p, cx| {
-        p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+    project_remote.update(cx_b, |project, cx| {
+        pretty_assertions::assert_eq!(
+            project.git_store().read(cx).repo_snapshots(cx),
+            HashMap::default()
+        );
+    });
+    project_remote_c.update(cx_c, |project, cx| {
+        pretty_assertions::assert_eq!(
+            project.git_store().read(cx).repo_snapshots(cx),
+            HashMap::default()
+        );
     });
-    let buffer_b = cx_b.executor().spawn(open_buffer);
-    cx_b.update(|_| drop(project_b));
-    drop(buffer_b);
-
-    // See that the guest has left.
-    executor.run_until_parked();
-
-    project_a.read_with(cx_a, |p, _| assert!(p.collaborators().is_empty()));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_canceling_buffer_opening(
+async fn test_fs_operations(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -3826,479 +3893,409 @@ async fn test_canceling_buffer_opening(
     client_a
         .fs()
         .insert_tree(
-            "/dir",
+            path!("/dir"),
             json!({
-                "a.txt": "abc",
+                "a.txt": "a-contents",
+                "b.txt": "b-contents",
             }),
         )
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    let buffer_a = project_a
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+    let worktree_a = project_a.read_with(cx_a, |project, cx| project.worktrees(cx).next().unwrap());
+    let worktree_b = project_b.read_with(cx_b, |project, cx| project.worktrees(cx).next().unwrap());
+
+    let entry = project_b
+        .update(cx_b, |project, cx| {
+            project.create_entry((worktree_id, rel_path("c.txt")), false, cx)
         })
         .await
+        .unwrap()
+        .into_included()
         .unwrap();
 
-    // Open a buffer as client B but cancel after a random amount of time.
-    let buffer_b = project_b.update(cx_b, |p, cx| {
-        p.open_buffer_by_id(buffer_a.read_with(cx_a, |a, _| a.remote_id()), cx)
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [rel_path("a.txt"), rel_path("b.txt"), rel_path("c.txt")]
+        );
     });
-    executor.simulate_random_delay().await;
-    drop(buffer_b);
 
-    // Try opening the same buffer again as client B, and ensure we can
-    // still do it despite the cancellation above.
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_by_id(buffer_a.read_with(cx_a, |a, _| a.remote_id()), cx)
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [rel_path("a.txt"), rel_path("b.txt"), rel_path("c.txt")]
+        );
+    });
+
+    project_b
+        .update(cx_b, |project, cx| {
+            project.rename_entry(entry.id, (worktree_id, rel_path("d.txt")).into(), cx)
         })
         .await
+        .unwrap()
+        .into_included()
         .unwrap();
 
-    buffer_b.read_with(cx_b, |buf, _| assert_eq!(buf.text(), "abc"));
-}
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [rel_path("a.txt"), rel_path("b.txt"), rel_path("d.txt")]
+        );
+    });
 
-#[gpui::test(iterations = 10)]
-async fn test_leaving_project(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
-    server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "d.txt"]
+        );
+    });
 
-    client_a
-        .fs()
-        .insert_tree(
-            "/a",
-            json!({
-                "a.txt": "a-contents",
-                "b.txt": "b-contents",
-            }),
-        )
-        .await;
-    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+    let dir_entry = project_b
+        .update(cx_b, |project, cx| {
+            project.create_entry((worktree_id, rel_path("DIR")), true, cx)
+        })
         .await
+        .unwrap()
+        .into_included()
         .unwrap();
-    let project_b1 = client_b.join_remote_project(project_id, cx_b).await;
-    let project_c = client_c.join_remote_project(project_id, cx_c).await;
 
-    // Client A sees that a guest has joined.
-    executor.run_until_parked();
-
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
-    });
-
-    project_b1.read_with(cx_b, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["DIR", "a.txt", "b.txt", "d.txt"]
+        );
     });
 
-    project_c.read_with(cx_c, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["DIR", "a.txt", "b.txt", "d.txt"]
+        );
     });
 
-    // Client B opens a buffer.
-    let buffer_b1 = project_b1
+    project_b
         .update(cx_b, |project, cx| {
-            let worktree_id = project.worktrees(cx).next().unwrap().read(cx).id();
-            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
+            project.create_entry((worktree_id, rel_path("DIR/e.txt")), false, cx)
         })
         .await
+        .unwrap()
+        .into_included()
         .unwrap();
 
-    buffer_b1.read_with(cx_b, |buffer, _| assert_eq!(buffer.text(), "a-contents"));
+    project_b
+        .update(cx_b, |project, cx| {
+            project.create_entry((worktree_id, rel_path("DIR/SUBDIR")), true, cx)
+        })
+        .await
+        .unwrap()
+        .into_included()
+        .unwrap();
 
-    // Drop client B's project and ensure client A and client C observe client B leaving.
-    cx_b.update(|_| drop(project_b1));
-    executor.run_until_parked();
+    project_b
+        .update(cx_b, |project, cx| {
+            project.create_entry((worktree_id, rel_path("DIR/SUBDIR/f.txt")), false, cx)
+        })
+        .await
+        .unwrap()
+        .into_included()
+        .unwrap();
 
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 1);
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [
+                rel_path("DIR"),
+                rel_path("DIR/SUBDIR"),
+                rel_path("DIR/SUBDIR/f.txt"),
+                rel_path("DIR/e.txt"),
+                rel_path("a.txt"),
+                rel_path("b.txt"),
+                rel_path("d.txt")
+            ]
+        );
     });
 
-    project_c.read_with(cx_c, |project, _| {
-        assert_eq!(project.collaborators().len(), 1);
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [
+                rel_path("DIR"),
+                rel_path("DIR/SUBDIR"),
+                rel_path("DIR/SUBDIR/f.txt"),
+                rel_path("DIR/e.txt"),
+                rel_path("a.txt"),
+                rel_path("b.txt"),
+                rel_path("d.txt")
+            ]
+        );
     });
 
-    // Client B re-joins the project and can open buffers as before.
-    let project_b2 = client_b.join_remote_project(project_id, cx_b).await;
-    executor.run_until_parked();
-
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
-    });
+    project_b
+        .update(cx_b, |project, cx| {
+            project.copy_entry(
+                entry.id,
+                (worktree_b.read(cx).id(), rel_path("f.txt")).into(),
+                cx,
+            )
+        })
+        .await
+        .unwrap()
+        .unwrap();
 
-    project_b2.read_with(cx_b, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [
+                rel_path("DIR"),
+                rel_path("DIR/SUBDIR"),
+                rel_path("DIR/SUBDIR/f.txt"),
+                rel_path("DIR/e.txt"),
+                rel_path("a.txt"),
+                rel_path("b.txt"),
+                rel_path("d.txt"),
+                rel_path("f.txt")
+            ]
+        );
     });
 
-    project_c.read_with(cx_c, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree.paths().collect::<Vec<_>>(),
+            [
+                rel_path("DIR"),
+                rel_path("DIR/SUBDIR"),
+                rel_path("DIR/SUBDIR/f.txt"),
+                rel_path("DIR/e.txt"),
+                rel_path("a.txt"),
+                rel_path("b.txt"),
+                rel_path("d.txt"),
+                rel_path("f.txt")
+            ]
+        );
     });
 
-    let buffer_b2 = project_b2
+    project_b
         .update(cx_b, |project, cx| {
-            let worktree_id = project.worktrees(cx).next().unwrap().read(cx).id();
-            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
+            project.delete_entry(dir_entry.id, false, cx).unwrap()
         })
         .await
         .unwrap();
+    executor.run_until_parked();
 
-    buffer_b2.read_with(cx_b, |buffer, _| assert_eq!(buffer.text(), "a-contents"));
-
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 2);
-    });
-
-    // Drop client B's connection and ensure client A and client C observe client B leaving.
-    client_b.disconnect(&cx_b.to_async());
-    executor.advance_clock(RECONNECT_TIMEOUT);
-
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 1);
-    });
-
-    project_b2.read_with(cx_b, |project, cx| {
-        assert!(project.is_disconnected(cx));
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "d.txt", "f.txt"]
+        );
     });
 
-    project_c.read_with(cx_c, |project, _| {
-        assert_eq!(project.collaborators().len(), 1);
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "d.txt", "f.txt"]
+        );
     });
 
-    // Client B can't join the project, unless they re-join the room.
-    cx_b.spawn(|cx| {
-        Project::in_room(
-            project_id,
-            client_b.app_state.client.clone(),
-            client_b.user_store().clone(),
-            client_b.language_registry().clone(),
-            FakeFs::new(cx.background_executor().clone()),
-            cx,
-        )
-    })
-    .await
-    .unwrap_err();
-
-    // Simulate connection loss for client C and ensure client A observes client C leaving the project.
-    client_c.wait_for_current_user(cx_c).await;
-    server.forbid_connections();
-    server.disconnect_client(client_c.peer_id().unwrap());
-    executor.advance_clock(RECEIVE_TIMEOUT + RECONNECT_TIMEOUT);
-    executor.run_until_parked();
-
-    project_a.read_with(cx_a, |project, _| {
-        assert_eq!(project.collaborators().len(), 0);
-    });
+    project_b
+        .update(cx_b, |project, cx| {
+            project.delete_entry(entry.id, false, cx).unwrap()
+        })
+        .await
+        .unwrap();
 
-    project_b2.read_with(cx_b, |project, cx| {
-        assert!(project.is_disconnected(cx));
+    worktree_a.read_with(cx_a, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "f.txt"]
+        );
     });
 
-    project_c.read_with(cx_c, |project, cx| {
-        assert!(project.is_disconnected(cx));
+    worktree_b.read_with(cx_b, |worktree, _| {
+        assert_eq!(
+            worktree
+                .paths()
+                .map(|p| p.as_unix_str())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "f.txt"]
+        );
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_collaborating_with_diagnostics(
+async fn test_local_settings(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    client_a.language_registry().add(Arc::new(Language::new(
-        LanguageConfig {
-            name: "Rust".into(),
-            matcher: LanguageMatcher {
-                path_suffixes: vec!["rs".to_string()],
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        Some(tree_sitter_rust::LANGUAGE.into()),
-    )));
-    let mut fake_language_servers = client_a
-        .language_registry()
-        .register_fake_lsp("Rust", Default::default());
-
-    // Share a project as client A
+    // As client A, open a project that contains some local settings files
     client_a
         .fs()
         .insert_tree(
-            path!("/a"),
+            "/dir",
             json!({
-                "a.rs": "let one = two",
-                "other.rs": "",
+                ".zed": {
+                    "settings.json": r#"{ "tab_size": 2 }"#
+                },
+                "a": {
+                    ".zed": {
+                        "settings.json": r#"{ "tab_size": 8 }"#
+                    },
+                    "a.txt": "a-contents",
+                },
+                "b": {
+                    "b.txt": "b-contents",
+                }
             }),
         )
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
-
-    // Cause the language server to start.
-    let _buffer = project_a
-        .update(cx_a, |project, cx| {
-            project.open_local_buffer_with_lsp(path!("/a/other.rs"), cx)
-        })
-        .await
-        .unwrap();
-
-    // Simulate a language server reporting errors for a file.
-    let mut fake_language_server = fake_language_servers.next().await.unwrap();
-    fake_language_server
-        .receive_notification::<lsp::notification::DidOpenTextDocument>()
-        .await;
-    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
-        lsp::PublishDiagnosticsParams {
-            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
-            version: None,
-            diagnostics: vec![lsp::Diagnostic {
-                severity: Some(lsp::DiagnosticSeverity::WARNING),
-                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
-                message: "message 0".to_string(),
-                ..Default::default()
-            }],
-        },
-    );
-
-    // Client A shares the project and, simultaneously, the language server
-    // publishes a diagnostic. This is done to ensure that the server always
-    // observes the latest diagnostics for a worktree.
+    let (project_a, _) = client_a.build_local_project("/dir", cx_a).await;
+    executor.run_until_parked();
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
-        lsp::PublishDiagnosticsParams {
-            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
-            version: None,
-            diagnostics: vec![lsp::Diagnostic {
-                severity: Some(lsp::DiagnosticSeverity::ERROR),
-                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
-                message: "message 1".to_string(),
-                ..Default::default()
-            }],
-        },
-    );
+    executor.run_until_parked();
 
-    // Join the worktree as client B.
+    // As client B, join that project and observe the local settings.
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Wait for server to see the diagnostics update.
+    let worktree_b = project_b.read_with(cx_b, |project, cx| project.worktrees(cx).next().unwrap());
     executor.run_until_parked();
-
-    // Ensure client B observes the new diagnostics.
-
-    project_b.read_with(cx_b, |project, cx| {
+    cx_b.read(|cx| {
+        let store = cx.global::<SettingsStore>();
         assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            &[(
-                ProjectPath {
-                    worktree_id,
-                    path: rel_path("a.rs").into(),
-                },
-                LanguageServerId(0),
-                DiagnosticSummary {
-                    error_count: 1,
-                    warning_count: 0,
-                },
-            )]
+            store
+                .local_settings(worktree_b.read(cx).id())
+                .map(|(path, content)| (
+                    path,
+                    content.all_languages.defaults.tab_size.map(Into::into)
+                ))
+                .collect::<Vec<_>>(),
+            &[
+                (rel_path("").into(), Some(2)),
+                (rel_path("a").into(), Some(8)),
+            ]
         )
     });
 
-    // Join project as client C and observe the diagnostics.
-    let project_c = client_c.join_remote_project(project_id, cx_c).await;
+    // As client A, update a settings file. As Client B, see the changed settings.
+    client_a
+        .fs()
+        .insert_file("/dir/.zed/settings.json", r#"{}"#.into())
+        .await;
     executor.run_until_parked();
-    let project_c_diagnostic_summaries =
-        Rc::new(RefCell::new(project_c.read_with(cx_c, |project, cx| {
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>()
-        })));
-    project_c.update(cx_c, |_, cx| {
-        let summaries = project_c_diagnostic_summaries.clone();
-        cx.subscribe(&project_c, {
-            move |p, _, event, cx| {
-                if let project::Event::DiskBasedDiagnosticsFinished { .. } = event {
-                    *summaries.borrow_mut() = p.diagnostic_summaries(false, cx).collect();
-                }
-            }
-        })
-        .detach();
+    cx_b.read(|cx| {
+        let store = cx.global::<SettingsStore>();
+        assert_eq!(
+            store
+                .local_settings(worktree_b.read(cx).id())
+                .map(|(path, content)| (
+                    path,
+                    content.all_languages.defaults.tab_size.map(Into::into)
+                ))
+                .collect::<Vec<_>>(),
+            &[(rel_path("").into(), None), (rel_path("a").into(), Some(8)),]
+        )
     });
 
+    // As client A, create and remove some settings files. As client B, see the changed settings.
+    client_a
+        .fs()
+        .remove_file("/dir/.zed/settings.json".as_ref(), Default::default())
+        .await
+        .unwrap();
+    client_a
+        .fs()
+        .create_dir("/dir/b/.zed".as_ref())
+        .await
+        .unwrap();
+    client_a
+        .fs()
+        .insert_file("/dir/b/.zed/settings.json", r#"{"tab_size": 4}"#.into())
+        .await;
     executor.run_until_parked();
-    assert_eq!(
-        project_c_diagnostic_summaries.borrow().as_slice(),
-        &[(
-            ProjectPath {
-                worktree_id,
-                path: rel_path("a.rs").into(),
-            },
-            LanguageServerId(0),
-            DiagnosticSummary {
-                error_count: 1,
-                warning_count: 0,
-            },
-        )]
-    );
+    cx_b.read(|cx| {
+        let store = cx.global::<SettingsStore>();
+        assert_eq!(
+            store
+                .local_settings(worktree_b.read(cx).id())
+                .map(|(path, content)| (
+                    path,
+                    content.all_languages.defaults.tab_size.map(Into::into)
+                ))
+                .collect::<Vec<_>>(),
+            &[
+                (rel_path("a").into(), Some(8)),
+                (rel_path("b").into(), Some(4)),
+            ]
+        )
+    });
 
-    // Simulate a language server reporting more errors for a file.
-    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
-        lsp::PublishDiagnosticsParams {
-            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
-            version: None,
-            diagnostics: vec![
-                lsp::Diagnostic {
-                    severity: Some(lsp::DiagnosticSeverity::ERROR),
-                    range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
-                    message: "message 1".to_string(),
-                    ..Default::default()
-                },
-                lsp::Diagnostic {
-                    severity: Some(lsp::DiagnosticSeverity::WARNING),
-                    range: lsp::Range::new(lsp::Position::new(0, 10), lsp::Position::new(0, 13)),
-                    message: "message 2".to_string(),
-                    ..Default::default()
-                },
-            ],
-        },
-    );
+    // As client B, disconnect.
+    server.forbid_connections();
+    server.disconnect_client(client_b.peer_id().unwrap());
 
-    // Clients B and C get the updated summaries
+    // As client A, change and remove settings files while client B is disconnected.
+    client_a
+        .fs()
+        .insert_file("/dir/a/.zed/settings.json", r#"{"hard_tabs":true}"#.into())
+        .await;
+    client_a
+        .fs()
+        .remove_file("/dir/b/.zed/settings.json".as_ref(), Default::default())
+        .await
+        .unwrap();
     executor.run_until_parked();
 
-    project_b.read_with(cx_b, |project, cx| {
-        assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            [(
-                ProjectPath {
-                    worktree_id,
-                    path: rel_path("a.rs").into(),
-                },
-                LanguageServerId(0),
-                DiagnosticSummary {
-                    error_count: 1,
-                    warning_count: 1,
-                },
-            )]
-        );
-    });
-
-    project_c.read_with(cx_c, |project, cx| {
+    // As client B, reconnect and see the changed settings.
+    server.allow_connections();
+    executor.advance_clock(RECEIVE_TIMEOUT);
+    cx_b.read(|cx| {
+        let store = cx.global::<SettingsStore>();
         assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            [(
-                ProjectPath {
-                    worktree_id,
-                    path: rel_path("a.rs").into(),
-                },
-                LanguageServerId(0),
-                DiagnosticSummary {
-                    error_count: 1,
-                    warning_count: 1,
-                },
-            )]
-        );
+            store
+                .local_settings(worktree_b.read(cx).id())
+                .map(|(path, content)| (path, content.all_languages.defaults.hard_tabs))
+                .collect::<Vec<_>>(),
+            &[(rel_path("a").into(), Some(true))],
+        )
     });
-
-    // Open the file with the errors on client B. They should be present.
-    let open_buffer = project_b.update(cx_b, |p, cx| {
-        p.open_buffer((worktree_id, rel_path("a.rs")), cx)
-    });
-    let buffer_b = cx_b.executor().spawn(open_buffer).await.unwrap();
-
-    buffer_b.read_with(cx_b, |buffer, _| {
-        assert_eq!(
-            buffer
-                .snapshot()
-                .diagnostics_in_range::<_, Point>(0..buffer.len(), false)
-                .collect::<Vec<_>>(),
-            &[
-                DiagnosticEntry {
-                    range: Point::new(0, 4)..Point::new(0, 7),
-                    diagnostic: Diagnostic {
-                        group_id: 2,
-                        message: "message 1".to_string(),
-                        severity: lsp::DiagnosticSeverity::ERROR,
-                        is_primary: true,
-                        source_kind: DiagnosticSourceKind::Pushed,
-                        ..Diagnostic::default()
-                    }
-                },
-                DiagnosticEntry {
-                    range: Point::new(0, 10)..Point::new(0, 13),
-                    diagnostic: Diagnostic {
-                        group_id: 3,
-                        severity: lsp::DiagnosticSeverity::WARNING,
-                        message: "message 2".to_string(),
-                        is_primary: true,
-                        source_kind: DiagnosticSourceKind::Pushed,
-                        ..Diagnostic::default()
-                    }
-                }
-            ]
-        );
-    });
-
-    // Simulate a language server reporting no errors for a file.
-    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
-        lsp::PublishDiagnosticsParams {
-            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
-            version: None,
-            diagnostics: Vec::new(),
-        },
-    );
-    executor.run_until_parked();
-
-    project_a.read_with(cx_a, |project, cx| {
-        assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            []
-        )
-    });
-
-    project_b.read_with(cx_b, |project, cx| {
-        assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            []
-        )
-    });
-
-    project_c.read_with(cx_c, |project, cx| {
-        assert_eq!(
-            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
-            []
-        )
-    });
-}
+}
 
 #[gpui::test(iterations = 10)]
-async fn test_collaborating_with_lsp_progress_updates_and_diagnostics_ordering(
+async fn test_buffer_conflict_after_save(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -4309,129 +4306,62 @@ async fn test_collaborating_with_lsp_progress_updates_and_diagnostics_ordering(
     server
         .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
 
-    client_a.language_registry().add(rust_lang());
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "Rust",
-        FakeLspAdapter {
-            disk_based_diagnostics_progress_token: Some("the-disk-based-token".into()),
-            disk_based_diagnostics_sources: vec!["the-disk-based-diagnostics-source".into()],
-            ..Default::default()
-        },
-    );
-
-    let file_names = &["one.rs", "two.rs", "three.rs", "four.rs", "five.rs"];
     client_a
         .fs()
         .insert_tree(
-            path!("/test"),
+            path!("/dir"),
             json!({
-                "one.rs": "const ONE: usize = 1;",
-                "two.rs": "const TWO: usize = 2;",
-                "three.rs": "const THREE: usize = 3;",
-                "four.rs": "const FOUR: usize = 3;",
-                "five.rs": "const FIVE: usize = 3;",
+                "a.txt": "a-contents",
             }),
         )
         .await;
-
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/test"), cx_a).await;
-
-    // Share a project as client A
-    let active_call_a = cx_a.read(ActiveCall::global);
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-
-    // Join the project as client B and open all three files.
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
-    let guest_buffers = futures::future::try_join_all(file_names.iter().map(|file_name| {
-        project_b.update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path(file_name)), cx)
-        })
-    }))
-    .await
-    .unwrap();
 
-    // Simulate a language server reporting errors for a file.
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    executor.run_until_parked();
-    fake_language_server
-        .request::<lsp::request::WorkDoneProgressCreate>(
-            lsp::WorkDoneProgressCreateParams {
-                token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
-            },
-            DEFAULT_LSP_REQUEST_TIMEOUT,
-        )
+    // Open a buffer as client B
+    let buffer_b = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+        })
         .await
-        .into_response()
         .unwrap();
-    fake_language_server.notify::<lsp::notification::Progress>(lsp::ProgressParams {
-        token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
-        value: lsp::ProgressParamsValue::WorkDone(lsp::WorkDoneProgress::Begin(
-            lsp::WorkDoneProgressBegin {
-                title: "Progress Began".into(),
-                ..Default::default()
-            },
-        )),
-    });
-    for file_name in file_names {
-        fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
-            lsp::PublishDiagnosticsParams {
-                uri: lsp::Uri::from_file_path(Path::new(path!("/test")).join(file_name)).unwrap(),
-                version: None,
-                diagnostics: vec![lsp::Diagnostic {
-                    severity: Some(lsp::DiagnosticSeverity::WARNING),
-                    source: Some("the-disk-based-diagnostics-source".into()),
-                    range: lsp::Range::new(lsp::Position::new(0, 0), lsp::Position::new(0, 0)),
-                    message: "message one".to_string(),
-                    ..Default::default()
-                }],
-            },
-        );
-    }
-    fake_language_server.notify::<lsp::notification::Progress>(lsp::ProgressParams {
-        token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
-        value: lsp::ProgressParamsValue::WorkDone(lsp::WorkDoneProgress::End(
-            lsp::WorkDoneProgressEnd { message: None },
-        )),
+
+    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "world ")], None, cx));
+
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert!(buf.is_dirty());
+        assert!(!buf.has_conflict());
     });
 
-    // When the "disk base diagnostics finished" message is received, the buffers'
-    // diagnostics are expected to be present.
-    let disk_based_diagnostics_finished = Arc::new(AtomicBool::new(false));
-    project_b.update(cx_b, {
-        let project_b = project_b.clone();
-        let disk_based_diagnostics_finished = disk_based_diagnostics_finished.clone();
-        move |_, cx| {
-            cx.subscribe(&project_b, move |_, _, event, cx| {
-                if let project::Event::DiskBasedDiagnosticsFinished { .. } = event {
-                    disk_based_diagnostics_finished.store(true, SeqCst);
-                    for (buffer, _) in &guest_buffers {
-                        assert_eq!(
-                            buffer
-                                .read(cx)
-                                .snapshot()
-                                .diagnostics_in_range::<_, usize>(0..5, false)
-                                .count(),
-                            1,
-                            "expected a diagnostic for buffer {:?}",
-                            buffer.read(cx).file().unwrap().path(),
-                        );
-                    }
-                }
-            })
-            .detach();
-        }
+    project_b
+        .update(cx_b, |project, cx| {
+            project.save_buffer(buffer_b.clone(), cx)
+        })
+        .await
+        .unwrap();
+
+    buffer_b.read_with(cx_b, |buffer_b, _| assert!(!buffer_b.is_dirty()));
+
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert!(!buf.has_conflict());
     });
 
-    executor.run_until_parked();
-    assert!(disk_based_diagnostics_finished.load(SeqCst));
+    buffer_b.update(cx_b, |buf, cx| buf.edit([(0..0, "hello ")], None, cx));
+
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert!(buf.is_dirty());
+        assert!(!buf.has_conflict());
+    });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_reloading_buffer_manually(
+async fn test_buffer_reloading(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -4446,89 +4376,57 @@ async fn test_reloading_buffer_manually(
 
     client_a
         .fs()
-        .insert_tree(path!("/a"), json!({ "a.rs": "let one = 1;" }))
+        .insert_tree(
+            path!("/dir"),
+            json!({
+                "a.txt": "a\nb\nc",
+            }),
+        )
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
-    let buffer_a = project_a
-        .update(cx_a, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
-        })
-        .await
-        .unwrap();
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    let open_buffer = project_b.update(cx_b, |p, cx| {
-        p.open_buffer((worktree_id, rel_path("a.rs")), cx)
-    });
-    let buffer_b = cx_b.executor().spawn(open_buffer).await.unwrap();
-    buffer_b.update(cx_b, |buffer, cx| {
-        buffer.edit([(4..7, "six")], None, cx);
-        buffer.edit([(10..11, "6")], None, cx);
-        assert_eq!(buffer.text(), "let six = 6;");
-        assert!(buffer.is_dirty());
-        assert!(!buffer.has_conflict());
-    });
-    executor.run_until_parked();
+    // Open a buffer as client B
+    let buffer_b = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
+        })
+        .await
+        .unwrap();
 
-    buffer_a.read_with(cx_a, |buffer, _| assert_eq!(buffer.text(), "let six = 6;"));
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert!(!buf.is_dirty());
+        assert!(!buf.has_conflict());
+        assert_eq!(buf.line_ending(), LineEnding::Unix);
+    });
 
+    let new_contents = Rope::from("d\ne\nf");
     client_a
         .fs()
         .save(
-            path!("/a/a.rs").as_ref(),
-            &Rope::from("let seven = 7;"),
-            LineEnding::Unix,
+            path!("/dir/a.txt").as_ref(),
+            &new_contents,
+            LineEnding::Windows,
         )
         .await
         .unwrap();
-    executor.run_until_parked();
-
-    buffer_a.read_with(cx_a, |buffer, _| assert!(buffer.has_conflict()));
 
-    buffer_b.read_with(cx_b, |buffer, _| assert!(buffer.has_conflict()));
+    executor.run_until_parked();
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.reload_buffers(HashSet::from_iter([buffer_b.clone()]), true, cx)
-        })
-        .await
-        .unwrap();
-
-    buffer_a.read_with(cx_a, |buffer, _| {
-        assert_eq!(buffer.text(), "let seven = 7;");
-        assert!(!buffer.is_dirty());
-        assert!(!buffer.has_conflict());
-    });
-
-    buffer_b.read_with(cx_b, |buffer, _| {
-        assert_eq!(buffer.text(), "let seven = 7;");
-        assert!(!buffer.is_dirty());
-        assert!(!buffer.has_conflict());
-    });
-
-    buffer_a.update(cx_a, |buffer, cx| {
-        // Undoing on the host is a no-op when the reload was initiated by the guest.
-        buffer.undo(cx);
-        assert_eq!(buffer.text(), "let seven = 7;");
-        assert!(!buffer.is_dirty());
-        assert!(!buffer.has_conflict());
-    });
-    buffer_b.update(cx_b, |buffer, cx| {
-        // Undoing on the guest rolls back the buffer to before it was reloaded but the conflict gets cleared.
-        buffer.undo(cx);
-        assert_eq!(buffer.text(), "let six = 6;");
-        assert!(buffer.is_dirty());
-        assert!(!buffer.has_conflict());
+    buffer_b.read_with(cx_b, |buf, _| {
+        assert_eq!(buf.text(), new_contents.to_string());
+        assert!(!buf.is_dirty());
+        assert!(!buf.has_conflict());
+        assert_eq!(buf.line_ending(), LineEnding::Windows);
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_formatting_buffer(
+async fn test_editing_while_guest_opens_buffer(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -4541,109 +4439,46 @@ async fn test_formatting_buffer(
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    client_a.language_registry().add(rust_lang());
-    let mut fake_language_servers = client_a
-        .language_registry()
-        .register_fake_lsp("Rust", FakeLspAdapter::default());
-
-    // Here we insert a fake tree with a directory that exists on disk. This is needed
-    // because later we'll invoke a command, which requires passing a working directory
-    // that points to a valid location on disk.
-    let directory = env::current_dir().unwrap();
     client_a
         .fs()
-        .insert_tree(&directory, json!({ "a.rs": "let one = \"two\"" }))
+        .insert_tree(path!("/dir"), json!({ "a.txt": "a-contents" }))
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/dir"), cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
+    // Open a buffer as client A
+    let buffer_a = project_a
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
         })
         .await
         .unwrap();
 
-    let _handle = project_b.update(cx_b, |project, cx| {
-        project.register_buffer_with_language_servers(&buffer_b, cx)
-    });
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    executor.run_until_parked();
-    fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>(|_, _| async move {
-        Ok(Some(vec![
-            lsp::TextEdit {
-                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 4)),
-                new_text: "h".to_string(),
-            },
-            lsp::TextEdit {
-                range: lsp::Range::new(lsp::Position::new(0, 7), lsp::Position::new(0, 7)),
-                new_text: "y".to_string(),
-            },
-        ]))
+    // Start opening the same buffer as client B
+    let open_buffer = project_b.update(cx_b, |p, cx| {
+        p.open_buffer((worktree_id, rel_path("a.txt")), cx)
     });
+    let buffer_b = cx_b.executor().spawn(open_buffer);
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.format(
-                HashSet::from_iter([buffer_b.clone()]),
-                LspFormatTarget::Buffers,
-                true,
-                FormatTrigger::Save,
-                cx,
-            )
-        })
-        .await
-        .unwrap();
-
-    // The edits from the LSP are applied, and a final newline is added.
-    assert_eq!(
-        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
-        "let honey = \"two\"\n"
-    );
+    // Edit the buffer as client A while client B is still opening it.
+    cx_b.executor().simulate_random_delay().await;
+    buffer_a.update(cx_a, |buf, cx| buf.edit([(0..0, "X")], None, cx));
+    cx_b.executor().simulate_random_delay().await;
+    buffer_a.update(cx_a, |buf, cx| buf.edit([(1..1, "Y")], None, cx));
 
-    // There is no `awk` command on Windows.
-    #[cfg(not(target_os = "windows"))]
-    {
-        // Ensure buffer can be formatted using an external command. Notice how the
-        // host's configuration is honored as opposed to using the guest's settings.
-        cx_a.update(|cx| {
-            SettingsStore::update_global(cx, |store, cx| {
-                store.update_user_settings(cx, |file| {
-                    file.project.all_languages.defaults.formatter =
-                        Some(FormatterList::Single(Formatter::External {
-                            command: "awk".into(),
-                            arguments: Some(vec!["{sub(/two/,\"{buffer_path}\")}1".to_string()]),
-                        }));
-                });
-            });
-        });
+    let text = buffer_a.read_with(cx_a, |buf, _| buf.text());
+    let buffer_b = buffer_b.await.unwrap();
+    executor.run_until_parked();
 
-        executor.allow_parking();
-        project_b
-            .update(cx_b, |project, cx| {
-                project.format(
-                    HashSet::from_iter([buffer_b.clone()]),
-                    LspFormatTarget::Buffers,
-                    true,
-                    FormatTrigger::Save,
-                    cx,
-                )
-            })
-            .await
-            .unwrap();
-        assert_eq!(
-            buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
-            format!("let honey = \"{}/a.rs\"\n", directory.to_str().unwrap())
-        );
-    }
+    buffer_b.read_with(cx_b, |buf, _| assert_eq!(buf.text(), text));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_range_formatting_buffer(
+async fn test_leaving_worktree_while_opening_buffer(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -4656,85 +4491,38 @@ async fn test_range_formatting_buffer(
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    client_a.language_registry().add(rust_lang());
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "Rust",
-        FakeLspAdapter {
-            capabilities: lsp::ServerCapabilities {
-                document_range_formatting_provider: Some(OneOf::Left(true)),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    );
-
-    let directory = env::current_dir().unwrap();
     client_a
         .fs()
-        .insert_tree(&directory, json!({ "a.rs": "one\ntwo\nthree\n" }))
+        .insert_tree("/dir", json!({ "a.txt": "a-contents" }))
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
+    let (project_a, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    let buffer_b = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
-        })
-        .await
-        .unwrap();
-
-    let _handle = project_b.update(cx_b, |project, cx| {
-        project.register_buffer_with_language_servers(&buffer_b, cx)
-    });
-    let fake_language_server = fake_language_servers.next().await.unwrap();
+    // See that a guest has joined as client A.
     executor.run_until_parked();
 
-    fake_language_server.set_request_handler::<lsp::request::RangeFormatting, _, _>(
-        |params, _| async move {
-            assert_eq!(params.range.start, lsp::Position::new(0, 0));
-            assert_eq!(params.range.end, lsp::Position::new(1, 3));
-            Ok(Some(vec![lsp::TextEdit::new(
-                lsp::Range::new(lsp::Position::new(0, 3), lsp::Position::new(1, 0)),
-                ", ".to_string(),
-            )]))
-        },
-    );
+    project_a.read_with(cx_a, |p, _| assert_eq!(p.collaborators().len(), 1));
 
-    let buffer_id = buffer_b.read_with(cx_b, |buffer, _| buffer.remote_id());
-    let ranges = buffer_b.read_with(cx_b, |buffer, _| {
-        let start = buffer.anchor_before(0);
-        let end = buffer.anchor_after(7);
-        vec![start..end]
+    // Begin opening a buffer as client B, but leave the project before the open completes.
+    let open_buffer = project_b.update(cx_b, |p, cx| {
+        p.open_buffer((worktree_id, rel_path("a.txt")), cx)
     });
+    let buffer_b = cx_b.executor().spawn(open_buffer);
+    cx_b.update(|_| drop(project_b));
+    drop(buffer_b);
 
-    let mut ranges_map = BTreeMap::new();
-    ranges_map.insert(buffer_id, ranges);
-
-    project_b
-        .update(cx_b, |project, cx| {
-            project.format(
-                HashSet::from_iter([buffer_b.clone()]),
-                LspFormatTarget::Ranges(ranges_map),
-                true,
-                FormatTrigger::Manual,
-                cx,
-            )
-        })
-        .await
-        .unwrap();
+    // See that the guest has left.
+    executor.run_until_parked();
 
-    assert_eq!(
-        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
-        "one, two\nthree\n"
-    );
+    project_a.read_with(cx_a, |p, _| assert!(p.collaborators().is_empty()));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_prettier_formatting_buffer(
+async fn test_canceling_buffer_opening(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -4747,482 +4535,482 @@ async fn test_prettier_formatting_buffer(
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    let test_plugin = "test_plugin";
-
-    client_a.language_registry().add(Arc::new(Language::new(
-        LanguageConfig {
-            name: "TypeScript".into(),
-            matcher: LanguageMatcher {
-                path_suffixes: vec!["ts".to_string()],
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
-    )));
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "TypeScript",
-        FakeLspAdapter {
-            prettier_plugins: vec![test_plugin],
-            ..Default::default()
-        },
-    );
-
-    // Here we insert a fake tree with a directory that exists on disk. This is needed
-    // because later we'll invoke a command, which requires passing a working directory
-    // that points to a valid location on disk.
-    let directory = env::current_dir().unwrap();
-    let buffer_text = "let one = \"two\"";
     client_a
         .fs()
-        .insert_tree(&directory, json!({ "a.ts": buffer_text }))
+        .insert_tree(
+            "/dir",
+            json!({
+                "a.txt": "abc",
+            }),
+        )
         .await;
-    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
-    let prettier_format_suffix = project::TEST_PRETTIER_FORMAT_SUFFIX;
-    let open_buffer = project_a.update(cx_a, |p, cx| {
-        p.open_buffer((worktree_id, rel_path("a.ts")), cx)
-    });
-    let buffer_a = cx_a.executor().spawn(open_buffer).await.unwrap();
-
+    let (project_a, worktree_id) = client_a.build_local_project("/dir", cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
-    let (buffer_b, _) = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("a.ts")), cx)
-        })
-        .await
-        .unwrap();
-
-    cx_a.update(|cx| {
-        SettingsStore::update_global(cx, |store, cx| {
-            store.update_user_settings(cx, |file| {
-                file.project.all_languages.defaults.formatter = Some(FormatterList::default());
-                file.project.all_languages.defaults.prettier = Some(PrettierSettingsContent {
-                    allowed: Some(true),
-                    ..Default::default()
-                });
-            });
-        });
-    });
-    cx_b.update(|cx| {
-        SettingsStore::update_global(cx, |store, cx| {
-            store.update_user_settings(cx, |file| {
-                file.project.all_languages.defaults.formatter = Some(FormatterList::Single(
-                    Formatter::LanguageServer(LanguageServerFormatterSpecifier::Current),
-                ));
-                file.project.all_languages.defaults.prettier = Some(PrettierSettingsContent {
-                    allowed: Some(true),
-                    ..Default::default()
-                });
-            });
-        });
-    });
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>(|_, _| async move {
-        panic!(
-            "Unexpected: prettier should be preferred since it's enabled and language supports it"
-        )
-    });
 
-    project_b
-        .update(cx_b, |project, cx| {
-            project.format(
-                HashSet::from_iter([buffer_b.clone()]),
-                LspFormatTarget::Buffers,
-                true,
-                FormatTrigger::Save,
-                cx,
-            )
+    let buffer_a = project_a
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.txt")), cx)
         })
         .await
         .unwrap();
 
-    executor.run_until_parked();
-    assert_eq!(
-        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
-        buffer_text.to_string() + "\n" + prettier_format_suffix,
-        "Prettier formatting was not applied to client buffer after client's request"
-    );
+    // Open a buffer as client B but cancel after a random amount of time.
+    let buffer_b = project_b.update(cx_b, |p, cx| {
+        p.open_buffer_by_id(buffer_a.read_with(cx_a, |a, _| a.remote_id()), cx)
+    });
+    executor.simulate_random_delay().await;
+    drop(buffer_b);
 
-    project_a
-        .update(cx_a, |project, cx| {
-            project.format(
-                HashSet::from_iter([buffer_a.clone()]),
-                LspFormatTarget::Buffers,
-                true,
-                FormatTrigger::Manual,
-                cx,
-            )
+    // Try opening the same buffer again as client B, and ensure we can
+    // still do it despite the cancellation above.
+    let buffer_b = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_by_id(buffer_a.read_with(cx_a, |a, _| a.remote_id()), cx)
         })
         .await
         .unwrap();
 
-    executor.run_until_parked();
-    assert_eq!(
-        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
-        buffer_text.to_string() + "\n" + prettier_format_suffix + "\n" + prettier_format_suffix,
-        "Prettier formatting was not applied to client buffer after host's request"
-    );
+    buffer_b.read_with(cx_b, |buf, _| assert_eq!(buf.text(), "abc"));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_definition(
+async fn test_leaving_project(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    let capabilities = lsp::ServerCapabilities {
-        definition_provider: Some(OneOf::Left(true)),
-        type_definition_provider: Some(lsp::TypeDefinitionProviderCapability::Simple(true)),
-        ..lsp::ServerCapabilities::default()
-    };
-    client_a.language_registry().add(rust_lang());
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "Rust",
-        FakeLspAdapter {
-            capabilities: capabilities.clone(),
-            ..FakeLspAdapter::default()
-        },
-    );
-    client_b.language_registry().add(rust_lang());
-    client_b.language_registry().register_fake_lsp_adapter(
-        "Rust",
-        FakeLspAdapter {
-            capabilities,
-            ..FakeLspAdapter::default()
-        },
-    );
-
     client_a
         .fs()
         .insert_tree(
-            path!("/root"),
+            "/a",
             json!({
-                "dir-1": {
-                    "a.rs": "const ONE: usize = b::TWO + b::THREE;",
-                },
-                "dir-2": {
-                    "b.rs": "const TWO: c::T2 = 2;\nconst THREE: usize = 3;",
-                    "c.rs": "type T2 = usize;",
-                }
+                "a.txt": "a-contents",
+                "b.txt": "b-contents",
             }),
         )
         .await;
-    let (project_a, worktree_id) = client_a
-        .build_local_project(path!("/root/dir-1"), cx_a)
-        .await;
+    let (project_a, _) = client_a.build_local_project("/a", cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let project_b1 = client_b.join_remote_project(project_id, cx_b).await;
+    let project_c = client_c.join_remote_project(project_id, cx_c).await;
 
-    // Open the file on client B.
-    let (buffer_b, _handle) = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("a.rs")), cx)
+    // Client A sees that a guest has joined.
+    executor.run_until_parked();
+
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    project_b1.read_with(cx_b, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    project_c.read_with(cx_c, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    // Client B opens a buffer.
+    let buffer_b1 = project_b1
+        .update(cx_b, |project, cx| {
+            let worktree_id = project.worktrees(cx).next().unwrap().read(cx).id();
+            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
         })
         .await
         .unwrap();
 
-    // Request the definition of a symbol as the guest.
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
-        |_, _| async move {
-            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
-                lsp::Location::new(
-                    lsp::Uri::from_file_path(path!("/root/dir-2/b.rs")).unwrap(),
-                    lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
-                ),
-            )))
-        },
-    );
-    cx_a.run_until_parked();
-    cx_b.run_until_parked();
+    buffer_b1.read_with(cx_b, |buffer, _| assert_eq!(buffer.text(), "a-contents"));
 
-    let definitions_1 = project_b
-        .update(cx_b, |p, cx| p.definitions(&buffer_b, 23, cx))
-        .await
-        .unwrap()
-        .unwrap();
-    cx_b.read(|cx| {
-        assert_eq!(
-            definitions_1.len(),
-            1,
-            "Unexpected definitions: {definitions_1:?}"
-        );
-        assert_eq!(project_b.read(cx).worktrees(cx).count(), 2);
-        let target_buffer = definitions_1[0].target.buffer.read(cx);
-        assert_eq!(
-            target_buffer.text(),
-            "const TWO: c::T2 = 2;\nconst THREE: usize = 3;"
-        );
-        assert_eq!(
-            definitions_1[0].target.range.to_point(target_buffer),
-            Point::new(0, 6)..Point::new(0, 9)
-        );
+    // Drop client B's project and ensure client A and client C observe client B leaving.
+    cx_b.update(|_| drop(project_b1));
+    executor.run_until_parked();
+
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 1);
     });
 
-    // Try getting more definitions for the same buffer, ensuring the buffer gets reused from
-    // the previous call to `definition`.
-    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
-        |_, _| async move {
-            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
-                lsp::Location::new(
-                    lsp::Uri::from_file_path(path!("/root/dir-2/b.rs")).unwrap(),
-                    lsp::Range::new(lsp::Position::new(1, 6), lsp::Position::new(1, 11)),
-                ),
-            )))
-        },
-    );
+    project_c.read_with(cx_c, |project, _| {
+        assert_eq!(project.collaborators().len(), 1);
+    });
 
-    let definitions_2 = project_b
-        .update(cx_b, |p, cx| p.definitions(&buffer_b, 33, cx))
+    // Client B re-joins the project and can open buffers as before.
+    let project_b2 = client_b.join_remote_project(project_id, cx_b).await;
+    executor.run_until_parked();
+
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    project_b2.read_with(cx_b, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    project_c.read_with(cx_c, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
+    });
+
+    let buffer_b2 = project_b2
+        .update(cx_b, |project, cx| {
+            let worktree_id = project.worktrees(cx).next().unwrap().read(cx).id();
+            project.open_buffer((worktree_id, rel_path("a.txt")), cx)
+        })
         .await
-        .unwrap()
         .unwrap();
-    cx_b.read(|cx| {
-        assert_eq!(definitions_2.len(), 1);
-        assert_eq!(project_b.read(cx).worktrees(cx).count(), 2);
-        let target_buffer = definitions_2[0].target.buffer.read(cx);
-        assert_eq!(
-            target_buffer.text(),
-            "const TWO: c::T2 = 2;\nconst THREE: usize = 3;"
-        );
-        assert_eq!(
-            definitions_2[0].target.range.to_point(target_buffer),
-            Point::new(1, 6)..Point::new(1, 11)
-        );
+
+    buffer_b2.read_with(cx_b, |buffer, _| assert_eq!(buffer.text(), "a-contents"));
+
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 2);
     });
-    assert_eq!(
-        definitions_1[0].target.buffer,
-        definitions_2[0].target.buffer
-    );
 
-    fake_language_server.set_request_handler::<lsp::request::GotoTypeDefinition, _, _>(
-        |req, _| async move {
-            assert_eq!(
-                req.text_document_position_params.position,
-                lsp::Position::new(0, 7)
-            );
-            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
-                lsp::Location::new(
-                    lsp::Uri::from_file_path(path!("/root/dir-2/c.rs")).unwrap(),
-                    lsp::Range::new(lsp::Position::new(0, 5), lsp::Position::new(0, 7)),
-                ),
-            )))
-        },
-    );
+    // Drop client B's connection and ensure client A and client C observe client B leaving.
+    client_b.disconnect(&cx_b.to_async());
+    executor.advance_clock(RECONNECT_TIMEOUT);
 
-    let type_definitions = project_b
-        .update(cx_b, |p, cx| p.type_definitions(&buffer_b, 7, cx))
-        .await
-        .unwrap()
-        .unwrap();
-    cx_b.read(|cx| {
-        assert_eq!(
-            type_definitions.len(),
-            1,
-            "Unexpected type definitions: {type_definitions:?}"
-        );
-        let target_buffer = type_definitions[0].target.buffer.read(cx);
-        assert_eq!(target_buffer.text(), "type T2 = usize;");
-        assert_eq!(
-            type_definitions[0].target.range.to_point(target_buffer),
-            Point::new(0, 5)..Point::new(0, 7)
-        );
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 1);
+    });
+
+    project_b2.read_with(cx_b, |project, cx| {
+        assert!(project.is_disconnected(cx));
+    });
+
+    project_c.read_with(cx_c, |project, _| {
+        assert_eq!(project.collaborators().len(), 1);
+    });
+
+    // Client B can't join the project, unless they re-join the room.
+    cx_b.spawn(|cx| {
+        Project::in_room(
+            project_id,
+            client_b.app_state.client.clone(),
+            client_b.user_store().clone(),
+            client_b.language_registry().clone(),
+            FakeFs::new(cx.background_executor().clone()),
+            cx,
+        )
+    })
+    .await
+    .unwrap_err();
+
+    // Simulate connection loss for client C and ensure client A observes client C leaving the project.
+    client_c.wait_for_current_user(cx_c).await;
+    server.forbid_connections();
+    server.disconnect_client(client_c.peer_id().unwrap());
+    executor.advance_clock(RECEIVE_TIMEOUT + RECONNECT_TIMEOUT);
+    executor.run_until_parked();
+
+    project_a.read_with(cx_a, |project, _| {
+        assert_eq!(project.collaborators().len(), 0);
+    });
+
+    project_b2.read_with(cx_b, |project, cx| {
+        assert!(project.is_disconnected(cx));
+    });
+
+    project_c.read_with(cx_c, |project, cx| {
+        assert!(project.is_disconnected(cx));
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_references(
+async fn test_collaborating_with_diagnostics(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
-    let capabilities = lsp::ServerCapabilities {
-        references_provider: Some(lsp::OneOf::Left(true)),
-        ..lsp::ServerCapabilities::default()
-    };
-    client_a.language_registry().add(rust_lang());
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "Rust",
-        FakeLspAdapter {
-            name: "my-fake-lsp-adapter",
-            capabilities: capabilities.clone(),
-            ..FakeLspAdapter::default()
-        },
-    );
-    client_b.language_registry().add(rust_lang());
-    client_b.language_registry().register_fake_lsp_adapter(
-        "Rust",
-        FakeLspAdapter {
-            name: "my-fake-lsp-adapter",
-            capabilities,
-            ..FakeLspAdapter::default()
+    client_a.language_registry().add(Arc::new(Language::new(
+        LanguageConfig {
+            name: "Rust".into(),
+            matcher: LanguageMatcher {
+                path_suffixes: vec!["rs".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
         },
-    );
+        Some(tree_sitter_rust::LANGUAGE.into()),
+    )));
+    let mut fake_language_servers = client_a
+        .language_registry()
+        .register_fake_lsp("Rust", Default::default());
 
+    // Share a project as client A
     client_a
         .fs()
         .insert_tree(
-            path!("/root"),
+            path!("/a"),
             json!({
-                "dir-1": {
-                    "one.rs": "const ONE: usize = 1;",
-                    "two.rs": "const TWO: usize = one::ONE + one::ONE;",
-                },
-                "dir-2": {
-                    "three.rs": "const THREE: usize = two::TWO + one::ONE;",
-                }
+                "a.rs": "let one = two",
+                "other.rs": "",
             }),
         )
         .await;
-    let (project_a, worktree_id) = client_a
-        .build_local_project(path!("/root/dir-1"), cx_a)
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
+
+    // Cause the language server to start.
+    let _buffer = project_a
+        .update(cx_a, |project, cx| {
+            project.open_local_buffer_with_lsp(path!("/a/other.rs"), cx)
+        })
+        .await
+        .unwrap();
+
+    // Simulate a language server reporting errors for a file.
+    let mut fake_language_server = fake_language_servers.next().await.unwrap();
+    fake_language_server
+        .receive_notification::<lsp::notification::DidOpenTextDocument>()
         .await;
+    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
+        lsp::PublishDiagnosticsParams {
+            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
+            version: None,
+            diagnostics: vec![lsp::Diagnostic {
+                severity: Some(lsp::DiagnosticSeverity::WARNING),
+                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
+                message: "message 0".to_string(),
+                ..Default::default()
+            }],
+        },
+    );
+
+    // Client A shares the project and, simultaneously, the language server
+    // publishes a diagnostic. This is done to ensure that the server always
+    // observes the latest diagnostics for a worktree.
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
+        lsp::PublishDiagnosticsParams {
+            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
+            version: None,
+            diagnostics: vec![lsp::Diagnostic {
+                severity: Some(lsp::DiagnosticSeverity::ERROR),
+                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
+                message: "message 1".to_string(),
+                ..Default::default()
+            }],
+        },
+    );
 
-    // Open the file on client B.
-    let (buffer_b, _handle) = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("one.rs")), cx)
-        })
-        .await
-        .unwrap();
+    // Join the worktree as client B.
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Request references to a symbol as the guest.
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    let (lsp_response_tx, rx) = mpsc::unbounded::<Result<Option<Vec<lsp::Location>>>>();
-    fake_language_server.set_request_handler::<lsp::request::References, _, _>({
-        let rx = Arc::new(Mutex::new(Some(rx)));
-        move |params, _| {
-            assert_eq!(
-                params.text_document_position.text_document.uri.as_str(),
-                uri!("file:///root/dir-1/one.rs")
-            );
-            let rx = rx.clone();
-            async move {
-                let mut response_rx = rx.lock().take().unwrap();
-                let result = response_rx.next().await.unwrap();
-                *rx.lock() = Some(response_rx);
-                result
-            }
-        }
-    });
-    cx_a.run_until_parked();
-    cx_b.run_until_parked();
+    // Wait for server to see the diagnostics update.
+    executor.run_until_parked();
 
-    let references = project_b.update(cx_b, |p, cx| p.references(&buffer_b, 7, cx));
+    // Ensure client B observes the new diagnostics.
 
-    // User is informed that a request is pending.
-    executor.run_until_parked();
     project_b.read_with(cx_b, |project, cx| {
-        let status = project.language_server_statuses(cx).next().unwrap().1;
-        assert_eq!(status.name.0, "my-fake-lsp-adapter");
         assert_eq!(
-            status.pending_work.values().next().unwrap().message,
-            Some("Finding references...".into())
-        );
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            &[(
+                ProjectPath {
+                    worktree_id,
+                    path: rel_path("a.rs").into(),
+                },
+                LanguageServerId(0),
+                DiagnosticSummary {
+                    error_count: 1,
+                    warning_count: 0,
+                },
+            )]
+        )
     });
 
-    // Cause the language server to respond.
-    lsp_response_tx
-        .unbounded_send(Ok(Some(vec![
-            lsp::Location {
-                uri: lsp::Uri::from_file_path(path!("/root/dir-1/two.rs")).unwrap(),
-                range: lsp::Range::new(lsp::Position::new(0, 24), lsp::Position::new(0, 27)),
-            },
-            lsp::Location {
-                uri: lsp::Uri::from_file_path(path!("/root/dir-1/two.rs")).unwrap(),
-                range: lsp::Range::new(lsp::Position::new(0, 35), lsp::Position::new(0, 38)),
-            },
-            lsp::Location {
-                uri: lsp::Uri::from_file_path(path!("/root/dir-2/three.rs")).unwrap(),
-                range: lsp::Range::new(lsp::Position::new(0, 37), lsp::Position::new(0, 40)),
-            },
-        ])))
-        .unwrap();
-
-    let references = references.await.unwrap().unwrap();
+    // Join project as client C and observe the diagnostics.
+    let project_c = client_c.join_remote_project(project_id, cx_c).await;
     executor.run_until_parked();
-    project_b.read_with(cx_b, |project, cx| {
-        // User is informed that a request is no longer pending.
-        let status = project.language_server_statuses(cx).next().unwrap().1;
-        assert!(status.pending_work.is_empty());
-
-        assert_eq!(references.len(), 3);
-        assert_eq!(project.worktrees(cx).count(), 2);
-
-        let two_buffer = references[0].buffer.read(cx);
-        let three_buffer = references[2].buffer.read(cx);
+    let project_c_diagnostic_summaries =
+        Rc::new(RefCell::new(project_c.read_with(cx_c, |project, cx| {
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>()
+        })));
+    project_c.update(cx_c, |_, cx| {
+        let summaries = project_c_diagnostic_summaries.clone();
+        cx.subscribe(&project_c, {
+            move |p, _, event, cx| {
+                if let project::Event::DiskBasedDiagnosticsFinished { .. } = event {
+                    *summaries.borrow_mut() = p.diagnostic_summaries(false, cx).collect();
+                }
+            }
+        })
+        .detach();
+    });
+
+    executor.run_until_parked();
+    assert_eq!(
+        project_c_diagnostic_summaries.borrow().as_slice(),
+        &[(
+            ProjectPath {
+                worktree_id,
+                path: rel_path("a.rs").into(),
+            },
+            LanguageServerId(0),
+            DiagnosticSummary {
+                error_count: 1,
+                warning_count: 0,
+            },
+        )]
+    );
+
+    // Simulate a language server reporting more errors for a file.
+    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
+        lsp::PublishDiagnosticsParams {
+            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
+            version: None,
+            diagnostics: vec![
+                lsp::Diagnostic {
+                    severity: Some(lsp::DiagnosticSeverity::ERROR),
+                    range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 7)),
+                    message: "message 1".to_string(),
+                    ..Default::default()
+                },
+                lsp::Diagnostic {
+                    severity: Some(lsp::DiagnosticSeverity::WARNING),
+                    range: lsp::Range::new(lsp::Position::new(0, 10), lsp::Position::new(0, 13)),
+                    message: "message 2".to_string(),
+                    ..Default::default()
+                },
+            ],
+        },
+    );
+
+    // Clients B and C get the updated summaries
+    executor.run_until_parked();
+
+    project_b.read_with(cx_b, |project, cx| {
         assert_eq!(
-            two_buffer.file().unwrap().path().as_ref(),
-            rel_path("two.rs")
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            [(
+                ProjectPath {
+                    worktree_id,
+                    path: rel_path("a.rs").into(),
+                },
+                LanguageServerId(0),
+                DiagnosticSummary {
+                    error_count: 1,
+                    warning_count: 1,
+                },
+            )]
         );
-        assert_eq!(references[1].buffer, references[0].buffer);
+    });
+
+    project_c.read_with(cx_c, |project, cx| {
         assert_eq!(
-            three_buffer.file().unwrap().full_path(cx),
-            Path::new(path!("/root/dir-2/three.rs"))
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            [(
+                ProjectPath {
+                    worktree_id,
+                    path: rel_path("a.rs").into(),
+                },
+                LanguageServerId(0),
+                DiagnosticSummary {
+                    error_count: 1,
+                    warning_count: 1,
+                },
+            )]
         );
-
-        assert_eq!(references[0].range.to_offset(two_buffer), 24..27);
-        assert_eq!(references[1].range.to_offset(two_buffer), 35..38);
-        assert_eq!(references[2].range.to_offset(three_buffer), 37..40);
     });
 
-    let references = project_b.update(cx_b, |p, cx| p.references(&buffer_b, 7, cx));
+    // Open the file with the errors on client B. They should be present.
+    let open_buffer = project_b.update(cx_b, |p, cx| {
+        p.open_buffer((worktree_id, rel_path("a.rs")), cx)
+    });
+    let buffer_b = cx_b.executor().spawn(open_buffer).await.unwrap();
 
-    // User is informed that a request is pending.
-    executor.run_until_parked();
-    project_b.read_with(cx_b, |project, cx| {
-        let status = project.language_server_statuses(cx).next().unwrap().1;
-        assert_eq!(status.name.0, "my-fake-lsp-adapter");
+    buffer_b.read_with(cx_b, |buffer, _| {
         assert_eq!(
-            status.pending_work.values().next().unwrap().message,
-            Some("Finding references...".into())
+            buffer
+                .snapshot()
+                .diagnostics_in_range::<_, Point>(0..buffer.len(), false)
+                .collect::<Vec<_>>(),
+            &[
+                DiagnosticEntry {
+                    range: Point::new(0, 4)..Point::new(0, 7),
+                    diagnostic: Diagnostic {
+                        group_id: 2,
+                        message: "message 1".to_string(),
+                        severity: lsp::DiagnosticSeverity::ERROR,
+                        is_primary: true,
+                        source_kind: DiagnosticSourceKind::Pushed,
+                        ..Diagnostic::default()
+                    }
+                },
+                DiagnosticEntry {
+                    range: Point::new(0, 10)..Point::new(0, 13),
+                    diagnostic: Diagnostic {
+                        group_id: 3,
+                        severity: lsp::DiagnosticSeverity::WARNING,
+                        message: "message 2".to_string(),
+                        is_primary: true,
+                        source_kind: DiagnosticSourceKind::Pushed,
+                        ..Diagnostic::default()
+                    }
+                }
+            ]
         );
     });
 
-    // Cause the LSP request to fail.
-    lsp_response_tx
-        .unbounded_send(Err(anyhow!("can't find references")))
-        .unwrap();
-    assert_eq!(references.await.unwrap().unwrap(), []);
-
-    // User is informed that the request is no longer pending.
+    // Simulate a language server reporting no errors for a file.
+    fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
+        lsp::PublishDiagnosticsParams {
+            uri: lsp::Uri::from_file_path(path!("/a/a.rs")).unwrap(),
+            version: None,
+            diagnostics: Vec::new(),
+        },
+    );
     executor.run_until_parked();
+
+    project_a.read_with(cx_a, |project, cx| {
+        assert_eq!(
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            []
+        )
+    });
+
     project_b.read_with(cx_b, |project, cx| {
-        let status = project.language_server_statuses(cx).next().unwrap().1;
-        assert!(status.pending_work.is_empty());
+        assert_eq!(
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            []
+        )
+    });
+
+    project_c.read_with(cx_c, |project, cx| {
+        assert_eq!(
+            project.diagnostic_summaries(false, cx).collect::<Vec<_>>(),
+            []
+        )
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_project_search(
+async fn test_collaborating_with_lsp_progress_updates_and_diagnostics_ordering(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -5233,101 +5021,129 @@ async fn test_project_search(
     server
         .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
 
+    client_a.language_registry().add(rust_lang());
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "Rust",
+        FakeLspAdapter {
+            disk_based_diagnostics_progress_token: Some("the-disk-based-token".into()),
+            disk_based_diagnostics_sources: vec!["the-disk-based-diagnostics-source".into()],
+            ..Default::default()
+        },
+    );
+
+    let file_names = &["one.rs", "two.rs", "three.rs", "four.rs", "five.rs"];
     client_a
         .fs()
         .insert_tree(
-            "/root",
+            path!("/test"),
             json!({
-                "dir-1": {
-                    "a": "hello world",
-                    "b": "goodnight moon",
-                    "c": "a world of goo",
-                    "d": "world champion of clown world",
-                },
-                "dir-2": {
-                    "e": "disney world is fun",
-                }
+                "one.rs": "const ONE: usize = 1;",
+                "two.rs": "const TWO: usize = 2;",
+                "three.rs": "const THREE: usize = 3;",
+                "four.rs": "const FOUR: usize = 3;",
+                "five.rs": "const FIVE: usize = 3;",
             }),
         )
         .await;
-    let (project_a, _) = client_a.build_local_project("/root/dir-1", cx_a).await;
-    let (worktree_2, _) = project_a
-        .update(cx_a, |p, cx| {
-            p.find_or_create_worktree("/root/dir-2", true, cx)
-        })
-        .await
-        .unwrap();
-    worktree_2
-        .read_with(cx_a, |tree, _| tree.as_local().unwrap().scan_complete())
-        .await;
+
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/test"), cx_a).await;
+
+    // Share a project as client A
+    let active_call_a = cx_a.read(ActiveCall::global);
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
 
+    // Join the project as client B and open all three files.
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let guest_buffers = futures::future::try_join_all(file_names.iter().map(|file_name| {
+        project_b.update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path(file_name)), cx)
+        })
+    }))
+    .await
+    .unwrap();
 
-    // Perform a search as the guest.
-    let mut results = HashMap::default();
-    let search_rx = project_b.update(cx_b, |project, cx| {
-        project.search(
-            SearchQuery::text(
-                "world",
-                false,
-                false,
-                false,
-                Default::default(),
-                Default::default(),
-                false,
-                None,
-            )
-            .unwrap(),
-            cx,
+    // Simulate a language server reporting errors for a file.
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    executor.run_until_parked();
+    fake_language_server
+        .request::<lsp::request::WorkDoneProgressCreate>(
+            lsp::WorkDoneProgressCreateParams {
+                token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
+            },
+            DEFAULT_LSP_REQUEST_TIMEOUT,
         )
-    });
-    while let Ok(result) = search_rx.rx.recv().await {
-        match result {
-            SearchResult::Buffer { buffer, ranges } => {
-                results.entry(buffer).or_insert(ranges);
-            }
-            SearchResult::LimitReached => {
-                panic!(
-                    "Unexpectedly reached search limit in tests. If you do want to assert limit-reached, change this panic call."
-                )
-            }
-        };
+        .await
+        .into_response()
+        .unwrap();
+    fake_language_server.notify::<lsp::notification::Progress>(lsp::ProgressParams {
+        token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
+        value: lsp::ProgressParamsValue::WorkDone(lsp::WorkDoneProgress::Begin(
+            lsp::WorkDoneProgressBegin {
+                title: "Progress Began".into(),
+                ..Default::default()
+            },
+        )),
+    });
+    for file_name in file_names {
+        fake_language_server.notify::<lsp::notification::PublishDiagnostics>(
+            lsp::PublishDiagnosticsParams {
+                uri: lsp::Uri::from_file_path(Path::new(path!("/test")).join(file_name)).unwrap(),
+                version: None,
+                diagnostics: vec![lsp::Diagnostic {
+                    severity: Some(lsp::DiagnosticSeverity::WARNING),
+                    source: Some("the-disk-based-diagnostics-source".into()),
+                    range: lsp::Range::new(lsp::Position::new(0, 0), lsp::Position::new(0, 0)),
+                    message: "message one".to_string(),
+                    ..Default::default()
+                }],
+            },
+        );
     }
+    fake_language_server.notify::<lsp::notification::Progress>(lsp::ProgressParams {
+        token: lsp::NumberOrString::String("the-disk-based-token".to_string()),
+        value: lsp::ProgressParamsValue::WorkDone(lsp::WorkDoneProgress::End(
+            lsp::WorkDoneProgressEnd { message: None },
+        )),
+    });
 
-    let mut ranges_by_path = results
-        .into_iter()
-        .map(|(buffer, ranges)| {
-            buffer.read_with(cx_b, |buffer, cx| {
-                let path = buffer.file().unwrap().full_path(cx);
-                let offset_ranges = ranges
-                    .into_iter()
-                    .map(|range| range.to_offset(buffer))
-                    .collect::<Vec<_>>();
-                (path, offset_ranges)
+    // When the "disk base diagnostics finished" message is received, the buffers'
+    // diagnostics are expected to be present.
+    let disk_based_diagnostics_finished = Arc::new(AtomicBool::new(false));
+    project_b.update(cx_b, {
+        let project_b = project_b.clone();
+        let disk_based_diagnostics_finished = disk_based_diagnostics_finished.clone();
+        move |_, cx| {
+            cx.subscribe(&project_b, move |_, _, event, cx| {
+                if let project::Event::DiskBasedDiagnosticsFinished { .. } = event {
+                    disk_based_diagnostics_finished.store(true, SeqCst);
+                    for (buffer, _) in &guest_buffers {
+                        assert_eq!(
+                            buffer
+                                .read(cx)
+                                .snapshot()
+                                .diagnostics_in_range::<_, usize>(0..5, false)
+                                .count(),
+                            1,
+                            "expected a diagnostic for buffer {:?}",
+                            buffer.read(cx).file().unwrap().path(),
+                        );
+                    }
+                }
             })
-        })
-        .collect::<Vec<_>>();
-    ranges_by_path.sort_by_key(|(path, _)| path.clone());
+            .detach();
+        }
+    });
 
-    assert_eq!(
-        ranges_by_path,
-        &[
-            (PathBuf::from("dir-1/a"), vec![6..11]),
-            (PathBuf::from("dir-1/c"), vec![2..7]),
-            (PathBuf::from("dir-1/d"), vec![0..5, 24..29]),
-            (PathBuf::from("dir-2/e"), vec![7..12]),
-        ]
-    );
+    executor.run_until_parked();
+    assert!(disk_based_diagnostics_finished.load(SeqCst));
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_document_highlights(
+async fn test_reloading_buffer_manually(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -5342,110 +5158,89 @@ async fn test_document_highlights(
 
     client_a
         .fs()
-        .insert_tree(
-            path!("/root-1"),
-            json!({
-                "main.rs": "fn double(number: i32) -> i32 { number + number }",
-            }),
-        )
+        .insert_tree(path!("/a"), json!({ "a.rs": "let one = 1;" }))
         .await;
-
-    client_a.language_registry().add(rust_lang());
-    let capabilities = lsp::ServerCapabilities {
-        document_highlight_provider: Some(lsp::OneOf::Left(true)),
-        ..lsp::ServerCapabilities::default()
-    };
-    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
-        "Rust",
-        FakeLspAdapter {
-            capabilities: capabilities.clone(),
-            ..FakeLspAdapter::default()
-        },
-    );
-    client_b.language_registry().add(rust_lang());
-    client_b.language_registry().register_fake_lsp_adapter(
-        "Rust",
-        FakeLspAdapter {
-            capabilities,
-            ..FakeLspAdapter::default()
-        },
-    );
-
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/root-1"), cx_a).await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/a"), cx_a).await;
+    let buffer_a = project_a
+        .update(cx_a, |p, cx| {
+            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
+        })
+        .await
+        .unwrap();
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
+
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Open the file on client B.
-    let (buffer_b, _handle) = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("main.rs")), cx)
-        })
+    let open_buffer = project_b.update(cx_b, |p, cx| {
+        p.open_buffer((worktree_id, rel_path("a.rs")), cx)
+    });
+    let buffer_b = cx_b.executor().spawn(open_buffer).await.unwrap();
+    buffer_b.update(cx_b, |buffer, cx| {
+        buffer.edit([(4..7, "six")], None, cx);
+        buffer.edit([(10..11, "6")], None, cx);
+        assert_eq!(buffer.text(), "let six = 6;");
+        assert!(buffer.is_dirty());
+        assert!(!buffer.has_conflict());
+    });
+    executor.run_until_parked();
+
+    buffer_a.read_with(cx_a, |buffer, _| assert_eq!(buffer.text(), "let six = 6;"));
+
+    client_a
+        .fs()
+        .save(
+            path!("/a/a.rs").as_ref(),
+            &Rope::from("let seven = 7;"),
+            LineEnding::Unix,
+        )
         .await
         .unwrap();
+    executor.run_until_parked();
 
-    // Request document highlights as the guest.
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    fake_language_server.set_request_handler::<lsp::request::DocumentHighlightRequest, _, _>(
-        |params, _| async move {
-            assert_eq!(
-                params
-                    .text_document_position_params
-                    .text_document
-                    .uri
-                    .as_str(),
-                uri!("file:///root-1/main.rs")
-            );
-            assert_eq!(
-                params.text_document_position_params.position,
-                lsp::Position::new(0, 34)
-            );
-            Ok(Some(vec![
-                lsp::DocumentHighlight {
-                    kind: Some(lsp::DocumentHighlightKind::WRITE),
-                    range: lsp::Range::new(lsp::Position::new(0, 10), lsp::Position::new(0, 16)),
-                },
-                lsp::DocumentHighlight {
-                    kind: Some(lsp::DocumentHighlightKind::READ),
-                    range: lsp::Range::new(lsp::Position::new(0, 32), lsp::Position::new(0, 38)),
-                },
-                lsp::DocumentHighlight {
-                    kind: Some(lsp::DocumentHighlightKind::READ),
-                    range: lsp::Range::new(lsp::Position::new(0, 41), lsp::Position::new(0, 47)),
-                },
-            ]))
-        },
-    );
-    cx_a.run_until_parked();
-    cx_b.run_until_parked();
+    buffer_a.read_with(cx_a, |buffer, _| assert!(buffer.has_conflict()));
 
-    let highlights = project_b
-        .update(cx_b, |p, cx| p.document_highlights(&buffer_b, 34, cx))
+    buffer_b.read_with(cx_b, |buffer, _| assert!(buffer.has_conflict()));
+
+    project_b
+        .update(cx_b, |project, cx| {
+            project.reload_buffers(HashSet::from_iter([buffer_b.clone()]), true, cx)
+        })
         .await
         .unwrap();
 
+    buffer_a.read_with(cx_a, |buffer, _| {
+        assert_eq!(buffer.text(), "let seven = 7;");
+        assert!(!buffer.is_dirty());
+        assert!(!buffer.has_conflict());
+    });
+
     buffer_b.read_with(cx_b, |buffer, _| {
-        let snapshot = buffer.snapshot();
+        assert_eq!(buffer.text(), "let seven = 7;");
+        assert!(!buffer.is_dirty());
+        assert!(!buffer.has_conflict());
+    });
 
-        let highlights = highlights
-            .into_iter()
-            .map(|highlight| (highlight.kind, highlight.range.to_offset(&snapshot)))
-            .collect::<Vec<_>>();
-        assert_eq!(
-            highlights,
-            &[
-                (lsp::DocumentHighlightKind::WRITE, 10..16),
-                (lsp::DocumentHighlightKind::READ, 32..38),
-                (lsp::DocumentHighlightKind::READ, 41..47)
-            ]
-        )
+    buffer_a.update(cx_a, |buffer, cx| {
+        // Undoing on the host is a no-op when the reload was initiated by the guest.
+        buffer.undo(cx);
+        assert_eq!(buffer.text(), "let seven = 7;");
+        assert!(!buffer.is_dirty());
+        assert!(!buffer.has_conflict());
+    });
+    buffer_b.update(cx_b, |buffer, cx| {
+        // Undoing on the guest rolls back the buffer to before it was reloaded but the conflict gets cleared.
+        buffer.undo(cx);
+        assert_eq!(buffer.text(), "let six = 6;");
+        assert!(buffer.is_dirty());
+        assert!(!buffer.has_conflict());
     });
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_lsp_hover(
+async fn test_formatting_buffer(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -5458,211 +5253,109 @@ async fn test_lsp_hover(
         .await;
     let active_call_a = cx_a.read(ActiveCall::global);
 
+    client_a.language_registry().add(rust_lang());
+    let mut fake_language_servers = client_a
+        .language_registry()
+        .register_fake_lsp("Rust", FakeLspAdapter::default());
+
+    // Here we insert a fake tree with a directory that exists on disk. This is needed
+    // because later we'll invoke a command, which requires passing a working directory
+    // that points to a valid location on disk.
+    let directory = env::current_dir().unwrap();
     client_a
         .fs()
-        .insert_tree(
-            path!("/root-1"),
-            json!({
-                "main.rs": "use std::collections::HashMap;",
-            }),
-        )
+        .insert_tree(&directory, json!({ "a.rs": "let one = \"two\"" }))
         .await;
-
-    client_a.language_registry().add(rust_lang());
-    let language_server_names = ["rust-analyzer", "CrabLang-ls"];
-    let capabilities_1 = lsp::ServerCapabilities {
-        hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
-        ..lsp::ServerCapabilities::default()
-    };
-    let capabilities_2 = lsp::ServerCapabilities {
-        hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
-        ..lsp::ServerCapabilities::default()
-    };
-    let mut language_servers = [
-        client_a.language_registry().register_fake_lsp(
-            "Rust",
-            FakeLspAdapter {
-                name: language_server_names[0],
-                capabilities: capabilities_1.clone(),
-                ..FakeLspAdapter::default()
-            },
-        ),
-        client_a.language_registry().register_fake_lsp(
-            "Rust",
-            FakeLspAdapter {
-                name: language_server_names[1],
-                capabilities: capabilities_2.clone(),
-                ..FakeLspAdapter::default()
-            },
-        ),
-    ];
-    client_b.language_registry().add(rust_lang());
-    client_b.language_registry().register_fake_lsp_adapter(
-        "Rust",
-        FakeLspAdapter {
-            name: language_server_names[0],
-            capabilities: capabilities_1,
-            ..FakeLspAdapter::default()
-        },
-    );
-    client_b.language_registry().register_fake_lsp_adapter(
-        "Rust",
-        FakeLspAdapter {
-            name: language_server_names[1],
-            capabilities: capabilities_2,
-            ..FakeLspAdapter::default()
-        },
-    );
-
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/root-1"), cx_a).await;
+    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Open the file as the guest
-    let (buffer_b, _handle) = project_b
+    let buffer_b = project_b
         .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("main.rs")), cx)
+            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
         })
         .await
         .unwrap();
 
-    let mut servers_with_hover_requests = HashMap::default();
-    for i in 0..language_server_names.len() {
-        let new_server = language_servers[i].next().await.unwrap_or_else(|| {
-            panic!(
-                "Failed to get language server #{i} with name {}",
-                &language_server_names[i]
-            )
-        });
-        let new_server_name = new_server.server.name();
-        assert!(
-            !servers_with_hover_requests.contains_key(&new_server_name),
-            "Unexpected: initialized server with the same name twice. Name: `{new_server_name}`"
-        );
-        match new_server_name.as_ref() {
-            "CrabLang-ls" => {
-                servers_with_hover_requests.insert(
-                    new_server_name.clone(),
-                    new_server.set_request_handler::<lsp::request::HoverRequest, _, _>(
-                        move |params, _| {
-                            assert_eq!(
-                                params
-                                    .text_document_position_params
-                                    .text_document
-                                    .uri
-                                    .as_str(),
-                                uri!("file:///root-1/main.rs")
-                            );
-                            let name = new_server_name.clone();
-                            async move {
-                                Ok(Some(lsp::Hover {
-                                    contents: lsp::HoverContents::Scalar(
-                                        lsp::MarkedString::String(format!("{name} hover")),
-                                    ),
-                                    range: None,
-                                }))
-                            }
-                        },
-                    ),
-                );
-            }
-            "rust-analyzer" => {
-                servers_with_hover_requests.insert(
-                    new_server_name.clone(),
-                    new_server.set_request_handler::<lsp::request::HoverRequest, _, _>(
-                        |params, _| async move {
-                            assert_eq!(
-                                params
-                                    .text_document_position_params
-                                    .text_document
-                                    .uri
-                                    .as_str(),
-                                uri!("file:///root-1/main.rs")
-                            );
-                            assert_eq!(
-                                params.text_document_position_params.position,
-                                lsp::Position::new(0, 22)
-                            );
-                            Ok(Some(lsp::Hover {
-                                contents: lsp::HoverContents::Array(vec![
-                                    lsp::MarkedString::String("Test hover content.".to_string()),
-                                    lsp::MarkedString::LanguageString(lsp::LanguageString {
-                                        language: "Rust".to_string(),
-                                        value: "let foo = 42;".to_string(),
-                                    }),
-                                ]),
-                                range: Some(lsp::Range::new(
-                                    lsp::Position::new(0, 22),
-                                    lsp::Position::new(0, 29),
-                                )),
-                            }))
-                        },
-                    ),
-                );
-            }
-            unexpected => panic!("Unexpected server name: {unexpected}"),
-        }
-    }
-    cx_a.run_until_parked();
-    cx_b.run_until_parked();
+    let _handle = project_b.update(cx_b, |project, cx| {
+        project.register_buffer_with_language_servers(&buffer_b, cx)
+    });
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    executor.run_until_parked();
+    fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>(|_, _| async move {
+        Ok(Some(vec![
+            lsp::TextEdit {
+                range: lsp::Range::new(lsp::Position::new(0, 4), lsp::Position::new(0, 4)),
+                new_text: "h".to_string(),
+            },
+            lsp::TextEdit {
+                range: lsp::Range::new(lsp::Position::new(0, 7), lsp::Position::new(0, 7)),
+                new_text: "y".to_string(),
+            },
+        ]))
+    });
 
-    // Request hover information as the guest.
-    let mut hovers = project_b
-        .update(cx_b, |p, cx| p.hover(&buffer_b, 22, cx))
+    project_b
+        .update(cx_b, |project, cx| {
+            project.format(
+                HashSet::from_iter([buffer_b.clone()]),
+                LspFormatTarget::Buffers,
+                true,
+                FormatTrigger::Save,
+                cx,
+            )
+        })
         .await
         .unwrap();
+
+    // The edits from the LSP are applied, and a final newline is added.
     assert_eq!(
-        hovers.len(),
-        2,
-        "Expected two hovers from both language servers, but got: {hovers:?}"
+        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
+        "let honey = \"two\"\n"
     );
 
-    let _: Vec<()> = futures::future::join_all(servers_with_hover_requests.into_values().map(
-        |mut hover_request| async move {
-            hover_request
-                .next()
-                .await
-                .expect("All hover requests should have been triggered")
-        },
-    ))
-    .await;
+    // There is no `awk` command on Windows.
+    #[cfg(not(target_os = "windows"))]
+    {
+        // Ensure buffer can be formatted using an external command. Notice how the
+        // host's configuration is honored as opposed to using the guest's settings.
+        cx_a.update(|cx| {
+            SettingsStore::update_global(cx, |store, cx| {
+                store.update_user_settings(cx, |file| {
+                    file.project.all_languages.defaults.formatter =
+                        Some(FormatterList::Single(Formatter::External {
+                            command: "awk".into(),
+                            arguments: Some(vec!["{sub(/two/,\"{buffer_path}\")}1".to_string()]),
+                        }));
+                });
+            });
+        });
 
-    hovers.sort_by_key(|hover| hover.contents.len());
-    let first_hover = hovers.first().cloned().unwrap();
-    assert_eq!(
-        first_hover.contents,
-        vec![project::HoverBlock {
-            text: "CrabLang-ls hover".to_string(),
-            kind: HoverBlockKind::Markdown,
-        },]
-    );
-    let second_hover = hovers.last().cloned().unwrap();
-    assert_eq!(
-        second_hover.contents,
-        vec![
-            project::HoverBlock {
-                text: "Test hover content.".to_string(),
-                kind: HoverBlockKind::Markdown,
-            },
-            project::HoverBlock {
-                text: "let foo = 42;".to_string(),
-                kind: HoverBlockKind::Code {
-                    language: "Rust".to_string()
-                },
-            }
-        ]
-    );
-    buffer_b.read_with(cx_b, |buffer, _| {
-        let snapshot = buffer.snapshot();
-        assert_eq!(second_hover.range.unwrap().to_offset(&snapshot), 22..29);
-    });
+        executor.allow_parking();
+        project_b
+            .update(cx_b, |project, cx| {
+                project.format(
+                    HashSet::from_iter([buffer_b.clone()]),
+                    LspFormatTarget::Buffers,
+                    true,
+                    FormatTrigger::Save,
+                    cx,
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
+            format!("let honey = \"{}/a.rs\"\n", directory.to_str().unwrap())
+        );
+    }
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_project_symbols(
+async fn test_range_formatting_buffer(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
@@ -5680,112 +5373,218 @@ async fn test_project_symbols(
         "Rust",
         FakeLspAdapter {
             capabilities: lsp::ServerCapabilities {
-                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
                 ..Default::default()
             },
             ..Default::default()
         },
     );
 
+    let directory = env::current_dir().unwrap();
     client_a
         .fs()
-        .insert_tree(
-            path!("/code"),
-            json!({
-                "crate-1": {
-                    "one.rs": "const ONE: usize = 1;",
-                },
-                "crate-2": {
-                    "two.rs": "const TWO: usize = 2; const THREE: usize = 3;",
-                },
-                "private": {
-                    "passwords.txt": "the-password",
-                }
-            }),
-        )
-        .await;
-    let (project_a, worktree_id) = client_a
-        .build_local_project(path!("/code/crate-1"), cx_a)
+        .insert_tree(&directory, json!({ "a.rs": "one\ntwo\nthree\n" }))
         .await;
+    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
     let project_id = active_call_a
         .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
         .await
         .unwrap();
     let project_b = client_b.join_remote_project(project_id, cx_b).await;
 
-    // Cause the language server to start.
-    let _buffer = project_b
+    let buffer_b = project_b
         .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("one.rs")), cx)
+            p.open_buffer((worktree_id, rel_path("a.rs")), cx)
         })
         .await
         .unwrap();
 
+    let _handle = project_b.update(cx_b, |project, cx| {
+        project.register_buffer_with_language_servers(&buffer_b, cx)
+    });
     let fake_language_server = fake_language_servers.next().await.unwrap();
     executor.run_until_parked();
-    fake_language_server.set_request_handler::<lsp::WorkspaceSymbolRequest, _, _>(
-        |_, _| async move {
-            Ok(Some(lsp::WorkspaceSymbolResponse::Flat(vec![
-                #[allow(deprecated)]
-                lsp::SymbolInformation {
-                    name: "TWO".into(),
-                    location: lsp::Location {
-                        uri: lsp::Uri::from_file_path(path!("/code/crate-2/two.rs")).unwrap(),
-                        range: lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
-                    },
-                    kind: lsp::SymbolKind::CONSTANT,
-                    tags: None,
-                    container_name: None,
-                    deprecated: None,
-                },
-            ])))
+
+    fake_language_server.set_request_handler::<lsp::request::RangeFormatting, _, _>(
+        |params, _| async move {
+            assert_eq!(params.range.start, lsp::Position::new(0, 0));
+            assert_eq!(params.range.end, lsp::Position::new(1, 3));
+            Ok(Some(vec![lsp::TextEdit::new(
+                lsp::Range::new(lsp::Position::new(0, 3), lsp::Position::new(1, 0)),
+                ", ".to_string(),
+            )]))
         },
     );
 
-    // Request the definition of a symbol as the guest.
-    let symbols = project_b
-        .update(cx_b, |p, cx| p.symbols("two", cx))
+    let buffer_id = buffer_b.read_with(cx_b, |buffer, _| buffer.remote_id());
+    let ranges = buffer_b.read_with(cx_b, |buffer, _| {
+        let start = buffer.anchor_before(0);
+        let end = buffer.anchor_after(7);
+        vec![start..end]
+    });
+
+    let mut ranges_map = BTreeMap::new();
+    ranges_map.insert(buffer_id, ranges);
+
+    project_b
+        .update(cx_b, |project, cx| {
+            project.format(
+                HashSet::from_iter([buffer_b.clone()]),
+                LspFormatTarget::Ranges(ranges_map),
+                true,
+                FormatTrigger::Manual,
+                cx,
+            )
+        })
         .await
         .unwrap();
-    assert_eq!(symbols.len(), 1);
-    assert_eq!(symbols[0].name, "TWO");
 
-    // Open one of the returned symbols.
-    let buffer_b_2 = project_b
-        .update(cx_b, |project, cx| {
-            project.open_buffer_for_symbol(&symbols[0], cx)
+    assert_eq!(
+        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
+        "one, two\nthree\n"
+    );
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_prettier_formatting_buffer(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    let test_plugin = "test_plugin";
+
+    client_a.language_registry().add(Arc::new(Language::new(
+        LanguageConfig {
+            name: "TypeScript".into(),
+            matcher: LanguageMatcher {
+                path_suffixes: vec!["ts".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
+    )));
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "TypeScript",
+        FakeLspAdapter {
+            prettier_plugins: vec![test_plugin],
+            ..Default::default()
+        },
+    );
+
+    // Here we insert a fake tree with a directory that exists on disk. This is needed
+    // because later we'll invoke a command, which requires passing a working directory
+    // that points to a valid location on disk.
+    let directory = env::current_dir().unwrap();
+    let buffer_text = "let one = \"two\"";
+    client_a
+        .fs()
+        .insert_tree(&directory, json!({ "a.ts": buffer_text }))
+        .await;
+    let (project_a, worktree_id) = client_a.build_local_project(&directory, cx_a).await;
+    let prettier_format_suffix = project::TEST_PRETTIER_FORMAT_SUFFIX;
+    let open_buffer = project_a.update(cx_a, |p, cx| {
+        p.open_buffer((worktree_id, rel_path("a.ts")), cx)
+    });
+    let buffer_a = cx_a.executor().spawn(open_buffer).await.unwrap();
+
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    let (buffer_b, _) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("a.ts")), cx)
         })
         .await
         .unwrap();
 
-    buffer_b_2.read_with(cx_b, |buffer, cx| {
-        assert_eq!(
-            buffer.file().unwrap().full_path(cx),
-            Path::new(path!("/code/crate-2/two.rs"))
-        );
+    cx_a.update(|cx| {
+        SettingsStore::update_global(cx, |store, cx| {
+            store.update_user_settings(cx, |file| {
+                file.project.all_languages.defaults.formatter = Some(FormatterList::default());
+                file.project.all_languages.defaults.prettier = Some(PrettierSettingsContent {
+                    allowed: Some(true),
+                    ..Default::default()
+                });
+            });
+        });
+    });
+    cx_b.update(|cx| {
+        SettingsStore::update_global(cx, |store, cx| {
+            store.update_user_settings(cx, |file| {
+                file.project.all_languages.defaults.formatter = Some(FormatterList::Single(
+                    Formatter::LanguageServer(LanguageServerFormatterSpecifier::Current),
+                ));
+                file.project.all_languages.defaults.prettier = Some(PrettierSettingsContent {
+                    allowed: Some(true),
+                    ..Default::default()
+                });
+            });
+        });
+    });
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    fake_language_server.set_request_handler::<lsp::request::Formatting, _, _>(|_, _| async move {
+        panic!(
+            "Unexpected: prettier should be preferred since it's enabled and language supports it"
+        )
     });
 
-    // Attempt to craft a symbol and violate host's privacy by opening an arbitrary file.
-    let mut fake_symbol = symbols[0].clone();
-    fake_symbol.path = SymbolLocation::OutsideProject {
-        abs_path: Path::new(path!("/code/secrets")).into(),
-        signature: [0x17; 32],
-    };
-    let error = project_b
+    project_b
         .update(cx_b, |project, cx| {
-            project.open_buffer_for_symbol(&fake_symbol, cx)
+            project.format(
+                HashSet::from_iter([buffer_b.clone()]),
+                LspFormatTarget::Buffers,
+                true,
+                FormatTrigger::Save,
+                cx,
+            )
         })
         .await
-        .unwrap_err();
-    assert!(error.to_string().contains("invalid symbol signature"));
+        .unwrap();
+
+    executor.run_until_parked();
+    assert_eq!(
+        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
+        buffer_text.to_string() + "\n" + prettier_format_suffix,
+        "Prettier formatting was not applied to client buffer after client's request"
+    );
+
+    project_a
+        .update(cx_a, |project, cx| {
+            project.format(
+                HashSet::from_iter([buffer_a.clone()]),
+                LspFormatTarget::Buffers,
+                true,
+                FormatTrigger::Manual,
+                cx,
+            )
+        })
+        .await
+        .unwrap();
+
+    executor.run_until_parked();
+    assert_eq!(
+        buffer_b.read_with(cx_b, |buffer, _| buffer.text()),
+        buffer_text.to_string() + "\n" + prettier_format_suffix + "\n" + prettier_format_suffix,
+        "Prettier formatting was not applied to client buffer after host's request"
+    );
 }
 
 #[gpui::test(iterations = 10)]
-async fn test_open_buffer_while_getting_definition_pointing_to_it(
+async fn test_definition(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
-    mut rng: StdRng,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
     let client_a = server.create_client(cx_a, "user_a").await;
@@ -5797,6 +5596,7 @@ async fn test_open_buffer_while_getting_definition_pointing_to_it(
 
     let capabilities = lsp::ServerCapabilities {
         definition_provider: Some(OneOf::Left(true)),
+        type_definition_provider: Some(lsp::TypeDefinitionProviderCapability::Simple(true)),
         ..lsp::ServerCapabilities::default()
     };
     client_a.language_registry().add(rust_lang());
@@ -5816,96 +5616,1278 @@ async fn test_open_buffer_while_getting_definition_pointing_to_it(
         },
     );
 
-    client_a
-        .fs()
-        .insert_tree(
-            path!("/root"),
-            json!({
-                "a.rs": "const ONE: usize = b::TWO;",
-                "b.rs": "const TWO: usize = 2",
-            }),
-        )
-        .await;
-    let (project_a, worktree_id) = client_a.build_local_project(path!("/root"), cx_a).await;
-    let project_id = active_call_a
-        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/root"),
+            json!({
+                "dir-1": {
+                    "a.rs": "const ONE: usize = b::TWO + b::THREE;",
+                },
+                "dir-2": {
+                    "b.rs": "const TWO: c::T2 = 2;\nconst THREE: usize = 3;",
+                    "c.rs": "type T2 = usize;",
+                }
+            }),
+        )
+        .await;
+    let (project_a, worktree_id) = client_a
+        .build_local_project(path!("/root/dir-1"), cx_a)
+        .await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Open the file on client B.
+    let (buffer_b, _handle) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("a.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    // Request the definition of a symbol as the guest.
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
+        |_, _| async move {
+            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
+                lsp::Location::new(
+                    lsp::Uri::from_file_path(path!("/root/dir-2/b.rs")).unwrap(),
+                    lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
+                ),
+            )))
+        },
+    );
+    cx_a.run_until_parked();
+    cx_b.run_until_parked();
+
+    let definitions_1 = project_b
+        .update(cx_b, |p, cx| p.definitions(&buffer_b, 23, cx))
+        .await
+        .unwrap()
+        .unwrap();
+    cx_b.read(|cx| {
+        assert_eq!(
+            definitions_1.len(),
+            1,
+            "Unexpected definitions: {definitions_1:?}"
+        );
+        assert_eq!(project_b.read(cx).worktrees(cx).count(), 2);
+        let target_buffer = definitions_1[0].target.buffer.read(cx);
+        assert_eq!(
+            target_buffer.text(),
+            "const TWO: c::T2 = 2;\nconst THREE: usize = 3;"
+        );
+        assert_eq!(
+            definitions_1[0].target.range.to_point(target_buffer),
+            Point::new(0, 6)..Point::new(0, 9)
+        );
+    });
+
+    // Try getting more definitions for the same buffer, ensuring the buffer gets reused from
+    // the previous call to `definition`.
+    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
+        |_, _| async move {
+            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
+                lsp::Location::new(
+                    lsp::Uri::from_file_path(path!("/root/dir-2/b.rs")).unwrap(),
+                    lsp::Range::new(lsp::Position::new(1, 6), lsp::Position::new(1, 11)),
+                ),
+            )))
+        },
+    );
+
+    let definitions_2 = project_b
+        .update(cx_b, |p, cx| p.definitions(&buffer_b, 33, cx))
+        .await
+        .unwrap()
+        .unwrap();
+    cx_b.read(|cx| {
+        assert_eq!(definitions_2.len(), 1);
+        assert_eq!(project_b.read(cx).worktrees(cx).count(), 2);
+        let target_buffer = definitions_2[0].target.buffer.read(cx);
+        assert_eq!(
+            target_buffer.text(),
+            "const TWO: c::T2 = 2;\nconst THREE: usize = 3;"
+        );
+        assert_eq!(
+            definitions_2[0].target.range.to_point(target_buffer),
+            Point::new(1, 6)..Point::new(1, 11)
+        );
+    });
+    assert_eq!(
+        definitions_1[0].target.buffer,
+        definitions_2[0].target.buffer
+    );
+
+    fake_language_server.set_request_handler::<lsp::request::GotoTypeDefinition, _, _>(
+        |req, _| async move {
+            assert_eq!(
+                req.text_document_position_params.position,
+                lsp::Position::new(0, 7)
+            );
+            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
+                lsp::Location::new(
+                    lsp::Uri::from_file_path(path!("/root/dir-2/c.rs")).unwrap(),
+                    lsp::Range::new(lsp::Position::new(0, 5), lsp::Position::new(0, 7)),
+                ),
+            )))
+        },
+    );
+
+    let type_definitions = project_b
+        .update(cx_b, |p, cx| p.type_definitions(&buffer_b, 7, cx))
+        .await
+        .unwrap()
+        .unwrap();
+    cx_b.read(|cx| {
+        assert_eq!(
+            type_definitions.len(),
+            1,
+            "Unexpected type definitions: {type_definitions:?}"
+        );
+        let target_buffer = type_definitions[0].target.buffer.read(cx);
+        assert_eq!(target_buffer.text(), "type T2 = usize;");
+        assert_eq!(
+            type_definitions[0].target.range.to_point(target_buffer),
+            Point::new(0, 5)..Point::new(0, 7)
+        );
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_references(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    let capabilities = lsp::ServerCapabilities {
+        references_provider: Some(lsp::OneOf::Left(true)),
+        ..lsp::ServerCapabilities::default()
+    };
+    client_a.language_registry().add(rust_lang());
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "Rust",
+        FakeLspAdapter {
+            name: "my-fake-lsp-adapter",
+            capabilities: capabilities.clone(),
+            ..FakeLspAdapter::default()
+        },
+    );
+    client_b.language_registry().add(rust_lang());
+    client_b.language_registry().register_fake_lsp_adapter(
+        "Rust",
+        FakeLspAdapter {
+            name: "my-fake-lsp-adapter",
+            capabilities,
+            ..FakeLspAdapter::default()
+        },
+    );
+
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/root"),
+            json!({
+                "dir-1": {
+                    "one.rs": "const ONE: usize = 1;",
+                    "two.rs": "const TWO: usize = one::ONE + one::ONE;",
+                },
+                "dir-2": {
+                    "three.rs": "const THREE: usize = two::TWO + one::ONE;",
+                }
+            }),
+        )
+        .await;
+    let (project_a, worktree_id) = client_a
+        .build_local_project(path!("/root/dir-1"), cx_a)
+        .await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Open the file on client B.
+    let (buffer_b, _handle) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("one.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    // Request references to a symbol as the guest.
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    let (lsp_response_tx, rx) = mpsc::unbounded::<Result<Option<Vec<lsp::Location>>>>();
+    fake_language_server.set_request_handler::<lsp::request::References, _, _>({
+        let rx = Arc::new(Mutex::new(Some(rx)));
+        move |params, _| {
+            assert_eq!(
+                params.text_document_position.text_document.uri.as_str(),
+                uri!("file:///root/dir-1/one.rs")
+            );
+            let rx = rx.clone();
+            async move {
+                let mut response_rx = rx.lock().take().unwrap();
+                let result = response_rx.next().await.unwrap();
+                *rx.lock() = Some(response_rx);
+                result
+            }
+        }
+    });
+    cx_a.run_until_parked();
+    cx_b.run_until_parked();
+
+    let references = project_b.update(cx_b, |p, cx| p.references(&buffer_b, 7, cx));
+
+    // User is informed that a request is pending.
+    executor.run_until_parked();
+    project_b.read_with(cx_b, |project, cx| {
+        let status = project.language_server_statuses(cx).next().unwrap().1;
+        assert_eq!(status.name.0, "my-fake-lsp-adapter");
+        assert_eq!(
+            status.pending_work.values().next().unwrap().message,
+            Some("Finding references...".into())
+        );
+    });
+
+    // Cause the language server to respond.
+    lsp_response_tx
+        .unbounded_send(Ok(Some(vec![
+            lsp::Location {
+                uri: lsp::Uri::from_file_path(path!("/root/dir-1/two.rs")).unwrap(),
+                range: lsp::Range::new(lsp::Position::new(0, 24), lsp::Position::new(0, 27)),
+            },
+            lsp::Location {
+                uri: lsp::Uri::from_file_path(path!("/root/dir-1/two.rs")).unwrap(),
+                range: lsp::Range::new(lsp::Position::new(0, 35), lsp::Position::new(0, 38)),
+            },
+            lsp::Location {
+                uri: lsp::Uri::from_file_path(path!("/root/dir-2/three.rs")).unwrap(),
+                range: lsp::Range::new(lsp::Position::new(0, 37), lsp::Position::new(0, 40)),
+            },
+        ])))
+        .unwrap();
+
+    let references = references.await.unwrap().unwrap();
+    executor.run_until_parked();
+    project_b.read_with(cx_b, |project, cx| {
+        // User is informed that a request is no longer pending.
+        let status = project.language_server_statuses(cx).next().unwrap().1;
+        assert!(status.pending_work.is_empty());
+
+        assert_eq!(references.len(), 3);
+        assert_eq!(project.worktrees(cx).count(), 2);
+
+        let two_buffer = references[0].buffer.read(cx);
+        let three_buffer = references[2].buffer.read(cx);
+        assert_eq!(
+            two_buffer.file().unwrap().path().as_ref(),
+            rel_path("two.rs")
+        );
+        assert_eq!(references[1].buffer, references[0].buffer);
+        assert_eq!(
+            three_buffer.file().unwrap().full_path(cx),
+            Path::new(path!("/root/dir-2/three.rs"))
+        );
+
+        assert_eq!(references[0].range.to_offset(two_buffer), 24..27);
+        assert_eq!(references[1].range.to_offset(two_buffer), 35..38);
+        assert_eq!(references[2].range.to_offset(three_buffer), 37..40);
+    });
+
+    let references = project_b.update(cx_b, |p, cx| p.references(&buffer_b, 7, cx));
+
+    // User is informed that a request is pending.
+    executor.run_until_parked();
+    project_b.read_with(cx_b, |project, cx| {
+        let status = project.language_server_statuses(cx).next().unwrap().1;
+        assert_eq!(status.name.0, "my-fake-lsp-adapter");
+        assert_eq!(
+            status.pending_work.values().next().unwrap().message,
+            Some("Finding references...".into())
+        );
+    });
+
+    // Cause the LSP request to fail.
+    lsp_response_tx
+        .unbounded_send(Err(anyhow!("can't find references")))
+        .unwrap();
+    assert_eq!(references.await.unwrap().unwrap(), []);
+
+    // User is informed that the request is no longer pending.
+    executor.run_until_parked();
+    project_b.read_with(cx_b, |project, cx| {
+        let status = project.language_server_statuses(cx).next().unwrap().1;
+        assert!(status.pending_work.is_empty());
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_project_search(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    client_a
+        .fs()
+        .insert_tree(
+            "/root",
+            json!({
+                "dir-1": {
+                    "a": "hello world",
+                    "b": "goodnight moon",
+                    "c": "a world of goo",
+                    "d": "world champion of clown world",
+                },
+                "dir-2": {
+                    "e": "disney world is fun",
+                }
+            }),
+        )
+        .await;
+    let (project_a, _) = client_a.build_local_project("/root/dir-1", cx_a).await;
+    let (worktree_2, _) = project_a
+        .update(cx_a, |p, cx| {
+            p.find_or_create_worktree("/root/dir-2", true, cx)
+        })
+        .await
+        .unwrap();
+    worktree_2
+        .read_with(cx_a, |tree, _| tree.as_local().unwrap().scan_complete())
+        .await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Perform a search as the guest.
+    let mut results = HashMap::default();
+    let search_rx = project_b.update(cx_b, |project, cx| {
+        project.search(
+            SearchQuery::text(
+                "world",
+                false,
+                false,
+                false,
+                Default::default(),
+                Default::default(),
+                false,
+                None,
+            )
+            .unwrap(),
+            cx,
+        )
+    });
+    while let Ok(result) = search_rx.rx.recv().await {
+        match result {
+            SearchResult::Buffer { buffer, ranges } => {
+                results.entry(buffer).or_insert(ranges);
+            }
+            SearchResult::LimitReached => {
+                panic!(
+                    "Unexpectedly reached search limit in tests. If you do want to assert limit-reached, change this panic call."
+                )
+            }
+        };
+    }
+
+    let mut ranges_by_path = results
+        .into_iter()
+        .map(|(buffer, ranges)| {
+            buffer.read_with(cx_b, |buffer, cx| {
+                let path = buffer.file().unwrap().full_path(cx);
+                let offset_ranges = ranges
+                    .into_iter()
+                    .map(|range| range.to_offset(buffer))
+                    .collect::<Vec<_>>();
+                (path, offset_ranges)
+            })
+        })
+        .collect::<Vec<_>>();
+    ranges_by_path.sort_by_key(|(path, _)| path.clone());
+
+    assert_eq!(
+        ranges_by_path,
+        &[
+            (PathBuf::from("dir-1/a"), vec![6..11]),
+            (PathBuf::from("dir-1/c"), vec![2..7]),
+            (PathBuf::from("dir-1/d"), vec![0..5, 24..29]),
+            (PathBuf::from("dir-2/e"), vec![7..12]),
+        ]
+    );
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_document_highlights(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/root-1"),
+            json!({
+                "main.rs": "fn double(number: i32) -> i32 { number + number }",
+            }),
+        )
+        .await;
+
+    client_a.language_registry().add(rust_lang());
+    let capabilities = lsp::ServerCapabilities {
+        document_highlight_provider: Some(lsp::OneOf::Left(true)),
+        ..lsp::ServerCapabilities::default()
+    };
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "Rust",
+        FakeLspAdapter {
+            capabilities: capabilities.clone(),
+            ..FakeLspAdapter::default()
+        },
+    );
+    client_b.language_registry().add(rust_lang());
+    client_b.language_registry().register_fake_lsp_adapter(
+        "Rust",
+        FakeLspAdapter {
+            capabilities,
+            ..FakeLspAdapter::default()
+        },
+    );
+
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/root-1"), cx_a).await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Open the file on client B.
+    let (buffer_b, _handle) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("main.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    // Request document highlights as the guest.
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    fake_language_server.set_request_handler::<lsp::request::DocumentHighlightRequest, _, _>(
+        |params, _| async move {
+            assert_eq!(
+                params
+                    .text_document_position_params
+                    .text_document
+                    .uri
+                    .as_str(),
+                uri!("file:///root-1/main.rs")
+            );
+            assert_eq!(
+                params.text_document_position_params.position,
+                lsp::Position::new(0, 34)
+            );
+            Ok(Some(vec![
+                lsp::DocumentHighlight {
+                    kind: Some(lsp::DocumentHighlightKind::WRITE),
+                    range: lsp::Range::new(lsp::Position::new(0, 10), lsp::Position::new(0, 16)),
+                },
+                lsp::DocumentHighlight {
+                    kind: Some(lsp::DocumentHighlightKind::READ),
+                    range: lsp::Range::new(lsp::Position::new(0, 32), lsp::Position::new(0, 38)),
+                },
+                lsp::DocumentHighlight {
+                    kind: Some(lsp::DocumentHighlightKind::READ),
+                    range: lsp::Range::new(lsp::Position::new(0, 41), lsp::Position::new(0, 47)),
+                },
+            ]))
+        },
+    );
+    cx_a.run_until_parked();
+    cx_b.run_until_parked();
+
+    let highlights = project_b
+        .update(cx_b, |p, cx| p.document_highlights(&buffer_b, 34, cx))
+        .await
+        .unwrap();
+
+    buffer_b.read_with(cx_b, |buffer, _| {
+        let snapshot = buffer.snapshot();
+
+        let highlights = highlights
+            .into_iter()
+            .map(|highlight| (highlight.kind, highlight.range.to_offset(&snapshot)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            highlights,
+            &[
+                (lsp::DocumentHighlightKind::WRITE, 10..16),
+                (lsp::DocumentHighlightKind::READ, 32..38),
+                (lsp::DocumentHighlightKind::READ, 41..47)
+            ]
+        )
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_lsp_hover(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/root-1"),
+            json!({
+                "main.rs": "use std::collections::HashMap;",
+            }),
+        )
+        .await;
+
+    client_a.language_registry().add(rust_lang());
+    let language_server_names = ["rust-analyzer", "CrabLang-ls"];
+    let capabilities_1 = lsp::ServerCapabilities {
+        hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+        ..lsp::ServerCapabilities::default()
+    };
+    let capabilities_2 = lsp::ServerCapabilities {
+        hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+        ..lsp::ServerCapabilities::default()
+    };
+    let mut language_servers = [
+        client_a.language_registry().register_fake_lsp(
+            "Rust",
+            FakeLspAdapter {
+                name: language_server_names[0],
+                capabilities: capabilities_1.clone(),
+                ..FakeLspAdapter::default()
+            },
+        ),
+        client_a.language_registry().register_fake_lsp(
+            "Rust",
+            FakeLspAdapter {
+                name: language_server_names[1],
+                capabilities: capabilities_2.clone(),
+                ..FakeLspAdapter::default()
+            },
+        ),
+    ];
+    client_b.language_registry().add(rust_lang());
+    client_b.language_registry().register_fake_lsp_adapter(
+        "Rust",
+        FakeLspAdapter {
+            name: language_server_names[0],
+            capabilities: capabilities_1,
+            ..FakeLspAdapter::default()
+        },
+    );
+    client_b.language_registry().register_fake_lsp_adapter(
+        "Rust",
+        FakeLspAdapter {
+            name: language_server_names[1],
+            capabilities: capabilities_2,
+            ..FakeLspAdapter::default()
+        },
+    );
+
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/root-1"), cx_a).await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Open the file as the guest
+    let (buffer_b, _handle) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("main.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    let mut servers_with_hover_requests = HashMap::default();
+    for i in 0..language_server_names.len() {
+        let new_server = language_servers[i].next().await.unwrap_or_else(|| {
+            panic!(
+                "Failed to get language server #{i} with name {}",
+                &language_server_names[i]
+            )
+        });
+        let new_server_name = new_server.server.name();
+        assert!(
+            !servers_with_hover_requests.contains_key(&new_server_name),
+            "Unexpected: initialized server with the same name twice. Name: `{new_server_name}`"
+        );
+        match new_server_name.as_ref() {
+            "CrabLang-ls" => {
+                servers_with_hover_requests.insert(
+                    new_server_name.clone(),
+                    new_server.set_request_handler::<lsp::request::HoverRequest, _, _>(
+                        move |params, _| {
+                            assert_eq!(
+                                params
+                                    .text_document_position_params
+                                    .text_document
+                                    .uri
+                                    .as_str(),
+                                uri!("file:///root-1/main.rs")
+                            );
+                            let name = new_server_name.clone();
+                            async move {
+                                Ok(Some(lsp::Hover {
+                                    contents: lsp::HoverContents::Scalar(
+                                        lsp::MarkedString::String(format!("{name} hover")),
+                                    ),
+                                    range: None,
+                                }))
+                            }
+                        },
+                    ),
+                );
+            }
+            "rust-analyzer" => {
+                servers_with_hover_requests.insert(
+                    new_server_name.clone(),
+                    new_server.set_request_handler::<lsp::request::HoverRequest, _, _>(
+                        |params, _| async move {
+                            assert_eq!(
+                                params
+                                    .text_document_position_params
+                                    .text_document
+                                    .uri
+                                    .as_str(),
+                                uri!("file:///root-1/main.rs")
+                            );
+                            assert_eq!(
+                                params.text_document_position_params.position,
+                                lsp::Position::new(0, 22)
+                            );
+                            Ok(Some(lsp::Hover {
+                                contents: lsp::HoverContents::Array(vec![
+                                    lsp::MarkedString::String("Test hover content.".to_string()),
+                                    lsp::MarkedString::LanguageString(lsp::LanguageString {
+                                        language: "Rust".to_string(),
+                                        value: "let foo = 42;".to_string(),
+                                    }),
+                                ]),
+                                range: Some(lsp::Range::new(
+                                    lsp::Position::new(0, 22),
+                                    lsp::Position::new(0, 29),
+                                )),
+                            }))
+                        },
+                    ),
+                );
+            }
+            unexpected => panic!("Unexpected server name: {unexpected}"),
+        }
+    }
+    cx_a.run_until_parked();
+    cx_b.run_until_parked();
+
+    // Request hover information as the guest.
+    let mut hovers = project_b
+        .update(cx_b, |p, cx| p.hover(&buffer_b, 22, cx))
+        .await
+        .unwrap();
+    assert_eq!(
+        hovers.len(),
+        2,
+        "Expected two hovers from both language servers, but got: {hovers:?}"
+    );
+
+    let _: Vec<()> = futures::future::join_all(servers_with_hover_requests.into_values().map(
+        |mut hover_request| async move {
+            hover_request
+                .next()
+                .await
+                .expect("All hover requests should have been triggered")
+        },
+    ))
+    .await;
+
+    hovers.sort_by_key(|hover| hover.contents.len());
+    let first_hover = hovers.first().cloned().unwrap();
+    assert_eq!(
+        first_hover.contents,
+        vec![project::HoverBlock {
+            text: "CrabLang-ls hover".to_string(),
+            kind: HoverBlockKind::Markdown,
+        },]
+    );
+    let second_hover = hovers.last().cloned().unwrap();
+    assert_eq!(
+        second_hover.contents,
+        vec![
+            project::HoverBlock {
+                text: "Test hover content.".to_string(),
+                kind: HoverBlockKind::Markdown,
+            },
+            project::HoverBlock {
+                text: "let foo = 42;".to_string(),
+                kind: HoverBlockKind::Code {
+                    language: "Rust".to_string()
+                },
+            }
+        ]
+    );
+    buffer_b.read_with(cx_b, |buffer, _| {
+        let snapshot = buffer.snapshot();
+        assert_eq!(second_hover.range.unwrap().to_offset(&snapshot), 22..29);
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_project_symbols(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    client_a.language_registry().add(rust_lang());
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "Rust",
+        FakeLspAdapter {
+            capabilities: lsp::ServerCapabilities {
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/code"),
+            json!({
+                "crate-1": {
+                    "one.rs": "const ONE: usize = 1;",
+                },
+                "crate-2": {
+                    "two.rs": "const TWO: usize = 2; const THREE: usize = 3;",
+                },
+                "private": {
+                    "passwords.txt": "the-password",
+                }
+            }),
+        )
+        .await;
+    let (project_a, worktree_id) = client_a
+        .build_local_project(path!("/code/crate-1"), cx_a)
+        .await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    // Cause the language server to start.
+    let _buffer = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("one.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    executor.run_until_parked();
+    fake_language_server.set_request_handler::<lsp::WorkspaceSymbolRequest, _, _>(
+        |_, _| async move {
+            Ok(Some(lsp::WorkspaceSymbolResponse::Flat(vec![
+                #[allow(deprecated)]
+                lsp::SymbolInformation {
+                    name: "TWO".into(),
+                    location: lsp::Location {
+                        uri: lsp::Uri::from_file_path(path!("/code/crate-2/two.rs")).unwrap(),
+                        range: lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
+                    },
+                    kind: lsp::SymbolKind::CONSTANT,
+                    tags: None,
+                    container_name: None,
+                    deprecated: None,
+                },
+            ])))
+        },
+    );
+
+    // Request the definition of a symbol as the guest.
+    let symbols = project_b
+        .update(cx_b, |p, cx| p.symbols("two", cx))
+        .await
+        .unwrap();
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0].name, "TWO");
+
+    // Open one of the returned symbols.
+    let buffer_b_2 = project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer_for_symbol(&symbols[0], cx)
+        })
+        .await
+        .unwrap();
+
+    buffer_b_2.read_with(cx_b, |buffer, cx| {
+        assert_eq!(
+            buffer.file().unwrap().full_path(cx),
+            Path::new(path!("/code/crate-2/two.rs"))
+        );
+    });
+
+    // Attempt to craft a symbol and violate host's privacy by opening an arbitrary file.
+    let mut fake_symbol = symbols[0].clone();
+    fake_symbol.path = SymbolLocation::OutsideProject {
+        abs_path: Path::new(path!("/code/secrets")).into(),
+        signature: [0x17; 32],
+    };
+    let error = project_b
+        .update(cx_b, |project, cx| {
+            project.open_buffer_for_symbol(&fake_symbol, cx)
+        })
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("invalid symbol signature"));
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_open_buffer_while_getting_definition_pointing_to_it(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    mut rng: StdRng,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+
+    let capabilities = lsp::ServerCapabilities {
+        definition_provider: Some(OneOf::Left(true)),
+        ..lsp::ServerCapabilities::default()
+    };
+    client_a.language_registry().add(rust_lang());
+    let mut fake_language_servers = client_a.language_registry().register_fake_lsp(
+        "Rust",
+        FakeLspAdapter {
+            capabilities: capabilities.clone(),
+            ..FakeLspAdapter::default()
+        },
+    );
+    client_b.language_registry().add(rust_lang());
+    client_b.language_registry().register_fake_lsp_adapter(
+        "Rust",
+        FakeLspAdapter {
+            capabilities,
+            ..FakeLspAdapter::default()
+        },
+    );
+
+    client_a
+        .fs()
+        .insert_tree(
+            path!("/root"),
+            json!({
+                "a.rs": "const ONE: usize = b::TWO;",
+                "b.rs": "const TWO: usize = 2",
+            }),
+        )
+        .await;
+    let (project_a, worktree_id) = client_a.build_local_project(path!("/root"), cx_a).await;
+    let project_id = active_call_a
+        .update(cx_a, |call, cx| call.share_project(project_a.clone(), cx))
+        .await
+        .unwrap();
+    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+
+    let (buffer_b1, _lsp) = project_b
+        .update(cx_b, |p, cx| {
+            p.open_buffer_with_lsp((worktree_id, rel_path("a.rs")), cx)
+        })
+        .await
+        .unwrap();
+
+    let fake_language_server = fake_language_servers.next().await.unwrap();
+    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
+        |_, _| async move {
+            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
+                lsp::Location::new(
+                    lsp::Uri::from_file_path(path!("/root/b.rs")).unwrap(),
+                    lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
+                ),
+            )))
+        },
+    );
+
+    let definitions;
+    let buffer_b2;
+    if rng.random() {
+        cx_a.run_until_parked();
+        cx_b.run_until_parked();
+        definitions = project_b.update(cx_b, |p, cx| p.definitions(&buffer_b1, 23, cx));
+        (buffer_b2, _) = project_b
+            .update(cx_b, |p, cx| {
+                p.open_buffer_with_lsp((worktree_id, rel_path("b.rs")), cx)
+            })
+            .await
+            .unwrap();
+    } else {
+        (buffer_b2, _) = project_b
+            .update(cx_b, |p, cx| {
+                p.open_buffer_with_lsp((worktree_id, rel_path("b.rs")), cx)
+            })
+            .await
+            .unwrap();
+        cx_a.run_until_parked();
+        cx_b.run_until_parked();
+        definitions = project_b.update(cx_b, |p, cx| p.definitions(&buffer_b1, 23, cx));
+    }
+
+    let definitions = definitions.await.unwrap().unwrap();
+    assert_eq!(
+        definitions.len(),
+        1,
+        "Unexpected definitions: {definitions:?}"
+    );
+    assert_eq!(definitions[0].target.buffer, buffer_b2);
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_contacts(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+    cx_d: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    let client_d = server.create_client(cx_d, "user_d").await;
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
+        .await;
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let active_call_c = cx_c.read(ActiveCall::global);
+    let _active_call_d = cx_d.read(ActiveCall::global);
+
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_b".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(contacts(&client_d, cx_d), []);
+
+    server.disconnect_client(client_c.peer_id().unwrap());
+    server.forbid_connections();
+    executor.advance_clock(RECEIVE_TIMEOUT + RECONNECT_TIMEOUT);
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "offline", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_c".to_string(), "offline", "free")
+        ]
+    );
+    assert_eq!(contacts(&client_c, cx_c), []);
+    assert_eq!(contacts(&client_d, cx_d), []);
+
+    server.allow_connections();
+    client_c
+        .connect(false, &cx_c.to_async())
+        .await
+        .into_response()
+        .unwrap();
+
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_b".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(contacts(&client_d, cx_d), []);
+
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(contacts(&client_d, cx_d), []);
+
+    // Client B and client D become contacts while client B is being called.
+    server
+        .make_contacts(&mut [(&client_b, cx_b), (&client_d, cx_d)])
+        .await;
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free"),
+            ("user_d".to_string(), "online", "free"),
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "busy")]
+    );
+
+    active_call_b.update(cx_b, |call, cx| call.decline_incoming(cx).unwrap());
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "free"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "free"),
+            ("user_b".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "free")]
+    );
+
+    active_call_c
+        .update(cx_c, |call, cx| {
+            call.invite(client_a.user_id().unwrap(), None, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "busy"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "free")]
+    );
+
+    active_call_a
+        .update(cx_a, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
-    let project_b = client_b.join_remote_project(project_id, cx_b).await;
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "free"),
+            ("user_c".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "busy"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "free")]
+    );
 
-    let (buffer_b1, _lsp) = project_b
-        .update(cx_b, |p, cx| {
-            p.open_buffer_with_lsp((worktree_id, rel_path("a.rs")), cx)
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
         })
         .await
         .unwrap();
-
-    let fake_language_server = fake_language_servers.next().await.unwrap();
-    fake_language_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(
-        |_, _| async move {
-            Ok(Some(lsp::GotoDefinitionResponse::Scalar(
-                lsp::Location::new(
-                    lsp::Uri::from_file_path(path!("/root/b.rs")).unwrap(),
-                    lsp::Range::new(lsp::Position::new(0, 6), lsp::Position::new(0, 9)),
-                ),
-            )))
-        },
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "busy")
+        ]
     );
-
-    let definitions;
-    let buffer_b2;
-    if rng.random() {
-        cx_a.run_until_parked();
-        cx_b.run_until_parked();
-        definitions = project_b.update(cx_b, |p, cx| p.definitions(&buffer_b1, 23, cx));
-        (buffer_b2, _) = project_b
-            .update(cx_b, |p, cx| {
-                p.open_buffer_with_lsp((worktree_id, rel_path("b.rs")), cx)
-            })
-            .await
-            .unwrap();
-    } else {
-        (buffer_b2, _) = project_b
-            .update(cx_b, |p, cx| {
-                p.open_buffer_with_lsp((worktree_id, rel_path("b.rs")), cx)
-            })
-            .await
-            .unwrap();
-        cx_a.run_until_parked();
-        cx_b.run_until_parked();
-        definitions = project_b.update(cx_b, |p, cx| p.definitions(&buffer_b1, 23, cx));
-    }
-
-    let definitions = definitions.await.unwrap().unwrap();
     assert_eq!(
-        definitions.len(),
-        1,
-        "Unexpected definitions: {definitions:?}"
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "busy"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "busy")]
     );
-    assert_eq!(definitions[0].target.buffer, buffer_b2);
-}
-
-#[gpui::test(iterations = 10)]
-async fn test_contacts(
-    executor: BackgroundExecutor,
-    cx_a: &mut TestAppContext,
-    cx_b: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
-    cx_d: &mut TestAppContext,
-) {
-    let mut server = TestServer::start(executor.clone()).await;
-    let client_a = server.create_client(cx_a, "user_a").await;
-    let client_b = server.create_client(cx_b, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
-    let client_d = server.create_client(cx_d, "user_d").await;
-    server
-        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b), (&client_c, cx_c)])
-        .await;
-    let active_call_a = cx_a.read(ActiveCall::global);
-    let active_call_b = cx_b.read(ActiveCall::global);
-    let active_call_c = cx_c.read(ActiveCall::global);
-    let _active_call_d = cx_d.read(ActiveCall::global);
 
+    active_call_a
+        .update(cx_a, |call, cx| call.hang_up(cx))
+        .await
+        .unwrap();
     executor.run_until_parked();
     assert_eq!(
         contacts(&client_a, cx_a),
@@ -5918,7 +6900,8 @@ async fn test_contacts(
         contacts(&client_b, cx_b),
         [
             ("user_a".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free")
+            ("user_c".to_string(), "online", "free"),
+            ("user_d".to_string(), "online", "free")
         ]
     );
     assert_eq!(
@@ -5928,610 +6911,1085 @@ async fn test_contacts(
             ("user_b".to_string(), "online", "free")
         ]
     );
-    assert_eq!(contacts(&client_d, cx_d), []);
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "free")]
+    );
+
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(
+        contacts(&client_a, cx_a),
+        [
+            ("user_b".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_b, cx_b),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_c".to_string(), "online", "free"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "online", "busy"),
+            ("user_b".to_string(), "online", "busy")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "busy")]
+    );
 
-    server.disconnect_client(client_c.peer_id().unwrap());
     server.forbid_connections();
+    server.disconnect_client(client_a.peer_id().unwrap());
     executor.advance_clock(RECEIVE_TIMEOUT + RECONNECT_TIMEOUT);
+    assert_eq!(contacts(&client_a, cx_a), []);
     assert_eq!(
-        contacts(&client_a, cx_a),
+        contacts(&client_b, cx_b),
         [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "offline", "free")
+            ("user_a".to_string(), "offline", "free"),
+            ("user_c".to_string(), "online", "free"),
+            ("user_d".to_string(), "online", "free")
+        ]
+    );
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [
+            ("user_a".to_string(), "offline", "free"),
+            ("user_b".to_string(), "online", "free")
         ]
     );
+    assert_eq!(
+        contacts(&client_d, cx_d),
+        [("user_b".to_string(), "online", "free")]
+    );
+
+    // Test removing a contact
+    client_b
+        .user_store()
+        .update(cx_b, |store, cx| {
+            store.remove_contact(client_c.user_id().unwrap(), cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
     assert_eq!(
         contacts(&client_b, cx_b),
         [
-            ("user_a".to_string(), "online", "free"),
-            ("user_c".to_string(), "offline", "free")
+            ("user_a".to_string(), "offline", "free"),
+            ("user_d".to_string(), "online", "free")
         ]
     );
-    assert_eq!(contacts(&client_c, cx_c), []);
-    assert_eq!(contacts(&client_d, cx_d), []);
+    assert_eq!(
+        contacts(&client_c, cx_c),
+        [("user_a".to_string(), "offline", "free"),]
+    );
+
+    fn contacts(
+        client: &TestClient,
+        cx: &TestAppContext,
+    ) -> Vec<(String, &'static str, &'static str)> {
+        client.user_store().read_with(cx, |store, _| {
+            store
+                .contacts()
+                .iter()
+                .map(|contact| {
+                    (
+                        contact.user.github_login.clone().to_string(),
+                        if contact.online { "online" } else { "offline" },
+                        if contact.busy { "busy" } else { "free" },
+                    )
+                })
+                .collect()
+        })
+    }
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_contact_requests(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_a2: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+    cx_b2: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
+    cx_c2: &mut TestAppContext,
+) {
+    // Connect to a server as 3 clients.
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_a2 = server.create_client(cx_a2, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    let client_b2 = server.create_client(cx_b2, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
+    let client_c2 = server.create_client(cx_c2, "user_c").await;
 
-    server.allow_connections();
+    assert_eq!(client_a.user_id().unwrap(), client_a2.user_id().unwrap());
+    assert_eq!(client_b.user_id().unwrap(), client_b2.user_id().unwrap());
+    assert_eq!(client_c.user_id().unwrap(), client_c2.user_id().unwrap());
+
+    // User A and User C request that user B become their contact.
+    client_a
+        .user_store()
+        .update(cx_a, |store, cx| {
+            store.request_contact(client_b.user_id().unwrap(), cx)
+        })
+        .await
+        .unwrap();
     client_c
-        .connect(false, &cx_c.to_async())
+        .user_store()
+        .update(cx_c, |store, cx| {
+            store.request_contact(client_b.user_id().unwrap(), cx)
+        })
         .await
-        .into_response()
         .unwrap();
-
     executor.run_until_parked();
+
+    // All users see the pending request appear in all their clients.
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free")
-        ]
+        client_a.summarize_contacts(cx_a).outgoing_requests,
+        &["user_b"]
     );
     assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free")
-        ]
+        client_a2.summarize_contacts(cx_a2).outgoing_requests,
+        &["user_b"]
     );
     assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_b".to_string(), "online", "free")
-        ]
+        client_b.summarize_contacts(cx_b).incoming_requests,
+        &["user_a", "user_c"]
     );
-    assert_eq!(contacts(&client_d, cx_d), []);
-
-    active_call_a
-        .update(cx_a, |call, cx| {
-            call.invite(client_b.user_id().unwrap(), None, cx)
-        })
-        .await
-        .unwrap();
-    executor.run_until_parked();
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free")
-        ]
+        client_b2.summarize_contacts(cx_b2).incoming_requests,
+        &["user_a", "user_c"]
     );
     assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free")
-        ]
+        client_c.summarize_contacts(cx_c).outgoing_requests,
+        &["user_b"]
     );
     assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "busy")
-        ]
+        client_c2.summarize_contacts(cx_c2).outgoing_requests,
+        &["user_b"]
     );
-    assert_eq!(contacts(&client_d, cx_d), []);
 
-    // Client B and client D become contacts while client B is being called.
-    server
-        .make_contacts(&mut [(&client_b, cx_b), (&client_d, cx_d)])
-        .await;
+    // Contact requests are present upon connecting (tested here via disconnect/reconnect)
+    disconnect_and_reconnect(&client_a, cx_a).await;
+    disconnect_and_reconnect(&client_b, cx_b).await;
+    disconnect_and_reconnect(&client_c, cx_c).await;
     executor.run_until_parked();
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free"),
-            ("user_d".to_string(), "online", "free"),
-        ]
+        client_a.summarize_contacts(cx_a).outgoing_requests,
+        &["user_b"]
     );
     assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "busy")
-        ]
+        client_b.summarize_contacts(cx_b).incoming_requests,
+        &["user_a", "user_c"]
     );
     assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "busy")]
+        client_c.summarize_contacts(cx_c).outgoing_requests,
+        &["user_b"]
     );
 
-    active_call_b.update(cx_b, |call, cx| call.decline_incoming(cx).unwrap());
+    // User B accepts the request from user A.
+    client_b
+        .user_store()
+        .update(cx_b, |store, cx| {
+            store.respond_to_contact_request(client_a.user_id().unwrap(), true, cx)
+        })
+        .await
+        .unwrap();
+
+    executor.run_until_parked();
+
+    // User B sees user A as their contact now in all client, and the incoming request from them is removed.
+    let contacts_b = client_b.summarize_contacts(cx_b);
+    assert_eq!(contacts_b.current, &["user_a"]);
+    assert_eq!(contacts_b.incoming_requests, &["user_c"]);
+    let contacts_b2 = client_b2.summarize_contacts(cx_b2);
+    assert_eq!(contacts_b2.current, &["user_a"]);
+    assert_eq!(contacts_b2.incoming_requests, &["user_c"]);
+
+    // User A sees user B as their contact now in all clients, and the outgoing request to them is removed.
+    let contacts_a = client_a.summarize_contacts(cx_a);
+    assert_eq!(contacts_a.current, &["user_b"]);
+    assert!(contacts_a.outgoing_requests.is_empty());
+    let contacts_a2 = client_a2.summarize_contacts(cx_a2);
+    assert_eq!(contacts_a2.current, &["user_b"]);
+    assert!(contacts_a2.outgoing_requests.is_empty());
+
+    // Contacts are present upon connecting (tested here via disconnect/reconnect)
+    disconnect_and_reconnect(&client_a, cx_a).await;
+    disconnect_and_reconnect(&client_b, cx_b).await;
+    disconnect_and_reconnect(&client_c, cx_c).await;
     executor.run_until_parked();
+    assert_eq!(client_a.summarize_contacts(cx_a).current, &["user_b"]);
+    assert_eq!(client_b.summarize_contacts(cx_b).current, &["user_a"]);
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free")
-        ]
+        client_b.summarize_contacts(cx_b).incoming_requests,
+        &["user_c"]
     );
+    assert!(client_c.summarize_contacts(cx_c).current.is_empty());
     assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free"),
-            ("user_d".to_string(), "online", "free")
-        ]
+        client_c.summarize_contacts(cx_c).outgoing_requests,
+        &["user_b"]
     );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_b".to_string(), "online", "free")
-        ]
+
+    // User B rejects the request from user C.
+    client_b
+        .user_store()
+        .update(cx_b, |store, cx| {
+            store.respond_to_contact_request(client_c.user_id().unwrap(), false, cx)
+        })
+        .await
+        .unwrap();
+
+    executor.run_until_parked();
+
+    // User B doesn't see user C as their contact, and the incoming request from them is removed.
+    let contacts_b = client_b.summarize_contacts(cx_b);
+    assert_eq!(contacts_b.current, &["user_a"]);
+    assert!(contacts_b.incoming_requests.is_empty());
+    let contacts_b2 = client_b2.summarize_contacts(cx_b2);
+    assert_eq!(contacts_b2.current, &["user_a"]);
+    assert!(contacts_b2.incoming_requests.is_empty());
+
+    // User C doesn't see user B as their contact, and the outgoing request to them is removed.
+    let contacts_c = client_c.summarize_contacts(cx_c);
+    assert!(contacts_c.current.is_empty());
+    assert!(contacts_c.outgoing_requests.is_empty());
+    let contacts_c2 = client_c2.summarize_contacts(cx_c2);
+    assert!(contacts_c2.current.is_empty());
+    assert!(contacts_c2.outgoing_requests.is_empty());
+
+    // Incoming/outgoing requests are not present upon connecting (tested here via disconnect/reconnect)
+    disconnect_and_reconnect(&client_a, cx_a).await;
+    disconnect_and_reconnect(&client_b, cx_b).await;
+    disconnect_and_reconnect(&client_c, cx_c).await;
+    executor.run_until_parked();
+    assert_eq!(client_a.summarize_contacts(cx_a).current, &["user_b"]);
+    assert_eq!(client_b.summarize_contacts(cx_b).current, &["user_a"]);
+    assert!(
+        client_b
+            .summarize_contacts(cx_b)
+            .incoming_requests
+            .is_empty()
     );
-    assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "free")]
+    assert!(client_c.summarize_contacts(cx_c).current.is_empty());
+    assert!(
+        client_c
+            .summarize_contacts(cx_c)
+            .outgoing_requests
+            .is_empty()
     );
 
-    active_call_c
-        .update(cx_c, |call, cx| {
-            call.invite(client_a.user_id().unwrap(), None, cx)
+    async fn disconnect_and_reconnect(client: &TestClient, cx: &mut TestAppContext) {
+        client.disconnect(&cx.to_async());
+        client.clear_contacts(cx).await;
+        client
+            .connect(false, &cx.to_async())
+            .await
+            .into_response()
+            .unwrap();
+    }
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_join_call_after_screen_was_shared(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+
+    let active_call_a = cx_a.read(ActiveCall::global);
+    let active_call_b = cx_b.read(ActiveCall::global);
+
+    // Call users B and C from client A.
+    active_call_a
+        .update(cx_a, |call, cx| {
+            call.invite(client_b.user_id().unwrap(), None, cx)
         })
         .await
         .unwrap();
+
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
     executor.run_until_parked();
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "busy")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "busy"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "free")]
+        room_participants(&room_a, cx_a),
+        RoomParticipants {
+            remote: Default::default(),
+            pending: vec!["user_b".to_string()]
+        }
     );
 
-    active_call_a
-        .update(cx_a, |call, cx| call.accept_incoming(cx))
+    // User B receives the call.
+
+    let mut incoming_call_b = active_call_b.read_with(cx_b, |call, _| call.incoming());
+    let call_b = incoming_call_b.next().await.unwrap().unwrap();
+    assert_eq!(call_b.calling_user.github_login, "user_a");
+
+    // User A shares their screen
+    let display = gpui::TestScreenCaptureSource::new();
+    cx_a.set_screen_capture_sources(vec![display]);
+    let screen_a = cx_a
+        .update(|cx| cx.screen_capture_sources())
         .await
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .next()
         .unwrap();
-    executor.run_until_parked();
-    assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "busy")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "busy"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "free")]
-    );
 
     active_call_a
         .update(cx_a, |call, cx| {
-            call.invite(client_b.user_id().unwrap(), None, cx)
+            call.room()
+                .unwrap()
+                .update(cx, |room, cx| room.share_screen(screen_a, cx))
         })
         .await
         .unwrap();
-    executor.run_until_parked();
-    assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "busy")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "busy"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "busy")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "busy")]
-    );
 
-    active_call_a
-        .update(cx_a, |call, cx| call.hang_up(cx))
+    client_b.user_store().update(cx_b, |user_store, _| {
+        user_store.clear_cache();
+    });
+
+    // User B joins the room
+    active_call_b
+        .update(cx_b, |call, cx| call.accept_incoming(cx))
         .await
         .unwrap();
+
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    assert!(incoming_call_b.next().await.unwrap().is_none());
+
     executor.run_until_parked();
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_c".to_string(), "online", "free"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "free"),
-            ("user_b".to_string(), "online", "free")
-        ]
+        room_participants(&room_a, cx_a),
+        RoomParticipants {
+            remote: vec!["user_b".to_string()],
+            pending: vec![],
+        }
     );
     assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "free")]
+        room_participants(&room_b, cx_b),
+        RoomParticipants {
+            remote: vec!["user_a".to_string()],
+            pending: vec![],
+        }
     );
 
-    active_call_a
-        .update(cx_a, |call, cx| {
-            call.invite(client_b.user_id().unwrap(), None, cx)
+    // Ensure User B sees User A's screenshare.
+
+    room_b.read_with(cx_b, |room, _| {
+        assert_eq!(
+            room.remote_participants()
+                .get(&client_a.user_id().unwrap())
+                .unwrap()
+                .video_tracks
+                .len(),
+            1
+        );
+    });
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_share_camera(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
+
+    server.test_livekit_server.set_camera_devices(vec![
+        livekit_client::CameraDevice {
+            id: "front".into(),
+            name: "Front Camera".into(),
+        },
+        livekit_client::CameraDevice {
+            id: "back".into(),
+            name: "Back Camera".into(),
+        },
+    ]);
+
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_a = active_call_events(cx_a);
+
+    room_a
+        .update(cx_a, |room, cx| {
+            room.share_camera(Some("missing".into()), cx)
         })
         .await
+        .unwrap_err();
+    assert!(!room_a.read_with(cx_a, |room, _| room.is_sharing_camera()));
+
+    // User A shares both their camera and their screen.
+    room_a
+        .update(cx_a, |room, cx| room.share_camera(Some("back".into()), cx))
+        .await
+        .unwrap();
+    let display = gpui::TestScreenCaptureSource::new();
+    cx_a.set_screen_capture_sources(vec![display]);
+    let screen_a = cx_a
+        .update(|cx| cx.screen_capture_sources())
+        .await
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap();
+    room_a
+        .update(cx_a, |room, cx| room.share_screen(screen_a, cx))
+        .await
         .unwrap();
     executor.run_until_parked();
     assert_eq!(
-        contacts(&client_a, cx_a),
-        [
-            ("user_b".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_c".to_string(), "online", "free"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
-    assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "online", "busy"),
-            ("user_b".to_string(), "online", "busy")
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![
+            room::Event::LocalCameraStarted,
+            room::Event::LocalScreenShareStarted {
+                preset: ScreenSharePreset::High
+            },
         ]
     );
-    assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "busy")]
-    );
 
-    server.forbid_connections();
-    server.disconnect_client(client_a.peer_id().unwrap());
-    executor.advance_clock(RECEIVE_TIMEOUT + RECONNECT_TIMEOUT);
-    assert_eq!(contacts(&client_a, cx_a), []);
-    assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "offline", "free"),
-            ("user_c".to_string(), "online", "free"),
-            ("user_d".to_string(), "online", "free")
-        ]
-    );
+    // Lowering the screen share's quality keeps it going.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_screen_share_preset(ScreenSharePreset::Low, cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
     assert_eq!(
-        contacts(&client_c, cx_c),
-        [
-            ("user_a".to_string(), "offline", "free"),
-            ("user_b".to_string(), "online", "free")
-        ]
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![room::Event::ScreenSharePresetChanged {
+            preset: ScreenSharePreset::Low,
+            pinned: false,
+            stepped_down: false,
+        }]
     );
+    room_a.read_with(cx_a, |room, _| {
+        assert!(room.is_sharing_screen());
+        assert_eq!(room.screen_share_preset(), ScreenSharePreset::Low);
+    });
+
+    // User B can tell the two video tracks apart.
+    room_b.read_with(cx_b, |room, _| {
+        let participant = &room.remote_participants()[&client_a.user_id().unwrap()];
+        assert_eq!(participant.video_tracks.len(), 2);
+        let camera_tracks = participant.camera_tracks().collect::<Vec<_>>();
+        let screen_share_tracks = participant.screen_share_tracks().collect::<Vec<_>>();
+        assert_eq!(camera_tracks.len(), 1);
+        assert_eq!(screen_share_tracks.len(), 1);
+        assert_ne!(camera_tracks[0].sid(), screen_share_tracks[0].sid());
+        assert!(participant.has_screen_share_tracks());
+    });
+
+    // Unplugging the camera unshares it, leaving the screen share alone.
+    server.test_livekit_server.disconnect_camera_device("back");
+    executor.run_until_parked();
     assert_eq!(
-        contacts(&client_d, cx_d),
-        [("user_b".to_string(), "online", "free")]
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![
+            room::Event::LocalCameraDisconnected {
+                device_id: "back".into()
+            },
+            room::Event::LocalCameraStopped,
+        ]
     );
+    room_a.read_with(cx_a, |room, _| {
+        assert!(!room.is_sharing_camera());
+        assert!(room.is_sharing_screen());
+    });
 
-    // Test removing a contact
-    client_b
-        .user_store()
-        .update(cx_b, |store, cx| {
-            store.remove_contact(client_c.user_id().unwrap(), cx)
-        })
+    // Sharing the first available camera, then unsharing it explicitly.
+    room_a
+        .update(cx_a, |room, cx| room.share_camera(None, cx))
         .await
         .unwrap();
+    room_a
+        .update(cx_a, |room, cx| room.unshare_camera(cx))
+        .unwrap();
     executor.run_until_parked();
     assert_eq!(
-        contacts(&client_b, cx_b),
-        [
-            ("user_a".to_string(), "offline", "free"),
-            ("user_d".to_string(), "online", "free")
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![
+            room::Event::LocalCameraStarted,
+            room::Event::LocalCameraStopped,
         ]
     );
+}
+
+#[gpui::test(iterations = 10)]
+async fn test_audio_device_failover(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let audio_device = |id: &str, kind, is_default| livekit_client::AudioDevice {
+        id: id.into(),
+        name: format!("{id} name"),
+        kind,
+        is_default,
+    };
+    let built_in = audio_device("built-in", livekit_client::AudioDeviceKind::Input, true);
+    let headset = audio_device("headset", livekit_client::AudioDeviceKind::Input, false);
+    let speakers = audio_device("speakers", livekit_client::AudioDeviceKind::Output, true);
+
+    let mut server = TestServer::start(executor.clone()).await;
+    server.test_livekit_server.set_audio_devices(vec![
+        built_in.clone(),
+        headset.clone(),
+        speakers.clone(),
+    ]);
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    cx_a.update(|cx| <dyn fs::Fs>::set_global(client_a.fs(), cx));
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
+
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_a = active_call_events(cx_a);
+    let user_a_muted = |cx: &TestAppContext| {
+        room_b.read_with(cx, |room, _| {
+            let participant = &room.remote_participants()[&client_a.user_id().unwrap()];
+            assert_eq!(participant.audio_tracks.len(), 1);
+            participant.muted
+        })
+    };
+    room_a.read_with(cx_a, |room, _| {
+        assert_eq!(room.audio_devices().len(), 3);
+        assert_eq!(
+            room.audio_device(livekit_client::AudioDeviceKind::Input),
+            Some(&built_in)
+        );
+        assert_eq!(
+            room.audio_device(livekit_client::AudioDeviceKind::Output),
+            Some(&speakers)
+        );
+    });
+
+    // Picking the headset and muting it.
+    room_a.update(cx_a, |room, cx| {
+        room.set_input_device(Some("headset".into()), cx);
+        room.toggle_mute(cx);
+    });
+    executor.run_until_parked();
+    room_a.read_with(cx_a, |room, _| {
+        assert_eq!(
+            room.audio_device(livekit_client::AudioDeviceKind::Input),
+            Some(&headset)
+        );
+        assert!(room.is_muted());
+    });
+    assert!(user_a_muted(cx_b));
+    assert_eq!(mem::take(&mut *events_a.borrow_mut()), vec![]);
+
+    // Unplugging the headset switches to the default microphone, staying muted.
+    server
+        .test_livekit_server
+        .set_audio_devices(vec![built_in.clone(), speakers.clone()]);
+    executor.run_until_parked();
     assert_eq!(
-        contacts(&client_c, cx_c),
-        [("user_a".to_string(), "offline", "free"),]
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![room::Event::AudioDeviceSwitched {
+            device: built_in.clone()
+        }]
     );
+    room_a.read_with(cx_a, |room, _| {
+        assert_eq!(
+            room.audio_device(livekit_client::AudioDeviceKind::Input),
+            Some(&built_in)
+        );
+        assert!(room.is_muted());
+        assert!(room.is_sharing_mic());
+    });
+    assert!(user_a_muted(cx_b));
 
-    fn contacts(
-        client: &TestClient,
-        cx: &TestAppContext,
-    ) -> Vec<(String, &'static str, &'static str)> {
-        client.user_store().read_with(cx, |store, _| {
-            store
-                .contacts()
-                .iter()
-                .map(|contact| {
-                    (
-                        contact.user.github_login.clone().to_string(),
-                        if contact.online { "online" } else { "offline" },
-                        if contact.busy { "busy" } else { "free" },
-                    )
-                })
-                .collect()
-        })
-    }
+    // Plugging it back in switches back to it.
+    server.test_livekit_server.set_audio_devices(vec![
+        built_in.clone(),
+        headset.clone(),
+        speakers.clone(),
+    ]);
+    executor.run_until_parked();
+    assert_eq!(
+        mem::take(&mut *events_a.borrow_mut()),
+        vec![room::Event::AudioDeviceSwitched {
+            device: headset.clone()
+        }]
+    );
+    assert!(user_a_muted(cx_b));
+
+    // Unmuting after switching transmits from the new microphone.
+    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
+    executor.run_until_parked();
+    assert!(!user_a_muted(cx_b));
 }
 
-#[gpui::test(iterations = 10)]
-async fn test_contact_requests(
+#[gpui::test]
+async fn test_audio_processing(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
-    cx_a2: &mut TestAppContext,
     cx_b: &mut TestAppContext,
-    cx_b2: &mut TestAppContext,
-    cx_c: &mut TestAppContext,
-    cx_c2: &mut TestAppContext,
 ) {
-    // Connect to a server as 3 clients.
+    use call::{AudioProcessing::*, call_settings::CallSettings};
+    use livekit_client::AudioProcessingChange;
+    use settings::Settings as _;
+
     let mut server = TestServer::start(executor.clone()).await;
+    // Echo cancellation is set up when the microphone is opened, and gain can't be changed.
+    server.test_livekit_server.set_audio_processing_support(
+        EchoCancellation,
+        Some(AudioProcessingChange::RequiresNewTrack),
+    );
+    server
+        .test_livekit_server
+        .set_audio_processing_support(AutoGain, None);
     let client_a = server.create_client(cx_a, "user_a").await;
-    let client_a2 = server.create_client(cx_a2, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
-    let client_b2 = server.create_client(cx_b2, "user_b").await;
-    let client_c = server.create_client(cx_c, "user_c").await;
-    let client_c2 = server.create_client(cx_c2, "user_c").await;
 
-    assert_eq!(client_a.user_id().unwrap(), client_a2.user_id().unwrap());
-    assert_eq!(client_b.user_id().unwrap(), client_b2.user_id().unwrap());
-    assert_eq!(client_c.user_id().unwrap(), client_c2.user_id().unwrap());
+    // User A turned noise suppression off in their settings.
+    cx_a.update(|cx| {
+        SettingsStore::update_global(cx, |store, cx| {
+            store.update_user_settings(cx, |settings| {
+                settings.calls.get_or_insert_default().noise_suppression = Some(false);
+            });
+        });
+    });
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
 
-    // User A and User C request that user B become their contact.
-    client_a
-        .user_store()
-        .update(cx_a, |store, cx| {
-            store.request_contact(client_b.user_id().unwrap(), cx)
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let audio_processing = |cx: &TestAppContext| {
+        room_a.read_with(cx, |room, cx| {
+            [NoiseSuppression, EchoCancellation, AutoGain]
+                .map(|processing| room.audio_processing(processing, cx))
+        })
+    };
+    let user_a_audio_track = |cx: &TestAppContext| {
+        room_b.read_with(cx, |room, _| {
+            let participant = &room.remote_participants()[&client_a.user_id().unwrap()];
+            assert_eq!(participant.audio_tracks.len(), 1);
+            let sid = participant.audio_tracks.keys().next().unwrap().clone();
+            (sid, participant.muted)
+        })
+    };
+    assert_eq!(audio_processing(cx_a), [Some(false), Some(true), None]);
+    let (first_sid, muted) = user_a_audio_track(cx_b);
+    assert!(!muted);
+
+    // Turning noise suppression back on applies to the published track.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_audio_processing(NoiseSuppression, true, cx)
         })
-        .await
         .unwrap();
-    client_c
-        .user_store()
-        .update(cx_c, |store, cx| {
-            store.request_contact(client_b.user_id().unwrap(), cx)
+    executor.run_until_parked();
+    assert_eq!(audio_processing(cx_a), [Some(true), Some(true), None]);
+    assert_eq!(user_a_audio_track(cx_b), (first_sid.clone(), false));
+
+    // Turning echo cancellation off publishes a new track, which stays muted if the old one was.
+    room_a.update(cx_a, |room, cx| room.toggle_mute(cx));
+    executor.run_until_parked();
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_audio_processing(EchoCancellation, false, cx)
         })
-        .await
         .unwrap();
     executor.run_until_parked();
+    assert_eq!(audio_processing(cx_a), [Some(true), Some(false), None]);
+    let (second_sid, muted) = user_a_audio_track(cx_b);
+    assert_ne!(second_sid, first_sid);
+    assert!(muted);
+    room_a.read_with(cx_a, |room, _| {
+        assert!(room.is_muted());
+        assert!(room.is_sharing_mic());
+    });
 
-    // All users see the pending request appear in all their clients.
-    assert_eq!(
-        client_a.summarize_contacts(cx_a).outgoing_requests,
-        &["user_b"]
-    );
-    assert_eq!(
-        client_a2.summarize_contacts(cx_a2).outgoing_requests,
-        &["user_b"]
-    );
-    assert_eq!(
-        client_b.summarize_contacts(cx_b).incoming_requests,
-        &["user_a", "user_c"]
-    );
+    // Processing that can't be changed is reported as such.
+    room_a
+        .update(cx_a, |room, cx| {
+            room.set_audio_processing(AutoGain, false, cx)
+        })
+        .unwrap_err();
+
+    // The changes only last for this call, so the settings still apply to the next.
+    cx_a.read(|cx| {
+        let settings = CallSettings::get_global(cx);
+        assert!(!settings.noise_suppression);
+        assert!(settings.echo_cancellation);
+    });
+}
+
+#[gpui::test]
+async fn test_room_chat_messages(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
+
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_b = active_call_events(cx_b);
+    let chat_history = |room: &Entity<Room>, cx: &mut TestAppContext| {
+        room.read_with(cx, |room, _| {
+            room.chat_messages()
+                .iter()
+                .map(|message| {
+                    (
+                        message.sender.github_login.to_string(),
+                        message.text.clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Unicode and multi-line messages arrive intact, attributed to their sender.
+    let text = "héllo 👋\nsecond line\n\n  indented".to_string();
+    room_a
+        .update(cx_a, |room, cx| room.send_chat_message(text.clone(), cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
     assert_eq!(
-        client_b2.summarize_contacts(cx_b2).incoming_requests,
-        &["user_a", "user_c"]
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::ChatMessageReceived {
+            participant_id: client_a.peer_id().unwrap(),
+            message: room::ChatMessage {
+                sender: client_a
+                    .user_store()
+                    .read_with(cx_a, |store, _| { store.current_user().unwrap() }),
+                text: text.clone(),
+            },
+        }]
     );
     assert_eq!(
-        client_c.summarize_contacts(cx_c).outgoing_requests,
-        &["user_b"]
+        chat_history(&room_a, cx_a),
+        [("user_a".to_string(), text.clone())]
     );
     assert_eq!(
-        client_c2.summarize_contacts(cx_c2).outgoing_requests,
-        &["user_b"]
+        chat_history(&room_b, cx_b),
+        [("user_a".to_string(), text.clone())]
     );
 
-    // Contact requests are present upon connecting (tested here via disconnect/reconnect)
-    disconnect_and_reconnect(&client_a, cx_a).await;
-    disconnect_and_reconnect(&client_b, cx_b).await;
-    disconnect_and_reconnect(&client_c, cx_c).await;
+    // Empty and oversized messages are rejected without being sent.
+    room_b
+        .update(cx_b, |room, cx| room.send_chat_message(" \n".into(), cx))
+        .await
+        .unwrap_err();
+    room_b
+        .update(cx_b, |room, cx| {
+            room.send_chat_message("é".repeat(room::MAX_CHAT_MESSAGE_LEN / 2 + 1), cx)
+        })
+        .await
+        .unwrap_err();
+    room_b
+        .update(cx_b, |room, cx| {
+            room.send_chat_message("a".repeat(room::MAX_CHAT_MESSAGE_LEN), cx)
+        })
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(chat_history(&room_a, cx_a).len(), 2);
+    assert_eq!(chat_history(&room_b, cx_b).len(), 2);
+
+    // Messages sent while reconnecting fail instead of being delivered later.
+    server.forbid_connections();
+    server.disconnect_client(client_b.peer_id().unwrap());
+    executor.run_until_parked();
+    assert!(room_b.read_with(cx_b, |room, _| !room.status().is_online()));
+    room_b
+        .update(cx_b, |room, cx| {
+            room.send_chat_message("anyone there?".into(), cx)
+        })
+        .await
+        .unwrap_err();
+    server.allow_connections();
+    executor.advance_clock(RECEIVE_TIMEOUT);
+    executor.run_until_parked();
+    assert!(room_b.read_with(cx_b, |room, _| room.status().is_online()));
+    assert_eq!(chat_history(&room_a, cx_a).len(), 2);
+    assert_eq!(chat_history(&room_b, cx_b).len(), 2);
+
+    // Only the most recent messages are kept.
+    for ix in 0..room::MAX_CHAT_HISTORY_LEN {
+        room_a
+            .update(cx_a, |room, cx| room.send_chat_message(ix.to_string(), cx))
+            .await
+            .unwrap();
+    }
+    executor.run_until_parked();
+    let history_b = chat_history(&room_b, cx_b);
+    assert_eq!(history_b.len(), room::MAX_CHAT_HISTORY_LEN);
+    assert_eq!(history_b[0], ("user_a".to_string(), "0".to_string()));
+
+    // Leaving the call clears its history.
+    cx_b.read(ActiveCall::global)
+        .update(cx_b, |call, cx| call.hang_up(cx))
+        .await
+        .unwrap();
+    assert!(chat_history(&room_b, cx_b).is_empty());
+}
+
+#[gpui::test]
+async fn test_room_reactions(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
+
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_b = active_call_events(cx_b);
+    let user_a_id = client_a.user_id().unwrap();
+    let reaction_of_a = |cx_a: &mut TestAppContext, cx_b: &mut TestAppContext| {
+        (
+            room_a.read_with(cx_a, |room, _| {
+                room.local_participant()
+                    .last_reaction
+                    .as_ref()
+                    .map(|(emoji, _)| emoji.clone())
+            }),
+            room_b.read_with(cx_b, |room, _| {
+                room.remote_participants()[&user_a_id]
+                    .last_reaction
+                    .as_ref()
+                    .map(|(emoji, _)| emoji.clone())
+            }),
+        )
+    };
+
+    room_a
+        .update(cx_a, |room, cx| room.send_reaction("🎉", cx))
+        .await
+        .unwrap();
     executor.run_until_parked();
     assert_eq!(
-        client_a.summarize_contacts(cx_a).outgoing_requests,
-        &["user_b"]
-    );
-    assert_eq!(
-        client_b.summarize_contacts(cx_b).incoming_requests,
-        &["user_a", "user_c"]
+        reaction_of_a(cx_a, cx_b),
+        (Some("🎉".to_string()), Some("🎉".to_string()))
     );
     assert_eq!(
-        client_c.summarize_contacts(cx_c).outgoing_requests,
-        &["user_b"]
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::ParticipantStateChanged {
+            participant_id: client_a.peer_id().unwrap(),
+        }]
     );
 
-    // User B accepts the request from user A.
-    client_b
-        .user_store()
-        .update(cx_b, |store, cx| {
-            store.respond_to_contact_request(client_a.user_id().unwrap(), true, cx)
-        })
+    // Reactions sent faster than the rate limit are rejected.
+    room_a
+        .update(cx_a, |room, cx| room.send_reaction("👍", cx))
         .await
-        .unwrap();
-
-    executor.run_until_parked();
-
-    // User B sees user A as their contact now in all client, and the incoming request from them is removed.
-    let contacts_b = client_b.summarize_contacts(cx_b);
-    assert_eq!(contacts_b.current, &["user_a"]);
-    assert_eq!(contacts_b.incoming_requests, &["user_c"]);
-    let contacts_b2 = client_b2.summarize_contacts(cx_b2);
-    assert_eq!(contacts_b2.current, &["user_a"]);
-    assert_eq!(contacts_b2.incoming_requests, &["user_c"]);
-
-    // User A sees user B as their contact now in all clients, and the outgoing request to them is removed.
-    let contacts_a = client_a.summarize_contacts(cx_a);
-    assert_eq!(contacts_a.current, &["user_b"]);
-    assert!(contacts_a.outgoing_requests.is_empty());
-    let contacts_a2 = client_a2.summarize_contacts(cx_a2);
-    assert_eq!(contacts_a2.current, &["user_b"]);
-    assert!(contacts_a2.outgoing_requests.is_empty());
-
-    // Contacts are present upon connecting (tested here via disconnect/reconnect)
-    disconnect_and_reconnect(&client_a, cx_a).await;
-    disconnect_and_reconnect(&client_b, cx_b).await;
-    disconnect_and_reconnect(&client_c, cx_c).await;
+        .unwrap_err();
     executor.run_until_parked();
-    assert_eq!(client_a.summarize_contacts(cx_a).current, &["user_b"]);
-    assert_eq!(client_b.summarize_contacts(cx_b).current, &["user_a"]);
     assert_eq!(
-        client_b.summarize_contacts(cx_b).incoming_requests,
-        &["user_c"]
+        reaction_of_a(cx_a, cx_b),
+        (Some("🎉".to_string()), Some("🎉".to_string()))
     );
-    assert!(client_c.summarize_contacts(cx_c).current.is_empty());
+
+    // Reactions are cleared once they expire.
+    executor.advance_clock(room::REACTION_DURATION);
+    executor.run_until_parked();
+    assert_eq!(reaction_of_a(cx_a, cx_b), (None, None));
     assert_eq!(
-        client_c.summarize_contacts(cx_c).outgoing_requests,
-        &["user_b"]
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::ParticipantStateChanged {
+            participant_id: client_a.peer_id().unwrap(),
+        }]
     );
 
-    // User B rejects the request from user C.
-    client_b
-        .user_store()
-        .update(cx_b, |store, cx| {
-            store.respond_to_contact_request(client_c.user_id().unwrap(), false, cx)
-        })
+    // A newer reaction isn't cleared by the timer of the one it replaced.
+    room_a
+        .update(cx_a, |room, cx| room.send_reaction("👍", cx))
         .await
         .unwrap();
-
     executor.run_until_parked();
-
-    // User B doesn't see user C as their contact, and the incoming request from them is removed.
-    let contacts_b = client_b.summarize_contacts(cx_b);
-    assert_eq!(contacts_b.current, &["user_a"]);
-    assert!(contacts_b.incoming_requests.is_empty());
-    let contacts_b2 = client_b2.summarize_contacts(cx_b2);
-    assert_eq!(contacts_b2.current, &["user_a"]);
-    assert!(contacts_b2.incoming_requests.is_empty());
-
-    // User C doesn't see user B as their contact, and the outgoing request to them is removed.
-    let contacts_c = client_c.summarize_contacts(cx_c);
-    assert!(contacts_c.current.is_empty());
-    assert!(contacts_c.outgoing_requests.is_empty());
-    let contacts_c2 = client_c2.summarize_contacts(cx_c2);
-    assert!(contacts_c2.current.is_empty());
-    assert!(contacts_c2.outgoing_requests.is_empty());
-
-    // Incoming/outgoing requests are not present upon connecting (tested here via disconnect/reconnect)
-    disconnect_and_reconnect(&client_a, cx_a).await;
-    disconnect_and_reconnect(&client_b, cx_b).await;
-    disconnect_and_reconnect(&client_c, cx_c).await;
+    executor.advance_clock(room::REACTION_RATE_LIMIT);
+    room_a
+        .update(cx_a, |room, cx| room.send_reaction("🚀", cx))
+        .await
+        .unwrap();
+    executor.advance_clock(room::REACTION_DURATION - room::REACTION_RATE_LIMIT);
     executor.run_until_parked();
-    assert_eq!(client_a.summarize_contacts(cx_a).current, &["user_b"]);
-    assert_eq!(client_b.summarize_contacts(cx_b).current, &["user_a"]);
-    assert!(
-        client_b
-            .summarize_contacts(cx_b)
-            .incoming_requests
-            .is_empty()
-    );
-    assert!(client_c.summarize_contacts(cx_c).current.is_empty());
-    assert!(
-        client_c
-            .summarize_contacts(cx_c)
-            .outgoing_requests
-            .is_empty()
+    assert_eq!(
+        reaction_of_a(cx_a, cx_b),
+        (Some("🚀".to_string()), Some("🚀".to_string()))
     );
-
-    async fn disconnect_and_reconnect(client: &TestClient, cx: &mut TestAppContext) {
-        client.disconnect(&cx.to_async());
-        client.clear_contacts(cx).await;
-        client
-            .connect(false, &cx.to_async())
-            .await
-            .into_response()
-            .unwrap();
-    }
+    executor.advance_clock(room::REACTION_RATE_LIMIT);
+    executor.run_until_parked();
+    assert_eq!(reaction_of_a(cx_a, cx_b), (None, None));
 }
 
-#[gpui::test(iterations = 10)]
-async fn test_join_call_after_screen_was_shared(
+#[gpui::test]
+async fn test_raised_hands(
     executor: BackgroundExecutor,
     cx_a: &mut TestAppContext,
     cx_b: &mut TestAppContext,
+    cx_c: &mut TestAppContext,
 ) {
     let mut server = TestServer::start(executor.clone()).await;
-
     let client_a = server.create_client(cx_a, "user_a").await;
     let client_b = server.create_client(cx_b, "user_b").await;
+    let client_c = server.create_client(cx_c, "user_c").await;
     server
-        .make_contacts(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
         .await;
+    executor.run_until_parked();
 
     let active_call_a = cx_a.read(ActiveCall::global);
-    let active_call_b = cx_b.read(ActiveCall::global);
+    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = cx_b
+        .read(ActiveCall::global)
+        .read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_b = active_call_events(cx_b);
+    let user_a_id = client_a.user_id().unwrap();
 
-    // Call users B and C from client A.
+    room_a
+        .update(cx_a, |room, cx| room.set_hand_raised(true, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert!(room_a.read_with(cx_a, |room, _| room.local_participant().hand_raised));
+    assert!(room_b.read_with(cx_b, |room, _| {
+        room.remote_participants()[&user_a_id].hand_raised
+    }));
+    assert_eq!(
+        mem::take(&mut *events_b.borrow_mut()),
+        vec![room::Event::ParticipantStateChanged {
+            participant_id: client_a.peer_id().unwrap(),
+        }]
+    );
+
+    // A participant joining later sees the hand that was already raised.
+    server
+        .make_contacts(&mut [(&client_a, cx_a), (&client_c, cx_c)])
+        .await;
     active_call_a
         .update(cx_a, |call, cx| {
-            call.invite(client_b.user_id().unwrap(), None, cx)
+            call.invite(client_c.user_id().unwrap(), None, cx)
         })
         .await
         .unwrap();
+    executor.run_until_parked();
+    let active_call_c = cx_c.read(ActiveCall::global);
+    active_call_c
+        .update(cx_c, |call, cx| call.accept_incoming(cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    let room_c = active_call_c.read_with(cx_c, |call, _| call.room().unwrap().clone());
+    room_c.read_with(cx_c, |room, _| {
+        assert!(room.remote_participants()[&user_a_id].hand_raised);
+        assert!(!room.remote_participants()[&client_b.user_id().unwrap()].hand_raised);
+    });
 
-    let room_a = active_call_a.read_with(cx_a, |call, _| call.room().unwrap().clone());
+    room_a
+        .update(cx_a, |room, cx| room.set_hand_raised(false, cx))
+        .await
+        .unwrap();
     executor.run_until_parked();
-    assert_eq!(
-        room_participants(&room_a, cx_a),
-        RoomParticipants {
-            remote: Default::default(),
-            pending: vec!["user_b".to_string()]
-        }
-    );
+    assert!(!room_a.read_with(cx_a, |room, _| room.local_participant().hand_raised));
+    assert!(!room_c.read_with(cx_c, |room, _| {
+        room.remote_participants()[&user_a_id].hand_raised
+    }));
+}
 
-    // User B receives the call.
+#[gpui::test]
+async fn test_screen_remote_control(
+    executor: BackgroundExecutor,
+    cx_a: &mut TestAppContext,
+    cx_b: &mut TestAppContext,
+) {
+    use call::call_settings::CallSettings;
+    use settings::Settings as _;
 
-    let mut incoming_call_b = active_call_b.read_with(cx_b, |call, _| call.incoming());
-    let call_b = incoming_call_b.next().await.unwrap().unwrap();
-    assert_eq!(call_b.calling_user.github_login, "user_a");
+    let mut server = TestServer::start(executor.clone()).await;
+    let client_a = server.create_client(cx_a, "user_a").await;
+    let client_b = server.create_client(cx_b, "user_b").await;
+    server
+        .create_room(&mut [(&client_a, cx_a), (&client_b, cx_b)])
+        .await;
+    executor.run_until_parked();
+
+    let active_call_b = cx_b.read(ActiveCall::global);
+    let room_a = cx_a
+        .read(ActiveCall::global)
+        .read_with(cx_a, |call, _| call.room().unwrap().clone());
+    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
+    let events_a = active_call_events(cx_a);
+    let peer_id_a = client_a.peer_id().unwrap();
+    let peer_id_b = client_b.peer_id().unwrap();
+    let timeout = cx_a.read(|cx| CallSettings::get_global(cx).remote_control_timeout);
+
+    // Control can only be requested of someone sharing their screen.
+    room_b
+        .update(cx_b, |room, cx| room.request_control(peer_id_a, cx))
+        .await
+        .unwrap_err();
 
-    // User A shares their screen
     let display = gpui::TestScreenCaptureSource::new();
     cx_a.set_screen_capture_sources(vec![display]);
     let screen_a = cx_a
@@ -6542,57 +8000,131 @@ async fn test_join_call_after_screen_was_shared(
         .into_iter()
         .next()
         .unwrap();
+    room_a
+        .update(cx_a, |room, cx| room.share_screen(screen_a, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    events_a.borrow_mut().clear();
 
-    active_call_a
-        .update(cx_a, |call, cx| {
-            call.room()
-                .unwrap()
-                .update(cx, |room, cx| room.share_screen(screen_a, cx))
+    let request_control = |cx_a: &mut TestAppContext, cx_b: &mut TestAppContext| {
+        room_b
+            .update(cx_b, |room, cx| room.request_control(peer_id_a, cx))
+            .detach_and_log_err(cx_b);
+        executor.run_until_parked();
+        assert!(room_b.read_with(cx_b, |room, _| {
+            room.remote_control().is_requesting(peer_id_a)
+        }));
+        assert_eq!(
+            room_a.read_with(cx_a, |room, _| room.remote_control().requests().to_vec()),
+            [peer_id_b]
+        );
+    };
+    let controller = |cx_a: &mut TestAppContext, cx_b: &mut TestAppContext| {
+        let sharer_side = room_a.read_with(cx_a, |room, _| {
+            room.remote_control().grant().map(|grant| grant.controller)
+        });
+        let viewer_side = room_b.read_with(cx_b, |room, _| {
+            room.remote_control()
+                .controlled_screen(peer_id_a)
+                .map(|grant| grant.controller)
+        });
+        assert_eq!(sharer_side, viewer_side);
+        sharer_side
+    };
+
+    // User A is asked, and denies the request.
+    request_control(cx_a, cx_b);
+    assert!(
+        mem::take(&mut *events_a.borrow_mut()).contains(&room::Event::ControlRequested {
+            participant_id: peer_id_b
         })
+    );
+    room_a
+        .update(cx_a, |room, cx| room.deny_control(peer_id_b, cx))
         .await
         .unwrap();
+    executor.run_until_parked();
+    assert!(!room_b.read_with(cx_b, |room, _| {
+        room.remote_control().is_requesting(peer_id_a)
+    }));
+    assert_eq!(controller(cx_a, cx_b), None);
 
-    client_b.user_store().update(cx_b, |user_store, _| {
-        user_store.clear_cache();
-    });
-
-    // User B joins the room
-    active_call_b
-        .update(cx_b, |call, cx| call.accept_incoming(cx))
+    // User A grants the next request, then revokes it.
+    request_control(cx_a, cx_b);
+    room_a
+        .update(cx_a, |room, cx| room.grant_control(peer_id_b, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    assert_eq!(controller(cx_a, cx_b), Some(peer_id_b));
+    room_a
+        .update(cx_a, |room, cx| room.revoke_control(cx))
         .await
         .unwrap();
+    executor.run_until_parked();
+    assert_eq!(controller(cx_a, cx_b), None);
 
-    let room_b = active_call_b.read_with(cx_b, |call, _| call.room().unwrap().clone());
-    assert!(incoming_call_b.next().await.unwrap().is_none());
+    // Grants expire on both sides.
+    request_control(cx_a, cx_b);
+    room_a
+        .update(cx_a, |room, cx| room.grant_control(peer_id_b, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    executor.advance_clock(timeout / 2);
+    executor.run_until_parked();
+    assert_eq!(controller(cx_a, cx_b), Some(peer_id_b));
+    executor.advance_clock(timeout / 2);
+    executor.run_until_parked();
+    assert_eq!(controller(cx_a, cx_b), None);
 
+    // Grants end when the screen stops being shared.
+    request_control(cx_a, cx_b);
+    room_a
+        .update(cx_a, |room, cx| room.grant_control(peer_id_b, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    room_a
+        .update(cx_a, |room, cx| room.unshare_screen(false, cx))
+        .unwrap();
     executor.run_until_parked();
+    assert_eq!(controller(cx_a, cx_b), None);
     assert_eq!(
-        room_participants(&room_a, cx_a),
-        RoomParticipants {
-            remote: vec!["user_b".to_string()],
-            pending: vec![],
-        }
+        room_a.read_with(cx_a, |room, _| room.remote_control().requests().len()),
+        0
     );
+
+    // Grants end when the controller leaves the call.
+    let screen_a = cx_a
+        .update(|cx| cx.screen_capture_sources())
+        .await
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap();
+    room_a
+        .update(cx_a, |room, cx| room.share_screen(screen_a, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    request_control(cx_a, cx_b);
+    room_a
+        .update(cx_a, |room, cx| room.grant_control(peer_id_b, cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
+    active_call_b
+        .update(cx_b, |call, cx| call.hang_up(cx))
+        .await
+        .unwrap();
+    executor.run_until_parked();
     assert_eq!(
-        room_participants(&room_b, cx_b),
-        RoomParticipants {
-            remote: vec!["user_a".to_string()],
-            pending: vec![],
-        }
+        room_a.read_with(cx_a, |room, _| room.remote_control().grant().copied()),
+        None
     );
-
-    // Ensure User B sees User A's screenshare.
-
-    room_b.read_with(cx_b, |room, _| {
-        assert_eq!(
-            room.remote_participants()
-                .get(&client_a.user_id().unwrap())
-                .unwrap()
-                .video_tracks
-                .len(),
-            1
-        );
-    });
 }
 
 #[gpui::test]
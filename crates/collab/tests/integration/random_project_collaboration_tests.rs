@@ -173,7 +173,7 @@ impl RandomizedTest for ProjectCollaborationTest {
                 // Mutate the call
                 0..=29 => {
                     // Respond to an incoming call
-                    if call.read_with(cx, |call, _| call.incoming().borrow().is_some()) {
+                    if call.read_with(cx, |call, _| call.incoming().get_cloned().is_some()) {
                         break if rng.random_bool(0.7) {
                             ClientOperation::AcceptIncomingCall
                         } else {
@@ -511,7 +511,7 @@ impl RandomizedTest for ProjectCollaborationTest {
         match operation {
             ClientOperation::AcceptIncomingCall => {
                 let active_call = cx.read(ActiveCall::global);
-                if active_call.read_with(cx, |call, _| call.incoming().borrow().is_none()) {
+                if active_call.read_with(cx, |call, _| call.incoming().get_cloned().is_none()) {
                     Err(TestError::Inapplicable)?;
                 }
 
@@ -523,7 +523,7 @@ impl RandomizedTest for ProjectCollaborationTest {
 
             ClientOperation::RejectIncomingCall => {
                 let active_call = cx.read(ActiveCall::global);
-                if active_call.read_with(cx, |call, _| call.incoming().borrow().is_none()) {
+                if active_call.read_with(cx, |call, _| call.incoming().get_cloned().is_none()) {
                     Err(TestError::Inapplicable)?;
                 }
 
@@ -9,7 +9,7 @@ use client::{
 use clock::FakeSystemClock;
 use collab::{
     AppState, Config,
-    db::{NewUserParams, UserId},
+    db::{NewUserParams, RoomId, UserId},
     executor::Executor,
     rpc::{CLEANUP_TIMEOUT, Principal, RECONNECT_TIMEOUT, Server, ZedVersion},
 };
@@ -397,6 +397,14 @@ impl TestServer {
         deterministic.run_until_parked();
     }
 
+    /// Asks everyone in the room to rejoin it, as the server does when it moves a room.
+    pub async fn migrate_room(&self, room_id: u64) {
+        self.server
+            .migrate_room(RoomId::from_proto(room_id))
+            .await
+            .unwrap();
+    }
+
     pub fn forbid_connections(&self) {
         self.forbid_connections.store(true, SeqCst);
     }
@@ -0,0 +1,88 @@
+//! Adds a summary of a channel's call to the channel's notes when the call ends, for channels
+//! with `export_summary_to_notes` on.
+
+use call::{ActiveCall, CallSummary, room};
+use channel::{ChannelBuffer, ChannelStore};
+use chrono::Local;
+use client::ChannelId;
+use gpui::{App, AppContext as _, ClipboardItem, Entity};
+use workspace::notifications::{
+    NotificationId, show_app_notification, simple_message_notification::MessageNotification,
+};
+
+struct CallSummaryNotification;
+
+pub fn init(cx: &mut App) {
+    let active_call = ActiveCall::global(cx);
+    cx.subscribe(&active_call, |_, event, cx| {
+        if let room::Event::CallSummaryReady {
+            channel_id,
+            summary,
+        } = event
+        {
+            export_summary(*channel_id, summary, cx);
+        }
+    })
+    .detach();
+}
+
+/// Appends the summary to the channel's notes, or copies it to the clipboard when the notes
+/// can't be edited.
+fn export_summary(channel_id: ChannelId, summary: &CallSummary, cx: &mut App) {
+    let markdown = summary.to_markdown(&Local);
+    let channel_store = ChannelStore::global(cx);
+    let notes = match channel_store.read(cx).channel_for_id(channel_id) {
+        Some(channel) => format!("the notes of #{}", channel.name),
+        None => "the channel's notes".to_string(),
+    };
+    let channel_buffer =
+        channel_store.update(cx, |store, cx| store.open_channel_buffer(channel_id, cx));
+    cx.spawn(async move |cx| {
+        let appended = match channel_buffer.await {
+            Ok(channel_buffer) => cx.update(|cx| append_to_notes(&channel_buffer, &markdown, cx)),
+            Err(error) => {
+                log::error!("failed to open the notes of channel {channel_id}: {error:#}");
+                false
+            }
+        };
+        cx.update(|cx| {
+            let message = if appended {
+                format!("Added a summary of the call to {notes}.")
+            } else {
+                cx.write_to_clipboard(ClipboardItem::new_string(markdown));
+                format!(
+                    "Couldn't add a summary of the call to {notes}, so it was copied to \
+                    your clipboard."
+                )
+            };
+            show_app_notification(
+                NotificationId::unique::<CallSummaryNotification>(),
+                cx,
+                move |cx| cx.new(|cx| MessageNotification::new(message.clone(), cx)),
+            );
+        });
+    })
+    .detach();
+}
+
+/// Appends `markdown` to the notes, after a blank line. Returns `false` when the notes are
+/// read-only.
+fn append_to_notes(channel_buffer: &Entity<ChannelBuffer>, markdown: &str, cx: &mut App) -> bool {
+    let buffer = channel_buffer.read(cx).buffer();
+    buffer.update(cx, |buffer, cx| {
+        if buffer.read_only() {
+            return false;
+        }
+        let text = buffer.text();
+        let separator = if text.is_empty() || text.ends_with("\n\n") {
+            ""
+        } else if text.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        let end = buffer.len();
+        buffer.edit([(end..end, format!("{separator}{markdown}"))], None, cx);
+        true
+    })
+}
@@ -4,7 +4,7 @@ mod contact_finder;
 use self::channel_modal::ChannelModal;
 use crate::{CollaborationPanelSettings, channel_view::ChannelView};
 use anyhow::Context as _;
-use call::ActiveCall;
+use call::{ActiveCall, CallError};
 use channel::{Channel, ChannelEvent, ChannelStore};
 use client::{ChannelId, Client, Contact, User, UserStore};
 use collections::{HashMap, HashSet};
@@ -151,14 +151,15 @@ pub fn init(cx: &mut App) {
         workspace.register_action(|workspace, _: &ShareProject, window, cx| {
             let project = workspace.project().clone();
             println!("{project:?}");
-            window.defer(cx, move |_window, cx| {
+            window.defer(cx, move |window, cx| {
                 ActiveCall::global(cx).update(cx, move |call, cx| {
                     if let Some(room) = call.room() {
                         println!("{room:?}");
                         if room.read(cx).is_sharing_project() {
                             call.unshare_project(project, cx).ok();
                         } else {
-                            call.share_project(project, cx).detach_and_log_err(cx);
+                            call.share_project_after_review(project, window, cx)
+                                .detach_and_log_err(cx);
                         }
                     }
                 });
@@ -606,10 +607,11 @@ impl CollabPanel {
                             project_id: project.id,
                             worktree_root_names: project.worktree_root_names.clone(),
                             host_user_id: participant.user.id,
-                            is_last: projects.peek().is_none() && !participant.has_video_tracks(),
+                            is_last: projects.peek().is_none()
+                                && !participant.has_screen_share_tracks(),
                         });
                     }
-                    if participant.has_video_tracks() {
+                    if participant.has_screen_share_tracks() {
                         self.entries.push(ListEntry::ParticipantScreen {
                             peer_id: Some(participant.peer_id),
                             is_last: true,
@@ -1644,7 +1646,7 @@ impl CollabPanel {
                     if is_active {
                         self.open_channel_notes(channel.id, window, cx)
                     } else {
-                        self.join_channel(channel.id, window, cx)
+                        self.join_channel(channel.id, false, window, cx)
                     }
                 }
                 ListEntry::ContactPlaceholder => self.toggle_contact_finder(window, cx),
@@ -2279,14 +2281,30 @@ impl CollabPanel {
     }
 
     fn call(&mut self, recipient_user_id: u64, window: &mut Window, cx: &mut Context<Self>) {
-        ActiveCall::global(cx)
-            .update(cx, |call, cx| {
-                call.invite(recipient_user_id, Some(self.project.clone()), cx)
-            })
-            .detach_and_prompt_err("Call failed", window, cx, |_, _, _| None);
+        let invite = ActiveCall::global(cx).update(cx, |call, cx| {
+            call.invite(recipient_user_id, Some(self.project.clone()), cx)
+        });
+        cx.background_spawn(async move {
+            match invite.await {
+                // The user is already being called, so clicking again has nothing to report.
+                Err(CallError::AlreadyInvited) => anyhow::Ok(()),
+                result => Ok(result?),
+            }
+        })
+        .detach_and_prompt_err("Call failed", window, cx, |error, _, _| {
+            let detail = crate::call_error_detail(error.downcast_ref::<CallError>()?)?;
+            Some(detail.to_string())
+        });
     }
 
-    fn join_channel(&self, channel_id: ChannelId, window: &mut Window, cx: &mut Context<Self>) {
+    /// Joins the channel's call, only listening to it if `listen_only` is set.
+    fn join_channel(
+        &self,
+        channel_id: ChannelId,
+        listen_only: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
         let Some(workspace) = self.workspace.upgrade() else {
             return;
         };
@@ -2294,7 +2312,12 @@ impl CollabPanel {
         let Some(handle) = window.window_handle().downcast::<MultiWorkspace>() else {
             return;
         };
-        workspace::join_channel(
+        let join_channel = if listen_only {
+            workspace::join_channel_listen_only
+        } else {
+            workspace::join_channel
+        };
+        join_channel(
             channel_id,
             workspace.read(cx).app_state().clone(),
             Some(handle),
@@ -2939,11 +2962,12 @@ impl CollabPanel {
                     .on_toggle(cx.listener(move |this, _, window, cx| {
                         this.toggle_channel_collapsed(channel_id, window, cx)
                     }))
-                    .on_click(cx.listener(move |this, _, window, cx| {
+                    .on_click(cx.listener(move |this, event: &ClickEvent, window, cx| {
                         if is_active {
                             this.open_channel_notes(channel_id, window, cx)
                         } else {
-                            this.join_channel(channel_id, window, cx)
+                            // Alt-clicking joins without opening the microphone or camera.
+                            this.join_channel(channel_id, event.modifiers().alt, window, cx)
                         }
                     }))
                     .on_secondary_mouse_down(cx.listener(
@@ -3358,6 +3382,15 @@ impl Render for JoinChannelTooltip {
 
             container
                 .child(Label::new("Join channel"))
+                .child(
+                    Label::new(if cfg!(target_os = "macos") {
+                        "Option-click to only listen"
+                    } else {
+                        "Alt-click to only listen"
+                    })
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+                )
                 .children(participants.iter().map(|participant| {
                     h_flex()
                         .gap_2()
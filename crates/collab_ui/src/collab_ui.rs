@@ -1,3 +1,4 @@
+mod call_summary;
 pub mod channel_view;
 pub mod collab_panel;
 pub mod notification_panel;
@@ -6,6 +7,7 @@ mod panel_settings;
 
 use std::{rc::Rc, sync::Arc};
 
+use call::CallError;
 pub use collab_panel::CollabPanel;
 use gpui::{
     App, Pixels, PlatformDisplay, Size, WindowBackgroundAppearance, WindowBounds,
@@ -18,6 +20,7 @@ use workspace::AppState;
 
 // Another comment, nice.
 pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
+    call_summary::init(cx);
     channel_view::init(cx);
     collab_panel::init(cx);
     notification_panel::init(cx);
@@ -25,6 +28,31 @@ pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
     title_bar::init(cx);
 }
 
+/// Explains a failed call in terms of what the user can do about it, or returns `None` when the
+/// error itself is the best explanation.
+fn call_error_detail(error: &CallError) -> Option<&'static str> {
+    match error {
+        CallError::AlreadyInCall => Some("Leave your current call and try again."),
+        CallError::PermissionDenied => Some(concat!(
+            "You do not have access to this call. ",
+            "Please ask someone to add you and try again."
+        )),
+        CallError::ChannelFull => Some("This call is full. Please try again later."),
+        CallError::ServerUnreachable => {
+            Some("Please check your internet connection and try again.")
+        }
+        CallError::RoomVersionMismatch => Some(concat!(
+            "You are running an unsupported version of Zed. ",
+            "Please update to continue."
+        )),
+        CallError::AlreadyRequested => Some("Please wait for a channel admin to answer."),
+        CallError::AlreadyInvited
+        | CallError::NoIncomingCall
+        | CallError::NoJoinRequest
+        | CallError::Other(_) => None,
+    }
+}
+
 fn notification_window_options(
     screen: Rc<dyn PlatformDisplay>,
     size: Size<Pixels>,
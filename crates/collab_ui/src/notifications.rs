@@ -1,11 +1,19 @@
+pub mod control_request_notification;
 pub mod incoming_call_notification;
+pub mod join_request_notification;
+pub mod muted_on_join_notification;
 pub mod project_shared_notification;
+pub mod screen_share_quality_notification;
 
 use gpui::App;
 use std::sync::Arc;
 use workspace::AppState;
 
 pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
+    control_request_notification::init(app_state, cx);
     incoming_call_notification::init(app_state, cx);
+    join_request_notification::init(app_state, cx);
+    muted_on_join_notification::init(app_state, cx);
     project_shared_notification::init(app_state, cx);
+    screen_share_quality_notification::init(app_state, cx);
 }
@@ -0,0 +1,131 @@
+use crate::notification_window_options;
+use call::{ActiveCall, room};
+use client::{User, proto::PeerId};
+use collections::HashMap;
+use gpui::{App, Size};
+use std::sync::Arc;
+
+use ui::{CollabNotification, prelude::*};
+use util::ResultExt;
+use workspace::AppState;
+
+pub fn init(_: &Arc<AppState>, cx: &mut App) {
+    let active_call = ActiveCall::global(cx);
+    let mut notification_windows = HashMap::default();
+    cx.subscribe(&active_call, move |active_call, event, cx| match event {
+        room::Event::ControlRequested { participant_id } => {
+            let Some(viewer) = active_call.read(cx).room().and_then(|room| {
+                room.read(cx)
+                    .remote_participant_for_peer_id(*participant_id)
+                    .map(|participant| participant.user.clone())
+            }) else {
+                return;
+            };
+            let window_size = Size {
+                width: px(400.),
+                height: px(72.),
+            };
+
+            for screen in cx.displays() {
+                let options = notification_window_options(screen, window_size, cx);
+                let Some(window) = cx
+                    .open_window(options, |_, cx| {
+                        cx.new(|_| ControlRequestNotification::new(viewer.clone(), *participant_id))
+                    })
+                    .log_err()
+                else {
+                    continue;
+                };
+                notification_windows
+                    .entry(*participant_id)
+                    .or_insert(Vec::new())
+                    .push(window);
+            }
+        }
+
+        // Every answer to a request, and the end of the screen share, changes the state.
+        room::Event::RemoteControlChanged { participant_id } => {
+            let still_requested = active_call.read(cx).room().is_some_and(|room| {
+                room.read(cx)
+                    .remote_control()
+                    .requests()
+                    .contains(participant_id)
+            });
+            if !still_requested && let Some(windows) = notification_windows.remove(participant_id) {
+                for window in windows {
+                    window
+                        .update(cx, |_, window, _| {
+                            window.remove_window();
+                        })
+                        .ok();
+                }
+            }
+        }
+
+        room::Event::RoomLeft { .. } => {
+            for (_, windows) in notification_windows.drain() {
+                for window in windows {
+                    window
+                        .update(cx, |_, window, _| {
+                            window.remove_window();
+                        })
+                        .ok();
+                }
+            }
+        }
+        _ => {}
+    })
+    .detach();
+}
+
+/// Asks the local user whether a viewer of their shared screen may control it.
+pub struct ControlRequestNotification {
+    viewer: Arc<User>,
+    viewer_peer_id: PeerId,
+}
+
+impl ControlRequestNotification {
+    fn new(viewer: Arc<User>, viewer_peer_id: PeerId) -> Self {
+        Self {
+            viewer,
+            viewer_peer_id,
+        }
+    }
+
+    fn respond(&mut self, allow: bool, cx: &mut Context<Self>) {
+        let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() else {
+            return;
+        };
+        let viewer_peer_id = self.viewer_peer_id;
+        room.update(cx, |room, cx| {
+            if allow {
+                room.grant_control(viewer_peer_id, cx)
+            } else {
+                room.deny_control(viewer_peer_id, cx)
+            }
+        })
+        .detach_and_log_err(cx);
+    }
+}
+
+impl Render for ControlRequestNotification {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let ui_font = theme::setup_ui_font(window, cx);
+
+        div().size_full().font(ui_font).child(
+            CollabNotification::new(
+                self.viewer.avatar_uri.clone(),
+                Button::new("allow", "Allow").on_click(cx.listener(move |this, _event, _, cx| {
+                    this.respond(true, cx);
+                })),
+                Button::new("deny", "Deny").on_click(cx.listener(move |this, _event, _, cx| {
+                    this.respond(false, cx);
+                })),
+            )
+            .child(Label::new(format!(
+                "{} requests control of your screen",
+                self.viewer.github_login
+            ))),
+        )
+    }
+}
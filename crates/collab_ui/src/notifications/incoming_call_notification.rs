@@ -1,12 +1,17 @@
 use crate::notification_window_options;
-use call::{ActiveCall, IncomingCall};
+use call::{ActiveCall, CallError, IncomingCall};
 use futures::StreamExt;
 use gpui::{App, WindowHandle, prelude::*};
 
 use std::sync::{Arc, Weak};
-use ui::{CollabNotification, prelude::*};
+use ui::{CollabNotification, Tooltip, prelude::*};
 use util::ResultExt;
-use workspace::AppState;
+use workspace::{
+    AppState,
+    notifications::{
+        NotificationId, show_app_notification, simple_message_notification::MessageNotification,
+    },
+};
 
 pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
     let app_state = Arc::downgrade(app_state);
@@ -24,9 +29,15 @@ pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
 
             if let Some(incoming_call) = incoming_call {
                 let unique_screens = cx.update(|cx| cx.displays());
+                // Make room for the project's name and the button to join the call without it.
+                let height = if incoming_call.initial_project.is_some() {
+                    px(96.)
+                } else {
+                    px(72.)
+                };
                 let window_size = gpui::Size {
                     width: px(400.),
-                    height: px(72.),
+                    height,
                 };
 
                 for screen in unique_screens {
@@ -46,6 +57,14 @@ pub fn init(app_state: &Arc<AppState>, cx: &mut App) {
     .detach();
 }
 
+#[derive(Clone, Copy)]
+enum Response {
+    Accept,
+    /// Joins the call, but not the project the caller is sharing with it.
+    AcceptCallOnly,
+    Decline,
+}
+
 struct IncomingCallNotificationState {
     call: IncomingCall,
     app_state: Weak<AppState>,
@@ -53,74 +72,124 @@ struct IncomingCallNotificationState {
 
 pub struct IncomingCallNotification {
     state: Arc<IncomingCallNotificationState>,
+    project_names: Option<SharedString>,
 }
 impl IncomingCallNotificationState {
     pub fn new(call: IncomingCall, app_state: Weak<AppState>) -> Self {
         Self { call, app_state }
     }
 
-    fn respond(&self, accept: bool, cx: &mut App) {
+    fn respond(&self, response: Response, cx: &mut App) {
         let active_call = ActiveCall::global(cx);
-        if accept {
-            let join = active_call.update(cx, |active_call, cx| active_call.accept_incoming(cx));
-            let caller_user_id = self.call.calling_user.id;
-            let initial_project_id = self.call.initial_project.as_ref().map(|project| project.id);
-            let app_state = self.app_state.clone();
-            let cx: &mut App = cx;
-            cx.spawn(async move |cx| {
-                join.await?;
-                if let Some(project_id) = initial_project_id {
-                    cx.update(|cx| {
-                        if let Some(app_state) = app_state.upgrade() {
-                            workspace::join_in_room_project(
-                                project_id,
-                                caller_user_id,
-                                app_state,
-                                cx,
-                            )
-                            .detach_and_log_err(cx);
-                        }
-                    });
+        let join = match response {
+            Response::Accept => {
+                active_call.update(cx, |active_call, cx| active_call.accept_incoming(cx))
+            }
+            Response::AcceptCallOnly => active_call.update(cx, |active_call, cx| {
+                active_call.accept_incoming_call_only(cx)
+            }),
+            Response::Decline => {
+                active_call.update(cx, |active_call, cx| {
+                    active_call.decline_incoming(cx).log_err();
+                });
+                return;
+            }
+        };
+        let caller_user_id = self.call.calling_user.id;
+        let app_state = self.app_state.clone();
+        cx.spawn(async move |cx| {
+            let initial_project_id = match join.await {
+                Ok(initial_project_id) => initial_project_id,
+                Err(error) => {
+                    cx.update(|cx| show_join_error(&error, cx));
+                    return Err(error.into());
                 }
-                anyhow::Ok(())
-            })
-            .detach_and_log_err(cx);
-        } else {
-            active_call.update(cx, |active_call, cx| {
-                active_call.decline_incoming(cx).log_err();
-            });
-        }
+            };
+            if let Some(project_id) = initial_project_id {
+                cx.update(|cx| {
+                    if let Some(app_state) = app_state.upgrade() {
+                        workspace::join_in_room_project(project_id, caller_user_id, app_state, cx)
+                            .detach_and_log_err(cx);
+                    }
+                });
+            }
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
     }
 }
 
+fn show_join_error(error: &CallError, cx: &mut App) {
+    let message: SharedString = match error {
+        // The call was cancelled before it was accepted, and its notification is already gone.
+        CallError::NoIncomingCall => return,
+        error => match crate::call_error_detail(error) {
+            Some(detail) => format!("Failed to join the call. {detail}").into(),
+            None => format!("Failed to join the call: {error}").into(),
+        },
+    };
+    show_app_notification(
+        NotificationId::unique::<IncomingCallNotification>(),
+        cx,
+        move |cx| cx.new(|cx| MessageNotification::new(message.clone(), cx)),
+    );
+}
+
 impl IncomingCallNotification {
     pub fn new(call: IncomingCall, app_state: Weak<AppState>) -> Self {
         Self {
+            project_names: call.initial_project_names().map(SharedString::from),
             state: Arc::new(IncomingCallNotificationState::new(call, app_state)),
         }
     }
+
+    /// The names of the worktrees in the project the caller is sharing, as shown below the
+    /// caller's name.
+    pub fn project_names(&self) -> Option<&SharedString> {
+        self.project_names.as_ref()
+    }
 }
 
 impl Render for IncomingCallNotification {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let ui_font = theme::setup_ui_font(window, cx);
+        let punctuation = if self.project_names.is_some() {
+            ":"
+        } else {
+            ""
+        };
 
         div().size_full().font(ui_font).child(
             CollabNotification::new(
                 self.state.call.calling_user.avatar_uri.clone(),
                 Button::new("accept", "Accept").on_click({
                     let state = self.state.clone();
-                    move |_, _, cx| state.respond(true, cx)
+                    move |_, _, cx| state.respond(Response::Accept, cx)
                 }),
                 Button::new("decline", "Decline").on_click({
                     let state = self.state.clone();
-                    move |_, _, cx| state.respond(false, cx)
+                    move |_, _, cx| state.respond(Response::Decline, cx)
                 }),
             )
             .child(Label::new(format!(
-                "{} is sharing a project in Zed",
-                self.state.call.calling_user.github_login
-            ))),
+                "{} is sharing a project in Zed{}",
+                self.state.call.calling_user.github_login, punctuation
+            )))
+            .when_some(self.project_names.clone(), |this, project_names| {
+                this.child(Label::new(project_names).color(Color::Muted))
+            })
+            .when(self.state.call.initial_project.is_some(), |this| {
+                this.child(
+                    Button::new("accept_call_only", "Join Call Only")
+                        .style(ButtonStyle::Subtle)
+                        .label_size(LabelSize::Small)
+                        .tooltip(Tooltip::text("Join without opening the shared project"))
+                        .on_click({
+                            let state = self.state.clone();
+                            move |_, _, cx| state.respond(Response::AcceptCallOnly, cx)
+                        }),
+                )
+            }),
         )
     }
 }
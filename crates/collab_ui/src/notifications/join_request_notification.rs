@@ -0,0 +1,168 @@
+use crate::notification_window_options;
+use call::{ActiveCall, JoinRequest, room};
+use channel::ChannelStore;
+use client::ChannelId;
+use collections::HashMap;
+use futures::StreamExt;
+use gpui::{App, Size, WindowHandle, prelude::*};
+use std::sync::Arc;
+
+use ui::{CollabNotification, prelude::*};
+use util::ResultExt;
+use workspace::{
+    AppState,
+    notifications::{
+        NotificationId, show_app_notification, simple_message_notification::MessageNotification,
+    },
+};
+
+pub fn init(_: &Arc<AppState>, cx: &mut App) {
+    let active_call = ActiveCall::global(cx);
+
+    // Channel admins are asked whether to let in each user that requests to join.
+    let mut join_requests = active_call.read(cx).incoming_join_requests();
+    cx.spawn(async move |cx| {
+        let mut notification_windows =
+            HashMap::<(u64, ChannelId), Vec<WindowHandle<JoinRequestNotification>>>::default();
+        while let Some(join_requests) = join_requests.next().await {
+            notification_windows.retain(|(requester_id, channel_id), windows| {
+                let pending = join_requests.iter().any(|request| {
+                    request.requester.id == *requester_id && request.channel_id == *channel_id
+                });
+                if !pending {
+                    for window in windows.drain(..) {
+                        window
+                            .update(cx, |_, window, _| {
+                                window.remove_window();
+                            })
+                            .log_err();
+                    }
+                }
+                pending
+            });
+
+            for request in join_requests {
+                let key = (request.requester.id, request.channel_id);
+                if notification_windows.contains_key(&key) {
+                    continue;
+                }
+                let channel_name = cx.update(|cx| channel_name(request.channel_id, cx));
+                let window_size = Size {
+                    width: px(400.),
+                    height: px(72.),
+                };
+                let windows = notification_windows.entry(key).or_default();
+                for screen in cx.update(|cx| cx.displays()) {
+                    let options =
+                        cx.update(|cx| notification_window_options(screen, window_size, cx));
+                    if let Ok(window) = cx.open_window(options, |_, cx| {
+                        cx.new(|_| {
+                            JoinRequestNotification::new(request.clone(), channel_name.clone())
+                        })
+                    }) {
+                        windows.push(window);
+                    }
+                }
+            }
+        }
+    })
+    .detach();
+
+    // The user who asked to join hears back once an admin answers.
+    cx.subscribe(&active_call, |_, event, cx| {
+        if let room::Event::JoinRequestAnswered {
+            channel_id,
+            accepted,
+        } = event
+        {
+            show_join_request_answer(*channel_id, *accepted, cx);
+        }
+    })
+    .detach();
+}
+
+fn channel_name(channel_id: ChannelId, cx: &App) -> SharedString {
+    ChannelStore::global(cx)
+        .read(cx)
+        .channel_for_id(channel_id)
+        .map(|channel| format!("#{}", channel.name).into())
+        .unwrap_or_else(|| "the channel".into())
+}
+
+fn show_join_request_answer(channel_id: ChannelId, accepted: bool, cx: &mut App) {
+    let channel_name = channel_name(channel_id, cx);
+    show_app_notification(
+        NotificationId::composite::<JoinRequestNotification>(channel_id.0 as usize),
+        cx,
+        move |cx| {
+            let channel_name = channel_name.clone();
+            cx.new(|cx| {
+                if accepted {
+                    MessageNotification::new(
+                        format!("Your request to join {channel_name} was accepted."),
+                        cx,
+                    )
+                    .primary_message("Join")
+                    .primary_on_click(move |_, cx| {
+                        if let Some(app_state) = AppState::global(cx).upgrade() {
+                            workspace::join_channel(channel_id, app_state, None, None, cx)
+                                .detach_and_log_err(cx);
+                        }
+                    })
+                } else {
+                    MessageNotification::new(
+                        format!("Your request to join {channel_name} was declined."),
+                        cx,
+                    )
+                }
+            })
+        },
+    );
+}
+
+/// Asks a channel admin whether a user who isn't a member of the channel may join it.
+pub struct JoinRequestNotification {
+    request: JoinRequest,
+    channel_name: SharedString,
+}
+
+impl JoinRequestNotification {
+    fn new(request: JoinRequest, channel_name: SharedString) -> Self {
+        Self {
+            request,
+            channel_name,
+        }
+    }
+
+    fn respond(&mut self, accept: bool, cx: &mut Context<Self>) {
+        let requester_id = self.request.requester.id;
+        let channel_id = self.request.channel_id;
+        ActiveCall::global(cx)
+            .update(cx, |active_call, cx| {
+                active_call.respond_to_join_request(requester_id, channel_id, accept, cx)
+            })
+            .detach_and_log_err(cx);
+    }
+}
+
+impl Render for JoinRequestNotification {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let ui_font = theme::setup_ui_font(window, cx);
+
+        div().size_full().font(ui_font).child(
+            CollabNotification::new(
+                self.request.requester.avatar_uri.clone(),
+                Button::new("accept", "Let In").on_click(cx.listener(|this, _event, _, cx| {
+                    this.respond(true, cx);
+                })),
+                Button::new("decline", "Decline").on_click(cx.listener(|this, _event, _, cx| {
+                    this.respond(false, cx);
+                })),
+            )
+            .child(Label::new(format!(
+                "{} asks to join {}",
+                self.request.requester.github_login, self.channel_name
+            ))),
+        )
+    }
+}
@@ -0,0 +1,60 @@
+use call::{ActiveCall, room};
+use gpui::App;
+use std::{sync::Arc, time::Duration};
+use ui::prelude::*;
+use workspace::{
+    AppState,
+    notifications::{
+        NotificationId, dismiss_app_notification, show_app_notification,
+        simple_message_notification::MessageNotification,
+    },
+};
+
+/// How long the notice that a call was joined muted stays up.
+const NOTICE_DURATION: Duration = Duration::from_secs(8);
+
+struct MutedOnJoinNotification;
+
+pub fn init(_: &Arc<AppState>, cx: &mut App) {
+    let active_call = ActiveCall::global(cx);
+    cx.subscribe(&active_call, |_, event, cx| match event {
+        room::Event::MutedOnJoin { participant_count } => {
+            show_muted_on_join(*participant_count, cx);
+        }
+        room::Event::RoomLeft { .. } => {
+            dismiss_app_notification(&NotificationId::unique::<MutedOnJoinNotification>(), cx);
+        }
+        _ => {}
+    })
+    .detach();
+}
+
+fn show_muted_on_join(participant_count: usize, cx: &mut App) {
+    let id = NotificationId::unique::<MutedOnJoinNotification>();
+    show_app_notification(id.clone(), cx, move |cx| {
+        cx.new(|cx| {
+            MessageNotification::new(
+                format!(
+                    "You joined muted because {participant_count} people are already in the call."
+                ),
+                cx,
+            )
+            .primary_message("Unmute")
+            .primary_icon(IconName::Mic)
+            .primary_on_click(|_, cx| {
+                let room = ActiveCall::global(cx).read(cx).room().cloned();
+                if let Some(room) = room
+                    && room.read(cx).is_muted()
+                {
+                    title_bar::collab::toggle_mute(cx);
+                }
+            })
+        })
+    });
+
+    cx.spawn(async move |cx| {
+        cx.background_executor().timer(NOTICE_DURATION).await;
+        cx.update(|cx| dismiss_app_notification(&id, cx));
+    })
+    .detach();
+}
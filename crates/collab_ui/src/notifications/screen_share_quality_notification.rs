@@ -0,0 +1,64 @@
+use call::{ActiveCall, room, screen_share_quality::ScreenSharePreset};
+use gpui::App;
+use std::{sync::Arc, time::Duration};
+use ui::prelude::*;
+use workspace::{
+    AppState,
+    notifications::{
+        NotificationId, dismiss_app_notification, show_app_notification,
+        simple_message_notification::MessageNotification,
+    },
+};
+
+/// How long the notice that the screen share's quality was lowered stays up.
+const NOTICE_DURATION: Duration = Duration::from_secs(8);
+
+struct ScreenShareQualityNotification;
+
+pub fn init(_: &Arc<AppState>, cx: &mut App) {
+    let active_call = ActiveCall::global(cx);
+    cx.subscribe(&active_call, |_, event, cx| match event {
+        room::Event::ScreenSharePresetChanged {
+            preset,
+            stepped_down: true,
+            ..
+        } => {
+            show_stepped_down(*preset, cx);
+        }
+        room::Event::ScreenSharePresetChanged { pinned: true, .. }
+        | room::Event::RoomLeft { .. } => {
+            dismiss_app_notification(
+                &NotificationId::unique::<ScreenShareQualityNotification>(),
+                cx,
+            );
+        }
+        _ => {}
+    })
+    .detach();
+}
+
+fn show_stepped_down(preset: ScreenSharePreset, cx: &mut App) {
+    let id = NotificationId::unique::<ScreenShareQualityNotification>();
+    show_app_notification(id.clone(), cx, move |cx| {
+        cx.new(|cx| {
+            MessageNotification::new(
+                format!(
+                    "Your connection can't keep up, so your screen share was lowered to {} quality.",
+                    preset.label()
+                ),
+                cx,
+            )
+            .primary_message("Don't Lower Again")
+            .primary_icon(IconName::Screen)
+            .primary_on_click(|_, cx| {
+                title_bar::collab::set_screen_share_preset_pinned(true, cx);
+            })
+        })
+    });
+
+    cx.spawn(async move |cx| {
+        cx.background_executor().timer(NOTICE_DURATION).await;
+        cx.update(|cx| dismiss_app_notification(&id, cx));
+    })
+    .detach();
+}
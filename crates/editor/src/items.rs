@@ -969,6 +969,15 @@ impl Item for Editor {
         self.pixel_position_of_newest_cursor
     }
 
+    fn cursor_row(&self, cx: &App) -> Option<u32> {
+        let buffer = self.buffer.read(cx);
+        if !buffer.is_singleton() {
+            return None;
+        }
+        let head = self.selections.newest_anchor().head();
+        Some(head.to_point(&buffer.snapshot(cx)).row)
+    }
+
     fn breadcrumb_location(&self, cx: &App) -> ToolbarItemLocation {
         if self.show_breadcrumbs && self.buffer().read(cx).is_singleton() {
             ToolbarItemLocation::PrimaryLeft
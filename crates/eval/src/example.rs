@@ -331,6 +331,7 @@ impl ExampleContext {
                     ThreadEvent::SubagentSpawned(session) => {
                         println!("{log_prefix} Got subagent spawn: {session:?}");
                     }
+                    ThreadEvent::Plan(_) => {}
                     ThreadEvent::Retry(status) => {
                         println!("{log_prefix} Got retry: {status:?}");
                     }
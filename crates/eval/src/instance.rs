@@ -302,6 +302,7 @@ impl ExampleInstance {
                         root_name,
                         abs_path,
                         rules_file: None,
+                        instruction_files: Vec::new(),
                     }
                 }).collect::<Vec<_>>();
                 let project_context = cx.new(|_cx| ProjectContext::new(worktrees, vec![]));
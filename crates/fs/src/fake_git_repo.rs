@@ -605,6 +605,14 @@ impl GitRepository for FakeGitRepository {
         .boxed()
     }
 
+    fn file_history_in_range(
+        &self,
+        path: RepoPath,
+        _range: String,
+    ) -> BoxFuture<'_, Result<git::repository::FileHistory>> {
+        self.file_history_paginated(path, 0, None)
+    }
+
     fn stage_paths(
         &self,
         paths: Vec<RepoPath>,
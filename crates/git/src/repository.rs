@@ -784,6 +784,13 @@ pub trait GitRepository: Send + Sync {
         skip: usize,
         limit: Option<usize>,
     ) -> BoxFuture<'_, Result<FileHistory>>;
+    /// Returns the commits in a revision range, like `main..HEAD`, that changed the file at
+    /// `path`, newest first.
+    fn file_history_in_range(
+        &self,
+        path: RepoPath,
+        range: String,
+    ) -> BoxFuture<'_, Result<FileHistory>>;
 
     /// Returns the absolute path to the repository. For worktrees, this will be the path to the
     /// worktree's gitdir within the main repository (typically `.git/worktrees/<name>`).
@@ -1922,76 +1929,34 @@ impl GitRepository for RealGitRepository {
         let git_binary_path = self.any_git_binary_path.clone();
         self.executor
             .spawn(async move {
-                let working_directory = working_directory?;
-                // Use a unique delimiter with a hardcoded UUID to separate commits
-                // This essentially eliminates any chance of encountering the delimiter in actual commit data
-                let commit_delimiter =
-                    concat!("<<COMMIT_END-", "3f8a9c2e-7d4b-4e1a-9f6c-8b5d2a1e4c3f>>",);
-
-                let format_string = format!(
-                    "--pretty=format:%H%x00%s%x00%B%x00%at%x00%an%x00%ae{}",
-                    commit_delimiter
-                );
-
-                let mut args = vec!["--no-optional-locks", "log", "--follow", &format_string];
-
-                let skip_str;
-                let limit_str;
+                let mut args = Vec::new();
                 if skip > 0 {
-                    skip_str = skip.to_string();
-                    args.push("--skip");
-                    args.push(&skip_str);
+                    args.push("--skip".to_string());
+                    args.push(skip.to_string());
                 }
                 if let Some(n) = limit {
-                    limit_str = n.to_string();
-                    args.push("-n");
-                    args.push(&limit_str);
-                }
-
-                args.push("--");
-
-                let output = new_command(&git_binary_path)
-                    .current_dir(&working_directory)
-                    .args(&args)
-                    .arg(path.as_unix_str())
-                    .output()
-                    .await?;
-
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    bail!("git log failed: {stderr}");
+                    args.push("-n".to_string());
+                    args.push(n.to_string());
                 }
+                log_file_history(&git_binary_path, &working_directory?, path, args).await
+            })
+            .boxed()
+    }
 
-                let stdout = std::str::from_utf8(&output.stdout)?;
-                let mut entries = Vec::new();
-
-                for commit_block in stdout.split(commit_delimiter) {
-                    let commit_block = commit_block.trim();
-                    if commit_block.is_empty() {
-                        continue;
-                    }
-
-                    let fields: Vec<&str> = commit_block.split('\0').collect();
-                    if fields.len() >= 6 {
-                        let sha = fields[0].trim().to_string().into();
-                        let subject = fields[1].trim().to_string().into();
-                        let message = fields[2].trim().to_string().into();
-                        let commit_timestamp = fields[3].trim().parse().unwrap_or(0);
-                        let author_name = fields[4].trim().to_string().into();
-                        let author_email = fields[5].trim().to_string().into();
-
-                        entries.push(FileHistoryEntry {
-                            sha,
-                            subject,
-                            message,
-                            commit_timestamp,
-                            author_name,
-                            author_email,
-                        });
-                    }
+    fn file_history_in_range(
+        &self,
+        path: RepoPath,
+        range: String,
+    ) -> BoxFuture<'_, Result<FileHistory>> {
+        let working_directory = self.working_directory();
+        let git_binary_path = self.any_git_binary_path.clone();
+        self.executor
+            .spawn(async move {
+                // Keep the range from being taken as an option.
+                if range.is_empty() || range.starts_with('-') {
+                    bail!("invalid revision range: {range:?}");
                 }
-
-                Ok(FileHistory { entries, path })
+                log_file_history(&git_binary_path, &working_directory?, path, vec![range]).await
             })
             .boxed()
     }
@@ -3049,6 +3014,68 @@ fn git_status_args(path_prefixes: &[RepoPath]) -> Vec<OsString> {
 }
 
 /// Temporarily git-ignore commonly ignored files and files over 2MB
+/// Runs `git log --follow` on the file at `path`, with `args` coming before the path.
+async fn log_file_history(
+    git_binary_path: &Path,
+    working_directory: &Path,
+    path: RepoPath,
+    args: Vec<String>,
+) -> Result<FileHistory> {
+    // Use a unique delimiter with a hardcoded UUID to separate commits
+    // This essentially eliminates any chance of encountering the delimiter in actual commit data
+    let commit_delimiter = concat!("<<COMMIT_END-", "3f8a9c2e-7d4b-4e1a-9f6c-8b5d2a1e4c3f>>",);
+
+    let format_string = format!(
+        "--pretty=format:%H%x00%s%x00%B%x00%at%x00%an%x00%ae{}",
+        commit_delimiter
+    );
+
+    let output = new_command(git_binary_path)
+        .current_dir(working_directory)
+        .args(["--no-optional-locks", "log", "--follow", &format_string])
+        .args(&args)
+        .arg("--")
+        .arg(path.as_unix_str())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git log failed: {stderr}");
+    }
+
+    let stdout = std::str::from_utf8(&output.stdout)?;
+    let mut entries = Vec::new();
+
+    for commit_block in stdout.split(commit_delimiter) {
+        let commit_block = commit_block.trim();
+        if commit_block.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = commit_block.split('\0').collect();
+        if fields.len() >= 6 {
+            let sha = fields[0].trim().to_string().into();
+            let subject = fields[1].trim().to_string().into();
+            let message = fields[2].trim().to_string().into();
+            let commit_timestamp = fields[3].trim().parse().unwrap_or(0);
+            let author_name = fields[4].trim().to_string().into();
+            let author_email = fields[5].trim().to_string().into();
+
+            entries.push(FileHistoryEntry {
+                sha,
+                subject,
+                message,
+                commit_timestamp,
+                author_name,
+                author_email,
+            });
+        }
+    }
+
+    Ok(FileHistory { entries, path })
+}
+
 async fn exclude_files(git: &GitBinary) -> Result<GitExcludeOverride> {
     const MAX_SIZE: u64 = 2 * 1024 * 1024; // 2 MB
     let mut excludes = git.with_exclude_overrides().await?;
@@ -480,6 +480,18 @@ impl ForegroundExecutor {
     }
 }
 
+impl gpui_util::ordered_task_queue::Spawn for BackgroundExecutor {
+    fn spawn_detached(&self, future: futures::future::BoxFuture<'static, ()>) {
+        self.spawn(future).detach();
+    }
+}
+
+impl gpui_util::ordered_task_queue::Spawn for ForegroundExecutor {
+    fn spawn_detached(&self, future: futures::future::BoxFuture<'static, ()>) {
+        self.spawn(future).detach();
+    }
+}
+
 /// Scope manages a set of tasks that are enqueued and waited on together. See [`BackgroundExecutor::scoped`].
 pub struct Scope<'a> {
     executor: BackgroundExecutor,
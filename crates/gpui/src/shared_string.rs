@@ -24,6 +24,16 @@ impl SharedString {
         SharedString(ArcCow::Owned(str.into()))
     }
 
+    /// Creates a [`SharedString`] that shares the string an [`ArcCow`] holds, without copying it.
+    pub const fn from_arc_cow(str: ArcCow<'static, str>) -> Self {
+        Self(str)
+    }
+
+    /// Returns the underlying [`ArcCow`], which shares this string without copying it.
+    pub fn into_arc_cow(self) -> ArcCow<'static, str> {
+        self.0
+    }
+
     /// Get a &str from the underlying string.
     pub fn as_str(&self) -> &str {
         &self.0
@@ -106,10 +116,13 @@ impl From<&SharedString> for SharedString {
 
 impl From<SharedString> for Arc<str> {
     fn from(val: SharedString) -> Self {
-        match val.0 {
-            ArcCow::Borrowed(borrowed) => Arc::from(borrowed),
-            ArcCow::Owned(owned) => owned,
-        }
+        val.0.into_arc_str()
+    }
+}
+
+impl From<SharedString> for ArcCow<'static, str> {
+    fn from(val: SharedString) -> Self {
+        val.0
     }
 }
 
@@ -143,3 +156,25 @@ impl<'de> Deserialize<'de> for SharedString {
         Ok(SharedString::from(s))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions_keep_allocation() {
+        let arc: Arc<str> = Arc::from("python3");
+        let shared = SharedString::from_arc_cow(ArcCow::from(arc.clone()));
+        assert!(std::ptr::eq(shared.as_ptr(), arc.as_ptr()));
+
+        let cow: ArcCow<'static, str> = shared.clone().into();
+        assert!(std::ptr::eq(cow.as_ptr(), arc.as_ptr()));
+        assert!(ArcCow::ptr_eq(&cow, &shared.clone().into_arc_cow()));
+
+        let back: Arc<str> = shared.into();
+        assert!(Arc::ptr_eq(&back, &arc));
+
+        let shared = SharedString::new(arc.clone());
+        assert!(std::ptr::eq(shared.as_ptr(), arc.as_ptr()));
+    }
+}
@@ -1,5 +1,5 @@
 use collections::{BTreeMap, BTreeSet};
-use gpui_util::post_inc;
+use gpui_util::id_generator::IdGenerator;
 use std::{
     cell::{Cell, RefCell},
     fmt::Debug,
@@ -20,7 +20,7 @@ impl<EmitterKey, Callback> Clone for SubscriberSet<EmitterKey, Callback> {
 struct SubscriberSetState<EmitterKey, Callback> {
     subscribers: BTreeMap<EmitterKey, Option<BTreeMap<usize, Subscriber<Callback>>>>,
     dropped_subscribers: BTreeSet<(EmitterKey, usize)>,
-    next_subscriber_id: usize,
+    subscriber_ids: IdGenerator<usize>,
 }
 
 struct Subscriber<Callback> {
@@ -37,7 +37,7 @@ where
         Self(Rc::new(RefCell::new(SubscriberSetState {
             subscribers: Default::default(),
             dropped_subscribers: Default::default(),
-            next_subscriber_id: 0,
+            subscriber_ids: IdGenerator::new(),
         })))
     }
 
@@ -52,7 +52,7 @@ where
     ) -> (Subscription, impl FnOnce() + use<EmitterKey, Callback>) {
         let active = Rc::new(Cell::new(false));
         let mut lock = self.0.borrow_mut();
-        let subscriber_id = post_inc(&mut lock.next_subscriber_id);
+        let subscriber_id = lock.subscriber_ids.next_id();
         lock.subscribers
             .entry(emitter_key.clone())
             .or_default()
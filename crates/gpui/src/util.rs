@@ -1,4 +1,5 @@
 use crate::{BackgroundExecutor, Task};
+use gpui_util::WhenExt;
 use std::{
     future::Future,
     pin::Pin,
@@ -8,6 +9,10 @@ use std::{
 };
 
 /// A helper trait for building complex objects with imperative conditionals in a fluent style.
+///
+/// The conditionals are those of [`gpui_util::WhenExt`], which provides them for builders that
+/// aren't elements. That trait is implemented for every type, so gpui doesn't export it: with
+/// both traits in scope, calls on elements would be ambiguous.
 pub trait FluentBuilder {
     /// Imperatively modify self with the given closure.
     fn map<U>(self, f: impl FnOnce(Self) -> U) -> U
@@ -22,7 +27,7 @@ pub trait FluentBuilder {
     where
         Self: Sized,
     {
-        self.map(|this| if condition { then(this) } else { this })
+        WhenExt::when(self, condition, then)
     }
 
     /// Conditionally modify self with the given closure.
@@ -35,7 +40,7 @@ pub trait FluentBuilder {
     where
         Self: Sized,
     {
-        self.map(|this| if condition { then(this) } else { else_fn(this) })
+        WhenExt::when_else(self, condition, then, else_fn)
     }
 
     /// Conditionally unwrap and modify self with the given closure, if the given option is Some.
@@ -43,20 +48,22 @@ pub trait FluentBuilder {
     where
         Self: Sized,
     {
-        self.map(|this| {
-            if let Some(value) = option {
-                then(this, value)
-            } else {
-                this
-            }
-        })
+        WhenExt::when_some(self, option, then)
     }
     /// Conditionally unwrap and modify self with the given closure, if the given option is None.
     fn when_none<T>(self, option: &Option<T>, then: impl FnOnce(Self) -> Self) -> Self
     where
         Self: Sized,
     {
-        self.map(|this| if option.is_some() { this } else { then(this) })
+        WhenExt::when_none(self, option, then)
+    }
+
+    /// Same as [`FluentBuilder::when`], for steps that transform self rather than add to it.
+    fn map_if(self, condition: bool, f: impl FnOnce(Self) -> Self) -> Self
+    where
+        Self: Sized,
+    {
+        WhenExt::map_if(self, condition, f)
     }
 }
 
@@ -155,4 +162,45 @@ mod tests {
             .unwrap_or_else(|| panic!("timeout should have triggered"))
             .expect_err("timeout");
     }
+
+    // Elements get the conditionals from `FluentBuilder` through the prelude, and other builders
+    // from `WhenExt`. These only need to compile: a call that resolved to both traits wouldn't.
+    mod prelude_elements {
+        use crate::{Div, div, prelude::*};
+
+        #[allow(dead_code)]
+        fn build(label: Option<&'static str>, is_selected: bool) -> Div {
+            div()
+                .when_some(label, |this, label| this.child(label))
+                .when_else(
+                    is_selected,
+                    |this| this.font_weight(crate::FontWeight::BOLD),
+                    |this| this,
+                )
+                .map_if(!is_selected, |this| this.opacity(0.5))
+        }
+    }
+
+    mod glob_elements {
+        use crate::{prelude::FluentBuilder as _, *};
+
+        #[allow(dead_code)]
+        fn build(is_selected: bool) -> Div {
+            div().when(is_selected, |this| this.opacity(0.5))
+        }
+    }
+
+    mod other_builders {
+        use gpui_util::WhenExt as _;
+
+        #[allow(dead_code)]
+        fn build(display_name: Option<&str>) -> Vec<String> {
+            vec!["python".to_string()]
+                .when_some(display_name, |mut argv, name| {
+                    argv.push(format!("--name={name}"));
+                    argv
+                })
+                .map_if(cfg!(windows), |argv| argv)
+        }
+    }
 }
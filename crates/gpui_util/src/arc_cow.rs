@@ -11,6 +11,23 @@ pub enum ArcCow<'a, T: ?Sized> {
     Owned(Arc<T>),
 }
 
+impl<T: ?Sized> ArcCow<'_, T> {
+    /// Whether both point to the same value in memory, like [`Arc::ptr_eq`].
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        std::ptr::addr_eq(this.as_ref() as *const T, other.as_ref() as *const T)
+    }
+}
+
+impl ArcCow<'_, str> {
+    /// Converts into an `Arc<str>`, which only allocates when the string is borrowed.
+    pub fn into_arc_str(self) -> Arc<str> {
+        match self {
+            Self::Borrowed(borrowed) => Arc::from(borrowed),
+            Self::Owned(owned) => owned,
+        }
+    }
+}
+
 impl<T: ?Sized + PartialEq> PartialEq for ArcCow<'_, T> {
     fn eq(&self, other: &Self) -> bool {
         let a = self.as_ref();
@@ -90,6 +107,12 @@ impl<'a> From<Cow<'a, str>> for ArcCow<'a, str> {
     }
 }
 
+impl From<ArcCow<'_, str>> for Arc<str> {
+    fn from(value: ArcCow<'_, str>) -> Self {
+        value.into_arc_str()
+    }
+}
+
 impl<T> From<Vec<T>> for ArcCow<'_, [T]> {
     fn from(vec: Vec<T>) -> Self {
         ArcCow::Owned(Arc::from(vec))
@@ -139,3 +162,40 @@ impl<T: ?Sized + Debug> Debug for ArcCow<'_, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arc_str_round_trip_keeps_allocation() {
+        let arc: Arc<str> = Arc::from("python3");
+        let cow = ArcCow::from(arc.clone());
+        assert!(ArcCow::ptr_eq(&cow, &ArcCow::from(&arc)));
+
+        let back: Arc<str> = cow.into();
+        assert!(Arc::ptr_eq(&back, &arc));
+
+        // A borrowed string has to be copied into a new allocation.
+        let borrowed = "deno";
+        let copied = ArcCow::Borrowed(borrowed).into_arc_str();
+        assert_eq!(&*copied, borrowed);
+        assert!(!std::ptr::eq(copied.as_ptr(), borrowed.as_ptr()));
+    }
+
+    #[test]
+    fn test_ptr_eq() {
+        let owned = ArcCow::<str>::from(String::from("python3"));
+        assert!(ArcCow::ptr_eq(&owned, &owned.clone()));
+        assert!(!ArcCow::ptr_eq(
+            &owned,
+            &ArcCow::from(String::from("python3"))
+        ));
+
+        let borrowed = "deno";
+        assert!(ArcCow::ptr_eq(
+            &ArcCow::Borrowed(borrowed),
+            &ArcCow::Borrowed(borrowed)
+        ));
+    }
+}
@@ -0,0 +1,391 @@
+//! A value that's initialized asynchronously the first time it's needed, like a list of kernel
+//! specs that has to be fetched, and then shared by everyone who asks for it.
+//!
+//! Wrapping the initialization in a `OnceLock` would block the thread while it runs, and a shared
+//! future can't be retried once it has failed. [`AsyncLazy`] runs one initialization at a time,
+//! which every caller that comes along in the meantime waits on. If it fails, the next caller
+//! tries again, optionally after a cooldown so that a failing network request isn't retried in a
+//! tight loop. Nothing is spawned: whichever caller's future is polled does the work.
+
+use crate::id_generator::IdGenerator;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, MutexGuard, OnceLock},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+pub struct AsyncLazy<T> {
+    value: OnceLock<T>,
+    state: Mutex<InitState>,
+    retry_cooldown: Option<Duration>,
+}
+
+struct InitState {
+    /// Whether a caller is running its initialization.
+    initializing: bool,
+    /// When the last initialization failed, if it did since the last success or retry.
+    failed_at: Option<Instant>,
+    waiters: Vec<(u64, Waker)>,
+    waiter_ids: IdGenerator<u64>,
+}
+
+/// Returned by [`AsyncLazy::get_or_init`] when a recent initialization failed and it's too soon
+/// to try again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoolingDown {
+    pub retry_at: Instant,
+}
+
+impl fmt::Display for CoolingDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remaining = self.retry_at.saturating_duration_since(Instant::now());
+        write!(
+            f,
+            "initialization failed recently, retrying in {}ms",
+            remaining.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for CoolingDown {}
+
+impl<T> AsyncLazy<T> {
+    pub const fn new() -> Self {
+        Self {
+            value: OnceLock::new(),
+            state: Mutex::new(InitState {
+                initializing: false,
+                failed_at: None,
+                waiters: Vec::new(),
+                waiter_ids: IdGenerator::new(),
+            }),
+            retry_cooldown: None,
+        }
+    }
+
+    /// After a failed initialization, callers get a [`CoolingDown`] error until `cooldown` has
+    /// passed, rather than trying again right away.
+    pub const fn with_retry_cooldown(cooldown: Duration) -> Self {
+        let mut lazy = Self::new();
+        lazy.retry_cooldown = Some(cooldown);
+        lazy
+    }
+
+    /// Returns the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns the value, initializing it with `init` if no one has yet.
+    ///
+    /// If another caller is already initializing it, this waits for that to finish instead of
+    /// calling `init`. When that caller fails or is dropped before finishing, one of the waiting
+    /// callers takes over with its own `init`. A caller whose `init` fails gets its error.
+    pub async fn get_or_init<F, Fut, E>(&self, init: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<CoolingDown>,
+    {
+        match (Turn {
+            lazy: self,
+            waiter_id: None,
+        })
+        .await
+        {
+            TurnOutcome::Initialized(value) => return Ok(value),
+            TurnOutcome::CoolingDown(cooling_down) => return Err(cooling_down.into()),
+            TurnOutcome::Initialize => {}
+        }
+
+        // Lets a waiting caller take over if this future is dropped before `init` finishes.
+        let mut guard = InitGuard {
+            lazy: self,
+            failed: false,
+        };
+        match init().await {
+            Ok(value) => {
+                let value = self.value.get_or_init(|| value);
+                drop(guard);
+                Ok(value)
+            }
+            Err(error) => {
+                guard.failed = true;
+                drop(guard);
+                Err(error)
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InitState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Default for AsyncLazy<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsyncLazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncLazy")
+            .field("value", &self.value.get())
+            .finish_non_exhaustive()
+    }
+}
+
+enum TurnOutcome<'a, T> {
+    Initialized(&'a T),
+    CoolingDown(CoolingDown),
+    /// The caller has to initialize the value, and no one else will until it's done.
+    Initialize,
+}
+
+/// Waits until the value is initialized, or until it's the caller's turn to initialize it.
+struct Turn<'a, T> {
+    lazy: &'a AsyncLazy<T>,
+    waiter_id: Option<u64>,
+}
+
+impl<'a, T> Future for Turn<'a, T> {
+    type Output = TurnOutcome<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let lazy = self.lazy;
+        if let Some(value) = lazy.value.get() {
+            return Poll::Ready(TurnOutcome::Initialized(value));
+        }
+
+        let mut state = lazy.lock();
+        // The initializing caller sets the value before taking the lock to finish, so checking
+        // again under the lock means its wakeup can't be missed.
+        if let Some(value) = lazy.value.get() {
+            return Poll::Ready(TurnOutcome::Initialized(value));
+        }
+        if state.initializing {
+            let waiter_id = *self
+                .waiter_id
+                .get_or_insert_with(|| state.waiter_ids.next_id());
+            match state.waiters.iter_mut().find(|(id, _)| *id == waiter_id) {
+                Some((_, waker)) => waker.clone_from(cx.waker()),
+                None => state.waiters.push((waiter_id, cx.waker().clone())),
+            }
+            return Poll::Pending;
+        }
+
+        if let Some(waiter_id) = self.waiter_id.take() {
+            state.waiters.retain(|(id, _)| *id != waiter_id);
+        }
+        if let Some((failed_at, cooldown)) = state.failed_at.zip(lazy.retry_cooldown) {
+            let retry_at = failed_at + cooldown;
+            if Instant::now() < retry_at {
+                return Poll::Ready(TurnOutcome::CoolingDown(CoolingDown { retry_at }));
+            }
+        }
+        state.initializing = true;
+        state.failed_at = None;
+        Poll::Ready(TurnOutcome::Initialize)
+    }
+}
+
+impl<T> Drop for Turn<'_, T> {
+    fn drop(&mut self) {
+        if let Some(waiter_id) = self.waiter_id {
+            self.lazy.lock().waiters.retain(|(id, _)| *id != waiter_id);
+        }
+    }
+}
+
+/// Held while a caller runs its initialization, and hands the turn over when dropped, whether
+/// the initialization succeeded, failed, or was cancelled.
+struct InitGuard<'a, T> {
+    lazy: &'a AsyncLazy<T>,
+    failed: bool,
+}
+
+impl<T> Drop for InitGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.lazy.lock();
+        state.initializing = false;
+        if self.failed {
+            state.failed_at = Some(Instant::now());
+        }
+        // Every waiter checks again, and the first one polled takes over if the value still
+        // isn't set. The lock is released first so that they don't contend with it.
+        let waiters = std::mem::take(&mut state.waiters);
+        drop(state);
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        FutureExt as _,
+        channel::oneshot,
+        executor::block_on,
+        future::{self, join_all},
+        task::noop_waker_ref,
+    };
+    use std::{
+        sync::{
+            Barrier,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum TestError {
+        Failed(&'static str),
+        CoolingDown,
+    }
+
+    impl From<CoolingDown> for TestError {
+        fn from(_: CoolingDown) -> Self {
+            TestError::CoolingDown
+        }
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        future.poll_unpin(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn test_racing_callers_share_one_initialization() {
+        const CALLERS: usize = 8;
+        let lazy = AsyncLazy::<String>::new();
+        let init_count = AtomicUsize::new(0);
+        let barrier = Barrier::new(CALLERS);
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        assert_send_sync(&lazy);
+
+        let values = thread::scope(|scope| {
+            let threads = (0..CALLERS)
+                .map(|ix| {
+                    let (lazy, init_count, barrier) = (&lazy, &init_count, &barrier);
+                    scope.spawn(move || {
+                        barrier.wait();
+                        block_on(lazy.get_or_init(|| async move {
+                            init_count.fetch_add(1, Ordering::SeqCst);
+                            // Give the other threads time to find the initialization in flight.
+                            thread::sleep(Duration::from_millis(20));
+                            Ok::<_, TestError>(format!("initialized by {ix}"))
+                        }))
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(init_count.load(Ordering::SeqCst), 1);
+        let value = lazy.get().unwrap();
+        for result in values {
+            assert!(std::ptr::eq(result.unwrap(), value));
+        }
+    }
+
+    #[test]
+    fn test_waiters_take_over_after_a_failure() {
+        let lazy = AsyncLazy::<u32>::new();
+        assert_eq!(lazy.get(), None);
+        let (fail_tx, fail_rx) = oneshot::channel::<()>();
+
+        block_on(async {
+            let first = lazy.get_or_init(|| async move {
+                fail_rx.await.ok();
+                Err(TestError::Failed("first"))
+            });
+            let second = lazy.get_or_init(|| async { Ok::<_, TestError>(2) });
+            let third = lazy.get_or_init(|| async { Ok::<_, TestError>(3) });
+            // Fails the first initialization once the other callers are waiting on it.
+            let fail = async move { fail_tx.send(()).unwrap() };
+            let (first, second, third, ()) = future::join4(first, second, third, fail).await;
+            assert_eq!(first, Err(TestError::Failed("first")));
+            // The second caller took over, and the third one got its value.
+            assert_eq!(second, Ok(&2));
+            assert_eq!(third, Ok(&2));
+        });
+        assert_eq!(lazy.get(), Some(&2));
+
+        // Once initialized, `init` isn't called again.
+        let value = block_on(lazy.get_or_init(|| async { Err(TestError::Failed("unused")) }));
+        assert_eq!(value, Ok(&2));
+    }
+
+    #[test]
+    fn test_failure_then_success_after_cooldown() {
+        let cooldown = Duration::from_millis(50);
+        let lazy = AsyncLazy::<u32>::with_retry_cooldown(cooldown);
+        let result = block_on(lazy.get_or_init(|| async { Err(TestError::Failed("offline")) }));
+        assert_eq!(result, Err(TestError::Failed("offline")));
+
+        // Retrying right away doesn't call `init`.
+        let result = block_on(lazy.get_or_init(|| async { Ok(1) }));
+        assert_eq!(result, Err(TestError::CoolingDown));
+        assert_eq!(lazy.get(), None);
+
+        thread::sleep(cooldown);
+        let result = block_on(lazy.get_or_init(|| async { Ok::<_, TestError>(1) }));
+        assert_eq!(result, Ok(&1));
+    }
+
+    #[test]
+    fn test_cancelled_initialization_is_taken_over() {
+        let lazy = AsyncLazy::<&'static str>::with_retry_cooldown(Duration::from_secs(60));
+        let (_never_tx, never_rx) = oneshot::channel::<()>();
+
+        let mut first = Box::pin(lazy.get_or_init(|| async move {
+            never_rx.await.ok();
+            Ok::<_, TestError>("first")
+        }));
+        assert!(poll_once(&mut first).is_pending());
+
+        let mut waiters = (0..3)
+            .map(|_| Box::pin(lazy.get_or_init(|| async { Ok::<_, TestError>("waiter") })))
+            .collect::<Vec<_>>();
+        for waiter in &mut waiters {
+            assert!(poll_once(waiter).is_pending());
+        }
+        assert_eq!(lazy.lock().waiters.len(), 3);
+
+        // Dropping the initializing caller doesn't count as a failure, even with a cooldown.
+        drop(first);
+        let values = block_on(join_all(waiters));
+        assert_eq!(values, [Ok(&"waiter"); 3]);
+        assert!(lazy.lock().waiters.is_empty());
+        assert_eq!(lazy.lock().failed_at, None);
+    }
+
+    #[test]
+    fn test_dropped_waiters_are_unregistered() {
+        let lazy = AsyncLazy::<u32>::new();
+        let (_never_tx, never_rx) = oneshot::channel::<()>();
+        let mut first = Box::pin(lazy.get_or_init(|| async move {
+            never_rx.await.ok();
+            Ok::<_, TestError>(1)
+        }));
+        assert!(poll_once(&mut first).is_pending());
+
+        let mut waiter = Box::pin(lazy.get_or_init(|| async { Ok::<_, TestError>(2) }));
+        assert!(poll_once(&mut waiter).is_pending());
+        assert!(poll_once(&mut waiter).is_pending());
+        assert_eq!(lazy.lock().waiters.len(), 1);
+        drop(waiter);
+        assert!(lazy.lock().waiters.is_empty());
+    }
+}
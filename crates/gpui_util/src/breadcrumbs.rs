@@ -0,0 +1,217 @@
+//! A record of the most recent [`debug_panic!`](crate::debug_panic) occurrences in release
+//! builds, so that crash reports and bug reports can say which invariants were broken before
+//! things went wrong, without digging through the log.
+//!
+//! Like the [`log_buffer`](crate::log_buffer), breadcrumbs are written into fixed slots claimed
+//! with an atomic counter, each behind its own lock.
+
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    fmt,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    panic::Location,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::SystemTime,
+};
+
+/// The number of breadcrumbs kept by the global ring.
+pub const CAPACITY: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breadcrumb {
+    pub message: String,
+    pub file: &'static str,
+    pub line: u32,
+    pub timestamp: SystemTime,
+    pub thread: Option<String>,
+    /// Identifies the call stack the breadcrumb was recorded from, so that occurrences from
+    /// the same place can be grouped. `None` when backtraces aren't being captured.
+    pub backtrace_hash: Option<u32>,
+}
+
+impl Breadcrumb {
+    pub fn new(
+        location: &'static Location<'static>,
+        message: fmt::Arguments,
+        backtrace: &Backtrace,
+    ) -> Self {
+        Self {
+            message: message.to_string(),
+            file: location.file(),
+            line: location.line(),
+            timestamp: SystemTime::now(),
+            thread: thread::current().name().map(ToString::to_string),
+            backtrace_hash: backtrace_hash(backtrace),
+        }
+    }
+}
+
+fn backtrace_hash(backtrace: &Backtrace) -> Option<u32> {
+    if backtrace.status() != BacktraceStatus::Captured {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    format!("{backtrace:?}").hash(&mut hasher);
+    Some(hasher.finish() as u32)
+}
+
+/// A breadcrumb tagged with the sequence number it was written with.
+type Slot = Option<(u64, Breadcrumb)>;
+
+pub struct Breadcrumbs {
+    slots: Box<[Mutex<Slot>]>,
+    next_seq: AtomicU64,
+}
+
+impl Breadcrumbs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| Mutex::new(None)).collect(),
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, breadcrumb: Breadcrumb) {
+        let seq = self.next_seq.fetch_add(1, Ordering::AcqRel);
+        let index = (seq % self.slots.len() as u64) as usize;
+        let mut slot = self.slots[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slot.as_ref().is_none_or(|(slot_seq, _)| *slot_seq < seq) {
+            *slot = Some((seq, breadcrumb));
+        }
+    }
+
+    /// Returns the buffered breadcrumbs, oldest first.
+    pub fn recent(&self) -> Vec<Breadcrumb> {
+        let end = self.next_seq.load(Ordering::Acquire);
+        let start = end.saturating_sub(self.slots.len() as u64);
+        let mut breadcrumbs = Vec::new();
+        for seq in start..end {
+            let index = (seq % self.slots.len() as u64) as usize;
+            let slot = self.slots[index]
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some((slot_seq, breadcrumb)) = slot.as_ref()
+                && *slot_seq == seq
+            {
+                breadcrumbs.push(breadcrumb.clone());
+            }
+        }
+        breadcrumbs
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            *slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        }
+    }
+}
+
+pub fn global() -> &'static Breadcrumbs {
+    static GLOBAL: OnceLock<Breadcrumbs> = OnceLock::new();
+    GLOBAL.get_or_init(|| Breadcrumbs::new(CAPACITY))
+}
+
+/// Records a breadcrumb for the caller. Used by [`debug_panic!`](crate::debug_panic) and the
+/// other debug assertions when they fail in release builds.
+#[track_caller]
+pub fn record(message: fmt::Arguments, backtrace: &Backtrace) {
+    global().push(Breadcrumb::new(Location::caller(), message, backtrace));
+}
+
+pub fn recent() -> Vec<Breadcrumb> {
+    global().recent()
+}
+
+pub fn clear() {
+    global().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[track_caller]
+    fn breadcrumb(message: &str) -> Breadcrumb {
+        Breadcrumb::new(
+            Location::caller(),
+            format_args!("{message}"),
+            &Backtrace::disabled(),
+        )
+    }
+
+    fn messages(breadcrumbs: &[Breadcrumb]) -> Vec<&str> {
+        breadcrumbs
+            .iter()
+            .map(|breadcrumb| breadcrumb.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let breadcrumbs = Breadcrumbs::new(3);
+        for ix in 0..5 {
+            breadcrumbs.push(breadcrumb(&ix.to_string()));
+        }
+        assert_eq!(messages(&breadcrumbs.recent()), ["2", "3", "4"]);
+
+        breadcrumbs.clear();
+        assert!(breadcrumbs.recent().is_empty());
+        breadcrumbs.push(breadcrumb("5"));
+        assert_eq!(messages(&breadcrumbs.recent()), ["5"]);
+    }
+
+    #[test]
+    fn test_concurrent_recording() {
+        let breadcrumbs = Arc::new(Breadcrumbs::new(800));
+        let threads = (0..8)
+            .map(|thread_ix| {
+                let breadcrumbs = breadcrumbs.clone();
+                thread::Builder::new()
+                    .name(format!("thread_{thread_ix}"))
+                    .spawn(move || {
+                        for ix in 0..100 {
+                            breadcrumbs.push(breadcrumb(&ix.to_string()));
+                        }
+                    })
+                    .expect("failed to spawn thread")
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().expect("recording thread panicked");
+        }
+
+        let recent = breadcrumbs.recent();
+        assert_eq!(recent.len(), 800);
+        for thread_ix in 0..8 {
+            let thread_name = format!("thread_{thread_ix}");
+            let from_thread = recent
+                .iter()
+                .filter(|breadcrumb| breadcrumb.thread.as_ref() == Some(&thread_name))
+                .cloned()
+                .collect::<Vec<_>>();
+            let expected = (0..100).map(|ix| ix.to_string()).collect::<Vec<_>>();
+            assert_eq!(messages(&from_thread), expected);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "test_debug_panic_records"))]
+    fn test_debug_panic_records() {
+        let line = line!() + 1;
+        crate::debug_panic!("test_debug_panic_records {}", 42);
+
+        let recent = recent();
+        let breadcrumb = recent
+            .iter()
+            .find(|breadcrumb| breadcrumb.message == "test_debug_panic_records 42")
+            .expect("debug_panic should record a breadcrumb");
+        assert_eq!((breadcrumb.file, breadcrumb.line), (file!(), line));
+    }
+}
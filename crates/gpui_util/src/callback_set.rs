@@ -0,0 +1,360 @@
+//! A list of callbacks to notify about something, for registries that live outside of the
+//! entity system, such as the running kernels or the tool permission grants.
+//!
+//! Whoever registers a callback holds on to a [`CallbackHandle`], and the callback stays
+//! registered for as long as the handle does, so there's no need to route the notification
+//! through an entity just to get a `Subscription` that cleans up after itself.
+
+use crate::id_generator::IdGenerator;
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Callbacks that get called with `Args` whenever [`WeakCallbackSet::emit`] is.
+///
+/// Callbacks are called in the order they were added. The set doesn't hold its lock while
+/// calling them, so a callback may add or remove callbacks, or emit again, without
+/// deadlocking. A callback added during an emit is first called by the next one, and one removed
+/// during an emit isn't called by it anymore if it hadn't been yet.
+///
+/// Panics in a callback aren't caught: they unwind out of `emit`, skipping the callbacks after
+/// it for that call, but leave the set usable for the next one.
+pub struct WeakCallbackSet<Args> {
+    state: Arc<Mutex<State<Args>>>,
+}
+
+struct State<Args> {
+    entries: Vec<Arc<Entry<Args>>>,
+    ids: IdGenerator<u64>,
+}
+
+struct Entry<Args> {
+    id: u64,
+    /// Set as soon as the callback is removed, so that an emit that's already underway skips it.
+    removed: AtomicBool,
+    /// Returns whether the callback wants to stay registered.
+    callback: Box<dyn Fn(&Args) -> bool + Send + Sync>,
+}
+
+fn lock<Args>(state: &Mutex<State<Args>>) -> MutexGuard<'_, State<Args>> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+trait Unregister: Send + Sync {
+    fn unregister(&self, id: u64);
+}
+
+impl<Args> Unregister for Mutex<State<Args>> {
+    fn unregister(&self, id: u64) {
+        let mut state = lock(self);
+        if let Some(index) = state.entries.iter().position(|entry| entry.id == id) {
+            let entry = state.entries.remove(index);
+            entry.removed.store(true, Ordering::Release);
+        }
+    }
+}
+
+impl<Args: 'static> WeakCallbackSet<Args> {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                entries: Vec::new(),
+                ids: IdGenerator::new(),
+            })),
+        }
+    }
+
+    /// Adds `callback`, which stays registered until the returned handle is dropped.
+    pub fn add(&self, callback: impl Fn(&Args) + Send + Sync + 'static) -> CallbackHandle {
+        let id = self.insert(Box::new(move |args| {
+            callback(args);
+            true
+        }));
+        let state: Weak<Mutex<State<Args>>> = Arc::downgrade(&self.state);
+        CallbackHandle {
+            state: Some(state),
+            id,
+        }
+    }
+
+    /// Adds `callback`, which stays registered until it returns `false`, such as once the
+    /// weak handle it notifies can't be upgraded anymore.
+    pub fn retain_while(&self, callback: impl Fn(&Args) -> bool + Send + Sync + 'static) {
+        self.insert(Box::new(callback));
+    }
+
+    fn insert(&self, callback: Box<dyn Fn(&Args) -> bool + Send + Sync>) -> u64 {
+        let mut state = lock(&self.state);
+        let id = state.ids.next_id();
+        state.entries.push(Arc::new(Entry {
+            id,
+            removed: AtomicBool::new(false),
+            callback,
+        }));
+        id
+    }
+
+    /// Calls every registered callback with `args`, in the order they were added, and removes
+    /// the [`retain_while`](Self::retain_while) callbacks that ask for it.
+    pub fn emit(&self, args: Args) {
+        let entries = lock(&self.state).entries.clone();
+        for entry in entries {
+            if entry.removed.load(Ordering::Acquire) {
+                continue;
+            }
+            if !(entry.callback)(&args) {
+                self.state.unregister(entry.id);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        lock(&self.state).entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<Args: 'static> Default for WeakCallbackSet<Args> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Args> fmt::Debug for WeakCallbackSet<Args> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakCallbackSet")
+            .field("len", &lock(&self.state).entries.len())
+            .finish()
+    }
+}
+
+/// Keeps a callback in a [`WeakCallbackSet`] registered. Dropping it removes the callback.
+#[must_use = "dropping the handle removes the callback right away"]
+pub struct CallbackHandle {
+    state: Option<Weak<dyn Unregister>>,
+    id: u64,
+}
+
+impl CallbackHandle {
+    /// Keeps the callback registered for as long as the set exists.
+    pub fn detach(mut self) {
+        self.state.take();
+    }
+}
+
+impl Drop for CallbackHandle {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take().and_then(|state| state.upgrade()) {
+            state.unregister(self.id);
+        }
+    }
+}
+
+impl fmt::Debug for CallbackHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackHandle")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    fn recorder() -> (Arc<Mutex<Vec<String>>>, impl Fn(&str) + Clone) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = {
+            let calls = calls.clone();
+            move |call: &str| calls.lock().unwrap().push(call.to_string())
+        };
+        (calls, record)
+    }
+
+    fn take(calls: &Mutex<Vec<String>>) -> Vec<String> {
+        std::mem::take(&mut *calls.lock().unwrap())
+    }
+
+    #[test]
+    fn test_callbacks_run_in_registration_order() {
+        let set = WeakCallbackSet::<u32>::new();
+        let (calls, record) = recorder();
+        let handles = ["a", "b", "c"].map(|name| {
+            let record = record.clone();
+            set.add(move |n| record(&format!("{name}{n}")))
+        });
+
+        set.emit(1);
+        set.emit(2);
+        assert_eq!(take(&calls), ["a1", "b1", "c1", "a2", "b2", "c2"]);
+
+        // Removing one keeps the others in order, and new ones go last.
+        let [a, b, c] = handles;
+        drop(b);
+        let d = set.add({
+            let record = record.clone();
+            move |n| record(&format!("d{n}"))
+        });
+        set.emit(3);
+        assert_eq!(take(&calls), ["a3", "c3", "d3"]);
+        drop((a, c, d));
+    }
+
+    #[test]
+    fn test_dropping_the_handle_unregisters() {
+        let set = WeakCallbackSet::<()>::new();
+        let (calls, record) = recorder();
+        let handle = set.add({
+            let record = record.clone();
+            move |_| record("kept")
+        });
+        drop(set.add(move |_| record("dropped")));
+        assert_eq!(set.len(), 1);
+
+        set.emit(());
+        assert_eq!(take(&calls), ["kept"]);
+
+        drop(handle);
+        assert!(set.is_empty());
+        set.emit(());
+        assert!(take(&calls).is_empty());
+
+        // Handles outliving the set, or detached from it, don't do anything when dropped.
+        let set = WeakCallbackSet::<()>::new();
+        let outliving = set.add(|_| {});
+        set.add(|_| {}).detach();
+        assert_eq!(set.len(), 2);
+        drop(set);
+        drop(outliving);
+    }
+
+    #[test]
+    fn test_retain_while() {
+        let set = WeakCallbackSet::<u32>::new();
+        let (calls, record) = recorder();
+        set.retain_while(move |n| {
+            record(&format!("until-two{n}"));
+            *n < 2
+        });
+        set.emit(1);
+        set.emit(2);
+        set.emit(3);
+        assert_eq!(take(&calls), ["until-two1", "until-two2"]);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_reentrant_add_and_remove_during_emit() {
+        let set = Arc::new(WeakCallbackSet::<u32>::new());
+        let (calls, record) = recorder();
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let removed = Arc::new(Mutex::new(None::<CallbackHandle>));
+
+        set.add({
+            let set = Arc::downgrade(&set);
+            let record = record.clone();
+            let added = added.clone();
+            let removed = removed.clone();
+            move |n| {
+                record(&format!("first{n}"));
+                let Some(set) = set.upgrade() else { return };
+                let record = record.clone();
+                added
+                    .lock()
+                    .unwrap()
+                    .push(set.add(move |n| record(&format!("added{n}"))));
+                drop(removed.lock().unwrap().take());
+            }
+        })
+        .detach();
+        *removed.lock().unwrap() = Some(set.add({
+            let record = record.clone();
+            move |n| record(&format!("removed{n}"))
+        }));
+
+        // The callback removed by an earlier one isn't called anymore, and the one added is
+        // first called by the next emit.
+        set.emit(1);
+        assert_eq!(take(&calls), ["first1"]);
+        set.emit(2);
+        assert_eq!(take(&calls), ["first2", "added2"]);
+        assert_eq!(set.len(), 3);
+
+        // A callback can emit again on the same set without deadlocking.
+        let set = Arc::new(WeakCallbackSet::<u32>::new());
+        set.add({
+            let weak_set = Arc::downgrade(&set);
+            move |n| {
+                record(&format!("nested{n}"));
+                if *n > 0
+                    && let Some(set) = weak_set.upgrade()
+                {
+                    set.emit(n - 1);
+                }
+            }
+        })
+        .detach();
+        set.emit(2);
+        assert_eq!(take(&calls), ["nested2", "nested1", "nested0"]);
+    }
+
+    #[test]
+    fn test_panicking_callback_leaves_the_set_usable() {
+        let set = WeakCallbackSet::<bool>::new();
+        let (calls, record) = recorder();
+        let handles = [
+            set.add({
+                let record = record.clone();
+                move |_| record("before")
+            }),
+            set.add(|should_panic| {
+                if *should_panic {
+                    panic!("callback panicked");
+                }
+            }),
+            set.add(move |_| record("after")),
+        ];
+
+        // The panic reaches the caller and skips the callbacks after it...
+        let result = catch_unwind(AssertUnwindSafe(|| set.emit(true)));
+        assert!(result.is_err());
+        assert_eq!(take(&calls), ["before"]);
+
+        // ...but doesn't poison the set for the next emit.
+        set.emit(false);
+        assert_eq!(take(&calls), ["before", "after"]);
+        assert_eq!(set.len(), 3);
+        drop(handles);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_emit_from_other_threads() {
+        let set = Arc::new(WeakCallbackSet::<usize>::new());
+        let total = Arc::new(Mutex::new(0));
+        let _handle = set.add({
+            let total = total.clone();
+            move |n| *total.lock().unwrap() += n
+        });
+        let threads = (1..=4)
+            .map(|n| {
+                let set = set.clone();
+                std::thread::spawn(move || set.emit(n))
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*total.lock().unwrap(), 10);
+    }
+}
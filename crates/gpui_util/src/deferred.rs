@@ -0,0 +1,232 @@
+//! Guards that run a function when they're dropped, created with [`defer`] and [`defer_named`].
+//!
+//! In debug builds, named guards are tracked in a global table until they run or are aborted,
+//! so that [`report_outstanding`] can point at guards that were held across a long await or
+//! leaked in a reference cycle. Release builds don't track anything.
+
+use std::{panic::Location, time::Duration};
+
+pub struct Deferred<F: FnOnce()> {
+    f: Option<F>,
+    #[cfg(debug_assertions)]
+    registration: Option<registry::Registration>,
+}
+
+impl<F: FnOnce()> Deferred<F> {
+    /// Drop without running the deferred function.
+    pub fn abort(mut self) {
+        self.f.take();
+    }
+
+    /// Replaces the deferred function with `wrap(f)`, keeping the guard's name and location.
+    pub fn map<G: FnOnce()>(mut self, wrap: impl FnOnce(F) -> G) -> Deferred<G> {
+        Deferred {
+            f: self.f.take().map(wrap),
+            #[cfg(debug_assertions)]
+            registration: self.registration.take(),
+        }
+    }
+
+    /// Runs `then` after the deferred function, when the returned guard is dropped.
+    pub fn and_then(self, then: impl FnOnce()) -> Deferred<impl FnOnce()> {
+        self.map(|f| {
+            move || {
+                f();
+                then();
+            }
+        })
+    }
+}
+
+impl<F: FnOnce()> Drop for Deferred<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            f()
+        }
+    }
+}
+
+/// Run the given function when the returned value is dropped (unless it's cancelled).
+#[must_use]
+pub fn defer<F: FnOnce()>(f: F) -> Deferred<F> {
+    Deferred {
+        f: Some(f),
+        #[cfg(debug_assertions)]
+        registration: None,
+    }
+}
+
+/// Like [`defer`], but in debug builds the guard is listed by [`report_outstanding`] under `name`
+/// until it's dropped or aborted.
+#[must_use]
+#[track_caller]
+pub fn defer_named<F: FnOnce()>(name: &'static str, f: F) -> Deferred<F> {
+    #[cfg(not(debug_assertions))]
+    let _ = name;
+    Deferred {
+        f: Some(f),
+        #[cfg(debug_assertions)]
+        registration: Some(registry::Registration::new(name, Location::caller())),
+    }
+}
+
+/// A named guard that hasn't run yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutstandingDeferred {
+    pub name: &'static str,
+    /// Where [`defer_named`] was called.
+    pub location: &'static Location<'static>,
+    pub age: Duration,
+}
+
+/// The named guards that were created at least `older_than` ago and are still alive, oldest
+/// first. Always empty in release builds.
+#[cfg(debug_assertions)]
+pub fn report_outstanding(older_than: Duration) -> Vec<OutstandingDeferred> {
+    registry::outstanding(older_than)
+}
+
+#[cfg(not(debug_assertions))]
+pub fn report_outstanding(_older_than: Duration) -> Vec<OutstandingDeferred> {
+    Vec::new()
+}
+
+#[cfg(debug_assertions)]
+mod registry {
+    use super::OutstandingDeferred;
+    use std::{
+        collections::BTreeMap,
+        panic::Location,
+        sync::{
+            Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::{Duration, Instant},
+    };
+
+    struct Entry {
+        name: &'static str,
+        location: &'static Location<'static>,
+        created_at: Instant,
+    }
+
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    // Keyed by creation order, so iterating yields the oldest guards first.
+    static OUTSTANDING: Mutex<BTreeMap<u64, Entry>> = Mutex::new(BTreeMap::new());
+
+    /// Keeps a guard's entry in the table until it's dropped, whether or not the guard ran.
+    pub(super) struct Registration(u64);
+
+    impl Registration {
+        pub(super) fn new(name: &'static str, location: &'static Location<'static>) -> Self {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            OUTSTANDING
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .insert(
+                    id,
+                    Entry {
+                        name,
+                        location,
+                        created_at: Instant::now(),
+                    },
+                );
+            Self(id)
+        }
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            OUTSTANDING
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&self.0);
+        }
+    }
+
+    pub(super) fn outstanding(older_than: Duration) -> Vec<OutstandingDeferred> {
+        let now = Instant::now();
+        OUTSTANDING
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .map(|entry| OutstandingDeferred {
+                name: entry.name,
+                location: entry.location,
+                age: now.saturating_duration_since(entry.created_at),
+            })
+            .filter(|outstanding| outstanding.age >= older_than)
+            .collect()
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, thread};
+
+    // Other tests may create named guards concurrently, so each test only looks at its own names.
+    fn outstanding_named(name: &str, older_than: Duration) -> Vec<OutstandingDeferred> {
+        report_outstanding(older_than)
+            .into_iter()
+            .filter(|outstanding| outstanding.name == name)
+            .collect()
+    }
+
+    #[test]
+    fn test_named_guard_is_registered_until_dropped() {
+        let name = "test_named_guard_is_registered_until_dropped";
+        let ran = RefCell::new(false);
+        let line = line!() + 1;
+        let guard = defer_named(name, || *ran.borrow_mut() = true);
+
+        let outstanding = outstanding_named(name, Duration::ZERO);
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(outstanding[0].location.file(), file!());
+        assert_eq!(outstanding[0].location.line(), line);
+
+        drop(guard);
+        assert!(*ran.borrow());
+        assert!(outstanding_named(name, Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_report_outstanding_filters_by_age() {
+        let old_name = "test_report_outstanding_filters_by_age::old";
+        let new_name = "test_report_outstanding_filters_by_age::new";
+        let _old = defer_named(old_name, || {});
+        thread::sleep(Duration::from_millis(50));
+        let _new = defer_named(new_name, || {});
+
+        let threshold = Duration::from_millis(25);
+        assert_eq!(outstanding_named(old_name, threshold).len(), 1);
+        assert!(outstanding_named(new_name, threshold).is_empty());
+        assert_eq!(outstanding_named(new_name, Duration::ZERO).len(), 1);
+    }
+
+    #[test]
+    fn test_abort_removes_registration() {
+        let name = "test_abort_removes_registration";
+        let ran = RefCell::new(false);
+        let guard = defer_named(name, || *ran.borrow_mut() = true);
+        assert_eq!(outstanding_named(name, Duration::ZERO).len(), 1);
+
+        guard.abort();
+        assert!(!*ran.borrow());
+        assert!(outstanding_named(name, Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_and_then_runs_in_order_and_keeps_registration() {
+        let name = "test_and_then_runs_in_order_and_keeps_registration";
+        let calls = RefCell::new(Vec::new());
+        let guard = defer_named(name, || calls.borrow_mut().push("first"))
+            .and_then(|| calls.borrow_mut().push("second"))
+            .map(|f| Box::new(f) as Box<dyn FnOnce() + '_>);
+        assert_eq!(outstanding_named(name, Duration::ZERO).len(), 1);
+
+        drop(guard);
+        assert_eq!(*calls.borrow(), ["first", "second"]);
+        assert!(outstanding_named(name, Duration::ZERO).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Reading flags and settings from environment variables.
+//!
+//! [`flag`] and [`typed`] read the variable every time they're called, so callers that only want
+//! to read it once should keep the result in a `LazyLock`. Flags that are useful to flip in a
+//! running app, such as `ZED_MEASUREMENTS`, can use [`watchable`] instead, which reads the
+//! variable once and again whenever [`refresh_all`] is called.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    panic::Location,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+static WATCHED: Mutex<BTreeMap<&'static str, WatchableFlag>> = Mutex::new(BTreeMap::new());
+
+/// Whether `value` turns a flag on. Anything other than an empty string, `0`, `false`, `no` or
+/// `off` (ignoring case and surrounding whitespace) does.
+pub fn is_truthy(value: &str) -> bool {
+    let value = value.trim();
+    !(value.is_empty()
+        || value == "0"
+        || ["false", "no", "off"]
+            .iter()
+            .any(|falsy| value.eq_ignore_ascii_case(falsy)))
+}
+
+/// Whether the environment variable `name` is set to a truthy value, as defined by
+/// [`is_truthy`].
+pub fn flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| is_truthy(&value))
+}
+
+/// Parses the environment variable `name`, returning `None` if it's unset or empty, or logging
+/// an error and returning `None` if it doesn't parse.
+#[track_caller]
+pub fn typed<T>(name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = env::var(name).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(error) => {
+            crate::log_with_caller(
+                *Location::caller(),
+                format_args!("invalid value {value:?} for {name}: {error}"),
+                log::Level::Error,
+            );
+            None
+        }
+    }
+}
+
+/// A flag read from the environment that can be re-read while the app is running.
+#[derive(Clone, Debug)]
+pub struct WatchableFlag {
+    name: &'static str,
+    value: Arc<AtomicBool>,
+    /// What the environment variable was when last read, so that refreshing only replaces values
+    /// set with [`WatchableFlag::set`] when the variable changed.
+    from_env: Arc<AtomicBool>,
+}
+
+impl WatchableFlag {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn get(&self) -> bool {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Overrides the flag until the environment variable changes.
+    pub fn set(&self, value: bool) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    fn refresh(&self) {
+        let value = flag(self.name);
+        if self.from_env.swap(value, Ordering::Relaxed) != value {
+            self.value.store(value, Ordering::Relaxed);
+            log::info!("{} is now {}", self.name, if value { "on" } else { "off" });
+        }
+    }
+}
+
+/// Returns a handle to the flag `name`, read from the environment the first time it's watched
+/// and again on each [`refresh_all`]. Every handle for the same name shares its value.
+pub fn watchable(name: &'static str) -> WatchableFlag {
+    WATCHED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(name)
+        .or_insert_with(|| {
+            let value = flag(name);
+            WatchableFlag {
+                name,
+                value: Arc::new(AtomicBool::new(value)),
+                from_env: Arc::new(AtomicBool::new(value)),
+            }
+        })
+        .clone()
+}
+
+/// Re-reads every flag created with [`watchable`] from the environment. Flags whose variable
+/// changed take its new value, even if they were overridden with [`WatchableFlag::set`].
+pub fn refresh_all() {
+    for flag in WATCHED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .values()
+    {
+        flag.refresh();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_buffer;
+
+    /// Tests that change the environment hold this, since it's shared by the whole process.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn set_var(name: &str, value: Option<&str>) {
+        // SAFETY: Tests that touch the environment are serialized by `ENV_LOCK`, and nothing
+        // else in this crate's tests reads these variables.
+        unsafe {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        for value in ["1", "true", "TRUE", "yes", "on", " 1 ", "2", "enabled"] {
+            assert!(is_truthy(value), "{value:?} should be truthy");
+        }
+        for value in ["", " ", "0", "false", "False", "no", "NO", "off", " off "] {
+            assert!(!is_truthy(value), "{value:?} should be falsy");
+        }
+    }
+
+    #[test]
+    fn test_flag() {
+        let _lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let name = "GPUI_UTIL_TEST_FLAG";
+
+        set_var(name, None);
+        assert!(!flag(name));
+        set_var(name, Some("true"));
+        assert!(flag(name));
+        set_var(name, Some("0"));
+        assert!(!flag(name));
+        set_var(name, None);
+    }
+
+    #[test]
+    fn test_typed() {
+        let _lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let name = "GPUI_UTIL_TEST_TYPED";
+
+        set_var(name, None);
+        assert_eq!(typed::<u32>(name), None);
+        set_var(name, Some(" 42 "));
+        assert_eq!(typed::<u32>(name), Some(42));
+        set_var(name, Some(""));
+        assert_eq!(typed::<u32>(name), None);
+
+        set_var(name, Some("forty-two"));
+        let line = line!() + 1;
+        assert_eq!(typed::<u32>(name), None);
+        let entry = log_buffer::query(&log_buffer::Filter {
+            level: Some(log::Level::Error),
+            target_prefix: Some("gpui_util::env_flag".to_string()),
+        })
+        .into_iter()
+        .find(|entry| entry.message.contains(name))
+        .expect("the parse error should be logged");
+        assert_eq!(
+            entry.message,
+            format!("invalid value \"forty-two\" for {name}: invalid digit found in string")
+        );
+        assert_eq!(entry.line, Some(line));
+        set_var(name, None);
+    }
+
+    #[test]
+    fn test_watchable_refresh() {
+        let _lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let name = "GPUI_UTIL_TEST_WATCHABLE";
+
+        set_var(name, Some("1"));
+        let watched = watchable(name);
+        assert!(watched.get());
+
+        // The value is frozen until the flags are refreshed.
+        set_var(name, Some("off"));
+        assert!(watched.get());
+        assert!(!flag(name));
+        refresh_all();
+        assert!(!watched.get());
+
+        // Handles for the same name share the value, and overrides last until the variable changes.
+        let other = watchable(name);
+        other.set(true);
+        assert!(watched.get());
+        refresh_all();
+        assert!(watched.get());
+        set_var(name, Some("1"));
+        refresh_all();
+        set_var(name, None);
+        refresh_all();
+        assert!(!watched.get() && !other.get());
+    }
+}
@@ -0,0 +1,245 @@
+//! Human-readable formatting of durations, byte sizes and counts.
+//!
+//! The output only depends on the value being formatted (never on the locale or on floating
+//! point rounding), so it can be shown in the UI, written to logs and compared in snapshot tests.
+//! Values are truncated rather than rounded, so that e.g. 999ms is never shown as "1.0s".
+
+use std::{borrow::Cow, time::Duration};
+
+use unicode_segmentation::UnicodeSegmentation as _;
+
+const SECONDS_PER_MINUTE: u64 = 60;
+const SECONDS_PER_HOUR: u64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
+
+const BINARY_UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const SI_UNITS: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+
+/// Formats a duration with the largest unit that keeps it readable: "850µs", "999ms", "1.0s",
+/// "59.9s", "1m 12s", "2h 5m" or "3d 4h".
+///
+/// Durations under a minute keep their sub-second part (as a whole number of ns, µs or ms, or
+/// tenths of a second), while longer ones only show their two largest units.
+pub fn human_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if duration.is_zero() {
+        "0s".to_string()
+    } else if duration < Duration::from_micros(1) {
+        format!("{}ns", duration.subsec_nanos())
+    } else if duration < Duration::from_millis(1) {
+        format!("{}µs", duration.subsec_micros())
+    } else if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.subsec_millis())
+    } else if seconds < SECONDS_PER_MINUTE {
+        format!("{seconds}.{}s", duration.subsec_millis() / 100)
+    } else if seconds < SECONDS_PER_HOUR {
+        format!(
+            "{}m {}s",
+            seconds / SECONDS_PER_MINUTE,
+            seconds % SECONDS_PER_MINUTE
+        )
+    } else if seconds < SECONDS_PER_DAY {
+        format!(
+            "{}h {}m",
+            seconds / SECONDS_PER_HOUR,
+            seconds % SECONDS_PER_HOUR / SECONDS_PER_MINUTE
+        )
+    } else {
+        format!(
+            "{}d {}h",
+            seconds / SECONDS_PER_DAY,
+            seconds % SECONDS_PER_DAY / SECONDS_PER_HOUR
+        )
+    }
+}
+
+/// Formats a byte size in binary (1024-based) units, e.g. "1023 B", "1.0 KiB" or "2.3 MiB".
+pub fn human_bytes(bytes: u64) -> String {
+    scaled_bytes(bytes, 1024, &BINARY_UNITS)
+}
+
+/// Formats a byte size in SI (1000-based) units, e.g. "999 B", "1.0 kB" or "2.3 MB".
+pub fn human_bytes_si(bytes: u64) -> String {
+    scaled_bytes(bytes, 1000, &SI_UNITS)
+}
+
+fn scaled_bytes(bytes: u64, base: u64, units: &[&str]) -> String {
+    if bytes < base {
+        return format!("{bytes} B");
+    }
+    let (bytes, base) = (u128::from(bytes), u128::from(base));
+    let mut unit_size = base;
+    let mut unit_ix = 0;
+    while unit_ix + 1 < units.len() && bytes >= unit_size * base {
+        unit_size *= base;
+        unit_ix += 1;
+    }
+    let tenths = bytes * 10 / unit_size;
+    format!("{}.{} {}", tenths / 10, tenths % 10, units[unit_ix])
+}
+
+/// Formats a count with commas between groups of thousands, e.g. "1,234,567".
+pub fn human_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (ix, digit) in digits.chars().enumerate() {
+        if ix > 0 && (digits.len() - ix).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+/// Shortens `text` to at most `max_len` grapheme clusters by replacing its middle with "…",
+/// keeping the start and the end, e.g. "/Users/me/…/src/main.rs".
+///
+/// Grapheme clusters are never split, so emoji sequences and combining characters stay intact.
+/// Text that already fits is returned as is.
+pub fn truncate_middle(text: &str, max_len: usize) -> Cow<'_, str> {
+    let mut graphemes = text.grapheme_indices(true);
+    // `nth` skips `max_len` clusters, so anything left means the text is too long.
+    if graphemes.nth(max_len).is_none() {
+        return Cow::Borrowed(text);
+    }
+    if max_len == 0 {
+        return Cow::Borrowed("");
+    }
+
+    let kept = max_len - 1;
+    let suffix_len = kept / 2;
+    let prefix_len = kept - suffix_len;
+    let prefix_end = text
+        .grapheme_indices(true)
+        .nth(prefix_len)
+        .map_or(text.len(), |(ix, _)| ix);
+    let suffix_start = if suffix_len == 0 {
+        text.len()
+    } else {
+        text.grapheme_indices(true)
+            .nth_back(suffix_len - 1)
+            .map_or(0, |(ix, _)| ix)
+    };
+
+    let (prefix, suffix) = (&text[..prefix_end], &text[suffix_start..]);
+    let mut result = String::with_capacity(prefix.len() + '…'.len_utf8() + suffix.len());
+    result.push_str(prefix);
+    result.push('…');
+    result.push_str(suffix);
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_duration() {
+        use human_duration as f;
+        assert_eq!(f(Duration::ZERO), "0s");
+        assert_eq!(f(Duration::from_nanos(1)), "1ns");
+        assert_eq!(f(Duration::from_nanos(999)), "999ns");
+        assert_eq!(f(Duration::from_nanos(1_000)), "1µs");
+        assert_eq!(f(Duration::from_nanos(999_999)), "999µs");
+        assert_eq!(f(Duration::from_millis(1)), "1ms");
+        assert_eq!(f(Duration::from_micros(999_999)), "999ms");
+        assert_eq!(f(Duration::from_millis(999)), "999ms");
+        assert_eq!(f(Duration::from_millis(1_000)), "1.0s");
+        assert_eq!(f(Duration::from_millis(1_099)), "1.0s");
+        assert_eq!(f(Duration::from_millis(3_240)), "3.2s");
+        assert_eq!(f(Duration::from_millis(59_999)), "59.9s");
+        assert_eq!(f(Duration::from_secs(60)), "1m 0s");
+        assert_eq!(f(Duration::from_millis(72_900)), "1m 12s");
+        assert_eq!(f(Duration::from_secs(3_599)), "59m 59s");
+        assert_eq!(f(Duration::from_secs(3_600)), "1h 0m");
+        assert_eq!(f(Duration::from_secs(7_320)), "2h 2m");
+        assert_eq!(f(Duration::from_secs(86_399)), "23h 59m");
+        assert_eq!(f(Duration::from_secs(86_400)), "1d 0h");
+        assert_eq!(f(Duration::from_secs(3 * 86_400 + 4 * 3_600 + 59)), "3d 4h");
+        assert_eq!(f(Duration::MAX), "213503982334601d 7h");
+    }
+
+    #[test]
+    fn test_human_bytes() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(1024), "1.0 KiB");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(1024 * 1024 - 1), "1023.9 KiB");
+        assert_eq!(human_bytes(1024 * 1024), "1.0 MiB");
+        assert_eq!(human_bytes(2_411_724), "2.2 MiB");
+        assert_eq!(human_bytes(1 << 30), "1.0 GiB");
+        assert_eq!(human_bytes(1 << 40), "1.0 TiB");
+        assert_eq!(human_bytes(1 << 50), "1.0 PiB");
+        assert_eq!(human_bytes(1 << 60), "1.0 EiB");
+        assert_eq!(human_bytes(u64::MAX), "15.9 EiB");
+    }
+
+    #[test]
+    fn test_human_bytes_si() {
+        assert_eq!(human_bytes_si(0), "0 B");
+        assert_eq!(human_bytes_si(999), "999 B");
+        assert_eq!(human_bytes_si(1000), "1.0 kB");
+        assert_eq!(human_bytes_si(1023), "1.0 kB");
+        assert_eq!(human_bytes_si(1024), "1.0 kB");
+        assert_eq!(human_bytes_si(999_999), "999.9 kB");
+        assert_eq!(human_bytes_si(1_000_000), "1.0 MB");
+        assert_eq!(human_bytes_si(2_300_000), "2.3 MB");
+        assert_eq!(human_bytes_si(1_000_000_000), "1.0 GB");
+        assert_eq!(human_bytes_si(10u64.pow(12)), "1.0 TB");
+        assert_eq!(human_bytes_si(10u64.pow(15)), "1.0 PB");
+        assert_eq!(human_bytes_si(10u64.pow(18)), "1.0 EB");
+        assert_eq!(human_bytes_si(u64::MAX), "18.4 EB");
+    }
+
+    #[test]
+    fn test_human_count() {
+        assert_eq!(human_count(0), "0");
+        assert_eq!(human_count(7), "7");
+        assert_eq!(human_count(999), "999");
+        assert_eq!(human_count(1_000), "1,000");
+        assert_eq!(human_count(12_345), "12,345");
+        assert_eq!(human_count(999_999), "999,999");
+        assert_eq!(human_count(1_000_000), "1,000,000");
+        assert_eq!(human_count(u64::MAX), "18,446,744,073,709,551,615");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert!(matches!(
+            truncate_middle("hello", 5),
+            Cow::Borrowed("hello")
+        ));
+        assert!(matches!(
+            truncate_middle("hello", 10),
+            Cow::Borrowed("hello")
+        ));
+        assert!(matches!(truncate_middle("", 0), Cow::Borrowed("")));
+        assert_eq!(truncate_middle("hello", 0), "");
+        assert_eq!(truncate_middle("hello", 1), "…");
+        assert_eq!(truncate_middle("hello", 2), "h…");
+        assert_eq!(truncate_middle("hello", 3), "h…o");
+        assert_eq!(truncate_middle("hello", 4), "he…o");
+        assert_eq!(
+            truncate_middle("/Users/me/projects/zed/src/main.rs", 20),
+            "/Users/me/…c/main.rs"
+        );
+
+        // Multi-byte characters are kept whole.
+        assert_eq!(truncate_middle("ääääää", 5), "ää…ää");
+        assert_eq!(truncate_middle("日本語のテキスト", 5), "日本…スト");
+
+        // So are grapheme clusters made of several characters.
+        let family = "👨‍👩‍👧";
+        let thumbs_up = "👍🏽";
+        let flag = "🇯🇵";
+        let text = format!("{family}{thumbs_up}{flag}{family}{thumbs_up}{flag}");
+        assert_eq!(truncate_middle(&text, 6), text.as_str());
+        assert_eq!(
+            truncate_middle(&text, 5),
+            format!("{family}{thumbs_up}…{thumbs_up}{flag}")
+        );
+        assert_eq!(truncate_middle(&text, 2), format!("{family}…"));
+        assert_eq!(truncate_middle("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}…");
+    }
+}
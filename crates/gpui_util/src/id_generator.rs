@@ -0,0 +1,232 @@
+//! Counters for handing out unique ids that never wrap around.
+//!
+//! [`post_inc`](crate::post_inc) silently wraps once a counter reaches its maximum, after which
+//! it starts handing out ids that are already in use. The generators here stop at the maximum
+//! instead, and report exhaustion through [`debug_panic!`](crate::debug_panic).
+
+use crate::post_inc_checked;
+use std::{
+    fmt::Debug,
+    num::NonZero,
+    sync::atomic::{self, Ordering},
+};
+
+/// An unsigned integer type that can be used as a monotonically increasing id.
+pub trait MonotonicId: Copy + Ord + Debug + Send + Sync + 'static {
+    const ZERO: Self;
+    const MAX: Self;
+
+    /// The `NonZero` version of this type, which lets `Option<Id>` take no extra space.
+    type NonZero: Copy + Debug + Send + Sync + 'static;
+    /// The atomic version of this type.
+    type Atomic: Debug + Send + Sync + 'static;
+
+    fn checked_increment(self) -> Option<Self>;
+    fn to_non_zero(self) -> Option<Self::NonZero>;
+    fn new_atomic(value: Self) -> Self::Atomic;
+    /// Increments `atomic` unless it would overflow, returning its previous value.
+    fn fetch_increment(atomic: &Self::Atomic) -> Option<Self>;
+}
+
+macro_rules! impl_monotonic_id {
+    ($($ty:ty => $atomic:ty),* $(,)?) => {
+        $(
+            impl MonotonicId for $ty {
+                const ZERO: Self = 0;
+                const MAX: Self = <$ty>::MAX;
+
+                type NonZero = NonZero<$ty>;
+                type Atomic = $atomic;
+
+                fn checked_increment(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn to_non_zero(self) -> Option<Self::NonZero> {
+                    NonZero::new(self)
+                }
+
+                fn new_atomic(value: Self) -> Self::Atomic {
+                    <$atomic>::new(value)
+                }
+
+                fn fetch_increment(atomic: &Self::Atomic) -> Option<Self> {
+                    atomic
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+                            value.checked_add(1)
+                        })
+                        .ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_monotonic_id! {
+    u8 => atomic::AtomicU8,
+    u16 => atomic::AtomicU16,
+    u32 => atomic::AtomicU32,
+    u64 => atomic::AtomicU64,
+    usize => atomic::AtomicUsize,
+}
+
+#[track_caller]
+fn report_exhausted<T: MonotonicId>() {
+    crate::debug_panic!("ran out of {} ids", std::any::type_name::<T>());
+}
+
+/// Hands out increasing ids, starting from zero unless specified otherwise.
+///
+/// Once every id below `T::MAX` has been handed out, `T::MAX` is returned from then on.
+#[derive(Debug)]
+pub struct IdGenerator<T> {
+    next: T,
+}
+
+impl<T: MonotonicId> Default for IdGenerator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MonotonicId> IdGenerator<T> {
+    pub const fn new() -> Self {
+        Self::starting_at(T::ZERO)
+    }
+
+    pub const fn starting_at(first: T) -> Self {
+        Self { next: first }
+    }
+
+    #[track_caller]
+    pub fn next_id(&mut self) -> T {
+        post_inc_checked(&mut self.next).unwrap_or_else(|| {
+            report_exhausted::<T>();
+            T::MAX
+        })
+    }
+
+    /// Like [`IdGenerator::next_id`], but skips zero.
+    #[track_caller]
+    pub fn next_non_zero_id(&mut self) -> T::NonZero {
+        // Only the first id can be zero, so this loops at most twice.
+        loop {
+            if let Some(id) = self.next_id().to_non_zero() {
+                return id;
+            }
+        }
+    }
+}
+
+/// An [`IdGenerator`] that can hand out ids from several threads at once.
+#[derive(Debug)]
+pub struct AtomicIdGenerator<T: MonotonicId> {
+    next: T::Atomic,
+}
+
+impl<T: MonotonicId> Default for AtomicIdGenerator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MonotonicId> AtomicIdGenerator<T> {
+    pub fn new() -> Self {
+        Self::starting_at(T::ZERO)
+    }
+
+    pub fn starting_at(first: T) -> Self {
+        Self {
+            next: T::new_atomic(first),
+        }
+    }
+
+    #[track_caller]
+    pub fn next_id(&self) -> T {
+        T::fetch_increment(&self.next).unwrap_or_else(|| {
+            report_exhausted::<T>();
+            T::MAX
+        })
+    }
+
+    /// Like [`AtomicIdGenerator::next_id`], but skips zero.
+    #[track_caller]
+    pub fn next_non_zero_id(&self) -> T::NonZero {
+        // Only the first id can be zero, so this loops at most twice.
+        loop {
+            if let Some(id) = self.next_id().to_non_zero() {
+                return id;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, sync::Arc, thread};
+
+    #[test]
+    fn test_post_inc_checked_overflow() {
+        let mut value = 254u8;
+        assert_eq!(post_inc_checked(&mut value), Some(254));
+        assert_eq!(value, 255);
+        assert_eq!(post_inc_checked(&mut value), None);
+        assert_eq!(value, 255);
+    }
+
+    #[test]
+    fn test_id_generator_stops_at_max() {
+        let mut ids = IdGenerator::<u8>::starting_at(253);
+        assert_eq!(ids.next_id(), 253);
+        assert_eq!(ids.next_id(), 254);
+        assert!(
+            std::panic::catch_unwind(move || ids.next_id()).is_err(),
+            "exhausting the generator should panic in debug builds"
+        );
+
+        let ids = AtomicIdGenerator::<u8>::starting_at(254);
+        assert_eq!(ids.next_id(), 254);
+        assert!(std::panic::catch_unwind(move || ids.next_id()).is_err());
+    }
+
+    #[test]
+    fn test_non_zero_ids() {
+        let mut ids = IdGenerator::<u32>::new();
+        assert_eq!(ids.next_non_zero_id().get(), 1);
+        assert_eq!(ids.next_non_zero_id().get(), 2);
+        assert_eq!(ids.next_id(), 3);
+
+        let ids = AtomicIdGenerator::<u64>::new();
+        assert_eq!(ids.next_non_zero_id().get(), 1);
+
+        assert_eq!(
+            size_of::<Option<<u32 as MonotonicId>::NonZero>>(),
+            size_of::<u32>()
+        );
+    }
+
+    #[test]
+    fn test_atomic_id_generator_concurrent_increments() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<IdGenerator<u64>>();
+        assert_send_sync::<AtomicIdGenerator<u64>>();
+
+        let ids = Arc::new(AtomicIdGenerator::<u64>::new());
+        let threads = (0..8)
+            .map(|_| {
+                let ids = ids.clone();
+                thread::spawn(move || (0..1000).map(|_| ids.next_id()).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+
+        let mut all_ids = HashSet::new();
+        for thread in threads {
+            for id in thread.join().expect("id thread panicked") {
+                assert!(all_ids.insert(id), "id {id} was handed out twice");
+            }
+        }
+        assert_eq!(all_ids.len(), 8000);
+        assert_eq!(ids.next_id(), 8000);
+    }
+}
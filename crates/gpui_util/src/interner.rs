@@ -0,0 +1,338 @@
+//! A process-wide pool of strings, so that strings that come up over and over, like kernel and
+//! language names, share one allocation instead of getting a new one every time.
+//!
+//! The pool is split into shards by hash, each behind its own `RwLock`, so interning a string
+//! that's already in the pool only takes a read lock on one shard. Each shard holds a bounded
+//! number of strings and evicts the least recently used ones to make room. Evicted strings stay
+//! valid for whoever holds them; they just stop being shared with later interns.
+
+use crate::arc_cow::ArcCow;
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Arc, OnceLock, PoisonError, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+const SHARD_COUNT: usize = 16;
+/// The number of strings each shard of the global pool holds, not counting static ones.
+const SHARD_CAPACITY: usize = 512;
+
+static POOL: OnceLock<Interner> = OnceLock::new();
+
+fn pool() -> &'static Interner {
+    POOL.get_or_init(|| Interner::new(SHARD_COUNT, SHARD_CAPACITY))
+}
+
+/// Returns a shared copy of `string`, which is the same allocation as the one returned for
+/// earlier interns of an equal string unless it has since been evicted.
+pub fn intern(string: &str) -> ArcCow<'static, str> {
+    pool().intern(string)
+}
+
+/// Adds a string that's known ahead of time to the pool, so that interning an equal string
+/// returns it without allocating. Static strings are never evicted.
+pub fn intern_static(string: &'static str) -> ArcCow<'static, str> {
+    pool().intern_static(string)
+}
+
+pub fn stats() -> Stats {
+    pool().stats()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Interns that found the string already in the pool.
+    pub hits: u64,
+    /// Interns that had to allocate.
+    pub misses: u64,
+    pub evictions: u64,
+    /// The number of strings in the pool, including static ones.
+    pub strings: usize,
+    /// The length of all the strings the pool allocated that are still in it.
+    pub resident_bytes: usize,
+}
+
+impl Stats {
+    /// The share of interns that didn't allocate, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+struct Interner {
+    shards: Box<[RwLock<Shard>]>,
+    shard_capacity: usize,
+}
+
+#[derive(Default)]
+struct Shard {
+    strings: HashMap<ArcCow<'static, str>, Slot>,
+    /// Ticks on every intern, so that strings can be ordered by when they were last used.
+    clock: AtomicU64,
+    /// Counted under the read lock, unlike the other stats which change under the write lock.
+    hits: AtomicU64,
+    misses: u64,
+    evictions: u64,
+    /// The number of strings that aren't static, and so count towards the capacity.
+    evictable: usize,
+    resident_bytes: usize,
+}
+
+struct Slot {
+    last_used: AtomicU64,
+    is_static: bool,
+}
+
+impl Interner {
+    fn new(shard_count: usize, shard_capacity: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1)).map(|_| RwLock::default()).collect(),
+            shard_capacity: shard_capacity.max(1),
+        }
+    }
+
+    fn shard(&self, string: &str) -> &RwLock<Shard> {
+        let mut hasher = DefaultHasher::new();
+        string.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    fn intern(&self, string: &str) -> ArcCow<'static, str> {
+        let shard = self.shard(string);
+        if let Some(interned) = shard
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(string)
+        {
+            return interned;
+        }
+
+        let mut shard = shard.write().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have interned it between releasing the read lock and taking this one.
+        if let Some(interned) = shard.get(string) {
+            return interned;
+        }
+        if shard.evictable >= self.shard_capacity {
+            shard.evict(self.shard_capacity.div_ceil(8));
+        }
+        let interned: ArcCow<'static, str> = ArcCow::Owned(Arc::from(string));
+        shard.insert(interned.clone(), false);
+        shard.misses += 1;
+        shard.evictable += 1;
+        shard.resident_bytes += string.len();
+        interned
+    }
+
+    fn intern_static(&self, string: &'static str) -> ArcCow<'static, str> {
+        let shard = self.shard(string);
+        if let Some((interned, slot)) = shard
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .strings
+            .get_key_value(string)
+            && slot.is_static
+        {
+            return interned.clone();
+        }
+
+        let mut shard = shard.write().unwrap_or_else(PoisonError::into_inner);
+        match shard.strings.get_key_value(string) {
+            Some((interned, slot)) if slot.is_static => return interned.clone(),
+            // Replace the allocated copy, which stays valid for whoever holds it.
+            Some(_) => {
+                shard.strings.remove(string);
+                shard.evictable -= 1;
+                shard.resident_bytes -= string.len();
+            }
+            None => {}
+        }
+        let interned = ArcCow::Borrowed(string);
+        shard.insert(interned.clone(), true);
+        interned
+    }
+
+    fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for shard in &self.shards {
+            let shard = shard.read().unwrap_or_else(PoisonError::into_inner);
+            stats.hits += shard.hits.load(Ordering::Relaxed);
+            stats.misses += shard.misses;
+            stats.evictions += shard.evictions;
+            stats.strings += shard.strings.len();
+            stats.resident_bytes += shard.resident_bytes;
+        }
+        stats
+    }
+}
+
+impl Shard {
+    /// Looks up `string`, marking it as used. Only needs a shared reference, so that hits can be
+    /// served under the read lock.
+    fn get(&self, string: &str) -> Option<ArcCow<'static, str>> {
+        let (interned, slot) = self.strings.get_key_value(string)?;
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        slot.last_used.fetch_max(now, Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(interned.clone())
+    }
+
+    fn insert(&mut self, interned: ArcCow<'static, str>, is_static: bool) {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        self.strings.insert(
+            interned,
+            Slot {
+                last_used: AtomicU64::new(now),
+                is_static,
+            },
+        );
+    }
+
+    /// Removes the `count` least recently used strings that aren't static. Evicting several at
+    /// once spreads the cost of finding them over that many interns.
+    fn evict(&mut self, count: usize) {
+        let mut candidates = self
+            .strings
+            .iter()
+            .filter(|(_, slot)| !slot.is_static)
+            .map(|(interned, slot)| (slot.last_used.load(Ordering::Relaxed), interned.clone()))
+            .collect::<Vec<_>>();
+        let count = count.min(candidates.len());
+        if count == 0 {
+            return;
+        }
+        if count < candidates.len() {
+            candidates.select_nth_unstable_by_key(count - 1, |(last_used, _)| *last_used);
+        }
+        for (_, interned) in candidates.drain(..count) {
+            self.strings.remove(&*interned);
+            self.evictable -= 1;
+            self.resident_bytes -= interned.len();
+            self.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn same_allocation(a: &ArcCow<'static, str>, b: &ArcCow<'static, str>) -> bool {
+        std::ptr::eq(a.as_ptr(), b.as_ptr())
+    }
+
+    #[test]
+    fn test_repeated_interns_share_an_allocation() {
+        let interner = Interner::new(4, 16);
+        let first = interner.intern(&String::from("python3"));
+        let second = interner.intern(&String::from("python3"));
+        let other = interner.intern("deno");
+        assert_eq!(first, second);
+        assert!(same_allocation(&first, &second));
+        assert!(!same_allocation(&first, &other));
+
+        let stats = interner.stats();
+        assert_eq!(
+            stats,
+            Stats {
+                hits: 1,
+                misses: 2,
+                evictions: 0,
+                strings: 2,
+                resident_bytes: "python3".len() + "deno".len(),
+            }
+        );
+        assert_eq!(stats.hit_rate(), 1. / 3.);
+
+        // The global pool works the same way.
+        let first = intern(&String::from("test_repeated_interns_share_an_allocation"));
+        let second = intern(&String::from("test_repeated_interns_share_an_allocation"));
+        assert!(same_allocation(&first, &second));
+    }
+
+    #[test]
+    fn test_least_recently_used_strings_are_evicted() {
+        let interner = Interner::new(1, 8);
+        let strings = (0..8).map(|ix| format!("string {ix}")).collect::<Vec<_>>();
+        let interned = strings
+            .iter()
+            .map(|string| interner.intern(string))
+            .collect::<Vec<_>>();
+        // Using the first string again makes the second one the least recently used.
+        interner.intern(&strings[0]);
+
+        // The shard is full, so adding a string evicts one to make room.
+        interner.intern("string 8");
+        let stats = interner.stats();
+        assert_eq!(stats.strings, 8);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(
+            stats.resident_bytes,
+            strings[2..].iter().map(String::len).sum::<usize>()
+                + "string 0".len()
+                + "string 8".len()
+        );
+
+        assert!(same_allocation(&interner.intern(&strings[0]), &interned[0]));
+        assert!(same_allocation(&interner.intern(&strings[2]), &interned[2]));
+        // An evicted string is allocated again, while the old copy stays valid.
+        let reinterned = interner.intern(&strings[1]);
+        assert!(!same_allocation(&reinterned, &interned[1]));
+        assert_eq!(&*interned[1], "string 1");
+    }
+
+    #[test]
+    fn test_static_strings() {
+        let interner = Interner::new(1, 2);
+        let allocated = interner.intern(&String::from("python"));
+        let python = interner.intern_static("python");
+        assert!(matches!(python, ArcCow::Borrowed(_)));
+        assert!(!same_allocation(&python, &allocated));
+        assert!(same_allocation(&interner.intern("python"), &python));
+        assert_eq!(interner.stats().resident_bytes, 0);
+
+        // Static strings don't count towards the capacity and are never evicted.
+        for ix in 0..10 {
+            interner.intern(&format!("string {ix}"));
+        }
+        assert!(same_allocation(&interner.intern("python"), &python));
+        assert_eq!(interner.stats().strings, 3);
+    }
+
+    #[test]
+    fn test_concurrent_interns() {
+        let interner = Interner::new(4, 64);
+        let interned = thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..100)
+                            .map(|ix| interner.intern(&format!("string {}", ix % 10)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        for strings in &interned[1..] {
+            for (a, b) in strings.iter().zip(&interned[0]) {
+                assert!(same_allocation(a, b));
+            }
+        }
+        let stats = interner.stats();
+        assert_eq!(stats.misses, 10);
+        assert_eq!(stats.hits, 390);
+    }
+}
@@ -2,16 +2,32 @@
 // pub use gpui_util::{FutureExt, Timeout, arc_cow::ArcCow};
 
 use std::{
-    env,
     ops::AddAssign,
     panic::Location,
     pin::Pin,
-    sync::OnceLock,
     task::{Context, Poll},
-    time::Instant,
+    time::Duration,
 };
 
 pub mod arc_cow;
+pub mod async_lazy;
+pub mod breadcrumbs;
+pub mod callback_set;
+pub mod deferred;
+pub mod env_flag;
+pub mod fmt;
+pub mod id_generator;
+pub mod interner;
+pub mod log_buffer;
+pub mod measurements;
+pub mod ordered_task_queue;
+pub mod state_cell;
+mod when_ext;
+
+pub use callback_set::{CallbackHandle, WeakCallbackSet};
+pub use deferred::{Deferred, defer, defer_named};
+pub use interner::{intern, intern_static};
+pub use when_ext::WhenExt;
 
 pub fn post_inc<T: From<u8> + AddAssign<T> + Copy>(value: &mut T) -> T {
     let prev = *value;
@@ -19,25 +35,55 @@ pub fn post_inc<T: From<u8> + AddAssign<T> + Copy>(value: &mut T) -> T {
     prev
 }
 
-pub fn measure<R>(label: &str, f: impl FnOnce() -> R) -> R {
-    static ZED_MEASUREMENTS: OnceLock<bool> = OnceLock::new();
-    let zed_measurements = ZED_MEASUREMENTS.get_or_init(|| {
-        env::var("ZED_MEASUREMENTS")
-            .map(|measurements| measurements == "1" || measurements == "true")
-            .unwrap_or(false)
-    });
+/// Like [`post_inc`], but returns `None` and leaves `value` unchanged instead of wrapping
+/// around on overflow.
+pub fn post_inc_checked<T: id_generator::MonotonicId>(value: &mut T) -> Option<T> {
+    let prev = *value;
+    *value = prev.checked_increment()?;
+    Some(prev)
+}
 
-    if *zed_measurements {
-        let start = Instant::now();
+/// Runs `f`, printing how long it took and adding it to the [`measurements`] for `label` when
+/// measurements are enabled.
+pub fn measure<R>(label: &str, f: impl FnOnce() -> R) -> R {
+    if measurements::enabled() {
+        let start = measurements::now();
         let result = f();
-        let elapsed = start.elapsed();
-        eprintln!("{}: {:?}", label, elapsed);
+        let elapsed = measurements::now() - start;
+        eprintln!("{}: {}", label, fmt::human_duration(elapsed));
+        measurements::record(
+            label,
+            measurements::Measurement {
+                elapsed,
+                polled: elapsed,
+                suspended: Duration::ZERO,
+                cancelled: false,
+            },
+        );
         result
     } else {
         f()
     }
 }
 
+pub trait FutureExt {
+    /// Records how long this future takes under `label` when measurements are enabled,
+    /// splitting the time between being polled and waiting to be polled again. Does nothing,
+    /// and allocates nothing, when they're disabled.
+    fn measured(self, label: &'static str) -> measurements::Measured<Self>
+    where
+        Self: Sized;
+}
+
+impl<F: Future> FutureExt for F {
+    fn measured(self, label: &'static str) -> measurements::Measured<Self>
+    where
+        Self: Sized,
+    {
+        measurements::Measured::new(self, label)
+    }
+}
+
 #[macro_export]
 macro_rules! debug_panic {
     ( $($fmt_arg:tt)* ) => {
@@ -46,6 +92,7 @@ macro_rules! debug_panic {
         } else {
             let backtrace = std::backtrace::Backtrace::capture();
             log::error!("{}\n{:?}", format_args!($($fmt_arg)*), backtrace);
+            $crate::breadcrumbs::record(format_args!($($fmt_arg)*), &backtrace);
         }
     };
 }
@@ -56,6 +103,13 @@ pub fn some_or_debug_panic<T>(option: Option<T>) -> Option<T> {
     if option.is_none() {
         panic!("Unexpected None");
     }
+    #[cfg(not(debug_assertions))]
+    if option.is_none() {
+        breadcrumbs::record(
+            format_args!("Unexpected None"),
+            &std::backtrace::Backtrace::capture(),
+        );
+    }
     option
 }
 
@@ -135,10 +189,32 @@ fn log_error_with_caller<E>(caller: core::panic::Location<'_>, error: E, level:
 where
     E: std::fmt::Debug,
 {
-    #[cfg(not(windows))]
-    let file = caller.file();
+    log_with_caller(caller, format_args!("{:?}", error), level);
+}
+
+fn log_with_caller(
+    caller: core::panic::Location<'_>,
+    message: std::fmt::Arguments,
+    level: log::Level,
+) {
+    let (module_path, file) = caller_module_path(caller.file());
+    log_buffer::log(
+        &log::Record::builder()
+            .target(module_path.as_deref().unwrap_or(""))
+            .module_path(file.as_deref())
+            .args(message)
+            .file(Some(caller.file()))
+            .line(Some(caller.line()))
+            .level(level)
+            .build(),
+    );
+}
+
+/// Derives the module path (e.g. `foo::bar::baz`) and the repository-relative file path for a
+/// source file inside the `crates` directory.
+fn caller_module_path(file: &str) -> (Option<String>, Option<String>) {
     #[cfg(windows)]
-    let file = caller.file().replace('\\', "/");
+    let file = file.replace('\\', "/");
     // In this codebase all crates reside in a `crates` directory,
     // so discard the prefix up to that segment to find the crate name
     let file = file.split_once("crates/");
@@ -152,16 +228,7 @@ where
         }
     });
     let file = file.map(|(_, file)| format!("crates/{file}"));
-    log::logger().log(
-        &log::Record::builder()
-            .target(module_path.as_deref().unwrap_or(""))
-            .module_path(file.as_deref())
-            .args(format_args!("{:?}", error))
-            .file(Some(caller.file()))
-            .line(Some(caller.line()))
-            .level(level)
-            .build(),
-    );
+    (module_path, file)
 }
 
 pub fn log_err<E: std::fmt::Debug>(error: &E) {
@@ -180,6 +247,27 @@ pub trait TryFutureExt {
     fn warn_on_err(self) -> LogErrorFuture<Self>
     where
         Self: Sized;
+
+    /// Like [`TryFutureExt::log_err`], but prefixes the logged error with what `context`
+    /// returns. `context` is only called if the future fails, so it can do the formatting.
+    fn log_err_with_context<C>(self, context: C) -> LogErrorWithContextFuture<Self, C>
+    where
+        Self: Sized,
+        C: FnOnce() -> String + Send;
+
+    /// Like [`TryFutureExt::warn_on_err`], but prefixes the logged error with what `context`
+    /// returns, calling it only if the future fails.
+    fn warn_on_err_with_context<C>(self, context: C) -> LogErrorWithContextFuture<Self, C>
+    where
+        Self: Sized,
+        C: FnOnce() -> String + Send;
+
+    /// Turns the future's error into an [`anyhow::Error`] with `context` attached, along with
+    /// where this was called from, for callers that pass the error on rather than log it.
+    fn anyhow_context(self, context: &'static str) -> AnyhowContextFuture<Self>
+    where
+        Self: Sized;
+
     fn unwrap(self) -> UnwrapFuture<Self>
     where
         Self: Sized;
@@ -215,6 +303,46 @@ where
         LogErrorFuture(self, log::Level::Warn, *location)
     }
 
+    #[track_caller]
+    fn log_err_with_context<C>(self, context: C) -> LogErrorWithContextFuture<Self, C>
+    where
+        Self: Sized,
+        C: FnOnce() -> String + Send,
+    {
+        LogErrorWithContextFuture {
+            future: self,
+            context: Some(context),
+            level: log::Level::Error,
+            location: *Location::caller(),
+        }
+    }
+
+    #[track_caller]
+    fn warn_on_err_with_context<C>(self, context: C) -> LogErrorWithContextFuture<Self, C>
+    where
+        Self: Sized,
+        C: FnOnce() -> String + Send,
+    {
+        LogErrorWithContextFuture {
+            future: self,
+            context: Some(context),
+            level: log::Level::Warn,
+            location: *Location::caller(),
+        }
+    }
+
+    #[track_caller]
+    fn anyhow_context(self, context: &'static str) -> AnyhowContextFuture<Self>
+    where
+        Self: Sized,
+    {
+        AnyhowContextFuture {
+            future: self,
+            context,
+            location: *Location::caller(),
+        }
+    }
+
     fn unwrap(self) -> UnwrapFuture<Self>
     where
         Self: Sized,
@@ -250,6 +378,76 @@ where
     }
 }
 
+#[must_use]
+pub struct LogErrorWithContextFuture<F, C> {
+    future: F,
+    /// Taken when the error is logged.
+    context: Option<C>,
+    level: log::Level,
+    location: core::panic::Location<'static>,
+}
+
+impl<F, C, T, E> Future for LogErrorWithContextFuture<F, C>
+where
+    F: Future<Output = Result<T, E>>,
+    C: FnOnce() -> String,
+    E: std::fmt::Debug,
+{
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // Only `future` is structurally pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.future) };
+        match inner.poll(cx) {
+            Poll::Ready(Ok(output)) => Poll::Ready(Some(output)),
+            Poll::Ready(Err(error)) => {
+                match this.context.take() {
+                    Some(context) => log_with_caller(
+                        this.location,
+                        format_args!("{}: {:?}", context(), error),
+                        this.level,
+                    ),
+                    None => log_error_with_caller(this.location, error, this.level),
+                }
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[must_use]
+pub struct AnyhowContextFuture<F> {
+    future: F,
+    context: &'static str,
+    location: core::panic::Location<'static>,
+}
+
+impl<F, T, E> Future for AnyhowContextFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    type Output = anyhow::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let context = self.context;
+        let location = self.location;
+        let inner = unsafe { Pin::new_unchecked(&mut self.get_unchecked_mut().future) };
+        match inner.poll(cx) {
+            Poll::Ready(result) => Poll::Ready(result.map_err(|error| {
+                let (_, file) = caller_module_path(location.file());
+                let file = file.as_deref().unwrap_or(location.file());
+                error
+                    .into()
+                    .context(format!("{context} (at {file}:{})", location.line()))
+            })),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 pub struct UnwrapFuture<F>(F);
 
 impl<F, T, E> Future for UnwrapFuture<F>
@@ -268,25 +466,128 @@ where
     }
 }
 
-pub struct Deferred<F: FnOnce()>(Option<F>);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caller_module_path() {
+        assert_eq!(
+            caller_module_path("/home/user/zed/crates/foo/src/bar/baz.rs"),
+            (
+                Some("foo::bar::baz".to_string()),
+                Some("crates/foo/src/bar/baz.rs".to_string())
+            )
+        );
+        assert_eq!(
+            caller_module_path("crates/foo/src/foo.rs").0.as_deref(),
+            Some("foo")
+        );
+        assert_eq!(caller_module_path("src/main.rs"), (None, None));
+    }
 
-impl<F: FnOnce()> Deferred<F> {
-    /// Drop without running the deferred function.
-    pub fn abort(mut self) {
-        self.0.take();
+    #[test]
+    fn test_log_err_feeds_log_buffer() {
+        let message = "test_log_err_feeds_log_buffer error";
+        Err::<(), _>(message).log_err();
+
+        let entries = log_buffer::query(&log_buffer::Filter {
+            level: Some(log::Level::Error),
+            target_prefix: Some("gpui_util".to_string()),
+        });
+        let entry = entries
+            .iter()
+            .find(|entry| entry.message.contains(message))
+            .expect("error should be recorded in the log buffer");
+        assert_eq!(entry.target, "gpui_util::lib");
+        assert_eq!(entry.file.as_deref(), Some(file!()));
     }
-}
 
-impl<F: FnOnce()> Drop for Deferred<F> {
-    fn drop(&mut self) {
-        if let Some(f) = self.0.take() {
-            f()
-        }
+    fn buffered_log_entry(level: log::Level, message: &str) -> Option<log_buffer::Entry> {
+        log_buffer::query(&log_buffer::Filter {
+            level: Some(level),
+            target_prefix: Some("gpui_util".to_string()),
+        })
+        .into_iter()
+        .find(|entry| entry.message.contains(message))
     }
-}
 
-/// Run the given function when the returned value is dropped (unless it's cancelled).
-#[must_use]
-pub fn defer<F: FnOnce()>(f: F) -> Deferred<F> {
-    Deferred(Some(f))
+    #[test]
+    fn test_log_err_with_context() {
+        let context_called = std::sync::atomic::AtomicBool::new(false);
+        let output =
+            futures::executor::block_on(async { Ok::<_, &str>(1) }.log_err_with_context(|| {
+                context_called.store(true, std::sync::atomic::Ordering::SeqCst);
+                "test_log_err_with_context success".to_string()
+            }));
+        assert_eq!(output, Some(1));
+        assert!(!context_called.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(
+            buffered_log_entry(log::Level::Error, "test_log_err_with_context success").is_none()
+        );
+
+        let room_id = 42;
+        let line = line!() + 3;
+        let output = futures::executor::block_on(
+            async { Err::<(), _>("connection lost") }
+                .log_err_with_context(move || format!("test_log_err_with_context room {room_id}")),
+        );
+        assert_eq!(output, None);
+        let entry = buffered_log_entry(log::Level::Error, "test_log_err_with_context room 42")
+            .expect("error should be recorded in the log buffer");
+        assert_eq!(
+            entry.message,
+            "test_log_err_with_context room 42: \"connection lost\""
+        );
+        assert_eq!(entry.target, "gpui_util::lib");
+        assert_eq!(entry.file.as_deref(), Some(file!()));
+        assert_eq!(entry.line, Some(line));
+
+        futures::executor::block_on(
+            async { Err::<(), _>("slow") }
+                .warn_on_err_with_context(|| "test_warn_on_err_with_context".to_string()),
+        );
+        assert!(
+            buffered_log_entry(log::Level::Warn, "test_warn_on_err_with_context: \"slow\"")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_anyhow_context() {
+        let output = futures::executor::block_on(
+            async { Ok::<_, std::io::Error>(1) }.anyhow_context("reading"),
+        );
+        assert_eq!(output.ok(), Some(1));
+
+        let line = line!() + 3;
+        let error = futures::executor::block_on(
+            async { Err::<(), _>(std::io::Error::other("disk full")) }
+                .anyhow_context("writing the image"),
+        )
+        .expect_err("the error should be passed on");
+        assert_eq!(
+            error.to_string(),
+            format!("writing the image (at crates/gpui_util/src/lib.rs:{line})")
+        );
+        assert_eq!(error.root_cause().to_string(), "disk full");
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "test_debug_assert_ok_records")
+    )]
+    fn test_debug_assert_ok_records_caller() {
+        let result = Err::<(), _>("failed");
+        let line = line!() + 1;
+        result.debug_assert_ok("test_debug_assert_ok_records").ok();
+
+        let recent = breadcrumbs::recent();
+        let breadcrumb = recent
+            .iter()
+            .find(|breadcrumb| breadcrumb.message == "test_debug_assert_ok_records - \"failed\"")
+            .expect("debug_assert_ok should record a breadcrumb");
+        assert_eq!((breadcrumb.file, breadcrumb.line), (file!(), line));
+    }
 }
@@ -0,0 +1,319 @@
+//! An in-process ring buffer of recent log records, so that logs can be shown and filtered
+//! inside the app without reading the log file.
+//!
+//! Records are written into fixed slots claimed with an atomic counter, and each slot has its own
+//! lock, so concurrent writers only contend when the buffer wraps around onto the same slot.
+
+use std::{
+    cell::Cell,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::SystemTime,
+};
+
+/// The number of records kept by the global buffer.
+pub const CAPACITY: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub timestamp: SystemTime,
+    pub level: log::Level,
+    pub target: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl Entry {
+    pub fn from_record(record: &log::Record) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            level: record.level(),
+            target: record.target().to_string(),
+            file: record.file().map(ToString::to_string),
+            line: record.line(),
+            message: record.args().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only match entries at this level or more severe.
+    pub level: Option<log::Level>,
+    /// Only match entries whose target is this module path or one of its submodules.
+    pub target_prefix: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if let Some(level) = self.level
+            && entry.level > level
+        {
+            return false;
+        }
+        if let Some(prefix) = self.target_prefix.as_deref()
+            && !is_target_in(&entry.target, prefix)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+fn is_target_in(target: &str, prefix: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::") || prefix.ends_with("::"),
+        None => false,
+    }
+}
+
+/// An entry tagged with the sequence number it was written with.
+type Slot = Option<(u64, Entry)>;
+
+pub struct LogBuffer {
+    slots: Box<[Mutex<Slot>]>,
+    next_seq: AtomicU64,
+    subscriber_count: AtomicUsize,
+    subscribers: Mutex<Vec<mpsc::Sender<Entry>>>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| Mutex::new(None)).collect(),
+            next_seq: AtomicU64::new(0),
+            subscriber_count: AtomicUsize::new(0),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn push(&self, entry: Entry) {
+        if self.subscriber_count.load(Ordering::Acquire) > 0 {
+            self.notify_subscribers(&entry);
+        }
+
+        let seq = self.next_seq.fetch_add(1, Ordering::AcqRel);
+        let index = (seq % self.slots.len() as u64) as usize;
+        let mut slot = self.slots[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // A writer that claimed a later lap of this slot may have finished first.
+        if slot.as_ref().is_none_or(|(slot_seq, _)| *slot_seq < seq) {
+            *slot = Some((seq, entry));
+        }
+    }
+
+    /// Returns the buffered entries matching `filter`, oldest first.
+    pub fn query(&self, filter: &Filter) -> Vec<Entry> {
+        let end = self.next_seq.load(Ordering::Acquire);
+        let start = end.saturating_sub(self.slots.len() as u64);
+        let mut entries = Vec::new();
+        for seq in start..end {
+            let index = (seq % self.slots.len() as u64) as usize;
+            let slot = self.slots[index]
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some((slot_seq, entry)) = slot.as_ref()
+                && *slot_seq == seq
+                && filter.matches(entry)
+            {
+                entries.push(entry.clone());
+            }
+        }
+        entries
+    }
+
+    /// Returns a channel that receives every entry pushed from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<Entry> {
+        let (tx, rx) = mpsc::channel();
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        subscribers.push(tx);
+        self.subscriber_count
+            .store(subscribers.len(), Ordering::Release);
+        rx
+    }
+
+    fn notify_subscribers(&self, entry: &Entry) {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        subscribers.retain(|subscriber| subscriber.send(entry.clone()).is_ok());
+        self.subscriber_count
+            .store(subscribers.len(), Ordering::Release);
+    }
+}
+
+pub fn global() -> &'static LogBuffer {
+    static GLOBAL: OnceLock<LogBuffer> = OnceLock::new();
+    GLOBAL.get_or_init(|| LogBuffer::new(CAPACITY))
+}
+
+pub fn push(entry: Entry) {
+    global().push(entry);
+}
+
+pub fn query(filter: &Filter) -> Vec<Entry> {
+    global().query(filter)
+}
+
+pub fn subscribe() -> mpsc::Receiver<Entry> {
+    global().subscribe()
+}
+
+thread_local! {
+    static ALREADY_BUFFERED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Pushes `record` to the buffer and then logs it through the installed logger, making sure
+/// a [`BufferedLogger`] doesn't record it a second time.
+pub(crate) fn log(record: &log::Record) {
+    push(Entry::from_record(record));
+    ALREADY_BUFFERED.with(|already_buffered| already_buffered.set(true));
+    log::logger().log(record);
+    ALREADY_BUFFERED.with(|already_buffered| already_buffered.set(false));
+}
+
+/// Wraps a logger so that every record it accepts is also pushed to the global buffer.
+pub struct BufferedLogger<L>(L);
+
+impl<L> BufferedLogger<L> {
+    pub const fn new(logger: L) -> Self {
+        Self(logger)
+    }
+}
+
+impl<L: log::Log> log::Log for BufferedLogger<L> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.0.enabled(record.metadata())
+            && !ALREADY_BUFFERED.with(|already_buffered| already_buffered.get())
+        {
+            push(Entry::from_record(record));
+        }
+        self.0.log(record);
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    fn entry(level: log::Level, target: &str, message: &str) -> Entry {
+        Entry {
+            timestamp: SystemTime::now(),
+            level,
+            target: target.to_string(),
+            file: None,
+            line: None,
+            message: message.to_string(),
+        }
+    }
+
+    fn messages(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_capacity_eviction() {
+        let buffer = LogBuffer::new(3);
+        for ix in 0..5 {
+            buffer.push(entry(log::Level::Info, "foo", &ix.to_string()));
+        }
+        assert_eq!(messages(&buffer.query(&Filter::default())), ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let buffer = Arc::new(LogBuffer::new(10_000));
+        let threads = (0..8)
+            .map(|thread_ix| {
+                let buffer = buffer.clone();
+                thread::spawn(move || {
+                    for ix in 0..1000 {
+                        buffer.push(entry(
+                            log::Level::Info,
+                            &format!("thread_{thread_ix}"),
+                            &ix.to_string(),
+                        ));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().expect("writer thread panicked");
+        }
+
+        assert_eq!(buffer.query(&Filter::default()).len(), 8000);
+        for thread_ix in 0..8 {
+            let entries = buffer.query(&Filter {
+                level: None,
+                target_prefix: Some(format!("thread_{thread_ix}")),
+            });
+            let expected = (0..1000).map(|ix| ix.to_string()).collect::<Vec<_>>();
+            assert_eq!(messages(&entries), expected);
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let buffer = LogBuffer::new(10);
+        buffer.push(entry(log::Level::Error, "editor", "a"));
+        buffer.push(entry(log::Level::Warn, "editor::display_map", "b"));
+        buffer.push(entry(log::Level::Info, "editor::display_map", "c"));
+        buffer.push(entry(log::Level::Error, "editor_extensions", "d"));
+        buffer.push(entry(log::Level::Debug, "project", "e"));
+
+        let query = |level, target_prefix: Option<&str>| {
+            buffer.query(&Filter {
+                level,
+                target_prefix: target_prefix.map(ToString::to_string),
+            })
+        };
+        assert_eq!(
+            messages(&query(Some(log::Level::Warn), None)),
+            ["a", "b", "d"]
+        );
+        assert_eq!(messages(&query(None, Some("editor"))), ["a", "b", "c"]);
+        assert_eq!(
+            messages(&query(Some(log::Level::Warn), Some("editor::display_map"))),
+            ["b"]
+        );
+        assert_eq!(messages(&query(None, Some("editor::"))), ["b", "c"]);
+        assert_eq!(
+            messages(&query(None, Some("workspace"))),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let buffer = LogBuffer::new(10);
+        buffer.push(entry(log::Level::Info, "foo", "before"));
+        let rx = buffer.subscribe();
+        buffer.push(entry(log::Level::Info, "foo", "after"));
+        assert_eq!(
+            rx.try_iter().map(|entry| entry.message).collect::<Vec<_>>(),
+            ["after"]
+        );
+
+        drop(rx);
+        buffer.push(entry(log::Level::Info, "foo", "dropped"));
+        assert_eq!(buffer.subscriber_count.load(Ordering::Acquire), 0);
+    }
+}
@@ -0,0 +1,360 @@
+//! Timing statistics for labelled sections of code, collected when `ZED_MEASUREMENTS` is set.
+//!
+//! Synchronous sections are timed with [`crate::measure`] and futures with
+//! [`crate::FutureExt::measured`]. Both feed the same per-label [`Stats`], which can be read back
+//! with [`stats`] or [`snapshot`].
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, OnceLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use crate::env_flag::{self, WatchableFlag};
+
+static ENABLED: OnceLock<WatchableFlag> = OnceLock::new();
+static REGISTRY: Mutex<BTreeMap<String, Stats>> = Mutex::new(BTreeMap::new());
+
+fn enabled_flag() -> &'static WatchableFlag {
+    ENABLED.get_or_init(|| env_flag::watchable("ZED_MEASUREMENTS"))
+}
+
+/// Whether measurements are being collected, which defaults to the `ZED_MEASUREMENTS`
+/// environment variable and follows it when [`env_flag::refresh_all`] is called.
+pub fn enabled() -> bool {
+    enabled_flag().get()
+}
+
+/// Overrides whether measurements are collected, until `ZED_MEASUREMENTS` changes.
+pub fn set_enabled(enabled: bool) {
+    enabled_flag().set(enabled);
+}
+
+/// One timed run of a labelled section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Measurement {
+    /// Wall time from the start (or, for futures, the first poll) until completion or cancellation.
+    pub elapsed: Duration,
+    /// Time spent running, which for futures is the time spent inside `poll`.
+    pub polled: Duration,
+    /// Time a future spent waiting to be polled again after returning `Poll::Pending`.
+    pub suspended: Duration,
+    /// Whether the future was dropped before it completed.
+    pub cancelled: bool,
+}
+
+/// The accumulated measurements for a label.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub completed: u64,
+    pub cancelled: u64,
+    pub total_elapsed: Duration,
+    pub max_elapsed: Duration,
+    pub total_polled: Duration,
+    pub total_suspended: Duration,
+}
+
+impl Stats {
+    fn add(&mut self, measurement: &Measurement) {
+        if measurement.cancelled {
+            self.cancelled += 1;
+        } else {
+            self.completed += 1;
+        }
+        self.total_elapsed += measurement.elapsed;
+        self.max_elapsed = self.max_elapsed.max(measurement.elapsed);
+        self.total_polled += measurement.polled;
+        self.total_suspended += measurement.suspended;
+    }
+}
+
+pub fn record(label: &str, measurement: Measurement) {
+    let mut registry = REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match registry.get_mut(label) {
+        Some(stats) => stats.add(&measurement),
+        None => {
+            let mut stats = Stats::default();
+            stats.add(&measurement);
+            registry.insert(label.to_string(), stats);
+        }
+    }
+}
+
+pub fn stats(label: &str) -> Option<Stats> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(label)
+        .copied()
+}
+
+/// The stats for every label measured so far, sorted by label.
+pub fn snapshot() -> Vec<(String, Stats)> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(label, stats)| (label.clone(), *stats))
+        .collect()
+}
+
+pub fn reset() {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clear();
+}
+
+#[cfg(not(test))]
+pub(crate) fn now() -> Instant {
+    Instant::now()
+}
+
+#[cfg(test)]
+pub(crate) fn now() -> Instant {
+    tests::now()
+}
+
+/// A future that records how long it took, returned by [`crate::FutureExt::measured`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Measured<F> {
+    future: F,
+    label: &'static str,
+    /// `None` when measurements were disabled as the future was created.
+    timing: Option<Timing>,
+}
+
+#[derive(Default)]
+struct Timing {
+    first_polled_at: Option<Instant>,
+    suspended_since: Option<Instant>,
+    polled: Duration,
+    suspended: Duration,
+    completed: bool,
+}
+
+impl Timing {
+    fn measurement(&self, now: Instant, cancelled: bool) -> Measurement {
+        let mut suspended = self.suspended;
+        if let Some(suspended_since) = self.suspended_since {
+            suspended += now - suspended_since;
+        }
+        Measurement {
+            elapsed: self
+                .first_polled_at
+                .map_or(Duration::ZERO, |first_polled_at| now - first_polled_at),
+            polled: self.polled,
+            suspended,
+            cancelled,
+        }
+    }
+}
+
+impl<F> Measured<F> {
+    pub(crate) fn new(future: F, label: &'static str) -> Self {
+        Self {
+            future,
+            label,
+            timing: enabled().then(Timing::default),
+        }
+    }
+}
+
+impl<F: Future> Future for Measured<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // The future is never moved out of `self`, including in `drop`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let Some(timing) = this.timing.as_mut() else {
+            return future.poll(cx);
+        };
+
+        let poll_started_at = now();
+        timing.first_polled_at.get_or_insert(poll_started_at);
+        if let Some(suspended_since) = timing.suspended_since.take() {
+            timing.suspended += poll_started_at - suspended_since;
+        }
+
+        let result = future.poll(cx);
+        let poll_ended_at = now();
+        timing.polled += poll_ended_at - poll_started_at;
+        match result {
+            Poll::Ready(output) => {
+                timing.completed = true;
+                record(this.label, timing.measurement(poll_ended_at, false));
+                Poll::Ready(output)
+            }
+            Poll::Pending => {
+                timing.suspended_since = Some(poll_ended_at);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<F> Drop for Measured<F> {
+    fn drop(&mut self) {
+        if let Some(timing) = self.timing.as_ref()
+            && !timing.completed
+        {
+            record(self.label, timing.measurement(now(), true));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FutureExt as _;
+    use futures::task::noop_waker_ref;
+    use std::cell::Cell;
+
+    thread_local! {
+        static TEST_NOW: Cell<Option<Instant>> = const { Cell::new(None) };
+        static NEXT_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    /// A clock that only moves when a test advances it, so that durations are exact.
+    pub(super) fn now() -> Instant {
+        TEST_NOW.with(|test_now| {
+            let now = test_now.get().unwrap_or_else(Instant::now);
+            test_now.set(Some(now));
+            now
+        })
+    }
+
+    fn advance_clock(duration: Duration) {
+        let now = now();
+        TEST_NOW.with(|test_now| test_now.set(Some(now + duration)));
+    }
+
+    /// Completes once the test clock has advanced by `duration` since it was first polled.
+    struct Timer {
+        duration: Duration,
+        deadline: Option<Instant>,
+    }
+
+    fn timer(duration: Duration) -> Timer {
+        Timer {
+            duration,
+            deadline: None,
+        }
+    }
+
+    impl Future for Timer {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<()> {
+            let now = now();
+            let duration = self.duration;
+            let deadline = *self.deadline.get_or_insert(now + duration);
+            if now >= deadline {
+                Poll::Ready(())
+            } else {
+                NEXT_DEADLINE.with(|next_deadline| next_deadline.set(Some(deadline)));
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Polls `future` to completion, jumping the clock to the next timer deadline whenever it's
+    /// suspended, or polls it `max_polls` times if that comes first.
+    fn run<F: Future>(future: F, max_polls: usize) -> Option<F::Output> {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        for _ in 0..max_polls {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return Some(output);
+            }
+            let deadline = NEXT_DEADLINE.with(|next_deadline| next_deadline.take());
+            if let Some(deadline) = deadline {
+                advance_clock(deadline - now());
+            }
+        }
+        None
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_measured_future_splits_polled_and_suspended_time() {
+        set_enabled(true);
+        let label = "test_measured_future_splits_polled_and_suspended_time";
+
+        let future = async {
+            advance_clock(ms(1));
+            timer(ms(10)).await;
+            advance_clock(ms(2));
+            timer(ms(20)).await;
+            advance_clock(ms(3));
+            "done"
+        };
+        let future = future.measured(label);
+        // Measured futures can be spawned on the background executor.
+        fn assert_send(_: &impl Send) {}
+        assert_send(&future);
+        assert_eq!(run(future, 10), Some("done"));
+
+        assert_eq!(
+            stats(label),
+            Some(Stats {
+                completed: 1,
+                cancelled: 0,
+                total_elapsed: ms(36),
+                max_elapsed: ms(36),
+                total_polled: ms(6),
+                total_suspended: ms(30),
+            })
+        );
+    }
+
+    #[test]
+    fn test_measured_future_dropped_before_completion() {
+        set_enabled(true);
+        let label = "test_measured_future_dropped_before_completion";
+
+        let future = async {
+            advance_clock(ms(1));
+            timer(ms(10)).await;
+            timer(ms(10)).await;
+        };
+        // The first timer fires, and the future is dropped while waiting on the second one.
+        assert_eq!(run(future.measured(label), 2), None);
+
+        assert_eq!(
+            stats(label),
+            Some(Stats {
+                completed: 0,
+                cancelled: 1,
+                total_elapsed: ms(21),
+                max_elapsed: ms(21),
+                total_polled: ms(1),
+                total_suspended: ms(20),
+            })
+        );
+    }
+
+    #[test]
+    fn test_measure_feeds_stats() {
+        set_enabled(true);
+        let label = "test_measure_feeds_stats";
+
+        for millis in [5, 7] {
+            crate::measure(label, || advance_clock(ms(millis)));
+        }
+        let stats = stats(label).unwrap();
+        assert_eq!(stats.completed, 2);
+        assert_eq!(stats.total_elapsed, ms(12));
+        assert_eq!(stats.max_elapsed, ms(7));
+        assert_eq!(stats.total_suspended, Duration::ZERO);
+    }
+}
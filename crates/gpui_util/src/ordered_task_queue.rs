@@ -0,0 +1,331 @@
+//! Runs async jobs one at a time per key, in the order they were pushed, while jobs for different
+//! keys run concurrently. Useful wherever operations on the same resource mustn't overlap, like
+//! saves of the same buffer or requests to the same kernel.
+//!
+//! Each key with jobs waiting gets a single worker, spawned with the queue's [`Spawn`], which runs
+//! them back to back and exits once there are none left. The queue's lock is never held while a
+//! job runs, or while one is dropped, so jobs can push more jobs, including for their own key.
+
+use futures::{FutureExt as _, channel::oneshot, future::BoxFuture};
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    hash::Hash,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+
+/// Runs the workers of an [`OrderedTaskQueue`], which must keep running even though nothing waits
+/// on them. Implemented for closures, and by gpui for its executors.
+pub trait Spawn {
+    fn spawn_detached(&self, future: BoxFuture<'static, ()>);
+}
+
+impl<F: Fn(BoxFuture<'static, ()>)> Spawn for F {
+    fn spawn_detached(&self, future: BoxFuture<'static, ()>) {
+        self(future)
+    }
+}
+
+pub struct OrderedTaskQueue<K, S> {
+    state: Arc<Mutex<HashMap<K, KeyQueue>>>,
+    spawn: S,
+}
+
+/// A pushed future, which resolves to a function that hands its output to its [`QueuedTask`].
+/// The output is handed over only once the queue has been updated, so that a job is never seen
+/// as running after its result has arrived.
+type Job = BoxFuture<'static, Box<dyn FnOnce() + Send>>;
+
+/// The jobs for a key. It's in the queue's map for as long as the key's worker is alive.
+#[derive(Default)]
+struct KeyQueue {
+    pending: VecDeque<Job>,
+    running: bool,
+}
+
+/// The result of a job pushed onto an [`OrderedTaskQueue`], or `None` if the job was dropped
+/// before it ran. Dropping this doesn't cancel the job.
+pub struct QueuedTask<R>(oneshot::Receiver<R>);
+
+impl<R> Future for QueuedTask<R> {
+    type Output = Option<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_unpin(cx).map(Result::ok)
+    }
+}
+
+impl<K, S> OrderedTaskQueue<K, S>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    S: Spawn,
+{
+    pub fn new(spawn: S) -> Self {
+        Self {
+            state: Arc::default(),
+            spawn,
+        }
+    }
+
+    /// Queues `future` to run after every job already pushed for `key` has finished.
+    pub fn push<F, R>(&self, key: K, future: F) -> QueuedTask<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = async move {
+            let output = future.await;
+            Box::new(move || {
+                tx.send(output).ok();
+            }) as Box<dyn FnOnce() + Send>
+        }
+        .boxed();
+
+        let mut state = lock(&self.state);
+        let start_worker = !state.contains_key(&key);
+        state.entry(key.clone()).or_default().pending.push_back(job);
+        drop(state);
+
+        if start_worker {
+            self.spawn
+                .spawn_detached(run_jobs(self.state.clone(), key).boxed());
+        }
+        QueuedTask(rx)
+    }
+
+    /// Drops the jobs for `key` that haven't started yet, returning how many there were. A job
+    /// that's already running isn't affected.
+    pub fn cancel_pending(&self, key: &K) -> usize {
+        let pending = lock(&self.state)
+            .get_mut(key)
+            .map(|queue| mem::take(&mut queue.pending))
+            .unwrap_or_default();
+        pending.len()
+    }
+
+    /// The number of jobs for `key` that haven't finished, including the one running.
+    pub fn len(&self, key: &K) -> usize {
+        lock(&self.state)
+            .get(key)
+            .map_or(0, |queue| queue.pending.len() + usize::from(queue.running))
+    }
+
+    pub fn is_empty(&self, key: &K) -> bool {
+        self.len(key) == 0
+    }
+}
+
+impl<K, S> Drop for OrderedTaskQueue<K, S> {
+    /// Drops every job that hasn't started. Running jobs are left to finish.
+    fn drop(&mut self) {
+        let pending = lock(&self.state)
+            .values_mut()
+            .flat_map(|queue| mem::take(&mut queue.pending))
+            .collect::<Vec<_>>();
+        drop(pending);
+    }
+}
+
+async fn run_jobs<K: Eq + Hash>(state: Arc<Mutex<HashMap<K, KeyQueue>>>, key: K) {
+    let mut finished_job: Option<Box<dyn FnOnce() + Send>> = None;
+    loop {
+        let job = {
+            let mut state = lock(&state);
+            let job = state.get_mut(&key).and_then(|queue| {
+                let job = queue.pending.pop_front();
+                queue.running = job.is_some();
+                job
+            });
+            if job.is_none() {
+                state.remove(&key);
+            }
+            job
+        };
+        if let Some(deliver_output) = finished_job.take() {
+            deliver_output();
+        }
+        let Some(job) = job else {
+            return;
+        };
+        finished_job = Some(job.await);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::Shared};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    /// Runs each worker on a thread of its own, so that jobs for different keys really do run at
+    /// the same time.
+    fn thread_spawner(future: BoxFuture<'static, ()>) {
+        thread::spawn(move || block_on(future));
+    }
+
+    type Queue<K> = OrderedTaskQueue<K, fn(BoxFuture<'static, ()>)>;
+
+    fn queue<K: Clone + Eq + Hash + Send + 'static>() -> Queue<K> {
+        OrderedTaskQueue::new(thread_spawner as fn(BoxFuture<'static, ()>))
+    }
+
+    /// A job that doesn't finish until it's released.
+    fn gate() -> (oneshot::Sender<()>, Shared<oneshot::Receiver<()>>) {
+        let (tx, rx) = oneshot::channel();
+        (tx, rx.shared())
+    }
+
+    #[test]
+    fn test_ordering_within_key() {
+        let queue = Arc::new(queue::<&str>());
+        let pushed = Arc::new(Mutex::new(Vec::new()));
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicUsize::new(0));
+
+        let threads = (0..8)
+            .map(|thread_ix| {
+                let queue = queue.clone();
+                let pushed = pushed.clone();
+                let finished = finished.clone();
+                let running = running.clone();
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|ix| {
+                            let id = thread_ix * 100 + ix;
+                            let finished = finished.clone();
+                            let running = running.clone();
+                            // Pushes are recorded under the lock, so the recorded order is the
+                            // order the queue saw them in.
+                            let mut pushed_ids = lock(&pushed);
+                            pushed_ids.push(id);
+                            queue.push("buffer", async move {
+                                assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                                thread::yield_now();
+                                lock(&finished).push(id);
+                                running.fetch_sub(1, Ordering::SeqCst);
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            for task in thread.join().unwrap() {
+                assert_eq!(block_on(task), Some(()));
+            }
+        }
+        assert_eq!(*lock(&finished), *lock(&pushed));
+        assert_eq!(lock(&finished).len(), 800);
+        assert!(queue.is_empty(&"buffer"));
+    }
+
+    #[test]
+    fn test_keys_run_concurrently() {
+        let queue = queue::<u32>();
+        let (release_first, released) = gate();
+
+        // The first key's job only finishes once the second key's job has run, which would
+        // never happen if they shared a worker.
+        let first = queue.push(1, async move {
+            released.await.ok();
+            "first"
+        });
+        let second = queue.push(2, async move {
+            release_first.send(()).ok();
+            "second"
+        });
+
+        assert_eq!(block_on(second), Some("second"));
+        assert_eq!(block_on(first), Some("first"));
+    }
+
+    #[test]
+    fn test_cancel_pending() {
+        let queue = queue::<u32>();
+        let (release, released) = gate();
+        let (started_tx, started_rx) = oneshot::channel();
+
+        let running = queue.push(1, async move {
+            started_tx.send(()).ok();
+            released.await.ok();
+            1
+        });
+        let pending = [queue.push(1, async { 2 }), queue.push(1, async { 3 })];
+        let other_key = queue.push(2, async { 4 });
+
+        block_on(started_rx).unwrap();
+        assert_eq!(queue.len(&1), 3);
+        assert_eq!(queue.cancel_pending(&1), 2);
+        assert_eq!(queue.len(&1), 1);
+
+        release.send(()).unwrap();
+        assert_eq!(block_on(running), Some(1));
+        for task in pending {
+            assert_eq!(block_on(task), None);
+        }
+        assert_eq!(block_on(other_key), Some(4));
+
+        // The key can be used again once it has been drained.
+        assert_eq!(block_on(queue.push(1, async { 5 })), Some(5));
+    }
+
+    #[test]
+    fn test_job_pushes_to_own_key() {
+        let queue = Arc::new(queue::<u32>());
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (second_done_tx, second_done_rx) = oneshot::channel();
+
+        let first = queue.push(1, {
+            let queue = queue.clone();
+            let order = order.clone();
+            async move {
+                let second = queue.push(1, {
+                    let order = order.clone();
+                    async move { lock(&order).push("second") }
+                });
+                lock(&order).push("first");
+                second_done_tx.send(second).ok();
+            }
+        });
+
+        assert_eq!(block_on(first), Some(()));
+        let second = block_on(second_done_rx).unwrap();
+        assert_eq!(block_on(second), Some(()));
+        assert_eq!(*lock(&order), ["first", "second"]);
+    }
+
+    #[test]
+    fn test_drop_lets_running_job_finish() {
+        let queue = queue::<u32>();
+        let (release, released) = gate();
+        let (started_tx, started_rx) = oneshot::channel();
+
+        let running = queue.push(1, async move {
+            started_tx.send(()).ok();
+            released.await.ok();
+            1
+        });
+        let pending = queue.push(1, async { 2 });
+
+        block_on(started_rx).unwrap();
+        drop(queue);
+        assert_eq!(block_on(pending), None);
+
+        release.send(()).unwrap();
+        assert_eq!(block_on(running), Some(1));
+    }
+}
@@ -0,0 +1,409 @@
+//! A shared slot for a value that one party puts in and another takes out, such as an incoming
+//! call waiting to be accepted or declined.
+//!
+//! A watch channel can model this too, but taking the value out means borrowing the sender and
+//! replacing its contents, which every owner ends up reimplementing. [`StateCell`] does it in one
+//! place: [`StateCell::take`] hands the value to exactly one caller, no matter how many race for
+//! it, and every change wakes the tasks waiting in [`StateCell::changed`] or polling a
+//! [`Subscription`].
+
+use crate::id_generator::IdGenerator;
+use futures::Stream;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+/// A handle to a shared optional value. Cloning the handle shares the same value.
+///
+/// Subscriptions end once every handle has been dropped.
+pub struct StateCell<T> {
+    shared: Arc<Mutex<State<T>>>,
+}
+
+struct State<T> {
+    value: Option<T>,
+    /// Incremented every time the value is set or taken.
+    version: u64,
+    handle_count: usize,
+    listeners: Vec<(u64, Waker)>,
+    listener_ids: IdGenerator<u64>,
+}
+
+impl<T> State<T> {
+    fn is_closed(&self) -> bool {
+        self.handle_count == 0
+    }
+
+    fn register(&mut self, listener_id: u64, waker: &Waker) {
+        if let Some((_, registered)) = self.listeners.iter_mut().find(|(id, _)| *id == listener_id)
+        {
+            registered.clone_from(waker);
+        } else {
+            self.listeners.push((listener_id, waker.clone()));
+        }
+    }
+
+    fn unregister(&mut self, listener_id: u64) {
+        self.listeners.retain(|(id, _)| *id != listener_id);
+    }
+}
+
+fn lock<T>(shared: &Mutex<State<T>>) -> MutexGuard<'_, State<T>> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Records a change to the value and wakes every listener.
+fn notify<T>(mut state: MutexGuard<'_, State<T>>) {
+    state.version = state.version.wrapping_add(1);
+    wake_listeners(state);
+}
+
+/// Called with the lock held, which it releases before waking so that listeners polled on other
+/// threads don't contend with it.
+fn wake_listeners<T>(mut state: MutexGuard<'_, State<T>>) {
+    let listeners = std::mem::take(&mut state.listeners);
+    drop(state);
+    for (_, waker) in listeners {
+        waker.wake();
+    }
+}
+
+impl<T> StateCell<T> {
+    pub fn new() -> Self {
+        Self::with_value(None)
+    }
+
+    pub fn with_value(value: Option<T>) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(State {
+                value,
+                version: 0,
+                handle_count: 1,
+                listeners: Vec::new(),
+                listener_ids: IdGenerator::new(),
+            })),
+        }
+    }
+
+    /// Replaces the value, returning the previous one.
+    pub fn set(&self, value: T) -> Option<T> {
+        let mut state = lock(&self.shared);
+        let previous = state.value.replace(value);
+        notify(state);
+        previous
+    }
+
+    /// Takes the value out of the cell. When several callers race, exactly one of them gets it.
+    pub fn take(&self) -> Option<T> {
+        self.take_if(|_| true)
+    }
+
+    /// Takes the value out of the cell if it matches `predicate`.
+    ///
+    /// The check and the take happen atomically, so the value can't be replaced in between.
+    pub fn take_if(&self, predicate: impl FnOnce(&T) -> bool) -> Option<T> {
+        let mut state = lock(&self.shared);
+        let value = state.value.take_if(|value| predicate(value))?;
+        notify(state);
+        Some(value)
+    }
+
+    pub fn get_cloned(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        lock(&self.shared).value.clone()
+    }
+
+    pub fn is_some(&self) -> bool {
+        lock(&self.shared).value.is_some()
+    }
+
+    /// Resolves on the next change after this call, whether the value is set or taken.
+    pub fn changed(&self) -> Changed<'_, T> {
+        let mut state = lock(&self.shared);
+        Changed {
+            cell: self,
+            version: state.version,
+            listener_id: state.listener_ids.next_id(),
+        }
+    }
+
+    /// Returns a stream that yields the current value, and then the latest value after each
+    /// change.
+    ///
+    /// Changes that happen between two polls are coalesced, so a value that's set and taken
+    /// again before the subscriber runs may never be observed.
+    pub fn subscribe(&self) -> Subscription<T> {
+        let listener_id = lock(&self.shared).listener_ids.next_id();
+        Subscription {
+            shared: self.shared.clone(),
+            version: None,
+            listener_id,
+        }
+    }
+}
+
+impl<T> Default for StateCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for StateCell<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).handle_count += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for StateCell<T> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared);
+        state.handle_count -= 1;
+        if state.is_closed() {
+            wake_listeners(state);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for StateCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StateCell")
+            .field(&lock(&self.shared).value)
+            .finish()
+    }
+}
+
+/// The future returned by [`StateCell::changed`].
+pub struct Changed<'a, T> {
+    cell: &'a StateCell<T>,
+    version: u64,
+    listener_id: u64,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = lock(&self.cell.shared);
+        if state.version != self.version {
+            return Poll::Ready(());
+        }
+        state.register(self.listener_id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Changed<'_, T> {
+    fn drop(&mut self) {
+        lock(&self.cell.shared).unregister(self.listener_id);
+    }
+}
+
+/// A stream of the values of a [`StateCell`], returned by [`StateCell::subscribe`].
+pub struct Subscription<T> {
+    shared: Arc<Mutex<State<T>>>,
+    /// The version last yielded, or `None` before the first poll.
+    version: Option<u64>,
+    listener_id: u64,
+}
+
+impl<T: Clone> Subscription<T> {
+    /// The cell's current value, without marking it as seen.
+    pub fn get_cloned(&self) -> Option<T> {
+        lock(&self.shared).value.clone()
+    }
+}
+
+impl<T: Clone> Stream for Subscription<T> {
+    type Item = Option<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut state = lock(&this.shared);
+        if this.version != Some(state.version) {
+            this.version = Some(state.version);
+            return Poll::Ready(Some(state.value.clone()));
+        }
+        if state.is_closed() {
+            return Poll::Ready(None);
+        }
+        state.register(this.listener_id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl<T> Clone for Subscription<T> {
+    /// The clone starts out like a new subscription, yielding the current value first.
+    fn clone(&self) -> Self {
+        let listener_id = lock(&self.shared).listener_ids.next_id();
+        Self {
+            shared: self.shared.clone(),
+            version: None,
+            listener_id,
+        }
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        lock(&self.shared).unregister(self.listener_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{StreamExt as _, executor::block_on};
+    use std::{
+        sync::{Barrier, atomic::AtomicUsize, atomic::Ordering},
+        thread,
+    };
+
+    #[test]
+    fn test_set_and_take() {
+        let cell = StateCell::new();
+        assert_eq!(cell.take(), None);
+        assert_eq!(cell.set(1), None);
+        assert_eq!(cell.set(2), Some(1));
+        assert_eq!(cell.get_cloned(), Some(2));
+        assert_eq!(cell.take_if(|value| *value == 1), None);
+        assert_eq!(cell.take_if(|value| *value == 2), Some(2));
+        assert_eq!(cell.take(), None);
+        assert!(!cell.is_some());
+    }
+
+    #[test]
+    fn test_subscription() {
+        let cell = StateCell::with_value(Some(1));
+        let mut subscription = cell.subscribe();
+        assert_eq!(block_on(subscription.next()), Some(Some(1)));
+
+        // Changes between polls are coalesced into the latest value.
+        cell.set(2);
+        cell.set(3);
+        assert_eq!(block_on(subscription.next()), Some(Some(3)));
+        cell.take();
+        assert_eq!(block_on(subscription.next()), Some(None));
+
+        // The stream ends once every handle is gone, after yielding the last change.
+        let handle = cell.clone();
+        drop(cell);
+        handle.set(4);
+        drop(handle);
+        assert_eq!(block_on(subscription.next()), Some(Some(4)));
+        assert_eq!(block_on(subscription.next()), None);
+    }
+
+    #[test]
+    fn test_changed_wakes_waiting_consumer() {
+        let cell = StateCell::new();
+        let barrier = Arc::new(Barrier::new(2));
+        let consumer = thread::spawn({
+            let cell = cell.clone();
+            let barrier = barrier.clone();
+            move || {
+                let changed = cell.changed();
+                barrier.wait();
+                // A change made before the consumer starts waiting isn't missed.
+                thread::sleep(std::time::Duration::from_millis(10));
+                block_on(changed);
+                cell.take()
+            }
+        });
+        barrier.wait();
+        cell.set("call");
+        assert_eq!(consumer.join().expect("consumer panicked"), Some("call"));
+        assert!(!cell.is_some());
+    }
+
+    #[test]
+    fn test_concurrent_take_has_one_winner() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 1000;
+
+        let cell = StateCell::new();
+        let winners = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(THREADS + 1));
+        let threads = (0..THREADS)
+            .map(|_| {
+                let cell = cell.clone();
+                let winners = winners.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        barrier.wait();
+                        if cell.take().is_some() {
+                            winners.fetch_add(1, Ordering::Relaxed);
+                        }
+                        barrier.wait();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for round in 0..ROUNDS {
+            cell.set(round);
+            barrier.wait();
+            barrier.wait();
+            assert_eq!(winners.swap(0, Ordering::Relaxed), 1, "round {round}");
+        }
+        for thread in threads {
+            thread.join().expect("taking thread panicked");
+        }
+    }
+
+    #[test]
+    fn test_subscribers_observe_concurrent_changes() {
+        const WRITES: usize = 10_000;
+
+        let cell = StateCell::new();
+        let subscribers = (0..4)
+            .map(|_| {
+                let mut subscription = cell.subscribe();
+                thread::spawn(move || {
+                    let mut last_seen = None;
+                    while let Some(value) = block_on(subscription.next()) {
+                        if let Some(value) = value {
+                            assert!(last_seen < Some(value), "values went backwards");
+                            last_seen = Some(value);
+                        }
+                    }
+                    last_seen
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let writer = thread::spawn({
+            let cell = cell.clone();
+            move || {
+                for value in 0..WRITES {
+                    cell.set(value);
+                    if value % 3 == 0 {
+                        cell.take();
+                    }
+                }
+            }
+        });
+        writer.join().expect("writer panicked");
+        cell.set(WRITES);
+        drop(cell);
+
+        // Every subscriber sees the final value before its stream ends.
+        for subscriber in subscribers {
+            assert_eq!(
+                subscriber.join().expect("subscriber panicked"),
+                Some(WRITES)
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+/// Conditional steps for method chains, implemented for every type.
+///
+/// This brings the combinators of gpui's `FluentBuilder` to builders that aren't elements, so
+/// that an `Option` or a flag can be applied without breaking the chain:
+///
+/// ```
+/// use gpui_util::WhenExt as _;
+/// use std::process::Command;
+///
+/// let connection_file = Some("kernel-1.json");
+/// let mut command = Command::new("python");
+/// command
+///     .args(["-m", "ipykernel_launcher"])
+///     .when_some(connection_file, |command, file| command.args(["-f", file]))
+///     .when(cfg!(windows), |command| command.arg("--no-stdin"));
+/// ```
+///
+/// Element types already have the same methods through `FluentBuilder`, which is implemented in
+/// terms of this trait, and gpui's prelude doesn't export `WhenExt`. Keep it that way: with both
+/// traits in scope, calling one of these methods on an element is ambiguous.
+pub trait WhenExt: Sized {
+    /// Modifies self with `then` if `condition` is true.
+    fn when(self, condition: bool, then: impl FnOnce(Self) -> Self) -> Self {
+        if condition { then(self) } else { self }
+    }
+
+    /// Modifies self with `then` if `condition` is true, and with `otherwise` if it isn't.
+    fn when_else(
+        self,
+        condition: bool,
+        then: impl FnOnce(Self) -> Self,
+        otherwise: impl FnOnce(Self) -> Self,
+    ) -> Self {
+        if condition {
+            then(self)
+        } else {
+            otherwise(self)
+        }
+    }
+
+    /// Modifies self with `then` and the option's value if the option is `Some`.
+    fn when_some<T>(self, option: Option<T>, then: impl FnOnce(Self, T) -> Self) -> Self {
+        match option {
+            Some(value) => then(self, value),
+            None => self,
+        }
+    }
+
+    /// Modifies self with `then` if the option is `None`.
+    fn when_none<T>(self, option: &Option<T>, then: impl FnOnce(Self) -> Self) -> Self {
+        if option.is_some() { self } else { then(self) }
+    }
+
+    /// Same as [`WhenExt::when`], for chains where the step is a transformation rather than an
+    /// addition, like `.map_if(uppercase, |name| name.to_uppercase())`.
+    fn map_if(self, condition: bool, f: impl FnOnce(Self) -> Self) -> Self {
+        self.when(condition, f)
+    }
+}
+
+impl<T> WhenExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct KernelSpec {
+        argv: Vec<String>,
+        display_name: String,
+    }
+
+    impl KernelSpec {
+        fn arg(mut self, arg: &str) -> Self {
+            self.argv.push(arg.to_string());
+            self
+        }
+    }
+
+    #[test]
+    fn test_conditional_steps() {
+        let spec = KernelSpec::default()
+            .arg("python")
+            .when(false, |spec| spec.arg("-v"))
+            .when_some(Some("kernel.json"), |spec, file| spec.arg("-f").arg(file))
+            .when_some(None::<&str>, |spec, name| spec.arg(name))
+            .when_none(&None::<u16>, |spec| spec.arg("--ephemeral"))
+            .when_else(
+                true,
+                |spec| KernelSpec {
+                    display_name: "Python 3".into(),
+                    ..spec
+                },
+                |spec| spec,
+            );
+        assert_eq!(spec.argv, ["python", "-f", "kernel.json", "--ephemeral"]);
+        assert_eq!(spec.display_name, "Python 3");
+    }
+
+    #[test]
+    fn test_map_if_works_on_any_type() {
+        assert_eq!(2.map_if(true, |n| n * 10), 20);
+        assert_eq!(
+            "ipykernel".to_string().map_if(false, |name| name + "!"),
+            "ipykernel"
+        );
+        // References are types too, so builders that work through `&mut` chain the same way.
+        let mut argv = vec!["jupyter"];
+        (&mut argv).when(true, |argv| {
+            argv.push("lab");
+            argv
+        });
+        assert_eq!(argv, ["jupyter", "lab"]);
+    }
+}
@@ -3,6 +3,8 @@ use gpui::{
     App, Bounds, Context, ElementId, SharedString, Task, Window, WindowBounds, WindowOptions, div,
     px, rgb, size,
 };
+use gpui_util::fmt::{human_count, human_duration};
+use std::time::Duration;
 
 // ---------------------------------------------------------------------------
 // Prime counting (intentionally brute-force so it hammers the CPU)
@@ -84,7 +86,7 @@ struct Run {
     chunks_done: u64,
     chunk_results: Vec<ChunkResult>,
     total: Option<u64>,
-    elapsed: Option<f64>,
+    elapsed: Option<Duration>,
 }
 
 struct HelloWeb {
@@ -140,15 +142,15 @@ impl HelloWeb {
 
                         if run.chunks_done == NUM_CHUNKS {
                             let total: u64 = run.chunk_results.iter().map(|r| r.count).sum();
-                            let elapsed_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+                            let elapsed = start_time.elapsed();
                             run.total = Some(total);
-                            run.elapsed = Some(elapsed_ms);
+                            run.elapsed = Some(elapsed);
                             this.history.push(
                                 format!(
-                                    "π({}) = {} ({:.0} ms, {} chunks)",
-                                    format_number(run.limit),
-                                    format_number(total),
-                                    elapsed_ms,
+                                    "π({}) = {} ({}, {} chunks)",
+                                    human_count(run.limit),
+                                    human_count(total),
+                                    human_duration(elapsed),
                                     NUM_CHUNKS,
                                 )
                                 .into(),
@@ -165,18 +167,6 @@ impl HelloWeb {
     }
 }
 
-fn format_number(n: u64) -> String {
-    let s = n.to_string();
-    let mut result = String::new();
-    for (i, ch) in s.chars().rev().enumerate() {
-        if i > 0 && i % 3 == 0 {
-            result.push(',');
-        }
-        result.push(ch);
-    }
-    result.chars().rev().collect()
-}
-
 // ---------------------------------------------------------------------------
 // Render
 // ---------------------------------------------------------------------------
@@ -201,19 +191,17 @@ impl Render for HelloWeb {
             div().flex().flex_row().gap_2(),
             |row, (index, &preset)| {
                 let is_selected = preset == self.selected_preset;
-                let (bg, text_color) = if is_selected {
-                    (ACCENT_BLUE, BG_BASE)
-                } else {
-                    (BG_OVERLAY, TEXT_SECONDARY)
-                };
                 row.child(
                     div()
                         .id(ElementId::NamedInteger("preset".into(), index as u64))
                         .px_3()
                         .py_1()
                         .rounded_md()
-                        .bg(rgb(bg))
-                        .text_color(rgb(text_color))
+                        .when_else(
+                            is_selected,
+                            |this| this.bg(rgb(ACCENT_BLUE)).text_color(rgb(BG_BASE)),
+                            |this| this.bg(rgb(BG_OVERLAY)).text_color(rgb(TEXT_SECONDARY)),
+                        )
                         .text_sm()
                         .cursor_pointer()
                         .when(!is_running, |this| {
@@ -254,16 +242,16 @@ impl Render for HelloWeb {
 
             let status_text: SharedString = if let Some(total) = run.total {
                 format!(
-                    "Found {} primes below {} in {:.0} ms",
-                    format_number(total),
-                    format_number(run.limit),
-                    run.elapsed.unwrap_or(0.0),
+                    "Found {} primes below {} in {}",
+                    human_count(total),
+                    human_count(run.limit),
+                    human_duration(run.elapsed.unwrap_or_default()),
                 )
                 .into()
             } else {
                 format!(
                     "Searching up to {} … {}/{} chunks  ({}%)",
-                    format_number(run.limit),
+                    human_count(run.limit),
                     run.chunks_done,
                     NUM_CHUNKS,
                     progress_pct,
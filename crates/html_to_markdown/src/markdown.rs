@@ -1,23 +1,40 @@
 use crate::html_element::HtmlElement;
 use crate::markdown_writer::{HandleTag, HandlerOutcome, MarkdownWriter, StartTagOutcome};
 
+/// Removes the parts of a webpage that aren't its content, like scripts, navigation, and site
+/// headers and footers.
 pub struct WebpageChromeRemover;
 
 impl HandleTag for WebpageChromeRemover {
-    fn should_handle(&self, tag: &str) -> bool {
-        matches!(tag, "head" | "script" | "style" | "nav")
+    fn should_handle(&self, _tag: &str) -> bool {
+        true
     }
 
     fn handle_tag_start(
         &mut self,
         tag: &HtmlElement,
-        _writer: &mut MarkdownWriter,
+        writer: &mut MarkdownWriter,
     ) -> StartTagOutcome {
         match tag.tag() {
-            "head" | "script" | "style" | "nav" => return StartTagOutcome::Skip,
+            "head" | "script" | "style" | "nav" | "noscript" | "template" | "iframe" | "svg"
+            | "aside" | "form" => return StartTagOutcome::Skip,
+            // Headers and footers inside an article belong to it, like its title and byline.
+            "header" | "footer" if !(writer.is_inside("article") || writer.is_inside("main")) => {
+                return StartTagOutcome::Skip;
+            }
             _ => {}
         }
 
+        let role = tag.attr("role");
+        if matches!(
+            role.as_deref(),
+            Some("navigation" | "banner" | "contentinfo" | "complementary" | "search")
+        ) || tag.attr("hidden").is_some()
+            || tag.attr("aria-hidden").as_deref() == Some("true")
+        {
+            return StartTagOutcome::Skip;
+        }
+
         StartTagOutcome::Continue
     }
 }
@@ -33,6 +33,7 @@ pub struct VideoGrant<'a> {
     pub can_publish: Option<bool>,
     pub can_subscribe: Option<bool>,
     pub can_publish_data: Option<bool>,
+    pub can_update_own_metadata: Option<bool>,
     pub hidden: Option<bool>,
     pub recorder: Option<bool>,
 }
@@ -52,6 +53,7 @@ impl<'a> VideoGrant<'a> {
             room_join: Some(true),
             can_publish: Some(true),
             can_subscribe: Some(true),
+            can_update_own_metadata: Some(true),
             ..Default::default()
         }
     }
@@ -62,6 +64,7 @@ impl<'a> VideoGrant<'a> {
             room_join: Some(true),
             can_publish: Some(false),
             can_subscribe: Some(true),
+            can_update_own_metadata: Some(true),
             ..Default::default()
         }
     }
@@ -12,6 +12,7 @@ use gpui::{
 use livekit_client::{
     AudioStream, LocalTrackPublication, Participant, ParticipantIdentity, RemoteParticipant,
     RemoteTrackPublication, RemoteVideoTrack, RemoteVideoTrackView, Room, RoomEvent,
+    VideoEncodingLimits,
 };
 
 use livekit_api::token::{self, VideoGrant};
@@ -184,7 +185,7 @@ impl LivekitWindow {
                     livekit_client::RemoteTrack::Audio(track) => {
                         output.audio_output_stream = Some((
                             publication,
-                            room.play_remote_audio_track(&track, cx).unwrap(),
+                            room.play_remote_audio_track(&track, None, cx).unwrap(),
                         ));
                     }
                     livekit_client::RemoteTrack::Video(track) => {
@@ -256,7 +257,7 @@ impl LivekitWindow {
             let room = self.room.clone();
             cx.spawn_in(window, async move |this, cx| {
                 let (publication, stream) = room
-                    .publish_local_microphone_track("test_user".to_string(), false, cx)
+                    .publish_local_microphone_track("test_user".to_string(), false, None, cx)
                     .await
                     .unwrap();
                 this.update(cx, |this, cx| {
@@ -285,13 +286,19 @@ impl LivekitWindow {
                 let sources = sources.await.unwrap()?;
                 let source = sources.into_iter().next().unwrap();
 
+                let limits = VideoEncodingLimits {
+                    max_width: 1920,
+                    max_height: 1080,
+                    max_framerate: 30.,
+                    max_bitrate: 3_000_000,
+                };
                 let (publication, stream) = participant
-                    .publish_screenshare_track(&*source, cx)
+                    .publish_screenshare_track(&*source, limits, cx)
                     .await
                     .unwrap();
                 this.update(cx, |this, cx| {
                     this.screen_share_track = Some(publication);
-                    this.screen_share_stream = Some(stream);
+                    this.screen_share_stream = Some(Box::new(stream));
                     cx.notify();
                 })
             })
@@ -89,6 +89,115 @@ impl TrackPublication {
     }
 }
 
+/// What a track is being captured from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackSource {
+    Unknown,
+    Camera,
+    Microphone,
+    Screenshare,
+    ScreenshareAudio,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CameraDevice {
+    pub id: String,
+    pub name: String,
+}
+
+/// A microphone or speaker that calls can use. A device that can do both is listed once as
+/// each.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    pub kind: AudioDeviceKind,
+    /// Whether this is the system's default device of its kind.
+    pub is_default: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AudioDeviceKind {
+    Input,
+    Output,
+}
+
+/// Caps on how a video track is sent, which keep it within the bandwidth available.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VideoEncodingLimits {
+    /// The largest size frames are sent at. Larger frames are scaled down to fit, keeping their
+    /// aspect ratio.
+    pub max_width: u32,
+    pub max_height: u32,
+    pub max_framerate: f64,
+    /// In bits per second.
+    pub max_bitrate: u64,
+}
+
+impl VideoEncodingLimits {
+    /// The size a `width` by `height` frame is sent at.
+    pub fn scaled_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if width <= self.max_width && height <= self.max_height {
+            return (width, height);
+        }
+        let scale =
+            (self.max_width as f64 / width as f64).min(self.max_height as f64 / height as f64);
+        // Encoders want even dimensions.
+        let scaled = |length: u32| ((length as f64 * scale) as u32 & !1).max(2);
+        (scaled(width), scaled(height))
+    }
+}
+
+/// How well a participant's connection is keeping up, as estimated by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConnectionQuality {
+    Lost,
+    Poor,
+    Good,
+    Excellent,
+}
+
+/// Processing the microphone's audio goes through before it's sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AudioProcessing {
+    NoiseSuppression,
+    EchoCancellation,
+    AutoGain,
+}
+
+impl AudioProcessing {
+    pub const ALL: [Self; 3] = [
+        Self::NoiseSuppression,
+        Self::EchoCancellation,
+        Self::AutoGain,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::NoiseSuppression => "Noise Suppression",
+            Self::EchoCancellation => "Echo Cancellation",
+            Self::AutoGain => "Automatic Gain",
+        }
+    }
+}
+
+/// How turning audio processing on or off took effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioProcessingChange {
+    /// The published microphone track picked up the change.
+    Applied,
+    /// Only microphone tracks published from now on pick up the change, so the current one has
+    /// to be published again.
+    RequiresNewTrack,
+}
+
+pub struct CameraStream {
+    pub device: CameraDevice,
+    /// Resolves once the device stops producing frames, e.g. because it was unplugged.
+    /// It is canceled instead if capturing stops for any other reason.
+    pub device_lost: futures::channel::oneshot::Receiver<()>,
+}
+
 #[derive(Clone, Debug)]
 pub enum RemoteTrack {
     Audio(RemoteAudioTrack),
@@ -179,6 +288,15 @@ pub enum RoomEvent {
     ActiveSpeakersChanged {
         speakers: Vec<Participant>,
     },
+    ConnectionQualityChanged {
+        participant: Participant,
+        quality: ConnectionQuality,
+    },
+    DataReceived {
+        payload: Vec<u8>,
+        topic: Option<String>,
+        participant: Option<RemoteParticipant>,
+    },
     ConnectionStateChanged(ConnectionState),
     Connected {
         participants_with_tracks: Vec<(RemoteParticipant, Vec<RemoteTrackPublication>)>,
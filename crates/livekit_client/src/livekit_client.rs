@@ -1,21 +1,29 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::{Context as _, Result, anyhow};
 use audio::AudioSettings;
 use collections::HashMap;
+use cpal::traits::{DeviceTrait as _, HostTrait as _};
 use futures::{SinkExt, channel::mpsc};
-use gpui::{App, AsyncApp, ScreenCaptureSource, ScreenCaptureStream, Task};
+use gpui::{App, AsyncApp, ScreenCaptureSource, ScreenCaptureStream, SourceMetadata, Task};
 use gpui_tokio::Tokio;
 use log::info;
+use parking_lot::Mutex;
 use playback::capture_local_video_track;
 use settings::Settings;
+use util::ResultExt as _;
 
 mod playback;
 
 use crate::{
-    LocalTrack, Participant, RemoteTrack, RoomEvent, TrackPublication,
-    livekit_client::playback::Speaker,
+    AudioDevice, AudioDeviceKind, AudioProcessing, AudioProcessingChange, CameraDevice,
+    CameraStream, ConnectionQuality, LocalTrack, Participant, RemoteTrack, RoomEvent,
+    TrackPublication, TrackSource, VideoEncodingLimits, livekit_client::playback::Speaker,
 };
+
+/// How often the audio devices are listed to notice ones being plugged in or out, since cpal
+/// can't notify us about that.
+const AUDIO_DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
 pub use playback::AudioStream;
 pub(crate) use playback::{RemoteVideoFrame, play_remote_video_track};
 
@@ -37,6 +45,27 @@ pub struct LocalTrackPublication(livekit::publication::LocalTrackPublication);
 #[derive(Clone, Debug)]
 pub struct LocalParticipant(livekit::participant::LocalParticipant);
 
+/// A screen being captured for a screen share. Its frames are scaled down to the share's
+/// current limits as they're captured, so those can change without capturing it again.
+pub struct ScreenShareStream {
+    capture: Box<dyn ScreenCaptureStream>,
+    limits: Arc<Mutex<VideoEncodingLimits>>,
+}
+
+impl ScreenShareStream {
+    /// Scales the frames captured from now on to fit `limits`. The encoder only picks up the
+    /// rest of them with [`LocalParticipant::update_screenshare_limits`].
+    pub fn set_limits(&self, limits: VideoEncodingLimits) {
+        *self.limits.lock() = limits;
+    }
+}
+
+impl ScreenCaptureStream for ScreenShareStream {
+    fn metadata(&self) -> Result<SourceMetadata> {
+        self.capture.metadata()
+    }
+}
+
 pub struct Room {
     room: livekit::Room,
     _task: Task<()>,
@@ -106,15 +135,18 @@ impl Room {
         self.room.sid().await.to_string()
     }
 
+    /// Captures from the microphone with the given id, or the default one if it's `None` or
+    /// isn't connected.
     pub async fn publish_local_microphone_track(
         &self,
         user_name: String,
         is_staff: bool,
+        input_device: Option<String>,
         cx: &mut AsyncApp,
     ) -> Result<(LocalTrackPublication, playback::AudioStream)> {
-        let (track, stream) = self
-            .playback
-            .capture_local_microphone_track(user_name, is_staff, &cx)?;
+        let (track, stream) =
+            self.playback
+                .capture_local_microphone_track(user_name, is_staff, input_device, &cx)?;
         let publication = self
             .local_participant()
             .publish_track(
@@ -138,9 +170,59 @@ impl Room {
         self.local_participant().unpublish_track(sid, cx).await
     }
 
+    /// The WebRTC bindings we use can't capture from cameras yet, so none are ever listed.
+    pub fn camera_devices(&self) -> Result<Vec<CameraDevice>> {
+        Ok(Vec::new())
+    }
+
+    /// Lists the microphones and speakers right away, and again whenever one is plugged in or
+    /// out, or the system default changes.
+    pub fn audio_device_updates(&self, cx: &App) -> mpsc::UnboundedReceiver<Vec<AudioDevice>> {
+        let (tx, rx) = mpsc::unbounded();
+        let executor = cx.background_executor().clone();
+        cx.background_spawn(async move {
+            let mut last_devices = None;
+            while !tx.is_closed() {
+                let devices = audio_devices().log_err().unwrap_or_default();
+                if last_devices.as_ref() != Some(&devices) {
+                    tx.unbounded_send(devices.clone()).ok();
+                    last_devices = Some(devices);
+                }
+                executor.timer(AUDIO_DEVICE_POLL_INTERVAL).await;
+            }
+        })
+        .detach();
+        rx
+    }
+
+    /// Whether `processing` is turned on for the microphone, or `None` if it can't be turned
+    /// on or off in this call.
+    pub fn audio_processing(&self, processing: AudioProcessing, cx: &App) -> Option<bool> {
+        self.playback
+            .audio_processing(processing, AudioSettings::get_global(cx).rodio_audio)
+    }
+
+    /// Turns `processing` on or off for the microphone. Fails if that's not possible, in which
+    /// case [`Self::audio_processing`] returns `None`.
+    pub fn set_audio_processing(
+        &self,
+        processing: AudioProcessing,
+        enabled: bool,
+        cx: &App,
+    ) -> Result<AudioProcessingChange> {
+        self.playback.set_audio_processing(
+            processing,
+            enabled,
+            AudioSettings::get_global(cx).rodio_audio,
+        )
+    }
+
+    /// Plays on the speaker with the given id, or the default one if it's `None` or isn't
+    /// connected.
     pub fn play_remote_audio_track(
         &self,
         track: &RemoteAudioTrack,
+        output_device: Option<String>,
         cx: &mut App,
     ) -> Result<playback::AudioStream> {
         let speaker: Speaker =
@@ -152,9 +234,11 @@ impl Room {
 
         if AudioSettings::get_global(cx).rodio_audio {
             info!("Using experimental.rodio_audio audio pipeline for output");
-            playback::play_remote_audio_track(&track.0, speaker, cx)
+            playback::play_remote_audio_track(&track.0, speaker, output_device, cx)
         } else if speaker.sends_legacy_audio {
-            Ok(self.playback.play_remote_audio_track(&track.0))
+            Ok(self
+                .playback
+                .play_remote_audio_track(&track.0, output_device))
         } else {
             Err(anyhow!("Client version too old to play audio in call"))
         }
@@ -165,19 +249,46 @@ impl LocalParticipant {
     pub async fn publish_screenshare_track(
         &self,
         source: &dyn ScreenCaptureSource,
+        limits: VideoEncodingLimits,
         cx: &mut AsyncApp,
-    ) -> Result<(LocalTrackPublication, Box<dyn ScreenCaptureStream>)> {
-        let (track, stream) = capture_local_video_track(source, cx).await?;
-        let options = livekit::options::TrackPublishOptions {
-            source: livekit::track::TrackSource::Screenshare,
-            video_codec: livekit::options::VideoCodec::VP8,
-            ..Default::default()
-        };
+    ) -> Result<(LocalTrackPublication, ScreenShareStream)> {
+        let limits = Arc::new(Mutex::new(limits));
+        let (track, capture) = capture_local_video_track(source, limits.clone(), cx).await?;
+        let options = screenshare_publish_options(&limits.lock());
         let publication = self
             .publish_track(livekit::track::LocalTrack::Video(track.0), options, cx)
             .await?;
 
-        Ok((publication, stream))
+        Ok((publication, ScreenShareStream { capture, limits }))
+    }
+
+    /// Encodes a screen share within new limits. The screen keeps being captured, but the
+    /// encoder is only set up when a track is published, so the track is published again, and
+    /// its new publication is returned.
+    pub async fn update_screenshare_limits(
+        &self,
+        publication: &LocalTrackPublication,
+        limits: VideoEncodingLimits,
+        cx: &mut AsyncApp,
+    ) -> Result<LocalTrackPublication> {
+        let track = publication
+            .0
+            .track()
+            .context("the screen share has no track")?;
+        self.unpublish_track(publication.sid(), cx).await?;
+        self.publish_track(track, screenshare_publish_options(&limits), cx)
+            .await
+    }
+
+    pub async fn publish_camera_track(
+        &self,
+        device: &CameraDevice,
+        _cx: &mut AsyncApp,
+    ) -> Result<(LocalTrackPublication, CameraStream)> {
+        Err(anyhow!(
+            "capturing from camera {:?} is not supported",
+            device.name
+        ))
     }
 
     async fn publish_track(
@@ -206,6 +317,33 @@ impl LocalParticipant {
             .map(LocalTrackPublication)
             .context("unpublishing a track")
     }
+
+    /// Reliably sends `payload` to every other participant in the room.
+    pub async fn publish_data(
+        &self,
+        payload: Vec<u8>,
+        topic: String,
+        cx: &mut AsyncApp,
+    ) -> Result<()> {
+        let participant = self.0.clone();
+        let packet = livekit::DataPacket {
+            payload,
+            topic: Some(topic),
+            reliable: true,
+            ..Default::default()
+        };
+        Tokio::spawn(cx, async move { participant.publish_data(packet).await })
+            .await?
+            .context("publishing data")
+    }
+
+    /// Replaces this participant's metadata, which every other participant can read.
+    pub async fn set_metadata(&self, metadata: String, cx: &mut AsyncApp) -> Result<()> {
+        let participant = self.0.clone();
+        Tokio::spawn(cx, async move { participant.set_metadata(metadata).await })
+            .await?
+            .context("setting participant metadata")
+    }
 }
 
 impl LocalTrackPublication {
@@ -239,6 +377,10 @@ impl RemoteParticipant {
         ParticipantIdentity(self.0.identity().0)
     }
 
+    pub fn metadata(&self) -> String {
+        self.0.metadata()
+    }
+
     pub fn track_publications(&self) -> HashMap<TrackSid, RemoteTrackPublication> {
         self.0
             .track_publications()
@@ -258,6 +400,10 @@ impl RemoteVideoTrack {
     pub fn sid(&self) -> TrackSid {
         self.0.sid()
     }
+
+    pub fn source(&self) -> TrackSource {
+        track_source_from_livekit(self.0.source())
+    }
 }
 
 impl RemoteTrackPublication {
@@ -277,6 +423,10 @@ impl RemoteTrackPublication {
         self.0.kind() == livekit::track::TrackKind::Audio
     }
 
+    pub fn source(&self) -> TrackSource {
+        track_source_from_livekit(self.0.source())
+    }
+
     pub fn set_enabled(&self, enabled: bool, cx: &App) {
         let track = self.0.clone();
         Tokio::spawn(cx, async move { track.set_enabled(enabled) }).detach();
@@ -300,6 +450,20 @@ impl Participant {
     }
 }
 
+fn screenshare_publish_options(
+    limits: &VideoEncodingLimits,
+) -> livekit::options::TrackPublishOptions {
+    livekit::options::TrackPublishOptions {
+        source: livekit::track::TrackSource::Screenshare,
+        video_codec: livekit::options::VideoCodec::VP8,
+        video_encoding: Some(livekit::options::VideoEncoding {
+            max_bitrate: limits.max_bitrate,
+            max_framerate: limits.max_framerate,
+        }),
+        ..Default::default()
+    }
+}
+
 fn participant_from_livekit(participant: livekit::participant::Participant) -> Participant {
     match participant {
         livekit::participant::Participant::Local(local) => {
@@ -331,6 +495,16 @@ fn remote_track_from_livekit(track: livekit::track::RemoteTrack) -> RemoteTrack
     }
 }
 
+fn track_source_from_livekit(source: livekit::track::TrackSource) -> TrackSource {
+    match source {
+        livekit::track::TrackSource::Unknown => TrackSource::Unknown,
+        livekit::track::TrackSource::Camera => TrackSource::Camera,
+        livekit::track::TrackSource::Microphone => TrackSource::Microphone,
+        livekit::track::TrackSource::Screenshare => TrackSource::Screenshare,
+        livekit::track::TrackSource::ScreenshareAudio => TrackSource::ScreenshareAudio,
+    }
+}
+
 fn local_track_from_livekit(track: livekit::track::LocalTrack) -> LocalTrack {
     match track {
         livekit::track::LocalTrack::Audio(audio) => LocalTrack::Audio(LocalAudioTrack(audio)),
@@ -455,6 +629,28 @@ fn room_event_from_livekit(event: livekit::RoomEvent) -> Option<RoomEvent> {
                 speakers: speakers.into_iter().map(participant_from_livekit).collect(),
             }
         }
+        livekit::RoomEvent::ConnectionQualityChanged {
+            quality,
+            participant,
+        } => RoomEvent::ConnectionQualityChanged {
+            participant: participant_from_livekit(participant),
+            quality: match quality {
+                livekit::participant::ConnectionQuality::Excellent => ConnectionQuality::Excellent,
+                livekit::participant::ConnectionQuality::Good => ConnectionQuality::Good,
+                livekit::participant::ConnectionQuality::Poor => ConnectionQuality::Poor,
+                livekit::participant::ConnectionQuality::Lost => ConnectionQuality::Lost,
+            },
+        },
+        livekit::RoomEvent::DataReceived {
+            payload,
+            topic,
+            participant,
+            ..
+        } => RoomEvent::DataReceived {
+            payload: payload.to_vec(),
+            topic,
+            participant: participant.map(RemoteParticipant),
+        },
         livekit::RoomEvent::Connected {
             participants_with_tracks,
         } => RoomEvent::Connected {
@@ -483,3 +679,66 @@ fn room_event_from_livekit(event: livekit::RoomEvent) -> Option<RoomEvent> {
 
     Some(event)
 }
+
+/// The microphones and speakers that are currently connected.
+fn audio_devices() -> Result<Vec<AudioDevice>> {
+    let host = cpal::default_host();
+    let default_input = host
+        .default_input_device()
+        .and_then(|device| device.id().ok());
+    let default_output = host
+        .default_output_device()
+        .and_then(|device| device.id().ok());
+    let mut devices = Vec::new();
+    for device in host.devices().context("failed to list audio devices")? {
+        let (Ok(id), Ok(description)) = (device.id(), device.description()) else {
+            continue;
+        };
+        for (kind, supported, default_id) in [
+            (
+                AudioDeviceKind::Input,
+                description.supports_input(),
+                &default_input,
+            ),
+            (
+                AudioDeviceKind::Output,
+                description.supports_output(),
+                &default_output,
+            ),
+        ] {
+            if supported {
+                devices.push(AudioDevice {
+                    id: id.to_string(),
+                    name: description.name().to_string(),
+                    kind,
+                    is_default: default_id.as_ref() == Some(&id),
+                });
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// The device with the given id, falling back to the default one if there's none given or it
+/// isn't connected.
+fn audio_device(
+    input: bool,
+    device_id: Option<&str>,
+) -> Result<(cpal::Device, cpal::SupportedStreamConfig)> {
+    let Some(device) = device_id
+        .and_then(|id| id.parse::<cpal::DeviceId>().ok())
+        .and_then(|id| cpal::default_host().device_by_id(&id))
+    else {
+        return crate::default_device(input);
+    };
+    let config = if input {
+        device
+            .default_input_config()
+            .context("failed to get input config")?
+    } else {
+        device
+            .default_output_config()
+            .context("failed to get output config")?
+    };
+    Ok((device, config))
+}
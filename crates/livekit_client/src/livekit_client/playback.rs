@@ -1,6 +1,8 @@
 use anyhow::{Context as _, Result};
 
+use crate::{AudioProcessing, AudioProcessingChange};
 use audio::{AudioSettings, CHANNEL_COUNT, LEGACY_CHANNEL_COUNT, LEGACY_SAMPLE_RATE, SAMPLE_RATE};
+use collections::HashSet;
 use cpal::traits::{DeviceTrait, StreamTrait as _};
 use futures::channel::mpsc::UnboundedSender;
 use futures::{Stream, StreamExt as _};
@@ -36,14 +38,29 @@ mod source;
 pub(crate) struct AudioStack {
     executor: BackgroundExecutor,
     apm: Arc<Mutex<apm::AudioProcessingModule>>,
+    /// The processing that's turned on, which `apm` is set up for.
+    enabled_processing: Mutex<HashSet<AudioProcessing>>,
     mixer: Arc<Mutex<audio_mixer::AudioMixer>>,
-    _output_task: RefCell<Weak<Task<()>>>,
+    /// The task playing the mixed tracks, along with the id of the device it plays on.
+    _output_task: RefCell<(Option<String>, Weak<Task<()>>)>,
     next_ssrc: AtomicI32,
 }
 
+fn audio_processing_module(
+    enabled_processing: &HashSet<AudioProcessing>,
+) -> apm::AudioProcessingModule {
+    apm::AudioProcessingModule::new(
+        enabled_processing.contains(&AudioProcessing::EchoCancellation),
+        enabled_processing.contains(&AudioProcessing::AutoGain),
+        true,
+        enabled_processing.contains(&AudioProcessing::NoiseSuppression),
+    )
+}
+
 pub(crate) fn play_remote_audio_track(
     track: &livekit::track::RemoteAudioTrack,
     speaker: Speaker,
+    output_device: Option<String>,
     cx: &mut gpui::App,
 ) -> Result<AudioStream> {
     info!("speaker: {speaker:?}");
@@ -62,7 +79,8 @@ pub(crate) fn play_remote_audio_track(
 
     info!("sample_rate: {:?}", stream.sample_rate());
     info!("channel_count: {:?}", stream.channels());
-    audio::Audio::play_voip_stream(stream, speaker.name, speaker.is_staff, cx)
+    let output_device = output_device.and_then(|id| id.parse().ok());
+    audio::Audio::play_voip_stream(stream, speaker.name, speaker.is_staff, output_device, cx)
         .context("Could not play audio")?;
 
     let on_drop = util::defer(move || {
@@ -75,15 +93,15 @@ pub(crate) fn play_remote_audio_track(
 
 impl AudioStack {
     pub(crate) fn new(executor: BackgroundExecutor) -> Self {
-        let apm = Arc::new(Mutex::new(apm::AudioProcessingModule::new(
-            true, true, true, true,
-        )));
+        let enabled_processing = HashSet::from_iter(AudioProcessing::ALL);
+        let apm = Arc::new(Mutex::new(audio_processing_module(&enabled_processing)));
         let mixer = Arc::new(Mutex::new(audio_mixer::AudioMixer::new()));
         Self {
             executor,
             apm,
+            enabled_processing: Mutex::new(enabled_processing),
             mixer,
-            _output_task: RefCell::new(Weak::new()),
+            _output_task: RefCell::new((None, Weak::new())),
             next_ssrc: AtomicI32::new(1),
         }
     }
@@ -91,8 +109,9 @@ impl AudioStack {
     pub(crate) fn play_remote_audio_track(
         &self,
         track: &livekit::track::RemoteAudioTrack,
+        output_device: Option<String>,
     ) -> AudioStream {
-        let output_task = self.start_output();
+        let output_task = self.start_output(output_device);
 
         let next_ssrc = self.next_ssrc.fetch_add(1, Ordering::Relaxed);
         let source = AudioMixerSource {
@@ -130,25 +149,78 @@ impl AudioStack {
         }
     }
 
-    fn start_output(&self) -> Arc<Task<()>> {
-        if let Some(task) = self._output_task.borrow().upgrade() {
-            return task;
+    /// Whether `processing` is turned on, or `None` if the audio pipeline in use can't turn it
+    /// off.
+    pub(crate) fn audio_processing(
+        &self,
+        processing: AudioProcessing,
+        rodio_pipeline: bool,
+    ) -> Option<bool> {
+        // The experimental pipeline suppresses noise and adjusts the gain according to the
+        // `audio` settings instead.
+        if rodio_pipeline && processing != AudioProcessing::EchoCancellation {
+            return None;
+        }
+        Some(self.enabled_processing.lock().contains(&processing))
+    }
+
+    pub(crate) fn set_audio_processing(
+        &self,
+        processing: AudioProcessing,
+        enabled: bool,
+        rodio_pipeline: bool,
+    ) -> Result<AudioProcessingChange> {
+        if self.audio_processing(processing, rodio_pipeline).is_none() {
+            anyhow::bail!(
+                "{} is controlled by the experimental audio settings",
+                processing.label()
+            );
+        }
+        let mut enabled_processing = self.enabled_processing.lock();
+        if enabled {
+            enabled_processing.insert(processing);
+        } else {
+            enabled_processing.remove(&processing);
+        }
+        if rodio_pipeline {
+            // The experimental pipeline sets up echo cancellation when it opens the microphone.
+            Ok(AudioProcessingChange::RequiresNewTrack)
+        } else {
+            // The processing module can't be reconfigured, but the capture thread picks up a
+            // new one with the next frame.
+            *self.apm.lock() = audio_processing_module(&enabled_processing);
+            Ok(AudioProcessingChange::Applied)
+        }
+    }
+
+    /// Tracks played on another device than the running output task's keep it running until
+    /// they're dropped, so callers switching devices should drop the old tracks first.
+    fn start_output(&self, output_device: Option<String>) -> Arc<Task<()>> {
+        {
+            let (current_device, task) = &*self._output_task.borrow();
+            if *current_device == output_device
+                && let Some(task) = task.upgrade()
+            {
+                return task;
+            }
         }
         let task = Arc::new(self.executor.spawn({
             let apm = self.apm.clone();
             let mixer = self.mixer.clone();
+            let output_device = output_device.clone();
             async move {
                 Self::play_output(
                     apm,
                     mixer,
                     LEGACY_SAMPLE_RATE.get(),
                     LEGACY_CHANNEL_COUNT.get().into(),
+                    output_device,
                 )
                 .await
                 .log_err();
             }
         }));
-        *self._output_task.borrow_mut() = Arc::downgrade(&task);
+        *self._output_task.borrow_mut() = (output_device, Arc::downgrade(&task));
         task
     }
 
@@ -156,6 +228,7 @@ impl AudioStack {
         &self,
         user_name: String,
         is_staff: bool,
+        input_device: Option<String>,
         cx: &AsyncApp,
     ) -> Result<(crate::LocalAudioTrack, AudioStream)> {
         let legacy_audio_compatible =
@@ -208,7 +281,12 @@ impl AudioStack {
             AudioSettings::try_read_global(cx, |setting| setting.rodio_audio).unwrap_or_default();
         let capture_task = if rodio_pipeline {
             info!("Using experimental.rodio_audio audio pipeline");
-            let voip_parts = audio::VoipParts::new(cx)?;
+            let input_device = input_device.and_then(|id| id.parse().ok());
+            let echo_cancellation = self
+                .enabled_processing
+                .lock()
+                .contains(&AudioProcessing::EchoCancellation);
+            let voip_parts = audio::VoipParts::new(input_device, echo_cancellation, cx)?;
             // Audio needs to run real-time and should never be paused. That is
             // why we are using a normal std::thread and not a background task
             self.executor
@@ -225,6 +303,7 @@ impl AudioStack {
                     frame_tx,
                     LEGACY_SAMPLE_RATE.get(),
                     LEGACY_CHANNEL_COUNT.get().into(),
+                    input_device,
                 )
                 .await
             })
@@ -247,6 +326,7 @@ impl AudioStack {
         mixer: Arc<Mutex<audio_mixer::AudioMixer>>,
         sample_rate: u32,
         num_channels: u32,
+        output_device_id: Option<String>,
     ) -> Result<()> {
         // Prevent App Nap from throttling audio playback on macOS.
         // This guard is held for the entire duration of audio output.
@@ -255,7 +335,8 @@ impl AudioStack {
 
         loop {
             let mut device_change_listener = DeviceChangeListener::new(false)?;
-            let (output_device, output_config) = crate::default_device(false)?;
+            let (output_device, output_config) =
+                super::audio_device(false, output_device_id.as_deref())?;
             let (end_on_drop_tx, end_on_drop_rx) = std::sync::mpsc::channel::<()>();
             let mixer = mixer.clone();
             let apm = apm.clone();
@@ -327,10 +408,11 @@ impl AudioStack {
         frame_tx: UnboundedSender<AudioFrame<'static>>,
         sample_rate: u32,
         num_channels: u32,
+        input_device: Option<String>,
     ) -> Result<()> {
         loop {
             let mut device_change_listener = DeviceChangeListener::new(true)?;
-            let (device, config) = crate::default_device(true)?;
+            let (device, config) = super::audio_device(true, input_device.as_deref())?;
             let (end_on_drop_tx, end_on_drop_rx) = std::sync::mpsc::channel::<()>();
             let apm = apm.clone();
             let frame_tx = frame_tx.clone();
@@ -460,8 +542,10 @@ pub enum AudioStream {
     Output { _drop: Box<dyn std::any::Any> },
 }
 
+/// Captures a screen, scaling its frames down to fit `limits` as they are when each frame arrives.
 pub(crate) async fn capture_local_video_track(
     capture_source: &dyn ScreenCaptureSource,
+    limits: Arc<Mutex<crate::VideoEncodingLimits>>,
     cx: &mut gpui::AsyncApp,
 ) -> Result<(crate::LocalVideoTrack, Box<dyn ScreenCaptureStream>)> {
     let metadata = capture_source.metadata()?;
@@ -477,12 +561,27 @@ pub(crate) async fn capture_local_video_track(
         .stream(cx.foreground_executor(), {
             let track_source = track_source.clone();
             Box::new(move |frame| {
-                if let Some(buffer) = video_frame_buffer_to_webrtc(frame) {
+                let Some(buffer) = video_frame_buffer_to_webrtc(frame) else {
+                    return;
+                };
+                let (width, height) = (buffer.as_ref().width(), buffer.as_ref().height());
+                let (scaled_width, scaled_height) = limits.lock().scaled_size(width, height);
+                if (scaled_width, scaled_height) == (width, height) {
                     track_source.capture_frame(&VideoFrame {
                         rotation: VideoRotation::VideoRotation0,
                         timestamp_us: 0,
                         buffer,
                     });
+                } else {
+                    let scaled = buffer
+                        .as_ref()
+                        .to_i420()
+                        .scale(scaled_width as i32, scaled_height as i32);
+                    track_source.capture_frame(&VideoFrame {
+                        rotation: VideoRotation::VideoRotation0,
+                        timestamp_us: 0,
+                        buffer: scaled,
+                    });
                 }
             })
         })
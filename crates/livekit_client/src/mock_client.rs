@@ -13,6 +13,7 @@ pub type LocalVideoTrack = track::LocalVideoTrack;
 pub type LocalAudioTrack = track::LocalAudioTrack;
 pub type LocalTrackPublication = publication::LocalTrackPublication;
 pub type LocalParticipant = participant::LocalParticipant;
+pub type ScreenShareStream = participant::ScreenShareStream;
 
 pub type Room = test::Room;
 pub use test::{ConnectionState, ParticipantIdentity, TrackSid};
@@ -1,6 +1,7 @@
 use crate::{
-    AudioStream, LocalAudioTrack, LocalTrackPublication, LocalVideoTrack, Participant,
-    ParticipantIdentity, RemoteTrack, RemoteTrackPublication, TrackSid,
+    AudioStream, CameraDevice, CameraStream, LocalAudioTrack, LocalTrackPublication,
+    LocalVideoTrack, Participant, ParticipantIdentity, RemoteTrack, RemoteTrackPublication,
+    TrackSid, TrackSource, VideoEncodingLimits,
     test::{Room, WeakRoom},
 };
 use anyhow::Result;
@@ -60,21 +61,76 @@ impl LocalParticipant {
     pub async fn publish_screenshare_track(
         &self,
         _source: &dyn ScreenCaptureSource,
+        _limits: VideoEncodingLimits,
         _cx: &mut AsyncApp,
-    ) -> Result<(LocalTrackPublication, Box<dyn ScreenCaptureStream>)> {
+    ) -> Result<(LocalTrackPublication, ScreenShareStream)> {
         let this = self.clone();
         let server = this.room.test_server();
         let sid = server
-            .publish_video_track(this.room.token(), LocalVideoTrack {})
+            .publish_video_track(
+                this.room.token(),
+                LocalVideoTrack {},
+                TrackSource::Screenshare,
+            )
             .await?;
         Ok((
             LocalTrackPublication {
                 room: self.room.downgrade(),
                 sid,
             },
-            Box::new(TestScreenCaptureStream {}),
+            ScreenShareStream,
         ))
     }
+
+    pub async fn update_screenshare_limits(
+        &self,
+        publication: &LocalTrackPublication,
+        _limits: VideoEncodingLimits,
+        _cx: &mut AsyncApp,
+    ) -> Result<LocalTrackPublication> {
+        Ok(publication.clone())
+    }
+
+    pub async fn publish_camera_track(
+        &self,
+        device: &CameraDevice,
+        _cx: &mut AsyncApp,
+    ) -> Result<(LocalTrackPublication, CameraStream)> {
+        let server = self.room.test_server();
+        let device_lost = server.open_camera_device(&device.id)?;
+        let sid = server
+            .publish_video_track(self.room.token(), LocalVideoTrack {}, TrackSource::Camera)
+            .await?;
+        Ok((
+            LocalTrackPublication {
+                room: self.room.downgrade(),
+                sid,
+            },
+            CameraStream {
+                device: device.clone(),
+                device_lost,
+            },
+        ))
+    }
+
+    pub async fn publish_data(
+        &self,
+        payload: Vec<u8>,
+        topic: String,
+        _cx: &mut AsyncApp,
+    ) -> Result<()> {
+        self.room
+            .test_server()
+            .publish_data(self.room.token(), payload, topic)
+            .await
+    }
+
+    pub async fn set_metadata(&self, metadata: String, _cx: &mut AsyncApp) -> Result<()> {
+        self.room
+            .test_server()
+            .set_participant_metadata(self.room.token(), metadata)
+            .await
+    }
 }
 
 impl RemoteParticipant {
@@ -120,11 +176,26 @@ impl RemoteParticipant {
     pub fn identity(&self) -> ParticipantIdentity {
         self.identity.clone()
     }
+
+    pub fn metadata(&self) -> String {
+        self.room
+            .upgrade()
+            .and_then(|room| {
+                room.test_server()
+                    .participant_metadata(room.token(), &self.identity)
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
 }
 
-struct TestScreenCaptureStream;
+pub struct ScreenShareStream;
+
+impl ScreenShareStream {
+    pub fn set_limits(&self, _limits: VideoEncodingLimits) {}
+}
 
-impl ScreenCaptureStream for TestScreenCaptureStream {
+impl ScreenCaptureStream for ScreenShareStream {
     fn metadata(&self) -> Result<SourceMetadata> {
         Ok(SourceMetadata {
             id: 0,
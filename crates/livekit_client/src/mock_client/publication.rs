@@ -1,6 +1,6 @@
 use gpui::App;
 
-use crate::{RemoteTrack, TrackSid, test::WeakRoom};
+use crate::{RemoteTrack, TrackSid, TrackSource, test::WeakRoom};
 
 #[derive(Clone, Debug)]
 pub struct LocalTrackPublication {
@@ -60,6 +60,13 @@ impl RemoteTrackPublication {
         matches!(self.track, RemoteTrack::Audio(_))
     }
 
+    pub fn source(&self) -> TrackSource {
+        match &self.track {
+            RemoteTrack::Audio(_) => TrackSource::Microphone,
+            RemoteTrack::Video(track) => track.source(),
+        }
+    }
+
     pub fn is_muted(&self) -> bool {
         if let Some(room) = self.room.upgrade() {
             room.test_server()
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use crate::{
-    ParticipantIdentity, TrackSid,
+    ParticipantIdentity, TrackSid, TrackSource,
     test::{TestServerAudioTrack, TestServerVideoTrack, WeakRoom},
 };
 
@@ -50,6 +50,10 @@ impl RemoteVideoTrack {
         self.server_track.sid.clone()
     }
 
+    pub fn source(&self) -> TrackSource {
+        self.server_track.source
+    }
+
     pub fn publisher_id(&self) -> ParticipantIdentity {
         self.server_track.publisher_id.clone()
     }
@@ -1,9 +1,16 @@
-use crate::{AudioStream, Participant, RemoteTrack, RoomEvent, TrackPublication};
+use crate::{
+    AudioDevice, AudioProcessing, AudioProcessingChange, AudioStream, CameraDevice, Participant,
+    RemoteTrack, RoomEvent, TrackPublication, TrackSource,
+};
 
 use crate::mock_client::{participant::*, publication::*, track::*};
 use anyhow::{Context as _, Result};
 use async_trait::async_trait;
 use collections::{BTreeMap, HashMap, HashSet, btree_map::Entry as BTreeEntry, hash_map::Entry};
+use futures::channel::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
 use gpui::{App, AsyncApp, BackgroundExecutor};
 use livekit_api::{proto, token};
 use parking_lot::Mutex;
@@ -47,9 +54,20 @@ pub struct TestServer {
     pub api_key: String,
     pub secret_key: String,
     rooms: Mutex<HashMap<String, TestServerRoom>>,
+    camera_devices: Mutex<Vec<TestCameraDevice>>,
+    audio_devices: Mutex<Vec<AudioDevice>>,
+    audio_device_subscribers: Mutex<Vec<UnboundedSender<Vec<AudioDevice>>>>,
+    /// How turning each kind of audio processing on or off takes effect, where `None` means it
+    /// can't be. Processing that's missing is applied right away.
+    audio_processing_support: Mutex<HashMap<AudioProcessing, Option<AudioProcessingChange>>>,
     executor: BackgroundExecutor,
 }
 
+struct TestCameraDevice {
+    device: CameraDevice,
+    captures: Vec<oneshot::Sender<()>>,
+}
+
 impl TestServer {
     pub fn create(
         url: String,
@@ -64,6 +82,10 @@ impl TestServer {
                 api_key,
                 secret_key,
                 rooms: Default::default(),
+                camera_devices: Default::default(),
+                audio_devices: Default::default(),
+                audio_device_subscribers: Default::default(),
+                audio_processing_support: Default::default(),
                 executor,
             });
             e.insert(server.clone());
@@ -193,6 +215,7 @@ impl TestServer {
         room.client_rooms.remove(&identity).with_context(|| {
             format!("{identity:?} attempted to leave room {room_name:?} before joining it")
         })?;
+        room.participant_metadata.remove(&identity);
         Ok(())
     }
 
@@ -243,6 +266,7 @@ impl TestServer {
         room.client_rooms
             .remove(&identity)
             .with_context(|| format!("participant {identity:?} did not join room {room_name:?}"))?;
+        room.participant_metadata.remove(&identity);
         Ok(())
     }
 
@@ -263,6 +287,89 @@ impl TestServer {
         Ok(())
     }
 
+    /// Sets the cameras that every client connected to this server can capture from.
+    pub fn set_camera_devices(&self, devices: Vec<CameraDevice>) {
+        *self.camera_devices.lock() = devices
+            .into_iter()
+            .map(|device| TestCameraDevice {
+                device,
+                captures: Vec::new(),
+            })
+            .collect();
+    }
+
+    /// Simulates unplugging a camera, notifying everyone capturing from it.
+    pub fn disconnect_camera_device(&self, device_id: &str) {
+        self.camera_devices.lock().retain_mut(|camera| {
+            if camera.device.id == device_id {
+                for capture in camera.captures.drain(..) {
+                    capture.send(()).ok();
+                }
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    pub(crate) fn camera_devices(&self) -> Vec<CameraDevice> {
+        self.camera_devices
+            .lock()
+            .iter()
+            .map(|camera| camera.device.clone())
+            .collect()
+    }
+
+    pub(crate) fn open_camera_device(&self, device_id: &str) -> Result<oneshot::Receiver<()>> {
+        let mut camera_devices = self.camera_devices.lock();
+        let camera = camera_devices
+            .iter_mut()
+            .find(|camera| camera.device.id == device_id)
+            .with_context(|| format!("camera {device_id:?} does not exist"))?;
+        let (tx, rx) = oneshot::channel();
+        camera.captures.push(tx);
+        Ok(rx)
+    }
+
+    /// Sets the microphones and speakers that every client connected to this server can use,
+    /// notifying the ones watching for changes.
+    pub fn set_audio_devices(&self, devices: Vec<AudioDevice>) {
+        *self.audio_devices.lock() = devices.clone();
+        self.audio_device_subscribers
+            .lock()
+            .retain(|subscriber| subscriber.unbounded_send(devices.clone()).is_ok());
+    }
+
+    /// Sets how turning `processing` on or off takes effect for every client connected to this
+    /// server, or that it can't be turned on or off when `change` is `None`.
+    pub fn set_audio_processing_support(
+        &self,
+        processing: AudioProcessing,
+        change: Option<AudioProcessingChange>,
+    ) {
+        self.audio_processing_support
+            .lock()
+            .insert(processing, change);
+    }
+
+    pub(crate) fn audio_processing_support(
+        &self,
+        processing: AudioProcessing,
+    ) -> Option<AudioProcessingChange> {
+        self.audio_processing_support
+            .lock()
+            .get(&processing)
+            .copied()
+            .unwrap_or(Some(AudioProcessingChange::Applied))
+    }
+
+    pub(crate) fn audio_device_updates(&self) -> UnboundedReceiver<Vec<AudioDevice>> {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        tx.unbounded_send(self.audio_devices.lock().clone()).ok();
+        self.audio_device_subscribers.lock().push(tx);
+        rx
+    }
+
     pub async fn disconnect_client(&self, client_identity: String) {
         let client_identity = ParticipantIdentity(client_identity);
 
@@ -286,6 +393,7 @@ impl TestServer {
         &self,
         token: String,
         _local_track: LocalVideoTrack,
+        source: TrackSource,
     ) -> Result<TrackSid> {
         self.simulate_random_delay().await;
 
@@ -311,6 +419,7 @@ impl TestServer {
         let server_track = Arc::new(TestServerVideoTrack {
             sid: sid.clone(),
             publisher_id: identity.clone(),
+            source,
         });
 
         room.video_tracks.push(server_track.clone());
@@ -411,7 +520,164 @@ impl TestServer {
         Ok(sid)
     }
 
-    pub(crate) async fn unpublish_track(&self, _token: String, _track: &TrackSid) -> Result<()> {
+    pub(crate) async fn publish_data(
+        &self,
+        token: String,
+        payload: Vec<u8>,
+        topic: String,
+    ) -> Result<()> {
+        self.simulate_random_delay().await;
+
+        let claims = livekit_api::token::validate(&token, &self.secret_key)?;
+        let identity = ParticipantIdentity(claims.sub.unwrap().to_string());
+        let room_name = claims.video.room.unwrap();
+
+        let server_rooms = self.rooms.lock();
+        let room = server_rooms
+            .get(&*room_name)
+            .with_context(|| format!("room {room_name} does not exist"))?;
+        anyhow::ensure!(
+            room.client_rooms.contains_key(&identity),
+            "{identity:?} is not a participant in room {room_name:?}"
+        );
+
+        for (room_identity, client_room) in &room.client_rooms {
+            if *room_identity != identity {
+                let participant = RemoteParticipant {
+                    identity: identity.clone(),
+                    room: client_room.downgrade(),
+                };
+                client_room
+                    .0
+                    .lock()
+                    .updates_tx
+                    .blocking_send(RoomEvent::DataReceived {
+                        payload: payload.clone(),
+                        topic: Some(topic.clone()),
+                        participant: Some(participant),
+                    })
+                    .ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn set_participant_metadata(
+        &self,
+        token: String,
+        metadata: String,
+    ) -> Result<()> {
+        self.simulate_random_delay().await;
+
+        let claims = livekit_api::token::validate(&token, &self.secret_key)?;
+        let identity = ParticipantIdentity(claims.sub.unwrap().to_string());
+        let room_name = claims.video.room.unwrap();
+        anyhow::ensure!(
+            claims.video.can_update_own_metadata.unwrap_or(false),
+            "{identity:?} is not allowed to update its metadata"
+        );
+
+        let mut server_rooms = self.rooms.lock();
+        let room = server_rooms
+            .get_mut(&*room_name)
+            .with_context(|| format!("room {room_name} does not exist"))?;
+        anyhow::ensure!(
+            room.client_rooms.contains_key(&identity),
+            "{identity:?} is not a participant in room {room_name:?}"
+        );
+        let old_metadata = room
+            .participant_metadata
+            .insert(identity.clone(), metadata.clone())
+            .unwrap_or_default();
+
+        for (room_identity, client_room) in &room.client_rooms {
+            if *room_identity != identity {
+                let participant = Participant::Remote(RemoteParticipant {
+                    identity: identity.clone(),
+                    room: client_room.downgrade(),
+                });
+                client_room
+                    .0
+                    .lock()
+                    .updates_tx
+                    .blocking_send(RoomEvent::ParticipantMetadataChanged {
+                        participant,
+                        old_metadata: old_metadata.clone(),
+                        metadata: metadata.clone(),
+                    })
+                    .ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn participant_metadata(
+        &self,
+        token: String,
+        identity: &ParticipantIdentity,
+    ) -> Result<String> {
+        let claims = livekit_api::token::validate(&token, &self.secret_key)?;
+        let room_name = claims.video.room.unwrap();
+
+        let server_rooms = self.rooms.lock();
+        let room = server_rooms
+            .get(&*room_name)
+            .with_context(|| format!("room {room_name} does not exist"))?;
+        Ok(room
+            .participant_metadata
+            .get(identity)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Stops sending an audio track, unsubscribing everyone else in the room from it.
+    pub(crate) async fn unpublish_track(&self, token: String, track_sid: &TrackSid) -> Result<()> {
+        let claims = livekit_api::token::validate(&token, &self.secret_key)?;
+        let identity = ParticipantIdentity(claims.sub.unwrap().to_string());
+        let room_name = claims.video.room.unwrap();
+        let mut server_rooms = self.rooms.lock();
+        // The room is already gone when the last participant unpublishes as they leave.
+        let Some(room) = server_rooms.get_mut(&*room_name) else {
+            return Ok(());
+        };
+        let Some(ix) = room
+            .audio_tracks
+            .iter()
+            .position(|track| track.sid == *track_sid && track.publisher_id == identity)
+        else {
+            return Ok(());
+        };
+        let server_track = room.audio_tracks.remove(ix);
+
+        for (room_identity, client_room) in &room.client_rooms {
+            if *room_identity != identity {
+                let track = RemoteTrack::Audio(RemoteAudioTrack {
+                    server_track: server_track.clone(),
+                    room: client_room.downgrade(),
+                });
+                let publication = RemoteTrackPublication {
+                    sid: track_sid.clone(),
+                    room: client_room.downgrade(),
+                    track: track.clone(),
+                };
+                let participant = RemoteParticipant {
+                    identity: identity.clone(),
+                    room: client_room.downgrade(),
+                };
+                client_room
+                    .0
+                    .lock()
+                    .updates_tx
+                    .blocking_send(RoomEvent::TrackUnsubscribed {
+                        track,
+                        publication,
+                        participant,
+                    })
+                    .ok();
+            }
+        }
         Ok(())
     }
 
@@ -547,12 +813,14 @@ struct TestServerRoom {
     video_tracks: Vec<Arc<TestServerVideoTrack>>,
     audio_tracks: Vec<Arc<TestServerAudioTrack>>,
     participant_permissions: HashMap<ParticipantIdentity, proto::ParticipantPermission>,
+    participant_metadata: HashMap<ParticipantIdentity, String>,
 }
 
 #[derive(Debug)]
 pub(crate) struct TestServerVideoTrack {
     pub(crate) sid: TrackSid,
     pub(crate) publisher_id: ParticipantIdentity,
+    pub(crate) source: TrackSource,
     // frames_rx: async_broadcast::Receiver<Frame>,
 }
 
@@ -633,6 +901,7 @@ pub(crate) struct RoomState {
     pub(crate) local_identity: ParticipantIdentity,
     pub(crate) connection_state: ConnectionState,
     pub(crate) paused_audio_tracks: HashSet<TrackSid>,
+    pub(crate) enabled_audio_processing: HashSet<AudioProcessing>,
     pub(crate) updates_tx: mpsc::Sender<RoomEvent>,
 }
 
@@ -684,6 +953,7 @@ impl Room {
             token: token.to_string(),
             connection_state: ConnectionState::Disconnected,
             paused_audio_tracks: Default::default(),
+            enabled_audio_processing: HashSet::from_iter(AudioProcessing::ALL),
             updates_tx,
         })));
 
@@ -725,11 +995,44 @@ impl Room {
     pub fn play_remote_audio_track(
         &self,
         _track: &RemoteAudioTrack,
+        _output_device: Option<String>,
         _cx: &App,
     ) -> anyhow::Result<AudioStream> {
         Ok(AudioStream {})
     }
 
+    pub fn audio_device_updates(&self, _cx: &App) -> UnboundedReceiver<Vec<AudioDevice>> {
+        self.test_server().audio_device_updates()
+    }
+
+    pub fn camera_devices(&self) -> Result<Vec<CameraDevice>> {
+        Ok(self.test_server().camera_devices())
+    }
+
+    pub fn audio_processing(&self, processing: AudioProcessing, _cx: &App) -> Option<bool> {
+        self.test_server().audio_processing_support(processing)?;
+        Some(self.0.lock().enabled_audio_processing.contains(&processing))
+    }
+
+    pub fn set_audio_processing(
+        &self,
+        processing: AudioProcessing,
+        enabled: bool,
+        _cx: &App,
+    ) -> Result<AudioProcessingChange> {
+        let change = self
+            .test_server()
+            .audio_processing_support(processing)
+            .with_context(|| format!("{} is not supported", processing.label()))?;
+        let mut state = self.0.lock();
+        if enabled {
+            state.enabled_audio_processing.insert(processing);
+        } else {
+            state.enabled_audio_processing.remove(&processing);
+        }
+        Ok(change)
+    }
+
     pub async fn unpublish_local_track(&self, sid: TrackSid, cx: &mut AsyncApp) -> Result<()> {
         self.local_participant().unpublish_track(sid, cx).await
     }
@@ -738,6 +1041,7 @@ impl Room {
         &self,
         _track_name: String,
         _is_staff: bool,
+        _input_device: Option<String>,
         cx: &mut AsyncApp,
     ) -> Result<(LocalTrackPublication, AudioStream)> {
         self.local_participant().publish_microphone_track(cx).await
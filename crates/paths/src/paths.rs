@@ -180,6 +180,12 @@ pub fn hang_traces_dir() -> &'static PathBuf {
     LOGS_DIR.get_or_init(|| data_dir().join("hang_traces"))
 }
 
+/// Returns the path to the directory kernel message traces are written to.
+pub fn kernel_traces_dir() -> &'static PathBuf {
+    static KERNEL_TRACES_DIR: OnceLock<PathBuf> = OnceLock::new();
+    KERNEL_TRACES_DIR.get_or_init(|| data_dir().join("kernel_traces"))
+}
+
 /// Returns the path to the logs directory.
 pub fn logs_dir() -> &'static PathBuf {
     static LOGS_DIR: OnceLock<PathBuf> = OnceLock::new();
@@ -1203,6 +1203,18 @@ impl GitStore {
         cx.spawn(|_: &mut AsyncApp| async move { rx.await? })
     }
 
+    pub fn file_history_in_range(
+        &self,
+        repo: &Entity<Repository>,
+        path: RepoPath,
+        range: String,
+        cx: &mut App,
+    ) -> Task<Result<git::repository::FileHistory>> {
+        let rx = repo.update(cx, |repo, _| repo.file_history_in_range(path, range));
+
+        cx.spawn(|_: &mut AsyncApp| async move { rx.await? })
+    }
+
     pub fn get_permalink_to_line(
         &self,
         buffer: &Entity<Buffer>,
@@ -2520,7 +2532,10 @@ impl GitStore {
 
         let file_history = repository_handle
             .update(&mut cx, |repository_handle, _| {
-                repository_handle.file_history_paginated(path, skip, limit)
+                match envelope.payload.range {
+                    Some(range) => repository_handle.file_history_in_range(path, range),
+                    None => repository_handle.file_history_paginated(path, skip, limit),
+                }
             })
             .await??;
 
@@ -4381,13 +4396,32 @@ impl Repository {
         path: RepoPath,
         skip: usize,
         limit: Option<usize>,
+    ) -> oneshot::Receiver<Result<git::repository::FileHistory>> {
+        self.load_file_history(path, skip, limit, None)
+    }
+
+    pub fn file_history_in_range(
+        &mut self,
+        path: RepoPath,
+        range: String,
+    ) -> oneshot::Receiver<Result<git::repository::FileHistory>> {
+        self.load_file_history(path, 0, None, Some(range))
+    }
+
+    fn load_file_history(
+        &mut self,
+        path: RepoPath,
+        skip: usize,
+        limit: Option<usize>,
+        range: Option<String>,
     ) -> oneshot::Receiver<Result<git::repository::FileHistory>> {
         let id = self.id;
         self.send_job(None, move |git_repo, _cx| async move {
             match git_repo {
-                RepositoryState::Local(LocalRepositoryState { backend, .. }) => {
-                    backend.file_history_paginated(path, skip, limit).await
-                }
+                RepositoryState::Local(LocalRepositoryState { backend, .. }) => match range {
+                    Some(range) => backend.file_history_in_range(path, range).await,
+                    None => backend.file_history_paginated(path, skip, limit).await,
+                },
                 RepositoryState::Remote(RemoteRepositoryState { client, project_id }) => {
                     let response = client
                         .request(proto::GitFileHistory {
@@ -4396,6 +4430,7 @@ impl Repository {
                             path: path.to_proto(),
                             skip: skip as u64,
                             limit: limit.map(|l| l as u64),
+                            range,
                         })
                         .await?;
                     Ok(git::repository::FileHistory {
@@ -2394,10 +2394,12 @@ impl LocalLspStore {
             }));
         }
 
+        // Formatting is abandoned by dropping its task, which shouldn't leave the command running.
         let mut child = child
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()?;
 
         let stdin = child.stdin.as_mut().context("failed to acquire stdin")?;
@@ -76,7 +76,7 @@ pub use environment::ProjectEnvironment;
 use futures::{
     StreamExt,
     channel::mpsc::{self, UnboundedReceiver},
-    future::try_join_all,
+    future::{join_all, try_join_all},
 };
 pub use image_store::{ImageItem, ImageStore};
 use image_store::{ImageItemEvent, ImageStoreEvent};
@@ -124,6 +124,7 @@ use std::{
     ops::{Not as _, Range},
     path::{Path, PathBuf},
     pin::pin,
+    rc::Rc,
     str::{self, FromStr},
     sync::Arc,
     time::Duration,
@@ -203,6 +204,21 @@ pub enum OpenedBufferEvent {
 /// Maps [`Worktree`] entries with its own logic using [`ProjectEntryId`] and [`ProjectPath`] structs.
 ///
 /// Can be either local (for the project opened on the same host) or remote.(for collab projects, browsed by multiple remote users).
+/// How far along joining a project shared in a call is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinProjectProgress {
+    Connecting,
+    /// The request to join was sent, and the host's collab server is gathering the project.
+    WaitingForHost,
+    /// The project was joined, and the entries of the host's worktrees are arriving.
+    ReceivingWorktrees {
+        worktrees: usize,
+        /// The worktrees whose entries have all arrived.
+        synced_worktrees: usize,
+        entries: usize,
+    },
+}
+
 pub struct Project {
     active_entry: Option<ProjectEntryId>,
     buffer_ordered_messages_tx: mpsc::UnboundedSender<BufferOrderedMessage>,
@@ -1613,6 +1629,26 @@ impl Project {
         fs: Arc<dyn Fs>,
         cx: AsyncApp,
     ) -> Result<Entity<Self>> {
+        Self::in_room_with_progress(remote_id, client, user_store, languages, fs, |_, _| {}, cx)
+            .await
+    }
+
+    /// Joins a project shared in a call, calling `progress` as the join moves along, and returning
+    /// once the entries of the host's worktrees have been received.
+    ///
+    /// Dropping the returned future abandons the join, leaving the project on the host's side if
+    /// the request to join it was already sent.
+    pub async fn in_room_with_progress(
+        remote_id: u64,
+        client: Arc<Client>,
+        user_store: Entity<UserStore>,
+        languages: Arc<LanguageRegistry>,
+        fs: Arc<dyn Fs>,
+        progress: impl Fn(JoinProjectProgress, &mut App) + 'static,
+        cx: AsyncApp,
+    ) -> Result<Entity<Self>> {
+        let progress: Rc<dyn Fn(JoinProjectProgress, &mut App)> = Rc::new(progress);
+        cx.update(|cx| progress(JoinProjectProgress::Connecting, cx));
         client.connect(true, &cx).await.into_response()?;
 
         let subscriptions = [
@@ -1632,24 +1668,101 @@ impl Project {
             ),
         ];
         let committer = get_git_committer(&cx).await;
+        cx.update(|cx| progress(JoinProjectProgress::WaitingForHost, cx));
+        // Once the host has the request, being dropped before the project exists to leave it
+        // on release would leave this client behind as a collaborator.
+        let leave_if_cancelled = util::defer({
+            let client = client.clone();
+            move || {
+                client
+                    .send(proto::LeaveProject {
+                        project_id: remote_id,
+                    })
+                    .log_err();
+            }
+        });
         let response = client
             .request_envelope(proto::JoinProject {
                 project_id: remote_id,
                 committer_email: committer.email,
                 committer_name: committer.name,
             })
-            .await?;
-        Self::from_join_project_response(
-            response,
+            .await;
+        // The project is created without yielding from here, so it leaves the project itself if
+        // the join is dropped from now on.
+        leave_if_cancelled.abort();
+        let project = Self::from_join_project_response(
+            response?,
             subscriptions,
             client,
             false,
             user_store,
             languages,
             fs,
-            cx,
+            cx.clone(),
         )
-        .await
+        .await?;
+        Self::wait_for_remote_worktrees(&project, progress, cx).await;
+        Ok(project)
+    }
+
+    /// Waits for the host to send each worktree's entries, reporting how many have arrived.
+    async fn wait_for_remote_worktrees(
+        project: &Entity<Self>,
+        progress: Rc<dyn Fn(JoinProjectProgress, &mut App)>,
+        mut cx: AsyncApp,
+    ) {
+        let worktrees =
+            project.read_with(&cx, |project, cx| project.worktrees(cx).collect::<Vec<_>>());
+        let report = Rc::new({
+            let worktrees = worktrees.clone();
+            move |cx: &mut App| {
+                let mut synced_worktrees = 0;
+                let mut entries = 0;
+                for worktree in &worktrees {
+                    let worktree = worktree.read(cx);
+                    if worktree.completed_scan_id() > 0 {
+                        synced_worktrees += 1;
+                    }
+                    entries += worktree.entry_count();
+                }
+                progress(
+                    JoinProjectProgress::ReceivingWorktrees {
+                        worktrees: worktrees.len(),
+                        synced_worktrees,
+                        entries,
+                    },
+                    cx,
+                );
+            }
+        });
+
+        let _subscriptions = cx.update(|cx| {
+            report(cx);
+            worktrees
+                .iter()
+                .map(|worktree| {
+                    let report = report.clone();
+                    cx.subscribe(worktree, move |_, event, cx| {
+                        if let worktree::Event::UpdatedEntries(_) = event {
+                            report(cx);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+        let snapshots = worktrees
+            .iter()
+            .filter_map(|worktree| {
+                worktree.update(&mut cx, |worktree, _| {
+                    Some(worktree.as_remote_mut()?.wait_for_snapshot(1))
+                })
+            })
+            .collect::<Vec<_>>();
+        // A worktree whose host disconnects stops waiting, and the project finds out it was
+        // disconnected as usual.
+        join_all(snapshots).await;
+        cx.update(|cx| report(cx));
     }
 
     async fn from_join_project_response(
@@ -5838,7 +5951,6 @@ impl Project {
 
     #[cfg(feature = "test-support")]
     pub fn git_scans_complete(&self, cx: &Context<Self>) -> Task<()> {
-        use futures::future::join_all;
         cx.spawn(async move |this, cx| {
             let scans_complete = this
                 .read_with(cx, |this, cx| {
@@ -37,6 +37,8 @@ pub struct ProjectContext {
     pub worktrees: Vec<WorktreeContext>,
     /// Whether any worktree has a rules_file. Provided as a field because handlebars can't do this.
     pub has_rules: bool,
+    /// Whether any worktree has instruction files. Provided as a field because handlebars can't do this.
+    pub has_instruction_files: bool,
     pub user_rules: Vec<UserRulesContext>,
     /// `!user_rules.is_empty()` - provided as a field because handlebars can't do this.
     pub has_user_rules: bool,
@@ -50,9 +52,13 @@ impl ProjectContext {
         let has_rules = worktrees
             .iter()
             .any(|worktree| worktree.rules_file.is_some());
+        let has_instruction_files = worktrees
+            .iter()
+            .any(|worktree| !worktree.instruction_files.is_empty());
         Self {
             worktrees,
             has_rules,
+            has_instruction_files,
             has_user_rules: !default_user_rules.is_empty(),
             user_rules: default_user_rules,
             os: std::env::consts::OS.to_string(),
@@ -75,6 +81,7 @@ pub struct WorktreeContext {
     pub root_name: String,
     pub abs_path: Arc<Path>,
     pub rules_file: Option<RulesFileContext>,
+    pub instruction_files: Vec<InstructionFileContext>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
@@ -87,6 +94,16 @@ pub struct RulesFileContext {
     pub project_entry_id: usize,
 }
 
+/// A file describing the project's conventions, which is only summarized in the prompt so that
+/// the model can read it in full when it needs to.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct InstructionFileContext {
+    pub path_in_worktree: Arc<RelPath>,
+    /// The start of the file's text, on a single line.
+    pub excerpt: String,
+    pub len: usize,
+}
+
 #[derive(Serialize)]
 pub struct ContentPromptDiagnosticContext {
     pub line_number: usize,
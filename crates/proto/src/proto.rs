@@ -144,6 +144,9 @@ messages!(
     (JoinChannelBufferResponse, Foreground),
     (JoinChannelChat, Foreground),
     (JoinChannelChatResponse, Foreground),
+    (JoinChannelRequestAnswered, Foreground),
+    (JoinChannelRequestCanceled, Foreground),
+    (JoinChannelRequested, Foreground),
     (JoinProject, Foreground),
     (JoinProjectResponse, Foreground),
     (JoinRoom, Foreground),
@@ -234,10 +237,13 @@ messages!(
     (RefreshCodeLens, Background),
     (GetCodeLens, Background),
     (GetCodeLensResponse, Background),
+    (RequestToJoinChannel, Foreground),
     (RespondToChannelInvite, Foreground),
     (RespondToContactRequest, Foreground),
+    (RespondToJoinChannelRequest, Foreground),
     (RestartLanguageServers, Foreground),
     (StopLanguageServers, Background),
+    (RoomMigrated, Foreground),
     (RoomUpdated, Foreground),
     (SaveBuffer, Foreground),
     (SendChannelMessage, Background),
@@ -461,8 +467,10 @@ request_messages!(
     (GetDocumentColor, GetDocumentColorResponse),
     (GetFoldingRanges, GetFoldingRangesResponse),
     (GetColorPresentation, GetColorPresentationResponse),
+    (RequestToJoinChannel, Ack),
     (RespondToChannelInvite, Ack),
     (RespondToContactRequest, Ack),
+    (RespondToJoinChannelRequest, Ack),
     (SaveBuffer, BufferSaved),
     (Stage, Ack),
     (FindSearchCandidates, Ack),
@@ -951,6 +951,15 @@ impl RemoteClient {
         connection.build_forward_ports_command(forwards)
     }
 
+    pub fn build_control_master_forward_command(
+        &self,
+        forwards: Vec<(u16, String, u16)>,
+        cancel: bool,
+    ) -> Option<CommandTemplate> {
+        self.remote_connection()?
+            .build_control_master_forward_command(forwards, cancel)
+    }
+
     pub fn upload_directory(
         &self,
         src_path: PathBuf,
@@ -1364,6 +1373,18 @@ pub trait RemoteConnection: Send + Sync {
         &self,
         forwards: Vec<(u16, String, u16)>,
     ) -> Result<CommandTemplate>;
+    /// Builds a command that asks the connection's control master to start forwarding
+    /// `forwards` (or to stop, when `cancel` is set) and exits once it has, so that forwards
+    /// share the existing connection instead of each needing a process of their own.
+    ///
+    /// Returns `None` when the connection has no control master.
+    fn build_control_master_forward_command(
+        &self,
+        _forwards: Vec<(u16, String, u16)>,
+        _cancel: bool,
+    ) -> Option<CommandTemplate> {
+        None
+    }
     fn connection_options(&self) -> RemoteConnectionOptions;
     fn path_style(&self) -> PathStyle;
     fn shell(&self) -> String;
@@ -367,6 +367,34 @@ impl RemoteConnection for SshRemoteConnection {
         })
     }
 
+    #[cfg(not(windows))]
+    fn build_control_master_forward_command(
+        &self,
+        forwards: Vec<(u16, String, u16)>,
+        cancel: bool,
+    ) -> Option<CommandTemplate> {
+        let Self { socket, .. } = self;
+        // `ssh_command_options` points at the master's control socket.
+        let mut args = socket.ssh_command_options();
+        args.push("-O".into());
+        args.push(if cancel { "cancel" } else { "forward" }.into());
+        for (local_port, host, remote_port) in forwards {
+            args.push("-L".into());
+            args.push(format!(
+                "{}:{}:{}",
+                local_port,
+                bracket_ipv6(&host),
+                remote_port
+            ));
+        }
+        args.push(socket.connection_options.ssh_destination());
+        Some(CommandTemplate {
+            program: "ssh".into(),
+            args,
+            env: Default::default(),
+        })
+    }
+
     fn upload_directory(
         &self,
         src_path: PathBuf,
@@ -32,12 +32,12 @@ use rpc::{
     AnyProtoClient, TypedEnvelope,
     proto::{self, REMOTE_SERVER_PEER_ID, REMOTE_SERVER_PROJECT_ID},
 };
-use smol::process::Child;
 
 use settings::initial_server_settings_content;
 use std::{
     num::NonZeroU64,
     path::{Path, PathBuf},
+    process::Stdio,
     sync::{
         Arc,
         atomic::{AtomicU64, AtomicUsize, Ordering},
@@ -45,7 +45,7 @@ use std::{
     time::Instant,
 };
 use sysinfo::{ProcessRefreshKind, RefreshKind, System, UpdateKind};
-use util::{ResultExt, paths::PathStyle, rel_path::RelPath};
+use util::{ResultExt, paths::PathStyle, process::Child, rel_path::RelPath};
 use worktree::Worktree;
 
 pub struct HeadlessProject {
@@ -69,6 +69,8 @@ pub struct HeadlessProject {
     // Used mostly to keep alive the toolchain store for RPC handlers.
     // Local variant is used within LSP store, but that's a separate entity.
     pub _toolchain_store: Entity<ToolchainStore>,
+    /// Kernel processes by kernel id. Each kernel runs in its own process group, so that killing
+    /// it also kills any processes it started.
     pub kernels: HashMap<String, Child>,
 }
 
@@ -952,7 +954,7 @@ impl HeadlessProject {
 
         // Spawn kernel
         let spawn_kernel = |binary: &str, args: &[String]| {
-            let mut command = smol::process::Command::new(binary);
+            let mut command = std::process::Command::new(binary);
 
             if !args.is_empty() {
                 for arg in args {
@@ -989,7 +991,7 @@ impl HeadlessProject {
             if let Some(wd) = &working_directory {
                 command.current_dir(wd);
             }
-            command.spawn()
+            Child::spawn(command, Stdio::null(), Stdio::inherit(), Stdio::inherit())
         };
 
         // We need to manage the child process lifecycle
@@ -1004,6 +1006,7 @@ impl HeadlessProject {
                 .context("failed to spawn kernel process (tried python3 and python)")?
         };
 
+        let pid = child.id();
         this.update(&mut cx.clone(), |this, _cx| {
             this.kernels.insert(kernel_id.clone(), child);
         });
@@ -1011,6 +1014,7 @@ impl HeadlessProject {
         Ok(proto::SpawnKernelResponse {
             kernel_id,
             connection_file: connection_file_content,
+            pid,
         })
     }
 
@@ -1022,7 +1026,11 @@ impl HeadlessProject {
         let kernel_id = envelope.payload.kernel_id;
         let child = this.update(&mut cx, |this, _| this.kernels.remove(&kernel_id));
         if let Some(mut child) = child {
+            log::info!("killing kernel {kernel_id} (pid {})", child.id());
             child.kill().log_err();
+            // Reap the process so that it doesn't linger as a zombie.
+            cx.background_spawn(async move { child.status().await.log_err() })
+                .detach();
         }
         Ok(proto::Ack {})
     }
@@ -1,5 +1,7 @@
+mod execution_history_picker;
 mod kernel_list_item;
 mod kernel_options;
 
+pub use execution_history_picker::*;
 pub use kernel_list_item::*;
 pub use kernel_options::*;
@@ -0,0 +1,207 @@
+use crate::Session;
+use crate::execution_history::{HistoryEntry, HistoryEntryStatus};
+
+use chrono::Local;
+use editor::Editor;
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, Task, WeakEntity};
+use picker::{Picker, PickerDelegate};
+use std::sync::Arc;
+use ui::{ListItem, ListItemSpacing, prelude::*};
+use util::ResultExt as _;
+use workspace::ModalView;
+
+/// Lists what a session's kernel ran, newest first, and runs the selected code again.
+pub struct ExecutionHistoryPicker {
+    picker: Entity<Picker<ExecutionHistoryDelegate>>,
+}
+
+impl ExecutionHistoryPicker {
+    pub fn new(
+        session: Entity<Session>,
+        editor: WeakEntity<Editor>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let entries = session
+            .read(cx)
+            .history()
+            .entries()
+            .cloned()
+            .collect::<Vec<_>>();
+        let delegate = ExecutionHistoryDelegate {
+            history_picker: cx.entity().downgrade(),
+            session: session.downgrade(),
+            editor,
+            matches: (0..entries.len()).collect(),
+            entries,
+            selected_index: 0,
+        };
+        let picker = cx.new(|cx| Picker::uniform_list(delegate, window, cx));
+        Self { picker }
+    }
+}
+
+impl Render for ExecutionHistoryPicker {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .key_context("ExecutionHistoryPicker")
+            .w(rems(34.))
+            .child(self.picker.clone())
+    }
+}
+
+impl Focusable for ExecutionHistoryPicker {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.picker.focus_handle(cx)
+    }
+}
+
+impl EventEmitter<DismissEvent> for ExecutionHistoryPicker {}
+impl ModalView for ExecutionHistoryPicker {}
+
+pub struct ExecutionHistoryDelegate {
+    history_picker: WeakEntity<ExecutionHistoryPicker>,
+    session: WeakEntity<Session>,
+    editor: WeakEntity<Editor>,
+    /// The session's history when the picker was opened, newest first.
+    entries: Vec<HistoryEntry>,
+    /// Indices into `entries` of the executions matching the query.
+    matches: Vec<usize>,
+    selected_index: usize,
+}
+
+impl PickerDelegate for ExecutionHistoryDelegate {
+    type ListItem = ListItem;
+
+    fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    fn set_selected_index(&mut self, ix: usize, _: &mut Window, _: &mut Context<Picker<Self>>) {
+        self.selected_index = ix;
+    }
+
+    fn placeholder_text(&self, _window: &mut Window, _cx: &mut App) -> Arc<str> {
+        "Search executed code...".into()
+    }
+
+    fn no_matches_text(&self, _window: &mut Window, _cx: &mut App) -> Option<SharedString> {
+        Some(if self.entries.is_empty() {
+            "No code has run in this session yet".into()
+        } else {
+            "No matches".into()
+        })
+    }
+
+    fn update_matches(
+        &mut self,
+        query: String,
+        _window: &mut Window,
+        _cx: &mut Context<Picker<Self>>,
+    ) -> Task<()> {
+        let query = query.to_lowercase();
+        self.matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.code.to_lowercase().contains(&query))
+            .map(|(ix, _)| ix)
+            .collect();
+        self.selected_index = 0;
+        Task::ready(())
+    }
+
+    fn confirm(&mut self, _secondary: bool, window: &mut Window, cx: &mut Context<Picker<Self>>) {
+        if let Some(entry) = self
+            .matches
+            .get(self.selected_index)
+            .and_then(|ix| self.entries.get(*ix))
+        {
+            let code = entry.code.clone();
+            self.session
+                .update(cx, |session, cx| session.rerun(code, window, cx))
+                .log_err();
+        }
+        self.dismissed(window, cx);
+    }
+
+    fn dismissed(&mut self, _: &mut Window, cx: &mut Context<Picker<Self>>) {
+        self.history_picker
+            .update(cx, |_, cx| cx.emit(DismissEvent))
+            .log_err();
+    }
+
+    fn render_match(
+        &self,
+        ix: usize,
+        selected: bool,
+        _: &mut Window,
+        cx: &mut Context<Picker<Self>>,
+    ) -> Option<Self::ListItem> {
+        let entry = self.entries.get(*self.matches.get(ix)?)?;
+
+        let mut lines = entry.code.lines().filter(|line| !line.trim().is_empty());
+        let mut preview = lines.next().unwrap_or_default().trim_end().to_string();
+        if lines.next().is_some() {
+            preview.push_str(" …");
+        }
+
+        let (icon, color) = match entry.status {
+            HistoryEntryStatus::Running => (IconName::ArrowCircle, Color::Muted),
+            HistoryEntryStatus::Succeeded => (IconName::Check, Color::Success),
+            HistoryEntryStatus::Failed => (IconName::XCircle, Color::Error),
+        };
+        let details = format!(
+            "In [{}] at {}",
+            entry.execution_count,
+            entry.executed_at.with_timezone(&Local).format("%H:%M:%S")
+        );
+
+        Some(
+            ListItem::new(ix)
+                .inset(true)
+                .spacing(ListItemSpacing::Sparse)
+                .toggle_state(selected)
+                .start_slot(Icon::new(icon).size(IconSize::Small).color(color))
+                .child(
+                    v_flex().child(Label::new(preview).buffer_font(cx)).child(
+                        Label::new(details)
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    ),
+                ),
+        )
+    }
+
+    fn render_footer(
+        &self,
+        _: &mut Window,
+        cx: &mut Context<Picker<Self>>,
+    ) -> Option<gpui::AnyElement> {
+        Some(
+            h_flex()
+                .w_full()
+                .border_t_1()
+                .border_color(cx.theme().colors().border_variant)
+                .p_1()
+                .justify_end()
+                .child(
+                    Button::new("copy-session-as-script", "Copy Session as Script")
+                        .disabled(self.entries.is_empty())
+                        .on_click(cx.listener(|picker, _, window, cx| {
+                            crate::copy_session_as_script(
+                                picker.delegate.editor.clone(),
+                                window,
+                                cx,
+                            );
+                            picker.delegate.dismissed(window, cx);
+                        })),
+                )
+                .into_any(),
+        )
+    }
+}
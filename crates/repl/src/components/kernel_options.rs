@@ -1,12 +1,16 @@
 use crate::KERNEL_DOCS_URL;
-use crate::kernels::KernelSpecification;
+use crate::kernels::{KernelSpecification, RemoteKernelSpecification, shutdown_remote_kernel};
 use crate::repl_store::ReplStore;
 
+use chrono::Utc;
 use gpui::{AnyView, DismissEvent, FontWeight, SharedString, Task};
+use jupyter_websocket_client::RemoteServer;
 use picker::{Picker, PickerDelegate};
 use project::WorktreeId;
 use std::sync::Arc;
-use ui::{ListItem, ListItemSpacing, PopoverMenu, PopoverMenuHandle, PopoverTrigger, prelude::*};
+use ui::{
+    ListItem, ListItemSpacing, PopoverMenu, PopoverMenuHandle, PopoverTrigger, Tooltip, prelude::*,
+};
 
 type OnSelect = Box<dyn Fn(KernelSpecification, &mut Window, &mut App)>;
 
@@ -19,7 +23,11 @@ pub enum KernelPickerEntry {
     },
 }
 
-fn build_grouped_entries(store: &ReplStore, worktree_id: WorktreeId) -> Vec<KernelPickerEntry> {
+fn build_grouped_entries(
+    store: &ReplStore,
+    worktree_id: WorktreeId,
+    language: Option<&str>,
+) -> Vec<KernelPickerEntry> {
     let mut entries = Vec::new();
     let mut recommended_entry: Option<KernelPickerEntry> = None;
     let mut found_selected = false;
@@ -28,8 +36,13 @@ fn build_grouped_entries(store: &ReplStore, worktree_id: WorktreeId) -> Vec<Kern
     let mut python_envs = Vec::new();
     let mut jupyter_kernels = Vec::new();
     let mut remote_kernels = Vec::new();
+    let mut running_kernels = Vec::new();
 
     for spec in store.kernel_specifications_for_worktree(worktree_id) {
+        if language.is_some_and(|language| !spec.language().eq_ignore_ascii_case(language)) {
+            continue;
+        }
+
         let is_recommended = store.is_recommended_kernel(worktree_id, spec);
         let is_selected = selected_kernel.map_or(false, |s| s == spec);
 
@@ -59,6 +72,14 @@ fn build_grouped_entries(store: &ReplStore, worktree_id: WorktreeId) -> Vec<Kern
                     is_recommended,
                 });
             }
+            KernelSpecification::JupyterServer(remote_spec)
+                if remote_spec.running_kernel.is_some() =>
+            {
+                running_kernels.push(KernelPickerEntry::Kernel {
+                    spec: spec.clone(),
+                    is_recommended,
+                });
+            }
             KernelSpecification::JupyterServer(_)
             | KernelSpecification::SshRemote(_)
             | KernelSpecification::WslRemote(_) => {
@@ -70,19 +91,10 @@ fn build_grouped_entries(store: &ReplStore, worktree_id: WorktreeId) -> Vec<Kern
         }
     }
 
-    // Sort Python envs: has_ipykernel first, then by name
-    python_envs.sort_by(|a, b| {
-        let (spec_a, spec_b) = match (a, b) {
-            (
-                KernelPickerEntry::Kernel { spec: sa, .. },
-                KernelPickerEntry::Kernel { spec: sb, .. },
-            ) => (sa, sb),
-            _ => return std::cmp::Ordering::Equal,
-        };
-        spec_b
-            .has_ipykernel()
-            .cmp(&spec_a.has_ipykernel())
-            .then_with(|| spec_a.name().cmp(&spec_b.name()))
+    // Python envs with ipykernel first, otherwise keeping the store's ranking
+    python_envs.sort_by_key(|entry| match entry {
+        KernelPickerEntry::Kernel { spec, .. } => !spec.has_ipykernel(),
+        KernelPickerEntry::SectionHeader(_) => false,
     });
 
     // Recommended section
@@ -111,6 +123,14 @@ fn build_grouped_entries(store: &ReplStore, worktree_id: WorktreeId) -> Vec<Kern
         entries.extend(remote_kernels);
     }
 
+    // Kernels already running on a Jupyter server, which are attached to rather than started
+    if !running_kernels.is_empty() {
+        entries.push(KernelPickerEntry::SectionHeader(
+            "Running on Jupyter Server".into(),
+        ));
+        entries.extend(running_kernels);
+    }
+
     entries
 }
 
@@ -126,6 +146,7 @@ where
     tooltip: TT,
     info_text: Option<SharedString>,
     worktree_id: WorktreeId,
+    language: Option<SharedString>,
 }
 
 pub struct KernelPickerDelegate {
@@ -149,6 +170,7 @@ where
             tooltip,
             info_text: None,
             worktree_id,
+            language: None,
         }
     }
 
@@ -161,6 +183,13 @@ where
         self.info_text = Some(text.into());
         self
     }
+
+    /// Only offers kernels for `language`, such as when starting a kernel for a notebook's SQL
+    /// cells.
+    pub fn for_language(mut self, language: impl Into<SharedString>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
 }
 
 impl KernelPickerDelegate {
@@ -190,6 +219,51 @@ impl KernelPickerDelegate {
 
         from
     }
+
+    /// Shuts down a kernel running on a Jupyter server and drops it from the picker.
+    fn shut_down_running_kernel(
+        &mut self,
+        spec: RemoteKernelSpecification,
+        cx: &mut Context<Picker<Self>>,
+    ) {
+        let Some(running_kernel) = spec.running_kernel.clone() else {
+            return;
+        };
+        let remote_server = RemoteServer {
+            base_url: spec.url.clone(),
+            token: spec.token.clone(),
+        };
+        let http_client = cx.http_client();
+        let spec = KernelSpecification::JupyterServer(spec);
+        cx.spawn(async move |picker, cx| {
+            shutdown_remote_kernel(&remote_server, http_client, &running_kernel.id).await?;
+            picker.update(cx, |picker, cx| {
+                let delegate = &mut picker.delegate;
+                let is_shut_down = |entry: &KernelPickerEntry| match entry {
+                    KernelPickerEntry::Kernel {
+                        spec: entry_spec, ..
+                    } => *entry_spec == spec,
+                    KernelPickerEntry::SectionHeader(_) => false,
+                };
+                delegate.all_entries.retain(|entry| !is_shut_down(entry));
+                delegate
+                    .filtered_entries
+                    .retain(|entry| !is_shut_down(entry));
+                if delegate.selected_kernelspec.as_ref() == Some(&spec) {
+                    delegate.selected_kernelspec = None;
+                }
+                delegate.selected_index = delegate
+                    .selected_index
+                    .min(delegate.filtered_entries.len().saturating_sub(1));
+                cx.notify();
+            })?;
+            cx.update(|cx| {
+                ReplStore::global(cx).update(cx, |store, cx| store.refresh_kernelspecs(cx))
+            })
+            .await
+        })
+        .detach_and_log_err(cx);
+    }
 }
 
 impl PickerDelegate for KernelPickerDelegate {
@@ -322,14 +396,29 @@ impl PickerDelegate for KernelPickerDelegate {
                 let is_currently_selected = self.selected_kernelspec.as_ref() == Some(spec);
                 let icon = spec.icon(cx);
                 let has_ipykernel = spec.has_ipykernel();
+                let running_kernel = match spec {
+                    KernelSpecification::JupyterServer(remote_spec) => {
+                        remote_spec.running_kernel.as_ref()
+                    }
+                    _ => None,
+                };
+                let title = running_kernel
+                    .map(|running_kernel| SharedString::from(running_kernel.title()))
+                    .unwrap_or_else(|| spec.name());
 
                 let subtitle = match spec {
-                    KernelSpecification::Jupyter(_) => None,
-                    KernelSpecification::PythonEnv(_)
+                    KernelSpecification::Jupyter(local_spec) if !local_spec.is_custom() => None,
+                    KernelSpecification::Jupyter(_)
+                    | KernelSpecification::PythonEnv(_)
                     | KernelSpecification::JupyterServer(_)
                     | KernelSpecification::SshRemote(_)
                     | KernelSpecification::WslRemote(_) => {
-                        let env_kind = spec.environment_kind_label();
+                        let env_kind = match running_kernel {
+                            Some(running_kernel) => {
+                                Some(SharedString::from(running_kernel.describe(Utc::now())))
+                            }
+                            None => spec.environment_kind_label(),
+                        };
                         let path = spec.path();
                         match env_kind {
                             Some(kind) => Some(format!("{} \u{2013} {}", kind, path)),
@@ -363,7 +452,7 @@ impl PickerDelegate for KernelPickerDelegate {
                                                         .flex_shrink()
                                                         .text_ellipsis()
                                                         .child(
-                                                            Label::new(spec.name())
+                                                            Label::new(title)
                                                                 .weight(FontWeight::MEDIUM)
                                                                 .size(LabelSize::Default),
                                                         ),
@@ -400,7 +489,30 @@ impl PickerDelegate for KernelPickerDelegate {
                                     .color(Color::Accent)
                                     .size(IconSize::Small),
                             )
-                        }),
+                        })
+                        .when_some(
+                            match spec {
+                                KernelSpecification::JupyterServer(remote_spec)
+                                    if running_kernel.is_some() =>
+                                {
+                                    Some(remote_spec.clone())
+                                }
+                                _ => None,
+                            },
+                            |item, remote_spec| {
+                                item.end_hover_slot(
+                                    IconButton::new(("shut-down-kernel", ix), IconName::Stop)
+                                        .icon_size(IconSize::Small)
+                                        .tooltip(Tooltip::text("Shut Down Kernel"))
+                                        .on_click(cx.listener(move |picker, _, _, cx| {
+                                            cx.stop_propagation();
+                                            picker
+                                                .delegate
+                                                .shut_down_running_kernel(remote_spec.clone(), cx);
+                                        })),
+                                )
+                            },
+                        ),
                 )
             }
         }
@@ -439,7 +551,7 @@ where
     fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
         let store = ReplStore::global(cx).read(cx);
 
-        let all_entries = build_grouped_entries(store, self.worktree_id);
+        let all_entries = build_grouped_entries(store, self.worktree_id, self.language.as_deref());
         let selected_kernelspec = store.active_kernelspec(self.worktree_id, None, cx);
         let selected_index = all_entries
             .iter()
@@ -467,7 +579,11 @@ where
                 .max_height(Some(rems(24.).into()))
         });
 
-        PopoverMenu::new("kernel-switcher")
+        let menu_id: SharedString = match &self.language {
+            Some(language) => format!("kernel-switcher-{language}").into(),
+            None => "kernel-switcher".into(),
+        };
+        PopoverMenu::new(menu_id)
             .menu(move |_window, _cx| Some(picker_view.clone()))
             .trigger_with_tooltip(self.trigger, self.tooltip)
             .attach(gpui::Corner::BottomLeft)
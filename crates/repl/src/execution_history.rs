@@ -0,0 +1,258 @@
+use chrono::{DateTime, Utc};
+use runtimelib::{ExecutionState, JupyterMessage, JupyterMessageContent, ReplyStatus};
+use std::collections::VecDeque;
+
+/// The code a session's kernel has run, most recent last.
+///
+/// Entries are recorded from the `execute_input` messages the kernel broadcasts on iopub, so
+/// code run by other clients of the same kernel shows up too. Their replies go to the client
+/// that sent the request, which is why an execution's outcome is also taken from iopub: an
+/// `error` marks it failed, and the kernel going idle afterwards marks it finished.
+#[derive(Clone, Debug)]
+pub struct ExecutionHistory {
+    entries: VecDeque<HistoryEntry>,
+    max_entries: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub code: String,
+    pub execution_count: usize,
+    pub executed_at: DateTime<Utc>,
+    pub status: HistoryEntryStatus,
+    /// The id of the request that ran the code, which the kernel's messages about it refer to.
+    request_id: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryEntryStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl ExecutionHistory {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_entries,
+        }
+    }
+
+    /// Drops the oldest entries beyond `max_entries`.
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
+    pub fn record(&mut self, message: &JupyterMessage, received_at: DateTime<Utc>) {
+        let Some(request_id) = message
+            .parent_header
+            .as_ref()
+            .map(|header| header.msg_id.as_str())
+        else {
+            return;
+        };
+
+        if let JupyterMessageContent::ExecuteInput(input) = &message.content {
+            self.entries.push_back(HistoryEntry {
+                code: input.code.clone(),
+                execution_count: input.execution_count.0,
+                executed_at: received_at,
+                status: HistoryEntryStatus::Running,
+                request_id: request_id.to_string(),
+            });
+            self.evict();
+            return;
+        }
+
+        let Some(entry) = self
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.request_id == request_id)
+        else {
+            return;
+        };
+        match &message.content {
+            JupyterMessageContent::ErrorOutput(_) => entry.status = HistoryEntryStatus::Failed,
+            JupyterMessageContent::ExecuteReply(reply) if reply.status != ReplyStatus::Ok => {
+                entry.status = HistoryEntryStatus::Failed;
+            }
+            JupyterMessageContent::Status(status)
+                if status.execution_state == ExecutionState::Idle
+                    && entry.status == HistoryEntryStatus::Running =>
+            {
+                entry.status = HistoryEntryStatus::Succeeded;
+            }
+            _ => {}
+        }
+    }
+
+    /// The recorded executions, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().rev()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The code of every execution that succeeded, in the order it ran, as a single script.
+    pub fn script(&self) -> String {
+        let mut script = self
+            .entries
+            .iter()
+            .filter(|entry| entry.status == HistoryEntryStatus::Succeeded)
+            .map(|entry| entry.code.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if !script.is_empty() {
+            script.push('\n');
+        }
+        script
+    }
+
+    fn evict(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runtimelib::{ErrorOutput, ExecuteInput, ExecuteRequest, ExecutionCount, Status};
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + chrono::Duration::seconds(seconds)
+    }
+
+    /// Records the messages the kernel sends on iopub for running `code`, and returns the
+    /// request they're for.
+    fn run(
+        history: &mut ExecutionHistory,
+        code: &str,
+        execution_count: usize,
+        errors: bool,
+    ) -> JupyterMessage {
+        let request: JupyterMessage = ExecuteRequest {
+            code: code.to_string(),
+            ..Default::default()
+        }
+        .into();
+        let received_at = at(execution_count as i64);
+
+        history.record(
+            &ExecuteInput {
+                code: code.to_string(),
+                execution_count: ExecutionCount::new(execution_count),
+            }
+            .as_child_of(&request),
+            received_at,
+        );
+        if errors {
+            history.record(
+                &ErrorOutput {
+                    ename: "NameError".to_string(),
+                    evalue: "name 'x' is not defined".to_string(),
+                    traceback: Vec::new(),
+                }
+                .as_child_of(&request),
+                received_at,
+            );
+        }
+        history.record(
+            &Status {
+                execution_state: ExecutionState::Idle,
+            }
+            .as_child_of(&request),
+            received_at,
+        );
+        request
+    }
+
+    fn codes(history: &ExecutionHistory) -> Vec<&str> {
+        history.entries().map(|entry| entry.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_entries_are_newest_first() {
+        let mut history = ExecutionHistory::new(10);
+        assert!(history.is_empty());
+
+        run(&mut history, "a = 1", 1, false);
+        run(&mut history, "b = 2", 2, false);
+        run(&mut history, "a + b", 3, false);
+
+        assert_eq!(codes(&history), ["a + b", "b = 2", "a = 1"]);
+        let newest = history.entries().next().unwrap();
+        assert_eq!(newest.execution_count, 3);
+        assert_eq!(newest.executed_at, at(3));
+        assert_eq!(newest.status, HistoryEntryStatus::Succeeded);
+    }
+
+    #[test]
+    fn test_oldest_entries_are_evicted() {
+        let mut history = ExecutionHistory::new(2);
+        run(&mut history, "a = 1", 1, false);
+        run(&mut history, "b = 2", 2, false);
+        run(&mut history, "c = 3", 3, false);
+        assert_eq!(codes(&history), ["c = 3", "b = 2"]);
+
+        history.set_max_entries(1);
+        assert_eq!(codes(&history), ["c = 3"]);
+
+        history.set_max_entries(0);
+        run(&mut history, "d = 4", 4, false);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_errors_are_flagged() {
+        let mut history = ExecutionHistory::new(10);
+        run(&mut history, "x", 1, true);
+        run(&mut history, "y = 1", 2, false);
+
+        let statuses = history
+            .entries()
+            .map(|entry| entry.status)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [HistoryEntryStatus::Succeeded, HistoryEntryStatus::Failed]
+        );
+
+        // An execution that hasn't finished yet is still running, even if an unrelated one does.
+        let request: JupyterMessage = ExecuteRequest::default().into();
+        history.record(
+            &ExecuteInput {
+                code: "sleep(10)".to_string(),
+                execution_count: ExecutionCount::new(3),
+            }
+            .as_child_of(&request),
+            at(3),
+        );
+        run(&mut history, "z = 2", 4, false);
+        assert_eq!(
+            history.entries().nth(1).map(|entry| entry.status),
+            Some(HistoryEntryStatus::Running)
+        );
+    }
+
+    #[test]
+    fn test_script_skips_failed_executions() {
+        let mut history = ExecutionHistory::new(10);
+        assert_eq!(history.script(), "");
+
+        run(&mut history, "import math\n", 1, false);
+        run(&mut history, "math.tau(", 2, true);
+        run(&mut history, "r = 2", 3, false);
+        run(&mut history, "print(math.pi * r ** 2)", 4, false);
+
+        assert_eq!(
+            history.script(),
+            "import math\n\nr = 2\n\nprint(math.pi * r ** 2)\n"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use ui::{Chip, Tooltip, prelude::*};
+
+/// Cell magics that hand the cell to a shell, so they're badged like shell escapes.
+const SHELL_CELL_MAGICS: &[&str] = &["bash", "sh", "zsh", "script", "system"];
+
+/// What an execution runs, as far as can be told from how its code starts.
+///
+/// IPython runs shell escapes and cell magics itself instead of evaluating them as Python.
+/// Their output arrives as stdout and stderr streams, usually without an `execute_result`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionKind {
+    #[default]
+    Code,
+    /// A shell escape like `!pip install numpy`, or a cell magic that runs a shell.
+    Shell,
+    /// Any other cell magic, like `%%timeit`, by name.
+    CellMagic(SharedString),
+}
+
+impl ExecutionKind {
+    pub fn detect(code: &str) -> Self {
+        // IPython drops leading blank lines and indentation before looking for magics.
+        let Some(first_line) = code
+            .lines()
+            .map(str::trim_start)
+            .find(|line| !line.is_empty())
+        else {
+            return Self::Code;
+        };
+
+        if let Some(magic) = first_line.strip_prefix("%%") {
+            match magic.split_whitespace().next() {
+                Some(name) if SHELL_CELL_MAGICS.contains(&name) => Self::Shell,
+                Some(name) => Self::CellMagic(SharedString::from(name.to_string())),
+                None => Self::Code,
+            }
+        } else if first_line.starts_with('!') {
+            Self::Shell
+        } else {
+            Self::Code
+        }
+    }
+
+    /// The label for the badge shown next to the execution's status.
+    pub fn badge_label(&self) -> Option<&'static str> {
+        match self {
+            Self::Code => None,
+            Self::Shell => Some("shell"),
+            Self::CellMagic(_) => Some("magic"),
+        }
+    }
+
+    pub fn render_badge(&self) -> Option<Chip> {
+        let label = self.badge_label()?;
+        let tooltip = match self {
+            Self::CellMagic(name) => format!("Run by the %%{name} cell magic"),
+            _ => "Run by the kernel's shell".to_string(),
+        };
+        Some(
+            Chip::new(label)
+                .label_color(Color::Muted)
+                .tooltip(Tooltip::text(tooltip)),
+        )
+    }
+
+    /// Whether the execution's result is normally an `execute_result`, rather than whatever
+    /// it writes to its streams.
+    pub fn expects_execute_result(&self) -> bool {
+        matches!(self, Self::Code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_execution_kind() {
+        assert_eq!(ExecutionKind::detect("print('hi')"), ExecutionKind::Code);
+        assert_eq!(ExecutionKind::detect(""), ExecutionKind::Code);
+        assert_eq!(ExecutionKind::detect("x = 1\n!ls"), ExecutionKind::Code);
+        assert_eq!(ExecutionKind::detect("%time x = 1"), ExecutionKind::Code);
+        assert_eq!(ExecutionKind::detect("%%\nx = 1"), ExecutionKind::Code);
+
+        assert_eq!(
+            ExecutionKind::detect("!pip install numpy"),
+            ExecutionKind::Shell
+        );
+        assert_eq!(ExecutionKind::detect("\n  !ls -la\n"), ExecutionKind::Shell);
+        assert_eq!(
+            ExecutionKind::detect("%%bash\necho hi >&2"),
+            ExecutionKind::Shell
+        );
+        assert_eq!(
+            ExecutionKind::detect("%%script python3 --verbose\nprint(1)"),
+            ExecutionKind::Shell
+        );
+
+        assert_eq!(
+            ExecutionKind::detect("%%timeit -n 10\nsum(range(100))"),
+            ExecutionKind::CellMagic("timeit".into())
+        );
+        assert_eq!(
+            ExecutionKind::detect("\n%%capture output\nprint(1)"),
+            ExecutionKind::CellMagic("capture".into())
+        );
+    }
+
+    #[test]
+    fn test_only_code_expects_execute_result() {
+        assert!(ExecutionKind::Code.expects_execute_result());
+        assert!(!ExecutionKind::Shell.expects_execute_result());
+        assert!(!ExecutionKind::CellMagic("timeit".into()).expects_execute_result());
+
+        assert_eq!(ExecutionKind::Code.badge_label(), None);
+        assert_eq!(ExecutionKind::Shell.badge_label(), Some("shell"));
+        assert_eq!(
+            ExecutionKind::CellMagic("timeit".into()).badge_label(),
+            Some("magic")
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use chrono::{DateTime, Utc};
+use runtimelib::{ExecutionState, JupyterMessage, JupyterMessageContent};
+use std::time::Duration;
+
+/// Tracks how long a single execute request spent waiting in the kernel's queue and running.
+///
+/// Messages for an execution arrive on two channels: the `execute_reply` on shell, and
+/// `status` updates on iopub. Their relative order isn't guaranteed, so each event is
+/// recorded on its own and the durations are derived from whatever has arrived so far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionTiming {
+    /// When we sent the execute request.
+    sent_at: DateTime<Utc>,
+    /// When we received the kernel's `busy` status for this request.
+    busy_received_at: Option<DateTime<Utc>>,
+    /// When we received the `execute_reply`.
+    reply_received_at: Option<DateTime<Utc>>,
+    /// When we received the kernel's final `idle` status for this request.
+    idle_received_at: Option<DateTime<Utc>>,
+    /// The kernel's own start and end times, from the `started` metadata that ipykernel
+    /// attaches to `execute_reply` and the reply's header date.
+    kernel_run: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// When the most recent message of any kind arrived for this request, which is how the
+    /// session notices an execution that has gone quiet.
+    last_activity_at: DateTime<Utc>,
+}
+
+impl ExecutionTiming {
+    pub fn new(sent_at: DateTime<Utc>) -> Self {
+        Self {
+            sent_at,
+            busy_received_at: None,
+            reply_received_at: None,
+            idle_received_at: None,
+            kernel_run: None,
+            last_activity_at: sent_at,
+        }
+    }
+
+    pub fn record(&mut self, message: &JupyterMessage, received_at: DateTime<Utc>) {
+        self.last_activity_at = self.last_activity_at.max(received_at);
+        match &message.content {
+            JupyterMessageContent::Status(status) => match status.execution_state {
+                ExecutionState::Busy => {
+                    self.busy_received_at.get_or_insert(received_at);
+                }
+                ExecutionState::Idle => {
+                    self.idle_received_at.get_or_insert(received_at);
+                }
+                _ => {}
+            },
+            JupyterMessageContent::ExecuteReply(_) => {
+                self.reply_received_at.get_or_insert(received_at);
+                let started = message
+                    .metadata
+                    .get("started")
+                    .and_then(|started| started.as_str())
+                    .and_then(|started| DateTime::parse_from_rfc3339(started).ok());
+                if let Some(started) = started {
+                    self.kernel_run = Some((started.with_timezone(&Utc), message.header.date));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether both the reply and the final idle status have arrived, after which no further
+    /// messages will change the timing.
+    pub fn is_complete(&self) -> bool {
+        self.reply_received_at.is_some() && self.idle_received_at.is_some()
+    }
+
+    /// When the request was sent, or when anything for it last arrived, whichever is later.
+    pub fn last_activity_at(&self) -> DateTime<Utc> {
+        self.last_activity_at
+    }
+
+    /// How long the request waited before the kernel started running it.
+    pub fn queue_wait(&self) -> Option<Duration> {
+        Some(duration_between(self.sent_at, self.busy_before_finish()?))
+    }
+
+    /// How long the kernel spent running the request, as reported by the kernel when it
+    /// provides timing metadata, and otherwise as observed from our side.
+    pub fn run_duration(&self) -> Option<Duration> {
+        if let Some((started, finished)) = self.kernel_run {
+            return Some(duration_between(started, finished));
+        }
+        let started = self.busy_before_finish().unwrap_or(self.sent_at);
+        Some(duration_between(started, self.finished_at()?))
+    }
+
+    /// Whichever of the reply or the idle status arrives first marks the end of the run.
+    fn finished_at(&self) -> Option<DateTime<Utc>> {
+        match (self.reply_received_at, self.idle_received_at) {
+            (Some(reply), Some(idle)) => Some(reply.min(idle)),
+            (reply, idle) => reply.or(idle),
+        }
+    }
+
+    /// A busy status that arrives after the run finished says nothing about when it started.
+    fn busy_before_finish(&self) -> Option<DateTime<Utc>> {
+        let busy_received_at = self.busy_received_at?;
+        match self.finished_at() {
+            Some(finished_at) if busy_received_at > finished_at => None,
+            _ => Some(busy_received_at),
+        }
+    }
+}
+
+fn duration_between(start: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+    // The kernel's clock and ours can disagree slightly, so clamp negative spans to zero.
+    (end - start).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runtimelib::{ExecuteReply, ExecuteRequest, Status};
+
+    fn at(milliseconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + chrono::Duration::milliseconds(milliseconds)
+    }
+
+    fn status(request: &JupyterMessage, execution_state: ExecutionState) -> JupyterMessage {
+        Status { execution_state }.as_child_of(request)
+    }
+
+    fn reply(request: &JupyterMessage, kernel_times: Option<(i64, i64)>) -> JupyterMessage {
+        let mut reply = ExecuteReply::default().as_child_of(request);
+        if let Some((started, finished)) = kernel_times {
+            reply.metadata = serde_json::json!({ "started": at(started).to_rfc3339() });
+            reply.header.date = at(finished);
+        }
+        reply
+    }
+
+    fn request() -> JupyterMessage {
+        ExecuteRequest {
+            code: "sleep(3)".to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_timing_prefers_kernel_metadata() {
+        let request = request();
+        let mut timing = ExecutionTiming::new(at(0));
+
+        assert_eq!(timing.last_activity_at(), at(0));
+        timing.record(&status(&request, ExecutionState::Busy), at(500));
+        assert_eq!(timing.queue_wait(), Some(Duration::from_millis(500)));
+        assert_eq!(timing.last_activity_at(), at(500));
+        assert!(!timing.is_complete());
+
+        timing.record(&reply(&request, Some((600, 3800))), at(4000));
+        timing.record(&status(&request, ExecutionState::Idle), at(4100));
+        assert!(timing.is_complete());
+        assert_eq!(timing.run_duration(), Some(Duration::from_millis(3200)));
+    }
+
+    #[test]
+    fn test_timing_falls_back_to_wall_clock() {
+        let request = request();
+        let mut timing = ExecutionTiming::new(at(0));
+        assert_eq!(timing.run_duration(), None);
+
+        timing.record(&status(&request, ExecutionState::Busy), at(1000));
+        assert_eq!(timing.run_duration(), None);
+
+        timing.record(&status(&request, ExecutionState::Idle), at(3500));
+        assert!(!timing.is_complete());
+        assert_eq!(timing.run_duration(), Some(Duration::from_millis(2500)));
+
+        timing.record(&reply(&request, None), at(3600));
+        assert!(timing.is_complete());
+        assert_eq!(timing.run_duration(), Some(Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn test_timing_with_reply_before_idle() {
+        let request = request();
+        let mut timing = ExecutionTiming::new(at(0));
+
+        timing.record(&status(&request, ExecutionState::Busy), at(200));
+        timing.record(&reply(&request, None), at(2200));
+        assert!(!timing.is_complete());
+        assert_eq!(timing.run_duration(), Some(Duration::from_secs(2)));
+
+        timing.record(&status(&request, ExecutionState::Idle), at(2500));
+        assert!(timing.is_complete());
+        assert_eq!(timing.run_duration(), Some(Duration::from_secs(2)));
+        assert_eq!(timing.queue_wait(), Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_timing_ignores_repeated_and_late_messages() {
+        let request = request();
+        let mut timing = ExecutionTiming::new(at(0));
+
+        // The reply overtakes every iopub message, including busy.
+        timing.record(&reply(&request, None), at(1000));
+        assert_eq!(timing.run_duration(), Some(Duration::from_secs(1)));
+        assert_eq!(timing.queue_wait(), None);
+
+        timing.record(&status(&request, ExecutionState::Busy), at(1100));
+        timing.record(&status(&request, ExecutionState::Busy), at(1200));
+        timing.record(&status(&request, ExecutionState::Idle), at(1300));
+        timing.record(&status(&request, ExecutionState::Idle), at(9000));
+        assert!(timing.is_complete());
+        assert_eq!(timing.queue_wait(), None);
+        assert_eq!(timing.run_duration(), Some(Duration::from_secs(1)));
+    }
+}
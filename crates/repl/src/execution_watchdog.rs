@@ -0,0 +1,249 @@
+use chrono::{DateTime, Utc};
+use runtimelib::JupyterMessage;
+use std::time::Duration;
+
+use crate::execution_timing::ExecutionTiming;
+
+/// How often a session checks on its kernel.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long the kernel has to answer a probe before it's considered unresponsive.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the session should do about a kernel that has been busy and quiet for too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// Send a `kernel_info_request` to find out whether the kernel is still running the code.
+    Probe,
+    /// The kernel didn't answer the probe in time.
+    Unresponsive,
+}
+
+/// Notices a kernel that stays busy after an execution went quiet, which usually means that the
+/// execution's `execute_reply` or final `idle` status was dropped on the way.
+///
+/// A kernel handles shell requests one at a time, so it only answers a `kernel_info_request`
+/// once it's done with every execution sent before it. A prompt answer means those executions
+/// finished and their last messages were lost; no answer means the kernel really is stuck.
+#[derive(Debug, Default)]
+pub struct ExecutionWatchdog {
+    probe: Option<Probe>,
+}
+
+#[derive(Debug)]
+struct Probe {
+    msg_id: String,
+    sent_at: DateTime<Utc>,
+    timed_out: bool,
+}
+
+impl ExecutionWatchdog {
+    /// Checks on a kernel given whether it's busy and the executions that haven't completed.
+    pub fn check<'a>(
+        &mut self,
+        busy: bool,
+        executions: impl IntoIterator<Item = &'a ExecutionTiming>,
+        now: DateTime<Utc>,
+        busy_timeout: Duration,
+    ) -> Option<WatchdogAction> {
+        if !busy {
+            self.probe = None;
+            return None;
+        }
+        if let Some(probe) = &mut self.probe {
+            if !probe.timed_out && elapsed(probe.sent_at, now) >= PROBE_TIMEOUT {
+                probe.timed_out = true;
+                return Some(WatchdogAction::Unresponsive);
+            }
+            return None;
+        }
+        let last_activity_at = executions
+            .into_iter()
+            .map(ExecutionTiming::last_activity_at)
+            .max()?;
+        (elapsed(last_activity_at, now) >= busy_timeout).then_some(WatchdogAction::Probe)
+    }
+
+    pub fn probe_sent(&mut self, msg_id: String, sent_at: DateTime<Utc>) {
+        self.probe = Some(Probe {
+            msg_id,
+            sent_at,
+            timed_out: false,
+        });
+    }
+
+    /// Returns when the probe was sent if `message` answers it before it timed out. A late
+    /// answer only ends the probe, since whatever was stuck has had time to finish normally.
+    ///
+    /// The kernel's status updates for the probe count as an answer as much as its reply does,
+    /// since it only gets to either once it's done with everything that came before.
+    pub fn probe_answered(&mut self, message: &JupyterMessage) -> Option<DateTime<Utc>> {
+        let parent_msg_id = &message.parent_header.as_ref()?.msg_id;
+        let probe = self.probe.take_if(|probe| probe.msg_id == *parent_msg_id)?;
+        (!probe.timed_out).then_some(probe.sent_at)
+    }
+}
+
+/// The executions, keyed by message id, that nothing arrived for since `probe_sent_at`. Once
+/// the kernel has answered the probe, these are finished but will never hear from it again.
+pub fn lost_executions<'a>(
+    executions: impl IntoIterator<Item = (&'a String, &'a ExecutionTiming)>,
+    probe_sent_at: DateTime<Utc>,
+) -> Vec<String> {
+    executions
+        .into_iter()
+        .filter(|(_, timing)| timing.last_activity_at() <= probe_sent_at)
+        .map(|(msg_id, _)| msg_id.clone())
+        .collect()
+}
+
+fn elapsed(since: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (now - since).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collections::HashMap;
+    use runtimelib::{
+        ExecuteReply, ExecuteRequest, ExecutionState, JupyterMessageContent, KernelInfoRequest,
+        Status,
+    };
+
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + chrono::Duration::seconds(seconds)
+    }
+
+    /// A session's view of its kernel, fed with a scripted stream of messages.
+    #[derive(Default)]
+    struct Session {
+        busy: bool,
+        executions: HashMap<String, ExecutionTiming>,
+        watchdog: ExecutionWatchdog,
+    }
+
+    impl Session {
+        fn execute(&mut self, now: DateTime<Utc>) -> JupyterMessage {
+            let request: JupyterMessage = ExecuteRequest::default().into();
+            self.executions
+                .insert(request.header.msg_id.clone(), ExecutionTiming::new(now));
+            request
+        }
+
+        fn receive(&mut self, message: JupyterMessage, now: DateTime<Utc>) -> Vec<String> {
+            if let JupyterMessageContent::Status(status) = &message.content {
+                self.busy = status.execution_state == ExecutionState::Busy;
+            }
+            let mut lost = Vec::new();
+            if let Some(probe_sent_at) = self.watchdog.probe_answered(&message) {
+                lost = lost_executions(&self.executions, probe_sent_at);
+                self.executions.retain(|msg_id, _| !lost.contains(msg_id));
+                if self.executions.is_empty() {
+                    self.busy = false;
+                }
+            }
+            let parent_msg_id = &message.parent_header.as_ref().unwrap().msg_id;
+            if let Some(timing) = self.executions.get_mut(parent_msg_id) {
+                timing.record(&message, now);
+                if timing.is_complete() {
+                    self.executions.remove(parent_msg_id);
+                }
+            }
+            lost
+        }
+
+        fn check(&mut self, now: DateTime<Utc>) -> Option<WatchdogAction> {
+            self.watchdog
+                .check(self.busy, self.executions.values(), now, BUSY_TIMEOUT)
+        }
+
+        fn probe(&mut self, now: DateTime<Utc>) -> JupyterMessage {
+            let request: JupyterMessage = KernelInfoRequest {}.into();
+            self.watchdog.probe_sent(request.header.msg_id.clone(), now);
+            request
+        }
+    }
+
+    fn status(request: &JupyterMessage, execution_state: ExecutionState) -> JupyterMessage {
+        Status { execution_state }.as_child_of(request)
+    }
+
+    #[test]
+    fn test_lost_idle_is_recovered_by_probe() {
+        let mut session = Session::default();
+        let request = session.execute(at(0));
+        session.receive(status(&request, ExecutionState::Busy), at(1));
+        session.receive(ExecuteReply::default().as_child_of(&request), at(30));
+        // The final idle status never arrives.
+
+        assert_eq!(session.check(at(60)), None);
+        assert_eq!(session.check(at(90)), Some(WatchdogAction::Probe));
+        let probe = session.probe(at(90));
+        // No second probe while the first is outstanding.
+        assert_eq!(session.check(at(95)), None);
+
+        let lost = session.receive(status(&probe, ExecutionState::Busy), at(91));
+        assert_eq!(lost, [request.header.msg_id.clone()]);
+        session.receive(status(&probe, ExecutionState::Idle), at(91));
+        assert!(!session.busy);
+        assert!(session.executions.is_empty());
+        assert_eq!(session.check(at(200)), None);
+    }
+
+    #[test]
+    fn test_hung_kernel_escalates() {
+        let mut session = Session::default();
+        let request = session.execute(at(0));
+        session.receive(status(&request, ExecutionState::Busy), at(1));
+
+        assert_eq!(session.check(at(61)), Some(WatchdogAction::Probe));
+        let probe = session.probe(at(61));
+        assert_eq!(session.check(at(66)), None);
+        assert_eq!(session.check(at(71)), Some(WatchdogAction::Unresponsive));
+        // Escalating happens once.
+        assert_eq!(session.check(at(76)), None);
+
+        // The kernel eventually finishes, getting to the probe after the execution's own
+        // messages, so nothing is lost and it's free to be probed again later.
+        session.receive(ExecuteReply::default().as_child_of(&request), at(300));
+        session.receive(status(&request, ExecutionState::Idle), at(301));
+        let lost = session.receive(status(&probe, ExecutionState::Busy), at(302));
+        assert_eq!(lost, Vec::<String>::new());
+        session.receive(status(&probe, ExecutionState::Idle), at(302));
+        assert!(!session.busy);
+        assert!(session.executions.is_empty());
+    }
+
+    #[test]
+    fn test_only_quiet_executions_are_lost() {
+        let mut session = Session::default();
+        let first = session.execute(at(0));
+        session.receive(status(&first, ExecutionState::Busy), at(1));
+        assert_eq!(session.check(at(70)), Some(WatchdogAction::Probe));
+        let probe = session.probe(at(70));
+
+        // Output for the first execution arrives after the probe went out, and a second
+        // execution is sent after it, so neither of them is lost.
+        session.receive(ExecuteReply::default().as_child_of(&first), at(71));
+        let second = session.execute(at(72));
+        let lost = session.receive(status(&probe, ExecutionState::Busy), at(73));
+        assert_eq!(lost, Vec::<String>::new());
+        assert!(session.executions.contains_key(&first.header.msg_id));
+        assert!(session.executions.contains_key(&second.header.msg_id));
+    }
+
+    #[test]
+    fn test_idle_kernel_is_not_probed() {
+        let mut session = Session::default();
+        let request = session.execute(at(0));
+        // The kernel hasn't reported busy, so the request is just waiting to be picked up.
+        assert_eq!(session.check(at(120)), None);
+
+        session.receive(status(&request, ExecutionState::Busy), at(121));
+        assert_eq!(session.check(at(200)), Some(WatchdogAction::Probe));
+        session.probe(at(200));
+        session.receive(status(&request, ExecutionState::Idle), at(201));
+        assert_eq!(session.check(at(300)), None);
+    }
+}
@@ -2,11 +2,12 @@ use collections::HashMap;
 
 use editor::EditorSettings;
 use gpui::App;
-use settings::{RegisterSetting, Settings};
+use settings::{CustomKernelContent, RegisterSetting, Settings};
 
 #[derive(Debug, Default, RegisterSetting)]
 pub struct JupyterSettings {
     pub kernel_selections: HashMap<String, String>,
+    pub custom_kernels: Vec<CustomKernelContent>,
 }
 
 impl JupyterSettings {
@@ -23,6 +24,7 @@ impl Settings for JupyterSettings {
         let jupyter = content.editor.jupyter.clone().unwrap();
         Self {
             kernel_selections: jupyter.kernel_selections.unwrap_or_default(),
+            custom_kernels: jupyter.custom_kernels.unwrap_or_default(),
         }
     }
 }
@@ -0,0 +1,177 @@
+//! The debug adapter the debugger UI uses for Jupyter kernels. There's no adapter process to
+//! start: the REPL session listens on a local port for the UI to connect to, and relays its
+//! messages through a [`KernelDebugger`](crate::kernel_debugger::KernelDebugger).
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result, bail};
+use async_trait::async_trait;
+use collections::HashMap;
+use dap::{
+    StartDebuggingRequestArguments, StartDebuggingRequestArgumentsRequest,
+    adapters::{
+        DapDelegate, DebugAdapter, DebugAdapterBinary, DebugAdapterName, DebugTaskDefinition,
+        TcpArguments,
+    },
+};
+use futures::{
+    AsyncBufRead, AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, StreamExt as _,
+    channel::mpsc, io::BufReader,
+};
+use gpui::{AsyncApp, WeakEntity};
+use serde_json::{Value, json};
+use smol::net::TcpListener;
+use task::{DebugScenario, ZedDebugConfig};
+
+use crate::Session;
+
+pub struct JupyterKernelDebugAdapter;
+
+impl JupyterKernelDebugAdapter {
+    pub const ADAPTER_NAME: &'static str = "Jupyter Kernel";
+}
+
+#[async_trait(?Send)]
+impl DebugAdapter for JupyterKernelDebugAdapter {
+    fn name(&self) -> DebugAdapterName {
+        DebugAdapterName(Self::ADAPTER_NAME.into())
+    }
+
+    async fn config_from_zed_format(&self, _: ZedDebugConfig) -> Result<DebugScenario> {
+        bail!("Jupyter kernels are debugged from their REPL session, with `repl: debug kernel`")
+    }
+
+    async fn request_kind(&self, _: &Value) -> Result<StartDebuggingRequestArgumentsRequest> {
+        Ok(StartDebuggingRequestArgumentsRequest::Attach)
+    }
+
+    fn dap_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "request": {
+                    "type": "string",
+                    "enum": ["attach"],
+                    "description": "Attach to the kernel of a REPL session"
+                }
+            }
+        })
+    }
+
+    async fn get_binary(
+        &self,
+        _: &Arc<dyn DapDelegate>,
+        config: &DebugTaskDefinition,
+        _: Option<PathBuf>,
+        _: Option<Vec<String>>,
+        _: Option<HashMap<String, String>>,
+        _: &mut AsyncApp,
+    ) -> Result<DebugAdapterBinary> {
+        let connection = config
+            .tcp_connection
+            .as_ref()
+            .and_then(|connection| {
+                Some(TcpArguments {
+                    host: connection.host(),
+                    port: connection.port?,
+                    timeout: connection.timeout,
+                })
+            })
+            .context("Jupyter kernels can only be debugged from their REPL session")?;
+
+        Ok(DebugAdapterBinary {
+            command: None,
+            arguments: Vec::new(),
+            envs: HashMap::default(),
+            cwd: None,
+            connection: Some(connection),
+            request_args: StartDebuggingRequestArguments {
+                request: StartDebuggingRequestArgumentsRequest::Attach,
+                configuration: config.config.clone(),
+            },
+        })
+    }
+}
+
+/// Accepts the debugger UI's connection, then relays messages between it and the session until
+/// it disconnects.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    mut messages_to_client: mpsc::UnboundedReceiver<Value>,
+    session: WeakEntity<Session>,
+    cx: &mut AsyncApp,
+) -> Result<()> {
+    let (stream, _) = listener.accept().await?;
+
+    let mut writer = stream.clone();
+    let _writer = cx.background_spawn(async move {
+        while let Some(message) = messages_to_client.next().await {
+            if writer.write_all(&encode_message(&message)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut reader = BufReader::new(stream);
+    while let Some(message) = read_message(&mut reader).await? {
+        session.update(cx, |session, cx| {
+            session.handle_debug_client_message(message, cx)
+        })?;
+    }
+    Ok(())
+}
+
+fn encode_message(message: &Value) -> Vec<u8> {
+    let content = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{content}", content.len()).into_bytes()
+}
+
+/// Reads a message framed the way the protocol frames them, or `None` once the connection is
+/// closed.
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(("Content-Length", value)) = header.split_once(": ") {
+            content_length = Some(value.parse::<usize>().context("invalid content length")?);
+        }
+    }
+
+    let mut content = vec![0; content_length.context("missing content length")?];
+    reader.read_exact(&mut content).await?;
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_framing() {
+        let messages = [
+            json!({ "seq": 1, "type": "request", "command": "initialize" }),
+            json!({ "seq": 2, "type": "event", "event": "output", "body": { "output": "é\r\n" } }),
+        ];
+        let bytes = messages.iter().flat_map(encode_message).collect::<Vec<_>>();
+
+        let mut reader = BufReader::new(bytes.as_slice());
+        smol::block_on(async {
+            for message in &messages {
+                assert_eq!(
+                    read_message(&mut reader).await.unwrap().as_ref(),
+                    Some(message)
+                );
+            }
+            assert_eq!(read_message(&mut reader).await.unwrap(), None);
+        });
+    }
+}
@@ -0,0 +1,666 @@
+//! Translates between the Debug Adapter Protocol spoken by the debugger UI and the one spoken by
+//! Jupyter kernels.
+//!
+//! Kernels that can be debugged (ipykernel 6 and later, which report `debugger: true` in their
+//! kernel info) accept DAP requests wrapped in `debug_request` messages on the control channel,
+//! answer them with `debug_reply`, and send DAP events as `debug_event` messages on iopub. Code
+//! run in the kernel isn't compiled from the editor's file, though, but from a temporary file per
+//! cell, which the kernel writes when asked to `dumpCell`. So breakpoints are moved into those
+//! files before a cell runs, and stack frames and other locations are moved back into the
+//! editor's file on their way to the UI.
+
+use collections::HashMap;
+use runtimelib::{DebugRequest, JupyterMessage, JupyterMessageContent};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+/// A message produced by the [`KernelDebugger`], for whoever it's addressed to.
+#[derive(Debug)]
+pub enum DebugMessage {
+    /// A `debug_request` for the kernel's control channel.
+    Kernel(JupyterMessage),
+    /// A DAP response or event for the debugger UI.
+    Client(Value),
+    /// An execute request that was held back until the breakpoints in its cell were set.
+    Execute(JupyterMessage),
+}
+
+/// Whether the message is one the [`KernelDebugger`] handles.
+pub fn is_debug_message(message: &JupyterMessage) -> bool {
+    matches!(message.content, JupyterMessageContent::DebugReply(_))
+        || message.message_type() == "debug_event"
+}
+
+pub struct KernelDebugger {
+    /// The editor's file, which the debugger UI sets breakpoints in.
+    path: PathBuf,
+    next_seq: u64,
+    /// Requests sent to the kernel, by the message id of their `debug_request`.
+    pending: HashMap<String, PendingRequest>,
+    /// `setBreakpoints` requests from the debugger UI that are waiting on the kernel, by id.
+    breakpoint_requests: HashMap<u64, BreakpointRequest>,
+    next_breakpoint_request_id: u64,
+    /// The breakpoints the debugger UI set in the editor's file, as DAP `SourceBreakpoint`s.
+    breakpoints: Vec<Value>,
+    cells: Vec<Cell>,
+}
+
+/// Code that was run in the kernel while debugging.
+#[derive(Clone, Debug)]
+struct Cell {
+    /// The temporary file the kernel compiled the cell from.
+    source_path: String,
+    /// The row in the editor's file that the cell's first line came from.
+    start_row: u32,
+    line_count: u32,
+}
+
+impl Cell {
+    /// Returns the line in the cell for a line in the editor's file. Lines are 1-based, as in
+    /// the protocol.
+    fn cell_line(&self, line: u64) -> Option<u64> {
+        let start_row = u64::from(self.start_row);
+        (start_row + 1..=start_row + u64::from(self.line_count))
+            .contains(&line)
+            .then(|| line - start_row)
+    }
+}
+
+#[derive(Debug)]
+enum PendingRequest {
+    /// A request from the debugger UI, whose response goes back under the UI's `seq`.
+    Client { seq: u64 },
+    /// A `dumpCell` for a cell that's about to run.
+    DumpCell {
+        start_row: u32,
+        line_count: u32,
+        execute: JupyterMessage,
+    },
+    /// A `setBreakpoints` in a cell's temporary file.
+    CellBreakpoints {
+        /// The indices in `breakpoints` of the breakpoints that were sent, in order.
+        indices: Vec<usize>,
+        /// The `setBreakpoints` request from the debugger UI this is part of, if any.
+        request_id: Option<u64>,
+        execute: Option<JupyterMessage>,
+    },
+}
+
+struct BreakpointRequest {
+    seq: u64,
+    /// The kernel's answer for each of the UI's breakpoints, for those in cells that ran.
+    results: Vec<Option<Value>>,
+    remaining_cells: usize,
+}
+
+impl KernelDebugger {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            next_seq: 1,
+            pending: HashMap::default(),
+            breakpoint_requests: HashMap::default(),
+            next_breakpoint_request_id: 0,
+            breakpoints: Vec::new(),
+            cells: Vec::new(),
+        }
+    }
+
+    /// Handles a message from the debugger UI.
+    pub fn handle_client_message(&mut self, message: Value) -> Vec<DebugMessage> {
+        if message["type"] != "request" {
+            return Vec::new();
+        }
+        let seq = message["seq"].as_u64().unwrap_or_default();
+        let command = message["command"].as_str().unwrap_or_default().to_string();
+        let mut arguments = message
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        if command == "setBreakpoints" && self.is_editor_source(&arguments["source"]) {
+            return self.set_breakpoints(seq, &arguments);
+        }
+
+        self.move_to_cell(&mut arguments);
+        vec![self.kernel_request(&command, arguments, PendingRequest::Client { seq })]
+    }
+
+    /// Asks the kernel for the file it will compile `code` from, and sets the breakpoints in it,
+    /// before releasing `execute` to run the code. `start_row` is where the code starts in the
+    /// editor's file.
+    pub fn prepare_cell(
+        &mut self,
+        code: &str,
+        start_row: u32,
+        execute: JupyterMessage,
+    ) -> Vec<DebugMessage> {
+        let line_count = code.lines().count().max(1) as u32;
+        vec![self.kernel_request(
+            "dumpCell",
+            json!({ "code": code }),
+            PendingRequest::DumpCell {
+                start_row,
+                line_count,
+                execute,
+            },
+        )]
+    }
+
+    /// Handles a `debug_reply` or `debug_event` from the kernel.
+    pub fn handle_kernel_message(&mut self, message: &JupyterMessage) -> Vec<DebugMessage> {
+        match &message.content {
+            JupyterMessageContent::DebugReply(reply) => {
+                let Some(pending) = message
+                    .parent_header
+                    .as_ref()
+                    .and_then(|parent| self.pending.remove(&parent.msg_id))
+                else {
+                    return Vec::new();
+                };
+                self.handle_reply(pending, reply.content.clone())
+            }
+            content if message.message_type() == "debug_event" => {
+                let Ok(mut event) = serde_json::to_value(content) else {
+                    return Vec::new();
+                };
+                if !event.is_object() {
+                    return Vec::new();
+                }
+                if let Some(body) = event.get_mut("body") {
+                    self.move_to_editor(body);
+                }
+                event["seq"] = self.next_seq().into();
+                vec![DebugMessage::Client(event)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn handle_reply(&mut self, pending: PendingRequest, mut reply: Value) -> Vec<DebugMessage> {
+        if !reply.is_object() {
+            reply = json!({ "success": false });
+        }
+        let success = reply["success"].as_bool().unwrap_or(false);
+        if let Some(body) = reply.get_mut("body") {
+            self.move_to_editor(body);
+        }
+
+        match pending {
+            PendingRequest::Client { seq } => {
+                reply["seq"] = self.next_seq().into();
+                reply["request_seq"] = seq.into();
+                vec![DebugMessage::Client(reply)]
+            }
+            PendingRequest::DumpCell {
+                start_row,
+                line_count,
+                execute,
+            } => {
+                let Some(source_path) = reply["body"]["sourcePath"].as_str().filter(|_| success)
+                else {
+                    log::warn!(
+                        "kernel failed to dump a cell for debugging: {}",
+                        reply["message"]
+                    );
+                    return vec![DebugMessage::Execute(execute)];
+                };
+                let cell = Cell {
+                    source_path: source_path.to_string(),
+                    start_row,
+                    line_count,
+                };
+
+                // Code that was run again, or overwritten by other code, won't be run from its
+                // old file anymore.
+                let end_row = start_row + line_count;
+                self.cells.retain(|existing| {
+                    existing.source_path != cell.source_path
+                        && (existing.start_row + existing.line_count <= start_row
+                            || existing.start_row >= end_row)
+                });
+                self.cells.push(cell.clone());
+
+                vec![self.set_cell_breakpoints(&cell, None, Some(execute))]
+            }
+            PendingRequest::CellBreakpoints {
+                indices,
+                request_id,
+                execute,
+            } => {
+                let mut messages = Vec::new();
+                if let Some(request_id) = request_id
+                    && let Some(request) = self.breakpoint_requests.get_mut(&request_id)
+                {
+                    let breakpoints = reply["body"]["breakpoints"]
+                        .as_array()
+                        .cloned()
+                        .unwrap_or_default();
+                    for (index, breakpoint) in indices.into_iter().zip(breakpoints) {
+                        if let Some(result) = request.results.get_mut(index) {
+                            *result = Some(breakpoint);
+                        }
+                    }
+                    request.remaining_cells = request.remaining_cells.saturating_sub(1);
+                    if request.remaining_cells == 0
+                        && let Some(request) = self.breakpoint_requests.remove(&request_id)
+                    {
+                        messages.push(self.breakpoints_response(request.seq, request.results));
+                    }
+                }
+                messages.extend(execute.map(DebugMessage::Execute));
+                messages
+            }
+        }
+    }
+
+    fn set_breakpoints(&mut self, seq: u64, arguments: &Value) -> Vec<DebugMessage> {
+        self.breakpoints = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let results = vec![None; self.breakpoints.len()];
+        if self.cells.is_empty() {
+            return vec![self.breakpoints_response(seq, results)];
+        }
+
+        let request_id = self.next_breakpoint_request_id;
+        self.next_breakpoint_request_id += 1;
+        self.breakpoint_requests.insert(
+            request_id,
+            BreakpointRequest {
+                seq,
+                results,
+                remaining_cells: self.cells.len(),
+            },
+        );
+        self.cells
+            .clone()
+            .iter()
+            .map(|cell| self.set_cell_breakpoints(cell, Some(request_id), None))
+            .collect()
+    }
+
+    fn set_cell_breakpoints(
+        &mut self,
+        cell: &Cell,
+        request_id: Option<u64>,
+        execute: Option<JupyterMessage>,
+    ) -> DebugMessage {
+        let mut indices = Vec::new();
+        let mut breakpoints = Vec::new();
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            if let Some(line) = breakpoint["line"]
+                .as_u64()
+                .and_then(|line| cell.cell_line(line))
+            {
+                let mut breakpoint = breakpoint.clone();
+                breakpoint["line"] = line.into();
+                indices.push(index);
+                breakpoints.push(breakpoint);
+            }
+        }
+
+        self.kernel_request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": cell.source_path },
+                "breakpoints": breakpoints,
+                "sourceModified": false,
+            }),
+            PendingRequest::CellBreakpoints {
+                indices,
+                request_id,
+                execute,
+            },
+        )
+    }
+
+    /// Answers a `setBreakpoints` request from the debugger UI. Breakpoints in code that hasn't
+    /// been run while debugging can't be set yet, since the kernel has no file for it.
+    fn breakpoints_response(&mut self, seq: u64, results: Vec<Option<Value>>) -> DebugMessage {
+        let breakpoints = results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    json!({
+                        "verified": false,
+                        "message": "Run this code to stop at the breakpoint",
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        DebugMessage::Client(json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": seq,
+            "success": true,
+            "command": "setBreakpoints",
+            "body": { "breakpoints": breakpoints },
+        }))
+    }
+
+    fn kernel_request(
+        &mut self,
+        command: &str,
+        arguments: Value,
+        pending: PendingRequest,
+    ) -> DebugMessage {
+        let message = JupyterMessage::new(
+            DebugRequest {
+                content: json!({
+                    "seq": self.next_seq(),
+                    "type": "request",
+                    "command": command,
+                    "arguments": arguments,
+                }),
+            },
+            None,
+        );
+        self.pending.insert(message.header.msg_id.clone(), pending);
+        DebugMessage::Kernel(message)
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    fn is_editor_source(&self, source: &Value) -> bool {
+        source["path"]
+            .as_str()
+            .is_some_and(|path| Path::new(path) == self.path)
+    }
+
+    /// Moves a location in the arguments of a request from the editor's file into the cell
+    /// that was run from it.
+    fn move_to_cell(&self, arguments: &mut Value) {
+        if !self.is_editor_source(&arguments["source"]) {
+            return;
+        }
+        let Some(line) = arguments["line"].as_u64() else {
+            return;
+        };
+        let Some(cell) = self
+            .cells
+            .iter()
+            .rev()
+            .find(|cell| cell.cell_line(line).is_some())
+        else {
+            return;
+        };
+
+        let start_row = u64::from(cell.start_row);
+        arguments["source"] = json!({ "path": cell.source_path });
+        for key in ["line", "endLine"] {
+            if let Some(line) = arguments[key].as_u64() {
+                arguments[key] = line.saturating_sub(start_row).max(1).into();
+            }
+        }
+    }
+
+    /// Moves every location in a response or event body that's in a cell's file back to where
+    /// the cell came from in the editor's file.
+    fn move_to_editor(&self, value: &mut Value) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    self.move_to_editor(item);
+                }
+            }
+            Value::Object(object) => {
+                let start_row = object
+                    .get("source")
+                    .and_then(|source| source.get("path"))
+                    .and_then(Value::as_str)
+                    .and_then(|path| self.cells.iter().find(|cell| cell.source_path == path))
+                    .map(|cell| u64::from(cell.start_row));
+                if let Some(start_row) = start_row {
+                    object.insert(
+                        "source".into(),
+                        json!({
+                            "name": self.path.file_name().map(|name| name.to_string_lossy()),
+                            "path": self.path.to_string_lossy(),
+                        }),
+                    );
+                    for key in ["line", "endLine"] {
+                        if let Some(line) = object.get(key).and_then(Value::as_u64) {
+                            object.insert(key.into(), (line + start_row).into());
+                        }
+                    }
+                }
+                for value in object.values_mut() {
+                    self.move_to_editor(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runtimelib::{DebugReply, UnknownMessage};
+    use util::path;
+
+    const CELL_PATH: &str = "/tmp/ipykernel_42/3141592.py";
+
+    /// Everything the debugger sent while the fake kernel answered its requests.
+    #[derive(Default)]
+    struct Transcript {
+        kernel: Vec<Value>,
+        client: Vec<Value>,
+        executed: Vec<String>,
+    }
+
+    /// Plays the part of a kernel, answering each request the way ipykernel would, until the
+    /// debugger has nothing more to send it.
+    fn run(debugger: &mut KernelDebugger, mut messages: Vec<DebugMessage>) -> Transcript {
+        let mut transcript = Transcript::default();
+        while !messages.is_empty() {
+            for message in std::mem::take(&mut messages) {
+                match message {
+                    DebugMessage::Kernel(request) => {
+                        let JupyterMessageContent::DebugRequest(DebugRequest { content }) =
+                            &request.content
+                        else {
+                            panic!("expected a debug request, got {:?}", request.content);
+                        };
+                        transcript.kernel.push(content.clone());
+                        let reply = JupyterMessage::new(
+                            DebugReply {
+                                content: fake_kernel_response(content),
+                            },
+                            Some(&request),
+                        );
+                        messages.extend(debugger.handle_kernel_message(&reply));
+                    }
+                    DebugMessage::Client(message) => transcript.client.push(message),
+                    DebugMessage::Execute(request) => {
+                        transcript.executed.push(request.header.msg_id.clone())
+                    }
+                }
+            }
+        }
+        transcript
+    }
+
+    fn fake_kernel_response(request: &Value) -> Value {
+        let arguments = &request["arguments"];
+        let body = match request["command"].as_str().unwrap() {
+            "initialize" => json!({ "supportsConfigurationDoneRequest": true }),
+            "dumpCell" => json!({ "sourcePath": CELL_PATH }),
+            "setBreakpoints" => json!({
+                "breakpoints": arguments["breakpoints"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|breakpoint| json!({
+                        "verified": true,
+                        "line": breakpoint["line"],
+                        "source": arguments["source"],
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            "stackTrace" => json!({
+                "stackFrames": [{
+                    "id": 7,
+                    "name": "<module>",
+                    "line": 2,
+                    "column": 1,
+                    "source": { "path": CELL_PATH },
+                }],
+                "totalFrames": 1,
+            }),
+            "continue" => json!({ "allThreadsContinued": true }),
+            _ => json!({}),
+        };
+        json!({
+            "seq": 1000,
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        })
+    }
+
+    fn kernel_event(event: &str, body: Value) -> JupyterMessage {
+        JupyterMessage::new(
+            UnknownMessage {
+                msg_type: "debug_event".to_string(),
+                content: json!({ "seq": 2000, "type": "event", "event": event, "body": body }),
+            },
+            None,
+        )
+    }
+
+    fn client_request(seq: u64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    fn send(debugger: &mut KernelDebugger, request: Value) -> Transcript {
+        let messages = debugger.handle_client_message(request);
+        run(debugger, messages)
+    }
+
+    fn receive(debugger: &mut KernelDebugger, message: JupyterMessage) -> Transcript {
+        let messages = debugger.handle_kernel_message(&message);
+        run(debugger, messages)
+    }
+
+    #[test]
+    fn test_breakpoint_hit_stack_and_continue() {
+        let file = path!("/project/analysis.py");
+        let mut debugger = KernelDebugger::new(PathBuf::from(file));
+
+        let transcript = send(
+            &mut debugger,
+            client_request(1, "initialize", json!({ "adapterID": "Jupyter Kernel" })),
+        );
+        assert_eq!(transcript.kernel[0]["command"], "initialize");
+        assert_eq!(transcript.client[0]["request_seq"], 1);
+        assert_eq!(
+            transcript.client[0]["body"]["supportsConfigurationDoneRequest"],
+            true
+        );
+
+        let transcript = send(&mut debugger, client_request(2, "attach", json!({})));
+        assert_eq!(transcript.client[0]["command"], "attach");
+        assert_eq!(transcript.client[0]["request_seq"], 2);
+
+        // Nothing has run yet, so the kernel has no file to set the breakpoints in.
+        let set_breakpoints = client_request(
+            3,
+            "setBreakpoints",
+            json!({
+                "source": { "path": file },
+                "breakpoints": [{ "line": 3 }, { "line": 12 }],
+            }),
+        );
+        let transcript = send(&mut debugger, set_breakpoints.clone());
+        assert!(transcript.kernel.is_empty());
+        assert_eq!(transcript.client[0]["request_seq"], 3);
+        assert_eq!(
+            transcript.client[0]["body"]["breakpoints"][0]["verified"],
+            false
+        );
+
+        // Running rows 1 to 3 sets the breakpoint on line 3 of the file, which is the cell's
+        // second line, before the code is executed.
+        let execute = JupyterMessage::new(
+            runtimelib::ExecuteRequest {
+                code: "x = 1\ny = x + 1\nprint(y)".to_string(),
+                ..Default::default()
+            },
+            None,
+        );
+        let execute_id = execute.header.msg_id.clone();
+        let messages = debugger.prepare_cell("x = 1\ny = x + 1\nprint(y)", 1, execute);
+        let transcript = run(&mut debugger, messages);
+        assert_eq!(transcript.kernel[0]["command"], "dumpCell");
+        assert_eq!(transcript.kernel[1]["command"], "setBreakpoints");
+        assert_eq!(
+            transcript.kernel[1]["arguments"],
+            json!({
+                "source": { "path": CELL_PATH },
+                "breakpoints": [{ "line": 2 }],
+                "sourceModified": false,
+            })
+        );
+        assert_eq!(transcript.executed, [execute_id]);
+
+        let transcript = receive(
+            &mut debugger,
+            kernel_event(
+                "stopped",
+                json!({ "reason": "breakpoint", "threadId": 1, "allThreadsStopped": true }),
+            ),
+        );
+        assert_eq!(transcript.client[0]["event"], "stopped");
+        assert_eq!(transcript.client[0]["body"]["threadId"], 1);
+
+        let transcript = send(
+            &mut debugger,
+            client_request(4, "stackTrace", json!({ "threadId": 1 })),
+        );
+        let frame = &transcript.client[0]["body"]["stackFrames"][0];
+        assert_eq!(transcript.client[0]["request_seq"], 4);
+        assert_eq!(frame["source"]["path"], file);
+        assert_eq!(frame["source"]["name"], "analysis.py");
+        assert_eq!(frame["line"], 3);
+
+        let mut transcript = send(
+            &mut debugger,
+            client_request(5, "continue", json!({ "threadId": 1 })),
+        );
+        let continued = receive(
+            &mut debugger,
+            kernel_event(
+                "continued",
+                json!({ "threadId": 1, "allThreadsContinued": true }),
+            ),
+        );
+        transcript.client.extend(continued.client);
+        assert_eq!(transcript.client[0]["command"], "continue");
+        assert_eq!(transcript.client[0]["request_seq"], 5);
+        assert_eq!(transcript.client[1]["event"], "continued");
+
+        // Now that the cell has run, setting the breakpoints again reaches the kernel.
+        let mut set_breakpoints = set_breakpoints;
+        set_breakpoints["seq"] = 6.into();
+        let transcript = send(&mut debugger, set_breakpoints);
+        assert_eq!(
+            transcript.kernel[0]["arguments"]["source"]["path"],
+            CELL_PATH
+        );
+        let breakpoints = &transcript.client[0]["body"]["breakpoints"];
+        assert_eq!(transcript.client[0]["request_seq"], 6);
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 3);
+        assert_eq!(breakpoints[0]["source"]["path"], file);
+        assert_eq!(breakpoints[1]["verified"], false);
+    }
+}
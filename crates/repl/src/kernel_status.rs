@@ -0,0 +1,827 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use gpui::{
+    Action, AnyElement, App, Context, Corner, Div, Empty, Entity, EntityId, Global, PromptLevel,
+    SharedString, Subscription, Task, WeakEntity, Window, prelude::*,
+};
+use ui::{ContextMenu, Indicator, PopoverMenu, Tooltip, prelude::*};
+use workspace::{ItemHandle, StatusItemView, Workspace};
+
+use crate::{
+    ClearOutputs, InterruptKernel, KernelSpecification, KernelStatus, RestartKernel,
+    ShutdownKernel, kernels::ResourceUsage,
+};
+
+/// How long kernel status changes are coalesced for before the summary is recomputed, so that
+/// kernels flipping between idle and busy don't re-render the status bar on every message.
+const SUMMARY_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// A kernel lifecycle command, run from the kernel menus or the `repl::*Kernel` actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KernelCommand {
+    Interrupt,
+    Restart,
+    Shutdown,
+    ClearOutputs,
+}
+
+impl KernelCommand {
+    /// Whether a kernel with the given status accepts the command.
+    pub fn is_enabled(self, status: &KernelStatus) -> bool {
+        match self {
+            Self::Interrupt => status.is_connected(),
+            Self::Restart | Self::Shutdown => !matches!(
+                status,
+                KernelStatus::Shutdown | KernelStatus::ShuttingDown | KernelStatus::Restarting
+            ),
+            Self::ClearOutputs => true,
+        }
+    }
+}
+
+/// An editor session or notebook that runs a kernel, as listed in the kernel status bar item.
+pub trait KernelOwner: Sized + 'static {
+    fn kernel_status(&self) -> KernelStatus;
+    fn kernel_specification(&self) -> Option<&KernelSpecification>;
+    fn file_name(&self, cx: &App) -> SharedString;
+    fn request_interrupt(&mut self, window: &mut Window, cx: &mut Context<Self>);
+    fn request_restart(&mut self, window: &mut Window, cx: &mut Context<Self>);
+    fn request_shutdown(&mut self, window: &mut Window, cx: &mut Context<Self>);
+    fn request_clear_outputs(&mut self, window: &mut Window, cx: &mut Context<Self>);
+
+    /// The CPU and memory used by the kernel's process, when Zed can see it.
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        None
+    }
+
+    /// The workspace item that the kernel's commands are dispatched from, when it isn't the
+    /// owner itself.
+    fn item_id(&self) -> Option<EntityId> {
+        None
+    }
+
+    /// Whether `command` can be run right now. Buttons and actions for the command are all
+    /// enabled by this, so they can't disagree.
+    fn can_run(&self, command: KernelCommand) -> bool {
+        command.is_enabled(&self.kernel_status())
+    }
+}
+
+/// Runs `command` on `owner` if it can run right now, asking for confirmation before
+/// restarting a kernel that's busy.
+pub fn run_kernel_command<T: KernelOwner>(
+    owner: &WeakEntity<T>,
+    command: KernelCommand,
+    window: &mut Window,
+    cx: &mut App,
+) {
+    let Some(status) = owner.upgrade().and_then(|owner| {
+        let owner = owner.read(cx);
+        owner.can_run(command).then(|| owner.kernel_status())
+    }) else {
+        return;
+    };
+
+    if command == KernelCommand::Restart && status == KernelStatus::Busy {
+        let answer = window.prompt(
+            PromptLevel::Warning,
+            "Restart the kernel while it's running code?",
+            Some("The current execution will be stopped and all variables will be lost."),
+            &["Restart", "Cancel"],
+            cx,
+        );
+        let owner = owner.clone();
+        window
+            .spawn(cx, async move |cx| {
+                if answer.await.ok() != Some(0) {
+                    return;
+                }
+                owner
+                    .update_in(cx, |owner, window, cx| owner.request_restart(window, cx))
+                    .ok();
+            })
+            .detach();
+        return;
+    }
+
+    owner
+        .update(cx, |owner, cx| match command {
+            KernelCommand::Interrupt => owner.request_interrupt(window, cx),
+            KernelCommand::Restart => owner.request_restart(window, cx),
+            KernelCommand::Shutdown => owner.request_shutdown(window, cx),
+            KernelCommand::ClearOutputs => owner.request_clear_outputs(window, cx),
+        })
+        .ok();
+}
+
+/// A type-erased [`KernelOwner`], held weakly so that the registry never keeps one alive.
+trait KernelOwnerHandle {
+    fn status(&self, cx: &App) -> Option<KernelStatus>;
+    fn row(&self, cx: &App) -> Option<KernelSessionRow>;
+    fn item_id(&self, cx: &App) -> Option<EntityId>;
+    fn can_run(&self, command: KernelCommand, cx: &App) -> bool;
+    fn run(&self, command: KernelCommand, window: &mut Window, cx: &mut App);
+}
+
+impl<T: KernelOwner> KernelOwnerHandle for WeakEntity<T> {
+    fn status(&self, cx: &App) -> Option<KernelStatus> {
+        Some(self.upgrade()?.read(cx).kernel_status())
+    }
+
+    fn row(&self, cx: &App) -> Option<KernelSessionRow> {
+        let owner = self.upgrade()?;
+        let owner = owner.read(cx);
+        let kernel = match owner.kernel_specification() {
+            Some(specification) => {
+                format!("{} ({})", specification.name(), specification.language()).into()
+            }
+            None => "No Kernel".into(),
+        };
+        Some(KernelSessionRow {
+            file_name: owner.file_name(cx),
+            kernel,
+            status: owner.kernel_status(),
+            resource_usage: owner.resource_usage(),
+        })
+    }
+
+    fn item_id(&self, cx: &App) -> Option<EntityId> {
+        let owner = self.upgrade()?;
+        Some(owner.read(cx).item_id().unwrap_or(owner.entity_id()))
+    }
+
+    fn can_run(&self, command: KernelCommand, cx: &App) -> bool {
+        self.upgrade()
+            .is_some_and(|owner| owner.read(cx).can_run(command))
+    }
+
+    fn run(&self, command: KernelCommand, window: &mut Window, cx: &mut App) {
+        run_kernel_command(self, command, window, cx);
+    }
+}
+
+struct KernelSessionRow {
+    file_name: SharedString,
+    kernel: SharedString,
+    status: KernelStatus,
+    resource_usage: Option<ResourceUsage>,
+}
+
+/// The kernels that aren't shut down, rolled up for the status bar.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KernelSummary {
+    pub running: usize,
+    pub busy: usize,
+    /// The most severe status among the running kernels.
+    pub worst_status: Option<KernelStatus>,
+}
+
+impl KernelSummary {
+    pub fn new(statuses: impl IntoIterator<Item = KernelStatus>) -> Self {
+        let mut summary = Self::default();
+        for status in statuses {
+            if matches!(status, KernelStatus::Shutdown) {
+                continue;
+            }
+            summary.running += 1;
+            if matches!(status, KernelStatus::Busy) {
+                summary.busy += 1;
+            }
+            if summary
+                .worst_status
+                .as_ref()
+                .is_none_or(|worst_status| severity(&status) > severity(worst_status))
+            {
+                summary.worst_status = Some(status);
+            }
+        }
+        summary
+    }
+
+    pub fn label(&self) -> SharedString {
+        let kernels = if self.running == 1 {
+            "1 kernel".to_string()
+        } else {
+            format!("{} kernels", self.running)
+        };
+        if self.busy > 0 {
+            format!("{kernels} · {} busy", self.busy).into()
+        } else {
+            kernels.into()
+        }
+    }
+}
+
+fn severity(status: &KernelStatus) -> u8 {
+    match status {
+        KernelStatus::Shutdown => 0,
+        KernelStatus::Idle => 1,
+        KernelStatus::ShuttingDown => 2,
+        KernelStatus::Starting => 3,
+        KernelStatus::Busy => 4,
+        KernelStatus::Restarting => 5,
+        KernelStatus::Unresponsive => 6,
+        KernelStatus::Error => 7,
+    }
+}
+
+fn status_color(status: &KernelStatus) -> Color {
+    match status {
+        KernelStatus::Idle => Color::Success,
+        KernelStatus::Busy | KernelStatus::Restarting | KernelStatus::Unresponsive => {
+            Color::Warning
+        }
+        KernelStatus::Starting | KernelStatus::ShuttingDown => Color::Muted,
+        KernelStatus::Error => Color::Error,
+        KernelStatus::Shutdown => Color::Disabled,
+    }
+}
+
+struct GlobalKernelSessions(Entity<KernelSessions>);
+
+impl Global for GlobalKernelSessions {}
+
+/// Every live editor session and notebook with a kernel, across all workspaces.
+pub struct KernelSessions {
+    sessions: Vec<RegisteredSession>,
+    summary: KernelSummary,
+    refresh_summary_task: Option<Task<()>>,
+}
+
+struct RegisteredSession {
+    entity_id: EntityId,
+    handle: Rc<dyn KernelOwnerHandle>,
+    _subscriptions: [Subscription; 2],
+}
+
+impl KernelSessions {
+    pub(crate) fn init(cx: &mut App) {
+        let kernel_sessions = cx.new(|_| Self {
+            sessions: Vec::new(),
+            summary: KernelSummary::default(),
+            refresh_summary_task: None,
+        });
+        cx.set_global(GlobalKernelSessions(kernel_sessions));
+    }
+
+    pub fn try_global(cx: &App) -> Option<Entity<Self>> {
+        cx.try_global::<GlobalKernelSessions>()
+            .map(|global| global.0.clone())
+    }
+
+    /// Registers the owner that's being constructed, which is dropped from the registry as soon
+    /// as the owner is released.
+    pub fn register<T: KernelOwner>(cx: &mut Context<T>) {
+        let Some(kernel_sessions) = Self::try_global(cx) else {
+            return;
+        };
+        // The entity can't be observed until its constructor has returned.
+        let owner = cx.weak_entity();
+        cx.defer(move |cx| {
+            if let Some(owner) = owner.upgrade() {
+                kernel_sessions
+                    .update(cx, |kernel_sessions, cx| kernel_sessions.insert(&owner, cx));
+            }
+        });
+    }
+
+    fn insert<T: KernelOwner>(&mut self, owner: &Entity<T>, cx: &mut Context<Self>) {
+        let entity_id = owner.entity_id();
+        let subscriptions = [
+            cx.observe(owner, |this, _, cx| this.schedule_summary_refresh(cx)),
+            cx.observe_release(owner, move |this, _, cx| this.remove(entity_id, cx)),
+        ];
+        self.sessions.push(RegisteredSession {
+            entity_id,
+            handle: Rc::new(owner.downgrade()),
+            _subscriptions: subscriptions,
+        });
+        self.refresh_summary(cx);
+    }
+
+    fn remove(&mut self, entity_id: EntityId, cx: &mut Context<Self>) {
+        self.sessions
+            .retain(|session| session.entity_id != entity_id);
+        self.refresh_summary(cx);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn summary(&self) -> &KernelSummary {
+        &self.summary
+    }
+
+    fn schedule_summary_refresh(&mut self, cx: &mut Context<Self>) {
+        if self.refresh_summary_task.is_some() {
+            return;
+        }
+        self.refresh_summary_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(SUMMARY_DEBOUNCE).await;
+            this.update(cx, |this, cx| {
+                this.refresh_summary_task = None;
+                this.refresh_summary(cx);
+            })
+            .ok();
+        }));
+    }
+
+    fn refresh_summary(&mut self, cx: &mut Context<Self>) {
+        let summary = KernelSummary::new(
+            self.sessions
+                .iter()
+                .filter_map(|session| session.handle.status(cx)),
+        );
+        if summary != self.summary {
+            self.summary = summary;
+            cx.notify();
+        }
+    }
+
+    fn handles(&self) -> Vec<Rc<dyn KernelOwnerHandle>> {
+        self.sessions
+            .iter()
+            .map(|session| session.handle.clone())
+            .collect()
+    }
+
+    fn owner_for_item(&self, item_id: EntityId, cx: &App) -> Option<Rc<dyn KernelOwnerHandle>> {
+        self.sessions
+            .iter()
+            .find(|session| session.handle.item_id(cx) == Some(item_id))
+            .map(|session| session.handle.clone())
+    }
+}
+
+/// Registers the kernel actions, which run on the kernel of the active item and are only
+/// available when that kernel can run them.
+pub(crate) fn register_workspace_actions(workspace: &mut Workspace) {
+    workspace.register_action_renderer(|div, workspace, _, cx| {
+        let Some(kernel_sessions) = KernelSessions::try_global(cx) else {
+            return div;
+        };
+        let Some(owner) = workspace
+            .active_item(cx)
+            .and_then(|item| kernel_sessions.read(cx).owner_for_item(item.item_id(), cx))
+        else {
+            return div;
+        };
+
+        let div = on_kernel_action::<InterruptKernel>(div, &owner, KernelCommand::Interrupt, cx);
+        let div = on_kernel_action::<RestartKernel>(div, &owner, KernelCommand::Restart, cx);
+        let div = on_kernel_action::<ShutdownKernel>(div, &owner, KernelCommand::Shutdown, cx);
+        on_kernel_action::<ClearOutputs>(div, &owner, KernelCommand::ClearOutputs, cx)
+    });
+}
+
+fn on_kernel_action<A: Action>(
+    div: Div,
+    owner: &Rc<dyn KernelOwnerHandle>,
+    command: KernelCommand,
+    cx: &App,
+) -> Div {
+    let owner = owner.clone();
+    div.when(owner.can_run(command, cx), |div| {
+        div.on_action(move |_: &A, window, cx| owner.run(command, window, cx))
+    })
+}
+
+fn build_menu(
+    kernel_sessions: &Entity<KernelSessions>,
+    window: &mut Window,
+    cx: &mut App,
+) -> Entity<ContextMenu> {
+    let handles = kernel_sessions.read(cx).handles();
+    ContextMenu::build(window, cx, move |mut menu, _, _| {
+        menu = menu.header("Kernels");
+        for (ix, handle) in handles.into_iter().enumerate() {
+            menu = menu.custom_row(move |_, cx| render_session_row(ix, &handle, cx));
+        }
+        menu
+    })
+}
+
+fn render_session_row(ix: usize, handle: &Rc<dyn KernelOwnerHandle>, cx: &App) -> AnyElement {
+    let Some(row) = handle.row(cx) else {
+        return Empty.into_any_element();
+    };
+    let mut description = format!("{} · {}", row.kernel, row.status.to_string());
+    if let Some(usage) = row.resource_usage {
+        description.push_str(&format!(" — {usage}"));
+    }
+    h_flex()
+        .w_full()
+        .gap_4()
+        .justify_between()
+        .child(
+            v_flex().child(Label::new(row.file_name)).child(
+                h_flex()
+                    .gap_1()
+                    .child(Indicator::dot().color(status_color(&row.status)))
+                    .child(
+                        Label::new(description)
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    ),
+            ),
+        )
+        .child(
+            h_flex()
+                .gap_0p5()
+                .child(
+                    IconButton::new(("interrupt-kernel", ix), IconName::Stop)
+                        .icon_size(IconSize::Small)
+                        .disabled(!handle.can_run(KernelCommand::Interrupt, cx))
+                        .tooltip(Tooltip::text("Interrupt Kernel"))
+                        .on_click({
+                            let handle = handle.clone();
+                            move |_, window, cx| handle.run(KernelCommand::Interrupt, window, cx)
+                        }),
+                )
+                .child(
+                    IconButton::new(("restart-kernel", ix), IconName::RotateCw)
+                        .icon_size(IconSize::Small)
+                        .disabled(!handle.can_run(KernelCommand::Restart, cx))
+                        .tooltip(Tooltip::text("Restart Kernel"))
+                        .on_click({
+                            let handle = handle.clone();
+                            move |_, window, cx| handle.run(KernelCommand::Restart, window, cx)
+                        }),
+                )
+                .child(
+                    IconButton::new(("shutdown-kernel", ix), IconName::Power)
+                        .icon_size(IconSize::Small)
+                        .disabled(!handle.can_run(KernelCommand::Shutdown, cx))
+                        .tooltip(Tooltip::text("Shut Down Kernel"))
+                        .on_click({
+                            let handle = handle.clone();
+                            move |_, window, cx| handle.run(KernelCommand::Shutdown, window, cx)
+                        }),
+                ),
+        )
+        .into_any_element()
+}
+
+/// Shows how many kernels are running in the status bar, with a menu for managing them.
+pub struct KernelStatusItem {
+    kernel_sessions: Option<Entity<KernelSessions>>,
+    _observe_kernel_sessions: Option<Subscription>,
+}
+
+impl KernelStatusItem {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let kernel_sessions = KernelSessions::try_global(cx);
+        let observe_kernel_sessions = kernel_sessions
+            .as_ref()
+            .map(|kernel_sessions| cx.observe(kernel_sessions, |_, _, cx| cx.notify()));
+        Self {
+            kernel_sessions,
+            _observe_kernel_sessions: observe_kernel_sessions,
+        }
+    }
+}
+
+impl Render for KernelStatusItem {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let Some(kernel_sessions) = self.kernel_sessions.clone() else {
+            return div().hidden();
+        };
+        let summary = kernel_sessions.read(cx).summary().clone();
+        let Some(worst_status) = summary.worst_status.as_ref() else {
+            return div().hidden();
+        };
+
+        div().child(
+            PopoverMenu::new("kernel-status-menu")
+                .menu(move |window, cx| Some(build_menu(&kernel_sessions, window, cx)))
+                .anchor(Corner::BottomRight)
+                .trigger_with_tooltip(
+                    Button::new("kernel-status", summary.label())
+                        .label_size(LabelSize::Small)
+                        .icon(IconName::ReplNeutral)
+                        .icon_position(IconPosition::Start)
+                        .icon_size(IconSize::Small)
+                        .icon_color(status_color(worst_status)),
+                    Tooltip::text("Running Kernels"),
+                ),
+        )
+    }
+}
+
+impl StatusItemView for KernelStatusItem {
+    fn set_active_pane_item(
+        &mut self,
+        _active_pane_item: Option<&dyn ItemHandle>,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::{TestAppContext, VisualTestContext};
+    use project::{FakeFs, Project};
+    use settings::SettingsStore;
+    use std::path::Path;
+    use workspace::{MultiWorkspace, item::test::TestItem};
+
+    struct TestKernelOwner {
+        status: KernelStatus,
+        item_id: Option<EntityId>,
+        commands: Vec<KernelCommand>,
+    }
+
+    impl TestKernelOwner {
+        fn new(status: KernelStatus, cx: &mut Context<Self>) -> Self {
+            KernelSessions::register(cx);
+            Self {
+                status,
+                item_id: None,
+                commands: Vec::new(),
+            }
+        }
+    }
+
+    impl KernelOwner for TestKernelOwner {
+        fn kernel_status(&self) -> KernelStatus {
+            self.status.clone()
+        }
+
+        fn kernel_specification(&self) -> Option<&KernelSpecification> {
+            None
+        }
+
+        fn file_name(&self, _cx: &App) -> SharedString {
+            "test.py".into()
+        }
+
+        fn request_interrupt(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+            self.commands.push(KernelCommand::Interrupt);
+        }
+
+        fn request_restart(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+            self.commands.push(KernelCommand::Restart);
+        }
+
+        fn request_shutdown(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+            self.commands.push(KernelCommand::Shutdown);
+        }
+
+        fn request_clear_outputs(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+            self.commands.push(KernelCommand::ClearOutputs);
+        }
+
+        fn item_id(&self) -> Option<EntityId> {
+            self.item_id
+        }
+    }
+
+    #[test]
+    fn test_command_enablement() {
+        use KernelCommand::*;
+
+        let enabled_commands = |status: KernelStatus| {
+            [Interrupt, Restart, Shutdown, ClearOutputs]
+                .into_iter()
+                .filter(|command| command.is_enabled(&status))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            enabled_commands(KernelStatus::Idle),
+            [Interrupt, Restart, Shutdown, ClearOutputs]
+        );
+        assert_eq!(
+            enabled_commands(KernelStatus::Busy),
+            [Interrupt, Restart, Shutdown, ClearOutputs]
+        );
+        assert_eq!(
+            enabled_commands(KernelStatus::Starting),
+            [Restart, Shutdown, ClearOutputs]
+        );
+        assert_eq!(
+            enabled_commands(KernelStatus::Error),
+            [Restart, Shutdown, ClearOutputs]
+        );
+        assert_eq!(
+            enabled_commands(KernelStatus::Unresponsive),
+            [Restart, Shutdown, ClearOutputs]
+        );
+        assert_eq!(enabled_commands(KernelStatus::Restarting), [ClearOutputs]);
+        assert_eq!(enabled_commands(KernelStatus::ShuttingDown), [ClearOutputs]);
+        assert_eq!(enabled_commands(KernelStatus::Shutdown), [ClearOutputs]);
+    }
+
+    #[test]
+    fn test_kernel_summary() {
+        let summary = KernelSummary::new([]);
+        assert_eq!(summary, KernelSummary::default());
+
+        let summary = KernelSummary::new([
+            KernelStatus::Idle,
+            KernelStatus::Busy,
+            KernelStatus::Shutdown,
+            KernelStatus::Busy,
+        ]);
+        assert_eq!(summary.running, 3);
+        assert_eq!(summary.busy, 2);
+        assert_eq!(summary.worst_status, Some(KernelStatus::Busy));
+        assert_eq!(summary.label().as_ref(), "3 kernels · 2 busy");
+
+        let summary = KernelSummary::new([
+            KernelStatus::Error,
+            KernelStatus::Unresponsive,
+            KernelStatus::Busy,
+        ]);
+        assert_eq!(summary.worst_status, Some(KernelStatus::Error));
+
+        let summary = KernelSummary::new([KernelStatus::Idle]);
+        assert_eq!(summary.label().as_ref(), "1 kernel");
+
+        // Kernels that were shut down aren't counted.
+        let summary = KernelSummary::new([KernelStatus::Shutdown]);
+        assert_eq!(summary.running, 0);
+        assert_eq!(summary.worst_status, None);
+    }
+
+    #[gpui::test]
+    fn test_registry_add_and_remove(cx: &mut TestAppContext) {
+        cx.update(KernelSessions::init);
+        let kernel_sessions = cx.update(|cx| KernelSessions::try_global(cx).unwrap());
+
+        let idle = cx.new(|cx| TestKernelOwner::new(KernelStatus::Idle, cx));
+        let busy = cx.new(|cx| TestKernelOwner::new(KernelStatus::Busy, cx));
+        cx.run_until_parked();
+        kernel_sessions.read_with(cx, |kernel_sessions, _| {
+            assert_eq!(kernel_sessions.len(), 2);
+            assert_eq!(
+                kernel_sessions.summary().label().as_ref(),
+                "2 kernels · 1 busy"
+            );
+        });
+
+        drop(busy);
+        cx.run_until_parked();
+        kernel_sessions.read_with(cx, |kernel_sessions, _| {
+            assert_eq!(kernel_sessions.len(), 1);
+            assert_eq!(kernel_sessions.summary().label().as_ref(), "1 kernel");
+        });
+
+        drop(idle);
+        cx.run_until_parked();
+        kernel_sessions.read_with(cx, |kernel_sessions, _| {
+            assert!(kernel_sessions.is_empty());
+            assert_eq!(kernel_sessions.summary().worst_status, None);
+        });
+    }
+
+    #[gpui::test]
+    fn test_status_changes_are_debounced(cx: &mut TestAppContext) {
+        cx.update(KernelSessions::init);
+        let kernel_sessions = cx.update(|cx| KernelSessions::try_global(cx).unwrap());
+        let owner = cx.new(|cx| TestKernelOwner::new(KernelStatus::Idle, cx));
+        cx.run_until_parked();
+
+        for status in [KernelStatus::Busy, KernelStatus::Idle, KernelStatus::Busy] {
+            owner.update(cx, |owner, cx| {
+                owner.status = status;
+                cx.notify();
+            });
+        }
+        cx.run_until_parked();
+        kernel_sessions.read_with(cx, |kernel_sessions, _| {
+            assert_eq!(kernel_sessions.summary().busy, 0);
+        });
+
+        cx.executor().advance_clock(SUMMARY_DEBOUNCE);
+        cx.run_until_parked();
+        kernel_sessions.read_with(cx, |kernel_sessions, _| {
+            assert_eq!(kernel_sessions.summary().busy, 1);
+            assert_eq!(
+                kernel_sessions.summary().worst_status,
+                Some(KernelStatus::Busy)
+            );
+        });
+    }
+
+    async fn init_workspace(cx: &mut TestAppContext) -> (Entity<Workspace>, VisualTestContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+            theme::init(theme::LoadThemes::JustBase, cx);
+            KernelSessions::init(cx);
+        });
+        let fs = FakeFs::new(cx.background_executor.clone());
+        let project = Project::test(fs, [] as [&Path; 0], cx).await;
+        let window = cx.add_window(|window, cx| MultiWorkspace::test_new(project, window, cx));
+        let workspace = window
+            .read_with(cx, |multi_workspace, _| multi_workspace.workspace().clone())
+            .unwrap();
+        workspace.update(cx, |workspace, _| register_workspace_actions(workspace));
+        (workspace, VisualTestContext::from_window(window.into(), cx))
+    }
+
+    fn add_item_with_kernel(
+        status: KernelStatus,
+        workspace: &Entity<Workspace>,
+        cx: &mut VisualTestContext,
+    ) -> (Entity<TestItem>, Entity<TestKernelOwner>) {
+        let item = cx.new(TestItem::new);
+        let owner = cx.new(|cx| TestKernelOwner {
+            item_id: Some(item.entity_id()),
+            ..TestKernelOwner::new(status, cx)
+        });
+        workspace.update_in(cx, |workspace, window, cx| {
+            workspace.add_item_to_active_pane(Box::new(item.clone()), None, true, window, cx);
+        });
+        cx.run_until_parked();
+        (item, owner)
+    }
+
+    fn activate(
+        item: &Entity<TestItem>,
+        workspace: &Entity<Workspace>,
+        cx: &mut VisualTestContext,
+    ) {
+        workspace.update_in(cx, |workspace, window, cx| {
+            workspace.activate_item(item, true, true, window, cx);
+        });
+        cx.run_until_parked();
+    }
+
+    fn take_commands(
+        owner: &Entity<TestKernelOwner>,
+        cx: &mut VisualTestContext,
+    ) -> Vec<KernelCommand> {
+        owner.update(cx, |owner, _| std::mem::take(&mut owner.commands))
+    }
+
+    #[gpui::test]
+    async fn test_actions_run_on_the_active_items_kernel(cx: &mut TestAppContext) {
+        let (workspace, mut cx) = init_workspace(cx).await;
+        let cx = &mut cx;
+        let (first_item, first_owner) = add_item_with_kernel(KernelStatus::Idle, &workspace, cx);
+        let (second_item, second_owner) = add_item_with_kernel(KernelStatus::Idle, &workspace, cx);
+
+        cx.dispatch_action(InterruptKernel);
+        cx.dispatch_action(ClearOutputs);
+        assert_eq!(take_commands(&first_owner, cx), []);
+        assert_eq!(
+            take_commands(&second_owner, cx),
+            [KernelCommand::Interrupt, KernelCommand::ClearOutputs]
+        );
+
+        activate(&first_item, &workspace, cx);
+        cx.dispatch_action(ShutdownKernel);
+        assert_eq!(take_commands(&first_owner, cx), [KernelCommand::Shutdown]);
+        assert_eq!(take_commands(&second_owner, cx), []);
+
+        // Commands the kernel's status doesn't allow aren't available.
+        first_owner.update(cx, |owner, cx| {
+            owner.status = KernelStatus::Shutdown;
+            cx.notify();
+        });
+        cx.run_until_parked();
+        cx.dispatch_action(InterruptKernel);
+        cx.dispatch_action(RestartKernel);
+        assert_eq!(take_commands(&first_owner, cx), []);
+
+        activate(&second_item, &workspace, cx);
+        cx.dispatch_action(RestartKernel);
+        assert_eq!(take_commands(&second_owner, cx), [KernelCommand::Restart]);
+    }
+
+    #[gpui::test]
+    async fn test_restarting_a_busy_kernel_asks_for_confirmation(cx: &mut TestAppContext) {
+        let (workspace, mut cx) = init_workspace(cx).await;
+        let cx = &mut cx;
+        let (_item, owner) = add_item_with_kernel(KernelStatus::Busy, &workspace, cx);
+
+        cx.dispatch_action(RestartKernel);
+        assert!(cx.has_pending_prompt());
+        cx.simulate_prompt_answer("Cancel");
+        cx.run_until_parked();
+        assert_eq!(take_commands(&owner, cx), []);
+
+        cx.dispatch_action(RestartKernel);
+        cx.simulate_prompt_answer("Restart");
+        cx.run_until_parked();
+        assert_eq!(take_commands(&owner, cx), [KernelCommand::Restart]);
+
+        owner.update(cx, |owner, cx| {
+            owner.status = KernelStatus::Idle;
+            cx.notify();
+        });
+        cx.run_until_parked();
+        cx.dispatch_action(RestartKernel);
+        assert!(!cx.has_pending_prompt());
+        assert_eq!(take_commands(&owner, cx), [KernelCommand::Restart]);
+    }
+}
@@ -0,0 +1,392 @@
+//! Installs ipykernel into a Python environment that doesn't have it, so the environment can be
+//! used as a kernel.
+
+use std::{fmt, path::PathBuf};
+
+use anyhow::{Context as _, Result};
+use futures::{
+    AsyncBufReadExt as _, StreamExt as _,
+    channel::mpsc,
+    future::{BoxFuture, Either},
+    io::BufReader,
+};
+
+use super::PythonEnvKernelSpecification;
+
+/// A program to run along with its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl InstallCommand {
+    fn new(program: impl Into<String>, args: &[&str]) -> Self {
+        Self {
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+impl fmt::Display for InstallCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The output of a command that ran to completion, with stdout and stderr interleaved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub output: String,
+}
+
+/// Runs the commands that install and probe for ipykernel, so tests can script their results.
+pub trait CommandRunner: Send + Sync {
+    /// Runs `command`, sending each line it prints to `output_tx` as it comes in.
+    fn run(
+        &self,
+        command: &InstallCommand,
+        output_tx: mpsc::UnboundedSender<String>,
+    ) -> BoxFuture<'static, Result<CommandOutput>>;
+}
+
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(
+        &self,
+        command: &InstallCommand,
+        output_tx: mpsc::UnboundedSender<String>,
+    ) -> BoxFuture<'static, Result<CommandOutput>> {
+        let command = command.clone();
+        Box::pin(async move {
+            let mut process = util::command::new_command(&command.program)
+                .args(&command.args)
+                .stdout(util::command::Stdio::piped())
+                .stderr(util::command::Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| format!("failed to run `{command}`"))?;
+
+            let stdout_lines = match process.stdout.take() {
+                Some(stdout) => Either::Left(BufReader::new(stdout).lines()),
+                None => Either::Right(futures::stream::empty()),
+            };
+            let stderr_lines = match process.stderr.take() {
+                Some(stderr) => Either::Left(BufReader::new(stderr).lines()),
+                None => Either::Right(futures::stream::empty()),
+            };
+            let mut lines = futures::stream::select(stdout_lines, stderr_lines);
+            let mut output = String::new();
+            while let Some(Ok(line)) = lines.next().await {
+                output.push_str(&line);
+                output.push('\n');
+                output_tx.unbounded_send(line).ok();
+            }
+
+            let status = process.status().await?;
+            Ok(CommandOutput {
+                success: status.success(),
+                output,
+            })
+        })
+    }
+}
+
+/// The command that installs ipykernel with the tool that manages the environment: `uv pip` for
+/// uv environments, `conda` for Conda ones, and the environment's own pip otherwise.
+pub fn ipykernel_install_command(spec: &PythonEnvKernelSpecification) -> InstallCommand {
+    let python = spec.path.to_string_lossy();
+    match spec.environment_kind.as_deref() {
+        Some("uv" | "uv (Workspace)") => {
+            InstallCommand::new("uv", &["pip", "install", "--python", &python, "ipykernel"])
+        }
+        Some("Conda") => match conda_prefix(spec) {
+            Some(prefix) => InstallCommand::new(
+                "conda",
+                &[
+                    "install",
+                    "--yes",
+                    "--prefix",
+                    &prefix.to_string_lossy(),
+                    "ipykernel",
+                ],
+            ),
+            None => pip_install_command(spec),
+        },
+        _ => pip_install_command(spec),
+    }
+}
+
+fn pip_install_command(spec: &PythonEnvKernelSpecification) -> InstallCommand {
+    InstallCommand::new(
+        spec.path.to_string_lossy(),
+        &["-m", "pip", "install", "ipykernel"],
+    )
+}
+
+/// The root of a Conda environment, which holds the interpreter in `bin` on Unix and directly on
+/// Windows.
+fn conda_prefix(spec: &PythonEnvKernelSpecification) -> Option<PathBuf> {
+    let parent = spec.path.parent()?;
+    if parent.file_name().is_some_and(|name| name == "bin") {
+        parent.parent().map(PathBuf::from)
+    } else {
+        Some(parent.to_path_buf())
+    }
+}
+
+fn ipykernel_probe_command(spec: &PythonEnvKernelSpecification) -> InstallCommand {
+    InstallCommand::new(spec.path.to_string_lossy(), &["-c", "import ipykernel"])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpykernelInstallError {
+    pub message: String,
+    /// Everything the install printed.
+    pub output: String,
+    /// What to try instead, when the failure is one we recognize.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for IpykernelInstallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for IpykernelInstallError {}
+
+/// Installs ipykernel into the environment, then checks that its interpreter can import it.
+pub async fn install_ipykernel(
+    spec: &PythonEnvKernelSpecification,
+    runner: &dyn CommandRunner,
+    output_tx: mpsc::UnboundedSender<String>,
+) -> Result<(), IpykernelInstallError> {
+    let command = ipykernel_install_command(spec);
+    let install = runner
+        .run(&command, output_tx.clone())
+        .await
+        .map_err(|error| IpykernelInstallError {
+            message: format!("{error:#}"),
+            output: String::new(),
+            suggestion: None,
+        })?;
+    if !install.success {
+        let suggestion =
+            is_externally_managed(&install.output).then(|| externally_managed_suggestion(&command));
+        return Err(IpykernelInstallError {
+            message: format!("`{command}` failed"),
+            output: install.output,
+            suggestion,
+        });
+    }
+
+    let probe = runner
+        .run(&ipykernel_probe_command(spec), output_tx)
+        .await
+        .map_err(|error| IpykernelInstallError {
+            message: format!("{error:#}"),
+            output: install.output.clone(),
+            suggestion: None,
+        })?;
+    if !probe.success {
+        return Err(IpykernelInstallError {
+            message: format!(
+                "ipykernel was installed, but {} still can't import it",
+                spec.path.display()
+            ),
+            output: install.output + &probe.output,
+            suggestion: None,
+        });
+    }
+    Ok(())
+}
+
+/// Whether pip or uv refused to install because the environment is managed by the system
+/// package manager (PEP 668).
+fn is_externally_managed(output: &str) -> bool {
+    output.contains("externally-managed-environment") || output.contains("externally managed")
+}
+
+fn externally_managed_suggestion(command: &InstallCommand) -> String {
+    format!(
+        "This environment is managed by your system's package manager. Run `{}` to install \
+        into it anyway, or create a virtual environment with `uv venv` and select that instead.",
+        command.clone().arg("--break-system-packages")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jupyter_protocol::JupyterKernelspec;
+    use std::{collections::VecDeque, path::Path, sync::Mutex};
+
+    /// Returns scripted results in order and records the commands it was asked to run.
+    #[derive(Default)]
+    struct FakeCommandRunner {
+        results: Mutex<VecDeque<CommandOutput>>,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl FakeCommandRunner {
+        fn new(results: impl IntoIterator<Item = (bool, &'static str)>) -> Self {
+            let results = results
+                .into_iter()
+                .map(|(success, output)| CommandOutput {
+                    success,
+                    output: output.to_string(),
+                })
+                .collect();
+            Self {
+                results: Mutex::new(results),
+                commands: Mutex::default(),
+            }
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.commands.lock().unwrap().clone()
+        }
+    }
+
+    impl CommandRunner for FakeCommandRunner {
+        fn run(
+            &self,
+            command: &InstallCommand,
+            output_tx: mpsc::UnboundedSender<String>,
+        ) -> BoxFuture<'static, Result<CommandOutput>> {
+            self.commands.lock().unwrap().push(command.to_string());
+            let result = self.results.lock().unwrap().pop_front();
+            Box::pin(async move {
+                let result = result.context("unexpected command")?;
+                for line in result.output.lines() {
+                    output_tx.unbounded_send(line.to_string()).ok();
+                }
+                Ok(result)
+            })
+        }
+    }
+
+    fn spec(path: &str, environment_kind: Option<&str>) -> PythonEnvKernelSpecification {
+        PythonEnvKernelSpecification {
+            name: "env".to_string(),
+            path: Path::new(path).to_path_buf(),
+            kernelspec: JupyterKernelspec {
+                argv: Vec::new(),
+                display_name: "env".to_string(),
+                language: "python".to_string(),
+                interrupt_mode: None,
+                metadata: None,
+                env: None,
+            },
+            has_ipykernel: false,
+            environment_kind: environment_kind.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_install_command_for_environment_kind() {
+        let command = |path, kind| ipykernel_install_command(&spec(path, kind)).to_string();
+        assert_eq!(
+            command("/project/.venv/bin/python", Some("uv")),
+            "uv pip install --python /project/.venv/bin/python ipykernel"
+        );
+        assert_eq!(
+            command("/project/.venv/bin/python", Some("uv (Workspace)")),
+            "uv pip install --python /project/.venv/bin/python ipykernel"
+        );
+        assert_eq!(
+            command("/opt/conda/envs/science/bin/python", Some("Conda")),
+            "conda install --yes --prefix /opt/conda/envs/science ipykernel"
+        );
+        assert_eq!(
+            command("/project/.venv/bin/python", Some("venv")),
+            "/project/.venv/bin/python -m pip install ipykernel"
+        );
+        assert_eq!(
+            command("/usr/bin/python3", None),
+            "/usr/bin/python3 -m pip install ipykernel"
+        );
+    }
+
+    #[test]
+    fn test_install_probes_for_ipykernel_afterwards() {
+        let spec = spec("/project/.venv/bin/python", Some("venv"));
+        let runner =
+            FakeCommandRunner::new([(true, "Successfully installed ipykernel"), (true, "")]);
+        let (output_tx, output_rx) = mpsc::unbounded();
+        smol::block_on(install_ipykernel(&spec, &runner, output_tx)).unwrap();
+        assert_eq!(
+            runner.commands(),
+            [
+                "/project/.venv/bin/python -m pip install ipykernel",
+                "/project/.venv/bin/python -c import ipykernel",
+            ]
+        );
+        assert_eq!(
+            smol::block_on(output_rx.collect::<Vec<_>>()),
+            ["Successfully installed ipykernel"]
+        );
+
+        // An install that succeeds but leaves ipykernel unimportable still fails.
+        let runner = FakeCommandRunner::new([
+            (true, "Successfully installed ipykernel\n"),
+            (false, "ModuleNotFoundError: No module named 'ipykernel'\n"),
+        ]);
+        let error =
+            smol::block_on(install_ipykernel(&spec, &runner, mpsc::unbounded().0)).unwrap_err();
+        assert_eq!(
+            error.message,
+            "ipykernel was installed, but /project/.venv/bin/python still can't import it"
+        );
+        assert_eq!(
+            error.output,
+            "Successfully installed ipykernel\nModuleNotFoundError: No module named 'ipykernel'\n"
+        );
+    }
+
+    #[test]
+    fn test_failed_install_skips_probe_and_explains_pep_668() {
+        let spec = spec("/usr/bin/python3", Some("global"));
+        let runner = FakeCommandRunner::new([(
+            false,
+            "error: externally-managed-environment\n\n× This environment is externally managed\n",
+        )]);
+        let error =
+            smol::block_on(install_ipykernel(&spec, &runner, mpsc::unbounded().0)).unwrap_err();
+        assert_eq!(
+            runner.commands(),
+            ["/usr/bin/python3 -m pip install ipykernel"]
+        );
+        assert_eq!(
+            error.message,
+            "`/usr/bin/python3 -m pip install ipykernel` failed"
+        );
+        assert!(error.output.contains("externally-managed-environment"));
+        let suggestion = error.suggestion.unwrap();
+        assert!(
+            suggestion
+                .contains("`/usr/bin/python3 -m pip install ipykernel --break-system-packages`"),
+            "{suggestion}"
+        );
+        assert!(suggestion.contains("uv venv"), "{suggestion}");
+
+        // Other failures don't get a suggestion.
+        let runner = FakeCommandRunner::new([(false, "ERROR: No matching distribution found\n")]);
+        let error =
+            smol::block_on(install_ipykernel(&spec, &runner, mpsc::unbounded().0)).unwrap_err();
+        assert_eq!(error.suggestion, None);
+    }
+}
@@ -0,0 +1,307 @@
+use super::KernelRequestSender;
+use anyhow::{Context as _, Result};
+use jupyter_protocol::JupyterKernelspec;
+use runtimelib::InterruptRequest;
+
+/// The environment variables a kernel reads its interrupt event handle from on Windows.
+/// `IPY_INTERRUPT_EVENT` is the name older versions of ipykernel look for.
+pub const INTERRUPT_EVENT_ENV_VARS: [&str; 2] = ["JPY_INTERRUPT_EVENT", "IPY_INTERRUPT_EVENT"];
+
+/// How a kernel expects to be interrupted, as declared by `interrupt_mode` in its kernelspec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InterruptMode {
+    /// Send an `interrupt_request` on the control channel.
+    #[default]
+    Message,
+    /// Send SIGINT to the kernel on Unix, or set its interrupt event on Windows.
+    Signal,
+}
+
+impl InterruptMode {
+    pub fn from_kernelspec(kernelspec: &JupyterKernelspec) -> Self {
+        match kernelspec.interrupt_mode.as_deref() {
+            Some("signal") => Self::Signal,
+            _ => Self::Message,
+        }
+    }
+}
+
+/// An event that a Windows kernel process inherits and waits on, interrupting itself when it's set.
+pub trait InterruptEvent: Send {
+    /// The handle's value, which the kernel reads from [`INTERRUPT_EVENT_ENV_VARS`].
+    fn handle_value(&self) -> String;
+    fn set(&self) -> Result<()>;
+}
+
+/// The environment to spawn a kernel with so it can find its interrupt event.
+pub fn interrupt_event_env(event: &dyn InterruptEvent) -> Vec<(&'static str, String)> {
+    let handle_value = event.handle_value();
+    INTERRUPT_EVENT_ENV_VARS
+        .iter()
+        .map(|name| (*name, handle_value.clone()))
+        .collect()
+}
+
+/// Creates the interrupt event for a kernel that's interrupted with signals, on the platforms
+/// that use one.
+pub fn create_interrupt_event(mode: InterruptMode) -> Result<Option<Box<dyn InterruptEvent>>> {
+    if mode != InterruptMode::Signal {
+        return Ok(None);
+    }
+    #[cfg(windows)]
+    {
+        Ok(Some(Box::new(windows_event::WindowsInterruptEvent::new()?)))
+    }
+    #[cfg(not(windows))]
+    {
+        Ok(None)
+    }
+}
+
+/// How to interrupt a running native kernel, chosen when it's spawned.
+pub enum KernelInterrupt {
+    /// Send an `interrupt_request` to the kernel.
+    Message,
+    /// Send SIGINT to the process group the kernel leads.
+    ProcessGroup(u32),
+    /// Set the event the kernel inherited.
+    Event(Box<dyn InterruptEvent>),
+}
+
+impl KernelInterrupt {
+    pub fn new(
+        mode: InterruptMode,
+        process_id: u32,
+        event: Option<Box<dyn InterruptEvent>>,
+    ) -> Self {
+        match (mode, event) {
+            (InterruptMode::Message, _) => Self::Message,
+            (InterruptMode::Signal, Some(event)) => Self::Event(event),
+            (InterruptMode::Signal, None) => Self::ProcessGroup(process_id),
+        }
+    }
+
+    pub fn interrupt(&self, request_tx: &mut KernelRequestSender) -> Result<()> {
+        match self {
+            Self::Message => request_tx
+                .try_send(InterruptRequest {}.into())
+                .context("failed to send the interrupt request"),
+            Self::ProcessGroup(process_id) => interrupt_process_group(*process_id),
+            Self::Event(event) => event.set(),
+        }
+    }
+}
+
+impl std::fmt::Debug for KernelInterrupt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Message => write!(f, "Message"),
+            Self::ProcessGroup(process_id) => {
+                f.debug_tuple("ProcessGroup").field(process_id).finish()
+            }
+            Self::Event(event) => f.debug_tuple("Event").field(&event.handle_value()).finish(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn interrupt_process_group(process_id: u32) -> Result<()> {
+    // The kernel is spawned in a new session, so its process group id is its own pid.
+    let result = unsafe { libc::killpg(process_id as libc::pid_t, libc::SIGINT) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to send SIGINT to kernel process {process_id}"));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn interrupt_process_group(process_id: u32) -> Result<()> {
+    anyhow::bail!("can't send a signal to kernel process {process_id} on this platform")
+}
+
+#[cfg(windows)]
+mod windows_event {
+    use super::InterruptEvent;
+    use anyhow::{Context as _, Result};
+    use windows::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        Security::SECURITY_ATTRIBUTES,
+        System::Threading::{CreateEventW, SetEvent},
+    };
+    use windows::core::PCWSTR;
+
+    pub struct WindowsInterruptEvent(HANDLE);
+
+    // The handle is only ever used to set the event, which is safe from any thread.
+    unsafe impl Send for WindowsInterruptEvent {}
+
+    impl WindowsInterruptEvent {
+        pub fn new() -> Result<Self> {
+            // The kernel has to inherit the handle to wait on it.
+            let attributes = SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: std::ptr::null_mut(),
+                bInheritHandle: true.into(),
+            };
+            let handle =
+                unsafe { CreateEventW(Some(&raw const attributes), false, false, PCWSTR::null()) }
+                    .context("failed to create the kernel interrupt event")?;
+            Ok(Self(handle))
+        }
+    }
+
+    impl InterruptEvent for WindowsInterruptEvent {
+        fn handle_value(&self) -> String {
+            (self.0.0 as usize).to_string()
+        }
+
+        fn set(&self) -> Result<()> {
+            unsafe { SetEvent(self.0) }.context("failed to set the kernel interrupt event")
+        }
+    }
+
+    impl Drop for WindowsInterruptEvent {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) }.ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::kernel_request_channel;
+    use jupyter_protocol::JupyterMessageContent;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    struct FakeInterruptEvent {
+        set_count: Arc<AtomicUsize>,
+    }
+
+    impl InterruptEvent for FakeInterruptEvent {
+        fn handle_value(&self) -> String {
+            "1234".to_string()
+        }
+
+        fn set(&self) -> Result<()> {
+            self.set_count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn kernelspec(interrupt_mode: Option<&str>) -> JupyterKernelspec {
+        JupyterKernelspec {
+            argv: vec!["ir".to_string(), "{connection_file}".to_string()],
+            display_name: "R".to_string(),
+            language: "R".to_string(),
+            interrupt_mode: interrupt_mode.map(str::to_string),
+            metadata: None,
+            env: None,
+        }
+    }
+
+    #[test]
+    fn test_interrupt_mode_from_kernelspec() {
+        assert_eq!(
+            InterruptMode::from_kernelspec(&kernelspec(None)),
+            InterruptMode::Message
+        );
+        assert_eq!(
+            InterruptMode::from_kernelspec(&kernelspec(Some("message"))),
+            InterruptMode::Message
+        );
+        assert_eq!(
+            InterruptMode::from_kernelspec(&kernelspec(Some("signal"))),
+            InterruptMode::Signal
+        );
+    }
+
+    #[test]
+    fn test_interrupt_event() {
+        let set_count = Arc::new(AtomicUsize::new(0));
+        let event = FakeInterruptEvent {
+            set_count: set_count.clone(),
+        };
+        assert_eq!(
+            interrupt_event_env(&event),
+            [
+                ("JPY_INTERRUPT_EVENT", "1234".to_string()),
+                ("IPY_INTERRUPT_EVENT", "1234".to_string())
+            ]
+        );
+
+        // A kernel in signal mode with an event is interrupted through it, without a message.
+        let (mut request_tx, request_rx) = kernel_request_channel(1);
+        let interrupt = KernelInterrupt::new(InterruptMode::Signal, 42, Some(Box::new(event)));
+        interrupt.interrupt(&mut request_tx).unwrap();
+        interrupt.interrupt(&mut request_tx).unwrap();
+        assert_eq!(set_count.load(Ordering::SeqCst), 2);
+        assert!(request_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_message_interrupt() {
+        let set_count = Arc::new(AtomicUsize::new(0));
+        let event = FakeInterruptEvent {
+            set_count: set_count.clone(),
+        };
+        let (mut request_tx, request_rx) = kernel_request_channel(1);
+        let interrupt = KernelInterrupt::new(InterruptMode::Message, 42, Some(Box::new(event)));
+        interrupt.interrupt(&mut request_tx).unwrap();
+
+        let message = request_rx.try_recv().unwrap();
+        assert!(matches!(
+            message.content,
+            JupyterMessageContent::InterruptRequest(_)
+        ));
+        assert_eq!(set_count.load(Ordering::SeqCst), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_group_interrupt() {
+        use std::process::Stdio;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let ready = dir.path().join("ready");
+        let interrupted = dir.path().join("interrupted");
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(format!(
+            "trap 'touch \"{}\"; exit 0' INT; touch \"{}\"; while :; do sleep 0.1; done",
+            interrupted.display(),
+            ready.display()
+        ));
+        let mut child =
+            util::process::Child::spawn(command, Stdio::null(), Stdio::null(), Stdio::null())
+                .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !ready.exists() {
+            assert!(Instant::now() < deadline, "the child never became ready");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let (mut request_tx, _request_rx) = kernel_request_channel(1);
+        let interrupt = KernelInterrupt::new(InterruptMode::Signal, child.id(), None);
+        assert!(matches!(interrupt, KernelInterrupt::ProcessGroup(_)));
+        interrupt.interrupt(&mut request_tx).unwrap();
+
+        let status = loop {
+            if let Some(status) = child.try_status().unwrap() {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                child.kill().ok();
+                panic!("the child didn't exit after being interrupted");
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(status.success(), "{status:?}");
+        assert!(interrupted.exists());
+    }
+}
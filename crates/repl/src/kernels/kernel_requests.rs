@@ -0,0 +1,302 @@
+use anyhow::anyhow;
+use futures::{Sink, SinkExt as _};
+use jupyter_protocol::{JupyterMessage, JupyterMessageContent};
+use smol::channel;
+
+use super::{KernelChannel, KernelMessageInspectors};
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// How many requests can wait to be sent to a kernel when no capacity is configured for it.
+pub const DEFAULT_REQUEST_CAPACITY: usize = 100;
+
+/// Why a request couldn't be handed to a kernel. The message is handed back either way.
+#[derive(Debug)]
+pub enum KernelRequestError {
+    /// The kernel's request queue is full. The request can be sent once it drains.
+    KernelBusy(JupyterMessage),
+    /// The kernel's connection is gone, so its queue won't drain.
+    Disconnected(JupyterMessage),
+}
+
+impl KernelRequestError {
+    pub fn into_message(self) -> JupyterMessage {
+        match self {
+            Self::KernelBusy(message) | Self::Disconnected(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for KernelRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KernelBusy(message) => write!(
+                f,
+                "kernel is busy: its request queue is full, so the {} wasn't sent",
+                message.header.msg_type
+            ),
+            Self::Disconnected(message) => write!(
+                f,
+                "kernel is disconnected, so the {} wasn't sent",
+                message.header.msg_type
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KernelRequestError {}
+
+/// How full a kernel's request queue is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestQueueMetrics {
+    pub capacity: usize,
+    /// The requests waiting to be sent right now.
+    pub depth: usize,
+    /// The most requests that were ever waiting at once.
+    pub high_water_mark: usize,
+}
+
+/// Sends requests to a kernel through a bounded queue, which the kernel's routing task drains
+/// onto its sockets. Clones share the queue.
+#[derive(Clone)]
+pub struct KernelRequestSender {
+    tx: channel::Sender<JupyterMessage>,
+    high_water_mark: Arc<AtomicUsize>,
+}
+
+impl fmt::Debug for KernelRequestSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KernelRequestSender")
+            .field("metrics", &self.metrics())
+            .field("closed", &self.tx.is_closed())
+            .finish()
+    }
+}
+
+/// Creates a kernel's request queue, which holds up to `capacity` requests.
+pub fn kernel_request_channel(
+    capacity: usize,
+) -> (KernelRequestSender, channel::Receiver<JupyterMessage>) {
+    let (tx, rx) = channel::bounded(capacity.max(1));
+    (
+        KernelRequestSender {
+            tx,
+            high_water_mark: Arc::default(),
+        },
+        rx,
+    )
+}
+
+impl KernelRequestSender {
+    /// Queues a request without waiting, failing with [`KernelRequestError::KernelBusy`] when
+    /// the queue is full.
+    pub fn try_send(&mut self, message: JupyterMessage) -> Result<(), KernelRequestError> {
+        match self.tx.try_send(message) {
+            Ok(()) => {
+                self.record_depth();
+                Ok(())
+            }
+            Err(channel::TrySendError::Full(message)) => {
+                Err(KernelRequestError::KernelBusy(message))
+            }
+            Err(channel::TrySendError::Closed(message)) => {
+                Err(KernelRequestError::Disconnected(message))
+            }
+        }
+    }
+
+    /// Queues a request, waiting for room in the queue if it's full.
+    pub async fn send(&mut self, message: JupyterMessage) -> Result<(), KernelRequestError> {
+        self.tx
+            .send(message)
+            .await
+            .map_err(|error| KernelRequestError::Disconnected(error.into_inner()))?;
+        self.record_depth();
+        Ok(())
+    }
+
+    pub fn metrics(&self) -> RequestQueueMetrics {
+        RequestQueueMetrics {
+            capacity: self.tx.capacity().unwrap_or_default(),
+            depth: self.tx.len(),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn close_channel(&self) {
+        self.tx.close();
+    }
+
+    fn record_depth(&self) {
+        self.high_water_mark
+            .fetch_max(self.tx.len(), Ordering::Relaxed);
+    }
+}
+
+/// The requests a kernel's routing task couldn't send, because one of its sockets failed.
+#[derive(Debug)]
+pub struct RequestsFailed {
+    pub error: anyhow::Error,
+    /// The message ids of the request that failed to send, followed by every request that was
+    /// still queued.
+    pub msg_ids: Vec<String>,
+}
+
+/// Sends queued requests to the kernel until the queue is closed, routing control requests to
+/// the control socket and everything else to the shell socket. Each request is shown to
+/// `inspectors` just before it's sent.
+///
+/// When a socket fails, the queue is closed and drained so that nothing waits on requests that
+/// will never be sent.
+pub async fn route_requests(
+    requests: channel::Receiver<JupyterMessage>,
+    shell: impl Sink<JupyterMessage, Error = anyhow::Error>,
+    control: impl Sink<JupyterMessage, Error = anyhow::Error>,
+    inspectors: KernelMessageInspectors,
+) -> Result<(), RequestsFailed> {
+    futures::pin_mut!(shell, control);
+    while let Ok(message) = requests.recv().await {
+        let msg_id = message.header.msg_id.clone();
+        let socket = match message.content {
+            JupyterMessageContent::DebugRequest(_)
+            | JupyterMessageContent::InterruptRequest(_)
+            | JupyterMessageContent::ShutdownRequest(_) => KernelChannel::Control,
+            _ => KernelChannel::Shell,
+        };
+        inspectors.outgoing(&message, socket);
+        let result = match socket {
+            KernelChannel::Control => control.send(message).await,
+            _ => shell.send(message).await,
+        };
+        if let Err(error) = result {
+            requests.close();
+            let mut msg_ids = vec![msg_id];
+            while let Ok(message) = requests.try_recv() {
+                msg_ids.push(message.header.msg_id);
+            }
+            return Err(RequestsFailed {
+                error: anyhow!("failed to send a request on the {socket} socket: {error}"),
+                msg_ids,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{StreamExt as _, channel::mpsc, executor::block_on};
+    use runtimelib::{ExecuteRequest, InterruptRequest};
+
+    fn execute_request(code: &str) -> JupyterMessage {
+        ExecuteRequest {
+            code: code.to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn code(message: &JupyterMessage) -> &str {
+        match &message.content {
+            JupyterMessageContent::ExecuteRequest(request) => &request.code,
+            content => panic!("expected an execute request, got {content:?}"),
+        }
+    }
+
+    #[test]
+    fn test_try_send_backpressure() {
+        // A shell socket that never takes a message, like one whose kernel stopped reading.
+        let (stalled_shell, _stalled_shell_rx) = mpsc::channel::<JupyterMessage>(0);
+        let (control, _control_rx) = mpsc::unbounded::<JupyterMessage>();
+        let (mut tx, rx) = kernel_request_channel(2);
+        let mut routing = Box::pin(route_requests(
+            rx,
+            stalled_shell.sink_map_err(anyhow::Error::from),
+            control.sink_map_err(anyhow::Error::from),
+            KernelMessageInspectors::default(),
+        ));
+
+        tx.try_send(execute_request("0")).unwrap();
+        tx.try_send(execute_request("1")).unwrap();
+        // The routing task takes the first request and gets stuck sending it.
+        assert!(block_on(async { futures::poll!(&mut routing) }).is_pending());
+        assert_eq!(tx.metrics().depth, 1);
+
+        tx.try_send(execute_request("2")).unwrap();
+        let error = tx.try_send(execute_request("3")).unwrap_err();
+        assert!(
+            matches!(error, KernelRequestError::KernelBusy(_)),
+            "{error}"
+        );
+        assert_eq!(code(&error.into_message()), "3");
+        assert_eq!(
+            tx.metrics(),
+            RequestQueueMetrics {
+                capacity: 2,
+                depth: 2,
+                high_water_mark: 2,
+            }
+        );
+
+        // Polling again doesn't get the stalled request any further.
+        assert!(block_on(async { futures::poll!(&mut routing) }).is_pending());
+        assert_eq!(tx.metrics().depth, 2);
+
+        tx.close_channel();
+        assert!(matches!(
+            tx.try_send(execute_request("5")),
+            Err(KernelRequestError::Disconnected(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_socket_drains_queue() {
+        let failing_shell = futures::sink::unfold((), |(), _message: JupyterMessage| async {
+            Err::<(), _>(anyhow!("socket closed"))
+        });
+        let (control, mut control_rx) = mpsc::unbounded::<JupyterMessage>();
+        let (mut tx, rx) = kernel_request_channel(10);
+
+        tx.try_send(InterruptRequest {}.into()).unwrap();
+        let mut msg_ids = Vec::new();
+        for code in ["a", "b", "c"] {
+            let message = execute_request(code);
+            msg_ids.push(message.header.msg_id.clone());
+            tx.try_send(message).unwrap();
+        }
+        assert_eq!(tx.metrics().high_water_mark, 4);
+
+        let failed = block_on(route_requests(
+            rx,
+            failing_shell,
+            control.sink_map_err(anyhow::Error::from),
+            KernelMessageInspectors::default(),
+        ))
+        .unwrap_err();
+
+        // The interrupt went out on the control socket before the shell socket failed.
+        assert!(matches!(
+            block_on(control_rx.next()).unwrap().content,
+            JupyterMessageContent::InterruptRequest(_)
+        ));
+        assert!(
+            failed.error.to_string().contains("shell socket"),
+            "{}",
+            failed.error
+        );
+        assert_eq!(failed.msg_ids, msg_ids);
+
+        // Nothing can be queued behind the failed socket.
+        assert_eq!(tx.metrics().depth, 0);
+        assert!(matches!(
+            tx.try_send(execute_request("d")),
+            Err(KernelRequestError::Disconnected(_))
+        ));
+    }
+}
@@ -0,0 +1,219 @@
+use super::RunningKernel;
+use futures::FutureExt as _;
+use gpui::{App, Task, Window};
+use std::time::Duration;
+
+/// How long a kernel gets to come back from an in-place restart before it's relaunched instead.
+pub const IN_PLACE_RESTART_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a kernel is being restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartKind {
+    /// The kernel was sent a `shutdown_request` with `restart: true`, and comes back with the
+    /// same connection info.
+    InPlace,
+    /// The kernel was killed, and a new one is being launched with a new connection file.
+    Relaunch,
+}
+
+impl RestartKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::InPlace => "Restarting",
+            Self::Relaunch => "Relaunching",
+        }
+    }
+}
+
+pub enum RestartOutcome {
+    /// The kernel restarted in place, and this is the kernel to use from now on.
+    Restarted(Box<dyn RunningKernel>),
+    /// The kernel can't restart in place, or didn't come back in time. This is the original
+    /// kernel, for the caller to shut down before launching a new one.
+    NeedsRelaunch(Box<dyn RunningKernel>),
+}
+
+/// Restarts a kernel in place if it supports it, falling back to a relaunch when the kernel
+/// isn't back within [`IN_PLACE_RESTART_TIMEOUT`].
+pub fn restart_in_place(
+    mut kernel: Box<dyn RunningKernel>,
+    window: &mut Window,
+    cx: &mut App,
+) -> Task<RestartOutcome> {
+    let Some(restart) = kernel.restart_in_place(window, cx) else {
+        return Task::ready(RestartOutcome::NeedsRelaunch(kernel));
+    };
+    let timeout = cx.background_executor().timer(IN_PLACE_RESTART_TIMEOUT);
+    cx.spawn(async move |_| {
+        futures::select_biased! {
+            restarted = restart.fuse() => match restarted {
+                Ok(restarted) => RestartOutcome::Restarted(restarted),
+                Err(error) => {
+                    log::warn!("kernel failed to restart in place, relaunching it: {error:#}");
+                    RestartOutcome::NeedsRelaunch(kernel)
+                }
+            },
+            _ = timeout.fuse() => {
+                log::warn!(
+                    "kernel didn't come back within {IN_PLACE_RESTART_TIMEOUT:?} of restarting in place, relaunching it"
+                );
+                RestartOutcome::NeedsRelaunch(kernel)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::{KernelRequestSender, kernel_request_channel};
+    use anyhow::Result;
+    use futures::channel::{mpsc, oneshot};
+    use gpui::TestAppContext;
+    use runtimelib::{
+        ExecutionState, JupyterMessage, JupyterMessageContent, KernelInfoReply, ShutdownRequest,
+    };
+    use smol::channel::Receiver;
+    use std::path::{Path, PathBuf};
+
+    /// Restarts in place by sending a restart request and waiting for the test, which plays
+    /// the kernel's process, to hand it the kernel that came back.
+    #[derive(Debug)]
+    struct FakeKernel {
+        request_tx: KernelRequestSender,
+        stdin_tx: mpsc::Sender<JupyterMessage>,
+        working_directory: PathBuf,
+        execution_state: ExecutionState,
+        kernel_info: Option<KernelInfoReply>,
+        /// `None` for kernels that can't restart in place.
+        restarted: Option<oneshot::Receiver<FakeKernel>>,
+    }
+
+    impl FakeKernel {
+        fn new(
+            working_directory: &str,
+            restarted: Option<oneshot::Receiver<FakeKernel>>,
+        ) -> (Self, Receiver<JupyterMessage>) {
+            let (request_tx, request_rx) = kernel_request_channel(16);
+            let kernel = Self {
+                request_tx,
+                stdin_tx: mpsc::channel(1).0,
+                working_directory: PathBuf::from(working_directory),
+                execution_state: ExecutionState::Idle,
+                kernel_info: None,
+                restarted,
+            };
+            (kernel, request_rx)
+        }
+    }
+
+    impl RunningKernel for FakeKernel {
+        fn request_tx(&self) -> KernelRequestSender {
+            self.request_tx.clone()
+        }
+
+        fn stdin_tx(&self) -> mpsc::Sender<JupyterMessage> {
+            self.stdin_tx.clone()
+        }
+
+        fn working_directory(&self) -> &PathBuf {
+            &self.working_directory
+        }
+
+        fn execution_state(&self) -> &ExecutionState {
+            &self.execution_state
+        }
+
+        fn set_execution_state(&mut self, state: ExecutionState) {
+            self.execution_state = state;
+        }
+
+        fn kernel_info(&self) -> Option<&KernelInfoReply> {
+            self.kernel_info.as_ref()
+        }
+
+        fn set_kernel_info(&mut self, info: KernelInfoReply) {
+            self.kernel_info = Some(info);
+        }
+
+        fn restart_in_place(
+            &mut self,
+            _: &mut Window,
+            cx: &mut App,
+        ) -> Option<Task<Result<Box<dyn RunningKernel>>>> {
+            let restarted = self.restarted.take()?;
+            let mut request_tx = self.request_tx.clone();
+            Some(cx.spawn(async move |_| {
+                request_tx.try_send(ShutdownRequest { restart: true }.into())?;
+                let kernel = restarted.await?;
+                Ok(Box::new(kernel) as Box<dyn RunningKernel>)
+            }))
+        }
+
+        fn force_shutdown(&mut self, _: &mut Window, _: &mut App) -> Task<Result<()>> {
+            Task::ready(Ok(()))
+        }
+
+        fn kill(&mut self) {}
+    }
+
+    /// The `restart` flag of each shutdown request the kernel was sent.
+    fn shutdown_requests(rx: &Receiver<JupyterMessage>) -> Vec<bool> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|message| match message.content {
+                JupyterMessageContent::ShutdownRequest(request) => Some(request.restart),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[gpui::test]
+    async fn test_kernel_honoring_restart_comes_back_in_place(cx: &mut TestAppContext) {
+        let (restarted_tx, restarted_rx) = oneshot::channel();
+        let (kernel, requests) = FakeKernel::new("/original", Some(restarted_rx));
+        let cx = cx.add_empty_window();
+        let restart = cx.update(|window, cx| restart_in_place(Box::new(kernel), window, cx));
+        cx.run_until_parked();
+        assert_eq!(shutdown_requests(&requests), [true]);
+
+        let (restarted, _requests) = FakeKernel::new("/restarted", None);
+        restarted_tx.send(restarted).unwrap();
+        match restart.await {
+            RestartOutcome::Restarted(kernel) => {
+                assert_eq!(kernel.working_directory(), Path::new("/restarted"));
+            }
+            RestartOutcome::NeedsRelaunch(_) => panic!("expected the kernel to restart in place"),
+        }
+    }
+
+    #[gpui::test]
+    async fn test_kernel_ignoring_restart_is_relaunched(cx: &mut TestAppContext) {
+        let (_restarted_tx, restarted_rx) = oneshot::channel();
+        let (kernel, requests) = FakeKernel::new("/original", Some(restarted_rx));
+        let cx = cx.add_empty_window();
+        let mut restart = cx.update(|window, cx| restart_in_place(Box::new(kernel), window, cx));
+        cx.run_until_parked();
+        assert_eq!(shutdown_requests(&requests), [true]);
+
+        // The kernel never comes back, so it's handed back for a relaunch once the time is up.
+        cx.executor()
+            .advance_clock(IN_PLACE_RESTART_TIMEOUT - Duration::from_millis(1));
+        cx.run_until_parked();
+        assert!(futures::poll!(&mut restart).is_pending());
+        cx.executor().advance_clock(Duration::from_millis(1));
+        match restart.await {
+            RestartOutcome::NeedsRelaunch(kernel) => {
+                assert_eq!(kernel.working_directory(), Path::new("/original"));
+            }
+            RestartOutcome::Restarted(_) => panic!("expected the kernel to be relaunched"),
+        }
+
+        // Kernels that can't restart in place are relaunched without being asked to.
+        let (kernel, requests) = FakeKernel::new("/remote", None);
+        let outcome = cx
+            .update(|window, cx| restart_in_place(Box::new(kernel), window, cx))
+            .await;
+        assert!(matches!(outcome, RestartOutcome::NeedsRelaunch(_)));
+        assert!(shutdown_requests(&requests).is_empty());
+    }
+}
@@ -0,0 +1,569 @@
+//! Hooks that see every message exchanged with a kernel, for debugging the Jupyter protocol
+//! without adding log lines to the tasks that talk to it.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use gpui::{App, BackgroundExecutor};
+use jupyter_protocol::JupyterMessage;
+use serde_json::Value;
+use settings::Settings as _;
+
+use crate::ReplSettings;
+
+/// How large a kernel's trace file grows before it's moved aside and a new one is started.
+pub const MAX_TRACE_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Message contents larger than this, once serialized, are left out of the trace.
+const MAX_TRACED_CONTENT_BYTES: usize = 16 * 1024;
+
+/// Fields whose strings are kept in redacted traces, since they describe the protocol rather
+/// than the user's code or its output.
+const UNREDACTED_FIELDS: &[&str] = &[
+    "status",
+    "execution_state",
+    "name",
+    "ename",
+    "restart",
+    "protocol_version",
+    "implementation",
+    "implementation_version",
+];
+
+/// The socket a message went out or came in on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KernelChannel {
+    Shell,
+    Control,
+    Stdin,
+    IoPub,
+}
+
+impl KernelChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Shell => "shell",
+            Self::Control => "control",
+            Self::Stdin => "stdin",
+            Self::IoPub => "iopub",
+        }
+    }
+}
+
+impl fmt::Display for KernelChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessageDirection {
+    Outgoing,
+    Incoming,
+}
+
+impl MessageDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Outgoing => "outgoing",
+            Self::Incoming => "incoming",
+        }
+    }
+}
+
+/// Sees the messages sent to and received from a kernel. Inspectors only get to look, so they
+/// can't change or drop what's sent or routed.
+///
+/// Outgoing messages are seen on the background task that sends them, just before they're
+/// sent, and incoming ones just before they're routed to the session.
+pub trait KernelMessageInspector: Send + Sync {
+    fn on_outgoing(&self, _message: &JupyterMessage, _channel: KernelChannel) {}
+    fn on_incoming(&self, _message: &JupyterMessage, _channel: KernelChannel) {}
+}
+
+struct InspectorSlot {
+    inspector: Box<dyn KernelMessageInspector>,
+    panicked: AtomicBool,
+}
+
+/// The inspectors of one kernel, shared by the tasks that talk to it. Having none costs a
+/// length check per message.
+///
+/// An inspector that panics is logged and skipped from then on, so that it can't take down
+/// the kernel's connection.
+#[derive(Clone, Default)]
+pub struct KernelMessageInspectors(Arc<[InspectorSlot]>);
+
+impl KernelMessageInspectors {
+    pub fn new(inspectors: Vec<Box<dyn KernelMessageInspector>>) -> Self {
+        Self(
+            inspectors
+                .into_iter()
+                .map(|inspector| InspectorSlot {
+                    inspector,
+                    panicked: AtomicBool::new(false),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn outgoing(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.each(|inspector| inspector.on_outgoing(message, channel));
+    }
+
+    pub fn incoming(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.each(|inspector| inspector.on_incoming(message, channel));
+    }
+
+    fn each(&self, inspect: impl Fn(&dyn KernelMessageInspector)) {
+        for slot in self.0.iter() {
+            if slot.panicked.load(Ordering::Relaxed) {
+                continue;
+            }
+            if let Err(error) = crashes::recoverable_panic(|| inspect(slot.inspector.as_ref())) {
+                slot.panicked.store(true, Ordering::Relaxed);
+                log::error!("kernel message inspector stopped after a {error}");
+            }
+        }
+    }
+}
+
+/// Builds the inspectors that the `repl` settings ask for, along with the message counts that
+/// they keep for the session, if any.
+pub fn kernel_message_inspectors(
+    kernel_name: &str,
+    cx: &App,
+) -> (KernelMessageInspectors, Option<KernelMessageCounts>) {
+    let settings = ReplSettings::get_global(cx);
+    if !settings.trace_kernel_messages {
+        return (KernelMessageInspectors::default(), None);
+    }
+
+    let counts = KernelMessageCounts::default();
+    let path = paths::kernel_traces_dir().join(trace_file_name(kernel_name, Utc::now()));
+    log::info!(
+        "tracing messages of kernel {kernel_name} to {}",
+        path.display()
+    );
+    let tracer = ProtocolTracer::new(
+        path,
+        settings.redact_kernel_message_traces,
+        MAX_TRACE_FILE_BYTES,
+        cx.background_executor(),
+    );
+    (
+        KernelMessageInspectors::new(vec![Box::new(tracer), Box::new(counts.clone())]),
+        Some(counts),
+    )
+}
+
+fn trace_file_name(kernel_name: &str, now: DateTime<Utc>) -> String {
+    let kernel_name = kernel_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{kernel_name}-{}.jsonl", now.format("%Y%m%d-%H%M%S%.3f"))
+}
+
+/// What a [`KernelMessageCounts`] counts messages by.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageCountKey {
+    pub direction: MessageDirection,
+    pub channel: KernelChannel,
+    pub msg_type: String,
+}
+
+/// Counts a kernel's messages by direction, channel and type. Clones share the counts.
+#[derive(Clone, Debug, Default)]
+pub struct KernelMessageCounts(Arc<Mutex<BTreeMap<MessageCountKey, usize>>>);
+
+impl KernelMessageCounts {
+    pub fn counts(&self) -> BTreeMap<MessageCountKey, usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn total(&self) -> usize {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .sum()
+    }
+
+    fn count(&self, direction: MessageDirection, message: &JupyterMessage, channel: KernelChannel) {
+        let mut counts = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = MessageCountKey {
+            direction,
+            channel,
+            msg_type: message.header.msg_type.clone(),
+        };
+        *counts.entry(key).or_default() += 1;
+    }
+}
+
+impl KernelMessageInspector for KernelMessageCounts {
+    fn on_outgoing(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.count(MessageDirection::Outgoing, message, channel);
+    }
+
+    fn on_incoming(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.count(MessageDirection::Incoming, message, channel);
+    }
+}
+
+/// Writes a kernel's messages to a JSONL file, one line per message. The lines are formatted
+/// where the messages are seen, and written on a background task.
+pub struct ProtocolTracer {
+    redact: bool,
+    lines: smol::channel::Sender<String>,
+}
+
+impl ProtocolTracer {
+    pub fn new(path: PathBuf, redact: bool, max_bytes: u64, executor: &BackgroundExecutor) -> Self {
+        let (lines, rx) = smol::channel::unbounded::<String>();
+        executor
+            .spawn(async move {
+                let mut file = TraceFile::new(path, max_bytes);
+                while let Ok(line) = rx.recv().await {
+                    if let Err(error) = file.write_line(&line) {
+                        log::error!(
+                            "failed to write kernel trace to {}: {error}",
+                            file.path.display()
+                        );
+                        break;
+                    }
+                }
+            })
+            .detach();
+        Self { redact, lines }
+    }
+
+    fn trace(&self, direction: MessageDirection, message: &JupyterMessage, channel: KernelChannel) {
+        let line = trace_line(direction, message, channel, self.redact, Utc::now());
+        self.lines.try_send(line).ok();
+    }
+}
+
+impl KernelMessageInspector for ProtocolTracer {
+    fn on_outgoing(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.trace(MessageDirection::Outgoing, message, channel);
+    }
+
+    fn on_incoming(&self, message: &JupyterMessage, channel: KernelChannel) {
+        self.trace(MessageDirection::Incoming, message, channel);
+    }
+}
+
+fn trace_line(
+    direction: MessageDirection,
+    message: &JupyterMessage,
+    channel: KernelChannel,
+    redact: bool,
+    now: DateTime<Utc>,
+) -> String {
+    let mut content = serde_json::to_value(&message.content).unwrap_or(Value::Null);
+    if redact {
+        redact_strings(&mut content);
+    }
+    let content_len = content.to_string().len();
+    if content_len > MAX_TRACED_CONTENT_BYTES {
+        content = Value::String(format!("<{content_len} bytes left out>"));
+    }
+
+    serde_json::json!({
+        "time": now.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "direction": direction.as_str(),
+        "channel": channel.as_str(),
+        "msg_type": message.header.msg_type,
+        "msg_id": message.header.msg_id,
+        "parent_msg_id": message.parent_header.as_ref().map(|header| &header.msg_id),
+        "content": content,
+    })
+    .to_string()
+}
+
+/// Replaces every string in `value` with its length, except in [`UNREDACTED_FIELDS`], so that
+/// no code or output ends up in the trace.
+fn redact_strings(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = format!("<{} bytes>", text.len()),
+        Value::Array(values) => values.iter_mut().for_each(redact_strings),
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if !UNREDACTED_FIELDS.contains(&key.as_str()) {
+                    redact_strings(value);
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// A trace file that's moved to `<path>.1` once it reaches `max_bytes`, replacing the one
+/// moved there before.
+struct TraceFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Option<File>,
+    len: u64,
+}
+
+impl TraceFile {
+    fn new(path: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            path,
+            max_bytes,
+            file: None,
+            len: 0,
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_len = line.len() as u64 + 1;
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.len > 0 && self.len + line_len > self.max_bytes {
+            self.file = None;
+            fs::rename(&self.path, rotated_path(&self.path))?;
+            self.open()?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{line}")?;
+            self.len += line_len;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.len = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = OsString::from(path.as_os_str());
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runtimelib::{ExecuteRequest, ExecutionState, Status};
+
+    fn execute_request(code: &str) -> JupyterMessage {
+        ExecuteRequest {
+            code: code.to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn status(execution_state: ExecutionState) -> JupyterMessage {
+        Status { execution_state }.into()
+    }
+
+    /// Records what it sees as `"<name> <direction> <channel> <msg_type>"`.
+    struct Recorder {
+        name: &'static str,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Recorder {
+        fn record(&self, direction: &str, message: &JupyterMessage, channel: KernelChannel) {
+            self.seen.lock().unwrap().push(format!(
+                "{} {direction} {channel} {}",
+                self.name, message.header.msg_type
+            ));
+        }
+    }
+
+    impl KernelMessageInspector for Recorder {
+        fn on_outgoing(&self, message: &JupyterMessage, channel: KernelChannel) {
+            self.record("out", message, channel);
+        }
+
+        fn on_incoming(&self, message: &JupyterMessage, channel: KernelChannel) {
+            self.record("in", message, channel);
+        }
+    }
+
+    struct Panicker;
+
+    impl KernelMessageInspector for Panicker {
+        fn on_incoming(&self, _: &JupyterMessage, _: KernelChannel) {
+            panic!("inspector bug");
+        }
+    }
+
+    #[test]
+    fn test_inspectors_are_called_in_order() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name| {
+            Box::new(Recorder {
+                name,
+                seen: seen.clone(),
+            }) as Box<dyn KernelMessageInspector>
+        };
+        let inspectors =
+            KernelMessageInspectors::new(vec![recorder("a"), Box::new(Panicker), recorder("b")]);
+
+        inspectors.outgoing(&execute_request("1 + 1"), KernelChannel::Shell);
+        inspectors.incoming(&status(ExecutionState::Busy), KernelChannel::IoPub);
+        inspectors.incoming(&status(ExecutionState::Idle), KernelChannel::IoPub);
+
+        // The panicking inspector is skipped from its first panic on, without affecting the
+        // others.
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "a out shell execute_request",
+                "b out shell execute_request",
+                "a in iopub status",
+                "b in iopub status",
+                "a in iopub status",
+                "b in iopub status",
+            ]
+        );
+        assert!(KernelMessageInspectors::default().is_empty());
+    }
+
+    #[test]
+    fn test_message_counts() {
+        let counts = KernelMessageCounts::default();
+        let inspectors = KernelMessageInspectors::new(vec![Box::new(counts.clone())]);
+        inspectors.outgoing(&execute_request("a"), KernelChannel::Shell);
+        inspectors.outgoing(&execute_request("b"), KernelChannel::Shell);
+        inspectors.incoming(&status(ExecutionState::Idle), KernelChannel::IoPub);
+
+        let key = |direction, channel, msg_type: &str| MessageCountKey {
+            direction,
+            channel,
+            msg_type: msg_type.to_string(),
+        };
+        assert_eq!(
+            counts.counts(),
+            BTreeMap::from_iter([
+                (
+                    key(
+                        MessageDirection::Outgoing,
+                        KernelChannel::Shell,
+                        "execute_request"
+                    ),
+                    2
+                ),
+                (
+                    key(MessageDirection::Incoming, KernelChannel::IoPub, "status"),
+                    1
+                ),
+            ])
+        );
+        assert_eq!(counts.total(), 3);
+    }
+
+    #[test]
+    fn test_trace_redaction() {
+        let message = execute_request("secret_token = 'hunter2'");
+        let trace = |redact| {
+            let line = trace_line(
+                MessageDirection::Outgoing,
+                &message,
+                KernelChannel::Shell,
+                redact,
+                DateTime::UNIX_EPOCH,
+            );
+            serde_json::from_str::<Value>(&line).unwrap()
+        };
+
+        let redacted = trace(true);
+        assert!(!redacted.to_string().contains("hunter2"));
+        assert_eq!(redacted["content"]["code"], "<24 bytes>");
+        assert_eq!(redacted["msg_type"], "execute_request");
+        assert_eq!(redacted["channel"], "shell");
+        assert_eq!(redacted["direction"], "outgoing");
+        assert_eq!(redacted["msg_id"], message.header.msg_id.as_str());
+
+        assert_eq!(trace(false)["content"]["code"], "secret_token = 'hunter2'");
+
+        // Fields that describe the protocol are kept.
+        let status = trace_line(
+            MessageDirection::Incoming,
+            &status(ExecutionState::Busy),
+            KernelChannel::IoPub,
+            true,
+            DateTime::UNIX_EPOCH,
+        );
+        let status = serde_json::from_str::<Value>(&status).unwrap();
+        assert_eq!(status["content"]["execution_state"], "busy");
+    }
+
+    #[test]
+    fn test_trace_file_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traces").join("python3.jsonl");
+        let mut file = TraceFile::new(path.clone(), 10);
+
+        file.write_line("aaaa").unwrap();
+        file.write_line("bbbb").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "aaaa\nbbbb\n");
+
+        // The next line would take the file past its cap, so it starts a new one.
+        file.write_line("cccc").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cccc\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path)).unwrap(),
+            "aaaa\nbbbb\n"
+        );
+
+        // Only one rotated file is kept.
+        file.write_line("dddd").unwrap();
+        file.write_line("eeee").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "eeee\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path)).unwrap(),
+            "cccc\ndddd\n"
+        );
+    }
+
+    #[test]
+    fn test_trace_file_name() {
+        let now = DateTime::UNIX_EPOCH;
+        assert_eq!(
+            trace_file_name("Python 3 (ipykernel)", now),
+            "Python_3__ipykernel_-19700101-000000.000.jsonl"
+        );
+    }
+}
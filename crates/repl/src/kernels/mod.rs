@@ -1,5 +1,26 @@
+mod ipykernel_install;
+pub use ipykernel_install::*;
+
+mod kernel_interrupt;
+pub use kernel_interrupt::*;
+
+mod kernel_requests;
+pub use kernel_requests::*;
+
+mod kernel_restart;
+pub use kernel_restart::*;
+
+mod message_inspector;
+pub use message_inspector::*;
+
 mod native_kernel;
-use std::{fmt::Debug, future::Future, path::PathBuf};
+mod python_env_validation;
+pub use python_env_validation::*;
+use std::{
+    fmt::Debug,
+    future::Future,
+    path::{Path, PathBuf},
+};
 
 use futures::{channel::mpsc, future::Shared};
 use gpui::{App, Entity, Task, Window};
@@ -7,28 +28,38 @@ use language::LanguageName;
 use log;
 pub use native_kernel::*;
 
+mod remote_connection;
+pub use remote_connection::*;
+
 mod remote_kernels;
-use project::{Project, ProjectPath, Toolchains, WorktreeId};
+use project::{Fs, Project, ProjectPath, Toolchains, WorktreeId};
 pub use remote_kernels::*;
 
+mod resource_usage;
+pub use resource_usage::*;
+
 mod ssh_kernel;
 pub use ssh_kernel::*;
 
+mod ssh_tunnel;
+pub use ssh_tunnel::*;
+
 mod wsl_kernel;
 pub use wsl_kernel::*;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
+use db::kvp::KEY_VALUE_STORE;
 use futures::{FutureExt, StreamExt};
 use gpui::{AppContext, AsyncWindowContext, Context};
-use jupyter_protocol::{JupyterKernelspec, JupyterMessageContent};
+use jupyter_protocol::JupyterKernelspec;
 use runtimelib::{
     ClientControlConnection, ClientIoPubConnection, ClientShellConnection, ClientStdinConnection,
     ExecutionState, JupyterMessage, KernelInfoReply,
 };
 use ui::{Icon, IconName, SharedString};
-use util::rel_path::RelPath;
+use util::{ResultExt as _, rel_path::RelPath};
 
 pub fn start_kernel_tasks<S: KernelSession + 'static>(
     session: Entity<S>,
@@ -36,20 +67,59 @@ pub fn start_kernel_tasks<S: KernelSession + 'static>(
     shell_socket: ClientShellConnection,
     control_socket: ClientControlConnection,
     stdin_socket: ClientStdinConnection,
+    request_capacity: usize,
+    inspectors: KernelMessageInspectors,
     cx: &mut AsyncWindowContext,
 ) -> (
+    KernelRequestSender,
     futures::channel::mpsc::Sender<JupyterMessage>,
+) {
+    start_kernel_tasks_with_error_handler(
+        session.clone(),
+        iopub_socket,
+        shell_socket,
+        control_socket,
+        stdin_socket,
+        request_capacity,
+        inspectors,
+        move |error_message, cx| {
+            session.update(cx, |session, cx| {
+                session.kernel_errored(error_message, cx);
+                cx.notify();
+            });
+        },
+        cx,
+    )
+}
+
+/// Like [`start_kernel_tasks`], but calls `on_task_error` instead of erroring the session when
+/// one of the tasks fails, which happens when the kernel's sockets stop working.
+///
+/// Every message sent or received is shown to `inspectors` first.
+pub fn start_kernel_tasks_with_error_handler<S: KernelSession + 'static>(
+    session: Entity<S>,
+    iopub_socket: ClientIoPubConnection,
+    shell_socket: ClientShellConnection,
+    control_socket: ClientControlConnection,
+    stdin_socket: ClientStdinConnection,
+    request_capacity: usize,
+    inspectors: KernelMessageInspectors,
+    mut on_task_error: impl FnMut(String, &mut AsyncWindowContext) + 'static,
+    cx: &mut AsyncWindowContext,
+) -> (
+    KernelRequestSender,
     futures::channel::mpsc::Sender<JupyterMessage>,
 ) {
-    let (mut shell_send, shell_recv) = shell_socket.split();
-    let (mut control_send, control_recv) = control_socket.split();
+    let (shell_send, shell_recv) = shell_socket.split();
+    let (control_send, control_recv) = control_socket.split();
     let (mut stdin_send, stdin_recv) = stdin_socket.split();
 
-    let (request_tx, mut request_rx) = futures::channel::mpsc::channel::<JupyterMessage>(100);
+    let (request_tx, request_rx) = kernel_request_channel(request_capacity);
     let (stdin_tx, mut stdin_rx) = futures::channel::mpsc::channel::<JupyterMessage>(100);
 
     let recv_task = cx.spawn({
         let session = session.clone();
+        let inspectors = inspectors.clone();
         let mut iopub = iopub_socket;
         let mut shell = shell_recv;
         let mut control = control_recv;
@@ -58,13 +128,14 @@ pub fn start_kernel_tasks<S: KernelSession + 'static>(
         async move |cx| -> anyhow::Result<()> {
             loop {
                 let (channel, result) = futures::select! {
-                    msg = iopub.read().fuse() => ("iopub", msg),
-                    msg = shell.read().fuse() => ("shell", msg),
-                    msg = control.read().fuse() => ("control", msg),
-                    msg = stdin.read().fuse() => ("stdin", msg),
+                    msg = iopub.read().fuse() => (KernelChannel::IoPub, msg),
+                    msg = shell.read().fuse() => (KernelChannel::Shell, msg),
+                    msg = control.read().fuse() => (KernelChannel::Control, msg),
+                    msg = stdin.read().fuse() => (KernelChannel::Stdin, msg),
                 };
                 match result {
                     Ok(message) => {
+                        inspectors.incoming(&message, channel);
                         session
                             .update_in(cx, |session, window, cx| {
                                 session.route(&message, window, cx);
@@ -93,24 +164,45 @@ pub fn start_kernel_tasks<S: KernelSession + 'static>(
         }
     });
 
-    let routing_task = cx.background_spawn(async move {
-        while let Some(message) = request_rx.next().await {
-            match message.content {
-                JupyterMessageContent::DebugRequest(_)
-                | JupyterMessageContent::InterruptRequest(_)
-                | JupyterMessageContent::ShutdownRequest(_) => {
-                    control_send.send(message).await?;
-                }
-                _ => {
-                    shell_send.send(message).await?;
-                }
-            }
+    let shell = futures::sink::unfold(
+        shell_send,
+        |mut shell_send, message: JupyterMessage| async move {
+            shell_send.send(message).await?;
+            anyhow::Ok(shell_send)
+        },
+    );
+    let control = futures::sink::unfold(
+        control_send,
+        |mut control_send, message: JupyterMessage| async move {
+            control_send.send(message).await?;
+            anyhow::Ok(control_send)
+        },
+    );
+    let routing = cx.background_spawn(route_requests(
+        request_rx,
+        shell,
+        control,
+        inspectors.clone(),
+    ));
+    let routing_task = cx.spawn({
+        let session = session.clone();
+        async move |cx| -> anyhow::Result<()> {
+            let Err(failed) = routing.await else {
+                return Ok(());
+            };
+            let error_message = format!("{:#}", failed.error);
+            log::warn!("kernel: {error_message}");
+            session.update(cx, |session, cx| {
+                session.requests_failed(&failed.msg_ids, error_message, cx);
+                cx.notify();
+            });
+            Err(failed.error)
         }
-        anyhow::Ok(())
     });
 
     let stdin_routing_task = cx.background_spawn(async move {
         while let Some(message) = stdin_rx.next().await {
+            inspectors.outgoing(&message, KernelChannel::Stdin);
             stdin_send.send(message).await?;
         }
         anyhow::Ok(())
@@ -132,10 +224,7 @@ pub fn start_kernel_tasks<S: KernelSession + 'static>(
 
             while let Some((name, result)) = tasks.next().await {
                 if let Err(err) = result {
-                    session.update(cx, |session, cx| {
-                        session.kernel_errored(format!("handling failed for {name}: {err}"), cx);
-                        cx.notify();
-                    });
+                    on_task_error(format!("handling failed for {name}: {err}"), cx);
                 }
             }
         }
@@ -148,6 +237,14 @@ pub fn start_kernel_tasks<S: KernelSession + 'static>(
 pub trait KernelSession: Sized {
     fn route(&mut self, message: &JupyterMessage, window: &mut Window, cx: &mut Context<Self>);
     fn kernel_errored(&mut self, error_message: String, cx: &mut Context<Self>);
+    /// Called with the message ids of requests that were queued for the kernel but will never
+    /// reach it, because the socket they were going out on failed.
+    fn requests_failed(
+        &mut self,
+        msg_ids: &[String],
+        error_message: String,
+        cx: &mut Context<Self>,
+    );
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +271,7 @@ impl PythonEnvKernelSpecification {
             name: self.name.clone(),
             path: self.path.clone(),
             kernelspec: self.kernelspec.clone(),
+            source: LocalKernelSource::Kernelspec,
         }
     }
 }
@@ -232,18 +330,21 @@ impl PartialEq for WslKernelSpecification {
 impl Eq for WslKernelSpecification {}
 
 impl KernelSpecification {
+    /// Kernel names and languages are interned, since the kernel picker, status and sessions ask
+    /// for them over and over but they only ever take a handful of values.
     pub fn name(&self) -> SharedString {
-        match self {
-            Self::Jupyter(spec) => spec.name.clone().into(),
-            Self::PythonEnv(spec) => spec.name.clone().into(),
-            Self::JupyterServer(spec) => spec.name.clone().into(),
-            Self::SshRemote(spec) => spec.name.clone().into(),
-            Self::WslRemote(spec) => spec.name.clone().into(),
-        }
+        SharedString::from_arc_cow(util::intern(match self {
+            Self::Jupyter(spec) => &spec.name,
+            Self::PythonEnv(spec) => &spec.name,
+            Self::JupyterServer(spec) => &spec.name,
+            Self::SshRemote(spec) => &spec.name,
+            Self::WslRemote(spec) => &spec.name,
+        }))
     }
 
     pub fn type_name(&self) -> SharedString {
         match self {
+            Self::Jupyter(spec) if spec.is_custom() => "Custom".into(),
             Self::Jupyter(_) => "Jupyter".into(),
             Self::PythonEnv(spec) => SharedString::from(
                 spec.environment_kind
@@ -257,23 +358,42 @@ impl KernelSpecification {
     }
 
     pub fn path(&self) -> SharedString {
-        SharedString::from(match self {
-            Self::Jupyter(spec) => spec.path.to_string_lossy().into_owned(),
-            Self::PythonEnv(spec) => spec.path.to_string_lossy().into_owned(),
-            Self::JupyterServer(spec) => spec.url.to_string(),
-            Self::SshRemote(spec) => spec.path.to_string(),
-            Self::WslRemote(_) => "WSL".to_string(),
-        })
+        match self {
+            Self::Jupyter(spec) => SharedString::new(spec.path.to_string_lossy()),
+            Self::PythonEnv(spec) => SharedString::new(spec.path.to_string_lossy()),
+            Self::JupyterServer(spec) => SharedString::new(spec.url.as_str()),
+            Self::SshRemote(spec) => spec.path.clone(),
+            Self::WslRemote(_) => SharedString::new_static("WSL"),
+        }
     }
 
     pub fn language(&self) -> SharedString {
-        SharedString::from(match self {
-            Self::Jupyter(spec) => spec.kernelspec.language.clone(),
-            Self::PythonEnv(spec) => spec.kernelspec.language.clone(),
-            Self::JupyterServer(spec) => spec.kernelspec.language.clone(),
-            Self::SshRemote(spec) => spec.kernelspec.language.clone(),
-            Self::WslRemote(spec) => spec.kernelspec.language.clone(),
-        })
+        SharedString::from_arc_cow(util::intern(match self {
+            Self::Jupyter(spec) => &spec.kernelspec.language,
+            Self::PythonEnv(spec) => &spec.kernelspec.language,
+            Self::JupyterServer(spec) => &spec.kernelspec.language,
+            Self::SshRemote(spec) => &spec.kernelspec.language,
+            Self::WslRemote(spec) => &spec.kernelspec.language,
+        }))
+    }
+
+    /// The interpreter that runs this kernel, when it is known locally.
+    pub fn interpreter_path(&self) -> Option<&Path> {
+        match self {
+            Self::PythonEnv(spec) => Some(spec.path.as_path()),
+            Self::Jupyter(spec) => spec
+                .kernelspec
+                .argv
+                .first()
+                .map(Path::new)
+                .filter(|path| path.is_absolute()),
+            Self::JupyterServer(_) | Self::SshRemote(_) | Self::WslRemote(_) => None,
+        }
+    }
+
+    /// Identifies the kernel across restarts, for remembering which one was used last.
+    pub fn last_used_key(&self) -> String {
+        format!("{}@{}", self.name(), self.path())
     }
 
     pub fn has_ipykernel(&self) -> bool {
@@ -291,6 +411,7 @@ impl KernelSpecification {
                 .environment_kind
                 .as_ref()
                 .map(|kind| SharedString::from(kind.clone())),
+            Self::Jupyter(spec) if spec.is_custom() => Some("Custom".into()),
             Self::Jupyter(_) => Some("Jupyter".into()),
             Self::JupyterServer(_) => Some("Jupyter Server".into()),
             Self::SshRemote(_) => Some("SSH Remote".into()),
@@ -314,6 +435,47 @@ impl KernelSpecification {
     }
 }
 
+/// Returns whether `spec` was discovered from a kernelspec directory but runs the same
+/// interpreter as one of the toolchain-derived `python_env_specs`, which carry richer
+/// environment information and should be preferred.
+pub fn is_duplicate_kernel_specification(
+    spec: &KernelSpecification,
+    python_env_specs: &[KernelSpecification],
+) -> bool {
+    let KernelSpecification::Jupyter(_) = spec else {
+        return false;
+    };
+    let Some(interpreter) = spec.interpreter_path() else {
+        return false;
+    };
+    python_env_specs.iter().any(|other| {
+        matches!(other, KernelSpecification::PythonEnv(_))
+            && other.interpreter_path() == Some(interpreter)
+    })
+}
+
+/// Combines the kernels defined in the `jupyter.custom_kernels` setting with the discovered ones.
+/// A custom kernel replaces any discovered kernel with the same name, so that a kernelspec can be
+/// overridden without uninstalling it.
+pub fn merge_custom_kernel_specifications(
+    custom_specs: &[KernelSpecification],
+    discovered_specs: &[KernelSpecification],
+) -> Vec<KernelSpecification> {
+    let custom_names = custom_specs
+        .iter()
+        .map(KernelSpecification::name)
+        .collect::<HashSet<_>>();
+    custom_specs
+        .iter()
+        .chain(
+            discovered_specs
+                .iter()
+                .filter(|spec| !custom_names.contains(&spec.name())),
+        )
+        .cloned()
+        .collect()
+}
+
 fn extract_environment_kind(toolchain_json: &serde_json::Value) -> Option<String> {
     let kind_str = toolchain_json.get("kind")?.as_str()?;
     let label = match kind_str {
@@ -578,19 +740,233 @@ pub fn python_env_kernel_specifications(
     }
 }
 
+/// Links are followed at most this many times when resolving an interpreter, in case they form a
+/// cycle.
+const MAX_INTERPRETER_LINKS: usize = 8;
+
+/// Interpreter paths with symlinks resolved, so that an interpreter reached through two paths is
+/// recognized as the same one.
+#[derive(Debug, Default, Clone)]
+pub struct CanonicalInterpreterPaths(HashMap<PathBuf, PathBuf>);
+
+impl CanonicalInterpreterPaths {
+    /// Resolves each of `paths`. The directory containing the interpreter is resolved fully, but
+    /// the interpreter itself is only followed while it links within that directory, because a
+    /// venv's `python` links to its base interpreter and yet runs with the venv's packages.
+    pub async fn resolve(paths: impl IntoIterator<Item = PathBuf>, fs: &dyn Fs) -> Self {
+        let mut canonical_paths = HashMap::new();
+        for path in paths {
+            if canonical_paths.contains_key(&path) {
+                continue;
+            }
+            let (Some(directory), Some(file_name)) = (path.parent(), path.file_name()) else {
+                continue;
+            };
+            let Ok(canonical_directory) = fs.canonicalize(directory).await else {
+                continue;
+            };
+
+            let mut canonical_path = canonical_directory.join(file_name);
+            for _ in 0..MAX_INTERPRETER_LINKS {
+                let Ok(target) = fs.read_link(&canonical_path).await else {
+                    break;
+                };
+                let target = canonical_directory.join(target);
+                match (target.parent(), target.file_name()) {
+                    (Some(parent), Some(file_name)) if parent == canonical_directory => {
+                        canonical_path = canonical_directory.join(file_name);
+                    }
+                    _ => break,
+                }
+            }
+            canonical_paths.insert(path, canonical_path);
+        }
+        Self(canonical_paths)
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.0.extend(other.0);
+    }
+
+    /// The resolved form of `path`, or `path` itself if it wasn't resolved.
+    pub fn get<'a>(&'a self, path: &'a Path) -> &'a Path {
+        self.0.get(path).map_or(path, PathBuf::as_path)
+    }
+}
+
+/// What [`rank_kernel_specifications`] knows about the worktree the kernels are listed for.
+pub struct KernelRanking<'a> {
+    pub worktree_root: Option<&'a Path>,
+    /// The interpreter of the worktree's selected Python toolchain.
+    pub active_toolchain_path: Option<&'a str>,
+    /// The [`KernelSpecification::last_used_key`] of the kernel picked last in the worktree.
+    pub last_used_kernel: Option<&'a str>,
+    pub canonical_paths: &'a CanonicalInterpreterPaths,
+}
+
+impl KernelRanking<'_> {
+    fn canonical_interpreter<'a>(&'a self, spec: &'a KernelSpecification) -> Option<&'a Path> {
+        Some(self.canonical_paths.get(spec.interpreter_path()?))
+    }
+
+    fn is_project_local(&self, spec: &KernelSpecification) -> bool {
+        let Some(worktree_root) = self.worktree_root else {
+            return false;
+        };
+        spec.interpreter_path()
+            .is_some_and(|interpreter| interpreter.starts_with(worktree_root))
+    }
+
+    fn matches_active_toolchain(&self, spec: &KernelSpecification) -> bool {
+        let Some(active_toolchain_path) = self.active_toolchain_path else {
+            return false;
+        };
+        // Remote kernels have no local interpreter, but their path is the toolchain's.
+        spec.path().as_ref() == active_toolchain_path
+            || self.canonical_interpreter(spec)
+                == Some(self.canonical_paths.get(Path::new(active_toolchain_path)))
+    }
+
+    fn is_last_used(&self, spec: &KernelSpecification) -> bool {
+        self.last_used_kernel
+            .is_some_and(|last_used_kernel| spec.last_used_key() == last_used_kernel)
+    }
+}
+
+/// How much a Python environment kernel says about its environment, for choosing between
+/// kernels that run the same interpreter.
+fn descriptiveness(spec: &PythonEnvKernelSpecification) -> (bool, bool, usize) {
+    (
+        spec.environment_kind.is_some(),
+        spec.has_ipykernel,
+        spec.name.len(),
+    )
+}
+
+/// Drops kernels that run the same interpreter as a more descriptive Python environment kernel.
+/// Custom kernels are always kept, since their arguments and environment are deliberate.
+fn dedupe_kernel_specifications(
+    specs: Vec<KernelSpecification>,
+    ranking: &KernelRanking,
+) -> Vec<KernelSpecification> {
+    let mut best_for_interpreter = HashMap::<&Path, usize>::new();
+    for (index, spec) in specs.iter().enumerate() {
+        let KernelSpecification::PythonEnv(python_env) = spec else {
+            continue;
+        };
+        let Some(interpreter) = ranking.canonical_interpreter(spec) else {
+            continue;
+        };
+        let best_index = best_for_interpreter.entry(interpreter).or_insert(index);
+        if let KernelSpecification::PythonEnv(best) = &specs[*best_index]
+            && descriptiveness(python_env) > descriptiveness(best)
+        {
+            *best_index = index;
+        }
+    }
+
+    let keep = specs
+        .iter()
+        .enumerate()
+        .map(|(index, spec)| {
+            let is_dedupable = match spec {
+                KernelSpecification::PythonEnv(_) => true,
+                KernelSpecification::Jupyter(spec) => !spec.is_custom(),
+                _ => false,
+            };
+            !is_dedupable
+                || ranking
+                    .canonical_interpreter(spec)
+                    .and_then(|interpreter| best_for_interpreter.get(interpreter))
+                    .is_none_or(|best_index| *best_index == index)
+        })
+        .collect::<Vec<_>>();
+    specs
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(spec, keep)| keep.then_some(spec))
+        .collect()
+}
+
+/// Removes duplicate interpreters from the combined discovery results and orders them so that
+/// the first is the one to recommend: environments inside the worktree, then the worktree's
+/// selected toolchain, then the kernel used last, then the rest by name.
+pub fn rank_kernel_specifications(
+    specs: Vec<KernelSpecification>,
+    ranking: &KernelRanking,
+) -> Vec<KernelSpecification> {
+    let mut specs = dedupe_kernel_specifications(specs, ranking);
+    specs.sort_by_cached_key(|spec| {
+        (
+            !ranking.is_project_local(spec),
+            !ranking.matches_active_toolchain(spec),
+            !ranking.is_last_used(spec),
+            spec.name().to_lowercase(),
+        )
+    });
+    specs
+}
+
+const LAST_USED_KERNEL_NAMESPACE: &str = "repl_last_used_kernel";
+
+/// The [`KernelSpecification::last_used_key`] of the kernel last picked in the worktree at
+/// `worktree_root`.
+pub fn read_last_used_kernel(worktree_root: &Path) -> Option<String> {
+    KEY_VALUE_STORE
+        .scoped(LAST_USED_KERNEL_NAMESPACE)
+        .read(&worktree_root.to_string_lossy())
+        .log_err()
+        .flatten()
+}
+
+pub async fn write_last_used_kernel(worktree_root: &Path, last_used_key: String) -> Result<()> {
+    KEY_VALUE_STORE
+        .scoped(LAST_USED_KERNEL_NAMESPACE)
+        .write(worktree_root.to_string_lossy().into_owned(), last_used_key)
+        .await
+}
+
 pub trait RunningKernel: Send + Debug {
-    fn request_tx(&self) -> mpsc::Sender<JupyterMessage>;
+    fn request_tx(&self) -> KernelRequestSender;
     fn stdin_tx(&self) -> mpsc::Sender<JupyterMessage>;
     fn working_directory(&self) -> &PathBuf;
     fn execution_state(&self) -> &ExecutionState;
     fn set_execution_state(&mut self, state: ExecutionState);
     fn kernel_info(&self) -> Option<&KernelInfoReply>;
     fn set_kernel_info(&mut self, info: KernelInfoReply);
+    /// Interrupts the code the kernel is running, by default with an `interrupt_request`.
+    fn interrupt(&mut self) -> Result<()> {
+        self.request_tx()
+            .try_send(runtimelib::InterruptRequest {}.into())
+            .context("failed to send the interrupt request")
+    }
+    /// Asks the kernel to restart without changing its connection info, resolving to the kernel
+    /// to use once it's ready for requests again. Kernels that can't do that return `None`, and
+    /// are killed and launched again instead.
+    fn restart_in_place(
+        &mut self,
+        _window: &mut Window,
+        _cx: &mut App,
+    ) -> Option<Task<Result<Box<dyn RunningKernel>>>> {
+        None
+    }
     fn force_shutdown(&mut self, window: &mut Window, cx: &mut App) -> Task<anyhow::Result<()>>;
     fn kill(&mut self);
+    /// Overrides the status reported for the kernel while its connection is being recovered.
+    fn connection_status(&self) -> Option<KernelStatus> {
+        None
+    }
+    /// The CPU and memory used by the kernel's process, for kernels whose process Zed can see.
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        None
+    }
+    /// How many messages of each type went to and from the kernel, when that's being traced.
+    fn message_counts(&self) -> Option<KernelMessageCounts> {
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KernelStatus {
     Idle,
     Busy,
@@ -599,6 +975,8 @@ pub enum KernelStatus {
     ShuttingDown,
     Shutdown,
     Restarting,
+    /// The kernel stopped answering heartbeats, but hasn't been given up on yet.
+    Unresponsive,
 }
 
 impl KernelStatus {
@@ -617,6 +995,7 @@ impl ToString for KernelStatus {
             KernelStatus::ShuttingDown => "Shutting Down".to_string(),
             KernelStatus::Shutdown => "Shutdown".to_string(),
             KernelStatus::Restarting => "Restarting".to_string(),
+            KernelStatus::Unresponsive => "Unresponsive".to_string(),
         }
     }
 }
@@ -628,28 +1007,33 @@ pub enum Kernel {
     ErroredLaunch(String),
     ShuttingDown,
     Shutdown,
-    Restarting,
+    Restarting(RestartKind),
 }
 
 impl From<&Kernel> for KernelStatus {
     fn from(kernel: &Kernel) -> Self {
         match kernel {
-            Kernel::RunningKernel(kernel) => match kernel.execution_state() {
-                ExecutionState::Idle => KernelStatus::Idle,
-                ExecutionState::Busy => KernelStatus::Busy,
-                ExecutionState::Unknown => KernelStatus::Error,
-                ExecutionState::Starting => KernelStatus::Starting,
-                ExecutionState::Restarting => KernelStatus::Restarting,
-                ExecutionState::Terminating => KernelStatus::ShuttingDown,
-                ExecutionState::AutoRestarting => KernelStatus::Restarting,
-                ExecutionState::Dead => KernelStatus::Error,
-                ExecutionState::Other(_) => KernelStatus::Error,
-            },
+            Kernel::RunningKernel(kernel) => {
+                if let Some(status) = kernel.connection_status() {
+                    return status;
+                }
+                match kernel.execution_state() {
+                    ExecutionState::Idle => KernelStatus::Idle,
+                    ExecutionState::Busy => KernelStatus::Busy,
+                    ExecutionState::Unknown => KernelStatus::Error,
+                    ExecutionState::Starting => KernelStatus::Starting,
+                    ExecutionState::Restarting => KernelStatus::Restarting,
+                    ExecutionState::Terminating => KernelStatus::ShuttingDown,
+                    ExecutionState::AutoRestarting => KernelStatus::Restarting,
+                    ExecutionState::Dead => KernelStatus::Error,
+                    ExecutionState::Other(_) => KernelStatus::Error,
+                }
+            }
             Kernel::StartingKernel(_) => KernelStatus::Starting,
             Kernel::ErroredLaunch(_) => KernelStatus::Error,
             Kernel::ShuttingDown => KernelStatus::ShuttingDown,
             Kernel::Shutdown => KernelStatus::Shutdown,
-            Kernel::Restarting => KernelStatus::Restarting,
+            Kernel::Restarting(_) => KernelStatus::Restarting,
         }
     }
 }
@@ -659,6 +1043,14 @@ impl Kernel {
         self.into()
     }
 
+    /// The status to show for the kernel, which also tells a restart in place from a relaunch.
+    pub fn status_label(&self) -> String {
+        match self {
+            Kernel::Restarting(kind) => kind.label().to_string(),
+            _ => self.status().to_string(),
+        }
+    }
+
     pub fn set_execution_state(&mut self, status: &ExecutionState) {
         if let Kernel::RunningKernel(running_kernel) = self {
             running_kernel.set_execution_state(status.clone());
@@ -671,9 +1063,23 @@ impl Kernel {
         }
     }
 
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        match self {
+            Kernel::RunningKernel(running_kernel) => running_kernel.resource_usage(),
+            _ => None,
+        }
+    }
+
+    pub fn message_counts(&self) -> Option<KernelMessageCounts> {
+        match self {
+            Kernel::RunningKernel(running_kernel) => running_kernel.message_counts(),
+            _ => None,
+        }
+    }
+
     pub fn is_shutting_down(&self) -> bool {
         match self {
-            Kernel::Restarting | Kernel::ShuttingDown => true,
+            Kernel::Restarting(_) | Kernel::ShuttingDown => true,
             Kernel::RunningKernel(_)
             | Kernel::StartingKernel(_)
             | Kernel::ErroredLaunch(_)
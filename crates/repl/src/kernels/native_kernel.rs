@@ -1,4 +1,5 @@
 use anyhow::{Context as _, Result};
+use collections::HashSet;
 use futures::{
     AsyncBufReadExt as _, StreamExt as _,
     channel::mpsc::{self},
@@ -6,29 +7,46 @@ use futures::{
 };
 use gpui::{App, Entity, EntityId, Task, Window};
 use jupyter_protocol::{
-    ExecutionState, JupyterKernelspec, JupyterMessage, KernelInfoReply,
+    ExecutionState, JupyterKernelspec, JupyterMessage, KernelInfoReply, ShutdownRequest,
     connection_info::{ConnectionInfo, Transport},
 };
 use project::Fs;
 use runtimelib::dirs;
+use settings::{CONNECTION_FILE_PLACEHOLDER, CustomKernelContent, Settings as _};
 use smol::net::TcpListener;
 use std::{
     env,
     fmt::Debug,
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
-use util::command::Command;
 use uuid::Uuid;
 
-use super::{KernelSession, RunningKernel, start_kernel_tasks};
+use super::{
+    InterruptMode, KernelInterrupt, KernelMessageCounts, KernelRequestSender, KernelSession,
+    PYTHON_ENV_VALIDATION_TIMEOUT, ProcessSampler, PythonEnvKernelSpecification, ResourceMonitor,
+    ResourceUsage, RunningKernel, SystemCommandRunner, SystemProcessSampler,
+    create_interrupt_event, interrupt_event_env, kernel_message_inspectors, start_kernel_tasks,
+    validate_python_env,
+};
+use crate::repl_settings::ReplSettings;
 
 #[derive(Debug, Clone)]
 pub struct LocalKernelSpecification {
     pub name: String,
     pub path: PathBuf,
     pub kernelspec: JupyterKernelspec,
+    pub source: LocalKernelSource,
+}
+
+/// Where a [`LocalKernelSpecification`] came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalKernelSource {
+    /// A `kernel.json` in one of the Jupyter data directories, or a spec derived from a toolchain.
+    Kernelspec,
+    /// An entry in the `jupyter.custom_kernels` setting.
+    Settings { cwd: Option<PathBuf> },
 }
 
 impl PartialEq for LocalKernelSpecification {
@@ -39,28 +57,78 @@ impl PartialEq for LocalKernelSpecification {
 
 impl Eq for LocalKernelSpecification {}
 
+impl From<&CustomKernelContent> for LocalKernelSpecification {
+    fn from(kernel: &CustomKernelContent) -> Self {
+        Self {
+            name: kernel.name.clone(),
+            // Settings validation guarantees a non-empty `argv`.
+            path: kernel.argv.first().map(PathBuf::from).unwrap_or_default(),
+            kernelspec: JupyterKernelspec {
+                argv: kernel.argv.clone(),
+                display_name: kernel
+                    .display_name
+                    .clone()
+                    .unwrap_or_else(|| kernel.name.clone()),
+                language: kernel.language.clone(),
+                interrupt_mode: None,
+                metadata: None,
+                env: (!kernel.env.is_empty()).then(|| {
+                    kernel
+                        .env
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect()
+                }),
+            },
+            source: LocalKernelSource::Settings {
+                cwd: kernel.cwd.clone(),
+            },
+        }
+    }
+}
+
 impl LocalKernelSpecification {
-    #[must_use]
-    fn command(&self, connection_path: &PathBuf) -> Result<Command> {
+    pub fn is_custom(&self) -> bool {
+        matches!(self.source, LocalKernelSource::Settings { .. })
+    }
+
+    /// The arguments to start the kernel with, with the connection file substituted in.
+    fn args(&self, connection_path: &Path) -> Result<Vec<String>> {
         let argv = &self.kernelspec.argv;
 
         anyhow::ensure!(!argv.is_empty(), "Empty argv in kernelspec {}", self.name);
         anyhow::ensure!(argv.len() >= 2, "Invalid argv in kernelspec {}", self.name);
         anyhow::ensure!(
-            argv.iter().any(|arg| arg == "{connection_file}"),
+            argv.iter()
+                .any(|arg| arg.contains(CONNECTION_FILE_PLACEHOLDER)),
             "Missing 'connection_file' in argv in kernelspec {}",
             self.name
         );
 
-        let mut cmd = util::command::new_command(&argv[0]);
+        let connection_path = connection_path.to_string_lossy();
+        Ok(argv[1..]
+            .iter()
+            .map(|arg| arg.replace(CONNECTION_FILE_PLACEHOLDER, &connection_path))
+            .collect())
+    }
 
-        for arg in &argv[1..] {
-            if arg == "{connection_file}" {
-                cmd.arg(connection_path);
-            } else {
-                cmd.arg(arg);
+    #[must_use]
+    fn command(
+        &self,
+        connection_path: &Path,
+        working_directory: &Path,
+    ) -> Result<std::process::Command> {
+        let args = self.args(connection_path)?;
+        let mut cmd = util::command::new_std_command(&self.kernelspec.argv[0]);
+        cmd.args(args);
+
+        let working_directory = match &self.source {
+            LocalKernelSource::Settings { cwd: Some(cwd) } => cwd.as_path(),
+            LocalKernelSource::Settings { cwd: None } | LocalKernelSource::Kernelspec => {
+                working_directory
             }
-        }
+        };
+        cmd.current_dir(working_directory);
 
         if let Some(env) = &self.kernelspec.env {
             log::info!(
@@ -76,6 +144,38 @@ impl LocalKernelSpecification {
     }
 }
 
+/// Writes a connection file for a new kernel, on ports that are free right now.
+async fn new_connection_file(
+    kernel_name: &str,
+    entity_id: EntityId,
+    fs: &dyn Fs,
+) -> Result<ConnectionFile> {
+    let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let ports = peek_ports(ip).await?;
+
+    let info = ConnectionInfo {
+        transport: Transport::TCP,
+        ip: ip.to_string(),
+        stdin_port: ports[0],
+        control_port: ports[1],
+        hb_port: ports[2],
+        shell_port: ports[3],
+        iopub_port: ports[4],
+        signature_scheme: "hmac-sha256".to_string(),
+        key: uuid::Uuid::new_v4().to_string(),
+        kernel_name: Some(format!("zed-{kernel_name}")),
+    };
+
+    let runtime_dir = dirs::runtime_dir();
+    fs.create_dir(&runtime_dir)
+        .await
+        .with_context(|| format!("Failed to create jupyter runtime dir {runtime_dir:?}"))?;
+    let path = runtime_dir.join(format!("kernel-zed-{entity_id}.json"));
+    let content = serde_json::to_string(&info)?;
+    fs.atomic_write(path.clone(), content).await?;
+    Ok(ConnectionFile { path, info })
+}
+
 // Find a set of open ports. This creates a listener with port set to 0. The listener will be closed at the end when it goes out of scope.
 // There's a race condition between closing the ports and usage by a kernel, but it's inherent to the Jupyter protocol.
 async fn peek_ports(ip: IpAddr) -> Result<[u16; 5]> {
@@ -90,21 +190,43 @@ async fn peek_ports(ip: IpAddr) -> Result<[u16; 5]> {
     Ok(ports)
 }
 
+/// A kernel's connection file, which is removed once the last kernel launched with it is gone.
+/// A kernel restarted in place is launched again with the same one.
+struct ConnectionFile {
+    path: PathBuf,
+    info: ConnectionInfo,
+}
+
+impl Drop for ConnectionFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Launches the kernel again with its connection file, once it has exited to restart.
+type Relaunch =
+    Arc<dyn Fn(&mut Window, &mut App) -> Task<Result<Box<dyn RunningKernel>>> + Send + Sync>;
+
 pub struct NativeRunningKernel {
-    pub process: util::command::Child,
-    connection_path: PathBuf,
+    pub process: util::process::Child,
+    /// Also keeps the kernel's connection file around.
+    relaunch: Relaunch,
+    interrupt: KernelInterrupt,
     _process_status_task: Option<Task<()>>,
+    resource_monitor: Option<ResourceMonitor>,
     pub working_directory: PathBuf,
-    pub request_tx: mpsc::Sender<JupyterMessage>,
+    pub request_tx: KernelRequestSender,
     pub stdin_tx: mpsc::Sender<JupyterMessage>,
     pub execution_state: ExecutionState,
     pub kernel_info: Option<KernelInfoReply>,
+    message_counts: Option<KernelMessageCounts>,
 }
 
 impl Debug for NativeRunningKernel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RunningKernel")
-            .field("process", &self.process)
+            .field("process_id", &self.process.id())
+            .field("interrupt", &self.interrupt)
             .finish()
     }
 }
@@ -120,59 +242,103 @@ impl NativeRunningKernel {
         window: &mut Window,
         cx: &mut App,
     ) -> Task<Result<Box<dyn RunningKernel>>> {
+        Self::launch(
+            kernel_specification,
+            None,
+            entity_id,
+            working_directory,
+            fs,
+            session,
+            window,
+            cx,
+        )
+    }
+
+    /// Launches the kernel with an existing connection file, or with a new one on free ports.
+    fn launch<S: KernelSession + 'static>(
+        kernel_specification: LocalKernelSpecification,
+        connection_file: Option<Arc<ConnectionFile>>,
+        entity_id: EntityId,
+        working_directory: PathBuf,
+        fs: Arc<dyn Fs>,
+        session: Entity<S>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Box<dyn RunningKernel>>> {
+        let request_capacity =
+            ReplSettings::get_global(cx).request_queue_capacity(&kernel_specification.name);
+        let (inspectors, message_counts) =
+            kernel_message_inspectors(&kernel_specification.name, cx);
         window.spawn(cx, async move |cx| {
-            let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-            let ports = peek_ports(ip).await?;
-
-            let connection_info = ConnectionInfo {
-                transport: Transport::TCP,
-                ip: ip.to_string(),
-                stdin_port: ports[0],
-                control_port: ports[1],
-                hb_port: ports[2],
-                shell_port: ports[3],
-                iopub_port: ports[4],
-                signature_scheme: "hmac-sha256".to_string(),
-                key: uuid::Uuid::new_v4().to_string(),
-                kernel_name: Some(format!("zed-{}", kernel_specification.name)),
+            let connection_file = match connection_file {
+                Some(connection_file) => connection_file,
+                None => Arc::new(
+                    new_connection_file(&kernel_specification.name, entity_id, fs.as_ref()).await?,
+                ),
             };
+            let connection_info = &connection_file.info;
 
-            let runtime_dir = dirs::runtime_dir();
-            fs.create_dir(&runtime_dir)
-                .await
-                .with_context(|| format!("Failed to create jupyter runtime dir {runtime_dir:?}"))?;
-            let connection_path = runtime_dir.join(format!("kernel-zed-{entity_id}.json"));
-            let content = serde_json::to_string(&connection_info)?;
-            fs.atomic_write(connection_path.clone(), content).await?;
-
-            let mut cmd = kernel_specification.command(&connection_path)?;
-
-            let mut process = cmd
-                .current_dir(&working_directory)
-                .stdout(util::command::Stdio::piped())
-                .stderr(util::command::Stdio::piped())
-                .stdin(util::command::Stdio::piped())
-                .kill_on_drop(true)
-                .spawn()
-                .context("failed to start the kernel process")?;
+            let relaunch: Relaunch = Arc::new({
+                let kernel_specification = kernel_specification.clone();
+                let connection_file = connection_file.clone();
+                let working_directory = working_directory.clone();
+                let fs = fs.clone();
+                let session = session.clone();
+                move |window, cx| {
+                    Self::launch(
+                        kernel_specification.clone(),
+                        Some(connection_file.clone()),
+                        entity_id,
+                        working_directory.clone(),
+                        fs.clone(),
+                        session.clone(),
+                        window,
+                        cx,
+                    )
+                }
+            });
+
+            let mut cmd =
+                kernel_specification.command(&connection_file.path, &working_directory)?;
+
+            let interrupt_mode = InterruptMode::from_kernelspec(&kernel_specification.kernelspec);
+            let interrupt_event = create_interrupt_event(interrupt_mode)?;
+            if let Some(interrupt_event) = &interrupt_event {
+                cmd.envs(interrupt_event_env(interrupt_event.as_ref()));
+            }
+
+            // The kernel leads its own process group, so that signals meant for it and whatever
+            // it spawns don't reach Zed.
+            let mut process = util::process::Child::spawn(
+                cmd,
+                std::process::Stdio::piped(),
+                std::process::Stdio::piped(),
+                std::process::Stdio::piped(),
+            )
+            .context("failed to start the kernel process")?;
+            let interrupt = KernelInterrupt::new(interrupt_mode, process.id(), interrupt_event);
+            let sampler =
+                Box::new(SystemProcessSampler::new(process.id())) as Box<dyn ProcessSampler>;
+            let resource_monitor =
+                ResourceMonitor::new(async move { Some(sampler) }, cx.background_executor());
 
             let session_id = Uuid::new_v4().to_string();
 
             let iopub_socket =
-                runtimelib::create_client_iopub_connection(&connection_info, "", &session_id)
+                runtimelib::create_client_iopub_connection(connection_info, "", &session_id)
                     .await?;
             let control_socket =
-                runtimelib::create_client_control_connection(&connection_info, &session_id).await?;
+                runtimelib::create_client_control_connection(connection_info, &session_id).await?;
 
             let peer_identity = runtimelib::peer_identity_for_session(&session_id)?;
             let shell_socket = runtimelib::create_client_shell_connection_with_identity(
-                &connection_info,
+                connection_info,
                 &session_id,
                 peer_identity.clone(),
             )
             .await?;
             let stdin_socket = runtimelib::create_client_stdin_connection_with_identity(
-                &connection_info,
+                connection_info,
                 &session_id,
                 peer_identity,
             )
@@ -184,6 +350,8 @@ impl NativeRunningKernel {
                 shell_socket,
                 control_socket,
                 stdin_socket,
+                request_capacity,
+                inspectors,
                 cx,
             );
 
@@ -244,20 +412,73 @@ impl NativeRunningKernel {
 
             anyhow::Ok(Box::new(Self {
                 process,
+                relaunch,
                 request_tx,
                 stdin_tx,
                 working_directory,
                 _process_status_task: Some(process_status_task),
-                connection_path,
+                resource_monitor: Some(resource_monitor),
+                interrupt,
                 execution_state: ExecutionState::Idle,
                 kernel_info: None,
+                message_counts,
             }) as Box<dyn RunningKernel>)
         })
     }
+
+    /// Launches a kernel from a Python environment, first checking that the environment can
+    /// still run ipykernel unless that's turned off in the settings.
+    pub fn new_for_python_env<S: KernelSession + 'static>(
+        env_specification: PythonEnvKernelSpecification,
+        entity_id: EntityId,
+        working_directory: PathBuf,
+        fs: Arc<dyn Fs>,
+        session: Entity<S>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Box<dyn RunningKernel>>> {
+        if !ReplSettings::get_global(cx).validate_python_environments {
+            return Self::new(
+                env_specification.as_local_spec(),
+                entity_id,
+                working_directory,
+                fs,
+                session,
+                window,
+                cx,
+            );
+        }
+
+        let timeout = cx
+            .background_executor()
+            .timer(PYTHON_ENV_VALIDATION_TIMEOUT);
+        let validation =
+            cx.background_spawn({
+                let env_specification = env_specification.clone();
+                async move {
+                    validate_python_env(&env_specification, &SystemCommandRunner, timeout).await
+                }
+            });
+        window.spawn(cx, async move |cx| {
+            validation.await?;
+            cx.update(|window, cx| {
+                Self::new(
+                    env_specification.as_local_spec(),
+                    entity_id,
+                    working_directory,
+                    fs,
+                    session,
+                    window,
+                    cx,
+                )
+            })?
+            .await
+        })
+    }
 }
 
 impl RunningKernel for NativeRunningKernel {
-    fn request_tx(&self) -> mpsc::Sender<JupyterMessage> {
+    fn request_tx(&self) -> KernelRequestSender {
         self.request_tx.clone()
     }
 
@@ -285,13 +506,52 @@ impl RunningKernel for NativeRunningKernel {
         self.kernel_info = Some(info);
     }
 
+    fn interrupt(&mut self) -> Result<()> {
+        self.interrupt.interrupt(&mut self.request_tx)
+    }
+
+    fn restart_in_place(
+        &mut self,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Option<Task<Result<Box<dyn RunningKernel>>>> {
+        let mut request_tx = self.request_tx.clone();
+        let exited = self.process.status();
+        let relaunch = self.relaunch.clone();
+        let window_handle = window.window_handle();
+        Some(cx.spawn(async move |cx| {
+            let message: JupyterMessage = ShutdownRequest { restart: true }.into();
+            request_tx
+                .try_send(message)
+                .context("failed to send the restart request")?;
+
+            // ipykernel exits once it has replied, leaving it to whoever launched it to start
+            // it again with the same connection file, the way Jupyter's kernel manager does.
+            let status = exited
+                .await
+                .context("failed to wait for the kernel to exit")?;
+            log::info!("kernel exited to restart: {status}");
+            cx.update_window(window_handle, |_, window, cx| relaunch(window, cx))?
+                .await
+        }))
+    }
+
     fn force_shutdown(&mut self, _window: &mut Window, _cx: &mut App) -> Task<anyhow::Result<()>> {
         self.kill();
         Task::ready(Ok(()))
     }
 
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.resource_monitor.as_ref()?.usage()
+    }
+
+    fn message_counts(&self) -> Option<KernelMessageCounts> {
+        self.message_counts.clone()
+    }
+
     fn kill(&mut self) {
         self._process_status_task.take();
+        self.resource_monitor.take();
         self.request_tx.close_channel();
         self.stdin_tx.close_channel();
         self.process.kill().ok();
@@ -300,7 +560,6 @@ impl RunningKernel for NativeRunningKernel {
 
 impl Drop for NativeRunningKernel {
     fn drop(&mut self) {
-        std::fs::remove_file(&self.connection_path).ok();
         self.kill();
     }
 }
@@ -309,6 +568,7 @@ async fn read_kernelspec_at(
     // Path should be a directory to a jupyter kernelspec, as in
     // /usr/local/share/jupyter/kernels/python3
     kernel_dir: PathBuf,
+    search_paths: &[PathBuf],
     fs: &dyn Fs,
 ) -> Result<LocalKernelSpecification> {
     let path = kernel_dir;
@@ -324,27 +584,68 @@ async fn read_kernelspec_at(
 
     let expected_kernel_json = path.join("kernel.json");
     let spec = fs.load(expected_kernel_json.as_path()).await?;
-    let spec = serde_json::from_str::<JupyterKernelspec>(&spec)?;
+    let mut spec = serde_json::from_str::<JupyterKernelspec>(&spec)
+        .with_context(|| format!("Failed to parse {expected_kernel_json:?}"))?;
+
+    if let Some(program) = spec.argv.first_mut()
+        && let Some(resolved) = resolve_kernel_program(program, search_paths, fs).await
+    {
+        *program = resolved.to_string_lossy().into_owned();
+    }
 
     Ok(LocalKernelSpecification {
         name: kernel_name,
         path,
         kernelspec: spec,
+        source: LocalKernelSource::Kernelspec,
     })
 }
 
+/// Kernelspecs usually refer to their interpreter by a bare name such as `python3`.
+/// Resolving it up front makes specs comparable with the toolchain-derived ones.
+async fn resolve_kernel_program(
+    program: &str,
+    search_paths: &[PathBuf],
+    fs: &dyn Fs,
+) -> Option<PathBuf> {
+    let program_path = Path::new(program);
+    if program_path.is_absolute() || program_path.components().count() != 1 {
+        return None;
+    }
+
+    for dir in search_paths {
+        let candidate = dir.join(program_path);
+        if fs.is_file(&candidate).await {
+            return Some(candidate);
+        }
+        if cfg!(windows) && program_path.extension().is_none() {
+            let candidate = candidate.with_extension("exe");
+            if fs.is_file(&candidate).await {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
 /// Read a directory of kernelspec directories
-async fn read_kernels_dir(path: PathBuf, fs: &dyn Fs) -> Result<Vec<LocalKernelSpecification>> {
+async fn read_kernels_dir(
+    path: PathBuf,
+    search_paths: &[PathBuf],
+    fs: &dyn Fs,
+) -> Result<Vec<LocalKernelSpecification>> {
     let mut kernelspec_dirs = fs.read_dir(&path).await?;
 
     let mut valid_kernelspecs = Vec::new();
     while let Some(path) = kernelspec_dirs.next().await {
         match path {
             Ok(path) => {
-                if fs.is_dir(path.as_path()).await
-                    && let Ok(kernelspec) = read_kernelspec_at(path, fs).await
-                {
-                    valid_kernelspecs.push(kernelspec);
+                if !fs.is_dir(path.as_path()).await {
+                    continue;
+                }
+                match read_kernelspec_at(path.clone(), search_paths, fs).await {
+                    Ok(kernelspec) => valid_kernelspecs.push(kernelspec),
+                    Err(err) => log::warn!("Skipping kernelspec at {path:?}: {err:#}"),
                 }
             }
             Err(err) => log::warn!("Error reading kernelspec directory: {err:?}"),
@@ -355,7 +656,11 @@ async fn read_kernels_dir(path: PathBuf, fs: &dyn Fs) -> Result<Vec<LocalKernelS
 }
 
 pub async fn local_kernel_specifications(fs: Arc<dyn Fs>) -> Result<Vec<LocalKernelSpecification>> {
-    let mut data_dirs = dirs::data_dirs();
+    // `JUPYTER_PATH` may hold several directories, so split every entry as a path list.
+    let mut data_dirs = dirs::data_dirs()
+        .iter()
+        .flat_map(env::split_paths)
+        .collect::<Vec<_>>();
 
     // Pick up any kernels from conda or conda environment
     if let Ok(conda_prefix) = env::var("CONDA_PREFIX") {
@@ -382,10 +687,17 @@ pub async fn local_kernel_specifications(fs: Arc<dyn Fs>) -> Result<Vec<LocalKer
         }
     }
 
+    let mut seen_dirs = HashSet::default();
+    data_dirs.retain(|dir| seen_dirs.insert(dir.clone()));
+
+    let search_paths = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default();
+
     let kernel_dirs = data_dirs
         .iter()
         .map(|dir| dir.join("kernels"))
-        .map(|path| read_kernels_dir(path, fs.as_ref()))
+        .map(|path| read_kernels_dir(path, &search_paths, fs.as_ref()))
         .collect::<Vec<_>>();
 
     let kernel_dirs = futures::future::join_all(kernel_dirs).await;
@@ -401,9 +713,15 @@ pub async fn local_kernel_specifications(fs: Arc<dyn Fs>) -> Result<Vec<LocalKer
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::kernels::{
+        CanonicalInterpreterPaths, KernelRanking, KernelSpecification,
+        PythonEnvKernelSpecification, SshRemoteKernelSpecification,
+        is_duplicate_kernel_specification, merge_custom_kernel_specifications,
+        rank_kernel_specifications, read_last_used_kernel, write_last_used_kernel,
+    };
     use std::path::PathBuf;
 
-    use gpui::TestAppContext;
+    use gpui::{SharedString, TestAppContext};
     use project::FakeFs;
     use serde_json::json;
 
@@ -443,7 +761,7 @@ mod test {
         )
         .await;
 
-        let mut kernels = read_kernels_dir(PathBuf::from("/jupyter/kernels"), fs.as_ref())
+        let mut kernels = read_kernels_dir(PathBuf::from("/jupyter/kernels"), &[], fs.as_ref())
             .await
             .unwrap();
 
@@ -454,4 +772,427 @@ mod test {
             vec!["deno", "python"]
         );
     }
+
+    #[gpui::test]
+    async fn test_kernelspec_discovery_skips_malformed_and_resolves_argv(cx: &mut TestAppContext) {
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            "/root",
+            json!({
+                "bin": {
+                    "python3": "",
+                    "R": "",
+                },
+                "kernels": {
+                    "ir": {
+                        "kernel.json": r#"{
+                            "display_name": "R",
+                            "language": "R",
+                            "argv": ["R", "--slave", "-e", "IRkernel::main()", "--args", "{connection_file}"]
+                        }"#
+                    },
+                    "python3": {
+                        "kernel.json": r#"{
+                            "display_name": "Python 3",
+                            "language": "python",
+                            "argv": ["/opt/python/bin/python", "-m", "ipykernel_launcher", "-f", "{connection_file}"]
+                        }"#
+                    },
+                    "broken": {
+                        "kernel.json": "{ not json"
+                    },
+                    "missing": {},
+                    "julia": {
+                        "kernel.json": r#"{
+                            "display_name": "Julia",
+                            "language": "julia",
+                            "argv": ["julia", "-i", "{connection_file}"]
+                        }"#
+                    }
+                },
+            }),
+        )
+        .await;
+
+        let mut kernels = read_kernels_dir(
+            PathBuf::from("/root/kernels"),
+            &[PathBuf::from("/root/bin")],
+            fs.as_ref(),
+        )
+        .await
+        .unwrap();
+        kernels.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(
+            kernels
+                .iter()
+                .map(|kernel| (
+                    kernel.name.as_str(),
+                    kernel.path.clone(),
+                    kernel.kernelspec.argv[0].as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("ir", PathBuf::from("/root/kernels/ir"), "/root/bin/R"),
+                // Not found on the search path, so left as-is.
+                ("julia", PathBuf::from("/root/kernels/julia"), "julia"),
+                (
+                    "python3",
+                    PathBuf::from("/root/kernels/python3"),
+                    "/opt/python/bin/python"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_kernel_specifications_prefer_python_env() {
+        let kernelspec = |program: &str| JupyterKernelspec {
+            argv: vec![
+                program.to_string(),
+                "-m".to_string(),
+                "ipykernel_launcher".to_string(),
+                "-f".to_string(),
+                "{connection_file}".to_string(),
+            ],
+            display_name: "Python 3".to_string(),
+            language: "python".to_string(),
+            interrupt_mode: None,
+            metadata: None,
+            env: None,
+        };
+        let python_env = KernelSpecification::PythonEnv(PythonEnvKernelSpecification {
+            name: ".venv".to_string(),
+            path: PathBuf::from("/project/.venv/bin/python"),
+            kernelspec: kernelspec("/project/.venv/bin/python"),
+            has_ipykernel: true,
+            environment_kind: Some("venv".to_string()),
+        });
+        let same_interpreter = KernelSpecification::Jupyter(LocalKernelSpecification {
+            name: "venv".to_string(),
+            path: PathBuf::from("/share/jupyter/kernels/venv"),
+            kernelspec: kernelspec("/project/.venv/bin/python"),
+            source: LocalKernelSource::Kernelspec,
+        });
+        let other_interpreter = KernelSpecification::Jupyter(LocalKernelSpecification {
+            name: "python3".to_string(),
+            path: PathBuf::from("/share/jupyter/kernels/python3"),
+            kernelspec: kernelspec("/usr/bin/python3"),
+            source: LocalKernelSource::Kernelspec,
+        });
+
+        let python_env_specs = [python_env.clone()];
+        assert!(is_duplicate_kernel_specification(
+            &same_interpreter,
+            &python_env_specs
+        ));
+        assert!(!is_duplicate_kernel_specification(
+            &other_interpreter,
+            &python_env_specs
+        ));
+        assert!(!is_duplicate_kernel_specification(
+            &python_env,
+            &python_env_specs
+        ));
+    }
+
+    fn python_kernelspec(program: &str) -> JupyterKernelspec {
+        JupyterKernelspec {
+            argv: vec![
+                program.to_string(),
+                "-m".to_string(),
+                "ipykernel_launcher".to_string(),
+                "-f".to_string(),
+                "{connection_file}".to_string(),
+            ],
+            display_name: "Python 3".to_string(),
+            language: "python".to_string(),
+            interrupt_mode: None,
+            metadata: None,
+            env: None,
+        }
+    }
+
+    fn python_env(name: &str, path: &str, environment_kind: Option<&str>) -> KernelSpecification {
+        KernelSpecification::PythonEnv(PythonEnvKernelSpecification {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+            kernelspec: python_kernelspec(path),
+            has_ipykernel: true,
+            environment_kind: environment_kind.map(ToString::to_string),
+        })
+    }
+
+    fn names(specs: &[KernelSpecification]) -> Vec<String> {
+        specs.iter().map(|spec| spec.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_kernel_specification_strings_are_not_copied() {
+        let first = python_env(".venv", "/project/.venv/bin/python", Some("venv"));
+        let second = python_env(".venv", "/other/.venv/bin/python", Some("venv"));
+        assert!(std::ptr::eq(first.name().as_ptr(), second.name().as_ptr()));
+        assert!(std::ptr::eq(
+            first.language().as_ptr(),
+            second.language().as_ptr()
+        ));
+
+        let path = SharedString::from(String::from("/home/user/.venv/bin/python"));
+        let remote = KernelSpecification::SshRemote(SshRemoteKernelSpecification {
+            name: "Remote .venv".to_string(),
+            path: path.clone(),
+            kernelspec: python_kernelspec("/home/user/.venv/bin/python"),
+        });
+        assert!(std::ptr::eq(remote.path().as_ptr(), path.as_ptr()));
+    }
+
+    #[gpui::test]
+    async fn test_rank_kernel_specifications_dedupes_interpreters(cx: &mut TestAppContext) {
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            "/",
+            json!({
+                "usr": { "bin": { "python3.12": "" } },
+                "project": { ".venv": { "bin": { "python3.12": "" } } },
+            }),
+        )
+        .await;
+        // Merged `/usr`, where `/bin` is the same directory as `/usr/bin`.
+        fs.insert_symlink("/bin", PathBuf::from("/usr/bin")).await;
+        fs.insert_symlink("/usr/bin/python3", PathBuf::from("python3.12"))
+            .await;
+        // The venv's interpreter links out to the base one, but is a different environment.
+        fs.insert_symlink(
+            "/project/.venv/bin/python",
+            PathBuf::from("/usr/bin/python3.12"),
+        )
+        .await;
+
+        let specs = vec![
+            python_env("Python 3.12", "/bin/python3", None),
+            python_env(
+                "Python 3.12 (global)",
+                "/usr/bin/python3.12",
+                Some("global"),
+            ),
+            python_env("Python 3", "/usr/bin/python3", None),
+            python_env(".venv", "/project/.venv/bin/python", Some("venv")),
+            KernelSpecification::Jupyter(LocalKernelSpecification {
+                name: "python3".to_string(),
+                path: PathBuf::from("/share/jupyter/kernels/python3"),
+                kernelspec: python_kernelspec("/bin/python3.12"),
+                source: LocalKernelSource::Kernelspec,
+            }),
+            KernelSpecification::Jupyter(LocalKernelSpecification::from(&custom_kernel(
+                "custom-python",
+                &[
+                    "/usr/bin/python3",
+                    "-m",
+                    "ipykernel",
+                    "-f",
+                    "{connection_file}",
+                ],
+            ))),
+        ];
+        let canonical_paths = CanonicalInterpreterPaths::resolve(
+            specs
+                .iter()
+                .filter_map(|spec| spec.interpreter_path().map(Path::to_path_buf)),
+            fs.as_ref(),
+        )
+        .await;
+        assert_eq!(
+            canonical_paths.get(Path::new("/bin/python3")),
+            Path::new("/usr/bin/python3.12")
+        );
+        assert_eq!(
+            canonical_paths.get(Path::new("/project/.venv/bin/python")),
+            Path::new("/project/.venv/bin/python")
+        );
+
+        let ranked = rank_kernel_specifications(
+            specs,
+            &KernelRanking {
+                worktree_root: None,
+                active_toolchain_path: None,
+                last_used_kernel: None,
+                canonical_paths: &canonical_paths,
+            },
+        );
+        // The global interpreter is listed once, under its most descriptive entry, and the
+        // kernelspec for it is dropped. Custom kernels are kept even when they share it.
+        assert_eq!(
+            names(&ranked),
+            [".venv", "custom-python", "Python 3.12 (global)"]
+        );
+    }
+
+    #[test]
+    fn test_rank_kernel_specifications_order() {
+        let specs = vec![
+            python_env("conda-base", "/opt/conda/bin/python", Some("Conda")),
+            python_env("Alpha", "/usr/bin/python3", Some("global")),
+            python_env(
+                "pyenv-3.11",
+                "/home/user/.pyenv/versions/3.11/bin/python",
+                None,
+            ),
+            python_env(".venv", "/project/.venv/bin/python", Some("venv")),
+            python_env("beta", "/opt/beta/bin/python", None),
+        ];
+        let canonical_paths = CanonicalInterpreterPaths::default();
+        let rank = |active_toolchain_path, last_used_kernel| {
+            names(&rank_kernel_specifications(
+                specs.clone(),
+                &KernelRanking {
+                    worktree_root: Some(Path::new("/project")),
+                    active_toolchain_path,
+                    last_used_kernel,
+                    canonical_paths: &canonical_paths,
+                },
+            ))
+        };
+
+        assert_eq!(
+            rank(None, None),
+            [".venv", "Alpha", "beta", "conda-base", "pyenv-3.11"]
+        );
+
+        let last_used = specs[2].last_used_key();
+        assert_eq!(
+            rank(None, Some(last_used.as_str())),
+            [".venv", "pyenv-3.11", "Alpha", "beta", "conda-base"]
+        );
+        assert_eq!(
+            rank(Some("/opt/conda/bin/python"), Some(last_used.as_str())),
+            [".venv", "conda-base", "pyenv-3.11", "Alpha", "beta"]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_last_used_kernel_round_trip() {
+        let worktree_root = Path::new("/test_last_used_kernel_round_trip");
+        assert_eq!(read_last_used_kernel(worktree_root), None);
+
+        let venv = python_env(
+            ".venv",
+            "/test_last_used_kernel_round_trip/.venv/bin/python",
+            None,
+        );
+        write_last_used_kernel(worktree_root, venv.last_used_key())
+            .await
+            .unwrap();
+        assert_eq!(
+            read_last_used_kernel(worktree_root),
+            Some(venv.last_used_key())
+        );
+
+        let global = python_env("Python 3", "/usr/bin/python3", None);
+        write_last_used_kernel(worktree_root, global.last_used_key())
+            .await
+            .unwrap();
+        assert_eq!(
+            read_last_used_kernel(worktree_root),
+            Some(global.last_used_key())
+        );
+        assert_eq!(
+            read_last_used_kernel(Path::new("/some/other/worktree")),
+            None
+        );
+    }
+
+    fn custom_kernel(name: &str, argv: &[&str]) -> CustomKernelContent {
+        CustomKernelContent {
+            name: name.to_string(),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            cwd: Some(PathBuf::from("/project")),
+            env: [("KERNEL_MODE".to_string(), "docker".to_string())]
+                .into_iter()
+                .collect(),
+            language: "python".to_string(),
+            display_name: None,
+        }
+    }
+
+    #[test]
+    fn test_custom_kernel_args() {
+        let spec = LocalKernelSpecification::from(&custom_kernel(
+            "docker-python",
+            &[
+                "./kernel.sh",
+                "--connection-file",
+                "{connection_file}",
+                "--file={connection_file}",
+            ],
+        ));
+        assert!(spec.is_custom());
+        assert_eq!(spec.path, PathBuf::from("./kernel.sh"));
+        assert_eq!(spec.kernelspec.display_name, "docker-python");
+        assert_eq!(
+            spec.kernelspec
+                .env
+                .as_ref()
+                .and_then(|env| env.get("KERNEL_MODE")),
+            Some(&"docker".to_string())
+        );
+        assert_eq!(
+            spec.source,
+            LocalKernelSource::Settings {
+                cwd: Some(PathBuf::from("/project"))
+            }
+        );
+
+        let connection_path = Path::new("/runtime/kernel-zed-1.json");
+        assert_eq!(
+            spec.args(connection_path).unwrap(),
+            [
+                "--connection-file",
+                "/runtime/kernel-zed-1.json",
+                "--file=/runtime/kernel-zed-1.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_kernels_merge_with_discovered_kernels() {
+        let discovered = |name: &str| {
+            KernelSpecification::Jupyter(LocalKernelSpecification {
+                name: name.to_string(),
+                path: PathBuf::from(format!("/share/jupyter/kernels/{name}")),
+                kernelspec: JupyterKernelspec {
+                    argv: vec!["python3".to_string(), "{connection_file}".to_string()],
+                    display_name: name.to_string(),
+                    language: "python".to_string(),
+                    interrupt_mode: None,
+                    metadata: None,
+                    env: None,
+                },
+                source: LocalKernelSource::Kernelspec,
+            })
+        };
+        let custom = |name: &str| {
+            KernelSpecification::Jupyter(LocalKernelSpecification::from(&custom_kernel(
+                name,
+                &["./kernel.sh", "{connection_file}"],
+            )))
+        };
+
+        let merged = merge_custom_kernel_specifications(
+            &[custom("python3"), custom("docker-python")],
+            &[discovered("ir"), discovered("python3")],
+        );
+        assert_eq!(
+            merged
+                .iter()
+                .map(|spec| format!("{} ({})", spec.name(), spec.type_name()))
+                .collect::<Vec<_>>(),
+            ["python3 (Custom)", "docker-python (Custom)", "ir (Jupyter)"]
+        );
+
+        // Without custom kernels, the discovered ones are left as they are.
+        assert_eq!(
+            merge_custom_kernel_specifications(&[], &[discovered("ir")]),
+            [discovered("ir")]
+        );
+    }
 }
@@ -0,0 +1,306 @@
+//! Checks that a Python environment can still run ipykernel before a kernel is launched from it.
+//!
+//! A virtual environment whose base interpreter was upgraded or removed otherwise produces a
+//! kernel that starts and immediately dies, which only shows up as a confusing socket error.
+
+use std::{fmt, future::Future, io, path::Path, time::Duration};
+
+use futures::{FutureExt as _, channel::mpsc};
+
+use super::{
+    CommandRunner, InstallCommand, PythonEnvKernelSpecification, ipykernel_install_command,
+};
+
+/// How long the environment's interpreter gets to import what the kernel needs.
+pub const PYTHON_ENV_VALIDATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+const VALIDATION_SCRIPT: &str = "import sys, zmq, ipykernel; print(sys.version_info[:2])";
+
+/// What's wrong with a Python environment that can't run a kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonEnvProblem {
+    /// The interpreter doesn't exist, usually because the interpreter the environment was
+    /// created from was upgraded or removed.
+    InterpreterMissing,
+    /// The interpreter can't import a module the kernel needs.
+    MissingModule(String),
+    /// The interpreter didn't finish importing in time.
+    Hung,
+    /// The interpreter failed some other way. Holds the last line it printed.
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PythonEnvValidationError {
+    pub problem: PythonEnvProblem,
+    pub message: String,
+    /// What to do to fix the environment.
+    pub suggestion: String,
+}
+
+impl PythonEnvValidationError {
+    fn new(problem: PythonEnvProblem, spec: &PythonEnvKernelSpecification) -> Self {
+        let python = spec.path.display();
+        let (message, suggestion) = match &problem {
+            PythonEnvProblem::InterpreterMissing => (
+                format!("The environment's interpreter no longer exists at {python}"),
+                format!(
+                    "The Python it was created from may have been upgraded or removed. Recreate \
+                    the environment, for example with {}, and install ipykernel into it.",
+                    recreate_command(&spec.path)
+                ),
+            ),
+            PythonEnvProblem::MissingModule(module) => (
+                format!("{python} can't import `{module}`"),
+                format!(
+                    "Install ipykernel, which brings in everything the kernel needs, with `{}`.",
+                    ipykernel_install_command(spec)
+                ),
+            ),
+            PythonEnvProblem::Hung => (
+                format!(
+                    "The environment's interpreter hung: {python} didn't start within {} seconds",
+                    PYTHON_ENV_VALIDATION_TIMEOUT.as_secs()
+                ),
+                "If the environment is on a slow network file system, turn off \
+                `repl.validate_python_environments` to skip this check."
+                    .to_string(),
+            ),
+            PythonEnvProblem::Failed(output) => (
+                format!("{python} failed to start: {output}"),
+                format!(
+                    "Recreate the environment, for example with {}, and install ipykernel into \
+                    it.",
+                    recreate_command(&spec.path)
+                ),
+            ),
+        };
+        Self {
+            problem,
+            message,
+            suggestion,
+        }
+    }
+}
+
+impl fmt::Display for PythonEnvValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}. {}", self.message, self.suggestion)
+    }
+}
+
+impl std::error::Error for PythonEnvValidationError {}
+
+/// Runs the environment's interpreter to check that it exists and can import ipykernel and
+/// pyzmq, giving up once `timeout` resolves.
+///
+/// Environments that were already found not to have ipykernel fail without running anything.
+pub async fn validate_python_env(
+    spec: &PythonEnvKernelSpecification,
+    runner: &dyn CommandRunner,
+    timeout: impl Future<Output = ()>,
+) -> Result<(), PythonEnvValidationError> {
+    if !spec.has_ipykernel {
+        return Err(PythonEnvValidationError::new(
+            PythonEnvProblem::MissingModule("ipykernel".to_string()),
+            spec,
+        ));
+    }
+
+    let command = InstallCommand {
+        program: spec.path.to_string_lossy().into_owned(),
+        args: vec!["-c".to_string(), VALIDATION_SCRIPT.to_string()],
+    };
+    let result = futures::select_biased! {
+        result = runner.run(&command, mpsc::unbounded().0).fuse() => result,
+        _ = timeout.fuse() => {
+            return Err(PythonEnvValidationError::new(PythonEnvProblem::Hung, spec));
+        }
+    };
+
+    let problem = match result {
+        Ok(output) if output.success => {
+            log::debug!(
+                "{} is ready to run a kernel: Python {}",
+                spec.path.display(),
+                output.output.trim()
+            );
+            return Ok(());
+        }
+        Ok(output) => missing_module(&output.output)
+            .map(PythonEnvProblem::MissingModule)
+            .unwrap_or_else(|| {
+                let last_line = output
+                    .output
+                    .lines()
+                    .rev()
+                    .find(|line| !line.trim().is_empty());
+                PythonEnvProblem::Failed(last_line.unwrap_or("no output").trim().to_string())
+            }),
+        Err(error) if is_not_found(&error) => PythonEnvProblem::InterpreterMissing,
+        Err(error) => PythonEnvProblem::Failed(format!("{error:#}")),
+    };
+    Err(PythonEnvValidationError::new(problem, spec))
+}
+
+/// The module named by the `ModuleNotFoundError` a failed import printed.
+fn missing_module(output: &str) -> Option<String> {
+    output.lines().rev().find_map(|line| {
+        let module = line.trim().split_once("No module named ")?.1;
+        Some(module.trim_matches(['\'', '"']).to_string())
+    })
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|error| error.kind() == io::ErrorKind::NotFound)
+    })
+}
+
+/// The command that recreates the environment the interpreter at `python` belongs to.
+fn recreate_command(python: &Path) -> String {
+    let environment = python
+        .parent()
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name == "bin" || name == "Scripts")
+        })
+        .and_then(Path::parent);
+    match environment {
+        Some(environment) => format!("`python3 -m venv --clear {}`", environment.display()),
+        None => "`python3 -m venv --clear <environment>`".to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::kernels::SystemCommandRunner;
+    use jupyter_protocol::JupyterKernelspec;
+    use std::{os::unix::fs::PermissionsExt as _, path::PathBuf};
+
+    /// Writes a script that stands in for the environment's interpreter.
+    fn fake_python(dir: &Path, script: &str) -> PathBuf {
+        let bin = dir.join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let path = bin.join("python");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn spec(path: PathBuf, has_ipykernel: bool) -> PythonEnvKernelSpecification {
+        PythonEnvKernelSpecification {
+            name: "env".to_string(),
+            path,
+            kernelspec: JupyterKernelspec {
+                argv: Vec::new(),
+                display_name: "env".to_string(),
+                language: "python".to_string(),
+                interrupt_mode: None,
+                metadata: None,
+                env: None,
+            },
+            has_ipykernel,
+            environment_kind: Some("venv".to_string()),
+        }
+    }
+
+    fn validate(spec: &PythonEnvKernelSpecification) -> Result<(), PythonEnvValidationError> {
+        smol::block_on(validate_python_env(
+            spec,
+            &SystemCommandRunner,
+            smol::Timer::after(Duration::from_millis(500)).map(|_| ()),
+        ))
+    }
+
+    #[test]
+    fn test_working_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = fake_python(
+            dir.path(),
+            &format!("[ \"$2\" = \"{VALIDATION_SCRIPT}\" ] || exit 1\necho '(3, 12)'"),
+        );
+        validate(&spec(python, true)).unwrap();
+    }
+
+    #[test]
+    fn test_missing_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = spec(dir.path().join("bin/python"), true);
+        let error = validate(&spec).unwrap_err();
+        assert_eq!(error.problem, PythonEnvProblem::InterpreterMissing);
+        assert!(error.message.contains("no longer exists"), "{error}");
+        assert!(
+            error.suggestion.contains(&format!(
+                "`python3 -m venv --clear {}`",
+                dir.path().display()
+            )),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_missing_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = fake_python(
+            dir.path(),
+            "echo 'Traceback (most recent call last):' >&2\n\
+            echo '  File \"<string>\", line 1, in <module>' >&2\n\
+            echo \"ModuleNotFoundError: No module named 'zmq'\" >&2\n\
+            exit 1",
+        );
+        let error = validate(&spec(python.clone(), true)).unwrap_err();
+        assert_eq!(
+            error.problem,
+            PythonEnvProblem::MissingModule("zmq".to_string())
+        );
+        assert!(
+            error
+                .suggestion
+                .contains(&format!("`{} -m pip install ipykernel`", python.display())),
+            "{error}"
+        );
+
+        // Other failures keep what the interpreter said.
+        let python = fake_python(
+            dir.path(),
+            "echo 'dyld: Library not loaded: libpython3.11.dylib' >&2\nexit 134",
+        );
+        let error = validate(&spec(python, true)).unwrap_err();
+        assert_eq!(
+            error.problem,
+            PythonEnvProblem::Failed("dyld: Library not loaded: libpython3.11.dylib".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hung_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = fake_python(dir.path(), "exec sleep 30");
+        let error = validate(&spec(python, true)).unwrap_err();
+        assert_eq!(error.problem, PythonEnvProblem::Hung);
+        assert!(error.message.contains("hung"), "{error}");
+        assert!(
+            error
+                .suggestion
+                .contains("repl.validate_python_environments"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_known_missing_ipykernel_skips_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let ran = dir.path().join("ran");
+        let python = fake_python(dir.path(), &format!("touch \"{}\"", ran.display()));
+        let error = validate(&spec(python, false)).unwrap_err();
+        assert_eq!(
+            error.problem,
+            PythonEnvProblem::MissingModule("ipykernel".to_string())
+        );
+        assert!(!ran.exists());
+    }
+}
@@ -0,0 +1,434 @@
+//! # Remote Kernel Connections
+//!
+//! A kernel on a Jupyter server talks to Zed over a single websocket, which doesn't survive the
+//! laptop going to sleep or a change of network. The kernel keeps running on the server with
+//! all of its state, so when the websocket drops it's reopened instead of the session being
+//! errored. Requests sent in the meantime wait in the kernel's queue until it's back.
+
+use std::{pin::Pin, time::Duration};
+
+use anyhow::{Context as _, Result};
+use futures::{FutureExt as _, Sink, SinkExt as _, Stream, StreamExt as _};
+use gpui::{BackgroundExecutor, FutureExt as _};
+use jupyter_protocol::{JupyterMessage, JupyterMessageContent, KernelInfoRequest};
+
+use super::{KernelStatus, OwnMessageFilter};
+
+/// What executions in flight when the connection dropped are marked with, since the kernel
+/// may or may not have finished them.
+pub const EXECUTION_LOST_MESSAGE: &str = "Result unknown (connection lost)";
+
+pub(crate) type ChannelsWriter = Pin<Box<dyn Sink<JupyterMessage, Error = anyhow::Error>>>;
+pub(crate) type ChannelsReader = Pin<Box<dyn Stream<Item = Result<JupyterMessage>>>>;
+
+/// The server a remote kernel runs on and the session it belongs to, which the
+/// [`ChannelsSupervisor`] relays messages between.
+pub(crate) trait RemoteKernelClient {
+    /// Opens a websocket to the kernel's channels.
+    async fn open_channels(&mut self) -> Result<(ChannelsWriter, ChannelsReader)>;
+
+    /// Whether the server still has the kernel. Servers forget kernels that were shut down or
+    /// that died.
+    async fn kernel_exists(&mut self) -> Result<bool>;
+
+    /// Hands a message from the kernel to the session.
+    fn receive(&mut self, message: JupyterMessage);
+
+    /// Called with the `execute_request`s whose replies were lost with the connection.
+    fn executions_lost(&mut self, msg_ids: &[String]);
+
+    /// Called with `Some` while the connection is being recovered, and with `None` once it's
+    /// healthy again.
+    fn set_status(&mut self, status: Option<KernelStatus>);
+}
+
+/// Relays a remote kernel's messages over its websocket, and reopens the websocket when it
+/// drops.
+pub(crate) struct ChannelsSupervisor {
+    /// Requests and stdin replies for the kernel. They wait here while the connection is down.
+    pub outgoing: Pin<Box<dyn Stream<Item = JupyterMessage>>>,
+}
+
+impl ChannelsSupervisor {
+    pub const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+    pub const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(16);
+    /// How long to keep trying before the kernel is given up on.
+    pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+    /// How long a reopened connection has to answer the `kernel_info_request` sent over it.
+    pub const KERNEL_INFO_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Runs until the session is done with the kernel, or fails with why the connection
+    /// couldn't be recovered.
+    pub async fn run(
+        self,
+        client: &mut impl RemoteKernelClient,
+        channels: (ChannelsWriter, ChannelsReader),
+        executor: &BackgroundExecutor,
+    ) -> Result<()> {
+        let Self { mut outgoing } = self;
+        let (mut writer, mut reader) = channels;
+        let mut message_filter = OwnMessageFilter::default();
+        // The `execute_request`s sent over the current connection that haven't been replied to.
+        let mut in_flight = Vec::<String>::new();
+        loop {
+            let error = loop {
+                futures::select_biased! {
+                    message = reader.next().fuse() => match message {
+                        Some(Ok(message)) => {
+                            if !message_filter.accepts(&message) {
+                                continue;
+                            }
+                            if let JupyterMessageContent::ExecuteReply(_) = &message.content
+                                && let Some(parent) = &message.parent_header
+                            {
+                                in_flight.retain(|msg_id| *msg_id != parent.msg_id);
+                            }
+                            client.receive(message);
+                        }
+                        Some(Err(error)) => {
+                            log::error!("remote kernel: error receiving message: {error:?}");
+                        }
+                        None => break anyhow::anyhow!("the websocket was closed"),
+                    },
+                    message = outgoing.next().fuse() => {
+                        let Some(message) = message else {
+                            return Ok(());
+                        };
+                        message_filter.request_sent(&message);
+                        if let JupyterMessageContent::ExecuteRequest(_) = &message.content {
+                            in_flight.push(message.header.msg_id.clone());
+                        }
+                        if let Err(error) = writer.send(message).await {
+                            break error;
+                        }
+                    }
+                }
+            };
+
+            log::warn!("remote kernel: lost the connection: {error:#}");
+            client.set_status(Some(KernelStatus::Restarting));
+            if !in_flight.is_empty() {
+                client.executions_lost(&std::mem::take(&mut in_flight));
+            }
+            (writer, reader) = Self::reconnect(client, &mut message_filter, executor).await?;
+            client.set_status(None);
+        }
+    }
+
+    async fn reconnect(
+        client: &mut impl RemoteKernelClient,
+        message_filter: &mut OwnMessageFilter,
+        executor: &BackgroundExecutor,
+    ) -> Result<(ChannelsWriter, ChannelsReader)> {
+        let started_at = executor.now();
+        let mut backoff = Self::RECONNECT_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let result = async {
+                if !client.kernel_exists().await? {
+                    return Ok(None);
+                }
+                let channels = Self::reopen(client, message_filter, executor).await?;
+                anyhow::Ok(Some(channels))
+            }
+            .await;
+            let error = match result {
+                Ok(Some(channels)) => {
+                    log::info!("remote kernel: connection restored on attempt {attempt}");
+                    return Ok(channels);
+                }
+                Ok(None) => anyhow::bail!(
+                    "Lost the connection to the remote kernel, and the server no longer has it. \
+                     It may have been shut down, or culled for being idle."
+                ),
+                Err(error) => error,
+            };
+
+            log::warn!("remote kernel: failed to restore the connection: {error:#}");
+            if executor.now().saturating_duration_since(started_at) + backoff
+                > Self::RECONNECT_TIMEOUT
+            {
+                return Err(error.context(format!(
+                    "couldn't reconnect to the remote kernel within {} seconds",
+                    Self::RECONNECT_TIMEOUT.as_secs()
+                )));
+            }
+            executor.timer(backoff).await;
+            backoff = (backoff * 2).min(Self::MAX_RECONNECT_BACKOFF);
+            attempt += 1;
+        }
+    }
+
+    /// Opens the kernel's channels again, and waits for the kernel to answer a
+    /// `kernel_info_request` over them.
+    async fn reopen(
+        client: &mut impl RemoteKernelClient,
+        message_filter: &mut OwnMessageFilter,
+        executor: &BackgroundExecutor,
+    ) -> Result<(ChannelsWriter, ChannelsReader)> {
+        let (mut writer, mut reader) = client.open_channels().await?;
+        let request: JupyterMessage = KernelInfoRequest {}.into();
+        let request_id = request.header.msg_id.clone();
+        message_filter.request_sent(&request);
+        writer.send(request).await?;
+
+        let answered = async {
+            while let Some(message) = reader.next().await {
+                let Ok(message) = message else {
+                    continue;
+                };
+                if !message_filter.accepts(&message) {
+                    continue;
+                }
+                // Anything about the request shows that the kernel is alive, whether it's the
+                // reply on shell or the status updates on iopub.
+                let is_answer = message
+                    .parent_header
+                    .as_ref()
+                    .is_some_and(|parent| parent.msg_id == request_id);
+                client.receive(message);
+                if is_answer {
+                    return Ok(());
+                }
+            }
+            anyhow::bail!("the websocket was closed before the kernel answered")
+        }
+        .with_timeout(Self::KERNEL_INFO_TIMEOUT, executor)
+        .await
+        .context("the kernel didn't answer a kernel_info_request")?;
+        answered?;
+        Ok((writer, reader))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use gpui::{Task, TestAppContext};
+    use jupyter_protocol::{ExecuteReply, ExecuteRequest, ExecutionState, Status};
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    /// The server's end of a websocket the supervisor opened.
+    struct FakeConnection {
+        from_client: mpsc::UnboundedReceiver<JupyterMessage>,
+        to_client: mpsc::UnboundedSender<Result<JupyterMessage>>,
+    }
+
+    impl FakeConnection {
+        fn sent_by_client(&mut self) -> Vec<JupyterMessage> {
+            let mut messages = Vec::new();
+            while let Ok(Some(message)) = self.from_client.try_next() {
+                messages.push(message);
+            }
+            messages
+        }
+
+        fn reply(&self, message: JupyterMessage) {
+            self.to_client.unbounded_send(Ok(message)).unwrap();
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeServerState {
+        unreachable: bool,
+        kernel_gone: bool,
+        open_attempts: usize,
+        /// The connections that are still open, oldest first.
+        connections: Vec<FakeConnection>,
+        received: Vec<JupyterMessage>,
+        lost: Vec<String>,
+        statuses: Vec<Option<String>>,
+    }
+
+    /// A Jupyter server whose websockets are channels the test reads and writes.
+    #[derive(Clone, Default)]
+    struct FakeServer(Arc<Mutex<FakeServerState>>);
+
+    impl FakeServer {
+        fn state(&self) -> MutexGuard<'_, FakeServerState> {
+            self.0.lock().unwrap()
+        }
+
+        /// Drops every open websocket, like the network going away does.
+        fn drop_connections(&self) {
+            self.state().connections.clear();
+        }
+    }
+
+    impl RemoteKernelClient for FakeServer {
+        async fn open_channels(&mut self) -> Result<(ChannelsWriter, ChannelsReader)> {
+            let mut state = self.state();
+            state.open_attempts += 1;
+            anyhow::ensure!(!state.unreachable, "network is unreachable");
+            let (writer, from_client) = mpsc::unbounded();
+            let (to_client, reader) = mpsc::unbounded();
+            state.connections.push(FakeConnection {
+                from_client,
+                to_client,
+            });
+            Ok((
+                Box::pin(writer.sink_map_err(anyhow::Error::from)),
+                Box::pin(reader),
+            ))
+        }
+
+        async fn kernel_exists(&mut self) -> Result<bool> {
+            let state = self.state();
+            anyhow::ensure!(!state.unreachable, "network is unreachable");
+            Ok(!state.kernel_gone)
+        }
+
+        fn receive(&mut self, message: JupyterMessage) {
+            self.state().received.push(message);
+        }
+
+        fn executions_lost(&mut self, msg_ids: &[String]) {
+            self.state().lost.extend_from_slice(msg_ids);
+        }
+
+        fn set_status(&mut self, status: Option<KernelStatus>) {
+            self.state()
+                .statuses
+                .push(status.as_ref().map(ToString::to_string));
+        }
+    }
+
+    fn start_supervisor(
+        server: &FakeServer,
+        cx: &mut TestAppContext,
+    ) -> (mpsc::UnboundedSender<JupyterMessage>, Task<Result<()>>) {
+        let mut client = server.clone();
+        let channels = cx
+            .foreground_executor()
+            .block_test(client.open_channels())
+            .unwrap();
+        let (requests, outgoing) = mpsc::unbounded();
+        let supervisor = ChannelsSupervisor {
+            outgoing: Box::pin(outgoing),
+        };
+        let executor = cx.executor();
+        let task =
+            cx.spawn(|_| async move { supervisor.run(&mut client, channels, &executor).await });
+        (requests, task)
+    }
+
+    fn execute(code: &str) -> JupyterMessage {
+        ExecuteRequest {
+            code: code.to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn msg_ids(messages: &[JupyterMessage]) -> Vec<&str> {
+        messages
+            .iter()
+            .map(|message| message.header.msg_id.as_str())
+            .collect()
+    }
+
+    /// Answers the `kernel_info_request` the supervisor sent over the newest connection.
+    fn answer_kernel_info(server: &FakeServer, cx: &mut TestAppContext) {
+        let mut state = server.state();
+        let connection = state.connections.last_mut().unwrap();
+        let sent = connection.sent_by_client();
+        let request = sent
+            .iter()
+            .find(|message| matches!(message.content, JupyterMessageContent::KernelInfoRequest(_)))
+            .expect("the reopened connection should start with a kernel_info_request");
+        connection.reply(
+            Status {
+                execution_state: ExecutionState::Idle,
+            }
+            .as_child_of(request),
+        );
+        drop(state);
+        cx.run_until_parked();
+    }
+
+    #[gpui::test]
+    fn test_reconnects_after_connection_drops(cx: &mut TestAppContext) {
+        let server = FakeServer::default();
+        let (requests, _supervisor) = start_supervisor(&server, cx);
+
+        let first = execute("x = 1");
+        requests.unbounded_send(first.clone()).unwrap();
+        cx.run_until_parked();
+        assert_eq!(
+            msg_ids(&server.state().connections[0].sent_by_client()),
+            msg_ids(&[first])
+        );
+
+        // While the network is down, reconnecting is retried with backoff.
+        server.state().unreachable = true;
+        server.drop_connections();
+        cx.run_until_parked();
+        assert_eq!(server.state().statuses, [Some("Restarting".into())]);
+        assert_eq!(server.state().open_attempts, 1);
+
+        // Requests wait for the connection to come back.
+        let second = execute("x + 1");
+        requests.unbounded_send(second.clone()).unwrap();
+        cx.executor()
+            .advance_clock(ChannelsSupervisor::RECONNECT_BACKOFF);
+        cx.run_until_parked();
+        server.state().unreachable = false;
+        cx.executor()
+            .advance_clock(ChannelsSupervisor::RECONNECT_BACKOFF * 2);
+        cx.run_until_parked();
+        assert_eq!(server.state().open_attempts, 2);
+        assert_eq!(server.state().statuses, [Some("Restarting".into())]);
+
+        // The kernel answering the kernel_info_request restores the session.
+        answer_kernel_info(&server, cx);
+        assert_eq!(server.state().statuses, [Some("Restarting".into()), None]);
+        assert_eq!(
+            msg_ids(&server.state().connections[0].sent_by_client()),
+            msg_ids(&[second])
+        );
+        assert_eq!(server.state().received.len(), 1);
+    }
+
+    #[gpui::test]
+    fn test_gives_up_when_kernel_is_gone(cx: &mut TestAppContext) {
+        let server = FakeServer::default();
+        let (_requests, supervisor) = start_supervisor(&server, cx);
+
+        server.state().kernel_gone = true;
+        server.drop_connections();
+        cx.run_until_parked();
+        let error = cx.foreground_executor().block_test(supervisor).unwrap_err();
+
+        assert!(
+            error.to_string().contains("the server no longer has it"),
+            "{error:#}"
+        );
+        assert_eq!(server.state().statuses, [Some("Restarting".into())]);
+        assert_eq!(server.state().open_attempts, 1);
+    }
+
+    #[gpui::test]
+    fn test_in_flight_executions_are_marked_lost(cx: &mut TestAppContext) {
+        let server = FakeServer::default();
+        let (requests, _supervisor) = start_supervisor(&server, cx);
+
+        let finished = execute("print('done')");
+        let running = execute("train_model()");
+        requests.unbounded_send(finished.clone()).unwrap();
+        requests.unbounded_send(running.clone()).unwrap();
+        cx.run_until_parked();
+        server.state().connections[0].reply(ExecuteReply::default().as_child_of(&finished));
+        cx.run_until_parked();
+
+        server.drop_connections();
+        cx.run_until_parked();
+        assert_eq!(server.state().lost, [running.header.msg_id.clone()]);
+
+        // Executions sent over the restored connection are tracked from scratch.
+        answer_kernel_info(&server, cx);
+        server.drop_connections();
+        cx.run_until_parked();
+        assert_eq!(server.state().lost, [running.header.msg_id]);
+    }
+}
@@ -1,7 +1,11 @@
+use chrono::{DateTime, Utc};
+use collections::{HashMap, HashSet};
 use futures::{SinkExt as _, channel::mpsc};
-use gpui::{App, AppContext as _, Entity, Task, Window};
-use http_client::{AsyncBody, HttpClient, Request};
+use gpui::{App, AppContext as _, AsyncWindowContext, Entity, Task, Window};
+use http_client::{AsyncBody, HttpClient, Request, StatusCode};
 use jupyter_protocol::{ExecutionState, JupyterKernelspec, JupyterMessage, KernelInfoReply};
+use serde::Deserialize;
+use settings::Settings as _;
 
 use async_tungstenite::tokio::connect_async;
 use async_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue};
@@ -9,13 +13,28 @@ use async_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValu
 use futures::StreamExt;
 use smol::io::AsyncReadExt as _;
 
-use super::{KernelSession, RunningKernel};
+use super::{
+    ChannelsReader, ChannelsSupervisor, ChannelsWriter, EXECUTION_LOST_MESSAGE,
+    KernelRequestSender, KernelSession, KernelStatus, RemoteKernelClient, RunningKernel,
+    kernel_request_channel,
+};
+use crate::repl_settings::ReplSettings;
 use anyhow::Result;
 use jupyter_websocket_client::{
     JupyterWebSocket, JupyterWebSocketReader, JupyterWebSocketWriter, KernelLaunchRequest,
     KernelSpecsResponse, RemoteServer,
 };
-use std::{fmt::Debug, sync::Arc};
+use util::TryFutureExt as _;
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// How many of the requests sent to a remote kernel are remembered for telling its replies apart
+/// from those to other clients.
+const MAX_TRACKED_REQUESTS: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct RemoteKernelSpecification {
@@ -23,6 +42,131 @@ pub struct RemoteKernelSpecification {
     pub url: String,
     pub token: String,
     pub kernelspec: JupyterKernelspec,
+    /// A kernel that's already running on the server, which is attached to instead of starting
+    /// a new one.
+    pub running_kernel: Option<RunningRemoteKernel>,
+}
+
+/// A kernel running on a Jupyter server, as listed by its `/api/kernels` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningRemoteKernel {
+    pub id: String,
+    /// The name of the kernelspec the kernel was started from.
+    pub name: String,
+    pub last_activity: Option<DateTime<Utc>>,
+    pub execution_state: String,
+    /// The number of clients connected to the kernel's websocket.
+    pub connections: u64,
+    /// The server session that owns the kernel, like a notebook open in JupyterLab.
+    pub session: Option<RemoteSession>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteSession {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+struct KernelResponse {
+    id: String,
+    name: String,
+    #[serde(default)]
+    last_activity: Option<DateTime<Utc>>,
+    #[serde(default)]
+    execution_state: Option<String>,
+    #[serde(default)]
+    connections: u64,
+}
+
+#[derive(Deserialize)]
+struct SessionResponse {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    path: String,
+    kernel: Option<KernelIdResponse>,
+}
+
+#[derive(Deserialize)]
+struct KernelIdResponse {
+    id: String,
+}
+
+impl RunningRemoteKernel {
+    /// What the kernel picker calls the kernel: its session's name, falling back to the kernel's.
+    pub fn title(&self) -> String {
+        match &self.session {
+            Some(session) if !session.name.is_empty() => session.name.clone(),
+            Some(session) if !session.path.is_empty() => session.path.clone(),
+            _ => format!("{} ({})", self.name, short_kernel_id(&self.id)),
+        }
+    }
+
+    pub fn describe(&self, now: DateTime<Utc>) -> String {
+        let mut description = format!("Running, {}", self.execution_state);
+        match self.connections {
+            0 => {}
+            1 => description.push_str(", 1 connection"),
+            connections => description.push_str(&format!(", {connections} connections")),
+        }
+        if let Some(last_activity) = self.last_activity {
+            let minutes = (now - last_activity).num_minutes().max(0);
+            let ago = match minutes {
+                0 => "just now".to_string(),
+                1..60 => format!("{minutes}m ago"),
+                _ => format!("{}h ago", minutes / 60),
+            };
+            description.push_str(&format!(", active {ago}"));
+        }
+        description
+    }
+
+    fn initial_execution_state(&self) -> ExecutionState {
+        match self.execution_state.as_str() {
+            "busy" => ExecutionState::Busy,
+            "starting" => ExecutionState::Starting,
+            "restarting" => ExecutionState::Restarting,
+            "dead" => ExecutionState::Dead,
+            _ => ExecutionState::Idle,
+        }
+    }
+}
+
+fn short_kernel_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+fn api_request(
+    remote_server: &RemoteServer,
+    method: &str,
+    path: &str,
+    body: AsyncBody,
+) -> Result<Request<AsyncBody>> {
+    Ok(Request::builder()
+        .method(method)
+        .uri(&remote_server.api_url(path))
+        .header("Authorization", format!("token {}", remote_server.token))
+        .body(body)?)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    remote_server: &RemoteServer,
+    http_client: &Arc<dyn HttpClient>,
+    path: &str,
+) -> Result<T> {
+    let request = api_request(remote_server, "GET", path, AsyncBody::default())?;
+    let response = http_client.send(request).await?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "Failed to fetch {path}: {}",
+        response.status()
+    );
+    let mut body_bytes = Vec::new();
+    response.into_body().read_to_end(&mut body_bytes).await?;
+    Ok(serde_json::from_slice(&body_bytes)?)
 }
 
 pub async fn launch_remote_kernel(
@@ -97,6 +241,7 @@ pub async fn list_remote_kernelspecs(
             url: remote_server.base_url.clone(),
             token: remote_server.token.clone(),
             kernelspec: spec.spec,
+            running_kernel: None,
         })
         .collect::<Vec<RemoteKernelSpecification>>();
 
@@ -104,9 +249,264 @@ pub async fn list_remote_kernelspecs(
     Ok(remote_kernelspecs)
 }
 
+/// Lists the kernels running on the server, along with the sessions they belong to, most
+/// recently active first.
+pub async fn list_running_remote_kernels(
+    remote_server: &RemoteServer,
+    http_client: Arc<dyn HttpClient>,
+) -> Result<Vec<RunningRemoteKernel>> {
+    let kernels: Vec<KernelResponse> = get_json(remote_server, &http_client, "/kernels")
+        .anyhow_context("listing the server's kernels")
+        .await?;
+    // Servers without the sessions API still have kernels worth listing.
+    let sessions: Vec<SessionResponse> = get_json(remote_server, &http_client, "/sessions")
+        .warn_on_err_with_context(|| format!("listing sessions on {}", remote_server.base_url))
+        .await
+        .unwrap_or_default();
+
+    let mut sessions_by_kernel_id = sessions
+        .into_iter()
+        .filter_map(|session| {
+            let kernel_id = session.kernel?.id;
+            Some((
+                kernel_id,
+                RemoteSession {
+                    id: session.id,
+                    name: session.name,
+                    path: session.path,
+                },
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut running_kernels = kernels
+        .into_iter()
+        .map(|kernel| RunningRemoteKernel {
+            session: sessions_by_kernel_id.remove(&kernel.id),
+            id: kernel.id,
+            name: kernel.name,
+            last_activity: kernel.last_activity,
+            execution_state: kernel
+                .execution_state
+                .unwrap_or_else(|| "unknown".to_string()),
+            connections: kernel.connections,
+        })
+        .collect::<Vec<_>>();
+    running_kernels.sort_by(|a, b| b.last_activity.cmp(&a.last_activity));
+    Ok(running_kernels)
+}
+
+/// Pairs running kernels with the kernelspecs they were started from, so that they can be
+/// offered in the kernel picker. Kernels from kernelspecs the server no longer lists are left out,
+/// since there's no telling what language they run.
+pub fn running_remote_kernel_specifications(
+    kernelspecs: &[RemoteKernelSpecification],
+    running_kernels: Vec<RunningRemoteKernel>,
+) -> Vec<RemoteKernelSpecification> {
+    running_kernels
+        .into_iter()
+        .filter_map(|running_kernel| {
+            let spec = kernelspecs
+                .iter()
+                .find(|spec| spec.running_kernel.is_none() && spec.name == running_kernel.name)?;
+            Some(RemoteKernelSpecification {
+                running_kernel: Some(running_kernel),
+                ..spec.clone()
+            })
+        })
+        .collect()
+}
+
+/// Shuts down a kernel on the server, disconnecting every client attached to it.
+pub async fn shutdown_remote_kernel(
+    remote_server: &RemoteServer,
+    http_client: Arc<dyn HttpClient>,
+    kernel_id: &str,
+) -> Result<()> {
+    let request = api_request(
+        remote_server,
+        "DELETE",
+        &format!("/kernels/{kernel_id}"),
+        AsyncBody::default(),
+    )?;
+    let response = http_client.send(request).await?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "Failed to shutdown kernel: {}",
+        response.status()
+    );
+    Ok(())
+}
+
+/// Renames a session on the server, which is what JupyterLab shows for the kernel it owns.
+pub async fn rename_remote_session(
+    remote_server: &RemoteServer,
+    http_client: Arc<dyn HttpClient>,
+    session_id: &str,
+    name: &str,
+) -> Result<()> {
+    let body = serde_json::to_string(&serde_json::json!({ "name": name }))?;
+    let request = api_request(
+        remote_server,
+        "PATCH",
+        &format!("/sessions/{session_id}"),
+        AsyncBody::from(body),
+    )?;
+    let response = http_client.send(request).await?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "Failed to rename session: {}",
+        response.status()
+    );
+    Ok(())
+}
+
+/// Whether the server still has the kernel. Servers forget kernels that were shut down, that
+/// died, or that were culled for being idle.
+pub async fn remote_kernel_exists(
+    remote_server: &RemoteServer,
+    http_client: Arc<dyn HttpClient>,
+    kernel_id: &str,
+) -> Result<bool> {
+    let request = api_request(
+        remote_server,
+        "GET",
+        &format!("/kernels/{kernel_id}"),
+        AsyncBody::default(),
+    )?;
+    let response = http_client.send(request).await?;
+    match response.status() {
+        status if status.is_success() => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        status => anyhow::bail!("Failed to fetch kernel: {status}"),
+    }
+}
+
+/// Opens a websocket to a kernel's channels, which carries the messages of all of them.
+async fn open_kernel_channels(
+    remote_server: &RemoteServer,
+    kernel_id: &str,
+) -> Result<(ChannelsWriter, ChannelsReader)> {
+    let ws_url = format!(
+        "{}/api/kernels/{}/channels?token={}",
+        remote_server.base_url.replace("http", "ws"),
+        kernel_id,
+        remote_server.token
+    );
+
+    let mut req: Request<()> = ws_url.into_client_request()?;
+    let headers = req.headers_mut();
+
+    headers.insert(
+        "User-Agent",
+        HeaderValue::from_str(&format!(
+            "Zed/{} ({}; {})",
+            "repl",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ))?,
+    );
+
+    let (ws_stream, _response) = connect_async(req).await?;
+
+    let kernel_socket = JupyterWebSocket { inner: ws_stream };
+
+    let (w, r): (JupyterWebSocketWriter, JupyterWebSocketReader) = kernel_socket.split();
+    Ok((
+        Box::pin(w.sink_map_err(anyhow::Error::from)),
+        Box::pin(r.map(|message| message.map_err(anyhow::Error::from))),
+    ))
+}
+
+/// The websocket of a [`RemoteRunningKernel`], which relays the kernel's messages to its
+/// session.
+struct RemoteKernelChannels<S: KernelSession + 'static> {
+    remote_server: RemoteServer,
+    http_client: Arc<dyn HttpClient>,
+    kernel_id: String,
+    session: Entity<S>,
+    connection_status: Arc<Mutex<Option<KernelStatus>>>,
+    cx: AsyncWindowContext,
+}
+
+impl<S: KernelSession + 'static> RemoteKernelClient for RemoteKernelChannels<S> {
+    async fn open_channels(&mut self) -> Result<(ChannelsWriter, ChannelsReader)> {
+        open_kernel_channels(&self.remote_server, &self.kernel_id).await
+    }
+
+    async fn kernel_exists(&mut self) -> Result<bool> {
+        remote_kernel_exists(
+            &self.remote_server,
+            self.http_client.clone(),
+            &self.kernel_id,
+        )
+        .await
+    }
+
+    fn receive(&mut self, message: JupyterMessage) {
+        self.session
+            .update_in(&mut self.cx, |session, window, cx| {
+                session.route(&message, window, cx);
+            })
+            .ok();
+    }
+
+    fn executions_lost(&mut self, msg_ids: &[String]) {
+        self.session.update(&mut self.cx, |session, cx| {
+            session.requests_failed(msg_ids, EXECUTION_LOST_MESSAGE.to_string(), cx);
+            cx.notify();
+        });
+    }
+
+    fn set_status(&mut self, status: Option<KernelStatus>) {
+        *self
+            .connection_status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = status;
+        self.session.update(&mut self.cx, |_, cx| cx.notify());
+    }
+}
+
+/// Remembers the requests this client sent to a kernel, so that messages caused by other clients
+/// attached to the same kernel can be ignored. The server broadcasts iopub messages to every
+/// connection, and replies to other clients' requests can arrive interleaved with ours.
+#[derive(Debug, Default)]
+pub struct OwnMessageFilter {
+    sent: HashSet<String>,
+    /// The tracked requests, oldest first, so that the oldest can be forgotten.
+    order: VecDeque<String>,
+}
+
+impl OwnMessageFilter {
+    pub fn request_sent(&mut self, message: &JupyterMessage) {
+        let msg_id = message.header.msg_id.clone();
+        if !self.sent.insert(msg_id.clone()) {
+            return;
+        }
+        self.order.push_back(msg_id);
+        if self.order.len() > MAX_TRACKED_REQUESTS
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.sent.remove(&oldest);
+        }
+    }
+
+    /// Messages without a parent, like the status the kernel reports when it starts, aren't
+    /// caused by any client, so they're always accepted.
+    pub fn accepts(&self, message: &JupyterMessage) -> bool {
+        match &message.parent_header {
+            Some(parent) if !parent.msg_id.is_empty() => self.sent.contains(&parent.msg_id),
+            _ => true,
+        }
+    }
+}
+
 impl PartialEq for RemoteKernelSpecification {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.url == other.url
+        self.name == other.name
+            && self.url == other.url
+            && self.running_kernel.as_ref().map(|kernel| &kernel.id)
+                == other.running_kernel.as_ref().map(|kernel| &kernel.id)
     }
 }
 
@@ -114,15 +514,20 @@ impl Eq for RemoteKernelSpecification {}
 
 pub struct RemoteRunningKernel {
     remote_server: RemoteServer,
-    _receiving_task: Task<Result<()>>,
-    _routing_task: Task<Result<()>>,
+    /// Relays messages over the kernel's websocket, and reopens it when it drops.
+    _connection_task: Task<()>,
+    /// Set while the websocket is being reopened.
+    connection_status: Arc<Mutex<Option<KernelStatus>>>,
     http_client: Arc<dyn HttpClient>,
     pub working_directory: std::path::PathBuf,
-    pub request_tx: mpsc::Sender<JupyterMessage>,
+    pub request_tx: KernelRequestSender,
     pub stdin_tx: mpsc::Sender<JupyterMessage>,
     pub execution_state: ExecutionState,
     pub kernel_info: Option<KernelInfoReply>,
     pub kernel_id: String,
+    /// Whether this attached to a kernel that was already running, which is left running when
+    /// the session is done with it.
+    pub attached: bool,
 }
 
 impl RemoteRunningKernel {
@@ -140,91 +545,71 @@ impl RemoteRunningKernel {
 
         let http_client = cx.http_client();
 
-        window.spawn(cx, async move |cx| {
-            let kernel_id = launch_remote_kernel(
-                &remote_server,
-                http_client.clone(),
-                &kernelspec.name,
-                working_directory.to_str().unwrap_or_default(),
-            )
-            .await?;
-
-            let ws_url = format!(
-                "{}/api/kernels/{}/channels?token={}",
-                remote_server.base_url.replace("http", "ws"),
-                kernel_id,
-                remote_server.token
-            );
-
-            let mut req: Request<()> = ws_url.into_client_request()?;
-            let headers = req.headers_mut();
-
-            headers.insert(
-                "User-Agent",
-                HeaderValue::from_str(&format!(
-                    "Zed/{} ({}; {})",
-                    "repl",
-                    std::env::consts::OS,
-                    std::env::consts::ARCH
-                ))?,
-            );
-
-            let response = connect_async(req).await;
+        let running_kernel = kernelspec.running_kernel;
+        let request_capacity =
+            ReplSettings::get_global(cx).request_queue_capacity(&kernelspec.name);
 
-            let (ws_stream, _response) = response?;
-
-            let kernel_socket = JupyterWebSocket { inner: ws_stream };
-
-            let (mut w, mut r): (JupyterWebSocketWriter, JupyterWebSocketReader) =
-                kernel_socket.split();
-
-            let (request_tx, mut request_rx) =
-                futures::channel::mpsc::channel::<JupyterMessage>(100);
-
-            let routing_task = cx.background_spawn({
-                async move {
-                    while let Some(message) = request_rx.next().await {
-                        w.send(message).await.ok();
-                    }
-                    Ok(())
+        window.spawn(cx, async move |cx| {
+            let (kernel_id, execution_state) = match &running_kernel {
+                Some(running_kernel) => (
+                    running_kernel.id.clone(),
+                    running_kernel.initial_execution_state(),
+                ),
+                None => {
+                    let kernel_id = launch_remote_kernel(
+                        &remote_server,
+                        http_client.clone(),
+                        &kernelspec.name,
+                        working_directory.to_str().unwrap_or_default(),
+                    )
+                    .await?;
+                    (kernel_id, ExecutionState::Idle)
                 }
-            });
-
-            let receiving_task = cx.spawn({
-                let session = session.clone();
-
-                async move |cx| {
-                    while let Some(message) = r.next().await {
-                        match message {
-                            Ok(message) => {
-                                session
-                                    .update_in(cx, |session, window, cx| {
-                                        session.route(&message, window, cx);
-                                    })
-                                    .ok();
-                            }
-                            Err(e) => {
-                                log::error!("Error receiving message: {:?}", e);
-                            }
-                        }
-                    }
-                    Ok(())
+            };
+
+            let channels = open_kernel_channels(&remote_server, &kernel_id).await?;
+
+            let (request_tx, request_rx) = kernel_request_channel(request_capacity);
+            // The websocket carries stdin replies along with everything else.
+            let (stdin_tx, stdin_rx) = mpsc::channel::<JupyterMessage>(100);
+            let supervisor = ChannelsSupervisor {
+                outgoing: Box::pin(futures::stream::select(Box::pin(request_rx), stdin_rx)),
+            };
+            let connection_status = Arc::<Mutex<Option<KernelStatus>>>::default();
+            let mut client = RemoteKernelChannels {
+                remote_server: RemoteServer {
+                    base_url: remote_server.base_url.clone(),
+                    token: remote_server.token.clone(),
+                },
+                http_client: http_client.clone(),
+                kernel_id: kernel_id.clone(),
+                session: session.clone(),
+                connection_status: connection_status.clone(),
+                cx: cx.clone(),
+            };
+            let executor = cx.background_executor().clone();
+            let connection_task = cx.spawn(async move |cx| {
+                if let Err(error) = supervisor.run(&mut client, channels, &executor).await {
+                    log::error!("remote kernel: {error:#}");
+                    client.set_status(None);
+                    session.update(cx, |session, cx| {
+                        session.kernel_errored(format!("{error:#}"), cx);
+                        cx.notify();
+                    });
                 }
             });
 
-            let stdin_tx = request_tx.clone();
-
             anyhow::Ok(Box::new(Self {
-                _routing_task: routing_task,
-                _receiving_task: receiving_task,
+                _connection_task: connection_task,
+                connection_status,
                 remote_server,
                 working_directory,
                 request_tx,
                 stdin_tx,
-                // todo(kyle): pull this from the kernel API to start with
-                execution_state: ExecutionState::Idle,
+                execution_state,
                 kernel_info: None,
                 kernel_id,
+                attached: running_kernel.is_some(),
                 http_client: http_client.clone(),
             }) as Box<dyn RunningKernel>)
         })
@@ -240,12 +625,13 @@ impl Debug for RemoteRunningKernel {
             .field("request_tx", &self.request_tx)
             .field("execution_state", &self.execution_state)
             .field("kernel_info", &self.kernel_info)
+            .field("attached", &self.attached)
             .finish()
     }
 }
 
 impl RunningKernel for RemoteRunningKernel {
-    fn request_tx(&self) -> futures::channel::mpsc::Sender<runtimelib::JupyterMessage> {
+    fn request_tx(&self) -> KernelRequestSender {
         self.request_tx.clone()
     }
 
@@ -274,27 +660,22 @@ impl RunningKernel for RemoteRunningKernel {
     }
 
     fn force_shutdown(&mut self, window: &mut Window, cx: &mut App) -> Task<anyhow::Result<()>> {
-        let url = self
-            .remote_server
-            .api_url(&format!("/kernels/{}", self.kernel_id));
-        let token = self.remote_server.token.clone();
+        // A kernel that was attached to belongs to whoever started it, so only disconnect from
+        // it. Closing the channels also drops the shutdown request the session sends next.
+        if self.attached {
+            self.kill();
+            return Task::ready(Ok(()));
+        }
+
+        let remote_server = RemoteServer {
+            base_url: self.remote_server.base_url.clone(),
+            token: self.remote_server.token.clone(),
+        };
+        let kernel_id = self.kernel_id.clone();
         let http_client = self.http_client.clone();
 
         window.spawn(cx, async move |_| {
-            let request = Request::builder()
-                .method("DELETE")
-                .uri(&url)
-                .header("Authorization", format!("token {}", token))
-                .body(AsyncBody::default())?;
-
-            let response = http_client.send(request).await?;
-
-            anyhow::ensure!(
-                response.status().is_success(),
-                "Failed to shutdown kernel: {}",
-                response.status()
-            );
-            Ok(())
+            shutdown_remote_kernel(&remote_server, http_client, &kernel_id).await
         })
     }
 
@@ -302,4 +683,287 @@ impl RunningKernel for RemoteRunningKernel {
         self.request_tx.close_channel();
         self.stdin_tx.close_channel();
     }
+
+    fn connection_status(&self) -> Option<KernelStatus> {
+        self.connection_status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_client::{FakeHttpClient, Method, Response};
+    use runtimelib::{ExecuteReply, ExecuteRequest, Status};
+    use smol::io::AsyncReadExt as _;
+
+    fn status(execution_state: ExecutionState) -> Status {
+        Status { execution_state }
+    }
+
+    /// The requests a stub server received, as their method, path and body.
+    type Requests = Arc<Mutex<Vec<(Method, String, String)>>>;
+
+    /// A stub Jupyter server that answers the kernel and session APIs with fixed responses.
+    fn stub_server() -> (Arc<dyn HttpClient>, Requests) {
+        let requests = Requests::default();
+        let http_client = FakeHttpClient::create({
+            let requests = requests.clone();
+            move |request| {
+                let requests = requests.clone();
+                async move {
+                    let method = request.method().clone();
+                    let path = request.uri().path().to_string();
+                    let mut body = String::new();
+                    request.into_body().read_to_string(&mut body).await?;
+                    requests
+                        .lock()
+                        .unwrap()
+                        .push((method.clone(), path.clone(), body));
+
+                    let response = match (method, path.as_str()) {
+                        (Method::GET, "/user/ada/api/kernels") => serde_json::json!([
+                            {
+                                "id": "0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0",
+                                "name": "python3",
+                                "last_activity": "2026-10-16T10:00:00.000000Z",
+                                "execution_state": "idle",
+                                "connections": 1
+                            },
+                            {
+                                "id": "9a8b7c6d-5e4f-3a2b-1c0d-e9f8a7b6c5d4",
+                                "name": "python3",
+                                "last_activity": "2026-10-16T11:30:00.000000Z",
+                                "execution_state": "busy",
+                                "connections": 0
+                            },
+                            {
+                                "id": "deadbeef-0000-0000-0000-000000000000",
+                                "name": "retired",
+                                "execution_state": "idle",
+                                "connections": 0
+                            }
+                        ]),
+                        (Method::GET, "/user/ada/api/sessions") => serde_json::json!([
+                            {
+                                "id": "f00dfeed-1111-2222-3333-444455556666",
+                                "path": "analysis.ipynb",
+                                "name": "analysis.ipynb",
+                                "type": "notebook",
+                                "kernel": { "id": "0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0" }
+                            }
+                        ]),
+                        (
+                            Method::GET,
+                            "/user/ada/api/kernels/0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0",
+                        ) => serde_json::json!({
+                            "id": "0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0",
+                            "name": "python3"
+                        }),
+                        (
+                            Method::DELETE,
+                            "/user/ada/api/kernels/9a8b7c6d-5e4f-3a2b-1c0d-e9f8a7b6c5d4",
+                        )
+                        | (
+                            Method::PATCH,
+                            "/user/ada/api/sessions/f00dfeed-1111-2222-3333-444455556666",
+                        ) => {
+                            serde_json::json!({})
+                        }
+                        _ => {
+                            return Ok(Response::builder()
+                                .status(404)
+                                .body(AsyncBody::default())?);
+                        }
+                    };
+                    Ok(Response::builder()
+                        .status(200)
+                        .body(AsyncBody::from(response.to_string()))?)
+                }
+            }
+        });
+        (http_client, requests)
+    }
+
+    fn remote_server() -> RemoteServer {
+        RemoteServer {
+            base_url: "http://hub.test/user/ada".to_string(),
+            token: "secret".to_string(),
+        }
+    }
+
+    fn kernelspec(name: &str) -> RemoteKernelSpecification {
+        RemoteKernelSpecification {
+            name: name.to_string(),
+            url: "http://hub.test/user/ada".to_string(),
+            token: "secret".to_string(),
+            kernelspec: JupyterKernelspec {
+                argv: Vec::new(),
+                display_name: "Python 3".to_string(),
+                language: "python".to_string(),
+                metadata: None,
+                interrupt_mode: None,
+                env: None,
+            },
+            running_kernel: None,
+        }
+    }
+
+    #[test]
+    fn test_list_running_remote_kernels() {
+        let (http_client, _) = stub_server();
+        let running_kernels =
+            smol::block_on(list_running_remote_kernels(&remote_server(), http_client)).unwrap();
+
+        // Most recently active first, with the session the kernel belongs to.
+        assert_eq!(
+            running_kernels
+                .iter()
+                .map(|kernel| (kernel.title(), kernel.execution_state.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("python3 (9a8b7c6d)".to_string(), "busy"),
+                ("analysis.ipynb".to_string(), "idle"),
+                ("retired (deadbeef)".to_string(), "idle"),
+            ]
+        );
+        assert_eq!(
+            running_kernels[1].session,
+            Some(RemoteSession {
+                id: "f00dfeed-1111-2222-3333-444455556666".to_string(),
+                name: "analysis.ipynb".to_string(),
+                path: "analysis.ipynb".to_string(),
+            })
+        );
+
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        assert_eq!(
+            running_kernels[0].describe(now),
+            "Running, busy, active 30m ago"
+        );
+        assert_eq!(
+            running_kernels[1].describe(now),
+            "Running, idle, 1 connection, active 2h ago"
+        );
+        assert_eq!(running_kernels[2].describe(now), "Running, idle");
+
+        // Only kernels from known kernelspecs can be offered, since the language is needed.
+        let specs = running_remote_kernel_specifications(&[kernelspec("python3")], running_kernels);
+        assert_eq!(
+            specs
+                .iter()
+                .map(|spec| spec.running_kernel.as_ref().unwrap().id.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "9a8b7c6d-5e4f-3a2b-1c0d-e9f8a7b6c5d4",
+                "0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0"
+            ]
+        );
+        assert_ne!(specs[0], specs[1]);
+        assert_ne!(specs[0], kernelspec("python3"));
+        assert_eq!(
+            specs[0]
+                .running_kernel
+                .as_ref()
+                .unwrap()
+                .initial_execution_state(),
+            ExecutionState::Busy
+        );
+    }
+
+    #[test]
+    fn test_shut_down_and_rename() {
+        let (http_client, requests) = stub_server();
+        smol::block_on(async {
+            shutdown_remote_kernel(
+                &remote_server(),
+                http_client.clone(),
+                "9a8b7c6d-5e4f-3a2b-1c0d-e9f8a7b6c5d4",
+            )
+            .await
+            .unwrap();
+            rename_remote_session(
+                &remote_server(),
+                http_client.clone(),
+                "f00dfeed-1111-2222-3333-444455556666",
+                "Quarterly report",
+            )
+            .await
+            .unwrap();
+            assert!(
+                shutdown_remote_kernel(&remote_server(), http_client, "missing")
+                    .await
+                    .is_err()
+            );
+        });
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[..2],
+            [
+                (
+                    Method::DELETE,
+                    "/user/ada/api/kernels/9a8b7c6d-5e4f-3a2b-1c0d-e9f8a7b6c5d4".to_string(),
+                    String::new()
+                ),
+                (
+                    Method::PATCH,
+                    "/user/ada/api/sessions/f00dfeed-1111-2222-3333-444455556666".to_string(),
+                    r#"{"name":"Quarterly report"}"#.to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remote_kernel_exists() {
+        let (http_client, _) = stub_server();
+        smol::block_on(async {
+            assert!(
+                remote_kernel_exists(
+                    &remote_server(),
+                    http_client.clone(),
+                    "0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0"
+                )
+                .await
+                .unwrap()
+            );
+            // A kernel the server no longer has is reported as gone, not as an error.
+            assert!(
+                !remote_kernel_exists(&remote_server(), http_client, "culled")
+                    .await
+                    .unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_messages_for_other_clients_are_filtered() {
+        let mut filter = OwnMessageFilter::default();
+        let ours: JupyterMessage = ExecuteRequest::default().into();
+        let theirs: JupyterMessage = ExecuteRequest::default().into();
+        filter.request_sent(&ours);
+
+        // Another client's execution shows up on iopub and its reply interleaves with ours.
+        let their_status = status(ExecutionState::Busy).as_child_of(&theirs);
+        let their_reply = ExecuteReply::default().as_child_of(&theirs);
+        let our_status = status(ExecutionState::Busy).as_child_of(&ours);
+        let our_reply = ExecuteReply::default().as_child_of(&ours);
+        assert!(!filter.accepts(&their_status));
+        assert!(filter.accepts(&our_status));
+        assert!(!filter.accepts(&their_reply));
+        assert!(filter.accepts(&our_reply));
+
+        // The kernel's own status updates aren't a reply to anyone.
+        let starting: JupyterMessage = status(ExecutionState::Starting).into();
+        assert!(filter.accepts(&starting));
+
+        // Only the most recent requests are remembered.
+        for _ in 0..MAX_TRACKED_REQUESTS {
+            filter.request_sent(&ExecuteRequest::default().into());
+        }
+        assert!(!filter.accepts(&our_reply));
+    }
 }
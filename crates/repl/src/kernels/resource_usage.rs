@@ -0,0 +1,405 @@
+//! Samples the CPU and memory use of the processes running the kernels Zed spawned, so the
+//! kernel that's eating all the memory can be told apart from the others.
+
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+use futures::{FutureExt as _, channel::mpsc, future::BoxFuture};
+use gpui::{BackgroundExecutor, Task};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use super::{CommandRunner, InstallCommand};
+
+/// How often a kernel's process is sampled.
+pub const RESOURCE_SAMPLE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Clock ticks per second in `/proc/<pid>/stat`, which Linux fixes at 100 for userspace.
+const PROC_CLOCK_TICKS_PER_SECOND: u64 = 100;
+
+/// How much of the machine a kernel's process is using.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResourceUsage {
+    /// The share of a single CPU the process used since the previous sample, which goes over 100
+    /// when it runs on several cores.
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MB: f64 = 1024.0 * 1024.0;
+        const GB: f64 = 1024.0 * MB;
+
+        let rss = self.rss_bytes as f64;
+        if rss >= GB {
+            write!(f, "{:.1} GB", rss / GB)?;
+        } else {
+            write!(f, "{:.0} MB", rss / MB)?;
+        }
+        write!(f, ", {:.0}% CPU", self.cpu_percent)
+    }
+}
+
+/// A reading of a process's counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcessSample {
+    /// When the process started, in whatever units the sampler reads it in. Only compared with
+    /// other samples from the same sampler, to notice the process id being reused.
+    pub start_time: u64,
+    /// The CPU time the process has used since it started.
+    pub cpu_time: Duration,
+    pub rss_bytes: u64,
+}
+
+/// Reads the counters of the process running a kernel.
+pub trait ProcessSampler: Send + Sync {
+    /// Samples the process, resolving to `None` once it no longer exists. Errors mean the
+    /// process couldn't be read this time, and are worth trying again.
+    fn sample(&self) -> BoxFuture<'static, Result<Option<ProcessSample>>>;
+}
+
+/// Samples a process on this machine.
+pub struct SystemProcessSampler {
+    pid: Pid,
+    system: Mutex<System>,
+}
+
+impl SystemProcessSampler {
+    pub fn new(pid: u32) -> Self {
+        Self {
+            pid: Pid::from_u32(pid),
+            system: Mutex::new(System::new()),
+        }
+    }
+}
+
+impl ProcessSampler for SystemProcessSampler {
+    fn sample(&self) -> BoxFuture<'static, Result<Option<ProcessSample>>> {
+        let mut system = self.system.lock().unwrap_or_else(PoisonError::into_inner);
+        let refreshed = system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[self.pid]),
+            true,
+            ProcessRefreshKind::nothing().with_cpu().with_memory(),
+        );
+        let sample = system
+            .process(self.pid)
+            .filter(|_| refreshed == 1)
+            .map(|process| ProcessSample {
+                start_time: process.start_time(),
+                cpu_time: Duration::from_millis(process.accumulated_cpu_time()),
+                rss_bytes: process.memory(),
+            });
+        futures::future::ready(Ok(sample)).boxed()
+    }
+}
+
+/// Samples a Linux process that isn't on this machine by reading its `/proc` entries, running
+/// `cat` through a command that reaches the other machine, such as `wsl --exec` or `ssh`.
+pub struct ProcFsSampler {
+    pid: u32,
+    command: InstallCommand,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl ProcFsSampler {
+    /// The arguments to `cat` that print what's sampled for `pid`.
+    pub fn cat_args(pid: u32) -> Vec<String> {
+        vec![format!("/proc/{pid}/stat"), format!("/proc/{pid}/status")]
+    }
+
+    /// `command` should run `cat` with [`Self::cat_args`] for `pid`.
+    pub fn new(pid: u32, command: InstallCommand, runner: Arc<dyn CommandRunner>) -> Self {
+        Self {
+            pid,
+            command,
+            runner,
+        }
+    }
+}
+
+impl ProcessSampler for ProcFsSampler {
+    fn sample(&self) -> BoxFuture<'static, Result<Option<ProcessSample>>> {
+        let pid = self.pid;
+        let output = self.runner.run(&self.command, mpsc::unbounded().0);
+        async move {
+            let output = output.await?;
+            if !output.success {
+                // `/proc` is missing the process once it's gone, and missing entirely on hosts
+                // that aren't Linux. Anything else, like the connection dropping, may pass.
+                if output.output.contains("No such file or directory") {
+                    return Ok(None);
+                }
+                anyhow::bail!("failed to read /proc/{pid}: {}", output.output.trim());
+            }
+            parse_proc_fs(pid, &output.output)
+                .map(Some)
+                .with_context(|| format!("unexpected contents in /proc/{pid}"))
+        }
+        .boxed()
+    }
+}
+
+/// Parses the output of `cat /proc/<pid>/stat /proc/<pid>/status`.
+fn parse_proc_fs(pid: u32, output: &str) -> Option<ProcessSample> {
+    let stat = output
+        .lines()
+        .find(|line| line.starts_with(&format!("{pid} (")))?;
+    // The command name is in parentheses and can contain anything, including spaces and
+    // parentheses, so the fields are counted from the last closing parenthesis, which ends
+    // the second field.
+    let fields = stat
+        .rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let field = |number: usize| fields.get(number - 3)?.parse::<u64>().ok();
+    let cpu_ticks = field(14)? + field(15)?;
+    let start_time = field(22)?;
+
+    let rss_kb = output
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(ProcessSample {
+        start_time,
+        cpu_time: Duration::from_millis(cpu_ticks * 1000 / PROC_CLOCK_TICKS_PER_SECOND),
+        rss_bytes: rss_kb * 1024,
+    })
+}
+
+/// Samples a kernel's process every [`RESOURCE_SAMPLE_INTERVAL`] until it's dropped, or until
+/// the process is gone.
+pub struct ResourceMonitor {
+    usage: Arc<Mutex<Option<ResourceUsage>>>,
+    _sample_task: Task<()>,
+}
+
+impl ResourceMonitor {
+    /// Starts sampling once `sampler` resolves, which it does with `None` when the kernel's
+    /// process can't be found.
+    pub fn new(
+        sampler: impl Future<Output = Option<Box<dyn ProcessSampler>>> + Send + 'static,
+        executor: &BackgroundExecutor,
+    ) -> Self {
+        let usage = Arc::new(Mutex::new(None));
+        let sample_task = executor.spawn({
+            let usage = usage.clone();
+            let executor = executor.clone();
+            async move {
+                let Some(sampler) = sampler.await else {
+                    return;
+                };
+                let mut tracker = UsageTracker::default();
+                loop {
+                    let reading = match sampler.sample().await {
+                        Ok(sample) => tracker.record(sample, executor.now()),
+                        Err(error) => {
+                            log::debug!("failed to sample kernel process: {error:#}");
+                            Reading::Usage(None)
+                        }
+                    };
+                    let (latest, gone) = match reading {
+                        Reading::Usage(usage) => (usage, false),
+                        Reading::Gone => (None, true),
+                    };
+                    *usage.lock().unwrap_or_else(PoisonError::into_inner) = latest;
+                    if gone {
+                        break;
+                    }
+                    executor.timer(RESOURCE_SAMPLE_INTERVAL).await;
+                }
+            }
+        });
+        Self {
+            usage,
+            _sample_task: sample_task,
+        }
+    }
+
+    /// The usage as of the latest sample, or `None` until there have been two samples to
+    /// measure CPU use between.
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        *self.usage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+enum Reading {
+    Usage(Option<ResourceUsage>),
+    /// The process exited, or its id now belongs to another process.
+    Gone,
+}
+
+/// Turns a process's samples into usage.
+#[derive(Default)]
+struct UsageTracker {
+    start_time: Option<u64>,
+    previous: Option<(ProcessSample, Instant)>,
+}
+
+impl UsageTracker {
+    fn record(&mut self, sample: Option<ProcessSample>, now: Instant) -> Reading {
+        let Some(sample) = sample else {
+            return Reading::Gone;
+        };
+        let start_time = *self.start_time.get_or_insert(sample.start_time);
+        if sample.start_time != start_time {
+            log::debug!("kernel process exited and its id was reused");
+            return Reading::Gone;
+        }
+
+        let previous = self.previous.replace((sample, now));
+        let usage = previous.and_then(|(previous, sampled_at)| {
+            let elapsed = now.checked_duration_since(sampled_at)?;
+            if elapsed.is_zero() {
+                return None;
+            }
+            let cpu_time = sample.cpu_time.saturating_sub(previous.cpu_time);
+            Some(ResourceUsage {
+                cpu_percent: (cpu_time.as_secs_f64() / elapsed.as_secs_f64() * 100.0) as f32,
+                rss_bytes: sample.rss_bytes,
+            })
+        });
+        Reading::Usage(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    /// Reports whatever the test last set, counting how often it's sampled.
+    #[derive(Clone, Default)]
+    struct FakeSampler {
+        sample: Arc<Mutex<Option<ProcessSample>>>,
+        samples_taken: Arc<AtomicUsize>,
+    }
+
+    impl FakeSampler {
+        fn set(&self, sample: Option<ProcessSample>) {
+            *self.sample.lock().unwrap() = sample;
+        }
+
+        fn samples_taken(&self) -> usize {
+            self.samples_taken.load(Ordering::SeqCst)
+        }
+    }
+
+    impl ProcessSampler for FakeSampler {
+        fn sample(&self) -> BoxFuture<'static, Result<Option<ProcessSample>>> {
+            self.samples_taken.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(Ok(*self.sample.lock().unwrap())).boxed()
+        }
+    }
+
+    fn sample(start_time: u64, cpu_millis: u64, rss_bytes: u64) -> Option<ProcessSample> {
+        Some(ProcessSample {
+            start_time,
+            cpu_time: Duration::from_millis(cpu_millis),
+            rss_bytes,
+        })
+    }
+
+    fn monitor(sampler: &FakeSampler, cx: &TestAppContext) -> ResourceMonitor {
+        let sampler = Box::new(sampler.clone()) as Box<dyn ProcessSampler>;
+        ResourceMonitor::new(async move { Some(sampler) }, &cx.executor())
+    }
+
+    fn next_sample(cx: &TestAppContext) {
+        cx.executor().advance_clock(RESOURCE_SAMPLE_INTERVAL);
+        cx.run_until_parked();
+    }
+
+    #[gpui::test]
+    fn test_usage_between_samples(cx: &mut TestAppContext) {
+        let sampler = FakeSampler::default();
+        sampler.set(sample(1, 0, 2 * GB));
+        let monitor = monitor(&sampler, cx);
+        cx.run_until_parked();
+        assert_eq!(monitor.usage(), None);
+
+        sampler.set(sample(1, 2_610, 2 * GB + GB / 3));
+        next_sample(cx);
+        let usage = monitor.usage().unwrap();
+        assert_eq!(usage.to_string(), "2.3 GB, 87% CPU");
+
+        // Dropping the monitor, as shutting the kernel down does, stops the sampling.
+        drop(monitor);
+        let samples_taken = sampler.samples_taken();
+        next_sample(cx);
+        assert_eq!(sampler.samples_taken(), samples_taken);
+    }
+
+    #[gpui::test]
+    fn test_reused_pid_stops_sampling(cx: &mut TestAppContext) {
+        let sampler = FakeSampler::default();
+        sampler.set(sample(1, 0, GB));
+        let monitor = monitor(&sampler, cx);
+        cx.run_until_parked();
+        sampler.set(sample(1, 1_000, GB));
+        next_sample(cx);
+        assert!(monitor.usage().is_some());
+
+        // The kernel exited and another process got its id.
+        sampler.set(sample(2, 5_000, 10 * GB));
+        next_sample(cx);
+        assert_eq!(monitor.usage(), None);
+        let samples_taken = sampler.samples_taken();
+        next_sample(cx);
+        assert_eq!(sampler.samples_taken(), samples_taken);
+        assert_eq!(monitor.usage(), None);
+    }
+
+    #[gpui::test]
+    fn test_exited_process_stops_sampling(cx: &mut TestAppContext) {
+        let sampler = FakeSampler::default();
+        sampler.set(sample(1, 0, GB));
+        let monitor = monitor(&sampler, cx);
+        cx.run_until_parked();
+        sampler.set(sample(1, 500, 512 * 1024 * 1024));
+        next_sample(cx);
+        assert_eq!(monitor.usage().unwrap().to_string(), "512 MB, 17% CPU");
+
+        sampler.set(None);
+        next_sample(cx);
+        assert_eq!(monitor.usage(), None);
+        let samples_taken = sampler.samples_taken();
+        next_sample(cx);
+        assert_eq!(sampler.samples_taken(), samples_taken);
+    }
+
+    #[test]
+    fn test_parse_proc_fs() {
+        let output = "\
+            4242 (python3 (ipykernel)) S 1 4242 4242 0 -1 4194560 52830 0 0 0 1234 56 0 0 20 0 \
+            3 0 987654 3000000000 600000 18446744073709551615 1 1 0 0 0 0 0 16781312 17642 0 0 0 \
+            17 2 0 0 0 0 0\n\
+            Name:\tpython3\n\
+            State:\tS (sleeping)\n\
+            VmPeak:\t 3100000 kB\n\
+            VmRSS:\t 2400000 kB\n\
+            Threads:\t3\n";
+        assert_eq!(
+            parse_proc_fs(4242, output),
+            Some(ProcessSample {
+                start_time: 987654,
+                cpu_time: Duration::from_millis(12_900),
+                rss_bytes: 2_400_000 * 1024,
+            })
+        );
+        assert_eq!(parse_proc_fs(4243, output), None);
+    }
+}
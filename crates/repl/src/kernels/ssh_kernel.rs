@@ -1,30 +1,59 @@
-use super::{KernelSession, RunningKernel, SshRemoteKernelSpecification, start_kernel_tasks};
+use super::{
+    InstallCommand, KernelMessageCounts, KernelMessageInspectors, KernelPorts, KernelRequestSender,
+    KernelSession, KernelStatus, KernelTunnel, ProcFsSampler, ProcessSampler, ResourceMonitor,
+    ResourceUsage, RunningKernel, SshPortForwarder, SshRemoteKernelSpecification,
+    SystemCommandRunner, TunnelClient, TunnelSupervisor, kernel_message_inspectors,
+    kernel_request_channel, start_kernel_tasks_with_error_handler,
+};
+use crate::repl_settings::ReplSettings;
 use anyhow::{Context as _, Result};
-use client::proto;
+use client::{AnyProtoClient, proto};
+use collections::HashMap;
 
-use futures::{
-    AsyncBufReadExt as _, StreamExt as _,
-    channel::mpsc::{self},
-    io::BufReader,
-};
-use gpui::{App, Entity, Task, Window};
+use futures::{SinkExt as _, Stream, StreamExt as _, channel::mpsc};
+use gpui::{App, AsyncWindowContext, BackgroundExecutor, Entity, Task, Window};
 use project::Project;
+use remote::Interactive;
 use runtimelib::{ExecutionState, JupyterMessage, KernelInfoReply};
-use std::path::PathBuf;
+use settings::Settings as _;
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    future::Future,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError},
+};
 use util::ResultExt;
 
-#[derive(Debug)]
 pub struct SshRunningKernel {
-    request_tx: mpsc::Sender<JupyterMessage>,
+    request_tx: KernelRequestSender,
     stdin_tx: mpsc::Sender<JupyterMessage>,
     execution_state: ExecutionState,
     kernel_info: Option<KernelInfoReply>,
     working_directory: PathBuf,
-    _ssh_tunnel_process: util::command::Child,
+    /// Set by the tunnel supervisor while the connection is being recovered.
+    connection_status: Arc<Mutex<Option<KernelStatus>>>,
+    /// Relays requests to the kernel's current connection, and keeps the connection alive.
+    connection_tasks: Vec<Task<()>>,
+    resource_monitor: Option<ResourceMonitor>,
     _local_connection_file: PathBuf,
     kernel_id: String,
-    project: Entity<Project>,
+    remote_pid: u32,
+    proto_client: AnyProtoClient,
     project_id: u64,
+    executor: BackgroundExecutor,
+    message_counts: Option<KernelMessageCounts>,
+}
+
+impl Debug for SshRunningKernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SshRunningKernel")
+            .field("kernel_id", &self.kernel_id)
+            .field("remote_pid", &self.remote_pid)
+            .field("working_directory", &self.working_directory)
+            .finish()
+    }
 }
 
 impl SshRunningKernel {
@@ -42,6 +71,14 @@ impl SshRunningKernel {
             .read(cx)
             .remote_id()
             .unwrap_or(proto::REMOTE_SERVER_PROJECT_ID);
+        let proto_client = match remote_client.as_ref() {
+            Some(remote_client) => remote_client.read(cx).proto_client(),
+            None => AnyProtoClient::from(client),
+        };
+        let executor = cx.background_executor().clone();
+        let request_capacity =
+            ReplSettings::get_global(cx).request_queue_capacity(&kernel_spec.name);
+        let (inspectors, message_counts) = kernel_message_inspectors(&kernel_spec.name, cx);
 
         window.spawn(cx, async move |cx| {
             let command = kernel_spec
@@ -65,205 +102,276 @@ impl SshRunningKernel {
                 command,
                 args,
             };
-            let response = if let Some(remote_client) = remote_client.as_ref() {
-                remote_client
-                    .read_with(cx, |client, _| client.proto_client())
-                    .request(request)
-                    .await?
-            } else {
-                client.request(request).await?
-            };
+            let response = proto_client.request(request).await?;
 
             let kernel_id = response.kernel_id.clone();
-            let connection_info: serde_json::Value =
-                serde_json::from_str(&response.connection_file)?;
-
-            // Setup SSH Tunneling - allocate local ports
-            let mut local_ports = Vec::new();
-            for _ in 0..5 {
-                let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
-                let port = listener.local_addr()?.port();
-                drop(listener);
-                local_ports.push(port);
-            }
+            let remote_pid = response.pid;
+            log::info!("spawned remote kernel {kernel_id} (pid {remote_pid})");
+
+            let connect = async {
+                let connection_info: serde_json::Value =
+                    serde_json::from_str(&response.connection_file)?;
+                let remote_ports = KernelPorts::from_connection_info(&connection_info)?;
+                let remote_client = remote_client.context("no remote client")?;
+                let sample_command = remote_client
+                    .read_with(cx, |remote_client, _| {
+                        remote_client.build_command_with_options(
+                            Some("cat".to_string()),
+                            &ProcFsSampler::cat_args(remote_pid),
+                            &HashMap::default(),
+                            None,
+                            None,
+                            Interactive::No,
+                        )
+                    })
+                    .log_err();
+                let forwarder = Arc::new(SshPortForwarder::new(remote_client));
+                let tunnel =
+                    KernelTunnel::establish(forwarder.as_ref(), remote_ports, None, cx).await?;
+
+                let local_connection_file =
+                    std::env::temp_dir().join(format!("zed_ssh_kernel_{}.json", kernel_id));
+                let (request_tx, request_rx) = kernel_request_channel(request_capacity);
+                let (stdin_tx, stdin_rx) = mpsc::channel(100);
+                let mut connection = SshKernelConnection {
+                    session: session.clone(),
+                    connection_info,
+                    local_connection_file: local_connection_file.clone(),
+                    current_connection_info: None,
+                    heartbeat: None,
+                    request_target: Rc::new(RefCell::new(kernel_request_channel(1).0)),
+                    stdin_target: Rc::new(RefCell::new(mpsc::channel(0).0)),
+                    request_capacity,
+                    inspectors,
+                    connection_status: Arc::default(),
+                    cx: cx.clone(),
+                };
+                connection.connect(tunnel.local_ports()).await?;
+
+                let connection_status = connection.connection_status.clone();
+                let relay_requests = relay(
+                    Box::pin(request_rx),
+                    connection.request_target.clone(),
+                    |mut sender: KernelRequestSender, message| async move {
+                        sender.send(message).await.is_ok()
+                    },
+                    cx,
+                );
+                let relay_stdin = relay(
+                    stdin_rx,
+                    connection.stdin_target.clone(),
+                    |mut sender: mpsc::Sender<JupyterMessage>, message| async move {
+                        sender.send(message).await.is_ok()
+                    },
+                    cx,
+                );
+                let resource_monitor = ResourceMonitor::new(
+                    async move {
+                        let command = sample_command?;
+                        let command = InstallCommand {
+                            program: command.program,
+                            args: command.args,
+                        };
+                        let sampler =
+                            ProcFsSampler::new(remote_pid, command, Arc::new(SystemCommandRunner));
+                        Some(Box::new(sampler) as Box<dyn ProcessSampler>)
+                    },
+                    &executor,
+                );
+                let supervisor = TunnelSupervisor {
+                    forwarder,
+                    remote_ports,
+                    tunnel,
+                };
+                let supervise = cx.spawn(async move |cx| {
+                    let error = supervisor.run(&mut connection, cx).await;
+                    log::error!("ssh kernel: {error:#}");
+                    connection.set_status(None);
+                    session.update(cx, |session, cx| {
+                        session.kernel_errored(format!("{error:#}"), cx);
+                        cx.notify();
+                    });
+                });
+
+                anyhow::Ok(SshRunningKernel {
+                    request_tx,
+                    stdin_tx,
+                    execution_state: ExecutionState::Idle,
+                    kernel_info: None,
+                    working_directory,
+                    connection_status,
+                    connection_tasks: vec![relay_requests, relay_stdin, supervise],
+                    resource_monitor: Some(resource_monitor),
+                    _local_connection_file: local_connection_file,
+                    kernel_id: kernel_id.clone(),
+                    remote_pid,
+                    proto_client: proto_client.clone(),
+                    project_id,
+                    executor,
+                    message_counts,
+                })
+            };
 
-            let remote_shell_port = connection_info["shell_port"]
-                .as_u64()
-                .context("missing shell_port")? as u16;
-            let remote_iopub_port = connection_info["iopub_port"]
-                .as_u64()
-                .context("missing iopub_port")? as u16;
-            let remote_stdin_port = connection_info["stdin_port"]
-                .as_u64()
-                .context("missing stdin_port")? as u16;
-            let remote_control_port = connection_info["control_port"]
-                .as_u64()
-                .context("missing control_port")? as u16;
-            let remote_hb_port = connection_info["hb_port"]
-                .as_u64()
-                .context("missing hb_port")? as u16;
-
-            let forwards = vec![
-                (local_ports[0], "127.0.0.1".to_string(), remote_shell_port),
-                (local_ports[1], "127.0.0.1".to_string(), remote_iopub_port),
-                (local_ports[2], "127.0.0.1".to_string(), remote_stdin_port),
-                (local_ports[3], "127.0.0.1".to_string(), remote_control_port),
-                (local_ports[4], "127.0.0.1".to_string(), remote_hb_port),
-            ];
-
-            let remote_client = remote_client.ok_or_else(|| anyhow::anyhow!("no remote client"))?;
-            let command_template = cx.update(|_window, cx| {
-                remote_client.read(cx).build_forward_ports_command(forwards)
-            })??;
-
-            let mut command = util::command::new_command(&command_template.program);
-            command.args(&command_template.args);
-            command.envs(&command_template.env);
-
-            let mut ssh_tunnel_process = command.spawn().context("failed to spawn ssh tunnel")?;
-
-            let stderr = ssh_tunnel_process.stderr.take();
-            cx.spawn(async move |_cx| {
-                if let Some(stderr) = stderr {
-                    let reader = BufReader::new(stderr);
-                    let mut lines = reader.lines();
-                    while let Some(Ok(line)) = lines.next().await {
-                        log::warn!("ssh tunnel stderr: {}", line);
-                    }
+            match connect.await {
+                Ok(kernel) => Ok(Box::new(kernel) as Box<dyn RunningKernel>),
+                Err(error) => {
+                    // The kernel is already running on the remote host, and nothing else is
+                    // going to stop it.
+                    kill_remote_kernel(&proto_client, kernel_id, project_id)
+                        .await
+                        .log_err();
+                    Err(error)
                 }
-            })
-            .detach();
+            }
+        })
+    }
+}
 
-            let stdout = ssh_tunnel_process.stdout.take();
-            cx.spawn(async move |_cx| {
-                if let Some(stdout) = stdout {
-                    let reader = BufReader::new(stdout);
-                    let mut lines = reader.lines();
-                    while let Some(Ok(line)) = lines.next().await {
-                        log::debug!("ssh tunnel stdout: {}", line);
-                    }
-                }
-            })
-            .detach();
+async fn kill_remote_kernel(
+    proto_client: &AnyProtoClient,
+    kernel_id: String,
+    project_id: u64,
+) -> Result<()> {
+    proto_client
+        .request(proto::KillKernel {
+            kernel_id,
+            project_id,
+        })
+        .await?;
+    Ok(())
+}
 
-            // We might or might not need this, perhaps we can just wait for a second or test it this way
-            let shell_port = local_ports[0];
-            let max_attempts = 100;
-            let mut connected = false;
-            for attempt in 0..max_attempts {
-                match smol::net::TcpStream::connect(format!("127.0.0.1:{}", shell_port)).await {
-                    Ok(_) => {
-                        connected = true;
-                        log::info!(
-                            "SSH tunnel established for kernel {} on attempt {}",
-                            kernel_id,
-                            attempt + 1
-                        );
-                        // giving the tunnel a moment to fully establish forwarding
-                        cx.background_executor()
-                            .timer(std::time::Duration::from_millis(500))
-                            .await;
-                        break;
-                    }
-                    Err(err) => {
-                        if attempt % 10 == 0 {
-                            log::debug!(
-                                "Waiting for SSH tunnel (attempt {}/{}): {}",
-                                attempt + 1,
-                                max_attempts,
-                                err
-                            );
-                        }
-                        if attempt < max_attempts - 1 {
-                            cx.background_executor()
-                                .timer(std::time::Duration::from_millis(100))
-                                .await;
-                        }
-                    }
-                }
-            }
-            if !connected {
-                anyhow::bail!(
-                    "SSH tunnel failed to establish after {} attempts",
-                    max_attempts
-                );
+/// Forwards messages from the kernel's long-lived channel to its current connection, which is
+/// replaced whenever the connection is restored.
+fn relay<T: Clone + 'static, F: Future<Output = bool> + 'static>(
+    mut messages: impl Stream<Item = JupyterMessage> + Unpin + 'static,
+    target: Rc<RefCell<T>>,
+    send: impl Fn(T, JupyterMessage) -> F + 'static,
+    cx: &mut AsyncWindowContext,
+) -> Task<()> {
+    cx.spawn(async move |_cx| {
+        while let Some(message) = messages.next().await {
+            let sender = target.borrow().clone();
+            if !send(sender, message).await {
+                log::warn!("ssh kernel: dropped a message while the connection was down");
             }
+        }
+    })
+}
 
-            let mut local_connection_info = connection_info.clone();
-            local_connection_info["shell_port"] = serde_json::json!(local_ports[0]);
-            local_connection_info["iopub_port"] = serde_json::json!(local_ports[1]);
-            local_connection_info["stdin_port"] = serde_json::json!(local_ports[2]);
-            local_connection_info["control_port"] = serde_json::json!(local_ports[3]);
-            local_connection_info["hb_port"] = serde_json::json!(local_ports[4]);
-            local_connection_info["ip"] = serde_json::json!("127.0.0.1");
-
-            let local_connection_file =
-                std::env::temp_dir().join(format!("zed_ssh_kernel_{}.json", kernel_id));
-            std::fs::write(
-                &local_connection_file,
-                serde_json::to_string_pretty(&local_connection_info)?,
-            )?;
-
-            // Parse connection info and create ZMQ connections
-            let connection_info_struct: runtimelib::ConnectionInfo =
-                serde_json::from_value(local_connection_info)?;
-            let session_id = uuid::Uuid::new_v4().to_string();
-
-            let output_socket = runtimelib::create_client_iopub_connection(
-                &connection_info_struct,
-                "",
-                &session_id,
-            )
-            .await
-            .context("failed to create iopub connection")?;
-
-            let peer_identity = runtimelib::peer_identity_for_session(&session_id)?;
-            let shell_socket = runtimelib::create_client_shell_connection_with_identity(
-                &connection_info_struct,
-                &session_id,
-                peer_identity.clone(),
-            )
-            .await
-            .context("failed to create shell connection")?;
-            let control_socket =
-                runtimelib::create_client_control_connection(&connection_info_struct, &session_id)
+/// The local end of an SSH kernel's connection: the ZMQ sockets that talk to the kernel through
+/// the tunnel, which are rebuilt whenever the tunnel is.
+struct SshKernelConnection<S: KernelSession + 'static> {
+    session: Entity<S>,
+    /// The connection info the remote kernel was started with.
+    connection_info: serde_json::Value,
+    local_connection_file: PathBuf,
+    /// The connection info for the current local ends of the tunnel.
+    current_connection_info: Option<runtimelib::ConnectionInfo>,
+    heartbeat: Option<runtimelib::ClientHeartbeatConnection>,
+    request_target: Rc<RefCell<KernelRequestSender>>,
+    stdin_target: Rc<RefCell<mpsc::Sender<JupyterMessage>>>,
+    request_capacity: usize,
+    /// Shared by every connection, so tracing carries on across reconnects.
+    inspectors: KernelMessageInspectors,
+    connection_status: Arc<Mutex<Option<KernelStatus>>>,
+    cx: AsyncWindowContext,
+}
+
+impl<S: KernelSession + 'static> SshKernelConnection<S> {
+    async fn connect(&mut self, local_ports: KernelPorts) -> Result<()> {
+        let mut local_connection_info = self.connection_info.clone();
+        local_ports.apply_to_connection_info(&mut local_connection_info);
+        std::fs::write(
+            &self.local_connection_file,
+            serde_json::to_string_pretty(&local_connection_info)?,
+        )?;
+
+        let connection_info: runtimelib::ConnectionInfo =
+            serde_json::from_value(local_connection_info)?;
+        let session_id = uuid::Uuid::new_v4().to_string();
+
+        let output_socket =
+            runtimelib::create_client_iopub_connection(&connection_info, "", &session_id)
+                .await
+                .context("failed to create iopub connection")?;
+        let peer_identity = runtimelib::peer_identity_for_session(&session_id)?;
+        let shell_socket = runtimelib::create_client_shell_connection_with_identity(
+            &connection_info,
+            &session_id,
+            peer_identity.clone(),
+        )
+        .await
+        .context("failed to create shell connection")?;
+        let control_socket =
+            runtimelib::create_client_control_connection(&connection_info, &session_id)
+                .await
+                .context("failed to create control connection")?;
+        let stdin_socket = runtimelib::create_client_stdin_connection_with_identity(
+            &connection_info,
+            &session_id,
+            peer_identity,
+        )
+        .await
+        .context("failed to create stdin connection")?;
+
+        let (request_tx, stdin_tx) = start_kernel_tasks_with_error_handler(
+            self.session.clone(),
+            output_socket,
+            shell_socket,
+            control_socket,
+            stdin_socket,
+            self.request_capacity,
+            self.inspectors.clone(),
+            // The sockets stop working when the tunnel drops. The heartbeat notices that too,
+            // and the supervisor decides whether the kernel can be reconnected to.
+            |error_message, _cx| log::warn!("ssh kernel: {error_message}"),
+            &mut self.cx,
+        );
+        *self.request_target.borrow_mut() = request_tx;
+        *self.stdin_target.borrow_mut() = stdin_tx;
+        self.current_connection_info = Some(connection_info);
+        self.heartbeat = None;
+        Ok(())
+    }
+}
+
+impl<S: KernelSession + 'static> TunnelClient for SshKernelConnection<S> {
+    async fn heartbeat(&mut self) -> Result<()> {
+        // A heartbeat socket that's still waiting for a reply can't send another request, so
+        // one that timed out (and was dropped with this future) is replaced.
+        let mut heartbeat = match self.heartbeat.take() {
+            Some(heartbeat) => heartbeat,
+            None => {
+                let connection_info = self
+                    .current_connection_info
+                    .as_ref()
+                    .context("kernel is not connected")?;
+                runtimelib::create_client_heartbeat_connection(connection_info)
                     .await
-                    .context("failed to create control connection")?;
-            let stdin_socket = runtimelib::create_client_stdin_connection_with_identity(
-                &connection_info_struct,
-                &session_id,
-                peer_identity,
-            )
-            .await
-            .context("failed to create stdin connection")?;
-
-            let (request_tx, stdin_tx) = start_kernel_tasks(
-                session.clone(),
-                output_socket,
-                shell_socket,
-                control_socket,
-                stdin_socket,
-                cx,
-            );
-
-            Ok(Box::new(SshRunningKernel {
-                request_tx,
-                stdin_tx,
-                execution_state: ExecutionState::Idle,
-                kernel_info: None,
-                working_directory,
-                _ssh_tunnel_process: ssh_tunnel_process,
-                _local_connection_file: local_connection_file,
-                kernel_id,
-                project,
-                project_id,
-            }) as Box<dyn RunningKernel>)
-        })
+                    .context("failed to create heartbeat connection")?
+            }
+        };
+        heartbeat.single_heartbeat().await?;
+        self.heartbeat = Some(heartbeat);
+        Ok(())
+    }
+
+    async fn reconnect(&mut self, local_ports: KernelPorts) -> Result<()> {
+        self.connect(local_ports).await
+    }
+
+    fn set_status(&mut self, status: Option<KernelStatus>) {
+        *self
+            .connection_status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = status;
+        self.session.update(&mut self.cx, |_, cx| cx.notify());
     }
 }
 
 impl RunningKernel for SshRunningKernel {
-    fn request_tx(&self) -> mpsc::Sender<JupyterMessage> {
+    fn request_tx(&self) -> KernelRequestSender {
         self.request_tx.clone()
     }
 
@@ -292,21 +400,49 @@ impl RunningKernel for SshRunningKernel {
     }
 
     fn force_shutdown(&mut self, _window: &mut Window, cx: &mut App) -> Task<Result<()>> {
+        self.resource_monitor.take();
         let kernel_id = self.kernel_id.clone();
         let project_id = self.project_id;
-        let client = self.project.read(cx).client();
-
-        cx.background_executor().spawn(async move {
-            let request = proto::KillKernel {
-                kernel_id,
-                project_id,
-            };
-            client.request::<proto::KillKernel>(request).await?;
-            Ok(())
-        })
+        let proto_client = self.proto_client.clone();
+        log::info!(
+            "shutting down remote kernel {kernel_id} (pid {})",
+            self.remote_pid
+        );
+
+        cx.background_executor()
+            .spawn(async move { kill_remote_kernel(&proto_client, kernel_id, project_id).await })
     }
 
     fn kill(&mut self) {
-        self._ssh_tunnel_process.kill().log_err();
+        self.connection_tasks.clear();
+        self.resource_monitor.take();
+        self.request_tx.close_channel();
+        self.stdin_tx.close_channel();
+
+        let kernel_id = self.kernel_id.clone();
+        let project_id = self.project_id;
+        let proto_client = self.proto_client.clone();
+        self.executor
+            .spawn(async move {
+                kill_remote_kernel(&proto_client, kernel_id, project_id)
+                    .await
+                    .log_err();
+            })
+            .detach();
+    }
+
+    fn connection_status(&self) -> Option<KernelStatus> {
+        self.connection_status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.resource_monitor.as_ref()?.usage()
+    }
+
+    fn message_counts(&self) -> Option<KernelMessageCounts> {
+        self.message_counts.clone()
     }
 }
@@ -0,0 +1,860 @@
+//! # SSH Kernel Tunnels
+//!
+//! A kernel on a remote host listens on five ZMQ ports that are only reachable from that host,
+//! so each one is forwarded from a local port over SSH. This module allocates the local ports,
+//! sets up the forwards, and keeps them alive: when the kernel's heartbeat stops answering, the
+//! remote kernel is usually still running and only the forwards died with the SSH connection,
+//! so they're re-established instead of the kernel being declared dead.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    net::TcpListener,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use futures::{AsyncBufReadExt as _, StreamExt as _, channel::mpsc, io::BufReader};
+use gpui::{AsyncApp, BackgroundExecutor, Entity, FutureExt as _, Task};
+use remote::{CommandTemplate, RemoteClient};
+use util::{ResultExt as _, command::Stdio};
+
+use super::KernelStatus;
+
+/// The ports of a kernel's five ZMQ channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KernelPorts {
+    pub shell: u16,
+    pub iopub: u16,
+    pub stdin: u16,
+    pub control: u16,
+    pub hb: u16,
+}
+
+impl KernelPorts {
+    const CONNECTION_INFO_KEYS: [&str; 5] = [
+        "shell_port",
+        "iopub_port",
+        "stdin_port",
+        "control_port",
+        "hb_port",
+    ];
+
+    pub fn from_connection_info(connection_info: &serde_json::Value) -> Result<Self> {
+        let mut ports = [0; 5];
+        for (port, key) in ports.iter_mut().zip(Self::CONNECTION_INFO_KEYS) {
+            let value = connection_info[key]
+                .as_u64()
+                .with_context(|| format!("missing {key}"))?;
+            *port = u16::try_from(value).with_context(|| format!("invalid {key} {value}"))?;
+        }
+        Ok(Self::from_array(ports))
+    }
+
+    /// Points `connection_info` at these ports on the local host.
+    pub fn apply_to_connection_info(&self, connection_info: &mut serde_json::Value) {
+        for (port, key) in self.to_array().into_iter().zip(Self::CONNECTION_INFO_KEYS) {
+            connection_info[key] = serde_json::json!(port);
+        }
+        connection_info["ip"] = serde_json::json!("127.0.0.1");
+    }
+
+    pub fn to_array(self) -> [u16; 5] {
+        [self.shell, self.iopub, self.stdin, self.control, self.hb]
+    }
+
+    fn from_array([shell, iopub, stdin, control, hb]: [u16; 5]) -> Self {
+        Self {
+            shell,
+            iopub,
+            stdin,
+            control,
+            hb,
+        }
+    }
+
+    /// Pairs each local port with the remote port of the same channel.
+    fn forwards_to(self, remote: KernelPorts) -> Vec<(u16, String, u16)> {
+        self.to_array()
+            .into_iter()
+            .zip(remote.to_array())
+            .map(|(local_port, remote_port)| (local_port, "127.0.0.1".to_string(), remote_port))
+            .collect()
+    }
+}
+
+/// Local ports that are reserved by a kernel in this process. Ports have to be released before
+/// SSH can listen on them, so this is what keeps two kernels started at the same time from
+/// picking the same ones.
+static RESERVED_LOCAL_PORTS: Mutex<BTreeSet<u16>> = Mutex::new(BTreeSet::new());
+
+fn reserved_local_ports() -> MutexGuard<'static, BTreeSet<u16>> {
+    RESERVED_LOCAL_PORTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The local ports of one kernel's forwards, which stay reserved until this is dropped.
+#[derive(Debug)]
+pub struct LocalPortReservation {
+    ports: KernelPorts,
+}
+
+impl LocalPortReservation {
+    const MAX_ALLOCATION_ATTEMPTS: usize = 10;
+
+    /// Reserves five free local ports, none of which are in `excluded`.
+    pub fn allocate(excluded: &BTreeSet<u16>) -> Result<Self> {
+        for _ in 0..Self::MAX_ALLOCATION_ATTEMPTS {
+            // All five listeners are open at once, so the OS hands out five different ports.
+            let listeners = (0..5)
+                .map(|_| TcpListener::bind("127.0.0.1:0"))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let mut ports = [0; 5];
+            for (port, listener) in ports.iter_mut().zip(&listeners) {
+                *port = listener.local_addr()?.port();
+            }
+
+            let mut reserved = reserved_local_ports();
+            if ports
+                .iter()
+                .any(|port| reserved.contains(port) || excluded.contains(port))
+            {
+                continue;
+            }
+            reserved.extend(ports);
+            return Ok(Self {
+                ports: KernelPorts::from_array(ports),
+            });
+        }
+        anyhow::bail!(
+            "failed to allocate local ports after {} attempts",
+            Self::MAX_ALLOCATION_ATTEMPTS
+        )
+    }
+
+    /// Reserves specific ports again, such as the ones a kernel was using before its
+    /// connection dropped. Returns `None` when another kernel has reserved any of them since.
+    pub fn reclaim(ports: KernelPorts) -> Option<Self> {
+        let mut reserved = reserved_local_ports();
+        if ports.to_array().iter().any(|port| reserved.contains(port)) {
+            return None;
+        }
+        reserved.extend(ports.to_array());
+        Some(Self { ports })
+    }
+
+    pub fn ports(&self) -> KernelPorts {
+        self.ports
+    }
+}
+
+impl Drop for LocalPortReservation {
+    fn drop(&mut self) {
+        let mut reserved = reserved_local_ports();
+        for port in self.ports.to_array() {
+            reserved.remove(&port);
+        }
+    }
+}
+
+/// Returned by a [`KernelPortForwarder`] when a local port it was asked to listen on has been
+/// taken by another process since it was allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortInUse(pub u16);
+
+impl fmt::Display for PortInUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "local port {} is already in use", self.0)
+    }
+}
+
+impl std::error::Error for PortInUse {}
+
+/// Sets up the forwards of an SSH kernel's ports. This is a trait so that tests can simulate
+/// port conflicts and dropped connections.
+pub trait KernelPortForwarder: Send + Sync {
+    /// Starts forwarding each local port to the remote port of the same channel. Fails with a
+    /// [`PortInUse`] error when one of the local ports couldn't be listened on.
+    fn forward(
+        &self,
+        local_ports: KernelPorts,
+        remote_ports: KernelPorts,
+        cx: &mut AsyncApp,
+    ) -> Task<Result<Box<dyn PortForwards>>>;
+}
+
+/// Forwards started by a [`KernelPortForwarder`], which stop when this is dropped.
+pub trait PortForwards: Send + fmt::Debug {}
+
+/// Forwards ports through the project's remote connection.
+///
+/// When the connection has an SSH control master, the forwards are added to it, so that every
+/// kernel on the host shares one connection. Otherwise an `ssh -N` process is started per kernel.
+pub struct SshPortForwarder {
+    remote_client: Entity<RemoteClient>,
+}
+
+impl SshPortForwarder {
+    const MAX_TUNNEL_WAIT_ATTEMPTS: usize = 100;
+
+    pub fn new(remote_client: Entity<RemoteClient>) -> Self {
+        Self { remote_client }
+    }
+
+    async fn forward_through_control_master(
+        forward_command: CommandTemplate,
+        cancel_command: CommandTemplate,
+        local_ports: KernelPorts,
+        executor: BackgroundExecutor,
+    ) -> Result<Box<dyn PortForwards>> {
+        let output = command(&forward_command)
+            .output()
+            .await
+            .context("failed to run ssh")?;
+        if output.status.success() {
+            return Ok(Box::new(ControlMasterForwards {
+                cancel_command,
+                executor,
+            }));
+        }
+
+        // Some of the forwards might have been set up before one of them failed.
+        command(&cancel_command).output().await.log_err();
+        // The master logs which port it failed to listen on, but only tells us that forwarding
+        // failed, so check which of the ports is taken.
+        if let Some(port) = local_ports
+            .to_array()
+            .into_iter()
+            .find(|port| TcpListener::bind(("127.0.0.1", *port)).is_err())
+        {
+            return Err(PortInUse(port).into());
+        }
+        anyhow::bail!(
+            "ssh port forwarding failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+
+    async fn forward_through_process(
+        forward_command: CommandTemplate,
+        local_ports: KernelPorts,
+        executor: BackgroundExecutor,
+    ) -> Result<Box<dyn PortForwards>> {
+        let mut process = command(&forward_command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to spawn ssh tunnel")?;
+
+        let (conflicts_tx, mut conflicts_rx) = mpsc::unbounded();
+        if let Some(stderr) = process.stderr.take() {
+            executor
+                .spawn(async move {
+                    let mut lines = BufReader::new(stderr).lines();
+                    while let Some(Ok(line)) = lines.next().await {
+                        log::warn!("ssh tunnel stderr: {}", line);
+                        if let Some(port) = parse_port_in_use(&line) {
+                            conflicts_tx.unbounded_send(port).ok();
+                        }
+                    }
+                })
+                .detach();
+        }
+        if let Some(stdout) = process.stdout.take() {
+            executor
+                .spawn(async move {
+                    let mut lines = BufReader::new(stdout).lines();
+                    while let Some(Ok(line)) = lines.next().await {
+                        log::debug!("ssh tunnel stdout: {}", line);
+                    }
+                })
+                .detach();
+        }
+
+        // ssh doesn't say when its forwards are ready, so wait until all of the local ports
+        // accept connections. A port that another process is listening on accepts them too,
+        // which is why conflicts are also checked for in ssh's output.
+        for attempt in 0..Self::MAX_TUNNEL_WAIT_ATTEMPTS {
+            if let Ok(Some(port)) = conflicts_rx.try_next() {
+                process.kill().log_err();
+                return Err(PortInUse(port).into());
+            }
+            if let Ok(Some(status)) = process.try_status() {
+                anyhow::bail!("ssh tunnel exited with {status}");
+            }
+
+            let mut all_connected = true;
+            for port in local_ports.to_array() {
+                if smol::net::TcpStream::connect(("127.0.0.1", port))
+                    .await
+                    .is_err()
+                {
+                    all_connected = false;
+                    break;
+                }
+            }
+            if all_connected {
+                log::info!("SSH tunnel established on attempt {}", attempt + 1);
+                // Give ssh a moment to report forwards that failed.
+                executor.timer(Duration::from_millis(500)).await;
+                if let Ok(Some(port)) = conflicts_rx.try_next() {
+                    process.kill().log_err();
+                    return Err(PortInUse(port).into());
+                }
+                return Ok(Box::new(ProcessForwards { process }));
+            }
+            executor.timer(Duration::from_millis(100)).await;
+        }
+
+        process.kill().log_err();
+        anyhow::bail!(
+            "SSH tunnel failed to establish after {} attempts",
+            Self::MAX_TUNNEL_WAIT_ATTEMPTS
+        )
+    }
+}
+
+impl KernelPortForwarder for SshPortForwarder {
+    fn forward(
+        &self,
+        local_ports: KernelPorts,
+        remote_ports: KernelPorts,
+        cx: &mut AsyncApp,
+    ) -> Task<Result<Box<dyn PortForwards>>> {
+        let forwards = local_ports.forwards_to(remote_ports);
+        let executor = cx.background_executor().clone();
+        // The remote client's connection changes when it reconnects, so the commands are built
+        // against the current one every time.
+        let (control_master_commands, process_command) =
+            self.remote_client.read_with(cx, |remote_client, _| {
+                let control_master_commands = remote_client
+                    .build_control_master_forward_command(forwards.clone(), false)
+                    .zip(
+                        remote_client.build_control_master_forward_command(forwards.clone(), true),
+                    );
+                (
+                    control_master_commands,
+                    remote_client.build_forward_ports_command(forwards),
+                )
+            });
+
+        cx.background_spawn(async move {
+            match control_master_commands {
+                Some((forward_command, cancel_command)) => {
+                    Self::forward_through_control_master(
+                        forward_command,
+                        cancel_command,
+                        local_ports,
+                        executor,
+                    )
+                    .await
+                }
+                None => {
+                    Self::forward_through_process(process_command?, local_ports, executor).await
+                }
+            }
+        })
+    }
+}
+
+fn command(template: &CommandTemplate) -> util::command::Command {
+    let mut command = util::command::new_command(&template.program);
+    command.args(&template.args);
+    command.envs(&template.env);
+    command
+}
+
+/// Recognizes ssh's `channel_setup_fwd_listener_tcpip: cannot listen to port: 1234` message.
+fn parse_port_in_use(line: &str) -> Option<u16> {
+    let (_, port) = line.split_once("cannot listen to port: ")?;
+    port.trim().parse().ok()
+}
+
+#[derive(Debug)]
+struct ControlMasterForwards {
+    cancel_command: CommandTemplate,
+    executor: BackgroundExecutor,
+}
+
+impl PortForwards for ControlMasterForwards {}
+
+impl Drop for ControlMasterForwards {
+    fn drop(&mut self) {
+        let mut cancel_command = command(&self.cancel_command);
+        self.executor
+            .spawn(async move { cancel_command.output().await.log_err() })
+            .detach();
+    }
+}
+
+#[derive(Debug)]
+struct ProcessForwards {
+    process: util::command::Child,
+}
+
+impl PortForwards for ProcessForwards {}
+
+impl Drop for ProcessForwards {
+    fn drop(&mut self) {
+        self.process.kill().log_err();
+    }
+}
+
+/// An SSH kernel's forwards, along with the local ports they listen on.
+#[derive(Debug)]
+pub struct KernelTunnel {
+    // Dropped before the reservation, so that the ports aren't handed out while they're still
+    // being listened on.
+    _forwards: Box<dyn PortForwards>,
+    reservation: LocalPortReservation,
+}
+
+impl KernelTunnel {
+    pub const MAX_FORWARD_ATTEMPTS: usize = 5;
+
+    /// Forwards `remote_ports` from newly allocated local ports, starting with
+    /// `preferred_local_ports` if they're given and still available. Ports that turn out to be
+    /// taken are swapped for new ones.
+    pub async fn establish(
+        forwarder: &dyn KernelPortForwarder,
+        remote_ports: KernelPorts,
+        preferred_local_ports: Option<KernelPorts>,
+        cx: &mut AsyncApp,
+    ) -> Result<Self> {
+        let mut ports_in_use = BTreeSet::new();
+        for attempt in 0..Self::MAX_FORWARD_ATTEMPTS {
+            let reservation = match preferred_local_ports {
+                Some(ports) if attempt == 0 => LocalPortReservation::reclaim(ports),
+                _ => None,
+            };
+            let reservation = match reservation {
+                Some(reservation) => reservation,
+                None => LocalPortReservation::allocate(&ports_in_use)?,
+            };
+
+            match forwarder
+                .forward(reservation.ports(), remote_ports, cx)
+                .await
+            {
+                Ok(forwards) => {
+                    return Ok(Self {
+                        _forwards: forwards,
+                        reservation,
+                    });
+                }
+                Err(error) => {
+                    let Some(PortInUse(port)) = error.downcast_ref::<PortInUse>() else {
+                        return Err(error);
+                    };
+                    log::info!("retrying kernel port forwarding: {error}");
+                    ports_in_use.insert(*port);
+                }
+            }
+        }
+        anyhow::bail!(
+            "failed to forward kernel ports after {} attempts",
+            Self::MAX_FORWARD_ATTEMPTS
+        )
+    }
+
+    pub fn local_ports(&self) -> KernelPorts {
+        self.reservation.ports()
+    }
+}
+
+/// The local end of a kernel's connection, which the [`TunnelSupervisor`] checks on and
+/// rebuilds.
+pub(crate) trait TunnelClient {
+    /// Sends one heartbeat to the kernel and waits for its reply.
+    async fn heartbeat(&mut self) -> Result<()>;
+
+    /// Connects to the kernel again through forwards on `local_ports`.
+    async fn reconnect(&mut self, local_ports: KernelPorts) -> Result<()>;
+
+    /// Called with `Some` while the connection is being recovered, and with `None` once it's
+    /// healthy again.
+    fn set_status(&mut self, status: Option<KernelStatus>);
+}
+
+/// Watches an SSH kernel's heartbeat, and re-establishes its forwards when it stops answering.
+pub(crate) struct TunnelSupervisor {
+    pub forwarder: Arc<dyn KernelPortForwarder>,
+    pub remote_ports: KernelPorts,
+    pub tunnel: KernelTunnel,
+}
+
+impl TunnelSupervisor {
+    pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+    pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(3);
+    /// Heartbeats missed in a row before the forwards are assumed dead. A busy kernel can miss
+    /// a single one.
+    pub const MISSED_HEARTBEATS_BEFORE_RECONNECT: usize = 2;
+    pub const MAX_RECONNECT_ATTEMPTS: usize = 3;
+    pub const RECONNECT_BACKOFF: Duration = Duration::from_secs(2);
+
+    /// Runs until the connection can't be recovered, returning why.
+    pub async fn run(self, client: &mut impl TunnelClient, cx: &mut AsyncApp) -> anyhow::Error {
+        let Self {
+            forwarder,
+            remote_ports,
+            tunnel,
+        } = self;
+        let executor = cx.background_executor().clone();
+        let mut tunnel = Some(tunnel);
+        let mut missed_heartbeats = 0;
+        loop {
+            executor.timer(Self::HEARTBEAT_INTERVAL).await;
+
+            let heartbeat = client
+                .heartbeat()
+                .with_timeout(Self::HEARTBEAT_TIMEOUT, &executor)
+                .await;
+            match heartbeat {
+                Ok(Ok(())) => {
+                    if missed_heartbeats > 0 {
+                        missed_heartbeats = 0;
+                        client.set_status(None);
+                    }
+                    continue;
+                }
+                Ok(Err(error)) => log::warn!("kernel heartbeat failed: {error:#}"),
+                Err(_) => log::warn!("kernel heartbeat timed out"),
+            }
+
+            missed_heartbeats += 1;
+            if missed_heartbeats < Self::MISSED_HEARTBEATS_BEFORE_RECONNECT {
+                client.set_status(Some(KernelStatus::Unresponsive));
+                continue;
+            }
+
+            client.set_status(Some(KernelStatus::Restarting));
+            let local_ports = tunnel.as_ref().map(KernelTunnel::local_ports);
+            let mut last_error = None;
+            for attempt in 0..Self::MAX_RECONNECT_ATTEMPTS {
+                if attempt > 0 {
+                    executor.timer(Self::RECONNECT_BACKOFF).await;
+                }
+                // The old forwards have to go before new ones can listen on the same ports.
+                tunnel.take();
+                let result = async {
+                    let new_tunnel =
+                        KernelTunnel::establish(forwarder.as_ref(), remote_ports, local_ports, cx)
+                            .await?;
+                    client.reconnect(new_tunnel.local_ports()).await?;
+                    anyhow::Ok(new_tunnel)
+                }
+                .await;
+                match result {
+                    Ok(new_tunnel) => {
+                        log::info!("kernel connection restored on attempt {}", attempt + 1);
+                        tunnel = Some(new_tunnel);
+                        last_error = None;
+                        break;
+                    }
+                    Err(error) => {
+                        log::warn!("failed to restore kernel connection: {error:#}");
+                        last_error = Some(error);
+                    }
+                }
+            }
+
+            if let Some(error) = last_error {
+                return error.context("lost the connection to the remote kernel");
+            }
+            missed_heartbeats = 0;
+            client.set_status(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use std::{
+        collections::VecDeque,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    /// Forwards nothing, but reports conflicts and failures when asked to.
+    #[derive(Default)]
+    struct FakeForwarder {
+        conflicts_remaining: AtomicUsize,
+        fail: AtomicBool,
+        forwarded: Mutex<Vec<KernelPorts>>,
+    }
+
+    #[derive(Debug)]
+    struct FakeForwards;
+
+    impl PortForwards for FakeForwards {}
+
+    impl FakeForwarder {
+        fn forwarded(&self) -> Vec<KernelPorts> {
+            self.forwarded.lock().unwrap().clone()
+        }
+    }
+
+    impl KernelPortForwarder for FakeForwarder {
+        fn forward(
+            &self,
+            local_ports: KernelPorts,
+            _remote_ports: KernelPorts,
+            _cx: &mut AsyncApp,
+        ) -> Task<Result<Box<dyn PortForwards>>> {
+            self.forwarded.lock().unwrap().push(local_ports);
+            if self.fail.load(Ordering::SeqCst) {
+                return Task::ready(Err(anyhow::anyhow!("connection refused")));
+            }
+            let has_conflict = self
+                .conflicts_remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                    count.checked_sub(1)
+                })
+                .is_ok();
+            if has_conflict {
+                return Task::ready(Err(PortInUse(local_ports.iopub).into()));
+            }
+            Task::ready(Ok(Box::new(FakeForwards)))
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeClientState {
+        heartbeats: VecDeque<Result<()>>,
+        reconnected_to: Vec<KernelPorts>,
+        statuses: Vec<Option<String>>,
+    }
+
+    /// A connection whose heartbeats answer as scripted, and succeed once the script runs out.
+    #[derive(Clone, Default)]
+    struct FakeClient(Arc<Mutex<FakeClientState>>);
+
+    impl FakeClient {
+        fn with_heartbeats(heartbeats: impl IntoIterator<Item = Result<()>>) -> Self {
+            let client = Self::default();
+            client.state().heartbeats.extend(heartbeats);
+            client
+        }
+
+        fn state(&self) -> MutexGuard<'_, FakeClientState> {
+            self.0.lock().unwrap()
+        }
+    }
+
+    impl TunnelClient for FakeClient {
+        async fn heartbeat(&mut self) -> Result<()> {
+            self.state().heartbeats.pop_front().unwrap_or(Ok(()))
+        }
+
+        async fn reconnect(&mut self, local_ports: KernelPorts) -> Result<()> {
+            self.state().reconnected_to.push(local_ports);
+            Ok(())
+        }
+
+        fn set_status(&mut self, status: Option<KernelStatus>) {
+            self.state()
+                .statuses
+                .push(status.as_ref().map(ToString::to_string));
+        }
+    }
+
+    fn remote_ports() -> KernelPorts {
+        KernelPorts::from_array([9000, 9001, 9002, 9003, 9004])
+    }
+
+    fn connection_reset() -> Result<()> {
+        Err(anyhow::anyhow!("connection reset"))
+    }
+
+    fn start_supervisor(
+        forwarder: &Arc<FakeForwarder>,
+        client: &FakeClient,
+        cx: &mut TestAppContext,
+    ) -> (KernelPorts, Task<anyhow::Error>) {
+        let tunnel = cx
+            .foreground_executor()
+            .block_test(KernelTunnel::establish(
+                forwarder.as_ref(),
+                remote_ports(),
+                None,
+                &mut cx.to_async(),
+            ))
+            .unwrap();
+        let local_ports = tunnel.local_ports();
+        let supervisor = TunnelSupervisor {
+            forwarder: forwarder.clone(),
+            remote_ports: remote_ports(),
+            tunnel,
+        };
+        let mut client = client.clone();
+        let task = cx.spawn(|mut cx| async move { supervisor.run(&mut client, &mut cx).await });
+        (local_ports, task)
+    }
+
+    fn advance_heartbeats(count: usize, cx: &mut TestAppContext) {
+        for _ in 0..count {
+            cx.executor()
+                .advance_clock(TunnelSupervisor::HEARTBEAT_INTERVAL);
+            cx.run_until_parked();
+        }
+    }
+
+    #[test]
+    fn test_concurrent_reservations_do_not_overlap() {
+        let first = LocalPortReservation::allocate(&BTreeSet::new()).unwrap();
+        let second = LocalPortReservation::allocate(&BTreeSet::new()).unwrap();
+        let first_ports = first.ports();
+        assert_eq!(
+            first_ports.to_array().iter().collect::<BTreeSet<_>>().len(),
+            5
+        );
+        assert!(
+            first_ports
+                .to_array()
+                .iter()
+                .all(|port| !second.ports().to_array().contains(port))
+        );
+
+        assert!(LocalPortReservation::reclaim(first_ports).is_none());
+        drop(first);
+        assert!(LocalPortReservation::reclaim(first_ports).is_some());
+    }
+
+    #[test]
+    fn test_connection_info_ports() {
+        let mut connection_info = serde_json::json!({
+            "shell_port": 9000,
+            "iopub_port": 9001,
+            "stdin_port": 9002,
+            "control_port": 9003,
+            "hb_port": 9004,
+            "ip": "0.0.0.0",
+            "key": "secret",
+        });
+        assert_eq!(
+            KernelPorts::from_connection_info(&connection_info).unwrap(),
+            remote_ports()
+        );
+
+        let local_ports = KernelPorts::from_array([1, 2, 3, 4, 5]);
+        local_ports.apply_to_connection_info(&mut connection_info);
+        assert_eq!(
+            KernelPorts::from_connection_info(&connection_info).unwrap(),
+            local_ports
+        );
+        assert_eq!(connection_info["ip"], "127.0.0.1");
+        assert_eq!(connection_info["key"], "secret");
+
+        connection_info["hb_port"] = serde_json::json!(70000);
+        assert!(KernelPorts::from_connection_info(&connection_info).is_err());
+    }
+
+    #[test]
+    fn test_parse_port_in_use() {
+        assert_eq!(
+            parse_port_in_use("channel_setup_fwd_listener_tcpip: cannot listen to port: 50123"),
+            Some(50123)
+        );
+        assert_eq!(
+            parse_port_in_use("bind [127.0.0.1]:50123: Address already in use"),
+            None
+        );
+    }
+
+    #[gpui::test]
+    async fn test_establish_retries_on_port_conflicts(cx: &mut TestAppContext) {
+        let forwarder = FakeForwarder::default();
+        forwarder.conflicts_remaining.store(2, Ordering::SeqCst);
+
+        let tunnel = KernelTunnel::establish(&forwarder, remote_ports(), None, &mut cx.to_async())
+            .await
+            .unwrap();
+
+        let forwarded = forwarder.forwarded();
+        assert_eq!(forwarded.len(), 3);
+        assert_eq!(forwarded[2], tunnel.local_ports());
+        // Ports that were reported as taken aren't tried again.
+        let local_ports = tunnel.local_ports().to_array();
+        assert!(!local_ports.contains(&forwarded[0].iopub));
+        assert!(!local_ports.contains(&forwarded[1].iopub));
+    }
+
+    #[gpui::test]
+    async fn test_establish_gives_up_on_repeated_conflicts(cx: &mut TestAppContext) {
+        let forwarder = FakeForwarder::default();
+        forwarder
+            .conflicts_remaining
+            .store(KernelTunnel::MAX_FORWARD_ATTEMPTS, Ordering::SeqCst);
+
+        let result =
+            KernelTunnel::establish(&forwarder, remote_ports(), None, &mut cx.to_async()).await;
+        assert!(result.is_err());
+        assert_eq!(
+            forwarder.forwarded().len(),
+            KernelTunnel::MAX_FORWARD_ATTEMPTS
+        );
+    }
+
+    #[gpui::test]
+    fn test_reconnects_after_connection_drops(cx: &mut TestAppContext) {
+        let forwarder = Arc::new(FakeForwarder::default());
+        let client = FakeClient::with_heartbeats([Ok(()), connection_reset(), connection_reset()]);
+        let (local_ports, _supervisor) = start_supervisor(&forwarder, &client, cx);
+
+        // A single missed heartbeat only marks the kernel as unresponsive.
+        advance_heartbeats(2, cx);
+        assert_eq!(client.state().statuses, [Some("Unresponsive".into())]);
+        assert_eq!(forwarder.forwarded().len(), 1);
+
+        // A second one gets the forwards re-established, on the same local ports so that the
+        // kernel's connection file stays valid.
+        advance_heartbeats(1, cx);
+        assert_eq!(
+            client.state().statuses,
+            [Some("Unresponsive".into()), Some("Restarting".into()), None]
+        );
+        assert_eq!(forwarder.forwarded(), [local_ports, local_ports]);
+        assert_eq!(client.state().reconnected_to, [local_ports]);
+
+        // Healthy heartbeats don't change anything.
+        advance_heartbeats(3, cx);
+        assert_eq!(client.state().statuses.len(), 3);
+        assert_eq!(forwarder.forwarded().len(), 2);
+    }
+
+    #[gpui::test]
+    fn test_gives_up_when_forwards_cannot_be_restored(cx: &mut TestAppContext) {
+        let forwarder = Arc::new(FakeForwarder::default());
+        let client = FakeClient::with_heartbeats(
+            (0..TunnelSupervisor::MISSED_HEARTBEATS_BEFORE_RECONNECT).map(|_| connection_reset()),
+        );
+        let (_, supervisor) = start_supervisor(&forwarder, &client, cx);
+        forwarder.fail.store(true, Ordering::SeqCst);
+
+        advance_heartbeats(TunnelSupervisor::MISSED_HEARTBEATS_BEFORE_RECONNECT, cx);
+        for _ in 1..TunnelSupervisor::MAX_RECONNECT_ATTEMPTS {
+            cx.executor()
+                .advance_clock(TunnelSupervisor::RECONNECT_BACKOFF);
+            cx.run_until_parked();
+        }
+        let error = cx.foreground_executor().block_test(supervisor);
+
+        assert!(
+            format!("{error:#}").contains("lost the connection to the remote kernel"),
+            "{error:#}"
+        );
+        assert_eq!(
+            client.state().statuses,
+            [Some("Unresponsive".into()), Some("Restarting".into())]
+        );
+        assert!(client.state().reconnected_to.is_empty());
+        assert_eq!(
+            forwarder.forwarded().len(),
+            1 + TunnelSupervisor::MAX_RECONNECT_ATTEMPTS
+        );
+    }
+}
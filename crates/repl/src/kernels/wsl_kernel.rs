@@ -1,10 +1,16 @@
 use super::{
-    KernelSession, KernelSpecification, RunningKernel, WslKernelSpecification, start_kernel_tasks,
+    InstallCommand, KernelMessageCounts, KernelRequestSender, KernelSession, KernelSpecification,
+    ProcFsSampler, ProcessSampler, ResourceMonitor, ResourceUsage, RunningKernel,
+    SystemCommandRunner, WslKernelSpecification, kernel_message_inspectors, start_kernel_tasks,
 };
+use crate::repl_settings::ReplSettings;
 use anyhow::{Context as _, Result};
 use futures::{
     AsyncBufReadExt as _, StreamExt as _,
-    channel::mpsc::{self},
+    channel::{
+        mpsc::{self},
+        oneshot,
+    },
     io::BufReader,
 };
 use gpui::{App, BackgroundExecutor, Entity, EntityId, Task, Window};
@@ -14,6 +20,7 @@ use jupyter_protocol::{
 };
 use project::Fs;
 use runtimelib::dirs;
+use settings::Settings as _;
 use smol::net::TcpListener;
 use std::{
     fmt::Debug,
@@ -23,6 +30,10 @@ use std::{
 };
 use uuid::Uuid;
 
+/// Printed to stderr, followed by the kernel's process id inside the distro, by the shell that
+/// `exec`s the kernel.
+const KERNEL_PID_PREFIX: &str = "zed: kernel pid ";
+
 // Find a set of open ports. This creates a listener with port set to 0. The listener will be closed at the end when it goes out of scope.
 // There's a race condition between closing the ports and usage by a kernel, but it's inherent to the Jupyter protocol.
 async fn peek_ports(ip: IpAddr) -> Result<[u16; 5]> {
@@ -41,11 +52,13 @@ pub struct WslRunningKernel {
     pub process: util::command::Child,
     connection_path: PathBuf,
     _process_status_task: Option<Task<()>>,
+    resource_monitor: Option<ResourceMonitor>,
     pub working_directory: PathBuf,
-    pub request_tx: mpsc::Sender<JupyterMessage>,
+    pub request_tx: KernelRequestSender,
     pub stdin_tx: mpsc::Sender<JupyterMessage>,
     pub execution_state: ExecutionState,
     pub kernel_info: Option<KernelInfoReply>,
+    message_counts: Option<KernelMessageCounts>,
 }
 
 impl Debug for WslRunningKernel {
@@ -66,6 +79,10 @@ impl WslRunningKernel {
         window: &mut Window,
         cx: &mut App,
     ) -> Task<Result<Box<dyn RunningKernel>>> {
+        let request_capacity =
+            ReplSettings::get_global(cx).request_queue_capacity(&kernel_specification.name);
+        let (inspectors, message_counts) =
+            kernel_message_inspectors(&kernel_specification.name, cx);
         window.spawn(cx, async move |cx| {
             // For WSL2, we need to get the WSL VM's IP address to connect to it
             // because WSL2 runs in a lightweight VM with its own network namespace.
@@ -257,7 +274,7 @@ impl WslRunningKernel {
                     cd_command, rest_string, rest_string, rest_string, rest_string
                 )
             } else {
-                kernel_args
+                let command = kernel_args
                     .iter()
                     .map(|arg| {
                         if arg.contains(' ') || arg.contains('\'') || arg.contains('"') {
@@ -267,8 +284,12 @@ impl WslRunningKernel {
                         }
                     })
                     .collect::<Vec<_>>()
-                    .join(" ")
+                    .join(" ");
+                format!("exec {command}")
             };
+            // The shell `exec`s the kernel, so its process id is the kernel's. wsl.exe's own
+            // process id is of no use for sampling the kernel's resource usage.
+            let shell_command = format!("echo \"{KERNEL_PID_PREFIX}$$\" >&2; {shell_command}");
 
             cmd.arg("bash")
                 .arg("-l")
@@ -358,15 +379,45 @@ impl WslRunningKernel {
                 shell_socket,
                 control_socket,
                 stdin_socket,
+                request_capacity,
+                inspectors,
                 cx,
             );
 
+            let (pid_tx, pid_rx) = oneshot::channel::<u32>();
+            let distro = kernel_specification.distro.clone();
+            let resource_monitor = ResourceMonitor::new(
+                async move {
+                    let pid = pid_rx.await.ok()?;
+                    let command = InstallCommand {
+                        program: "wsl".to_string(),
+                        args: ["-d", &distro, "--exec", "cat"]
+                            .into_iter()
+                            .map(ToString::to_string)
+                            .chain(ProcFsSampler::cat_args(pid))
+                            .collect(),
+                    };
+                    let sampler = ProcFsSampler::new(pid, command, Arc::new(SystemCommandRunner));
+                    Some(Box::new(sampler) as Box<dyn ProcessSampler>)
+                },
+                cx.background_executor(),
+            );
+
             let stderr = process.stderr.take();
             cx.spawn(async move |_cx| {
                 if let Some(stderr) = stderr {
                     let reader = BufReader::new(stderr);
                     let mut lines = reader.lines();
+                    let mut pid_tx = Some(pid_tx);
                     while let Some(Ok(line)) = lines.next().await {
+                        if let Some(pid) = line
+                            .strip_prefix(KERNEL_PID_PREFIX)
+                            .and_then(|pid| pid.trim().parse().ok())
+                            && let Some(pid_tx) = pid_tx.take()
+                        {
+                            pid_tx.send(pid).ok();
+                            continue;
+                        }
                         log::warn!("wsl kernel stderr: {}", line);
                     }
                 }
@@ -412,16 +463,18 @@ impl WslRunningKernel {
                 stdin_tx,
                 working_directory,
                 _process_status_task: Some(process_status_task),
+                resource_monitor: Some(resource_monitor),
                 connection_path,
                 execution_state: ExecutionState::Idle,
                 kernel_info: None,
+                message_counts,
             }) as Box<dyn RunningKernel>)
         })
     }
 }
 
 impl RunningKernel for WslRunningKernel {
-    fn request_tx(&self) -> mpsc::Sender<JupyterMessage> {
+    fn request_tx(&self) -> KernelRequestSender {
         self.request_tx.clone()
     }
 
@@ -449,8 +502,17 @@ impl RunningKernel for WslRunningKernel {
         self.kernel_info = Some(info);
     }
 
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.resource_monitor.as_ref()?.usage()
+    }
+
+    fn message_counts(&self) -> Option<KernelMessageCounts> {
+        self.message_counts.clone()
+    }
+
     fn force_shutdown(&mut self, _window: &mut Window, _cx: &mut App) -> Task<anyhow::Result<()>> {
         self._process_status_task.take();
+        self.resource_monitor.take();
         self.request_tx.close_channel();
         self.process.kill().ok();
         Task::ready(Ok(()))
@@ -458,6 +520,7 @@ impl RunningKernel for WslRunningKernel {
 
     fn kill(&mut self) {
         self._process_status_task.take();
+        self.resource_monitor.take();
         self.request_tx.close_channel();
         self.process.kill().ok();
     }
@@ -1,4 +1,6 @@
 mod cell;
+mod notebook_kernels;
 mod notebook_ui;
+mod output_diff;
 pub use cell::*;
 pub use notebook_ui::*;
@@ -16,10 +16,17 @@ use runtimelib::{JupyterMessage, JupyterMessageContent};
 use settings::Settings as _;
 use theme::ThemeSettings;
 use ui::{CommonAnimationExt, IconButtonShape, prelude::*};
-use util::ResultExt;
+use util::{ResultExt, fmt::human_duration};
 
 use crate::{
-    notebook::{CODE_BLOCK_INSET, GUTTER_WIDTH},
+    execution_kind::ExecutionKind,
+    notebook::{
+        CODE_BLOCK_INSET, GUTTER_WIDTH,
+        output_diff::{
+            OutputComparison, OutputGeneration, OutputHistory, OutputHistoryView,
+            render_output_diff, render_previous_outputs,
+        },
+    },
     outputs::{Output, plain, plain::TerminalOutput, user_error::ErrorView},
     repl_settings::ReplSettings,
 };
@@ -31,6 +38,10 @@ pub enum CellPosition {
     Last,
 }
 
+/// Where Zed keeps its own settings in a cell's metadata.
+const ZED_METADATA_KEY: &str = "zed";
+const KERNEL_LANGUAGE_KEY: &str = "kernel_language";
+
 pub enum CellControlType {
     RunCell,
     RerunCell,
@@ -112,11 +123,15 @@ fn convert_outputs(
                 content: cx.new(|cx| TerminalOutput::from(&text.0, window, cx)),
             },
             nbformat::v4::Output::DisplayData(display_data) => {
-                Output::new(&display_data.data, None, window, cx)
-            }
-            nbformat::v4::Output::ExecuteResult(execute_result) => {
-                Output::new(&execute_result.data, None, window, cx)
+                Output::new(&display_data.data, &display_data.metadata, None, window, cx)
             }
+            nbformat::v4::Output::ExecuteResult(execute_result) => Output::new(
+                &execute_result.data,
+                &execute_result.metadata,
+                None,
+                window,
+                cx,
+            ),
             nbformat::v4::Output::Error(error) => Output::ErrorOutput(ErrorView {
                 ename: error.ename.clone(),
                 evalue: error.evalue.clone(),
@@ -609,7 +624,13 @@ pub struct CodeCell {
     language_task: Task<()>,
     execution_start_time: Option<Instant>,
     execution_duration: Option<Duration>,
+    execution_kind: ExecutionKind,
     is_executing: bool,
+    outputs_stale: bool,
+    /// Set when the cell's metadata changed since the notebook was last saved.
+    metadata_dirty: bool,
+    output_history: OutputHistory,
+    compare_outputs_task: Task<()>,
 }
 
 impl EventEmitter<CellEvent> for CodeCell {}
@@ -673,7 +694,12 @@ impl CodeCell {
             language_task,
             execution_start_time: None,
             execution_duration: None,
+            execution_kind: ExecutionKind::Code,
             is_executing: false,
+            outputs_stale: false,
+            metadata_dirty: false,
+            output_history: OutputHistory::default(),
+            compare_outputs_task: Task::ready(()),
         }
     }
 
@@ -751,7 +777,12 @@ impl CodeCell {
             language_task,
             execution_start_time: None,
             execution_duration: None,
+            execution_kind: ExecutionKind::Code,
             is_executing: false,
+            outputs_stale: false,
+            metadata_dirty: false,
+            output_history: OutputHistory::default(),
+            compare_outputs_task: Task::ready(()),
         }
     }
 
@@ -769,7 +800,47 @@ impl CodeCell {
     }
 
     pub fn is_dirty(&self, cx: &App) -> bool {
-        self.editor.read(cx).buffer().read(cx).is_dirty(cx)
+        self.metadata_dirty || self.editor.read(cx).buffer().read(cx).is_dirty(cx)
+    }
+
+    pub fn mark_metadata_saved(&mut self) {
+        self.metadata_dirty = false;
+    }
+
+    /// The language of the kernel the cell runs on, when it isn't the notebook's primary
+    /// kernel. It's kept in the cell's `zed` metadata, so that it's saved with the notebook.
+    pub fn kernel_language(&self) -> Option<SharedString> {
+        self.metadata
+            .additional
+            .get(ZED_METADATA_KEY)?
+            .get(KERNEL_LANGUAGE_KEY)?
+            .as_str()
+            .map(|language| SharedString::from(language.to_string()))
+    }
+
+    pub fn set_kernel_language(&mut self, language: Option<SharedString>) {
+        if self.kernel_language() == language {
+            return;
+        }
+        let zed_metadata = self
+            .metadata
+            .additional
+            .entry(ZED_METADATA_KEY.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+        if let Some(zed_metadata) = zed_metadata.as_object_mut() {
+            match language {
+                Some(language) => {
+                    zed_metadata.insert(KERNEL_LANGUAGE_KEY.to_string(), language.as_ref().into());
+                }
+                None => {
+                    zed_metadata.remove(KERNEL_LANGUAGE_KEY);
+                }
+            }
+            if zed_metadata.is_empty() {
+                self.metadata.additional.remove(ZED_METADATA_KEY);
+            }
+        }
+        self.metadata_dirty = true;
     }
 
     pub fn to_nbformat_cell(&self, cx: &App) -> nbformat::v4::Cell {
@@ -801,12 +872,109 @@ impl CodeCell {
     pub fn clear_outputs(&mut self) {
         self.outputs.clear();
         self.execution_duration = None;
+        self.outputs_stale = false;
+        self.forget_previous_outputs();
     }
 
-    pub fn start_execution(&mut self) {
+    /// Clears the outputs before the cell runs again, keeping enough of them to show how the
+    /// new run's outputs differ.
+    pub fn rotate_outputs(&mut self, cx: &App) {
+        let ran_before = self.has_outputs() || self.execution_count.is_some();
+        let generation = (ran_before && !self.outputs_stale).then(|| {
+            let max_bytes = ReplSettings::get_global(cx).output_max_bytes;
+            OutputGeneration::capture(&self.outputs, max_bytes, cx)
+        });
+        self.clear_outputs();
+        if let Some(generation) = generation {
+            self.output_history.retain(generation);
+        }
+    }
+
+    /// Drops the previous run's outputs, such as when the kernel they came from restarts.
+    pub fn forget_previous_outputs(&mut self) {
+        self.output_history.clear();
+        self.compare_outputs_task = Task::ready(());
+    }
+
+    fn compare_outputs(&mut self, cx: &mut Context<Self>) {
+        let Some(previous) = self.output_history.previous().cloned() else {
+            return;
+        };
+        let max_bytes = ReplSettings::get_global(cx).output_max_bytes;
+        let current = OutputGeneration::capture(&self.outputs, max_bytes, cx);
+        let comparison = cx.background_spawn({
+            let previous = previous.clone();
+            async move { OutputComparison::compute(&previous, &current).await }
+        });
+        self.compare_outputs_task = cx.spawn(async move |this, cx| {
+            let comparison = comparison.await;
+            this.update(cx, |this, cx| {
+                if this.output_history.set_comparison(&previous, comparison) {
+                    cx.notify();
+                }
+            })
+            .ok();
+        });
+    }
+
+    fn render_output_comparison(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let comparison = self.output_history.comparison()?;
+        let changed = comparison.changed()?;
+        let view = self.output_history.view();
+        let toggle = |id: &'static str, label: &'static str, target: OutputHistoryView| {
+            Button::new(id, label)
+                .label_size(LabelSize::XSmall)
+                .toggle_state(view == target)
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.output_history.toggle_view(target);
+                    cx.notify();
+                }))
+        };
+        Some(
+            h_flex()
+                .gap_1()
+                .child(
+                    Label::new(if changed {
+                        "Output changed"
+                    } else {
+                        "Output unchanged"
+                    })
+                    .size(LabelSize::XSmall)
+                    .color(if changed {
+                        Color::Modified
+                    } else {
+                        Color::Muted
+                    }),
+                )
+                .when(changed, |this| {
+                    this.child(toggle(
+                        "show-previous-output",
+                        "Previous",
+                        OutputHistoryView::Previous,
+                    ))
+                    .when(comparison.has_diff(), |this| {
+                        this.child(toggle("show-output-diff", "Diff", OutputHistoryView::Diff))
+                    })
+                })
+                .into_any_element(),
+        )
+    }
+
+    /// Keeps the outputs visible but dims them, since they came from a kernel that is gone.
+    pub fn mark_outputs_stale(&mut self) {
+        self.outputs_stale = true;
+    }
+
+    pub fn reset_execution_count(&mut self) {
+        self.execution_count = None;
+    }
+
+    pub fn start_execution(&mut self, cx: &App) {
+        self.execution_kind = ExecutionKind::detect(&self.current_source(cx));
         self.execution_start_time = Some(Instant::now());
         self.execution_duration = None;
         self.is_executing = true;
+        self.outputs_stale = false;
     }
 
     pub fn finish_execution(&mut self) {
@@ -824,19 +992,6 @@ impl CodeCell {
         self.execution_duration
     }
 
-    fn format_duration(duration: Duration) -> String {
-        let total_secs = duration.as_secs_f64();
-        if total_secs < 1.0 {
-            format!("{:.0}ms", duration.as_millis())
-        } else if total_secs < 60.0 {
-            format!("{:.1}s", total_secs)
-        } else {
-            let minutes = (total_secs / 60.0).floor() as u64;
-            let secs = total_secs % 60.0;
-            format!("{}m {:.1}s", minutes, secs)
-        }
-    }
-
     pub fn handle_message(
         &mut self,
         message: &JupyterMessage,
@@ -845,17 +1000,33 @@ impl CodeCell {
     ) {
         match &message.content {
             JupyterMessageContent::StreamContent(stream) => {
-                self.outputs.push(Output::Stream {
-                    content: cx.new(|cx| TerminalOutput::from(&stream.text, window, cx)),
-                });
+                if let Some(output) = Output::append_stream(
+                    self.outputs.last_mut(),
+                    &stream.text,
+                    &stream.name,
+                    window,
+                    cx,
+                ) {
+                    self.outputs.push(output);
+                }
             }
             JupyterMessageContent::DisplayData(display_data) => {
-                self.outputs
-                    .push(Output::new(&display_data.data, None, window, cx));
+                self.outputs.push(Output::new(
+                    &display_data.data,
+                    &display_data.metadata,
+                    None,
+                    window,
+                    cx,
+                ));
             }
             JupyterMessageContent::ExecuteResult(execute_result) => {
-                self.outputs
-                    .push(Output::new(&execute_result.data, None, window, cx));
+                self.outputs.push(Output::new(
+                    &execute_result.data,
+                    &execute_result.metadata,
+                    None,
+                    window,
+                    cx,
+                ));
             }
             JupyterMessageContent::ExecuteInput(input) => {
                 self.execution_count = serde_json::to_value(&input.execution_count)
@@ -865,6 +1036,7 @@ impl CodeCell {
             }
             JupyterMessageContent::ExecuteReply(_) => {
                 self.finish_execution();
+                self.compare_outputs(cx);
             }
             JupyterMessageContent::ErrorOutput(error) => {
                 self.outputs.push(Output::ErrorOutput(ErrorView {
@@ -1076,6 +1248,12 @@ impl Render for CodeCell {
             .as_singleton()
             .and_then(|buffer| buffer.read(cx).language())
             .map(|lang| lang.name().to_string());
+        // A cell running on another kernel than the notebook's is labeled with that kernel's
+        // language instead.
+        let language_name = self
+            .kernel_language()
+            .map(|language| language.to_string())
+            .or(language_name);
 
         v_flex()
             .size_full()
@@ -1127,7 +1305,24 @@ impl Render for CodeCell {
             .when(
                 self.has_outputs() || self.execution_duration.is_some() || self.is_executing,
                 |this| {
-                    let execution_time_label = self.execution_duration.map(Self::format_duration);
+                    let execution_time_label = self.execution_duration.map(human_duration);
+                    let execution_kind_badge = self.execution_kind.render_badge();
+                    let output_comparison = self.render_output_comparison(cx);
+                    let previous_output = match self.output_history.view() {
+                        OutputHistoryView::Current => None,
+                        OutputHistoryView::Previous => self
+                            .output_history
+                            .previous()
+                            .map(|previous| render_previous_outputs(previous, cx)),
+                        OutputHistoryView::Diff => self
+                            .output_history
+                            .comparison()
+                            .map(|comparison| render_output_diff(comparison, cx)),
+                    };
+                    // The current outputs stay mounted while the previous ones are shown.
+                    let outputs_div = div()
+                        .w_full()
+                        .when(previous_output.is_some(), |this| this.hidden());
                     let is_executing = self.is_executing;
                     this.child(
                         h_flex()
@@ -1147,6 +1342,7 @@ impl Render for CodeCell {
                                         .px_5()
                                         .rounded_lg()
                                         .border_1()
+                                        .when(self.outputs_stale, |this| this.opacity(0.5))
                                         // execution status/time at the TOP
                                         .when(
                                             is_executing || execution_time_label.is_some(),
@@ -1194,11 +1390,19 @@ impl Render for CodeCell {
                                                 } else {
                                                     div().into_any_element()
                                                 };
-                                                this.child(div().mb_2().child(time_element))
+                                                this.child(
+                                                    h_flex()
+                                                        .mb_2()
+                                                        .gap_2()
+                                                        .child(time_element)
+                                                        .children(execution_kind_badge)
+                                                        .children(output_comparison),
+                                                )
                                             },
                                         )
                                         // output at bottom
-                                        .child(div().w_full().children(self.outputs.iter().map(
+                                        .children(previous_output)
+                                        .child(outputs_div.children(self.outputs.iter().map(
                                             |output| {
                                                 let content = match output {
                                                     Output::Plain { content, .. } => {
@@ -0,0 +1,587 @@
+//! The kernels a notebook runs its cells on.
+//!
+//! A notebook has a primary kernel, which its cells run on unless they say otherwise, and can
+//! start kernels for other languages next to it, such as a SQL kernel whose query results a
+//! Python cell then plots. Kernels are keyed by their language, and a code cell that names
+//! another language in its metadata runs on that language's kernel.
+
+use anyhow::{Result, bail};
+use collections::HashMap;
+use futures::FutureExt as _;
+use gpui::{Context, Entity, SharedString, Task, WeakEntity, Window};
+use nbformat::v4::CellId;
+use runtimelib::{ExecutionState, JupyterMessage};
+use util::{ResultExt as _, post_inc};
+
+use super::NotebookEditor;
+use crate::kernel_status::KernelSummary;
+use crate::kernels::{Kernel, KernelSession, KernelSpecification, KernelStatus};
+
+/// Tells a notebook's kernels apart, including a kernel from the one that replaced it for the
+/// same language, whose messages may still be on their way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KernelId(u64);
+
+pub struct NotebookKernel {
+    pub id: KernelId,
+    /// The language the kernel runs, lowercased.
+    pub language: SharedString,
+    pub specification: KernelSpecification,
+    pub kernel: Kernel,
+    /// Where the kernel sends its messages. It lives as long as the kernel is in the notebook.
+    router: Option<Entity<KernelRouter>>,
+}
+
+impl NotebookKernel {
+    pub fn status(&self) -> KernelStatus {
+        self.kernel.status()
+    }
+
+    pub fn is_starting(&self) -> bool {
+        matches!(
+            self.kernel,
+            Kernel::StartingKernel(_) | Kernel::Restarting(_)
+        )
+    }
+
+    pub fn set_router(&mut self, router: Entity<KernelRouter>) {
+        self.router = Some(router);
+    }
+}
+
+/// Lowercases a kernel or cell language, so that "R" and "r" find the same kernel.
+pub fn kernel_language(language: &str) -> SharedString {
+    language.trim().to_lowercase().into()
+}
+
+#[derive(Default)]
+pub struct NotebookKernels {
+    /// The primary kernel first, then the others in the order they were started.
+    kernels: Vec<NotebookKernel>,
+    has_primary: bool,
+    /// The cell and kernel of each execute request sent, keyed by the request's message id.
+    executions: HashMap<String, (CellId, KernelId)>,
+    next_id: u64,
+}
+
+impl NotebookKernels {
+    pub fn primary(&self) -> Option<&NotebookKernel> {
+        self.kernels.first().filter(|_| self.has_primary)
+    }
+
+    pub fn is_primary(&self, id: KernelId) -> bool {
+        self.primary().is_some_and(|primary| primary.id == id)
+    }
+
+    /// The kernels started next to the primary one.
+    pub fn secondary(&self) -> impl Iterator<Item = &NotebookKernel> {
+        self.kernels.iter().skip(usize::from(self.has_primary))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NotebookKernel> {
+        self.kernels.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut NotebookKernel> {
+        self.kernels.iter_mut()
+    }
+
+    pub fn get(&self, language: &str) -> Option<&NotebookKernel> {
+        let language = kernel_language(language);
+        self.kernels
+            .iter()
+            .find(|kernel| kernel.language == language)
+    }
+
+    pub fn get_mut(&mut self, language: &str) -> Option<&mut NotebookKernel> {
+        let language = kernel_language(language);
+        self.kernels
+            .iter_mut()
+            .find(|kernel| kernel.language == language)
+    }
+
+    pub fn by_id(&self, id: KernelId) -> Option<&NotebookKernel> {
+        self.kernels.iter().find(|kernel| kernel.id == id)
+    }
+
+    pub fn by_id_mut(&mut self, id: KernelId) -> Option<&mut NotebookKernel> {
+        self.kernels.iter_mut().find(|kernel| kernel.id == id)
+    }
+
+    /// The kernel a cell runs on, given the language it asks for, if any. Cells that don't ask
+    /// for one, or ask for the primary kernel's language, run on the primary kernel.
+    pub fn for_cell(&self, cell_language: Option<&str>) -> Option<&NotebookKernel> {
+        match cell_language {
+            Some(language) => self.get(language),
+            None => self.primary(),
+        }
+    }
+
+    pub fn for_cell_mut(&mut self, cell_language: Option<&str>) -> Option<&mut NotebookKernel> {
+        match cell_language {
+            Some(language) => self.get_mut(language),
+            None if self.has_primary => self.kernels.first_mut(),
+            None => None,
+        }
+    }
+
+    /// Adds a kernel for `specification`'s language, which starts out as starting until the
+    /// caller hands it the task launching it. Returns the new kernel's id along with the
+    /// kernels it replaces, which the caller still has to shut down and finish the executions
+    /// of: the one that ran the same language, and when adding the primary kernel, the previous
+    /// primary kernel.
+    pub fn insert(
+        &mut self,
+        specification: KernelSpecification,
+        primary: bool,
+    ) -> (KernelId, Vec<NotebookKernel>) {
+        let language = kernel_language(&specification.language());
+        let mut replaced = Vec::new();
+        if primary && self.has_primary {
+            replaced.push(self.kernels.remove(0));
+            self.has_primary = false;
+        }
+        if let Some(ix) = self
+            .kernels
+            .iter()
+            .position(|kernel| kernel.language == language)
+        {
+            if ix == 0 && self.has_primary {
+                // Starting a secondary kernel in the primary kernel's language replaces the
+                // primary kernel, since cells in that language run on it anyway.
+                self.has_primary = false;
+                return self.insert(specification, true);
+            }
+            replaced.push(self.kernels.remove(ix));
+        }
+
+        let id = KernelId(post_inc(&mut self.next_id));
+        let kernel = NotebookKernel {
+            id,
+            language,
+            specification,
+            kernel: Kernel::StartingKernel(Task::ready(()).shared()),
+            router: None,
+        };
+        if primary {
+            self.kernels.insert(0, kernel);
+            self.has_primary = true;
+        } else {
+            self.kernels.push(kernel);
+        }
+        (id, replaced)
+    }
+
+    pub fn remove(&mut self, id: KernelId) -> Option<NotebookKernel> {
+        let ix = self.kernels.iter().position(|kernel| kernel.id == id)?;
+        if ix == 0 && self.has_primary {
+            self.has_primary = false;
+        }
+        Some(self.kernels.remove(ix))
+    }
+
+    /// Sends a cell's execute request to the kernel the cell runs on, returning that kernel.
+    /// Fails when there's no kernel for the cell's language, or it isn't running.
+    pub fn execute(
+        &mut self,
+        cell_id: CellId,
+        cell_language: Option<&str>,
+        message: JupyterMessage,
+    ) -> Result<KernelId> {
+        let Some(kernel) = self.for_cell_mut(cell_language) else {
+            match cell_language {
+                Some(language) => bail!("no {language} kernel is running in this notebook"),
+                None => bail!("no kernel is running in this notebook"),
+            }
+        };
+        let Kernel::RunningKernel(running_kernel) = &mut kernel.kernel else {
+            bail!("the {} kernel isn't running", kernel.specification.name());
+        };
+        let id = kernel.id;
+        let msg_id = message.header.msg_id.clone();
+        running_kernel.request_tx().try_send(message)?;
+        self.execution_sent(msg_id, cell_id, id);
+        Ok(id)
+    }
+
+    pub fn interrupt(&mut self, id: KernelId) -> Result<()> {
+        match self.by_id_mut(id).map(|kernel| &mut kernel.kernel) {
+            Some(Kernel::RunningKernel(kernel)) => kernel.interrupt(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Interrupts the kernels that aren't idle, leaving the others to carry on undisturbed.
+    pub fn interrupt_active(&mut self) {
+        for kernel in &mut self.kernels {
+            if kernel.status() != KernelStatus::Idle
+                && let Kernel::RunningKernel(running_kernel) = &mut kernel.kernel
+            {
+                running_kernel.interrupt().log_err();
+            }
+        }
+    }
+
+    /// The status shown for the notebook as a whole: the one most in need of attention.
+    pub fn status(&self) -> KernelStatus {
+        KernelSummary::new(self.kernels.iter().map(NotebookKernel::status))
+            .worst_status
+            .unwrap_or(KernelStatus::Shutdown)
+    }
+
+    /// Whether a kernel is still on its way up, so cells can't all run yet.
+    pub fn is_starting(&self) -> bool {
+        self.kernels.iter().any(NotebookKernel::is_starting)
+    }
+
+    pub fn set_execution_state(&mut self, id: KernelId, state: &ExecutionState) {
+        if let Some(kernel) = self.by_id_mut(id) {
+            kernel.kernel.set_execution_state(state);
+        }
+    }
+
+    pub fn execution_sent(&mut self, msg_id: String, cell_id: CellId, kernel: KernelId) {
+        self.executions.insert(msg_id, (cell_id, kernel));
+    }
+
+    /// The cell a message from `kernel` is about, when it's part of an execution sent to it.
+    pub fn cell_for_message(&self, kernel: KernelId, parent_msg_id: &str) -> Option<&CellId> {
+        self.executions
+            .get(parent_msg_id)
+            .filter(|(_, execution_kernel)| *execution_kernel == kernel)
+            .map(|(cell_id, _)| cell_id)
+    }
+
+    pub fn remove_execution(&mut self, msg_id: &str) -> Option<CellId> {
+        self.executions.remove(msg_id).map(|(cell_id, _)| cell_id)
+    }
+
+    /// Forgets the executions sent to `kernel`, returning the cells they were for.
+    pub fn forget_executions(&mut self, kernel: KernelId) -> Vec<CellId> {
+        let mut cells = Vec::new();
+        self.executions.retain(|_, (cell_id, execution_kernel)| {
+            if *execution_kernel == kernel {
+                cells.push(cell_id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        cells
+    }
+
+    pub fn clear_executions(&mut self) {
+        self.executions.clear();
+    }
+}
+
+/// Passes the messages of one of a notebook's kernels on to the notebook, along with which of
+/// its kernels they came from.
+pub struct KernelRouter {
+    notebook: WeakEntity<NotebookEditor>,
+    kernel: KernelId,
+}
+
+impl KernelRouter {
+    pub fn new(notebook: WeakEntity<NotebookEditor>, kernel: KernelId) -> Self {
+        Self { notebook, kernel }
+    }
+}
+
+impl KernelSession for KernelRouter {
+    fn route(&mut self, message: &JupyterMessage, window: &mut Window, cx: &mut Context<Self>) {
+        let kernel = self.kernel;
+        self.notebook
+            .update(cx, |notebook, cx| {
+                notebook.route_kernel_message(kernel, message, window, cx)
+            })
+            .ok();
+    }
+
+    fn kernel_errored(&mut self, error_message: String, cx: &mut Context<Self>) {
+        let kernel = self.kernel;
+        self.notebook
+            .update(cx, |notebook, cx| {
+                notebook.kernel_errored(kernel, error_message, cx)
+            })
+            .ok();
+    }
+
+    fn requests_failed(
+        &mut self,
+        msg_ids: &[String],
+        error_message: String,
+        cx: &mut Context<Self>,
+    ) {
+        let kernel = self.kernel;
+        self.notebook
+            .update(cx, |notebook, cx| {
+                notebook.requests_failed(kernel, msg_ids, error_message, cx)
+            })
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernels::{
+        KernelRequestSender, LocalKernelSource, LocalKernelSpecification, RunningKernel,
+        kernel_request_channel,
+    };
+    use futures::channel::mpsc;
+    use gpui::App;
+    use jupyter_protocol::JupyterKernelspec;
+    use runtimelib::{ExecuteRequest, JupyterMessageContent, KernelInfoReply};
+    use smol::channel::Receiver;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    /// Hands the requests it's sent to the test, and interrupts with the default
+    /// `interrupt_request`.
+    #[derive(Debug)]
+    struct FakeKernel {
+        request_tx: KernelRequestSender,
+        stdin_tx: mpsc::Sender<JupyterMessage>,
+        working_directory: PathBuf,
+        execution_state: ExecutionState,
+        kernel_info: Option<KernelInfoReply>,
+    }
+
+    impl RunningKernel for FakeKernel {
+        fn request_tx(&self) -> KernelRequestSender {
+            self.request_tx.clone()
+        }
+
+        fn stdin_tx(&self) -> mpsc::Sender<JupyterMessage> {
+            self.stdin_tx.clone()
+        }
+
+        fn working_directory(&self) -> &PathBuf {
+            &self.working_directory
+        }
+
+        fn execution_state(&self) -> &ExecutionState {
+            &self.execution_state
+        }
+
+        fn set_execution_state(&mut self, state: ExecutionState) {
+            self.execution_state = state;
+        }
+
+        fn kernel_info(&self) -> Option<&KernelInfoReply> {
+            self.kernel_info.as_ref()
+        }
+
+        fn set_kernel_info(&mut self, info: KernelInfoReply) {
+            self.kernel_info = Some(info);
+        }
+
+        fn force_shutdown(&mut self, _: &mut Window, _: &mut App) -> Task<Result<()>> {
+            Task::ready(Ok(()))
+        }
+
+        fn kill(&mut self) {}
+    }
+
+    fn specification(name: &str, language: &str) -> KernelSpecification {
+        KernelSpecification::Jupyter(LocalKernelSpecification {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            kernelspec: JupyterKernelspec {
+                argv: vec![name.to_string(), "{connection_file}".to_string()],
+                display_name: name.to_string(),
+                language: language.to_string(),
+                interrupt_mode: None,
+                metadata: None,
+                env: None,
+            },
+            source: LocalKernelSource::Kernelspec,
+        })
+    }
+
+    /// Adds a kernel and marks it as running, returning where its requests end up.
+    fn start(
+        kernels: &mut NotebookKernels,
+        name: &str,
+        language: &str,
+        primary: bool,
+    ) -> (KernelId, Receiver<JupyterMessage>) {
+        let (id, replaced) = kernels.insert(specification(name, language), primary);
+        assert!(replaced.is_empty());
+        let (request_tx, request_rx) = kernel_request_channel(16);
+        let kernel = FakeKernel {
+            request_tx,
+            stdin_tx: mpsc::channel(1).0,
+            working_directory: PathBuf::from("/notebooks"),
+            execution_state: ExecutionState::Idle,
+            kernel_info: None,
+        };
+        kernels.by_id_mut(id).unwrap().kernel = Kernel::RunningKernel(Box::new(kernel));
+        (id, request_rx)
+    }
+
+    fn execute_request(code: &str) -> JupyterMessage {
+        ExecuteRequest {
+            code: code.to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn received(rx: &Receiver<JupyterMessage>) -> Vec<JupyterMessageContent> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|message| message.content)
+            .collect()
+    }
+
+    fn new_cell() -> CellId {
+        CellId::from(Uuid::new_v4())
+    }
+
+    #[test]
+    fn test_cells_run_on_their_language_kernel() {
+        let (query_cell, plot_cell) = (new_cell(), new_cell());
+        let mut kernels = NotebookKernels::default();
+        let (python, python_rx) = start(&mut kernels, "python3", "python", true);
+        let (sql, sql_rx) = start(&mut kernels, "xsql", "SQL", false);
+
+        assert_eq!(kernels.primary().map(|kernel| kernel.id), Some(python));
+        assert_eq!(
+            kernels
+                .secondary()
+                .map(|kernel| kernel.id)
+                .collect::<Vec<_>>(),
+            [sql]
+        );
+
+        let query = execute_request("select * from runs");
+        let query_id = query.header.msg_id.clone();
+        assert_eq!(
+            kernels
+                .execute(query_cell.clone(), Some("sql"), query)
+                .unwrap(),
+            sql
+        );
+        let plot = execute_request("plot(runs)");
+        let plot_id = plot.header.msg_id.clone();
+        assert_eq!(
+            kernels.execute(plot_cell.clone(), None, plot).unwrap(),
+            python
+        );
+        // Naming the primary kernel's language is the same as not naming one.
+        assert_eq!(
+            kernels
+                .execute(new_cell(), Some("Python"), execute_request("print(1)"))
+                .unwrap(),
+            python
+        );
+
+        assert!(matches!(
+            received(&sql_rx).as_slice(),
+            [JupyterMessageContent::ExecuteRequest(request)] if request.code == "select * from runs"
+        ));
+        assert_eq!(received(&python_rx).len(), 2);
+
+        // Replies are matched to cells only when they come from the kernel that ran them.
+        assert_eq!(kernels.cell_for_message(sql, &query_id), Some(&query_cell));
+        assert_eq!(kernels.cell_for_message(python, &query_id), None);
+        assert_eq!(kernels.cell_for_message(python, &plot_id), Some(&plot_cell));
+
+        // A cell asking for a language nothing runs doesn't get sent anywhere.
+        assert!(
+            kernels
+                .execute(new_cell(), Some("r"), execute_request("summary(runs)"))
+                .is_err()
+        );
+        assert!(received(&python_rx).is_empty());
+        assert!(received(&sql_rx).is_empty());
+    }
+
+    #[test]
+    fn test_interrupts_only_reach_active_kernels() {
+        let mut kernels = NotebookKernels::default();
+        let (python, python_rx) = start(&mut kernels, "python3", "python", true);
+        let (sql, sql_rx) = start(&mut kernels, "xsql", "sql", false);
+
+        kernels.set_execution_state(sql, &ExecutionState::Busy);
+        kernels.interrupt_active();
+        assert!(received(&python_rx).is_empty());
+        assert!(matches!(
+            received(&sql_rx).as_slice(),
+            [JupyterMessageContent::InterruptRequest(_)]
+        ));
+
+        kernels.interrupt(python).unwrap();
+        assert!(matches!(
+            received(&python_rx).as_slice(),
+            [JupyterMessageContent::InterruptRequest(_)]
+        ));
+        assert!(received(&sql_rx).is_empty());
+    }
+
+    #[test]
+    fn test_status_is_the_worst_of_the_kernels() {
+        let mut kernels = NotebookKernels::default();
+        assert_eq!(kernels.status(), KernelStatus::Shutdown);
+
+        let (python, _python_rx) = start(&mut kernels, "python3", "python", true);
+        assert_eq!(kernels.status(), KernelStatus::Idle);
+
+        let (sql, _sql_rx) = start(&mut kernels, "xsql", "sql", false);
+        kernels.set_execution_state(sql, &ExecutionState::Busy);
+        assert_eq!(kernels.status(), KernelStatus::Busy);
+        assert_eq!(kernels.primary().unwrap().status(), KernelStatus::Idle);
+
+        kernels.by_id_mut(python).unwrap().kernel =
+            Kernel::ErroredLaunch("kernel died".to_string());
+        assert_eq!(kernels.status(), KernelStatus::Error);
+
+        kernels.insert(specification("ir", "r"), false);
+        assert!(kernels.is_starting());
+    }
+
+    #[test]
+    fn test_replacing_a_kernel() {
+        let query_cell = new_cell();
+        let mut kernels = NotebookKernels::default();
+        let (python, _python_rx) = start(&mut kernels, "python3", "python", true);
+        let (sql, _sql_rx) = start(&mut kernels, "xsql", "sql", false);
+        kernels
+            .execute(query_cell.clone(), Some("sql"), execute_request("select 1"))
+            .unwrap();
+        kernels
+            .execute(new_cell(), None, execute_request("plot()"))
+            .unwrap();
+
+        // Starting another SQL kernel replaces the running one, whose executions the notebook
+        // then finishes.
+        let (new_sql, replaced) = kernels.insert(specification("duckdb", "sql"), false);
+        assert_eq!(
+            replaced.iter().map(|kernel| kernel.id).collect::<Vec<_>>(),
+            [sql]
+        );
+        assert_ne!(new_sql, sql);
+        assert_eq!(kernels.forget_executions(sql), [query_cell]);
+        assert_eq!(kernels.get("sql").map(|kernel| kernel.id), Some(new_sql));
+        assert!(kernels.by_id(sql).is_none());
+
+        // A secondary kernel in the primary kernel's language takes over as the primary one.
+        let (new_python, replaced) = kernels.insert(specification("python3.12", "python"), false);
+        assert_eq!(
+            replaced.iter().map(|kernel| kernel.id).collect::<Vec<_>>(),
+            [python]
+        );
+        assert!(kernels.is_primary(new_python));
+        assert_eq!(kernels.secondary().count(), 1);
+
+        // Removing the primary kernel leaves the secondary ones running.
+        kernels.remove(new_python);
+        assert!(kernels.primary().is_none());
+        assert!(kernels.for_cell(None).is_none());
+        assert_eq!(
+            kernels.for_cell(Some("sql")).map(|kernel| kernel.id),
+            Some(new_sql)
+        );
+    }
+}
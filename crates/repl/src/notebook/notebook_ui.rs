@@ -1,4 +1,5 @@
 #![allow(unused, dead_code)]
+use std::collections::VecDeque;
 use std::future::Future;
 use std::{path::PathBuf, sync::Arc};
 
@@ -19,10 +20,14 @@ use log;
 use project::{Project, ProjectEntryId, ProjectPath};
 use settings::Settings as _;
 use ui::{CommonAnimationExt, Tooltip, prelude::*};
+use util::{ResultExt as _, TryFutureExt as _};
 use workspace::item::{ItemEvent, SaveOptions, TabContentParams};
 use workspace::searchable::SearchableItemHandle;
 use workspace::{Item, ItemHandle, Pane, ProjectItem, ToolbarItemLocation};
 
+use super::notebook_kernels::{
+    KernelId, KernelRouter, NotebookKernel, NotebookKernels, kernel_language,
+};
 use super::{Cell, CellEvent, CellPosition, MarkdownCellEvent, RenderableCell};
 
 use nbformat::v4::CellId;
@@ -31,15 +36,22 @@ use serde_json;
 use uuid::Uuid;
 
 use crate::components::{KernelPickerDelegate, KernelSelector};
+use crate::kernel_status::{KernelCommand, KernelOwner, KernelSessions, run_kernel_command};
 use crate::kernels::{
-    Kernel, KernelSession, KernelSpecification, KernelStatus, LocalKernelSpecification,
-    NativeRunningKernel, RemoteRunningKernel, SshRunningKernel, WslRunningKernel,
+    Kernel, KernelSpecification, KernelStatus, LocalKernelSource, LocalKernelSpecification,
+    NativeRunningKernel, RemoteRunningKernel, ResourceUsage, RestartKind, RestartOutcome,
+    RunningKernel, SshRunningKernel, WslRunningKernel, restart_in_place,
 };
+use crate::repl_settings::ReplSettings;
 use crate::repl_store::ReplStore;
+use crate::{ClearOutputs, InterruptKernel, RestartKernel, offer_ipykernel_install};
 
 use picker::Picker;
-use runtimelib::{ExecuteRequest, JupyterMessage, JupyterMessageContent};
-use ui::PopoverMenuHandle;
+use runtimelib::{
+    ExecuteRequest, JupyterMessage, JupyterMessageContent, KernelInfoRequest, ReplyStatus,
+    ShutdownRequest,
+};
+use ui::{ContextMenu, PopoverMenu, PopoverMenuHandle};
 use zed_actions::editor::{MoveDown, MoveUp};
 use zed_actions::notebook::{NotebookMoveDown, NotebookMoveUp};
 
@@ -52,8 +64,6 @@ actions!(
         RunAll,
         /// Runs the current cell.
         Run,
-        /// Clears all cell outputs.
-        ClearOutputs,
         /// Moves the current cell up.
         MoveCellUp,
         /// Moves the current cell down.
@@ -62,10 +72,8 @@ actions!(
         AddMarkdownBlock,
         /// Adds a new code cell.
         AddCodeBlock,
-        /// Restarts the kernel.
-        RestartKernel,
-        /// Interrupts the current execution.
-        InterruptKernel,
+        /// Restarts the kernel and runs all cells in order.
+        RestartAndRunAll,
     ]
 );
 
@@ -111,12 +119,68 @@ pub struct NotebookEditor {
     cell_order: Vec<CellId>,
     original_cell_order: Vec<CellId>,
     cell_map: HashMap<CellId, Cell>,
-    kernel: Kernel,
-    kernel_specification: Option<KernelSpecification>,
-    execution_requests: HashMap<String, CellId>,
+    kernels: NotebookKernels,
+    run_all_queue: Option<RunAllQueue>,
     kernel_picker_handle: PopoverMenuHandle<Picker<KernelPickerDelegate>>,
 }
 
+/// Code cells waiting to be replayed after [`RestartAndRunAll`].
+///
+/// Cells are sent one at a time, so that an erroring cell can stop the ones after it
+/// instead of leaving them queued inside the kernel.
+#[derive(Debug)]
+struct RunAllQueue {
+    pending: VecDeque<CellId>,
+    in_flight: Option<String>,
+    kernel_ready: bool,
+    continue_on_error: bool,
+}
+
+impl RunAllQueue {
+    fn new(cells: Vec<CellId>, continue_on_error: bool) -> Self {
+        Self {
+            pending: cells.into(),
+            in_flight: None,
+            kernel_ready: false,
+            continue_on_error,
+        }
+    }
+
+    /// Returns the first cell to run once the restarted kernel has answered `kernel_info`.
+    fn kernel_ready(&mut self) -> Option<CellId> {
+        if self.kernel_ready {
+            return None;
+        }
+        self.kernel_ready = true;
+        self.pending.pop_front()
+    }
+
+    fn cell_sent(&mut self, msg_id: String) {
+        self.in_flight = Some(msg_id);
+    }
+
+    /// Returns the next cell to run once the in-flight cell has replied.
+    fn reply_received(&mut self, parent_msg_id: &str, status: &ReplyStatus) -> Option<CellId> {
+        if self.in_flight.as_deref() != Some(parent_msg_id) {
+            return None;
+        }
+        self.in_flight = None;
+
+        if *status != ReplyStatus::Ok && !self.continue_on_error {
+            self.pending.clear();
+        }
+        self.pending.pop_front()
+    }
+
+    fn skip_cell(&mut self) -> Option<CellId> {
+        self.pending.pop_front()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.kernel_ready && self.in_flight.is_none() && self.pending.is_empty()
+    }
+}
+
 impl NotebookEditor {
     pub fn new(
         project: Entity<Project>,
@@ -243,11 +307,11 @@ impl NotebookEditor {
             cell_order: cell_order.clone(),
             original_cell_order: cell_order.clone(),
             cell_map: cell_map.clone(),
-            kernel: Kernel::Shutdown, // TODO: use recommended kernel after the implementation is done in repl
-            kernel_specification: None,
-            execution_requests: HashMap::default(),
+            kernels: NotebookKernels::default(), // TODO: use recommended kernel after the implementation is done in repl
+            run_all_queue: None,
             kernel_picker_handle: PopoverMenuHandle::default(),
         };
+        KernelSessions::register(cx);
         editor.launch_kernel(window, cx);
         editor.refresh_language(cx);
 
@@ -261,24 +325,68 @@ impl NotebookEditor {
 
     fn refresh_language(&mut self, cx: &mut Context<Self>) {
         let notebook_language = self.notebook_item.read(cx).notebook_language();
+        let languages = self.languages.clone();
         let task = cx.spawn(async move |this, cx| {
             let language = notebook_language.await;
             if let Some(this) = this.upgrade() {
-                this.update(cx, |this, cx| {
-                    for cell in this.cell_map.values() {
-                        if let Cell::Code(code_cell) = cell {
-                            code_cell.update(cx, |cell, cx| {
-                                cell.set_language(language.clone(), cx);
-                            });
-                        }
-                    }
+                let code_cells = this.update(cx, |this, cx| {
+                    this.cell_map
+                        .values()
+                        .filter_map(|cell| match cell {
+                            Cell::Code(code_cell) => {
+                                Some((code_cell.clone(), code_cell.read(cx).kernel_language()))
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
                 });
+                for (code_cell, kernel_language) in code_cells {
+                    // Cells running on another language's kernel are highlighted as that language.
+                    let cell_language = match kernel_language {
+                        Some(kernel_language) => {
+                            languages.language_for_name(&kernel_language).await.ok()
+                        }
+                        None => language.clone(),
+                    };
+                    code_cell.update(cx, |cell, cx| {
+                        cell.set_language(cell_language, cx);
+                    });
+                }
             }
             language
         });
         self.notebook_language = task.shared();
     }
 
+    /// Runs a code cell on the kernel for `language`, or on the primary kernel when it's `None`.
+    fn set_cell_kernel_language(
+        &mut self,
+        cell_id: &CellId,
+        language: Option<SharedString>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(Cell::Code(code_cell)) = self.cell_map.get(cell_id) else {
+            return;
+        };
+        let code_cell = code_cell.clone();
+        code_cell.update(cx, |cell, cx| {
+            cell.set_kernel_language(language.clone());
+            cx.notify();
+        });
+
+        let languages = self.languages.clone();
+        let notebook_language = self.notebook_language.clone();
+        cx.spawn(async move |_, cx| {
+            let cell_language = match language {
+                Some(language) => languages.language_for_name(&language).await.ok(),
+                None => notebook_language.await,
+            };
+            code_cell.update(cx, |cell, cx| cell.set_language(cell_language, cx));
+        })
+        .detach();
+        cx.notify();
+    }
+
     fn has_structural_changes(&self) -> bool {
         self.cell_order != self.original_cell_order
     }
@@ -315,6 +423,7 @@ impl NotebookEditor {
             match cell {
                 Cell::Code(code_cell) => {
                     code_cell.update(cx, |code_cell, cx| {
+                        code_cell.mark_metadata_saved();
                         let editor = code_cell.editor();
                         editor.update(cx, |editor, cx| {
                             editor.buffer().update(cx, |buffer, cx| {
@@ -351,37 +460,44 @@ impl NotebookEditor {
 
     fn launch_kernel(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         // use default Python kernel if no specification is set
-        let spec = self.kernel_specification.clone().unwrap_or_else(|| {
-            KernelSpecification::Jupyter(LocalKernelSpecification {
-                name: "python3".to_string(),
-                path: PathBuf::from("python3"),
-                kernelspec: JupyterKernelspec {
-                    argv: vec![
-                        "python3".to_string(),
-                        "-m".to_string(),
-                        "ipykernel_launcher".to_string(),
-                        "-f".to_string(),
-                        "{connection_file}".to_string(),
-                    ],
-                    display_name: "Python 3".to_string(),
-                    language: "python".to_string(),
-                    interrupt_mode: None,
-                    metadata: None,
-                    env: None,
-                },
-            })
-        });
+        let spec = self
+            .kernels
+            .primary()
+            .map(|kernel| kernel.specification.clone())
+            .unwrap_or_else(|| {
+                KernelSpecification::Jupyter(LocalKernelSpecification {
+                    name: "python3".to_string(),
+                    path: PathBuf::from("python3"),
+                    kernelspec: JupyterKernelspec {
+                        argv: vec![
+                            "python3".to_string(),
+                            "-m".to_string(),
+                            "ipykernel_launcher".to_string(),
+                            "-f".to_string(),
+                            "{connection_file}".to_string(),
+                        ],
+                        display_name: "Python 3".to_string(),
+                        language: "python".to_string(),
+                        interrupt_mode: None,
+                        metadata: None,
+                        env: None,
+                    },
+                    source: LocalKernelSource::Kernelspec,
+                })
+            });
 
-        self.launch_kernel_with_spec(spec, window, cx);
+        self.launch_kernel_with_spec(spec, true, window, cx);
     }
 
+    /// Starts a kernel for `spec`'s language, replacing the one running it. The primary kernel
+    /// also becomes the notebook's kernelspec.
     fn launch_kernel_with_spec(
         &mut self,
         spec: KernelSpecification,
+        primary: bool,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let entity_id = cx.entity_id();
         let working_directory = self
             .project
             .read(cx)
@@ -390,33 +506,49 @@ impl NotebookEditor {
             .map(|worktree| worktree.read(cx).abs_path().to_path_buf())
             .unwrap_or_else(std::env::temp_dir);
         let fs = self.project.read(cx).fs().clone();
-        let view = cx.entity();
-
-        self.kernel_specification = Some(spec.clone());
-
-        self.notebook_item.update(cx, |item, cx| {
-            let kernel_name = spec.name().to_string();
-            let language = spec.language().to_string();
+        // A kernel for the primary kernel's language takes its place.
+        let primary = primary
+            || self
+                .kernels
+                .primary()
+                .is_some_and(|kernel| kernel.language == kernel_language(&spec.language()));
+
+        if primary {
+            self.notebook_item.update(cx, |item, cx| {
+                let kernel_name = spec.name().to_string();
+                let language = spec.language().to_string();
+
+                let display_name = match &spec {
+                    KernelSpecification::Jupyter(s) => s.kernelspec.display_name.clone(),
+                    KernelSpecification::PythonEnv(s) => s.kernelspec.display_name.clone(),
+                    KernelSpecification::JupyterServer(s) => s.kernelspec.display_name.clone(),
+                    KernelSpecification::SshRemote(s) => s.kernelspec.display_name.clone(),
+                    KernelSpecification::WslRemote(s) => s.kernelspec.display_name.clone(),
+                };
 
-            let display_name = match &spec {
-                KernelSpecification::Jupyter(s) => s.kernelspec.display_name.clone(),
-                KernelSpecification::PythonEnv(s) => s.kernelspec.display_name.clone(),
-                KernelSpecification::JupyterServer(s) => s.kernelspec.display_name.clone(),
-                KernelSpecification::SshRemote(s) => s.kernelspec.display_name.clone(),
-                KernelSpecification::WslRemote(s) => s.kernelspec.display_name.clone(),
-            };
+                let kernelspec_json = serde_json::json!({
+                    "display_name": display_name,
+                    "name": kernel_name,
+                    "language": language
+                });
 
-            let kernelspec_json = serde_json::json!({
-                "display_name": display_name,
-                "name": kernel_name,
-                "language": language
+                if let Ok(k) = serde_json::from_value(kernelspec_json) {
+                    item.notebook.metadata.kernelspec = Some(k);
+                    cx.emit(());
+                }
             });
+        }
 
-            if let Ok(k) = serde_json::from_value(kernelspec_json) {
-                item.notebook.metadata.kernelspec = Some(k);
-                cx.emit(());
-            }
-        });
+        let (kernel_id, replaced) = self.kernels.insert(spec.clone(), primary);
+        for kernel in replaced {
+            self.drop_kernel(kernel, window, cx);
+        }
+
+        // Each kernel reports to its own router, which also gives it its own connection file.
+        let notebook = cx.weak_entity();
+        let router = cx.new(|_| KernelRouter::new(notebook, kernel_id));
+        let entity_id = router.entity_id();
+        let view = router.clone();
 
         let kernel_task = match spec {
             KernelSpecification::Jupyter(local_spec) => NativeRunningKernel::new(
@@ -428,8 +560,8 @@ impl NotebookEditor {
                 window,
                 cx,
             ),
-            KernelSpecification::PythonEnv(env_spec) => NativeRunningKernel::new(
-                env_spec.as_local_spec(),
+            KernelSpecification::PythonEnv(env_spec) => NativeRunningKernel::new_for_python_env(
+                env_spec,
                 entity_id,
                 working_directory,
                 fs,
@@ -453,31 +585,74 @@ impl NotebookEditor {
         let pending_kernel = cx
             .spawn(async move |this, cx| {
                 let kernel = kernel_task.await;
-
-                match kernel {
-                    Ok(kernel) => {
-                        this.update(cx, |editor, cx| {
-                            editor.kernel = Kernel::RunningKernel(kernel);
-                            cx.notify();
-                        })
-                        .ok();
-                    }
-                    Err(err) => {
-                        log::error!("Kernel failed to start: {:?}", err);
-                        this.update(cx, |editor, cx| {
-                            editor.kernel = Kernel::ErroredLaunch(err.to_string());
-                            cx.notify();
-                        })
-                        .ok();
-                    }
-                }
+                this.update(cx, |editor, cx| {
+                    editor.kernel_launched(kernel_id, kernel, cx)
+                })
+                .ok();
             })
             .shared();
 
-        self.kernel = Kernel::StartingKernel(pending_kernel);
+        if let Some(kernel) = self.kernels.by_id_mut(kernel_id) {
+            kernel.kernel = Kernel::StartingKernel(pending_kernel);
+            kernel.set_router(router);
+        }
         cx.notify();
     }
 
+    fn kernel_launched(
+        &mut self,
+        kernel_id: KernelId,
+        kernel: Result<Box<dyn RunningKernel>>,
+        cx: &mut Context<Self>,
+    ) {
+        // The kernel was replaced or shut down while it was starting.
+        let Some(entry) = self
+            .kernels
+            .by_id_mut(kernel_id)
+            .filter(|entry| matches!(entry.kernel, Kernel::StartingKernel(_)))
+        else {
+            if let Ok(mut kernel) = kernel {
+                kernel.kill();
+            }
+            return;
+        };
+
+        match kernel {
+            Ok(kernel) => {
+                let message: JupyterMessage = KernelInfoRequest {}.into();
+                kernel.request_tx().try_send(message).log_err();
+                entry.kernel = Kernel::RunningKernel(kernel);
+            }
+            Err(err) => {
+                log::error!("Kernel failed to start: {:?}", err);
+                entry.kernel = Kernel::ErroredLaunch(err.to_string());
+                self.run_all_queue = None;
+            }
+        }
+        cx.notify();
+    }
+
+    /// Shuts down a kernel that was taken out of the notebook, and stops waiting on the cells
+    /// it was running.
+    fn drop_kernel(&mut self, kernel: NotebookKernel, window: &mut Window, cx: &mut Context<Self>) {
+        self.finish_executions(kernel.id, cx);
+        if let Kernel::RunningKernel(running_kernel) = kernel.kernel {
+            shut_down_running_kernel(running_kernel, kernel.specification.name(), window, cx)
+                .detach();
+        }
+    }
+
+    fn finish_executions(&mut self, kernel_id: KernelId, cx: &mut Context<Self>) {
+        for cell_id in self.kernels.forget_executions(kernel_id) {
+            if let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id) {
+                cell.update(cx, |cell, cx| {
+                    cell.finish_execution();
+                    cx.notify();
+                });
+            }
+        }
+    }
+
     // Note: Python environments are only detected as kernels if ipykernel is installed.
     // Users need to run `pip install ipykernel` (or `uv pip install ipykernel`) in their
     // virtual environment for it to appear in the kernel selector.
@@ -489,25 +664,264 @@ impl NotebookEditor {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if let Kernel::RunningKernel(kernel) = &mut self.kernel {
-            kernel.force_shutdown(window, cx).detach();
+        self.run_all_queue = None;
+        self.launch_kernel_with_spec(spec, true, window, cx);
+    }
+
+    /// Starts a kernel next to the primary one, for cells in another language.
+    fn start_language_kernel(
+        &mut self,
+        spec: KernelSpecification,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.launch_kernel_with_spec(spec, false, window, cx);
+    }
+
+    fn restart_kernel(&mut self, _: &RestartKernel, window: &mut Window, cx: &mut Context<Self>) {
+        if self.kernels.primary().is_none() {
+            return;
+        }
+        self.run_all_queue = None;
+        self.forget_previous_outputs(cx);
+        let kernel_ids = self
+            .kernels
+            .iter()
+            .map(|kernel| kernel.id)
+            .collect::<Vec<_>>();
+        for kernel_id in kernel_ids {
+            self.restart_kernel_with_id(kernel_id, window, cx);
         }
+        cx.notify();
+    }
 
-        self.execution_requests.clear();
+    /// Drops every cell's previous outputs, which aren't worth comparing with once the kernel
+    /// that produced them is gone.
+    fn forget_previous_outputs(&mut self, cx: &mut Context<Self>) {
+        for cell in self.cell_map.values() {
+            if let Cell::Code(code_cell) = cell {
+                code_cell.update(cx, |cell, cx| {
+                    cell.forget_previous_outputs();
+                    cx.notify();
+                });
+            }
+        }
+    }
 
-        self.launch_kernel_with_spec(spec, window, cx);
+    /// Restarts a kernel in place if it can, and relaunches it from its specification if not.
+    fn restart_kernel_with_id(
+        &mut self,
+        kernel_id: KernelId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        // Whichever way the kernel comes back, it won't answer what was sent to it before.
+        self.finish_executions(kernel_id, cx);
+        let primary = self.kernels.is_primary(kernel_id);
+        let Some(entry) = self.kernels.by_id_mut(kernel_id) else {
+            return;
+        };
+        match std::mem::replace(&mut entry.kernel, Kernel::Restarting(RestartKind::InPlace)) {
+            Kernel::RunningKernel(kernel) => {
+                let restart = restart_in_place(kernel, window, cx);
+                cx.spawn_in(window, async move |this, cx| {
+                    let outcome = restart.await;
+                    this.update_in(cx, |editor, window, cx| {
+                        editor.kernel_restarted(kernel_id, outcome, window, cx);
+                    })
+                    .ok();
+                })
+                .detach();
+            }
+            Kernel::Restarting(kind) => entry.kernel = Kernel::Restarting(kind),
+            _ => {
+                let spec = entry.specification.clone();
+                self.launch_kernel_with_spec(spec, primary, window, cx);
+            }
+        }
     }
 
-    fn restart_kernel(&mut self, _: &RestartKernel, window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(spec) = self.kernel_specification.clone() {
-            if let Kernel::RunningKernel(kernel) = &mut self.kernel {
-                kernel.force_shutdown(window, cx).detach();
+    fn kernel_restarted(
+        &mut self,
+        kernel_id: KernelId,
+        outcome: RestartOutcome,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let primary = self.kernels.is_primary(kernel_id);
+        // The kernel was replaced or shut down while it was restarting.
+        let Some(entry) = self
+            .kernels
+            .by_id_mut(kernel_id)
+            .filter(|entry| matches!(entry.kernel, Kernel::Restarting(_)))
+        else {
+            let (RestartOutcome::Restarted(mut kernel) | RestartOutcome::NeedsRelaunch(mut kernel)) =
+                outcome;
+            kernel.kill();
+            return;
+        };
+
+        match outcome {
+            RestartOutcome::Restarted(kernel) => {
+                let message: JupyterMessage = KernelInfoRequest {}.into();
+                kernel.request_tx().try_send(message).log_err();
+                entry.kernel = Kernel::RunningKernel(kernel);
+            }
+            RestartOutcome::NeedsRelaunch(mut kernel) => {
+                entry.kernel = Kernel::Restarting(RestartKind::Relaunch);
+                let spec = entry.specification.clone();
+                let forced = kernel.force_shutdown(window, cx);
+                cx.spawn_in(window, async move |this, cx| {
+                    forced.await.log_err();
+                    this.update_in(cx, |editor, window, cx| {
+                        // Unless the kernel was shut down or taken out of the notebook meanwhile.
+                        let still_restarting = editor
+                            .kernels
+                            .by_id(kernel_id)
+                            .is_some_and(|entry| matches!(entry.kernel, Kernel::Restarting(_)));
+                        if still_restarting {
+                            editor.launch_kernel_with_spec(spec, primary, window, cx);
+                        }
+                    })
+                    .ok();
+                })
+                .detach();
             }
+        }
+        cx.notify();
+    }
 
-            self.kernel = Kernel::Restarting;
+    fn shutdown_kernel(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.run_all_queue = None;
+        let kernel_ids = self
+            .kernels
+            .iter()
+            .map(|kernel| kernel.id)
+            .collect::<Vec<_>>();
+        for kernel_id in kernel_ids {
+            self.shut_down_kernel(kernel_id, window, cx);
+        }
+        cx.notify();
+    }
+
+    /// Shuts a kernel down, keeping it in the notebook so that it can be restarted.
+    fn shut_down_kernel(
+        &mut self,
+        kernel_id: KernelId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.finish_executions(kernel_id, cx);
+        let Some(entry) = self.kernels.by_id_mut(kernel_id) else {
+            return;
+        };
+        let kernel_name = entry.specification.name();
+        match std::mem::replace(&mut entry.kernel, Kernel::ShuttingDown) {
+            Kernel::RunningKernel(kernel) => {
+                let shutdown = shut_down_running_kernel(kernel, kernel_name, window, cx);
+                cx.spawn(async move |this, cx| {
+                    shutdown.await;
+                    this.update(cx, |editor, cx| {
+                        if let Some(entry) = editor.kernels.by_id_mut(kernel_id)
+                            && matches!(entry.kernel, Kernel::ShuttingDown)
+                        {
+                            entry.kernel = Kernel::Shutdown;
+                            cx.notify();
+                        }
+                    })
+                    .ok();
+                })
+                .detach();
+            }
+            _ => entry.kernel = Kernel::Shutdown,
+        }
+    }
+
+    /// Shuts down a kernel started next to the primary one, and takes it out of the notebook.
+    fn remove_language_kernel(
+        &mut self,
+        kernel_id: KernelId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(kernel) = self.kernels.remove(kernel_id) {
+            self.drop_kernel(kernel, window, cx);
             cx.notify();
+        }
+    }
+
+    fn restart_and_run_all(
+        &mut self,
+        _: &RestartAndRunAll,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.kernels.primary().is_none() {
+            return;
+        }
+        if self
+            .kernels
+            .iter()
+            .any(|kernel| matches!(kernel.kernel, Kernel::Restarting(_)))
+        {
+            return;
+        }
+
+        let settings = ReplSettings::get_global(cx);
+        let clear_outputs = settings.clear_outputs_on_restart;
+        let continue_on_error = settings.continue_on_error;
+
+        let mut code_cells = Vec::new();
+        for cell_id in &self.cell_order {
+            if let Some(Cell::Code(cell)) = self.cell_map.get(cell_id) {
+                cell.update(cx, |cell, cx| {
+                    cell.reset_execution_count();
+                    cell.forget_previous_outputs();
+                    if clear_outputs {
+                        cell.clear_outputs();
+                    } else {
+                        cell.mark_outputs_stale();
+                    }
+                    cx.notify();
+                });
+                code_cells.push(cell_id.clone());
+            }
+        }
+
+        self.kernels.clear_executions();
+        self.run_all_queue = Some(RunAllQueue::new(code_cells, continue_on_error));
+
+        // Every kernel restarts, and the replay waits for all of them.
+        let kernel_ids = self
+            .kernels
+            .iter()
+            .map(|kernel| kernel.id)
+            .collect::<Vec<_>>();
+        for kernel_id in kernel_ids {
+            self.restart_kernel_with_id(kernel_id, window, cx);
+        }
+        cx.notify();
+    }
+
+    /// Sends the next replayable cell, skipping cells that were removed since the replay started.
+    fn run_queued_cells(&mut self, mut next_cell: Option<CellId>, cx: &mut Context<Self>) {
+        while let Some(cell_id) = next_cell {
+            if let Some(msg_id) = self.execute_cell(cell_id, cx) {
+                if let Some(queue) = self.run_all_queue.as_mut() {
+                    queue.cell_sent(msg_id);
+                }
+                return;
+            }
+            next_cell = self.run_all_queue.as_mut().and_then(RunAllQueue::skip_cell);
+        }
 
-            self.launch_kernel_with_spec(spec, window, cx);
+        if self
+            .run_all_queue
+            .as_ref()
+            .is_some_and(RunAllQueue::is_finished)
+        {
+            self.run_all_queue = None;
+            cx.notify();
         }
     }
 
@@ -517,35 +931,33 @@ impl NotebookEditor {
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if let Kernel::RunningKernel(kernel) = &self.kernel {
-            let interrupt_request = runtimelib::InterruptRequest {};
-            let message: JupyterMessage = interrupt_request.into();
-            kernel.request_tx().try_send(message).ok();
-            cx.notify();
-        }
+        // Cancelling a replay drops the remaining cells, while the in-flight one still
+        // receives its reply and finishes normally.
+        self.run_all_queue = None;
+        self.kernels.interrupt_active();
+        cx.notify();
+    }
+
+    fn interrupt_language_kernel(&mut self, kernel_id: KernelId, cx: &mut Context<Self>) {
+        self.run_all_queue = None;
+        self.kernels.interrupt(kernel_id).log_err();
+        cx.notify();
     }
 
-    fn execute_cell(&mut self, cell_id: CellId, cx: &mut Context<Self>) {
-        let code = if let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id) {
+    fn execute_cell(&mut self, cell_id: CellId, cx: &mut Context<Self>) -> Option<String> {
+        let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id) else {
+            return None;
+        };
+        let cell = cell.clone();
+        let code = {
             let editor = cell.read(cx).editor().clone();
             let buffer = editor.read(cx).buffer().read(cx);
             buffer
                 .as_singleton()
                 .map(|b| b.read(cx).text())
                 .unwrap_or_default()
-        } else {
-            return;
         };
-
-        if let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id) {
-            cell.update(cx, |cell, cx| {
-                if cell.has_outputs() {
-                    cell.clear_outputs();
-                }
-                cell.start_execution();
-                cx.notify();
-            });
-        }
+        let cell_language = cell.read(cx).kernel_language();
 
         let request = ExecuteRequest {
             code,
@@ -554,11 +966,16 @@ impl NotebookEditor {
         let message: JupyterMessage = request.into();
         let msg_id = message.header.msg_id.clone();
 
-        self.execution_requests.insert(msg_id, cell_id.clone());
+        self.kernels
+            .execute(cell_id, cell_language.as_deref(), message)
+            .log_err()?;
 
-        if let Kernel::RunningKernel(kernel) = &mut self.kernel {
-            kernel.request_tx().try_send(message).ok();
-        }
+        cell.update(cx, |cell, cx| {
+            cell.rotate_outputs(cx);
+            cell.start_execution(cx);
+            cx.notify();
+        });
+        Some(msg_id)
     }
 
     fn has_outputs(&self, window: &mut Window, cx: &mut Context<Self>) -> bool {
@@ -1019,7 +1436,7 @@ impl NotebookEditor {
                             .tooltip(move |window, cx| (Tooltip::text("More options"))(window, cx)),
                     )
                     .child(Self::button_group(window, cx).child({
-                        let kernel_status = self.kernel.status();
+                        let kernel_status = self.kernels.status();
                         let (icon, icon_color) = match &kernel_status {
                             KernelStatus::Idle => (IconName::ReplNeutral, Color::Success),
                             KernelStatus::Busy => (IconName::ReplNeutral, Color::Warning),
@@ -1028,21 +1445,40 @@ impl NotebookEditor {
                             KernelStatus::ShuttingDown => (IconName::ReplNeutral, Color::Muted),
                             KernelStatus::Shutdown => (IconName::ReplNeutral, Color::Disabled),
                             KernelStatus::Restarting => (IconName::ReplNeutral, Color::Warning),
+                            KernelStatus::Unresponsive => (IconName::ReplNeutral, Color::Warning),
+                        };
+                        let tooltip = if self.kernels.secondary().next().is_some() {
+                            let kernels = self
+                                .kernels
+                                .iter()
+                                .map(|kernel| {
+                                    format!(
+                                        "{} ({})",
+                                        kernel.specification.name(),
+                                        kernel.kernel.status_label()
+                                    )
+                                })
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            format!("{kernels}. Click to change the primary kernel.")
+                        } else {
+                            let (kernel_name, status_label) = self
+                                .kernels
+                                .primary()
+                                .map(|kernel| {
+                                    (
+                                        kernel.specification.name().to_string(),
+                                        kernel.kernel.status_label(),
+                                    )
+                                })
+                                .unwrap_or_else(|| {
+                                    ("Select Kernel".to_string(), kernel_status.to_string())
+                                });
+                            format!("{kernel_name} ({status_label}). Click to change kernel.")
                         };
-                        let kernel_name = self
-                            .kernel_specification
-                            .as_ref()
-                            .map(|spec| spec.name().to_string())
-                            .unwrap_or_else(|| "Select Kernel".to_string());
                         IconButton::new("repl", icon)
                             .icon_color(icon_color)
-                            .tooltip(move |window, cx| {
-                                Tooltip::text(format!(
-                                    "{} ({}). Click to change kernel.",
-                                    kernel_name,
-                                    kernel_status.to_string()
-                                ))(window, cx)
-                            })
+                            .tooltip(Tooltip::text(tooltip))
                             .on_click(cx.listener(|this, _, window, cx| {
                                 this.kernel_picker_handle.toggle(window, cx);
                             }))
@@ -1055,43 +1491,17 @@ impl NotebookEditor {
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) -> impl IntoElement {
-        let kernel_status = self.kernel.status();
+        let kernel_status = self
+            .kernels
+            .primary()
+            .map(NotebookKernel::status)
+            .unwrap_or(KernelStatus::Shutdown);
         let kernel_name = self
-            .kernel_specification
-            .as_ref()
-            .map(|spec| spec.name().to_string())
+            .kernels
+            .primary()
+            .map(|kernel| kernel.specification.name().to_string())
             .unwrap_or_else(|| "Select Kernel".to_string());
-
-        let (status_icon, status_color) = match &kernel_status {
-            KernelStatus::Idle => (IconName::Circle, Color::Success),
-            KernelStatus::Busy => (IconName::ArrowCircle, Color::Warning),
-            KernelStatus::Starting => (IconName::ArrowCircle, Color::Muted),
-            KernelStatus::Error => (IconName::XCircle, Color::Error),
-            KernelStatus::ShuttingDown => (IconName::ArrowCircle, Color::Muted),
-            KernelStatus::Shutdown => (IconName::Circle, Color::Muted),
-            KernelStatus::Restarting => (IconName::ArrowCircle, Color::Warning),
-        };
-
-        let is_spinning = matches!(
-            kernel_status,
-            KernelStatus::Busy
-                | KernelStatus::Starting
-                | KernelStatus::ShuttingDown
-                | KernelStatus::Restarting
-        );
-
-        let status_icon_element = if is_spinning {
-            Icon::new(status_icon)
-                .size(IconSize::Small)
-                .color(status_color)
-                .with_rotate_animation(2)
-                .into_any_element()
-        } else {
-            Icon::new(status_icon)
-                .size(IconSize::Small)
-                .color(status_color)
-                .into_any_element()
-        };
+        let (status_icon, status_color) = status_icon(&kernel_status);
 
         let worktree_id = self.worktree_id;
         let kernel_picker_handle = self.kernel_picker_handle.clone();
@@ -1106,28 +1516,58 @@ impl NotebookEditor {
             .justify_between()
             .bg(cx.theme().colors().status_bar_background)
             .child(
-                KernelSelector::new(
-                    Box::new(move |spec: KernelSpecification, window, cx| {
-                        if let Some(view) = view.upgrade() {
-                            view.update(cx, |this, cx| {
-                                this.change_kernel(spec, window, cx);
-                            });
-                        }
-                    }),
-                    worktree_id,
-                    Button::new("kernel-selector", kernel_name.clone())
-                        .label_size(LabelSize::Small)
-                        .icon(status_icon)
-                        .icon_size(IconSize::Small)
-                        .icon_color(status_color)
-                        .icon_position(IconPosition::Start),
-                    Tooltip::text(format!(
-                        "Kernel: {} ({}). Click to change.",
-                        kernel_name,
-                        kernel_status.to_string()
-                    )),
-                )
-                .with_handle(kernel_picker_handle),
+                h_flex()
+                    .gap_2()
+                    .child(
+                        KernelSelector::new(
+                            Box::new(move |spec: KernelSpecification, window, cx| {
+                                let change_kernel = {
+                                    let view = view.clone();
+                                    move |spec: KernelSpecification,
+                                          window: &mut Window,
+                                          cx: &mut App| {
+                                        if let Some(view) = view.upgrade() {
+                                            view.update(cx, |this, cx| {
+                                                this.change_kernel(spec, window, cx);
+                                            });
+                                        }
+                                    }
+                                };
+                                match spec {
+                                    KernelSpecification::PythonEnv(env_spec)
+                                        if !env_spec.has_ipykernel =>
+                                    {
+                                        offer_ipykernel_install(
+                                            env_spec,
+                                            change_kernel,
+                                            window,
+                                            cx,
+                                        );
+                                    }
+                                    spec => change_kernel(spec, window, cx),
+                                }
+                            }),
+                            worktree_id,
+                            Button::new("kernel-selector", kernel_name.clone())
+                                .label_size(LabelSize::Small)
+                                .icon(status_icon)
+                                .icon_size(IconSize::Small)
+                                .icon_color(status_color)
+                                .icon_position(IconPosition::Start),
+                            Tooltip::text(format!(
+                                "Kernel: {} ({}). Click to change.",
+                                kernel_name,
+                                kernel_status.to_string()
+                            )),
+                        )
+                        .with_handle(kernel_picker_handle),
+                    )
+                    .children(
+                        self.kernels
+                            .secondary()
+                            .map(|kernel| self.render_language_kernel(kernel, cx)),
+                    )
+                    .children(self.render_cell_kernel_controls(cx)),
             )
             .child(
                 h_flex()
@@ -1135,27 +1575,237 @@ impl NotebookEditor {
                     .child(
                         IconButton::new("restart-kernel", IconName::RotateCw)
                             .icon_size(IconSize::Small)
+                            .disabled(!self.can_run(KernelCommand::Restart))
                             .tooltip(|window, cx| {
                                 Tooltip::for_action("Restart Kernel", &RestartKernel, cx)
                             })
+                            .on_click({
+                                let notebook = cx.weak_entity();
+                                move |_, window, cx| {
+                                    run_kernel_command(
+                                        &notebook,
+                                        KernelCommand::Restart,
+                                        window,
+                                        cx,
+                                    );
+                                }
+                            }),
+                    )
+                    .child(
+                        IconButton::new("restart-and-run-all", IconName::Rerun)
+                            .icon_size(IconSize::Small)
+                            .tooltip(|window, cx| {
+                                Tooltip::for_action(
+                                    "Restart Kernel and Run All",
+                                    &RestartAndRunAll,
+                                    cx,
+                                )
+                            })
                             .on_click(cx.listener(|this, _, window, cx| {
-                                this.restart_kernel(&RestartKernel, window, cx);
+                                this.restart_and_run_all(&RestartAndRunAll, window, cx);
                             })),
                     )
                     .child(
                         IconButton::new("interrupt-kernel", IconName::Stop)
                             .icon_size(IconSize::Small)
-                            .disabled(!matches!(kernel_status, KernelStatus::Busy))
+                            .disabled(!self.can_run(KernelCommand::Interrupt))
                             .tooltip(|window, cx| {
                                 Tooltip::for_action("Interrupt Kernel", &InterruptKernel, cx)
                             })
-                            .on_click(cx.listener(|this, _, window, cx| {
-                                this.interrupt_kernel(&InterruptKernel, window, cx);
-                            })),
+                            .on_click({
+                                let notebook = cx.weak_entity();
+                                move |_, window, cx| {
+                                    run_kernel_command(
+                                        &notebook,
+                                        KernelCommand::Interrupt,
+                                        window,
+                                        cx,
+                                    );
+                                }
+                            }),
                     ),
             )
     }
 
+    /// A kernel started next to the primary one, which can be interrupted or shut down on its
+    /// own.
+    fn render_language_kernel(
+        &self,
+        kernel: &NotebookKernel,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let kernel_id = kernel.id;
+        let kernel_status = kernel.status();
+        let kernel_name = kernel.specification.name();
+        let element_id = |name: &str| SharedString::from(format!("{name}-{}", kernel.language));
+
+        h_flex()
+            .id(element_id("language-kernel"))
+            .gap_1()
+            .child(render_status_icon(&kernel_status))
+            .child(Label::new(kernel_name.clone()).size(LabelSize::Small))
+            .tooltip(Tooltip::text(format!(
+                "{kernel_name} ({}), running {} cells",
+                kernel_status.to_string(),
+                kernel.language
+            )))
+            .child(
+                IconButton::new(element_id("interrupt-kernel"), IconName::Stop)
+                    .icon_size(IconSize::XSmall)
+                    .disabled(!matches!(kernel.kernel, Kernel::RunningKernel(_)))
+                    .tooltip(Tooltip::text(format!("Interrupt {kernel_name}")))
+                    .on_click(cx.listener(move |this, _, _window, cx| {
+                        this.interrupt_language_kernel(kernel_id, cx);
+                    })),
+            )
+            .child(
+                IconButton::new(element_id("shut-down-kernel"), IconName::Close)
+                    .icon_size(IconSize::XSmall)
+                    .tooltip(Tooltip::text(format!("Shut Down {kernel_name}")))
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.remove_language_kernel(kernel_id, window, cx);
+                    })),
+            )
+    }
+
+    /// Which kernel the selected code cell runs on, and a picker to start one for its language
+    /// when none is running.
+    fn render_cell_kernel_controls(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let cell_id = self.cell_order.get(self.selected_cell_index)?.clone();
+        let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id) else {
+            return None;
+        };
+        let cell_language = cell.read(cx).kernel_language();
+
+        let primary_language = self.kernels.primary().map(|kernel| kernel.language.clone());
+        let mut languages = self
+            .kernels
+            .iter()
+            .map(|kernel| kernel.language.clone())
+            .chain(
+                ReplStore::global(cx)
+                    .read(cx)
+                    .kernel_specifications_for_worktree(self.worktree_id)
+                    .map(|spec| kernel_language(&spec.language())),
+            )
+            .filter(|language| Some(language) != primary_language.as_ref())
+            .collect::<Vec<_>>();
+        languages.sort();
+        languages.dedup();
+
+        let notebook = cx.weak_entity();
+        let menu = PopoverMenu::new("cell-kernel-menu")
+            .menu({
+                let cell_id = cell_id.clone();
+                let cell_language = cell_language.clone();
+                move |window, cx| {
+                    let notebook = notebook.clone();
+                    let cell_id = cell_id.clone();
+                    let cell_language = cell_language.clone();
+                    let languages = languages.clone();
+                    Some(ContextMenu::build(window, cx, move |mut menu, _, _| {
+                        menu = menu.header("Run Cell On");
+                        let set_language = |language: Option<SharedString>| {
+                            let notebook = notebook.clone();
+                            let cell_id = cell_id.clone();
+                            move |_: &mut Window, cx: &mut App| {
+                                notebook
+                                    .update(cx, |notebook, cx| {
+                                        notebook.set_cell_kernel_language(
+                                            &cell_id,
+                                            language.clone(),
+                                            cx,
+                                        );
+                                    })
+                                    .ok();
+                            }
+                        };
+                        menu = menu.toggleable_entry(
+                            "Notebook Kernel",
+                            cell_language.is_none(),
+                            IconPosition::Start,
+                            None,
+                            set_language(None),
+                        );
+                        for language in languages {
+                            menu = menu.toggleable_entry(
+                                language.clone(),
+                                cell_language.as_ref() == Some(&language),
+                                IconPosition::Start,
+                                None,
+                                set_language(Some(language.clone())),
+                            );
+                        }
+                        menu
+                    }))
+                }
+            })
+            .trigger_with_tooltip(
+                Button::new(
+                    "cell-kernel",
+                    cell_language
+                        .clone()
+                        .unwrap_or_else(|| "Notebook Kernel".into()),
+                )
+                .label_size(LabelSize::Small)
+                .icon(IconName::ChevronDown)
+                .icon_size(IconSize::XSmall)
+                .icon_position(IconPosition::End),
+                Tooltip::text("The kernel the selected cell runs on"),
+            )
+            .attach(gpui::Corner::BottomLeft);
+
+        let missing_language =
+            cell_language.filter(|language| self.kernels.get(language).is_none());
+        let view = cx.weak_entity();
+        Some(
+            h_flex()
+                .gap_1()
+                .child(menu)
+                .when_some(missing_language, |this, language| {
+                    this.child(
+                        KernelSelector::new(
+                            Box::new(move |spec: KernelSpecification, window, cx| {
+                                let start_kernel = {
+                                    let view = view.clone();
+                                    move |spec: KernelSpecification,
+                                          window: &mut Window,
+                                          cx: &mut App| {
+                                        view.update(cx, |this, cx| {
+                                            this.start_language_kernel(spec, window, cx);
+                                        })
+                                        .ok();
+                                    }
+                                };
+                                match spec {
+                                    KernelSpecification::PythonEnv(env_spec)
+                                        if !env_spec.has_ipykernel =>
+                                    {
+                                        offer_ipykernel_install(env_spec, start_kernel, window, cx);
+                                    }
+                                    spec => start_kernel(spec, window, cx),
+                                }
+                            }),
+                            self.worktree_id,
+                            Button::new(
+                                "start-language-kernel",
+                                format!("Start {language} Kernel"),
+                            )
+                            .label_size(LabelSize::Small)
+                            .icon(IconName::PlayOutlined)
+                            .icon_size(IconSize::Small)
+                            .icon_position(IconPosition::Start),
+                            Tooltip::text(format!(
+                                "No {language} kernel is running for this cell. Click to start one."
+                            )),
+                        )
+                        .for_language(language),
+                    )
+                })
+                .into_any_element(),
+        )
+    }
+
     fn cell_list(&self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let view = cx.entity();
         list(self.cell_list.clone(), move |index, window, cx| {
@@ -1421,6 +2071,11 @@ impl Render for NotebookEditor {
             .on_action(
                 cx.listener(|this, action, window, cx| this.restart_kernel(action, window, cx)),
             )
+            .on_action(
+                cx.listener(|this, action, window, cx| {
+                    this.restart_and_run_all(action, window, cx)
+                }),
+            )
             .on_action(
                 cx.listener(|this, action, window, cx| this.interrupt_kernel(action, window, cx)),
             )
@@ -1839,20 +2494,82 @@ impl ProjectItem for NotebookEditor {
     }
 }
 
-impl KernelSession for NotebookEditor {
-    fn route(&mut self, message: &JupyterMessage, window: &mut Window, cx: &mut Context<Self>) {
+impl KernelOwner for NotebookEditor {
+    fn kernel_status(&self) -> KernelStatus {
+        self.kernels.status()
+    }
+
+    fn kernel_specification(&self) -> Option<&KernelSpecification> {
+        self.kernels.primary().map(|kernel| &kernel.specification)
+    }
+
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.kernels.primary()?.kernel.resource_usage()
+    }
+
+    fn file_name(&self, cx: &App) -> SharedString {
+        self.tab_content_text(0, cx)
+    }
+
+    fn request_interrupt(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.interrupt_kernel(&InterruptKernel, window, cx);
+    }
+
+    fn request_restart(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.restart_kernel(&RestartKernel, window, cx);
+    }
+
+    fn request_shutdown(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.shutdown_kernel(window, cx);
+    }
+
+    fn request_clear_outputs(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.clear_outputs(window, cx);
+    }
+
+    fn can_run(&self, command: KernelCommand) -> bool {
+        match command {
+            // Restarting relaunches the kernels from their specifications.
+            KernelCommand::Restart if self.kernels.primary().is_none() => false,
+            // Interrupting also cancels a "Run All" that's waiting on the kernels.
+            KernelCommand::Interrupt if self.run_all_queue.is_some() => true,
+            _ => command.is_enabled(&self.kernel_status()),
+        }
+    }
+}
+
+/// What the notebook does with the messages its kernels send through their [`KernelRouter`]s.
+impl NotebookEditor {
+    pub(crate) fn route_kernel_message(
+        &mut self,
+        kernel_id: KernelId,
+        message: &JupyterMessage,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        // Messages from a kernel that has since been replaced are dropped.
+        if self.kernels.by_id(kernel_id).is_none() {
+            return;
+        }
+
         // Handle kernel status updates (these are broadcast to all)
         if let JupyterMessageContent::Status(status) = &message.content {
-            self.kernel.set_execution_state(&status.execution_state);
+            self.kernels
+                .set_execution_state(kernel_id, &status.execution_state);
             cx.notify();
         }
 
         if let JupyterMessageContent::KernelInfoReply(reply) = &message.content {
-            self.kernel.set_kernel_info(reply);
+            if let Some(kernel) = self.kernels.by_id_mut(kernel_id) {
+                kernel.kernel.set_kernel_info(reply);
+            }
 
-            if let Ok(language_info) = serde_json::from_value::<nbformat::v4::LanguageInfo>(
-                serde_json::to_value(&reply.language_info).unwrap(),
-            ) {
+            // The notebook's language is the primary kernel's.
+            if self.kernels.is_primary(kernel_id)
+                && let Ok(language_info) = serde_json::from_value::<nbformat::v4::LanguageInfo>(
+                    serde_json::to_value(&reply.language_info).unwrap(),
+                )
+            {
                 self.notebook_item.update(cx, |item, cx| {
                     item.notebook.metadata.language_info = Some(language_info);
                     cx.emit(());
@@ -1862,19 +2579,257 @@ impl KernelSession for NotebookEditor {
         }
 
         // Handle cell-specific messages
-        if let Some(parent_header) = &message.parent_header {
-            if let Some(cell_id) = self.execution_requests.get(&parent_header.msg_id) {
-                if let Some(Cell::Code(cell)) = self.cell_map.get(cell_id) {
-                    cell.update(cx, |cell, cx| {
-                        cell.handle_message(message, window, cx);
-                    });
-                }
-            }
+        if let Some(parent_header) = &message.parent_header
+            && let Some(cell_id) = self
+                .kernels
+                .cell_for_message(kernel_id, &parent_header.msg_id)
+            && let Some(Cell::Code(cell)) = self.cell_map.get(cell_id)
+        {
+            cell.update(cx, |cell, cx| {
+                cell.handle_message(message, window, cx);
+            });
+        }
+
+        // A replay starts once the last of the kernels it restarted is up.
+        let kernels_ready = self
+            .kernels
+            .by_id(kernel_id)
+            .is_some_and(|kernel| matches!(kernel.kernel, Kernel::RunningKernel(_)))
+            && !self.kernels.is_starting();
+        if let Some(queue) = self.run_all_queue.as_mut() {
+            let next_cell = match &message.content {
+                JupyterMessageContent::KernelInfoReply(_) if kernels_ready => queue.kernel_ready(),
+                JupyterMessageContent::ExecuteReply(reply) => message
+                    .parent_header
+                    .as_ref()
+                    .and_then(|parent| queue.reply_received(&parent.msg_id, &reply.status)),
+                _ => None,
+            };
+            self.run_queued_cells(next_cell, cx);
         }
     }
 
-    fn kernel_errored(&mut self, error_message: String, cx: &mut Context<Self>) {
-        self.kernel = Kernel::ErroredLaunch(error_message);
+    pub(crate) fn kernel_errored(
+        &mut self,
+        kernel_id: KernelId,
+        error_message: String,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(kernel) = self.kernels.by_id_mut(kernel_id) else {
+            return;
+        };
+        // A kernel exiting because it was asked to, such as mid-restart, shouldn't cancel the
+        // replay that is waiting on its replacement.
+        if kernel.kernel.is_shutting_down() {
+            return;
+        }
+        if matches!(kernel.kernel, Kernel::RunningKernel(_)) {
+            self.run_all_queue = None;
+        }
+        kernel.kernel = Kernel::ErroredLaunch(error_message);
+        self.finish_executions(kernel_id, cx);
         cx.notify();
     }
+
+    pub(crate) fn requests_failed(
+        &mut self,
+        kernel_id: KernelId,
+        msg_ids: &[String],
+        error_message: String,
+        cx: &mut Context<Self>,
+    ) {
+        let kernel_name = self
+            .kernels
+            .by_id(kernel_id)
+            .map(|kernel| kernel.specification.name())
+            .unwrap_or_default();
+        log::error!("notebook: {kernel_name} kernel: {error_message}");
+        for msg_id in msg_ids {
+            if self
+                .run_all_queue
+                .as_ref()
+                .is_some_and(|queue| queue.in_flight.as_ref() == Some(msg_id))
+            {
+                self.run_all_queue = None;
+            }
+            if let Some(cell_id) = self.kernels.remove_execution(msg_id)
+                && let Some(Cell::Code(cell)) = self.cell_map.get(&cell_id)
+            {
+                cell.update(cx, |cell, cx| {
+                    cell.finish_execution();
+                    cx.notify();
+                });
+            }
+        }
+        cx.notify();
+    }
+}
+
+fn status_icon(status: &KernelStatus) -> (IconName, Color) {
+    match status {
+        KernelStatus::Idle => (IconName::Circle, Color::Success),
+        KernelStatus::Busy => (IconName::ArrowCircle, Color::Warning),
+        KernelStatus::Starting => (IconName::ArrowCircle, Color::Muted),
+        KernelStatus::Error => (IconName::XCircle, Color::Error),
+        KernelStatus::ShuttingDown => (IconName::ArrowCircle, Color::Muted),
+        KernelStatus::Shutdown => (IconName::Circle, Color::Muted),
+        KernelStatus::Restarting => (IconName::ArrowCircle, Color::Warning),
+        KernelStatus::Unresponsive => (IconName::Warning, Color::Warning),
+    }
+}
+
+fn render_status_icon(status: &KernelStatus) -> AnyElement {
+    let (icon, color) = status_icon(status);
+    let is_spinning = matches!(
+        status,
+        KernelStatus::Busy
+            | KernelStatus::Starting
+            | KernelStatus::ShuttingDown
+            | KernelStatus::Restarting
+    );
+
+    let icon = Icon::new(icon).size(IconSize::Small).color(color);
+    if is_spinning {
+        icon.with_rotate_animation(2).into_any_element()
+    } else {
+        icon.into_any_element()
+    }
+}
+
+/// Asks a kernel to shut down and makes sure it does, the same way a REPL session shuts down
+/// its kernel.
+fn shut_down_running_kernel(
+    mut kernel: Box<dyn RunningKernel>,
+    kernel_name: SharedString,
+    window: &mut Window,
+    cx: &mut App,
+) -> Task<()> {
+    let mut request_tx = kernel.request_tx();
+    let forced = kernel.force_shutdown(window, cx);
+    cx.spawn(async move |_| {
+        let message: JupyterMessage = ShutdownRequest { restart: false }.into();
+        request_tx.try_send(message).ok();
+
+        forced
+            .log_err_with_context(|| format!("shutting down the {kernel_name} kernel"))
+            .await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replies to execute requests in order, failing any cell whose code raises.
+    struct FakeKernel {
+        executed: Vec<String>,
+    }
+
+    impl FakeKernel {
+        fn handle_request(&mut self, request: &JupyterMessage) -> JupyterMessage {
+            let JupyterMessageContent::ExecuteRequest(execute_request) = &request.content else {
+                panic!("expected an execute request, got {:?}", request.content);
+            };
+            self.executed.push(execute_request.code.clone());
+
+            let status = if execute_request.code.starts_with("raise") {
+                ReplyStatus::Error
+            } else {
+                ReplyStatus::Ok
+            };
+            runtimelib::ExecuteReply {
+                status,
+                ..Default::default()
+            }
+            .as_child_of(request)
+        }
+    }
+
+    fn replay(cells: &[(CellId, &str)], continue_on_error: bool) -> Vec<String> {
+        let mut kernel = FakeKernel {
+            executed: Vec::new(),
+        };
+        let mut queue = RunAllQueue::new(
+            cells.iter().map(|(cell_id, _)| cell_id.clone()).collect(),
+            continue_on_error,
+        );
+        assert!(!queue.is_finished());
+
+        let mut next_cell = queue.kernel_ready();
+        assert_eq!(queue.kernel_ready(), None);
+
+        while let Some(cell_id) = next_cell {
+            let code = cells
+                .iter()
+                .find(|(id, _)| *id == cell_id)
+                .map(|(_, code)| code.to_string())
+                .unwrap_or_default();
+            let request: JupyterMessage = ExecuteRequest {
+                code,
+                ..Default::default()
+            }
+            .into();
+            queue.cell_sent(request.header.msg_id.clone());
+
+            let unrelated_reply =
+                runtimelib::ExecuteReply::default().as_child_of(&ExecuteRequest::default().into());
+            assert_eq!(
+                queue.reply_received(
+                    &unrelated_reply.parent_header.as_ref().unwrap().msg_id,
+                    &ReplyStatus::Ok
+                ),
+                None
+            );
+            assert!(!queue.is_finished());
+
+            let reply = kernel.handle_request(&request);
+            let JupyterMessageContent::ExecuteReply(execute_reply) = &reply.content else {
+                panic!("expected an execute reply");
+            };
+            next_cell = queue.reply_received(
+                &reply.parent_header.as_ref().unwrap().msg_id,
+                &execute_reply.status,
+            );
+        }
+
+        assert!(queue.is_finished());
+        kernel.executed
+    }
+
+    #[test]
+    fn test_run_all_queue_stops_at_erroring_cell() {
+        let cells = [
+            "a = 1",
+            "b = a + 1",
+            "raise ValueError()",
+            "c = b + 1",
+            "print(c)",
+        ]
+        .into_iter()
+        .map(|code| (CellId::from(Uuid::new_v4()), code))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            replay(&cells, false),
+            ["a = 1", "b = a + 1", "raise ValueError()"]
+        );
+        assert_eq!(
+            replay(&cells, true),
+            [
+                "a = 1",
+                "b = a + 1",
+                "raise ValueError()",
+                "c = b + 1",
+                "print(c)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_all_queue_waits_for_kernel_info() {
+        let mut queue = RunAllQueue::new(Vec::new(), false);
+        assert!(!queue.is_finished());
+        assert_eq!(queue.kernel_ready(), None);
+        assert!(queue.is_finished());
+    }
 }
@@ -0,0 +1,576 @@
+//! Comparing a code cell's outputs with the ones from its previous run, so that a re-run can
+//! show whether its output changed.
+
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+use std::sync::Arc;
+
+use futures::future::Shared;
+use gpui::{AnyElement, App, Task};
+use ui::prelude::*;
+
+use crate::outputs::Output;
+
+/// What's kept of a single output for comparing it with the next run.
+pub(crate) enum OutputSnapshot {
+    /// Stream output, which is diffed line by line.
+    Stream(SnapshotText),
+    /// The text of an execute result or display, which is only compared as a whole.
+    Plain(SnapshotText),
+    /// An error with its traceback, which is diffed like a stream.
+    Error(SnapshotText),
+    /// A hash of the image's data, resolved in the background.
+    Image(Shared<Task<u64>>),
+    /// Outputs like tables and markdown, which aren't compared.
+    Other,
+}
+
+pub(crate) enum SnapshotText {
+    Full(String),
+    /// Text past the size cap is only kept as a hash. It can still be compared, but not diffed
+    /// or shown.
+    Hashed(u64),
+}
+
+impl SnapshotText {
+    fn hash(&self) -> u64 {
+        match self {
+            SnapshotText::Full(text) => hash_text(text),
+            SnapshotText::Hashed(hash) => *hash,
+        }
+    }
+}
+
+/// The outputs of one run of a cell.
+pub(crate) struct OutputGeneration {
+    outputs: Vec<OutputSnapshot>,
+    /// How many more bytes of text are kept before only hashes are.
+    remaining_bytes: usize,
+}
+
+impl OutputGeneration {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            outputs: Vec::new(),
+            remaining_bytes: max_bytes,
+        }
+    }
+
+    /// Snapshots a cell's outputs, keeping at most `max_bytes` of their text.
+    pub fn capture(outputs: &[Output], max_bytes: usize, cx: &App) -> Self {
+        let mut generation = Self::new(max_bytes);
+        for output in outputs {
+            match output {
+                Output::Stream { content } => generation.push_stream(content.read(cx).full_text()),
+                Output::Plain { content, .. } => {
+                    generation.push_plain(content.read(cx).full_text())
+                }
+                Output::Message(message) => generation.push_plain(message.clone()),
+                Output::ErrorOutput(error) => generation.push_error(format!(
+                    "{}: {}\n{}",
+                    error.ename,
+                    error.evalue,
+                    error.traceback.read(cx).full_text()
+                )),
+                Output::Image { content, .. } => {
+                    generation.push_image(content.read(cx).content_hash())
+                }
+                Output::Markdown { .. } | Output::Table { .. } | Output::Json { .. } => {
+                    generation.outputs.push(OutputSnapshot::Other)
+                }
+                Output::ClearOutputWaitMarker => {}
+            }
+        }
+        generation
+    }
+
+    pub fn push_stream(&mut self, text: String) {
+        let text = self.keep_text(text);
+        self.outputs.push(OutputSnapshot::Stream(text));
+    }
+
+    pub fn push_plain(&mut self, text: String) {
+        let text = self.keep_text(text);
+        self.outputs.push(OutputSnapshot::Plain(text));
+    }
+
+    pub fn push_error(&mut self, text: String) {
+        let text = self.keep_text(text);
+        self.outputs.push(OutputSnapshot::Error(text));
+    }
+
+    pub fn push_image(&mut self, hash: Shared<Task<u64>>) {
+        self.outputs.push(OutputSnapshot::Image(hash));
+    }
+
+    pub fn outputs(&self) -> &[OutputSnapshot] {
+        &self.outputs
+    }
+
+    fn keep_text(&mut self, text: String) -> SnapshotText {
+        if text.len() <= self.remaining_bytes {
+            self.remaining_bytes -= text.len();
+            SnapshotText::Full(text)
+        } else {
+            SnapshotText::Hashed(hash_text(&text))
+        }
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DiffLine {
+    Unchanged(String),
+    Removed(String),
+    Added(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum OutputChange {
+    Unchanged,
+    /// The output changed, with a line diff when it's a stream or error whose text was kept in
+    /// full for both runs.
+    Changed(Option<Vec<DiffLine>>),
+    Added,
+    Removed,
+    /// The output is of a kind that isn't compared.
+    Unknown,
+}
+
+/// How each of a cell's outputs changed since its previous run.
+pub(crate) struct OutputComparison {
+    changes: Vec<OutputChange>,
+}
+
+impl OutputComparison {
+    pub async fn compute(previous: &OutputGeneration, current: &OutputGeneration) -> Self {
+        let mut changes = Vec::new();
+        for (previous, current) in previous.outputs.iter().zip(&current.outputs) {
+            changes.push(compare_output(previous, current).await);
+        }
+        let compared = changes.len();
+        changes.extend(
+            previous.outputs[compared..]
+                .iter()
+                .map(|_| OutputChange::Removed),
+        );
+        changes.extend(
+            current.outputs[compared..]
+                .iter()
+                .map(|_| OutputChange::Added),
+        );
+        Self { changes }
+    }
+
+    /// Whether the outputs changed, or `None` when some of them couldn't be compared and the
+    /// rest are the same.
+    pub fn changed(&self) -> Option<bool> {
+        if self.changes.iter().any(|change| {
+            matches!(
+                change,
+                OutputChange::Changed(_) | OutputChange::Added | OutputChange::Removed
+            )
+        }) {
+            Some(true)
+        } else if self
+            .changes
+            .iter()
+            .all(|change| *change == OutputChange::Unchanged)
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn has_diff(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| matches!(change, OutputChange::Changed(Some(_))))
+    }
+
+    pub fn changes(&self) -> &[OutputChange] {
+        &self.changes
+    }
+}
+
+async fn compare_output(previous: &OutputSnapshot, current: &OutputSnapshot) -> OutputChange {
+    match (previous, current) {
+        (OutputSnapshot::Stream(previous), OutputSnapshot::Stream(current))
+        | (OutputSnapshot::Error(previous), OutputSnapshot::Error(current)) => {
+            compare_text(previous, current, true)
+        }
+        (OutputSnapshot::Plain(previous), OutputSnapshot::Plain(current)) => {
+            compare_text(previous, current, false)
+        }
+        (OutputSnapshot::Image(previous), OutputSnapshot::Image(current)) => {
+            if previous.clone().await == current.clone().await {
+                OutputChange::Unchanged
+            } else {
+                OutputChange::Changed(None)
+            }
+        }
+        (OutputSnapshot::Other, OutputSnapshot::Other) => OutputChange::Unknown,
+        _ => OutputChange::Changed(None),
+    }
+}
+
+fn compare_text(previous: &SnapshotText, current: &SnapshotText, diff: bool) -> OutputChange {
+    match (previous, current) {
+        (SnapshotText::Full(previous), SnapshotText::Full(current)) => {
+            if previous == current {
+                OutputChange::Unchanged
+            } else if diff {
+                OutputChange::Changed(Some(line_diff(previous, current)))
+            } else {
+                OutputChange::Changed(None)
+            }
+        }
+        _ if previous.hash() == current.hash() => OutputChange::Unchanged,
+        _ => OutputChange::Changed(None),
+    }
+}
+
+fn line_diff(previous: &str, current: &str) -> Vec<DiffLine> {
+    let previous_lines = previous.lines().collect::<Vec<_>>();
+    let current_lines = current.lines().collect::<Vec<_>>();
+    let lines_in = |lines: &[&str], rows: std::ops::Range<usize>| -> Vec<String> {
+        lines
+            .get(rows)
+            .unwrap_or_default()
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    };
+
+    let mut diff = Vec::new();
+    let mut previous_row = 0;
+    for (previous_rows, current_rows) in language::line_diff(previous, current) {
+        let previous_rows = previous_rows.start as usize..previous_rows.end as usize;
+        let current_rows = current_rows.start as usize..current_rows.end as usize;
+        diff.extend(
+            lines_in(&previous_lines, previous_row..previous_rows.start)
+                .into_iter()
+                .map(DiffLine::Unchanged),
+        );
+        diff.extend(
+            lines_in(&previous_lines, previous_rows.clone())
+                .into_iter()
+                .map(DiffLine::Removed),
+        );
+        diff.extend(
+            lines_in(&current_lines, current_rows)
+                .into_iter()
+                .map(DiffLine::Added),
+        );
+        previous_row = previous_rows.end;
+    }
+    diff.extend(
+        lines_in(&previous_lines, previous_row..previous_lines.len())
+            .into_iter()
+            .map(DiffLine::Unchanged),
+    );
+    diff
+}
+
+/// What a cell shows in place of its outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum OutputHistoryView {
+    #[default]
+    Current,
+    Previous,
+    Diff,
+}
+
+/// The outputs of a cell's previous run, and how the latest run compares to them.
+#[derive(Default)]
+pub(crate) struct OutputHistory {
+    previous: Option<Arc<OutputGeneration>>,
+    comparison: Option<OutputComparison>,
+    view: OutputHistoryView,
+}
+
+impl OutputHistory {
+    /// Keeps the outputs of the run that's being replaced, dropping the ones before it.
+    pub fn retain(&mut self, generation: OutputGeneration) {
+        self.previous = Some(Arc::new(generation));
+        self.comparison = None;
+        self.view = OutputHistoryView::Current;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn previous(&self) -> Option<&Arc<OutputGeneration>> {
+        self.previous.as_ref()
+    }
+
+    /// Stores a comparison against `previous`, unless the history moved on while it was
+    /// computed. Returns whether it was stored.
+    pub fn set_comparison(
+        &mut self,
+        previous: &Arc<OutputGeneration>,
+        comparison: OutputComparison,
+    ) -> bool {
+        if self
+            .previous
+            .as_ref()
+            .is_some_and(|kept| Arc::ptr_eq(kept, previous))
+        {
+            self.comparison = Some(comparison);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn comparison(&self) -> Option<&OutputComparison> {
+        self.comparison.as_ref()
+    }
+
+    pub fn view(&self) -> OutputHistoryView {
+        if self.comparison.is_some() {
+            self.view
+        } else {
+            OutputHistoryView::Current
+        }
+    }
+
+    /// Switches to `view`, or back to the current outputs when it's already shown.
+    pub fn toggle_view(&mut self, view: OutputHistoryView) {
+        self.view = if self.view == view {
+            OutputHistoryView::Current
+        } else {
+            view
+        };
+    }
+}
+
+fn render_text(text: &str, cx: &App) -> AnyElement {
+    div()
+        .font_buffer(cx)
+        .text_buffer(cx)
+        .whitespace_normal()
+        .children(text.lines().map(|line| div().child(line.to_string())))
+        .into_any_element()
+}
+
+fn render_note(note: &'static str) -> AnyElement {
+    Label::new(note)
+        .size(LabelSize::Small)
+        .color(Color::Muted)
+        .into_any_element()
+}
+
+pub(crate) fn render_previous_outputs(generation: &OutputGeneration, cx: &App) -> AnyElement {
+    v_flex()
+        .gap_1()
+        .children(generation.outputs().iter().map(|output| match output {
+            OutputSnapshot::Stream(SnapshotText::Full(text))
+            | OutputSnapshot::Plain(SnapshotText::Full(text))
+            | OutputSnapshot::Error(SnapshotText::Full(text)) => render_text(text, cx),
+            OutputSnapshot::Stream(SnapshotText::Hashed(_))
+            | OutputSnapshot::Plain(SnapshotText::Hashed(_))
+            | OutputSnapshot::Error(SnapshotText::Hashed(_)) => {
+                render_note("Output was too large to keep")
+            }
+            OutputSnapshot::Image(_) => render_note("Image"),
+            OutputSnapshot::Other => render_note("Output wasn't kept"),
+        }))
+        .into_any_element()
+}
+
+pub(crate) fn render_output_diff(comparison: &OutputComparison, cx: &App) -> AnyElement {
+    v_flex()
+        .gap_1()
+        .children(comparison.changes().iter().map(|change| {
+            match change {
+                OutputChange::Unchanged => render_note("Unchanged output"),
+                OutputChange::Changed(Some(lines)) => v_flex()
+                    .font_buffer(cx)
+                    .text_buffer(cx)
+                    .children(lines.iter().map(|line| {
+                        let (prefix, line, color) = match line {
+                            DiffLine::Unchanged(line) => (" ", line, Color::Default),
+                            DiffLine::Removed(line) => ("-", line, Color::Deleted),
+                            DiffLine::Added(line) => ("+", line, Color::Created),
+                        };
+                        div()
+                            .text_color(color.color(cx))
+                            .child(format!("{prefix} {line}"))
+                    }))
+                    .into_any_element(),
+                OutputChange::Changed(None) => render_note("Changed output"),
+                OutputChange::Added => render_note("New output"),
+                OutputChange::Removed => render_note("Removed output"),
+                OutputChange::Unknown => render_note("Output can't be compared"),
+            }
+        }))
+        .into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt as _;
+
+    fn image(hash: u64) -> Shared<Task<u64>> {
+        Task::ready(hash).shared()
+    }
+
+    async fn compare(previous: &OutputGeneration, current: &OutputGeneration) -> OutputComparison {
+        OutputComparison::compute(previous, current).await
+    }
+
+    #[gpui::test]
+    async fn test_stream_outputs_are_diffed_by_line() {
+        let mut previous = OutputGeneration::new(1024);
+        previous.push_stream("epoch 1\nloss 0.5\ndone\n".to_string());
+        let mut same = OutputGeneration::new(1024);
+        same.push_stream("epoch 1\nloss 0.5\ndone\n".to_string());
+        let mut changed = OutputGeneration::new(1024);
+        changed.push_stream("epoch 1\nloss 0.4\ndone\n".to_string());
+
+        let comparison = compare(&previous, &same).await;
+        assert_eq!(comparison.changed(), Some(false));
+        assert!(!comparison.has_diff());
+
+        let comparison = compare(&previous, &changed).await;
+        assert_eq!(comparison.changed(), Some(true));
+        assert_eq!(
+            comparison.changes(),
+            [OutputChange::Changed(Some(vec![
+                DiffLine::Unchanged("epoch 1".to_string()),
+                DiffLine::Removed("loss 0.5".to_string()),
+                DiffLine::Added("loss 0.4".to_string()),
+                DiffLine::Unchanged("done".to_string()),
+            ]))]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_plain_outputs_are_flagged_without_a_diff() {
+        let mut previous = OutputGeneration::new(1024);
+        previous.push_plain("42".to_string());
+        let mut same = OutputGeneration::new(1024);
+        same.push_plain("42".to_string());
+        let mut changed = OutputGeneration::new(1024);
+        changed.push_plain("43".to_string());
+
+        assert_eq!(compare(&previous, &same).await.changed(), Some(false));
+        let comparison = compare(&previous, &changed).await;
+        assert_eq!(comparison.changes(), [OutputChange::Changed(None)]);
+    }
+
+    #[gpui::test]
+    async fn test_image_outputs_are_compared_by_hash() {
+        let mut previous = OutputGeneration::new(1024);
+        previous.push_image(image(1));
+        let mut same = OutputGeneration::new(1024);
+        same.push_image(image(1));
+        let mut changed = OutputGeneration::new(1024);
+        changed.push_image(image(2));
+
+        assert_eq!(compare(&previous, &same).await.changed(), Some(false));
+        assert_eq!(
+            compare(&previous, &changed).await.changes(),
+            [OutputChange::Changed(None)]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_error_outputs_are_diffed_by_line() {
+        let mut previous = OutputGeneration::new(1024);
+        previous.push_error("ValueError: bad\n  line 1".to_string());
+        let mut changed = OutputGeneration::new(1024);
+        changed.push_error("ValueError: worse\n  line 1".to_string());
+
+        let comparison = compare(&previous, &changed).await;
+        assert_eq!(comparison.changed(), Some(true));
+        assert!(comparison.has_diff());
+    }
+
+    #[gpui::test]
+    async fn test_added_removed_and_uncomparable_outputs() {
+        let mut previous = OutputGeneration::new(1024);
+        previous.push_plain("1".to_string());
+        let mut current = OutputGeneration::new(1024);
+        current.push_plain("1".to_string());
+        current.push_stream("more\n".to_string());
+
+        assert_eq!(
+            compare(&previous, &current).await.changes(),
+            [OutputChange::Unchanged, OutputChange::Added]
+        );
+        assert_eq!(
+            compare(&current, &previous).await.changes(),
+            [OutputChange::Unchanged, OutputChange::Removed]
+        );
+
+        let mut previous = OutputGeneration::new(1024);
+        previous.outputs.push(OutputSnapshot::Other);
+        let mut current = OutputGeneration::new(1024);
+        current.outputs.push(OutputSnapshot::Other);
+        assert_eq!(compare(&previous, &current).await.changed(), None);
+    }
+
+    #[gpui::test]
+    async fn test_text_past_the_cap_is_only_hashed() {
+        let mut previous = OutputGeneration::new(8);
+        previous.push_stream("short\n".to_string());
+        previous.push_stream("this is past the cap\n".to_string());
+        assert!(matches!(
+            previous.outputs(),
+            [
+                OutputSnapshot::Stream(SnapshotText::Full(_)),
+                OutputSnapshot::Stream(SnapshotText::Hashed(_))
+            ]
+        ));
+
+        // The latest run keeps its full text, but is still compared with the hash.
+        let mut same = OutputGeneration::new(1024);
+        same.push_stream("short\n".to_string());
+        same.push_stream("this is past the cap\n".to_string());
+        assert_eq!(compare(&previous, &same).await.changed(), Some(false));
+
+        let mut changed = OutputGeneration::new(1024);
+        changed.push_stream("short\n".to_string());
+        changed.push_stream("this is also past the cap\n".to_string());
+        assert_eq!(
+            compare(&previous, &changed).await.changes(),
+            [OutputChange::Unchanged, OutputChange::Changed(None)]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_clearing_history_drops_previous_outputs() {
+        let mut history = OutputHistory::default();
+        let mut generation = OutputGeneration::new(1024);
+        generation.push_plain("1".to_string());
+        history.retain(generation);
+
+        let previous = history.previous().cloned().unwrap();
+        let mut current = OutputGeneration::new(1024);
+        current.push_plain("2".to_string());
+        let comparison = compare(&previous, &current).await;
+        assert!(history.set_comparison(&previous, comparison));
+        history.toggle_view(OutputHistoryView::Previous);
+        assert_eq!(history.view(), OutputHistoryView::Previous);
+
+        // A kernel restart or clearing all outputs forgets the previous run.
+        history.clear();
+        assert!(history.previous().is_none());
+        assert!(history.comparison().is_none());
+        assert_eq!(history.view(), OutputHistoryView::Current);
+
+        // A comparison that finishes after the history was cleared is dropped.
+        let comparison = compare(&previous, &current).await;
+        assert!(!history.set_comparison(&previous, comparison));
+        assert!(history.comparison().is_none());
+    }
+}
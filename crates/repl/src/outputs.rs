@@ -14,10 +14,16 @@
 //! The module supports several output types, including:
 //! - Plain text
 //! - Markdown
-//! - Images (PNG and JPEG)
+//! - Images (PNG, JPEG and SVG)
 //! - Tables
 //! - Error messages
 //!
+//! ## Output Limits
+//!
+//! Stream output past the limits in `ReplSettings` is written to a temporary file instead
+//! of being kept in memory, and rich outputs over their own limit are replaced with a
+//! placeholder. See the `spool` module.
+//!
 //! ## Clipboard Support
 //!
 //! Most output types implement the `SupportsClipboard` trait, allowing
@@ -37,11 +43,14 @@ use editor::{Editor, MultiBuffer};
 use gpui::{AnyElement, ClipboardItem, Entity, EventEmitter, Render, WeakEntity};
 use language::Buffer;
 use menu;
-use runtimelib::{ExecutionState, JupyterMessage, JupyterMessageContent, MimeBundle, MimeType};
+use runtimelib::{
+    ExecutionState, JupyterMessage, JupyterMessageContent, MimeBundle, MimeType, Stdio,
+};
+use std::time::Duration;
 use ui::{CommonAnimationExt, CopyButton, IconButton, Tooltip, prelude::*};
 
 mod image;
-use image::ImageView;
+use image::{ImageSizeHint, ImageView};
 
 mod markdown;
 use markdown::MarkdownView;
@@ -54,31 +63,60 @@ use json::JsonView;
 
 mod html;
 
+pub mod ansi;
+
 pub mod plain;
 use plain::TerminalOutput;
 
+mod spool;
+use spool::{OutputSpool, StreamLimit};
+
 pub(crate) mod user_error;
 use user_error::ErrorView;
 use workspace::Workspace;
 
+use crate::execution_kind::ExecutionKind;
+use crate::execution_timing::ExecutionTiming;
 use crate::repl_settings::ReplSettings;
 use settings::Settings;
+use util::{fmt::human_duration, size::format_file_size};
 
-/// When deciding what to render from a collection of mediatypes, we need to rank them in order of importance
+/// When deciding what to render from a collection of mediatypes, we need to rank them in order of importance.
+/// This follows the order Jupyter prefers them in, with tables that Zed can render natively first.
 fn rank_mime_type(mimetype: &MimeType) -> usize {
     match mimetype {
-        MimeType::DataTable(_) => 7,
-        MimeType::Html(_) => 6,
-        MimeType::Json(_) => 5,
+        MimeType::DataTable(_) => 8,
+        MimeType::Html(_) => 7,
+        MimeType::Markdown(_) => 6,
+        MimeType::Svg(_) => 5,
         MimeType::Png(_) => 4,
         MimeType::Jpeg(_) => 3,
-        MimeType::Markdown(_) => 2,
+        MimeType::Json(_) => 2,
         MimeType::Plain(_) => 1,
         // All other media types are not supported in Zed at this time
         _ => 0,
     }
 }
 
+/// The size of the data behind a media type, for the types that are sent as text. Structured
+/// types like JSON have already been parsed by the time we see them, so they aren't measured.
+fn payload_size(mimetype: &MimeType) -> Option<usize> {
+    match mimetype {
+        MimeType::Plain(text)
+        | MimeType::Markdown(text)
+        | MimeType::Html(text)
+        | MimeType::Svg(text)
+        | MimeType::Png(text)
+        | MimeType::Jpeg(text) => Some(text.len()),
+        _ => None,
+    }
+}
+
+/// Shown above HTML that's rendered as markdown when the kernel didn't send anything else, since
+/// scripts and interactive figures like plotly's are lost along the way.
+const HTML_ONLY_NOTICE: &str = "> This output is only available as HTML, so it's shown as text. \
+    Scripts and interactive content aren't supported.\n\n";
+
 pub(crate) trait OutputContent {
     fn clipboard_content(&self, window: &Window, cx: &App) -> Option<ClipboardItem>;
     fn has_clipboard_content(&self, _window: &Window, _cx: &App) -> bool {
@@ -310,16 +348,10 @@ impl Output {
                 Self::ErrorOutput(err) => Some(
                     h_flex()
                         .pl_1()
-                        .child({
-                            let ename = err.ename.clone();
-                            let evalue = err.evalue.clone();
-                            let traceback = err.traceback.clone();
-                            let traceback_text = traceback.read(cx).full_text();
-                            let full_error = format!("{}: {}\n{}", ename, evalue, traceback_text);
-
-                            CopyButton::new("copy-full-error", full_error)
-                                .tooltip_label("Copy Full Error")
-                        })
+                        .child(
+                            CopyButton::new("copy-full-error", err.full_text(cx))
+                                .tooltip_label("Copy Full Error"),
+                        )
                         .child(
                             IconButton::new(
                                 ElementId::Name("open-full-error-in-buffer-traceback".into()),
@@ -328,14 +360,10 @@ impl Output {
                             .style(ButtonStyle::Transparent)
                             .tooltip(Tooltip::text("Open Full Error in Buffer"))
                             .on_click({
-                                let ename = err.ename.clone();
-                                let evalue = err.evalue.clone();
-                                let traceback = err.traceback.clone();
+                                let err = err.clone();
                                 move |_, window, cx| {
                                     if let Some(workspace) = workspace.upgrade() {
-                                        let traceback_text = traceback.read(cx).full_text();
-                                        let full_error =
-                                            format!("{}: {}\n{}", ename, evalue, traceback_text);
+                                        let full_error = err.full_text(cx);
                                         let buffer = cx.new(|cx| {
                                             let mut buffer = Buffer::local(full_error, cx)
                                                 .with_language(language::PLAIN_TEXT.clone(), cx);
@@ -388,11 +416,25 @@ impl Output {
 
     pub fn new(
         data: &MimeBundle,
+        metadata: &serde_json::Map<String, serde_json::Value>,
         display_id: Option<String>,
         window: &mut Window,
         cx: &mut App,
     ) -> Self {
-        match data.richest(rank_mime_type) {
+        let richest = data.richest(rank_mime_type);
+
+        let max_payload_size = ReplSettings::get_global(cx).display_data_max_bytes;
+        if let Some(size) = richest.and_then(payload_size)
+            && size > max_payload_size
+        {
+            return Output::Message(format!(
+                "Output too large to display ({}, the limit is {})",
+                format_file_size(size as u64, true),
+                format_file_size(max_payload_size as u64, true)
+            ));
+        }
+
+        match richest {
             Some(MimeType::Json(json_value)) => match JsonView::from_value(json_value.clone()) {
                 Ok(json_view) => Output::Json {
                     content: cx.new(|_| json_view),
@@ -411,19 +453,40 @@ impl Output {
                     display_id,
                 }
             }
-            Some(MimeType::Png(data)) | Some(MimeType::Jpeg(data)) => match ImageView::from(data) {
-                Ok(view) => Output::Image {
-                    content: cx.new(|_| view),
-                    display_id,
-                },
-                Err(error) => Output::Message(format!("Failed to load image: {}", error)),
+            Some(MimeType::Png(data)) => Output::Image {
+                content: cx.new(|cx| {
+                    let size_hint = ImageSizeHint::from_metadata(metadata, "image/png");
+                    ImageView::from_base64(data.clone(), size_hint, cx)
+                }),
+                display_id,
+            },
+            Some(MimeType::Jpeg(data)) => Output::Image {
+                content: cx.new(|cx| {
+                    let size_hint = ImageSizeHint::from_metadata(metadata, "image/jpeg");
+                    ImageView::from_base64(data.clone(), size_hint, cx)
+                }),
+                display_id,
+            },
+            Some(MimeType::Svg(svg)) => Output::Image {
+                content: cx.new(|cx| {
+                    let size_hint = ImageSizeHint::from_metadata(metadata, "image/svg+xml");
+                    ImageView::from_svg(svg.clone(), size_hint, window, cx)
+                }),
+                display_id,
             },
             Some(MimeType::DataTable(data)) => Output::Table {
                 content: cx.new(|cx| TableView::new(data, window, cx)),
                 display_id,
             },
             Some(MimeType::Html(html_content)) => match html::html_to_markdown(html_content) {
-                Ok(markdown_text) => {
+                Ok(mut markdown_text) => {
+                    if data
+                        .content
+                        .iter()
+                        .all(|mimetype| matches!(mimetype, MimeType::Html(_)))
+                    {
+                        markdown_text.insert_str(0, HTML_ONLY_NOTICE);
+                    }
                     let content = cx.new(|cx| MarkdownView::from(markdown_text, cx));
                     Output::Markdown {
                         content,
@@ -439,6 +502,32 @@ impl Output {
             _ => Output::Message("Unsupported media type".to_string()),
         }
     }
+
+    /// Appends stream text to `last_output` when it's a stream, so that consecutive chunks from
+    /// stdout and stderr form one block, and otherwise returns a new stream output.
+    pub fn append_stream(
+        last_output: Option<&mut Output>,
+        text: &str,
+        stream: &Stdio,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Option<Output> {
+        if let Some(Output::Stream { content }) = last_output {
+            content.update(cx, |content, cx| {
+                content.append_stream_text(text, stream, cx);
+                cx.notify();
+            });
+            return None;
+        }
+
+        Some(Output::Stream {
+            content: cx.new(|cx| {
+                let mut content = TerminalOutput::new(window, cx);
+                content.append_stream_text(text, stream, cx);
+                content
+            }),
+        })
+    }
 }
 
 #[derive(Default, Clone, Debug)]
@@ -449,6 +538,8 @@ pub enum ExecutionStatus {
     Queued,
     Executing,
     Finished,
+    /// The kernel finished the execution, but its last messages never arrived.
+    ReplyLost,
     ShuttingDown,
     Shutdown,
     KernelErrored(String),
@@ -478,7 +569,12 @@ pub struct ExecutionView {
     workspace: WeakEntity<Workspace>,
     pub outputs: Vec<Output>,
     pub status: ExecutionStatus,
+    kind: ExecutionKind,
     pending_input: Option<PendingInput>,
+    timing: Option<ExecutionTiming>,
+    stream_limit: StreamLimit,
+    /// Created once the stream output goes over `stream_limit`.
+    spool: Option<Entity<OutputSpool>>,
 }
 
 impl EventEmitter<ExecutionViewFinishedEmpty> for ExecutionView {}
@@ -488,17 +584,41 @@ impl EventEmitter<InputReplyEvent> for ExecutionView {}
 impl ExecutionView {
     pub fn new(
         status: ExecutionStatus,
+        kind: ExecutionKind,
         workspace: WeakEntity<Workspace>,
-        _cx: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) -> Self {
         Self {
             workspace,
             outputs: Default::default(),
             status,
+            kind,
             pending_input: None,
+            timing: None,
+            stream_limit: StreamLimit::from_settings(ReplSettings::get_global(cx)),
+            spool: None,
+        }
+    }
+
+    pub fn set_timing(&mut self, timing: ExecutionTiming, cx: &mut Context<Self>) {
+        if self.timing.as_ref() != Some(&timing) {
+            self.timing = Some(timing);
+            cx.notify();
         }
     }
 
+    /// Marks the execution as finished after the kernel turned out to be done with it, even
+    /// though its `execute_reply` or final `idle` status was lost.
+    pub fn finish_with_lost_reply(&mut self, cx: &mut Context<Self>) {
+        self.status = ExecutionStatus::ReplyLost;
+        self.pending_input = None;
+        cx.notify();
+    }
+
+    fn run_duration(&self) -> Option<Duration> {
+        self.timing.as_ref()?.run_duration()
+    }
+
     fn submit_input(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
         if let Some(pending_input) = self.pending_input.take() {
             let value = pending_input.editor.read(cx).text(cx);
@@ -558,19 +678,36 @@ impl ExecutionView {
         let output: Output = match message {
             JupyterMessageContent::ExecuteResult(result) => Output::new(
                 &result.data,
+                &result.metadata,
                 result.transient.as_ref().and_then(|t| t.display_id.clone()),
                 window,
                 cx,
             ),
             JupyterMessageContent::DisplayData(result) => Output::new(
                 &result.data,
+                &result.metadata,
                 result.transient.as_ref().and_then(|t| t.display_id.clone()),
                 window,
                 cx,
             ),
             JupyterMessageContent::StreamContent(result) => {
-                // Previous stream data will combine together, handling colors, carriage returns, etc
-                if let Some(new_terminal) = self.apply_terminal_text(&result.text, window, cx) {
+                let (kept_text, suppressed_text) = self.stream_limit.split(&result.text);
+                if !suppressed_text.is_empty() {
+                    self.spool_stream_text(suppressed_text, cx);
+                }
+                if kept_text.is_empty() {
+                    return;
+                }
+
+                // Stdout and stderr combine with the previous stream output in the order they
+                // arrive, handling colors, carriage returns, etc
+                if let Some(new_terminal) = Output::append_stream(
+                    self.outputs.last_mut(),
+                    kept_text,
+                    &result.name,
+                    window,
+                    cx,
+                ) {
                     new_terminal
                 } else {
                     return;
@@ -589,7 +726,7 @@ impl ExecutionView {
             JupyterMessageContent::ExecuteReply(reply) => {
                 for payload in reply.payload.iter() {
                     if let runtimelib::Payload::Page { data, .. } = payload {
-                        let output = Output::new(data, None, window, cx);
+                        let output = Output::new(data, &Default::default(), None, window, cx);
                         self.outputs.push(output);
                     }
                 }
@@ -598,7 +735,7 @@ impl ExecutionView {
             }
             JupyterMessageContent::ClearOutput(options) => {
                 if !options.wait {
-                    self.outputs.clear();
+                    self.clear_outputs(cx);
                     cx.notify();
                     return;
                 }
@@ -646,7 +783,7 @@ impl ExecutionView {
         if let Some(output) = self.outputs.last()
             && let Output::ClearOutputWaitMarker = output
         {
-            self.outputs.clear();
+            self.clear_outputs(cx);
         }
 
         self.outputs.push(output);
@@ -654,9 +791,35 @@ impl ExecutionView {
         cx.notify();
     }
 
+    /// Clears the outputs, along with the output that was spooled and the limit's usage, so that
+    /// output that's repeatedly cleared and redrawn (like a progress bar) is never cut off.
+    fn clear_outputs(&mut self, cx: &App) {
+        self.outputs.clear();
+        self.stream_limit = StreamLimit::from_settings(ReplSettings::get_global(cx));
+        self.spool = None;
+    }
+
+    fn spool_stream_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        let spool = self.spool.get_or_insert_with(|| {
+            let kept_text = self
+                .outputs
+                .iter()
+                .filter_map(|output| match output {
+                    Output::Stream { content } => Some(content.read(cx).full_text()),
+                    _ => None,
+                })
+                .collect::<String>();
+            let workspace = self.workspace.clone();
+            cx.notify();
+            cx.new(|cx| OutputSpool::new(kept_text, workspace, cx))
+        });
+        spool.read(cx).push(text.to_string());
+    }
+
     pub fn update_display_data(
         &mut self,
         data: &MimeBundle,
+        metadata: &serde_json::Map<String, serde_json::Value>,
         display_id: &str,
         window: &mut Window,
         cx: &mut Context<Self>,
@@ -667,7 +830,7 @@ impl ExecutionView {
             if let Some(other_display_id) = output.display_id().as_ref()
                 && other_display_id == display_id
             {
-                *output = Output::new(data, Some(display_id.to_owned()), window, cx);
+                *output = Output::new(data, metadata, Some(display_id.to_owned()), window, cx);
                 any = true;
             }
         });
@@ -687,9 +850,11 @@ impl ExecutionView {
 
         let output = self.outputs.first()?;
 
-        // Only Plain outputs can be inlined
+        // Only Plain outputs can be inlined, along with stream output from shell commands and
+        // cell magics, which is their result in place of an `execute_result`.
         let content = match output {
             Output::Plain { content, .. } => content,
+            Output::Stream { content } if !self.kind.expects_execute_result() => content,
             _ => return None,
         };
 
@@ -705,31 +870,6 @@ impl ExecutionView {
 
         Some(trimmed.to_string())
     }
-
-    fn apply_terminal_text(
-        &mut self,
-        text: &str,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) -> Option<Output> {
-        if let Some(last_output) = self.outputs.last_mut()
-            && let Output::Stream {
-                content: last_stream,
-            } = last_output
-        {
-            // Don't need to add a new output, we already have a terminal output
-            // and can just update the most recent terminal output
-            last_stream.update(cx, |last_stream, cx| {
-                last_stream.append_text(text, cx);
-                cx.notify();
-            });
-            return None;
-        }
-
-        Some(Output::Stream {
-            content: cx.new(|cx| TerminalOutput::from(text, window, cx)),
-        })
-    }
 }
 
 impl ExecutionView {
@@ -760,9 +900,37 @@ impl Render for ExecutionView {
                         .with_rotate_animation(3),
                 )
                 .child(Label::new("Executing...").color(Color::Muted))
+                .children(self.kind.render_badge())
+                .into_any_element(),
+            ExecutionStatus::Finished => h_flex()
+                .gap_2()
+                .child(Icon::new(IconName::Check).size(IconSize::Small))
+                .children(self.kind.render_badge())
+                .children(self.run_duration().map(|run_duration| {
+                    let queue_wait = self.timing.as_ref().and_then(ExecutionTiming::queue_wait);
+                    div()
+                        .id("execution-timing")
+                        .child(
+                            Label::new(format!("Ran in {}", human_duration(run_duration)))
+                                .size(LabelSize::Small)
+                                .color(Color::Muted),
+                        )
+                        .when_some(queue_wait, |this, queue_wait| {
+                            this.tooltip(Tooltip::text(format!(
+                                "Waited {} for the kernel",
+                                human_duration(queue_wait)
+                            )))
+                        })
+                }))
                 .into_any_element(),
-            ExecutionStatus::Finished => Icon::new(IconName::Check)
-                .size(IconSize::Small)
+            ExecutionStatus::ReplyLost => h_flex()
+                .gap_2()
+                .child(Icon::new(IconName::Check).size(IconSize::Small))
+                .child(
+                    Label::new("Completed (reply lost)")
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
                 .into_any_element(),
             ExecutionStatus::Unknown => Label::new("Unknown status")
                 .color(Color::Muted)
@@ -812,7 +980,7 @@ impl Render for ExecutionView {
                 )
         });
 
-        if self.outputs.is_empty() && pending_input_element.is_none() {
+        if self.outputs.is_empty() && self.spool.is_none() && pending_input_element.is_none() {
             return v_flex()
                 .min_h(window.line_height())
                 .justify_center()
@@ -827,10 +995,13 @@ impl Render for ExecutionView {
                     .iter()
                     .map(|output| output.render(self.workspace.clone(), window, cx)),
             )
+            .children(self.spool.clone())
             .children(pending_input_element)
             .children(match self.status {
                 ExecutionStatus::Executing => vec![status],
                 ExecutionStatus::Queued => vec![status],
+                ExecutionStatus::Finished if self.run_duration().is_some() => vec![status],
+                ExecutionStatus::ReplyLost => vec![status],
                 _ => vec![],
             })
             .into_any_element()
@@ -853,37 +1024,86 @@ mod tests {
     fn test_rank_mime_type_ordering() {
         let data_table = MimeType::DataTable(Box::default());
         let html = MimeType::Html(String::new());
-        let json = MimeType::Json(serde_json::json!({}));
+        let markdown = MimeType::Markdown(String::new());
+        let svg = MimeType::Svg(String::new());
         let png = MimeType::Png(String::new());
         let jpeg = MimeType::Jpeg(String::new());
-        let markdown = MimeType::Markdown(String::new());
+        let json = MimeType::Json(serde_json::json!({}));
         let plain = MimeType::Plain(String::new());
 
-        assert_eq!(rank_mime_type(&data_table), 7);
-        assert_eq!(rank_mime_type(&html), 6);
-        assert_eq!(rank_mime_type(&json), 5);
+        assert_eq!(rank_mime_type(&data_table), 8);
+        assert_eq!(rank_mime_type(&html), 7);
+        assert_eq!(rank_mime_type(&markdown), 6);
+        assert_eq!(rank_mime_type(&svg), 5);
         assert_eq!(rank_mime_type(&png), 4);
         assert_eq!(rank_mime_type(&jpeg), 3);
-        assert_eq!(rank_mime_type(&markdown), 2);
+        assert_eq!(rank_mime_type(&json), 2);
         assert_eq!(rank_mime_type(&plain), 1);
 
         assert!(rank_mime_type(&data_table) > rank_mime_type(&html));
-        assert!(rank_mime_type(&html) > rank_mime_type(&json));
-        assert!(rank_mime_type(&json) > rank_mime_type(&png));
+        assert!(rank_mime_type(&html) > rank_mime_type(&markdown));
+        assert!(rank_mime_type(&markdown) > rank_mime_type(&svg));
+        assert!(rank_mime_type(&svg) > rank_mime_type(&png));
         assert!(rank_mime_type(&png) > rank_mime_type(&jpeg));
-        assert!(rank_mime_type(&jpeg) > rank_mime_type(&markdown));
-        assert!(rank_mime_type(&markdown) > rank_mime_type(&plain));
+        assert!(rank_mime_type(&jpeg) > rank_mime_type(&json));
+        assert!(rank_mime_type(&json) > rank_mime_type(&plain));
     }
 
     #[test]
     fn test_rank_mime_type_unsupported_returns_zero() {
-        let svg = MimeType::Svg(String::new());
         let latex = MimeType::Latex(String::new());
 
-        assert_eq!(rank_mime_type(&svg), 0);
         assert_eq!(rank_mime_type(&latex), 0);
     }
 
+    fn bundle(json: serde_json::Value) -> MimeBundle {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_richest_mime_type_in_figure_bundles() {
+        // What matplotlib's inline backend sends for a figure.
+        let matplotlib = bundle(serde_json::json!({
+            "text/plain": "<Figure size 640x480 with 1 Axes>",
+            "image/png": "iVBORw0KGgo=",
+        }));
+        assert!(matches!(
+            matplotlib.richest(rank_mime_type),
+            Some(MimeType::Png(_))
+        ));
+
+        // With `figure_formats = {'png', 'svg'}`, both are sent and the SVG is shown.
+        let matplotlib_svg = bundle(serde_json::json!({
+            "text/plain": "<Figure size 640x480 with 1 Axes>",
+            "image/png": "iVBORw0KGgo=",
+            "image/svg+xml": "<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>",
+        }));
+        assert!(matches!(
+            matplotlib_svg.richest(rank_mime_type),
+            Some(MimeType::Svg(_))
+        ));
+
+        // HTML comes before images, like it does in Jupyter.
+        let html_and_png = bundle(serde_json::json!({
+            "text/html": "<div id=\"plot\"></div>",
+            "image/png": "iVBORw0KGgo=",
+            "text/plain": "Figure()",
+        }));
+        assert!(matches!(
+            html_and_png.richest(rank_mime_type),
+            Some(MimeType::Html(_))
+        ));
+
+        let latex = bundle(serde_json::json!({
+            "text/latex": "$x^2$",
+            "text/plain": "x**2",
+        }));
+        assert!(matches!(
+            latex.richest(rank_mime_type),
+            Some(MimeType::Plain(_))
+        ));
+    }
+
     async fn init_test(
         cx: &mut TestAppContext,
     ) -> (gpui::VisualTestContext, WeakEntity<workspace::Workspace>) {
@@ -909,7 +1129,14 @@ mod tests {
         weak_workspace: WeakEntity<workspace::Workspace>,
     ) -> Entity<ExecutionView> {
         cx.update(|_window, cx| {
-            cx.new(|cx| ExecutionView::new(ExecutionStatus::Queued, weak_workspace, cx))
+            cx.new(|cx| {
+                ExecutionView::new(
+                    ExecutionStatus::Queued,
+                    ExecutionKind::Code,
+                    weak_workspace,
+                    cx,
+                )
+            })
         })
     }
 
@@ -971,6 +1198,351 @@ mod tests {
         });
     }
 
+    fn set_output_limits(
+        cx: &mut gpui::VisualTestContext,
+        max_bytes: usize,
+        max_lines: usize,
+        display_data_max_bytes: usize,
+    ) {
+        cx.update(|_, cx| {
+            cx.update_global::<SettingsStore, _>(|store, cx| {
+                store.update_user_settings(cx, |settings| {
+                    let repl = settings.repl.get_or_insert_default();
+                    repl.output_max_bytes = Some(max_bytes);
+                    repl.output_max_lines = Some(max_lines);
+                    repl.display_data_max_bytes = Some(display_data_max_bytes);
+                });
+            });
+        });
+    }
+
+    fn push_stream_lines(
+        execution_view: &Entity<ExecutionView>,
+        lines: std::ops::Range<usize>,
+        cx: &mut gpui::VisualTestContext,
+    ) -> String {
+        let mut pushed_text = String::new();
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                for ix in lines {
+                    let text = format!("line {ix}\n");
+                    pushed_text.push_str(&text);
+                    let message = JupyterMessageContent::StreamContent(StreamContent {
+                        name: Stdio::Stdout,
+                        text,
+                    });
+                    view.push_message(&message, window, cx);
+                }
+            });
+        });
+        pushed_text
+    }
+
+    #[gpui::test]
+    async fn test_stream_flood_is_spooled(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        set_output_limits(&mut cx, 1024, 10_000, 1024);
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        let full_text = push_stream_lines(&execution_view, 0..20_000, &mut cx);
+        cx.run_until_parked();
+
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            let shown_text = view.output_as_stream_text(cx).unwrap();
+            assert!(shown_text.len() <= 1024, "{} bytes shown", shown_text.len());
+            assert!(full_text.starts_with(&shown_text));
+
+            let spool = view
+                .spool
+                .as_ref()
+                .expect("output should be spooled")
+                .read(cx);
+            assert_eq!(spool.suppressed_bytes(), full_text.len() - shown_text.len());
+            let spooled_text = std::fs::read_to_string(spool.path().unwrap()).unwrap();
+            assert_eq!(spooled_text, full_text);
+        });
+    }
+
+    #[gpui::test]
+    async fn test_stream_line_limit(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        set_output_limits(&mut cx, 1024 * 1024, 3, 1024);
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        let full_text = push_stream_lines(&execution_view, 0..10, &mut cx);
+        cx.run_until_parked();
+
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            assert_eq!(
+                view.output_as_stream_text(cx).unwrap(),
+                "line 0\nline 1\nline 2\n"
+            );
+            let spool = view.spool.as_ref().unwrap().read(cx);
+            let spooled_text = std::fs::read_to_string(spool.path().unwrap()).unwrap();
+            assert_eq!(spooled_text, full_text);
+        });
+
+        // Clearing the output starts over with the full limit and no spool.
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                let message = JupyterMessageContent::ClearOutput(ClearOutput { wait: false });
+                view.push_message(&message, window, cx);
+            });
+        });
+        push_stream_lines(&execution_view, 0..2, &mut cx);
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            assert!(view.spool.is_none());
+            assert_eq!(view.output_as_stream_text(cx).unwrap(), "line 0\nline 1\n");
+        });
+    }
+
+    #[gpui::test]
+    async fn test_oversized_display_data_is_not_decoded(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        set_output_limits(&mut cx, 1024, 1024, 16);
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                let mut data = MimeBundle::default();
+                data.content.push(MimeType::Png("A".repeat(17)));
+                let message =
+                    JupyterMessageContent::DisplayData(runtimelib::DisplayData::new(data));
+                view.push_message(&message, window, cx);
+            });
+        });
+
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            assert!(matches!(
+                &view.outputs[..],
+                [Output::Message(message)] if message.starts_with("Output too large to display")
+            ));
+        });
+    }
+
+    #[gpui::test]
+    async fn test_html_only_output_has_notice(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                for data in [
+                    bundle(serde_json::json!({ "text/html": "<p>plot</p>" })),
+                    bundle(serde_json::json!({
+                        "text/html": "<p>table</p>",
+                        "text/plain": "table",
+                    })),
+                ] {
+                    let message =
+                        JupyterMessageContent::DisplayData(runtimelib::DisplayData::new(data));
+                    view.push_message(&message, window, cx);
+                }
+            });
+        });
+
+        cx.update(|window, cx| {
+            let view = execution_view.read(cx);
+            let sources = view
+                .outputs
+                .iter()
+                .map(|output| match output {
+                    Output::Markdown { content, .. } => content
+                        .clipboard_content(window, cx)
+                        .and_then(|item| item.text())
+                        .unwrap_or_default(),
+                    _ => panic!("expected a markdown output"),
+                })
+                .collect::<Vec<_>>();
+            assert!(sources[0].starts_with(HTML_ONLY_NOTICE), "{}", sources[0]);
+            assert!(sources[0].contains("plot"));
+            assert!(!sources[1].contains(HTML_ONLY_NOTICE), "{}", sources[1]);
+        });
+    }
+
+    #[gpui::test]
+    async fn test_push_message_stream_with_escape_sequences(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                // The color sequence is split across messages, and the progress line is
+                // rewritten with carriage returns.
+                for text in ["\x1b[3", "1mred\x1b[0m\nloading  10%", "\rloading 100%\n"] {
+                    let message = JupyterMessageContent::StreamContent(StreamContent {
+                        name: Stdio::Stdout,
+                        text: text.to_string(),
+                    });
+                    view.push_message(&message, window, cx);
+                }
+            });
+        });
+
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            assert_eq!(
+                view.output_as_stream_text(cx).as_deref(),
+                Some("red\nloading 100%\n")
+            );
+        });
+    }
+
+    fn push_streams(
+        execution_view: &Entity<ExecutionView>,
+        chunks: &[(Stdio, &str)],
+        cx: &mut gpui::VisualTestContext,
+    ) {
+        cx.update(|window, cx| {
+            execution_view.update(cx, |view, cx| {
+                for (name, text) in chunks {
+                    let message = JupyterMessageContent::StreamContent(StreamContent {
+                        name: name.clone(),
+                        text: text.to_string(),
+                    });
+                    view.push_message(&message, window, cx);
+                }
+            });
+        });
+    }
+
+    fn rendered_stream_lines(
+        execution_view: &Entity<ExecutionView>,
+        cx: &mut gpui::VisualTestContext,
+    ) -> Vec<(String, Vec<usize>)> {
+        cx.update(|_, cx| {
+            let view = execution_view.read(cx);
+            assert_eq!(view.outputs.len(), 1, "streams should share one output");
+            let Output::Stream { content } = &view.outputs[0] else {
+                panic!("expected a stream output");
+            };
+            content.read(cx).rendered_lines()
+        })
+    }
+
+    #[gpui::test]
+    async fn test_interleaved_streams_keep_arrival_order(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        push_streams(
+            &execution_view,
+            &[
+                (Stdio::Stdout, "step 1\n"),
+                (Stdio::Stderr, "warning: slow\n"),
+                (Stdio::Stdout, "step 2\n"),
+                (Stdio::Stderr, "\x1b[31mfailed\x1b[0m to "),
+                (Stdio::Stderr, "connect"),
+                (Stdio::Stdout, "!\n"),
+            ],
+            &mut cx,
+        );
+
+        cx.update(|_, cx| {
+            assert_eq!(
+                execution_view.read(cx).output_as_stream_text(cx).as_deref(),
+                Some("step 1\nwarning: slow\nstep 2\nfailed to connect!\n")
+            );
+        });
+        // Stderr stays dimmed after the reset sequence within it, and stdout isn't dimmed on
+        // the line that stderr started.
+        assert_eq!(
+            rendered_stream_lines(&execution_view, &mut cx),
+            [
+                ("step 1".to_string(), vec![]),
+                ("warning: slow".to_string(), (0..13).collect()),
+                ("step 2".to_string(), vec![]),
+                ("failed to connect!".to_string(), (0..17).collect()),
+            ]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_carriage_returns_overwrite_across_chunks(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+        let execution_view = create_execution_view(&mut cx, workspace);
+
+        // A progress bar on stderr that rewrites its line, as tqdm and `%%timeit` do, followed
+        // by a summary on stdout.
+        push_streams(
+            &execution_view,
+            &[
+                (Stdio::Stderr, "\r 10%"),
+                (Stdio::Stderr, "\r 55%"),
+                (Stdio::Stderr, "\r100%\n"),
+                (Stdio::Stdout, "3.2 ms per loop\n"),
+                (Stdio::Stdout, "retrying"),
+                (Stdio::Stderr, "\rfailed"),
+                (Stdio::Stdout, "\rok\n"),
+            ],
+            &mut cx,
+        );
+
+        cx.update(|_, cx| {
+            assert_eq!(
+                execution_view.read(cx).output_as_stream_text(cx).as_deref(),
+                Some("100%\n3.2 ms per loop\nokiledng\n")
+            );
+        });
+        assert_eq!(
+            rendered_stream_lines(&execution_view, &mut cx),
+            [
+                ("100%".to_string(), (0..4).collect()),
+                ("3.2 ms per loop".to_string(), vec![]),
+                ("okiledng".to_string(), (2..6).collect()),
+            ]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_shell_stream_output_is_shown_inline(cx: &mut TestAppContext) {
+        let (mut cx, workspace) = init_test(cx).await;
+
+        for (code, expected_inline) in [("!echo hi", Some("hi")), ("print('hi')", None)] {
+            let execution_view = cx.update(|_, cx| {
+                cx.new(|cx| {
+                    ExecutionView::new(
+                        ExecutionStatus::Queued,
+                        ExecutionKind::detect(code),
+                        workspace.clone(),
+                        cx,
+                    )
+                })
+            });
+            let inline_output = Arc::new(std::sync::Mutex::new(None));
+            cx.update(|_, cx| {
+                let inline_output = inline_output.clone();
+                cx.subscribe(
+                    &execution_view,
+                    move |_, event: &ExecutionViewFinishedSmall, _cx| {
+                        *inline_output.lock().unwrap() = Some(event.0.clone());
+                    },
+                )
+                .detach();
+            });
+
+            push_streams(&execution_view, &[(Stdio::Stdout, "hi\n")], &mut cx);
+            cx.update(|window, cx| {
+                execution_view.update(cx, |view, cx| {
+                    let idle = JupyterMessageContent::Status(Status {
+                        execution_state: ExecutionState::Idle,
+                    });
+                    view.push_message(&idle, window, cx);
+                });
+            });
+
+            assert_eq!(
+                inline_output.lock().unwrap().as_deref(),
+                expected_inline,
+                "inline output for {code:?}"
+            );
+        }
+    }
+
     #[gpui::test]
     async fn test_push_message_error_output(cx: &mut TestAppContext) {
         let (mut cx, workspace) = init_test(cx).await;
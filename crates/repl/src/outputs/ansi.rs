@@ -0,0 +1,461 @@
+//! # ANSI Escape Sequences
+//!
+//! Kernels pass along whatever the user's code wrote to stdout and stderr, and tracebacks from
+//! IPython are colored by default, so both tend to contain ANSI escape sequences.
+//!
+//! [`AnsiParser`] turns that text into plain text plus the styled runs it covers. Only the SGR
+//! subset that affects styling (colors, bold, italic and underline) is kept. Other control
+//! sequences are stripped, except for carriage returns, which overwrite the current line like
+//! they do in a terminal so that progress bars don't repeat themselves.
+
+use gpui::{FontStyle, FontWeight, HighlightStyle, Hsla, UnderlineStyle, px};
+use std::ops::Range;
+use theme::Theme;
+
+/// Sequences longer than this are assumed to be malformed and are dropped, so that a stray
+/// escape character can't swallow the rest of the output.
+const MAX_SEQUENCE_LEN: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnsiColor {
+    /// An index into the 256-color palette, where the first 16 are the theme's terminal colors.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl AnsiColor {
+    pub fn to_hsla(self, theme: &Theme) -> Hsla {
+        match self {
+            AnsiColor::Indexed(index) => terminal::get_color_at_index(index as usize, theme),
+            AnsiColor::Rgb(r, g, b) => terminal::rgba_color(r, g, b),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnsiStyle {
+    pub foreground: Option<AnsiColor>,
+    pub background: Option<AnsiColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl AnsiStyle {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn highlight_style(&self, theme: &Theme) -> HighlightStyle {
+        HighlightStyle {
+            color: self.foreground.map(|color| color.to_hsla(theme)),
+            background_color: self.background.map(|color| color.to_hsla(theme)),
+            font_weight: self.bold.then_some(FontWeight::BOLD),
+            font_style: self.italic.then_some(FontStyle::Italic),
+            underline: self.underline.then(|| UnderlineStyle {
+                thickness: px(1.),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &str) {
+        // `:` separates sub-parameters in the ITU T.416 form of the extended colors, which
+        // otherwise carry the same values as the more common `;` form.
+        let mut params = params
+            .split([';', ':'])
+            .map(|param| param.parse::<u16>().ok());
+        while let Some(param) = params.next() {
+            // An empty parameter means the default, which is zero for every SGR attribute.
+            match param.unwrap_or(0) {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                code @ 30..=37 => self.foreground = Some(AnsiColor::Indexed(code as u8 - 30)),
+                38 => self.foreground = parse_extended_color(&mut params).or(self.foreground),
+                39 => self.foreground = None,
+                code @ 40..=47 => self.background = Some(AnsiColor::Indexed(code as u8 - 40)),
+                48 => self.background = parse_extended_color(&mut params).or(self.background),
+                49 => self.background = None,
+                code @ 90..=97 => self.foreground = Some(AnsiColor::Indexed(code as u8 - 90 + 8)),
+                code @ 100..=107 => {
+                    self.background = Some(AnsiColor::Indexed(code as u8 - 100 + 8))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Parses the color that follows a `38` or `48` parameter, either `5;index` or `2;r;g;b`.
+fn parse_extended_color(params: &mut impl Iterator<Item = Option<u16>>) -> Option<AnsiColor> {
+    let mode = params.next().flatten()?;
+    let mut next_channel = || {
+        params
+            .next()
+            .flatten()
+            .and_then(|value| u8::try_from(value).ok())
+    };
+    match mode {
+        5 => Some(AnsiColor::Indexed(next_channel()?)),
+        2 => Some(AnsiColor::Rgb(
+            next_channel()?,
+            next_channel()?,
+            next_channel()?,
+        )),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnsiText {
+    pub text: String,
+    /// The ranges of `text` that have a non-default style, in order.
+    pub runs: Vec<(Range<usize>, AnsiStyle)>,
+}
+
+impl AnsiText {
+    pub fn parse(input: &str) -> Self {
+        let mut parser = AnsiParser::default();
+        parser.push(input);
+        parser.text()
+    }
+
+    pub fn highlights(&self, theme: &Theme) -> Vec<(Range<usize>, HighlightStyle)> {
+        self.runs
+            .iter()
+            .map(|(range, style)| (range.clone(), style.highlight_style(theme)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    /// A control sequence, along with the parameter and intermediate bytes seen so far.
+    Csi(String),
+    /// An operating system command, such as setting the window title or a hyperlink.
+    Osc {
+        len: usize,
+        saw_escape: bool,
+    },
+}
+
+type Line = Vec<(char, AnsiStyle)>;
+
+/// Incrementally parses text containing ANSI escape sequences.
+///
+/// Output can arrive split across several messages at arbitrary points, including in the middle
+/// of an escape sequence, so the parser keeps its state between calls to [`AnsiParser::push`].
+#[derive(Clone, Debug, Default)]
+pub struct AnsiParser {
+    state: State,
+    style: AnsiStyle,
+    lines: Vec<Line>,
+    current_line: Line,
+    cursor: usize,
+}
+
+impl AnsiParser {
+    pub fn push(&mut self, input: &str) {
+        for ch in input.chars() {
+            self.advance(ch);
+        }
+    }
+
+    fn advance(&mut self, ch: char) {
+        match &mut self.state {
+            State::Ground => self.print(ch),
+            State::Escape => {
+                self.state = match ch {
+                    '[' => State::Csi(String::new()),
+                    ']' => State::Osc {
+                        len: 0,
+                        saw_escape: false,
+                    },
+                    // Intermediate bytes, as in the `ESC ( B` that selects a character set.
+                    '\x1b' | '\x20'..='\x2f' => State::Escape,
+                    // Any other escape is a sequence we don't need.
+                    _ => State::Ground,
+                };
+            }
+            State::Csi(params) => match ch {
+                '\x20'..='\x3f' if params.len() < MAX_SEQUENCE_LEN => params.push(ch),
+                '\x40'..='\x7e' => {
+                    let params = std::mem::take(params);
+                    self.state = State::Ground;
+                    // Parameters starting with a private marker like `?` aren't SGR, even
+                    // when they end in `m`.
+                    let is_sgr = ch == 'm'
+                        && params
+                            .bytes()
+                            .all(|byte| byte.is_ascii_digit() || byte == b';' || byte == b':');
+                    if is_sgr {
+                        self.style.apply_sgr(&params);
+                    }
+                }
+                _ => {
+                    self.state = State::Ground;
+                    self.print(ch);
+                }
+            },
+            State::Osc { len, saw_escape } => {
+                if ch == '\x07' || (*saw_escape && ch == '\\') {
+                    self.state = State::Ground;
+                } else if *len < MAX_SEQUENCE_LEN {
+                    *len += 1;
+                    *saw_escape = ch == '\x1b';
+                } else {
+                    self.state = State::Ground;
+                    self.print(ch);
+                }
+            }
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        match ch {
+            '\x1b' => self.state = State::Escape,
+            '\n' => {
+                self.lines.push(std::mem::take(&mut self.current_line));
+                self.cursor = 0;
+            }
+            '\r' => self.cursor = 0,
+            '\x08' => self.cursor = self.cursor.saturating_sub(1),
+            '\t' => self.write('\t'),
+            ch if ch.is_control() => {}
+            ch => self.write(ch),
+        }
+    }
+
+    fn write(&mut self, ch: char) {
+        if let Some(cell) = self.current_line.get_mut(self.cursor) {
+            *cell = (ch, self.style);
+        } else {
+            self.current_line.push((ch, self.style));
+        }
+        self.cursor += 1;
+    }
+
+    /// Returns the text parsed so far, with escape sequences removed and carriage returns applied.
+    pub fn text(&self) -> AnsiText {
+        fn push_line(line: &Line, result: &mut AnsiText) {
+            for (ch, style) in line {
+                let start = result.text.len();
+                result.text.push(*ch);
+                let end = result.text.len();
+                if style.is_default() {
+                    continue;
+                }
+                match result.runs.last_mut() {
+                    Some((range, last_style)) if range.end == start && last_style == style => {
+                        range.end = end;
+                    }
+                    _ => result.runs.push((start..end, *style)),
+                }
+            }
+        }
+
+        let mut result = AnsiText::default();
+        for line in &self.lines {
+            push_line(line, &mut result);
+            result.text.push('\n');
+        }
+        push_line(&self.current_line, &mut result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(update: impl FnOnce(&mut AnsiStyle)) -> AnsiStyle {
+        let mut style = AnsiStyle::default();
+        update(&mut style);
+        style
+    }
+
+    fn styled_parts(text: &AnsiText) -> Vec<(&str, AnsiStyle)> {
+        text.runs
+            .iter()
+            .map(|(range, style)| (&text.text[range.clone()], *style))
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_text_is_unchanged() {
+        let text = AnsiText::parse("hello\n  wörld 👋\n\nlast");
+        assert_eq!(text.text, "hello\n  wörld 👋\n\nlast");
+        assert!(text.runs.is_empty());
+    }
+
+    #[test]
+    fn test_basic_colors_and_reset() {
+        let text = AnsiText::parse("\x1b[31mred\x1b[0m plain \x1b[1;94mbold blue\x1b[m!");
+        assert_eq!(text.text, "red plain bold blue!");
+        assert_eq!(
+            styled_parts(&text),
+            [
+                (
+                    "red",
+                    style(|style| style.foreground = Some(AnsiColor::Indexed(1)))
+                ),
+                (
+                    "bold blue",
+                    style(|style| {
+                        style.foreground = Some(AnsiColor::Indexed(12));
+                        style.bold = true;
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_sgr() {
+        let text = AnsiText::parse(
+            "\x1b[1mbold \x1b[3;4mall\x1b[22m not bold\x1b[24;23m none\x1b[42m bg\x1b[49m",
+        );
+        assert_eq!(text.text, "bold all not bold none bg");
+        assert_eq!(
+            styled_parts(&text),
+            [
+                ("bold ", style(|style| style.bold = true)),
+                (
+                    "all",
+                    style(|style| {
+                        style.bold = true;
+                        style.italic = true;
+                        style.underline = true;
+                    })
+                ),
+                (
+                    " not bold",
+                    style(|style| {
+                        style.italic = true;
+                        style.underline = true;
+                    })
+                ),
+                (
+                    " bg",
+                    style(|style| style.background = Some(AnsiColor::Indexed(2)))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let text = AnsiText::parse(
+            "\x1b[38;5;208morange\x1b[48;2;10;20;30mon rgb\x1b[39;49m \x1b[38:2:1:2:3mcolon",
+        );
+        assert_eq!(text.text, "orangeon rgb colon");
+        assert_eq!(
+            styled_parts(&text),
+            [
+                (
+                    "orange",
+                    style(|style| style.foreground = Some(AnsiColor::Indexed(208)))
+                ),
+                (
+                    "on rgb",
+                    style(|style| {
+                        style.foreground = Some(AnsiColor::Indexed(208));
+                        style.background = Some(AnsiColor::Rgb(10, 20, 30));
+                    })
+                ),
+                (
+                    "colon",
+                    style(|style| style.foreground = Some(AnsiColor::Rgb(1, 2, 3)))
+                ),
+            ]
+        );
+
+        // Out of range and incomplete colors are ignored, without affecting what follows.
+        let text = AnsiText::parse("\x1b[38;5;300;1ma\x1b[0;48;2;1mb");
+        assert_eq!(text.text, "ab");
+        assert_eq!(
+            styled_parts(&text),
+            [("a", style(|style| style.bold = true))]
+        );
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites_line() {
+        let mut parser = AnsiParser::default();
+        for percent in [10, 50, 100] {
+            parser.push(&format!("\rprogress: {percent:>3}%"));
+        }
+        parser.push("\ndone\r\n");
+        assert_eq!(parser.text().text, "progress: 100%\ndone\n");
+
+        // Shorter text only overwrites the start of the line, like in a terminal.
+        let text = AnsiText::parse("hello world\r\x1b[32mbye\x1b[0m");
+        assert_eq!(text.text, "byelo world");
+        assert_eq!(
+            styled_parts(&text),
+            [(
+                "bye",
+                style(|style| style.foreground = Some(AnsiColor::Indexed(2)))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_other_sequences_are_stripped() {
+        let text = AnsiText::parse(
+            "\x1b[2K\x1b[1Aup\x1b[?25l\x1b[?1049h \x1b]0;title\x07\x1b]8;;https://zed.dev\x1b\\link\x1b]8;;\x1b\\\x1b(B\x07",
+        );
+        assert_eq!(text.text, "up link");
+        assert!(text.runs.is_empty());
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let input = "\x1b[38;5;9merror\x1b[0m: \x1b]0;title\x07ok";
+        let expected = AnsiText::parse(input);
+        for split in 0..input.len() {
+            let mut parser = AnsiParser::default();
+            parser.push(&input[..split]);
+            parser.push(&input[split..]);
+            assert_eq!(parser.text(), expected, "split at {split}");
+        }
+
+        let mut parser = AnsiParser::default();
+        parser.push("abc\x1b[3");
+        assert_eq!(parser.text().text, "abc");
+        parser.push("1mdef");
+        assert_eq!(
+            styled_parts(&parser.text()),
+            [(
+                "def",
+                style(|style| style.foreground = Some(AnsiColor::Indexed(1)))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_malformed_sequences() {
+        // Sequences interrupted by text or never terminated must not swallow the output.
+        assert_eq!(AnsiText::parse("a\x1b[31\nb").text, "a\nb");
+        assert_eq!(AnsiText::parse("a\x1b[é").text, "aé");
+        assert_eq!(AnsiText::parse("\x1b[99999999999999999999mx").text, "x");
+        assert_eq!(AnsiText::parse("trailing\x1b").text, "trailing");
+        assert_eq!(AnsiText::parse("\x1b\x1b[1mx").runs.len(), 1);
+
+        let unterminated = format!("\x1b[{}m after", "1;".repeat(MAX_SEQUENCE_LEN));
+        assert!(AnsiText::parse(&unterminated).text.ends_with(" after"));
+        let unterminated = format!("\x1b]{} after", "x".repeat(MAX_SEQUENCE_LEN));
+        assert!(AnsiText::parse(&unterminated).text.ends_with(" after"));
+
+        assert_eq!(AnsiText::parse("\x08\x08a\x08b\x00").text, "b");
+    }
+}
@@ -3,20 +3,68 @@ use base64::{
     Engine as _, alphabet,
     engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
 };
-use gpui::{App, ClipboardItem, Image, ImageFormat, RenderImage, Window, img};
+use futures::{FutureExt as _, future::Shared};
+use gpui::{
+    App, ClipboardItem, Image, ImageFormat, RenderImage, SMOOTH_SVG_SCALE_FACTOR, Size,
+    SvgRenderer, Task, Window, img, size,
+};
+use serde_json::Value;
 use settings::Settings as _;
-use std::sync::Arc;
-use ui::{IntoElement, Styled, div, prelude::*};
+use std::{
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    sync::Arc,
+};
+use ui::{ContextMenu, IntoElement, Styled, div, prelude::*, right_click_menu};
+use util::{ResultExt as _, TryFutureExt as _};
 
 use crate::outputs::{OutputContent, plain};
 use crate::repl_settings::ReplSettings;
 
-/// ImageView renders an image inline in an editor, adapting to the line height to fit the image.
+/// ImageView renders a PNG, JPEG or SVG output, decoding it in the background and fitting it
+/// to the output area once it's ready.
 pub struct ImageView {
-    clipboard_image: Arc<Image>,
-    height: u32,
-    width: u32,
+    state: ImageState,
+    content_hash: Shared<Task<u64>>,
+    _decode: Task<()>,
+}
+
+enum ImageState {
+    Loading,
+    Loaded(DecodedImage),
+    Failed(SharedString),
+}
+
+struct DecodedImage {
+    /// The image as the kernel sent it, for saving and copying.
+    source: Arc<Image>,
     image: Arc<RenderImage>,
+    /// The size to show the image at before it's fit to the output area.
+    size: Size<Pixels>,
+}
+
+/// The size an image output asks to be shown at, from the `width` and `height` a kernel can put
+/// in the metadata for its media type.
+///
+/// Retina figures from matplotlib are drawn at twice their size and use these to ask to be shown
+/// at their original size, so they'd look doubled without them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ImageSizeHint {
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+impl ImageSizeHint {
+    pub fn from_metadata(metadata: &serde_json::Map<String, Value>, mime_type: &str) -> Self {
+        let hints = metadata.get(mime_type);
+        let dimension = |key: &str| {
+            let value = hints?.get(key)?.as_f64()?;
+            (value > 0.0).then_some(value as f32)
+        };
+        Self {
+            width: dimension("width"),
+            height: dimension("height"),
+        }
+    }
 }
 
 pub const STANDARD_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
@@ -27,89 +75,245 @@ pub const STANDARD_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
 );
 
 impl ImageView {
-    pub fn from(base64_encoded_data: &str) -> Result<Self> {
-        let filtered =
-            base64_encoded_data.replace(&[' ', '\n', '\t', '\r', '\x0b', '\x0c'][..], "");
-        let bytes = STANDARD_INDIFFERENT.decode(filtered)?;
+    /// Decodes a base64 encoded raster image, like a PNG or JPEG, in the background.
+    pub fn from_base64(data: String, size_hint: ImageSizeHint, cx: &mut Context<Self>) -> Self {
+        let data = Arc::new(data);
+        let content_hash = hash_in_background(data.clone(), cx);
+        let decode = cx.background_spawn(async move { decode_base64_image(&data, size_hint) });
+        Self::new(decode, content_hash, cx)
+    }
 
-        let format = image::guess_format(&bytes)?;
+    /// Renders an SVG in the background, at the window's scale factor so that it stays sharp.
+    pub fn from_svg(
+        svg: String,
+        size_hint: ImageSizeHint,
+        window: &Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let renderer = cx.svg_renderer();
+        let scale_factor = window.scale_factor();
+        let svg = Arc::new(svg.into_bytes());
+        let content_hash = hash_in_background(svg.clone(), cx);
+        let decode = cx.background_spawn(async move {
+            render_svg(
+                Arc::unwrap_or_clone(svg),
+                size_hint,
+                &renderer,
+                scale_factor,
+            )
+        });
+        Self::new(decode, content_hash, cx)
+    }
 
-        let mut data = image::load_from_memory_with_format(&bytes, format)?.into_rgba8();
+    fn new(
+        decode: Task<Result<DecodedImage>>,
+        content_hash: Shared<Task<u64>>,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let decode = cx.spawn(async move |this, cx| {
+            let state = match decode.await {
+                Ok(image) => ImageState::Loaded(image),
+                Err(error) => ImageState::Failed(format!("Failed to load image: {error}").into()),
+            };
+            this.update(cx, |this, cx| {
+                this.state = state;
+                cx.notify();
+            })
+            .ok();
+        });
+        Self {
+            state: ImageState::Loading,
+            content_hash,
+            _decode: decode,
+        }
+    }
 
-        // Convert from RGBA to BGRA.
-        for pixel in data.chunks_exact_mut(4) {
-            pixel.swap(0, 2);
+    /// A hash of the data the kernel sent, for telling whether two image outputs are the same
+    /// without keeping their bytes around.
+    pub fn content_hash(&self) -> Shared<Task<u64>> {
+        self.content_hash.clone()
+    }
+
+    fn copy_image(&mut self, cx: &mut Context<Self>) {
+        if let ImageState::Loaded(image) = &self.state {
+            cx.write_to_clipboard(ClipboardItem::new_image(image.source.as_ref()));
         }
+    }
 
-        let height = data.height();
-        let width = data.width();
-
-        let gpui_image_data = RenderImage::new(vec![image::Frame::new(data)]);
-
-        let format = match format {
-            image::ImageFormat::Png => ImageFormat::Png,
-            image::ImageFormat::Jpeg => ImageFormat::Jpeg,
-            image::ImageFormat::Gif => ImageFormat::Gif,
-            image::ImageFormat::WebP => ImageFormat::Webp,
-            image::ImageFormat::Tiff => ImageFormat::Tiff,
-            image::ImageFormat::Bmp => ImageFormat::Bmp,
-            image::ImageFormat::Ico => ImageFormat::Ico,
-            format => {
-                anyhow::bail!("unsupported image format {format:?}");
-            }
+    /// Asks where to save the image, then writes the bytes the kernel sent there.
+    fn save_image_as(&mut self, cx: &mut Context<Self>) {
+        let ImageState::Loaded(image) = &self.state else {
+            return;
         };
+        let source = image.source.clone();
+        let suggested_name = format!("figure.{}", file_extension(source.format()));
+        let path = cx.prompt_for_new_path(util::paths::home_dir(), Some(&suggested_name));
+        cx.background_spawn(async move {
+            let Some(path) = path
+                .await
+                .log_err()
+                .and_then(|path| path.log_err())
+                .flatten()
+            else {
+                return;
+            };
+            smol::fs::write(&path, source.bytes())
+                .log_err_with_context(|| format!("saving image to {}", path.display()))
+                .await;
+        })
+        .detach();
+    }
+}
+
+fn hash_in_background(
+    data: Arc<impl AsRef<[u8]> + Send + Sync + 'static>,
+    cx: &mut Context<ImageView>,
+) -> Shared<Task<u64>> {
+    cx.background_spawn(async move {
+        let mut hasher = DefaultHasher::new();
+        let bytes: &[u8] = (*data).as_ref();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    })
+    .shared()
+}
 
-        // Convert back to a GPUI image for use with the clipboard
-        let clipboard_image = Arc::new(Image::from_bytes(format, bytes));
+fn decode_base64_image(
+    base64_encoded_data: &str,
+    size_hint: ImageSizeHint,
+) -> Result<DecodedImage> {
+    let filtered = base64_encoded_data.replace(&[' ', '\n', '\t', '\r', '\x0b', '\x0c'][..], "");
+    let bytes = STANDARD_INDIFFERENT.decode(filtered)?;
 
-        Ok(ImageView {
-            clipboard_image,
-            height,
-            width,
-            image: Arc::new(gpui_image_data),
-        })
+    let format = image::guess_format(&bytes)?;
+
+    let mut data = image::load_from_memory_with_format(&bytes, format)?.into_rgba8();
+
+    // Convert from RGBA to BGRA.
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
     }
 
-    fn scaled_size(
-        &self,
-        line_height: Pixels,
-        max_width: Option<Pixels>,
-        max_height: Option<Pixels>,
-    ) -> (Pixels, Pixels) {
-        let (mut height, mut width) = if self.height as f32 / f32::from(line_height)
-            == u8::MAX as f32
-        {
-            let height = u8::MAX as f32 * line_height;
-            let width = Pixels::from(self.width as f32 * f32::from(height) / self.height as f32);
-            (height, width)
-        } else {
-            (self.height.into(), self.width.into())
-        };
+    let natural_size = size(px(data.width() as f32), px(data.height() as f32));
 
-        let mut scale: f32 = 1.0;
-        if let Some(max_width) = max_width {
-            if width > max_width {
-                scale = scale.min(max_width / width);
-            }
-        }
+    let gpui_image_data = RenderImage::new(vec![image::Frame::new(data)]);
 
-        if let Some(max_height) = max_height {
-            if height > max_height {
-                scale = scale.min(max_height / height);
-            }
+    let format = match format {
+        image::ImageFormat::Png => ImageFormat::Png,
+        image::ImageFormat::Jpeg => ImageFormat::Jpeg,
+        image::ImageFormat::Gif => ImageFormat::Gif,
+        image::ImageFormat::WebP => ImageFormat::Webp,
+        image::ImageFormat::Tiff => ImageFormat::Tiff,
+        image::ImageFormat::Bmp => ImageFormat::Bmp,
+        image::ImageFormat::Ico => ImageFormat::Ico,
+        format => {
+            anyhow::bail!("unsupported image format {format:?}");
         }
+    };
 
-        if scale < 1.0 {
-            width *= scale;
-            height *= scale;
+    Ok(DecodedImage {
+        source: Arc::new(Image::from_bytes(format, bytes)),
+        image: Arc::new(gpui_image_data),
+        size: display_size(natural_size, size_hint),
+    })
+}
+
+fn render_svg(
+    bytes: Vec<u8>,
+    size_hint: ImageSizeHint,
+    renderer: &SvgRenderer,
+    scale_factor: f32,
+) -> Result<DecodedImage> {
+    let image = renderer.render_single_frame(&bytes, scale_factor, true)?;
+
+    // The renderer draws SVGs at a multiple of the scale factor, so this is the size the SVG
+    // declares for itself.
+    let pixel_size = image.size(0);
+    let render_scale = scale_factor * SMOOTH_SVG_SCALE_FACTOR;
+    let natural_size = size(
+        px(pixel_size.width.0 as f32 / render_scale),
+        px(pixel_size.height.0 as f32 / render_scale),
+    );
+
+    Ok(DecodedImage {
+        source: Arc::new(Image::from_bytes(ImageFormat::Svg, bytes)),
+        image,
+        size: display_size(natural_size, size_hint),
+    })
+}
+
+/// The size to show an image at, given the size it was drawn at and the size it asks for. When
+/// only one dimension is asked for, the other keeps the image's aspect ratio.
+fn display_size(natural_size: Size<Pixels>, size_hint: ImageSizeHint) -> Size<Pixels> {
+    let natural_width = f32::from(natural_size.width);
+    let natural_height = f32::from(natural_size.height);
+    match (size_hint.width, size_hint.height) {
+        (Some(width), Some(height)) => size(px(width), px(height)),
+        (Some(width), None) if natural_width > 0.0 => {
+            size(px(width), px(natural_height * width / natural_width))
         }
+        (None, Some(height)) if natural_height > 0.0 => {
+            size(px(natural_width * height / natural_height), px(height))
+        }
+        _ => natural_size,
+    }
+}
+
+/// Shrinks an image to fit within the output area, keeping its aspect ratio.
+fn fit_to_bounds(
+    image_size: Size<Pixels>,
+    max_width: Option<Pixels>,
+    max_height: Option<Pixels>,
+) -> Size<Pixels> {
+    let mut scale: f32 = 1.0;
+    if let Some(max_width) = max_width
+        && image_size.width > max_width
+    {
+        scale = scale.min(max_width / image_size.width);
+    }
 
-        (height, width)
+    if let Some(max_height) = max_height
+        && image_size.height > max_height
+    {
+        scale = scale.min(max_height / image_size.height);
+    }
+
+    if scale < 1.0 {
+        size(image_size.width * scale, image_size.height * scale)
+    } else {
+        image_size
+    }
+}
+
+fn file_extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Webp => "webp",
+        ImageFormat::Gif => "gif",
+        ImageFormat::Svg => "svg",
+        ImageFormat::Bmp => "bmp",
+        ImageFormat::Tiff => "tiff",
+        ImageFormat::Ico => "ico",
     }
 }
 
 impl Render for ImageView {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let image = match &self.state {
+            ImageState::Loading => {
+                return Label::new("Loading image…")
+                    .color(Color::Muted)
+                    .into_any_element();
+            }
+            ImageState::Failed(message) => {
+                return Label::new(message.clone())
+                    .color(Color::Error)
+                    .into_any_element();
+            }
+            ImageState::Loaded(image) => image,
+        };
+
         let settings = ReplSettings::get_global(cx);
         let line_height = window.line_height();
 
@@ -121,21 +325,46 @@ impl Render for ImageView {
             None
         };
 
-        let (height, width) = self.scaled_size(line_height, max_width, max_height);
-
-        let image = self.image.clone();
-
-        div().h(height).w(width).child(img(image))
+        let image_size = fit_to_bounds(image.size, max_width, max_height);
+        let render_image = image.image.clone();
+        let view = cx.entity();
+
+        right_click_menu(("image-output", cx.entity_id()))
+            .trigger(move |_, _, _| {
+                div()
+                    .h(image_size.height)
+                    .w(image_size.width)
+                    .child(img(render_image))
+            })
+            .menu(move |window, cx| {
+                let view = view.clone();
+                ContextMenu::build(window, cx, move |menu, window, _| {
+                    menu.entry(
+                        "Copy Image",
+                        None,
+                        window.handler_for(&view, |this, _, cx| this.copy_image(cx)),
+                    )
+                    .entry(
+                        "Save Image As…",
+                        None,
+                        window.handler_for(&view, |this, _, cx| this.save_image_as(cx)),
+                    )
+                })
+            })
+            .into_any_element()
     }
 }
 
 impl OutputContent for ImageView {
     fn clipboard_content(&self, _window: &Window, _cx: &App) -> Option<ClipboardItem> {
-        Some(ClipboardItem::new_image(self.clipboard_image.as_ref()))
+        match &self.state {
+            ImageState::Loaded(image) => Some(ClipboardItem::new_image(image.source.as_ref())),
+            ImageState::Loading | ImageState::Failed(_) => None,
+        }
     }
 
     fn has_clipboard_content(&self, _window: &Window, _cx: &App) -> bool {
-        true
+        matches!(self.state, ImageState::Loaded(_))
     }
 }
 
@@ -157,36 +386,101 @@ mod tests {
         base64::engine::general_purpose::STANDARD.encode(bytes)
     }
 
+    fn hint(width: Option<f32>, height: Option<f32>) -> ImageSizeHint {
+        ImageSizeHint { width, height }
+    }
+
     #[test]
     fn test_image_view_scaled_size_respects_limits() {
-        let encoded = encode_test_image(200, 120);
-        let image_view = match ImageView::from(&encoded) {
-            Ok(view) => view,
-            Err(error) => panic!("failed to decode image view: {error}"),
-        };
+        let image_size = size(px(200.), px(120.));
+        let fitted = fit_to_bounds(image_size, Some(px(50.)), Some(px(40.)));
+        assert_eq!(fitted, size(px(50.), px(30.)));
+    }
 
-        let line_height = Pixels::from(10.0);
-        let max_width = Pixels::from(50.0);
-        let max_height = Pixels::from(40.0);
-        let (height, width) =
-            image_view.scaled_size(line_height, Some(max_width), Some(max_height));
+    #[test]
+    fn test_image_view_scaled_size_unbounded() {
+        let image_size = size(px(200.), px(120.));
+        assert_eq!(fit_to_bounds(image_size, None, None), image_size);
+    }
 
-        assert_eq!(f32::from(width), 50.0);
-        assert_eq!(f32::from(height), 30.0);
+    #[test]
+    fn test_decode_base64_image() {
+        // Kernels wrap long base64 payloads across lines.
+        let encoded = encode_test_image(200, 120)
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let decoded = decode_base64_image(&encoded, ImageSizeHint::default()).unwrap();
+        assert_eq!(decoded.size, size(px(200.), px(120.)));
+        assert_eq!(decoded.source.format(), ImageFormat::Png);
+        assert!(decoded.source.bytes().starts_with(b"\x89PNG"));
+
+        assert!(decode_base64_image("not an image", ImageSizeHint::default()).is_err());
     }
 
     #[test]
-    fn test_image_view_scaled_size_unbounded() {
-        let encoded = encode_test_image(200, 120);
-        let image_view = match ImageView::from(&encoded) {
-            Ok(view) => view,
-            Err(error) => panic!("failed to decode image view: {error}"),
-        };
+    fn test_retina_figure_is_shown_at_hinted_size() {
+        // `%config InlineBackend.figure_format = 'retina'` draws a 640x480 figure at 1280x960.
+        let metadata = serde_json::json!({
+            "image/png": { "width": 640, "height": 480 }
+        });
+        let size_hint = ImageSizeHint::from_metadata(metadata.as_object().unwrap(), "image/png");
+        assert_eq!(size_hint, hint(Some(640.), Some(480.)));
+        let decoded = decode_base64_image(&encode_test_image(1280, 960), size_hint).unwrap();
+        assert_eq!(decoded.size, size(px(640.), px(480.)));
+        assert_eq!(decoded.image.size(0).width.0, 1280);
+    }
 
-        let line_height = Pixels::from(10.0);
-        let (height, width) = image_view.scaled_size(line_height, None, None);
+    #[test]
+    fn test_display_size() {
+        let natural_size = size(px(1280.), px(960.));
+        assert_eq!(
+            display_size(natural_size, ImageSizeHint::default()),
+            natural_size
+        );
+        assert_eq!(
+            display_size(natural_size, hint(Some(640.), None)),
+            size(px(640.), px(480.))
+        );
+        assert_eq!(
+            display_size(natural_size, hint(None, Some(240.))),
+            size(px(320.), px(240.))
+        );
+        // Both hints win over the aspect ratio, like they do in Jupyter.
+        assert_eq!(
+            display_size(natural_size, hint(Some(100.), Some(100.))),
+            size(px(100.), px(100.))
+        );
+
+        // Hints for other media types, and ones that aren't sizes, are ignored.
+        let metadata = serde_json::json!({
+            "image/jpeg": { "width": 10 },
+            "image/png": { "width": "wide", "height": 0 }
+        });
+        assert_eq!(
+            ImageSizeHint::from_metadata(metadata.as_object().unwrap(), "image/png"),
+            ImageSizeHint::default()
+        );
+    }
 
-        assert_eq!(f32::from(width), 200.0);
-        assert_eq!(f32::from(height), 120.0);
+    #[test]
+    fn test_render_svg() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"></svg>"#;
+        let renderer = SvgRenderer::new(Arc::new(()));
+
+        // On a retina display the SVG is drawn with more pixels, but shown at the same size.
+        let decoded = render_svg(svg.to_vec(), ImageSizeHint::default(), &renderer, 2.0).unwrap();
+        assert_eq!(decoded.size, size(px(100.), px(50.)));
+        assert_eq!(
+            decoded.image.size(0).width.0 as f32,
+            100.0 * 2.0 * SMOOTH_SVG_SCALE_FACTOR
+        );
+        assert_eq!(decoded.source.format(), ImageFormat::Svg);
+        assert_eq!(decoded.source.bytes(), svg);
+
+        let decoded = render_svg(svg.to_vec(), hint(Some(50.), None), &renderer, 1.0).unwrap();
+        assert_eq!(decoded.size, size(px(50.), px(25.)));
     }
 }
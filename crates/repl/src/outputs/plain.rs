@@ -14,23 +14,25 @@
 //! - Plain text content
 //! - Error tracebacks
 //!
+//! An execution's stdout and stderr share a single `TerminalOutput`, so that chunks from both
+//! streams stay in the order they arrived. Text from stderr is dimmed to tell the two apart.
+//!
 
 use alacritty_terminal::{
     event::VoidListener,
-    grid::Dimensions as _,
-    index::{Column, Line, Point},
-    term::Config,
+    term::{Config, cell::Flags},
     vte::ansi::Processor,
 };
 use gpui::{Bounds, ClipboardItem, Entity, FontStyle, TextStyle, WhiteSpace, canvas, size};
 use language::Buffer;
+use runtimelib::Stdio;
 use settings::Settings as _;
 use terminal::terminal_settings::TerminalSettings;
 use terminal_view::terminal_element::TerminalElement;
 use theme::ThemeSettings;
 use ui::{IntoElement, prelude::*};
 
-use crate::outputs::OutputContent;
+use crate::outputs::{OutputContent, ansi::AnsiParser};
 use crate::repl_settings::ReplSettings;
 
 /// The `TerminalOutput` struct handles the parsing and rendering of text input,
@@ -52,6 +54,9 @@ pub struct TerminalOutput {
     parser: Processor,
     /// Alacritty terminal instance that manages the terminal state and content.
     handler: alacritty_terminal::Term<VoidListener>,
+    /// The same output as plain text. Unlike the terminal grid, this isn't limited to the
+    /// number of lines that fit in the terminal, so it's what gets copied and saved.
+    plain_text: AnsiParser,
 }
 
 /// Returns the default text style for the terminal output.
@@ -151,6 +156,7 @@ impl TerminalOutput {
         Self {
             parser: Processor::new(),
             handler: term,
+            plain_text: AnsiParser::default(),
             full_buffer: None,
         }
     }
@@ -200,7 +206,20 @@ impl TerminalOutput {
     ///
     /// * `text` - A string slice containing the text to be appended.
     pub fn append_text(&mut self, text: &str, cx: &mut App) {
+        self.append_stream_text(text, &Stdio::Stdout, cx);
+    }
+
+    /// Appends text that the kernel wrote to `stream`, after whatever either stream wrote
+    /// before it. Carriage returns overwrite the current line no matter which stream wrote it,
+    /// as they would in a terminal.
+    pub fn append_stream_text(&mut self, text: &str, stream: &Stdio, cx: &mut App) {
+        let dim_stderr = matches!(stream, Stdio::Stderr);
         for byte in text.as_bytes() {
+            // Escape sequences in the text can reset the style, so stderr's dimming is
+            // reapplied before every byte rather than once per chunk.
+            if dim_stderr {
+                self.set_template_dim(true);
+            }
             if *byte == b'\n' {
                 // Dirty (?) hack to move the cursor down
                 self.parser.advance(&mut self.handler, &[b'\r']);
@@ -209,59 +228,62 @@ impl TerminalOutput {
                 self.parser.advance(&mut self.handler, &[*byte]);
             }
         }
+        if dim_stderr {
+            self.set_template_dim(false);
+        }
+
+        self.plain_text.push(text);
 
-        // This will keep the buffer up to date, though with some terminal codes it won't be perfect
+        // Carriage returns can rewrite earlier text, so the buffer is replaced rather than appended to.
         if let Some(buffer) = self.full_buffer.as_ref() {
+            let full_text = self.full_text();
             buffer.update(cx, |buffer, cx| {
-                buffer.edit([(buffer.len()..buffer.len(), text)], None, cx);
+                buffer.edit([(0..buffer.len(), full_text)], None, cx);
             });
         }
     }
 
+    /// Returns the output with escape sequences stripped and carriage returns applied.
     pub fn full_text(&self) -> String {
-        fn sanitize(mut line: String) -> Option<String> {
-            line.retain(|ch| ch != '\u{0}' && ch != '\r');
-            if line.trim().is_empty() {
-                return None;
-            }
-            let trimmed = line.trim_end_matches([' ', '\t']);
-            Some(trimmed.to_owned())
-        }
-
-        let mut lines = Vec::new();
+        self.plain_text.text().text
+    }
 
-        // Get the total number of lines, including history
-        let total_lines = self.handler.grid().total_lines();
-        let visible_lines = self.handler.screen_lines();
-        let history_lines = total_lines - visible_lines;
+    /// Sets whether the cells written next are dimmed.
+    fn set_template_dim(&mut self, dim: bool) {
+        self.handler
+            .grid_mut()
+            .cursor
+            .template
+            .flags
+            .set(Flags::DIM, dim);
+    }
 
-        // Capture history lines in correct order (oldest to newest)
-        for line in (0..history_lines).rev() {
-            let line_index = Line(-(line as i32) - 1);
-            let start = Point::new(line_index, Column(0));
-            let end = Point::new(line_index, Column(self.handler.columns() - 1));
-            if let Some(cleaned) = sanitize(self.handler.bounds_to_string(start, end)) {
-                lines.push(cleaned);
+    /// The visible lines, with trailing blanks trimmed, and for each one the columns that are
+    /// dimmed because stderr wrote them.
+    #[cfg(test)]
+    pub(crate) fn rendered_lines(&self) -> Vec<(String, Vec<usize>)> {
+        let mut lines: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut current_line = None;
+        for indexed in self.handler.renderable_content().display_iter {
+            if current_line != Some(indexed.point.line) {
+                current_line = Some(indexed.point.line);
+                lines.push(Default::default());
             }
-        }
-
-        // Capture visible lines
-        for line in 0..visible_lines {
-            let line_index = Line(line as i32);
-            let start = Point::new(line_index, Column(0));
-            let end = Point::new(line_index, Column(self.handler.columns() - 1));
-            if let Some(cleaned) = sanitize(self.handler.bounds_to_string(start, end)) {
-                lines.push(cleaned);
+            let Some((text, dimmed_columns)) = lines.last_mut() else {
+                continue;
+            };
+            if indexed.cell.flags.contains(Flags::DIM) {
+                dimmed_columns.push(indexed.point.column.0);
             }
+            text.push(indexed.cell.c);
         }
-
-        if lines.is_empty() {
-            String::new()
-        } else {
-            let mut full_text = lines.join("\n");
-            full_text.push('\n');
-            full_text
+        for (text, _) in &mut lines {
+            text.truncate(text.trim_end().len());
+        }
+        while lines.last().is_some_and(|(text, _)| text.is_empty()) {
+            lines.pop();
         }
+        lines
     }
 }
 
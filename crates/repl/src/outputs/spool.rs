@@ -0,0 +1,274 @@
+//! # Output Spooling
+//!
+//! A kernel can print far more than the REPL can render, for example when printing a huge
+//! dataframe or when stuck in a loop of prints. `StreamLimit` decides how much stream text an
+//! execution keeps in memory, and `OutputSpool` writes everything past that to a temporary file
+//! on the background executor, so that the full output can still be opened in an editor.
+
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use futures::{StreamExt as _, channel::mpsc};
+use gpui::{Task, WeakEntity};
+use ui::{Tooltip, prelude::*};
+use util::{ResultExt as _, size::format_file_size};
+use workspace::{OpenOptions, OpenVisible, Workspace};
+
+use crate::repl_settings::ReplSettings;
+
+/// Tracks how much stream text an execution has kept, and splits off the text that's over the
+/// byte and line limits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamLimit {
+    max_bytes: usize,
+    max_lines: usize,
+    stored_bytes: usize,
+    stored_lines: usize,
+    exceeded: bool,
+}
+
+impl StreamLimit {
+    pub fn new(max_bytes: usize, max_lines: usize) -> Self {
+        Self {
+            max_bytes,
+            max_lines,
+            stored_bytes: 0,
+            stored_lines: 0,
+            exceeded: false,
+        }
+    }
+
+    pub fn from_settings(settings: &ReplSettings) -> Self {
+        Self::new(settings.output_max_bytes, settings.output_max_lines)
+    }
+
+    /// Splits `text` into the part that still fits within the limits and the part that doesn't.
+    ///
+    /// Once a chunk has been cut, every later chunk is suppressed in full, so that the kept
+    /// output is a prefix of what the kernel printed.
+    pub fn split<'a>(&mut self, text: &'a str) -> (&'a str, &'a str) {
+        if self.exceeded {
+            return ("", text);
+        }
+
+        let remaining_bytes = self.max_bytes.saturating_sub(self.stored_bytes);
+        let mut kept_len = text.floor_char_boundary(remaining_bytes.min(text.len()));
+        let remaining_lines = self.max_lines.saturating_sub(self.stored_lines);
+        if remaining_lines == 0 {
+            kept_len = 0;
+        } else if let Some((newline_ix, _)) = text[..kept_len]
+            .match_indices('\n')
+            .nth(remaining_lines - 1)
+        {
+            kept_len = newline_ix + 1;
+        }
+
+        let (kept, suppressed) = text.split_at(kept_len);
+        self.stored_bytes += kept.len();
+        self.stored_lines += kept.matches('\n').count();
+        self.exceeded = !suppressed.is_empty();
+        (kept, suppressed)
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+/// The output of an execution that didn't fit within its [`StreamLimit`], written to a
+/// temporary file that's deleted when the spool is dropped.
+pub struct OutputSpool {
+    chunks_tx: mpsc::UnboundedSender<String>,
+    path: Option<PathBuf>,
+    suppressed_bytes: usize,
+    workspace: WeakEntity<Workspace>,
+    _spool_task: Task<()>,
+}
+
+impl OutputSpool {
+    /// Starts a spool whose file begins with `kept_text`, the output that's still shown in the
+    /// execution view, so that the file holds the full output.
+    pub fn new(
+        kept_text: String,
+        workspace: WeakEntity<Workspace>,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let (chunks_tx, chunks_rx) = mpsc::unbounded();
+        let spool_task = cx.spawn(async move |this, cx| {
+            Self::write_chunks(kept_text, chunks_rx, this, cx)
+                .await
+                .log_err();
+        });
+
+        Self {
+            chunks_tx,
+            path: None,
+            suppressed_bytes: 0,
+            workspace,
+            _spool_task: spool_task,
+        }
+    }
+
+    async fn write_chunks(
+        kept_text: String,
+        mut chunks_rx: mpsc::UnboundedReceiver<String>,
+        this: WeakEntity<Self>,
+        cx: &mut gpui::AsyncApp,
+    ) -> Result<()> {
+        let mut file = cx
+            .background_spawn(async move {
+                let mut file = tempfile::Builder::new()
+                    .prefix("repl-output-")
+                    .suffix(".txt")
+                    .tempfile()
+                    .context("creating REPL output spool file")?;
+                file.write_all(kept_text.as_bytes())?;
+                anyhow::Ok(file)
+            })
+            .await?;
+        let path = file.path().to_path_buf();
+        this.update(cx, |this, cx| {
+            this.path = Some(path);
+            cx.notify();
+        })?;
+
+        while let Some(chunk) = chunks_rx.next().await {
+            // Everything that arrived while the previous batch was being written goes out in a
+            // single write and a single re-render.
+            let mut chunks = vec![chunk];
+            while let Ok(Some(chunk)) = chunks_rx.try_next() {
+                chunks.push(chunk);
+            }
+
+            let (returned_file, written_bytes) = cx
+                .background_spawn(async move {
+                    let mut written_bytes = 0;
+                    for chunk in &chunks {
+                        file.write_all(chunk.as_bytes())?;
+                        written_bytes += chunk.len();
+                    }
+                    file.flush()?;
+                    anyhow::Ok((file, written_bytes))
+                })
+                .await?;
+            file = returned_file;
+
+            this.update(cx, |this, cx| {
+                this.suppressed_bytes += written_bytes;
+                cx.notify();
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Queues text that's over the limit to be written to the spool file.
+    pub fn push(&self, text: String) {
+        self.chunks_tx.unbounded_send(text).ok();
+    }
+
+    /// The spool file, once it has been created.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// How many bytes have been written to the spool file beyond the output that's shown.
+    pub fn suppressed_bytes(&self) -> usize {
+        self.suppressed_bytes
+    }
+
+    fn open_full_output(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace
+                    .open_abs_path(
+                        path,
+                        OpenOptions {
+                            visible: Some(OpenVisible::None),
+                            ..Default::default()
+                        },
+                        window,
+                        cx,
+                    )
+                    .detach_and_log_err(cx);
+            })
+            .log_err();
+    }
+}
+
+impl Render for OutputSpool {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        h_flex()
+            .gap_2()
+            .py_1()
+            .child(
+                Icon::new(IconName::Warning)
+                    .size(IconSize::Small)
+                    .color(Color::Warning),
+            )
+            .child(
+                Label::new(format!(
+                    "Output truncated ({} suppressed)",
+                    format_file_size(self.suppressed_bytes as u64, true)
+                ))
+                .size(LabelSize::Small)
+                .color(Color::Muted),
+            )
+            .child(
+                Button::new("open-full-output", "Open Full Output")
+                    .label_size(LabelSize::Small)
+                    .disabled(self.path.is_none())
+                    .tooltip(Tooltip::text("Open the full output in an editor"))
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.open_full_output(window, cx);
+                    })),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_limit_bytes() {
+        let mut limit = StreamLimit::new(10, 100);
+        assert_eq!(limit.split("01234"), ("01234", ""));
+        assert_eq!(limit.split("56789"), ("56789", ""));
+        assert!(!limit.is_exceeded());
+
+        assert_eq!(limit.split("a"), ("", "a"));
+        assert!(limit.is_exceeded());
+        assert_eq!(limit.split("b"), ("", "b"));
+    }
+
+    #[test]
+    fn test_stream_limit_does_not_split_characters() {
+        let mut limit = StreamLimit::new(4, 100);
+        assert_eq!(limit.split("日本"), ("日", "本"));
+
+        // Text after a cut is suppressed even when it would fit.
+        assert_eq!(limit.split("a"), ("", "a"));
+    }
+
+    #[test]
+    fn test_stream_limit_lines() {
+        let mut limit = StreamLimit::new(1024, 2);
+        assert_eq!(limit.split("one\n"), ("one\n", ""));
+        assert_eq!(
+            limit.split("two\nthree\nfour\n"),
+            ("two\n", "three\nfour\n")
+        );
+        assert!(limit.is_exceeded());
+
+        // A line without a trailing newline would start a third line, so it's cut too.
+        let mut limit = StreamLimit::new(1024, 1);
+        assert_eq!(limit.split("one\n"), ("one\n", ""));
+        assert!(!limit.is_exceeded());
+        assert_eq!(limit.split("two"), ("", "two"));
+    }
+}
@@ -1,7 +1,7 @@
-use gpui::{AnyElement, App, Entity, FontWeight, Window};
+use gpui::{AnyElement, App, Entity, FontWeight, StyledText, Window};
 use ui::{Label, h_flex, prelude::*, v_flex};
 
-use crate::outputs::plain::TerminalOutput;
+use crate::outputs::{ansi::AnsiText, plain::TerminalOutput};
 
 /// Userspace error from the kernel
 #[derive(Clone)]
@@ -12,8 +12,21 @@ pub struct ErrorView {
 }
 
 impl ErrorView {
+    /// The error and its traceback as plain text, for copying.
+    pub fn full_text(&self, cx: &App) -> String {
+        format!(
+            "{}: {}\n{}",
+            self.ename,
+            AnsiText::parse(&self.evalue).text,
+            self.traceback.read(cx).full_text()
+        )
+    }
+
     pub fn render(&self, window: &mut Window, cx: &mut App) -> Option<AnyElement> {
         let theme = cx.theme();
+        // Some kernels color the error message the same way as the traceback.
+        let evalue = AnsiText::parse(&self.evalue);
+        let evalue_highlights = evalue.highlights(theme);
 
         let padding = window.line_height() / 2.;
 
@@ -28,7 +41,9 @@ impl ErrorView {
                                 .color(Color::Error)
                                 .weight(FontWeight::BOLD),
                         )
-                        .child(Label::new(self.evalue.clone()).weight(FontWeight::BOLD)),
+                        .child(div().font_weight(FontWeight::BOLD).child(
+                            StyledText::new(evalue.text).with_highlights(evalue_highlights),
+                        )),
                 )
                 .child(
                     div()
@@ -1,5 +1,12 @@
 pub mod components;
+mod execution_history;
+mod execution_kind;
+mod execution_timing;
+mod execution_watchdog;
 mod jupyter_settings;
+mod kernel_debug_adapter;
+mod kernel_debugger;
+mod kernel_status;
 pub mod kernels;
 pub mod notebook;
 mod outputs;
@@ -12,26 +19,38 @@ mod session;
 use std::{sync::Arc, time::Duration};
 
 use async_dispatcher::{Dispatcher, Runnable, set_dispatcher};
-use gpui::{App, PlatformDispatcher, Priority, RunnableMeta};
+use dap::DapRegistry;
+use gpui::{App, BorrowAppContext as _, PlatformDispatcher, Priority, RunnableMeta};
 use project::Fs;
 pub use runtimelib::ExecutionState;
 
 pub use crate::jupyter_settings::JupyterSettings;
+pub use crate::kernel_status::{
+    KernelCommand, KernelOwner, KernelSessions, KernelStatusItem, KernelSummary, run_kernel_command,
+};
 pub use crate::kernels::{Kernel, KernelSpecification, KernelStatus, PythonEnvKernelSpecification};
 pub use crate::repl_editor::*;
 pub use crate::repl_sessions_ui::{
-    ClearCurrentOutput, ClearOutputs, Interrupt, ReplSessionsPage, Restart, Run, Sessions, Shutdown,
+    ClearCurrentOutput, ClearOutputs, CopySessionAsScript, DebugKernel, InterruptKernel,
+    ReplSessionsPage, RestartKernel, Run, RunCellAndAdvance, RunFile, RunSelection, Sessions,
+    ShowHistory, ShutdownKernel,
 };
 pub use crate::repl_settings::ReplSettings;
 pub use crate::repl_store::ReplStore;
 pub use crate::session::Session;
 
+use crate::kernel_debug_adapter::JupyterKernelDebugAdapter;
+
 pub const KERNEL_DOCS_URL: &str = "https://zed.dev/docs/repl#changing-kernels";
 
 pub fn init(fs: Arc<dyn Fs>, cx: &mut App) {
     set_dispatcher(zed_dispatcher(cx));
     repl_sessions_ui::init(cx);
     ReplStore::init(fs, cx);
+    KernelSessions::init(cx);
+    cx.update_default_global(|registry: &mut DapRegistry, _cx| {
+        registry.add_adapter(Arc::new(JupyterKernelDebugAdapter));
+    });
 }
 
 fn zed_dispatcher(cx: &mut App) -> impl Dispatcher {
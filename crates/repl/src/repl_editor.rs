@@ -1,21 +1,28 @@
 //! REPL operations on an [`Editor`].
 
 use std::ops::Range;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use anyhow::{Context as _, Result};
-use editor::{Editor, MultiBufferOffset};
-use gpui::{App, Entity, WeakEntity, Window, prelude::*};
+use editor::{Anchor, Editor, MultiBufferOffset, MultiBufferSnapshot};
+use futures::{StreamExt as _, channel::mpsc};
+use gpui::{App, AsyncApp, Entity, WeakEntity, Window, prelude::*};
 use language::{BufferSnapshot, Language, LanguageName, Point};
 use project::{ProjectItem as _, WorktreeId};
-use workspace::{Workspace, notifications::NotificationId};
+use util::ResultExt as _;
+use workspace::{
+    Toast, Workspace,
+    notifications::{NotificationId, simple_message_notification::MessageNotification},
+};
 
-use crate::kernels::PythonEnvKernelSpecification;
+use crate::components::ExecutionHistoryPicker;
+use crate::kernels::{PythonEnvKernelSpecification, SystemCommandRunner, install_ipykernel};
 use crate::repl_store::ReplStore;
 use crate::session::SessionEvent;
 use crate::{
-    ClearCurrentOutput, ClearOutputs, Interrupt, JupyterSettings, KernelSpecification, Restart,
-    Session, Shutdown,
+    ClearCurrentOutput, CopySessionAsScript, DebugKernel, JupyterSettings, KernelCommand,
+    KernelSpecification, Session, ShowHistory, run_kernel_command,
 };
 
 pub fn assign_kernelspec(
@@ -75,67 +82,127 @@ pub fn assign_kernelspec(
     Ok(())
 }
 
+/// Assigns the kernel to the editor, first offering to install ipykernel if it's a Python
+/// environment without it.
 pub fn install_ipykernel_and_assign(
     kernel_specification: KernelSpecification,
     weak_editor: WeakEntity<Editor>,
     window: &mut Window,
     cx: &mut App,
 ) -> Result<()> {
-    let KernelSpecification::PythonEnv(ref env_spec) = kernel_specification else {
-        return assign_kernelspec(kernel_specification, weak_editor, window, cx);
+    match kernel_specification {
+        KernelSpecification::PythonEnv(env_spec) if !env_spec.has_ipykernel => {
+            offer_ipykernel_install(
+                env_spec,
+                move |kernel_specification, window, cx| {
+                    assign_kernelspec(kernel_specification, weak_editor.clone(), window, cx)
+                        .log_err();
+                },
+                window,
+                cx,
+            );
+            Ok(())
+        }
+        kernel_specification => assign_kernelspec(kernel_specification, weak_editor, window, cx),
+    }
+}
+
+/// Shows a notification offering to install ipykernel into the environment, which calls
+/// `on_installed` with the updated kernel once the install succeeds.
+pub fn offer_ipykernel_install(
+    env_spec: PythonEnvKernelSpecification,
+    on_installed: impl Fn(KernelSpecification, &mut Window, &mut App) + 'static,
+    window: &mut Window,
+    cx: &mut App,
+) {
+    let Some(workspace) = Workspace::for_window(window, cx) else {
+        return;
     };
 
-    let python_path = env_spec.path.clone();
-    let env_name = env_spec.name.clone();
-    let env_spec = env_spec.clone();
+    struct IpykernelInstallOffer;
+    let on_installed = Rc::new(on_installed);
+    workspace.update(cx, |workspace, cx| {
+        workspace.show_toast(
+            Toast::new(
+                NotificationId::unique::<IpykernelInstallOffer>(),
+                format!(
+                    "{} doesn't have ipykernel installed, so it can't run as a kernel yet.",
+                    env_spec.name
+                ),
+            )
+            .on_click(
+                "Install ipykernel into this environment",
+                move |window, cx| {
+                    let on_installed = on_installed.clone();
+                    install_ipykernel_in_background(
+                        env_spec.clone(),
+                        move |kernel_specification, window, cx| {
+                            on_installed(kernel_specification, window, cx)
+                        },
+                        window,
+                        cx,
+                    );
+                },
+            ),
+            cx,
+        );
+    });
+}
 
+fn install_ipykernel_in_background(
+    env_spec: PythonEnvKernelSpecification,
+    on_installed: impl FnOnce(KernelSpecification, &mut Window, &mut App) + 'static,
+    window: &mut Window,
+    cx: &mut App,
+) {
     struct IpykernelInstall;
     let notification_id = NotificationId::unique::<IpykernelInstall>();
-
-    let workspace = Workspace::for_window(window, cx);
-    if let Some(workspace) = &workspace {
-        workspace.update(cx, |workspace, cx| {
-            workspace.show_toast(
-                workspace::Toast::new(
-                    notification_id.clone(),
-                    format!("Installing ipykernel in {}...", env_name),
-                ),
-                cx,
-            );
-        });
-    }
-
-    let weak_workspace = workspace.map(|w| w.downgrade());
+    let env_name = env_spec.name.clone();
+    let weak_workspace = Workspace::for_window(window, cx).map(|workspace| workspace.downgrade());
     let window_handle = window.window_handle();
 
-    let install_task = cx.background_spawn(async move {
-        let output = util::command::new_command(python_path.to_string_lossy().as_ref())
-            .args(&["-m", "pip", "install", "ipykernel"])
-            .output()
-            .await
-            .context("failed to run pip install ipykernel")?;
-
-        if output.status.success() {
-            anyhow::Ok(())
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("{}", stderr.lines().last().unwrap_or("unknown error"))
+    let show_progress = {
+        let notification_id = notification_id.clone();
+        let env_name = env_name.clone();
+        move |workspace: &WeakEntity<Workspace>, line: Option<&str>, cx: &mut AsyncApp| {
+            let message = match line {
+                Some(line) => format!("Installing ipykernel in {env_name}: {line}"),
+                None => format!("Installing ipykernel in {env_name}..."),
+            };
+            workspace
+                .update(cx, |workspace, cx| {
+                    workspace.show_toast(Toast::new(notification_id.clone(), message), cx);
+                })
+                .ok();
         }
+    };
+
+    let (output_tx, mut output_rx) = mpsc::unbounded();
+    let install_task = cx.background_spawn({
+        let env_spec = env_spec.clone();
+        async move { install_ipykernel(&env_spec, &SystemCommandRunner, output_tx).await }
     });
 
     cx.spawn(async move |cx| {
-        let result = install_task.await;
+        if let Some(workspace) = &weak_workspace {
+            show_progress(workspace, None, cx);
+        }
+        while let Some(line) = output_rx.next().await {
+            log::info!("ipykernel install in {env_name}: {line}");
+            if let Some(workspace) = &weak_workspace {
+                show_progress(workspace, Some(line.trim()), cx);
+            }
+        }
 
-        match result {
+        match install_task.await {
             Ok(()) => {
-                if let Some(weak_workspace) = &weak_workspace {
-                    weak_workspace
+                if let Some(workspace) = &weak_workspace {
+                    workspace
                         .update(cx, |workspace, cx| {
-                            workspace.dismiss_toast(&notification_id, cx);
                             workspace.show_toast(
-                                workspace::Toast::new(
+                                Toast::new(
                                     notification_id.clone(),
-                                    format!("ipykernel installed in {}", env_name),
+                                    format!("ipykernel installed in {env_name}"),
                                 )
                                 .autohide(),
                                 cx,
@@ -144,32 +211,38 @@ pub fn install_ipykernel_and_assign(
                         .ok();
                 }
 
+                let kernel_specification =
+                    KernelSpecification::PythonEnv(PythonEnvKernelSpecification {
+                        has_ipykernel: true,
+                        ..env_spec
+                    });
                 window_handle
                     .update(cx, |_, window, cx| {
-                        let updated_spec =
-                            KernelSpecification::PythonEnv(PythonEnvKernelSpecification {
-                                has_ipykernel: true,
-                                ..env_spec
-                            });
-                        assign_kernelspec(updated_spec, weak_editor, window, cx).ok();
+                        on_installed(kernel_specification, window, cx)
                     })
                     .ok();
             }
             Err(error) => {
-                if let Some(weak_workspace) = &weak_workspace {
-                    weak_workspace
+                log::error!("failed to install ipykernel in {env_name}: {error}");
+                let mut message = error.message;
+                if let Some(suggestion) = error.suggestion {
+                    message = format!("{message}\n\n{suggestion}");
+                }
+                let output = error.output.trim();
+                if !output.is_empty() {
+                    message = format!("{message}\n\n{output}");
+                }
+                if let Some(workspace) = &weak_workspace {
+                    workspace
                         .update(cx, |workspace, cx| {
                             workspace.dismiss_toast(&notification_id, cx);
-                            workspace.show_toast(
-                                workspace::Toast::new(
-                                    notification_id.clone(),
-                                    format!(
-                                        "Failed to install ipykernel in {}: {}",
-                                        env_name, error
-                                    ),
-                                ),
-                                cx,
-                            );
+                            workspace.show_notification(notification_id.clone(), cx, |cx| {
+                                cx.new(|cx| {
+                                    MessageNotification::new(message, cx).with_title(format!(
+                                        "Failed to install ipykernel in {env_name}"
+                                    ))
+                                })
+                            });
                         })
                         .ok();
                 }
@@ -177,8 +250,18 @@ pub fn install_ipykernel_and_assign(
         }
     })
     .detach();
+}
 
-    Ok(())
+/// Which code around the cursor a run action sends to the kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunScope {
+    /// The Jupytext cells, Markdown code blocks, or lines that the selection touches.
+    Cells,
+    /// The selected text, or the line at the cursor when nothing is selected.
+    Selection,
+    /// The `# %%` cell at the cursor, which is the whole file when it has no cell markers.
+    JupytextCell,
+    File,
 }
 
 pub fn run(
@@ -186,6 +269,18 @@ pub fn run(
     move_down: bool,
     window: &mut Window,
     cx: &mut App,
+) -> Result<()> {
+    run_scope(editor, RunScope::Cells, move_down, window, cx)
+}
+
+/// Runs the code in `scope` in the editor's session, starting one with the kernel chosen for the
+/// file's worktree if needed. With `move_down`, the cursor then moves past the code that ran.
+pub fn run_scope(
+    editor: WeakEntity<Editor>,
+    scope: RunScope,
+    move_down: bool,
+    window: &mut Window,
+    cx: &mut App,
 ) -> Result<()> {
     let store = ReplStore::global(cx);
     if !store.read(cx).is_enabled() {
@@ -209,18 +304,39 @@ pub fn run(
         return Ok(());
     };
 
-    let (runnable_ranges, next_cell_point) =
-        runnable_ranges(&buffer.read(cx).snapshot(), selected_range, cx);
+    // A selection is run where it is, since moving the cursor would lose it.
+    let move_down = move_down && (scope != RunScope::Selection || selected_range.is_empty());
+    let buffer_snapshot = buffer.read(cx).snapshot();
+    let (runnable_ranges, next_cell_point) = match scope {
+        RunScope::Cells => runnable_ranges(&buffer_snapshot, selected_range, cx),
+        RunScope::Selection => selection_or_line(&buffer_snapshot, selected_range),
+        RunScope::JupytextCell => {
+            let (cell, next_cell) = jupytext_cell_at(&buffer_snapshot, selected_range.start.row);
+            (vec![cell], next_cell)
+        }
+        RunScope::File => (
+            vec![cell_range(
+                &buffer_snapshot,
+                0,
+                buffer_snapshot.max_point().row,
+            )],
+            None,
+        ),
+    };
 
     for runnable_range in runnable_ranges {
         let Some(language) = multibuffer.read(cx).language_at(runnable_range.start, cx) else {
             continue;
         };
 
-        let kernel_specification = store
-            .read(cx)
-            .active_kernelspec(project_path.worktree_id, Some(language.clone()), cx)
-            .with_context(|| format!("No kernel found for language: {}", language.name()))?;
+        let Some(kernel_specification) =
+            store
+                .read(cx)
+                .active_kernelspec(project_path.worktree_id, Some(language.clone()), cx)
+        else {
+            show_kernel_needed_toast(&language, window, cx);
+            anyhow::bail!("No kernel found for language: {}", language.name());
+        };
 
         let fs = store.read(cx).fs().clone();
 
@@ -263,8 +379,7 @@ pub fn run(
             selected_text = snapshot
                 .text_for_range(runnable_range.clone())
                 .collect::<String>();
-            anchor_range = snapshot.anchor_before(runnable_range.start)
-                ..snapshot.anchor_after(runnable_range.end);
+            anchor_range = execution_anchor_range(&snapshot, runnable_range);
             next_cursor = next_cell_point.map(|point| snapshot.anchor_after(point));
         }
 
@@ -283,6 +398,31 @@ pub fn run(
     anyhow::Ok(())
 }
 
+/// Anchors for code that was sent to the kernel, which its output is shown below. They grow to
+/// include text typed at either end, so the output stays with the code as it's edited.
+fn execution_anchor_range(snapshot: &MultiBufferSnapshot, range: Range<Point>) -> Range<Anchor> {
+    snapshot.anchor_before(range.start)..snapshot.anchor_after(range.end)
+}
+
+fn show_kernel_needed_toast(language: &Language, window: &mut Window, cx: &mut App) {
+    struct KernelNeeded;
+    let Some(workspace) = Workspace::for_window(window, cx) else {
+        return;
+    };
+    workspace.update(cx, |workspace, cx| {
+        workspace.show_toast(
+            workspace::Toast::new(
+                NotificationId::unique::<KernelNeeded>(),
+                format!(
+                    "No {} kernel is selected for this project. Choose one from the REPL menu in the toolbar.",
+                    language.name()
+                ),
+            ),
+            cx,
+        );
+    });
+}
+
 pub enum SessionSupport {
     ActiveSession(Entity<Session>),
     Inactive(KernelSpecification),
@@ -388,10 +528,7 @@ pub fn shutdown(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
         return;
     };
 
-    session.update(cx, |session, cx| {
-        session.shutdown(window, cx);
-        cx.notify();
-    });
+    run_kernel_command(&session.downgrade(), KernelCommand::Shutdown, window, cx);
 }
 
 pub fn restart(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
@@ -409,26 +546,106 @@ pub fn restart(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
         return;
     };
 
-    session.update(cx, |session, cx| {
-        session.restart(window, cx);
-        cx.notify();
+    run_kernel_command(&session.downgrade(), KernelCommand::Restart, window, cx);
+}
+
+pub fn debug_kernel(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
+    let Some(editor) = editor.upgrade() else {
+        return;
+    };
+    let Some(workspace) = editor.read(cx).workspace() else {
+        return;
+    };
+    let Some(session) = ReplStore::global(cx)
+        .read(cx)
+        .get_session(editor.entity_id())
+        .cloned()
+    else {
+        return;
+    };
+
+    let result = session.update(cx, |session, cx| {
+        session.start_debugging(workspace.downgrade(), window, cx)
     });
+    if let Err(error) = result {
+        struct KernelNotDebuggable;
+        workspace.update(cx, |workspace, cx| {
+            workspace.show_toast(
+                Toast::new(
+                    NotificationId::unique::<KernelNotDebuggable>(),
+                    error.to_string(),
+                ),
+                cx,
+            );
+        });
+    }
 }
 
-pub fn setup_editor_session_actions(editor: &mut Editor, editor_handle: WeakEntity<Editor>) {
-    editor
-        .register_action({
-            let editor_handle = editor_handle.clone();
-            move |_: &ClearOutputs, _, cx| {
-                if !JupyterSettings::enabled(cx) {
-                    return;
-                }
+pub fn show_history(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
+    let Some(editor_entity) = editor.upgrade() else {
+        return;
+    };
+    let Some(workspace) = editor_entity.read(cx).workspace() else {
+        return;
+    };
+    let Some(session) = ReplStore::global(cx)
+        .read(cx)
+        .get_session(editor_entity.entity_id())
+        .cloned()
+    else {
+        return;
+    };
 
-                crate::clear_outputs(editor_handle.clone(), cx);
-            }
+    workspace.update(cx, |workspace, cx| {
+        workspace.toggle_modal(window, cx, |window, cx| {
+            ExecutionHistoryPicker::new(session, editor, window, cx)
+        });
+    });
+}
+
+/// Opens the code that ran successfully in the editor's session in a new buffer, in the
+/// editor's language.
+pub fn copy_session_as_script(editor: WeakEntity<Editor>, window: &mut Window, cx: &mut App) {
+    let Some(editor) = editor.upgrade() else {
+        return;
+    };
+    let Some(workspace) = editor.read(cx).workspace() else {
+        return;
+    };
+    let Some(session) = ReplStore::global(cx)
+        .read(cx)
+        .get_session(editor.entity_id())
+        .cloned()
+    else {
+        return;
+    };
+
+    let script = session.read(cx).history().script();
+    let language = editor
+        .read(cx)
+        .buffer()
+        .read(cx)
+        .as_singleton()
+        .and_then(|buffer| buffer.read(cx).language().cloned());
+    let project = workspace.read(cx).project().clone();
+    window
+        .spawn(cx, async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.create_buffer(language, true, cx))
+                .await?;
+            buffer.update(cx, |buffer, cx| buffer.set_text(script, cx));
+
+            workspace.update_in(cx, |workspace, window, cx| {
+                let editor =
+                    cx.new(|cx| Editor::for_buffer(buffer, Some(project.clone()), window, cx));
+                workspace.add_item_to_active_pane(Box::new(editor), None, true, window, cx);
+            })?;
+            anyhow::Ok(())
         })
-        .detach();
+        .detach_and_log_err(cx);
+}
 
+pub fn setup_editor_session_actions(editor: &mut Editor, editor_handle: WeakEntity<Editor>) {
     editor
         .register_action({
             let editor_handle = editor_handle.clone();
@@ -445,12 +662,12 @@ pub fn setup_editor_session_actions(editor: &mut Editor, editor_handle: WeakEnti
     editor
         .register_action({
             let editor_handle = editor_handle.clone();
-            move |_: &Interrupt, _, cx| {
+            move |_: &DebugKernel, window, cx| {
                 if !JupyterSettings::enabled(cx) {
                     return;
                 }
 
-                crate::interrupt(editor_handle.clone(), cx);
+                crate::debug_kernel(editor_handle.clone(), window, cx);
             }
         })
         .detach();
@@ -458,26 +675,23 @@ pub fn setup_editor_session_actions(editor: &mut Editor, editor_handle: WeakEnti
     editor
         .register_action({
             let editor_handle = editor_handle.clone();
-            move |_: &Shutdown, window, cx| {
+            move |_: &ShowHistory, window, cx| {
                 if !JupyterSettings::enabled(cx) {
                     return;
                 }
 
-                crate::shutdown(editor_handle.clone(), window, cx);
+                crate::show_history(editor_handle.clone(), window, cx);
             }
         })
         .detach();
 
     editor
-        .register_action({
-            let editor_handle = editor_handle;
-            move |_: &Restart, window, cx| {
-                if !JupyterSettings::enabled(cx) {
-                    return;
-                }
-
-                crate::restart(editor_handle.clone(), window, cx);
+        .register_action(move |_: &CopySessionAsScript, window, cx| {
+            if !JupyterSettings::enabled(cx) {
+                return;
             }
+
+            crate::copy_session_as_script(editor_handle.clone(), window, cx);
         })
         .detach();
 }
@@ -490,6 +704,62 @@ fn cell_range(buffer: &BufferSnapshot, start_row: u32, end_row: u32) -> Range<Po
     Point::new(start_row, 0)..Point::new(snippet_end_row, buffer.line_len(snippet_end_row))
 }
 
+/// The prefixes of lines that start a Jupytext cell, like `# %%`, in the buffer's language.
+fn jupytext_prefixes(buffer: &BufferSnapshot) -> Vec<String> {
+    let Some(language) = buffer.language() else {
+        return Vec::new();
+    };
+    language
+        .default_scope()
+        .line_comment_prefixes()
+        .iter()
+        .map(|comment_prefix| format!("{comment_prefix}%%"))
+        .collect()
+}
+
+/// Returns the `# %%` cell containing `row`, from its marker up to the next one, and where the
+/// next cell starts. Lines before the first marker form a cell of their own, and a file without
+/// markers is a single cell.
+fn jupytext_cell_at(buffer: &BufferSnapshot, row: u32) -> (Range<Point>, Option<Point>) {
+    let jupytext_prefixes = jupytext_prefixes(buffer);
+    let is_marker = |row: u32| {
+        jupytext_prefixes
+            .iter()
+            .any(|prefix| buffer.contains_str_at(Point::new(row, 0), prefix))
+    };
+
+    let max_row = buffer.max_point().row;
+    let start_row = (0..=row).rev().find(|row| is_marker(*row)).unwrap_or(0);
+    let next_cell_row = (row + 1..=max_row).find(|row| is_marker(*row));
+    let end_row = next_cell_row.map_or(max_row, |next_cell_row| next_cell_row - 1);
+    (
+        cell_range(buffer, start_row, end_row),
+        next_cell_row.map(|row| Point::new(row, 0)),
+    )
+}
+
+/// Returns the selection, or the line at the cursor and the next line with code on it when the
+/// selection is empty. Blank lines at the cursor are skipped, so that running line by line steps
+/// over them.
+fn selection_or_line(
+    buffer: &BufferSnapshot,
+    selection: Range<Point>,
+) -> (Vec<Range<Point>>, Option<Point>) {
+    if !selection.is_empty() {
+        return (vec![selection], None);
+    }
+
+    let max_row = buffer.max_point().row;
+    let Some(row) = (selection.start.row..=max_row).find(|row| !buffer.is_line_blank(*row)) else {
+        return (Vec::new(), None);
+    };
+    let next_row = (row + 1..=max_row).find(|row| !buffer.is_line_blank(*row));
+    (
+        vec![Point::new(row, 0)..Point::new(row, buffer.line_len(row))],
+        next_row.map(|row| Point::new(row, 0)),
+    )
+}
+
 // Returns the ranges of the snippets in the buffer and the next point for moving the cursor to
 fn jupytext_cells(
     buffer: &BufferSnapshot,
@@ -497,21 +767,11 @@ fn jupytext_cells(
 ) -> (Vec<Range<Point>>, Option<Point>) {
     let mut current_row = range.start.row;
 
-    let Some(language) = buffer.language() else {
-        return (Vec::new(), None);
-    };
-
-    let default_scope = language.default_scope();
-    let comment_prefixes = default_scope.line_comment_prefixes();
-    if comment_prefixes.is_empty() {
+    let jupytext_prefixes = jupytext_prefixes(buffer);
+    if jupytext_prefixes.is_empty() {
         return (Vec::new(), None);
     }
 
-    let jupytext_prefixes = comment_prefixes
-        .iter()
-        .map(|comment_prefix| format!("{comment_prefix}%%"))
-        .collect::<Vec<_>>();
-
     let mut snippet_start_row = None;
     loop {
         if jupytext_prefixes
@@ -813,7 +1073,7 @@ mod tests {
 
     #[gpui::test]
     fn test_markdown_code_blocks(cx: &mut App) {
-        use crate::kernels::LocalKernelSpecification;
+        use crate::kernels::{LocalKernelSource, LocalKernelSpecification};
         use jupyter_protocol::JupyterKernelspec;
 
         // Initialize settings
@@ -838,6 +1098,7 @@ mod tests {
                     env: None,
                 },
                 path: std::path::PathBuf::new(),
+                source: LocalKernelSource::Kernelspec,
             });
 
             let python_spec = KernelSpecification::Jupyter(LocalKernelSpecification {
@@ -851,6 +1112,7 @@ mod tests {
                     env: None,
                 },
                 path: std::path::PathBuf::new(),
+                source: LocalKernelSource::Kernelspec,
             });
 
             store.set_kernel_specs_for_testing(vec![typescript_spec, python_spec], cx);
@@ -1063,4 +1325,155 @@ mod tests {
         let (snippets, _) = runnable_ranges(&snapshot, Point::new(1, 0)..Point::new(1, 0), cx);
         assert!(snippets.is_empty());
     }
+
+    fn test_language() -> Arc<Language> {
+        Arc::new(Language::new(
+            LanguageConfig {
+                name: "TestLang".into(),
+                line_comments: vec!["# ".into()],
+                ..Default::default()
+            },
+            None,
+        ))
+    }
+
+    #[gpui::test]
+    fn test_jupytext_cell_at(cx: &mut App) {
+        let buffer = cx.new(|cx| {
+            Buffer::local(
+                indoc! { r#"
+                    import math
+
+                    # %%
+                    x = math.pi
+
+                    # %% Plot it
+                    print(x)
+                    print(x * 2)
+
+
+                "# },
+                cx,
+            )
+            .with_language(test_language(), cx)
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        let cell_at = |row| {
+            let (range, next_cell) = jupytext_cell_at(&snapshot, row);
+            (
+                snapshot.text_for_range(range).collect::<String>(),
+                next_cell,
+            )
+        };
+
+        // Lines before the first marker are a cell of their own.
+        assert_eq!(
+            cell_at(0),
+            ("import math".to_string(), Some(Point::new(2, 0)))
+        );
+        assert_eq!(
+            cell_at(2),
+            ("# %%\nx = math.pi".to_string(), Some(Point::new(5, 0)))
+        );
+        assert_eq!(
+            cell_at(4),
+            ("# %%\nx = math.pi".to_string(), Some(Point::new(5, 0)))
+        );
+        // The last cell runs to the end of the file, without its trailing blank lines.
+        assert_eq!(
+            cell_at(9),
+            ("# %% Plot it\nprint(x)\nprint(x * 2)".to_string(), None)
+        );
+
+        // A file without markers is one cell.
+        let buffer = cx.new(|cx| {
+            Buffer::local("print(1)\n\nprint(2)\n", cx).with_language(test_language(), cx)
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        let (range, next_cell) = jupytext_cell_at(&snapshot, 2);
+        assert_eq!(
+            snapshot.text_for_range(range).collect::<String>(),
+            "print(1)\n\nprint(2)"
+        );
+        assert_eq!(next_cell, None);
+    }
+
+    #[gpui::test]
+    fn test_selection_or_line(cx: &mut App) {
+        let buffer = cx.new(|cx| {
+            Buffer::local("x = 1\n\n\ny = x + 1\nprint(y)\n", cx).with_language(test_language(), cx)
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        let run = |selection: Range<Point>| {
+            let (ranges, next_line) = selection_or_line(&snapshot, selection);
+            let texts = ranges
+                .into_iter()
+                .map(|range| snapshot.text_for_range(range).collect::<String>())
+                .collect::<Vec<_>>();
+            (texts, next_line)
+        };
+
+        // Running a line advances past the blank lines after it.
+        assert_eq!(
+            run(Point::new(0, 2)..Point::new(0, 2)),
+            (vec!["x = 1".to_string()], Some(Point::new(3, 0)))
+        );
+        // A cursor on a blank line runs the next line with code on it.
+        assert_eq!(
+            run(Point::new(1, 0)..Point::new(1, 0)),
+            (vec!["y = x + 1".to_string()], Some(Point::new(4, 0)))
+        );
+        assert_eq!(
+            run(Point::new(4, 0)..Point::new(4, 0)),
+            (vec!["print(y)".to_string()], None)
+        );
+        assert_eq!(run(Point::new(5, 0)..Point::new(5, 0)), (Vec::new(), None));
+
+        // A selection is run exactly as selected.
+        assert_eq!(
+            run(Point::new(3, 4)..Point::new(4, 5)),
+            (vec!["x + 1\nprint".to_string()], None)
+        );
+    }
+
+    #[gpui::test]
+    fn test_execution_anchors_move_with_edits(cx: &mut App) {
+        let buffer = cx.new(|cx| {
+            Buffer::local("# %%\nx = 1\n# %%\ny = 2\n", cx).with_language(test_language(), cx)
+        });
+        let multibuffer = cx.new(|cx| multi_buffer::MultiBuffer::singleton(buffer.clone(), cx));
+        let (cell, _) = jupytext_cell_at(&buffer.read(cx).snapshot(), 3);
+        let anchor_range = execution_anchor_range(&multibuffer.read(cx).snapshot(cx), cell);
+        let executed_text = |cx: &mut App| {
+            multibuffer
+                .read(cx)
+                .snapshot(cx)
+                .text_for_range(anchor_range.clone())
+                .collect::<String>()
+        };
+        assert_eq!(executed_text(cx), "# %%\ny = 2");
+
+        // Lines added above shift the range down with the code.
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit(
+                [(Point::new(0, 0)..Point::new(0, 0), "import os\n")],
+                None,
+                cx,
+            )
+        });
+        assert_eq!(executed_text(cx), "# %%\ny = 2");
+
+        // Edits inside the range, and text typed at its end, stay in it.
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(Point::new(4, 4)..Point::new(4, 5), "20")], None, cx);
+            buffer.edit([(Point::new(4, 6)..Point::new(4, 6), "0")], None, cx);
+        });
+        assert_eq!(executed_text(cx), "# %%\ny = 200");
+
+        // Text added after the line that ends the range isn't part of it.
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(Point::new(5, 0)..Point::new(5, 0), "z = 3\n")], None, cx)
+        });
+        assert_eq!(executed_text(cx), "# %%\ny = 200");
+    }
 }
@@ -10,6 +10,8 @@ use workspace::item::ItemEvent;
 use workspace::{Workspace, item::Item};
 
 use crate::jupyter_settings::JupyterSettings;
+use crate::kernel_status;
+use crate::repl_editor::RunScope;
 use crate::repl_store::ReplStore;
 
 actions!(
@@ -19,18 +21,35 @@ actions!(
         Run,
         /// Runs the current cell without advancing.
         RunInPlace,
+        /// Runs the selected text, or runs the current line and advances to the next one.
+        RunSelection,
+        /// Runs the `# %%` cell at the cursor and advances to the next one.
+        RunCellAndAdvance,
+        /// Runs the whole file.
+        RunFile,
         /// Clears all outputs in the REPL.
+        #[action(deprecated_aliases = ["notebook::ClearOutputs"])]
         ClearOutputs,
         /// Clears the output of the cell at the current cursor position.
         ClearCurrentOutput,
         /// Opens the REPL sessions panel.
         Sessions,
         /// Interrupts the currently running kernel.
-        Interrupt,
+        #[action(deprecated_aliases = ["repl::Interrupt", "notebook::InterruptKernel"])]
+        InterruptKernel,
         /// Shuts down the current kernel.
-        Shutdown,
+        #[action(deprecated_aliases = ["repl::Shutdown"])]
+        ShutdownKernel,
         /// Restarts the current kernel.
-        Restart,
+        #[action(deprecated_aliases = ["repl::Restart", "notebook::RestartKernel"])]
+        RestartKernel,
+        /// Debugs the current kernel in the debugger panel, stopping at breakpoints in code run
+        /// from then on.
+        DebugKernel,
+        /// Lists the code run in the current kernel, newest first, to run it again.
+        ShowHistory,
+        /// Opens the code that ran successfully in the current kernel, in order, in a new buffer.
+        CopySessionAsScript,
         /// Refreshes the list of available kernelspecs.
         RefreshKernelspecs
     ]
@@ -66,6 +85,8 @@ pub fn init(cx: &mut App) {
                     store.refresh_kernelspecs(cx).detach();
                 });
             });
+
+            kernel_status::register_workspace_actions(workspace);
         },
     )
     .detach();
@@ -132,6 +153,7 @@ pub fn init(cx: &mut App) {
 
                 editor
                     .register_action({
+                        let editor_handle = editor_handle.clone();
                         move |_: &RunInPlace, window, cx| {
                             if !JupyterSettings::enabled(cx) {
                                 return;
@@ -141,6 +163,65 @@ pub fn init(cx: &mut App) {
                         }
                     })
                     .detach();
+
+                editor
+                    .register_action({
+                        let editor_handle = editor_handle.clone();
+                        move |_: &RunSelection, window, cx| {
+                            if !JupyterSettings::enabled(cx) {
+                                return;
+                            }
+
+                            crate::run_scope(
+                                editor_handle.clone(),
+                                RunScope::Selection,
+                                true,
+                                window,
+                                cx,
+                            )
+                            .log_err();
+                        }
+                    })
+                    .detach();
+
+                editor
+                    .register_action({
+                        let editor_handle = editor_handle.clone();
+                        move |_: &RunCellAndAdvance, window, cx| {
+                            if !JupyterSettings::enabled(cx) {
+                                return;
+                            }
+
+                            crate::run_scope(
+                                editor_handle.clone(),
+                                RunScope::JupytextCell,
+                                true,
+                                window,
+                                cx,
+                            )
+                            .log_err();
+                        }
+                    })
+                    .detach();
+
+                editor
+                    .register_action({
+                        move |_: &RunFile, window, cx| {
+                            if !JupyterSettings::enabled(cx) {
+                                return;
+                            }
+
+                            crate::run_scope(
+                                editor_handle.clone(),
+                                RunScope::File,
+                                false,
+                                window,
+                                cx,
+                            )
+                            .log_err();
+                        }
+                    })
+                    .detach();
             });
         },
     )
@@ -1,4 +1,6 @@
+use collections::HashMap;
 use settings::{RegisterSetting, Settings};
+use std::time::Duration;
 
 /// Settings for configuring REPL display and behavior.
 #[derive(Clone, Debug, RegisterSetting)]
@@ -32,6 +34,65 @@ pub struct ReplSettings {
     ///
     /// Default: 0
     pub output_max_width_columns: usize,
+    /// Whether restarting the kernel to run all notebook cells clears their previous outputs.
+    /// When disabled, previous outputs are dimmed until their cell runs again.
+    ///
+    /// Default: true
+    pub clear_outputs_on_restart: bool,
+    /// Whether running all notebook cells after a restart keeps going past a cell that errors.
+    ///
+    /// Default: false
+    pub continue_on_error: bool,
+    /// Maximum number of bytes of stream output a single execution keeps.
+    ///
+    /// Default: 1048576
+    pub output_max_bytes: usize,
+    /// Maximum number of lines of stream output a single execution keeps.
+    ///
+    /// Default: 10000
+    pub output_max_lines: usize,
+    /// Maximum size in bytes of a rich output before it's replaced with a placeholder.
+    ///
+    /// Default: 20971520
+    pub display_data_max_bytes: usize,
+    /// How long a kernel can stay busy without sending anything for the running execution
+    /// before it's probed, or `None` to never probe.
+    ///
+    /// Default: 60 seconds
+    pub busy_probe_timeout: Option<Duration>,
+    /// Maximum number of requests that can wait to be sent to a kernel.
+    ///
+    /// Default: 100
+    pub request_queue_capacity: usize,
+    /// Overrides `request_queue_capacity` for kernels by name.
+    pub kernel_request_queue_capacity: HashMap<String, usize>,
+    /// Whether to check that a Python environment can run ipykernel before starting a kernel
+    /// from it.
+    ///
+    /// Default: true
+    pub validate_python_environments: bool,
+    /// Maximum number of executions each session keeps in its history.
+    ///
+    /// Default: 500
+    pub execution_history_max_entries: usize,
+    /// Whether to write the messages sent to and received from kernels to trace files.
+    ///
+    /// Default: false
+    pub trace_kernel_messages: bool,
+    /// Whether kernel message traces leave out the text of the messages they record.
+    ///
+    /// Default: true
+    pub redact_kernel_message_traces: bool,
+}
+
+impl ReplSettings {
+    pub fn request_queue_capacity(&self, kernel_name: &str) -> usize {
+        self.kernel_request_queue_capacity
+            .get(kernel_name)
+            .copied()
+            .unwrap_or(self.request_queue_capacity)
+            .max(1)
+    }
 }
 
 impl Settings for ReplSettings {
@@ -45,6 +106,25 @@ impl Settings for ReplSettings {
             inline_output_max_length: repl.inline_output_max_length.unwrap_or(50),
             output_max_height_lines: repl.output_max_height_lines.unwrap_or(0),
             output_max_width_columns: repl.output_max_width_columns.unwrap_or(0),
+            clear_outputs_on_restart: repl.clear_outputs_on_restart.unwrap_or(true),
+            continue_on_error: repl.continue_on_error.unwrap_or(false),
+            output_max_bytes: repl.output_max_bytes.unwrap_or(1024 * 1024),
+            output_max_lines: repl.output_max_lines.unwrap_or(10_000),
+            display_data_max_bytes: repl.display_data_max_bytes.unwrap_or(20 * 1024 * 1024),
+            busy_probe_timeout: Some(repl.busy_probe_timeout_secs.unwrap_or(60))
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+            request_queue_capacity: repl
+                .request_queue_capacity
+                .unwrap_or(crate::kernels::DEFAULT_REQUEST_CAPACITY),
+            kernel_request_queue_capacity: repl
+                .kernel_request_queue_capacity
+                .clone()
+                .unwrap_or_default(),
+            validate_python_environments: repl.validate_python_environments.unwrap_or(true),
+            execution_history_max_entries: repl.execution_history_max_entries.unwrap_or(500),
+            trace_kernel_messages: repl.trace_kernel_messages.unwrap_or(false),
+            redact_kernel_message_traces: repl.redact_kernel_message_traces.unwrap_or(true),
         }
     }
 }
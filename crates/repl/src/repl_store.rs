@@ -1,4 +1,5 @@
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{Context as _, Result};
@@ -8,12 +9,15 @@ use gpui::{App, Context, Entity, EntityId, Global, SharedString, Subscription, T
 use jupyter_websocket_client::RemoteServer;
 use language::{Language, LanguageName};
 use project::{Fs, Project, ProjectPath, WorktreeId};
-use settings::{Settings, SettingsStore};
-use util::rel_path::RelPath;
+use settings::{CustomKernelContent, Settings, SettingsStore};
+use util::{ResultExt as _, TryFutureExt as _, rel_path::RelPath};
 
 use crate::kernels::{
-    Kernel, list_remote_kernelspecs, local_kernel_specifications, python_env_kernel_specifications,
-    wsl_kernel_specifications,
+    CanonicalInterpreterPaths, Kernel, KernelRanking, LocalKernelSpecification,
+    list_remote_kernelspecs, list_running_remote_kernels, local_kernel_specifications,
+    merge_custom_kernel_specifications, python_env_kernel_specifications,
+    rank_kernel_specifications, read_last_used_kernel, running_remote_kernel_specifications,
+    write_last_used_kernel, wsl_kernel_specifications,
 };
 use crate::{JupyterSettings, KernelSpecification, Session};
 
@@ -25,10 +29,19 @@ pub struct ReplStore {
     fs: Arc<dyn Fs>,
     enabled: bool,
     sessions: HashMap<EntityId, Entity<Session>>,
+    /// The discovered kernels merged with the ones defined in the settings.
     kernel_specifications: Vec<KernelSpecification>,
+    discovered_kernel_specifications: Vec<KernelSpecification>,
+    custom_kernels: Vec<CustomKernelContent>,
     selected_kernel_for_worktree: HashMap<WorktreeId, KernelSpecification>,
     kernel_specifications_for_worktree: HashMap<WorktreeId, Vec<KernelSpecification>>,
+    /// The worktree's kernels combined with the global ones, deduplicated and with the
+    /// recommended kernel first.
+    ranked_kernel_specifications_for_worktree: HashMap<WorktreeId, Vec<KernelSpecification>>,
+    canonical_interpreter_paths: CanonicalInterpreterPaths,
     active_python_toolchain_for_worktree: HashMap<WorktreeId, SharedString>,
+    last_used_kernel_for_worktree: HashMap<WorktreeId, String>,
+    worktree_roots: HashMap<WorktreeId, Arc<Path>>,
     remote_worktrees: HashSet<WorktreeId>,
     _subscriptions: Vec<Subscription>,
 }
@@ -55,22 +68,30 @@ impl ReplStore {
         let subscriptions = vec![
             cx.observe_global::<SettingsStore>(move |this, cx| {
                 this.set_enabled(JupyterSettings::enabled(cx), cx);
+                this.set_custom_kernels(&JupyterSettings::get_global(cx).custom_kernels, cx);
             }),
             cx.on_app_quit(Self::shutdown_all_sessions),
         ];
 
-        let this = Self {
+        let mut this = Self {
             fs,
             enabled: JupyterSettings::enabled(cx),
             sessions: HashMap::default(),
             kernel_specifications: Vec::new(),
+            discovered_kernel_specifications: Vec::new(),
+            custom_kernels: Vec::new(),
             _subscriptions: subscriptions,
             kernel_specifications_for_worktree: HashMap::default(),
+            ranked_kernel_specifications_for_worktree: HashMap::default(),
+            canonical_interpreter_paths: CanonicalInterpreterPaths::default(),
             selected_kernel_for_worktree: HashMap::default(),
             active_python_toolchain_for_worktree: HashMap::default(),
+            last_used_kernel_for_worktree: HashMap::default(),
+            worktree_roots: HashMap::default(),
             remote_worktrees: HashSet::default(),
         };
         this.on_enabled_changed(cx);
+        this.set_custom_kernels(&JupyterSettings::get_global(cx).custom_kernels, cx);
         this
     }
 
@@ -87,21 +108,55 @@ impl ReplStore {
             .contains_key(&worktree_id)
     }
 
+    /// The kernels available in the worktree, with the recommended one first. Worktrees whose
+    /// Python environments haven't been discovered yet only have the global kernels.
     pub fn kernel_specifications_for_worktree(
         &self,
         worktree_id: WorktreeId,
     ) -> impl Iterator<Item = &KernelSpecification> {
+        self.ranked_kernel_specifications_for_worktree
+            .get(&worktree_id)
+            .unwrap_or(&self.kernel_specifications)
+            .iter()
+    }
+
+    fn rerank_worktree(&mut self, worktree_id: WorktreeId) {
+        let Some(worktree_specs) = self.kernel_specifications_for_worktree.get(&worktree_id) else {
+            return;
+        };
         let global_specs = if self.remote_worktrees.contains(&worktree_id) {
-            None
+            &[][..]
         } else {
-            Some(self.kernel_specifications.iter())
+            self.kernel_specifications.as_slice()
+        };
+        let specs = worktree_specs.iter().chain(global_specs).cloned().collect();
+
+        let ranking = KernelRanking {
+            worktree_root: self.worktree_roots.get(&worktree_id).map(AsRef::as_ref),
+            active_toolchain_path: self
+                .active_python_toolchain_for_worktree
+                .get(&worktree_id)
+                .map(AsRef::as_ref),
+            last_used_kernel: self
+                .last_used_kernel_for_worktree
+                .get(&worktree_id)
+                .map(String::as_str),
+            canonical_paths: &self.canonical_interpreter_paths,
         };
+        let ranked_specs = rank_kernel_specifications(specs, &ranking);
+        self.ranked_kernel_specifications_for_worktree
+            .insert(worktree_id, ranked_specs);
+    }
 
-        self.kernel_specifications_for_worktree
-            .get(&worktree_id)
-            .into_iter()
-            .flat_map(|specs| specs.iter())
-            .chain(global_specs.into_iter().flatten())
+    fn rerank_all_worktrees(&mut self) {
+        let worktree_ids = self
+            .kernel_specifications_for_worktree
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        for worktree_id in worktree_ids {
+            self.rerank_worktree(worktree_id);
+        }
     }
 
     pub fn pure_jupyter_kernel_specifications(&self) -> impl Iterator<Item = &KernelSpecification> {
@@ -112,6 +167,42 @@ impl ReplStore {
         self.sessions.values()
     }
 
+    fn set_custom_kernels(
+        &mut self,
+        custom_kernels: &[CustomKernelContent],
+        cx: &mut Context<Self>,
+    ) {
+        if self.custom_kernels == custom_kernels {
+            return;
+        }
+
+        self.custom_kernels = custom_kernels.to_vec();
+        self.merge_kernel_specifications(cx);
+    }
+
+    fn set_discovered_kernel_specifications(
+        &mut self,
+        specs: Vec<KernelSpecification>,
+        cx: &mut Context<Self>,
+    ) {
+        self.discovered_kernel_specifications = specs;
+        self.merge_kernel_specifications(cx);
+    }
+
+    fn merge_kernel_specifications(&mut self, cx: &mut Context<Self>) {
+        let custom_kernel_specifications = self
+            .custom_kernels
+            .iter()
+            .map(|kernel| KernelSpecification::Jupyter(LocalKernelSpecification::from(kernel)))
+            .collect::<Vec<_>>();
+        self.kernel_specifications = merge_custom_kernel_specifications(
+            &custom_kernel_specifications,
+            &self.discovered_kernel_specifications,
+        );
+        self.rerank_all_worktrees();
+        cx.notify();
+    }
+
     fn set_enabled(&mut self, enabled: bool, cx: &mut Context<Self>) {
         if self.enabled == enabled {
             return;
@@ -144,6 +235,10 @@ impl ReplStore {
         cx: &mut Context<Self>,
     ) -> Task<Result<()>> {
         let is_remote = project.read(cx).is_remote();
+        let worktree_root = project
+            .read(cx)
+            .worktree_for_id(worktree_id, cx)
+            .map(|worktree| worktree.read(cx).abs_path());
         let kernel_specifications = python_env_kernel_specifications(project, worktree_id, cx);
         let active_toolchain = project.read(cx).active_toolchain(
             ProjectPath {
@@ -154,6 +249,8 @@ impl ReplStore {
             cx,
         );
 
+        let fs = self.fs.clone();
+
         cx.spawn(async move |this, cx| {
             let kernel_specifications = kernel_specifications
                 .await
@@ -161,18 +258,43 @@ impl ReplStore {
 
             let active_toolchain_path = active_toolchain.await.map(|toolchain| toolchain.path);
 
+            // Remote interpreters can't be resolved through the local file system.
+            let canonical_paths = if is_remote {
+                CanonicalInterpreterPaths::default()
+            } else {
+                let interpreter_paths = kernel_specifications
+                    .iter()
+                    .filter_map(|spec| spec.interpreter_path().map(Path::to_path_buf))
+                    .chain(
+                        active_toolchain_path
+                            .as_ref()
+                            .map(|path| PathBuf::from(path.as_ref())),
+                    );
+                CanonicalInterpreterPaths::resolve(interpreter_paths, fs.as_ref()).await
+            };
+            let last_used_kernel = worktree_root.as_deref().and_then(read_last_used_kernel);
+
             this.update(cx, |this, cx| {
                 this.kernel_specifications_for_worktree
                     .insert(worktree_id, kernel_specifications);
+                this.canonical_interpreter_paths.extend(canonical_paths);
                 if let Some(path) = active_toolchain_path {
                     this.active_python_toolchain_for_worktree
                         .insert(worktree_id, path);
                 }
+                if let Some(last_used_kernel) = last_used_kernel {
+                    this.last_used_kernel_for_worktree
+                        .insert(worktree_id, last_used_kernel);
+                }
+                if let Some(worktree_root) = worktree_root {
+                    this.worktree_roots.insert(worktree_id, worktree_root);
+                }
                 if is_remote {
                     this.remote_worktrees.insert(worktree_id);
                 } else {
                     this.remote_worktrees.remove(&worktree_id);
                 }
+                this.rerank_worktree(worktree_id);
                 cx.notify();
             })
         })
@@ -193,14 +315,22 @@ impl ReplStore {
                 };
                 let http_client = cx.http_client();
                 Some(cx.spawn(async move |_, _| {
-                    list_remote_kernelspecs(remote_server, http_client)
-                        .await
-                        .map(|specs| {
-                            specs
-                                .into_iter()
-                                .map(KernelSpecification::JupyterServer)
-                                .collect()
-                        })
+                    let running_kernels =
+                        list_running_remote_kernels(&remote_server, http_client.clone())
+                            .log_err_with_context(|| {
+                                format!("listing running kernels on {}", remote_server.base_url)
+                            })
+                            .await
+                            .unwrap_or_default();
+                    let mut specs = list_remote_kernelspecs(remote_server, http_client).await?;
+                    specs.extend(running_remote_kernel_specifications(
+                        &specs,
+                        running_kernels,
+                    ));
+                    Ok(specs
+                        .into_iter()
+                        .map(KernelSpecification::JupyterServer)
+                        .collect())
                 }))
             }
             _ => None,
@@ -208,6 +338,7 @@ impl ReplStore {
     }
 
     pub fn refresh_kernelspecs(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let fs = self.fs.clone();
         let local_kernel_specifications = local_kernel_specifications(self.fs.clone());
         let wsl_kernel_specifications = wsl_kernel_specifications(cx.background_executor().clone());
 
@@ -230,16 +361,23 @@ impl ReplStore {
                 all_specs.extend(remote_specs);
             }
 
-            anyhow::Ok(all_specs)
+            let interpreter_paths = all_specs
+                .iter()
+                .filter_map(|spec| spec.interpreter_path().map(Path::to_path_buf))
+                .collect::<Vec<_>>();
+            let canonical_paths =
+                CanonicalInterpreterPaths::resolve(interpreter_paths, fs.as_ref()).await;
+
+            anyhow::Ok((all_specs, canonical_paths))
         });
 
         cx.spawn(async move |this, cx| {
             let all_specs = all_specs.await;
 
-            if let Ok(specs) = all_specs {
+            if let Ok((specs, canonical_paths)) = all_specs {
                 this.update(cx, |this, cx| {
-                    this.kernel_specifications = specs;
-                    cx.notify();
+                    this.canonical_interpreter_paths.extend(canonical_paths);
+                    this.set_discovered_kernel_specifications(specs, cx);
                 })
                 .ok();
             }
@@ -252,10 +390,25 @@ impl ReplStore {
         &mut self,
         worktree_id: WorktreeId,
         kernelspec: KernelSpecification,
-        _cx: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) {
+        let last_used_key = kernelspec.last_used_key();
         self.selected_kernel_for_worktree
             .insert(worktree_id, kernelspec);
+
+        if self.last_used_kernel_for_worktree.get(&worktree_id) == Some(&last_used_key) {
+            return;
+        }
+        if let Some(worktree_root) = self.worktree_roots.get(&worktree_id).cloned() {
+            let last_used_key = last_used_key.clone();
+            db::write_and_log(cx, move || async move {
+                write_last_used_kernel(&worktree_root, last_used_key).await
+            });
+        }
+        self.last_used_kernel_for_worktree
+            .insert(worktree_id, last_used_key);
+        self.rerank_worktree(worktree_id);
+        cx.notify();
     }
 
     pub fn active_python_toolchain_path(&self, worktree_id: WorktreeId) -> Option<&SharedString> {
@@ -266,16 +419,16 @@ impl ReplStore {
         self.selected_kernel_for_worktree.get(&worktree_id)
     }
 
+    /// Whether `spec` ranks first among the worktree's kernels.
     pub fn is_recommended_kernel(
         &self,
         worktree_id: WorktreeId,
         spec: &KernelSpecification,
     ) -> bool {
-        if let Some(active_path) = self.active_python_toolchain_path(worktree_id) {
-            spec.path().as_ref() == active_path.as_ref()
-        } else {
-            false
-        }
+        self.ranked_kernel_specifications_for_worktree
+            .get(&worktree_id)
+            .and_then(|specs| specs.first())
+            == Some(spec)
     }
 
     pub fn active_kernelspec(
@@ -393,7 +546,6 @@ impl ReplStore {
         specs: Vec<KernelSpecification>,
         cx: &mut Context<Self>,
     ) {
-        self.kernel_specifications = specs;
-        cx.notify();
+        self.set_discovered_kernel_specifications(specs, cx);
     }
 }
@@ -1,10 +1,17 @@
 use crate::components::KernelListItem;
+use crate::execution_history::ExecutionHistory;
+use crate::execution_kind::ExecutionKind;
+use crate::execution_timing::ExecutionTiming;
+use crate::execution_watchdog::{self, ExecutionWatchdog, WatchdogAction};
+use crate::kernel_debug_adapter::{self, JupyterKernelDebugAdapter};
+use crate::kernel_debugger::{self, DebugMessage, KernelDebugger};
 use crate::setup_editor_session_actions;
 use crate::{
-    KernelStatus,
+    KernelCommand, KernelOwner, KernelSessions, KernelStatus,
     kernels::{
-        Kernel, KernelSession, KernelSpecification, NativeRunningKernel, RemoteRunningKernel,
-        SshRunningKernel, WslRunningKernel,
+        Kernel, KernelRequestError, KernelSession, KernelSpecification, NativeRunningKernel,
+        RemoteRunningKernel, RequestQueueMetrics, ResourceUsage, RestartKind, RestartOutcome,
+        SshRunningKernel, WslRunningKernel, restart_in_place,
     },
     outputs::{
         ExecutionStatus, ExecutionView, ExecutionViewFinishedEmpty, ExecutionViewFinishedSmall,
@@ -13,6 +20,7 @@ use crate::{
     repl_settings::ReplSettings,
 };
 use anyhow::Context as _;
+use chrono::{DateTime, Utc};
 use collections::{HashMap, HashSet};
 use editor::SelectionEffects;
 use editor::{
@@ -28,21 +36,28 @@ use project::InlayId;
 /// Marker types
 enum ReplExecutedRange {}
 
-use futures::FutureExt as _;
+use futures::{FutureExt as _, channel::mpsc};
 use gpui::{
-    Context, Entity, EventEmitter, Render, Subscription, Task, WeakEntity, Window, div, prelude::*,
+    Context, Entity, EntityId, EventEmitter, Render, Subscription, Task, WeakEntity, Window, div,
+    prelude::*,
 };
 use language::Point;
 use project::Fs;
 use runtimelib::{
-    ExecuteRequest, ExecutionState, InputReply, InterruptRequest, JupyterMessage,
-    JupyterMessageContent, KernelInfoRequest, ReplyStatus, ShutdownRequest,
+    ExecuteRequest, ExecutionState, InputReply, JupyterMessage, JupyterMessageContent,
+    KernelInfoRequest, ReplyStatus, ShutdownRequest,
 };
+use serde_json::{Value, json};
 use settings::Settings as _;
-use std::{env::temp_dir, ops::Range, sync::Arc, time::Duration};
+use smol::net::TcpListener;
+use std::{
+    collections::VecDeque, env::temp_dir, net::Ipv4Addr, ops::Range, sync::Arc, time::Duration,
+};
+use task::{DebugScenario, SharedTaskContext, TcpArgumentsTemplate};
 use theme::ActiveTheme;
 use ui::{IconButtonShape, Tooltip, prelude::*};
-use util::ResultExt as _;
+use util::{ResultExt as _, TryFutureExt as _, fmt::human_duration};
+use workspace::Workspace;
 
 pub struct Session {
     fs: Arc<dyn Fs>,
@@ -54,7 +69,35 @@ pub struct Session {
     result_inlays: HashMap<String, (InlayId, Range<Anchor>, usize)>,
     next_inlay_id: usize,
 
+    /// Timing for executions whose reply or final idle status hasn't arrived yet, keyed by
+    /// the execute request's message id. These outlive their blocks, which are replaced by
+    /// inlays as soon as the kernel goes idle.
+    execution_timings: HashMap<String, ExecutionTiming>,
+    total_compute_time: Duration,
+    watchdog: ExecutionWatchdog,
+    /// Set when the kernel stayed busy and didn't answer the watchdog's probe, until the next
+    /// message from it arrives.
+    unresponsive: bool,
+    /// The kernel process's resource usage as of the last render, which is checked for changes
+    /// along with the watchdog.
+    resource_usage: Option<ResourceUsage>,
+    /// Execute requests that didn't fit in the kernel's request queue, in the order they were
+    /// made. They're sent as the queue drains.
+    queued_requests: VecDeque<JupyterMessage>,
+    queued_requests_task: Option<Task<()>>,
+    /// Set while the kernel is being debugged from the debugger UI.
+    debug_connection: Option<DebugConnection>,
+    /// Everything the kernel ran, including code sent by its other clients.
+    history: ExecutionHistory,
+
     _subscriptions: Vec<Subscription>,
+    _watchdog_task: Task<()>,
+}
+
+struct DebugConnection {
+    debugger: KernelDebugger,
+    messages_to_client: mpsc::UnboundedSender<Value>,
+    _task: Task<()>,
 }
 
 struct EditorBlock {
@@ -72,13 +115,15 @@ impl EditorBlock {
         editor: WeakEntity<Editor>,
         code_range: Range<Anchor>,
         status: ExecutionStatus,
+        kind: ExecutionKind,
         on_close: CloseBlockFn,
         cx: &mut Context<Session>,
     ) -> anyhow::Result<Self> {
         let editor = editor.upgrade().context("editor is not open")?;
         let workspace = editor.read(cx).workspace().context("workspace dropped")?;
 
-        let execution_view = cx.new(|cx| ExecutionView::new(status, workspace.downgrade(), cx));
+        let execution_view =
+            cx.new(|cx| ExecutionView::new(status, kind, workspace.downgrade(), cx));
 
         let (block_id, invalidation_anchor) = editor.update(cx, |editor, cx| {
             let buffer = editor.buffer().clone();
@@ -257,10 +302,38 @@ impl Session {
             blocks: HashMap::default(),
             result_inlays: HashMap::default(),
             next_inlay_id: 0,
+            execution_timings: HashMap::default(),
+            total_compute_time: Duration::ZERO,
+            watchdog: ExecutionWatchdog::default(),
+            unresponsive: false,
+            resource_usage: None,
+            queued_requests: VecDeque::new(),
+            queued_requests_task: None,
+            debug_connection: None,
+            history: ExecutionHistory::new(
+                ReplSettings::get_global(cx).execution_history_max_entries,
+            ),
             kernel_specification,
             _subscriptions: vec![subscription],
+            _watchdog_task: cx.spawn(async move |this, cx| {
+                loop {
+                    cx.background_executor()
+                        .timer(execution_watchdog::CHECK_INTERVAL)
+                        .await;
+                    if this
+                        .update(cx, |session, cx| {
+                            session.check_watchdog(cx);
+                            session.refresh_resource_usage(cx);
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }),
         };
 
+        KernelSessions::register(cx);
         session.start_kernel(window, cx);
         session
     }
@@ -316,15 +389,17 @@ impl Session {
                 window,
                 cx,
             ),
-            KernelSpecification::PythonEnv(env_specification) => NativeRunningKernel::new(
-                env_specification.as_local_spec(),
-                entity_id,
-                working_directory,
-                self.fs.clone(),
-                session_view,
-                window,
-                cx,
-            ),
+            KernelSpecification::PythonEnv(env_specification) => {
+                NativeRunningKernel::new_for_python_env(
+                    env_specification,
+                    entity_id,
+                    working_directory,
+                    self.fs.clone(),
+                    session_view,
+                    window,
+                    cx,
+                )
+            }
             KernelSpecification::JupyterServer(remote_kernel_specification) => {
                 RemoteRunningKernel::new(
                     remote_kernel_specification,
@@ -392,12 +467,17 @@ impl Session {
     }
 
     pub fn kernel_errored(&mut self, error_message: String, cx: &mut Context<Self>) {
+        // The old kernel going away mid-restart isn't an error, and mustn't stop the restart.
+        if let Kernel::Restarting(_) = self.kernel {
+            log::debug!("ignoring an error from a restarting kernel: {error_message}");
+            return;
+        }
         self.kernel(Kernel::ErroredLaunch(error_message.clone()), cx);
 
         self.blocks.values().for_each(|block| {
             block.execution_view.update(cx, |execution_view, cx| {
                 match execution_view.status {
-                    ExecutionStatus::Finished => {
+                    ExecutionStatus::Finished | ExecutionStatus::ReplyLost => {
                         // Do nothing when the output was good
                     }
                     _ => {
@@ -486,6 +566,142 @@ impl Session {
         anyhow::Ok(())
     }
 
+    fn send_execute_request(&mut self, message: JupyterMessage, cx: &mut Context<Self>) {
+        self.execution_timings.insert(
+            message.header.msg_id.clone(),
+            ExecutionTiming::new(Utc::now()),
+        );
+
+        // Executions run in the order they were made, so once one is waiting for room in the
+        // kernel's queue, the rest wait behind it.
+        if self.queued_requests_task.is_some() {
+            self.queued_requests.push_back(message);
+            return;
+        }
+        let Kernel::RunningKernel(kernel) = &mut self.kernel else {
+            return;
+        };
+        match kernel.request_tx().try_send(message) {
+            Ok(()) => {}
+            Err(KernelRequestError::KernelBusy(message)) => {
+                log::debug!("kernel request queue is full, queueing the execution locally");
+                self.queued_requests.push_back(message);
+                self.send_queued_requests(cx);
+            }
+            Err(error) => {
+                log::error!("{error}");
+            }
+        }
+    }
+
+    fn send_queued_requests(&mut self, cx: &mut Context<Self>) {
+        let Kernel::RunningKernel(kernel) = &self.kernel else {
+            return;
+        };
+        let mut request_tx = kernel.request_tx();
+        self.queued_requests_task = Some(cx.spawn(async move |this, cx| {
+            loop {
+                let Ok(Some(message)) = this.update(cx, |session, _| {
+                    let message = session.queued_requests.pop_front();
+                    if message.is_none() {
+                        session.queued_requests_task = None;
+                    }
+                    message
+                }) else {
+                    break;
+                };
+                if let Err(error) = request_tx.send(message).await {
+                    log::error!("{error}");
+                    this.update(cx, |session, _| session.queued_requests_task = None)
+                        .ok();
+                    break;
+                }
+            }
+        }));
+    }
+
+    /// How full the kernel's request queue is, for debugging a kernel that isn't keeping up.
+    pub fn request_queue_metrics(&self) -> Option<RequestQueueMetrics> {
+        match &self.kernel {
+            Kernel::RunningKernel(kernel) => Some(kernel.request_tx().metrics()),
+            _ => None,
+        }
+    }
+
+    /// The executions waiting in Zed for room in the kernel's request queue.
+    pub fn locally_queued_requests(&self) -> usize {
+        self.queued_requests.len()
+    }
+
+    /// Probes a kernel that has been busy without any word on its executions for too long, and
+    /// marks it unresponsive when the probe goes unanswered.
+    fn check_watchdog(&mut self, cx: &mut Context<Self>) {
+        let Some(busy_timeout) = ReplSettings::get_global(cx).busy_probe_timeout else {
+            return;
+        };
+        let busy = match &self.kernel {
+            Kernel::RunningKernel(kernel) => {
+                matches!(kernel.execution_state(), ExecutionState::Busy)
+            }
+            _ => false,
+        };
+        let now = Utc::now();
+        match self
+            .watchdog
+            .check(busy, self.execution_timings.values(), now, busy_timeout)
+        {
+            Some(WatchdogAction::Probe) => {
+                let message: JupyterMessage = KernelInfoRequest {}.into();
+                self.watchdog.probe_sent(message.header.msg_id.clone(), now);
+                self.send(message, cx).log_err();
+            }
+            Some(WatchdogAction::Unresponsive) => {
+                log::warn!(
+                    "kernel has been busy for over {busy_timeout:?} and didn't answer a kernel_info_request"
+                );
+                self.unresponsive = true;
+                cx.notify();
+            }
+            None => {}
+        }
+    }
+
+    fn refresh_resource_usage(&mut self, cx: &mut Context<Self>) {
+        let resource_usage = self.kernel.resource_usage();
+        if resource_usage != self.resource_usage {
+            self.resource_usage = resource_usage;
+            cx.notify();
+        }
+    }
+
+    /// Finishes the executions the kernel was done with before it answered the watchdog's
+    /// probe, but whose reply or final idle status never arrived.
+    fn recover_lost_executions(&mut self, probe_sent_at: DateTime<Utc>, cx: &mut Context<Self>) {
+        let lost = execution_watchdog::lost_executions(&self.execution_timings, probe_sent_at);
+        if lost.is_empty() {
+            return;
+        }
+        for msg_id in &lost {
+            log::warn!("never received the reply to execution {msg_id}; marking it as completed");
+            self.execution_timings.remove(msg_id);
+            if let Some(block) = self.blocks.get(msg_id) {
+                block.execution_view.update(cx, |execution_view, cx| {
+                    execution_view.finish_with_lost_reply(cx);
+                });
+            }
+        }
+        if self.execution_timings.is_empty() {
+            self.kernel.set_execution_state(&ExecutionState::Idle);
+        }
+        cx.notify();
+    }
+
+    /// The time the kernel has spent running executions from this session, for display in
+    /// the status bar.
+    pub fn total_compute_time(&self) -> Duration {
+        self.total_compute_time
+    }
+
     fn send_stdin_reply(
         &mut self,
         value: String,
@@ -633,6 +849,8 @@ impl Session {
             return;
         }
 
+        let kind = ExecutionKind::detect(&code);
+        let debugged_code = self.debug_connection.is_some().then(|| code.clone());
         let execute_request = ExecuteRequest {
             code,
             allow_stdin: true,
@@ -681,7 +899,7 @@ impl Session {
             .ok();
 
         let status = match &self.kernel {
-            Kernel::Restarting => ExecutionStatus::Restarting,
+            Kernel::Restarting(_) => ExecutionStatus::Restarting,
             Kernel::RunningKernel(_) => ExecutionStatus::Queued,
             Kernel::StartingKernel(_) => ExecutionStatus::ConnectingToKernel,
             Kernel::ErroredLaunch(error) => ExecutionStatus::KernelErrored(error.clone()),
@@ -721,6 +939,7 @@ impl Session {
             self.editor.clone(),
             anchor_range.clone(),
             status,
+            kind,
             on_close,
             cx,
         ) else {
@@ -774,7 +993,15 @@ impl Session {
 
         match &self.kernel {
             Kernel::RunningKernel(_) => {
-                self.send(message, cx).ok();
+                if let Some(code) = debugged_code
+                    && let Some(connection) = self.debug_connection.as_mut()
+                {
+                    let start_row = anchor_range.start.to_point(&buffer).row;
+                    let messages = connection.debugger.prepare_cell(&code, start_row, message);
+                    self.handle_debug_messages(messages, cx);
+                } else {
+                    self.send_execute_request(message, cx);
+                }
             }
             Kernel::StartingKernel(task) => {
                 // Queue up the execution as a task to run after the kernel starts
@@ -783,7 +1010,7 @@ impl Session {
                 cx.spawn(async move |this, cx| {
                     task.await;
                     this.update(cx, |session, cx| {
-                        session.send(message, cx).ok();
+                        session.send_execute_request(message, cx);
                     })
                     .ok();
                 })
@@ -806,10 +1033,133 @@ impl Session {
         }
     }
 
-    pub fn interrupt(&mut self, cx: &mut Context<Self>) {
+    pub fn history(&self) -> &ExecutionHistory {
+        &self.history
+    }
+
+    /// Runs `code` again, showing its output at the cursor.
+    pub fn rerun(&mut self, code: String, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+        let cursor = editor.read(cx).selections.newest_anchor().head();
+        self.execute(code, cursor..cursor, None, false, window, cx);
+    }
+
+    /// Whether the kernel can be debugged, which Python kernels that say so in their kernel info
+    /// can.
+    pub fn supports_debugging(&self) -> bool {
+        let Kernel::RunningKernel(kernel) = &self.kernel else {
+            return false;
+        };
+        self.kernel_specification
+            .language()
+            .eq_ignore_ascii_case("python")
+            && kernel.kernel_info().is_some_and(|info| info.debugger)
+    }
+
+    /// Starts a debug session for the kernel in the debugger UI. Code run from then on stops at
+    /// the breakpoints set in the editor.
+    pub fn start_debugging(
+        &mut self,
+        workspace: WeakEntity<Workspace>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.supports_debugging(),
+            "The {} kernel can't be debugged. Debugging needs a Python kernel that supports it, like ipykernel 6 or later.",
+            self.kernel_specification.name()
+        );
+        if self.debug_connection.is_some() {
+            return Ok(());
+        }
+        let path = self
+            .editor
+            .upgrade()
+            .and_then(|editor| {
+                let buffer = editor.read(cx).buffer().read(cx).as_singleton()?;
+                let file = buffer.read(cx).file()?.as_local()?.abs_path(cx);
+                Some(file)
+            })
+            .context("Only files saved on this machine can be debugged")?;
+
+        let label = format!("{} kernel", self.kernel_specification.name());
+        let (messages_to_client, messages_from_session) = mpsc::unbounded();
+        let task = cx.spawn_in(window, async move |this, cx| {
+            let result = async {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+                let port = listener.local_addr()?.port();
+                workspace.update_in(cx, |workspace, window, cx| {
+                    let scenario = DebugScenario {
+                        adapter: JupyterKernelDebugAdapter::ADAPTER_NAME.into(),
+                        label: label.into(),
+                        build: None,
+                        config: json!({ "request": "attach" }),
+                        tcp_connection: Some(TcpArgumentsTemplate {
+                            port: Some(port),
+                            host: Some(Ipv4Addr::LOCALHOST),
+                            timeout: None,
+                        }),
+                    };
+                    workspace.start_debug_session(
+                        scenario,
+                        SharedTaskContext::default(),
+                        None,
+                        None,
+                        window,
+                        cx,
+                    );
+                })?;
+                kernel_debug_adapter::serve(listener, messages_from_session, this.clone(), cx).await
+            }
+            .await;
+            result.log_err();
+
+            this.update(cx, |session, cx| {
+                session.debug_connection = None;
+                cx.notify();
+            })
+            .ok();
+        });
+
+        self.debug_connection = Some(DebugConnection {
+            debugger: KernelDebugger::new(path),
+            messages_to_client,
+            _task: task,
+        });
+        cx.notify();
+        Ok(())
+    }
+
+    pub(crate) fn handle_debug_client_message(&mut self, message: Value, cx: &mut Context<Self>) {
+        let Some(connection) = self.debug_connection.as_mut() else {
+            return;
+        };
+        let messages = connection.debugger.handle_client_message(message);
+        self.handle_debug_messages(messages, cx);
+    }
+
+    fn handle_debug_messages(&mut self, messages: Vec<DebugMessage>, cx: &mut Context<Self>) {
+        for message in messages {
+            match message {
+                DebugMessage::Kernel(request) => {
+                    self.send(request, cx).log_err();
+                }
+                DebugMessage::Client(message) => {
+                    if let Some(connection) = &self.debug_connection {
+                        connection.messages_to_client.unbounded_send(message).ok();
+                    }
+                }
+                DebugMessage::Execute(request) => self.send_execute_request(request, cx),
+            }
+        }
+    }
+
+    pub fn interrupt(&mut self, _cx: &mut Context<Self>) {
         match &mut self.kernel {
-            Kernel::RunningKernel(_kernel) => {
-                self.send(InterruptRequest {}.into(), cx).ok();
+            Kernel::RunningKernel(kernel) => {
+                kernel.interrupt().log_err();
             }
             Kernel::StartingKernel(_task) => {
                 // NOTE: If we switch to a literal queue instead of chaining on to the task, clear all queued executions
@@ -833,10 +1183,20 @@ impl Session {
             repl_session_id = cx.entity_id().to_string(),
         );
 
+        if !matches!(kernel, Kernel::RunningKernel(_)) {
+            // Executions still in flight will never get their replies from a new kernel.
+            self.execution_timings.clear();
+            self.watchdog = ExecutionWatchdog::default();
+            self.unresponsive = false;
+            self.queued_requests.clear();
+            self.queued_requests_task = None;
+        }
+
         self.kernel = kernel;
     }
 
     pub fn shutdown(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.debug_connection = None;
         let kernel = std::mem::replace(&mut self.kernel, Kernel::ShuttingDown);
 
         match kernel {
@@ -844,12 +1204,15 @@ impl Session {
                 let mut request_tx = kernel.request_tx();
 
                 let forced = kernel.force_shutdown(window, cx);
+                let kernel_name = self.kernel_specification.name();
 
                 cx.spawn(async move |this, cx| {
                     let message: JupyterMessage = ShutdownRequest { restart: false }.into();
                     request_tx.try_send(message).ok();
 
-                    forced.await.log_err();
+                    forced
+                        .log_err_with_context(|| format!("shutting down the {kernel_name} kernel"))
+                        .await;
 
                     // Give the kernel a bit of time to clean up
                     cx.background_executor().timer(Duration::from_secs(3)).await;
@@ -871,34 +1234,23 @@ impl Session {
     }
 
     pub fn restart(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let kernel = std::mem::replace(&mut self.kernel, Kernel::Restarting);
+        self.debug_connection = None;
+        let kernel = std::mem::replace(&mut self.kernel, Kernel::Restarting(RestartKind::InPlace));
 
         match kernel {
-            Kernel::Restarting => {
+            Kernel::Restarting(kind) => {
                 // Do nothing if already restarting
+                self.kernel = Kernel::Restarting(kind);
             }
-            Kernel::RunningKernel(mut kernel) => {
-                let mut request_tx = kernel.request_tx();
-
-                let forced = kernel.force_shutdown(window, cx);
-
+            Kernel::RunningKernel(kernel) => {
+                // Whichever way the kernel comes back, it won't answer what was sent to it before.
+                self.kernel(Kernel::Restarting(RestartKind::InPlace), cx);
+                log::debug!("restarting kernel");
+                let restart = restart_in_place(kernel, window, cx);
                 cx.spawn_in(window, async move |this, cx| {
-                    // Send shutdown request with restart flag
-                    log::debug!("restarting kernel");
-                    let message: JupyterMessage = ShutdownRequest { restart: true }.into();
-                    request_tx.try_send(message).ok();
-
-                    // Wait for kernel to shutdown
-                    cx.background_executor().timer(Duration::from_secs(1)).await;
-
-                    // Force kill the kernel if it hasn't shut down
-                    forced.await.log_err();
-
-                    // Start a new kernel
+                    let outcome = restart.await;
                     this.update_in(cx, |session, window, cx| {
-                        // TODO: Differentiate between restart and restart+clear-outputs
-                        session.clear_outputs(cx);
-                        session.start_kernel(window, cx);
+                        session.kernel_restarted(outcome, window, cx);
                     })
                     .ok();
                 })
@@ -911,6 +1263,53 @@ impl Session {
         }
         cx.notify();
     }
+
+    fn kernel_restarted(
+        &mut self,
+        outcome: RestartOutcome,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        // The kernel was shut down while it was restarting.
+        if !matches!(self.kernel, Kernel::Restarting(_)) {
+            let (RestartOutcome::Restarted(mut kernel) | RestartOutcome::NeedsRelaunch(mut kernel)) =
+                outcome;
+            kernel.kill();
+            return;
+        }
+
+        // TODO: Differentiate between restart and restart+clear-outputs
+        self.clear_outputs(cx);
+        match outcome {
+            RestartOutcome::Restarted(kernel) => {
+                self.kernel(Kernel::RunningKernel(kernel), cx);
+                let request = JupyterMessageContent::KernelInfoRequest(KernelInfoRequest {});
+                self.send(request.into(), cx).log_err();
+            }
+            RestartOutcome::NeedsRelaunch(mut kernel) => {
+                self.kernel(Kernel::Restarting(RestartKind::Relaunch), cx);
+                let forced = kernel.force_shutdown(window, cx);
+                let kernel_name = self.kernel_specification.name();
+
+                cx.spawn_in(window, async move |this, cx| {
+                    forced
+                        .log_err_with_context(|| {
+                            format!("shutting down the {kernel_name} kernel to restart it")
+                        })
+                        .await;
+
+                    this.update_in(cx, |session, window, cx| {
+                        if let Kernel::Restarting(_) = session.kernel {
+                            session.start_kernel(window, cx);
+                        }
+                    })
+                    .ok();
+                })
+                .detach();
+            }
+        }
+        cx.notify();
+    }
 }
 
 pub enum SessionEvent {
@@ -919,6 +1318,52 @@ pub enum SessionEvent {
 
 impl EventEmitter<SessionEvent> for Session {}
 
+impl KernelOwner for Session {
+    fn kernel_status(&self) -> KernelStatus {
+        if self.unresponsive {
+            return KernelStatus::Unresponsive;
+        }
+        self.kernel.status()
+    }
+
+    fn kernel_specification(&self) -> Option<&KernelSpecification> {
+        Some(&self.kernel_specification)
+    }
+
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.kernel.resource_usage()
+    }
+
+    fn file_name(&self, cx: &App) -> SharedString {
+        self.editor
+            .upgrade()
+            .map(|editor| editor.read(cx).buffer().read(cx).title(cx).to_string())
+            .unwrap_or_default()
+            .into()
+    }
+
+    fn request_interrupt(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        self.interrupt(cx);
+    }
+
+    fn request_restart(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.restart(window, cx);
+    }
+
+    fn request_shutdown(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.shutdown(window, cx);
+    }
+
+    fn request_clear_outputs(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        self.clear_outputs(cx);
+        cx.notify();
+    }
+
+    fn item_id(&self) -> Option<EntityId> {
+        Some(self.editor.entity_id())
+    }
+}
+
 impl Render for Session {
     fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let (status_text, interrupt_button) = match &self.kernel {
@@ -930,6 +1375,7 @@ impl Render for Session {
                 Some(
                     Button::new("interrupt", "Interrupt")
                         .style(ButtonStyle::Subtle)
+                        .disabled(!self.can_run(KernelCommand::Interrupt))
                         .on_click(cx.listener(move |session, _, _, cx| {
                             session.interrupt(cx);
                         })),
@@ -939,11 +1385,17 @@ impl Render for Session {
             Kernel::ErroredLaunch(err) => (Some(format!("Error: {err}")), None),
             Kernel::ShuttingDown => (Some("Shutting Down".into()), None),
             Kernel::Shutdown => (Some("Shutdown".into()), None),
-            Kernel::Restarting => (Some("Restarting".into()), None),
+            Kernel::Restarting(kind) => (Some(kind.label().into()), None),
+        };
+        let status_text = if self.unresponsive {
+            Some("Unresponsive".into())
+        } else {
+            status_text
         };
 
         KernelListItem::new(self.kernel_specification.clone())
             .status_color(match &self.kernel {
+                Kernel::RunningKernel(_) if self.unresponsive => Color::Warning,
                 Kernel::RunningKernel(kernel) => match kernel.execution_state() {
                     ExecutionState::Idle => Color::Success,
                     ExecutionState::Busy => Color::Modified,
@@ -959,14 +1411,28 @@ impl Render for Session {
                 Kernel::ErroredLaunch(_) => Color::Error,
                 Kernel::ShuttingDown => Color::Modified,
                 Kernel::Shutdown => Color::Disabled,
-                Kernel::Restarting => Color::Modified,
+                Kernel::Restarting(_) => Color::Modified,
             })
             .child(Label::new(self.kernel_specification.name()))
             .children(status_text.map(|status_text| Label::new(format!("({status_text})"))))
+            .when(!self.total_compute_time.is_zero(), |item| {
+                item.child(
+                    Label::new(format!(
+                        "{} compute",
+                        human_duration(self.total_compute_time)
+                    ))
+                    .color(Color::Muted),
+                )
+            })
+            .children(
+                self.kernel
+                    .resource_usage()
+                    .map(|usage| Label::new(usage.to_string()).color(Color::Muted)),
+            )
             .button(
                 Button::new("shutdown", "Shutdown")
                     .style(ButtonStyle::Subtle)
-                    .disabled(self.kernel.is_shutting_down())
+                    .disabled(!self.can_run(KernelCommand::Shutdown))
                     .on_click(cx.listener(move |session, _, window, cx| {
                         session.shutdown(window, cx);
                     })),
@@ -977,11 +1443,30 @@ impl Render for Session {
 
 impl KernelSession for Session {
     fn route(&mut self, message: &JupyterMessage, window: &mut Window, cx: &mut Context<Self>) {
+        // Debug events aren't always sent in reply to a request, so they're handled before
+        // messages without a parent are dropped.
+        if kernel_debugger::is_debug_message(message) {
+            if let Some(connection) = self.debug_connection.as_mut() {
+                let messages = connection.debugger.handle_kernel_message(message);
+                self.handle_debug_messages(messages, cx);
+            }
+            return;
+        }
+
         let parent_message_id = match message.parent_header.as_ref() {
             Some(header) => &header.msg_id,
             None => return,
         };
 
+        if self.unresponsive {
+            self.unresponsive = false;
+            cx.notify();
+        }
+
+        self.history
+            .set_max_entries(ReplSettings::get_global(cx).execution_history_max_entries);
+        self.history.record(message, Utc::now());
+
         match &message.content {
             JupyterMessageContent::Status(status) => {
                 self.kernel.set_execution_state(&status.execution_state);
@@ -1008,7 +1493,13 @@ impl KernelSession for Session {
 
                 self.blocks.iter_mut().for_each(|(_, block)| {
                     block.execution_view.update(cx, |execution_view, cx| {
-                        execution_view.update_display_data(&update.data, &display_id, window, cx);
+                        execution_view.update_display_data(
+                            &update.data,
+                            &update.metadata,
+                            &display_id,
+                            window,
+                            cx,
+                        );
                     });
                 });
                 return;
@@ -1016,6 +1507,30 @@ impl KernelSession for Session {
             _ => {}
         }
 
+        // The lost executions are recovered after the probe's own status is applied, so that
+        // its busy status doesn't undo the kernel going back to idle.
+        if let Some(probe_sent_at) = self.watchdog.probe_answered(message) {
+            self.recover_lost_executions(probe_sent_at, cx);
+        }
+
+        if let Some(timing) = self.execution_timings.get_mut(parent_message_id) {
+            timing.record(message, Utc::now());
+            let timing = timing.clone();
+            if timing.is_complete() {
+                self.total_compute_time += timing.run_duration().unwrap_or_default();
+                self.execution_timings.remove(parent_message_id);
+                cx.notify();
+            }
+
+            // Update the timing before the block sees the idle status, so that it's
+            // available when the block finishes.
+            if let Some(block) = self.blocks.get(parent_message_id) {
+                block.execution_view.update(cx, |execution_view, cx| {
+                    execution_view.set_timing(timing, cx);
+                });
+            }
+        }
+
         if let Some(block) = self.blocks.get_mut(parent_message_id) {
             block.handle_message(message, window, cx);
         }
@@ -1024,4 +1539,21 @@ impl KernelSession for Session {
     fn kernel_errored(&mut self, error_message: String, cx: &mut Context<Self>) {
         self.kernel_errored(error_message, cx);
     }
+
+    fn requests_failed(
+        &mut self,
+        msg_ids: &[String],
+        error_message: String,
+        cx: &mut Context<Self>,
+    ) {
+        for msg_id in msg_ids {
+            self.execution_timings.remove(msg_id);
+            if let Some(block) = self.blocks.get(msg_id) {
+                block.execution_view.update(cx, |execution_view, cx| {
+                    execution_view.status = ExecutionStatus::KernelErrored(error_message.clone());
+                    cx.notify();
+                });
+            }
+        }
+    }
 }
@@ -1044,6 +1044,9 @@ impl VsCodeSettings {
                         .collect::<Vec<_>>()
                 })
                 .filter(|r| !r.is_empty()),
+            new_file_bom: self
+                .read_str("files.encoding")
+                .map(|encoding| encoding == "utf8bom"),
         }
     }
 }
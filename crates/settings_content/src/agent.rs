@@ -107,10 +107,88 @@ pub struct AgentSettingsContent {
     ///
     /// Default: 4
     pub message_editor_min_lines: Option<usize>,
-    /// Whether to show turn statistics (elapsed time during generation, final turn duration).
+    /// Whether to show turn statistics (elapsed time during generation, final turn duration, and
+    /// the time the turn's tool calls took).
     ///
     /// Default: false
     pub show_turn_stats: Option<bool>,
+    /// Whether the edit file tool should re-read the edited regions after saving and warn the
+    /// agent when they don't contain the text it wrote.
+    ///
+    /// Default: false
+    pub verify_edits: Option<bool>,
+    /// What the edit file tool does when an edit introduces syntax errors into a file whose
+    /// language has a parser.
+    ///
+    /// Default: warn
+    pub edit_syntax_check: Option<EditSyntaxCheck>,
+    /// How the edit file tools look for credentials, like API keys and private keys, in the
+    /// text that an edit adds. When they find one, you're asked before the edit is saved.
+    pub edit_secret_scan: Option<EditSecretScanContent>,
+    /// Whether the edit file tool may edit files on top of their unsaved changes without asking
+    /// first, when the agent requests it. Otherwise, you're asked the first time in each thread.
+    ///
+    /// Default: false
+    pub always_allow_unsaved_edits: Option<bool>,
+    /// Whether the edit file tool leaves its edits unsaved until you approve them. Edits are
+    /// shown in the editor right away, and approving saves every file with pending edits.
+    ///
+    /// Default: false
+    pub edits_require_approval: Option<bool>,
+    /// The size in bytes above which the edit file tool refuses to edit or write a file, unless
+    /// the agent explicitly asks to work on a large file.
+    ///
+    /// Default: 2097152
+    pub edit_file_max_size: Option<u64>,
+    /// How many bytes of new content the edit file tool applies at a time when writing a whole
+    /// file, so that large writes don't block the editor.
+    ///
+    /// Default: 262144
+    pub edit_file_chunk_size: Option<usize>,
+    /// How similar, from 0 to 1, the text an edit replaces must be to the `old_text` the agent
+    /// gave for the edit file tool to apply it. Below this, the edit fails and the agent is shown
+    /// the closest text in the file so that it can correct itself.
+    ///
+    /// Default: 0.8
+    pub edit_match_min_similarity: Option<f32>,
+    /// How similar, from 0 to 1, the replaced text must be to the agent's `old_text` for the edit
+    /// file tool to treat the match as exact. Less similar matches are still applied, but are
+    /// pointed out to the agent and highlighted in the edit card. Whitespace at the start and end
+    /// of lines is ignored.
+    ///
+    /// Default: 1.0
+    pub edit_match_exact_similarity: Option<f32>,
+    /// When an edit file tool edit deletes whole lines, whether the line break and, if the lines
+    /// were framed by blank lines, one of those are removed along with them, so the deletion doesn't
+    /// leave an empty line or a double blank line behind.
+    ///
+    /// Default: true
+    pub edit_tidy_deletions: Option<bool>,
+    /// How many read-only tool calls from one response, like reading files and searching, the
+    /// agent runs at once. Calls that change things, like edits and terminal commands, always
+    /// run one at a time, after the reads.
+    ///
+    /// Default: 4
+    pub max_concurrent_tool_calls: Option<usize>,
+    /// Globs for files that describe a project's conventions, like contribution guides, matched
+    /// against paths relative to each worktree. The agent is told which of them exist, with a
+    /// short excerpt of each, and can read them in full with the `read_instructions` tool.
+    ///
+    /// Default: ["AGENTS.md", ".zed/agent.md", "CONTRIBUTING.md"]
+    pub instruction_files: Option<Vec<String>>,
+    /// Which addresses the fetch tool may fetch from.
+    pub fetch: Option<FetchSettingsContent>,
+    /// Prices to estimate the cost of threads with, keyed by model ID, or by `provider/model`
+    /// to only apply to one provider's model. These take precedence over the built-in prices
+    /// for well-known models.
+    ///
+    /// Default: {}
+    pub model_prices: Option<HashMap<String, ModelPriceContent>>,
+    /// An estimated cost, in US dollars, past which the agent panel warns that a thread has gone
+    /// over budget. The warning doesn't stop the thread.
+    ///
+    /// Default: null
+    pub thread_cost_budget: Option<f64>,
     /// Per-tool permission rules for granular control over which tool actions
     /// require confirmation.
     ///
@@ -245,6 +323,115 @@ pub enum NotifyWhenAgentWaiting {
     Never,
 }
 
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    Debug,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    MergeFrom,
+    PartialEq,
+    strum::VariantArray,
+    strum::VariantNames,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EditSyntaxCheck {
+    /// Don't check edited files for syntax errors.
+    Off,
+    /// Keep the edits, and tell the agent where the new syntax errors are.
+    #[default]
+    Warn,
+    /// Undo the edits and report the new syntax errors as a failure.
+    Revert,
+}
+
+#[with_fallible_options]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+pub struct EditSecretScanContent {
+    /// Whether to look for credentials in the agent's edits.
+    ///
+    /// Default: true
+    pub enabled: Option<bool>,
+    /// Patterns for credentials to look for, on top of the built-in ones for AWS access keys,
+    /// private keys, and `token = "..."` style assignments.
+    /// Patterns accumulate across settings layers.
+    ///
+    /// Default: []
+    pub patterns: Option<ExtendingVec<SecretPatternContent>>,
+    /// Globs for paths whose edits aren't scanned, like test fixtures, matched against the
+    /// path relative to its worktree.
+    /// Globs accumulate across settings layers.
+    ///
+    /// Default: []
+    pub allowed_paths: Option<ExtendingVec<String>>,
+    /// Case-sensitive regexes for matches that aren't credentials, like a well-known example
+    /// key.
+    /// Patterns accumulate across settings layers.
+    ///
+    /// Default: []
+    pub ignored_matches: Option<ExtendingVec<String>>,
+}
+
+#[with_fallible_options]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+pub struct SecretPatternContent {
+    /// What the pattern finds, shown when asking about the edit.
+    #[serde(default)]
+    pub name: String,
+    /// The regex to match. If it has a capture group, the first group is the secret itself.
+    #[serde(default)]
+    pub pattern: String,
+    /// Whether the regex is case-sensitive.
+    /// Default: false (case-insensitive)
+    pub case_sensitive: Option<bool>,
+    /// The Shannon entropy, in bits per character, that the secret must reach for a match to
+    /// count. Use it for patterns that would otherwise also match placeholders and ordinary
+    /// identifiers.
+    /// Default: none
+    pub min_entropy: Option<f32>,
+}
+
+#[with_fallible_options]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+pub struct FetchSettingsContent {
+    /// Whether the fetch tool may fetch from loopback and private network addresses, like
+    /// `localhost` and `192.168.1.10`, including through redirects and domains that resolve
+    /// to them.
+    ///
+    /// Default: false
+    pub allow_private_network: Option<bool>,
+    /// Domains the fetch tool may fetch from, along with their subdomains. When empty, it may
+    /// fetch from any domain that isn't denied.
+    ///
+    /// Default: []
+    pub allowed_domains: Option<Vec<String>>,
+    /// Domains the fetch tool refuses to fetch from, along with their subdomains.
+    /// Domains accumulate across settings layers.
+    ///
+    /// Default: []
+    pub denied_domains: Option<ExtendingVec<String>>,
+}
+
+/// What a model costs, in US dollars per million tokens.
+#[with_fallible_options]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+pub struct ModelPriceContent {
+    /// The price of input tokens.
+    pub input: Option<f64>,
+    /// The price of output tokens.
+    pub output: Option<f64>,
+    /// The price of input tokens written to the prompt cache.
+    ///
+    /// Default: the input price
+    pub cache_write: Option<f64>,
+    /// The price of input tokens read from the prompt cache.
+    ///
+    /// Default: the input price
+    pub cache_read: Option<f64>,
+}
+
 #[with_fallible_options]
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, MergeFrom, PartialEq)]
 pub struct LanguageModelSelection {
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use std::num;
+use std::path::PathBuf;
 
 use collections::HashMap;
 use schemars::JsonSchema;
@@ -843,6 +844,84 @@ pub struct JupyterContent {
     ///
     /// Default: `{}`
     pub kernel_selections: Option<HashMap<String, String>>,
+
+    /// Kernels to offer in the kernel picker alongside the discovered kernelspecs, for kernels
+    /// that are started by a custom launcher instead of an installed kernelspec.
+    ///
+    /// Default: `[]`
+    pub custom_kernels: Option<Vec<CustomKernelContent>>,
+}
+
+/// A kernel started by a command of the user's choosing, defined in the `jupyter.custom_kernels`
+/// setting.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CustomKernelContent {
+    /// The name of the kernel, which can be used in `kernel_selections`.
+    pub name: String,
+    /// The command that starts the kernel, followed by its arguments. One of the arguments must
+    /// contain `{connection_file}`, which is replaced by the path of the kernel's connection file.
+    pub argv: Vec<String>,
+    /// The directory to start the kernel in. Defaults to the directory the kernel is started for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Environment variables to set for the kernel process.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// The language the kernel runs, such as "python".
+    pub language: String,
+    /// The name to show in the kernel picker. Defaults to `name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+}
+
+/// The placeholder in a kernel's `argv` for the path of its connection file.
+pub const CONNECTION_FILE_PLACEHOLDER: &str = "{connection_file}";
+
+impl<'de> Deserialize<'de> for CustomKernelContent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields {
+            name: String,
+            argv: Vec<String>,
+            #[serde(default)]
+            cwd: Option<PathBuf>,
+            #[serde(default)]
+            env: HashMap<String, String>,
+            language: String,
+            #[serde(default)]
+            display_name: Option<String>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        // Report broken kernels when the settings are loaded, rather than when they're started.
+        if fields.argv.is_empty() {
+            return Err(serde::de::Error::custom(format!(
+                "custom kernel `{}` has an empty `argv`",
+                fields.name
+            )));
+        }
+        if !fields
+            .argv
+            .iter()
+            .any(|arg| arg.contains(CONNECTION_FILE_PLACEHOLDER))
+        {
+            return Err(serde::de::Error::custom(format!(
+                "custom kernel `{}` must pass `{CONNECTION_FILE_PLACEHOLDER}` in its `argv`",
+                fields.name
+            )));
+        }
+        Ok(Self {
+            name: fields.name,
+            argv: fields.argv,
+            cwd: fields.cwd,
+            env: fields.env,
+            language: fields.language,
+            display_name: fields.display_name,
+        })
+    }
 }
 
 /// Whether to allow drag and drop text selection in buffer.
@@ -1041,3 +1120,50 @@ impl schemars::JsonSchema for CenteredPaddingSettings {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseStatus, fallible_options};
+
+    #[test]
+    fn test_custom_kernel_validation() {
+        let (content, status) = fallible_options::parse_json::<JupyterContent>(
+            r#"{
+                "custom_kernels": [{
+                    "name": "docker-python",
+                    "argv": ["./launch.sh", "--connection={connection_file}"],
+                    "language": "python"
+                }]
+            }"#,
+        );
+        assert_eq!(status, ParseStatus::Success);
+        let kernel = &content.unwrap().custom_kernels.unwrap()[0];
+        assert_eq!(kernel.name, "docker-python");
+        assert_eq!(kernel.cwd, None);
+        assert!(kernel.env.is_empty());
+
+        for (argv, expected_error) in [
+            ("[]", "custom kernel `broken` has an empty `argv`"),
+            (
+                r#"["python", "-m", "ipykernel_launcher"]"#,
+                "custom kernel `broken` must pass `{connection_file}` in its `argv`",
+            ),
+        ] {
+            let (content, status) = fallible_options::parse_json::<JupyterContent>(&format!(
+                r#"{{
+                    "enabled": true,
+                    "custom_kernels": [{{ "name": "broken", "argv": {argv}, "language": "python" }}]
+                }}"#
+            ));
+            let ParseStatus::Failed { error } = status else {
+                panic!("expected {argv} to be rejected");
+            };
+            assert!(error.contains(expected_error), "unexpected error: {error}");
+            // The rest of the settings still apply.
+            let content = content.unwrap();
+            assert_eq!(content.enabled, Some(true));
+            assert_eq!(content.custom_kernels, None);
+        }
+    }
+}
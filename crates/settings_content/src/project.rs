@@ -138,6 +138,12 @@ pub struct WorktreeSettingsContent {
     /// external dependencies that should not be modified directly.
     /// Default: []
     pub read_only_files: Option<Vec<String>>,
+
+    /// Whether files created by the agent start with a UTF-8 byte order mark (BOM).
+    /// Existing files keep the BOM they have, if any.
+    ///
+    /// Default: false
+    pub new_file_bom: Option<bool>,
 }
 
 #[with_fallible_options]
@@ -395,14 +395,12 @@ pub struct AudioSettingsContent {
     /// You need to rejoin a call for this setting to apply
     #[serde(rename = "experimental.legacy_audio_compatible")]
     pub legacy_audio_compatible: Option<bool>,
-    /// Requires 'rodio_audio: true'
-    ///
-    /// Select specific output audio device.
+    /// Select specific output audio device. While it's unplugged during a call,
+    /// the system default is used instead.
     #[serde(rename = "experimental.output_audio_device")]
     pub output_audio_device: Option<AudioOutputDeviceName>,
-    /// Requires 'rodio_audio: true'
-    ///
-    /// Select specific input audio device.
+    /// Select specific input audio device. While it's unplugged during a call,
+    /// the system default is used instead.
     #[serde(rename = "experimental.input_audio_device")]
     pub input_audio_device: Option<AudioInputDeviceName>,
 }
@@ -567,10 +565,128 @@ pub struct CallSettingsContent {
     /// Default: false
     pub mute_on_join: Option<bool>,
 
+    /// Join muted anyway when more than this many other people are already in the call,
+    /// unless the channel has its own `mute_on_join` preference. Set to 0 to never do this.
+    ///
+    /// Default: 5
+    pub auto_mute_on_join_threshold: Option<usize>,
+
     /// Whether your current project should be shared when joining an empty channel.
     ///
     /// Default: false
     pub share_on_join: Option<bool>,
+
+    /// How to join the calls of particular channels, keyed by channel ID. Any preference a
+    /// channel leaves out falls back to `mute_on_join` and `share_on_join`.
+    ///
+    /// Default: {}
+    pub channels: Option<HashMap<u64, ChannelCallSettingsContent>>,
+
+    /// The sound played while someone is calling you.
+    ///
+    /// Default: classic
+    pub incoming_call_sound: Option<IncomingCallSound>,
+
+    /// The sound file to play for incoming calls when `incoming_call_sound` is `custom`.
+    ///
+    /// Must be an absolute path (or start with `~`) to a WAV file. If it can't be read,
+    /// the classic sound is played instead.
+    ///
+    /// Default: null
+    pub custom_incoming_call_sound_path: Option<String>,
+
+    /// The volume of the incoming call sound, from 0 to 1.
+    ///
+    /// Default: 1.0
+    #[serde(serialize_with = "crate::serialize_optional_f32_with_two_decimal_places")]
+    pub incoming_call_volume: Option<f32>,
+
+    /// The volume of the sounds played during a call, such as when someone joins or leaves,
+    /// from 0 to 1.
+    ///
+    /// Default: 1.0
+    #[serde(serialize_with = "crate::serialize_optional_f32_with_two_decimal_places")]
+    pub call_notification_volume: Option<f32>,
+
+    /// How long someone you let control your shared screen keeps control, in minutes, unless
+    /// you take it back sooner.
+    ///
+    /// Default: 15
+    pub remote_control_timeout_minutes: Option<u64>,
+
+    /// Globs for the names of files that are likely to hold secrets. Before sharing a project
+    /// that contains any, you're asked whether to share them, keep them from guests, or cancel.
+    ///
+    /// Default: [".env*", "id_rsa", "*.pem", "credentials.json"]
+    pub secret_files: Option<Vec<String>>,
+
+    /// Whether others in the call can see which file you have open in a shared project, and
+    /// which line your cursor is on. When off, they only see which project you're in.
+    ///
+    /// Default: true
+    pub share_active_location: Option<bool>,
+
+    /// Whether to filter background noise, like typing or fans, out of your microphone. It can
+    /// be turned off for a single call from the microphone's menu.
+    ///
+    /// Default: true
+    pub noise_suppression: Option<bool>,
+
+    /// Whether to keep the call's audio from echoing back through your microphone, for when
+    /// you're not wearing headphones.
+    ///
+    /// Default: true
+    pub echo_cancellation: Option<bool>,
+
+    /// Whether to adjust your microphone's volume so that you're neither too quiet nor too
+    /// loud.
+    ///
+    /// Default: true
+    pub auto_gain: Option<bool>,
+}
+
+/// How to join a single channel's call.
+#[with_fallible_options]
+#[derive(Clone, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema, MergeFrom, Debug)]
+pub struct ChannelCallSettingsContent {
+    /// Whether the microphone should be muted when joining this channel.
+    pub mute_on_join: Option<bool>,
+
+    /// Whether other participants should be silenced when joining this channel.
+    pub deafen_on_join: Option<bool>,
+
+    /// Whether your current project should be shared when joining this channel while it's empty.
+    pub share_on_join: Option<bool>,
+
+    /// Whether to add a summary of the call, with who attended and what was shared, to the
+    /// channel's notes when you're the last one to leave it. The summary is copied to the
+    /// clipboard when the notes can't be edited.
+    pub export_summary_to_notes: Option<bool>,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    Debug,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    MergeFrom,
+    PartialEq,
+    Eq,
+    strum::VariantArray,
+    strum::VariantNames,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum IncomingCallSound {
+    #[default]
+    Classic,
+    Chime,
+    /// The file set in `custom_incoming_call_sound_path`.
+    Custom,
+    /// Don't play a sound; incoming calls are only shown on screen.
+    Silent,
 }
 
 #[with_fallible_options]
@@ -1122,7 +1238,7 @@ pub struct SshPortForwardOption {
 
 /// Settings for configuring REPL display and behavior.
 #[with_fallible_options]
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
 pub struct ReplSettingsContent {
     /// Maximum number of lines to keep in REPL's scrollback buffer.
     /// Clamped with [4, 256] range.
@@ -1153,6 +1269,67 @@ pub struct ReplSettingsContent {
     ///
     /// Default: 0
     pub output_max_width_columns: Option<usize>,
+    /// Whether restarting the kernel to run all notebook cells clears their previous outputs.
+    /// When disabled, previous outputs are dimmed until their cell runs again.
+    ///
+    /// Default: true
+    pub clear_outputs_on_restart: Option<bool>,
+    /// Whether running all notebook cells after a restart keeps going past a cell that errors.
+    ///
+    /// Default: false
+    pub continue_on_error: Option<bool>,
+    /// Maximum number of bytes of stream output (stdout and stderr) a single execution keeps.
+    /// Output past this limit is written to a temporary file that can be opened in an editor.
+    ///
+    /// Default: 1048576
+    pub output_max_bytes: Option<usize>,
+    /// Maximum number of lines of stream output (stdout and stderr) a single execution keeps.
+    /// Output past this limit is written to a temporary file that can be opened in an editor.
+    ///
+    /// Default: 10000
+    pub output_max_lines: Option<usize>,
+    /// Maximum size in bytes of a rich output, such as an image, before it's replaced with
+    /// a placeholder instead of being displayed.
+    ///
+    /// Default: 20971520
+    pub display_data_max_bytes: Option<usize>,
+    /// Seconds a kernel can stay busy without sending anything for the running execution
+    /// before it's asked whether it's still there. If it answers, the execution's final
+    /// messages are assumed lost and it's marked as completed; if it doesn't, the kernel is
+    /// marked as unresponsive. Set to 0 to never check.
+    ///
+    /// Default: 60
+    pub busy_probe_timeout_secs: Option<u64>,
+    /// Maximum number of requests, such as code to run, that can wait to be sent to a kernel.
+    /// Code run while the queue is full waits in Zed until the kernel catches up.
+    ///
+    /// Default: 100
+    pub request_queue_capacity: Option<usize>,
+    /// Overrides `request_queue_capacity` for kernels by name.
+    ///
+    /// Default: {}
+    pub kernel_request_queue_capacity: Option<HashMap<String, usize>>,
+    /// Whether to check that a Python environment can run ipykernel before starting a kernel
+    /// from it, so that a broken environment is reported instead of failing to connect.
+    /// Turn this off if environments live on a file system too slow for the check.
+    ///
+    /// Default: true
+    pub validate_python_environments: Option<bool>,
+    /// Maximum number of executions each REPL session keeps in its history.
+    /// Set to 0 to not keep a history.
+    ///
+    /// Default: 500
+    pub execution_history_max_entries: Option<usize>,
+    /// Whether to write every message sent to and received from a kernel to a trace file
+    /// in the `kernel_traces` directory, one file per kernel launch.
+    ///
+    /// Default: false
+    pub trace_kernel_messages: Option<bool>,
+    /// Whether kernel message traces replace the text in messages with its length, so that
+    /// code and outputs don't end up in the trace files.
+    ///
+    /// Default: true
+    pub redact_kernel_message_traces: Option<bool>,
 }
 
 /// Settings for configuring the which-key popup behaviour.
@@ -6759,7 +6759,7 @@ fn version_control_page() -> SettingsPage {
 }
 
 fn collaboration_page() -> SettingsPage {
-    fn calls_section() -> [SettingsPageItem; 3] {
+    fn calls_section() -> [SettingsPageItem; 14] {
         [
             SettingsPageItem::SectionHeader("Calls"),
             SettingsPageItem::SettingItem(SettingItem {
@@ -6775,6 +6775,28 @@ fn collaboration_page() -> SettingsPage {
                 metadata: None,
                 files: USER,
             }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Auto Mute On Join Threshold",
+                description: "Join muted when more than this many people are already in the call. Set to 0 to disable.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.auto_mute_on_join_threshold"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .auto_mute_on_join_threshold
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .auto_mute_on_join_threshold = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
             SettingsPageItem::SettingItem(SettingItem {
                 title: "Share On Join",
                 description: "Whether your current project should be shared when joining an empty channel.",
@@ -6790,6 +6812,202 @@ fn collaboration_page() -> SettingsPage {
                 metadata: None,
                 files: USER,
             }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Incoming Call Sound",
+                description: "The sound played while someone is calling you.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.incoming_call_sound"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .incoming_call_sound
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .incoming_call_sound = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Custom Incoming Call Sound Path",
+                description: "The WAV file played for incoming calls when the incoming call sound is set to custom.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.custom_incoming_call_sound_path"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .custom_incoming_call_sound_path
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .custom_incoming_call_sound_path = value;
+                    },
+                }),
+                metadata: Some(Box::new(SettingsFieldMetadata {
+                    placeholder: Some("~/sounds/ringtone.wav"),
+                    ..Default::default()
+                })),
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Incoming Call Volume",
+                description: "The volume of the incoming call sound, from 0 to 1.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.incoming_call_volume"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .incoming_call_volume
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .incoming_call_volume = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::ActionLink(ActionLink {
+                title: "Preview Incoming Call Sound".into(),
+                description: Some("Play the incoming call sound once.".into()),
+                button_text: "Preview".into(),
+                on_click: Arc::new(|_settings_window, window, cx| {
+                    window.dispatch_action(
+                        zed_actions::call::PreviewIncomingCallSound.boxed_clone(),
+                        cx,
+                    );
+                }),
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Call Notification Volume",
+                description: "The volume of sounds played during a call, like participants joining or leaving, from 0 to 1.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.call_notification_volume"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .call_notification_volume
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .call_notification_volume = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Remote Control Timeout",
+                description: "How many minutes someone you let control your shared screen keeps control.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.remote_control_timeout_minutes"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .remote_control_timeout_minutes
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .remote_control_timeout_minutes = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Share Active Location",
+                description: "Show others in the call which file and line you're on in a shared project.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.share_active_location"),
+                    pick: |settings_content| {
+                        settings_content
+                            .calls
+                            .as_ref()?
+                            .share_active_location
+                            .as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .share_active_location = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Noise Suppression",
+                description: "Filter background noise, like typing or fans, out of your microphone.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.noise_suppression"),
+                    pick: |settings_content| {
+                        settings_content.calls.as_ref()?.noise_suppression.as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .noise_suppression = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Echo Cancellation",
+                description: "Keep the call's audio from echoing back through your microphone.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.echo_cancellation"),
+                    pick: |settings_content| {
+                        settings_content.calls.as_ref()?.echo_cancellation.as_ref()
+                    },
+                    write: |settings_content, value| {
+                        settings_content
+                            .calls
+                            .get_or_insert_default()
+                            .echo_cancellation = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
+            SettingsPageItem::SettingItem(SettingItem {
+                title: "Automatic Gain",
+                description: "Adjust your microphone's volume so that you're neither too quiet nor too loud.",
+                field: Box::new(SettingField {
+                    json_path: Some("calls.auto_gain"),
+                    pick: |settings_content| settings_content.calls.as_ref()?.auto_gain.as_ref(),
+                    write: |settings_content, value| {
+                        settings_content.calls.get_or_insert_default().auto_gain = value;
+                    },
+                }),
+                metadata: None,
+                files: USER,
+            }),
         ]
     }
 
@@ -62,6 +62,12 @@ const TOOLS: &[ToolInfo] = &[
         description: "File saving operations",
         regex_explanation: "Patterns are matched against the file path being saved.",
     },
+    ToolInfo {
+        id: "format_file",
+        name: "Format File",
+        description: "Formatting files with their configured formatter",
+        regex_explanation: "Patterns are matched against the file path being formatted.",
+    },
     ToolInfo {
         id: "fetch",
         name: "Fetch",
@@ -304,6 +310,7 @@ fn get_tool_render_fn(
         "move_path" => render_move_path_tool_config,
         "create_directory" => render_create_directory_tool_config,
         "save_file" => render_save_file_tool_config,
+        "format_file" => render_format_file_tool_config,
         "fetch" => render_fetch_tool_config,
         "web_search" => render_web_search_tool_config,
         "restore_file_from_disk" => render_restore_file_from_disk_tool_config,
@@ -1380,6 +1387,7 @@ tool_config_page_fn!(render_copy_path_tool_config, "copy_path");
 tool_config_page_fn!(render_move_path_tool_config, "move_path");
 tool_config_page_fn!(render_create_directory_tool_config, "create_directory");
 tool_config_page_fn!(render_save_file_tool_config, "save_file");
+tool_config_page_fn!(render_format_file_tool_config, "format_file");
 tool_config_page_fn!(render_fetch_tool_config, "fetch");
 tool_config_page_fn!(render_web_search_tool_config, "web_search");
 tool_config_page_fn!(
@@ -1399,13 +1407,18 @@ mod tests {
         //   2. Add it to this list with a comment explaining why it's excluded.
         const EXCLUDED_TOOLS: &[&str] = &[
             // Read-only / low-risk tools that don't call decide_permission_from_settings
+            "code_symbols",
             "diagnostics",
+            "file_history",
             "find_path",
             "grep",
             "list_directory",
+            "list_touched_files",
             "now",
             "open",
             "read_file",
+            "read_instructions",
+            "task_list",
             "thinking",
             // streaming_edit_file uses "edit_file" for permission lookups,
             // so its rules are configured under the edit_file entry.
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 use std::sync::Arc;
 
-use call::{ActiveCall, Room};
+use call::{ActiveCall, AudioProcessing, Room, screen_share_quality::ScreenSharePreset};
 use channel::ChannelStore;
 use client::{User, proto::PeerId};
 use gpui::{
@@ -14,11 +14,12 @@ use rpc::proto::{self};
 use settings::{Settings as _, SettingsLocation};
 use theme::ActiveTheme;
 use ui::{
-    Avatar, AvatarAudioStatusIndicator, ContextMenu, ContextMenuItem, Divider, DividerColor,
-    Facepile, PopoverMenu, SplitButton, SplitButtonStyle, TintColor, Tooltip, prelude::*,
+    AudioStatus, Avatar, AvatarAudioStatusIndicator, ContextMenu, ContextMenuEntry,
+    ContextMenuItem, Divider, DividerColor, Facepile, PopoverMenu, SplitButton, SplitButtonStyle,
+    TintColor, Tooltip, prelude::*,
 };
-use util::rel_path::RelPath;
-use workspace::{ParticipantLocation, notifications::DetachAndPromptErr};
+use util::{ResultExt as _, rel_path::RelPath};
+use workspace::{ParticipantCapabilities, ParticipantLocation, notifications::DetachAndPromptErr};
 
 use crate::TitleBar;
 
@@ -98,6 +99,32 @@ pub fn toggle_mute(cx: &mut App) {
     }
 }
 
+pub fn set_screen_share_preset(preset: ScreenSharePreset, cx: &mut App) {
+    if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
+        room.update(cx, |room, cx| room.set_screen_share_preset(preset, cx))
+            .detach_and_log_err(cx);
+    }
+}
+
+/// Stops or resumes lowering the screen share's quality when the connection can't keep up.
+pub fn set_screen_share_preset_pinned(pinned: bool, cx: &mut App) {
+    if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
+        room.update(cx, |room, cx| {
+            room.set_screen_share_preset_pinned(pinned, cx)
+        });
+    }
+}
+
+/// Turns `processing` on or off for the rest of the current call.
+pub fn set_audio_processing(processing: AudioProcessing, enabled: bool, cx: &mut App) {
+    if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
+        room.update(cx, |room, cx| {
+            room.set_audio_processing(processing, enabled, cx)
+        })
+        .log_err();
+    }
+}
+
 pub fn toggle_deafen(cx: &mut App) {
     if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
         room.update(cx, |room, cx| room.toggle_deafen(cx));
@@ -139,6 +166,7 @@ impl TitleBar {
         let current_user = self.user_store.read(cx).current_user();
         let client = self.client.clone();
         let project_id = self.project.read(cx).remote_id();
+        let path_style = self.project.read(cx).path_style(cx);
         let workspace = self.workspace.upgrade();
 
         h_flex()
@@ -151,6 +179,9 @@ impl TitleBar {
                 |this, ((current_user, peer_id), room)| {
                     let player_colors = cx.theme().players();
                     let room = room.read(cx);
+                    let can_follow = room
+                        .local_capabilities()
+                        .contains(ParticipantCapabilities::FOLLOW);
                     let mut remote_participants =
                         room.remote_participants().values().collect::<Vec<_>>();
                     remote_participants.sort_by_key(|p| p.participant_index.0);
@@ -160,7 +191,11 @@ impl TitleBar {
                         peer_id,
                         true,
                         room.is_speaking(),
-                        room.is_muted(),
+                        if room.is_listen_only() {
+                            Some(AudioStatus::Listening)
+                        } else {
+                            room.is_muted().then_some(AudioStatus::Muted)
+                        },
                         None,
                         room,
                         project_id,
@@ -193,7 +228,11 @@ impl TitleBar {
                             collaborator.peer_id,
                             is_present,
                             collaborator.speaking,
-                            collaborator.muted,
+                            if collaborator.listening {
+                                Some(AudioStatus::Listening)
+                            } else {
+                                collaborator.muted.then_some(AudioStatus::Muted)
+                            },
                             is_following.then_some(player_color.selection),
                             room,
                             project_id,
@@ -206,30 +245,45 @@ impl TitleBar {
                                 .id(("collaborator", collaborator.user.id))
                                 .child(facepile)
                                 .child(render_color_ribbon(player_color.cursor))
-                                .cursor_pointer()
                                 .on_mouse_down(MouseButton::Left, |_, window, _| {
                                     window.prevent_default()
                                 })
-                                .on_click({
+                                .when(can_follow, |this| {
                                     let peer_id = collaborator.peer_id;
-                                    cx.listener(move |this, _, window, cx| {
-                                        cx.stop_propagation();
-
-                                        this.workspace
-                                            .update(cx, |workspace, cx| {
-                                                if is_following {
-                                                    workspace.unfollow(peer_id, window, cx);
-                                                } else {
-                                                    workspace.follow(peer_id, window, cx);
-                                                }
-                                            })
-                                            .ok();
-                                    })
+                                    this.cursor_pointer().on_click(cx.listener(
+                                        move |this, _, window, cx| {
+                                            cx.stop_propagation();
+
+                                            this.workspace
+                                                .update(cx, |workspace, cx| {
+                                                    if is_following {
+                                                        workspace.unfollow(peer_id, window, cx);
+                                                    } else {
+                                                        workspace.follow(peer_id, window, cx);
+                                                    }
+                                                })
+                                                .ok();
+                                        },
+                                    ))
                                 })
                                 .occlude()
                                 .tooltip({
-                                    let login = collaborator.user.github_login.clone();
-                                    Tooltip::text(format!("Follow {login}"))
+                                    let title = SharedString::from(format!(
+                                        "Follow {}",
+                                        collaborator.user.github_login
+                                    ));
+                                    let active_location = collaborator
+                                        .active_location_label(path_style)
+                                        .filter(|_| is_present);
+                                    move |_window, cx| match active_location.clone() {
+                                        Some(active_location) => Tooltip::with_meta(
+                                            title.clone(),
+                                            None,
+                                            active_location,
+                                            cx,
+                                        ),
+                                        None => Tooltip::simple(title.clone(), cx),
+                                    }
                                 }),
                         )
                     }))
@@ -243,7 +297,7 @@ impl TitleBar {
         peer_id: PeerId,
         is_present: bool,
         is_speaking: bool,
-        is_muted: bool,
+        audio_status: Option<AudioStatus>,
         leader_selection_color: Option<Hsla>,
         room: &Room,
         project_id: Option<u64>,
@@ -279,13 +333,17 @@ impl TitleBar {
                                     // the layout shift that would come with adding/removing the border.
                                     gpui::transparent_black()
                                 })
-                                .when(is_muted, |avatar| {
+                                .when_some(audio_status, |avatar, audio_status| {
                                     avatar.indicator(
-                                        AvatarAudioStatusIndicator::new(ui::AudioStatus::Muted)
-                                            .tooltip({
-                                                let github_login = user.github_login.clone();
-                                                Tooltip::text(format!("{} is muted", github_login))
-                                            }),
+                                        AvatarAudioStatusIndicator::new(audio_status).tooltip({
+                                            let github_login = user.github_login.clone();
+                                            Tooltip::text(match audio_status {
+                                                AudioStatus::Listening => {
+                                                    format!("{} is only listening", github_login)
+                                                }
+                                                _ => format!("{} is muted", github_login),
+                                            })
+                                        }),
                                     )
                                 }),
                         )
@@ -343,8 +401,14 @@ impl TitleBar {
         let muted_by_user = room.muted_by_user();
         let is_deafened = room.is_deafened().unwrap_or(false);
         let is_screen_sharing = room.is_sharing_screen();
-        let can_use_microphone = room.can_use_microphone();
-        let can_share_projects = room.can_share_projects();
+        let is_listen_only = room.is_listen_only();
+        let capabilities = room.local_capabilities();
+        let can_use_microphone = capabilities.contains(ParticipantCapabilities::USE_MICROPHONE);
+        let can_share_screen = capabilities.contains(ParticipantCapabilities::SHARE_SCREEN);
+        let can_process_audio = AudioProcessing::ALL
+            .iter()
+            .any(|processing| room.audio_processing(*processing, cx).is_some());
+        let can_share_projects = capabilities.contains(ParticipantCapabilities::SHARE_PROJECTS);
         let screen_sharing_supported = cx.is_screen_capture_supported();
 
         let channel_store = ChannelStore::global(cx);
@@ -372,6 +436,15 @@ impl TitleBar {
                 .into_any_element(),
         );
 
+        if room.status().is_migrating() {
+            children.push(
+                LoadingLabel::new("Reconnecting")
+                    .size(LabelSize::Small)
+                    .color(Color::Muted)
+                    .into_any_element(),
+            );
+        }
+
         if is_local && can_share_projects && !is_connecting_to_project {
             let is_sharing_disabled = channel.is_some_and(|channel| match channel.visibility {
                 proto::ChannelVisibility::Public => project.visible_worktrees(cx).any(|worktree| {
@@ -410,46 +483,66 @@ impl TitleBar {
                     if is_shared {
                         this.unshare_project(window, cx);
                     } else {
-                        this.share_project(cx);
+                        this.share_project(window, cx);
                     }
                 }))
                 .into_any_element(),
             );
         }
 
-        if can_use_microphone {
+        if is_listen_only && can_use_microphone {
             children.push(
-                IconButton::new(
-                    "mute-microphone",
-                    if is_muted {
-                        IconName::MicMute
-                    } else {
-                        IconName::Mic
-                    },
-                )
-                .tooltip(move |_window, cx| {
-                    if is_muted {
-                        if is_deafened {
-                            Tooltip::with_meta(
-                                "Unmute Microphone",
-                                None,
-                                "Audio will be unmuted",
-                                cx,
-                            )
-                        } else {
-                            Tooltip::simple("Unmute Microphone", cx)
+                Button::new("start-participating", "Start Participating")
+                    .style(ButtonStyle::Tinted(TintColor::Accent))
+                    .label_size(LabelSize::Small)
+                    .tooltip(Tooltip::text(
+                        "Open your microphone and join the conversation",
+                    ))
+                    .on_click(move |_, _, cx| {
+                        if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
+                            room.update(cx, |room, cx| room.start_participating(cx))
+                                .detach_and_log_err(cx);
                         }
+                    })
+                    .into_any_element(),
+            );
+        } else if can_use_microphone {
+            let mute_button = IconButton::new(
+                "mute-microphone",
+                if is_muted {
+                    IconName::MicMute
+                } else {
+                    IconName::Mic
+                },
+            )
+            .tooltip(move |_window, cx| {
+                if is_muted {
+                    if is_deafened {
+                        Tooltip::with_meta("Unmute Microphone", None, "Audio will be unmuted", cx)
                     } else {
-                        Tooltip::simple("Mute Microphone", cx)
+                        Tooltip::simple("Unmute Microphone", cx)
                     }
-                })
-                .style(ButtonStyle::Subtle)
-                .icon_size(IconSize::Small)
-                .toggle_state(is_muted)
-                .selected_style(ButtonStyle::Tinted(TintColor::Error))
-                .on_click(move |_, _window, cx| toggle_mute(cx))
-                .into_any_element(),
-            );
+                } else {
+                    Tooltip::simple("Mute Microphone", cx)
+                }
+            })
+            .style(ButtonStyle::Subtle)
+            .icon_size(IconSize::Small)
+            .toggle_state(is_muted)
+            .selected_style(ButtonStyle::Tinted(TintColor::Error))
+            .on_click(move |_, _window, cx| toggle_mute(cx));
+            if can_process_audio {
+                children.push(
+                    SplitButton::new(
+                        mute_button,
+                        self.render_microphone_menu().into_any_element(),
+                    )
+                    .style(SplitButtonStyle::Transparent)
+                    .into_any_element(),
+                );
+            } else {
+                children.push(mute_button.into_any_element());
+            }
         }
 
         children.push(
@@ -488,7 +581,30 @@ impl TitleBar {
             .into_any_element(),
         );
 
-        if can_use_microphone && screen_sharing_supported {
+        if let Some(controller) = room
+            .remote_control()
+            .grant()
+            .and_then(|grant| room.remote_participant_for_peer_id(grant.controller))
+        {
+            children.push(
+                Button::new("revoke-screen-control", "Revoke Control")
+                    .style(ButtonStyle::Tinted(TintColor::Warning))
+                    .label_size(LabelSize::Small)
+                    .tooltip(Tooltip::text(format!(
+                        "{} can control your screen",
+                        controller.user.github_login
+                    )))
+                    .on_click(move |_, _, cx| {
+                        if let Some(room) = ActiveCall::global(cx).read(cx).room().cloned() {
+                            room.update(cx, |room, cx| room.revoke_control(cx))
+                                .detach_and_log_err(cx);
+                        }
+                    })
+                    .into_any_element(),
+            );
+        }
+
+        if can_share_screen && screen_sharing_supported && !is_listen_only {
             let trigger = IconButton::new("screen-share", IconName::Screen)
                 .style(ButtonStyle::Subtle)
                 .icon_size(IconSize::Small)
@@ -534,6 +650,43 @@ impl TitleBar {
         children
     }
 
+    /// The processing the microphone can go through in the current call, as the call's audio
+    /// pipeline reports it when the menu opens.
+    fn render_microphone_menu(&self) -> impl IntoElement {
+        PopoverMenu::new("microphone-menu")
+            .with_handle(self.microphone_popover_handle.clone())
+            .trigger(
+                ui::ButtonLike::new_rounded_right("microphone-menu-trigger")
+                    .child(
+                        h_flex()
+                            .mx_neg_0p5()
+                            .h_full()
+                            .justify_center()
+                            .child(Icon::new(IconName::ChevronDown).size(IconSize::XSmall)),
+                    )
+                    .toggle_state(self.microphone_popover_handle.is_deployed()),
+            )
+            .menu(|window, cx| {
+                let room = ActiveCall::global(cx).read(cx).room().cloned()?;
+                Some(ContextMenu::build(window, cx, |mut menu, _, cx| {
+                    menu = menu.header("Audio Processing");
+                    for processing in AudioProcessing::ALL {
+                        let Some(enabled) = room.read(cx).audio_processing(processing, cx) else {
+                            continue;
+                        };
+                        menu = menu.item(
+                            ContextMenuEntry::new(processing.label())
+                                .toggleable(IconPosition::Start, enabled)
+                                .handler(move |_, cx| {
+                                    set_audio_processing(processing, !enabled, cx)
+                                }),
+                        );
+                    }
+                    menu
+                }))
+            })
+    }
+
     fn render_screen_list(&self) -> impl IntoElement {
         PopoverMenu::new("screen-share-screen-list")
             .with_handle(self.screen_share_popover_handle.clone())
@@ -554,9 +707,9 @@ impl TitleBar {
                     cx.spawn(async move |this: WeakEntity<ContextMenu>, cx| {
                         let screens = screens.await??;
                         this.update(cx, |this, cx| {
-                            let active_screenshare_id = ActiveCall::global(cx)
-                                .read(cx)
-                                .room()
+                            let room = ActiveCall::global(cx).read(cx).room().cloned();
+                            let active_screenshare_id = room
+                                .as_ref()
                                 .and_then(|room| room.read(cx).shared_screen_id());
                             for screen in screens {
                                 let Ok(meta) = screen.metadata() else {
@@ -601,6 +754,33 @@ impl TitleBar {
                                     }),
                                 });
                             }
+
+                            let Some(room) = room else {
+                                return;
+                            };
+                            let (current_preset, pinned) = {
+                                let room = room.read(cx);
+                                (
+                                    room.screen_share_preset(),
+                                    room.is_screen_share_preset_pinned(),
+                                )
+                            };
+                            this.push_item(ContextMenuItem::Separator);
+                            this.push_item(ContextMenuItem::Header("Quality".into()));
+                            for preset in ScreenSharePreset::ALL {
+                                this.push_item(
+                                    ContextMenuEntry::new(preset.label())
+                                        .toggleable(IconPosition::Start, preset == current_preset)
+                                        .handler(move |_, cx| set_screen_share_preset(preset, cx)),
+                                );
+                            }
+                            this.push_item(
+                                ContextMenuEntry::new("Lower on Poor Connection")
+                                    .toggleable(IconPosition::Start, !pinned)
+                                    .handler(move |_, cx| {
+                                        set_screen_share_preset_pinned(!pinned, cx)
+                                    }),
+                            );
                         })
                     })
                     .detach_and_log_err(cx);
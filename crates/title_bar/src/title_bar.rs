@@ -156,6 +156,7 @@ pub struct TitleBar {
     banner: Entity<OnboardingBanner>,
     update_version: Entity<UpdateVersion>,
     screen_share_popover_handle: PopoverMenuHandle<ContextMenu>,
+    microphone_popover_handle: PopoverMenuHandle<ContextMenu>,
 }
 
 impl Render for TitleBar {
@@ -407,6 +408,7 @@ impl TitleBar {
             banner,
             update_version,
             screen_share_popover_handle: PopoverMenuHandle::default(),
+            microphone_popover_handle: PopoverMenuHandle::default(),
         }
     }
 
@@ -875,11 +877,13 @@ impl TitleBar {
         cx.notify();
     }
 
-    fn share_project(&mut self, cx: &mut Context<Self>) {
+    fn share_project(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let active_call = ActiveCall::global(cx);
         let project = self.project.clone();
         active_call
-            .update(cx, |call, cx| call.share_project(project, cx))
+            .update(cx, |call, cx| {
+                call.share_project_after_review(project, window, cx)
+            })
             .detach_and_log_err(cx);
     }
 
@@ -118,6 +118,8 @@ pub enum AudioStatus {
     Muted,
     /// The player's microphone is muted, and collaboration audio is disabled.
     Deafened,
+    /// The player joined only to listen, so they have no microphone to unmute.
+    Listening,
 }
 
 /// An indicator that shows the audio status of a player.
@@ -162,15 +164,24 @@ impl RenderOnce for AvatarAudioStatusIndicator {
                     .justify_center()
                     .px(padding_x)
                     .py(px(2.))
-                    .bg(cx.theme().status().error_background)
+                    .bg(match self.audio_status {
+                        AudioStatus::Muted | AudioStatus::Deafened => {
+                            cx.theme().status().error_background
+                        }
+                        AudioStatus::Listening => cx.theme().colors().element_background,
+                    })
                     .rounded_sm()
                     .child(
                         Icon::new(match self.audio_status {
                             AudioStatus::Muted => IconName::MicMute,
                             AudioStatus::Deafened => IconName::AudioOff,
+                            AudioStatus::Listening => IconName::AudioOn,
                         })
                         .size(icon_size)
-                        .color(Color::Error),
+                        .color(match self.audio_status {
+                            AudioStatus::Muted | AudioStatus::Deafened => Color::Error,
+                            AudioStatus::Listening => Color::Muted,
+                        }),
                     )
                     .when_some(self.tooltip, |this, tooltip| {
                         this.tooltip(move |window, cx| tooltip(window, cx))
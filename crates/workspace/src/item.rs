@@ -358,6 +358,12 @@ pub trait Item: Focusable + EventEmitter<Self::Event> + Render + Sized {
         None
     }
 
+    /// The row of the cursor in the file the item shows, counting from zero. Others in a call
+    /// see it next to the file.
+    fn cursor_row(&self, _: &App) -> Option<u32> {
+        None
+    }
+
     fn preserve_preview(&self, _cx: &App) -> bool {
         false
     }
@@ -540,6 +546,7 @@ pub trait ItemHandle: 'static + Send {
     fn breadcrumb_prefix(&self, window: &mut Window, cx: &mut App) -> Option<gpui::AnyElement>;
     fn show_toolbar(&self, cx: &App) -> bool;
     fn pixel_position_of_cursor(&self, cx: &App) -> Option<Point<Pixels>>;
+    fn cursor_row(&self, cx: &App) -> Option<u32>;
     fn downgrade_item(&self) -> Box<dyn WeakItemHandle>;
     fn workspace_settings<'a>(&self, cx: &'a App) -> &'a WorkspaceSettings;
     fn preserve_preview(&self, cx: &App) -> bool;
@@ -843,6 +850,10 @@ impl<T: Item> ItemHandle for Entity<T> {
                         }
                     }
 
+                    if item.item_focus_handle(cx).contains_focused(window, cx) {
+                        workspace.update_active_location(window, cx);
+                    }
+
                     if let Some(item) = item.to_serializable_item_handle(cx)
                         && item.should_serialize(event, cx)
                     {
@@ -1087,6 +1098,10 @@ impl<T: Item> ItemHandle for Entity<T> {
         self.read(cx).pixel_position_of_cursor(cx)
     }
 
+    fn cursor_row(&self, cx: &App) -> Option<u32> {
+        self.read(cx).cursor_row(cx)
+    }
+
     fn downgrade_item(&self) -> Box<dyn WeakItemHandle> {
         Box::new(self.downgrade())
     }
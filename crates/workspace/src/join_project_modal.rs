@@ -0,0 +1,104 @@
+//! Shows how far along joining a project shared in a call is, since it can take a while for
+//! large projects, and lets the user give up on it.
+
+use futures::channel::oneshot;
+use gpui::{DismissEvent, EventEmitter, FocusHandle, Focusable};
+use project::JoinProjectProgress;
+use ui::{AlertModal, CommonAnimationExt, prelude::*};
+
+use crate::ModalView;
+
+pub struct JoinProjectModal {
+    project_id: u64,
+    progress: JoinProjectProgress,
+    focus_handle: FocusHandle,
+    /// Dropped along with the modal, which cancels the join.
+    _cancel: oneshot::Sender<()>,
+}
+
+impl JoinProjectModal {
+    pub fn new(project_id: u64, cancel: oneshot::Sender<()>, cx: &mut Context<Self>) -> Self {
+        Self {
+            project_id,
+            progress: JoinProjectProgress::Connecting,
+            focus_handle: cx.focus_handle(),
+            _cancel: cancel,
+        }
+    }
+
+    pub fn set_progress(
+        &mut self,
+        project_id: u64,
+        progress: JoinProjectProgress,
+        cx: &mut Context<Self>,
+    ) {
+        if project_id == self.project_id {
+            self.progress = progress;
+            cx.notify();
+        }
+    }
+
+    fn dismiss(&mut self, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    fn progress_label(&self) -> String {
+        match self.progress {
+            JoinProjectProgress::Connecting => "Connecting…".to_string(),
+            JoinProjectProgress::WaitingForHost => "Waiting for the host…".to_string(),
+            JoinProjectProgress::ReceivingWorktrees {
+                worktrees,
+                synced_worktrees,
+                entries,
+            } => {
+                let entries = match entries {
+                    1 => "1 file".to_string(),
+                    entries => format!("{entries} files"),
+                };
+                if worktrees > 1 {
+                    format!("Receiving {entries} ({synced_worktrees} of {worktrees} folders done)…")
+                } else {
+                    format!("Receiving {entries}…")
+                }
+            }
+        }
+    }
+}
+
+impl Focusable for JoinProjectModal {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for JoinProjectModal {}
+
+impl ModalView for JoinProjectModal {}
+
+impl Render for JoinProjectModal {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        AlertModal::new("join-project-modal")
+            .width(rems(28.))
+            .key_context("JoinProjectModal")
+            .track_focus(&self.focus_handle(cx))
+            .on_action(cx.listener(|this, _: &menu::Cancel, _, cx| this.dismiss(cx)))
+            .title("Joining Project")
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        Icon::new(IconName::ArrowCircle)
+                            .size(IconSize::Small)
+                            .color(Color::Muted)
+                            .with_rotate_animation(2),
+                    )
+                    .child(Label::new(self.progress_label()).color(Color::Muted)),
+            )
+            .footer(
+                h_flex().p_3().justify_end().child(
+                    Button::new("cancel-join-project", "Cancel")
+                        .on_click(cx.listener(|this, _, _, cx| this.dismiss(cx))),
+                ),
+            )
+    }
+}
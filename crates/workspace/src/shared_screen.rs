@@ -5,22 +5,58 @@ use crate::{
 use client::{User, proto::PeerId};
 use gpui::{
     AnyView, AppContext as _, Entity, EventEmitter, FocusHandle, Focusable, InteractiveElement,
-    ParentElement, Render, SharedString, Styled, Task, div,
+    ParentElement, Render, SharedString, Styled, Subscription, Task, div,
 };
 use std::sync::Arc;
-use ui::{Icon, IconName, prelude::*};
+use ui::{Icon, IconName, Tooltip, prelude::*};
 
 pub enum Event {
     Close,
 }
 
+/// How far the local user got in asking to control a shared screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RemoteControlStatus {
+    #[default]
+    NotRequested,
+    Requested,
+    Granted,
+}
+
+/// Emitted by [`ScreenControl`] when the user asks to control the screen.
+pub struct RequestControl;
+
+/// Whether the local user may control a shared screen. The call keeps it up to date and
+/// handles [`RequestControl`], and all the views of one screen share it.
+#[derive(Default)]
+pub struct ScreenControl {
+    status: RemoteControlStatus,
+}
+
+impl EventEmitter<RequestControl> for ScreenControl {}
+
+impl ScreenControl {
+    pub fn status(&self) -> RemoteControlStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: RemoteControlStatus, cx: &mut Context<Self>) {
+        if self.status != status {
+            self.status = status;
+            cx.notify();
+        }
+    }
+}
+
 pub struct SharedScreen {
     pub peer_id: PeerId,
     user: Arc<User>,
     nav_history: Option<ItemNavHistory>,
     view: AnyView,
     clone_view: fn(&AnyView, &mut Window, &mut App) -> AnyView,
+    control: Entity<ScreenControl>,
     focus: FocusHandle,
+    _observe_control: Subscription,
 }
 
 impl SharedScreen {
@@ -29,6 +65,7 @@ impl SharedScreen {
         user: Arc<User>,
         view: AnyView,
         clone_view: fn(&AnyView, &mut Window, &mut App) -> AnyView,
+        control: Entity<ScreenControl>,
         cx: &mut Context<Self>,
     ) -> Self {
         Self {
@@ -38,8 +75,52 @@ impl SharedScreen {
             nav_history: Default::default(),
             focus: cx.focus_handle(),
             clone_view,
+            _observe_control: cx.observe(&control, |_, _, cx| cx.notify()),
+            control,
         }
     }
+
+    fn render_control(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = self.control.read(cx).status();
+        let content = match status {
+            RemoteControlStatus::NotRequested => Button::new("request-control", "Request Control")
+                .style(ButtonStyle::Filled)
+                .icon(IconName::Screen)
+                .icon_position(IconPosition::Start)
+                .icon_size(IconSize::Small)
+                .tooltip(Tooltip::text(format!(
+                    "Ask {} to let you control their screen",
+                    self.user.github_login
+                )))
+                .on_click(cx.listener(|this, _, _, cx| {
+                    this.control.update(cx, |_, cx| cx.emit(RequestControl));
+                }))
+                .into_any_element(),
+            RemoteControlStatus::Requested => Button::new("request-control", "Waiting for Answer…")
+                .style(ButtonStyle::Filled)
+                .disabled(true)
+                .into_any_element(),
+            RemoteControlStatus::Granted => h_flex()
+                .gap_1()
+                .child(
+                    Icon::new(IconName::Check)
+                        .size(IconSize::Small)
+                        .color(Color::Success),
+                )
+                .child(Label::new("Control Granted").size(LabelSize::Small))
+                .into_any_element(),
+        };
+        h_flex()
+            .absolute()
+            .top_2()
+            .right_2()
+            .p_1()
+            .rounded_md()
+            .bg(cx.theme().colors().elevated_surface_background)
+            .border_1()
+            .border_color(cx.theme().colors().border)
+            .child(content)
+    }
 }
 
 impl EventEmitter<Event> for SharedScreen {}
@@ -55,8 +136,10 @@ impl Render for SharedScreen {
             .bg(cx.theme().colors().editor_background)
             .track_focus(&self.focus)
             .key_context("SharedScreen")
+            .relative()
             .size_full()
             .child(self.view.clone())
+            .child(self.render_control(cx))
     }
 }
 
@@ -106,13 +189,15 @@ impl Item for SharedScreen {
     ) -> Task<Option<Entity<Self>>> {
         let clone_view = self.clone_view;
         let cloned_view = clone_view(&self.view, window, cx);
-        Task::ready(Some(cx.new(|cx| Self {
-            view: cloned_view,
-            peer_id: self.peer_id,
-            user: self.user.clone(),
-            nav_history: Default::default(),
-            focus: cx.focus_handle(),
-            clone_view,
+        Task::ready(Some(cx.new(|cx| {
+            Self::new(
+                self.peer_id,
+                self.user.clone(),
+                cloned_view,
+                clone_view,
+                self.control.clone(),
+                cx,
+            )
         })))
     }
 
@@ -2,6 +2,7 @@ pub mod dock;
 pub mod history_manager;
 pub mod invalid_item_view;
 pub mod item;
+mod join_project_modal;
 mod modal_layer;
 mod multi_workspace;
 pub mod notifications;
@@ -51,11 +52,11 @@ use futures::{
 };
 use gpui::{
     Action, AnyEntity, AnyView, AnyWeakView, App, AsyncApp, AsyncWindowContext, Bounds, Context,
-    CursorStyle, Decorations, DragMoveEvent, Entity, EntityId, EventEmitter, FocusHandle,
-    Focusable, Global, HitboxBehavior, Hsla, KeyContext, Keystroke, ManagedView, MouseButton,
-    PathPromptOptions, Point, PromptLevel, Render, ResizeEdge, Size, Stateful, Subscription,
-    SystemWindowTabController, Task, Tiling, WeakEntity, WindowBounds, WindowHandle, WindowId,
-    WindowOptions, actions, canvas, point, relative, size, transparent_black,
+    CursorStyle, Decorations, DismissEvent, DragMoveEvent, Entity, EntityId, EventEmitter,
+    FocusHandle, Focusable, Global, HitboxBehavior, Hsla, KeyContext, Keystroke, ManagedView,
+    MouseButton, PathPromptOptions, Point, PromptLevel, Render, ResizeEdge, Size, Stateful,
+    Subscription, SystemWindowTabController, Task, Tiling, WeakEntity, WindowBounds, WindowHandle,
+    WindowId, WindowOptions, actions, canvas, point, relative, size, transparent_black,
 };
 pub use history_manager::*;
 pub use item::{
@@ -83,8 +84,8 @@ pub use persistence::{
 };
 use postage::stream::Stream;
 use project::{
-    DirectoryLister, Project, ProjectEntryId, ProjectPath, ResolvedPath, Worktree, WorktreeId,
-    WorktreeSettings,
+    DirectoryLister, JoinProjectProgress, Project, ProjectEntryId, ProjectPath, ResolvedPath,
+    Worktree, WorktreeId, WorktreeSettings,
     debugger::{breakpoint_store::BreakpointStoreEvent, session::ThreadStatus},
     project_settings::ProjectSettings,
     toolchain_store::ToolchainStoreEvent,
@@ -146,6 +147,7 @@ use zed_actions::{Spawn, feedback::FileBugReport};
 
 use crate::{item::ItemBufferKind, notifications::NotificationId};
 use crate::{
+    join_project_modal::JoinProjectModal,
     persistence::{
         SerializedAxis,
         model::{DockData, DockStructure, SerializedItem, SerializedPane, SerializedPaneGroup},
@@ -5547,6 +5549,8 @@ impl Workspace {
     }
 
     pub fn update_active_view_for_followers(&mut self, window: &mut Window, cx: &mut App) {
+        self.update_active_location(window, cx);
+
         let mut is_project_item = true;
         let mut update = proto::UpdateActiveView::default();
         if window.is_window_active() {
@@ -5597,6 +5601,23 @@ impl Workspace {
         }
     }
 
+    /// Tells the others in the call which file is open in the active pane, and where the
+    /// cursor is in it, while this window is the one the local user is in.
+    pub(crate) fn update_active_location(&self, window: &Window, cx: &mut App) {
+        if !window.is_window_active() {
+            return;
+        }
+        let Some(active_call) = GlobalAnyActiveCall::try_global(cx).cloned() else {
+            return;
+        };
+        let active_path = self
+            .active_item(cx)
+            .and_then(|item| Some((item.project_path(cx)?, item.cursor_row(cx))));
+        active_call
+            .0
+            .set_active_location(&self.project, active_path, cx);
+    }
+
     fn active_item_for_followers(
         &self,
         window: &mut Window,
@@ -5810,6 +5831,23 @@ impl Workspace {
             | ActiveCallEvent::RemoteVideoTracksChanged { participant_id } => {
                 self.leader_updated(participant_id, window, cx);
             }
+            ActiveCallEvent::ProjectShared { project_id, .. }
+            | ActiveCallEvent::ProjectUnshared { project_id } => {
+                if *project_id == self.project.entity_id() {
+                    cx.notify();
+                }
+            }
+            ActiveCallEvent::LocalCapabilitiesChanged => cx.notify(),
+            ActiveCallEvent::JoinProjectProgress {
+                project_id,
+                progress,
+            } => {
+                if let Some(modal) = self.active_modal::<JoinProjectModal>(cx) {
+                    modal.update(cx, |modal, cx| {
+                        modal.set_progress(*project_id, *progress, cx)
+                    });
+                }
+            }
         }
     }
 
@@ -7084,14 +7122,31 @@ pub trait AnyActiveCall {
     fn hang_up(&self, _: &mut App) -> Task<Result<()>>;
     fn unshare_project(&self, _: Entity<Project>, _: &mut App) -> Result<()>;
     fn remote_participant_for_peer_id(&self, _: PeerId, _: &App) -> Option<RemoteCollaborator>;
+    /// Tells the others in the call which file is open in `project`, and which row the cursor
+    /// is on in it.
+    fn set_active_location(
+        &self,
+        _: &Entity<Project>,
+        _: Option<(ProjectPath, Option<u32>)>,
+        _: &mut App,
+    );
     fn is_sharing_project(&self, _: &App) -> bool;
     fn has_remote_participants(&self, _: &App) -> bool;
-    fn local_participant_is_guest(&self, _: &App) -> bool;
+    /// What the local participant may do in the call, or nothing when not in one.
+    fn local_capabilities(&self, _: &App) -> ParticipantCapabilities;
     fn client(&self, _: &App) -> Arc<Client>;
     fn share_on_join(&self, _: &App) -> bool;
     fn join_channel(&self, _: ChannelId, _: &mut App) -> Task<Result<bool>>;
+    /// Joins the channel's call without publishing any audio or video.
+    fn join_channel_listen_only(&self, _: ChannelId, _: &mut App) -> Task<Result<bool>>;
+    /// Asks the channel admins in the channel's call to let the local user join it.
+    fn request_to_join_channel(&self, _: ChannelId, _: &mut App) -> Task<Result<()>>;
     fn room_update_completed(&self, _: &mut App) -> Task<()>;
-    fn most_active_project(&self, _: &App) -> Option<(u64, u64)>;
+    fn project_activity(&self, _: &App) -> Vec<ProjectActivity>;
+    /// The project with the most participants in it, which is where newcomers to the room join.
+    fn most_active_project(&self, cx: &App) -> Option<ProjectActivity> {
+        self.project_activity(cx).into_iter().next()
+    }
     fn share_project(&self, _: Entity<Project>, _: &mut App) -> Task<Result<u64>>;
     fn join_project(
         &self,
@@ -7153,6 +7208,64 @@ impl ParticipantLocation {
         }
     }
 }
+/// How many participants of a call are in one of the projects shared in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectActivity {
+    pub owner_user_id: u64,
+    pub project_id: u64,
+    /// The participants whose location is this project, including its host.
+    pub participant_count: usize,
+    /// Whether the host is among the participants in the project.
+    pub includes_host: bool,
+}
+
+bitflags::bitflags! {
+    /// What a participant in a call may do, which follows from their role in the channel.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct ParticipantCapabilities: u8 {
+        const USE_MICROPHONE = 1 << 0;
+        const USE_CAMERA = 1 << 1;
+        const SHARE_SCREEN = 1 << 2;
+        const SHARE_PROJECTS = 1 << 3;
+        /// Editing the buffers of projects that others shared.
+        const EDIT_PROJECTS = 1 << 4;
+        /// Following other participants and joining the projects they shared.
+        const FOLLOW = 1 << 5;
+        /// Changing the roles of other participants.
+        const MANAGE_PARTICIPANTS = 1 << 6;
+    }
+}
+
+impl ParticipantCapabilities {
+    pub fn for_role(role: proto::ChannelRole) -> Self {
+        match role {
+            proto::ChannelRole::Admin => Self::all(),
+            proto::ChannelRole::Member => Self::all().difference(Self::MANAGE_PARTICIPANTS),
+            proto::ChannelRole::Talker => {
+                Self::USE_MICROPHONE | Self::USE_CAMERA | Self::SHARE_SCREEN | Self::FOLLOW
+            }
+            proto::ChannelRole::Guest => Self::FOLLOW,
+            proto::ChannelRole::Banned => Self::empty(),
+        }
+    }
+
+    /// What the first of these capabilities lets a participant do, such as "share projects".
+    pub fn description(&self) -> &'static str {
+        [
+            (Self::USE_MICROPHONE, "use the microphone"),
+            (Self::USE_CAMERA, "use the camera"),
+            (Self::SHARE_SCREEN, "share the screen"),
+            (Self::SHARE_PROJECTS, "share projects"),
+            (Self::EDIT_PROJECTS, "edit shared projects"),
+            (Self::FOLLOW, "follow other participants"),
+            (Self::MANAGE_PARTICIPANTS, "change participants' roles"),
+        ]
+        .into_iter()
+        .find_map(|(capability, description)| self.contains(capability).then_some(description))
+        .unwrap_or("do that")
+    }
+}
+
 /// Workspace-local view of a remote collaborator's state.
 /// This is the subset of `call::RemoteParticipant` that workspace needs.
 #[derive(Clone)]
@@ -7160,12 +7273,35 @@ pub struct RemoteCollaborator {
     pub user: Arc<User>,
     pub peer_id: PeerId,
     pub location: ParticipantLocation,
+    /// The file the collaborator has open in the shared project they're in, unless they keep
+    /// it to themselves.
+    pub active_path: Option<ProjectPath>,
+    /// The row of the collaborator's cursor in `active_path`, counting from zero.
+    pub cursor_row: Option<u32>,
     pub participant_index: ParticipantIndex,
+    pub capabilities: ParticipantCapabilities,
 }
 
 pub enum ActiveCallEvent {
-    ParticipantLocationChanged { participant_id: PeerId },
-    RemoteVideoTracksChanged { participant_id: PeerId },
+    ParticipantLocationChanged {
+        participant_id: PeerId,
+    },
+    RemoteVideoTracksChanged {
+        participant_id: PeerId,
+    },
+    ProjectShared {
+        project_id: EntityId,
+        remote_id: u64,
+    },
+    ProjectUnshared {
+        project_id: EntityId,
+    },
+    /// The local participant's role changed in a way that lets them do more or less in the call.
+    LocalCapabilitiesChanged,
+    JoinProjectProgress {
+        project_id: u64,
+        progress: JoinProjectProgress,
+    },
 }
 
 fn leader_border_for_pane(
@@ -8203,6 +8339,7 @@ actions!(
 
 async fn join_channel_internal(
     channel_id: ChannelId,
+    listen_only: bool,
     app_state: &Arc<AppState>,
     requesting_window: Option<WindowHandle<MultiWorkspace>>,
     requesting_workspace: Option<WeakEntity<Workspace>>,
@@ -8223,11 +8360,14 @@ async fn join_channel_internal(
 
     if already_in_channel {
         let task = cx.update(|cx| {
-            if let Some((project, host)) = active_call.most_active_project(cx) {
-                Some(join_in_room_project(project, host, app_state.clone(), cx))
-            } else {
-                None
-            }
+            active_call.most_active_project(cx).map(|project| {
+                join_in_room_project(
+                    project.project_id,
+                    project.owner_user_id,
+                    app_state.clone(),
+                    cx,
+                )
+            })
         });
         if let Some(task) = task {
             task.await?;
@@ -8286,7 +8426,13 @@ async fn join_channel_internal(
     }
 
     let joined = cx
-        .update(|cx| active_call.join_channel(channel_id, cx))
+        .update(|cx| {
+            if listen_only {
+                active_call.join_channel_listen_only(channel_id, cx)
+            } else {
+                active_call.join_channel(channel_id, cx)
+            }
+        })
         .await?;
 
     if !joined {
@@ -8296,13 +8442,22 @@ async fn join_channel_internal(
     cx.update(|cx| active_call.room_update_completed(cx)).await;
 
     let task = cx.update(|cx| {
-        if let Some((project, host)) = active_call.most_active_project(cx) {
-            return Some(join_in_room_project(project, host, app_state.clone(), cx));
+        if let Some(project) = active_call.most_active_project(cx) {
+            return Some(join_in_room_project(
+                project.project_id,
+                project.owner_user_id,
+                app_state.clone(),
+                cx,
+            ));
         }
 
-        // If you are the first to join a channel, see if you should share your project.
-        if !active_call.has_remote_participants(cx)
-            && !active_call.local_participant_is_guest(cx)
+        // If you are the first to join a channel, see if you should share your project. Joining
+        // only to listen never shares anything.
+        if !listen_only
+            && !active_call.has_remote_participants(cx)
+            && active_call
+                .local_capabilities(cx)
+                .contains(ParticipantCapabilities::SHARE_PROJECTS)
             && let Some(workspace) = requesting_workspace.as_ref().and_then(|w| w.upgrade())
         {
             let project = workspace.update(cx, |workspace, cx| {
@@ -8348,11 +8503,49 @@ pub fn join_channel(
     requesting_window: Option<WindowHandle<MultiWorkspace>>,
     requesting_workspace: Option<WeakEntity<Workspace>>,
     cx: &mut App,
+) -> Task<Result<()>> {
+    join_channel_in_mode(
+        channel_id,
+        false,
+        app_state,
+        requesting_window,
+        requesting_workspace,
+        cx,
+    )
+}
+
+/// Like [`join_channel`], but only listens to the call, without opening the microphone or
+/// camera, until the user starts participating.
+pub fn join_channel_listen_only(
+    channel_id: ChannelId,
+    app_state: Arc<AppState>,
+    requesting_window: Option<WindowHandle<MultiWorkspace>>,
+    requesting_workspace: Option<WeakEntity<Workspace>>,
+    cx: &mut App,
+) -> Task<Result<()>> {
+    join_channel_in_mode(
+        channel_id,
+        true,
+        app_state,
+        requesting_window,
+        requesting_workspace,
+        cx,
+    )
+}
+
+fn join_channel_in_mode(
+    channel_id: ChannelId,
+    listen_only: bool,
+    app_state: Arc<AppState>,
+    requesting_window: Option<WindowHandle<MultiWorkspace>>,
+    requesting_workspace: Option<WeakEntity<Workspace>>,
+    cx: &mut App,
 ) -> Task<Result<()>> {
     let active_call = GlobalAnyActiveCall::global(cx).clone();
     cx.spawn(async move |cx| {
         let result = join_channel_internal(
             channel_id,
+            listen_only,
             &app_state,
             requesting_window,
             requesting_workspace,
@@ -8400,8 +8593,9 @@ pub fn join_channel(
 
         if let Err(err) = result {
             log::error!("failed to join channel: {}", err);
+            let forbidden = err.error_code() == ErrorCode::Forbidden;
             if let Some(active_window) = active_window {
-                active_window
+                let answer = active_window
                     .update(cx, |_, window, cx| {
                         let detail: SharedString = match err.error_code() {
                             ErrorCode::SignedOut => "Please sign in to continue.".into(),
@@ -8417,7 +8611,7 @@ pub fn join_channel(
                             .into(),
                             ErrorCode::Forbidden => concat!(
                                 "This channel is private, and you do not have access. ",
-                                "Please ask someone to add you and try again."
+                                "You can ask a channel admin in its call to let you in."
                             )
                             .into(),
                             ErrorCode::Disconnected => {
@@ -8425,16 +8619,40 @@ pub fn join_channel(
                             }
                             _ => format!("{}\n\nPlease try again.", err).into(),
                         };
+                        let answers: &[&str] = if forbidden {
+                            &["Request to Join", "Cancel"]
+                        } else {
+                            &["Ok"]
+                        };
                         window.prompt(
                             PromptLevel::Critical,
                             "Failed to join channel",
                             Some(&detail),
-                            &["Ok"],
+                            answers,
                             cx,
                         )
                     })?
                     .await
                     .ok();
+
+                if forbidden && answer == Some(0) {
+                    let request =
+                        cx.update(|cx| active_call.0.request_to_join_channel(channel_id, cx));
+                    if let Err(err) = request.await {
+                        active_window
+                            .update(cx, |_, window, cx| {
+                                window.prompt(
+                                    PromptLevel::Critical,
+                                    "Failed to request to join channel",
+                                    Some(&err.to_string()),
+                                    &["Ok"],
+                                    cx,
+                                )
+                            })?
+                            .await
+                            .ok();
+                    }
+                }
             }
         }
 
@@ -9201,16 +9419,32 @@ pub fn join_in_room_project(
             existing_window
         } else {
             let active_call = cx.update(|cx| GlobalAnyActiveCall::global(cx).clone());
-            let project = cx
-                .update(|cx| {
-                    active_call.0.join_project(
-                        project_id,
-                        app_state.languages.clone(),
-                        app_state.fs.clone(),
-                        cx,
-                    )
-                })
-                .await?;
+            let (cancel_tx, cancel_rx) = oneshot::channel();
+            let modal = cx.update(|cx| show_join_project_modal(project_id, cancel_tx, cx));
+            let join = cx.update(|cx| {
+                active_call.0.join_project(
+                    project_id,
+                    app_state.languages.clone(),
+                    app_state.fs.clone(),
+                    cx,
+                )
+            });
+            // Closing the modal drops the join, which leaves the partially joined project.
+            let cancelled = async move {
+                if modal.is_some() {
+                    cancel_rx.await.ok();
+                } else {
+                    futures::future::pending::<()>().await;
+                }
+            };
+            let project = futures::select_biased! {
+                project = join.fuse() => project,
+                _ = cancelled.fuse() => return Ok(()),
+            };
+            if let Some(modal) = modal {
+                modal.update(cx, |_, cx| cx.emit(DismissEvent)).ok();
+            }
+            let project = project?;
 
             let window_bounds_override = window_bounds_env_override();
             cx.update(|cx| {
@@ -9255,6 +9489,29 @@ pub fn join_in_room_project(
     })
 }
 
+/// Shows the progress of joining a project in the active window, returning the modal if it's shown.
+/// The modal holds on to `cancel` until it's closed.
+fn show_join_project_modal(
+    project_id: u64,
+    cancel: oneshot::Sender<()>,
+    cx: &mut App,
+) -> Option<WeakEntity<JoinProjectModal>> {
+    let window = cx.active_window()?.downcast::<MultiWorkspace>()?;
+    window
+        .update(cx, |multi_workspace, window, cx| {
+            multi_workspace.workspace().update(cx, |workspace, cx| {
+                workspace.toggle_modal(window, cx, |_, cx| {
+                    JoinProjectModal::new(project_id, cancel, cx)
+                });
+                workspace
+                    .active_modal::<JoinProjectModal>(cx)
+                    .map(|modal| modal.downgrade())
+            })
+        })
+        .ok()
+        .flatten()
+}
+
 pub fn reload(cx: &mut App) {
     let should_confirm = WorkspaceSettings::get_global(cx).confirm_quit;
     let mut workspace_windows = cx
@@ -9866,6 +10123,27 @@ mod tests {
     use settings::SettingsStore;
     use util::rel_path::rel_path;
 
+    #[test]
+    fn test_participant_capabilities_for_role() {
+        type Caps = ParticipantCapabilities;
+        let media = Caps::USE_MICROPHONE | Caps::USE_CAMERA | Caps::SHARE_SCREEN;
+
+        assert_eq!(Caps::for_role(proto::ChannelRole::Admin), Caps::all());
+        assert_eq!(
+            Caps::for_role(proto::ChannelRole::Member),
+            media | Caps::SHARE_PROJECTS | Caps::EDIT_PROJECTS | Caps::FOLLOW
+        );
+        assert_eq!(
+            Caps::for_role(proto::ChannelRole::Talker),
+            media | Caps::FOLLOW
+        );
+        assert_eq!(Caps::for_role(proto::ChannelRole::Guest), Caps::FOLLOW);
+        assert_eq!(Caps::for_role(proto::ChannelRole::Banned), Caps::empty());
+
+        assert_eq!(Caps::SHARE_PROJECTS.description(), "share projects");
+        assert_eq!(Caps::empty().description(), "do that");
+    }
+
     #[gpui::test]
     async fn test_tab_disambiguation(cx: &mut TestAppContext) {
         init_test(cx);
@@ -136,6 +136,8 @@ pub struct LocalWorktree {
     next_entry_id: Arc<AtomicUsize>,
     settings: WorktreeSettings,
     share_private_files: bool,
+    /// Paths kept from guests on top of the `private_files` setting, chosen when sharing.
+    unshared_paths: Arc<HashSet<Arc<RelPath>>>,
     scanning_enabled: bool,
 }
 
@@ -461,6 +463,7 @@ impl Worktree {
             let (path_prefixes_to_scan_tx, path_prefixes_to_scan_rx) = channel::unbounded();
             let mut worktree = LocalWorktree {
                 share_private_files,
+                unshared_paths: Default::default(),
                 next_entry_id,
                 snapshot,
                 is_scanning: watch::channel_with(true),
@@ -1035,7 +1038,21 @@ impl LocalWorktree {
     }
 
     pub fn is_path_private(&self, path: &RelPath) -> bool {
-        !self.share_private_files && self.settings.is_path_private(path)
+        !self.share_private_files
+            && (self.settings.is_path_private(path) || is_path_unshared(&self.unshared_paths, path))
+    }
+
+    pub fn unshared_paths(&self) -> &HashSet<Arc<RelPath>> {
+        &self.unshared_paths
+    }
+
+    /// Marks `paths`, and everything under them, as private, in addition to the files that the
+    /// `private_files` setting covers.
+    pub fn set_unshared_paths(&mut self, paths: HashSet<Arc<RelPath>>, cx: &Context<Worktree>) {
+        if *self.unshared_paths != paths {
+            self.unshared_paths = Arc::new(paths);
+            self.restart_background_scanners(cx);
+        }
     }
 
     pub fn fs_is_case_sensitive(&self) -> bool {
@@ -1068,6 +1085,7 @@ impl LocalWorktree {
     ) {
         let snapshot = self.snapshot();
         let share_private_files = self.share_private_files;
+        let unshared_paths = self.unshared_paths.clone();
         let next_entry_id = self.next_entry_id.clone();
         let fs = self.fs.clone();
         let scanning_enabled = self.scanning_enabled;
@@ -1104,6 +1122,7 @@ impl LocalWorktree {
                     }),
                     phase: BackgroundScannerPhase::InitialScan,
                     share_private_files,
+                    unshared_paths,
                     settings,
                     watcher,
                 };
@@ -3731,6 +3750,7 @@ struct BackgroundScanner {
     watcher: Arc<dyn Watcher>,
     settings: WorktreeSettings,
     share_private_files: bool,
+    unshared_paths: Arc<HashSet<Arc<RelPath>>>,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -5131,7 +5151,8 @@ impl BackgroundScanner {
     }
 
     fn is_path_private(&self, path: &RelPath) -> bool {
-        !self.share_private_files && self.settings.is_path_private(path)
+        !self.share_private_files
+            && (self.settings.is_path_private(path) || is_path_unshared(&self.unshared_paths, path))
     }
 
     async fn next_scan_request(&self) -> Result<ScanRequest> {
@@ -5876,6 +5897,13 @@ impl CreatedEntry {
     }
 }
 
+fn is_path_unshared(unshared_paths: &HashSet<Arc<RelPath>>, path: &RelPath) -> bool {
+    !unshared_paths.is_empty()
+        && path
+            .ancestors()
+            .any(|ancestor| unshared_paths.contains(ancestor))
+}
+
 fn parse_gitfile(content: &str) -> anyhow::Result<&Path> {
     let path = content
         .strip_prefix("gitdir:")
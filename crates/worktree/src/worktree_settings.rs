@@ -21,6 +21,8 @@ pub struct WorktreeSettings {
     pub private_files: PathMatcher,
     pub hidden_files: PathMatcher,
     pub read_only_files: PathMatcher,
+    /// Whether files created by the agent start with a UTF-8 byte order mark.
+    pub new_file_bom: bool,
 }
 
 impl WorktreeSettings {
@@ -97,6 +99,7 @@ impl Settings for WorktreeSettings {
             read_only_files: path_matchers(read_only_files, "read_only_files")
                 .log_err()
                 .unwrap_or_default(),
+            new_file_bom: worktree.new_file_bom.unwrap_or_default(),
         }
     }
 }
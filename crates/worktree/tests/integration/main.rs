@@ -1,10 +1,13 @@
 mod worktree_settings;
 
 use anyhow::Result;
+use collections::HashSet;
 use encoding_rs;
 use fs::{FakeFs, Fs, RealFs, RemoveOptions};
 use git::{DOT_GIT, GITIGNORE, REPO_EXCLUDE};
-use gpui::{AppContext as _, BackgroundExecutor, BorrowAppContext, Context, Task, TestAppContext};
+use gpui::{
+    AppContext as _, BackgroundExecutor, BorrowAppContext, Context, Entity, Task, TestAppContext,
+};
 use parking_lot::Mutex;
 use postage::stream::Stream;
 use pretty_assertions::assert_eq;
@@ -2279,6 +2282,72 @@ async fn test_private_single_file_worktree(cx: &mut TestAppContext) {
     });
 }
 
+#[gpui::test]
+async fn test_unshared_paths(cx: &mut TestAppContext) {
+    init_test(cx);
+    let fs = FakeFs::new(cx.background_executor.clone());
+    fs.insert_tree(
+        "/root",
+        json!({
+            "config": { "credentials.json": "{}", "settings.json": "{}" },
+            "keys": { "id_rsa": "", "id_rsa.pub": "" },
+            "src": { "main.rs": "" },
+        }),
+    )
+    .await;
+    let tree = Worktree::local(
+        Path::new("/root"),
+        true,
+        fs.clone(),
+        Default::default(),
+        true,
+        WorktreeId::from_proto(0),
+        &mut cx.to_async(),
+    )
+    .await
+    .unwrap();
+    cx.read(|cx| tree.read(cx).as_local().unwrap().scan_complete())
+        .await;
+
+    let private_paths = |tree: &Entity<Worktree>, cx: &TestAppContext| {
+        tree.read_with(cx, |tree, _| {
+            tree.entries(true, 0)
+                .filter(|entry| entry.is_private)
+                .map(|entry| entry.path.as_unix_str().to_string())
+                .collect::<Vec<_>>()
+        })
+    };
+    assert_eq!(private_paths(&tree, cx), Vec::<String>::new());
+
+    tree.update(cx, |tree, cx| {
+        tree.as_local_mut().unwrap().set_unshared_paths(
+            HashSet::from_iter([
+                rel_path("config/credentials.json").into(),
+                rel_path("keys").into(),
+            ]),
+            cx,
+        );
+    });
+    cx.run_until_parked();
+    assert_eq!(
+        private_paths(&tree, cx),
+        [
+            "config/credentials.json",
+            "keys",
+            "keys/id_rsa",
+            "keys/id_rsa.pub"
+        ]
+    );
+
+    tree.update(cx, |tree, cx| {
+        tree.as_local_mut()
+            .unwrap()
+            .set_unshared_paths(HashSet::default(), cx);
+    });
+    cx.run_until_parked();
+    assert_eq!(private_paths(&tree, cx), Vec::<String>::new());
+}
+
 #[gpui::test]
 async fn test_repository_above_root(executor: BackgroundExecutor, cx: &mut TestAppContext) {
     init_test(cx);
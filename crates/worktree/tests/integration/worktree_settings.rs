@@ -19,6 +19,7 @@ fn make_settings_with_read_only(patterns: &[&str]) -> WorktreeSettings {
             PathStyle::local(),
         )
         .unwrap(),
+        new_file_bom: false,
     }
 }
 
@@ -443,6 +443,13 @@ fn session_state(session: Entity<Session>, cx: &mut App) -> ReplMenuState {
             true,
         )
     };
+    let unresponsive = || {
+        transitional(
+            format!("{} is not responding", kernel_name).into(),
+            false,
+            true,
+        )
+    };
     let unknown = || transitional(format!("{} state unknown", kernel_name).into(), false, true);
     let other = |state: &str| {
         transitional(
@@ -464,7 +471,23 @@ fn session_state(session: Entity<Session>, cx: &mut App) -> ReplMenuState {
     };
 
     match &session.kernel {
-        Kernel::Restarting => restarting(),
+        Kernel::Restarting(kind) => transitional(
+            format!("{} {}", kind.label(), kernel_name).into(),
+            true,
+            true,
+        ),
+        // A remote kernel whose connection is being recovered is still running, but can't be
+        // reached until it's back.
+        Kernel::RunningKernel(kernel)
+            if matches!(kernel.connection_status(), Some(KernelStatus::Unresponsive)) =>
+        {
+            unresponsive()
+        }
+        Kernel::RunningKernel(kernel)
+            if matches!(kernel.connection_status(), Some(KernelStatus::Restarting)) =>
+        {
+            restarting()
+        }
         Kernel::RunningKernel(kernel) => match &kernel.execution_state() {
             ExecutionState::Idle => ReplMenuState {
                 tooltip: format!("Run code on {} ({})", kernel_name, kernel_language).into(),
@@ -639,6 +639,20 @@ actions!(
     ]
 );
 
+pub mod call {
+    use gpui::actions;
+    actions!(
+        call,
+        [
+            /// Plays the incoming call sound once, using the current call settings.
+            PreviewIncomingCallSound,
+            /// Saves whether you're muted, deafened, and sharing a project as the defaults for
+            /// joining the current channel's call.
+            RememberChannelCallPreferences
+        ]
+    );
+}
+
 pub mod debug_panel {
     use gpui::actions;
     actions!(
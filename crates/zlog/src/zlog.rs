@@ -1,6 +1,8 @@
 //! # logger
 pub use log as log_impl;
 
+use gpui_util::log_buffer::BufferedLogger;
+
 mod env_config;
 pub mod filter;
 pub mod sink;
@@ -57,7 +59,7 @@ pub fn process_env(filter: Option<String>) {
     }
 }
 
-static ZLOG: Zlog = Zlog {};
+static ZLOG: BufferedLogger<Zlog> = BufferedLogger::new(Zlog {});
 
 pub struct Zlog {}
 